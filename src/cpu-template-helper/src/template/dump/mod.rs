@@ -1,21 +1,11 @@
 // Copyright 2023 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-#[cfg(target_arch = "aarch64")]
-mod aarch64;
-#[cfg(target_arch = "x86_64")]
-mod x86_64;
-
 use std::sync::{Arc, Mutex};
 
-use vmm::cpu_config::templates::CustomCpuTemplate;
+use vmm::cpu_config::templates::{CustomCpuTemplate, config_to_template};
 use vmm::{DumpCpuConfigError, Vmm};
 
-#[cfg(target_arch = "aarch64")]
-use crate::template::dump::aarch64::config_to_template;
-#[cfg(target_arch = "x86_64")]
-use crate::template::dump::x86_64::config_to_template;
-
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum DumpError {
     /// Failed to dump CPU config: {0}
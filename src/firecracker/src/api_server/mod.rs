@@ -123,6 +123,16 @@ impl ApiServer {
         request: &Request,
         request_processing_start_us: u64,
     ) -> Response {
+        let method = request.method().to_str();
+        let path = request.uri().get_abs_path();
+        vmm::usdt_probe!(
+            api_request,
+            method.as_ptr(),
+            method.len(),
+            path.as_ptr(),
+            path.len()
+        );
+
         match ParsedRequest::try_from(request).map(|r| r.into_parts()) {
             Ok((req_action, mut parsing_info)) => {
                 let mut response = match req_action {
@@ -173,6 +183,11 @@ impl ApiServer {
         self.to_vmm_fd.write(1).expect("Cannot update send VMM fd");
         let vmm_outcome = *(self.vmm_response_receiver.recv().expect("VMM disconnected"));
         let response = ParsedRequest::convert_to_response(&vmm_outcome);
+        vmm::usdt_probe!(
+            api_response,
+            vmm_outcome.is_ok(),
+            get_time_us(ClockType::Monotonic) - request_processing_start_us
+        );
 
         if vmm_outcome.is_ok()
             && let Some((metric, action)) = metric_with_action
@@ -211,7 +226,7 @@ mod tests {
     use vmm::rpc_interface::{VmmActionError, VmmData};
     use vmm::seccomp::get_empty_filters;
     use vmm::vmm_config::instance_info::InstanceInfo;
-    use vmm::vmm_config::snapshot::CreateSnapshotParams;
+    use vmm::vmm_config::snapshot::{CreateSnapshotParams, SnapshotConsistency};
     use vmm_sys_util::tempfile::TempFile;
 
     use super::request::cpu_configuration::parse_put_cpu_config;
@@ -276,6 +291,10 @@ mod tests {
                 snapshot_type: SnapshotType::Diff,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                memory_digests: false,
+                consistency: SnapshotConsistency::Pause,
+                snapshot_fd: None,
+                mem_file_fd: None,
             })),
             start_time_us,
         );
@@ -289,6 +308,10 @@ mod tests {
                 snapshot_type: SnapshotType::Diff,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                memory_digests: false,
+                consistency: SnapshotConsistency::Pause,
+                snapshot_fd: None,
+                mem_file_fd: None,
             })),
             start_time_us,
         );
@@ -13,25 +13,38 @@ use super::ApiServer;
 use super::request::actions::parse_put_actions;
 use super::request::balloon::{parse_get_balloon, parse_patch_balloon, parse_put_balloon};
 use super::request::boot_source::parse_put_boot_source;
-use super::request::cpu_configuration::parse_put_cpu_config;
+use super::request::cold_memory::parse_put_cold_memory;
+use super::request::config_drive::parse_put_config_drive;
+use super::request::cpu_configuration::{parse_get_cpu_config, parse_put_cpu_config};
 use super::request::drive::{parse_patch_drive, parse_put_drive};
-use super::request::entropy::parse_put_entropy;
+use super::request::entropy::{parse_patch_entropy, parse_put_entropy};
+use super::request::firmware::parse_put_firmware;
+use super::request::fw_cfg::parse_put_fw_cfg;
+use super::request::gpio::parse_put_gpio;
+use super::request::i2c::parse_put_i2c;
 use super::request::instance_info::parse_get_instance_info;
+use super::request::interrupt_affinity::parse_put_interrupt_affinity;
+use super::request::io_cpu_budget::parse_put_io_cpu_budget;
 use super::request::logger::parse_put_logger;
 use super::request::machine_configuration::{
     parse_get_machine_config, parse_patch_machine_config, parse_put_machine_config,
 };
+use super::request::memory_backing::parse_put_memory_backing;
 use super::request::metrics::parse_put_metrics;
 use super::request::mmds::{parse_get_mmds, parse_patch_mmds, parse_put_mmds};
-use super::request::net::{parse_patch_net, parse_put_net};
+use super::request::net::{parse_get_net, parse_patch_net, parse_put_net};
+use super::request::p9::parse_put_p9;
 use super::request::pmem::parse_put_pmem;
-use super::request::rdma::parse_put_rdma;
+use super::request::rdma::{parse_get_rdma, parse_patch_rdma, parse_put_rdma};
+use super::request::resource_usage::parse_get_resource_usage;
+use super::request::smbios::parse_put_smbios;
 use super::request::snapshot::{parse_patch_vm_state, parse_put_snapshot};
 use super::request::version::parse_get_version;
-use super::request::vsock::parse_put_vsock;
+use super::request::vsock::{parse_get_vsock_connections, parse_put_vsock};
 use crate::api_server::request::hotplug::memory::{
     parse_get_memory_hotplug, parse_patch_memory_hotplug, parse_put_memory_hotplug,
 };
+use crate::api_server::request::hotplug::remove::parse_put_hotplug_remove;
 use crate::api_server::request::serial::parse_put_serial;
 
 #[derive(Debug)]
@@ -83,47 +96,81 @@ impl TryFrom<&Request> for ParsedRequest {
         match (request.method(), path, request.body.as_ref()) {
             (Method::Get, "", None) => parse_get_instance_info(),
             (Method::Get, "balloon", None) => parse_get_balloon(path_tokens),
+            (Method::Get, "cpu-config", None) => parse_get_cpu_config(),
             (Method::Get, "version", None) => parse_get_version(),
-            (Method::Get, "vm", None) if path_tokens.next() == Some("config") => {
-                Ok(ParsedRequest::new_sync(VmmAction::GetFullVmConfig))
-            }
+            (Method::Get, "vm", None) => match path_tokens.next() {
+                Some("config") => Ok(ParsedRequest::new_sync(VmmAction::GetFullVmConfig)),
+                Some("resources") => parse_get_resource_usage(),
+                _ => Err(RequestError::InvalidPathMethod(path.to_string(), Method::Get)),
+            },
             (Method::Get, "machine-config", None) => parse_get_machine_config(),
             (Method::Get, "mmds", None) => parse_get_mmds(),
             (Method::Get, "hotplug", None) if path_tokens.next() == Some("memory") => {
                 parse_get_memory_hotplug()
             }
+            (Method::Get, "vsock", None) if path_tokens.next() == Some("connections") => {
+                parse_get_vsock_connections()
+            }
+            (Method::Get, "network-interfaces", None) => parse_get_net(path_tokens),
+            (Method::Get, "rdma-devices", None) => parse_get_rdma(path_tokens),
             (Method::Get, _, Some(_)) => method_to_error(Method::Get),
             (Method::Put, "actions", Some(body)) => parse_put_actions(body),
             (Method::Put, "balloon", Some(body)) => parse_put_balloon(body),
             (Method::Put, "boot-source", Some(body)) => parse_put_boot_source(body),
+            (Method::Put, "cold-memory", Some(body)) => parse_put_cold_memory(body),
+            (Method::Put, "config-drive", Some(body)) => parse_put_config_drive(body),
             (Method::Put, "cpu-config", Some(body)) => parse_put_cpu_config(body),
-            (Method::Put, "drives", Some(body)) => parse_put_drive(body, path_tokens.next()),
+            (Method::Put, "drives", Some(body)) => {
+                parse_put_drive(body, path_tokens.next(), &request.files)
+            }
+            (Method::Put, "firmware", Some(body)) => parse_put_firmware(body),
+            (Method::Put, "fw-cfg", Some(body)) => parse_put_fw_cfg(body),
+            (Method::Put, "smbios", Some(body)) => parse_put_smbios(body),
             (Method::Put, "pmem", Some(body)) => parse_put_pmem(body, path_tokens.next()),
+            (Method::Put, "9p", Some(body)) => parse_put_p9(body, path_tokens.next()),
+            (Method::Put, "gpio", Some(body)) => parse_put_gpio(body, path_tokens.next()),
+            (Method::Put, "i2c", Some(body)) => parse_put_i2c(body, path_tokens.next()),
+            (Method::Put, "interrupt-affinity", Some(body)) => {
+                parse_put_interrupt_affinity(body, path_tokens.next())
+            }
+            (Method::Put, "io-cpu-budget", Some(body)) => parse_put_io_cpu_budget(body),
             (Method::Put, "logger", Some(body)) => parse_put_logger(body),
             (Method::Put, "serial", Some(body)) => parse_put_serial(body),
             (Method::Put, "machine-config", Some(body)) => parse_put_machine_config(body),
+            (Method::Put, "memory-backing", Some(body)) => parse_put_memory_backing(body),
             (Method::Put, "metrics", Some(body)) => parse_put_metrics(body),
             (Method::Put, "mmds", Some(body)) => parse_put_mmds(body, path_tokens.next()),
             (Method::Put, "network-interfaces", Some(body)) => {
-                parse_put_net(body, path_tokens.next())
+                parse_put_net(body, path_tokens.next(), &request.files)
             }
             (Method::Put, "rdma-devices", Some(body)) => {
                 parse_put_rdma(body, path_tokens.next())
             }
-            (Method::Put, "snapshot", Some(body)) => parse_put_snapshot(body, path_tokens.next()),
+            (Method::Put, "snapshot", Some(body)) => {
+                parse_put_snapshot(body, path_tokens.next(), &request.files)
+            }
             (Method::Put, "vsock", Some(body)) => parse_put_vsock(body),
             (Method::Put, "entropy", Some(body)) => parse_put_entropy(body),
-            (Method::Put, "hotplug", Some(body)) if path_tokens.next() == Some("memory") => {
-                parse_put_memory_hotplug(body)
-            }
+            (Method::Put, "hotplug", Some(body)) => match path_tokens.next() {
+                Some("memory") => parse_put_memory_hotplug(body),
+                Some("remove") => parse_put_hotplug_remove(body),
+                _ => Err(RequestError::InvalidPathMethod(
+                    path.to_string(),
+                    Method::Put,
+                )),
+            },
             (Method::Put, _, None) => method_to_error(Method::Put),
             (Method::Patch, "balloon", body) => parse_patch_balloon(body, path_tokens),
             (Method::Patch, "drives", Some(body)) => parse_patch_drive(body, path_tokens.next()),
+            (Method::Patch, "entropy", Some(body)) => parse_patch_entropy(body),
             (Method::Patch, "machine-config", Some(body)) => parse_patch_machine_config(body),
-            (Method::Patch, "mmds", Some(body)) => parse_patch_mmds(body),
+            (Method::Patch, "mmds", Some(body)) => parse_patch_mmds(body, path_tokens.next()),
             (Method::Patch, "network-interfaces", Some(body)) => {
                 parse_patch_net(body, path_tokens.next())
             }
+            (Method::Patch, "rdma-devices", Some(body)) => {
+                parse_patch_rdma(body, path_tokens.next())
+            }
             (Method::Patch, "vm", Some(body)) => parse_patch_vm_state(body),
             (Method::Patch, "hotplug", Some(body)) if path_tokens.next() == Some("memory") => {
                 parse_patch_memory_hotplug(body)
@@ -200,6 +247,17 @@ impl ParsedRequest {
                     &serde_json::json!({ "firecracker_version": version.as_str() }),
                 ),
                 VmmData::FullVmConfig(config) => Self::success_response_with_data(config),
+                VmmData::CpuConfiguration(template) => Self::success_response_with_data(template),
+                VmmData::VsockConnections(connections) => {
+                    Self::success_response_with_data(connections)
+                }
+                VmmData::NetworkFlows(flows) => Self::success_response_with_data(flows),
+                VmmData::RdmaDeviceStatus(status) => Self::success_response_with_data(status),
+                VmmData::ResourceUsage(usage) => Self::success_response_with_data(usage),
+                VmmData::SnapshotValidation(report) => Self::success_response_with_data(report),
+                VmmData::GracefulShutdownOutcome(outcome) => {
+                    Self::success_response_with_data(outcome)
+                }
             },
             Err(vmm_action_error) => {
                 let mut response = match vmm_action_error {
@@ -344,13 +402,20 @@ pub mod tests {
 
     use micro_http::HttpConnection;
     use vmm::builder::StartMicrovmError;
+    use vmm::cpu_config::templates::CustomCpuTemplate;
     use vmm::cpu_config::templates::test_utils::build_test_template;
     use vmm::devices::virtio::balloon::device::HintingStatus;
+    use vmm::devices::virtio::net::flows::NetworkFlows;
+    use vmm::devices::virtio::rdma::device::{RdmaBackendState, RdmaDeviceStatus};
+    use vmm::devices::virtio::vsock::VsockConnectionInfo;
+    use vmm::persist::SnapshotValidationReport;
+    use vmm::resource_usage::{ResourceUsage, VcpuUsage};
     use vmm::resources::VmmConfig;
     use vmm::rpc_interface::VmmActionError;
     use vmm::vmm_config::balloon::{BalloonDeviceConfig, BalloonStats};
     use vmm::vmm_config::instance_info::InstanceInfo;
     use vmm::vmm_config::machine_config::MachineConfig;
+    use vmm::vmm_config::shutdown::{GracefulShutdownOutcome, ShutdownPath};
 
     use super::*;
 
@@ -601,6 +666,9 @@ pub mod tests {
                 VmmData::FullVmConfig(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
+                VmmData::CpuConfiguration(template) => {
+                    http_response(&serde_json::to_string(template).unwrap(), 200)
+                }
                 VmmData::MachineConfiguration(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
@@ -614,6 +682,24 @@ pub mod tests {
                     &serde_json::json!({ "firecracker_version": version.as_str() }).to_string(),
                     200,
                 ),
+                VmmData::VsockConnections(connections) => {
+                    http_response(&serde_json::to_string(connections).unwrap(), 200)
+                }
+                VmmData::NetworkFlows(flows) => {
+                    http_response(&serde_json::to_string(flows).unwrap(), 200)
+                }
+                VmmData::RdmaDeviceStatus(status) => {
+                    http_response(&serde_json::to_string(status).unwrap(), 200)
+                }
+                VmmData::ResourceUsage(usage) => {
+                    http_response(&serde_json::to_string(usage).unwrap(), 200)
+                }
+                VmmData::SnapshotValidation(report) => {
+                    http_response(&serde_json::to_string(report).unwrap(), 200)
+                }
+                VmmData::GracefulShutdownOutcome(outcome) => {
+                    http_response(&serde_json::to_string(outcome).unwrap(), 200)
+                }
             };
             let response = ParsedRequest::convert_to_response(&data);
             response.write_all(&mut buf).unwrap();
@@ -631,10 +717,45 @@ pub mod tests {
         }));
         verify_ok_response_with(VmmData::Empty);
         verify_ok_response_with(VmmData::FullVmConfig(VmmConfig::default()));
+        verify_ok_response_with(VmmData::CpuConfiguration(CustomCpuTemplate::default()));
         verify_ok_response_with(VmmData::MachineConfiguration(MachineConfig::default()));
         verify_ok_response_with(VmmData::MmdsValue(serde_json::from_str("{}").unwrap()));
         verify_ok_response_with(VmmData::InstanceInformation(InstanceInfo::default()));
         verify_ok_response_with(VmmData::VmmVersion(String::default()));
+        verify_ok_response_with(VmmData::VsockConnections(vec![VsockConnectionInfo {
+            local_port: 52,
+            peer_port: 1025,
+            host_initiated: false,
+            state: "established",
+            rx_bytes: 1,
+            tx_bytes: 2,
+        }]));
+        verify_ok_response_with(VmmData::NetworkFlows(NetworkFlows {
+            sample_rate: 10,
+            sampled_frames: 0,
+            flows: vec![],
+        }));
+        verify_ok_response_with(VmmData::RdmaDeviceStatus(RdmaDeviceStatus {
+            id: String::from("rdma0"),
+            link_up: false,
+            backend_state: RdmaBackendState::Down,
+            reconnect_attempts: 3,
+        }));
+        verify_ok_response_with(VmmData::ResourceUsage(ResourceUsage {
+            vmm_rss_bytes: 1 << 20,
+            vcpus: vec![VcpuUsage {
+                index: 0,
+                cpu_time_us: 10,
+            }],
+            ..Default::default()
+        }));
+        verify_ok_response_with(VmmData::SnapshotValidation(
+            SnapshotValidationReport::default(),
+        ));
+        verify_ok_response_with(VmmData::GracefulShutdownOutcome(GracefulShutdownOutcome {
+            path: ShutdownPath::Forced,
+            elapsed_ms: 30_000,
+        }));
 
         // Error.
         let error = VmmActionError::StartMicrovm(StartMicrovmError::MissingKernelConfig);
@@ -731,6 +852,96 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_get_cpu_config() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/cpu-config", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            ParsedRequest::try_from(&req).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GetCpuConfiguration)
+        );
+    }
+
+    #[test]
+    fn test_try_from_get_vsock_connections() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/vsock/connections", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            ParsedRequest::try_from(&req).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GetVsockConnections)
+        );
+
+        // Only the connections of the vsock device can be retrieved.
+        sender
+            .write_all(http_request("GET", "/vsock", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap_err();
+    }
+
+    #[test]
+    fn test_try_from_get_resource_usage() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/vm/resources", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            ParsedRequest::try_from(&req).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GetResourceUsage)
+        );
+
+        sender
+            .write_all(http_request("GET", "/vm/usage", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap_err();
+    }
+
+    #[test]
+    fn test_try_from_get_network_flows() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/network-interfaces/eth0/flows", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            ParsedRequest::try_from(&req).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GetNetworkFlows(String::from("eth0")))
+        );
+    }
+
+    #[test]
+    fn test_try_from_get_rdma() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/rdma-devices/rdma0", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            ParsedRequest::try_from(&req).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GetRdmaDevice(String::from("rdma0")))
+        );
+    }
+
     #[test]
     fn test_try_from_put_actions() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -773,6 +984,20 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_patch_entropy() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"rate_limiter\": { \"bandwidth\" : { \"size\": 1000, \
+                    \"refill_time\": 100 } } }";
+        sender
+            .write_all(http_request("PATCH", "/entropy", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_boot() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -786,6 +1011,59 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_put_firmware() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"path\": \"string\", \"vars_path\": \"string\" }";
+        sender
+            .write_all(http_request("PUT", "/firmware", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_put_config_drive() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"drive_id\": \"cidata\", \"path_on_host\": \"cidata.img\", \
+                    \"user_data\": \"#cloud-config\" }";
+        sender
+            .write_all(http_request("PUT", "/config-drive", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_put_fw_cfg() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"entries\": { \"role\": \"db\" } }";
+        sender
+            .write_all(http_request("PUT", "/fw-cfg", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_put_smbios() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"manufacturer\": \"string\", \"serial_number\": \"string\" }";
+        sender
+            .write_all(http_request("PUT", "/smbios", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_drives() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -1079,6 +1357,14 @@ pub mod tests {
         connection.try_read().unwrap();
         let req = connection.pop_parsed_request().unwrap();
         ParsedRequest::try_from(&req).unwrap();
+
+        let body = "{ \"size_limit\": 1048576 }";
+        sender
+            .write_all(http_request("PATCH", "/mmds/config", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
     }
 
     #[test]
@@ -4,11 +4,11 @@
 use serde::{Deserialize, Serialize};
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
+#[cfg(target_arch = "x86_64")]
+use vmm::vmm_config::shutdown::GracefulShutdownConfig;
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
-use super::Body;
-#[cfg(target_arch = "aarch64")]
-use super::StatusCode;
+use super::{Body, StatusCode};
 
 // The names of the members from this enum must precisely correspond (as a string) to the possible
 // values of "action_type" from the json request body. This is useful to get a strongly typed
@@ -18,6 +18,7 @@ enum ActionType {
     FlushMetrics,
     InstanceStart,
     SendCtrlAltDel,
+    GracefulShutdown,
 }
 
 // The model of the json body from a sync request. We use Serde to transform each associated
@@ -26,6 +27,9 @@ enum ActionType {
 #[serde(deny_unknown_fields)]
 struct ActionBody {
     action_type: ActionType,
+    // Only valid for `GracefulShutdown`.
+    #[serde(default)]
+    timeout_ms: Option<u64>,
 }
 
 pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, RequestError> {
@@ -34,6 +38,16 @@ pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, RequestErr
         METRICS.put_api_requests.actions_fails.inc();
     })?;
 
+    if action_body.timeout_ms.is_some()
+        && !matches!(action_body.action_type, ActionType::GracefulShutdown)
+    {
+        METRICS.put_api_requests.actions_fails.inc();
+        return Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "timeout_ms is only valid for GracefulShutdown.".to_string(),
+        ));
+    }
+
     match action_body.action_type {
         ActionType::FlushMetrics => Ok(ParsedRequest::new_sync(VmmAction::FlushMetrics)),
         ActionType::InstanceStart => Ok(ParsedRequest::new_sync(VmmAction::StartMicroVm)),
@@ -48,6 +62,35 @@ pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, RequestErr
             #[cfg(target_arch = "x86_64")]
             Ok(ParsedRequest::new_sync(VmmAction::SendCtrlAltDel))
         }
+        ActionType::GracefulShutdown => {
+            // The ACPI power button is not available on aarch64, and the guest can't be asked to
+            // shut down in any other way.
+            #[cfg(target_arch = "aarch64")]
+            {
+                METRICS.put_api_requests.actions_fails.inc();
+                return Err(RequestError::Generic(
+                    StatusCode::BadRequest,
+                    "GracefulShutdown is not supported on aarch64, which has no ACPI power button."
+                        .to_string(),
+                ));
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            {
+                let mut config = GracefulShutdownConfig::default();
+                if let Some(timeout_ms) = action_body.timeout_ms {
+                    if timeout_ms == 0 {
+                        METRICS.put_api_requests.actions_fails.inc();
+                        return Err(RequestError::Generic(
+                            StatusCode::BadRequest,
+                            "timeout_ms must be greater than 0.".to_string(),
+                        ));
+                    }
+                    config.timeout_ms = timeout_ms;
+                }
+                Ok(ParsedRequest::new_sync(VmmAction::GracefulShutdown(config)))
+            }
+        }
     }
 }
 
@@ -98,6 +141,46 @@ mod tests {
             let req: ParsedRequest = ParsedRequest::new_sync(VmmAction::FlushMetrics);
             let result = parse_put_actions(&Body::new(json));
             assert_eq!(result.unwrap(), req);
+
+            let json = r#"{
+                "action_type": "FlushMetrics",
+                "timeout_ms": 1000
+            }"#;
+            parse_put_actions(&Body::new(json)).unwrap_err();
         }
     }
+
+    #[test]
+    fn test_parse_graceful_shutdown() {
+        let json = r#"{
+            "action_type": "GracefulShutdown"
+        }"#;
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            parse_put_actions(&Body::new(json)).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GracefulShutdown(
+                GracefulShutdownConfig::default()
+            ))
+        );
+        #[cfg(target_arch = "aarch64")]
+        parse_put_actions(&Body::new(json)).unwrap_err();
+
+        let json = r#"{
+            "action_type": "GracefulShutdown",
+            "timeout_ms": 5000
+        }"#;
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            parse_put_actions(&Body::new(json)).unwrap(),
+            ParsedRequest::new_sync(VmmAction::GracefulShutdown(GracefulShutdownConfig {
+                timeout_ms: 5000
+            }))
+        );
+
+        let json = r#"{
+            "action_type": "GracefulShutdown",
+            "timeout_ms": 0
+        }"#;
+        parse_put_actions(&Body::new(json)).unwrap_err();
+    }
 }
@@ -0,0 +1,61 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::cold_memory::ColdMemoryConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_cold_memory(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.cold_memory_count.inc();
+    let config = serde_json::from_slice::<ColdMemoryConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.cold_memory_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetColdMemory(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_cold_memory_request() {
+        parse_put_cold_memory(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with missing backing file.
+        let body = r#"{
+            "evict_batch_mib": 16
+        }"#;
+        parse_put_cold_memory(&Body::new(body)).unwrap_err();
+
+        // PUT with unknown fields.
+        let body = r#"{
+            "backing_file_path": "/tmp/cold",
+            "foo": "bar"
+        }"#;
+        parse_put_cold_memory(&Body::new(body)).unwrap_err();
+
+        // PUT with valid input fields.
+        let body = r#"{
+            "backing_file_path": "/tmp/cold",
+            "pressure_stall_us": 100000,
+            "pressure_window_us": 2000000,
+            "evict_batch_mib": 16
+        }"#;
+        let expected_config = ColdMemoryConfig {
+            backing_file_path: PathBuf::from("/tmp/cold"),
+            pressure_stall_us: 100_000,
+            pressure_window_us: 2_000_000,
+            evict_batch_mib: 16,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_cold_memory(&Body::new(body)).unwrap()),
+            VmmAction::SetColdMemory(expected_config)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::config_drive::ConfigDriveConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_config_drive(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.config_drive_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::SetConfigDrive(
+        serde_json::from_slice::<ConfigDriveConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.config_drive_fails.inc();
+        })?,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_drive_request() {
+        parse_put_config_drive(&Body::new("invalid_payload")).unwrap_err();
+        // Missing user data.
+        parse_put_config_drive(&Body::new(
+            r#"{"drive_id": "cidata", "path_on_host": "cidata.img"}"#,
+        ))
+        .unwrap_err();
+        parse_put_config_drive(&Body::new(
+            r#"{"drive_id": "cidata", "path_on_host": "cidata.img", "user_data": "", "foo": 1}"#,
+        ))
+        .unwrap_err();
+
+        let body = r##"{
+            "drive_id": "cidata",
+            "path_on_host": "cidata.img",
+            "user_data": "#cloud-config\n",
+            "meta_data": "instance-id: i-1234\n",
+            "network_config": "version: 2\n"
+        }"##;
+        let same_body = ConfigDriveConfig {
+            drive_id: String::from("cidata"),
+            path_on_host: String::from("cidata.img"),
+            user_data: String::from("#cloud-config\n"),
+            meta_data: String::from("instance-id: i-1234\n"),
+            network_config: Some(String::from("version: 2\n")),
+            vendor_data: None,
+        };
+        let parsed_req = parse_put_config_drive(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::SetConfigDrive(same_body))
+        );
+    }
+}
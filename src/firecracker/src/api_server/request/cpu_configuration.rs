@@ -8,6 +8,11 @@ use vmm::rpc_interface::VmmAction;
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
 
+pub(crate) fn parse_get_cpu_config() -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.cpu_cfg_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::GetCpuConfiguration))
+}
+
 pub(crate) fn parse_put_cpu_config(body: &Body) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.cpu_cfg_count.inc();
 
@@ -30,6 +35,19 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_cpu_config_request() {
+        let expected_count = METRICS.get_api_requests.cpu_cfg_count.count() + 1;
+        assert_eq!(
+            vmm_action_from_request(parse_get_cpu_config().unwrap()),
+            VmmAction::GetCpuConfiguration
+        );
+        assert_eq!(
+            METRICS.get_api_requests.cpu_cfg_count.count(),
+            expected_count
+        );
+    }
+
     #[test]
     fn test_parse_put_cpu_config_request() {
         let cpu_template = build_test_template();
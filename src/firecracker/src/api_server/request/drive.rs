@@ -1,8 +1,11 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::fs::File;
+
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::PassedFd;
 use vmm::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig};
 
 use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
@@ -11,6 +14,7 @@ use super::{Body, StatusCode};
 pub(crate) fn parse_put_drive(
     body: &Body,
     id_from_path: Option<&str>,
+    files: &[File],
 ) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.drive_count.inc();
     let id = if let Some(id) = id_from_path {
@@ -20,21 +24,43 @@ pub(crate) fn parse_put_drive(
         return Err(RequestError::EmptyID);
     };
 
-    let device_cfg = serde_json::from_slice::<BlockDeviceConfig>(body.raw()).inspect_err(|_| {
-        METRICS.put_api_requests.drive_fails.inc();
-    })?;
+    let mut device_cfg =
+        serde_json::from_slice::<BlockDeviceConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.drive_fails.inc();
+        })?;
 
     if id != device_cfg.drive_id {
         METRICS.put_api_requests.drive_fails.inc();
-        Err(RequestError::Generic(
+        return Err(RequestError::Generic(
             StatusCode::BadRequest,
             "The id from the path does not match the id from the body!".to_string(),
-        ))
-    } else {
-        Ok(ParsedRequest::new_sync(VmmAction::InsertBlockDevice(
-            device_cfg,
-        )))
+        ));
+    }
+
+    // A backing file descriptor passed along with the request is used instead of `path_on_host`.
+    match files {
+        [] => (),
+        [backing_file] => {
+            let backing_file = backing_file.try_clone().map_err(|err| {
+                METRICS.put_api_requests.drive_fails.inc();
+                RequestError::Generic(
+                    StatusCode::BadRequest,
+                    format!("Cannot use the passed backing file descriptor: {}", err),
+                )
+            })?;
+            device_cfg.backing_fd = Some(PassedFd(backing_file));
+        }
+        _ => {
+            METRICS.put_api_requests.drive_fails.inc();
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                "Only one backing file descriptor can be passed per drive.".to_string(),
+            ));
+        }
     }
+    Ok(ParsedRequest::new_sync(VmmAction::InsertBlockDevice(
+        device_cfg,
+    )))
 }
 
 pub(crate) fn parse_patch_drive(
@@ -198,15 +224,15 @@ mod tests {
 
     #[test]
     fn test_parse_put_drive_request() {
-        parse_put_drive(&Body::new("invalid_payload"), None).unwrap_err();
-        parse_put_drive(&Body::new("invalid_payload"), Some("id")).unwrap_err();
+        parse_put_drive(&Body::new("invalid_payload"), None, &[]).unwrap_err();
+        parse_put_drive(&Body::new("invalid_payload"), Some("id"), &[]).unwrap_err();
 
         // PUT with invalid fields.
         let body = r#"{
             "drive_id": "bar",
             "is_read_only": false
         }"#;
-        parse_put_drive(&Body::new(body), Some("2")).unwrap_err();
+        parse_put_drive(&Body::new(body), Some("2"), &[]).unwrap_err();
 
         // PUT with missing all optional fields.
         let body = r#"{
@@ -215,10 +241,10 @@ mod tests {
             "is_root_device": true,
             "is_read_only": true
         }"#;
-        parse_put_drive(&Body::new(body), Some("1000")).unwrap();
+        parse_put_drive(&Body::new(body), Some("1000"), &[]).unwrap();
 
         // PUT with invalid types on fields. Adding a drive_id as number instead of string.
-        parse_put_drive(&Body::new(body), Some("foo")).unwrap_err();
+        parse_put_drive(&Body::new(body), Some("foo"), &[]).unwrap_err();
 
         // PUT with the complete configuration.
         let body = r#"{
@@ -229,6 +255,11 @@ mod tests {
             "is_read_only": true,
             "cache_type": "Unsafe",
             "io_engine": "Sync",
+            "strict_ordering": true,
+            "host_cache": {
+                "advice": "Sequential",
+                "read_ahead_kib": 1024
+            },
             "rate_limiter": {
                 "bandwidth": {
                     "size": 0,
@@ -242,6 +273,31 @@ mod tests {
                 }
             }
         }"#;
-        parse_put_drive(&Body::new(body), Some("1000")).unwrap();
+        parse_put_drive(&Body::new(body), Some("1000"), &[]).unwrap();
+
+        // PUT with a passed backing file descriptor instead of `path_on_host`.
+        let body = r#"{
+            "drive_id": "1000",
+            "is_root_device": false,
+            "is_read_only": true
+        }"#;
+        let backing_file = vmm_sys_util::tempfile::TempFile::new().unwrap().into_file();
+        let action = vmm_action_from_request(
+            parse_put_drive(&Body::new(body), Some("1000"), &[backing_file]).unwrap(),
+        );
+        match action {
+            VmmAction::InsertBlockDevice(config) => {
+                assert!(config.path_on_host.is_none());
+                assert!(config.backing_fd.is_some());
+            }
+            _ => panic!("Test failed: Invalid parameters"),
+        }
+
+        // Only one backing file descriptor can be passed.
+        let files = [
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+        ];
+        parse_put_drive(&Body::new(body), Some("1000"), &files).unwrap_err();
     }
 }
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use vmm::rpc_interface::VmmAction;
-use vmm::vmm_config::entropy::EntropyDeviceConfig;
+use vmm::vmm_config::entropy::{EntropyDeviceConfig, EntropyDeviceUpdateConfig};
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
@@ -12,9 +12,15 @@ pub(crate) fn parse_put_entropy(body: &Body) -> Result<ParsedRequest, RequestErr
     Ok(ParsedRequest::new_sync(VmmAction::SetEntropyDevice(cfg)))
 }
 
+pub(crate) fn parse_patch_entropy(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let cfg = serde_json::from_slice::<EntropyDeviceUpdateConfig>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::UpdateEntropyDevice(cfg)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
     #[test]
     fn test_parse_put_entropy_request() {
@@ -30,4 +36,35 @@ mod tests {
         let body = r#"{}"#;
         parse_put_entropy(&Body::new(body)).unwrap();
     }
+
+    #[test]
+    fn test_parse_patch_entropy_request() {
+        parse_patch_entropy(&Body::new("invalid_payload")).unwrap_err();
+
+        // PATCH with invalid fields.
+        let body = r#"{
+            "some_id": 4
+        }"#;
+        parse_patch_entropy(&Body::new(body)).unwrap_err();
+
+        // PATCH with valid fields.
+        let body = r#"{
+            "rate_limiter": {
+                "bandwidth": {
+                    "size": 1000,
+                    "refill_time": 100
+                }
+            }
+        }"#;
+        let expected_config = EntropyDeviceUpdateConfig {
+            rate_limiter: Some(
+                serde_json::from_str(r#"{"bandwidth": {"size": 1000, "refill_time": 100}}"#)
+                    .unwrap(),
+            ),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_entropy(&Body::new(body)).unwrap()),
+            VmmAction::UpdateEntropyDevice(expected_config)
+        );
+    }
 }
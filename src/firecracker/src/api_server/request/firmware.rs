@@ -0,0 +1,44 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::firmware::FirmwareConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_firmware(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.firmware_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::ConfigureFirmware(
+        serde_json::from_slice::<FirmwareConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.firmware_fails.inc();
+        })?,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_firmware_request() {
+        parse_put_firmware(&Body::new("invalid_payload")).unwrap_err();
+        parse_put_firmware(&Body::new(r#"{"path": "/foo/bar", "foo": 1}"#)).unwrap_err();
+
+        let body = r#"{
+            "path": "/foo/OVMF.fd",
+            "vars_path": "/foo/OVMF_VARS.fd"
+        }"#;
+        let same_body = FirmwareConfig {
+            path: String::from("/foo/OVMF.fd"),
+            vars_path: Some(String::from("/foo/OVMF_VARS.fd")),
+        };
+        let parsed_req = parse_put_firmware(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::ConfigureFirmware(same_body))
+        );
+    }
+}
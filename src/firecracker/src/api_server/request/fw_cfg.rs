@@ -0,0 +1,46 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::fw_cfg::FwCfgConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_fw_cfg(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.fw_cfg_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::SetFwCfgDevice(
+        serde_json::from_slice::<FwCfgConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.fw_cfg_fails.inc();
+        })?,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fw_cfg_request() {
+        parse_put_fw_cfg(&Body::new("invalid_payload")).unwrap_err();
+        parse_put_fw_cfg(&Body::new(r#"{"entries": {}, "foo": 1}"#)).unwrap_err();
+        parse_put_fw_cfg(&Body::new(r#"{"entries": {"role": 1}}"#)).unwrap_err();
+
+        let body = r#"{
+            "entries": {
+                "role": "db"
+            }
+        }"#;
+        let mut same_body = FwCfgConfig::default();
+        same_body
+            .entries
+            .insert(String::from("role"), String::from("db"));
+        let parsed_req = parse_put_fw_cfg(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::SetFwCfgDevice(same_body))
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::gpio::GpioConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_put_gpio(
+    body: &Body,
+    id_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.gpio_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.put_api_requests.gpio_fails.inc();
+        return Err(RequestError::EmptyID);
+    };
+
+    let device_cfg = serde_json::from_slice::<GpioConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.gpio_fails.inc();
+    })?;
+
+    if id != device_cfg.id {
+        METRICS.put_api_requests.gpio_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "The id from the path does not match the id from the body!".to_string(),
+        ))
+    } else {
+        Ok(ParsedRequest::new_sync(VmmAction::InsertGpioDevice(
+            device_cfg,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::vmm_config::gpio::GpioMockConfig;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_gpio_request() {
+        parse_put_gpio(&Body::new("invalid_payload"), None).unwrap_err();
+        parse_put_gpio(&Body::new("invalid_payload"), Some("id")).unwrap_err();
+
+        let body = r#"{
+            "id": "bar",
+            "host_chip": "/dev/gpiochip0"
+        }"#;
+        parse_put_gpio(&Body::new(body), Some("1")).unwrap_err();
+        let body = r#"{
+            "id": "1",
+            "host_chip": "/dev/gpiochip0",
+            "foo": "1"
+        }"#;
+        parse_put_gpio(&Body::new(body), Some("1")).unwrap_err();
+
+        let body = r#"{
+            "id": "1000",
+            "host_chip": "/dev/gpiochip0"
+        }"#;
+        let r = vmm_action_from_request(parse_put_gpio(&Body::new(body), Some("1000")).unwrap());
+        let expected_config = GpioConfig {
+            id: "1000".to_string(),
+            host_chip: Some("/dev/gpiochip0".to_string()),
+            mock: None,
+        };
+        assert_eq!(r, VmmAction::InsertGpioDevice(expected_config));
+
+        let body = r#"{
+            "id": "1000",
+            "mock": {
+                "socket_path": "/tmp/gpio.sock",
+                "num_lines": 8,
+                "line_names": ["led"]
+            }
+        }"#;
+        let r = vmm_action_from_request(parse_put_gpio(&Body::new(body), Some("1000")).unwrap());
+        let expected_config = GpioConfig {
+            id: "1000".to_string(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: "/tmp/gpio.sock".to_string(),
+                num_lines: 8,
+                line_names: vec!["led".to_string()],
+            }),
+        };
+        assert_eq!(r, VmmAction::InsertGpioDevice(expected_config));
+    }
+}
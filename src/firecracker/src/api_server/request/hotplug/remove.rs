@@ -0,0 +1,107 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use micro_http::Body;
+#[cfg(target_arch = "aarch64")]
+use micro_http::StatusCode;
+use vmm::logger::{IncMetric, METRICS};
+#[cfg(target_arch = "x86_64")]
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::device_removal::DeviceRemovalConfig;
+
+use crate::api_server::parsed_request::{ParsedRequest, RequestError};
+
+pub(crate) fn parse_put_hotplug_remove(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.hotplug_remove_count.inc();
+    let config = serde_json::from_slice::<DeviceRemovalConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.hotplug_remove_fails.inc();
+    })?;
+
+    // The guest is asked to eject devices through ACPI, which is not available on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    {
+        METRICS.put_api_requests.hotplug_remove_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Cannot remove device {}: device removal is not supported on aarch64.",
+                config.id
+            ),
+        ))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    Ok(ParsedRequest::new_sync(VmmAction::RemoveDevice(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "x86_64")]
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_hotplug_remove_request() {
+        parse_put_hotplug_remove(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with an unknown device type.
+        let body = r#"{
+            "device_type": "Balloon",
+            "id": "balloon"
+        }"#;
+        parse_put_hotplug_remove(&Body::new(body)).unwrap_err();
+
+        // PUT with an unknown field.
+        let body = r#"{
+            "device_type": "Drive",
+            "id": "scratch",
+            "force": true
+        }"#;
+        parse_put_hotplug_remove(&Body::new(body)).unwrap_err();
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            use vmm::vmm_config::device_removal::{
+                DEFAULT_REMOVAL_TIMEOUT_MS, RemovableDeviceType,
+            };
+
+            // PUT with the default timeout.
+            let body = r#"{
+                "device_type": "NetworkInterface",
+                "id": "eth0"
+            }"#;
+            assert_eq!(
+                vmm_action_from_request(parse_put_hotplug_remove(&Body::new(body)).unwrap()),
+                VmmAction::RemoveDevice(DeviceRemovalConfig {
+                    device_type: RemovableDeviceType::NetworkInterface,
+                    id: String::from("eth0"),
+                    timeout_ms: DEFAULT_REMOVAL_TIMEOUT_MS,
+                })
+            );
+
+            // PUT with a timeout.
+            let body = r#"{
+                "device_type": "RdmaDevice",
+                "id": "rdma0",
+                "timeout_ms": 100
+            }"#;
+            assert_eq!(
+                vmm_action_from_request(parse_put_hotplug_remove(&Body::new(body)).unwrap()),
+                VmmAction::RemoveDevice(DeviceRemovalConfig {
+                    device_type: RemovableDeviceType::RdmaDevice,
+                    id: String::from("rdma0"),
+                    timeout_ms: 100,
+                })
+            );
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            let body = r#"{
+                "device_type": "Drive",
+                "id": "scratch"
+            }"#;
+            parse_put_hotplug_remove(&Body::new(body)).unwrap_err();
+        }
+    }
+}
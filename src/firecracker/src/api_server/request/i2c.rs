@@ -0,0 +1,91 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::i2c::I2cConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_put_i2c(
+    body: &Body,
+    id_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.i2c_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.put_api_requests.i2c_fails.inc();
+        return Err(RequestError::EmptyID);
+    };
+
+    let device_cfg = serde_json::from_slice::<I2cConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.i2c_fails.inc();
+    })?;
+
+    if id != device_cfg.id {
+        METRICS.put_api_requests.i2c_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "The id from the path does not match the id from the body!".to_string(),
+        ))
+    } else {
+        Ok(ParsedRequest::new_sync(VmmAction::InsertI2cDevice(
+            device_cfg,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::vmm_config::i2c::I2cMockDeviceConfig;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_i2c_request() {
+        parse_put_i2c(&Body::new("invalid_payload"), None).unwrap_err();
+        parse_put_i2c(&Body::new("invalid_payload"), Some("id")).unwrap_err();
+
+        let body = r#"{
+            "id": "bar",
+            "host_adapter": "/dev/i2c-1"
+        }"#;
+        parse_put_i2c(&Body::new(body), Some("1")).unwrap_err();
+        let body = r#"{
+            "id": "1",
+            "host_adapter": "/dev/i2c-1",
+            "foo": "1"
+        }"#;
+        parse_put_i2c(&Body::new(body), Some("1")).unwrap_err();
+
+        let body = r#"{
+            "id": "1000",
+            "host_adapter": "/dev/i2c-1"
+        }"#;
+        let r = vmm_action_from_request(parse_put_i2c(&Body::new(body), Some("1000")).unwrap());
+        let expected_config = I2cConfig {
+            id: "1000".to_string(),
+            host_adapter: Some("/dev/i2c-1".to_string()),
+            mock_devices: vec![],
+        };
+        assert_eq!(r, VmmAction::InsertI2cDevice(expected_config));
+
+        let body = r#"{
+            "id": "1000",
+            "mock_devices": [{"address": 80, "registers": [1, 2]}]
+        }"#;
+        let r = vmm_action_from_request(parse_put_i2c(&Body::new(body), Some("1000")).unwrap());
+        let expected_config = I2cConfig {
+            id: "1000".to_string(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig {
+                address: 80,
+                registers: vec![1, 2],
+            }],
+        };
+        assert_eq!(r, VmmAction::InsertI2cDevice(expected_config));
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::interrupt_affinity::InterruptAffinityConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_put_interrupt_affinity(
+    body: &Body,
+    id_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.interrupt_affinity_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.put_api_requests.interrupt_affinity_fails.inc();
+        return Err(RequestError::EmptyID);
+    };
+
+    let config =
+        serde_json::from_slice::<InterruptAffinityConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.interrupt_affinity_fails.inc();
+        })?;
+
+    if id != config.device_id {
+        METRICS.put_api_requests.interrupt_affinity_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "The id from the path does not match the id from the body!".to_string(),
+        ))
+    } else {
+        Ok(ParsedRequest::new_sync(VmmAction::SetInterruptAffinity(
+            config,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_interrupt_affinity_request() {
+        parse_put_interrupt_affinity(&Body::new("invalid_payload"), None).unwrap_err();
+        parse_put_interrupt_affinity(&Body::new("invalid_payload"), Some("eth0")).unwrap_err();
+
+        // PUT with missing vCPUs.
+        let body = r#"{
+            "device_id": "eth0"
+        }"#;
+        parse_put_interrupt_affinity(&Body::new(body), Some("eth0")).unwrap_err();
+
+        // PUT with mismatched IDs.
+        let body = r#"{
+            "device_id": "eth0",
+            "queue_vcpus": [1, 1]
+        }"#;
+        parse_put_interrupt_affinity(&Body::new(body), Some("eth1")).unwrap_err();
+
+        // PUT with valid input fields.
+        let expected_config = InterruptAffinityConfig {
+            device_id: "eth0".to_string(),
+            queue_vcpus: vec![1, 1],
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_interrupt_affinity(&Body::new(body), Some("eth0")).unwrap()
+            ),
+            VmmAction::SetInterruptAffinity(expected_config)
+        );
+    }
+}
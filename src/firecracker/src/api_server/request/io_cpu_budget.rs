@@ -0,0 +1,61 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::io_cpu_budget::IoCpuBudgetConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_io_cpu_budget(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.io_cpu_budget_count.inc();
+    let config = serde_json::from_slice::<IoCpuBudgetConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.io_cpu_budget_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetIoCpuBudget(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_io_cpu_budget_request() {
+        parse_put_io_cpu_budget(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with missing CPU share.
+        let body = r#"{
+            "period_ms": 100
+        }"#;
+        parse_put_io_cpu_budget(&Body::new(body)).unwrap_err();
+
+        // PUT with valid input fields with defaults.
+        let body = r#"{
+            "cpu_percent": 30
+        }"#;
+        let expected_config = IoCpuBudgetConfig {
+            cpu_percent: 30,
+            period_ms: 100,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_io_cpu_budget(&Body::new(body)).unwrap()),
+            VmmAction::SetIoCpuBudget(expected_config)
+        );
+
+        // PUT with valid input fields.
+        let body = r#"{
+            "cpu_percent": 30,
+            "period_ms": 20
+        }"#;
+        let expected_config = IoCpuBudgetConfig {
+            cpu_percent: 30,
+            period_ms: 20,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_io_cpu_budget(&Body::new(body)).unwrap()),
+            VmmAction::SetIoCpuBudget(expected_config)
+        );
+    }
+}
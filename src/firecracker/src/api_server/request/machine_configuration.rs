@@ -74,7 +74,7 @@ pub(crate) fn parse_patch_machine_config(body: &Body) -> Result<ParsedRequest, R
 #[cfg(test)]
 mod tests {
     use vmm::cpu_config::templates::StaticCpuTemplate;
-    use vmm::vmm_config::machine_config::HugePageConfig;
+    use vmm::vmm_config::machine_config::{CpuTopology, HugePageConfig};
 
     use super::*;
     use crate::api_server::parsed_request::tests::{depr_action_from_req, vmm_action_from_request};
@@ -123,6 +123,10 @@ mod tests {
                 cpu_template: None,
                 track_dirty_pages: Some(false),
                 huge_pages: Some(expected),
+                cpu_topology: None,
+                tsc: None,
+                ipa_size: None,
+                power_button: None,
                 #[cfg(feature = "gdb")]
                 gdb_socket_path: None,
             };
@@ -144,6 +148,10 @@ mod tests {
             cpu_template: Some(StaticCpuTemplate::None),
             track_dirty_pages: Some(false),
             huge_pages: Some(HugePageConfig::None),
+            cpu_topology: None,
+            tsc: None,
+            ipa_size: None,
+            power_button: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         };
@@ -165,6 +173,10 @@ mod tests {
             cpu_template: None,
             track_dirty_pages: Some(true),
             huge_pages: Some(HugePageConfig::None),
+            cpu_topology: None,
+            tsc: None,
+            ipa_size: None,
+            power_button: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         };
@@ -190,6 +202,10 @@ mod tests {
                 cpu_template: Some(StaticCpuTemplate::T2),
                 track_dirty_pages: Some(true),
                 huge_pages: Some(HugePageConfig::None),
+                cpu_topology: None,
+                tsc: None,
+                ipa_size: None,
+                power_button: None,
                 #[cfg(feature = "gdb")]
                 gdb_socket_path: None,
             };
@@ -217,6 +233,10 @@ mod tests {
             cpu_template: None,
             track_dirty_pages: Some(true),
             huge_pages: Some(HugePageConfig::None),
+            cpu_topology: None,
+            tsc: None,
+            ipa_size: None,
+            power_button: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         };
@@ -232,6 +252,39 @@ mod tests {
             "huge_pages": "7M"
         }"#;
         parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 7. Test that the CPU topology is passed through.
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "cpu_topology": {
+                "sockets": 2,
+                "cores_per_socket": 4,
+                "threads_per_core": 1
+            }
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            cpu_topology: Some(CpuTopology {
+                sockets: 2,
+                cores_per_socket: 4,
+                threads_per_core: 1,
+            }),
+            tsc: None,
+            ipa_size: None,
+            power_button: None,
+            #[cfg(feature = "gdb")]
+            gdb_socket_path: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateMachineConfiguration(expected_config)
+        );
     }
 
     #[test]
@@ -0,0 +1,66 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::memory_backing::MemoryBackingConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_memory_backing(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.memory_backing_count.inc();
+    let config = serde_json::from_slice::<MemoryBackingConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.memory_backing_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetMemoryBacking(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_memory_backing_request() {
+        parse_put_memory_backing(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with missing path.
+        let body = r#"{
+            "flush_on_pause": true
+        }"#;
+        parse_put_memory_backing(&Body::new(body)).unwrap_err();
+
+        // PUT with valid input fields with defaults.
+        let body = r#"{
+            "path": "/tmp/guest_mem"
+        }"#;
+        let expected_config = MemoryBackingConfig {
+            path: PathBuf::from("/tmp/guest_mem"),
+            writeback_interval_ms: None,
+            flush_on_pause: false,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_memory_backing(&Body::new(body)).unwrap()),
+            VmmAction::SetMemoryBacking(expected_config)
+        );
+
+        // PUT with valid input fields.
+        let body = r#"{
+            "path": "/tmp/guest_mem",
+            "writeback_interval_ms": 500,
+            "flush_on_pause": true
+        }"#;
+        let expected_config = MemoryBackingConfig {
+            path: PathBuf::from("/tmp/guest_mem"),
+            writeback_interval_ms: Some(500),
+            flush_on_pause: true,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_memory_backing(&Body::new(body)).unwrap()),
+            VmmAction::SetMemoryBacking(expected_config)
+        );
+    }
+}
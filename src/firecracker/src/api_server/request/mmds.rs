@@ -5,7 +5,7 @@ use micro_http::StatusCode;
 use vmm::logger::{IncMetric, METRICS};
 use vmm::mmds::data_store::MmdsVersion;
 use vmm::rpc_interface::VmmAction;
-use vmm::vmm_config::mmds::MmdsConfig;
+use vmm::vmm_config::mmds::{MmdsConfig, MmdsConfigUpdate};
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
@@ -56,19 +56,40 @@ pub(crate) fn parse_put_mmds(
     }
 }
 
-pub(crate) fn parse_patch_mmds(body: &Body) -> Result<ParsedRequest, RequestError> {
+pub(crate) fn parse_patch_mmds(
+    body: &Body,
+    path_second_token: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
     METRICS.patch_api_requests.mmds_count.inc();
-    Ok(ParsedRequest::new_sync(VmmAction::PatchMMDS(
-        serde_json::from_slice(body.raw()).inspect_err(|_| {
+    match path_second_token {
+        None => Ok(ParsedRequest::new_sync(VmmAction::PatchMMDS(
+            serde_json::from_slice(body.raw()).inspect_err(|_| {
+                METRICS.patch_api_requests.mmds_fails.inc();
+            })?,
+        ))),
+        Some("config") => {
+            let update: MmdsConfigUpdate =
+                serde_json::from_slice(body.raw()).inspect_err(|_| {
+                    METRICS.patch_api_requests.mmds_fails.inc();
+                })?;
+            Ok(ParsedRequest::new_sync(VmmAction::UpdateMmdsConfiguration(
+                update,
+            )))
+        }
+        Some(unrecognized) => {
             METRICS.patch_api_requests.mmds_fails.inc();
-        })?,
-    )))
+            Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                format!("Unrecognized PATCH request path `{}`.", unrecognized),
+            ))
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::api_server::parsed_request::tests::depr_action_from_req;
+    use crate::api_server::parsed_request::tests::{depr_action_from_req, vmm_action_from_request};
 
     #[test]
     fn test_parse_get_mmds_request() {
@@ -166,9 +187,26 @@ mod tests {
         let body = r#"{
             "foo": "bar"
         }"#;
-        parse_patch_mmds(&Body::new(body)).unwrap();
+        parse_patch_mmds(&Body::new(body), None).unwrap();
         assert!(METRICS.patch_api_requests.mmds_count.count() > 0);
-        parse_patch_mmds(&Body::new("invalid_body")).unwrap_err();
+        parse_patch_mmds(&Body::new("invalid_body"), None).unwrap_err();
         assert!(METRICS.patch_api_requests.mmds_fails.count() > 0);
+
+        // Test `config` path.
+        let body = r#"{
+            "size_limit": 1048576
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_patch_mmds(&Body::new(body), Some("config")).unwrap()),
+            VmmAction::UpdateMmdsConfiguration(MmdsConfigUpdate {
+                size_limit: 1048576
+            })
+        );
+        let body = r#"{
+            "version": "V2"
+        }"#;
+        parse_patch_mmds(&Body::new(body), Some("config")).unwrap_err();
+        parse_patch_mmds(&Body::new("{}"), Some("config")).unwrap_err();
+        parse_patch_mmds(&Body::new("{}"), Some("invalid_path")).unwrap_err();
     }
 }
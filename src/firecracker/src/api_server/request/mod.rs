@@ -4,19 +4,31 @@
 pub mod actions;
 pub mod balloon;
 pub mod boot_source;
+pub mod cold_memory;
+pub mod config_drive;
 pub mod cpu_configuration;
 pub mod drive;
 pub mod entropy;
+pub mod firmware;
+pub mod fw_cfg;
+pub mod gpio;
 pub mod hotplug;
+pub mod i2c;
 pub mod instance_info;
+pub mod interrupt_affinity;
+pub mod io_cpu_budget;
 pub mod logger;
 pub mod machine_configuration;
+pub mod memory_backing;
 pub mod metrics;
 pub mod mmds;
 pub mod net;
+pub mod p9;
 pub mod pmem;
 pub mod rdma;
+pub mod resource_usage;
 pub mod serial;
+pub mod smbios;
 pub mod snapshot;
 pub mod version;
 pub mod vsock;
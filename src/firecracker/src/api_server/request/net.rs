@@ -1,16 +1,41 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::fs::File;
+
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::PassedFd;
 use vmm::vmm_config::net::{NetworkInterfaceConfig, NetworkInterfaceUpdateConfig};
 
 use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
 use super::{Body, StatusCode};
 
+pub(crate) fn parse_get_net<'a, T>(mut path_tokens: T) -> Result<ParsedRequest, RequestError>
+where
+    T: Iterator<Item = &'a str>,
+{
+    METRICS.get_api_requests.network_flows_count.inc();
+    let id = checked_id(path_tokens.next().ok_or(RequestError::EmptyID)?)?;
+    match path_tokens.next() {
+        Some("flows") => Ok(ParsedRequest::new_sync(VmmAction::GetNetworkFlows(
+            id.to_string(),
+        ))),
+        Some(unknown_path) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unrecognized GET request path `{}`.", unknown_path),
+        )),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Only the flows of a network interface can be retrieved.".to_string(),
+        )),
+    }
+}
+
 pub(crate) fn parse_put_net(
     body: &Body,
     id_from_path: Option<&str>,
+    files: &[File],
 ) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.network_count.inc();
     let id = if let Some(id) = id_from_path {
@@ -20,9 +45,10 @@ pub(crate) fn parse_put_net(
         return Err(RequestError::EmptyID);
     };
 
-    let netif = serde_json::from_slice::<NetworkInterfaceConfig>(body.raw()).inspect_err(|_| {
-        METRICS.put_api_requests.network_fails.inc();
-    })?;
+    let mut netif =
+        serde_json::from_slice::<NetworkInterfaceConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.network_fails.inc();
+        })?;
     if id != netif.iface_id.as_str() {
         METRICS.put_api_requests.network_fails.inc();
         return Err(RequestError::Generic(
@@ -34,6 +60,28 @@ pub(crate) fn parse_put_net(
             ),
         ));
     }
+
+    // A tap file descriptor passed along with the request is used instead of `host_dev_name`.
+    match files {
+        [] => (),
+        [tap_file] => {
+            let tap_file = tap_file.try_clone().map_err(|err| {
+                METRICS.put_api_requests.network_fails.inc();
+                RequestError::Generic(
+                    StatusCode::BadRequest,
+                    format!("Cannot use the passed tap file descriptor: {}", err),
+                )
+            })?;
+            netif.tap_fd = Some(PassedFd(tap_file));
+        }
+        _ => {
+            METRICS.put_api_requests.network_fails.inc();
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                "Only one tap file descriptor can be passed per network interface.".to_string(),
+            ));
+        }
+    }
     Ok(ParsedRequest::new_sync(VmmAction::InsertNetworkDevice(
         netif,
     )))
@@ -76,6 +124,18 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_net_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_net(["foo", "flows"].into_iter()).unwrap()),
+            VmmAction::GetNetworkFlows(String::from("foo"))
+        );
+        parse_get_net(std::iter::empty()).unwrap_err();
+        parse_get_net(["foo"].into_iter()).unwrap_err();
+        parse_get_net(["foo", "bar"].into_iter()).unwrap_err();
+        parse_get_net(["foo$", "flows"].into_iter()).unwrap_err();
+    }
+
     #[test]
     fn test_parse_put_net_request() {
         let body = r#"{
@@ -84,14 +144,14 @@ mod tests {
             "guest_mac": "12:34:56:78:9A:BC"
         }"#;
         // 1. Exercise infamous "The id from the path does not match id from the body!".
-        parse_put_net(&Body::new(body), Some("bar")).unwrap_err();
+        parse_put_net(&Body::new(body), Some("bar"), &[]).unwrap_err();
         // 2. The `id_from_path` cannot be None.
-        parse_put_net(&Body::new(body), None).unwrap_err();
+        parse_put_net(&Body::new(body), None, &[]).unwrap_err();
 
         // 3. Success case.
         let expected_config = serde_json::from_str::<NetworkInterfaceConfig>(body).unwrap();
         assert_eq!(
-            vmm_action_from_request(parse_put_net(&Body::new(body), Some("foo")).unwrap()),
+            vmm_action_from_request(parse_put_net(&Body::new(body), Some("foo"), &[]).unwrap()),
             VmmAction::InsertNetworkDevice(expected_config)
         );
 
@@ -111,7 +171,31 @@ mod tests {
                 }
             }
         }"#;
-        parse_put_net(&Body::new(body), Some("foo")).unwrap_err();
+        parse_put_net(&Body::new(body), Some("foo"), &[]).unwrap_err();
+
+        // 5. A passed tap file descriptor replaces the host device name.
+        let body = r#"{
+            "iface_id": "foo",
+            "guest_mac": "12:34:56:78:9A:BC"
+        }"#;
+        let tap_file = vmm_sys_util::tempfile::TempFile::new().unwrap().into_file();
+        let action = vmm_action_from_request(
+            parse_put_net(&Body::new(body), Some("foo"), &[tap_file]).unwrap(),
+        );
+        match action {
+            VmmAction::InsertNetworkDevice(config) => {
+                assert!(config.host_dev_name.is_empty());
+                assert!(config.tap_fd.is_some());
+            }
+            _ => panic!("Test failed: Invalid parameters"),
+        }
+
+        // 6. Only one tap file descriptor can be passed.
+        let files = [
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+        ];
+        parse_put_net(&Body::new(body), Some("foo"), &files).unwrap_err();
     }
 
     #[test]
@@ -150,5 +234,20 @@ mod tests {
             }
         }"#;
         parse_patch_net(&Body::new(body), Some("foo")).unwrap_err();
+
+        // 5. Link state update.
+        let body = r#"{
+            "iface_id": "foo",
+            "link_up": false
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_patch_net(&Body::new(body), Some("foo")).unwrap()),
+            VmmAction::UpdateNetworkInterface(NetworkInterfaceUpdateConfig {
+                iface_id: "foo".to_string(),
+                rx_rate_limiter: None,
+                tx_rate_limiter: None,
+                link_up: Some(false),
+            })
+        );
     }
 }
@@ -0,0 +1,80 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::p9::P9Config;
+
+use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_put_p9(
+    body: &Body,
+    id_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.p9_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.put_api_requests.p9_fails.inc();
+        return Err(RequestError::EmptyID);
+    };
+
+    let device_cfg = serde_json::from_slice::<P9Config>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.p9_fails.inc();
+    })?;
+
+    if id != device_cfg.id {
+        METRICS.put_api_requests.p9_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "The id from the path does not match the id from the body!".to_string(),
+        ))
+    } else {
+        Ok(ParsedRequest::new_sync(VmmAction::InsertP9Device(
+            device_cfg,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_p9_request() {
+        parse_put_p9(&Body::new("invalid_payload"), None).unwrap_err();
+        parse_put_p9(&Body::new("invalid_payload"), Some("id")).unwrap_err();
+
+        let body = r#"{
+            "id": "bar",
+            "path_on_host": "/srv/share",
+            "mount_tag": "share"
+        }"#;
+        parse_put_p9(&Body::new(body), Some("1")).unwrap_err();
+        let body = r#"{
+            "id": "1",
+            "path_on_host": "/srv/share",
+            "mount_tag": "share",
+            "foo": "1"
+        }"#;
+        parse_put_p9(&Body::new(body), Some("1")).unwrap_err();
+
+        let body = r#"{
+            "id": "1000",
+            "path_on_host": "/srv/share",
+            "mount_tag": "share",
+            "read_only": true
+        }"#;
+        let r = vmm_action_from_request(parse_put_p9(&Body::new(body), Some("1000")).unwrap());
+
+        let expected_config = P9Config {
+            id: "1000".to_string(),
+            path_on_host: "/srv/share".to_string(),
+            mount_tag: "share".to_string(),
+            read_only: true,
+        };
+        assert_eq!(r, VmmAction::InsertP9Device(expected_config));
+    }
+}
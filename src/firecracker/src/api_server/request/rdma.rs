@@ -3,11 +3,28 @@
 
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
-use vmm::vmm_config::rdma::RdmaDeviceConfig;
+use vmm::vmm_config::rdma::{RdmaDeviceConfig, RdmaDeviceUpdateConfig};
 
 use super::super::parsed_request::{ParsedRequest, RequestError, checked_id};
 use super::{Body, StatusCode};
 
+pub(crate) fn parse_get_rdma<'a, T>(mut path_tokens: T) -> Result<ParsedRequest, RequestError>
+where
+    T: Iterator<Item = &'a str>,
+{
+    METRICS.get_api_requests.rdma_status_count.inc();
+    let id = checked_id(path_tokens.next().ok_or(RequestError::EmptyID)?)?;
+    match path_tokens.next() {
+        None => Ok(ParsedRequest::new_sync(VmmAction::GetRdmaDevice(
+            id.to_string(),
+        ))),
+        Some(unknown_path) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unrecognized GET request path `{}`.", unknown_path),
+        )),
+    }
+}
+
 pub(crate) fn parse_put_rdma(
     body: &Body,
     id_from_path: Option<&str>,
@@ -37,11 +54,58 @@ pub(crate) fn parse_put_rdma(
     }
 }
 
+pub(crate) fn parse_patch_rdma(
+    body: &Body,
+    id_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    METRICS.patch_api_requests.rdma_count.inc();
+    let id = if let Some(id) = id_from_path {
+        checked_id(id)?
+    } else {
+        METRICS.patch_api_requests.rdma_fails.inc();
+        return Err(RequestError::EmptyID);
+    };
+
+    let update_cfg =
+        serde_json::from_slice::<RdmaDeviceUpdateConfig>(body.raw()).inspect_err(|_| {
+            METRICS.patch_api_requests.rdma_fails.inc();
+        })?;
+
+    if id != update_cfg.id {
+        METRICS.patch_api_requests.rdma_fails.inc();
+        Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "The id from the path does not match the id from the body!".to_string(),
+        ))
+    } else {
+        Ok(ParsedRequest::new_sync(VmmAction::UpdateRdmaDevice(
+            update_cfg,
+        )))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use vmm::devices::virtio::rdma::backend::RdmaBackendConfig;
+    use vmm::devices::virtio::rdma::backend::fault::RdmaFaultPolicy;
+    use vmm::devices::virtio::rdma::backend::udp::UdpBackendConfig;
+    use vmm::devices::virtio::rdma::device::RdmaCqModeration;
+    use vmm::vmm_config::{RateLimiterConfig, TokenBucketConfig};
+
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_rdma_request() {
+        parse_get_rdma(std::iter::empty()).unwrap_err();
+        parse_get_rdma(["rdma0", "flows"].into_iter()).unwrap_err();
+        parse_get_rdma(["rdma$"].into_iter()).unwrap_err();
+        assert_eq!(
+            vmm_action_from_request(parse_get_rdma(["rdma0"].into_iter()).unwrap()),
+            VmmAction::GetRdmaDevice("rdma0".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_put_rdma_request() {
         parse_put_rdma(&Body::new("invalid_payload"), None).unwrap_err();
@@ -63,7 +127,167 @@ mod tests {
 
         let expected_config = RdmaDeviceConfig {
             id: "rdma0".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(r, VmmAction::InsertRdmaDevice(expected_config));
+
+        let body = r#"{
+            "id": "rdma0",
+            "mac": "06:00:ac:10:00:02",
+            "ip": "172.16.0.2",
+            "pkeys": [32769, 2],
+            "num_queues": 4,
+            "mtu": 4096,
+            "cq_moderation": {
+                "max_cqes": 16,
+                "max_usecs": 50
+            },
+            "backend": {
+                "type": "udp",
+                "bind_ip": "172.16.0.1",
+                "port": 4792
+            },
+            "fault_policy": {
+                "drop_every": 10,
+                "delay_usecs": 100
+            },
+            "max_qp": 64,
+            "max_cq": 128,
+            "max_mr": 256,
+            "max_memory_registration_bytes": 1073741824,
+            "max_outstanding_wr_per_qp": 512,
+            "max_outstanding_bytes_per_pd": 16777216,
+            "rate_limiter": {
+                "ops": {
+                    "size": 1000,
+                    "refill_time": 100
+                }
+            }
+        }"#;
+        let r = vmm_action_from_request(parse_put_rdma(&Body::new(body), Some("rdma0")).unwrap());
+
+        let expected_config = RdmaDeviceConfig {
+            id: "rdma0".to_string(),
+            mac: Some("06:00:ac:10:00:02".parse().unwrap()),
+            ip: Some("172.16.0.2".parse().unwrap()),
+            pkeys: vec![0x8001, 0x0002],
+            num_queues: Some(4),
+            mtu: Some(4096),
+            cq_moderation: Some(RdmaCqModeration {
+                max_cqes: 16,
+                max_usecs: 50,
+            }),
+            backend: Some(RdmaBackendConfig::Udp(UdpBackendConfig {
+                bind_ip: "172.16.0.1".parse().unwrap(),
+                port: 4792,
+            })),
+            fault_policy: Some(RdmaFaultPolicy {
+                drop_every: 10,
+                delay_usecs: 100,
+                ..Default::default()
+            }),
+            max_qp: Some(64),
+            max_cq: Some(128),
+            max_mr: Some(256),
+            max_memory_registration_bytes: Some(1 << 30),
+            max_outstanding_wr_per_qp: Some(512),
+            max_outstanding_bytes_per_pd: Some(16 << 20),
+            rate_limiter: Some(RateLimiterConfig {
+                bandwidth: None,
+                ops: Some(TokenBucketConfig {
+                    size: 1000,
+                    one_time_burst: None,
+                    refill_time: 100,
+                }),
+            }),
+            ..Default::default()
         };
         assert_eq!(r, VmmAction::InsertRdmaDevice(expected_config));
+
+        // The backend must be one of the known ones.
+        let body = r#"{
+            "id": "rdma0",
+            "backend": {
+                "type": "ibverbs"
+            }
+        }"#;
+        parse_put_rdma(&Body::new(body), Some("rdma0")).unwrap_err();
+
+        // The IP address must be a literal address.
+        let body = r#"{
+            "id": "rdma0",
+            "ip": "localhost"
+        }"#;
+        parse_put_rdma(&Body::new(body), Some("rdma0")).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_patch_rdma_request() {
+        parse_patch_rdma(&Body::new("invalid_payload"), None).unwrap_err();
+        parse_patch_rdma(&Body::new("invalid_payload"), Some("id")).unwrap_err();
+
+        let body = r#"{
+            "id": "bar"
+        }"#;
+        parse_patch_rdma(&Body::new(body), Some("rdma0")).unwrap_err();
+        // Only the moderation, the backend and the rate limiter can be updated.
+        let body = r#"{
+            "id": "rdma0",
+            "mtu": 4096
+        }"#;
+        parse_patch_rdma(&Body::new(body), Some("rdma0")).unwrap_err();
+
+        let body = r#"{
+            "id": "rdma0",
+            "cq_moderation": {
+                "max_cqes": 16,
+                "max_usecs": 50
+            },
+            "backend": {
+                "type": "udp",
+                "bind_ip": "172.16.0.2"
+            },
+            "rate_limiter": {
+                "bandwidth": {
+                    "size": 1048576,
+                    "refill_time": 1000
+                }
+            }
+        }"#;
+        let r = vmm_action_from_request(parse_patch_rdma(&Body::new(body), Some("rdma0")).unwrap());
+        let expected_config = RdmaDeviceUpdateConfig {
+            id: "rdma0".to_string(),
+            cq_moderation: Some(RdmaCqModeration {
+                max_cqes: 16,
+                max_usecs: 50,
+            }),
+            backend: Some(RdmaBackendConfig::Udp(UdpBackendConfig {
+                bind_ip: "172.16.0.2".parse().unwrap(),
+                port: 4791,
+            })),
+            rate_limiter: Some(RateLimiterConfig {
+                bandwidth: Some(TokenBucketConfig {
+                    size: 1 << 20,
+                    one_time_burst: None,
+                    refill_time: 1000,
+                }),
+                ops: None,
+            }),
+        };
+        assert_eq!(r, VmmAction::UpdateRdmaDevice(expected_config));
+
+        // A missing moderation disables it, while a missing backend or rate limiter keeps the
+        // current one.
+        let body = r#"{
+            "id": "rdma0"
+        }"#;
+        let r = vmm_action_from_request(parse_patch_rdma(&Body::new(body), Some("rdma0")).unwrap());
+        let expected_config = RdmaDeviceUpdateConfig {
+            id: "rdma0".to_string(),
+            cq_moderation: None,
+            backend: None,
+            rate_limiter: None,
+        };
+        assert_eq!(r, VmmAction::UpdateRdmaDevice(expected_config));
     }
 }
@@ -0,0 +1,26 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+
+pub(crate) fn parse_get_resource_usage() -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.resource_usage_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::GetResourceUsage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::parsed_request::RequestAction;
+    use super::*;
+
+    #[test]
+    fn test_parse_get_resource_usage_request() {
+        match parse_get_resource_usage().unwrap().into_parts() {
+            (RequestAction::Sync(action), _) if *action == VmmAction::GetResourceUsage => {}
+            _ => panic!("Test failed."),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::logger::{IncMetric, METRICS};
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::smbios::SmbiosConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_smbios(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.smbios_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::ConfigureSmbios(
+        serde_json::from_slice::<SmbiosConfig>(body.raw()).inspect_err(|_| {
+            METRICS.put_api_requests.smbios_fails.inc();
+        })?,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smbios_request() {
+        parse_put_smbios(&Body::new("invalid_payload")).unwrap_err();
+        parse_put_smbios(&Body::new(r#"{"product": "foo", "bar": 1}"#)).unwrap_err();
+
+        let body = r#"{
+            "manufacturer": "Example Corp.",
+            "product": "Example VM",
+            "serial_number": "SN-0001",
+            "uuid": "5b1e4c9a-2a5f-4f4e-9d63-4f3c2b1a0e9d"
+        }"#;
+        let same_body = SmbiosConfig {
+            manufacturer: Some(String::from("Example Corp.")),
+            product: Some(String::from("Example VM")),
+            serial_number: Some(String::from("SN-0001")),
+            uuid: Some(String::from("5b1e4c9a-2a5f-4f4e-9d63-4f3c2b1a0e9d")),
+        };
+        let parsed_req = parse_put_smbios(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::ConfigureSmbios(same_body))
+        );
+    }
+}
@@ -1,12 +1,15 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::fs::File;
+
 use serde::de::Error as DeserializeError;
 use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::PassedFd;
 use vmm::vmm_config::snapshot::{
     CreateSnapshotParams, LoadSnapshotConfig, LoadSnapshotParams, MemBackendConfig, MemBackendType,
-    Vm, VmState,
+    MemVerification, SnapshotAgentConfig, ValidateSnapshotParams, Vm, VmState,
 };
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
@@ -22,15 +25,24 @@ pub const MISSING_FIELD: &str =
 /// Only specifying one of them is allowed.
 pub const TOO_MANY_FIELDS: &str =
     "too many fields: either `mem_backend` or `mem_file_path` exclusively is required";
+/// None of the snapshot files has been passed, and their paths are missing.
+pub const MISSING_SNAPSHOT_PATH: &str =
+    "missing field: `snapshot_path` and `mem_file_path` are required unless the files are passed";
+/// The memory served through UFFD cannot be verified.
+pub const UFFD_MEM_VERIFICATION: &str =
+    "invalid field: `mem_verification` is only supported with the `File` memory backend";
 
 pub(crate) fn parse_put_snapshot(
     body: &Body,
     request_type_from_path: Option<&str>,
+    files: &[File],
 ) -> Result<ParsedRequest, RequestError> {
     match request_type_from_path {
         Some(request_type) => match request_type {
-            "create" => parse_put_snapshot_create(body),
+            "create" => parse_put_snapshot_create(body, files),
             "load" => parse_put_snapshot_load(body),
+            "agent" => parse_put_snapshot_agent(body),
+            "validate" => parse_put_snapshot_validate(body),
             _ => Err(RequestError::InvalidPathMethod(
                 format!("/snapshot/{}", request_type),
                 Method::Put,
@@ -52,13 +64,59 @@ pub(crate) fn parse_patch_vm_state(body: &Body) -> Result<ParsedRequest, Request
     }
 }
 
-fn parse_put_snapshot_create(body: &Body) -> Result<ParsedRequest, RequestError> {
-    let snapshot_config = serde_json::from_slice::<CreateSnapshotParams>(body.raw())?;
+fn parse_put_snapshot_create(body: &Body, files: &[File]) -> Result<ParsedRequest, RequestError> {
+    let mut snapshot_config = serde_json::from_slice::<CreateSnapshotParams>(body.raw())?;
+
+    // The snapshot and memory files passed along with the request are used instead of their paths.
+    match files {
+        [] => {
+            if snapshot_config.snapshot_path.as_os_str().is_empty()
+                || snapshot_config.mem_file_path.as_os_str().is_empty()
+            {
+                return Err(RequestError::SerdeJson(serde_json::Error::custom(
+                    MISSING_SNAPSHOT_PATH,
+                )));
+            }
+        }
+        [snapshot_file, mem_file] => {
+            let passed_fd = |file: &File| {
+                file.try_clone().map(PassedFd).map_err(|err| {
+                    RequestError::Generic(
+                        StatusCode::BadRequest,
+                        format!("Cannot use the passed snapshot file descriptor: {}", err),
+                    )
+                })
+            };
+            snapshot_config.snapshot_fd = Some(passed_fd(snapshot_file)?);
+            snapshot_config.mem_file_fd = Some(passed_fd(mem_file)?);
+        }
+        _ => {
+            return Err(RequestError::Generic(
+                StatusCode::BadRequest,
+                "Either no file descriptor or the snapshot and memory file descriptors, in this \
+                 order, must be passed."
+                    .to_string(),
+            ));
+        }
+    }
     Ok(ParsedRequest::new_sync(VmmAction::CreateSnapshot(
         snapshot_config,
     )))
 }
 
+fn parse_put_snapshot_agent(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.put_api_requests.snapshot_agent_count.inc();
+    let config = serde_json::from_slice::<SnapshotAgentConfig>(body.raw()).inspect_err(|_| {
+        METRICS.put_api_requests.snapshot_agent_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetSnapshotAgent(config)))
+}
+
+fn parse_put_snapshot_validate(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let params = serde_json::from_slice::<ValidateSnapshotParams>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::ValidateSnapshot(params)))
+}
+
 fn parse_put_snapshot_load(body: &Body) -> Result<ParsedRequest, RequestError> {
     let snapshot_config = serde_json::from_slice::<LoadSnapshotConfig>(body.raw())?;
 
@@ -78,6 +136,20 @@ fn parse_put_snapshot_load(body: &Body) -> Result<ParsedRequest, RequestError> {
         _ => {}
     }
 
+    if snapshot_config.mem_verification != MemVerification::Length
+        && matches!(
+            snapshot_config.mem_backend,
+            Some(MemBackendConfig {
+                backend_type: MemBackendType::Uffd,
+                ..
+            })
+        )
+    {
+        return Err(RequestError::SerdeJson(serde_json::Error::custom(
+            UFFD_MEM_VERIFICATION,
+        )));
+    }
+
     // Check for the presence of deprecated `mem_file_path` field and create
     // deprecation message if found.
     let mut deprecation_message = None;
@@ -110,6 +182,8 @@ fn parse_put_snapshot_load(body: &Body) -> Result<ParsedRequest, RequestError> {
             || snapshot_config.track_dirty_pages,
         resume_vm: snapshot_config.resume_vm,
         network_overrides: snapshot_config.network_overrides,
+        mem_verification: snapshot_config.mem_verification,
+        rdma_allow_degraded_restore: snapshot_config.rdma_allow_degraded_restore,
     };
 
     // Construct the `ParsedRequest` object.
@@ -134,7 +208,7 @@ mod tests {
     fn test_parse_put_snapshot() {
         use std::path::PathBuf;
 
-        use vmm::vmm_config::snapshot::SnapshotType;
+        use vmm::vmm_config::snapshot::{SnapshotConsistency, SnapshotType};
 
         let body = r#"{
             "snapshot_type": "Diff",
@@ -145,9 +219,15 @@ mod tests {
             snapshot_type: SnapshotType::Diff,
             snapshot_path: PathBuf::from("foo"),
             mem_file_path: PathBuf::from("bar"),
+            memory_digests: false,
+            consistency: SnapshotConsistency::Pause,
+            snapshot_fd: None,
+            mem_file_fd: None,
         };
         assert_eq!(
-            vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some("create")).unwrap()),
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("create"), &[]).unwrap()
+            ),
             VmmAction::CreateSnapshot(expected_config)
         );
 
@@ -159,9 +239,57 @@ mod tests {
             snapshot_type: SnapshotType::Full,
             snapshot_path: PathBuf::from("foo"),
             mem_file_path: PathBuf::from("bar"),
+            memory_digests: false,
+            consistency: SnapshotConsistency::Pause,
+            snapshot_fd: None,
+            mem_file_fd: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("create"), &[]).unwrap()
+            ),
+            VmmAction::CreateSnapshot(expected_config)
+        );
+
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_file_path": "bar",
+            "memory_digests": true
+        }"#;
+        let expected_config = CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+            memory_digests: true,
+            consistency: SnapshotConsistency::Pause,
+            snapshot_fd: None,
+            mem_file_fd: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("create"), &[]).unwrap()
+            ),
+            VmmAction::CreateSnapshot(expected_config)
+        );
+
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_file_path": "bar",
+            "consistency": "CrashConsistent"
+        }"#;
+        let expected_config = CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+            memory_digests: false,
+            consistency: SnapshotConsistency::CrashConsistent,
+            snapshot_fd: None,
+            mem_file_fd: None,
         };
         assert_eq!(
-            vmm_action_from_request(parse_put_snapshot(&Body::new(body), Some("create")).unwrap()),
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("create"), &[]).unwrap()
+            ),
             VmmAction::CreateSnapshot(expected_config)
         );
 
@@ -169,7 +297,29 @@ mod tests {
             "invalid_field": "foo",
             "mem_file_path": "bar"
         }"#;
-        parse_put_snapshot(&Body::new(invalid_body), Some("create")).unwrap_err();
+        parse_put_snapshot(&Body::new(invalid_body), Some("create"), &[]).unwrap_err();
+
+        // The paths are required unless the files are passed.
+        let body = r#"{
+            "snapshot_type": "Diff"
+        }"#;
+        parse_put_snapshot(&Body::new(body), Some("create"), &[]).unwrap_err();
+        let files = [
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+            vmm_sys_util::tempfile::TempFile::new().unwrap().into_file(),
+        ];
+        match vmm_action_from_request(
+            parse_put_snapshot(&Body::new(body), Some("create"), &files).unwrap(),
+        ) {
+            VmmAction::CreateSnapshot(config) => {
+                assert!(config.snapshot_path.as_os_str().is_empty());
+                assert!(config.snapshot_fd.is_some());
+                assert!(config.mem_file_fd.is_some());
+            }
+            _ => panic!("Test failed: Invalid parameters"),
+        }
+        // Both files have to be passed.
+        parse_put_snapshot(&Body::new(body), Some("create"), &files[..1]).unwrap_err();
 
         let body = r#"{
             "snapshot_path": "foo",
@@ -187,8 +337,10 @@ mod tests {
             track_dirty_pages: false,
             resume_vm: false,
             network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         };
-        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load")).unwrap();
+        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap();
         assert!(
             parsed_request
                 .parsing_info()
@@ -217,8 +369,10 @@ mod tests {
             track_dirty_pages: true,
             resume_vm: false,
             network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         };
-        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load")).unwrap();
+        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap();
         assert!(
             parsed_request
                 .parsing_info()
@@ -247,8 +401,10 @@ mod tests {
             track_dirty_pages: false,
             resume_vm: true,
             network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         };
-        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load")).unwrap();
+        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap();
         assert!(
             parsed_request
                 .parsing_info()
@@ -286,8 +442,10 @@ mod tests {
                 iface_id: String::from("eth0"),
                 host_dev_name: String::from("vmtap2"),
             }],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         };
-        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load")).unwrap();
+        let mut parsed_request = parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap();
         assert!(
             parsed_request
                 .parsing_info()
@@ -313,8 +471,10 @@ mod tests {
             track_dirty_pages: false,
             resume_vm: true,
             network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         };
-        let parsed_request = parse_put_snapshot(&Body::new(body), Some("load")).unwrap();
+        let parsed_request = parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap();
         assert_eq!(
             depr_action_from_req(parsed_request, Some(LOAD_DEPRECATION_MESSAGE.to_string())),
             VmmAction::LoadSnapshot(expected_config)
@@ -327,7 +487,7 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            parse_put_snapshot(&Body::new(body), Some("load"))
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
                 .err()
                 .unwrap()
                 .to_string(),
@@ -342,7 +502,7 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            parse_put_snapshot(&Body::new(body), Some("load"))
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
                 .err()
                 .unwrap()
                 .to_string(),
@@ -359,7 +519,7 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            parse_put_snapshot(&Body::new(body), Some("load"))
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
                 .err()
                 .unwrap()
                 .to_string(),
@@ -371,7 +531,7 @@ mod tests {
             "snapshot_path": "foo"
         }"#;
         assert_eq!(
-            parse_put_snapshot(&Body::new(body), Some("load"))
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
                 .err()
                 .unwrap()
                 .to_string(),
@@ -379,6 +539,77 @@ mod tests {
                 .to_string()
         );
 
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_backend": {
+                "backend_path": "bar",
+                "backend_type": "File"
+            },
+            "mem_verification": "Sampled"
+        }"#;
+        let expected_config = LoadSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_backend: MemBackendConfig {
+                backend_path: PathBuf::from("bar"),
+                backend_type: MemBackendType::File,
+            },
+            track_dirty_pages: false,
+            resume_vm: false,
+            network_overrides: vec![],
+            mem_verification: MemVerification::Sampled,
+            rdma_allow_degraded_restore: false,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap()
+            ),
+            VmmAction::LoadSnapshot(expected_config)
+        );
+
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_backend": {
+                "backend_path": "bar",
+                "backend_type": "Uffd"
+            },
+            "rdma_allow_degraded_restore": true
+        }"#;
+        let expected_config = LoadSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_backend: MemBackendConfig {
+                backend_path: PathBuf::from("bar"),
+                backend_type: MemBackendType::Uffd,
+            },
+            track_dirty_pages: false,
+            resume_vm: false,
+            network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: true,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("load"), &[]).unwrap()
+            ),
+            VmmAction::LoadSnapshot(expected_config)
+        );
+
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_backend": {
+                "backend_path": "bar",
+                "backend_type": "Uffd"
+            },
+            "mem_verification": "Full"
+        }"#;
+        assert_eq!(
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
+                .err()
+                .unwrap()
+                .to_string(),
+            RequestError::SerdeJson(serde_json::Error::custom(UFFD_MEM_VERIFICATION.to_string()))
+                .to_string()
+        );
+
         let body = r#"{
             "mem_backend": {
                 "backend_path": "bar",
@@ -386,15 +617,96 @@ mod tests {
             }
         }"#;
         assert_eq!(
-            parse_put_snapshot(&Body::new(body), Some("load"))
+            parse_put_snapshot(&Body::new(body), Some("load"), &[])
                 .err()
                 .unwrap()
                 .to_string(),
             "An error occurred when deserializing the json body of a request: missing field \
              `snapshot_path` at line 6 column 9."
         );
-        parse_put_snapshot(&Body::new(body), Some("invalid")).unwrap_err();
-        parse_put_snapshot(&Body::new(body), None).unwrap_err();
+        parse_put_snapshot(&Body::new(body), Some("invalid"), &[]).unwrap_err();
+        parse_put_snapshot(&Body::new(body), None, &[]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_put_snapshot_agent() {
+        use std::path::PathBuf;
+
+        use vmm::vmm_config::snapshot::SnapshotType;
+
+        let body = r#"{
+            "port": 5000,
+            "snapshot_type": "Diff",
+            "snapshot_path": "foo",
+            "mem_file_path": "bar",
+            "freeze_timeout_ms": 2000
+        }"#;
+        let expected_config = SnapshotAgentConfig {
+            port: 5000,
+            snapshot_type: SnapshotType::Diff,
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: PathBuf::from("bar"),
+            freeze_timeout_ms: 2000,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("agent"), &[]).unwrap()
+            ),
+            VmmAction::SetSnapshotAgent(expected_config)
+        );
+
+        let body = r#"{
+            "port": 5000,
+            "snapshot_path": "foo"
+        }"#;
+        parse_put_snapshot(&Body::new(body), Some("agent"), &[]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_put_snapshot_validate() {
+        use std::path::PathBuf;
+
+        let body = r#"{
+            "snapshot_path": "foo"
+        }"#;
+        let expected_params = ValidateSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: None,
+            mem_verification: MemVerification::Length,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("validate"), &[]).unwrap()
+            ),
+            VmmAction::ValidateSnapshot(expected_params)
+        );
+
+        let body = r#"{
+            "snapshot_path": "foo",
+            "mem_file_path": "bar",
+            "mem_verification": "Full"
+        }"#;
+        let expected_params = ValidateSnapshotParams {
+            snapshot_path: PathBuf::from("foo"),
+            mem_file_path: Some(PathBuf::from("bar")),
+            mem_verification: MemVerification::Full,
+        };
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_snapshot(&Body::new(body), Some("validate"), &[]).unwrap()
+            ),
+            VmmAction::ValidateSnapshot(expected_params)
+        );
+
+        let body = r#"{
+            "mem_file_path": "bar"
+        }"#;
+        parse_put_snapshot(&Body::new(body), Some("validate"), &[]).unwrap_err();
+        let body = r#"{
+            "snapshot_path": "foo",
+            "resume_vm": true
+        }"#;
+        parse_put_snapshot(&Body::new(body), Some("validate"), &[]).unwrap_err();
     }
 
     #[test]
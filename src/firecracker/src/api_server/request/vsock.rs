@@ -8,6 +8,11 @@ use vmm::vmm_config::vsock::VsockDeviceConfig;
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
 
+pub(crate) fn parse_get_vsock_connections() -> Result<ParsedRequest, RequestError> {
+    METRICS.get_api_requests.vsock_connections_count.inc();
+    Ok(ParsedRequest::new_sync(VmmAction::GetVsockConnections))
+}
+
 pub(crate) fn parse_put_vsock(body: &Body) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.vsock_count.inc();
     let vsock_cfg = serde_json::from_slice::<VsockDeviceConfig>(body.raw()).inspect_err(|_| {
@@ -12,7 +12,7 @@ use vmm::logger::{ProcessTimeReporter, error, info, warn};
 use vmm::resources::VmResources;
 use vmm::rpc_interface::{
     ApiRequest, ApiResponse, BuildMicrovmFromRequestsError, PrebootApiController,
-    RuntimeApiController, VmmAction,
+    RuntimeApiController, VmmAction, VmmActionError, VmmData,
 };
 use vmm::seccomp::BpfThreadMap;
 use vmm::vmm_config::instance_info::InstanceInfo;
@@ -42,6 +42,9 @@ struct ApiServerAdapter {
     from_api: Receiver<ApiRequest>,
     to_api: Sender<ApiResponse>,
     controller: RuntimeApiController,
+    // Whether the response to an accepted graceful shutdown waits for the microVM to stop.
+    #[cfg(target_arch = "x86_64")]
+    pending_shutdown: bool,
 }
 
 impl ApiServerAdapter {
@@ -60,24 +63,62 @@ impl ApiServerAdapter {
             from_api,
             to_api,
             controller: RuntimeApiController::new(vm_resources, vmm.clone()),
+            #[cfg(target_arch = "x86_64")]
+            pending_shutdown: false,
         }));
-        event_manager.add_subscriber(api_adapter);
+        event_manager.add_subscriber(api_adapter.clone());
         loop {
             event_manager
                 .run()
                 .expect("EventManager events driver fatal error");
 
-            match vmm.lock().unwrap().shutdown_exit_code() {
-                Some(FcExitCode::Ok) => break,
-                Some(exit_code) => return Err(ApiServerError::MicroVMStoppedWithError(exit_code)),
-                None => continue,
+            // The devices added by the API requests handled above get their events from now on.
+            #[cfg(target_arch = "x86_64")]
+            for subscriber in vmm.lock().unwrap().take_pending_subscribers() {
+                event_manager.add_subscriber(subscriber);
+            }
+
+            let Some(exit_code) = vmm.lock().unwrap().shutdown_exit_code() else {
+                continue;
+            };
+            #[cfg(target_arch = "x86_64")]
+            api_adapter.lock().unwrap().finish_shutdown(&vmm);
+            match exit_code {
+                FcExitCode::Ok => break,
+                exit_code => return Err(ApiServerError::MicroVMStoppedWithError(exit_code)),
             }
         }
         Ok(())
     }
 
     fn handle_request(&mut self, req_action: VmmAction) {
+        #[cfg(target_arch = "x86_64")]
+        let request_is_shutdown = matches!(req_action, VmmAction::GracefulShutdown(_));
         let response = self.controller.handle_request(req_action);
+        // The outcome of an accepted graceful shutdown is sent once the microVM stopped.
+        #[cfg(target_arch = "x86_64")]
+        if request_is_shutdown && response.is_ok() {
+            self.pending_shutdown = true;
+            return;
+        }
+        self.send_response(response);
+    }
+
+    // Answers the pending graceful shutdown request, if any, with its outcome.
+    #[cfg(target_arch = "x86_64")]
+    fn finish_shutdown(&mut self, vmm: &Mutex<Vmm>) {
+        if !std::mem::take(&mut self.pending_shutdown) {
+            return;
+        }
+        // The microVM may also have stopped for another reason before the shutdown ended.
+        let response = match vmm.lock().unwrap().graceful_shutdown_outcome() {
+            Some(outcome) => VmmData::GracefulShutdownOutcome(outcome),
+            None => VmmData::Empty,
+        };
+        self.send_response(Ok(response));
+    }
+
+    fn send_response(&mut self, response: Result<VmmData, VmmActionError>) {
         // Send back the result.
         self.to_api
             .send(Box::new(response))
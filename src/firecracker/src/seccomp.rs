@@ -8,6 +8,9 @@ use std::path::Path;
 use vmm::seccomp::{BpfThreadMap, DeserializationError, deserialize_binary, get_empty_filters};
 
 const THREAD_CATEGORIES: [&str; 3] = ["vmm", "api", "vcpu"];
+// Categories of the threads of the devices, which the filters only need to have if the microVM
+// has such devices.
+const OPTIONAL_THREAD_CATEGORIES: [&str; 2] = ["rdma", "rdma_launcher"];
 
 /// Error retrieving seccomp filters.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -78,9 +81,11 @@ fn get_custom_filters<R: Read + Debug>(reader: R) -> Result<BpfThreadMap, Filter
 
 /// Return an error if the BpfThreadMap contains invalid thread categories.
 fn filter_thread_categories(map: BpfThreadMap) -> Result<BpfThreadMap, FilterError> {
-    let (filters, invalid_filters): (BpfThreadMap, BpfThreadMap) = map
-        .into_iter()
-        .partition(|(k, _)| THREAD_CATEGORIES.contains(&k.as_str()));
+    let (filters, invalid_filters): (BpfThreadMap, BpfThreadMap) =
+        map.into_iter().partition(|(k, _)| {
+            THREAD_CATEGORIES.contains(&k.as_str())
+                || OPTIONAL_THREAD_CATEGORIES.contains(&k.as_str())
+        });
     if !invalid_filters.is_empty() {
         // build the error message
         let mut thread_categories_string =
@@ -117,16 +122,20 @@ mod tests {
     #[test]
     fn test_get_filters() {
         let mut filters = get_empty_filters();
-        assert_eq!(filters.len(), 3);
+        assert_eq!(filters.len(), 5);
         assert!(filters.remove("vmm").is_some());
         assert!(filters.remove("api").is_some());
         assert!(filters.remove("vcpu").is_some());
+        assert!(filters.remove("rdma").is_some());
+        assert!(filters.remove("rdma_launcher").is_some());
 
         let mut filters = get_empty_filters();
-        assert_eq!(filters.len(), 3);
+        assert_eq!(filters.len(), 5);
         assert_eq!(filters.remove("vmm").unwrap().len(), 0);
         assert_eq!(filters.remove("api").unwrap().len(), 0);
         assert_eq!(filters.remove("vcpu").unwrap().len(), 0);
+        assert_eq!(filters.remove("rdma").unwrap().len(), 0);
+        assert_eq!(filters.remove("rdma_launcher").unwrap().len(), 0);
 
         let file = TempFile::new().unwrap().into_file();
 
@@ -143,6 +152,16 @@ mod tests {
 
         assert_eq!(filter_thread_categories(map).unwrap().len(), 3);
 
+        // optional category
+        let mut map = BpfThreadMap::new();
+        map.insert("vcpu".to_string(), Arc::new(vec![]));
+        map.insert("vmm".to_string(), Arc::new(vec![]));
+        map.insert("api".to_string(), Arc::new(vec![]));
+        map.insert("rdma".to_string(), Arc::new(vec![]));
+        map.insert("rdma_launcher".to_string(), Arc::new(vec![]));
+
+        assert_eq!(filter_thread_categories(map).unwrap().len(), 5);
+
         // invalid categories
         let mut map = BpfThreadMap::new();
         map.insert("vcpu".to_string(), Arc::new(vec![]));
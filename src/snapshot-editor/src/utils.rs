@@ -37,6 +37,11 @@ pub fn save_vmstate(microvm_state: MicrovmState, output_path: &PathBuf) -> Resul
         .open(output_path)
         .map_err(UtilsError::OutputFileOpen)?;
     let mut snapshot = Snapshot::new(microvm_state);
+    // The edited sections no longer match the digests recorded in the snapshot.
+    snapshot
+        .data
+        .update_section_digests()
+        .map_err(UtilsError::VmStateSave)?;
     snapshot
         .save(&mut output_file)
         .map_err(UtilsError::VmStateSave)?;
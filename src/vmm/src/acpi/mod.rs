@@ -99,6 +99,13 @@ impl AcpiTableWriter<'_> {
             pci_segment.append_aml_bytes(&mut dsdt_data)?;
         }
 
+        if let Some(hotplug) = &device_manager.pci_devices.hotplug {
+            hotplug
+                .lock()
+                .expect("Poisoned lock")
+                .append_aml_bytes(&mut dsdt_data)?;
+        }
+
         // Architecture specific DSDT data
         setup_arch_dsdt(&mut dsdt_data)?;
 
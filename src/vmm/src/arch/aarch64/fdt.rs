@@ -23,6 +23,7 @@ use crate::device_manager::pci_mngr::PciDevices;
 use crate::devices::acpi::vmclock::{VMCLOCK_SIZE, VmClock};
 use crate::devices::acpi::vmgenid::{VMGENID_MEM_SIZE, VmGenId};
 use crate::initrd::InitrdConfig;
+use crate::vmm_config::machine_config::CpuTopology;
 use crate::vstate::memory::{Address, GuestMemory, GuestMemoryMmap, GuestRegionType};
 
 // This is a value for uniquely identifying the FDT node declaring the interrupt controller.
@@ -31,6 +32,9 @@ const GIC_PHANDLE: u32 = 1;
 const CLOCK_PHANDLE: u32 = 2;
 // This is a value for uniquely identifying the FDT node declaring the MSI controller.
 const MSI_PHANDLE: u32 = 3;
+// This is the phandle of the FDT node of the first cpu, the following cpus use the following
+// values. These phandles are only set when describing an explicit cpu topology.
+const FIRST_CPU_PHANDLE: u32 = 4;
 // You may be wondering why this big value?
 // This phandle is used to uniquely identify the FDT nodes containing cache information. Each cpu
 // can have a variable number of caches, some of these caches may be shared with other cpus.
@@ -67,6 +71,7 @@ pub enum FdtError {
 pub fn create_fdt(
     guest_mem: &GuestMemoryMmap,
     vcpu_mpidr: Vec<u64>,
+    cpu_topology: Option<&CpuTopology>,
     cmdline: CString,
     device_manager: &DeviceManager,
     gic_device: &GICDevice,
@@ -89,7 +94,7 @@ pub fn create_fdt(
     // This is not mandatory but we use it to point the root node to the node
     // containing description of the interrupt controller for this VM.
     fdt_writer.property_u32("interrupt-parent", GIC_PHANDLE)?;
-    create_cpu_nodes(&mut fdt_writer, &vcpu_mpidr)?;
+    create_cpu_nodes(&mut fdt_writer, &vcpu_mpidr, cpu_topology)?;
     create_memory_node(&mut fdt_writer, guest_mem)?;
     create_chosen_node(&mut fdt_writer, cmdline, initrd)?;
     create_gic_node(&mut fdt_writer, gic_device)?;
@@ -110,7 +115,11 @@ pub fn create_fdt(
 }
 
 // Following are the auxiliary function for creating the different nodes that we append to our FDT.
-fn create_cpu_nodes(fdt: &mut FdtWriter, vcpu_mpidr: &[u64]) -> Result<(), FdtError> {
+fn create_cpu_nodes(
+    fdt: &mut FdtWriter,
+    vcpu_mpidr: &[u64],
+    cpu_topology: Option<&CpuTopology>,
+) -> Result<(), FdtError> {
     // Since the L1 caches are not shareable among CPUs and they are direct attributes of the
     // cpu in the device tree, we process the L1 and non-L1 caches separately.
     // We use sysfs for extracting the cache information.
@@ -136,6 +145,9 @@ fn create_cpu_nodes(fdt: &mut FdtWriter, vcpu_mpidr: &[u64]) -> Result<(), FdtEr
         // Set the field to first 24 bits of the MPIDR - Multiprocessor Affinity Register.
         // See http://infocenter.arm.com/help/index.jsp?topic=/com.arm.doc.ddi0488c/BABHBJCI.html.
         fdt.property_u64("reg", mpidr & 0x7FFFFF)?;
+        if cpu_topology.is_some() {
+            fdt.property_u32("phandle", cpu_phandle(cpu_index))?;
+        }
 
         for cache in l1_caches.iter() {
             // Please check out
@@ -212,11 +224,50 @@ fn create_cpu_nodes(fdt: &mut FdtWriter, vcpu_mpidr: &[u64]) -> Result<(), FdtEr
 
         fdt.end_node(cpu)?;
     }
+    if let Some(topology) = cpu_topology {
+        create_cpu_map_node(fdt, topology)?;
+    }
     fdt.end_node(cpus)?;
 
     Ok(())
 }
 
+fn cpu_phandle(cpu_index: usize) -> u32 {
+    FIRST_CPU_PHANDLE + u32::try_from(cpu_index).unwrap() // Safe because the number of CPUs is bounded
+}
+
+fn create_cpu_map_node(fdt: &mut FdtWriter, topology: &CpuTopology) -> Result<(), FdtError> {
+    // See https://www.kernel.org/doc/Documentation/devicetree/bindings/cpu/cpu-topology.txt.
+    // The vCPUs are laid out in order: the threads of a core, the cores of a socket and then the
+    // sockets. Each socket holds a single cluster.
+    let cpu_map = fdt.begin_node("cpu-map")?;
+    let mut cpu_index = 0;
+    for socket_index in 0..topology.sockets {
+        let socket = fdt.begin_node(&format!("socket{socket_index}"))?;
+        let cluster = fdt.begin_node("cluster0")?;
+        for core_index in 0..topology.cores_per_socket {
+            let core = fdt.begin_node(&format!("core{core_index}"))?;
+            if topology.threads_per_core == 1 {
+                fdt.property_u32("cpu", cpu_phandle(cpu_index))?;
+                cpu_index += 1;
+            } else {
+                for thread_index in 0..topology.threads_per_core {
+                    let thread = fdt.begin_node(&format!("thread{thread_index}"))?;
+                    fdt.property_u32("cpu", cpu_phandle(cpu_index))?;
+                    fdt.end_node(thread)?;
+                    cpu_index += 1;
+                }
+            }
+            fdt.end_node(core)?;
+        }
+        fdt.end_node(cluster)?;
+        fdt.end_node(socket)?;
+    }
+    fdt.end_node(cpu_map)?;
+
+    Ok(())
+}
+
 fn create_memory_node(fdt: &mut FdtWriter, guest_mem: &GuestMemoryMmap) -> Result<(), FdtError> {
     // See https://github.com/torvalds/linux/blob/master/Documentation/devicetree/booting-without-of.txt#L960
     // for an explanation of this.
@@ -448,6 +499,18 @@ fn create_rtc_node(fdt: &mut FdtWriter, dev_info: &MMIODeviceInfo) -> Result<(),
     Ok(())
 }
 
+fn create_fw_cfg_node(fdt: &mut FdtWriter, dev_info: &MMIODeviceInfo) -> Result<(), FdtError> {
+    // Driver requirements:
+    // https://elixir.bootlin.com/linux/latest/source/Documentation/devicetree/bindings/firmware/qemu,fw-cfg-mmio.yaml
+    let fw_cfg = fdt.begin_node(&format!("fw-cfg@{:x}", dev_info.addr))?;
+    fdt.property_null("dma-coherent")?;
+    fdt.property_string("compatible", "qemu,fw-cfg-mmio")?;
+    fdt.property_array_u64("reg", &[dev_info.addr, dev_info.len])?;
+    fdt.end_node(fw_cfg)?;
+
+    Ok(())
+}
+
 fn create_devices_node(
     fdt: &mut FdtWriter,
     device_manager: &DeviceManager,
@@ -460,6 +523,10 @@ fn create_devices_node(
         create_serial_node(fdt, serial_info)?;
     }
 
+    if let Some(fw_cfg_info) = device_manager.mmio_devices.fw_cfg_device_info() {
+        create_fw_cfg_node(fdt, fw_cfg_info)?;
+    }
+
     let mut virtio_mmio = device_manager.mmio_devices.virtio_device_info();
 
     // Sort out virtio devices by address from low to high and insert them into fdt table.
@@ -594,6 +661,7 @@ mod tests {
         create_fdt(
             &mem,
             vec![0],
+            None,
             cmdline.as_cstring().unwrap(),
             &device_manager,
             &gic,
@@ -619,6 +687,7 @@ mod tests {
         let current_dtb_bytes = create_fdt(
             &mem,
             vec![0],
+            None,
             CString::new("console=tty0").unwrap(),
             &device_manager,
             &gic,
@@ -681,6 +750,7 @@ mod tests {
         let current_dtb_bytes = create_fdt(
             &mem,
             vec![0],
+            None,
             CString::new("console=tty0").unwrap(),
             &device_manager,
             &gic,
@@ -720,4 +790,45 @@ mod tests {
             format!("{:?}", generated_fdt)
         );
     }
+
+    #[test]
+    fn test_create_fdt_with_cpu_topology() {
+        let mem = arch_mem(layout::FDT_MAX_SIZE + 0x1000);
+        let device_manager = default_device_manager();
+        let kvm = Kvm::new(vec![]).unwrap();
+        let vm = Vm::new(&kvm).unwrap();
+        let gic = create_gic(vm.fd(), 4, None).unwrap();
+        let topology = CpuTopology {
+            sockets: 2,
+            cores_per_socket: 2,
+            threads_per_core: 1,
+        };
+
+        let dtb_bytes = create_fdt(
+            &mem,
+            vec![0, 1, 2, 3],
+            Some(&topology),
+            CString::new("console=tty0").unwrap(),
+            &device_manager,
+            &gic,
+            &None,
+        )
+        .unwrap();
+
+        let fdt = device_tree::DeviceTree::load(&dtb_bytes).unwrap();
+        for cpu_index in 0..4usize {
+            let cpu = fdt.find(&format!("/cpus/cpu@{cpu_index}")).unwrap();
+            assert_eq!(cpu.prop_u32("phandle").unwrap(), cpu_phandle(cpu_index));
+
+            let core = fdt
+                .find(&format!(
+                    "/cpus/cpu-map/socket{}/cluster0/core{}",
+                    cpu_index / 2,
+                    cpu_index % 2
+                ))
+                .unwrap();
+            assert_eq!(core.prop_u32("cpu").unwrap(), cpu_phandle(cpu_index));
+        }
+        assert!(fdt.find("/cpus/cpu-map/socket2").is_none());
+    }
 }
@@ -1,14 +1,21 @@
 // Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::convert::Infallible;
-
-use kvm_ioctls::Kvm as KvmFd;
+use kvm_ioctls::{Kvm as KvmFd, VmFd};
 
 use crate::cpu_config::templates::KvmCapability;
 
-/// ['Kvm'] initialization can't fail for Aarch64
-pub type KvmArchError = Infallible;
+/// IPA size, in bits, of the VMs created by KVM when the host doesn't report its IPA limit.
+pub const KVM_DEFAULT_IPA_SIZE: u8 = 40;
+
+/// Architecture specific error for KVM initialization
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum KvmArchError {
+    /// The host supports an IPA size of up to {1} bits, {0} bits were requested.
+    IpaSizeNotSupported(u8, u8),
+    /// The guest memory requires an IPA size of at least {0} bits, the VM is limited to {1} bits.
+    IpaSizeTooSmall(u8, u8),
+}
 
 /// Optional capabilities.
 #[derive(Debug, Default)]
@@ -24,6 +31,9 @@ pub struct Kvm {
     pub fd: KvmFd,
     /// Additional capabilities that were specified in cpu template.
     pub kvm_cap_modifiers: Vec<KvmCapability>,
+    /// IPA size, in bits, of the VMs. When unset, VMs get the largest IPA size supported by the
+    /// host.
+    pub ipa_size: Option<u8>,
 }
 
 impl Kvm {
@@ -45,9 +55,51 @@ impl Kvm {
         Ok(Self {
             fd,
             kvm_cap_modifiers,
+            ipa_size: None,
         })
     }
 
+    /// Returns the largest IPA size, in bits, supported by the host.
+    pub fn host_ipa_limit(&self) -> u8 {
+        // KVM reports a limit of 0 when it doesn't support configuring the IPA size.
+        u8::try_from(self.fd.get_host_ipa_limit())
+            .ok()
+            .filter(|limit| *limit != 0)
+            .unwrap_or(KVM_DEFAULT_IPA_SIZE)
+    }
+
+    /// Sets the IPA size of the VMs, checking that the host supports it and that it covers the
+    /// guest memory, whose last address is `last_mem_addr`. When `ipa_size` is unset, the
+    /// largest IPA size supported by the host is checked instead.
+    pub fn set_ipa_size(
+        &mut self,
+        ipa_size: Option<u8>,
+        last_mem_addr: u64,
+    ) -> Result<(), KvmArchError> {
+        let host_limit = self.host_ipa_limit();
+        let limit = ipa_size.unwrap_or(host_limit);
+        if limit > host_limit {
+            return Err(KvmArchError::IpaSizeNotSupported(limit, host_limit));
+        }
+
+        let required = ipa_size_for(last_mem_addr);
+        if required > limit {
+            return Err(KvmArchError::IpaSizeTooSmall(required, limit));
+        }
+
+        self.ipa_size = ipa_size;
+        Ok(())
+    }
+
+    /// Creates a VM fd, with the configured IPA size if any.
+    pub fn create_vm(&self) -> Result<VmFd, kvm_ioctls::Error> {
+        match self.ipa_size {
+            // The IPA size is held in the lowest 8 bits of the VM type.
+            Some(ipa_size) => self.fd.create_vm_with_type(u64::from(ipa_size)),
+            None => self.fd.create_vm(),
+        }
+    }
+
     /// Returns struct with optional capabilities statuses.
     pub fn optional_capabilities(&self) -> OptionalCapabilities {
         OptionalCapabilities {
@@ -58,3 +110,50 @@ impl Kvm {
         }
     }
 }
+
+/// Returns the IPA size, in bits, needed to address `last_addr`.
+fn ipa_size_for(last_addr: u64) -> u8 {
+    u8::try_from(u64::BITS - last_addr.leading_zeros()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipa_size_for() {
+        assert_eq!(ipa_size_for(0), 0);
+        assert_eq!(ipa_size_for((1 << 40) - 1), 40);
+        assert_eq!(ipa_size_for(1 << 40), 41);
+        assert_eq!(ipa_size_for(u64::MAX), 64);
+    }
+
+    #[test]
+    fn test_set_ipa_size() {
+        let mut kvm = Kvm::new(vec![]).unwrap();
+        let host_limit = kvm.host_ipa_limit();
+
+        kvm.set_ipa_size(None, (1 << 32) - 1).unwrap();
+        assert_eq!(kvm.ipa_size, None);
+        kvm.set_ipa_size(Some(host_limit), (1 << host_limit) - 1)
+            .unwrap();
+        assert_eq!(kvm.ipa_size, Some(host_limit));
+        kvm.create_vm().unwrap();
+
+        assert_eq!(
+            kvm.set_ipa_size(Some(host_limit + 1), 0),
+            Err(KvmArchError::IpaSizeNotSupported(
+                host_limit + 1,
+                host_limit
+            ))
+        );
+        assert_eq!(
+            kvm.set_ipa_size(Some(36), 1 << 36),
+            Err(KvmArchError::IpaSizeTooSmall(37, 36))
+        );
+        assert_eq!(
+            kvm.set_ipa_size(None, 1 << host_limit),
+            Err(KvmArchError::IpaSizeTooSmall(host_limit + 1, host_limit))
+        );
+    }
+}
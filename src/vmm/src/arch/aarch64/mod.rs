@@ -105,6 +105,7 @@ pub fn configure_system_for_boot(
     let vcpu_config = VcpuConfig {
         vcpu_count: machine_config.vcpu_count,
         smt: machine_config.smt,
+        cpus_per_socket: machine_config.topology().cpus_per_socket(),
         cpu_config,
     };
 
@@ -130,6 +131,7 @@ pub fn configure_system_for_boot(
     let fdt = fdt::create_fdt(
         vm.guest_memory(),
         vcpu_mpidr,
+        machine_config.cpu_topology.as_ref(),
         cmdline,
         device_manager,
         vm.get_irqchip(),
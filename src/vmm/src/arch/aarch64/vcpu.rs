@@ -596,6 +596,7 @@ mod tests {
         let vcpu_config = VcpuConfig {
             vcpu_count: 1,
             smt: false,
+            cpus_per_socket: 1,
             cpu_config: CpuConfiguration::default(),
         };
 
@@ -37,8 +37,8 @@ pub use x86_64::vm::{ArchVm, ArchVmError, VmState};
 
 #[cfg(target_arch = "x86_64")]
 pub use crate::arch::x86_64::{
-    ConfigurationError, arch_memory_regions, configure_system_for_boot, get_kernel_start,
-    initrd_load_addr, layout::*, load_kernel,
+    ConfigurationError, arch_memory_regions, configure_system_for_boot, firmware_entry_point,
+    get_kernel_start, initrd_load_addr, layout::*, load_kernel,
 };
 
 /// Types of devices that can get attached to this platform.
@@ -88,6 +88,9 @@ pub enum BootProtocol {
     #[cfg(target_arch = "x86_64")]
     /// PVH boot protocol (x86/HVM direct boot ABI)
     PvhBoot,
+    #[cfg(target_arch = "x86_64")]
+    /// Firmware executed from the reset vector
+    Firmware,
 }
 
 impl fmt::Display for BootProtocol {
@@ -96,6 +99,8 @@ impl fmt::Display for BootProtocol {
             BootProtocol::LinuxBoot => write!(f, "Linux 64-bit boot protocol"),
             #[cfg(target_arch = "x86_64")]
             BootProtocol::PvhBoot => write!(f, "PVH boot protocol"),
+            #[cfg(target_arch = "x86_64")]
+            BootProtocol::Firmware => write!(f, "firmware boot"),
         }
     }
 }
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use kvm_bindings::{CpuId, KVM_MAX_CPUID_ENTRIES, MsrList};
-use kvm_ioctls::Kvm as KvmFd;
+use kvm_ioctls::{Kvm as KvmFd, VmFd};
 
 use crate::arch::x86_64::xstate::{XstateError, request_dynamic_xstate_features};
 use crate::cpu_config::templates::KvmCapability;
@@ -63,6 +63,11 @@ impl Kvm {
         })
     }
 
+    /// Creates a VM fd.
+    pub fn create_vm(&self) -> Result<VmFd, kvm_ioctls::Error> {
+        self.fd.create_vm()
+    }
+
     /// Msrs needed to be saved on snapshot creation.
     pub fn msrs_to_save(&self) -> Result<MsrList, crate::arch::x86_64::msr::MsrError> {
         crate::arch::x86_64::msr::get_msrs_to_save(&self.fd)
@@ -37,8 +37,12 @@ pub const GSI_MSI_END: u32 = 4095;
 /// Number of GSI available for MSI.
 pub const GSI_MSI_NUM: u32 = GSI_MSI_END - GSI_MSI_START + 1;
 
-/// Address for the TSS setup.
-pub const KVM_TSS_ADDRESS: u64 = 0xfffb_d000;
+/// Address for the TSS setup. It sits right below the firmware flash, which ends at 4 GiB.
+pub const KVM_TSS_ADDRESS: u64 = 0xfeff_d000;
+
+/// Address of the identity map page table used by KVM to run the vCPUs in real mode, right below
+/// the TSS.
+pub const KVM_IDENTITY_MAP_ADDRESS: u64 = 0xfeff_c000;
 
 /// Address of the hvm_start_info struct used in PVH boot
 pub const PVH_INFO_START: u64 = 0x6000;
@@ -63,6 +67,9 @@ pub const IOAPIC_ADDR: u32 = 0xfec0_0000;
 /// Location of RSDP pointer in x86 machines
 pub const RSDP_ADDR: u64 = 0x000e_0000;
 
+/// Location of the SMBIOS entry point, in the legacy BIOS area scanned by the guest kernel.
+pub const SMBIOS_START: u64 = 0x000f_0000;
+
 /// Start of memory region we will use for system data (MPTable, ACPI, etc). We are putting its
 /// start address where EBDA normally starts, i.e. in the last 1 KiB of the first 640KiB of memory
 pub const SYSTEM_MEM_START: u64 = 0x9fc00;
@@ -94,6 +101,12 @@ pub const SYSTEM_MEM_SIZE: u64 = RSDP_ADDR - SYSTEM_MEM_START;
 /// First address that cannot be addressed using 32 bit anymore.
 pub const FIRST_ADDR_PAST_32BITS: u64 = 1 << 32;
 
+/// Maximum size of the firmware flash, mapped right below 4 GiB.
+pub const FIRMWARE_MAX_SIZE: u64 = 16 << 20;
+
+/// Address of the first instruction executed by the vCPUs out of reset, in the firmware flash.
+pub const RESET_VECTOR: u64 = 0xffff_fff0;
+
 /// The size of the memory area reserved for MMIO 32-bit accesses.
 pub const MMIO32_MEM_SIZE: u64 = mib_to_bytes(1024) as u64;
 /// The start of the memory area reserved for MMIO 32-bit accesses.
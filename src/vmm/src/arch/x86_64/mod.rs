@@ -21,6 +21,8 @@ mod mptable;
 pub mod msr;
 /// Logic for configuring x86_64 registers.
 pub mod regs;
+/// Logic for writing the SMBIOS tables.
+pub mod smbios;
 /// Architecture specific vCPU code
 pub mod vcpu;
 /// Architecture specific VM state code
@@ -100,6 +102,8 @@ pub enum ConfigurationError {
     VcpuConfigure(#[from] KvmVcpuConfigureError),
     /// Error configuring ACPI: {0}
     Acpi(#[from] crate::acpi::AcpiError),
+    /// Error writing the SMBIOS tables: {0}
+    Smbios(#[from] smbios::SmbiosError),
 }
 
 /// Returns a Vec of the valid memory addresses.
@@ -192,6 +196,8 @@ pub fn configure_system_for_boot(
     let vcpu_config = VcpuConfig {
         vcpu_count: machine_config.vcpu_count,
         smt: machine_config.smt,
+        cpus_per_socket: machine_config.topology().cpus_per_socket(),
+        tsc: machine_config.tsc,
         cpu_config,
     };
 
@@ -224,7 +230,8 @@ pub fn configure_system_for_boot(
     .map_err(ConfigurationError::MpTableSetup)?;
 
     match entry_point.protocol {
-        BootProtocol::PvhBoot => {
+        // The firmware reads the memory map from the PVH start info.
+        BootProtocol::PvhBoot | BootProtocol::Firmware => {
             configure_pvh(vm.guest_memory(), GuestAddress(CMDLINE_START), initrd)?;
         }
         BootProtocol::LinuxBoot => {
@@ -463,6 +470,14 @@ pub fn load_kernel(
     })
 }
 
+/// Entry point of the firmware loaded in the flash right below 4 GiB.
+pub fn firmware_entry_point() -> EntryPoint {
+    EntryPoint {
+        entry_addr: GuestAddress(layout::RESET_VECTOR),
+        protocol: BootProtocol::Firmware,
+    }
+}
+
 #[cfg(kani)]
 mod verification {
 
@@ -91,6 +91,15 @@ pub fn setup_regs(vcpu: &VcpuFd, entry_point: EntryPoint) -> Result<(), SetupReg
             rip: entry_point.entry_addr.raw_value(),
             ..Default::default()
         },
+        BootProtocol::Firmware => kvm_regs {
+            // Real mode reset state. The code segment base set by KVM on reset makes the vCPU
+            // fetch its first instruction at the reset vector. The firmware finds the PVH start
+            // info at the same address as the PVH kernels.
+            rflags: 0x0000_0000_0000_0002u64,
+            rbx: super::layout::PVH_INFO_START,
+            rip: entry_point.entry_addr.raw_value() & 0xffff,
+            ..Default::default()
+        },
         BootProtocol::LinuxBoot => kvm_regs {
             // Configure regs as required by Linux 64-bit boot protocol.
             rflags: 0x0000_0000_0000_0002u64,
@@ -144,6 +153,11 @@ pub fn setup_sregs(
     vcpu: &VcpuFd,
     boot_prot: BootProtocol,
 ) -> Result<(), SetupSpecialRegistersError> {
+    // The firmware starts in real mode, from the special registers set by KVM on reset.
+    if let BootProtocol::Firmware = boot_prot {
+        return Ok(());
+    }
+
     let mut sregs: kvm_sregs = vcpu
         .get_sregs()
         .map_err(SetupSpecialRegistersError::GetSpecialRegisters)?;
@@ -216,6 +230,7 @@ fn configure_segments_and_sregs(
                 gdt_entry(0x808b, 0, 0xfffff), // TSS
             ]
         }
+        BootProtocol::Firmware => unreachable!("The firmware sets up its own GDT"),
     };
 
     let code_seg = kvm_segment_from_gdt(gdt_table[1], 1);
@@ -249,6 +264,7 @@ fn configure_segments_and_sregs(
             sregs.cr0 |= X86_CR0_PE;
             sregs.efer |= EFER_LME | EFER_LMA;
         }
+        BootProtocol::Firmware => unreachable!("The firmware starts in real mode"),
     }
 
     Ok(())
@@ -421,6 +437,29 @@ mod tests {
             });
     }
 
+    #[test]
+    fn test_setup_firmware() {
+        let kvm = Kvm::new().unwrap();
+        let vm = kvm.create_vm().unwrap();
+        let vcpu = vm.create_vcpu(0).unwrap();
+        let gm = single_region_mem(0x10000);
+        let reset_sregs = vcpu.get_sregs().unwrap();
+
+        let entry_point = EntryPoint {
+            entry_addr: GuestAddress(super::super::layout::RESET_VECTOR),
+            protocol: BootProtocol::Firmware,
+        };
+        setup_regs(&vcpu, entry_point).unwrap();
+        setup_sregs(&gm, &vcpu, BootProtocol::Firmware).unwrap();
+
+        let regs = vcpu.get_regs().unwrap();
+        assert_eq!(regs.rip, 0xfff0);
+        assert_eq!(regs.rbx, super::super::layout::PVH_INFO_START);
+        let sregs = vcpu.get_sregs().unwrap();
+        assert_eq!(sregs.cs.base, reset_sregs.cs.base);
+        assert_eq!(sregs.cr0 & X86_CR0_PE, 0);
+    }
+
     #[test]
     fn test_write_gdt_table() {
         // Not enough memory for the gdt table to be written.
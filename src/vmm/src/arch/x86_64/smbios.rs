@@ -0,0 +1,269 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Writes the SMBIOS tables describing the system to the guest memory.
+//!
+//! Only a SMBIOS 3.0 entry point, found by the guest kernel when scanning the legacy BIOS area,
+//! and the BIOS information (type 0), system information (type 1) and end-of-table (type 127)
+//! structures are provided.
+
+use crate::arch::x86_64::layout::SMBIOS_START;
+use crate::vmm_config::smbios::{SmbiosConfig, SmbiosConfigError};
+use crate::vstate::memory::{Bytes, GuestAddress, GuestMemoryMmap};
+
+const SM3_ANCHOR: &[u8; 5] = b"_SM3_";
+const SM3_ENTRY_POINT_LEN: u8 = 0x18;
+const SM3_ENTRY_POINT_REVISION: u8 = 1;
+const SMBIOS_MAJOR_VERSION: u8 = 3;
+const SMBIOS_MINOR_VERSION: u8 = 2;
+
+/// The structure table follows the entry point, 16 bytes aligned.
+const STRUCTURE_TABLE_OFFSET: u64 = 0x20;
+
+const TYPE_BIOS_INFORMATION: u8 = 0;
+const TYPE_SYSTEM_INFORMATION: u8 = 1;
+const TYPE_END_OF_TABLE: u8 = 127;
+
+/// BIOS characteristics are not supported.
+const BIOS_CHARACTERISTICS_NOT_SUPPORTED: u64 = 1 << 3;
+/// The SMBIOS tables describe a virtual machine.
+const BIOS_CHARACTERISTICS_EXT2_VIRTUAL_MACHINE: u8 = 1 << 4;
+/// The system was woken up by the power switch.
+const WAKE_UP_TYPE_POWER_SWITCH: u8 = 6;
+
+const DEFAULT_VENDOR: &str = "Firecracker";
+
+/// Errors thrown while writing the SMBIOS tables.
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum SmbiosError {
+    /// Invalid SMBIOS configuration: {0}
+    Config(#[from] SmbiosConfigError),
+    /// Failure to write the SMBIOS entry point.
+    WriteEntryPoint,
+    /// Failure to write the SMBIOS structure table.
+    WriteStructureTable,
+}
+
+/// A SMBIOS structure along with its strings.
+#[derive(Debug)]
+struct Structure {
+    bytes: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl Structure {
+    fn new(type_: u8, len: u8, handle: u16) -> Self {
+        let mut bytes = vec![type_, len];
+        bytes.extend_from_slice(&handle.to_le_bytes());
+        Structure {
+            bytes,
+            strings: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    /// Appends the index of the given string, 0 when there is no string.
+    fn push_string(&mut self, string: Option<&str>) -> &mut Self {
+        let index = match string {
+            Some(string) => {
+                self.strings.extend_from_slice(string.as_bytes());
+                self.strings.push(0);
+                u8::try_from(self.strings.iter().filter(|byte| **byte == 0).count()).unwrap()
+            }
+            None => 0,
+        };
+        self.push(&[index])
+    }
+
+    /// Serializes the structure, terminating the set of strings with an additional NUL. A
+    /// structure without strings is followed by two NULs.
+    fn finish(&self, table: &mut Vec<u8>) {
+        debug_assert_eq!(usize::from(self.bytes[1]), self.bytes.len());
+        table.extend_from_slice(&self.bytes);
+        if self.strings.is_empty() {
+            table.push(0);
+        } else {
+            table.extend_from_slice(&self.strings);
+        }
+        table.push(0);
+    }
+}
+
+fn structure_table(config: &SmbiosConfig) -> Result<Vec<u8>, SmbiosError> {
+    let uuid = config.parsed_uuid()?.unwrap_or_default();
+    let mut table = Vec::new();
+
+    let mut bios = Structure::new(TYPE_BIOS_INFORMATION, 0x18, 0);
+    bios.push_string(Some(DEFAULT_VENDOR))
+        // Version, starting address segment, release date and ROM size.
+        .push_string(None)
+        .push(&0u16.to_le_bytes())
+        .push_string(None)
+        .push(&[0])
+        .push(&BIOS_CHARACTERISTICS_NOT_SUPPORTED.to_le_bytes())
+        .push(&[0, BIOS_CHARACTERISTICS_EXT2_VIRTUAL_MACHINE])
+        // System BIOS and embedded controller firmware releases.
+        .push(&[0, 0, 0xff, 0xff]);
+    bios.finish(&mut table);
+
+    let mut system = Structure::new(TYPE_SYSTEM_INFORMATION, 0x1b, 1);
+    system
+        .push_string(Some(
+            config.manufacturer.as_deref().unwrap_or(DEFAULT_VENDOR),
+        ))
+        .push_string(Some(config.product.as_deref().unwrap_or(DEFAULT_VENDOR)))
+        // Version.
+        .push_string(None)
+        .push_string(config.serial_number.as_deref())
+        // The first three fields of the UUID are encoded in little endian.
+        .push(&uuid.to_bytes_le())
+        .push(&[WAKE_UP_TYPE_POWER_SWITCH])
+        // SKU number and family.
+        .push_string(None)
+        .push_string(None);
+    system.finish(&mut table);
+
+    Structure::new(TYPE_END_OF_TABLE, 4, 2).finish(&mut table);
+    Ok(table)
+}
+
+fn entry_point(table_len: u32, table_addr: u64) -> Vec<u8> {
+    let mut entry_point = SM3_ANCHOR.to_vec();
+    // The checksum is computed once the entry point is complete.
+    entry_point.push(0);
+    entry_point.extend_from_slice(&[
+        SM3_ENTRY_POINT_LEN,
+        SMBIOS_MAJOR_VERSION,
+        SMBIOS_MINOR_VERSION,
+        // Specification revision.
+        0,
+        SM3_ENTRY_POINT_REVISION,
+        // Reserved.
+        0,
+    ]);
+    entry_point.extend_from_slice(&table_len.to_le_bytes());
+    entry_point.extend_from_slice(&table_addr.to_le_bytes());
+    let sum = entry_point
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    entry_point[5] = sum.wrapping_neg();
+    entry_point
+}
+
+/// Writes the SMBIOS tables reporting the configured system information in the legacy BIOS area.
+pub fn setup_smbios(mem: &GuestMemoryMmap, config: &SmbiosConfig) -> Result<(), SmbiosError> {
+    let table = structure_table(config)?;
+    let table_addr = SMBIOS_START + STRUCTURE_TABLE_OFFSET;
+    mem.write_slice(&table, GuestAddress(table_addr))
+        .map_err(|_| SmbiosError::WriteStructureTable)?;
+
+    let table_len = u32::try_from(table.len()).unwrap();
+    mem.write_slice(
+        &entry_point(table_len, table_addr),
+        GuestAddress(SMBIOS_START),
+    )
+    .map_err(|_| SmbiosError::WriteEntryPoint)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::single_region_mem_at;
+    use crate::utils::u64_to_usize;
+
+    /// Splits a structure in its formatted area and its strings.
+    fn parse_structure(table: &[u8]) -> (&[u8], Vec<&str>, &[u8]) {
+        let len = usize::from(table[1]);
+        let strings_end = table[len..]
+            .windows(2)
+            .position(|window| window == [0, 0])
+            .unwrap()
+            + len;
+        let strings = table[len..strings_end]
+            .split(|byte| *byte == 0)
+            .filter(|string| !string.is_empty())
+            .map(|string| std::str::from_utf8(string).unwrap())
+            .collect();
+        (&table[..len], strings, &table[strings_end + 2..])
+    }
+
+    #[test]
+    fn test_setup_smbios() {
+        let mem = single_region_mem_at(SMBIOS_START, 0x1000);
+        let config = SmbiosConfig {
+            manufacturer: Some(String::from("Example Corp.")),
+            product: None,
+            serial_number: Some(String::from("SN-0001")),
+            uuid: Some(String::from("00112233-4455-6677-8899-aabbccddeeff")),
+        };
+        setup_smbios(&mem, &config).unwrap();
+
+        let mut entry_point = [0u8; SM3_ENTRY_POINT_LEN as usize];
+        mem.read_slice(&mut entry_point, GuestAddress(SMBIOS_START))
+            .unwrap();
+        assert_eq!(&entry_point[..5], SM3_ANCHOR);
+        assert_eq!(
+            entry_point
+                .iter()
+                .fold(0u8, |sum, byte| sum.wrapping_add(*byte)),
+            0
+        );
+        assert_eq!(entry_point[7..9], [3, 2]);
+        let table_len = u32::from_le_bytes(entry_point[12..16].try_into().unwrap());
+        let table_addr = u64::from_le_bytes(entry_point[16..24].try_into().unwrap());
+        assert_eq!(table_addr, SMBIOS_START + STRUCTURE_TABLE_OFFSET);
+
+        let mut table = vec![0u8; u64_to_usize(u64::from(table_len))];
+        mem.read_slice(&mut table, GuestAddress(table_addr))
+            .unwrap();
+
+        let (bios, strings, table) = parse_structure(&table);
+        assert_eq!(bios[0], TYPE_BIOS_INFORMATION);
+        assert_eq!(strings, ["Firecracker"]);
+
+        let (system, strings, table) = parse_structure(table);
+        assert_eq!(system[0], TYPE_SYSTEM_INFORMATION);
+        assert_eq!(strings, ["Example Corp.", "Firecracker", "SN-0001"]);
+        // Manufacturer, product, version and serial number.
+        assert_eq!(system[4..8], [1, 2, 0, 3]);
+        assert_eq!(
+            system[8..24],
+            [
+                0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff
+            ]
+        );
+
+        let (end, strings, table) = parse_structure(table);
+        assert_eq!(end[0], TYPE_END_OF_TABLE);
+        assert!(strings.is_empty());
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_setup_smbios_errors() {
+        let config = SmbiosConfig {
+            uuid: Some(String::from("invalid")),
+            ..Default::default()
+        };
+        let mem = single_region_mem_at(SMBIOS_START, 0x1000);
+        assert_eq!(
+            setup_smbios(&mem, &config),
+            Err(SmbiosError::Config(SmbiosConfigError::InvalidUuid(
+                String::from("invalid")
+            )))
+        );
+
+        // The guest memory doesn't hold the SMBIOS area.
+        let mem = single_region_mem_at(0, 0x1000);
+        assert_eq!(
+            setup_smbios(&mem, &SmbiosConfig::default()),
+            Err(SmbiosError::WriteStructureTable)
+        );
+    }
+}
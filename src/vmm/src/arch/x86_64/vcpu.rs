@@ -137,6 +137,12 @@ pub enum KvmVcpuConfigureError {
     SetupSpecialRegisters(#[from] SetupSpecialRegistersError),
     /// Failed to configure LAPICs: {0}
     SetLint(#[from] interrupts::InterruptError),
+    /// Failed to set the TSC frequency: {0}
+    SetTscFrequency(#[from] SetTscError),
+    /// Failed to get the TSC frequency: {0}
+    GetTscFrequency(#[from] GetTscError),
+    /// Failed to advertise the TSC in CPUID: {0}
+    TscEntries(#[from] cpuid::TscEntriesError),
 }
 
 /// A wrapper around creating and using a kvm x86_64 vcpu.
@@ -211,10 +217,20 @@ impl KvmVcpu {
             self.index,
             // The total number of logical CPUs.
             vcpu_config.vcpu_count,
+            // The number of logical CPUs per socket.
+            vcpu_config.cpus_per_socket,
             // The number of bits needed to enumerate logical CPUs per core.
             u8::from(vcpu_config.vcpu_count > 1 && vcpu_config.smt),
         )?;
 
+        // Set the TSC frequency and advertise it to the guest.
+        if let Some(tsc) = &vcpu_config.tsc {
+            if let Some(frequency_khz) = tsc.frequency_khz {
+                self.set_tsc_khz(frequency_khz)?;
+            }
+            cpuid.update_tsc_entries(tsc.invariant, self.get_tsc_khz()?)?;
+        }
+
         // Set CPUID.
         let kvm_cpuid = kvm_bindings::CpuId::try_from(cpuid)?;
 
@@ -866,6 +882,8 @@ mod tests {
         Ok(VcpuConfig {
             vcpu_count: 1,
             smt: false,
+            cpus_per_socket: 1,
+            tsc: None,
             cpu_config,
         })
     }
@@ -1018,6 +1036,8 @@ mod tests {
         let vcpu_config = VcpuConfig {
             vcpu_count: 1,
             smt: false,
+            cpus_per_socket: 1,
+            tsc: None,
             cpu_config: CpuConfiguration {
                 cpuid: Cpuid::try_from(kvm.supported_cpuid.clone()).unwrap(),
                 msrs: BTreeMap::new(),
@@ -1087,6 +1107,8 @@ mod tests {
         let vcpu_config = VcpuConfig {
             vcpu_count: 1,
             smt: false,
+            cpus_per_socket: 1,
+            tsc: None,
             cpu_config: CpuConfiguration {
                 cpuid: Cpuid::try_from(kvm.supported_cpuid.clone()).unwrap(),
                 msrs: BTreeMap::new(),
@@ -48,6 +48,8 @@ pub enum ArchVmError {
     GetMsrsToSave(MsrError),
     /// Failed during KVM_SET_TSS_ADDRESS: {0}
     SetTssAddress(kvm_ioctls::Error),
+    /// Failed during KVM_SET_IDENTITY_MAP_ADDR: {0}
+    SetIdentityMapAddress(kvm_ioctls::Error),
 }
 
 /// Structure representing the current architecture's understand of what a "virtual machine" is.
@@ -95,6 +97,11 @@ impl ArchVm {
             .fd
             .set_tss_address(u64_to_usize(crate::arch::x86_64::layout::KVM_TSS_ADDRESS))
             .map_err(ArchVmError::SetTssAddress)?;
+        // Keep the identity map out of the firmware flash, where KVM places it by default.
+        common
+            .fd
+            .set_identity_map_address(crate::arch::x86_64::layout::KVM_IDENTITY_MAP_ADDRESS)
+            .map_err(ArchVmError::SetIdentityMapAddress)?;
 
         let pio_bus = Arc::new(Bus::new());
 
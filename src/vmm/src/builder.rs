@@ -12,7 +12,9 @@ use std::sync::{Arc, Mutex};
 use event_manager::SubscriberOps;
 use linux_loader::cmdline::Cmdline as LoaderKernelCmdline;
 use userfaultfd::Uffd;
-use utils::time::TimestampUs;
+#[cfg(target_arch = "x86_64")]
+use utils::time::TimerFd;
+use utils::time::{ClockType, TimestampUs, get_time_us};
 use vm_allocator::AllocPolicy;
 use vm_memory::GuestAddress;
 
@@ -32,26 +34,36 @@ use crate::device_manager::{
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::device::VirtioDevice;
+use crate::devices::virtio::gpio::device::Gpio;
+use crate::devices::virtio::i2c::device::I2c;
 use crate::devices::virtio::mem::{VIRTIO_MEM_DEFAULT_SLOT_SIZE_MIB, VirtioMem};
 use crate::devices::virtio::net::Net;
+use crate::devices::virtio::p9::device::P9;
 use crate::devices::virtio::pmem::device::Pmem;
 use crate::devices::virtio::rdma::VirtioRdma;
+use crate::devices::virtio::rdma::backend::thread::BackendLauncher;
 use crate::devices::virtio::rng::Entropy;
 use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
 #[cfg(feature = "gdb")]
 use crate::gdb;
 use crate::initrd::{InitrdConfig, InitrdError};
 use crate::logger::debug;
-use crate::persist::{MicrovmState, MicrovmStateError};
+use crate::persist::{MicrovmState, MicrovmStateError, VmInfo};
+use crate::rate_limiter::cpu_budget::CpuBudget;
 use crate::resources::VmResources;
 use crate::seccomp::BpfThreadMap;
 use crate::snapshot::Persist;
+use crate::snapshot_agent::{SnapshotAgent, SnapshotAgentError};
 use crate::utils::mib_to_bytes;
 use crate::vmm_config::instance_info::InstanceInfo;
+use crate::vmm_config::interrupt_affinity::InterruptAffinityConfigError;
+use crate::vmm_config::io_cpu_budget::IoCpuBudgetConfig;
 use crate::vmm_config::machine_config::MachineConfigError;
 use crate::vmm_config::memory_hotplug::MemoryHotplugConfig;
+use crate::vmm_config::rdma::RdmaDeviceBuilder;
+use crate::vstate::cold_memory::{ColdMemory, ColdMemoryError};
 use crate::vstate::kvm::{Kvm, KvmError};
-use crate::vstate::memory::GuestRegionMmap;
+use crate::vstate::memory::{GuestRegionMmap, MemoryWriteback};
 #[cfg(target_arch = "aarch64")]
 use crate::vstate::resources::ResourceAllocator;
 use crate::vstate::vcpu::VcpuError;
@@ -63,8 +75,18 @@ use crate::{EventManager, Vmm, VmmError};
 pub enum StartMicrovmError {
     /// Unable to attach block device to Vmm: {0}
     AttachBlockDevice(io::Error),
+    /// Cannot set up the cold memory backing store: {0}
+    ColdMemory(#[from] ColdMemoryError),
+    /// Cannot set up the snapshot agent channel: {0}
+    SnapshotAgent(#[from] SnapshotAgentError),
+    /// Cannot attach a device to the I/O CPU budget: {0}
+    IoCpuBudget(io::Error),
+    /// Invalid interrupt affinity: {0}
+    InterruptAffinity(#[from] InterruptAffinityConfigError),
     /// Could not attach device: {0}
     AttachDevice(#[from] AttachDeviceError),
+    /// Cannot subscribe a device to the removals of guest memory: {0}
+    MemoryRemovals(io::Error),
     /// System configuration error: {0}
     ConfigureSystem(#[from] ConfigurationError),
     /// Failed to create device manager: {0}
@@ -75,6 +97,8 @@ pub enum StartMicrovmError {
     CreateNetDevice(crate::devices::virtio::net::NetError),
     /// Cannot create pmem device: {0}
     CreatePmemDevice(#[from] crate::devices::virtio::pmem::device::PmemError),
+    /// Cannot create rdma device: {0}
+    CreateRdmaDevice(crate::devices::virtio::rdma::RdmaError),
     /// Cannot create RateLimiter: {0}
     CreateRateLimiter(io::Error),
     /// Error creating legacy device: {0}
@@ -99,12 +123,16 @@ pub enum StartMicrovmError {
     Kvm(#[from] KvmError),
     /// Cannot load command line string: {0}
     LoadCommandline(linux_loader::loader::Error),
+    /// Cannot start microvm with both a kernel and a firmware.
+    KernelAndFirmware,
     /// Cannot start microvm without kernel configuration.
     MissingKernelConfig,
     /// Cannot start microvm without guest mem_size config.
     MissingMemSizeConfig,
     /// No seccomp filter for thread category: {0}
     MissingSeccompFilters(String),
+    /// Cannot set up the threads of the rdma backends: {0}
+    RdmaBackendThreads(crate::devices::virtio::rdma::backend::RdmaBackendError),
     /// The net device configuration is missing the tap device.
     NetDeviceNotConfigured,
     /// Cannot open the block device backing file: {0}
@@ -149,11 +177,13 @@ pub fn build_microvm_for_boot(
     // Timestamp for measuring microVM boot duration.
     let request_ts = TimestampUs::default();
 
-    let boot_config = vm_resources
-        .boot_source
-        .builder
-        .as_ref()
-        .ok_or(StartMicrovmError::MissingKernelConfig)?;
+    // The microVM boots either a kernel or a firmware.
+    let boot_config = vm_resources.boot_source.builder.as_ref();
+    match (boot_config, &vm_resources.firmware) {
+        (Some(_), Some(_)) => return Err(StartMicrovmError::KernelAndFirmware),
+        (None, None) => return Err(StartMicrovmError::MissingKernelConfig),
+        _ => (),
+    }
 
     let guest_memory = vm_resources
         .allocate_guest_memory()
@@ -161,14 +191,24 @@ pub fn build_microvm_for_boot(
 
     // Clone the command-line so that a failed boot doesn't pollute the original.
     #[allow(unused_mut)]
-    let mut boot_cmdline = boot_config.cmdline.clone();
+    let mut boot_cmdline = match boot_config {
+        Some(boot_config) => boot_config.cmdline.clone(),
+        None => LoaderKernelCmdline::new(crate::arch::CMDLINE_MAX_SIZE)?,
+    };
 
     let cpu_template = vm_resources
         .machine_config
         .cpu_template
         .get_cpu_template()?;
 
-    let kvm = Kvm::new(cpu_template.kvm_capabilities.clone())?;
+    #[allow(unused_mut)]
+    let mut kvm = Kvm::new(cpu_template.kvm_capabilities.clone())?;
+    #[cfg(target_arch = "aarch64")]
+    set_ipa_size(
+        &mut kvm,
+        vm_resources.machine_config.ipa_size,
+        &guest_memory,
+    )?;
     // Set up Kvm Vm and register memory regions.
     // Build custom CPU config if a custom template is provided.
     let mut vm = Vm::new(&kvm)?;
@@ -200,12 +240,33 @@ pub fn build_microvm_for_boot(
 
     let vm = Arc::new(vm);
 
-    let entry_point = load_kernel(&boot_config.kernel_file, vm.guest_memory())?;
-    let initrd = InitrdConfig::from_config(boot_config, vm.guest_memory())?;
+    let (entry_point, initrd) = match (boot_config, &vm_resources.firmware) {
+        (Some(boot_config), _) => (
+            load_kernel(&boot_config.kernel_file, vm.guest_memory())?,
+            InitrdConfig::from_config(boot_config, vm.guest_memory())?,
+        ),
+        #[cfg(target_arch = "x86_64")]
+        (None, Some(firmware)) => {
+            device_manager.attach_firmware(&vm, firmware)?;
+            (crate::arch::firmware_entry_point(), None)
+        }
+        _ => return Err(StartMicrovmError::MissingKernelConfig),
+    };
 
     if vm_resources.pci_enabled {
         device_manager.enable_pci(&vm)?;
+        for config in &vm_resources.interrupt_affinity {
+            config.check_vcpus(vm_resources.machine_config.vcpu_count)?;
+            device_manager
+                .pci_devices
+                .interrupt_affinity
+                .insert(config.device_id.clone(), config.queue_vcpus.clone());
+        }
     } else {
+        // Only the MSI-X vectors of the PCI devices are routed by the VMM.
+        if !vm_resources.interrupt_affinity.is_empty() {
+            return Err(InterruptAffinityConfigError::PciDisabled.into());
+        }
         boot_cmdline.insert("pci", "off")?;
     }
 
@@ -226,6 +287,10 @@ pub fn build_microvm_for_boot(
         )?;
     }
 
+    if let Some(io_cpu_budget) = &vm_resources.io_cpu_budget {
+        attach_io_cpu_budget(io_cpu_budget, vm_resources, event_manager)?;
+    }
+
     attach_block_devices(
         &mut device_manager,
         &vm,
@@ -245,6 +310,7 @@ pub fn build_microvm_for_boot(
         &vm,
         &mut boot_cmdline,
         vm_resources.rdma.iter(),
+        vm_resources.machine_config.vcpu_count,
         event_manager,
     )?;
     attach_pmem_devices(
@@ -254,6 +320,27 @@ pub fn build_microvm_for_boot(
         vm_resources.pmem.devices.iter(),
         event_manager,
     )?;
+    attach_p9_devices(
+        &mut device_manager,
+        &vm,
+        &mut boot_cmdline,
+        vm_resources.p9.devices.iter(),
+        event_manager,
+    )?;
+    attach_gpio_devices(
+        &mut device_manager,
+        &vm,
+        &mut boot_cmdline,
+        vm_resources.gpio.devices.iter(),
+        event_manager,
+    )?;
+    attach_i2c_devices(
+        &mut device_manager,
+        &vm,
+        &mut boot_cmdline,
+        vm_resources.i2c.devices.iter(),
+        event_manager,
+    )?;
 
     if let Some(unix_vsock) = vm_resources.vsock.get() {
         attach_unixsock_vsock_device(
@@ -295,8 +382,17 @@ pub fn build_microvm_for_boot(
         vm_resources.serial_out_path.as_ref(),
     )?;
 
+    if let Some(fw_cfg) = &vm_resources.fw_cfg {
+        let launch_time_us = get_time_us(ClockType::Real);
+        device_manager.attach_fw_cfg_device(&vm, fw_cfg.files(instance_info, launch_time_us))?;
+    }
+
     device_manager.attach_vmgenid_device(&vm)?;
     device_manager.attach_vmclock_device(&vm)?;
+    #[cfg(target_arch = "x86_64")]
+    if vm_resources.machine_config.power_button {
+        device_manager.attach_power_button_device(&vm)?;
+    }
 
     #[cfg(target_arch = "aarch64")]
     if vcpus[0].kvm_vcpu.supports_pvtime() {
@@ -317,14 +413,74 @@ pub fn build_microvm_for_boot(
         boot_cmdline,
     )?;
 
+    #[cfg(target_arch = "x86_64")]
+    if let Some(smbios) = &vm_resources.smbios {
+        crate::arch::x86_64::smbios::setup_smbios(vm.guest_memory(), smbios)
+            .map_err(ConfigurationError::from)?;
+    }
+
+    // Guest memory is only handed over to the fault handling thread once it holds everything
+    // needed for boot, so that loading the guest doesn't go through it.
+    let cold_memory = match &vm_resources.cold_memory {
+        Some(config) => Some(ColdMemory::new(
+            config,
+            vm.guest_memory(),
+            vm_resources.machine_config.huge_pages.page_size(),
+            seccomp_filters
+                .get("vmm")
+                .ok_or_else(|| StartMicrovmError::MissingSeccompFilters("vmm".to_string()))?
+                .clone(),
+        )?),
+        None => None,
+    };
+
+    // The guest agent reaches the VMM through the vsock device.
+    let snapshot_agent = match &vm_resources.snapshot_agent {
+        Some(config) => {
+            let vsock = vm_resources
+                .vsock
+                .get()
+                .ok_or(SnapshotAgentError::MissingVsock)?;
+            let uds_path = vsock
+                .lock()
+                .expect("Poisoned lock")
+                .backend()
+                .host_sock_path()
+                .to_owned();
+            Some(SnapshotAgent::new(
+                config.clone(),
+                VmInfo::from(vm_resources),
+                &uds_path,
+            )?)
+        }
+        None => None,
+    };
+
     let vmm = Vmm {
         instance_info: instance_info.clone(),
         shutdown_exit_code: None,
         kvm,
         vm,
         uffd: None,
+        cold_memory,
+        memory_writeback: vm_resources
+            .memory_backing
+            .as_ref()
+            .map(MemoryWriteback::new),
+        snapshot_agent,
+        rdma_launcher: start_rdma_backend_launcher(
+            &vm_resources.rdma,
+            &device_manager,
+            seccomp_filters,
+        )?,
         vcpus_handles: Vec::new(),
         vcpus_exit_evt,
+        #[cfg(target_arch = "x86_64")]
+        shutdown_timer: TimerFd::new(),
+        #[cfg(target_arch = "x86_64")]
+        shutdown_start_us: None,
+        #[cfg(target_arch = "x86_64")]
+        shutdown_outcome: None,
         device_manager,
     };
     let vmm = Arc::new(Mutex::new(vmm));
@@ -450,8 +606,16 @@ pub fn build_microvm_from_snapshot(
     // Build Vmm.
     debug!("event_start: build microvm from snapshot");
 
-    let kvm = Kvm::new(microvm_state.kvm_state.kvm_cap_modifiers.clone())
+    #[allow(unused_mut)]
+    let mut kvm = Kvm::new(microvm_state.kvm_state.kvm_cap_modifiers.clone())
         .map_err(StartMicrovmError::Kvm)?;
+    #[cfg(target_arch = "aarch64")]
+    set_ipa_size(
+        &mut kvm,
+        vm_resources.machine_config.ipa_size,
+        &guest_memory,
+    )
+    .map_err(StartMicrovmError::Kvm)?;
     // Set up Kvm Vm and register memory regions.
     // Build custom CPU config if a custom template is provided.
     let mut vm = Vm::new(&kvm).map_err(StartMicrovmError::Vm)?;
@@ -523,8 +687,22 @@ pub fn build_microvm_from_snapshot(
         kvm,
         vm,
         uffd,
+        cold_memory: None,
+        memory_writeback: None,
+        snapshot_agent: None,
+        rdma_launcher: start_rdma_backend_launcher(
+            &vm_resources.rdma,
+            &device_manager,
+            seccomp_filters,
+        )?,
         vcpus_handles: Vec::new(),
         vcpus_exit_evt,
+        #[cfg(target_arch = "x86_64")]
+        shutdown_timer: TimerFd::new(),
+        #[cfg(target_arch = "x86_64")]
+        shutdown_start_us: None,
+        #[cfg(target_arch = "x86_64")]
+        shutdown_outcome: None,
         device_manager,
     };
 
@@ -612,6 +790,27 @@ fn attach_entropy_device(
     device_manager.attach_virtio_device(vm, id, entropy_device.clone(), cmdline, false)
 }
 
+/// Sets the IPA size of the VM, checking that it covers both the guest memory and the 64-bit MMIO
+/// gap.
+#[cfg(target_arch = "aarch64")]
+fn set_ipa_size(
+    kvm: &mut Kvm,
+    ipa_size: Option<u8>,
+    guest_memory: &[GuestRegionMmap],
+) -> Result<(), KvmError> {
+    use crate::arch::aarch64::layout::FIRST_ADDR_PAST_64BITS_MMIO;
+    use crate::vstate::memory::{Address, GuestMemoryRegion};
+
+    let last_mem_addr = guest_memory
+        .iter()
+        .map(|region| region.last_addr().raw_value())
+        .chain(std::iter::once(FIRST_ADDR_PAST_64BITS_MMIO - 1))
+        .max()
+        .unwrap();
+    kvm.set_ipa_size(ipa_size, last_mem_addr)
+        .map_err(KvmError::ArchError)
+}
+
 fn allocate_virtio_mem_address(
     vm: &Vm,
     total_size_mib: usize,
@@ -653,6 +852,35 @@ fn attach_virtio_mem_device(
     Ok(())
 }
 
+/// Charges the I/O processing of the block and net devices to a shared host CPU budget.
+fn attach_io_cpu_budget(
+    config: &IoCpuBudgetConfig,
+    vm_resources: &VmResources,
+    event_manager: &mut EventManager,
+) -> Result<(), StartMicrovmError> {
+    // A validated configuration never yields an empty budget.
+    let Some(budget) = CpuBudget::new(config.budget_us(), config.period_ms) else {
+        return Ok(());
+    };
+    let budget = Arc::new(Mutex::new(budget));
+    for block in vm_resources.block.devices.iter() {
+        block
+            .lock()
+            .expect("Poisoned lock")
+            .set_cpu_budget(&budget)
+            .map_err(StartMicrovmError::IoCpuBudget)?;
+    }
+    for net_device in vm_resources.net_builder.iter() {
+        net_device
+            .lock()
+            .expect("Poisoned lock")
+            .set_cpu_budget(&budget)
+            .map_err(StartMicrovmError::IoCpuBudget)?;
+    }
+    event_manager.add_subscriber(budget);
+    Ok(())
+}
+
 fn attach_block_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Block>>> + Debug>(
     device_manager: &mut DeviceManager,
     vm: &Arc<Vm>,
@@ -675,9 +903,14 @@ fn attach_block_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Block>>> + Debug>(
             }
             (locked.id().to_string(), locked.is_vhost_user())
         };
-        // The device mutex mustn't be locked here otherwise it will deadlock.
-        event_manager.add_subscriber(block.clone());
-        device_manager.attach_virtio_device(vm, id, block.clone(), cmdline, is_vhost_user)?;
+        device_manager.attach_hotpluggable_device(
+            vm,
+            id,
+            block.clone(),
+            cmdline,
+            event_manager,
+            is_vhost_user,
+        )?;
     }
     Ok(())
 }
@@ -691,24 +924,92 @@ fn attach_net_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Net>>> + Debug>(
 ) -> Result<(), StartMicrovmError> {
     for net_device in net_devices {
         let id = net_device.lock().expect("Poisoned lock").id().to_string();
-        event_manager.add_subscriber(net_device.clone());
-        // The device mutex mustn't be locked here otherwise it will deadlock.
-        device_manager.attach_virtio_device(vm, id, net_device.clone(), cmdline, false)?;
+        device_manager.attach_hotpluggable_device(
+            vm,
+            id,
+            net_device.clone(),
+            cmdline,
+            event_manager,
+            false,
+        )?;
     }
     Ok(())
 }
 
+// Spawns the launcher of the threads of the backends of the rdma devices, which must happen
+// before the VMM thread installs its seccomp filter, and hands it to the devices, whose backends
+// install the filter of the rdma threads. The launcher is only spawned when the microVM has rdma
+// devices or devices can be added to it, and is returned for the devices added later. The filters
+// only need to have the rdma categories when the microVM has rdma devices.
+#[cfg_attr(target_arch = "aarch64", allow(unused_variables))]
+fn start_rdma_backend_launcher(
+    rdma: &RdmaDeviceBuilder,
+    device_manager: &DeviceManager,
+    seccomp_filters: &BpfThreadMap,
+) -> Result<Option<Arc<BackendLauncher>>, StartMicrovmError> {
+    #[cfg(target_arch = "x86_64")]
+    let hotplug = device_manager.supports_hotplug();
+    #[cfg(target_arch = "aarch64")]
+    let hotplug = false;
+    let has_devices = rdma.iter().next().is_some();
+    if !has_devices && !hotplug {
+        return Ok(None);
+    }
+    let get_filter = |category: &str| {
+        seccomp_filters
+            .get(category)
+            .ok_or_else(|| StartMicrovmError::MissingSeccompFilters(category.to_string()))
+    };
+    let filters = get_filter("rdma_launcher")
+        .and_then(|launcher_filter| get_filter("rdma").map(|filter| (launcher_filter, filter)));
+    let (launcher_filter, filter) = match filters {
+        Ok(filters) => filters,
+        Err(_) if !has_devices => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let launcher = Arc::new(
+        BackendLauncher::new(launcher_filter.clone(), filter.clone())
+            .map_err(StartMicrovmError::RdmaBackendThreads)?,
+    );
+    for device in rdma.iter() {
+        device
+            .lock()
+            .expect("Poisoned lock")
+            .set_backend_launcher(launcher.clone())
+            .map_err(StartMicrovmError::RdmaBackendThreads)?;
+    }
+    Ok(Some(launcher))
+}
+
 fn attach_rdma_devices<'a, I: Iterator<Item = &'a Arc<Mutex<VirtioRdma>>> + Debug>(
     device_manager: &mut DeviceManager,
     vm: &Arc<Vm>,
     cmdline: &mut LoaderKernelCmdline,
     rdma_devices: I,
+    vcpu_count: u8,
     event_manager: &mut EventManager,
 ) -> Result<(), StartMicrovmError> {
     for rdma_device in rdma_devices {
-        let id = rdma_device.lock().expect("Poisoned lock").id().to_string();
-        event_manager.add_subscriber(rdma_device.clone());
-        device_manager.attach_virtio_device(vm, id, rdma_device.clone(), cmdline, false)?;
+        let id = {
+            let mut locked_dev = rdma_device.lock().expect("Poisoned lock");
+            locked_dev
+                .set_vcpu_count(vcpu_count)
+                .map_err(StartMicrovmError::CreateRdmaDevice)?;
+            let listener = vm
+                .memory_removals()
+                .subscribe()
+                .map_err(StartMicrovmError::MemoryRemovals)?;
+            locked_dev.set_memory_removal_listener(listener);
+            locked_dev.id().to_string()
+        };
+        device_manager.attach_hotpluggable_device(
+            vm,
+            id,
+            rdma_device.clone(),
+            cmdline,
+            event_manager,
+            false,
+        )?;
     }
     Ok(())
 }
@@ -741,6 +1042,54 @@ fn attach_pmem_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Pmem>>> + Debug>(
     Ok(())
 }
 
+fn attach_p9_devices<'a, I: Iterator<Item = &'a Arc<Mutex<P9>>> + Debug>(
+    device_manager: &mut DeviceManager,
+    vm: &Arc<Vm>,
+    cmdline: &mut LoaderKernelCmdline,
+    p9_devices: I,
+    event_manager: &mut EventManager,
+) -> Result<(), AttachDeviceError> {
+    for device in p9_devices {
+        let id = device.lock().expect("Poisoned lock").config.id.clone();
+
+        event_manager.add_subscriber(device.clone());
+        device_manager.attach_virtio_device(vm, id, device.clone(), cmdline, false)?;
+    }
+    Ok(())
+}
+
+fn attach_gpio_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Gpio>>> + Debug>(
+    device_manager: &mut DeviceManager,
+    vm: &Arc<Vm>,
+    cmdline: &mut LoaderKernelCmdline,
+    gpio_devices: I,
+    event_manager: &mut EventManager,
+) -> Result<(), AttachDeviceError> {
+    for device in gpio_devices {
+        let id = device.lock().expect("Poisoned lock").config.id.clone();
+
+        event_manager.add_subscriber(device.clone());
+        device_manager.attach_virtio_device(vm, id, device.clone(), cmdline, false)?;
+    }
+    Ok(())
+}
+
+fn attach_i2c_devices<'a, I: Iterator<Item = &'a Arc<Mutex<I2c>>> + Debug>(
+    device_manager: &mut DeviceManager,
+    vm: &Arc<Vm>,
+    cmdline: &mut LoaderKernelCmdline,
+    i2c_devices: I,
+    event_manager: &mut EventManager,
+) -> Result<(), AttachDeviceError> {
+    for device in i2c_devices {
+        let id = device.lock().expect("Poisoned lock").config.id.clone();
+
+        event_manager.add_subscriber(device.clone());
+        device_manager.attach_virtio_device(vm, id, device.clone(), cmdline, false)?;
+    }
+    Ok(())
+}
+
 fn attach_unixsock_vsock_device(
     device_manager: &mut DeviceManager,
     vm: &Arc<Vm>,
@@ -749,9 +1098,14 @@ fn attach_unixsock_vsock_device(
     event_manager: &mut EventManager,
 ) -> Result<(), AttachDeviceError> {
     let id = String::from(unix_vsock.lock().expect("Poisoned lock").id());
-    event_manager.add_subscriber(unix_vsock.clone());
-    // The device mutex mustn't be locked here otherwise it will deadlock.
-    device_manager.attach_virtio_device(vm, id, unix_vsock.clone(), cmdline, false)
+    device_manager.attach_hotpluggable_device(
+        vm,
+        id,
+        unix_vsock.clone(),
+        cmdline,
+        event_manager,
+        false,
+    )
 }
 
 fn attach_balloon_device(
@@ -761,7 +1115,11 @@ fn attach_balloon_device(
     balloon: &Arc<Mutex<Balloon>>,
     event_manager: &mut EventManager,
 ) -> Result<(), AttachDeviceError> {
-    let id = String::from(balloon.lock().expect("Poisoned lock").id());
+    let id = {
+        let mut locked_dev = balloon.lock().expect("Poisoned lock");
+        locked_dev.set_memory_removals(vm.memory_removals().clone());
+        String::from(locked_dev.id())
+    };
     event_manager.add_subscriber(balloon.clone());
     // The device mutex mustn't be locked here otherwise it will deadlock.
     device_manager.attach_virtio_device(vm, id, balloon.clone(), cmdline, false)
@@ -771,6 +1129,7 @@ fn attach_balloon_device(
 pub(crate) mod tests {
 
     use linux_loader::cmdline::Cmdline;
+    use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
@@ -781,12 +1140,16 @@ pub(crate) mod tests {
     use crate::devices::virtio::vsock::VSOCK_DEV_ID;
     use crate::mmds::data_store::{Mmds, MmdsVersion};
     use crate::mmds::ns::MmdsNetworkStack;
+    use crate::rate_limiter::RateLimiter;
     use crate::utils::mib_to_bytes;
     use crate::vmm_config::balloon::{BALLOON_DEV_ID, BalloonBuilder, BalloonDeviceConfig};
     use crate::vmm_config::boot_source::DEFAULT_KERNEL_CMDLINE;
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
     use crate::vmm_config::entropy::{EntropyDeviceBuilder, EntropyDeviceConfig};
+    use crate::vmm_config::gpio::{GpioBuilder, GpioConfig, GpioMockConfig};
+    use crate::vmm_config::i2c::{I2cBuilder, I2cConfig, I2cMockDeviceConfig};
     use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
+    use crate::vmm_config::p9::{P9Builder, P9Config};
     use crate::vmm_config::pmem::{PmemBuilder, PmemConfig};
     use crate::vmm_config::vsock::tests::default_config;
     use crate::vmm_config::vsock::{VsockBuilder, VsockDeviceConfig};
@@ -856,8 +1219,20 @@ pub(crate) mod tests {
             kvm,
             vm: Arc::new(vm),
             uffd: None,
+            cold_memory: None,
+            memory_writeback: None,
+            snapshot_agent: None,
+            rdma_launcher: Some(Arc::new(
+                BackendLauncher::new(Arc::default(), Arc::default()).unwrap(),
+            )),
             vcpus_handles: Vec::new(),
             vcpus_exit_evt,
+            #[cfg(target_arch = "x86_64")]
+            shutdown_timer: TimerFd::new(),
+            #[cfg(target_arch = "x86_64")]
+            shutdown_start_us: None,
+            #[cfg(target_arch = "x86_64")]
+            shutdown_outcome: None,
             device_manager: default_device_manager(),
         }
     }
@@ -889,8 +1264,14 @@ pub(crate) mod tests {
                         .unwrap()
                         .to_string(),
                 ),
+                backing_fd: None,
                 rate_limiter: None,
                 file_engine_type: None,
+                verity: None,
+                shared_page_cache: None,
+                strict_ordering: None,
+                host_cache: None,
+                nbd_uri: None,
 
                 socket: None,
             };
@@ -945,6 +1326,7 @@ pub(crate) mod tests {
         net.lock().unwrap().configure_mmds_network_stack(
             MmdsNetworkStack::default_ipv4_addr(),
             Arc::new(Mutex::new(mmds)),
+            RateLimiter::default(),
         );
 
         attach_net_devices(
@@ -1036,6 +1418,32 @@ pub(crate) mod tests {
         files
     }
 
+    pub(crate) fn insert_p9_devices(
+        vmm: &mut Vmm,
+        cmdline: &mut Cmdline,
+        event_manager: &mut EventManager,
+        configs: Vec<P9Config>,
+    ) -> Vec<TempDir> {
+        let mut builder = P9Builder::default();
+        let mut dirs = Vec::new();
+        for mut config in configs {
+            let tmp_dir = TempDir::new().unwrap();
+            config.path_on_host = tmp_dir.as_path().to_str().unwrap().to_string();
+            dirs.push(tmp_dir);
+            builder.build(config).unwrap();
+        }
+
+        attach_p9_devices(
+            &mut vmm.device_manager,
+            &vmm.vm,
+            cmdline,
+            builder.devices.iter(),
+            event_manager,
+        )
+        .unwrap();
+        dirs
+    }
+
     #[cfg(target_arch = "x86_64")]
     pub(crate) fn insert_vmgenid_device(vmm: &mut Vmm) {
         vmm.device_manager.attach_vmgenid_device(&vmm.vm).unwrap();
@@ -1083,6 +1491,8 @@ pub(crate) mod tests {
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
+            flow_sampling: None,
+            tap_fd: None,
         };
 
         let mut cmdline = default_kernel_cmdline();
@@ -1307,6 +1717,95 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_attach_p9_devices() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+
+        let id = String::from("share");
+        let configs = vec![P9Config {
+            id: id.clone(),
+            path_on_host: "".into(),
+            mount_tag: "share".into(),
+            read_only: true,
+        }];
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        _ = insert_p9_devices(&mut vmm, &mut cmdline, &mut event_manager, configs);
+        assert!(
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::P9, id.as_str())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_attach_gpio_devices() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let tmp_dir = TempDir::new().unwrap();
+
+        let id = String::from("chip0");
+        let mut builder = GpioBuilder::default();
+        builder
+            .build(GpioConfig {
+                id: id.clone(),
+                host_chip: None,
+                mock: Some(GpioMockConfig {
+                    socket_path: tmp_dir.as_path().join("gpio.sock").to_str().unwrap().into(),
+                    num_lines: 8,
+                    line_names: vec![],
+                }),
+            })
+            .unwrap();
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        attach_gpio_devices(
+            &mut vmm.device_manager,
+            &vmm.vm,
+            &mut cmdline,
+            builder.devices.iter(),
+            &mut event_manager,
+        )
+        .unwrap();
+        assert!(
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::Gpio, id.as_str())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_attach_i2c_devices() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+
+        let id = String::from("bus0");
+        let mut builder = I2cBuilder::default();
+        builder
+            .build(I2cConfig {
+                id: id.clone(),
+                host_adapter: None,
+                mock_devices: vec![I2cMockDeviceConfig {
+                    address: 0x50,
+                    registers: vec![],
+                }],
+            })
+            .unwrap();
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        attach_i2c_devices(
+            &mut vmm.device_manager,
+            &vmm.vm,
+            &mut cmdline,
+            builder.devices.iter(),
+            &mut event_manager,
+        )
+        .unwrap();
+        assert!(
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::I2c, id.as_str())
+                .is_some()
+        );
+    }
+
     #[test]
     fn test_attach_boot_timer_device() {
         let mut vmm = default_vmm();
@@ -1319,6 +1818,264 @@ pub(crate) mod tests {
         assert!(vmm.device_manager.mmio_devices.boot_timer.is_some());
     }
 
+    #[test]
+    fn test_attach_fw_cfg_device() {
+        use crate::vmm_config::fw_cfg::FwCfgConfig;
+
+        let mut vmm = default_vmm();
+        let files = FwCfgConfig::default().files(&InstanceInfo::default(), 0);
+
+        vmm.device_manager
+            .attach_fw_cfg_device(&vmm.vm, files.clone())
+            .unwrap();
+        let fw_cfg = vmm.device_manager.mmio_devices.fw_cfg.as_ref().unwrap();
+        assert_eq!(fw_cfg.inner.lock().unwrap().files(), files);
+        assert!(
+            vmm.vm
+                .common
+                .mmio_bus
+                .resolve(fw_cfg.resources.addr)
+                .is_some()
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_graceful_shutdown() {
+        use crate::devices::acpi::power_button::PowerButton;
+        use crate::vmm_config::instance_info::VmState;
+        use crate::vmm_config::shutdown::{GracefulShutdownConfig, ShutdownPath};
+
+        let mut vmm = default_vmm();
+        let config = GracefulShutdownConfig::default();
+
+        // The power button is opt-in.
+        assert!(matches!(
+            vmm.graceful_shutdown(&config),
+            Err(VmmError::NoPowerButton)
+        ));
+
+        vmm.device_manager.acpi_devices.power_button = Some(PowerButton::from_gsi(5));
+        vmm.instance_info.state = VmState::Paused;
+        assert!(matches!(
+            vmm.graceful_shutdown(&config),
+            Err(VmmError::ShutdownWhilePaused)
+        ));
+
+        vmm.instance_info.state = VmState::Running;
+        vmm.graceful_shutdown(&config).unwrap();
+        assert!(vmm.shutdown_start_us.is_some());
+        assert_eq!(
+            vmm.device_manager
+                .acpi_devices
+                .power_button
+                .as_ref()
+                .unwrap()
+                .interrupt_evt
+                .read()
+                .unwrap(),
+            1
+        );
+        // Only one shutdown can be pending at a time.
+        assert!(matches!(
+            vmm.graceful_shutdown(&config),
+            Err(VmmError::ShutdownInProgress)
+        ));
+
+        assert_eq!(vmm.graceful_shutdown_outcome(), None);
+        vmm.end_shutdown(ShutdownPath::Graceful).unwrap();
+        assert_eq!(
+            vmm.graceful_shutdown_outcome().unwrap().path,
+            ShutdownPath::Graceful
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_remove_device() {
+        use crate::device_manager::hotplug::HotplugError;
+        use crate::vmm_config::device_removal::{DeviceRemovalConfig, RemovableDeviceType};
+
+        let drive = |id: &str, timeout_ms| DeviceRemovalConfig {
+            device_type: RemovableDeviceType::Drive,
+            id: id.to_string(),
+            timeout_ms,
+        };
+
+        // Devices can only be removed from microVMs using PCI.
+        let mut vmm = default_vmm();
+        assert!(matches!(
+            vmm.remove_device(&drive("root", 1000)),
+            Err(VmmError::DeviceRemoval(HotplugError::Unsupported))
+        ));
+
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        vmm.device_manager.enable_pci(&vmm.vm).unwrap();
+        let mut cmdline = default_kernel_cmdline();
+        let block_configs = ["root", "scratch", "logs"]
+            .into_iter()
+            .map(|id| CustomBlockConfig::new(id.to_string(), false, None, true, CacheType::Unsafe))
+            .collect();
+        let _block_files =
+            insert_block_devices(&mut vmm, &mut cmdline, &mut event_manager, block_configs);
+        let hotplug = vmm.device_manager.pci_devices.hotplug.clone().unwrap();
+        let is_attached = |vmm: &Vmm, id: &str| {
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::Block, id)
+                .is_some()
+        };
+
+        assert!(matches!(
+            vmm.remove_device(&drive("unknown", 1000)),
+            Err(VmmError::DeviceRemoval(HotplugError::Detach(_)))
+        ));
+
+        // The guest releases the device in time.
+        vmm.remove_device(&drive("root", 1000)).unwrap();
+        assert!(matches!(
+            vmm.remove_device(&drive("root", 1000)),
+            Err(VmmError::DeviceRemoval(HotplugError::RemovalInProgress(_)))
+        ));
+        assert_eq!(hotplug.lock().unwrap().interrupt_evt.read().unwrap(), 1);
+        assert!(vmm.device_manager.hotplug.removal_timer.is_armed());
+        let slot = vmm.device_manager.hotplug.pending_removals[0].slot;
+        let address = hotplug.lock().unwrap().address;
+        vmm.vm
+            .common
+            .mmio_bus
+            .write(address + 0x8, &(1u32 << slot).to_le_bytes())
+            .unwrap();
+        vmm.device_manager.process_ejected_devices(&vmm.vm);
+        assert!(vmm.device_manager.hotplug.pending_removals.is_empty());
+        assert!(!vmm.device_manager.hotplug.removal_timer.is_armed());
+        assert!(!is_attached(&vmm, "root"));
+
+        // The guest does not release the device in time.
+        vmm.remove_device(&drive("scratch", 1)).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        vmm.device_manager.process_expired_removals(&vmm.vm);
+        assert!(vmm.device_manager.hotplug.pending_removals.is_empty());
+        assert!(!is_attached(&vmm, "scratch"));
+
+        // Without a timeout, the device is removed right away.
+        vmm.remove_device(&drive("logs", 0)).unwrap();
+        assert!(vmm.device_manager.hotplug.pending_removals.is_empty());
+        assert!(!is_attached(&vmm, "logs"));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_insert_rdma_device() {
+        use crate::device_manager::hotplug::HotplugError;
+        use crate::vmm_config::device_removal::{DeviceRemovalConfig, RemovableDeviceType};
+
+        let rdma = |id: &str| Arc::new(Mutex::new(VirtioRdma::new(id.to_string()).unwrap()));
+
+        // Devices can only be added to microVMs using PCI.
+        let mut vmm = default_vmm();
+        assert!(matches!(
+            vmm.insert_rdma_device(rdma("rdma0")),
+            Err(VmmError::DeviceInsertion(HotplugError::Unsupported))
+        ));
+
+        let mut vmm = default_vmm();
+        vmm.device_manager.enable_pci(&vmm.vm).unwrap();
+        let hotplug = vmm.device_manager.pci_devices.hotplug.clone().unwrap();
+
+        // The backends of the devices need their seccomp filter.
+        let launcher = vmm.rdma_launcher.take();
+        assert!(matches!(
+            vmm.insert_rdma_device(rdma("rdma0")),
+            Err(VmmError::MissingRdmaSeccompFilters)
+        ));
+        vmm.rdma_launcher = launcher;
+
+        // The guest is asked to check the slot of the device, which is handed to the event
+        // manager once.
+        vmm.insert_rdma_device(rdma("rdma0")).unwrap();
+        let slot = vmm
+            .device_manager
+            .pci_devices
+            .device_slot(VirtioDeviceType::Rdma, "rdma0")
+            .unwrap();
+        assert_eq!(hotplug.lock().unwrap().interrupt_evt.read().unwrap(), 1);
+        let address = hotplug.lock().unwrap().address;
+        let mut pciu = [0u8; 4];
+        vmm.vm.common.mmio_bus.read(address, &mut pciu).unwrap();
+        assert_eq!(u32::from_le_bytes(pciu), 1 << slot);
+        assert_eq!(vmm.take_pending_subscribers().len(), 1);
+        assert!(vmm.take_pending_subscribers().is_empty());
+
+        assert!(matches!(
+            vmm.insert_rdma_device(rdma("rdma0")),
+            Err(VmmError::DeviceInsertion(HotplugError::DeviceExists(id))) if id == "rdma0"
+        ));
+
+        // The added device can be removed again.
+        vmm.remove_device(&DeviceRemovalConfig {
+            device_type: RemovableDeviceType::RdmaDevice,
+            id: "rdma0".to_string(),
+            timeout_ms: 0,
+        })
+        .unwrap();
+        assert!(
+            vmm.device_manager
+                .get_virtio_device(VirtioDeviceType::Rdma, "rdma0")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_start_rdma_backend_launcher() {
+        use crate::seccomp::get_empty_filters;
+        use crate::vmm_config::rdma::RdmaDeviceConfig;
+
+        let filters = get_empty_filters();
+        let mut no_filters = get_empty_filters();
+        no_filters.remove("rdma_launcher");
+        let mut rdma = RdmaDeviceBuilder::new();
+
+        // Without rdma devices nor hotplug, the launcher isn't needed.
+        #[cfg_attr(target_arch = "aarch64", allow(unused_mut))]
+        let mut device_manager = default_device_manager();
+        assert!(
+            start_rdma_backend_launcher(&rdma, &device_manager, &filters)
+                .unwrap()
+                .is_none()
+        );
+
+        // Devices added later need the launcher, unless the filters don't allow it.
+        #[cfg(target_arch = "x86_64")]
+        {
+            let (_, vm) = setup_vm_with_memory(mib_to_bytes(128));
+            device_manager.enable_pci(&Arc::new(vm)).unwrap();
+            assert!(
+                start_rdma_backend_launcher(&rdma, &device_manager, &filters)
+                    .unwrap()
+                    .is_some()
+            );
+            assert!(
+                start_rdma_backend_launcher(&rdma, &device_manager, &no_filters)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+
+        // The backends of the rdma devices need both filters.
+        rdma.insert(RdmaDeviceConfig {
+            id: "rdma0".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(matches!(
+            start_rdma_backend_launcher(&rdma, &device_manager, &no_filters),
+            Err(StartMicrovmError::MissingSeccompFilters(category)) if category == "rdma_launcher"
+        ));
+        let launcher = start_rdma_backend_launcher(&rdma, &device_manager, &filters).unwrap();
+        assert!(launcher.is_some());
+    }
+
     #[test]
     fn test_attach_balloon_device() {
         let mut event_manager = EventManager::new().expect("Unable to create EventManager");
@@ -1330,6 +2087,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
 
         let mut cmdline = default_kernel_cmdline();
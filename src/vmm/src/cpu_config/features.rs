@@ -0,0 +1,182 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(target_arch = "x86_64")]
+use kvm_bindings::CpuId;
+use serde::{Deserialize, Serialize};
+
+use crate::vstate::kvm::Kvm;
+
+/// Register holding CPU feature bits, one bit per feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuFeatureRegister {
+    /// Name of the register. On x86_64, CPUID registers are named `leaf:subleaf:register`, e.g.
+    /// `0x7:0x0:ebx`.
+    pub register: String,
+    /// Feature bits of the register.
+    pub bits: u64,
+}
+
+/// CPUID registers holding feature flags, as (leaf, subleaf, register).
+#[cfg(target_arch = "x86_64")]
+const CPUID_FEATURE_REGISTERS: [(u32, u32, &str); 10] = [
+    (0x1, 0x0, "ecx"),
+    (0x1, 0x0, "edx"),
+    (0x7, 0x0, "ebx"),
+    (0x7, 0x0, "ecx"),
+    (0x7, 0x0, "edx"),
+    (0x7, 0x1, "eax"),
+    (0xd, 0x1, "eax"),
+    (0x8000_0001, 0x0, "ecx"),
+    (0x8000_0001, 0x0, "edx"),
+    (0x8000_0008, 0x0, "ebx"),
+];
+
+/// Returns the feature flags set in `cpuid`, leaving out the registers without any.
+#[cfg(target_arch = "x86_64")]
+pub fn cpuid_features(cpuid: &CpuId) -> Vec<CpuFeatureRegister> {
+    CPUID_FEATURE_REGISTERS
+        .iter()
+        .filter_map(|&(leaf, subleaf, register)| {
+            let entry = cpuid
+                .as_slice()
+                .iter()
+                .find(|entry| entry.function == leaf && entry.index == subleaf)?;
+            let bits = match register {
+                "eax" => entry.eax,
+                "ebx" => entry.ebx,
+                "ecx" => entry.ecx,
+                _ => entry.edx,
+            };
+            (bits != 0).then(|| CpuFeatureRegister {
+                register: format!("{leaf:#x}:{subleaf:#x}:{register}"),
+                bits: u64::from(bits),
+            })
+        })
+        .collect()
+}
+
+/// Returns the CPU features KVM supports on this host. Only x86_64 CPUs are described by their
+/// features, aarch64 ones are described by their manufacturer.
+#[cfg_attr(target_arch = "aarch64", allow(unused_variables))]
+pub fn host_features(kvm: &Kvm) -> Vec<CpuFeatureRegister> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        cpuid_features(&kvm.supported_cpuid)
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        Vec::new()
+    }
+}
+
+/// Returns the features of `required` which are not in `supported`.
+pub fn missing_features(
+    required: &[CpuFeatureRegister],
+    supported: &[CpuFeatureRegister],
+) -> Vec<CpuFeatureRegister> {
+    filter_features(required, |register| !register_bits(supported, register))
+}
+
+/// Returns the features of `observed` which are also in `supported`.
+pub fn common_features(
+    observed: &[CpuFeatureRegister],
+    supported: &[CpuFeatureRegister],
+) -> Vec<CpuFeatureRegister> {
+    filter_features(observed, |register| register_bits(supported, register))
+}
+
+// Returns the bits of the register named `register`, or 0 if there is none.
+fn register_bits(features: &[CpuFeatureRegister], register: &str) -> u64 {
+    features
+        .iter()
+        .find(|feature| feature.register == register)
+        .map_or(0, |feature| feature.bits)
+}
+
+// Masks the bits of each register of `features`, leaving out the registers without any bit left.
+fn filter_features(
+    features: &[CpuFeatureRegister],
+    mask: impl Fn(&str) -> u64,
+) -> Vec<CpuFeatureRegister> {
+    features
+        .iter()
+        .filter_map(|feature| {
+            let bits = feature.bits & mask(&feature.register);
+            (bits != 0).then(|| CpuFeatureRegister {
+                register: feature.register.clone(),
+                bits,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(register: &str, bits: u64) -> CpuFeatureRegister {
+        CpuFeatureRegister {
+            register: register.to_string(),
+            bits,
+        }
+    }
+
+    #[test]
+    fn test_missing_features() {
+        let required = [
+            register("0x1:0x0:ecx", 0b1011),
+            register("0x7:0x0:ebx", 0b1),
+        ];
+        let supported = [register("0x1:0x0:ecx", 0b0011)];
+        assert_eq!(
+            missing_features(&required, &supported),
+            [
+                register("0x1:0x0:ecx", 0b1000),
+                register("0x7:0x0:ebx", 0b1)
+            ]
+        );
+        assert_eq!(
+            common_features(&required, &supported),
+            [register("0x1:0x0:ecx", 0b0011)]
+        );
+        assert!(missing_features(&supported, &required).is_empty());
+        assert!(missing_features(&[], &supported).is_empty());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_cpuid_features() {
+        use kvm_bindings::kvm_cpuid_entry2;
+
+        let cpuid = CpuId::from_entries(&[
+            kvm_cpuid_entry2 {
+                function: 0x1,
+                ecx: 0x8000_0001,
+                edx: 0x10,
+                ..Default::default()
+            },
+            kvm_cpuid_entry2 {
+                function: 0x7,
+                index: 0x1,
+                eax: 0x20,
+                ..Default::default()
+            },
+            // Not a feature leaf.
+            kvm_cpuid_entry2 {
+                function: 0x2,
+                eax: 0xff,
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            cpuid_features(&cpuid),
+            [
+                register("0x1:0x0:ecx", 0x8000_0001),
+                register("0x1:0x0:edx", 0x10),
+                register("0x7:0x1:eax", 0x20),
+            ]
+        );
+    }
+}
@@ -5,6 +5,8 @@
 pub mod templates;
 /// Module with ser/de utils for custom CPU templates
 pub mod templates_serde;
+/// Module with the CPU features the guest observed, which snapshots require from the host
+pub mod features;
 
 /// Module containing type implementations needed for x86 CPU configuration
 #[cfg(target_arch = "x86_64")]
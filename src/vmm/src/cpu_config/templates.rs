@@ -4,6 +4,7 @@
 #[cfg(target_arch = "x86_64")]
 mod common_types {
     pub use crate::cpu_config::x86_64::custom_cpu_template::CustomCpuTemplate;
+    pub use crate::cpu_config::x86_64::dump::config_to_template;
     pub use crate::cpu_config::x86_64::static_cpu_templates::StaticCpuTemplate;
     pub use crate::cpu_config::x86_64::{
         CpuConfiguration, CpuConfigurationError as GuestConfigError, test_utils,
@@ -13,6 +14,7 @@ mod common_types {
 #[cfg(target_arch = "aarch64")]
 mod common_types {
     pub use crate::cpu_config::aarch64::custom_cpu_template::CustomCpuTemplate;
+    pub use crate::cpu_config::aarch64::dump::config_to_template;
     pub use crate::cpu_config::aarch64::static_cpu_templates::StaticCpuTemplate;
     pub use crate::cpu_config::aarch64::{
         CpuConfiguration, CpuConfigurationError as GuestConfigError, test_utils,
@@ -99,17 +99,17 @@ impl super::AmdCpuid {
         &mut self,
         // The index of the current logical CPU in the range [0..cpu_count].
         cpu_index: u8,
-        // The total number of logical CPUs.
-        cpu_count: u8,
+        // The number of logical CPUs per socket.
+        cpus_per_socket: u8,
         // The number of logical CPUs per core.
         cpus_per_core: u8,
     ) -> Result<(), NormalizeCpuidError> {
         self.passthrough_cache_topology()?;
         self.update_structured_extended_entry()?;
         self.update_extended_feature_fn_entry()?;
-        self.update_amd_feature_entry(cpu_count)?;
-        self.update_extended_cache_topology_entry(cpu_count, cpus_per_core)?;
-        self.update_extended_apic_id_entry(cpu_index, cpus_per_core)?;
+        self.update_amd_feature_entry(cpus_per_socket)?;
+        self.update_extended_cache_topology_entry(cpus_per_socket, cpus_per_core)?;
+        self.update_extended_apic_id_entry(cpu_index, cpus_per_socket, cpus_per_core)?;
         self.update_brand_string_entry()?;
 
         Ok(())
@@ -210,12 +210,12 @@ impl super::AmdCpuid {
 
     /// Update AMD feature entry.
     #[allow(clippy::unwrap_used, clippy::unwrap_in_result)]
-    fn update_amd_feature_entry(&mut self, cpu_count: u8) -> Result<(), FeatureEntryError> {
+    fn update_amd_feature_entry(&mut self, cpus_per_socket: u8) -> Result<(), FeatureEntryError> {
         /// This value allows at most 64 logical threads within a package.
         const THREAD_ID_MAX_SIZE: u32 = 7;
 
         // We don't support more then 128 threads right now.
-        // It's safe to put them all on the same processor.
+        // It's safe to put all the threads of a socket on the same processor.
         let leaf_80000008 = self
             .get_mut(&CpuidKey::leaf(0x80000008))
             .ok_or(FeatureEntryError::MissingLeaf0x80000008)?;
@@ -234,7 +234,7 @@ impl super::AmdCpuid {
         // CPUID Fn8000_0008_ECX[7:0] (Field Name: NC)
         // Number of physical threads - 1. The number of threads in the processor is NT+1
         // (e.g., if NT = 0, then there is one thread). See “Legacy Method” on page 633.
-        let sub = cpu_count
+        let sub = cpus_per_socket
             .checked_sub(1)
             .ok_or(FeatureEntryError::NumberOfPhysicalThreadsOverflow)?;
         set_range(&mut leaf_80000008.result.ecx, 0..=7, u32::from(sub))
@@ -247,7 +247,7 @@ impl super::AmdCpuid {
     #[allow(clippy::unwrap_in_result, clippy::unwrap_used)]
     fn update_extended_cache_topology_entry(
         &mut self,
-        cpu_count: u8,
+        cpus_per_socket: u8,
         cpus_per_core: u8,
     ) -> Result<(), ExtendedCacheTopologyError> {
         for i in 0.. {
@@ -287,9 +287,9 @@ impl super::AmdCpuid {
                             .map_err(|err| ExtendedCacheTopologyError::NumSharingCache(i, err))?;
                     }
                     // L3 Cache
-                    // The L3 cache is shared among all the logical threads
+                    // The L3 cache is shared among all the logical threads of a socket
                     3 => {
-                        let sub = cpu_count
+                        let sub = cpus_per_socket
                             .checked_sub(1)
                             .ok_or(ExtendedCacheTopologyError::NumSharingCacheOverflow(i))?;
                         set_range(&mut subleaf.result.eax, 14..=25, u32::from(sub))
@@ -309,6 +309,7 @@ impl super::AmdCpuid {
     fn update_extended_apic_id_entry(
         &mut self,
         cpu_index: u8,
+        cpus_per_socket: u8,
         cpus_per_core: u8,
     ) -> Result<(), ExtendedApicIdError> {
         /// 1 node per processor.
@@ -324,6 +325,10 @@ impl super::AmdCpuid {
         //
         // SAFETY: We know `cpus_per_core != 0` therefore this is always safe.
         let core_id = u32::from(cpu_index.checked_div(cpus_per_core).unwrap());
+        // Each socket holds a single node.
+        //
+        // SAFETY: We know `cpus_per_socket != 0` therefore this is always safe.
+        let node_id = u32::from(cpu_index.checked_div(cpus_per_socket).unwrap());
 
         let leaf_8000001e = self
             .get_mut(&CpuidKey::leaf(0x8000001e))
@@ -370,8 +375,8 @@ impl super::AmdCpuid {
         // Specifies the ID of the node containing the current logical processor. NodeId
         // values are unique across the system.
         //
-        // Put all the cpus in the same node.
-        set_range(&mut leaf_8000001e.result.ecx, 0..=7, 0).unwrap();
+        // SAFETY: We know the value always fits within the range and thus is always safe.
+        set_range(&mut leaf_8000001e.result.ecx, 0..=7, node_id).unwrap();
 
         Ok(())
     }
@@ -64,12 +64,12 @@ impl super::IntelCpuid {
         &mut self,
         // The index of the current logical CPU in the range [0..cpu_count].
         _cpu_index: u8,
-        // The total number of logical CPUs.
-        cpu_count: u8,
+        // The number of logical CPUs per socket.
+        cpus_per_socket: u8,
         // The number of logical CPUs per core.
         cpus_per_core: u8,
     ) -> Result<(), NormalizeCpuidError> {
-        self.update_deterministic_cache_entry(cpu_count, cpus_per_core)?;
+        self.update_deterministic_cache_entry(cpus_per_socket, cpus_per_core)?;
         self.update_power_management_entry()?;
         self.update_extended_feature_flags_entry()?;
         self.update_performance_monitoring_entry()?;
@@ -83,7 +83,7 @@ impl super::IntelCpuid {
     #[allow(clippy::unwrap_in_result)]
     fn update_deterministic_cache_entry(
         &mut self,
-        cpu_count: u8,
+        cpus_per_socket: u8,
         cpus_per_core: u8,
     ) -> Result<(), DeterministicCacheError> {
         for i in 0.. {
@@ -121,10 +121,10 @@ impl super::IntelCpuid {
                             .map_err(DeterministicCacheError::MaxCpusPerCore)?;
                     }
                     // L3 Cache
-                    // The L3 cache is shared among all the logical threads
+                    // The L3 cache is shared among all the logical threads of a socket
                     3 => {
                         let sub = u32::from(
-                            cpu_count
+                            cpus_per_socket
                                 .checked_sub(1)
                                 .ok_or(DeterministicCacheError::MaxCpusPerCoreUnderflow)?,
                         );
@@ -136,7 +136,7 @@ impl super::IntelCpuid {
 
                 // We know `cpus_per_core !=0` therefore this is always safe.
                 #[allow(clippy::unwrap_used)]
-                let cores = cpu_count.checked_div(cpus_per_core).unwrap();
+                let cores = cpus_per_socket.checked_div(cpus_per_core).unwrap();
 
                 // CPUID.04H:EAX[31:26]
                 // Maximum number of addressable IDs for processor cores in the physical package.
@@ -147,7 +147,7 @@ impl super::IntelCpuid {
                 // - The returned value is constant for valid initial values in ECX. Valid ECX
                 //   values start from 0.
 
-                // Put all the cores in the same socket
+                // Put all the cores of a socket in the same package
                 let sub = u32::from(cores)
                     .checked_sub(1)
                     .ok_or(DeterministicCacheError::MaxCorePerPackageUnderflow)?;
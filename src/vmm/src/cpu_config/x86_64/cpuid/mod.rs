@@ -49,7 +49,9 @@ pub use intel::IntelCpuid;
 /// CPUID normalize implementation.
 mod normalize;
 
-pub use normalize::{FeatureInformationError, GetMaxCpusPerPackageError, NormalizeCpuidError};
+pub use normalize::{
+    FeatureInformationError, GetMaxCpusPerPackageError, NormalizeCpuidError, TscEntriesError,
+};
 
 /// Intel brand string.
 pub const VENDOR_ID_INTEL: &[u8; 12] = b"GenuineIntel";
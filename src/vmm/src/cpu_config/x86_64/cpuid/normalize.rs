@@ -81,6 +81,15 @@ pub enum ExtendedCacheFeaturesError {
     MissingLeaf0x80000006,
 }
 
+/// Error type for [`super::Cpuid::update_tsc_entries`].
+#[derive(Debug, thiserror::Error, displaydoc::Display, Eq, PartialEq)]
+pub enum TscEntriesError {
+    /// Leaf 0x0 is missing from CPUID.
+    MissingLeaf0,
+    /// Leaf 0x80000007 is missing from CPUID.
+    MissingLeaf0x80000007,
+}
+
 /// Error type for setting a bit range.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
 #[error("Given value is greater than maximum storable value in bit range.")]
@@ -163,6 +172,8 @@ impl super::Cpuid {
         cpu_index: u8,
         // The total number of logical CPUs.
         cpu_count: u8,
+        // The number of logical CPUs per socket.
+        cpus_per_socket: u8,
         // The number of bits needed to enumerate logical CPUs per core.
         cpu_bits: u8,
     ) -> Result<(), NormalizeCpuidError> {
@@ -170,23 +181,109 @@ impl super::Cpuid {
             .checked_shl(u32::from(cpu_bits))
             .ok_or(NormalizeCpuidError::CpuBits(cpu_bits))?;
         self.update_vendor_id()?;
-        self.update_feature_info_entry(cpu_index, cpu_count)?;
-        self.update_extended_topology_entry(cpu_index, cpu_count, cpu_bits, cpus_per_core)?;
+        self.update_feature_info_entry(cpu_index, cpus_per_socket)?;
+        self.update_extended_topology_entry(
+            cpu_index,
+            cpu_count,
+            cpus_per_socket,
+            cpu_bits,
+            cpus_per_core,
+        )?;
         self.update_extended_cache_features()?;
 
         // Apply manufacturer specific modifications.
         match self {
             // Apply Intel specific modifications.
             Self::Intel(intel_cpuid) => {
-                intel_cpuid.normalize(cpu_index, cpu_count, cpus_per_core)?;
+                intel_cpuid.normalize(cpu_index, cpus_per_socket, cpus_per_core)?;
             }
             // Apply AMD specific modifications.
-            Self::Amd(amd_cpuid) => amd_cpuid.normalize(cpu_index, cpu_count, cpus_per_core)?,
+            Self::Amd(amd_cpuid) => {
+                amd_cpuid.normalize(cpu_index, cpus_per_socket, cpus_per_core)?;
+            }
         }
 
         Ok(())
     }
 
+    /// Advertises the TSC of a vCPU running at `tsc_khz`: sets the invariant TSC bit if
+    /// `invariant_tsc` is set and, on Intel, populates the TSC and processor frequency leaves.
+    ///
+    /// # Errors
+    ///
+    /// When leaf 0x0 or 0x80000007 is missing.
+    pub fn update_tsc_entries(
+        &mut self,
+        invariant_tsc: bool,
+        tsc_khz: u32,
+    ) -> Result<(), TscEntriesError> {
+        /// Frequency of the core crystal clock reported in leaf 0x15, in Hz.
+        const CRYSTAL_CLOCK_HZ: u32 = 1_000_000;
+
+        if invariant_tsc {
+            let leaf_80000007 = self
+                .get_mut(&CpuidKey::leaf(0x80000007))
+                .ok_or(TscEntriesError::MissingLeaf0x80000007)?;
+
+            // CPUID.80000007H:EDX[8] (Mnemonic: InvariantTSC)
+            // The TSC rate is constant in all ACPI P-, C- and T-states.
+            set_bit(&mut leaf_80000007.result.edx, 8, true);
+        }
+
+        // The TSC and processor frequency leaves are only defined on Intel.
+        if let Self::Amd(_) = self {
+            return Ok(());
+        }
+
+        let leaf_0 = self
+            .get_mut(&CpuidKey::leaf(0x0))
+            .ok_or(TscEntriesError::MissingLeaf0)?;
+        // CPUID.00H:EAX[31:0]
+        // Maximum input value for basic CPUID information.
+        leaf_0.result.eax = leaf_0.result.eax.max(0x16);
+
+        // CPUID.15H:EAX[31:0] and CPUID.15H:EBX[31:0]
+        // Denominator and numerator of the TSC / core crystal clock ratio.
+        //
+        // CPUID.15H:ECX[31:0]
+        // Nominal frequency of the core crystal clock in Hz.
+        //
+        // The TSC frequency is ECX * EBX / EAX, i.e. `tsc_khz` kHz.
+        self.inner_mut().insert(
+            CpuidKey::leaf(0x15),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: CRYSTAL_CLOCK_HZ / 1000,
+                    ebx: tsc_khz,
+                    ecx: CRYSTAL_CLOCK_HZ,
+                    edx: 0,
+                },
+            },
+        );
+
+        // CPUID.16H:EAX[15:0] and CPUID.16H:EBX[15:0]
+        // Processor base and maximum frequency in MHz.
+        //
+        // CPUID.16H:ECX[15:0]
+        // Bus (reference) frequency in MHz, not enumerated.
+        let tsc_mhz = tsc_khz / 1000;
+        self.inner_mut().insert(
+            CpuidKey::leaf(0x16),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: tsc_mhz,
+                    ebx: tsc_mhz,
+                    ecx: 0,
+                    edx: 0,
+                },
+            },
+        );
+
+        Ok(())
+    }
+
     /// Pass-through the vendor ID from the host. This is used to prevent modification of the vendor
     /// ID via custom CPU templates.
     fn update_vendor_id(&mut self) -> Result<(), VendorIdError> {
@@ -207,7 +304,7 @@ impl super::Cpuid {
     fn update_feature_info_entry(
         &mut self,
         cpu_index: u8,
-        cpu_count: u8,
+        cpus_per_socket: u8,
     ) -> Result<(), FeatureInformationError> {
         let leaf_1 = self
             .get_mut(&CpuidKey::leaf(0x1))
@@ -224,7 +321,7 @@ impl super::Cpuid {
         // unique initial APIC IDs reserved for addressing different logical processors in a
         // physical package. This field is only valid if CPUID.1.EDX.HTT[bit 28]= 1.
         let max_cpus_per_package = u32::from(
-            get_max_cpus_per_package(cpu_count)
+            get_max_cpus_per_package(cpus_per_socket)
                 .map_err(FeatureInformationError::GetMaxCpusPerPackage)?,
         );
         set_range(&mut leaf_1.result.ebx, 16..=23, max_cpus_per_package)
@@ -257,7 +354,7 @@ impl super::Cpuid {
         // is reserved. A value of 1 for HTT indicates the value in CPUID.1.EBX[23:16] (the Maximum
         // number of addressable IDs for logical processors in this package) is valid for the
         // package.
-        set_bit(&mut leaf_1.result.edx, 28, cpu_count > 1);
+        set_bit(&mut leaf_1.result.edx, 28, cpus_per_socket > 1);
 
         Ok(())
     }
@@ -267,9 +364,20 @@ impl super::Cpuid {
         &mut self,
         cpu_index: u8,
         cpu_count: u8,
+        cpus_per_socket: u8,
         cpu_bits: u8,
         cpus_per_core: u8,
     ) -> Result<(), ExtendedTopologyError> {
+        // With a single socket, the next higher-scoped domain (i.e. socket) includes all
+        // logical processors, so the x2APIC ID is shifted such that it can hold the maximum
+        // number of vCPUs. Otherwise, the shift selects the socket of the logical processor,
+        // which requires the number of logical processors per socket to be a power of 2.
+        let socket_shift = if cpus_per_socket == cpu_count {
+            MAX_SUPPORTED_VCPUS.next_power_of_two().ilog2()
+        } else {
+            cpus_per_socket.next_power_of_two().ilog2()
+        };
+
         // The following commit changed the behavior of KVM_GET_SUPPORTED_CPUID to no longer
         // include CPUID.(EAX=0BH,ECX=1).
         // https://lore.kernel.org/all/20221027092036.2698180-1-pbonzini@redhat.com/
@@ -348,18 +456,12 @@ impl super::Cpuid {
                     }
                     // Core domain
                     1 => {
-                        // Configure such that the next higher-scoped domain (i.e. socket) include
-                        // all logical processors.
-                        //
                         // The CPUID.(EAX=0BH,ECX=1).EAX[4:0] value must be an integer N such that
-                        // 2^N is greater than or equal to the maximum number of vCPUs.
-                        set_range(
-                            &mut subleaf.result.eax,
-                            0..=4,
-                            MAX_SUPPORTED_VCPUS.next_power_of_two().ilog2(),
-                        )
-                        .map_err(|err| ExtendedTopologyError::RightShiftBits(index, err))?;
-                        set_range(&mut subleaf.result.ebx, 0..=15, u32::from(cpu_count))
+                        // 2^N is greater than or equal to the number of logical processors in the
+                        // next higher-scoped domain (i.e. socket).
+                        set_range(&mut subleaf.result.eax, 0..=4, socket_shift)
+                            .map_err(|err| ExtendedTopologyError::RightShiftBits(index, err))?;
+                        set_range(&mut subleaf.result.ebx, 0..=15, u32::from(cpus_per_socket))
                             .map_err(|err| ExtendedTopologyError::NumLogicalProcs(index, err))?;
 
                         // Setting the input ECX value (i.e. `index`)
@@ -531,6 +633,7 @@ mod tests {
         let result = intel_cpuid.update_extended_topology_entry(
             cpu_index,
             cpu_count,
+            cpu_count,
             cpu_bits,
             cpus_per_core,
         );
@@ -556,12 +659,110 @@ mod tests {
                 },
             },
         )])));
-        let result =
-            amd_cpuid.update_extended_topology_entry(cpu_index, cpu_count, cpu_bits, cpus_per_core);
+        let result = amd_cpuid.update_extended_topology_entry(
+            cpu_index,
+            cpu_count,
+            cpu_count,
+            cpu_bits,
+            cpus_per_core,
+        );
         result.unwrap();
         assert!(amd_cpuid.inner().contains_key(&CpuidKey {
             leaf: 0xb,
             subleaf: 0x1
         }));
     }
+
+    #[test]
+    fn test_update_extended_topology_entry_sockets() {
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey {
+                leaf: 0xb,
+                subleaf: 0,
+            },
+            CpuidEntry {
+                flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                result: CpuidRegisters {
+                    eax: 0,
+                    ebx: 0,
+                    ecx: 0,
+                    edx: 0,
+                },
+            },
+        )])));
+
+        // A single socket holding all the vCPUs keeps the shift covering the maximum number of
+        // vCPUs.
+        cpuid.update_extended_topology_entry(5, 8, 8, 0, 1).unwrap();
+        let subleaf = cpuid.get(&CpuidKey::subleaf(0xb, 0x1)).unwrap();
+        assert_eq!(
+            get_range(subleaf.result.eax, 0..=4),
+            MAX_SUPPORTED_VCPUS.next_power_of_two().ilog2()
+        );
+        assert_eq!(get_range(subleaf.result.ebx, 0..=15), 8);
+
+        // With 2 sockets of 4 vCPUs, the shift selects the socket from the x2APIC ID.
+        cpuid.update_extended_topology_entry(5, 8, 4, 0, 1).unwrap();
+        let subleaf = cpuid.get(&CpuidKey::subleaf(0xb, 0x1)).unwrap();
+        assert_eq!(get_range(subleaf.result.eax, 0..=4), 2);
+        assert_eq!(get_range(subleaf.result.ebx, 0..=15), 4);
+        assert_eq!(subleaf.result.edx, 5);
+        assert_eq!(
+            subleaf.result.edx >> get_range(subleaf.result.eax, 0..=4),
+            1
+        );
+    }
+
+    #[test]
+    fn test_update_tsc_entries() {
+        // Intel CPUID
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (
+                CpuidKey::leaf(0x0),
+                CpuidEntry {
+                    result: CpuidRegisters {
+                        eax: 0xd,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ),
+            (CpuidKey::leaf(0x80000007), CpuidEntry::default()),
+        ])));
+        cpuid.update_tsc_entries(true, 2_500_000).unwrap();
+        assert_eq!(cpuid.get(&CpuidKey::leaf(0x0)).unwrap().result.eax, 0x16);
+        assert_eq!(
+            cpuid.get(&CpuidKey::leaf(0x80000007)).unwrap().result.edx,
+            1 << 8
+        );
+        let leaf_15 = &cpuid.get(&CpuidKey::leaf(0x15)).unwrap().result;
+        assert_eq!(
+            u64::from(leaf_15.ecx) * u64::from(leaf_15.ebx) / u64::from(leaf_15.eax),
+            2_500_000_000
+        );
+        let leaf_16 = &cpuid.get(&CpuidKey::leaf(0x16)).unwrap().result;
+        assert_eq!((leaf_16.eax, leaf_16.ebx), (2500, 2500));
+
+        // AMD CPUID
+        let mut cpuid = Cpuid::Amd(AmdCpuid(BTreeMap::from([(
+            CpuidKey::leaf(0x80000007),
+            CpuidEntry::default(),
+        )])));
+        cpuid.update_tsc_entries(false, 2_500_000).unwrap();
+        assert_eq!(
+            cpuid.get(&CpuidKey::leaf(0x80000007)).unwrap().result.edx,
+            0
+        );
+        assert!(cpuid.get(&CpuidKey::leaf(0x15)).is_none());
+        cpuid.update_tsc_entries(true, 2_500_000).unwrap();
+        assert_eq!(
+            cpuid.get(&CpuidKey::leaf(0x80000007)).unwrap().result.edx,
+            1 << 8
+        );
+
+        assert_eq!(
+            Cpuid::Amd(AmdCpuid(BTreeMap::new())).update_tsc_entries(true, 2_500_000),
+            Err(TscEntriesError::MissingLeaf0x80000007)
+        );
+    }
 }
@@ -3,58 +3,68 @@
 
 use std::collections::BTreeMap;
 
-use vmm::MSR_RANGE;
-use vmm::arch::x86_64::generated::msr_index::*;
-use vmm::arch::x86_64::msr::MsrRange;
-use vmm::cpu_config::templates::{CpuConfiguration, CustomCpuTemplate, RegisterValueFilter};
-use vmm::cpu_config::x86_64::cpuid::common::get_vendor_id_from_host;
-use vmm::cpu_config::x86_64::cpuid::{Cpuid, VENDOR_ID_AMD};
-use vmm::cpu_config::x86_64::custom_cpu_template::{
+use crate::MSR_RANGE;
+use crate::arch::x86_64::generated::msr_index::*;
+use crate::arch::x86_64::msr::MsrRange;
+use crate::cpu_config::templates::{CpuConfiguration, CustomCpuTemplate, RegisterValueFilter};
+use crate::cpu_config::x86_64::cpuid::Cpuid;
+use crate::cpu_config::x86_64::custom_cpu_template::{
     CpuidLeafModifier, CpuidRegister, CpuidRegisterModifier, RegisterModifier,
 };
 
-use crate::utils::x86_64::{cpuid_leaf_modifier, cpuid_reg_modifier, msr_modifier};
-
-/// Convert `&CpuConfiguration` to `CustomCputemplate`.
+/// Converts a guest CPU configuration to a custom CPU template setting every CPUID register and
+/// MSR to its value in the configuration.
 pub fn config_to_template(cpu_config: &CpuConfiguration) -> CustomCpuTemplate {
     CustomCpuTemplate {
         cpuid_modifiers: cpuid_to_modifiers(&cpu_config.cpuid),
-        msr_modifiers: msrs_to_modifier(&cpu_config.msrs),
+        msr_modifiers: msrs_to_modifier(
+            &cpu_config.msrs,
+            matches!(cpu_config.cpuid, Cpuid::Amd(_)),
+        ),
         ..Default::default()
     }
 }
 
 fn cpuid_to_modifiers(cpuid: &Cpuid) -> Vec<CpuidLeafModifier> {
+    let reg_modifier = |register, value| CpuidRegisterModifier {
+        register,
+        bitmap: RegisterValueFilter {
+            filter: u32::MAX,
+            value,
+        },
+    };
+
     cpuid
         .inner()
         .iter()
-        .map(|(key, entry)| {
-            cpuid_leaf_modifier!(
-                key.leaf,
-                key.subleaf,
-                entry.flags,
-                vec![
-                    cpuid_reg_modifier!(CpuidRegister::Eax, entry.result.eax),
-                    cpuid_reg_modifier!(CpuidRegister::Ebx, entry.result.ebx),
-                    cpuid_reg_modifier!(CpuidRegister::Ecx, entry.result.ecx),
-                    cpuid_reg_modifier!(CpuidRegister::Edx, entry.result.edx),
-                ]
-            )
+        .map(|(key, entry)| CpuidLeafModifier {
+            leaf: key.leaf,
+            subleaf: key.subleaf,
+            flags: entry.flags,
+            modifiers: vec![
+                reg_modifier(CpuidRegister::Eax, entry.result.eax),
+                reg_modifier(CpuidRegister::Ebx, entry.result.ebx),
+                reg_modifier(CpuidRegister::Ecx, entry.result.ecx),
+                reg_modifier(CpuidRegister::Edx, entry.result.edx),
+            ],
         })
         .collect()
 }
 
-fn msrs_to_modifier(msrs: &BTreeMap<u32, u64>) -> Vec<RegisterModifier> {
+fn msrs_to_modifier(msrs: &BTreeMap<u32, u64>, amd: bool) -> Vec<RegisterModifier> {
     let mut msrs: Vec<RegisterModifier> = msrs
         .iter()
-        .map(|(index, value)| msr_modifier!(*index, *value))
+        .filter(|(index, _)| !should_exclude_msr(**index))
+        .filter(|(index, _)| !(amd && should_exclude_msr_amd(**index)))
+        .map(|(index, value)| RegisterModifier {
+            addr: *index,
+            bitmap: RegisterValueFilter {
+                filter: u64::MAX,
+                value: *value,
+            },
+        })
         .collect();
 
-    msrs.retain(|modifier| !should_exclude_msr(modifier.addr));
-    if &get_vendor_id_from_host().unwrap() == VENDOR_ID_AMD {
-        msrs.retain(|modifier| !should_exclude_msr_amd(modifier.addr));
-    }
-
     msrs.sort_by_key(|modifier| modifier.addr);
     msrs
 }
@@ -121,14 +131,13 @@ fn should_exclude_msr_amd(index: u32) -> bool {
 mod tests {
     use std::collections::BTreeMap;
 
-    use vmm::cpu_config::x86_64::cpuid::{
-        CpuidEntry, CpuidKey, CpuidRegisters, IntelCpuid, KvmCpuidFlags,
-    };
-
     use super::*;
+    use crate::cpu_config::x86_64::cpuid::{
+        AmdCpuid, CpuidEntry, CpuidKey, CpuidRegisters, IntelCpuid, KvmCpuidFlags,
+    };
 
-    fn build_sample_cpuid() -> Cpuid {
-        Cpuid::Intel(IntelCpuid(BTreeMap::from([
+    fn build_sample_cpuid_entries() -> BTreeMap<CpuidKey, CpuidEntry> {
+        BTreeMap::from([
             (
                 CpuidKey {
                     leaf: 0x0,
@@ -159,39 +168,54 @@ mod tests {
                     },
                 },
             ),
-        ])))
+        ])
+    }
+
+    fn cpuid_leaf_modifier(
+        leaf: u32,
+        subleaf: u32,
+        flags: KvmCpuidFlags,
+        [eax, ebx, ecx, edx]: [u32; 4],
+    ) -> CpuidLeafModifier {
+        let reg_modifier = |register, value| CpuidRegisterModifier {
+            register,
+            bitmap: RegisterValueFilter {
+                filter: u32::MAX,
+                value,
+            },
+        };
+        CpuidLeafModifier {
+            leaf,
+            subleaf,
+            flags,
+            modifiers: vec![
+                reg_modifier(CpuidRegister::Eax, eax),
+                reg_modifier(CpuidRegister::Ebx, ebx),
+                reg_modifier(CpuidRegister::Ecx, ecx),
+                reg_modifier(CpuidRegister::Edx, edx),
+            ],
+        }
     }
 
     fn build_expected_cpuid_modifiers() -> Vec<CpuidLeafModifier> {
         vec![
-            cpuid_leaf_modifier!(
+            cpuid_leaf_modifier(
                 0x0,
                 0x0,
                 KvmCpuidFlags::EMPTY,
-                vec![
-                    cpuid_reg_modifier!(CpuidRegister::Eax, 0xffff_ffff),
-                    cpuid_reg_modifier!(CpuidRegister::Ebx, 0x0000_ffff),
-                    cpuid_reg_modifier!(CpuidRegister::Ecx, 0xffff_0000),
-                    cpuid_reg_modifier!(CpuidRegister::Edx, 0x0000_0000),
-                ]
+                [0xffff_ffff, 0x0000_ffff, 0xffff_0000, 0x0000_0000],
             ),
-            cpuid_leaf_modifier!(
+            cpuid_leaf_modifier(
                 0x1,
                 0x1,
                 KvmCpuidFlags::SIGNIFICANT_INDEX,
-                vec![
-                    cpuid_reg_modifier!(CpuidRegister::Eax, 0xaaaa_aaaa),
-                    cpuid_reg_modifier!(CpuidRegister::Ebx, 0xaaaa_5555),
-                    cpuid_reg_modifier!(CpuidRegister::Ecx, 0x5555_aaaa),
-                    cpuid_reg_modifier!(CpuidRegister::Edx, 0x5555_5555),
-                ]
+                [0xaaaa_aaaa, 0xaaaa_5555, 0x5555_aaaa, 0x5555_5555],
             ),
         ]
     }
 
     fn build_sample_msrs() -> BTreeMap<u32, u64> {
         let mut map = BTreeMap::from([
-            // should be sorted in the result.
             (0x1, 0xffff_ffff_ffff_ffff),
             (0x5, 0xffff_ffff_0000_0000),
             (0x3, 0x0000_0000_ffff_ffff),
@@ -209,17 +233,27 @@ mod tests {
         map
     }
 
-    fn build_expected_msr_modifiers() -> Vec<RegisterModifier> {
+    fn msr_modifier(addr: u32, value: u64) -> RegisterModifier {
+        RegisterModifier {
+            addr,
+            bitmap: RegisterValueFilter {
+                filter: u64::MAX,
+                value,
+            },
+        }
+    }
+
+    fn build_expected_msr_modifiers(amd: bool) -> Vec<RegisterModifier> {
         let mut v = vec![
-            msr_modifier!(0x1, 0xffff_ffff_ffff_ffff),
-            msr_modifier!(0x2, 0x0000_0000_0000_0000),
-            msr_modifier!(0x3, 0x0000_0000_ffff_ffff),
-            msr_modifier!(0x5, 0xffff_ffff_0000_0000),
+            msr_modifier(0x1, 0xffff_ffff_ffff_ffff),
+            msr_modifier(0x2, 0x0000_0000_0000_0000),
+            msr_modifier(0x3, 0x0000_0000_ffff_ffff),
+            msr_modifier(0x5, 0xffff_ffff_0000_0000),
         ];
-        if &get_vendor_id_from_host().unwrap() != VENDOR_ID_AMD {
+        if !amd {
             MSR_EXCLUSION_LIST_AMD.iter().for_each(|range| {
                 (range.base..(range.base + range.nmsrs)).for_each(|id| {
-                    v.push(msr_modifier!(id, 0));
+                    v.push(msr_modifier(id, 0));
                 })
             });
         }
@@ -228,15 +262,23 @@ mod tests {
 
     #[test]
     fn test_config_to_template() {
-        let cpu_config = CpuConfiguration {
-            cpuid: build_sample_cpuid(),
-            msrs: build_sample_msrs(),
-        };
-        let cpu_template = CustomCpuTemplate {
-            cpuid_modifiers: build_expected_cpuid_modifiers(),
-            msr_modifiers: build_expected_msr_modifiers(),
-            ..Default::default()
-        };
-        assert_eq!(config_to_template(&cpu_config), cpu_template);
+        for (cpuid, amd) in [
+            (
+                Cpuid::Intel(IntelCpuid(build_sample_cpuid_entries())),
+                false,
+            ),
+            (Cpuid::Amd(AmdCpuid(build_sample_cpuid_entries())), true),
+        ] {
+            let cpu_config = CpuConfiguration {
+                cpuid,
+                msrs: build_sample_msrs(),
+            };
+            let cpu_template = CustomCpuTemplate {
+                cpuid_modifiers: build_expected_cpuid_modifiers(),
+                msr_modifiers: build_expected_msr_modifiers(amd),
+                ..Default::default()
+            };
+            assert_eq!(config_to_template(&cpu_config), cpu_template);
+        }
     }
 }
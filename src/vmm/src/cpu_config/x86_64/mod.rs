@@ -5,6 +5,8 @@
 pub mod cpuid;
 /// Module for custom CPU templates
 pub mod custom_cpu_template;
+/// Module for converting CPU configurations to custom CPU templates
+pub mod dump;
 /// Module for static CPU templates
 pub mod static_cpu_templates;
 /// Module with test utils for custom CPU templates
@@ -6,6 +6,8 @@ use acpi_tables::{Aml, aml};
 use vm_memory::GuestMemoryError;
 
 use crate::Vm;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::acpi::power_button::PowerButton;
 use crate::devices::acpi::vmclock::VmClock;
 use crate::devices::acpi::vmgenid::VmGenId;
 use crate::vstate::resources::ResourceAllocator;
@@ -24,6 +26,9 @@ pub struct ACPIDeviceManager {
     pub vmgenid: VmGenId,
     /// VMclock device
     pub vmclock: VmClock,
+    /// ACPI power button device, when enabled in the machine configuration
+    #[cfg(target_arch = "x86_64")]
+    pub power_button: Option<PowerButton>,
 }
 
 impl ACPIDeviceManager {
@@ -32,6 +37,8 @@ impl ACPIDeviceManager {
         ACPIDeviceManager {
             vmgenid: VmGenId::new(resource_allocator),
             vmclock: VmClock::new(resource_allocator),
+            #[cfg(target_arch = "x86_64")]
+            power_button: None,
         }
     }
 
@@ -46,6 +53,17 @@ impl ACPIDeviceManager {
         self.vmclock.activate(vm.guest_memory())?;
         Ok(())
     }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn attach_power_button(
+        &mut self,
+        vm: &Vm,
+        power_button: PowerButton,
+    ) -> Result<(), ACPIDeviceError> {
+        vm.register_irq(&power_button.interrupt_evt, power_button.gsi)?;
+        self.power_button = Some(power_button);
+        Ok(())
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -55,6 +73,46 @@ impl Aml for ACPIDeviceManager {
         self.vmgenid.append_aml_bytes(v)?;
         // AML for [`VmClock`] device.
         self.vmclock.append_aml_bytes(v)?;
+        // AML for [`PowerButton`] device, when enabled.
+        if let Some(power_button) = &self.power_button {
+            power_button.append_aml_bytes(v)?;
+        }
+
+        // The devices notified through the GED interrupt handler, with their GSI.
+        let mut devices = vec![
+            (self.vmgenid.gsi, "\\_SB_.VGEN"),
+            (self.vmclock.gsi, "\\_SB_.VCLK"),
+        ];
+        if let Some(power_button) = &self.power_button {
+            devices.push((power_button.gsi, "\\_SB_.PWRB"));
+        }
+        let interrupts: Vec<_> = devices
+            .iter()
+            .map(|&(gsi, _)| aml::Interrupt::new(true, true, false, false, gsi))
+            .collect();
+        // We know that the maximum IRQ number fits in a u8. We have up to
+        // 32 IRQs in x86 and up to 128 in ARM (look into `vmm::crate::arch::layout::GSI_LEGACY_END`).
+        // The GSIs of the devices can safely be cast to `u8` without truncation, so we let clippy
+        // know.
+        #[allow(clippy::cast_possible_truncation)]
+        let gsis: Vec<u8> = devices.iter().map(|&(gsi, _)| gsi as u8).collect();
+        let paths = devices
+            .iter()
+            .map(|&(_, path)| aml::Path::new(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let arg = aml::Arg(0);
+        let predicates: Vec<_> = gsis.iter().map(|gsi| aml::Equal::new(&arg, gsi)).collect();
+        let notifications: Vec<_> = paths
+            .iter()
+            .map(|path| aml::Notify::new(path, &0x80usize))
+            .collect();
+        let handlers: Vec<_> = predicates
+            .iter()
+            .zip(&notifications)
+            .map(|(predicate, notification)| {
+                aml::If::new(predicate, vec![notification as &dyn Aml])
+            })
+            .collect();
 
         // Create the AML for the GED interrupt handler
         aml::Device::new(
@@ -63,37 +121,13 @@ impl Aml for ACPIDeviceManager {
                 &aml::Name::new("_HID".try_into()?, &"ACPI0013")?,
                 &aml::Name::new(
                     "_CRS".try_into()?,
-                    &aml::ResourceTemplate::new(vec![
-                        &aml::Interrupt::new(true, true, false, false, self.vmgenid.gsi),
-                        &aml::Interrupt::new(true, true, false, false, self.vmclock.gsi),
-                    ]),
+                    &aml::ResourceTemplate::new(interrupts.iter().map(|i| i as &dyn Aml).collect()),
                 )?,
-                // We know that the maximum IRQ number fits in a u8. We have up to
-                // 32 IRQs in x86 and up to 128 in ARM (look into `vmm::crate::arch::layout::GSI_LEGACY_END`).
-                // Both `vmgenid.gsi` and `vmclock.gsi` can safely be cast to `u8`
-                // without truncation, so we let clippy know.
                 &aml::Method::new(
                     "_EVT".try_into()?,
                     1,
                     true,
-                    vec![
-                        &aml::If::new(
-                            #[allow(clippy::cast_possible_truncation)]
-                            &aml::Equal::new(&aml::Arg(0), &(self.vmgenid.gsi as u8)),
-                            vec![&aml::Notify::new(
-                                &aml::Path::new("\\_SB_.VGEN")?,
-                                &0x80usize,
-                            )],
-                        ),
-                        &aml::If::new(
-                            #[allow(clippy::cast_possible_truncation)]
-                            &aml::Equal::new(&aml::Arg(0), &(self.vmclock.gsi as u8)),
-                            vec![&aml::Notify::new(
-                                &aml::Path::new("\\_SB_.VCLK")?,
-                                &0x80usize,
-                            )],
-                        ),
-                    ],
+                    handlers.iter().map(|h| h as &dyn Aml).collect(),
                 ),
             ],
         )
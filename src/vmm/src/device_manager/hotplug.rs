@@ -0,0 +1,304 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lifecycle of the devices added to, or removed from, a running microVM.
+//!
+//! Removing a device goes through the same steps whatever its type: the PCI slot of the device
+//! is looked up, the guest is asked to eject it through the PCI hotplug controller, and the
+//! device is detached once the guest released it, or once the guest ran out of time to do so.
+//! Detaching the device stops it, removes it from the buses and frees its slot, which also
+//! drops it from the device states saved in snapshots.
+//!
+//! Adding a device attaches it to a free slot, and asks the guest to check that slot through
+//! the same controller. The API requests are handled while the event manager dispatches events,
+//! so the device is only handed to the event manager once it is done dispatching them.
+
+use std::fmt::Debug;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use event_manager::{EventOps, Events, MutEventSubscriber};
+use utils::time::{ClockType, TimerFd, get_time_us};
+use vmm_sys_util::epoll::EventSet;
+
+use super::DeviceManager;
+use super::pci_mngr::PciManagerError;
+use crate::Vm;
+use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
+use crate::logger::{IncMetric, METRICS, error, info, warn};
+use crate::vmm_config::device_removal::DeviceRemovalConfig;
+
+/// Errors associated with the addition and removal of devices.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum HotplugError {
+    /// Devices can only be added to or removed from microVMs using PCI.
+    Unsupported,
+    /// Device {0} already exists.
+    DeviceExists(String),
+    /// Cannot ask the guest to check the device: {0}
+    NotifyInsert(io::Error),
+    /// Cannot add the device: {0}
+    Attach(PciManagerError),
+    /// Cannot ask the guest to eject the device: {0}
+    Notify(io::Error),
+    /// The removal of device {0} is already in progress.
+    RemovalInProgress(String),
+    /// Cannot remove the device: {0}
+    Detach(#[from] PciManagerError),
+}
+
+/// Device the guest was asked to eject, which is still attached.
+#[derive(Debug)]
+pub(crate) struct PendingRemoval {
+    device_type: VirtioDeviceType,
+    id: String,
+    pub(crate) slot: u32,
+    // Time of the eject request, in us.
+    start_us: u64,
+    // Time at which the device is removed anyway, in us.
+    deadline_us: u64,
+}
+
+/// Added devices not handed to the event manager yet.
+#[derive(Default)]
+pub(crate) struct PendingSubscribers(Vec<Arc<Mutex<dyn MutEventSubscriber>>>);
+
+impl Debug for PendingSubscribers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PendingSubscribers")
+            .field(&self.0.len())
+            .finish()
+    }
+}
+
+/// Additions and removals of devices in progress.
+#[derive(Debug)]
+pub struct DeviceHotplug {
+    // Bounds the time left to the guest to release the devices it was asked to eject.
+    pub(crate) removal_timer: TimerFd,
+    pub(crate) pending_removals: Vec<PendingRemoval>,
+    pub(crate) pending_subscribers: PendingSubscribers,
+}
+
+impl DeviceHotplug {
+    /// Creates a tracker without pending additions nor removals.
+    pub fn new() -> Self {
+        Self {
+            removal_timer: TimerFd::new(),
+            pending_removals: Vec::new(),
+            pending_subscribers: PendingSubscribers::default(),
+        }
+    }
+
+    // Arms the removal timer to expire at the earliest deadline of the pending removals, or
+    // disarms it if there are none.
+    fn arm_removal_timer(&mut self) {
+        let timeout = match self.pending_removals.iter().map(|r| r.deadline_us).min() {
+            Some(deadline_us) => {
+                let now_us = get_time_us(ClockType::Monotonic);
+                // A zero duration would disarm the timer.
+                Duration::from_micros(deadline_us.saturating_sub(now_us).max(1))
+            }
+            None => Duration::ZERO,
+        };
+        self.removal_timer.arm(timeout, None);
+    }
+}
+
+impl Default for DeviceHotplug {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceManager {
+    /// Whether devices can be added to the running microVM, which needs PCI.
+    pub fn supports_hotplug(&self) -> bool {
+        self.pci_devices.hotplug.is_some()
+    }
+
+    /// Attaches a device to a free slot of the running microVM, and asks the guest to check
+    /// that slot. The device is handed to the event manager by `take_pending_subscribers()`.
+    pub fn insert_device<T: 'static + VirtioDevice + MutEventSubscriber + Debug>(
+        &mut self,
+        vm: &Arc<Vm>,
+        id: String,
+        device: Arc<Mutex<T>>,
+    ) -> Result<(), HotplugError> {
+        let controller = self
+            .pci_devices
+            .hotplug
+            .clone()
+            .ok_or(HotplugError::Unsupported)?;
+        let device_type = device.lock().expect("Poisoned lock").device_type();
+        if self.pci_devices.device_slot(device_type, &id).is_some() {
+            return Err(HotplugError::DeviceExists(id));
+        }
+        self.pci_devices
+            .attach_pci_virtio_device(vm, id.clone(), device.clone())
+            .map_err(HotplugError::Attach)?;
+        // The device was just attached to its slot.
+        let slot = self.pci_devices.device_slot(device_type, &id).unwrap();
+        self.hotplug.pending_subscribers.0.push(device);
+
+        controller
+            .lock()
+            .expect("Poisoned lock")
+            .request_insert(slot)
+            .map_err(HotplugError::NotifyInsert)?;
+        info!("Asked the guest to check device {id}.");
+        Ok(())
+    }
+
+    /// Takes the added devices to hand to the event manager.
+    pub fn take_pending_subscribers(&mut self) -> Vec<Arc<Mutex<dyn MutEventSubscriber>>> {
+        std::mem::take(&mut self.hotplug.pending_subscribers.0)
+    }
+
+    /// Asks the guest to eject a device, which is removed once the guest released it, or when
+    /// the timeout of the removal expires.
+    pub fn remove_device(
+        &mut self,
+        vm: &Vm,
+        config: &DeviceRemovalConfig,
+    ) -> Result<(), HotplugError> {
+        let device_type = config.device_type.virtio_device_type();
+        let id = config.device_type.device_id(&config.id);
+        let controller = self
+            .pci_devices
+            .hotplug
+            .clone()
+            .ok_or(HotplugError::Unsupported)?;
+        if self
+            .hotplug
+            .pending_removals
+            .iter()
+            .any(|removal| removal.device_type == device_type && removal.id == id)
+        {
+            return Err(HotplugError::RemovalInProgress(id.to_string()));
+        }
+        let slot = self
+            .pci_devices
+            .device_slot(device_type, id)
+            .ok_or_else(|| PciManagerError::DeviceNotFound(device_type, id.to_string()))?;
+
+        if config.timeout_ms == 0 {
+            METRICS.vmm.device_surprise_removal_count.inc();
+            self.pci_devices.detach_virtio_device(vm, device_type, id)?;
+            return Ok(());
+        }
+
+        controller
+            .lock()
+            .expect("Poisoned lock")
+            .request_eject(slot)
+            .map_err(HotplugError::Notify)?;
+        let start_us = get_time_us(ClockType::Monotonic);
+        self.hotplug.pending_removals.push(PendingRemoval {
+            device_type,
+            id: id.to_string(),
+            slot,
+            start_us,
+            deadline_us: start_us.saturating_add(config.timeout_ms.saturating_mul(1000)),
+        });
+        self.hotplug.arm_removal_timer();
+        info!(
+            "Asked the guest to eject device {}, the guest has {} ms to release it.",
+            id, config.timeout_ms
+        );
+        Ok(())
+    }
+
+    // Detaches the pending devices matching `released`, and updates the removal timer.
+    fn complete_removals(
+        &mut self,
+        vm: &Vm,
+        released: impl Fn(&PendingRemoval) -> bool,
+        surprise: bool,
+    ) {
+        let (completed, pending): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.hotplug.pending_removals)
+                .into_iter()
+                .partition(released);
+        self.hotplug.pending_removals = pending;
+        self.hotplug.arm_removal_timer();
+
+        let now_us = get_time_us(ClockType::Monotonic);
+        for removal in completed {
+            if surprise {
+                warn!(
+                    "The guest did not release device {} in time, removing it anyway.",
+                    removal.id
+                );
+                METRICS.vmm.device_surprise_removal_count.inc();
+            } else {
+                info!(
+                    "The guest released device {} in {} ms.",
+                    removal.id,
+                    (now_us - removal.start_us) / 1000
+                );
+                METRICS.vmm.device_eject_count.inc();
+            }
+            if let Err(err) =
+                self.pci_devices
+                    .detach_virtio_device(vm, removal.device_type, &removal.id)
+            {
+                error!("Failed to remove device {}: {}", removal.id, err);
+            }
+        }
+    }
+
+    /// Removes the devices the guest ejected.
+    pub fn process_ejected_devices(&mut self, vm: &Vm) {
+        let Some(controller) = self.pci_devices.hotplug.clone() else {
+            return;
+        };
+        let ejected = controller.lock().expect("Poisoned lock").take_ejected();
+        self.complete_removals(vm, |removal| ejected & (1 << removal.slot) != 0, false);
+    }
+
+    /// Removes the devices the guest did not release in time.
+    pub fn process_expired_removals(&mut self, vm: &Vm) {
+        self.hotplug.removal_timer.read();
+        let now_us = get_time_us(ClockType::Monotonic);
+        self.complete_removals(vm, |removal| removal.deadline_us <= now_us, true);
+    }
+
+    /// Registers the events driving the removals of devices.
+    pub fn register_hotplug_events(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::new(&self.hotplug.removal_timer, EventSet::IN)) {
+            error!("Failed to register device removal timer: {}", err);
+        }
+        if let Some(controller) = &self.pci_devices.hotplug
+            && let Err(err) = ops.add(Events::new(
+                &controller.lock().expect("Poisoned lock").eject_evt,
+                EventSet::IN,
+            ))
+        {
+            error!("Failed to register PCI eject event: {}", err);
+        }
+    }
+
+    /// Handles an event registered by `register_hotplug_events()`, returning whether `source`
+    /// was one of them.
+    pub fn process_hotplug_event(&mut self, vm: &Vm, source: RawFd) -> bool {
+        if source == self.hotplug.removal_timer.as_raw_fd() {
+            self.process_expired_removals(vm);
+            return true;
+        }
+        let eject_fd = self.pci_devices.hotplug.as_ref().map(|controller| {
+            controller
+                .lock()
+                .expect("Poisoned lock")
+                .eject_evt
+                .as_raw_fd()
+        });
+        if eject_fd == Some(source) {
+            self.process_ejected_devices(vm);
+            return true;
+        }
+        false
+    }
+}
@@ -22,6 +22,11 @@ use crate::Vm;
 use crate::arch::BOOT_DEVICE_MEM_START;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::{RTC_MEM_START, SERIAL_MEM_START};
+use crate::devices::legacy::FwCfg;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::legacy::Pflash;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::legacy::pflash::PflashError;
 #[cfg(target_arch = "aarch64")]
 use crate::devices::legacy::{RTCDevice, SerialDevice};
 use crate::devices::pseudo::BootTimer;
@@ -52,6 +57,9 @@ pub enum MmioError {
     #[cfg(target_arch = "x86_64")]
     /// Failed to create AML code for device
     AmlError(#[from] aml::AmlError),
+    #[cfg(target_arch = "x86_64")]
+    /// Failed to map the firmware flash: {0}
+    Pflash(#[from] PflashError),
 }
 
 /// This represents the size of the mmio device specified to the kernel through ACPI and as a
@@ -106,6 +114,30 @@ fn add_virtio_aml(
     .append_aml_bytes(dsdt_data)
 }
 
+#[cfg(target_arch = "x86_64")]
+fn add_fw_cfg_aml(dsdt_data: &mut Vec<u8>, addr: u64, len: u64) -> Result<(), aml::AmlError> {
+    debug!(
+        "acpi: Building AML for fw_cfg device _SB_.FWCF. memory range: {:#010x}:{}",
+        addr, len
+    );
+    aml::Device::new(
+        "_SB_.FWCF".try_into()?,
+        vec![
+            &aml::Name::new("_HID".try_into()?, &"QEMU0002")?,
+            &aml::Name::new("_STA".try_into()?, &0x0Bu8)?,
+            &aml::Name::new(
+                "_CRS".try_into()?,
+                &aml::ResourceTemplate::new(vec![&aml::Memory32Fixed::new(
+                    true,
+                    addr.try_into().unwrap(),
+                    len.try_into().unwrap(),
+                )]),
+            )?,
+        ],
+    )
+    .append_aml_bytes(dsdt_data)
+}
+
 #[derive(Debug, Clone)]
 /// A descriptor for MMIO devices
 pub struct MMIODevice<T> {
@@ -122,6 +154,8 @@ pub struct MMIODeviceManager {
     pub(crate) virtio_devices: HashMap<(VirtioDeviceType, String), MMIODevice<MmioTransport>>,
     /// Boot timer device
     pub(crate) boot_timer: Option<MMIODevice<BootTimer>>,
+    /// fw_cfg device exposing metadata to the guest
+    pub(crate) fw_cfg: Option<MMIODevice<FwCfg>>,
     #[cfg(target_arch = "aarch64")]
     /// Real-Time clock on Aarch64 platforms
     pub(crate) rtc: Option<MMIODevice<RTCDevice>>,
@@ -129,6 +163,9 @@ pub struct MMIODeviceManager {
     /// Serial device on Aarch64 platforms
     pub(crate) serial: Option<MMIODevice<SerialDevice>>,
     #[cfg(target_arch = "x86_64")]
+    /// Flash devices holding the firmware code and variable store
+    pub(crate) pflash: Vec<MMIODevice<Pflash>>,
+    #[cfg(target_arch = "x86_64")]
     // We create the AML byte code for every VirtIO device in the order we build
     // it, so that we ensure the root block device is appears first in the DSDT.
     // This is needed, so that the root device appears as `/dev/vda` in the guest
@@ -233,9 +270,24 @@ impl MMIODeviceManager {
         &mut self,
         vm: &Vm,
         device_id: String,
-        mmio_device: MmioTransport,
+        mut mmio_device: MmioTransport,
         _cmdline: &mut kernel_cmdline::Cmdline,
     ) -> Result<(), MmioError> {
+        // The shared memory regions of the device are placed in the 64-bit MMIO address space,
+        // where the driver finds them through the transport.
+        let shm_regions = mmio_device.locked_device().shm_regions();
+        let mut placed_regions = Vec::with_capacity(shm_regions.len());
+        for (len, region) in shm_regions {
+            let addr = vm.resource_allocator().allocate_64bit_mmio_memory(
+                len,
+                MMIO_LEN,
+                AllocPolicy::FirstMatch,
+            )?;
+            vm.common.mmio_bus.insert(region, addr, len)?;
+            placed_regions.push((addr, len));
+        }
+        mmio_device.set_shm_regions(placed_regions);
+
         let device = MMIODevice {
             resources: self.allocate_mmio_resources(&mut vm.resource_allocator(), 1)?,
             inner: Arc::new(Mutex::new(mmio_device)),
@@ -352,6 +404,36 @@ impl MMIODeviceManager {
         Ok(())
     }
 
+    #[cfg(target_arch = "x86_64")]
+    /// Register a flash device at the specified MMIO range. Read-only flashes are also mapped to
+    /// the guest as ROM, so that the firmware executes from them without exits.
+    pub fn register_mmio_pflash(
+        &mut self,
+        vm: &Vm,
+        pflash: Arc<Mutex<Pflash>>,
+        device_info: MMIODeviceInfo,
+    ) -> Result<(), MmioError> {
+        {
+            let locked = pflash.lock().expect("Poisoned lock");
+            if locked.is_read_only() {
+                locked.set_mem_region(vm, device_info.addr)?;
+            }
+        }
+
+        let device = MMIODevice {
+            resources: device_info,
+            inner: pflash,
+        };
+
+        vm.common.mmio_bus.insert(
+            device.inner.clone(),
+            device.resources.addr,
+            device.resources.len,
+        )?;
+        self.pflash.push(device);
+        Ok(())
+    }
+
     /// Register a boot timer device.
     pub fn register_mmio_boot_timer(
         &mut self,
@@ -380,6 +462,40 @@ impl MMIODeviceManager {
         Ok(())
     }
 
+    /// Register a fw_cfg device at the specified MMIO configuration if given as parameter,
+    /// otherwise allocate new MMIO resources for it.
+    pub fn register_mmio_fw_cfg(
+        &mut self,
+        vm: &Vm,
+        fw_cfg: Arc<Mutex<FwCfg>>,
+        device_info_opt: Option<MMIODeviceInfo>,
+    ) -> Result<(), MmioError> {
+        // Create a new MMIODeviceInfo object on boot path or unwrap the
+        // existing object on restore path.
+        let device_info = match device_info_opt {
+            Some(device_info) => device_info,
+            None => {
+                let device_info = self.allocate_mmio_resources(&mut vm.resource_allocator(), 0)?;
+                #[cfg(target_arch = "x86_64")]
+                add_fw_cfg_aml(&mut self.dsdt_data, device_info.addr, device_info.len)?;
+                device_info
+            }
+        };
+
+        let device = MMIODevice {
+            resources: device_info,
+            inner: fw_cfg,
+        };
+
+        vm.common.mmio_bus.insert(
+            device.inner.clone(),
+            device.resources.addr,
+            device.resources.len,
+        )?;
+        self.fw_cfg = Some(device);
+        Ok(())
+    }
+
     /// Gets the specified device.
     pub fn get_virtio_device(
         &self,
@@ -419,6 +535,11 @@ impl MMIODeviceManager {
     pub fn serial_device_info(&self) -> Option<&MMIODeviceInfo> {
         self.serial.as_ref().map(|device| &device.resources)
     }
+
+    #[cfg(target_arch = "aarch64")]
+    pub fn fw_cfg_device_info(&self) -> Option<&MMIODeviceInfo> {
+        self.fw_cfg.as_ref().map(|device| &device.resources)
+    }
 }
 
 #[cfg(test)]
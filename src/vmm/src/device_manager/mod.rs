@@ -13,9 +13,13 @@ use std::sync::{Arc, Mutex};
 use acpi::ACPIDeviceManager;
 use event_manager::{MutEventSubscriber, SubscriberOps};
 #[cfg(target_arch = "x86_64")]
+use hotplug::DeviceHotplug;
+#[cfg(target_arch = "x86_64")]
 use legacy::{LegacyDeviceError, PortIODeviceManager};
 use linux_loader::loader::Cmdline;
 use log::{error, info};
+#[cfg(target_arch = "x86_64")]
+use mmio::MMIODeviceInfo;
 use mmio::{MMIODeviceManager, MmioError};
 use pci_mngr::{PciDevices, PciDevicesConstructorArgs, PciManagerError};
 use persist::MMIODevManagerConstructorArgs;
@@ -25,23 +29,31 @@ use vmm_sys_util::eventfd::EventFd;
 
 use crate::device_manager::acpi::ACPIDeviceError;
 #[cfg(target_arch = "x86_64")]
-use crate::devices::legacy::I8042Device;
+use crate::devices::acpi::power_button::PowerButton;
 #[cfg(target_arch = "aarch64")]
 use crate::devices::legacy::RTCDevice;
+use crate::devices::legacy::fw_cfg::FwCfgFile;
 use crate::devices::legacy::serial::SerialOut;
-use crate::devices::legacy::{IER_RDA_BIT, IER_RDA_OFFSET, SerialDevice};
+use crate::devices::legacy::{FwCfg, IER_RDA_BIT, IER_RDA_OFFSET, SerialDevice};
+#[cfg(target_arch = "x86_64")]
+use crate::devices::legacy::{I8042Device, Pflash};
 use crate::devices::pseudo::BootTimer;
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
 use crate::devices::virtio::transport::mmio::{IrqTrigger, MmioTransport};
 use crate::resources::VmResources;
 use crate::snapshot::Persist;
 use crate::utils::open_file_write_nonblock;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::firmware::Firmware;
 use crate::vstate::bus::BusError;
 use crate::vstate::memory::GuestMemoryMmap;
 use crate::{EmulateSerialInitError, EventManager, Vm};
 
 /// ACPI device manager.
 pub mod acpi;
+/// Removal of devices from a running microVM.
+#[cfg(target_arch = "x86_64")]
+pub mod hotplug;
 /// Legacy Device Manager.
 pub mod legacy;
 /// Memory Mapped I/O Manager.
@@ -101,6 +113,9 @@ pub struct DeviceManager {
     pub acpi_devices: ACPIDeviceManager,
     /// PCIe devices
     pub pci_devices: PciDevices,
+    #[cfg(target_arch = "x86_64")]
+    /// Additions and removals of devices in progress
+    pub hotplug: DeviceHotplug,
 }
 
 impl DeviceManager {
@@ -176,6 +191,8 @@ impl DeviceManager {
             legacy_devices,
             acpi_devices: ACPIDeviceManager::new(&mut vm.resource_allocator()),
             pci_devices: PciDevices::new(),
+            #[cfg(target_arch = "x86_64")]
+            hotplug: DeviceHotplug::new(),
         })
     }
 
@@ -218,6 +235,25 @@ impl DeviceManager {
         Ok(())
     }
 
+    /// Attaches a VirtioDevice which may be removed from the running microVM to the device
+    /// manager and event manager. With PCI enabled, the device gets its own slot, through which
+    /// `remove_device()` later asks the guest to eject it.
+    pub(crate) fn attach_hotpluggable_device<
+        T: 'static + VirtioDevice + MutEventSubscriber + Debug,
+    >(
+        &mut self,
+        vm: &Arc<Vm>,
+        id: String,
+        device: Arc<Mutex<T>>,
+        cmdline: &mut Cmdline,
+        event_manager: &mut EventManager,
+        is_vhost_user: bool,
+    ) -> Result<(), AttachDeviceError> {
+        event_manager.add_subscriber(device.clone());
+        // The device mutex mustn't be locked here otherwise it will deadlock.
+        self.attach_virtio_device(vm, id, device, cmdline, is_vhost_user)
+    }
+
     /// Attaches a [`BootTimer`] to the VM
     pub(crate) fn attach_boot_timer_device(
         &mut self,
@@ -232,6 +268,51 @@ impl DeviceManager {
         Ok(())
     }
 
+    /// Attaches the flash devices holding the firmware code and its variable store, stacked
+    /// right below 4 GiB.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn attach_firmware(
+        &mut self,
+        vm: &Vm,
+        firmware: &Firmware,
+    ) -> Result<(), AttachDeviceError> {
+        let units = std::iter::once((&firmware.code_file, None)).chain(
+            firmware
+                .vars_file
+                .iter()
+                .map(|file| (file, firmware.config.vars_path.clone())),
+        );
+
+        let mut end = crate::arch::FIRST_ADDR_PAST_32BITS;
+        for (file, backing_path) in units {
+            let pflash = Pflash::new(file, backing_path).map_err(MmioError::from)?;
+            let len = pflash.size();
+            end -= len;
+            let device_info = MMIODeviceInfo {
+                addr: end,
+                len,
+                gsi: None,
+            };
+            self.mmio_devices.register_mmio_pflash(
+                vm,
+                Arc::new(Mutex::new(pflash)),
+                device_info,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Attaches a fw_cfg device exposing the given items to the guest.
+    pub(crate) fn attach_fw_cfg_device(
+        &mut self,
+        vm: &Vm,
+        files: Vec<FwCfgFile>,
+    ) -> Result<(), AttachDeviceError> {
+        let fw_cfg = Arc::new(Mutex::new(FwCfg::new(files)));
+        self.mmio_devices.register_mmio_fw_cfg(vm, fw_cfg, None)?;
+        Ok(())
+    }
+
     pub(crate) fn attach_vmgenid_device(&mut self, vm: &Vm) -> Result<(), AttachDeviceError> {
         self.acpi_devices.attach_vmgenid(vm)?;
         Ok(())
@@ -242,6 +323,14 @@ impl DeviceManager {
         Ok(())
     }
 
+    /// Attaches an ACPI power button, taking one of the interrupt lines available to devices.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn attach_power_button_device(&mut self, vm: &Vm) -> Result<(), AttachDeviceError> {
+        let power_button = PowerButton::new(&mut vm.resource_allocator());
+        self.acpi_devices.attach_power_button(vm, power_button)?;
+        Ok(())
+    }
+
     #[cfg(target_arch = "aarch64")]
     pub(crate) fn attach_legacy_devices_aarch64(
         &mut self,
@@ -271,7 +360,10 @@ impl DeviceManager {
 
     /// Enables PCIe support for Firecracker devices
     pub fn enable_pci(&mut self, vm: &Arc<Vm>) -> Result<(), PciManagerError> {
-        self.pci_devices.attach_pci_segment(vm)
+        self.pci_devices.attach_pci_segment(vm)?;
+        #[cfg(target_arch = "x86_64")]
+        self.pci_devices.attach_hotplug_controller(vm, None)?;
+        Ok(())
     }
 
     /// Artificially kick VirtIO devices as if they had external events.
@@ -299,6 +391,29 @@ impl DeviceManager {
         }
     }
 
+    /// Process the queued requests of the VirtIO devices and complete their work in flight.
+    pub fn drain_virtio_devices(&self) {
+        info!("Draining devices");
+        let _: Result<(), MmioError> = self.mmio_devices.for_each_virtio_device(|_, _, device| {
+            let mmio_transport_locked = device.inner.lock().expect("Poisoned lock");
+            mmio_transport_locked
+                .device()
+                .lock()
+                .expect("Poisoned lock")
+                .drain();
+            Ok(())
+        });
+        for virtio_pci_device in self.pci_devices.virtio_devices.values() {
+            virtio_pci_device
+                .lock()
+                .expect("Poisoned lock")
+                .virtio_device()
+                .lock()
+                .expect("Poisoned lock")
+                .drain();
+        }
+    }
+
     fn do_mark_virtio_queue_memory_dirty(
         device: Arc<Mutex<dyn VirtioDevice>>,
         mem: &GuestMemoryMmap,
@@ -358,6 +473,28 @@ impl DeviceManager {
         }
     }
 
+    /// Returns the ids of the VirtIO devices of type `device_type`, whatever their transport.
+    pub fn virtio_device_ids(&self, device_type: VirtioDeviceType) -> Vec<String> {
+        let mut ids = Vec::new();
+        let _: Result<(), Infallible> =
+            self.mmio_devices
+                .for_each_virtio_device(|mmio_device_type, device_id, _| {
+                    if *mmio_device_type == device_type {
+                        ids.push(device_id.clone());
+                    }
+                    Ok(())
+                });
+        ids.extend(
+            self.pci_devices
+                .virtio_devices
+                .keys()
+                .filter(|(pci_device_type, _)| *pci_device_type == device_type)
+                .map(|(_, device_id)| device_id.clone()),
+        );
+        ids.sort();
+        ids
+    }
+
     /// Run fn `f()` for the virtio device matching `virtio_type` and `id`.
     pub fn with_virtio_device<T, F, R>(&self, id: &str, f: F) -> Result<R, FindDeviceError>
     where
@@ -387,6 +524,38 @@ pub struct DevicesState {
     pub pci_state: pci_mngr::PciDevicesState,
 }
 
+impl DevicesState {
+    /// Returns the types and ids of the virtio devices, whatever their transport.
+    pub fn virtio_devices(&self) -> Vec<(VirtioDeviceType, &str)> {
+        let mut devices = Vec::new();
+        // The MMIO and PCI states hold the same lists of devices, of different types.
+        macro_rules! push_devices {
+            ($device_type:ident, $field:ident) => {
+                for id in self
+                    .mmio_state
+                    .$field
+                    .iter()
+                    .map(|d| d.device_id.as_str())
+                    .chain(self.pci_state.$field.iter().map(|d| d.device_id.as_str()))
+                {
+                    devices.push((VirtioDeviceType::$device_type, id));
+                }
+            };
+        }
+        push_devices!(Block, block_devices);
+        push_devices!(Net, net_devices);
+        push_devices!(Vsock, vsock_device);
+        push_devices!(Balloon, balloon_device);
+        push_devices!(Rng, entropy_device);
+        push_devices!(Pmem, pmem_devices);
+        push_devices!(P9, p9_devices);
+        push_devices!(Gpio, gpio_devices);
+        push_devices!(I2c, i2c_devices);
+        push_devices!(Mem, memory_device);
+        devices
+    }
+}
+
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum DevicePersistError {
     /// Error restoring MMIO devices: {0}
@@ -484,6 +653,8 @@ impl<'a> Persist<'a> for DeviceManager {
             legacy_devices,
             acpi_devices,
             pci_devices,
+            #[cfg(target_arch = "x86_64")]
+            hotplug: DeviceHotplug::new(),
         };
 
         // Restore serial.
@@ -564,6 +735,8 @@ pub(crate) mod tests {
             legacy_devices,
             acpi_devices,
             pci_devices,
+            #[cfg(target_arch = "x86_64")]
+            hotplug: DeviceHotplug::new(),
         }
     }
 
@@ -12,17 +12,29 @@ use serde::{Deserialize, Serialize};
 
 use super::persist::MmdsState;
 use crate::devices::pci::PciSegment;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::pci::hotplug::{
+    PCI_HOTPLUG_MMIO_SIZE, PciHotplugController, PciHotplugControllerState,
+};
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::balloon::persist::{BalloonConstructorArgs, BalloonState};
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::block::persist::{BlockConstructorArgs, BlockState};
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::gpio::device::Gpio;
+use crate::devices::virtio::gpio::persist::{GpioConstructorArgs, GpioState};
+use crate::devices::virtio::i2c::device::I2c;
+use crate::devices::virtio::i2c::persist::{I2cConstructorArgs, I2cState};
 use crate::devices::virtio::mem::VirtioMem;
 use crate::devices::virtio::mem::persist::{VirtioMemConstructorArgs, VirtioMemState};
 use crate::devices::virtio::net::Net;
 use crate::devices::virtio::net::persist::{NetConstructorArgs, NetState};
+use crate::devices::virtio::p9::device::P9;
+use crate::devices::virtio::p9::persist::{P9ConstructorArgs, P9State};
 use crate::devices::virtio::pmem::device::Pmem;
 use crate::devices::virtio::pmem::persist::{PmemConstructorArgs, PmemState};
+use crate::devices::virtio::rdma::VirtioRdma;
+use crate::devices::virtio::rdma::persist::{RdmaConstructorArgs, RdmaState};
 use crate::devices::virtio::rng::Entropy;
 use crate::devices::virtio::rng::persist::{EntropyConstructorArgs, EntropyState};
 use crate::devices::virtio::transport::pci::device::{
@@ -48,6 +60,12 @@ pub struct PciDevices {
     pub pci_segment: Option<PciSegment>,
     /// All VirtIO PCI devices of the system
     pub virtio_devices: HashMap<(VirtioDeviceType, String), Arc<Mutex<VirtioPciDevice>>>,
+    /// Controller used to ask the guest to eject devices, if PCI is enabled.
+    #[cfg(target_arch = "x86_64")]
+    pub hotplug: Option<Arc<Mutex<PciHotplugController>>>,
+    /// vCPUs the interrupts of the queues of the devices attached from now on are delivered to,
+    /// by device ID.
+    pub interrupt_affinity: HashMap<String, Vec<u8>>,
 }
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -66,6 +84,8 @@ pub enum PciManagerError {
     Kvm(#[from] vmm_sys_util::errno::Error),
     /// MMDS error: {0}
     Mmds(#[from] MmdsConfigError),
+    /// No {0:?} device with ID {1}
+    DeviceNotFound(VirtioDeviceType, String),
 }
 
 impl PciDevices {
@@ -86,6 +106,87 @@ impl PciDevices {
         Ok(())
     }
 
+    /// Attaches the PCI hotplug controller, allocating its resources on the boot path, or reusing
+    /// the saved ones on the restore path.
+    #[cfg(target_arch = "x86_64")]
+    pub fn attach_hotplug_controller(
+        &mut self,
+        vm: &Vm,
+        state: Option<&PciHotplugControllerState>,
+    ) -> Result<(), PciManagerError> {
+        let controller = match state {
+            Some(state) => PciHotplugController::restore((), state).unwrap(),
+            None => PciHotplugController::new(&mut vm.resource_allocator())?,
+        };
+        vm.register_irq(&controller.interrupt_evt, controller.gsi)?;
+        let address = controller.address;
+        let controller = Arc::new(Mutex::new(controller));
+        vm.common
+            .mmio_bus
+            .insert(controller.clone(), address, PCI_HOTPLUG_MMIO_SIZE)?;
+        self.hotplug = Some(controller);
+
+        Ok(())
+    }
+
+    /// Returns the slot of a VirtIO PCI device.
+    pub fn device_slot(&self, device_type: VirtioDeviceType, device_id: &str) -> Option<u32> {
+        self.get_virtio_device(device_type, device_id)
+            .map(|device| {
+                device
+                    .lock()
+                    .expect("Poisoned lock")
+                    .pci_device_bdf()
+                    .device() as u32
+            })
+    }
+
+    /// Detaches a VirtIO PCI device: the device is stopped, then removed from the buses, and its
+    /// slot becomes available again.
+    pub fn detach_virtio_device(
+        &mut self,
+        vm: &Vm,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<(), PciManagerError> {
+        let virtio_device = self
+            .virtio_devices
+            .remove(&(device_type, device_id.to_string()))
+            .ok_or_else(|| PciManagerError::DeviceNotFound(device_type, device_id.to_string()))?;
+        let virtio_device_locked = virtio_device.lock().expect("Poisoned lock");
+
+        virtio_device_locked
+            .virtio_device()
+            .lock()
+            .expect("Poisoned lock")
+            .unplug();
+        virtio_device_locked.unregister_notification_ioevent(vm)?;
+        virtio_device_locked.disable_interrupts()?;
+
+        debug!(
+            "Removing MMIO BAR region: {:#x}:{:#x}",
+            virtio_device_locked.bar_address, CAPABILITY_BAR_SIZE
+        );
+        vm.common
+            .mmio_bus
+            .remove(virtio_device_locked.bar_address, CAPABILITY_BAR_SIZE)?;
+        for (addr, len, _) in virtio_device_locked.shm_regions() {
+            debug!("Removing shared memory region: {addr:#x}:{len:#x}");
+            vm.common.mmio_bus.remove(*addr, *len)?;
+        }
+        virtio_device_locked.free_bars(&mut vm.resource_allocator().mmio64_memory)?;
+
+        // We should only be reaching this point if PCI is enabled
+        let pci_segment = self.pci_segment.as_ref().unwrap();
+        pci_segment
+            .pci_bus
+            .lock()
+            .expect("Poisoned lock")
+            .remove_device(virtio_device_locked.pci_device_bdf().device() as u32)?;
+
+        Ok(())
+    }
+
     fn register_bars_with_bus(
         vm: &Vm,
         virtio_device: &Arc<Mutex<VirtioPciDevice>>,
@@ -101,6 +202,10 @@ impl PciDevices {
             virtio_device_locked.bar_address,
             CAPABILITY_BAR_SIZE,
         )?;
+        for (addr, len, region) in virtio_device_locked.shm_regions() {
+            debug!("Inserting shared memory region: {addr:#x}:{len:#x}");
+            vm.common.mmio_bus.insert(region.clone(), *addr, *len)?;
+        }
 
         Ok(())
     }
@@ -135,6 +240,9 @@ impl PciDevices {
             Arc::new(msix_vectors),
             pci_device_bdf.into(),
         )?;
+        if let Some(queue_vcpus) = self.interrupt_affinity.get(&id) {
+            virtio_device.set_interrupt_affinity(queue_vcpus.clone());
+        }
 
         // Allocate bars
         let mut resource_allocator_lock = vm.resource_allocator();
@@ -244,8 +352,20 @@ pub struct PciDevicesState {
     pub entropy_device: Option<VirtioDeviceState<EntropyState>>,
     /// Pmem device states.
     pub pmem_devices: Vec<VirtioDeviceState<PmemState>>,
+    /// 9P device states.
+    pub p9_devices: Vec<VirtioDeviceState<P9State>>,
+    /// GPIO device states.
+    pub gpio_devices: Vec<VirtioDeviceState<GpioState>>,
+    /// I2C device states.
+    pub i2c_devices: Vec<VirtioDeviceState<I2cState>>,
+    /// RDMA device states.
+    pub rdma_devices: Vec<VirtioDeviceState<RdmaState>>,
     /// Memory device state.
     pub memory_device: Option<VirtioDeviceState<VirtioMemState>>,
+    /// PCI hotplug controller state.
+    #[cfg(target_arch = "x86_64")]
+    #[serde(default)]
+    pub hotplug: Option<PciHotplugControllerState>,
 }
 
 pub struct PciDevicesConstructorArgs<'a> {
@@ -280,6 +400,14 @@ impl<'a> Persist<'a> for PciDevices {
             return state;
         }
 
+        #[cfg(target_arch = "x86_64")]
+        {
+            state.hotplug = self
+                .hotplug
+                .as_ref()
+                .map(|hotplug| hotplug.lock().expect("Poisoned lock").save());
+        }
+
         for pci_dev in self.virtio_devices.values() {
             let locked_pci_dev = pci_dev.lock().expect("Poisoned lock");
             let virtio_dev = locked_pci_dev.virtio_device();
@@ -407,6 +535,42 @@ impl<'a> Persist<'a> for PciDevices {
                         transport_state,
                     });
                 }
+                VirtioDeviceType::P9 => {
+                    let p9_dev = locked_virtio_dev.as_mut_any().downcast_mut::<P9>().unwrap();
+                    let device_state = p9_dev.save();
+                    state.p9_devices.push(VirtioDeviceState {
+                        device_id: p9_dev.config.id.clone(),
+                        pci_device_bdf,
+                        device_state,
+                        transport_state,
+                    });
+                }
+                VirtioDeviceType::Gpio => {
+                    let gpio_dev = locked_virtio_dev
+                        .as_mut_any()
+                        .downcast_mut::<Gpio>()
+                        .unwrap();
+                    let device_state = gpio_dev.save();
+                    state.gpio_devices.push(VirtioDeviceState {
+                        device_id: gpio_dev.config.id.clone(),
+                        pci_device_bdf,
+                        device_state,
+                        transport_state,
+                    });
+                }
+                VirtioDeviceType::I2c => {
+                    let i2c_dev = locked_virtio_dev
+                        .as_mut_any()
+                        .downcast_mut::<I2c>()
+                        .unwrap();
+                    let device_state = i2c_dev.save();
+                    state.i2c_devices.push(VirtioDeviceState {
+                        device_id: i2c_dev.config.id.clone(),
+                        pci_device_bdf,
+                        device_state,
+                        transport_state,
+                    });
+                }
                 VirtioDeviceType::Mem => {
                     let mem_dev = locked_virtio_dev
                         .as_mut_any()
@@ -422,7 +586,17 @@ impl<'a> Persist<'a> for PciDevices {
                     })
                 }
                 VirtioDeviceType::Rdma => {
-                    warn!("Skipping rdma device. Snapshotting is not supported yet");
+                    let rdma_dev = locked_virtio_dev
+                        .as_mut_any()
+                        .downcast_mut::<VirtioRdma>()
+                        .unwrap();
+                    let device_state = rdma_dev.save();
+                    state.rdma_devices.push(VirtioDeviceState {
+                        device_id: rdma_dev.id().to_string(),
+                        pci_device_bdf,
+                        device_state,
+                        transport_state,
+                    });
                 }
             }
         }
@@ -441,6 +615,12 @@ impl<'a> Persist<'a> for PciDevices {
         }
 
         pci_devices.attach_pci_segment(constructor_args.vm)?;
+        // Snapshots of older versions have no hotplug controller, as it is part of the ACPI
+        // tables the guest booted with.
+        #[cfg(target_arch = "x86_64")]
+        if let Some(hotplug_state) = &state.hotplug {
+            pci_devices.attach_hotplug_controller(constructor_args.vm, Some(hotplug_state))?;
+        }
 
         if let Some(balloon_state) = &state.balloon_device {
             let device = Arc::new(Mutex::new(
@@ -626,6 +806,90 @@ impl<'a> Persist<'a> for PciDevices {
                 .unwrap()
         }
 
+        for p9_state in &state.p9_devices {
+            let device = Arc::new(Mutex::new(
+                P9::restore(P9ConstructorArgs { mem }, &p9_state.device_state).unwrap(),
+            ));
+
+            constructor_args.vm_resources.p9.add_device(device.clone());
+
+            pci_devices
+                .restore_pci_device(
+                    constructor_args.vm,
+                    device,
+                    &p9_state.device_id,
+                    &p9_state.transport_state,
+                    constructor_args.event_manager,
+                )
+                .unwrap()
+        }
+
+        for gpio_state in &state.gpio_devices {
+            let device = Arc::new(Mutex::new(
+                Gpio::restore(GpioConstructorArgs { mem }, &gpio_state.device_state).unwrap(),
+            ));
+
+            constructor_args
+                .vm_resources
+                .gpio
+                .add_device(device.clone());
+
+            pci_devices
+                .restore_pci_device(
+                    constructor_args.vm,
+                    device,
+                    &gpio_state.device_id,
+                    &gpio_state.transport_state,
+                    constructor_args.event_manager,
+                )
+                .unwrap()
+        }
+
+        for i2c_state in &state.i2c_devices {
+            let device = Arc::new(Mutex::new(
+                I2c::restore(I2cConstructorArgs { mem }, &i2c_state.device_state).unwrap(),
+            ));
+
+            constructor_args.vm_resources.i2c.add_device(device.clone());
+
+            pci_devices
+                .restore_pci_device(
+                    constructor_args.vm,
+                    device,
+                    &i2c_state.device_id,
+                    &i2c_state.transport_state,
+                    constructor_args.event_manager,
+                )
+                .unwrap()
+        }
+
+        for rdma_state in &state.rdma_devices {
+            let ctor_args = RdmaConstructorArgs {
+                mem,
+                allow_degraded: constructor_args.vm_resources.rdma.allow_degraded_restore(),
+            };
+            let mut rdma = VirtioRdma::restore(ctor_args, &rdma_state.device_state).unwrap();
+            rdma.set_memory_removal_listener(
+                constructor_args.vm.memory_removals().subscribe().unwrap(),
+            );
+            let device = Arc::new(Mutex::new(rdma));
+
+            constructor_args
+                .vm_resources
+                .rdma
+                .add_device(device.clone());
+
+            pci_devices
+                .restore_pci_device(
+                    constructor_args.vm,
+                    device,
+                    &rdma_state.device_id,
+                    &rdma_state.transport_state,
+                    constructor_args.event_manager,
+                )
+                .unwrap()
+        }
+
         if let Some(memory_device) = &state.memory_device {
             let ctor_args = VirtioMemConstructorArgs::new(Arc::clone(constructor_args.vm));
             let device = VirtioMem::restore(ctor_args, &memory_device.device_state).unwrap();
@@ -692,6 +956,7 @@ mod tests {
                 stats_polling_interval_s: 1,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                stats_history_len: 0,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -712,6 +977,8 @@ mod tests {
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                flow_sampling: None,
+                tap_fd: None,
             };
             insert_net_device_with_mmds(
                 &mut vmm,
@@ -726,6 +993,7 @@ mod tests {
                 vsock_id: Some(vsock_dev_id.to_string()),
                 guest_cid: 3,
                 uds_path: tmp_sock_file.as_path().to_str().unwrap().to_string(),
+                io_engine: None,
             };
             insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
             // Add an entropy device.
@@ -15,10 +15,18 @@ use super::mmio::*;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::DeviceType;
 use crate::device_manager::acpi::ACPIDeviceError;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::acpi::power_button::{PowerButton, PowerButtonState};
 use crate::devices::acpi::vmclock::{VmClock, VmClockState};
 use crate::devices::acpi::vmgenid::{VMGenIDState, VmGenId};
+use crate::devices::legacy::FwCfg;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::legacy::Pflash;
 #[cfg(target_arch = "aarch64")]
 use crate::devices::legacy::RTCDevice;
+use crate::devices::legacy::fw_cfg::FwCfgState;
+#[cfg(target_arch = "x86_64")]
+use crate::devices::legacy::pflash::PflashState;
 use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::balloon::persist::{BalloonConstructorArgs, BalloonState};
 use crate::devices::virtio::balloon::{Balloon, BalloonError};
@@ -26,6 +34,14 @@ use crate::devices::virtio::block::BlockError;
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::block::persist::{BlockConstructorArgs, BlockState};
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::gpio::device::Gpio;
+use crate::devices::virtio::gpio::persist::{
+    GpioConstructorArgs, GpioPersistError as GpioError, GpioState,
+};
+use crate::devices::virtio::i2c::device::I2c;
+use crate::devices::virtio::i2c::persist::{
+    I2cConstructorArgs, I2cPersistError as I2cError, I2cState,
+};
 use crate::devices::virtio::mem::VirtioMem;
 use crate::devices::virtio::mem::persist::{
     VirtioMemConstructorArgs, VirtioMemPersistError, VirtioMemState,
@@ -34,11 +50,15 @@ use crate::devices::virtio::net::Net;
 use crate::devices::virtio::net::persist::{
     NetConstructorArgs, NetPersistError as NetError, NetState,
 };
+use crate::devices::virtio::p9::device::P9;
+use crate::devices::virtio::p9::persist::{P9ConstructorArgs, P9PersistError as P9Error, P9State};
 use crate::devices::virtio::persist::{MmioTransportConstructorArgs, MmioTransportState};
 use crate::devices::virtio::pmem::device::Pmem;
 use crate::devices::virtio::pmem::persist::{
     PmemConstructorArgs, PmemPersistError as PmemError, PmemState,
 };
+use crate::devices::virtio::rdma::VirtioRdma;
+use crate::devices::virtio::rdma::persist::{RdmaConstructorArgs, RdmaPersistError, RdmaState};
 use crate::devices::virtio::rng::Entropy;
 use crate::devices::virtio::rng::persist::{
     EntropyConstructorArgs, EntropyPersistError as EntropyError, EntropyState,
@@ -85,6 +105,16 @@ pub enum DevicePersistError {
     Entropy(#[from] EntropyError),
     /// Pmem: {0}
     Pmem(#[from] PmemError),
+    /// 9P: {0}
+    P9(#[from] P9Error),
+    /// GPIO: {0}
+    Gpio(#[from] GpioError),
+    /// I2C: {0}
+    I2c(#[from] I2cError),
+    /// RDMA: {0}
+    Rdma(#[from] RdmaPersistError),
+    /// Cannot subscribe a device to the removals of guest memory: {0}
+    MemoryRemovals(std::io::Error),
     /// virtio-mem: {0}
     VirtioMem(#[from] VirtioMemPersistError),
     /// Could not activate device: {0}
@@ -114,6 +144,25 @@ pub struct ConnectedLegacyState {
     pub device_info: MMIODeviceInfo,
 }
 
+/// Holds the state of a flash device holding the firmware.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectedPflashState {
+    /// Device state.
+    pub state: PflashState,
+    /// VmmResources.
+    pub device_info: MMIODeviceInfo,
+}
+
+/// Holds the state of the fw_cfg device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectedFwCfgState {
+    /// Device state.
+    pub state: FwCfgState,
+    /// VmmResources.
+    pub device_info: MMIODeviceInfo,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MmdsState {
     pub version: MmdsVersion,
@@ -126,6 +175,11 @@ pub struct DeviceStates {
     #[cfg(target_arch = "aarch64")]
     // State of legacy devices in MMIO space.
     pub legacy_devices: Vec<ConnectedLegacyState>,
+    #[cfg(target_arch = "x86_64")]
+    /// Firmware flash device states.
+    pub pflash_devices: Vec<ConnectedPflashState>,
+    /// fw_cfg device state.
+    pub fw_cfg_device: Option<ConnectedFwCfgState>,
     /// Block device states.
     pub block_devices: Vec<VirtioDeviceState<BlockState>>,
     /// Net device states.
@@ -140,6 +194,14 @@ pub struct DeviceStates {
     pub entropy_device: Option<VirtioDeviceState<EntropyState>>,
     /// Pmem device states.
     pub pmem_devices: Vec<VirtioDeviceState<PmemState>>,
+    /// 9P device states.
+    pub p9_devices: Vec<VirtioDeviceState<P9State>>,
+    /// GPIO device states.
+    pub gpio_devices: Vec<VirtioDeviceState<GpioState>>,
+    /// I2C device states.
+    pub i2c_devices: Vec<VirtioDeviceState<I2cState>>,
+    /// RDMA device states.
+    pub rdma_devices: Vec<VirtioDeviceState<RdmaState>>,
     /// Memory device state.
     pub memory_device: Option<VirtioDeviceState<VirtioMemState>>,
 }
@@ -166,8 +228,11 @@ impl fmt::Debug for MMIODevManagerConstructorArgs<'_> {
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct ACPIDeviceManagerState {
-    vmgenid: VMGenIDState,
+    /// State of the VMGenID device, which holds the snapshot epoch.
+    pub vmgenid: VMGenIDState,
     vmclock: VmClockState,
+    #[cfg(target_arch = "x86_64")]
+    power_button: Option<PowerButtonState>,
 }
 
 impl<'a> Persist<'a> for ACPIDeviceManager {
@@ -179,15 +244,20 @@ impl<'a> Persist<'a> for ACPIDeviceManager {
         ACPIDeviceManagerState {
             vmgenid: self.vmgenid.save(),
             vmclock: self.vmclock.save(),
+            #[cfg(target_arch = "x86_64")]
+            power_button: self.power_button.as_ref().map(PowerButton::save),
         }
     }
 
     fn restore(vm: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
-        let acpi_devices = ACPIDeviceManager {
+        #[cfg_attr(target_arch = "aarch64", allow(unused_mut))]
+        let mut acpi_devices = ACPIDeviceManager {
             // Safe to unwrap() here, this will never return an error.
             vmgenid: VmGenId::restore((), &state.vmgenid).unwrap(),
             // Safe to unwrap() here, this will never return an error.
             vmclock: VmClock::restore((), &state.vmclock).unwrap(),
+            #[cfg(target_arch = "x86_64")]
+            power_button: None,
         };
 
         vm.register_irq(
@@ -196,6 +266,12 @@ impl<'a> Persist<'a> for ACPIDeviceManager {
         )?;
 
         acpi_devices.attach_vmgenid(vm)?;
+        #[cfg(target_arch = "x86_64")]
+        if let Some(power_button) = &state.power_button {
+            // Safe to unwrap() here, this will never return an error.
+            acpi_devices
+                .attach_power_button(vm, PowerButton::restore((), power_button).unwrap())?;
+        }
         Ok(acpi_devices)
     }
 }
@@ -225,6 +301,21 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             }
         }
 
+        #[cfg(target_arch = "x86_64")]
+        for device in &self.pflash {
+            states.pflash_devices.push(ConnectedPflashState {
+                state: device.inner.lock().expect("Poisoned lock").save(),
+                device_info: device.resources,
+            });
+        }
+
+        if let Some(device) = &self.fw_cfg {
+            states.fw_cfg_device = Some(ConnectedFwCfgState {
+                state: device.inner.lock().expect("Poisoned lock").save(),
+                device_info: device.resources,
+            });
+        }
+
         let _: Result<(), ()> = self.for_each_virtio_device(|_, devid, device| {
             let mmio_transport_locked = device.inner.lock().expect("Poisoned lock");
             let mut locked_device = mmio_transport_locked.locked_device();
@@ -339,6 +430,36 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         device_info,
                     })
                 }
+                VirtioDeviceType::P9 => {
+                    let p9 = locked_device.as_mut_any().downcast_mut::<P9>().unwrap();
+                    let device_state = p9.save();
+                    states.p9_devices.push(VirtioDeviceState {
+                        device_id,
+                        device_state,
+                        transport_state,
+                        device_info,
+                    })
+                }
+                VirtioDeviceType::Gpio => {
+                    let gpio = locked_device.as_mut_any().downcast_mut::<Gpio>().unwrap();
+                    let device_state = gpio.save();
+                    states.gpio_devices.push(VirtioDeviceState {
+                        device_id,
+                        device_state,
+                        transport_state,
+                        device_info,
+                    })
+                }
+                VirtioDeviceType::I2c => {
+                    let i2c = locked_device.as_mut_any().downcast_mut::<I2c>().unwrap();
+                    let device_state = i2c.save();
+                    states.i2c_devices.push(VirtioDeviceState {
+                        device_id,
+                        device_state,
+                        transport_state,
+                        device_info,
+                    })
+                }
                 VirtioDeviceType::Mem => {
                     let mem = locked_device
                         .as_mut_any()
@@ -354,7 +475,17 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                     });
                 }
                 VirtioDeviceType::Rdma => {
-                    warn!("Skipping rdma device. Snapshotting is not supported yet");
+                    let rdma = locked_device
+                        .as_mut_any()
+                        .downcast_mut::<VirtioRdma>()
+                        .unwrap();
+                    let device_state = rdma.save();
+                    states.rdma_devices.push(VirtioDeviceState {
+                        device_id,
+                        device_state,
+                        transport_state,
+                        device_info,
+                    })
                 }
             };
 
@@ -389,6 +520,26 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             }
         }
 
+        #[cfg(target_arch = "x86_64")]
+        for state in &state.pflash_devices {
+            let pflash = Pflash::restore((), &state.state).map_err(MmioError::from)?;
+            dev_manager.register_mmio_pflash(
+                vm,
+                Arc::new(Mutex::new(pflash)),
+                state.device_info,
+            )?;
+        }
+
+        if let Some(state) = &state.fw_cfg_device {
+            // Safe to unwrap() here, this will never return an error.
+            let fw_cfg = FwCfg::restore((), &state.state).unwrap();
+            dev_manager.register_mmio_fw_cfg(
+                vm,
+                Arc::new(Mutex::new(fw_cfg)),
+                Some(state.device_info),
+            )?;
+        }
+
         let mut restore_helper = |device: Arc<Mutex<dyn VirtioDevice>>,
                                   activated: bool,
                                   is_vhost_user: bool,
@@ -405,10 +556,15 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                 device: device.clone(),
                 is_vhost_user,
             };
-            let mmio_transport = Arc::new(Mutex::new(
-                MmioTransport::restore(restore_args, state)
-                    .map_err(|()| DevicePersistError::MmioTransport)?,
-            ));
+            let mmio_transport = MmioTransport::restore(restore_args, state)
+                .map_err(|()| DevicePersistError::MmioTransport)?;
+            // The shared memory regions of the device are found by the driver where they were.
+            let shm_regions = device.lock().expect("Poisoned lock").shm_regions();
+            for (&(addr, len), (_, region)) in mmio_transport.shm_regions().iter().zip(shm_regions)
+            {
+                vm.common.mmio_bus.insert(region, addr, len)?;
+            }
+            let mmio_transport = Arc::new(Mutex::new(mmio_transport));
 
             dev_manager.register_mmio_virtio(
                 vm,
@@ -597,6 +753,103 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             )?;
         }
 
+        for p9_state in &state.p9_devices {
+            let device = Arc::new(Mutex::new(P9::restore(
+                P9ConstructorArgs { mem },
+                &p9_state.device_state,
+            )?));
+
+            constructor_args.vm_resources.p9.add_device(device.clone());
+
+            restore_helper(
+                device.clone(),
+                p9_state.device_state.virtio_state.activated,
+                false,
+                device,
+                &p9_state.device_id,
+                &p9_state.transport_state,
+                &p9_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
+        for gpio_state in &state.gpio_devices {
+            let device = Arc::new(Mutex::new(Gpio::restore(
+                GpioConstructorArgs { mem },
+                &gpio_state.device_state,
+            )?));
+
+            constructor_args
+                .vm_resources
+                .gpio
+                .add_device(device.clone());
+
+            restore_helper(
+                device.clone(),
+                gpio_state.device_state.virtio_state.activated,
+                false,
+                device,
+                &gpio_state.device_id,
+                &gpio_state.transport_state,
+                &gpio_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
+        for i2c_state in &state.i2c_devices {
+            let device = Arc::new(Mutex::new(I2c::restore(
+                I2cConstructorArgs { mem },
+                &i2c_state.device_state,
+            )?));
+
+            constructor_args.vm_resources.i2c.add_device(device.clone());
+
+            restore_helper(
+                device.clone(),
+                i2c_state.device_state.virtio_state.activated,
+                false,
+                device,
+                &i2c_state.device_id,
+                &i2c_state.transport_state,
+                &i2c_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
+        for rdma_state in &state.rdma_devices {
+            let device = Arc::new(Mutex::new(VirtioRdma::restore(
+                RdmaConstructorArgs {
+                    mem,
+                    allow_degraded: constructor_args.vm_resources.rdma.allow_degraded_restore(),
+                },
+                &rdma_state.device_state,
+            )?));
+            let listener = vm
+                .memory_removals()
+                .subscribe()
+                .map_err(DevicePersistError::MemoryRemovals)?;
+            device
+                .lock()
+                .expect("Poisoned lock")
+                .set_memory_removal_listener(listener);
+
+            constructor_args
+                .vm_resources
+                .rdma
+                .add_device(device.clone());
+
+            restore_helper(
+                device.clone(),
+                rdma_state.device_state.virtio_state.activated,
+                false,
+                device,
+                &rdma_state.device_id,
+                &rdma_state.transport_state,
+                &rdma_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
         if let Some(memory_state) = &state.memory_device {
             let ctor_args = VirtioMemConstructorArgs::new(Arc::clone(vm));
             let device = VirtioMem::restore(ctor_args, &memory_state.device_state)?;
@@ -704,6 +957,7 @@ mod tests {
                 stats_polling_interval_s: 1,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                stats_history_len: 0,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -724,6 +978,8 @@ mod tests {
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                flow_sampling: None,
+                tap_fd: None,
             };
             insert_net_device_with_mmds(
                 &mut vmm,
@@ -738,6 +994,7 @@ mod tests {
                 vsock_id: Some(vsock_dev_id.to_string()),
                 guest_cid: 3,
                 uds_path: tmp_sock_file.as_path().to_str().unwrap().to_string(),
+                io_engine: None,
             };
             insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
             // Add an entropy device.
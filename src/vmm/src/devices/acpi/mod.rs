@@ -2,5 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 mod generated;
+#[cfg(target_arch = "x86_64")]
+pub mod power_button;
 pub mod vmclock;
 pub mod vmgenid;
@@ -0,0 +1,108 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::convert::Infallible;
+
+use acpi_tables::{Aml, aml};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use vm_superio::Trigger;
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::devices::legacy::EventFdTrigger;
+use crate::snapshot::Persist;
+use crate::vstate::resources::ResourceAllocator;
+
+/// ACPI power button device
+///
+/// The microVM uses the hardware-reduced ACPI model, so the power button is a control method
+/// device (`PNP0C0C`). Pressing it raises an interrupt handled by the Generic Event Device, which
+/// notifies the power button device, asking the guest to shut down.
+#[derive(Debug)]
+pub struct PowerButton {
+    /// Interrupt line for notifying the guest about button presses
+    pub interrupt_evt: EventFdTrigger,
+    /// GSI number for the device
+    pub gsi: u32,
+}
+
+impl PowerButton {
+    /// Create a new power button device using the given GSI for sending notifications.
+    pub fn from_gsi(gsi: u32) -> Self {
+        let interrupt_evt = EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK)
+                .expect("power button: Could not create EventFd for power button device"),
+        );
+        Self { interrupt_evt, gsi }
+    }
+
+    /// Create a new power button device
+    pub fn new(resource_allocator: &mut ResourceAllocator) -> Self {
+        let gsi = resource_allocator
+            .allocate_gsi_legacy(1)
+            .expect("power button: Could not allocate GSI for power button");
+        Self::from_gsi(gsi[0])
+    }
+
+    /// Press the power button, notifying the guest.
+    pub fn press(&self) -> Result<(), std::io::Error> {
+        self.interrupt_evt
+            .trigger()
+            .inspect_err(|err| error!("power button: could not send guest notification: {err}"))?;
+        debug!("power button: notifying guest about button press");
+        Ok(())
+    }
+}
+
+/// (De)serialize-able state of the [`PowerButton`]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct PowerButtonState {
+    /// GSI used for notifying the guest about button presses
+    pub gsi: u32,
+}
+
+impl<'a> Persist<'a> for PowerButton {
+    type State = PowerButtonState;
+    type ConstructorArgs = ();
+    type Error = Infallible;
+
+    fn save(&self) -> Self::State {
+        PowerButtonState { gsi: self.gsi }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        Ok(Self::from_gsi(state.gsi))
+    }
+}
+
+impl Aml for PowerButton {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        aml::Device::new(
+            "_SB_.PWRB".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &aml::EisaName::new("PNP0C0C")?)?,
+                &aml::Name::new("_UID".try_into()?, &aml::ZERO)?,
+            ],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_press() {
+        let power_button = PowerButton::from_gsi(5);
+        power_button.press().unwrap();
+        assert_eq!(power_button.interrupt_evt.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let power_button = PowerButton::from_gsi(7);
+        let restored = PowerButton::restore((), &power_button.save()).unwrap();
+        assert_eq!(restored.gsi, 7);
+    }
+}
@@ -8,7 +8,7 @@ use aws_lc_rs::error::Unspecified as RandError;
 use aws_lc_rs::rand;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
-use vm_memory::{GuestAddress, GuestMemoryError};
+use vm_memory::{Address, GuestAddress, GuestMemoryError};
 use vm_superio::Trigger;
 use vmm_sys_util::eventfd::EventFd;
 
@@ -17,8 +17,10 @@ use crate::snapshot::Persist;
 use crate::vstate::memory::{Bytes, GuestMemoryMmap};
 use crate::vstate::resources::ResourceAllocator;
 
-/// Bytes of memory we allocate for VMGenID device
+/// Bytes of memory holding the generation ID
 pub const VMGENID_MEM_SIZE: u64 = 16;
+/// Bytes of memory, right after the generation ID, holding the snapshot epoch
+pub const SNAPSHOT_EPOCH_MEM_SIZE: u64 = 8;
 
 /// Virtual Machine Generation ID device
 ///
@@ -28,6 +30,12 @@ pub const VMGENID_MEM_SIZE: u64 = 16;
 /// microVM is created, either from scratch or restored from a snapshot.
 ///
 /// The device specification can be found here: https://go.microsoft.com/fwlink/?LinkId=260709
+///
+/// The generation ID is followed in guest memory by the snapshot epoch, a little-endian 64-bit
+/// counter incremented by every crash-consistent snapshot of the microVM. A guest restored from
+/// such a snapshot reads the epoch the snapshot was taken at. The address of the epoch is exposed
+/// by the `EPOC` object of the device, as `ADDR` does for the generation ID. The layout is part of
+/// the guest ABI described in `docs/snapshotting/snapshot-support.md`.
 #[derive(Debug)]
 pub struct VmGenId {
     /// Current generation ID of guest VM
@@ -38,6 +46,8 @@ pub struct VmGenId {
     pub guest_address: GuestAddress,
     /// GSI number for the device
     pub gsi: u32,
+    /// Number of crash-consistent snapshots taken of the microVM
+    pub snapshot_epoch: u64,
 }
 
 impl VmGenId {
@@ -59,6 +69,7 @@ impl VmGenId {
             interrupt_evt,
             guest_address,
             gsi,
+            snapshot_epoch: 0,
         }
     }
 
@@ -71,7 +82,11 @@ impl VmGenId {
             .expect("vmgenid: Could not allocate GSI for VMGenID");
         // The generation ID needs to live in an 8-byte aligned buffer
         let addr = resource_allocator
-            .allocate_system_memory(VMGENID_MEM_SIZE, 8, vm_allocator::AllocPolicy::LastMatch)
+            .allocate_system_memory(
+                VMGENID_MEM_SIZE + SNAPSHOT_EPOCH_MEM_SIZE,
+                8,
+                vm_allocator::AllocPolicy::LastMatch,
+            )
             .expect("vmgenid: Could not allocate guest RAM for VMGenID");
 
         Self::from_parts(GuestAddress(addr), gsi[0])
@@ -104,8 +119,28 @@ impl VmGenId {
         );
         mem.write_slice(&self.gen_id.to_le_bytes(), self.guest_address)
             .inspect_err(|err| error!("vmgenid: could not write generation ID to guest: {err}"))?;
+        self.write_snapshot_epoch(mem)
+    }
 
-        Ok(())
+    // Guest physical address of the snapshot epoch.
+    fn snapshot_epoch_address(&self) -> GuestAddress {
+        self.guest_address.unchecked_add(VMGENID_MEM_SIZE)
+    }
+
+    fn write_snapshot_epoch(&self, mem: &GuestMemoryMmap) -> Result<(), GuestMemoryError> {
+        mem.write_obj(self.snapshot_epoch.to_le(), self.snapshot_epoch_address())
+            .inspect_err(|err| error!("vmgenid: could not write snapshot epoch to guest: {err}"))
+    }
+
+    /// Increment the snapshot epoch and write it to guest memory, returning the new epoch.
+    pub fn bump_snapshot_epoch(&mut self, mem: &GuestMemoryMmap) -> Result<u64, GuestMemoryError> {
+        self.snapshot_epoch += 1;
+        debug!(
+            "vmgenid: writing snapshot epoch {} to guest",
+            self.snapshot_epoch
+        );
+        self.write_snapshot_epoch(mem)?;
+        Ok(self.snapshot_epoch)
     }
 }
 
@@ -117,6 +152,8 @@ pub struct VMGenIDState {
     pub gsi: u32,
     /// memory address of generation ID
     pub addr: u64,
+    /// number of crash-consistent snapshots taken of the microVM
+    pub snapshot_epoch: u64,
 }
 
 impl<'a> Persist<'a> for VmGenId {
@@ -128,11 +165,14 @@ impl<'a> Persist<'a> for VmGenId {
         VMGenIDState {
             gsi: self.gsi,
             addr: self.guest_address.0,
+            snapshot_epoch: self.snapshot_epoch,
         }
     }
 
     fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
-        Ok(Self::from_parts(GuestAddress(state.addr), state.gsi))
+        let mut vmgenid = Self::from_parts(GuestAddress(state.addr), state.gsi);
+        vmgenid.snapshot_epoch = state.snapshot_epoch;
+        Ok(vmgenid)
     }
 }
 
@@ -141,6 +181,10 @@ impl Aml for VmGenId {
         #[allow(clippy::cast_possible_truncation)]
         let addr_low = self.guest_address.0 as u32;
         let addr_high = (self.guest_address.0 >> 32) as u32;
+        let epoch_addr = self.snapshot_epoch_address().0;
+        #[allow(clippy::cast_possible_truncation)]
+        let epoch_addr_low = epoch_addr as u32;
+        let epoch_addr_high = (epoch_addr >> 32) as u32;
         aml::Device::new(
             "_SB_.VGEN".try_into()?,
             vec![
@@ -151,6 +195,10 @@ impl Aml for VmGenId {
                     "ADDR".try_into()?,
                     &aml::Package::new(vec![&addr_low, &addr_high]),
                 )?,
+                &aml::Name::new(
+                    "EPOC".try_into()?,
+                    &aml::Package::new(vec![&epoch_addr_low, &epoch_addr_high]),
+                )?,
             ],
         )
         .append_aml_bytes(v)
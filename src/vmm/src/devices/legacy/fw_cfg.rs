@@ -0,0 +1,267 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Emulates the MMIO interface of the QEMU firmware configuration device (fw_cfg), through which
+//! Firecracker exposes read-only metadata to the guest.
+//!
+//! Only the traditional interface is implemented, DMA is not offered: the guest writes the big
+//! endian key of an item to the selector register, then reads the content of the item from the
+//! data register. Linux guests built with `CONFIG_FW_CFG_SYSFS` expose the named items under
+//! `/sys/firmware/qemu_fw_cfg/by_name`.
+
+use std::convert::Infallible;
+use std::sync::{Arc, Barrier};
+
+use serde::{Deserialize, Serialize};
+
+use crate::logger::warn;
+use crate::snapshot::Persist;
+use crate::vstate::bus::BusDevice;
+
+/// Key of the signature of the device.
+const FW_CFG_SIGNATURE: u16 = 0x00;
+/// Key of the bitmap of the interfaces supported by the device.
+const FW_CFG_ID: u16 = 0x01;
+/// Key of the directory of the named items.
+const FW_CFG_FILE_DIR: u16 = 0x19;
+/// Key of the first named item.
+const FW_CFG_FILE_FIRST: u16 = 0x20;
+
+const SIGNATURE: &[u8] = b"QEMU";
+/// Only the traditional interface is supported.
+const FEATURE_TRADITIONAL: u32 = 1;
+const ID: &[u8] = &FEATURE_TRADITIONAL.to_le_bytes();
+
+/// Maximum length of the name of an item, including the NUL terminator.
+pub const FW_CFG_MAX_NAME_LEN: usize = 56;
+
+// The registers are laid out the way the Linux driver expects them on each architecture.
+#[cfg(target_arch = "x86_64")]
+const SELECTOR_OFFSET: u64 = 0x0;
+#[cfg(target_arch = "x86_64")]
+const DATA_OFFSET: u64 = 0x1;
+#[cfg(target_arch = "aarch64")]
+const SELECTOR_OFFSET: u64 = 0x8;
+#[cfg(target_arch = "aarch64")]
+const DATA_OFFSET: u64 = 0x0;
+
+/// A named item exposed to the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FwCfgFile {
+    /// Name of the item, such as `opt/org.firecracker/instance-id`.
+    pub name: String,
+    /// Content of the item.
+    pub data: Vec<u8>,
+}
+
+/// The fw_cfg device, holding a fixed set of named items.
+#[derive(Debug)]
+pub struct FwCfg {
+    files: Vec<FwCfgFile>,
+    directory: Vec<u8>,
+    selector: u16,
+    offset: usize,
+}
+
+impl FwCfg {
+    /// Creates a device exposing the given items, in order. The names are expected to fit in
+    /// [`FW_CFG_MAX_NAME_LEN`] bytes.
+    pub fn new(files: Vec<FwCfgFile>) -> Self {
+        let directory = Self::directory(&files);
+        FwCfg {
+            files,
+            directory,
+            selector: FW_CFG_SIGNATURE,
+            offset: 0,
+        }
+    }
+
+    /// Builds the content of the directory item: the big endian count of items, followed by
+    /// one `struct FWCfgFile` per item.
+    fn directory(files: &[FwCfgFile]) -> Vec<u8> {
+        let count = u32::try_from(files.len()).unwrap();
+        let mut directory = count.to_be_bytes().to_vec();
+        for (key, file) in (FW_CFG_FILE_FIRST..).zip(files) {
+            let size = u32::try_from(file.data.len()).unwrap();
+            directory.extend_from_slice(&size.to_be_bytes());
+            directory.extend_from_slice(&key.to_be_bytes());
+            directory.extend_from_slice(&[0; 2]);
+            let mut name = [0u8; FW_CFG_MAX_NAME_LEN];
+            let len = file.name.len().min(FW_CFG_MAX_NAME_LEN - 1);
+            name[..len].copy_from_slice(&file.name.as_bytes()[..len]);
+            directory.extend_from_slice(&name);
+        }
+        directory
+    }
+
+    /// The items exposed to the guest.
+    pub fn files(&self) -> &[FwCfgFile] {
+        &self.files
+    }
+
+    /// Content of the currently selected item. Unknown keys select an empty item.
+    fn selected(&self) -> &[u8] {
+        match self.selector {
+            FW_CFG_SIGNATURE => SIGNATURE,
+            FW_CFG_ID => ID,
+            FW_CFG_FILE_DIR => &self.directory,
+            key => key
+                .checked_sub(FW_CFG_FILE_FIRST)
+                .and_then(|index| self.files.get(usize::from(index)))
+                .map(|file| file.data.as_slice())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl BusDevice for FwCfg {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        if offset != DATA_OFFSET {
+            data.fill(0);
+            return;
+        }
+        // Reads past the end of the item return zeroes.
+        let item = self.selected();
+        let start = self.offset.min(item.len());
+        let end = (start + data.len()).min(item.len());
+        data.fill(0);
+        data[..end - start].copy_from_slice(&item[start..end]);
+        self.offset = self.offset.saturating_add(data.len());
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        match (offset, data) {
+            (SELECTOR_OFFSET, [high, low]) => {
+                self.selector = u16::from_be_bytes([*high, *low]);
+                self.offset = 0;
+            }
+            _ => warn!(
+                "fw_cfg: Unsupported write of {} bytes at offset {offset:#x}",
+                data.len()
+            ),
+        }
+        None
+    }
+}
+
+/// State of a fw_cfg device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FwCfgState {
+    /// The items exposed to the guest.
+    pub files: Vec<FwCfgFile>,
+    /// Key of the selected item.
+    pub selector: u16,
+    /// Offset of the next read in the selected item.
+    pub offset: usize,
+}
+
+impl Persist<'_> for FwCfg {
+    type State = FwCfgState;
+    type ConstructorArgs = ();
+    type Error = Infallible;
+
+    fn save(&self) -> Self::State {
+        FwCfgState {
+            files: self.files.clone(),
+            selector: self.selector,
+            offset: self.offset,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        let mut fw_cfg = Self::new(state.files.clone());
+        fw_cfg.selector = state.selector;
+        fw_cfg.offset = state.offset;
+        Ok(fw_cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(fw_cfg: &mut FwCfg, key: u16) {
+        fw_cfg.write(0, SELECTOR_OFFSET, &key.to_be_bytes());
+    }
+
+    fn read_item(fw_cfg: &mut FwCfg, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        for byte in data.iter_mut() {
+            fw_cfg.read(0, DATA_OFFSET, std::slice::from_mut(byte));
+        }
+        data
+    }
+
+    fn test_files() -> Vec<FwCfgFile> {
+        vec![
+            FwCfgFile {
+                name: String::from("opt/org.firecracker/instance-id"),
+                data: b"vm-0".to_vec(),
+            },
+            FwCfgFile {
+                name: String::from("opt/foo"),
+                data: b"bar".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_signature() {
+        let mut fw_cfg = FwCfg::new(vec![]);
+        // The signature is selected on reset.
+        assert_eq!(read_item(&mut fw_cfg, 4), b"QEMU");
+
+        select(&mut fw_cfg, FW_CFG_SIGNATURE);
+        let mut data = [0; 4];
+        fw_cfg.read(0, DATA_OFFSET, &mut data);
+        assert_eq!(&data, b"QEMU");
+
+        select(&mut fw_cfg, FW_CFG_ID);
+        assert_eq!(read_item(&mut fw_cfg, 4), FEATURE_TRADITIONAL.to_le_bytes());
+    }
+
+    #[test]
+    fn test_directory() {
+        let mut fw_cfg = FwCfg::new(test_files());
+        select(&mut fw_cfg, FW_CFG_FILE_DIR);
+        let directory = read_item(&mut fw_cfg, 4 + 2 * 64);
+        assert_eq!(directory[..4], 2u32.to_be_bytes());
+
+        let entry = &directory[68..132];
+        assert_eq!(entry[..4], 3u32.to_be_bytes());
+        assert_eq!(entry[4..6], (FW_CFG_FILE_FIRST + 1).to_be_bytes());
+        assert_eq!(&entry[8..15], b"opt/foo");
+        assert!(entry[15..].iter().all(|byte| *byte == 0));
+
+        select(&mut fw_cfg, FW_CFG_FILE_FIRST + 1);
+        // Reads past the end of the item return zeroes.
+        assert_eq!(read_item(&mut fw_cfg, 5), b"bar\0\0");
+
+        // Unknown items are empty.
+        select(&mut fw_cfg, FW_CFG_FILE_FIRST + 2);
+        assert_eq!(read_item(&mut fw_cfg, 2), [0, 0]);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut fw_cfg = FwCfg::new(test_files());
+        select(&mut fw_cfg, FW_CFG_FILE_FIRST);
+        fw_cfg.write(0, DATA_OFFSET, b"xx");
+        // A selector written one byte at a time is ignored.
+        fw_cfg.write(0, SELECTOR_OFFSET, &[0]);
+        assert_eq!(read_item(&mut fw_cfg, 4), b"vm-0");
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut fw_cfg = FwCfg::new(test_files());
+        select(&mut fw_cfg, FW_CFG_FILE_FIRST);
+        assert_eq!(read_item(&mut fw_cfg, 2), b"vm");
+
+        let mut restored = FwCfg::restore((), &fw_cfg.save()).unwrap();
+        assert_eq!(restored.files(), test_files());
+        assert_eq!(read_item(&mut restored, 2), b"-0");
+        select(&mut restored, FW_CFG_FILE_DIR);
+        assert_eq!(read_item(&mut restored, 4), 2u32.to_be_bytes());
+    }
+}
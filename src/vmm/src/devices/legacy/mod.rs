@@ -6,7 +6,10 @@
 // found in the THIRD-PARTY file.
 
 //! Implements legacy devices (UART, RTC etc).
+pub mod fw_cfg;
 mod i8042;
+#[cfg(target_arch = "x86_64")]
+pub mod pflash;
 #[cfg(target_arch = "aarch64")]
 pub mod rtc_pl031;
 pub mod serial;
@@ -19,7 +22,10 @@ use serde::ser::SerializeMap;
 use vm_superio::Trigger;
 use vmm_sys_util::eventfd::EventFd;
 
+pub use self::fw_cfg::FwCfg;
 pub use self::i8042::{I8042Device, I8042Error as I8042DeviceError};
+#[cfg(target_arch = "x86_64")]
+pub use self::pflash::Pflash;
 #[cfg(target_arch = "aarch64")]
 pub use self::rtc_pl031::RTCDevice;
 pub use self::serial::{
@@ -68,6 +74,8 @@ impl EventFdTrigger {
 pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
     let mut seq = serializer.serialize_map(Some(1))?;
     seq.serialize_entry("i8042", &i8042::METRICS)?;
+    #[cfg(target_arch = "x86_64")]
+    seq.serialize_entry("pflash", &pflash::METRICS)?;
     #[cfg(target_arch = "aarch64")]
     seq.serialize_entry("rtc", &rtc_pl031::METRICS)?;
     seq.serialize_entry("uart", &serial::METRICS)?;
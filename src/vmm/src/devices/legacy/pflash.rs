@@ -0,0 +1,454 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Emulates the CFI parallel flash (Intel command set) holding the UEFI firmware and its
+//! variable store.
+//!
+//! Only the commands used by firmwares are implemented: read array, read and clear status,
+//! single byte program and block erase.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Barrier};
+
+use kvm_bindings::{KVM_MEM_READONLY, kvm_userspace_memory_region};
+use serde::{Deserialize, Serialize};
+use vm_memory::VolatileMemory;
+use vm_memory::mmap::MmapRegionError;
+
+use crate::Vm;
+use crate::logger::{IncMetric, SharedIncMetric, error, warn};
+use crate::snapshot::Persist;
+use crate::utils::u64_to_usize;
+use crate::vstate::bus::BusDevice;
+use crate::vstate::memory::{Bytes, MmapRegion};
+use crate::vstate::vm::VmError;
+
+/// Size of the erase blocks of the flash.
+pub const PFLASH_BLOCK_SIZE: u64 = 0x1000;
+
+const CMD_READ_ARRAY: u8 = 0xff;
+const CMD_READ_ARRAY_ALT: u8 = 0x00;
+const CMD_READ_STATUS: u8 = 0x70;
+const CMD_CLEAR_STATUS: u8 = 0x50;
+const CMD_PROGRAM: u8 = 0x10;
+const CMD_PROGRAM_ALT: u8 = 0x40;
+const CMD_BLOCK_ERASE: u8 = 0x20;
+const CMD_ERASE_CONFIRM: u8 = 0xd0;
+
+/// The device is ready for a new command.
+const STATUS_READY: u8 = 0x80;
+const STATUS_ERASE_ERROR: u8 = 0x20;
+const STATUS_PROGRAM_ERROR: u8 = 0x10;
+
+/// Metrics specific to the pflash devices.
+#[derive(Debug, Serialize, Default)]
+pub struct PflashDeviceMetrics {
+    /// Number of bytes programmed by the guest.
+    pub program_count: SharedIncMetric,
+    /// Number of blocks erased by the guest.
+    pub erase_count: SharedIncMetric,
+    /// Errors triggered while using the pflash devices.
+    pub error_count: SharedIncMetric,
+}
+
+impl PflashDeviceMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            program_count: SharedIncMetric::new(),
+            erase_count: SharedIncMetric::new(),
+            error_count: SharedIncMetric::new(),
+        }
+    }
+}
+
+/// Stores aggregated metrics
+pub static METRICS: PflashDeviceMetrics = PflashDeviceMetrics::new();
+
+/// Errors associated with the pflash devices.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum PflashError {
+    /// Cannot allocate the flash content: {0}
+    Mmap(MmapRegionError),
+    /// Cannot read the flash image: {0}
+    ReadImage(io::Error),
+    /// Cannot open the file backing the flash: {0}
+    OpenBacking(io::Error),
+    /// Cannot write the file backing the flash: {0}
+    WriteBacking(io::Error),
+    /// Unable to allocate a KVM slot for the flash
+    NoKvmSlotAvailable,
+    /// Cannot map the flash to the guest: {0}
+    SetUserMemoryRegion(VmError),
+}
+
+/// Interpretation of the accesses of the guest, selected by the last command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PflashMode {
+    /// Reads return the content of the flash.
+    #[default]
+    ReadArray,
+    /// Reads return the status register.
+    ReadStatus,
+    /// The next write programs the flash.
+    Program,
+    /// The next write confirms the erase of a block.
+    EraseSetup,
+}
+
+/// File the writes of the guest are persisted to.
+#[derive(Debug)]
+struct PflashBacking {
+    path: String,
+    file: File,
+}
+
+/// A CFI flash mapped in the MMIO space.
+///
+/// Without a backing file, the flash is read-only and mapped to the guest as ROM, so that the
+/// guest executes from it without exits. Otherwise, every access of the guest goes through the
+/// device and the programmed or erased bytes are written to the backing file.
+#[derive(Debug)]
+pub struct Pflash {
+    contents: MmapRegion,
+    backing: Option<PflashBacking>,
+    mode: PflashMode,
+    status: u8,
+}
+
+impl Pflash {
+    /// Creates a flash holding the content of `image`, whose writes are persisted to the file at
+    /// `backing_path` if any.
+    pub fn new(image: &File, backing_path: Option<String>) -> Result<Self, PflashError> {
+        let len = image.metadata().map_err(PflashError::ReadImage)?.len();
+        let mut contents = vec![0u8; u64_to_usize(len)];
+        image
+            .read_exact_at(&mut contents, 0)
+            .map_err(PflashError::ReadImage)?;
+
+        let backing = match backing_path {
+            Some(path) => Some(PflashBacking {
+                file: image.try_clone().map_err(PflashError::OpenBacking)?,
+                path,
+            }),
+            None => None,
+        };
+        Self::from_contents(&contents, backing)
+    }
+
+    fn from_contents(contents: &[u8], backing: Option<PflashBacking>) -> Result<Self, PflashError> {
+        let region = MmapRegion::new(contents.len()).map_err(PflashError::Mmap)?;
+        region
+            .as_volatile_slice()
+            .write_slice(contents, 0)
+            .expect("the region fits the contents");
+
+        Ok(Pflash {
+            contents: region,
+            backing,
+            mode: PflashMode::ReadArray,
+            status: STATUS_READY,
+        })
+    }
+
+    /// Size of the flash in bytes.
+    pub fn size(&self) -> u64 {
+        self.contents.size() as u64
+    }
+
+    /// Whether the flash is mapped to the guest as ROM.
+    pub fn is_read_only(&self) -> bool {
+        self.backing.is_none()
+    }
+
+    /// Maps the read-only flash to the guest at `addr`.
+    pub fn set_mem_region(&self, vm: &Vm, addr: u64) -> Result<(), PflashError> {
+        let next_slot = vm.next_kvm_slot(1).ok_or(PflashError::NoKvmSlotAvailable)?;
+        let memory_region = kvm_userspace_memory_region {
+            slot: next_slot,
+            guest_phys_addr: addr,
+            memory_size: self.size(),
+            userspace_addr: self.contents.as_ptr() as u64,
+            flags: KVM_MEM_READONLY,
+        };
+
+        vm.set_user_memory_region(memory_region)
+            .map_err(PflashError::SetUserMemoryRegion)
+    }
+
+    fn contents(&self) -> Vec<u8> {
+        let mut contents = vec![0u8; self.contents.size()];
+        self.contents
+            .as_volatile_slice()
+            .read_slice(&mut contents, 0)
+            .expect("the contents fit the region");
+        contents
+    }
+
+    /// Writes `data` at `offset` in the flash and in its backing file.
+    fn store(&mut self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        self.contents
+            .as_volatile_slice()
+            .write_slice(data, u64_to_usize(offset))
+            .expect("the offset was checked by the caller");
+        match &self.backing {
+            Some(backing) => backing.file.write_all_at(data, offset),
+            None => Ok(()),
+        }
+    }
+
+    fn program(&mut self, offset: u64, data: &[u8]) {
+        METRICS.program_count.add(data.len() as u64);
+        if let Err(err) = self.store(offset, data) {
+            error!("pflash: Failed to persist the programmed bytes: {err}");
+            METRICS.error_count.inc();
+            self.status |= STATUS_PROGRAM_ERROR;
+        }
+    }
+
+    fn erase(&mut self, offset: u64) {
+        let block = offset - offset % PFLASH_BLOCK_SIZE;
+        let len = PFLASH_BLOCK_SIZE.min(self.size() - block);
+        METRICS.erase_count.inc();
+        if let Err(err) = self.store(block, &vec![0xff; u64_to_usize(len)]) {
+            error!("pflash: Failed to persist the erased block: {err}");
+            METRICS.error_count.inc();
+            self.status |= STATUS_ERASE_ERROR;
+        }
+    }
+
+    fn in_bounds(&self, offset: u64, len: usize) -> bool {
+        offset
+            .checked_add(len as u64)
+            .is_some_and(|end| end <= self.size())
+    }
+}
+
+impl BusDevice for Pflash {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        if !self.in_bounds(offset, data.len()) {
+            METRICS.error_count.inc();
+            return;
+        }
+        match self.mode {
+            PflashMode::ReadArray => self
+                .contents
+                .as_volatile_slice()
+                .read_slice(data, u64_to_usize(offset))
+                .expect("the offset was checked above"),
+            _ => data.fill(self.status),
+        }
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        if self.is_read_only() {
+            METRICS.error_count.inc();
+            return None;
+        }
+        if data.is_empty() || !self.in_bounds(offset, data.len()) {
+            METRICS.error_count.inc();
+            return None;
+        }
+
+        self.mode = match (self.mode, data[0]) {
+            (PflashMode::Program, _) => {
+                self.program(offset, data);
+                self.status |= STATUS_READY;
+                PflashMode::ReadStatus
+            }
+            (PflashMode::EraseSetup, CMD_ERASE_CONFIRM) => {
+                self.erase(offset);
+                self.status |= STATUS_READY;
+                PflashMode::ReadStatus
+            }
+            (_, CMD_READ_ARRAY | CMD_READ_ARRAY_ALT) => PflashMode::ReadArray,
+            (_, CMD_READ_STATUS) => PflashMode::ReadStatus,
+            (_, CMD_CLEAR_STATUS) => {
+                self.status = 0;
+                PflashMode::ReadArray
+            }
+            (_, CMD_PROGRAM | CMD_PROGRAM_ALT) => PflashMode::Program,
+            (_, CMD_BLOCK_ERASE) => PflashMode::EraseSetup,
+            (mode, command) => {
+                warn!("pflash: Unsupported command {command:#04x} in mode {mode:?}");
+                METRICS.error_count.inc();
+                PflashMode::ReadArray
+            }
+        };
+        None
+    }
+}
+
+/// State of a pflash device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PflashState {
+    /// Content of the flash.
+    pub contents: Vec<u8>,
+    /// Path of the file the writes are persisted to.
+    pub backing_path: Option<String>,
+    /// Interpretation of the accesses of the guest.
+    pub mode: PflashMode,
+    /// Status register.
+    pub status: u8,
+}
+
+impl Persist<'_> for Pflash {
+    type State = PflashState;
+    type ConstructorArgs = ();
+    type Error = PflashError;
+
+    fn save(&self) -> Self::State {
+        PflashState {
+            contents: self.contents(),
+            backing_path: self.backing.as_ref().map(|backing| backing.path.clone()),
+            mode: self.mode,
+            status: self.status,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        // The backing file is brought back to the content of the snapshot, so that it keeps
+        // matching what the guest sees.
+        let backing = match &state.backing_path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(path)
+                    .map_err(PflashError::OpenBacking)?;
+                file.set_len(state.contents.len() as u64)
+                    .and_then(|()| file.write_all_at(&state.contents, 0))
+                    .map_err(PflashError::WriteBacking)?;
+                Some(PflashBacking {
+                    path: path.clone(),
+                    file,
+                })
+            }
+            None => None,
+        };
+
+        let mut pflash = Self::from_contents(&state.contents, backing)?;
+        pflash.mode = state.mode;
+        pflash.status = state.status;
+        Ok(pflash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn image(contents: &[u8]) -> TempFile {
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(contents).unwrap();
+        file
+    }
+
+    fn read_byte(pflash: &mut Pflash, offset: u64) -> u8 {
+        let mut data = [0u8];
+        pflash.read(0, offset, &mut data);
+        data[0]
+    }
+
+    #[test]
+    fn test_read_only() {
+        let file = image(&[0xaa; 0x2000]);
+        let mut pflash = Pflash::new(file.as_file(), None).unwrap();
+        assert_eq!(pflash.size(), 0x2000);
+        assert!(pflash.is_read_only());
+
+        // Commands are ignored.
+        pflash.write(0, 0x10, &[CMD_BLOCK_ERASE]);
+        pflash.write(0, 0x10, &[CMD_ERASE_CONFIRM]);
+        assert_eq!(read_byte(&mut pflash, 0x10), 0xaa);
+
+        // Accesses out of the flash are ignored.
+        let mut data = [0u8; 2];
+        pflash.read(0, 0x1fff, &mut data);
+        assert_eq!(data, [0, 0]);
+    }
+
+    #[test]
+    fn test_commands() {
+        let file = image(&[0u8; 0x2000]);
+        let path = file.as_path().to_str().unwrap().to_string();
+        let mut pflash = Pflash::new(file.as_file(), Some(path)).unwrap();
+        assert!(!pflash.is_read_only());
+
+        // Flash detection as done by OVMF: clearing the status reads back the array, and the
+        // status reads back as cleared.
+        pflash.write(0, 0x1000, &[CMD_CLEAR_STATUS]);
+        assert_eq!(read_byte(&mut pflash, 0x1000), 0);
+        pflash.write(0, 0x1000, &[CMD_READ_STATUS]);
+        assert_eq!(read_byte(&mut pflash, 0x1000), 0);
+        pflash.write(0, 0x1000, &[CMD_READ_ARRAY]);
+
+        // Erase the second block.
+        pflash.write(0, 0x1004, &[CMD_BLOCK_ERASE]);
+        pflash.write(0, 0x1004, &[CMD_ERASE_CONFIRM]);
+        assert_eq!(read_byte(&mut pflash, 0x1000), STATUS_READY);
+        pflash.write(0, 0x1000, &[CMD_READ_ARRAY]);
+        assert_eq!(read_byte(&mut pflash, 0xfff), 0);
+        assert_eq!(read_byte(&mut pflash, 0x1000), 0xff);
+        assert_eq!(read_byte(&mut pflash, 0x1fff), 0xff);
+
+        // Program bytes.
+        pflash.write(0, 0x1001, &[CMD_PROGRAM]);
+        pflash.write(0, 0x1001, &[0x12]);
+        pflash.write(0, 0x1002, &[CMD_PROGRAM_ALT]);
+        pflash.write(0, 0x1002, &[0x34]);
+        pflash.write(0, 0x1002, &[CMD_READ_ARRAY]);
+        let mut data = [0u8; 4];
+        pflash.read(0, 0x1000, &mut data);
+        assert_eq!(data, [0xff, 0x12, 0x34, 0xff]);
+
+        // The writes are persisted to the backing file.
+        let mut persisted = [0u8; 4];
+        file.as_file()
+            .read_exact_at(&mut persisted, 0x1000)
+            .unwrap();
+        assert_eq!(persisted, data);
+
+        // Unsupported commands go back to read array mode.
+        pflash.write(0, 0x1000, &[CMD_READ_STATUS]);
+        pflash.write(0, 0x1000, &[0x98]);
+        assert_eq!(read_byte(&mut pflash, 0x1001), 0x12);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let file = image(&[0u8; 0x1000]);
+        let path = file.as_path().to_str().unwrap().to_string();
+        let mut pflash = Pflash::new(file.as_file(), Some(path)).unwrap();
+        pflash.write(0, 0x10, &[CMD_PROGRAM]);
+        pflash.write(0, 0x10, &[0x42]);
+
+        let state = pflash.save();
+        assert_eq!(state.mode, PflashMode::ReadStatus);
+
+        // Changes made after the snapshot are reverted in the backing file on restore.
+        pflash.write(0, 0x10, &[CMD_PROGRAM]);
+        pflash.write(0, 0x10, &[0x00]);
+        pflash.write(0, 0x10, &[CMD_READ_ARRAY]);
+
+        let mut restored = Pflash::restore((), &state).unwrap();
+        assert_eq!(restored.save().contents, state.contents);
+        restored.write(0, 0x10, &[CMD_READ_ARRAY]);
+        assert_eq!(read_byte(&mut restored, 0x10), 0x42);
+        let mut persisted = [0u8];
+        file.as_file().read_exact_at(&mut persisted, 0x10).unwrap();
+        assert_eq!(persisted[0], 0x42);
+
+        // A read-only flash doesn't need its image on restore.
+        let rom = Pflash::new(image(&[0x5a; 0x1000]).as_file(), None).unwrap();
+        let restored = Pflash::restore((), &rom.save()).unwrap();
+        assert!(restored.is_read_only());
+        assert_eq!(restored.contents(), vec![0x5a; 0x1000]);
+    }
+}
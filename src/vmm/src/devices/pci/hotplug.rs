@@ -0,0 +1,354 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! PCI hotplug controller of the ACPI PCI hotplug protocol.
+//!
+//! The AML of the PCI segment declares a device per slot, whose `_EJ0` method calls
+//! `\_SB_.PHPR.PCEJ`, and a `PCNT` method notifying the slots flagged in the `PCIU` (device
+//! check) and `PCID` (eject request) registers of `\_SB_.PHPR`. This module implements the
+//! `\_SB_.PHPR` device behind those registers. To ask the guest to eject a device, the VMM flags
+//! its slot in `PCID` and raises the interrupt of a Generic Event Device, whose handler calls
+//! `PCNT`. The guest then unbinds the driver of the device and calls `_EJ0`, which writes the
+//! slot to the `B0EJ` register. A device added to a slot is announced the same way, through
+//! `PCIU`, upon which the guest scans the slot and binds a driver to the device.
+
+use std::convert::Infallible;
+use std::io;
+use std::sync::{Arc, Barrier};
+
+use acpi_tables::{Aml, aml};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use vm_allocator::AllocPolicy;
+use vm_superio::Trigger;
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::devices::legacy::EventFdTrigger;
+use crate::snapshot::Persist;
+use crate::vstate::bus::BusDevice;
+use crate::vstate::resources::ResourceAllocator;
+
+/// Size of the register block of the controller.
+pub const PCI_HOTPLUG_MMIO_SIZE: u64 = 0x10;
+
+// Slots to notify with a device check, cleared when read.
+const PCIU_OFFSET: u64 = 0x0;
+// Slots to notify with an eject request, cleared when read.
+const PCID_OFFSET: u64 = 0x4;
+// Slots ejected by the guest, written by `_EJ0`.
+const B0EJ_OFFSET: u64 = 0x8;
+// PCI segment the other registers refer to.
+const PSEG_OFFSET: u64 = 0xc;
+
+/// PCI hotplug controller
+#[derive(Debug)]
+pub struct PciHotplugController {
+    /// Guest physical address of the registers
+    pub address: u64,
+    /// GSI number for the device
+    pub gsi: u32,
+    /// Interrupt line for notifying the guest about slot changes
+    pub interrupt_evt: EventFdTrigger,
+    /// Signalled when the guest ejects slots
+    pub eject_evt: EventFd,
+    // Bitmap of the slots to notify with a device check.
+    slots_up: u32,
+    // Bitmap of the slots to notify with an eject request.
+    slots_down: u32,
+    // Bitmap of the slots ejected by the guest, not yet collected by the VMM.
+    slots_ejected: u32,
+    // Segment selected by the guest.
+    segment: u32,
+}
+
+impl PciHotplugController {
+    /// Create a new controller using the given registers address and GSI.
+    pub fn from_parts(address: u64, gsi: u32) -> Self {
+        let interrupt_evt = EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK)
+                .expect("pci hotplug: Could not create EventFd for interrupts"),
+        );
+        let eject_evt = EventFd::new(libc::EFD_NONBLOCK)
+            .expect("pci hotplug: Could not create EventFd for ejections");
+        Self {
+            address,
+            gsi,
+            interrupt_evt,
+            eject_evt,
+            slots_up: 0,
+            slots_down: 0,
+            slots_ejected: 0,
+            segment: 0,
+        }
+    }
+
+    /// Create a new controller, allocating its registers and GSI.
+    pub fn new(resource_allocator: &mut ResourceAllocator) -> Result<Self, vm_allocator::Error> {
+        let gsi = resource_allocator.allocate_gsi_legacy(1)?[0];
+        let address = resource_allocator.allocate_32bit_mmio_memory(
+            PCI_HOTPLUG_MMIO_SIZE,
+            PCI_HOTPLUG_MMIO_SIZE,
+            AllocPolicy::FirstMatch,
+        )?;
+        Ok(Self::from_parts(address, gsi))
+    }
+
+    /// Ask the guest to eject the device of a slot of segment 0.
+    pub fn request_eject(&mut self, slot: u32) -> Result<(), io::Error> {
+        self.slots_down |= 1 << slot;
+        self.interrupt_evt
+            .trigger()
+            .inspect_err(|err| error!("pci hotplug: could not send guest notification: {err}"))?;
+        debug!("pci hotplug: asking guest to eject slot {slot}");
+        Ok(())
+    }
+
+    /// Ask the guest to check the slot of segment 0 a device was added to.
+    pub fn request_insert(&mut self, slot: u32) -> Result<(), io::Error> {
+        self.slots_up |= 1 << slot;
+        self.interrupt_evt
+            .trigger()
+            .inspect_err(|err| error!("pci hotplug: could not send guest notification: {err}"))?;
+        debug!("pci hotplug: asking guest to check slot {slot}");
+        Ok(())
+    }
+
+    /// Return the bitmap of the slots ejected by the guest since the last call.
+    pub fn take_ejected(&mut self) -> u32 {
+        // The eventfd is non-blocking, and is only used to wake up the VMM.
+        let _ = self.eject_evt.read();
+        std::mem::take(&mut self.slots_ejected)
+    }
+}
+
+impl BusDevice for PciHotplugController {
+    fn read(&mut self, _base: u64, offset: u64, data: &mut [u8]) {
+        if data.len() != 4 {
+            warn!("pci hotplug: invalid read of {} bytes", data.len());
+            return;
+        }
+        let value = match offset {
+            // Only segment 0 has hotplug slots.
+            PCIU_OFFSET | PCID_OFFSET if self.segment != 0 => 0,
+            PCIU_OFFSET => std::mem::take(&mut self.slots_up),
+            PCID_OFFSET => std::mem::take(&mut self.slots_down),
+            PSEG_OFFSET => self.segment,
+            _ => 0,
+        };
+        data.copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn write(&mut self, _base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        let Ok(data) = <[u8; 4]>::try_from(data) else {
+            warn!("pci hotplug: invalid write of {} bytes", data.len());
+            return None;
+        };
+        let value = u32::from_le_bytes(data);
+        match offset {
+            B0EJ_OFFSET if self.segment == 0 => {
+                self.slots_ejected |= value;
+                if let Err(err) = self.eject_evt.write(1) {
+                    error!("pci hotplug: could not signal ejection: {err}");
+                }
+            }
+            B0EJ_OFFSET => warn!("pci hotplug: ejection on segment {}", self.segment),
+            PSEG_OFFSET => self.segment = value,
+            _ => warn!("pci hotplug: invalid write at offset {offset:#x}"),
+        }
+        None
+    }
+}
+
+/// (De)serialize-able state of the [`PciHotplugController`]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct PciHotplugControllerState {
+    /// Guest physical address of the registers
+    pub address: u64,
+    /// GSI used for notifying the guest
+    pub gsi: u32,
+}
+
+impl<'a> Persist<'a> for PciHotplugController {
+    type State = PciHotplugControllerState;
+    type ConstructorArgs = ();
+    type Error = Infallible;
+
+    fn save(&self) -> Self::State {
+        PciHotplugControllerState {
+            address: self.address,
+            gsi: self.gsi,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        Ok(Self::from_parts(state.address, state.gsi))
+    }
+}
+
+impl Aml for PciHotplugController {
+    fn append_aml_bytes(&self, v: &mut Vec<u8>) -> Result<(), aml::AmlError> {
+        let address = u32::try_from(self.address).unwrap();
+        aml::Device::new(
+            "_SB_.PHPR".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &aml::EisaName::new("PNP0A06")?)?,
+                &aml::Name::new("_STA".try_into()?, &0x0bu8)?,
+                &aml::Name::new("_UID".try_into()?, &"PCI Hotplug Controller")?,
+                &aml::Mutex::new("BLCK".try_into()?, 0),
+                &aml::Name::new(
+                    "_CRS".try_into()?,
+                    &aml::ResourceTemplate::new(vec![&aml::Memory32Fixed::new(
+                        true,
+                        address,
+                        u32::try_from(PCI_HOTPLUG_MMIO_SIZE).unwrap(),
+                    )]),
+                )?,
+                &aml::OpRegion::new(
+                    "PCST".try_into()?,
+                    aml::OpRegionSpace::SystemMemory,
+                    usize::try_from(self.address).unwrap(),
+                    usize::try_from(PCI_HOTPLUG_MMIO_SIZE).unwrap(),
+                ),
+                &aml::Field::new(
+                    "PCST".try_into()?,
+                    aml::FieldAccessType::DWord,
+                    aml::FieldUpdateRule::WriteAsZeroes,
+                    vec![
+                        aml::FieldEntry::Named(*b"PCIU", 32),
+                        aml::FieldEntry::Named(*b"PCID", 32),
+                        aml::FieldEntry::Named(*b"B0EJ", 32),
+                        aml::FieldEntry::Named(*b"PSEG", 32),
+                    ],
+                ),
+                // Called by the `_EJ0` method of the slots, with their `_SUN` and `_SEG`.
+                &aml::Method::new(
+                    "PCEJ".try_into()?,
+                    2,
+                    true,
+                    vec![
+                        &aml::Acquire::new("BLCK".try_into()?, 0xffff),
+                        &aml::Store::new(&aml::Path::new("PSEG")?, &aml::Arg(1)),
+                        &aml::ShiftLeft::new(&aml::Path::new("B0EJ")?, &aml::ONE, &aml::Arg(0)),
+                        &aml::Release::new("BLCK".try_into()?),
+                        &aml::Return::new(&aml::ZERO),
+                    ],
+                ),
+                &aml::Method::new(
+                    "PSCN".try_into()?,
+                    0,
+                    true,
+                    vec![&aml::MethodCall::new(
+                        "\\_SB_.PC00.PCNT".try_into()?,
+                        vec![],
+                    )],
+                ),
+            ],
+        )
+        .append_aml_bytes(v)?;
+
+        // Generic Event Device scanning the slots when the controller raises its interrupt.
+        aml::Device::new(
+            "_SB_.PGED".try_into()?,
+            vec![
+                &aml::Name::new("_HID".try_into()?, &"ACPI0013")?,
+                &aml::Name::new("_UID".try_into()?, &aml::ONE)?,
+                &aml::Name::new(
+                    "_CRS".try_into()?,
+                    &aml::ResourceTemplate::new(vec![&aml::Interrupt::new(
+                        true, true, false, false, self.gsi,
+                    )]),
+                )?,
+                &aml::Method::new(
+                    "_EVT".try_into()?,
+                    1,
+                    true,
+                    vec![&aml::MethodCall::new(
+                        "\\_SB_.PHPR.PSCN".try_into()?,
+                        vec![],
+                    )],
+                ),
+            ],
+        )
+        .append_aml_bytes(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_reg(controller: &mut PciHotplugController, offset: u64) -> u32 {
+        let mut data = [0u8; 4];
+        controller.read(0, offset, &mut data);
+        u32::from_le_bytes(data)
+    }
+
+    #[test]
+    fn test_request_eject() {
+        let mut controller = PciHotplugController::from_parts(0x1000, 5);
+        controller.request_eject(3).unwrap();
+        controller.request_eject(4).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 2);
+
+        // The eject requests are cleared once read by the guest.
+        assert_eq!(read_reg(&mut controller, PCIU_OFFSET), 0);
+        assert_eq!(read_reg(&mut controller, PCID_OFFSET), 0b11000);
+        assert_eq!(read_reg(&mut controller, PCID_OFFSET), 0);
+
+        // Requests are not reported for the other segments.
+        controller.request_eject(3).unwrap();
+        controller.write(0, PSEG_OFFSET, &1u32.to_le_bytes());
+        assert_eq!(read_reg(&mut controller, PSEG_OFFSET), 1);
+        assert_eq!(read_reg(&mut controller, PCID_OFFSET), 0);
+        controller.write(0, PSEG_OFFSET, &0u32.to_le_bytes());
+        assert_eq!(read_reg(&mut controller, PCID_OFFSET), 0b1000);
+    }
+
+    #[test]
+    fn test_request_insert() {
+        let mut controller = PciHotplugController::from_parts(0x1000, 5);
+        controller.request_insert(3).unwrap();
+        controller.request_eject(4).unwrap();
+        assert_eq!(controller.interrupt_evt.read().unwrap(), 2);
+
+        // Device checks and eject requests are reported separately.
+        assert_eq!(read_reg(&mut controller, PCIU_OFFSET), 0b1000);
+        assert_eq!(read_reg(&mut controller, PCIU_OFFSET), 0);
+        assert_eq!(read_reg(&mut controller, PCID_OFFSET), 0b10000);
+    }
+
+    #[test]
+    fn test_eject() {
+        let mut controller = PciHotplugController::from_parts(0x1000, 5);
+        assert_eq!(controller.take_ejected(), 0);
+
+        controller.write(0, B0EJ_OFFSET, &(1u32 << 3).to_le_bytes());
+        controller.write(0, B0EJ_OFFSET, &(1u32 << 4).to_le_bytes());
+        assert_eq!(controller.eject_evt.read().unwrap(), 2);
+        assert_eq!(controller.take_ejected(), 0b11000);
+        assert_eq!(controller.take_ejected(), 0);
+
+        // Invalid accesses are ignored.
+        controller.write(0, B0EJ_OFFSET, &[1u8]);
+        controller.write(0, PSEG_OFFSET, &1u32.to_le_bytes());
+        controller.write(0, B0EJ_OFFSET, &1u32.to_le_bytes());
+        assert_eq!(controller.take_ejected(), 0);
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let controller = PciHotplugController::from_parts(0x1000, 5);
+        let restored = PciHotplugController::restore((), &controller.save()).unwrap();
+        assert_eq!(restored.address, 0x1000);
+        assert_eq!(restored.gsi, 5);
+    }
+
+    #[test]
+    fn test_aml() {
+        let controller = PciHotplugController::from_parts(0x1000, 5);
+        let mut aml = Vec::new();
+        controller.append_aml_bytes(&mut aml).unwrap();
+        assert!(aml.windows(4).any(|name| name == b"PHPR"));
+        assert!(aml.windows(4).any(|name| name == b"PGED"));
+    }
+}
@@ -1,13 +1,14 @@
 // Copyright 2020 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::VecDeque;
 use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
-use utils::time::TimerFd;
+use utils::time::{ClockType, TimerFd, get_time_ms};
 use vmm_sys_util::eventfd::EventFd;
 
 use super::super::ActivateError;
@@ -18,7 +19,7 @@ use super::util::compact_page_frame_numbers;
 use super::{
     BALLOON_DEV_ID, BALLOON_MIN_NUM_QUEUES, BALLOON_QUEUE_SIZE, DEFLATE_INDEX, FREE_PAGE_HINT_DONE,
     FREE_PAGE_HINT_STOP, INFLATE_INDEX, MAX_PAGE_COMPACT_BUFFER, MAX_PAGES_IN_DESC,
-    MIB_TO_4K_PAGES, STATS_INDEX, VIRTIO_BALLOON_F_DEFLATE_ON_OOM,
+    MAX_STATS_HISTORY_LEN, MIB_TO_4K_PAGES, STATS_INDEX, VIRTIO_BALLOON_F_DEFLATE_ON_OOM,
     VIRTIO_BALLOON_F_FREE_PAGE_HINTING, VIRTIO_BALLOON_F_FREE_PAGE_REPORTING,
     VIRTIO_BALLOON_F_STATS_VQ, VIRTIO_BALLOON_PFN_SHIFT, VIRTIO_BALLOON_S_ALLOC_STALL,
     VIRTIO_BALLOON_S_ASYNC_RECLAIM, VIRTIO_BALLOON_S_ASYNC_SCAN, VIRTIO_BALLOON_S_AVAIL,
@@ -36,6 +37,7 @@ use crate::logger::{IncMetric, log_dev_preview_warning};
 use crate::utils::u64_to_usize;
 use crate::vstate::memory::{
     Address, ByteValued, Bytes, GuestAddress, GuestMemoryExtension, GuestMemoryMmap,
+    GuestMemoryRemovals,
 };
 use crate::{impl_device_type, mem_size_mib};
 
@@ -135,10 +137,34 @@ pub struct BalloonConfig {
     /// Free page reporting enabled
     #[serde(default)]
     pub free_page_reporting: bool,
+    /// Number of statistics samples retained in the history.
+    #[serde(default)]
+    pub stats_history_len: u16,
 }
 
-/// BalloonStats holds statistics returned from the stats_queue.
+/// A past statistics update, as retained in the history of the balloon.
 #[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize)]
+pub struct BalloonStatsSample {
+    /// Host wall-clock time at which the guest reported the statistics, in milliseconds since
+    /// the Unix epoch.
+    pub timestamp_ms: u64,
+    /// The target size of the balloon, in MiB.
+    pub target_mib: u32,
+    /// The number of MiB the device was holding.
+    pub actual_mib: u32,
+    /// The amount of memory not being used for any purpose (in bytes).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_memory: Option<u64>,
+    /// Total amount of memory available (in bytes).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_memory: Option<u64>,
+    /// An estimate of how much memory is available (in bytes) for starting new applications.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_memory: Option<u64>,
+}
+
+/// BalloonStats holds statistics returned from the stats_queue.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct BalloonStats {
     /// The target size of the balloon, in 4K pages.
@@ -203,6 +229,9 @@ pub struct BalloonStats {
     /// Amount of memory reclaimed directly. since linux v6.12.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub direct_reclaim: Option<u64>,
+    /// The most recent statistics updates, oldest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<BalloonStatsSample>,
 }
 
 impl BalloonStats {
@@ -254,11 +283,16 @@ pub struct Balloon {
     // it is acknowledged after the stats queue is processed.
     pub(crate) stats_desc_index: Option<u16>,
     pub(crate) latest_stats: BalloonStats,
+    // The last `stats_history_len` statistics updates.
+    pub(crate) stats_history_len: u16,
+    pub(crate) stats_history: VecDeque<BalloonStatsSample>,
     // A buffer used as pfn accumulator during descriptor processing.
     pub(crate) pfn_buffer: [u32; MAX_PAGE_COMPACT_BUFFER],
 
     // Holds state for free page hinting
     pub(crate) hinting_state: HintingState,
+    // Notifies the other devices of the memory given to the balloon.
+    pub(crate) memory_removals: Option<Arc<GuestMemoryRemovals>>,
 }
 
 impl Balloon {
@@ -320,8 +354,11 @@ impl Balloon {
             stats_timer,
             stats_desc_index: None,
             latest_stats: BalloonStats::default(),
+            stats_history_len: 0,
+            stats_history: VecDeque::new(),
             pfn_buffer: [0u32; MAX_PAGE_COMPACT_BUFFER],
             hinting_state: Default::default(),
+            memory_removals: None,
         })
     }
 
@@ -365,6 +402,12 @@ impl Balloon {
         self.process_free_page_reporting_queue()
     }
 
+    /// Sets the notifier through which the other devices learn about the memory inflating the
+    /// balloon removes from the guest.
+    pub fn set_memory_removals(&mut self, memory_removals: Arc<GuestMemoryRemovals>) {
+        self.memory_removals = Some(memory_removals);
+    }
+
     pub(crate) fn process_inflate(&mut self) -> Result<(), BalloonError> {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = &self
@@ -441,12 +484,13 @@ impl Balloon {
                 let guest_addr =
                     GuestAddress(u64::from(page_frame_number) << VIRTIO_BALLOON_PFN_SHIFT);
 
-                if let Err(err) = mem.discard_range(
-                    guest_addr,
-                    usize::try_from(range_len).unwrap() << VIRTIO_BALLOON_PFN_SHIFT,
-                ) {
+                let len = usize::try_from(range_len).unwrap() << VIRTIO_BALLOON_PFN_SHIFT;
+                if let Err(err) = mem.discard_range(guest_addr, len) {
                     error!("Error removing memory range: {:?}", err);
                 }
+                if let Some(memory_removals) = &self.memory_removals {
+                    memory_removals.notify(guest_addr, len);
+                }
             }
         }
         queue.advance_used_ring_idx();
@@ -505,11 +549,29 @@ impl Balloon {
             }
 
             self.stats_desc_index = Some(head.index);
+            self.record_stats_sample();
         }
 
         Ok(())
     }
 
+    fn record_stats_sample(&mut self) {
+        if self.stats_history_len == 0 {
+            return;
+        }
+        if self.stats_history.len() == usize::from(self.stats_history_len) {
+            self.stats_history.pop_front();
+        }
+        self.stats_history.push_back(BalloonStatsSample {
+            timestamp_ms: get_time_ms(ClockType::Real),
+            target_mib: pages_to_mib(self.config_space.num_pages),
+            actual_mib: pages_to_mib(self.config_space.actual_pages),
+            free_memory: self.latest_stats.free_memory,
+            total_memory: self.latest_stats.total_memory,
+            available_memory: self.latest_stats.available_memory,
+        });
+    }
+
     pub(crate) fn process_free_page_hinting_queue(&mut self) -> Result<(), BalloonError> {
         let mem = &self
             .device_state
@@ -771,6 +833,18 @@ impl Balloon {
         self.stats_polling_interval_s
     }
 
+    /// Set the number of statistics samples retained in the history.
+    pub fn set_stats_history_len(&mut self, len: u16) -> Result<(), BalloonError> {
+        if len > MAX_STATS_HISTORY_LEN {
+            return Err(BalloonError::StatsHistoryTooLong(MAX_STATS_HISTORY_LEN));
+        }
+        self.stats_history_len = len;
+        while self.stats_history.len() > usize::from(len) {
+            self.stats_history.pop_front();
+        }
+        Ok(())
+    }
+
     /// Retrieve latest stats for the balloon device.
     pub fn latest_stats(&mut self) -> Result<BalloonStats, BalloonError> {
         if self.stats_enabled() {
@@ -778,7 +852,10 @@ impl Balloon {
             self.latest_stats.actual_pages = self.config_space.actual_pages;
             self.latest_stats.target_mib = pages_to_mib(self.latest_stats.target_pages);
             self.latest_stats.actual_mib = pages_to_mib(self.latest_stats.actual_pages);
-            Ok(self.latest_stats)
+            Ok(BalloonStats {
+                history: self.stats_history.iter().copied().collect(),
+                ..self.latest_stats.clone()
+            })
         } else {
             Err(BalloonError::StatisticsDisabled)
         }
@@ -844,6 +921,7 @@ impl Balloon {
             stats_polling_interval_s: self.stats_polling_interval_s(),
             free_page_hinting: self.free_page_hinting(),
             free_page_reporting: self.free_page_reporting(),
+            stats_history_len: self.stats_history_len,
         }
     }
 
@@ -1054,6 +1132,7 @@ pub(crate) mod tests {
             direct_scan: None,
             async_reclaim: None,
             direct_reclaim: None,
+            history: Vec::new(),
         };
 
         let mut stat = BalloonStat {
@@ -1158,6 +1237,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
         assert_eq!(balloon.config(), cfg);
 
@@ -1541,6 +1621,39 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_stats_history() {
+        let mut balloon = Balloon::new(0x10, true, 1, false, false).unwrap();
+        assert!(matches!(
+            balloon.set_stats_history_len(MAX_STATS_HISTORY_LEN + 1),
+            Err(BalloonError::StatsHistoryTooLong(MAX_STATS_HISTORY_LEN))
+        ));
+
+        // Nothing is retained by default.
+        balloon.record_stats_sample();
+        assert!(balloon.latest_stats().unwrap().history.is_empty());
+
+        // Only the most recent samples are retained, oldest first.
+        balloon.set_stats_history_len(2).unwrap();
+        for free_memory in 1..=3 {
+            balloon.latest_stats.free_memory = Some(free_memory);
+            balloon.record_stats_sample();
+        }
+        let history = balloon.latest_stats().unwrap().history;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].free_memory, Some(2));
+        assert_eq!(history[1].free_memory, Some(3));
+        assert_eq!(history[1].target_mib, 0x10);
+        assert!(history[0].timestamp_ms <= history[1].timestamp_ms);
+
+        // Shrinking the history drops the oldest samples.
+        balloon.set_stats_history_len(1).unwrap();
+        let history = balloon.latest_stats().unwrap().history;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].free_memory, Some(3));
+        assert_eq!(balloon.config().stats_history_len, 1);
+    }
+
     #[test]
     fn test_process_reporting() {
         let mem = create_virtio_mem();
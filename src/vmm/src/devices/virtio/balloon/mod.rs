@@ -12,7 +12,7 @@ mod util;
 
 use log::error;
 
-pub use self::device::{Balloon, BalloonConfig, BalloonStats};
+pub use self::device::{Balloon, BalloonConfig, BalloonStats, BalloonStatsSample};
 use super::queue::{InvalidAvailIdx, QueueError};
 use crate::devices::virtio::balloon::metrics::METRICS;
 use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
@@ -43,6 +43,8 @@ pub const INFLATE_INDEX: usize = 0;
 pub const DEFLATE_INDEX: usize = 1;
 /// The index of the stats queue from Balloon device queues/queues_evts vector.
 pub const STATS_INDEX: usize = 2;
+/// The maximum number of statistics samples retained in the history.
+pub const MAX_STATS_HISTORY_LEN: u16 = 1024;
 
 /// Command used in free page hinting to indicate the guest has finished
 pub const FREE_PAGE_HINT_STOP: u32 = 0;
@@ -96,6 +98,8 @@ pub enum BalloonError {
     StatisticsStateChange,
     /// Requested memory should be less than {0}MiB
     TooMuchMemoryRequested(u32),
+    /// The statistics history cannot retain more than {0} samples
+    StatsHistoryTooLong(u16),
     /// Error while processing the virt queues: {0}
     Queue(#[from] QueueError),
     /// {0}
@@ -91,6 +91,7 @@ impl BalloonStatsState {
             direct_scan: self.direct_scan,
             async_reclaim: self.async_reclaim,
             direct_reclaim: self.direct_reclaim,
+            history: Vec::new(),
         }
     }
 }
@@ -100,6 +101,7 @@ impl BalloonStatsState {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BalloonState {
     stats_polling_interval_s: u16,
+    stats_history_len: u16,
     stats_desc_index: Option<u16>,
     latest_stats: BalloonStatsState,
     config_space: BalloonConfigSpaceState,
@@ -122,6 +124,7 @@ impl Persist<'_> for Balloon {
     fn save(&self) -> Self::State {
         BalloonState {
             stats_polling_interval_s: self.stats_polling_interval_s,
+            stats_history_len: self.stats_history_len,
             stats_desc_index: self.stats_desc_index,
             latest_stats: BalloonStatsState::from_stats(&self.latest_stats),
             hinting_state: self.hinting_state,
@@ -180,6 +183,8 @@ impl Persist<'_> for Balloon {
         balloon.avail_features = state.virtio_state.avail_features;
         balloon.acked_features = state.virtio_state.acked_features;
         balloon.latest_stats = state.latest_stats.create_stats();
+        // The samples themselves are not saved, the history starts over after a restore.
+        balloon.stats_history_len = state.stats_history_len;
         balloon.config_space = ConfigSpace {
             num_pages: state.config_space.num_pages,
             actual_pages: state.config_space.actual_pages,
@@ -214,7 +219,8 @@ mod tests {
         let mut mem = vec![0; 4096];
 
         // Create and save the balloon device.
-        let balloon = Balloon::new(0x42, false, 2, false, false).unwrap();
+        let mut balloon = Balloon::new(0x42, false, 2, false, false).unwrap();
+        balloon.set_stats_history_len(4).unwrap();
 
         Snapshot::new(balloon.save())
             .save(&mut mem.as_mut_slice())
@@ -253,6 +259,7 @@ mod tests {
             restored_balloon.stats_polling_interval_s,
             balloon.stats_polling_interval_s
         );
+        assert_eq!(restored_balloon.stats_history_len, 4);
         assert_eq!(restored_balloon.stats_desc_index, balloon.stats_desc_index);
         assert_eq!(restored_balloon.latest_stats, balloon.latest_stats);
     }
@@ -1,15 +1,16 @@
 // Copyright 2023 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use event_manager::{EventOps, Events, MutEventSubscriber};
-use log::info;
+use log::{error, info};
 use vmm_sys_util::eventfd::EventFd;
 
 use super::BlockError;
 use super::persist::{BlockConstructorArgs, BlockState};
 use super::vhost_user::device::{VhostUserBlock, VhostUserBlockConfig};
+use super::virtio::VirtioBlockError;
 use super::virtio::device::{VirtioBlock, VirtioBlockConfig};
 use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
@@ -17,6 +18,7 @@ use crate::devices::virtio::queue::{InvalidAvailIdx, Queue};
 use crate::devices::virtio::transport::VirtioInterrupt;
 use crate::impl_device_type;
 use crate::rate_limiter::BucketUpdate;
+use crate::rate_limiter::cpu_budget::CpuBudget;
 use crate::snapshot::Persist;
 use crate::vmm_config::drive::BlockDeviceConfig;
 use crate::vstate::memory::GuestMemoryMmap;
@@ -31,10 +33,20 @@ pub enum Block {
 
 impl Block {
     pub fn new(config: BlockDeviceConfig) -> Result<Block, BlockError> {
-        if let Ok(config) = VirtioBlockConfig::try_from(&config) {
-            Ok(Self::Virtio(
-                VirtioBlock::new(config).map_err(BlockError::VirtioBackend)?,
-            ))
+        if let Ok(virtio_config) = VirtioBlockConfig::try_from(&config) {
+            let block = match &config.backing_fd {
+                Some(backing_fd) => {
+                    let backing_file = backing_fd
+                        .open(true, !virtio_config.is_read_only)
+                        .map_err(|err| {
+                            VirtioBlockError::BackingFile(err, virtio_config.path_on_host.clone())
+                        })
+                        .map_err(BlockError::VirtioBackend)?;
+                    VirtioBlock::new_with_backing_file(virtio_config, backing_file)
+                }
+                None => VirtioBlock::new(virtio_config),
+            };
+            Ok(Self::Virtio(block.map_err(BlockError::VirtioBackend)?))
         } else if let Ok(config) = VhostUserBlockConfig::try_from(&config) {
             Ok(Self::VhostUser(
                 VhostUserBlock::new(config).map_err(BlockError::VhostUserBackend)?,
@@ -81,6 +93,15 @@ impl Block {
         }
     }
 
+    /// Charges the I/O processing of the device to the given host CPU budget. The I/O of
+    /// vhost-user devices is processed by their backend.
+    pub fn set_cpu_budget(&mut self, budget: &Arc<Mutex<CpuBudget>>) -> std::io::Result<()> {
+        match self {
+            Self::Virtio(b) => b.set_cpu_budget(budget),
+            Self::VhostUser(_) => Ok(()),
+        }
+    }
+
     pub fn process_virtio_queues(&mut self) -> Result<(), InvalidAvailIdx> {
         match self {
             Self::Virtio(b) => b.process_virtio_queues(),
@@ -214,6 +235,23 @@ impl VirtioDevice for Block {
             Self::VhostUser(b) => b.prepare_save(),
         }
     }
+
+    fn drain(&mut self) {
+        // The requests of vhost-user devices are processed by their backend.
+        if let Self::Virtio(b) = self
+            && b.is_activated()
+            && let Err(err) = b.process_virtio_queues()
+        {
+            error!("Failed to drain block device {}: {}", b.id, err);
+        }
+    }
+
+    fn unplug(&mut self) {
+        match self {
+            Self::Virtio(b) => b.unplug(),
+            Self::VhostUser(b) => b.unplug(),
+        }
+    }
 }
 
 impl MutEventSubscriber for Block {
@@ -24,6 +24,15 @@ impl BlockState {
             BlockState::VhostUser(vhost_user_block_state) => false,
         }
     }
+
+    /// Returns the path of the host file backing the device, unless it uses a vhost-user or an NBD
+    /// backend.
+    pub fn disk_path(&self) -> Option<&str> {
+        match self {
+            BlockState::Virtio(virtio_block_state) => virtio_block_state.disk_path(),
+            BlockState::VhostUser(_) => None,
+        }
+    }
 }
 
 /// Auxiliary structure for creating a device when resuming from a snapshot.
@@ -68,12 +68,18 @@ impl TryFrom<&BlockDeviceConfig> for VhostUserBlockConfig {
     type Error = VhostUserBlockError;
 
     fn try_from(value: &BlockDeviceConfig) -> Result<Self, Self::Error> {
-        if let (Some(socket), None, None, None, None) = (
+        if let (Some(socket), None, None, None, None, None, None, None, None, None, None) = (
             &value.socket,
             &value.is_read_only,
             &value.path_on_host,
+            &value.backing_fd,
             &value.rate_limiter,
             &value.file_engine_type,
+            &value.verity,
+            &value.shared_page_cache,
+            &value.strict_ordering,
+            &value.host_cache,
+            &value.nbd_uri,
         ) {
             Ok(Self {
                 drive_id: value.drive_id.clone(),
@@ -99,8 +105,14 @@ impl From<VhostUserBlockConfig> for BlockDeviceConfig {
 
             is_read_only: None,
             path_on_host: None,
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: Some(value.socket),
         }
@@ -407,8 +419,14 @@ mod tests {
 
             is_read_only: None,
             path_on_host: None,
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: Some("sock".to_string()),
         };
@@ -422,8 +440,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some("path".to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -437,8 +461,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some("path".to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: Some("sock".to_string()),
         };
@@ -12,15 +12,17 @@ use std::io::{Seek, SeekFrom};
 use std::ops::Deref;
 use std::os::linux::fs::MetadataExt;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use block_io::FileEngine;
+use block_io::{FileEngine, NbdBackend, NbdUri};
 use serde::{Deserialize, Serialize};
 use vm_memory::ByteValued;
 use vmm_sys_util::eventfd::EventFd;
 
+use super::host_cache::{HostCacheConfig, HostCacheError, ReadAhead};
 use super::io::async_io;
 use super::request::*;
+use super::verity::{Verity, VerityConfig, VerityError};
 use super::{BLOCK_QUEUE_SIZES, SECTOR_SHIFT, SECTOR_SIZE, VirtioBlockError, io as block_io};
 use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::block::CacheType;
@@ -35,7 +37,9 @@ use crate::devices::virtio::queue::{InvalidAvailIdx, Queue};
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
 use crate::impl_device_type;
 use crate::logger::{IncMetric, error, warn};
+use crate::rate_limiter::cpu_budget::{CpuBudget, CpuBudgetHandle};
 use crate::rate_limiter::{BucketUpdate, RateLimiter};
+use crate::utils::file_mapping::{SharedFileMapping, SharedPageCacheConfig};
 use crate::utils::u64_to_usize;
 use crate::vmm_config::RateLimiterConfig;
 use crate::vmm_config::drive::BlockDeviceConfig;
@@ -55,9 +59,14 @@ pub enum FileEngineType {
 #[derive(Debug)]
 pub struct DiskProperties {
     pub file_path: String,
+    pub nbd_uri: Option<String>,
     pub file_engine: FileEngine,
     pub nsectors: u64,
     pub image_id: [u8; VIRTIO_BLK_ID_BYTES as usize],
+    pub verity: Option<Verity>,
+    pub shared_mapping: Option<(SharedPageCacheConfig, SharedFileMapping)>,
+    pub host_cache: HostCacheConfig,
+    pub read_ahead: Option<ReadAhead>,
 }
 
 impl DiskProperties {
@@ -75,7 +84,11 @@ impl DiskProperties {
         let disk_size = disk_image
             .seek(SeekFrom::End(0))
             .map_err(|x| VirtioBlockError::BackingFile(x, disk_image_path.to_string()))?;
+        Self::check_disk_size(disk_size);
+        Ok(disk_size)
+    }
 
+    fn check_disk_size(disk_size: u64) {
         // We only support disk size, which uses the first two words of the configuration space.
         // If the image is not a multiple of the sector size, the tail bits are not exposed.
         if disk_size % u64::from(SECTOR_SIZE) != 0 {
@@ -85,8 +98,39 @@ impl DiskProperties {
                 disk_size, SECTOR_SIZE
             );
         }
+    }
 
-        Ok(disk_size)
+    // Helper function that sets up integrity verification of the backing file
+    fn open_verity(
+        disk_image: &File,
+        is_disk_read_only: bool,
+        file_engine_type: FileEngineType,
+        verity_config: VerityConfig,
+    ) -> Result<Verity, VirtioBlockError> {
+        if !is_disk_read_only {
+            return Err(VirtioBlockError::Verity(VerityError::NotReadOnly));
+        }
+        if file_engine_type != FileEngineType::Sync {
+            return Err(VirtioBlockError::Verity(VerityError::UnsupportedEngine));
+        }
+        let file = disk_image
+            .try_clone()
+            .map_err(|err| VirtioBlockError::Verity(VerityError::BackingFile(err)))?;
+        Verity::new(verity_config, file).map_err(VirtioBlockError::Verity)
+    }
+
+    // Helper function that maps the backing file from the host page cache
+    fn open_shared_mapping(
+        disk_image: &File,
+        is_disk_read_only: bool,
+        config: SharedPageCacheConfig,
+    ) -> Result<(SharedPageCacheConfig, SharedFileMapping), VirtioBlockError> {
+        if !is_disk_read_only {
+            return Err(VirtioBlockError::SharedPageCacheNotReadOnly);
+        }
+        let mapping = SharedFileMapping::new(disk_image, config)
+            .map_err(VirtioBlockError::SharedPageCache)?;
+        Ok((config, mapping))
     }
 
     /// Create a new file for the block device using a FileEngine
@@ -94,20 +138,116 @@ impl DiskProperties {
         disk_image_path: String,
         is_disk_read_only: bool,
         file_engine_type: FileEngineType,
+        verity_config: Option<VerityConfig>,
+        shared_page_cache: Option<SharedPageCacheConfig>,
+    ) -> Result<Self, VirtioBlockError> {
+        let disk_image = Self::open_file(&disk_image_path, is_disk_read_only)?;
+        Self::from_file(
+            disk_image_path,
+            disk_image,
+            is_disk_read_only,
+            file_engine_type,
+            verity_config,
+            shared_page_cache,
+        )
+    }
+
+    /// Create the properties of the block device from an already open backing file
+    pub fn from_file(
+        disk_image_path: String,
+        mut disk_image: File,
+        is_disk_read_only: bool,
+        file_engine_type: FileEngineType,
+        verity_config: Option<VerityConfig>,
+        shared_page_cache: Option<SharedPageCacheConfig>,
     ) -> Result<Self, VirtioBlockError> {
-        let mut disk_image = Self::open_file(&disk_image_path, is_disk_read_only)?;
         let disk_size = Self::file_size(&disk_image_path, &mut disk_image)?;
         let image_id = Self::build_disk_image_id(&disk_image);
+        let verity = verity_config
+            .map(|config| {
+                Self::open_verity(&disk_image, is_disk_read_only, file_engine_type, config)
+            })
+            .transpose()?;
+        let shared_mapping = shared_page_cache
+            .map(|config| Self::open_shared_mapping(&disk_image, is_disk_read_only, config))
+            .transpose()?;
 
         Ok(Self {
             file_path: disk_image_path,
+            nbd_uri: None,
             file_engine: FileEngine::from_file(disk_image, file_engine_type)
                 .map_err(VirtioBlockError::FileEngine)?,
             nsectors: disk_size >> SECTOR_SHIFT,
             image_id,
+            verity,
+            shared_mapping,
+            host_cache: HostCacheConfig::default(),
+            read_ahead: None,
         })
     }
 
+    /// Create the properties of the block device from an export of an NBD server
+    pub fn from_nbd(
+        nbd_uri: String,
+        is_disk_read_only: bool,
+        file_engine_type: FileEngineType,
+        verity_config: Option<VerityConfig>,
+        shared_page_cache: Option<SharedPageCacheConfig>,
+    ) -> Result<Self, VirtioBlockError> {
+        if file_engine_type != FileEngineType::Sync {
+            return Err(VirtioBlockError::NoBackingFile("The Async IO engine"));
+        }
+        if verity_config.is_some() {
+            return Err(VirtioBlockError::NoBackingFile("Integrity verification"));
+        }
+        if shared_page_cache.is_some() {
+            return Err(VirtioBlockError::NoBackingFile("Sharing the page cache"));
+        }
+        let uri = NbdUri::parse(&nbd_uri).map_err(VirtioBlockError::Nbd)?;
+        let backend =
+            NbdBackend::connect(&uri, is_disk_read_only).map_err(VirtioBlockError::Nbd)?;
+        let disk_size = backend.size();
+        Self::check_disk_size(disk_size);
+
+        Ok(Self {
+            file_path: String::new(),
+            nbd_uri: Some(nbd_uri),
+            file_engine: FileEngine::Backend(Box::new(backend)),
+            nsectors: disk_size >> SECTOR_SHIFT,
+            image_id: Self::image_id_from(&uri.export),
+            verity: None,
+            shared_mapping: None,
+            host_cache: HostCacheConfig::default(),
+            read_ahead: None,
+        })
+    }
+
+    /// Set the host page cache policy of the backing file
+    pub fn set_host_cache(&mut self, config: HostCacheConfig) -> Result<(), VirtioBlockError> {
+        config.validate().map_err(VirtioBlockError::HostCache)?;
+        // Integrity verification and shared mappings read the backing file through the page cache.
+        if config.direct_io && self.verity.is_some() {
+            return Err(VirtioBlockError::HostCache(
+                HostCacheError::DirectIoConflict("integrity verification"),
+            ));
+        }
+        if config.direct_io && self.shared_mapping.is_some() {
+            return Err(VirtioBlockError::HostCache(
+                HostCacheError::DirectIoConflict("a shared page cache"),
+            ));
+        }
+
+        let file = self
+            .file_engine
+            .file()
+            .ok_or(VirtioBlockError::NoBackingFile("A host page cache policy"))?;
+        config.apply(file).map_err(VirtioBlockError::HostCache)?;
+        self.read_ahead = ReadAhead::new(file, &config)
+            .map_err(|err| VirtioBlockError::BackingFile(err, self.file_path.clone()))?;
+        self.host_cache = config;
+        Ok(())
+    }
+
     /// Update the path to the file backing the block device
     pub fn update(
         &mut self,
@@ -116,6 +256,29 @@ impl DiskProperties {
     ) -> Result<(), VirtioBlockError> {
         let mut disk_image = Self::open_file(&disk_image_path, is_disk_read_only)?;
         let disk_size = Self::file_size(&disk_image_path, &mut disk_image)?;
+        // The new backing file has to match the root hash the drive was configured with.
+        let verity = self
+            .verity
+            .as_ref()
+            .map(|verity| {
+                Self::open_verity(
+                    &disk_image,
+                    is_disk_read_only,
+                    FileEngineType::Sync,
+                    verity.config().clone(),
+                )
+            })
+            .transpose()?;
+        let shared_mapping = self
+            .shared_mapping
+            .as_ref()
+            .map(|(config, _)| Self::open_shared_mapping(&disk_image, is_disk_read_only, *config))
+            .transpose()?;
+        self.host_cache
+            .apply(&disk_image)
+            .map_err(VirtioBlockError::HostCache)?;
+        let read_ahead = ReadAhead::new(&disk_image, &self.host_cache)
+            .map_err(|x| VirtioBlockError::BackingFile(x, disk_image_path.clone()))?;
 
         self.image_id = Self::build_disk_image_id(&disk_image);
         self.file_engine
@@ -123,6 +286,10 @@ impl DiskProperties {
             .map_err(VirtioBlockError::FileEngine)?;
         self.nsectors = disk_size >> SECTOR_SHIFT;
         self.file_path = disk_image_path;
+        self.nbd_uri = None;
+        self.verity = verity;
+        self.shared_mapping = shared_mapping;
+        self.read_ahead = read_ahead;
 
         Ok(())
     }
@@ -142,20 +309,23 @@ impl DiskProperties {
     }
 
     fn build_disk_image_id(disk_file: &File) -> [u8; VIRTIO_BLK_ID_BYTES as usize] {
-        let mut default_id = [0; VIRTIO_BLK_ID_BYTES as usize];
         match Self::build_device_id(disk_file) {
             Err(_) => {
                 warn!("Could not generate device id. We'll use a default.");
+                [0; VIRTIO_BLK_ID_BYTES as usize]
             }
-            Ok(disk_id_string) => {
-                // The kernel only knows to read a maximum of VIRTIO_BLK_ID_BYTES.
-                // This will also zero out any leftover bytes.
-                let disk_id = disk_id_string.as_bytes();
-                let bytes_to_copy = cmp::min(disk_id.len(), VIRTIO_BLK_ID_BYTES as usize);
-                default_id[..bytes_to_copy].copy_from_slice(&disk_id[..bytes_to_copy]);
-            }
+            Ok(disk_id_string) => Self::image_id_from(&disk_id_string),
         }
-        default_id
+    }
+
+    fn image_id_from(disk_id: &str) -> [u8; VIRTIO_BLK_ID_BYTES as usize] {
+        let mut id = [0; VIRTIO_BLK_ID_BYTES as usize];
+        // The kernel only knows to read a maximum of VIRTIO_BLK_ID_BYTES.
+        // This will also zero out any leftover bytes.
+        let disk_id = disk_id.as_bytes();
+        let bytes_to_copy = cmp::min(disk_id.len(), VIRTIO_BLK_ID_BYTES as usize);
+        id[..bytes_to_copy].copy_from_slice(&disk_id[..bytes_to_copy]);
+        id
     }
 }
 
@@ -197,13 +367,35 @@ pub struct VirtioBlockConfig {
     #[serde(default)]
     #[serde(rename = "io_engine")]
     pub file_engine_type: FileEngineType,
+    /// Integrity verification settings of a read-only drive.
+    #[serde(default)]
+    pub verity: Option<VerityConfig>,
+    /// Serve the reads of a read-only drive from a shared mapping of the host page cache.
+    #[serde(default)]
+    pub shared_page_cache: Option<SharedPageCacheConfig>,
+    /// Make flush requests act as barriers across the IO engine submission queue.
+    #[serde(default)]
+    pub strict_ordering: bool,
+    /// Host page cache policy of the backing file.
+    #[serde(default)]
+    pub host_cache: Option<HostCacheConfig>,
+    /// URI of the NBD export backing the drive, used instead of a host file.
+    #[serde(default)]
+    pub nbd_uri: Option<String>,
 }
 
 impl TryFrom<&BlockDeviceConfig> for VirtioBlockConfig {
     type Error = VirtioBlockError;
 
     fn try_from(value: &BlockDeviceConfig) -> Result<Self, Self::Error> {
-        if let (Some(path_on_host), None) = (&value.path_on_host, &value.socket) {
+        // The path of a passed backing file is optional, and only reported back. NBD drives have
+        // no host file at all.
+        let path_on_host = match (&value.path_on_host, &value.backing_fd, &value.nbd_uri) {
+            (Some(path_on_host), _, None) => Some(path_on_host.clone()),
+            (None, Some(_), None) | (None, None, Some(_)) => Some(String::new()),
+            _ => None,
+        };
+        if let (Some(path_on_host), None) = (path_on_host, &value.socket) {
             Ok(Self {
                 drive_id: value.drive_id.clone(),
                 partuuid: value.partuuid.clone(),
@@ -211,9 +403,14 @@ impl TryFrom<&BlockDeviceConfig> for VirtioBlockConfig {
                 cache_type: value.cache_type,
 
                 is_read_only: value.is_read_only.unwrap_or(false),
-                path_on_host: path_on_host.clone(),
+                path_on_host,
                 rate_limiter: value.rate_limiter,
                 file_engine_type: value.file_engine_type.unwrap_or_default(),
+                verity: value.verity.clone(),
+                shared_page_cache: value.shared_page_cache,
+                strict_ordering: value.strict_ordering.unwrap_or(false),
+                host_cache: value.host_cache,
+                nbd_uri: value.nbd_uri.clone(),
             })
         } else {
             Err(VirtioBlockError::Config)
@@ -230,9 +427,15 @@ impl From<VirtioBlockConfig> for BlockDeviceConfig {
             cache_type: value.cache_type,
 
             is_read_only: Some(value.is_read_only),
-            path_on_host: Some(value.path_on_host),
+            path_on_host: value.nbd_uri.is_none().then_some(value.path_on_host),
+            backing_fd: None,
             rate_limiter: value.rate_limiter,
             file_engine_type: Some(value.file_engine_type),
+            verity: value.verity,
+            shared_page_cache: value.shared_page_cache,
+            strict_ordering: Some(value.strict_ordering),
+            host_cache: value.host_cache,
+            nbd_uri: value.nbd_uri,
 
             socket: None,
         }
@@ -259,11 +462,13 @@ pub struct VirtioBlock {
     pub cache_type: CacheType,
     pub root_device: bool,
     pub read_only: bool,
+    pub strict_ordering: bool,
 
     // Host file and properties.
     pub disk: DiskProperties,
     pub rate_limiter: RateLimiter,
     pub is_io_engine_throttled: bool,
+    pub cpu_budget: Option<CpuBudgetHandle>,
     pub metrics: Arc<BlockDeviceMetrics>,
 }
 
@@ -271,7 +476,7 @@ macro_rules! unwrap_async_file_engine_or_return {
     ($file_engine: expr) => {
         match $file_engine {
             FileEngine::Async(engine) => engine,
-            FileEngine::Sync(_) => {
+            FileEngine::Sync(_) | FileEngine::Backend(_) => {
                 error!("The block device doesn't use an async IO engine");
                 return;
             }
@@ -280,15 +485,58 @@ macro_rules! unwrap_async_file_engine_or_return {
 }
 
 impl VirtioBlock {
-    /// Create a new virtio block device that operates on the given file.
+    /// Create a new virtio block device that operates on the given file, or on the given NBD
+    /// export.
     ///
     /// The given file must be seekable and sizable.
     pub fn new(config: VirtioBlockConfig) -> Result<VirtioBlock, VirtioBlockError> {
-        let disk_properties = DiskProperties::new(
-            config.path_on_host,
+        let disk_properties = match &config.nbd_uri {
+            Some(nbd_uri) => DiskProperties::from_nbd(
+                nbd_uri.clone(),
+                config.is_read_only,
+                config.file_engine_type,
+                config.verity.clone(),
+                config.shared_page_cache,
+            )?,
+            None => DiskProperties::new(
+                config.path_on_host.clone(),
+                config.is_read_only,
+                config.file_engine_type,
+                config.verity.clone(),
+                config.shared_page_cache,
+            )?,
+        };
+        Self::with_disk_properties(config, disk_properties)
+    }
+
+    /// Create a new virtio block device that operates on an already open backing file.
+    ///
+    /// The backing file must have been opened with the access mode of the device.
+    pub fn new_with_backing_file(
+        config: VirtioBlockConfig,
+        backing_file: File,
+    ) -> Result<VirtioBlock, VirtioBlockError> {
+        let disk_properties = DiskProperties::from_file(
+            config.path_on_host.clone(),
+            backing_file,
             config.is_read_only,
             config.file_engine_type,
+            config.verity.clone(),
+            config.shared_page_cache,
         )?;
+        Self::with_disk_properties(config, disk_properties)
+    }
+
+    fn with_disk_properties(
+        config: VirtioBlockConfig,
+        mut disk_properties: DiskProperties,
+    ) -> Result<VirtioBlock, VirtioBlockError> {
+        disk_properties
+            .file_engine
+            .set_strict_ordering(config.strict_ordering);
+        if let Some(host_cache) = config.host_cache {
+            disk_properties.set_host_cache(host_cache)?;
+        }
 
         let rate_limiter = config
             .rate_limiter
@@ -330,10 +578,12 @@ impl VirtioBlock {
             cache_type: config.cache_type,
             root_device: config.is_root_device,
             read_only: config.is_read_only,
+            strict_ordering: config.strict_ordering,
 
             disk: disk_properties,
             rate_limiter,
             is_io_engine_throttled: false,
+            cpu_budget: None,
             metrics: BlockMetricsPerDevice::alloc(config.drive_id),
         })
     }
@@ -350,9 +600,23 @@ impl VirtioBlock {
             cache_type: self.cache_type,
             rate_limiter: rl.into_option(),
             file_engine_type: self.file_engine_type(),
+            verity: self
+                .disk
+                .verity
+                .as_ref()
+                .map(|verity| verity.config().clone()),
+            shared_page_cache: self.disk.shared_mapping.as_ref().map(|(config, _)| *config),
+            strict_ordering: self.strict_ordering,
+            host_cache: self.host_cache(),
+            nbd_uri: self.disk.nbd_uri.clone(),
         }
     }
 
+    /// Returns the host page cache policy of the backing file, if it isn't the default one.
+    pub fn host_cache(&self) -> Option<HostCacheConfig> {
+        Some(self.disk.host_cache).filter(|config| *config != HostCacheConfig::default())
+    }
+
     /// Process a single event in the Virtio queue.
     ///
     /// This function is called by the event manager when the guest notifies us
@@ -554,10 +818,16 @@ impl VirtioBlock {
         self.rate_limiter.update_buckets(bytes, ops);
     }
 
+    /// Charges the I/O processing of the device to the given host CPU budget.
+    pub fn set_cpu_budget(&mut self, budget: &Arc<Mutex<CpuBudget>>) -> std::io::Result<()> {
+        self.cpu_budget = Some(CpuBudget::attach(budget, &self.queue_evts)?);
+        Ok(())
+    }
+
     /// Retrieve the file engine type.
     pub fn file_engine_type(&self) -> FileEngineType {
         match self.disk.file_engine {
-            FileEngine::Sync(_) => FileEngineType::Sync,
+            FileEngine::Sync(_) | FileEngine::Backend(_) => FileEngineType::Sync,
             FileEngine::Async(_) => FileEngineType::Async,
         }
     }
@@ -674,6 +944,12 @@ impl VirtioDevice for VirtioBlock {
     fn is_activated(&self) -> bool {
         self.device_state.is_activated()
     }
+
+    fn unplug(&mut self) {
+        // Complete the in-flight requests and flush the written data before the device stops.
+        self.prepare_save();
+        self.device_state = DeviceState::Inactive;
+    }
 }
 
 impl Drop for VirtioBlock {
@@ -696,22 +972,27 @@ mod tests {
     use std::fs::metadata;
     use std::io::{Read, Write};
     use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixListener;
     use std::thread;
     use std::time::Duration;
 
+    use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
     use crate::check_metric_after_block;
     use crate::devices::virtio::block::virtio::IO_URING_NUM_ENTRIES;
+    use crate::devices::virtio::block::virtio::io::nbd::tests::nbd_server;
     use crate::devices::virtio::block::virtio::test_utils::{
         default_block, read_blk_req_descriptors, set_queue, set_rate_limiter,
         simulate_async_completion_event, simulate_queue_and_async_completion_events,
         simulate_queue_event,
     };
+    use crate::devices::virtio::block::virtio::verity::tests::build_hash_tree;
     use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
     use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
     use crate::rate_limiter::TokenType;
+    use crate::vmm_config::PassedFd;
     use crate::vstate::memory::{Address, Bytes, GuestAddress};
 
     #[test]
@@ -724,8 +1005,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some("path".to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Default::default(),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -739,8 +1026,14 @@ mod tests {
 
             is_read_only: None,
             path_on_host: None,
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Default::default(),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: Some("sock".to_string()),
         };
@@ -754,12 +1047,113 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some("path".to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Default::default(),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: Some("sock".to_string()),
         };
         VirtioBlockConfig::try_from(&block_config).unwrap_err();
+
+        // The path of a passed backing file is optional.
+        let block_config = BlockDeviceConfig {
+            drive_id: "".to_string(),
+            is_read_only: Some(true),
+            backing_fd: Some(PassedFd(TempFile::new().unwrap().into_file())),
+            ..Default::default()
+        };
+        let config = VirtioBlockConfig::try_from(&block_config).unwrap();
+        assert_eq!(config.path_on_host, "");
+
+        // NBD drives have no host file.
+        let block_config = BlockDeviceConfig {
+            drive_id: "".to_string(),
+            nbd_uri: Some("nbd://127.0.0.1/disk".to_string()),
+            ..Default::default()
+        };
+        let config = VirtioBlockConfig::try_from(&block_config).unwrap();
+        assert_eq!(config.path_on_host, "");
+        assert_eq!(BlockDeviceConfig::from(config).path_on_host, None);
+        let block_config = BlockDeviceConfig {
+            drive_id: "".to_string(),
+            path_on_host: Some("path".to_string()),
+            nbd_uri: Some("nbd://127.0.0.1/disk".to_string()),
+            ..Default::default()
+        };
+        VirtioBlockConfig::try_from(&block_config).unwrap_err();
+    }
+
+    #[test]
+    fn test_nbd_drive() {
+        let nbd_config = |nbd_uri: &str| VirtioBlockConfig {
+            drive_id: "nbd".to_string(),
+            partuuid: None,
+            is_root_device: false,
+            cache_type: CacheType::Writeback,
+            is_read_only: true,
+            path_on_host: String::new(),
+            rate_limiter: None,
+            file_engine_type: FileEngineType::Sync,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: false,
+            host_cache: None,
+            nbd_uri: Some(nbd_uri.to_string()),
+        };
+
+        // Features reading the backing file directly are rejected before connecting.
+        let config = VirtioBlockConfig {
+            file_engine_type: FileEngineType::Async,
+            ..nbd_config("nbd://127.0.0.1/disk")
+        };
+        assert!(matches!(
+            VirtioBlock::new(config),
+            Err(VirtioBlockError::NoBackingFile(_))
+        ));
+        let config = VirtioBlockConfig {
+            shared_page_cache: Some(SharedPageCacheConfig::default()),
+            ..nbd_config("nbd://127.0.0.1/disk")
+        };
+        assert!(matches!(
+            VirtioBlock::new(config),
+            Err(VirtioBlockError::NoBackingFile(_))
+        ));
+        assert!(matches!(
+            VirtioBlock::new(nbd_config("/dev/nbd0")),
+            Err(VirtioBlockError::Nbd(block_io::NbdError::InvalidUri(_)))
+        ));
+
+        let dir = TempDir::new().unwrap();
+        let socket = dir.as_path().join("nbd.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            nbd_server(stream, vec![0u8; 0x2000], 0, true)
+                .join()
+                .unwrap()
+        });
+        let nbd_uri = format!("nbd+unix:///disk?socket={}", socket.to_str().unwrap());
+        let mut block = VirtioBlock::new(nbd_config(&nbd_uri)).unwrap();
+        assert_eq!(block.disk.nsectors, 0x10);
+        assert_eq!(&block.disk.image_id[..5], b"disk\0");
+        assert!(block.disk.file_engine.file().is_none());
+        assert_eq!(block.file_engine_type(), FileEngineType::Sync);
+        assert_eq!(block.config(), nbd_config(&nbd_uri));
+        assert!(matches!(
+            block.disk.set_host_cache(HostCacheConfig {
+                read_ahead_kib: 128,
+                ..Default::default()
+            }),
+            Err(VirtioBlockError::NoBackingFile(_))
+        ));
+
+        drop(block);
+        server.join().unwrap();
     }
 
     #[test]
@@ -770,16 +1164,22 @@ mod tests {
         f.as_file().set_len(size).unwrap();
 
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
-            let disk_properties =
-                DiskProperties::new(String::from(f.as_path().to_str().unwrap()), true, engine)
-                    .unwrap();
+            let disk_properties = DiskProperties::new(
+                String::from(f.as_path().to_str().unwrap()),
+                true,
+                engine,
+                None,
+                None,
+            )
+            .unwrap();
 
             assert_eq!(size, u64::from(SECTOR_SIZE) * num_sectors);
             assert_eq!(disk_properties.nsectors, num_sectors);
             // Testing `backing_file.virtio_block_disk_image_id()` implies
             // duplicating that logic in tests, so skipping it.
 
-            let res = DiskProperties::new("invalid-disk-path".to_string(), true, engine);
+            let res =
+                DiskProperties::new("invalid-disk-path".to_string(), true, engine, None, None);
             assert!(
                 matches!(res, Err(VirtioBlockError::BackingFile(_, _))),
                 "{:?}",
@@ -788,6 +1188,145 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_disk_properties_verity() {
+        let f = TempFile::new().unwrap();
+        f.as_file().write_all(&[0xaa; 0x2000]).unwrap();
+        let path = String::from(f.as_path().to_str().unwrap());
+
+        let (tree, root_hash) = build_hash_tree(&[0xaa; 0x2000], 4096);
+        let tree_file = TempFile::new().unwrap();
+        tree_file.as_file().write_all(&tree).unwrap();
+        let verity_config = VerityConfig {
+            root_hash,
+            hash_tree_path: tree_file.as_path().to_str().unwrap().to_string(),
+        };
+
+        let res = DiskProperties::new(
+            path.clone(),
+            false,
+            FileEngineType::Sync,
+            Some(verity_config.clone()),
+            None,
+        );
+        assert!(matches!(
+            res,
+            Err(VirtioBlockError::Verity(VerityError::NotReadOnly))
+        ));
+
+        let res = DiskProperties::new(
+            path.clone(),
+            true,
+            FileEngineType::Async,
+            Some(verity_config.clone()),
+            None,
+        );
+        assert!(matches!(
+            res,
+            Err(VirtioBlockError::Verity(VerityError::UnsupportedEngine))
+        ));
+
+        let mut disk_properties =
+            DiskProperties::new(path, true, FileEngineType::Sync, Some(verity_config), None)
+                .unwrap();
+        assert!(disk_properties.verity.is_some());
+
+        // Swapping in a backing file that doesn't fit the hash tree is refused.
+        let other = TempFile::new().unwrap();
+        other.as_file().write_all(&[0xaa; 0x3000]).unwrap();
+        let res = disk_properties.update(String::from(other.as_path().to_str().unwrap()), true);
+        assert!(matches!(
+            res,
+            Err(VirtioBlockError::Verity(VerityError::DataSize(
+                0x3000, 0x2000
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_disk_properties_shared_page_cache() {
+        let f = TempFile::new().unwrap();
+        f.as_file().write_all(&[0xaa; 0x2000]).unwrap();
+        let path = String::from(f.as_path().to_str().unwrap());
+        let config = SharedPageCacheConfig { prefault: true };
+
+        let res = DiskProperties::new(
+            path.clone(),
+            false,
+            FileEngineType::Sync,
+            None,
+            Some(config),
+        );
+        assert!(matches!(
+            res,
+            Err(VirtioBlockError::SharedPageCacheNotReadOnly)
+        ));
+
+        let mut disk_properties =
+            DiskProperties::new(path, true, FileEngineType::Sync, None, Some(config)).unwrap();
+        let (mapped_config, mapping) = disk_properties.shared_mapping.as_ref().unwrap();
+        assert_eq!(*mapped_config, config);
+        assert_eq!(mapping.as_slice(), &[0xaa; 0x2000]);
+
+        // Updating the backing file maps the new one with the same settings.
+        let other = TempFile::new().unwrap();
+        other.as_file().write_all(&[0xbb; 0x3000]).unwrap();
+        disk_properties
+            .update(String::from(other.as_path().to_str().unwrap()), true)
+            .unwrap();
+        let (mapped_config, mapping) = disk_properties.shared_mapping.as_ref().unwrap();
+        assert_eq!(*mapped_config, config);
+        assert_eq!(mapping.as_slice(), &[0xbb; 0x3000]);
+    }
+
+    #[test]
+    fn test_disk_properties_host_cache() {
+        let f = TempFile::new().unwrap();
+        f.as_file().write_all(&[0xaa; 0x2000]).unwrap();
+        let path = String::from(f.as_path().to_str().unwrap());
+        let direct_io = HostCacheConfig {
+            direct_io: true,
+            ..Default::default()
+        };
+
+        // Direct IO can't be combined with features reading through the page cache.
+        let mut disk_properties = DiskProperties::new(
+            path.clone(),
+            true,
+            FileEngineType::Sync,
+            None,
+            Some(SharedPageCacheConfig::default()),
+        )
+        .unwrap();
+        let res = disk_properties.set_host_cache(direct_io);
+        assert!(matches!(
+            res,
+            Err(VirtioBlockError::HostCache(
+                HostCacheError::DirectIoConflict(_)
+            ))
+        ));
+
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut disk_properties =
+                DiskProperties::new(path.clone(), false, engine, None, None).unwrap();
+            let config = HostCacheConfig {
+                read_ahead_kib: 64,
+                ..Default::default()
+            };
+            disk_properties.set_host_cache(config).unwrap();
+            assert_eq!(disk_properties.host_cache, config);
+            assert!(disk_properties.read_ahead.is_some());
+
+            // Updating the backing file keeps the same settings.
+            let other = TempFile::new().unwrap();
+            disk_properties
+                .update(String::from(other.as_path().to_str().unwrap()), false)
+                .unwrap();
+            assert_eq!(disk_properties.host_cache, config);
+            assert!(disk_properties.read_ahead.is_some());
+        }
+    }
+
     #[test]
     fn test_virtio_features() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1136,9 +1675,16 @@ mod tests {
                     .disk
                     .file_engine
                     .file()
+                    .unwrap()
                     .seek(SeekFrom::Start(0))
                     .unwrap();
-                block.disk.file_engine.file().read_exact(&mut buf).unwrap();
+                block
+                    .disk
+                    .file_engine
+                    .file()
+                    .unwrap()
+                    .read_exact(&mut buf)
+                    .unwrap();
                 assert_eq!(buf, empty_data.as_slice());
             }
 
@@ -1276,9 +1822,16 @@ mod tests {
                     .disk
                     .file_engine
                     .file()
+                    .unwrap()
                     .seek(SeekFrom::End(0))
                     .unwrap();
-                block.disk.file_engine.file().set_len(size / 2).unwrap();
+                block
+                    .disk
+                    .file_engine
+                    .file()
+                    .unwrap()
+                    .set_len(size / 2)
+                    .unwrap();
                 mem.write_obj(10, GuestAddress(request_type_addr.0 + 8))
                     .unwrap();
 
@@ -1310,9 +1863,16 @@ mod tests {
                     .disk
                     .file_engine
                     .file()
+                    .unwrap()
                     .seek(SeekFrom::End(0))
                     .unwrap();
-                block.disk.file_engine.file().set_len(size / 2).unwrap();
+                block
+                    .disk
+                    .file_engine
+                    .file()
+                    .unwrap()
+                    .set_len(size / 2)
+                    .unwrap();
                 // Update sector number: stored at `request_type_addr.0 + 8`
                 mem.write_obj(5, GuestAddress(request_type_addr.0 + 8))
                     .unwrap();
@@ -1359,12 +1919,14 @@ mod tests {
                     .disk
                     .file_engine
                     .file()
+                    .unwrap()
                     .seek(SeekFrom::Start(512))
                     .unwrap();
                 block
                     .disk
                     .file_engine
                     .file()
+                    .unwrap()
                     .write_all(&rand_data[512..])
                     .unwrap();
 
@@ -1486,7 +2048,7 @@ mod tests {
             let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
             let data_addr = GuestAddress(vq.dtable[1].addr.get());
             let status_addr = GuestAddress(vq.dtable[2].addr.get());
-            let blk_metadata = block.disk.file_engine.file().metadata();
+            let blk_metadata = block.disk.file_engine.file().unwrap().metadata();
 
             // Test that the driver receives the correct device id.
             {
@@ -1683,6 +2245,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unplug() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+
+            let mem = default_mem();
+            let interrupt = default_interrupt();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            block.queues[0] = vq.create_queue();
+            block.activate(mem.clone(), interrupt).unwrap();
+
+            add_flush_requests_batch(&mut block, &vq, 5);
+            simulate_queue_event(&mut block, None);
+            block.unplug();
+
+            // The pending requests are completed before the device stops.
+            check_flush_requests_batch(5, &vq);
+            assert!(!block.is_activated());
+        }
+    }
+
     #[test]
     fn test_bandwidth_rate_limiter() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1861,7 +2444,14 @@ mod tests {
                 .unwrap();
 
             assert_eq!(
-                block.disk.file_engine.file().metadata().unwrap().st_ino(),
+                block
+                    .disk
+                    .file_engine
+                    .file()
+                    .unwrap()
+                    .metadata()
+                    .unwrap()
+                    .st_ino(),
                 mdata.st_ino()
             );
             assert_eq!(block.disk.image_id, id.as_slice());
@@ -6,7 +6,8 @@ use vmm_sys_util::epoll::EventSet;
 use super::io::FileEngine;
 use crate::devices::virtio::block::virtio::device::VirtioBlock;
 use crate::devices::virtio::device::VirtioDevice;
-use crate::logger::{error, warn};
+use crate::logger::{IncMetric, error, warn};
+use crate::rate_limiter::cpu_budget::CpuBudgetHandle;
 
 impl VirtioBlock {
     const PROCESS_ACTIVATE: u32 = 0;
@@ -83,6 +84,21 @@ impl MutEventSubscriber for VirtioBlock {
         }
 
         if self.is_activated() {
+            // Guest driven I/O waits for the CPU budget to unblock.
+            if source == Self::PROCESS_QUEUE
+                && self
+                    .cpu_budget
+                    .as_ref()
+                    .is_some_and(CpuBudgetHandle::throttle)
+            {
+                if let Err(err) = self.queue_evts[0].read() {
+                    error!("Failed to get deferred queue event: {:?}", err);
+                    self.metrics.event_fails.inc();
+                }
+                return;
+            }
+
+            let cpu_start_us = self.cpu_budget.as_ref().map(CpuBudgetHandle::start);
             match source {
                 Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
                 Self::PROCESS_QUEUE => self.process_queue_event(),
@@ -90,6 +106,9 @@ impl MutEventSubscriber for VirtioBlock {
                 Self::PROCESS_ASYNC_COMPLETION => self.process_async_completion_event(),
                 _ => warn!("Block: Spurious event received: {:?}", source),
             }
+            if let (Some(cpu_budget), Some(start_us)) = (&self.cpu_budget, cpu_start_us) {
+                cpu_budget.charge_since(start_us);
+            }
         } else {
             warn!(
                 "Block: The device is not yet activated. Spurious event received: {:?}",
@@ -0,0 +1,284 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host page cache policy of virtio block devices.
+//!
+//! The backing file of a drive is accessed through the host page cache by default. A drive can
+//! instead bypass it with `O_DIRECT`, advise the host kernel about the expected access pattern
+//! with `posix_fadvise`, or have the VMM prefetch the data following sequential guest reads into
+//! the page cache.
+
+use std::cmp;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum size of the read-ahead window, in KiB.
+pub const MAX_READ_AHEAD_KIB: u32 = 16 * 1024;
+
+/// Access pattern of the backing file advised to the host kernel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FileAdvice {
+    /// No advice, the host kernel uses its default read-ahead.
+    #[default]
+    Normal,
+    /// The file is read sequentially, the host kernel doubles its read-ahead.
+    Sequential,
+    /// The file is read randomly, the host kernel disables its read-ahead.
+    Random,
+    /// The data is accessed once, the host kernel may drop it from the page cache early.
+    NoReuse,
+}
+
+impl FileAdvice {
+    fn as_raw(self) -> libc::c_int {
+        match self {
+            FileAdvice::Normal => libc::POSIX_FADV_NORMAL,
+            FileAdvice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            FileAdvice::Random => libc::POSIX_FADV_RANDOM,
+            FileAdvice::NoReuse => libc::POSIX_FADV_NOREUSE,
+        }
+    }
+}
+
+/// Host page cache settings of a drive.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostCacheConfig {
+    /// Bypass the host page cache by accessing the backing file with `O_DIRECT`.
+    #[serde(default)]
+    pub direct_io: bool,
+    /// Access pattern of the backing file advised to the host kernel.
+    #[serde(default)]
+    pub advice: FileAdvice,
+    /// Size of the window prefetched after sequential guest reads, in KiB. 0 disables it.
+    #[serde(default)]
+    pub read_ahead_kib: u32,
+}
+
+/// Errors associated with the host page cache policy of a drive.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum HostCacheError {
+    /// Direct IO bypasses the host page cache, so it cannot be combined with {0}.
+    DirectIoConflict(&'static str),
+    /// Invalid read-ahead window of {0} KiB: the maximum is 16384 KiB.
+    ReadAheadTooLarge(u32),
+    /// Cannot open the backing file for direct IO: {0}
+    DirectIo(io::Error),
+    /// Cannot advise the host kernel about the backing file: {0}
+    Advise(io::Error),
+}
+
+fn fadvise(file: &File, offset: u64, len: u64, advice: libc::c_int) -> Result<(), io::Error> {
+    let offset =
+        libc::off_t::try_from(offset).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+    let len = libc::off_t::try_from(len).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+    // SAFETY: The file descriptor is valid and the call doesn't access memory.
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), offset, len, advice) } {
+        0 => Ok(()),
+        err => Err(io::Error::from_raw_os_error(err)),
+    }
+}
+
+impl HostCacheConfig {
+    /// Checks that the settings are consistent.
+    pub fn validate(&self) -> Result<(), HostCacheError> {
+        if self.read_ahead_kib > MAX_READ_AHEAD_KIB {
+            return Err(HostCacheError::ReadAheadTooLarge(self.read_ahead_kib));
+        }
+        if self.direct_io {
+            if self.advice != FileAdvice::Normal {
+                return Err(HostCacheError::DirectIoConflict("a file access advice"));
+            }
+            if self.read_ahead_kib != 0 {
+                return Err(HostCacheError::DirectIoConflict("a read-ahead window"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the settings to a newly opened backing file.
+    pub fn apply(&self, file: &File) -> Result<(), HostCacheError> {
+        if self.direct_io {
+            // SAFETY: The file descriptor is valid and the call doesn't access memory.
+            let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+            if flags < 0 {
+                return Err(HostCacheError::DirectIo(io::Error::last_os_error()));
+            }
+            // SAFETY: The file descriptor is valid and the call doesn't access memory.
+            if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFL, flags | libc::O_DIRECT) } < 0 {
+                return Err(HostCacheError::DirectIo(io::Error::last_os_error()));
+            }
+        }
+        if self.advice != FileAdvice::Normal {
+            fadvise(file, 0, 0, self.advice.as_raw()).map_err(HostCacheError::Advise)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prefetches the data following sequential guest reads into the host page cache.
+#[derive(Debug)]
+pub struct ReadAhead {
+    file: File,
+    window: u64,
+    next_offset: u64,
+    prefetched_until: u64,
+}
+
+impl ReadAhead {
+    /// Creates the read-ahead state of a backing file, `None` if the window is empty.
+    pub fn new(file: &File, config: &HostCacheConfig) -> Result<Option<Self>, io::Error> {
+        if config.read_ahead_kib == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            file: file.try_clone()?,
+            window: u64::from(config.read_ahead_kib) << 10,
+            next_offset: 0,
+            prefetched_until: 0,
+        }))
+    }
+
+    /// Records a guest read of `len` bytes at `offset`. When it continues the previous read, the
+    /// window following it is prefetched once half of the previous window has been consumed.
+    /// Returns whether a prefetch was requested.
+    pub fn on_read(&mut self, offset: u64, len: u32, disk_size: u64) -> bool {
+        let end = offset.saturating_add(u64::from(len));
+        let sequential = offset == self.next_offset;
+        self.next_offset = end;
+        if !sequential {
+            self.prefetched_until = 0;
+            return false;
+        }
+        if end.saturating_add(self.window / 2) <= self.prefetched_until {
+            return false;
+        }
+
+        let start = cmp::max(end, self.prefetched_until);
+        let until = cmp::min(end.saturating_add(self.window), disk_size);
+        if start >= until {
+            return false;
+        }
+        // Read-ahead is best effort, the guest read is served whether or not the hint succeeds.
+        if fadvise(&self.file, start, until - start, libc::POSIX_FADV_WILLNEED).is_err() {
+            return false;
+        }
+        self.prefetched_until = until;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        HostCacheConfig::default().validate().unwrap();
+        HostCacheConfig {
+            direct_io: true,
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+        HostCacheConfig {
+            advice: FileAdvice::Sequential,
+            read_ahead_kib: MAX_READ_AHEAD_KIB,
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+
+        assert!(matches!(
+            HostCacheConfig {
+                read_ahead_kib: MAX_READ_AHEAD_KIB + 1,
+                ..Default::default()
+            }
+            .validate(),
+            Err(HostCacheError::ReadAheadTooLarge(_))
+        ));
+        assert!(matches!(
+            HostCacheConfig {
+                direct_io: true,
+                advice: FileAdvice::Random,
+                ..Default::default()
+            }
+            .validate(),
+            Err(HostCacheError::DirectIoConflict(_))
+        ));
+        assert!(matches!(
+            HostCacheConfig {
+                direct_io: true,
+                read_ahead_kib: 128,
+                ..Default::default()
+            }
+            .validate(),
+            Err(HostCacheError::DirectIoConflict(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply() {
+        let file = TempFile::new().unwrap();
+        HostCacheConfig {
+            advice: FileAdvice::Random,
+            ..Default::default()
+        }
+        .apply(file.as_file())
+        .unwrap();
+
+        // Some file systems, such as tmpfs, don't support direct IO.
+        let direct_io = HostCacheConfig {
+            direct_io: true,
+            ..Default::default()
+        };
+        if direct_io.apply(file.as_file()).is_ok() {
+            // SAFETY: The file descriptor is valid.
+            let flags = unsafe { libc::fcntl(file.as_file().as_raw_fd(), libc::F_GETFL) };
+            assert_ne!(flags & libc::O_DIRECT, 0);
+        }
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let file = TempFile::new().unwrap();
+        file.as_file().write_all(&[0xaa; 0x10000]).unwrap();
+        let disk_size = 0x10000;
+
+        assert!(
+            ReadAhead::new(file.as_file(), &HostCacheConfig::default())
+                .unwrap()
+                .is_none()
+        );
+        let config = HostCacheConfig {
+            read_ahead_kib: 16,
+            ..Default::default()
+        };
+        let mut read_ahead = ReadAhead::new(file.as_file(), &config).unwrap().unwrap();
+
+        // The first read of the disk prefetches the following window.
+        assert!(read_ahead.on_read(0, 0x1000, disk_size));
+        assert_eq!(read_ahead.prefetched_until, 0x5000);
+        // Nothing is prefetched until half of the window has been consumed.
+        assert!(!read_ahead.on_read(0x1000, 0x1000, disk_size));
+        assert!(read_ahead.on_read(0x2000, 0x2000, disk_size));
+        assert_eq!(read_ahead.prefetched_until, 0x8000);
+
+        // Random reads don't prefetch anything.
+        assert!(!read_ahead.on_read(0x8000, 0x1000, disk_size));
+        assert!(!read_ahead.on_read(0x1000, 0x1000, disk_size));
+
+        // The window doesn't go past the end of the disk.
+        assert!(!read_ahead.on_read(0xe000, 0x1000, disk_size));
+        assert!(read_ahead.on_read(0xf000, 0x800, disk_size));
+        assert_eq!(read_ahead.prefetched_until, disk_size);
+        assert!(!read_ahead.on_read(0xf800, 0x800, disk_size));
+    }
+}
@@ -38,6 +38,7 @@ pub struct AsyncFileEngine {
     file: File,
     ring: IoUring<WrappedRequest>,
     completion_evt: EventFd,
+    strict_ordering: bool,
 }
 
 #[derive(Debug)]
@@ -82,6 +83,8 @@ impl AsyncFileEngine {
                 Restriction::AllowOpCode(OpCode::Read),
                 Restriction::AllowOpCode(OpCode::Write),
                 Restriction::AllowOpCode(OpCode::Fsync),
+                // Flushes are submitted as barriers in strict ordering mode.
+                Restriction::AllowIoDrain,
             ],
             Some(completion_fd),
         )
@@ -98,6 +101,7 @@ impl AsyncFileEngine {
             file,
             ring,
             completion_evt,
+            strict_ordering: false,
         })
     }
 
@@ -110,11 +114,15 @@ impl AsyncFileEngine {
         Ok(())
     }
 
-    #[cfg(test)]
     pub fn file(&self) -> &File {
         &self.file
     }
 
+    /// Submit flushes as barriers, so that no request is reordered across them.
+    pub fn set_strict_ordering(&mut self, strict_ordering: bool) {
+        self.strict_ordering = strict_ordering;
+    }
+
     pub fn completion_evt(&self) -> &EventFd {
         &self.completion_evt
     }
@@ -189,9 +197,13 @@ impl AsyncFileEngine {
 
     pub fn push_flush(&mut self, req: PendingRequest) -> Result<(), RequestError<AsyncIoError>> {
         let wrapped_user_data = WrappedRequest::new(req);
+        let mut operation = Operation::fsync(0, wrapped_user_data);
+        if self.strict_ordering {
+            operation.set_drain();
+        }
 
         self.ring
-            .push(Operation::fsync(0, wrapped_user_data))
+            .push(operation)
             .map_err(|(io_uring_error, data)| RequestError {
                 req: data.req,
                 error: AsyncIoError::IoUring(io_uring_error),
@@ -2,12 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod async_io;
+pub mod nbd;
 pub mod sync_io;
 
 use std::fmt::Debug;
 use std::fs::File;
 
 pub use self::async_io::{AsyncFileEngine, AsyncIoError};
+pub use self::nbd::{NbdBackend, NbdError, NbdUri};
 pub use self::sync_io::{SyncFileEngine, SyncIoError};
 use crate::devices::virtio::block::virtio::PendingRequest;
 use crate::devices::virtio::block::virtio::device::FileEngineType;
@@ -31,6 +33,8 @@ pub enum BlockIoError {
     Sync(SyncIoError),
     /// Async error: {0}
     Async(AsyncIoError),
+    /// NBD error: {0}
+    Nbd(NbdError),
 }
 
 impl BlockIoError {
@@ -48,12 +52,75 @@ pub struct RequestError<E> {
     pub error: E,
 }
 
+/// Storage backing a drive, accessed with blocking calls.
+pub trait BlockBackend: Debug + Send {
+    /// Reads `count` bytes at `offset` of the disk into guest memory at `addr`.
+    fn read(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError>;
+
+    /// Writes `count` bytes of guest memory at `addr` to `offset` of the disk.
+    fn write(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError>;
+
+    /// Makes the completed writes durable.
+    fn flush(&mut self) -> Result<(), BlockIoError>;
+}
+
+impl BlockBackend for SyncFileEngine {
+    fn read(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError> {
+        SyncFileEngine::read(self, offset, mem, addr, count).map_err(BlockIoError::Sync)
+    }
+
+    fn write(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError> {
+        SyncFileEngine::write(self, offset, mem, addr, count).map_err(BlockIoError::Sync)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockIoError> {
+        SyncFileEngine::flush(self).map_err(BlockIoError::Sync)
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum FileEngine {
     #[allow(unused)]
     Async(AsyncFileEngine),
     Sync(SyncFileEngine),
+    /// Storage that isn't a host file, such as an NBD export.
+    Backend(Box<dyn BlockBackend>),
+}
+
+// Completes a request executed by a blocking backend.
+fn executed(
+    res: Result<u32, BlockIoError>,
+    req: PendingRequest,
+) -> Result<FileEngineOk, RequestError<BlockIoError>> {
+    match res {
+        Ok(count) => Ok(FileEngineOk::Executed(RequestOk { req, count })),
+        Err(error) => Err(RequestError { req, error }),
+    }
 }
 
 impl FileEngine {
@@ -70,16 +137,26 @@ impl FileEngine {
         match self {
             FileEngine::Async(engine) => engine.update_file(file).map_err(BlockIoError::Async)?,
             FileEngine::Sync(engine) => engine.update_file(file),
+            FileEngine::Backend(_) => *self = FileEngine::Sync(SyncFileEngine::from_file(file)),
         };
 
         Ok(())
     }
 
-    #[cfg(test)]
-    pub fn file(&self) -> &File {
+    pub fn set_strict_ordering(&mut self, strict_ordering: bool) {
+        match self {
+            FileEngine::Async(engine) => engine.set_strict_ordering(strict_ordering),
+            // Requests are executed one at a time, in the order they are received.
+            FileEngine::Sync(_) | FileEngine::Backend(_) => (),
+        }
+    }
+
+    /// Returns the backing file, unless the storage isn't a host file.
+    pub fn file(&self) -> Option<&File> {
         match self {
-            FileEngine::Async(engine) => engine.file(),
-            FileEngine::Sync(engine) => engine.file(),
+            FileEngine::Async(engine) => Some(engine.file()),
+            FileEngine::Sync(engine) => Some(engine.file()),
+            FileEngine::Backend(_) => None,
         }
     }
 
@@ -99,13 +176,10 @@ impl FileEngine {
                     error: BlockIoError::Async(err.error),
                 }),
             },
-            FileEngine::Sync(engine) => match engine.read(offset, mem, addr, count) {
-                Ok(count) => Ok(FileEngineOk::Executed(RequestOk { req, count })),
-                Err(err) => Err(RequestError {
-                    req,
-                    error: BlockIoError::Sync(err),
-                }),
-            },
+            FileEngine::Sync(engine) => {
+                executed(BlockBackend::read(engine, offset, mem, addr, count), req)
+            }
+            FileEngine::Backend(backend) => executed(backend.read(offset, mem, addr, count), req),
         }
     }
 
@@ -125,13 +199,10 @@ impl FileEngine {
                     error: BlockIoError::Async(err.error),
                 }),
             },
-            FileEngine::Sync(engine) => match engine.write(offset, mem, addr, count) {
-                Ok(count) => Ok(FileEngineOk::Executed(RequestOk { req, count })),
-                Err(err) => Err(RequestError {
-                    req,
-                    error: BlockIoError::Sync(err),
-                }),
-            },
+            FileEngine::Sync(engine) => {
+                executed(BlockBackend::write(engine, offset, mem, addr, count), req)
+            }
+            FileEngine::Backend(backend) => executed(backend.write(offset, mem, addr, count), req),
         }
     }
 
@@ -147,20 +218,15 @@ impl FileEngine {
                     error: BlockIoError::Async(err.error),
                 }),
             },
-            FileEngine::Sync(engine) => match engine.flush() {
-                Ok(_) => Ok(FileEngineOk::Executed(RequestOk { req, count: 0 })),
-                Err(err) => Err(RequestError {
-                    req,
-                    error: BlockIoError::Sync(err),
-                }),
-            },
+            FileEngine::Sync(engine) => executed(BlockBackend::flush(engine).map(|_| 0), req),
+            FileEngine::Backend(backend) => executed(backend.flush().map(|_| 0), req),
         }
     }
 
     pub fn drain(&mut self, discard: bool) -> Result<(), BlockIoError> {
         match self {
             FileEngine::Async(engine) => engine.drain(discard).map_err(BlockIoError::Async),
-            FileEngine::Sync(_engine) => Ok(()),
+            FileEngine::Sync(_) | FileEngine::Backend(_) => Ok(()),
         }
     }
 
@@ -169,7 +235,8 @@ impl FileEngine {
             FileEngine::Async(engine) => {
                 engine.drain_and_flush(discard).map_err(BlockIoError::Async)
             }
-            FileEngine::Sync(engine) => engine.flush().map_err(BlockIoError::Sync),
+            FileEngine::Sync(engine) => BlockBackend::flush(engine),
+            FileEngine::Backend(backend) => backend.flush(),
         }
     }
 }
@@ -221,7 +288,7 @@ pub mod tests {
         }
     }
 
-    fn create_mem() -> GuestMemoryMmap {
+    pub(crate) fn create_mem() -> GuestMemoryMmap {
         GuestMemoryMmap::from_regions(
             memory::anonymous(
                 [(GuestAddress(0), MEM_LEN)].into_iter(),
@@ -392,4 +459,28 @@ pub mod tests {
         engine.drain(true).unwrap();
         engine.drain_and_flush(true).unwrap();
     }
+
+    #[test]
+    fn test_async_strict_ordering() {
+        let file = TempFile::new().unwrap().into_file();
+        let mut engine = FileEngine::from_file(file, FileEngineType::Async).unwrap();
+        engine.set_strict_ordering(true);
+
+        let mem = create_mem();
+        let addr = GuestAddress(0);
+        let partial_len = 50;
+        assert_queued!(engine.write(0, &mem, addr, FILE_LEN, PendingRequest::default()));
+        assert_queued!(engine.flush(PendingRequest::default()));
+        assert_queued!(engine.write(0, &mem, addr, partial_len, PendingRequest::default()));
+
+        // The flush is a barrier, so the requests complete in submission order.
+        let FileEngine::Async(engine) = &mut engine else {
+            unreachable!()
+        };
+        engine.drain(false).unwrap();
+        for count in [FILE_LEN, 0, partial_len] {
+            assert_eq!(engine.pop(&mem).unwrap().unwrap().result().unwrap(), count);
+        }
+        assert!(engine.pop(&mem).unwrap().is_none());
+    }
 }
@@ -0,0 +1,679 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client side of the network block device (NBD) protocol.
+//!
+//! The client negotiates an export with the fixed newstyle handshake, using `NBD_OPT_GO` and
+//! falling back to `NBD_OPT_EXPORT_NAME` on servers that don't support it. Requests are then
+//! sent one at a time and only simple replies are expected, since structured replies are never
+//! negotiated. The connection is not re-established if it breaks: every following request fails.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use vm_memory::GuestMemoryError;
+
+use super::{BlockBackend, BlockIoError};
+use crate::vstate::memory::{Bytes, GuestAddress, GuestMemoryMmap};
+
+/// Port of the NBD servers when the URI doesn't specify one.
+pub const NBD_DEFAULT_PORT: u16 = 10809;
+
+const NBD_MAGIC: u64 = 0x4e42_444d_4147_4943;
+const NBD_IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const NBD_OPT_REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const NBD_REQUEST_MAGIC: u32 = 0x2560_9513;
+const NBD_SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+const NBD_FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const NBD_FLAG_NO_ZEROES: u16 = 1 << 1;
+const NBD_FLAG_C_FIXED_NEWSTYLE: u32 = 1 << 0;
+const NBD_FLAG_C_NO_ZEROES: u32 = 1 << 1;
+
+const NBD_OPT_EXPORT_NAME: u32 = 1;
+const NBD_OPT_GO: u32 = 7;
+const NBD_REP_ACK: u32 = 1;
+const NBD_REP_INFO: u32 = 3;
+const NBD_REP_FLAG_ERROR: u32 = 1 << 31;
+const NBD_REP_ERR_UNSUP: u32 = NBD_REP_FLAG_ERROR | 1;
+const NBD_INFO_EXPORT: u16 = 0;
+// Longest export name, and longest option reply accepted from the server.
+const NBD_MAX_STRING: usize = 4096;
+// Padding sent after the export information in reply to `NBD_OPT_EXPORT_NAME`.
+const NBD_EXPORT_NAME_ZEROES: usize = 124;
+
+const NBD_FLAG_READ_ONLY: u16 = 1 << 1;
+const NBD_FLAG_SEND_FLUSH: u16 = 1 << 2;
+
+const NBD_CMD_READ: u16 = 0;
+const NBD_CMD_WRITE: u16 = 1;
+const NBD_CMD_DISC: u16 = 2;
+const NBD_CMD_FLUSH: u16 = 3;
+
+/// Errors of the NBD client.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum NbdError {
+    /// Invalid NBD URI {0}, expected nbd://ip[:port]/export or nbd+unix:///export?socket=path
+    InvalidUri(String),
+    /// Cannot connect to the NBD server: {0}
+    Connect(std::io::Error),
+    /// NBD connection error: {0}
+    Io(std::io::Error),
+    /// The NBD server doesn't support the fixed newstyle handshake
+    Handshake,
+    /// The NBD server rejected export {0:?} with error {1:#x}
+    Export(String, u32),
+    /// The NBD export is read-only but the drive is not
+    ReadOnlyExport,
+    /// Invalid NBD reply magic {0:#x}
+    ReplyMagic(u32),
+    /// NBD reply to request {0} received while waiting for request {1}
+    ReplyCookie(u64, u64),
+    /// The NBD server failed the request with error {0}
+    Server(u32),
+    /// Transfer: {0}
+    Transfer(GuestMemoryError),
+}
+
+/// Where the NBD server listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NbdAddress {
+    /// TCP address of the server.
+    Tcp(SocketAddr),
+    /// Path of a Unix domain socket.
+    Unix(PathBuf),
+}
+
+/// Export of an NBD server, parsed from an `nbd://` or `nbd+unix://` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NbdUri {
+    /// Address of the server.
+    pub address: NbdAddress,
+    /// Name of the export, empty for the default export of the server.
+    pub export: String,
+}
+
+impl NbdUri {
+    /// Parses `nbd://ip[:port]/export` and `nbd+unix:///export?socket=path`.
+    pub fn parse(uri: &str) -> Result<Self, NbdError> {
+        let invalid = || NbdError::InvalidUri(uri.to_string());
+        if uri.len() > NBD_MAX_STRING {
+            return Err(invalid());
+        }
+        if let Some(rest) = uri.strip_prefix("nbd://") {
+            let (host, export) = rest.split_once('/').unwrap_or((rest, ""));
+            if export.contains('?') {
+                return Err(invalid());
+            }
+            // Host names aren't resolved: there is no resolver configuration in the jail.
+            let address = host
+                .parse::<SocketAddr>()
+                .or_else(|_| {
+                    let ip = host.trim_start_matches('[').trim_end_matches(']');
+                    ip.parse::<IpAddr>()
+                        .map(|ip| SocketAddr::new(ip, NBD_DEFAULT_PORT))
+                })
+                .map_err(|_| invalid())?;
+            Ok(Self {
+                address: NbdAddress::Tcp(address),
+                export: export.to_string(),
+            })
+        } else if let Some(rest) = uri.strip_prefix("nbd+unix://") {
+            let (path, query) = rest.split_once('?').ok_or_else(invalid)?;
+            let export = path.strip_prefix('/').ok_or_else(invalid)?;
+            let socket = query.strip_prefix("socket=").ok_or_else(invalid)?;
+            if socket.is_empty() || socket.contains('&') {
+                return Err(invalid());
+            }
+            Ok(Self {
+                address: NbdAddress::Unix(PathBuf::from(socket)),
+                export: export.to_string(),
+            })
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Connection to an NBD server.
+#[derive(Debug)]
+pub enum NbdStream {
+    /// Connection to a TCP address.
+    Tcp(TcpStream),
+    /// Connection to a Unix domain socket.
+    Unix(UnixStream),
+}
+
+impl Read for NbdStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            NbdStream::Tcp(stream) => stream.read(buf),
+            NbdStream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for NbdStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            NbdStream::Tcp(stream) => stream.write(buf),
+            NbdStream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            NbdStream::Tcp(stream) => stream.flush(),
+            NbdStream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Block backend serving the requests of a drive from an NBD export.
+#[derive(Debug)]
+pub struct NbdBackend {
+    stream: NbdStream,
+    size: u64,
+    flags: u16,
+    cookie: u64,
+    buffer: Vec<u8>,
+}
+
+impl NbdBackend {
+    /// Connects to the export of the URI.
+    pub fn connect(uri: &NbdUri, read_only: bool) -> Result<Self, NbdError> {
+        let stream = match &uri.address {
+            NbdAddress::Tcp(address) => {
+                let stream = TcpStream::connect(address).map_err(NbdError::Connect)?;
+                stream.set_nodelay(true).map_err(NbdError::Connect)?;
+                NbdStream::Tcp(stream)
+            }
+            NbdAddress::Unix(path) => {
+                NbdStream::Unix(UnixStream::connect(path).map_err(NbdError::Connect)?)
+            }
+        };
+        Self::from_stream(stream, &uri.export, read_only)
+    }
+
+    /// Negotiates the export over an established connection.
+    pub fn from_stream(
+        mut stream: NbdStream,
+        export: &str,
+        read_only: bool,
+    ) -> Result<Self, NbdError> {
+        let (size, flags) = handshake(&mut stream, export)?;
+        if !read_only && flags & NBD_FLAG_READ_ONLY != 0 {
+            return Err(NbdError::ReadOnlyExport);
+        }
+        Ok(Self {
+            stream,
+            size,
+            flags,
+            cookie: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Size of the export in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // Sends a request and waits for its reply. The data of a read follows the reply.
+    fn request(
+        &mut self,
+        command: u16,
+        offset: u64,
+        length: u32,
+        data: &[u8],
+    ) -> Result<(), NbdError> {
+        self.cookie = self.cookie.wrapping_add(1);
+        let mut header = Vec::with_capacity(28 + data.len());
+        header.extend_from_slice(&NBD_REQUEST_MAGIC.to_be_bytes());
+        header.extend_from_slice(&0u16.to_be_bytes());
+        header.extend_from_slice(&command.to_be_bytes());
+        header.extend_from_slice(&self.cookie.to_be_bytes());
+        header.extend_from_slice(&offset.to_be_bytes());
+        header.extend_from_slice(&length.to_be_bytes());
+        header.extend_from_slice(data);
+        self.stream.write_all(&header).map_err(NbdError::Io)?;
+
+        let mut reply = [0u8; 16];
+        self.stream.read_exact(&mut reply).map_err(NbdError::Io)?;
+        let magic = u32::from_be_bytes(reply[0..4].try_into().unwrap());
+        let error = u32::from_be_bytes(reply[4..8].try_into().unwrap());
+        let cookie = u64::from_be_bytes(reply[8..16].try_into().unwrap());
+        if magic != NBD_SIMPLE_REPLY_MAGIC {
+            return Err(NbdError::ReplyMagic(magic));
+        }
+        if cookie != self.cookie {
+            return Err(NbdError::ReplyCookie(cookie, self.cookie));
+        }
+        if error != 0 {
+            return Err(NbdError::Server(error));
+        }
+        Ok(())
+    }
+
+    fn read_export(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, NbdError> {
+        self.buffer.resize(count as usize, 0);
+        self.request(NBD_CMD_READ, offset, count, &[])?;
+        self.stream
+            .read_exact(&mut self.buffer)
+            .map_err(NbdError::Io)?;
+        mem.write_slice(&self.buffer, addr)
+            .map_err(NbdError::Transfer)?;
+        Ok(count)
+    }
+
+    fn write_export(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, NbdError> {
+        let mut data = std::mem::take(&mut self.buffer);
+        data.resize(count as usize, 0);
+        let res = mem
+            .read_slice(&mut data, addr)
+            .map_err(NbdError::Transfer)
+            .and_then(|_| self.request(NBD_CMD_WRITE, offset, count, &data));
+        self.buffer = data;
+        res.map(|_| count)
+    }
+}
+
+impl BlockBackend for NbdBackend {
+    fn read(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError> {
+        self.read_export(offset, mem, addr, count)
+            .map_err(BlockIoError::Nbd)
+    }
+
+    fn write(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, BlockIoError> {
+        self.write_export(offset, mem, addr, count)
+            .map_err(BlockIoError::Nbd)
+    }
+
+    fn flush(&mut self) -> Result<(), BlockIoError> {
+        // Servers that don't advertise flushes write the data out on their own.
+        if self.flags & NBD_FLAG_SEND_FLUSH == 0 {
+            return Ok(());
+        }
+        self.request(NBD_CMD_FLUSH, 0, 0, &[])
+            .map_err(BlockIoError::Nbd)
+    }
+}
+
+impl Drop for NbdBackend {
+    fn drop(&mut self) {
+        // The server doesn't reply to disconnects, and a broken connection is closed anyway.
+        let mut header = [0u8; 28];
+        header[0..4].copy_from_slice(&NBD_REQUEST_MAGIC.to_be_bytes());
+        header[6..8].copy_from_slice(&NBD_CMD_DISC.to_be_bytes());
+        let _ = self.stream.write_all(&header);
+    }
+}
+
+fn read_u16(stream: &mut impl Read) -> Result<u16, NbdError> {
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf).map_err(NbdError::Io)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32(stream: &mut impl Read) -> Result<u32, NbdError> {
+    let mut buf = [0u8; 4];
+    stream.read_exact(&mut buf).map_err(NbdError::Io)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(stream: &mut impl Read) -> Result<u64, NbdError> {
+    let mut buf = [0u8; 8];
+    stream.read_exact(&mut buf).map_err(NbdError::Io)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn send_option(stream: &mut impl Write, option: u32, data: &[u8]) -> Result<(), NbdError> {
+    let mut buf = Vec::with_capacity(16 + data.len());
+    buf.extend_from_slice(&NBD_IHAVEOPT.to_be_bytes());
+    buf.extend_from_slice(&option.to_be_bytes());
+    // Options only carry export names, which are checked when parsing the URI.
+    buf.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+    buf.extend_from_slice(data);
+    stream.write_all(&buf).map_err(NbdError::Io)
+}
+
+// Negotiates the export and returns its size and transmission flags.
+fn handshake(stream: &mut (impl Read + Write), export: &str) -> Result<(u64, u16), NbdError> {
+    if read_u64(stream)? != NBD_MAGIC || read_u64(stream)? != NBD_IHAVEOPT {
+        return Err(NbdError::Handshake);
+    }
+    let server_flags = read_u16(stream)?;
+    if server_flags & NBD_FLAG_FIXED_NEWSTYLE == 0 {
+        return Err(NbdError::Handshake);
+    }
+    let no_zeroes = server_flags & NBD_FLAG_NO_ZEROES != 0;
+    let mut client_flags = NBD_FLAG_C_FIXED_NEWSTYLE;
+    if no_zeroes {
+        client_flags |= NBD_FLAG_C_NO_ZEROES;
+    }
+    stream
+        .write_all(&client_flags.to_be_bytes())
+        .map_err(NbdError::Io)?;
+
+    let mut go = Vec::with_capacity(6 + export.len());
+    go.extend_from_slice(&u32::try_from(export.len()).unwrap().to_be_bytes());
+    go.extend_from_slice(export.as_bytes());
+    // No information is requested besides the export size and flags, which are always sent.
+    go.extend_from_slice(&0u16.to_be_bytes());
+    send_option(stream, NBD_OPT_GO, &go)?;
+
+    let mut info = None;
+    loop {
+        if read_u64(stream)? != NBD_OPT_REPLY_MAGIC || read_u32(stream)? != NBD_OPT_GO {
+            return Err(NbdError::Handshake);
+        }
+        let reply_type = read_u32(stream)?;
+        let len = read_u32(stream)?;
+        let len = usize::try_from(len).map_err(|_| NbdError::Handshake)?;
+        if len > NBD_MAX_STRING {
+            return Err(NbdError::Handshake);
+        }
+        let mut data = vec![0u8; len];
+        stream.read_exact(&mut data).map_err(NbdError::Io)?;
+        match reply_type {
+            NBD_REP_ACK => return info.ok_or(NbdError::Handshake),
+            NBD_REP_INFO => {
+                let mut data = data.as_slice();
+                if data.len() >= 12 && read_u16(&mut data)? == NBD_INFO_EXPORT {
+                    info = Some((read_u64(&mut data)?, read_u16(&mut data)?));
+                }
+            }
+            NBD_REP_ERR_UNSUP => break,
+            reply_type if reply_type & NBD_REP_FLAG_ERROR != 0 => {
+                return Err(NbdError::Export(export.to_string(), reply_type));
+            }
+            // Other replies are informational.
+            _ => (),
+        }
+    }
+
+    // The server predates `NBD_OPT_GO` and closes the connection if the export doesn't exist.
+    send_option(stream, NBD_OPT_EXPORT_NAME, export.as_bytes())?;
+    let size = read_u64(stream)?;
+    let flags = read_u16(stream)?;
+    if !no_zeroes {
+        let mut zeroes = [0u8; NBD_EXPORT_NAME_ZEROES];
+        stream.read_exact(&mut zeroes).map_err(NbdError::Io)?;
+    }
+    Ok((size, flags))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::devices::virtio::block::virtio::io::tests::create_mem;
+    use crate::utils::u64_to_usize;
+
+    const EXPORT: &[u8] = b"disk";
+
+    // Serves the export from memory, answering `NBD_OPT_GO` if `go` is set.
+    pub(crate) fn nbd_server(
+        mut stream: UnixStream,
+        mut disk: Vec<u8>,
+        flags: u16,
+        go: bool,
+    ) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut greeting = NBD_MAGIC.to_be_bytes().to_vec();
+            greeting.extend_from_slice(&NBD_IHAVEOPT.to_be_bytes());
+            greeting
+                .extend_from_slice(&(NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES).to_be_bytes());
+            stream.write_all(&greeting).unwrap();
+            assert_eq!(
+                read_u32(&mut stream).unwrap(),
+                NBD_FLAG_C_FIXED_NEWSTYLE | NBD_FLAG_C_NO_ZEROES
+            );
+
+            loop {
+                assert_eq!(read_u64(&mut stream).unwrap(), NBD_IHAVEOPT);
+                let option = read_u32(&mut stream).unwrap();
+                let len = read_u32(&mut stream).unwrap();
+                let mut data = vec![0u8; usize::try_from(len).unwrap()];
+                stream.read_exact(&mut data).unwrap();
+                let mut export_info = u64::try_from(disk.len()).unwrap().to_be_bytes().to_vec();
+                export_info.extend_from_slice(&flags.to_be_bytes());
+                if option == NBD_OPT_EXPORT_NAME {
+                    assert_eq!(data, EXPORT);
+                    stream.write_all(&export_info).unwrap();
+                    break;
+                }
+                assert_eq!(option, NBD_OPT_GO);
+                let mut reply = |reply_type: u32, data: &[u8]| {
+                    let mut buf = NBD_OPT_REPLY_MAGIC.to_be_bytes().to_vec();
+                    buf.extend_from_slice(&NBD_OPT_GO.to_be_bytes());
+                    buf.extend_from_slice(&reply_type.to_be_bytes());
+                    buf.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+                    buf.extend_from_slice(data);
+                    stream.write_all(&buf).unwrap();
+                };
+                if !go {
+                    reply(NBD_REP_ERR_UNSUP, &[]);
+                    continue;
+                }
+                assert_eq!(&data[4..data.len() - 2], EXPORT);
+                let mut info = NBD_INFO_EXPORT.to_be_bytes().to_vec();
+                info.extend_from_slice(&export_info);
+                reply(NBD_REP_INFO, &info);
+                reply(NBD_REP_ACK, &[]);
+                break;
+            }
+
+            loop {
+                let mut request = [0u8; 28];
+                if stream.read_exact(&mut request).is_err() {
+                    return disk;
+                }
+                assert_eq!(
+                    u32::from_be_bytes(request[0..4].try_into().unwrap()),
+                    NBD_REQUEST_MAGIC
+                );
+                let command = u16::from_be_bytes(request[6..8].try_into().unwrap());
+                let offset = u64_to_usize(u64::from_be_bytes(request[16..24].try_into().unwrap()));
+                let length = u32::from_be_bytes(request[24..28].try_into().unwrap()) as usize;
+                let mut reply = NBD_SIMPLE_REPLY_MAGIC.to_be_bytes().to_vec();
+                let error: u32 = if offset + length > disk.len() { 22 } else { 0 };
+                reply.extend_from_slice(&error.to_be_bytes());
+                reply.extend_from_slice(&request[8..16]);
+                match command {
+                    NBD_CMD_READ if error == 0 => {
+                        reply.extend_from_slice(&disk[offset..offset + length])
+                    }
+                    NBD_CMD_WRITE => {
+                        let mut data = vec![0u8; length];
+                        stream.read_exact(&mut data).unwrap();
+                        if error == 0 {
+                            disk[offset..offset + length].copy_from_slice(&data);
+                        }
+                    }
+                    NBD_CMD_DISC => return disk,
+                    _ => (),
+                }
+                stream.write_all(&reply).unwrap();
+            }
+        })
+    }
+
+    fn connect(disk: Vec<u8>, flags: u16, go: bool) -> (NbdBackend, thread::JoinHandle<Vec<u8>>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = nbd_server(server, disk, flags, go);
+        let backend = NbdBackend::from_stream(NbdStream::Unix(client), "disk", false).unwrap();
+        (backend, server)
+    }
+
+    #[test]
+    fn test_parse_uri() {
+        assert_eq!(
+            NbdUri::parse("nbd://127.0.0.1/disk").unwrap(),
+            NbdUri {
+                address: NbdAddress::Tcp("127.0.0.1:10809".parse().unwrap()),
+                export: "disk".to_string(),
+            }
+        );
+        assert_eq!(
+            NbdUri::parse("nbd://10.0.0.1:2000").unwrap(),
+            NbdUri {
+                address: NbdAddress::Tcp("10.0.0.1:2000".parse().unwrap()),
+                export: String::new(),
+            }
+        );
+        assert_eq!(
+            NbdUri::parse("nbd://[::1]/disk").unwrap().address,
+            NbdAddress::Tcp("[::1]:10809".parse().unwrap())
+        );
+        assert_eq!(
+            NbdUri::parse("nbd://[::1]:2000/disk").unwrap().address,
+            NbdAddress::Tcp("[::1]:2000".parse().unwrap())
+        );
+        assert_eq!(
+            NbdUri::parse("nbd+unix:///disk?socket=/run/nbd.sock").unwrap(),
+            NbdUri {
+                address: NbdAddress::Unix(PathBuf::from("/run/nbd.sock")),
+                export: "disk".to_string(),
+            }
+        );
+
+        for uri in [
+            "/dev/nbd0",
+            "nbd:///disk",
+            "nbd://localhost/disk",
+            "nbd://127.0.0.1:port/disk",
+            "nbd://127.0.0.1/disk?socket=/run/nbd.sock",
+            "nbd+unix:///disk",
+            "nbd+unix://host/disk?socket=/run/nbd.sock",
+            "nbd+unix:///disk?socket=",
+            "nbd+unix:///disk?socket=/run/nbd.sock&x=y",
+        ] {
+            assert!(
+                matches!(NbdUri::parse(uri), Err(NbdError::InvalidUri(_))),
+                "{uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_write() {
+        let mem = create_mem();
+        let disk: Vec<u8> = (0..4096u32)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        let original = disk.clone();
+        let (mut backend, server) = connect(disk, NBD_FLAG_SEND_FLUSH, true);
+        assert_eq!(backend.size(), 4096);
+
+        assert_eq!(
+            backend.read(512, &mem, GuestAddress(0), 1024).unwrap(),
+            1024
+        );
+        let mut buf = vec![0u8; 1024];
+        mem.read_slice(&mut buf, GuestAddress(0)).unwrap();
+        assert_eq!(buf, original[512..1536]);
+
+        mem.write_slice(&[0xaa; 512], GuestAddress(0x1000)).unwrap();
+        assert_eq!(
+            backend.write(0, &mem, GuestAddress(0x1000), 512).unwrap(),
+            512
+        );
+        backend.flush().unwrap();
+
+        // The server fails requests past the end of the export.
+        assert!(matches!(
+            backend.read(4096, &mem, GuestAddress(0), 512),
+            Err(BlockIoError::Nbd(NbdError::Server(22)))
+        ));
+        assert!(matches!(
+            backend.write(4096, &mem, GuestAddress(0), 512),
+            Err(BlockIoError::Nbd(NbdError::Server(22)))
+        ));
+        // Guest memory is checked before anything is sent.
+        assert!(matches!(
+            backend.write(0, &mem, GuestAddress(0x1f00), 512),
+            Err(BlockIoError::Nbd(NbdError::Transfer(_)))
+        ));
+
+        drop(backend);
+        let disk = server.join().unwrap();
+        assert_eq!(disk[..512], [0xaa; 512]);
+        assert_eq!(disk[512..], original[512..]);
+    }
+
+    #[test]
+    fn test_export_name_fallback() {
+        let (backend, server) = connect(vec![0u8; 2048], 0, false);
+        assert_eq!(backend.size(), 2048);
+        drop(backend);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_read_only_export() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = nbd_server(server, vec![0u8; 512], NBD_FLAG_READ_ONLY, true);
+        assert!(matches!(
+            NbdBackend::from_stream(NbdStream::Unix(client), "disk", false),
+            Err(NbdError::ReadOnlyExport)
+        ));
+        server.join().unwrap();
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = nbd_server(server, vec![0u8; 512], NBD_FLAG_READ_ONLY, true);
+        let mut backend = NbdBackend::from_stream(NbdStream::Unix(client), "disk", true).unwrap();
+        // The export doesn't support flushes, so they aren't sent.
+        backend.flush().unwrap();
+        drop(backend);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_handshake_errors() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        // Oldstyle servers send their export right after the magic.
+        server.write_all(&NBD_MAGIC.to_be_bytes()).unwrap();
+        server
+            .write_all(&0x00_42_02_81_86_12_53u64.to_be_bytes())
+            .unwrap();
+        assert!(matches!(
+            NbdBackend::from_stream(NbdStream::Unix(client), "disk", true),
+            Err(NbdError::Handshake)
+        ));
+
+        let (client, server) = UnixStream::pair().unwrap();
+        drop(server);
+        assert!(matches!(
+            NbdBackend::from_stream(NbdStream::Unix(client), "disk", true),
+            Err(NbdError::Io(_))
+        ));
+    }
+}
@@ -33,7 +33,6 @@ impl SyncFileEngine {
         SyncFileEngine { file }
     }
 
-    #[cfg(test)]
     pub fn file(&self) -> &File {
         &self.file
     }
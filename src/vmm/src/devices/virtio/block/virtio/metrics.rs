@@ -177,6 +177,8 @@ pub struct BlockDeviceMetrics {
     pub read_agg: LatencyAggregateMetrics,
     /// Duration of all write operations.
     pub write_agg: LatencyAggregateMetrics,
+    /// Duration of all flush operations, from submission to completion.
+    pub flush_agg: LatencyAggregateMetrics,
     /// Number of rate limiter throttling events.
     pub rate_limiter_throttled_events: SharedIncMetric,
     /// Number of virtio events throttled because of the IO engine.
@@ -184,6 +186,10 @@ pub struct BlockDeviceMetrics {
     pub io_engine_throttled_events: SharedIncMetric,
     /// Number of remaining requests in the queue.
     pub remaining_reqs_count: SharedIncMetric,
+    /// Number of reads that failed integrity verification.
+    pub verity_fails: SharedIncMetric,
+    /// Number of read-ahead windows prefetched into the host page cache.
+    pub read_ahead_count: SharedIncMetric,
 }
 
 impl BlockDeviceMetrics {
@@ -192,6 +198,7 @@ impl BlockDeviceMetrics {
         Self {
             read_agg: LatencyAggregateMetrics::new(),
             write_agg: LatencyAggregateMetrics::new(),
+            flush_agg: LatencyAggregateMetrics::new(),
             ..Default::default()
         }
     }
@@ -224,12 +231,18 @@ impl BlockDeviceMetrics {
         self.write_agg
             .sum_us
             .add(other.write_agg.sum_us.fetch_diff());
+        self.flush_agg
+            .sum_us
+            .add(other.flush_agg.sum_us.fetch_diff());
         self.rate_limiter_throttled_events
             .add(other.rate_limiter_throttled_events.fetch_diff());
         self.io_engine_throttled_events
             .add(other.io_engine_throttled_events.fetch_diff());
         self.remaining_reqs_count
             .add(other.remaining_reqs_count.fetch_diff());
+        self.verity_fails.add(other.verity_fails.fetch_diff());
+        self.read_ahead_count
+            .add(other.read_ahead_count.fetch_diff());
     }
 }
 
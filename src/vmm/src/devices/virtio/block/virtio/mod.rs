@@ -5,11 +5,13 @@
 
 pub mod device;
 mod event_handler;
+pub mod host_cache;
 mod io;
 pub mod metrics;
 pub mod persist;
 pub mod request;
 pub mod test_utils;
+pub mod verity;
 
 use vm_memory::GuestMemoryError;
 
@@ -63,4 +65,16 @@ pub enum VirtioBlockError {
     RateLimiter(std::io::Error),
     /// Persistence error: {0}
     Persist(crate::devices::virtio::persist::PersistError),
+    /// Integrity verification error: {0}
+    Verity(verity::VerityError),
+    /// Sharing the page cache requires a read-only drive.
+    SharedPageCacheNotReadOnly,
+    /// Cannot map the backing file from the page cache: {0}
+    SharedPageCache(std::io::Error),
+    /// Host page cache policy error: {0}
+    HostCache(host_cache::HostCacheError),
+    /// {0} requires a drive backed by a host file.
+    NoBackingFile(&'static str),
+    /// NBD error: {0}
+    Nbd(io::NbdError),
 }
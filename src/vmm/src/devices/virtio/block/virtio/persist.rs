@@ -8,6 +8,8 @@ use serde::{Deserialize, Serialize};
 use vmm_sys_util::eventfd::EventFd;
 
 use super::device::DiskProperties;
+use super::host_cache::HostCacheConfig;
+use super::verity::VerityConfig;
 use super::*;
 use crate::devices::virtio::block::persist::BlockConstructorArgs;
 use crate::devices::virtio::block::virtio::device::FileEngineType;
@@ -18,6 +20,7 @@ use crate::devices::virtio::persist::VirtioDeviceState;
 use crate::rate_limiter::RateLimiter;
 use crate::rate_limiter::persist::RateLimiterState;
 use crate::snapshot::Persist;
+use crate::utils::file_mapping::SharedPageCacheConfig;
 
 /// Holds info about block's file engine type. Gets saved in snapshot.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -60,6 +63,18 @@ pub struct VirtioBlockState {
     pub virtio_state: VirtioDeviceState,
     rate_limiter_state: RateLimiterState,
     file_engine_type: FileEngineTypeState,
+    verity: Option<VerityConfig>,
+    shared_page_cache: Option<SharedPageCacheConfig>,
+    strict_ordering: bool,
+    host_cache: Option<HostCacheConfig>,
+    nbd_uri: Option<String>,
+}
+
+impl VirtioBlockState {
+    /// Returns the path of the host file backing the device, unless it is backed by an NBD export.
+    pub fn disk_path(&self) -> Option<&str> {
+        self.nbd_uri.is_none().then_some(self.disk_path.as_str())
+    }
 }
 
 impl Persist<'_> for VirtioBlock {
@@ -78,6 +93,15 @@ impl Persist<'_> for VirtioBlock {
             virtio_state: VirtioDeviceState::from_device(self),
             rate_limiter_state: self.rate_limiter.save(),
             file_engine_type: FileEngineTypeState::from(self.file_engine_type()),
+            verity: self
+                .disk
+                .verity
+                .as_ref()
+                .map(|verity| verity.config().clone()),
+            shared_page_cache: self.disk.shared_mapping.as_ref().map(|(config, _)| *config),
+            strict_ordering: self.strict_ordering,
+            host_cache: self.host_cache(),
+            nbd_uri: self.disk.nbd_uri.clone(),
         }
     }
 
@@ -89,11 +113,28 @@ impl Persist<'_> for VirtioBlock {
         let rate_limiter = RateLimiter::restore((), &state.rate_limiter_state)
             .map_err(VirtioBlockError::RateLimiter)?;
 
-        let disk_properties = DiskProperties::new(
-            state.disk_path.clone(),
-            is_read_only,
-            state.file_engine_type.into(),
-        )?;
+        let mut disk_properties = match &state.nbd_uri {
+            Some(nbd_uri) => DiskProperties::from_nbd(
+                nbd_uri.clone(),
+                is_read_only,
+                state.file_engine_type.into(),
+                state.verity.clone(),
+                state.shared_page_cache,
+            )?,
+            None => DiskProperties::new(
+                state.disk_path.clone(),
+                is_read_only,
+                state.file_engine_type.into(),
+                state.verity.clone(),
+                state.shared_page_cache,
+            )?,
+        };
+        disk_properties
+            .file_engine
+            .set_strict_ordering(state.strict_ordering);
+        if let Some(host_cache) = state.host_cache {
+            disk_properties.set_host_cache(host_cache)?;
+        }
 
         let queue_evts = [EventFd::new(libc::EFD_NONBLOCK).map_err(VirtioBlockError::EventFd)?];
 
@@ -129,10 +170,12 @@ impl Persist<'_> for VirtioBlock {
             cache_type: state.cache_type,
             root_device: state.root_device,
             read_only: is_read_only,
+            strict_ordering: state.strict_ordering,
 
             disk: disk_properties,
             rate_limiter,
             is_io_engine_throttled: false,
+            cpu_budget: None,
             metrics: BlockMetricsPerDevice::alloc(state.id.clone()),
         })
     }
@@ -163,6 +206,11 @@ mod tests {
             cache_type: CacheType::Writeback,
             rate_limiter: None,
             file_engine_type: FileEngineType::default(),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: false,
+            host_cache: None,
+            nbd_uri: None,
         };
 
         let block = VirtioBlock::new(config).unwrap();
@@ -207,6 +255,14 @@ mod tests {
             cache_type: CacheType::Unsafe,
             rate_limiter: None,
             file_engine_type: FileEngineType::default(),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: true,
+            host_cache: Some(HostCacheConfig {
+                read_ahead_kib: 128,
+                ..Default::default()
+            }),
+            nbd_uri: None,
         };
 
         let block = VirtioBlock::new(config).unwrap();
@@ -238,5 +294,8 @@ mod tests {
 
         // Test that block specific fields are the same.
         assert_eq!(restored_block.disk.file_path, block.disk.file_path);
+        assert!(restored_block.strict_ordering);
+        assert_eq!(restored_block.host_cache(), block.host_cache());
+        assert!(restored_block.disk.read_ahead.is_some());
     }
 }
@@ -7,8 +7,10 @@
 
 use std::convert::From;
 
+use utils::time::{ClockType, get_time_us};
 use vm_memory::GuestMemoryError;
 
+use super::verity::VerityError;
 use super::{SECTOR_SHIFT, SECTOR_SIZE, VirtioBlockError, io as block_io};
 use crate::devices::virtio::block::virtio::device::DiskProperties;
 use crate::devices::virtio::block::virtio::metrics::BlockDeviceMetrics;
@@ -19,13 +21,20 @@ pub use crate::devices::virtio::generated::virtio_blk::{
 use crate::devices::virtio::queue::DescriptorChain;
 use crate::logger::{IncMetric, error};
 use crate::rate_limiter::{RateLimiter, TokenType};
+use crate::utils::u64_to_usize;
 use crate::vstate::memory::{ByteValued, Bytes, GuestAddress, GuestMemoryMmap};
 
 #[derive(Debug, derive_more::From)]
 pub enum IoErr {
     GetId(GuestMemoryError),
-    PartialTransfer { completed: u32, expected: u32 },
+    PartialTransfer {
+        completed: u32,
+        expected: u32,
+    },
     FileEngine(block_io::BlockIoError),
+    Verity(VerityError),
+    #[from(ignore)]
+    SharedMapping(GuestMemoryError),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -95,6 +104,8 @@ pub struct PendingRequest {
     data_len: u32,
     status_addr: GuestAddress,
     desc_idx: u16,
+    // Submission time of a flush, used to account for its latency once it completes.
+    flush_start_us: Option<u64>,
 }
 
 impl PendingRequest {
@@ -150,6 +161,12 @@ impl PendingRequest {
         res: Result<u32, IoErr>,
         block_metrics: &BlockDeviceMetrics,
     ) -> FinishedRequest {
+        if let Some(start_us) = self.flush_start_us {
+            block_metrics
+                .flush_agg
+                .record_latency_us(get_time_us(ClockType::Monotonic) - start_us);
+        }
+
         let status = match (res, self.r#type) {
             (Ok(transferred_data_len), RequestType::In) => {
                 let status = Status::from_data(self.data_len, transferred_data_len, true);
@@ -360,9 +377,28 @@ impl Request {
             data_len: self.data_len,
             status_addr: self.status_addr,
             desc_idx,
+            flush_start_us: (self.r#type == RequestType::Flush)
+                .then(|| get_time_us(ClockType::Monotonic)),
         }
     }
 
+    // Copies the requested range of a drive mapped from the host page cache to guest memory.
+    fn read_shared_mapping(&self, mapping: &[u8], mem: &GuestMemoryMmap) -> Result<u32, IoErr> {
+        let start = u64_to_usize(self.offset());
+        // The request was checked against the disk size when parsed, but the backing file may
+        // have been truncated since it was mapped.
+        let data =
+            mapping
+                .get(start..start + self.data_len as usize)
+                .ok_or(IoErr::PartialTransfer {
+                    completed: 0,
+                    expected: self.data_len,
+                })?;
+        mem.write_slice(data, self.data_addr)
+            .map_err(IoErr::SharedMapping)?;
+        Ok(self.data_len)
+    }
+
     pub(crate) fn process(
         self,
         disk: &mut DiskProperties,
@@ -374,6 +410,28 @@ impl Request {
         let res = match self.r#type {
             RequestType::In => {
                 let _metric = block_metrics.read_agg.record_latency_metrics();
+                if let Some(read_ahead) = &mut disk.read_ahead
+                    && read_ahead.on_read(
+                        self.offset(),
+                        self.data_len,
+                        disk.nsectors << SECTOR_SHIFT,
+                    )
+                {
+                    block_metrics.read_ahead_count.inc();
+                }
+                if let Some(verity) = &mut disk.verity {
+                    let res = verity
+                        .read(self.offset(), mem, self.data_addr, self.data_len)
+                        .map_err(|err| {
+                            block_metrics.verity_fails.inc();
+                            IoErr::Verity(err)
+                        });
+                    return ProcessingResult::Executed(pending.finish(mem, res, block_metrics));
+                }
+                if let Some((_, mapping)) = &disk.shared_mapping {
+                    let res = self.read_shared_mapping(mapping.as_slice(), mem);
+                    return ProcessingResult::Executed(pending.finish(mem, res, block_metrics));
+                }
                 disk.file_engine
                     .read(self.offset(), mem, self.data_addr, self.data_len, pending)
             }
@@ -422,6 +480,7 @@ mod tests {
     use super::*;
     use crate::devices::virtio::queue::{Queue, VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
     use crate::devices::virtio::test_utils::{VirtQueue, default_mem};
+    use crate::logger::StoreMetric;
     use crate::vstate::memory::{Address, GuestAddress, GuestMemory};
 
     const NUM_DISK_SECTORS: u64 = 1024;
@@ -433,6 +492,7 @@ mod tests {
                 data_len: 0,
                 status_addr: Default::default(),
                 desc_idx: 0,
+                flush_start_us: None,
             }
         }
     }
@@ -670,6 +730,66 @@ mod tests {
         chain.check_parse(true);
     }
 
+    #[test]
+    fn test_read_shared_mapping() {
+        let mem = single_region_mem(0x1000);
+        let mapping: Vec<u8> = (0..4 * SECTOR_SIZE)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        let request = Request {
+            r#type: RequestType::In,
+            data_len: 2 * SECTOR_SIZE,
+            status_addr: GuestAddress(0),
+            sector: 1,
+            data_addr: GuestAddress(0x200),
+        };
+
+        assert_eq!(
+            request.read_shared_mapping(&mapping, &mem).unwrap(),
+            2 * SECTOR_SIZE
+        );
+        let mut read = vec![0u8; 2 * SECTOR_SIZE as usize];
+        mem.read_slice(&mut read, GuestAddress(0x200)).unwrap();
+        assert_eq!(read, mapping[512..1536]);
+
+        // Reads past the end of a truncated mapping fail.
+        assert!(matches!(
+            request.read_shared_mapping(&mapping[..1024], &mem),
+            Err(IoErr::PartialTransfer {
+                completed: 0,
+                expected: 1024
+            })
+        ));
+    }
+
+    #[test]
+    fn test_flush_latency() {
+        let mem = single_region_mem(0x1000);
+        let metrics = BlockDeviceMetrics::new();
+        let mut request = Request {
+            r#type: RequestType::In,
+            data_len: 0,
+            status_addr: GuestAddress(0),
+            sector: 0,
+            data_addr: GuestAddress(0),
+        };
+        assert!(request.to_pending_request(0).flush_start_us.is_none());
+
+        request.r#type = RequestType::Flush;
+        let mut pending = request.to_pending_request(0);
+        assert!(pending.flush_start_us.is_some());
+
+        // The latency of a flush is accounted for when it completes.
+        pending.flush_start_us = Some(0);
+        pending.finish(&mem, Ok(0), &metrics);
+        assert_eq!(metrics.flush_count.count(), 1);
+        assert_ne!(metrics.flush_agg.max_us.fetch(), 0);
+        assert_eq!(
+            metrics.flush_agg.min_us.fetch(),
+            metrics.flush_agg.max_us.fetch()
+        );
+    }
+
     use std::convert::TryInto;
 
     /// -------------------------------------
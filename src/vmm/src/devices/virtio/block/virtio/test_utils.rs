@@ -58,6 +58,11 @@ pub fn default_block_with_path(path: String, file_engine_type: FileEngineType) -
             }),
         }),
         file_engine_type,
+        verity: None,
+        shared_page_cache: None,
+        strict_ordering: false,
+        host_cache: None,
+        nbd_uri: None,
     };
 
     // The default block device is read-write and non-root.
@@ -119,7 +124,7 @@ pub fn simulate_queue_and_async_completion_events(b: &mut VirtioBlock, expected_
             simulate_queue_event(b, None);
             simulate_async_completion_event(b, expected_irq);
         }
-        FileEngine::Sync(_) => {
+        FileEngine::Sync(_) | FileEngine::Backend(_) => {
             simulate_queue_event(b, Some(expected_irq));
         }
     }
@@ -0,0 +1,584 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Read-only integrity verification for virtio block devices.
+//!
+//! A verity drive is backed by a data file and a hash device in the on-disk format of Linux
+//! dm-verity, as created by `veritysetup format`: a superblock describing the block sizes, the
+//! number of data blocks and the salt, followed by the levels of the hash tree, the topmost first.
+//! Every digest is the salted SHA-256 digest of a data or hash block, and the configured root hash
+//! is the digest of the single block of the topmost level.
+//!
+//! Hash blocks are read on demand: each data block read by the guest is checked against its
+//! digest, and the hash blocks on its path to the root are authenticated up to the root hash or to
+//! a hash block authenticated earlier. Authenticated hash blocks are kept in memory, so that they
+//! are neither read nor hashed again while cached.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+
+use aws_lc_rs::digest::{Context, SHA256, SHA256_OUTPUT_LEN};
+use serde::{Deserialize, Serialize};
+use vm_memory::GuestMemoryError;
+
+use super::SECTOR_SIZE;
+use crate::utils::{u64_to_usize, usize_to_u64};
+use crate::vstate::memory::{Bytes, GuestAddress, GuestMemoryMmap};
+
+/// Size of the dm-verity superblock, in bytes.
+const SUPERBLOCK_SIZE: usize = 512;
+/// Signature at the start of the dm-verity superblock.
+const SUPERBLOCK_SIGNATURE: &[u8; 8] = b"verity\0\0";
+/// Largest supported data or hash block size, in bytes.
+const MAX_BLOCK_SIZE: u32 = 1 << 16;
+/// Largest salt supported by the superblock, in bytes.
+const MAX_SALT_SIZE: usize = 256;
+/// Number of authenticated hash blocks kept in memory.
+const MAX_CACHED_HASH_BLOCKS: usize = 256;
+
+type Digest = [u8; SHA256_OUTPUT_LEN];
+
+/// Integrity verification settings of a read-only drive.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerityConfig {
+    /// Hex encoded root hash of the hash tree, as printed by `veritysetup format`.
+    pub root_hash: String,
+    /// Path of the dm-verity hash device on the host, starting with its superblock.
+    pub hash_tree_path: String,
+}
+
+/// Errors associated with drive integrity verification.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum VerityError {
+    /// Integrity verification requires a read-only drive.
+    NotReadOnly,
+    /// Integrity verification is only supported with the Sync IO engine.
+    UnsupportedEngine,
+    /// Invalid root hash: expected 64 hexadecimal characters.
+    InvalidRootHash,
+    /// Cannot access the hash tree file {1}: {0}
+    HashTreeFile(std::io::Error, String),
+    /// Invalid dm-verity superblock: {0}
+    InvalidSuperblock(&'static str),
+    /// Unsupported hash algorithm {0}: only sha256 is supported.
+    UnsupportedAlgorithm(String),
+    /// Invalid block size {0}: it must be a power of two between 512 bytes and 64 KiB.
+    InvalidBlockSize(u32),
+    /// The backing file holds {0} bytes, the hash tree covers {1} bytes.
+    DataSize(u64, u64),
+    /// The hash tree file holds {0} bytes, expected at least {1} bytes.
+    HashTreeSize(u64, u64),
+    /// The hash tree does not match the configured root hash.
+    RootHashMismatch,
+    /// Cannot read the backing file: {0}
+    BackingFile(std::io::Error),
+    /// Data block {0} failed integrity verification.
+    DataBlockMismatch(u64),
+    /// Cannot transfer verified data to guest memory: {0}
+    GuestMemory(GuestMemoryError),
+}
+
+fn parse_root_hash(root_hash: &str) -> Result<Digest, VerityError> {
+    let bytes = root_hash.as_bytes();
+    if bytes.len() != 2 * SHA256_OUTPUT_LEN {
+        return Err(VerityError::InvalidRootHash);
+    }
+
+    let mut parsed = [0u8; SHA256_OUTPUT_LEN];
+    for (byte, pair) in parsed.iter_mut().zip(bytes.chunks_exact(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| VerityError::InvalidRootHash)?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| VerityError::InvalidRootHash)?;
+    }
+    Ok(parsed)
+}
+
+fn block_size(size: u32) -> Result<u64, VerityError> {
+    if !size.is_power_of_two() || !(SECTOR_SIZE..=MAX_BLOCK_SIZE).contains(&size) {
+        return Err(VerityError::InvalidBlockSize(size));
+    }
+    Ok(u64::from(size))
+}
+
+/// Fields of the dm-verity superblock.
+#[derive(Debug)]
+struct Superblock {
+    data_block_size: u64,
+    hash_block_size: u64,
+    data_blocks: u64,
+    salt: Vec<u8>,
+}
+
+impl Superblock {
+    fn parse(sb: &[u8; SUPERBLOCK_SIZE]) -> Result<Self, VerityError> {
+        let u32_at = |offset: usize| u32::from_le_bytes(sb[offset..offset + 4].try_into().unwrap());
+
+        if &sb[0..8] != SUPERBLOCK_SIGNATURE {
+            return Err(VerityError::InvalidSuperblock("bad signature"));
+        }
+        if u32_at(8) != 1 {
+            return Err(VerityError::InvalidSuperblock("unsupported version"));
+        }
+        // Hash type 0 is the Chrome OS format, which salts the digests differently.
+        if u32_at(12) != 1 {
+            return Err(VerityError::InvalidSuperblock("unsupported hash type"));
+        }
+        let algorithm = &sb[32..64];
+        let algorithm = &algorithm[..algorithm.iter().position(|&b| b == 0).unwrap_or(32)];
+        if algorithm != b"sha256" {
+            return Err(VerityError::UnsupportedAlgorithm(
+                String::from_utf8_lossy(algorithm).into_owned(),
+            ));
+        }
+        let data_block_size = block_size(u32_at(64))?;
+        let hash_block_size = block_size(u32_at(68))?;
+        let data_blocks = u64::from_le_bytes(sb[72..80].try_into().unwrap());
+        if data_blocks == 0 {
+            return Err(VerityError::InvalidSuperblock("no data blocks"));
+        }
+        let salt_size = usize::from(u16::from_le_bytes(sb[80..82].try_into().unwrap()));
+        if salt_size > MAX_SALT_SIZE {
+            return Err(VerityError::InvalidSuperblock("salt too long"));
+        }
+
+        Ok(Self {
+            data_block_size,
+            hash_block_size,
+            data_blocks,
+            salt: sb[88..88 + salt_size].to_vec(),
+        })
+    }
+}
+
+/// Verifies the data read from a drive against an authenticated hash tree.
+#[derive(Debug)]
+pub struct Verity {
+    config: VerityConfig,
+    file: File,
+    hash_file: File,
+    data_block_size: u64,
+    hash_block_size: u64,
+    /// Log2 of the number of digests held by a hash block.
+    hash_per_block_bits: u32,
+    salt: Vec<u8>,
+    root_hash: Digest,
+    /// Index of the first hash block of each level, the level of the data block digests first.
+    level_start: Vec<u64>,
+    /// Authenticated hash blocks, keyed by their index in the hash tree file.
+    verified: HashMap<u64, Box<[u8]>>,
+}
+
+impl Verity {
+    /// Loads the superblock of the hash tree of the backing `file` and authenticates the top
+    /// of the tree.
+    pub fn new(config: VerityConfig, file: File) -> Result<Self, VerityError> {
+        let root_hash = parse_root_hash(&config.root_hash)?;
+        let tree_err = |err| VerityError::HashTreeFile(err, config.hash_tree_path.clone());
+
+        let hash_file = File::open(&config.hash_tree_path).map_err(tree_err)?;
+        let mut sb = [0u8; SUPERBLOCK_SIZE];
+        hash_file.read_exact_at(&mut sb, 0).map_err(tree_err)?;
+        let sb = Superblock::parse(&sb)?;
+
+        let file_size = file.metadata().map_err(VerityError::BackingFile)?.len();
+        let data_size = sb
+            .data_blocks
+            .checked_mul(sb.data_block_size)
+            .ok_or(VerityError::InvalidSuperblock("too many data blocks"))?;
+        if file_size != data_size {
+            return Err(VerityError::DataSize(file_size, data_size));
+        }
+
+        // The layout of the levels follows the one computed by the dm-verity target. The tree
+        // starts at the first hash block following the superblock.
+        let hash_per_block_bits = (sb.hash_block_size / usize_to_u64(SHA256_OUTPUT_LEN)).ilog2();
+        let mut levels = 0;
+        while hash_per_block_bits * levels < u64::BITS
+            && (sb.data_blocks - 1) >> (hash_per_block_bits * levels) != 0
+        {
+            levels += 1;
+        }
+        let mut level_start = vec![0; levels as usize];
+        let mut position = 1;
+        for level in (0..levels).rev() {
+            level_start[level as usize] = position;
+            position += match 1u64.checked_shl((level + 1) * hash_per_block_bits) {
+                Some(per_block) => sb.data_blocks.div_ceil(per_block),
+                None => 1,
+            };
+        }
+        let tree_size = position * sb.hash_block_size;
+        let hash_file_size = hash_file.metadata().map_err(tree_err)?.len();
+        if hash_file_size < tree_size {
+            return Err(VerityError::HashTreeSize(hash_file_size, tree_size));
+        }
+
+        let mut verity = Self {
+            config,
+            file,
+            hash_file,
+            data_block_size: sb.data_block_size,
+            hash_block_size: sb.hash_block_size,
+            hash_per_block_bits,
+            salt: sb.salt,
+            root_hash,
+            level_start,
+            verified: HashMap::new(),
+        };
+        verity.authenticate_root()?;
+        Ok(verity)
+    }
+
+    /// Returns the configuration this verifier was created from.
+    pub fn config(&self) -> &VerityConfig {
+        &self.config
+    }
+
+    fn hash(&self, block: &[u8]) -> Digest {
+        let mut ctx = Context::new(&SHA256);
+        ctx.update(&self.salt);
+        ctx.update(block);
+        ctx.finish().as_ref().try_into().unwrap()
+    }
+
+    fn read_hash_block(&self, index: u64) -> Result<Box<[u8]>, VerityError> {
+        let mut block = vec![0u8; u64_to_usize(self.hash_block_size)].into_boxed_slice();
+        self.hash_file
+            .read_exact_at(&mut block, index * self.hash_block_size)
+            .map_err(|err| VerityError::HashTreeFile(err, self.config.hash_tree_path.clone()))?;
+        Ok(block)
+    }
+
+    /// Checks the topmost block of the tree, or the only data block when there is no tree,
+    /// against the root hash.
+    fn authenticate_root(&mut self) -> Result<(), VerityError> {
+        let (index, block) = match self.level_start.last() {
+            Some(&index) => (Some(index), self.read_hash_block(index)?),
+            None => {
+                let mut block = vec![0u8; u64_to_usize(self.data_block_size)].into_boxed_slice();
+                self.file
+                    .read_exact_at(&mut block, 0)
+                    .map_err(VerityError::BackingFile)?;
+                (None, block)
+            }
+        };
+        if self.hash(&block) != self.root_hash {
+            return Err(VerityError::RootHashMismatch);
+        }
+        if let Some(index) = index {
+            self.verified.insert(index, block);
+        }
+        Ok(())
+    }
+
+    /// Checks the data block `index` against the hash tree, authenticating the hash blocks on
+    /// its path up to the root hash or to an already authenticated hash block.
+    fn verify_block(&mut self, index: u64, block: &[u8]) -> Result<(), VerityError> {
+        let mismatch = || VerityError::DataBlockMismatch(index);
+        let mut digest = self.hash(block);
+        let mut position = index;
+        let mut authenticated = false;
+        let mut read = Vec::new();
+        for &start in &self.level_start {
+            let hash_block = start + (position >> self.hash_per_block_bits);
+            let entry =
+                u64_to_usize(position & ((1 << self.hash_per_block_bits) - 1)) * SHA256_OUTPUT_LEN;
+            if let Some(cached) = self.verified.get(&hash_block) {
+                if cached[entry..entry + SHA256_OUTPUT_LEN] != digest {
+                    return Err(mismatch());
+                }
+                authenticated = true;
+                break;
+            }
+
+            let data = self.read_hash_block(hash_block)?;
+            if data[entry..entry + SHA256_OUTPUT_LEN] != digest {
+                return Err(mismatch());
+            }
+            digest = self.hash(&data);
+            read.push((hash_block, data));
+            position >>= self.hash_per_block_bits;
+        }
+        if !authenticated && digest != self.root_hash {
+            return Err(mismatch());
+        }
+
+        if self.verified.len() + read.len() > MAX_CACHED_HASH_BLOCKS {
+            self.verified.clear();
+        }
+        self.verified.extend(read);
+        Ok(())
+    }
+
+    /// Reads `count` bytes at `offset` from the backing file into guest memory at `addr`,
+    /// failing without touching guest memory if any data block covering the range does not
+    /// match the hash tree.
+    pub fn read(
+        &mut self,
+        offset: u64,
+        mem: &GuestMemoryMmap,
+        addr: GuestAddress,
+        count: u32,
+    ) -> Result<u32, VerityError> {
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let first_block = offset / self.data_block_size;
+        let last_block = (offset + u64::from(count) - 1) / self.data_block_size;
+        let start = first_block * self.data_block_size;
+        let mut buf =
+            vec![0u8; u64_to_usize((last_block - first_block + 1) * self.data_block_size)];
+        self.file
+            .read_exact_at(&mut buf, start)
+            .map_err(VerityError::BackingFile)?;
+
+        for (index, block) in
+            (first_block..).zip(buf.chunks_exact(u64_to_usize(self.data_block_size)))
+        {
+            self.verify_block(index, block)?;
+        }
+
+        let skip = u64_to_usize(offset - start);
+        mem.write_slice(&buf[skip..skip + count as usize], addr)
+            .map_err(VerityError::GuestMemory)?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::test_utils::single_region_mem;
+
+    const SALT: [u8; 32] = [0x5a; 32];
+
+    fn salted_digest(block: &[u8]) -> Digest {
+        let mut ctx = Context::new(&SHA256);
+        ctx.update(&SALT);
+        ctx.update(block);
+        ctx.finish().as_ref().try_into().unwrap()
+    }
+
+    /// Builds the dm-verity hash tree file of `data`, as `veritysetup format` would with equal
+    /// data and hash block sizes, and returns it with its hex encoded root hash.
+    pub(crate) fn build_hash_tree(data: &[u8], block_size: usize) -> (Vec<u8>, String) {
+        let mut levels = Vec::new();
+        let mut digests: Vec<Digest> = data.chunks(block_size).map(salted_digest).collect();
+        while digests.len() > 1 {
+            let mut level = Vec::new();
+            for chunk in digests.chunks(block_size / SHA256_OUTPUT_LEN) {
+                let mut block = chunk.concat();
+                block.resize(block_size, 0);
+                level.extend_from_slice(&block);
+            }
+            digests = level.chunks(block_size).map(salted_digest).collect();
+            levels.push(level);
+        }
+
+        let mut tree = vec![0u8; block_size];
+        let block_size = u32::try_from(block_size).unwrap();
+        tree[0..8].copy_from_slice(SUPERBLOCK_SIGNATURE);
+        tree[8..12].copy_from_slice(&1u32.to_le_bytes());
+        tree[12..16].copy_from_slice(&1u32.to_le_bytes());
+        tree[32..38].copy_from_slice(b"sha256");
+        tree[64..68].copy_from_slice(&block_size.to_le_bytes());
+        tree[68..72].copy_from_slice(&block_size.to_le_bytes());
+        let data_blocks = usize_to_u64(data.len()) / u64::from(block_size);
+        tree[72..80].copy_from_slice(&data_blocks.to_le_bytes());
+        tree[80..82].copy_from_slice(&u16::try_from(SALT.len()).unwrap().to_le_bytes());
+        tree[88..88 + SALT.len()].copy_from_slice(&SALT);
+        for level in levels.iter().rev() {
+            tree.extend_from_slice(level);
+        }
+
+        let root_hash = digests[0]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        (tree, root_hash)
+    }
+
+    fn verity_fixture(data: &[u8], block_size: usize) -> (TempFile, TempFile, VerityConfig) {
+        let data_file = TempFile::new().unwrap();
+        data_file.as_file().write_all(data).unwrap();
+        let (tree, root_hash) = build_hash_tree(data, block_size);
+        let tree_file = TempFile::new().unwrap();
+        tree_file.as_file().write_all(&tree).unwrap();
+
+        let config = VerityConfig {
+            root_hash,
+            hash_tree_path: tree_file.as_path().to_str().unwrap().to_string(),
+        };
+        (data_file, tree_file, config)
+    }
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_root_hash() {
+        parse_root_hash("00").unwrap_err();
+        parse_root_hash(&"zz".repeat(32)).unwrap_err();
+        assert_eq!(parse_root_hash(&"ab".repeat(32)).unwrap(), [0xab; 32]);
+    }
+
+    #[test]
+    fn test_parse_superblock() {
+        let (tree, _) = build_hash_tree(&test_data(4 * 4096), 4096);
+        let sb: [u8; SUPERBLOCK_SIZE] = tree[..SUPERBLOCK_SIZE].try_into().unwrap();
+        let parsed = Superblock::parse(&sb).unwrap();
+        assert_eq!(parsed.data_block_size, 4096);
+        assert_eq!(parsed.hash_block_size, 4096);
+        assert_eq!(parsed.data_blocks, 4);
+        assert_eq!(parsed.salt, SALT);
+
+        let mut bad = sb;
+        bad[0] = b'x';
+        assert!(matches!(
+            Superblock::parse(&bad),
+            Err(VerityError::InvalidSuperblock(_))
+        ));
+        let mut bad = sb;
+        bad[12] = 0;
+        assert!(matches!(
+            Superblock::parse(&bad),
+            Err(VerityError::InvalidSuperblock(_))
+        ));
+        let mut bad = sb;
+        bad[32..38].copy_from_slice(b"sha512");
+        assert!(matches!(
+            Superblock::parse(&bad),
+            Err(VerityError::UnsupportedAlgorithm(algorithm)) if algorithm == "sha512"
+        ));
+        let mut bad = sb;
+        bad[64..68].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(matches!(
+            Superblock::parse(&bad),
+            Err(VerityError::InvalidBlockSize(1000))
+        ));
+    }
+
+    #[test]
+    fn test_verity_new() {
+        let data = test_data(3 * 4096);
+        let (data_file, tree_file, config) = verity_fixture(&data, 4096);
+
+        let mut bad_config = config.clone();
+        bad_config.root_hash = "00".repeat(32);
+        assert!(matches!(
+            Verity::new(bad_config, data_file.as_file().try_clone().unwrap()),
+            Err(VerityError::RootHashMismatch)
+        ));
+
+        let mut bad_config = config.clone();
+        bad_config.hash_tree_path = "/invalid/hash/tree".to_string();
+        assert!(matches!(
+            Verity::new(bad_config, data_file.as_file().try_clone().unwrap()),
+            Err(VerityError::HashTreeFile(_, _))
+        ));
+
+        // A hash tree built for a different file size is rejected.
+        let (_other_data, _other_tree, other_config) = verity_fixture(&data[..4096], 4096);
+        assert!(matches!(
+            Verity::new(other_config, data_file.as_file().try_clone().unwrap()),
+            Err(VerityError::DataSize(0x3000, 0x1000))
+        ));
+
+        Verity::new(config.clone(), data_file.as_file().try_clone().unwrap()).unwrap();
+
+        // A truncated hash tree is rejected.
+        tree_file.as_file().set_len(4096).unwrap();
+        assert!(matches!(
+            Verity::new(config, data_file.as_file().try_clone().unwrap()),
+            Err(VerityError::HashTreeSize(0x1000, 0x2000))
+        ));
+    }
+
+    #[test]
+    fn test_verity_read() {
+        let data = test_data(3 * 4096);
+        let (data_file, _tree_file, config) = verity_fixture(&data, 4096);
+        let mut verity = Verity::new(config, data_file.as_file().try_clone().unwrap()).unwrap();
+        let mem = single_region_mem(0x10000);
+
+        // Unaligned read spanning two data blocks.
+        assert_eq!(
+            verity.read(3584, &mem, GuestAddress(0), 1024).unwrap(),
+            1024
+        );
+        let mut read = vec![0u8; 1024];
+        mem.read_slice(&mut read, GuestAddress(0)).unwrap();
+        assert_eq!(read, data[3584..4608]);
+
+        // Tamper with the second data block.
+        data_file.as_file().write_all_at(&[0xff], 4096 + 7).unwrap();
+        mem.write_slice(&[0u8; 512], GuestAddress(0x1000)).unwrap();
+        assert!(matches!(
+            verity.read(4096, &mem, GuestAddress(0x1000), 512),
+            Err(VerityError::DataBlockMismatch(1))
+        ));
+        // Guest memory is left untouched on verification failure.
+        let mut read = vec![0xffu8; 512];
+        mem.read_slice(&mut read, GuestAddress(0x1000)).unwrap();
+        assert_eq!(read, vec![0u8; 512]);
+
+        // Other blocks are still readable.
+        verity.read(0, &mem, GuestAddress(0), 4096).unwrap();
+    }
+
+    #[test]
+    fn test_verity_single_block() {
+        // A single data block has no hash tree: its digest is the root hash.
+        let data = test_data(4096);
+        let (data_file, tree_file, config) = verity_fixture(&data, 4096);
+        assert_eq!(tree_file.as_file().metadata().unwrap().len(), 4096);
+        let mut verity = Verity::new(config, data_file.as_file().try_clone().unwrap()).unwrap();
+        assert!(verity.level_start.is_empty());
+
+        let mem = single_region_mem(0x1000);
+        verity.read(512, &mem, GuestAddress(0), 1024).unwrap();
+        data_file.as_file().write_all_at(&[0xff], 0).unwrap();
+        assert!(matches!(
+            verity.read(512, &mem, GuestAddress(0), 1024),
+            Err(VerityError::DataBlockMismatch(0))
+        ));
+    }
+
+    #[test]
+    fn test_verity_multi_level() {
+        // 512 byte hash blocks hold 16 digests, so 40 data blocks need two levels.
+        let data = test_data(40 * 512);
+        let (data_file, tree_file, config) = verity_fixture(&data, 512);
+        let mut verity = Verity::new(config, data_file.as_file().try_clone().unwrap()).unwrap();
+        // The superblock, the top level, then the three blocks of the bottom level.
+        assert_eq!(verity.level_start, [2, 1]);
+        assert_eq!(tree_file.as_file().metadata().unwrap().len(), 5 * 512);
+
+        let mem = single_region_mem(0x10000);
+        verity.read(0, &mem, GuestAddress(0), 40 * 512).unwrap();
+        let mut read = vec![0u8; 40 * 512];
+        mem.read_slice(&mut read, GuestAddress(0)).unwrap();
+        assert_eq!(read, data);
+
+        // Tampering with a digest of the bottom level is detected, even if the hash block
+        // that holds it was not read before.
+        let mut verity = Verity::new(
+            verity.config().clone(),
+            data_file.as_file().try_clone().unwrap(),
+        )
+        .unwrap();
+        tree_file.as_file().write_all_at(&[0xff], 4 * 512).unwrap();
+        assert!(matches!(
+            verity.read(35 * 512, &mem, GuestAddress(0), 512),
+            Err(VerityError::DataBlockMismatch(35))
+        ));
+        verity.read(0, &mem, GuestAddress(0), 512).unwrap();
+    }
+}
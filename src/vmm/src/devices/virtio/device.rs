@@ -18,6 +18,7 @@ use super::transport::VirtioInterrupt;
 use crate::devices::virtio::AsAny;
 use crate::devices::virtio::generated::virtio_ids;
 use crate::logger::{error, info, warn};
+use crate::vstate::bus::BusDeviceSync;
 use crate::vstate::memory::GuestMemoryMmap;
 
 /// State of an active VirtIO device
@@ -67,6 +68,9 @@ pub enum VirtioDeviceType {
     Vsock = virtio_ids::VIRTIO_ID_VSOCK as u8,
     Mem = virtio_ids::VIRTIO_ID_MEM as u8,
     Pmem = virtio_ids::VIRTIO_ID_PMEM as u8,
+    P9 = virtio_ids::VIRTIO_ID_9P as u8,
+    I2c = virtio_ids::VIRTIO_ID_I2C_ADAPTER as u8,
+    Gpio = virtio_ids::VIRTIO_ID_GPIO as u8,
     Rdma = 42,
 }
 
@@ -159,6 +163,12 @@ pub trait VirtioDevice: AsAny + Send {
         self.set_acked_features(self.acked_features() | v);
     }
 
+    /// Returns the shared memory regions of the device by ID, as their length and the handler of
+    /// the accesses to them. The transport places them in the guest physical address space.
+    fn shm_regions(&self) -> Vec<(u64, Arc<dyn BusDeviceSync>)> {
+        Vec::new()
+    }
+
     /// Reads this device configuration space at `offset`.
     fn read_config(&self, offset: u64, data: &mut [u8]);
 
@@ -181,6 +191,19 @@ pub trait VirtioDevice: AsAny + Send {
         None
     }
 
+    /// Stops the device once it has been unplugged from the guest. The device must not access
+    /// the guest memory, nor notify the guest, afterwards. By default, the device is reset, so
+    /// devices which do not support resets must override this.
+    fn unplug(&mut self) {
+        if self.reset().is_none() {
+            warn!(
+                "[{:?}:{}] unplugged device cannot be stopped",
+                self.device_type(),
+                self.id()
+            );
+        }
+    }
+
     /// Mark pages used by queues as dirty.
     fn mark_queue_memory_dirty(&mut self, mem: &GuestMemoryMmap) -> Result<(), QueueError> {
         for queue in self.queues_mut() {
@@ -214,6 +237,10 @@ pub trait VirtioDevice: AsAny + Send {
 
     /// Prepare the device for saving its state
     fn prepare_save(&mut self) {}
+
+    /// Process the requests the guest queued and complete the work in flight, so that their
+    /// outcome is in guest memory before a crash-consistent snapshot is taken.
+    fn drain(&mut self) {}
 }
 
 impl fmt::Debug for dyn VirtioDevice {
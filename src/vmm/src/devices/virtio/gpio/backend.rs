@@ -0,0 +1,929 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backends of the lines of a virtio-gpio controller.
+//!
+//! Both backends expose a nested epoll FD, registered in the main VMM epoll loop, under which
+//! they register the FDs they need to poll: the line requests of the host gpiochip, reporting
+//! the edges of the input lines, or the control socket of the mock lines and its connections.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use serde::{Deserialize, Serialize};
+use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
+use vmm_sys_util::ioctl::ioctl_with_mut_ref;
+use vmm_sys_util::{ioctl_ior_nr, ioctl_iowr_nr};
+
+use super::{
+    VIRTIO_GPIO_DIRECTION_IN, VIRTIO_GPIO_DIRECTION_NONE, VIRTIO_GPIO_DIRECTION_OUT,
+    VIRTIO_GPIO_IRQ_TYPE_EDGE_FALLING, VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING,
+    VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH, VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW,
+};
+use crate::logger::warn;
+use crate::vmm_config::gpio::{GpioConfig, GpioMockConfig};
+
+const GPIO_IOCTL_TYPE: u32 = 0xB4;
+ioctl_ior_nr!(GPIO_GET_CHIPINFO_IOCTL, GPIO_IOCTL_TYPE, 0x01, GpioChipInfo);
+ioctl_iowr_nr!(
+    GPIO_V2_GET_LINEINFO_IOCTL,
+    GPIO_IOCTL_TYPE,
+    0x05,
+    GpioV2LineInfo
+);
+ioctl_iowr_nr!(
+    GPIO_V2_GET_LINE_IOCTL,
+    GPIO_IOCTL_TYPE,
+    0x07,
+    GpioV2LineRequest
+);
+ioctl_iowr_nr!(
+    GPIO_V2_LINE_SET_CONFIG_IOCTL,
+    GPIO_IOCTL_TYPE,
+    0x0D,
+    GpioV2LineConfig
+);
+ioctl_iowr_nr!(
+    GPIO_V2_LINE_GET_VALUES_IOCTL,
+    GPIO_IOCTL_TYPE,
+    0x0E,
+    GpioV2LineValues
+);
+ioctl_iowr_nr!(
+    GPIO_V2_LINE_SET_VALUES_IOCTL,
+    GPIO_IOCTL_TYPE,
+    0x0F,
+    GpioV2LineValues
+);
+
+const GPIO_MAX_NAME_SIZE: usize = 32;
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_OUTPUT: u64 = 1 << 3;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+const GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES: u32 = 2;
+/// Size of `struct gpio_v2_line_event`.
+const GPIO_V2_LINE_EVENT_SIZE: usize = 48;
+/// Consumer label of the lines requested on the host.
+const CONSUMER: &[u8] = b"firecracker";
+
+/// Epoll data of the control socket of the mock lines.
+const MOCK_LISTENER: u64 = u64::MAX;
+/// Maximum length of a command sent on the control socket.
+const MOCK_MAX_COMMAND_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GpioBackendError {
+    /// Exactly one of `host_chip` and `mock` must be set
+    InvalidBackend,
+    /// Error opening the host gpiochip: {0}
+    OpenChip(std::io::Error),
+    /// Error querying the host gpiochip: {0}
+    ChipInfo(std::io::Error),
+    /// Invalid number of lines: {0}
+    InvalidLineCount(usize),
+    /// More line names than lines
+    TooManyNames,
+    /// Error binding the control socket of the mock lines: {0}
+    Bind(std::io::Error),
+    /// Error creating the epoll FD: {0}
+    EpollCreate(std::io::Error),
+    /// Error registering a FD under the epoll FD: {0}
+    EpollAdd(std::io::Error),
+    /// Error requesting the line {0} on the host: {1}
+    RequestLine(u16, std::io::Error),
+    /// Error accessing the value of the line {0} on the host: {1}
+    LineValue(u16, std::io::Error),
+    /// The line {0} is not requested by the guest
+    LineNotRequested(u16),
+    /// The line {0} is an output of the guest
+    LineIsOutput(u16),
+    /// Level interrupts are not supported by the host gpiochip
+    UnsupportedIrqType,
+}
+
+/// State of a line, as configured by the guest.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpioLineState {
+    /// One of the `VIRTIO_GPIO_DIRECTION_*` values.
+    pub direction: u8,
+    /// Value driven by the guest on outputs, or last value read from inputs.
+    pub value: u8,
+    /// One of the `VIRTIO_GPIO_IRQ_TYPE_*` values.
+    pub irq_type: u8,
+}
+
+/// Layout of `struct gpiochip_info` of the GPIO character device interface.
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioChipInfo {
+    name: [u8; GPIO_MAX_NAME_SIZE],
+    label: [u8; GPIO_MAX_NAME_SIZE],
+    lines: u32,
+}
+
+/// Layout of `struct gpio_v2_line_attribute`. The union is represented by its largest member.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineAttribute {
+    id: u32,
+    padding: u32,
+    value: u64,
+}
+
+/// Layout of `struct gpio_v2_line_config_attribute`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineConfigAttribute {
+    attr: GpioV2LineAttribute,
+    mask: u64,
+}
+
+/// Layout of `struct gpio_v2_line_config`.
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [GpioV2LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+/// Layout of `struct gpio_v2_line_request`.
+#[repr(C)]
+#[derive(Debug)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    config: GpioV2LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+/// Layout of `struct gpio_v2_line_info`.
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineInfo {
+    name: [u8; GPIO_MAX_NAME_SIZE],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    offset: u32,
+    num_attrs: u32,
+    flags: u64,
+    attrs: [GpioV2LineAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+    padding: [u32; 4],
+}
+
+/// Layout of `struct gpio_v2_line_values`.
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(dead_code)] // Some fields are only accessed by the kernel.
+struct GpioV2LineValues {
+    bits: u64,
+    mask: u64,
+}
+
+const _: () = assert!(std::mem::size_of::<GpioChipInfo>() == 68);
+const _: () = assert!(std::mem::size_of::<GpioV2LineConfig>() == 272);
+const _: () = assert!(std::mem::size_of::<GpioV2LineRequest>() == 592);
+const _: () = assert!(std::mem::size_of::<GpioV2LineInfo>() == 256);
+
+/// Converts a NUL terminated name of the GPIO character device interface.
+fn name_to_string(name: &[u8]) -> String {
+    let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+/// Whether the interrupt of an input line fires when its value goes from `old` to `new`.
+fn irq_triggered(irq_type: u8, old: u8, new: u8) -> bool {
+    match irq_type {
+        VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH => new == 1,
+        VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW => new == 0,
+        _ => {
+            (old == 0 && new == 1 && irq_type & VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING != 0)
+                || (old == 1 && new == 0 && irq_type & VIRTIO_GPIO_IRQ_TYPE_EDGE_FALLING != 0)
+        }
+    }
+}
+
+/// Lines backed by a gpiochip of the host, such as `/dev/gpiochip0`.
+///
+/// Lines are requested on the host when the guest sets their direction, and released when the
+/// guest sets it back to none. Only edge interrupts are supported, since the host only reports
+/// edges.
+#[derive(Debug)]
+pub struct HostGpioChip {
+    chip: File,
+    names: Vec<String>,
+    lines: Vec<GpioLineState>,
+    /// Line requests of the lines whose direction is set.
+    requests: Vec<Option<File>>,
+    epoll: Epoll,
+}
+
+impl HostGpioChip {
+    pub fn new(path: &str) -> Result<Self, GpioBackendError> {
+        let chip = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(GpioBackendError::OpenChip)?;
+
+        let mut info = GpioChipInfo::default();
+        // SAFETY: `info` matches the layout expected by the ioctl. The return value is checked.
+        if unsafe { ioctl_with_mut_ref(&chip, GPIO_GET_CHIPINFO_IOCTL(), &mut info) } < 0 {
+            return Err(GpioBackendError::ChipInfo(std::io::Error::last_os_error()));
+        }
+        let num_lines = usize::try_from(info.lines).unwrap();
+        if num_lines == 0 || num_lines > usize::from(u16::MAX) {
+            return Err(GpioBackendError::InvalidLineCount(num_lines));
+        }
+
+        let mut names = Vec::with_capacity(num_lines);
+        for offset in 0..info.lines {
+            let mut line_info = GpioV2LineInfo {
+                offset,
+                ..Default::default()
+            };
+            // SAFETY: `line_info` matches the layout expected by the ioctl. The return value is
+            // checked.
+            if unsafe { ioctl_with_mut_ref(&chip, GPIO_V2_GET_LINEINFO_IOCTL(), &mut line_info) }
+                < 0
+            {
+                return Err(GpioBackendError::ChipInfo(std::io::Error::last_os_error()));
+            }
+            names.push(name_to_string(&line_info.name));
+        }
+
+        Ok(Self {
+            chip,
+            names,
+            lines: vec![GpioLineState::default(); num_lines],
+            requests: (0..num_lines).map(|_| None).collect(),
+            epoll: Epoll::new().map_err(GpioBackendError::EpollCreate)?,
+        })
+    }
+
+    /// Configuration of the line request matching the state of a line.
+    fn line_config(state: &GpioLineState) -> GpioV2LineConfig {
+        let mut config = GpioV2LineConfig::default();
+        if state.direction == VIRTIO_GPIO_DIRECTION_OUT {
+            config.flags = GPIO_V2_LINE_FLAG_OUTPUT;
+            config.num_attrs = 1;
+            config.attrs[0] = GpioV2LineConfigAttribute {
+                attr: GpioV2LineAttribute {
+                    id: GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES,
+                    padding: 0,
+                    value: u64::from(state.value),
+                },
+                mask: 1,
+            };
+        } else {
+            config.flags = GPIO_V2_LINE_FLAG_INPUT;
+            if state.irq_type & VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING != 0 {
+                config.flags |= GPIO_V2_LINE_FLAG_EDGE_RISING;
+            }
+            if state.irq_type & VIRTIO_GPIO_IRQ_TYPE_EDGE_FALLING != 0 {
+                config.flags |= GPIO_V2_LINE_FLAG_EDGE_FALLING;
+            }
+        }
+        config
+    }
+
+    /// Requests the line on the host, or updates the configuration of its request.
+    fn request_line(&mut self, line: u16) -> Result<(), GpioBackendError> {
+        let index = usize::from(line);
+        let mut config = Self::line_config(&self.lines[index]);
+        if let Some(request) = &self.requests[index] {
+            // SAFETY: `config` matches the layout expected by the ioctl. The return value is
+            // checked.
+            if unsafe { ioctl_with_mut_ref(request, GPIO_V2_LINE_SET_CONFIG_IOCTL(), &mut config) }
+                < 0
+            {
+                return Err(GpioBackendError::RequestLine(
+                    line,
+                    std::io::Error::last_os_error(),
+                ));
+            }
+            return Ok(());
+        }
+
+        // SAFETY: All the fields of the request are plain integers, for which zero is valid.
+        let mut request: GpioV2LineRequest = unsafe { std::mem::zeroed() };
+        request.offsets[0] = u32::from(line);
+        request.consumer[..CONSUMER.len()].copy_from_slice(CONSUMER);
+        request.config = config;
+        request.num_lines = 1;
+        // SAFETY: `request` matches the layout expected by the ioctl. The return value is
+        // checked.
+        if unsafe { ioctl_with_mut_ref(&self.chip, GPIO_V2_GET_LINE_IOCTL(), &mut request) } < 0 {
+            return Err(GpioBackendError::RequestLine(
+                line,
+                std::io::Error::last_os_error(),
+            ));
+        }
+        // SAFETY: The ioctl succeeded, so `fd` is a valid FD we own.
+        let file = unsafe { File::from_raw_fd(request.fd) };
+        self.epoll
+            .ctl(
+                ControlOperation::Add,
+                file.as_raw_fd(),
+                EpollEvent::new(EventSet::IN, u64::from(line)),
+            )
+            .map_err(GpioBackendError::EpollAdd)?;
+        self.requests[index] = Some(file);
+        Ok(())
+    }
+
+    /// Releases the line on the host.
+    fn release_line(&mut self, line: u16) {
+        if let Some(file) = self.requests[usize::from(line)].take() {
+            self.epoll
+                .ctl(
+                    ControlOperation::Delete,
+                    file.as_raw_fd(),
+                    EpollEvent::default(),
+                )
+                .unwrap_or_else(|err| warn!("gpio: Failed to unregister line {line}: {err}"));
+        }
+    }
+
+    fn set_direction(&mut self, line: u16, direction: u8) -> Result<(), GpioBackendError> {
+        self.lines[usize::from(line)].direction = direction;
+        if direction == VIRTIO_GPIO_DIRECTION_NONE {
+            self.release_line(line);
+            Ok(())
+        } else {
+            self.request_line(line)
+        }
+    }
+
+    fn value(&mut self, line: u16) -> Result<u8, GpioBackendError> {
+        let request = self.requests[usize::from(line)]
+            .as_ref()
+            .ok_or(GpioBackendError::LineNotRequested(line))?;
+        let mut values = GpioV2LineValues { bits: 0, mask: 1 };
+        // SAFETY: `values` matches the layout expected by the ioctl. The return value is checked.
+        if unsafe { ioctl_with_mut_ref(request, GPIO_V2_LINE_GET_VALUES_IOCTL(), &mut values) } < 0
+        {
+            return Err(GpioBackendError::LineValue(
+                line,
+                std::io::Error::last_os_error(),
+            ));
+        }
+        let value = u8::from(values.bits & 1 != 0);
+        self.lines[usize::from(line)].value = value;
+        Ok(value)
+    }
+
+    fn set_value(&mut self, line: u16, value: u8) -> Result<(), GpioBackendError> {
+        let index = usize::from(line);
+        self.lines[index].value = value;
+        // The value of the other lines is applied when they become outputs.
+        if self.lines[index].direction != VIRTIO_GPIO_DIRECTION_OUT {
+            return Ok(());
+        }
+        let Some(request) = &self.requests[index] else {
+            return Ok(());
+        };
+        let mut values = GpioV2LineValues {
+            bits: u64::from(value),
+            mask: 1,
+        };
+        // SAFETY: `values` matches the layout expected by the ioctl. The return value is checked.
+        if unsafe { ioctl_with_mut_ref(request, GPIO_V2_LINE_SET_VALUES_IOCTL(), &mut values) } < 0
+        {
+            return Err(GpioBackendError::LineValue(
+                line,
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn set_irq_type(&mut self, line: u16, irq_type: u8) -> Result<(), GpioBackendError> {
+        if irq_type & (VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH | VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW) != 0 {
+            return Err(GpioBackendError::UnsupportedIrqType);
+        }
+        self.lines[usize::from(line)].irq_type = irq_type;
+        if self.lines[usize::from(line)].direction == VIRTIO_GPIO_DIRECTION_IN {
+            self.request_line(line)?;
+        }
+        Ok(())
+    }
+
+    /// Consumes the edges reported by the host, returning the lines they occurred on.
+    fn process_events(&mut self) -> Vec<u16> {
+        let mut triggered = Vec::new();
+        let mut epoll_events = vec![EpollEvent::new(EventSet::empty(), 0); 32];
+        let ev_cnt = match self.epoll.wait(0, epoll_events.as_mut_slice()) {
+            Ok(ev_cnt) => ev_cnt,
+            Err(err) => {
+                warn!("gpio: Failed to consume host gpiochip events: {err}");
+                return triggered;
+            }
+        };
+        for ev in &epoll_events[..ev_cnt] {
+            let line = u16::try_from(ev.data()).unwrap();
+            let Some(request) = self.requests[usize::from(line)].as_mut() else {
+                continue;
+            };
+            // Epoll reported the request as readable, so this does not block. Events left
+            // unread are reported again by the next `wait`.
+            let mut buf = [0u8; GPIO_V2_LINE_EVENT_SIZE * 16];
+            match request.read(&mut buf) {
+                Ok(len) if len >= GPIO_V2_LINE_EVENT_SIZE => triggered.push(line),
+                Ok(_) => {}
+                Err(err) => warn!("gpio: Failed to read the events of line {line}: {err}"),
+            }
+        }
+        triggered
+    }
+
+    fn restore_lines(&mut self, lines: &[GpioLineState]) -> Result<(), GpioBackendError> {
+        for (line, state) in (0..=u16::MAX).zip(lines) {
+            self.lines[usize::from(line)] = *state;
+            if state.direction != VIRTIO_GPIO_DIRECTION_NONE {
+                self.request_line(line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A connection to the control socket of the mock lines.
+#[derive(Debug)]
+struct MockConnection {
+    stream: UnixStream,
+    /// Bytes received after the last complete command.
+    pending: Vec<u8>,
+}
+
+/// Lines emulated by Firecracker, driven from the host through a Unix socket.
+///
+/// The socket accepts newline terminated commands:
+/// - `get <line>`: replies with the value of the line;
+/// - `set <line> <0|1>`: drives the line, which must not be an output of the guest, and fires
+///   its interrupt if the change matches its trigger;
+/// - `direction <line>`: replies with `none`, `in` or `out`.
+///
+/// Errors are reported with a reply starting with `error:`, other commands reply `ok`.
+#[derive(Debug)]
+pub struct MockGpioChip {
+    names: Vec<String>,
+    pub lines: Vec<GpioLineState>,
+    listener: UnixListener,
+    connections: HashMap<RawFd, MockConnection>,
+    epoll: Epoll,
+}
+
+impl MockGpioChip {
+    pub fn new(config: &GpioMockConfig) -> Result<Self, GpioBackendError> {
+        let num_lines = usize::from(config.num_lines);
+        if num_lines == 0 {
+            return Err(GpioBackendError::InvalidLineCount(num_lines));
+        }
+        if config.line_names.len() > num_lines {
+            return Err(GpioBackendError::TooManyNames);
+        }
+        let mut names = config.line_names.clone();
+        names.resize(num_lines, String::new());
+
+        let listener = UnixListener::bind(&config.socket_path)
+            .and_then(|sock| sock.set_nonblocking(true).map(|_| sock))
+            .map_err(GpioBackendError::Bind)?;
+        let epoll = Epoll::new().map_err(GpioBackendError::EpollCreate)?;
+        epoll
+            .ctl(
+                ControlOperation::Add,
+                listener.as_raw_fd(),
+                EpollEvent::new(EventSet::IN, MOCK_LISTENER),
+            )
+            .map_err(GpioBackendError::EpollAdd)?;
+
+        Ok(Self {
+            names,
+            lines: vec![GpioLineState::default(); num_lines],
+            listener,
+            connections: HashMap::new(),
+            epoll,
+        })
+    }
+
+    /// Drives an input line from the host. Returns whether the interrupt of the line fires.
+    pub fn drive(&mut self, line: u16, value: u8) -> Result<bool, GpioBackendError> {
+        let state = &mut self.lines[usize::from(line)];
+        if state.direction == VIRTIO_GPIO_DIRECTION_OUT {
+            return Err(GpioBackendError::LineIsOutput(line));
+        }
+        let old = std::mem::replace(&mut state.value, value);
+        Ok(
+            state.direction == VIRTIO_GPIO_DIRECTION_IN
+                && irq_triggered(state.irq_type, old, value),
+        )
+    }
+
+    fn irq_asserted(&self, line: u16) -> bool {
+        let state = &self.lines[usize::from(line)];
+        state.direction == VIRTIO_GPIO_DIRECTION_IN
+            && matches!(
+                (state.irq_type, state.value),
+                (VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH, 1) | (VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW, 0)
+            )
+    }
+
+    /// Parses the number of a line given in a command.
+    fn parse_line(&self, line: &str) -> Result<u16, String> {
+        line.parse::<u16>()
+            .ok()
+            .filter(|line| usize::from(*line) < self.lines.len())
+            .ok_or_else(|| format!("invalid line {line}"))
+    }
+
+    /// Executes a command received on the control socket, returning the reply.
+    fn command(&mut self, command: &str, triggered: &mut Vec<u16>) -> Result<String, String> {
+        let args = command.split_whitespace().collect::<Vec<_>>();
+        match args.as_slice() {
+            ["get", line] => {
+                let line = self.parse_line(line)?;
+                Ok(self.lines[usize::from(line)].value.to_string())
+            }
+            ["set", line, value] => {
+                let line = self.parse_line(line)?;
+                let value = match *value {
+                    "0" => 0,
+                    "1" => 1,
+                    _ => return Err(format!("invalid value {value}")),
+                };
+                if self.drive(line, value).map_err(|err| err.to_string())? {
+                    triggered.push(line);
+                }
+                Ok("ok".to_string())
+            }
+            ["direction", line] => {
+                let line = self.parse_line(line)?;
+                Ok(match self.lines[usize::from(line)].direction {
+                    VIRTIO_GPIO_DIRECTION_OUT => "out",
+                    VIRTIO_GPIO_DIRECTION_IN => "in",
+                    _ => "none",
+                }
+                .to_string())
+            }
+            _ => Err(format!("unknown command {command:?}")),
+        }
+    }
+
+    fn accept_connections(&mut self) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    warn!("gpio: Failed to accept a control connection: {err}");
+                    return;
+                }
+            };
+            let fd = stream.as_raw_fd();
+            let registered = stream.set_nonblocking(true).and_then(|_| {
+                self.epoll.ctl(
+                    ControlOperation::Add,
+                    fd,
+                    EpollEvent::new(EventSet::IN, u64::try_from(fd).unwrap()),
+                )
+            });
+            match registered {
+                Ok(()) => {
+                    self.connections.insert(
+                        fd,
+                        MockConnection {
+                            stream,
+                            pending: Vec::new(),
+                        },
+                    );
+                }
+                Err(err) => warn!("gpio: Failed to register a control connection: {err}"),
+            }
+        }
+    }
+
+    /// Executes the commands received on a connection. Returns whether the connection is still
+    /// open.
+    fn handle_connection(&mut self, conn: &mut MockConnection, triggered: &mut Vec<u16>) -> bool {
+        let mut buf = [0u8; 256];
+        match conn.stream.read(&mut buf) {
+            Ok(0) => return false,
+            Ok(len) => conn.pending.extend_from_slice(&buf[..len]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+            Err(err) => {
+                warn!("gpio: Failed to read from a control connection: {err}");
+                return false;
+            }
+        }
+        while let Some(pos) = conn.pending.iter().position(|b| *b == b'\n') {
+            let command = conn.pending.drain(..=pos).collect::<Vec<_>>();
+            let reply = match self.command(&String::from_utf8_lossy(&command), triggered) {
+                Ok(reply) => reply,
+                Err(err) => format!("error: {err}"),
+            };
+            if conn
+                .stream
+                .write_all(format!("{reply}\n").as_bytes())
+                .is_err()
+            {
+                return false;
+            }
+        }
+        if conn.pending.len() > MOCK_MAX_COMMAND_LEN {
+            // The reply is best effort, the connection is closed anyway.
+            let _ = conn.stream.write_all(b"error: command too long\n");
+            return false;
+        }
+        true
+    }
+
+    /// Serves the control socket, returning the lines whose interrupt fired.
+    fn process_events(&mut self) -> Vec<u16> {
+        let mut triggered = Vec::new();
+        let mut epoll_events = vec![EpollEvent::new(EventSet::empty(), 0); 32];
+        let ev_cnt = match self.epoll.wait(0, epoll_events.as_mut_slice()) {
+            Ok(ev_cnt) => ev_cnt,
+            Err(err) => {
+                warn!("gpio: Failed to consume control socket events: {err}");
+                return triggered;
+            }
+        };
+        for ev in &epoll_events[..ev_cnt] {
+            if ev.data() == MOCK_LISTENER {
+                self.accept_connections();
+                continue;
+            }
+            let fd = RawFd::try_from(ev.data()).unwrap();
+            let Some(mut conn) = self.connections.remove(&fd) else {
+                continue;
+            };
+            if self.handle_connection(&mut conn, &mut triggered) {
+                self.connections.insert(fd, conn);
+            } else if let Err(err) =
+                self.epoll
+                    .ctl(ControlOperation::Delete, fd, EpollEvent::default())
+            {
+                warn!("gpio: Failed to unregister a control connection: {err}");
+            }
+        }
+        triggered
+    }
+}
+
+/// Backend of a virtio-gpio device.
+#[derive(Debug)]
+pub enum GpioBackend {
+    Host(HostGpioChip),
+    Mock(MockGpioChip),
+}
+
+impl GpioBackend {
+    pub fn new(config: &GpioConfig) -> Result<Self, GpioBackendError> {
+        match (&config.host_chip, &config.mock) {
+            (Some(path), None) => Ok(Self::Host(HostGpioChip::new(path)?)),
+            (None, Some(mock)) => Ok(Self::Mock(MockGpioChip::new(mock)?)),
+            _ => Err(GpioBackendError::InvalidBackend),
+        }
+    }
+
+    /// Names of the lines.
+    pub fn names(&self) -> &[String] {
+        match self {
+            Self::Host(chip) => &chip.names,
+            Self::Mock(chip) => &chip.names,
+        }
+    }
+
+    /// State of the lines.
+    pub fn lines(&self) -> &[GpioLineState] {
+        match self {
+            Self::Host(chip) => &chip.lines,
+            Self::Mock(chip) => &chip.lines,
+        }
+    }
+
+    pub fn set_direction(&mut self, line: u16, direction: u8) -> Result<(), GpioBackendError> {
+        match self {
+            Self::Host(chip) => chip.set_direction(line, direction),
+            Self::Mock(chip) => {
+                chip.lines[usize::from(line)].direction = direction;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn value(&mut self, line: u16) -> Result<u8, GpioBackendError> {
+        match self {
+            Self::Host(chip) => chip.value(line),
+            Self::Mock(chip) => Ok(chip.lines[usize::from(line)].value),
+        }
+    }
+
+    pub fn set_value(&mut self, line: u16, value: u8) -> Result<(), GpioBackendError> {
+        match self {
+            Self::Host(chip) => chip.set_value(line, value),
+            Self::Mock(chip) => {
+                chip.lines[usize::from(line)].value = value;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_irq_type(&mut self, line: u16, irq_type: u8) -> Result<(), GpioBackendError> {
+        match self {
+            Self::Host(chip) => chip.set_irq_type(line, irq_type),
+            Self::Mock(chip) => {
+                chip.lines[usize::from(line)].irq_type = irq_type;
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the level interrupt of a line is asserted.
+    pub fn irq_asserted(&self, line: u16) -> bool {
+        match self {
+            Self::Host(_) => false,
+            Self::Mock(chip) => chip.irq_asserted(line),
+        }
+    }
+
+    /// Handles the events pending under the epoll FD of the backend, returning the lines whose
+    /// interrupt fired.
+    pub fn process_events(&mut self) -> Vec<u16> {
+        match self {
+            Self::Host(chip) => chip.process_events(),
+            Self::Mock(chip) => chip.process_events(),
+        }
+    }
+
+    /// Restores the state of the lines saved in a snapshot.
+    pub fn restore_lines(&mut self, lines: &[GpioLineState]) -> Result<(), GpioBackendError> {
+        if lines.len() != self.lines().len() {
+            return Err(GpioBackendError::InvalidLineCount(lines.len()));
+        }
+        match self {
+            Self::Host(chip) => chip.restore_lines(lines),
+            Self::Mock(chip) => {
+                chip.lines.copy_from_slice(lines);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl AsRawFd for GpioBackend {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::Host(chip) => chip.epoll.as_raw_fd(),
+            Self::Mock(chip) => chip.epoll.as_raw_fd(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::gpio::VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH;
+
+    fn mock_config(dir: &TempDir) -> GpioConfig {
+        GpioConfig {
+            id: "gpio".into(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: dir.as_path().join("gpio.sock").to_str().unwrap().into(),
+                num_lines: 4,
+                line_names: vec!["led".into(), "button".into()],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_backend_config() {
+        let dir = TempDir::new().unwrap();
+        assert!(matches!(
+            GpioBackend::new(&GpioConfig::default()).unwrap_err(),
+            GpioBackendError::InvalidBackend
+        ));
+        let mut config = mock_config(&dir);
+        config.host_chip = Some("/dev/gpiochip0".into());
+        assert!(matches!(
+            GpioBackend::new(&config).unwrap_err(),
+            GpioBackendError::InvalidBackend
+        ));
+        config.mock = None;
+        config.host_chip = Some("/does/not/exist".into());
+        assert!(matches!(
+            GpioBackend::new(&config).unwrap_err(),
+            GpioBackendError::OpenChip(_)
+        ));
+
+        let mut config = mock_config(&dir);
+        config.mock.as_mut().unwrap().num_lines = 0;
+        assert!(matches!(
+            GpioBackend::new(&config).unwrap_err(),
+            GpioBackendError::InvalidLineCount(0)
+        ));
+        config.mock.as_mut().unwrap().num_lines = 1;
+        assert!(matches!(
+            GpioBackend::new(&config).unwrap_err(),
+            GpioBackendError::TooManyNames
+        ));
+
+        let backend = GpioBackend::new(&mock_config(&dir)).unwrap();
+        assert_eq!(backend.names(), ["led", "button", "", ""]);
+        assert_eq!(backend.lines().len(), 4);
+        // The socket is already bound.
+        assert!(matches!(
+            GpioBackend::new(&mock_config(&dir)).unwrap_err(),
+            GpioBackendError::Bind(_)
+        ));
+    }
+
+    #[test]
+    fn test_mock_irq() {
+        let dir = TempDir::new().unwrap();
+        let mut backend = GpioBackend::new(&mock_config(&dir)).unwrap();
+        let GpioBackend::Mock(chip) = &mut backend else {
+            panic!("Unexpected backend");
+        };
+
+        // Lines without direction never fire.
+        chip.lines[1].irq_type = VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING;
+        assert!(!chip.drive(1, 1).unwrap());
+        chip.drive(1, 0).unwrap();
+
+        chip.lines[1].direction = VIRTIO_GPIO_DIRECTION_IN;
+        assert!(chip.drive(1, 1).unwrap());
+        assert!(!chip.drive(1, 1).unwrap());
+        assert!(!chip.drive(1, 0).unwrap());
+
+        chip.lines[1].irq_type = VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW;
+        assert!(chip.drive(1, 0).unwrap());
+        assert!(chip.irq_asserted(1));
+        chip.drive(1, 1).unwrap();
+        assert!(!chip.irq_asserted(1));
+
+        chip.lines[0].direction = VIRTIO_GPIO_DIRECTION_OUT;
+        assert!(matches!(
+            chip.drive(0, 1).unwrap_err(),
+            GpioBackendError::LineIsOutput(0)
+        ));
+    }
+
+    #[test]
+    fn test_mock_socket() {
+        let dir = TempDir::new().unwrap();
+        let config = mock_config(&dir);
+        let mut backend = GpioBackend::new(&config).unwrap();
+        backend.set_direction(0, VIRTIO_GPIO_DIRECTION_OUT).unwrap();
+        backend.set_value(0, 1).unwrap();
+        backend.set_direction(1, VIRTIO_GPIO_DIRECTION_IN).unwrap();
+        backend
+            .set_irq_type(1, VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH)
+            .unwrap();
+
+        let mut stream = UnixStream::connect(&config.mock.as_ref().unwrap().socket_path).unwrap();
+        assert!(backend.process_events().is_empty());
+
+        stream
+            .write_all(b"get 0\ndirection 1\nset 1 1\nset 0 0\nfoo\nget 9\n")
+            .unwrap();
+        assert_eq!(backend.process_events(), [1]);
+        assert_eq!(backend.value(1).unwrap(), 1);
+
+        let mut reader = BufReader::new(stream);
+        let replies = (0..6)
+            .map(|_| {
+                let mut reply = String::new();
+                reader.read_line(&mut reply).unwrap();
+                reply
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(replies[0], "1\n");
+        assert_eq!(replies[1], "in\n");
+        assert_eq!(replies[2], "ok\n");
+        assert!(replies[3].starts_with("error:"));
+        assert!(replies[4].starts_with("error:"));
+        assert!(replies[5].starts_with("error:"));
+    }
+}
@@ -0,0 +1,729 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use vm_memory::GuestMemoryError;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::backend::{GpioBackend, GpioBackendError};
+use super::metrics::{GpioMetrics, GpioMetricsPerDevice};
+use super::{
+    EVENT_QUEUE, GPIO_QUEUE_SIZE, REQUEST_QUEUE, VIRTIO_GPIO_DIRECTION_IN,
+    VIRTIO_GPIO_DIRECTION_NONE, VIRTIO_GPIO_DIRECTION_OUT, VIRTIO_GPIO_F_IRQ,
+    VIRTIO_GPIO_IRQ_STATUS_INVALID, VIRTIO_GPIO_IRQ_STATUS_VALID, VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH,
+    VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH, VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW, VIRTIO_GPIO_IRQ_TYPE_NONE,
+    VIRTIO_GPIO_MSG_GET_DIRECTION, VIRTIO_GPIO_MSG_GET_NAMES, VIRTIO_GPIO_MSG_GET_VALUE,
+    VIRTIO_GPIO_MSG_IRQ_TYPE, VIRTIO_GPIO_MSG_SET_DIRECTION, VIRTIO_GPIO_MSG_SET_VALUE,
+    VIRTIO_GPIO_STATUS_ERR, VIRTIO_GPIO_STATUS_OK,
+};
+use crate::devices::virtio::ActivateError;
+use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
+use crate::devices::virtio::queue::{DescriptorChain, InvalidAvailIdx, Queue, QueueError};
+use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
+use crate::impl_device_type;
+use crate::logger::{IncMetric, debug, error, info};
+use crate::utils::u64_to_usize;
+use crate::vmm_config::gpio::GpioConfig;
+use crate::vstate::memory::{Bytes, GuestAddress, GuestMemoryMmap};
+
+/// Size of a request: type (`le16`), line (`le16`) and value (`le32`).
+const REQUEST_SIZE: u32 = 8;
+/// Size of a response: status (`u8`) and value (`u8`).
+const RESPONSE_SIZE: u32 = 2;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GpioError {
+    /// Error creating the backend: {0}
+    Backend(#[from] GpioBackendError),
+    /// Error with EventFd: {0}
+    EventFd(std::io::Error),
+    /// Received a descriptor chain which is too short
+    DescriptorChainTooShort,
+    /// Received a descriptor with an unexpected direction
+    UnexpectedDescriptorDirection,
+    /// Received a descriptor which is too small
+    DescriptorTooSmall,
+    /// Invalid line {0}
+    InvalidLine(u16),
+    /// Invalid argument {1} for request {0}
+    InvalidArgument(u16, u32),
+    /// Unknown request {0}
+    UnknownRequest(u16),
+    /// The lines have no names, or the response cannot hold them
+    NoNames,
+    /// Interrupts were not negotiated by the driver
+    IrqNotNegotiated,
+    /// Guest memory error: {0}
+    GuestMemory(#[from] GuestMemoryError),
+    /// Error handling the VirtIO queue: {0}
+    Queue(#[from] QueueError),
+    /// Error during obtaining the descriptor from the queue: {0}
+    QueuePop(#[from] InvalidAvailIdx),
+}
+
+/// Buffer of the event queue waiting for the interrupt of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpioIrqBuffer {
+    /// Index of the head of the descriptor chain.
+    pub index: u16,
+    /// Address of the status byte.
+    pub status_addr: u64,
+}
+
+/// State of the interrupt of a line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpioIrq {
+    /// Buffer queued by the driver to be notified of the next interrupt.
+    pub buffer: Option<GpioIrqBuffer>,
+    /// An edge occurred while no buffer was queued.
+    pub latched: bool,
+}
+
+#[derive(Debug)]
+pub struct Gpio {
+    // VirtIO fields
+    pub avail_features: u64,
+    pub acked_features: u64,
+    pub activate_event: EventFd,
+
+    // Transport fields
+    pub device_state: DeviceState,
+    pub queues: Vec<Queue>,
+    pub queue_events: Vec<EventFd>,
+
+    // GPIO specific fields
+    pub backend: GpioBackend,
+    pub irqs: Vec<GpioIrq>,
+    pub metrics: Arc<GpioMetrics>,
+    /// `ngpio` (`le16`), padding and `gpio_names_size` (`le32`).
+    pub config_space: Vec<u8>,
+    /// NUL terminated names of the lines, returned by `VIRTIO_GPIO_MSG_GET_NAMES`.
+    names: Vec<u8>,
+
+    pub config: GpioConfig,
+}
+
+impl Gpio {
+    /// Create a new virtio-gpio device.
+    pub fn new(config: GpioConfig) -> Result<Self, GpioError> {
+        let queues = vec![Queue::new(GPIO_QUEUE_SIZE), Queue::new(GPIO_QUEUE_SIZE)];
+        Self::new_with_queues(config, queues)
+    }
+
+    /// Create a new virtio-gpio device using a pre-created set of queues.
+    pub fn new_with_queues(config: GpioConfig, queues: Vec<Queue>) -> Result<Self, GpioError> {
+        let backend = GpioBackend::new(&config)?;
+        let num_lines = backend.lines().len();
+
+        // The driver does not request the names if they are all empty.
+        let mut names = Vec::new();
+        if backend.names().iter().any(|name| !name.is_empty()) {
+            for name in backend.names() {
+                names.extend_from_slice(name.as_bytes());
+                names.push(0);
+            }
+        }
+        let mut config_space = u16::try_from(num_lines).unwrap().to_le_bytes().to_vec();
+        config_space.extend_from_slice(&[0; 2]);
+        config_space.extend_from_slice(&u32::try_from(names.len()).unwrap().to_le_bytes());
+
+        Ok(Self {
+            avail_features: (1u64 << VIRTIO_F_VERSION_1) | (1u64 << VIRTIO_GPIO_F_IRQ),
+            acked_features: 0u64,
+            activate_event: EventFd::new(libc::EFD_NONBLOCK).map_err(GpioError::EventFd)?,
+            device_state: DeviceState::Inactive,
+            queues,
+            queue_events: vec![
+                EventFd::new(libc::EFD_NONBLOCK).map_err(GpioError::EventFd)?,
+                EventFd::new(libc::EFD_NONBLOCK).map_err(GpioError::EventFd)?,
+            ],
+            backend,
+            irqs: vec![GpioIrq::default(); num_lines],
+            metrics: GpioMetricsPerDevice::alloc(config.id.clone()),
+            config_space,
+            names,
+            config,
+        })
+    }
+
+    /// Guest memory of the activated device.
+    fn mem(&self) -> &GuestMemoryMmap {
+        // This is safe since we checked in the event handler that the device is activated.
+        &self.device_state.active_state().unwrap().mem
+    }
+
+    fn signal_used_queue(&mut self, queue_index: usize) {
+        self.queues[queue_index].advance_used_ring_idx();
+
+        if self.queues[queue_index].prepare_kick() {
+            // This is safe since we checked in the event handler that the device is activated.
+            let active_state = self.device_state.active_state().unwrap();
+            active_state
+                .interrupt
+                .trigger(VirtioInterruptType::Queue(
+                    u16::try_from(queue_index).unwrap(),
+                ))
+                .unwrap_or_else(|err| {
+                    error!("gpio: {err}");
+                    self.metrics.event_fails.inc();
+                });
+        }
+    }
+
+    /// Returns the device-readable and the device-writable descriptors of a chain made of
+    /// exactly one of each.
+    fn split_chain(
+        head: DescriptorChain,
+        min_readable: u32,
+        min_writable: u32,
+    ) -> Result<(GuestAddress, GuestAddress, u32), GpioError> {
+        if head.is_write_only() {
+            return Err(GpioError::UnexpectedDescriptorDirection);
+        }
+        if head.len < min_readable {
+            return Err(GpioError::DescriptorTooSmall);
+        }
+        let writable = head
+            .next_descriptor()
+            .ok_or(GpioError::DescriptorChainTooShort)?;
+        if !writable.is_write_only() {
+            return Err(GpioError::UnexpectedDescriptorDirection);
+        }
+        if writable.len < min_writable {
+            return Err(GpioError::DescriptorTooSmall);
+        }
+        Ok((head.addr, writable.addr, writable.len))
+    }
+
+    /// Executes the requests of the request queue.
+    pub fn handle_request_queue(&mut self) -> Result<(), GpioError> {
+        let event_next_used = self.queues[EVENT_QUEUE].next_used;
+        while let Some(head) = self.queues[REQUEST_QUEUE].pop()? {
+            let index = head.index;
+            let used_len = self.process_request(head).unwrap_or_else(|err| {
+                error!("gpio: {err}");
+                self.metrics.event_fails.inc();
+                0
+            });
+            self.queues[REQUEST_QUEUE].add_used(index, used_len)?;
+        }
+        self.signal_used_queue(REQUEST_QUEUE);
+        // Disabling interrupts returns buffers of the event queue.
+        if self.queues[EVENT_QUEUE].next_used != event_next_used {
+            self.signal_used_queue(EVENT_QUEUE);
+        }
+        Ok(())
+    }
+
+    /// Executes a request, returning the number of bytes written to the response.
+    fn process_request(&mut self, head: DescriptorChain) -> Result<u32, GpioError> {
+        let (request_addr, response_addr, response_len) =
+            Self::split_chain(head, REQUEST_SIZE, RESPONSE_SIZE)?;
+        let mut request = [0u8; 8];
+        self.mem().read_slice(&mut request, request_addr)?;
+        let msg_type = u16::from_le_bytes([request[0], request[1]]);
+        let line = u16::from_le_bytes([request[2], request[3]]);
+        let value = u32::from_le_bytes([request[4], request[5], request[6], request[7]]);
+
+        self.metrics.request_count.inc();
+        if msg_type == VIRTIO_GPIO_MSG_GET_NAMES {
+            // The names are followed by the status byte in the response.
+            if self.names.is_empty() || usize::try_from(response_len).unwrap() <= self.names.len() {
+                debug!("gpio: Request failed: {}", GpioError::NoNames);
+                self.metrics.request_fails.inc();
+                self.mem()
+                    .write_obj(VIRTIO_GPIO_STATUS_ERR, response_addr)?;
+                return Ok(1);
+            }
+            let mut response = vec![VIRTIO_GPIO_STATUS_OK];
+            response.extend_from_slice(&self.names);
+            self.mem().write_slice(&response, response_addr)?;
+            return Ok(u32::try_from(response.len()).unwrap());
+        }
+
+        let response = match self.execute(msg_type, line, value) {
+            Ok(value) => [VIRTIO_GPIO_STATUS_OK, value],
+            Err(err) => {
+                debug!("gpio: Request failed: {err}");
+                self.metrics.request_fails.inc();
+                [VIRTIO_GPIO_STATUS_ERR, 0]
+            }
+        };
+        self.mem().write_slice(&response, response_addr)?;
+        Ok(RESPONSE_SIZE)
+    }
+
+    /// Executes a request on a line, returning the value of the response.
+    fn execute(&mut self, msg_type: u16, line: u16, value: u32) -> Result<u8, GpioError> {
+        if usize::from(line) >= self.irqs.len() {
+            return Err(GpioError::InvalidLine(line));
+        }
+        let invalid_argument = || GpioError::InvalidArgument(msg_type, value);
+        match msg_type {
+            VIRTIO_GPIO_MSG_GET_DIRECTION => Ok(self.backend.lines()[usize::from(line)].direction),
+            VIRTIO_GPIO_MSG_SET_DIRECTION => {
+                let direction = u8::try_from(value)
+                    .ok()
+                    .filter(|direction| {
+                        matches!(
+                            *direction,
+                            VIRTIO_GPIO_DIRECTION_NONE
+                                | VIRTIO_GPIO_DIRECTION_OUT
+                                | VIRTIO_GPIO_DIRECTION_IN
+                        )
+                    })
+                    .ok_or_else(invalid_argument)?;
+                self.backend.set_direction(line, direction)?;
+                Ok(0)
+            }
+            VIRTIO_GPIO_MSG_GET_VALUE => Ok(self.backend.value(line)?),
+            VIRTIO_GPIO_MSG_SET_VALUE => {
+                let value = u8::try_from(value)
+                    .ok()
+                    .filter(|value| *value <= 1)
+                    .ok_or_else(invalid_argument)?;
+                self.backend.set_value(line, value)?;
+                Ok(0)
+            }
+            VIRTIO_GPIO_MSG_IRQ_TYPE => {
+                if self.acked_features & (1u64 << VIRTIO_GPIO_F_IRQ) == 0 {
+                    return Err(GpioError::IrqNotNegotiated);
+                }
+                let irq_type = u8::try_from(value)
+                    .ok()
+                    .filter(|irq_type| {
+                        *irq_type <= VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH
+                            || *irq_type == VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH
+                            || *irq_type == VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW
+                    })
+                    .ok_or_else(invalid_argument)?;
+                self.backend.set_irq_type(line, irq_type)?;
+                let irq = &mut self.irqs[usize::from(line)];
+                irq.latched = false;
+                // The buffer waiting for the interrupt is returned to the driver when the
+                // interrupt is disabled.
+                if irq_type == VIRTIO_GPIO_IRQ_TYPE_NONE {
+                    self.complete_irq(line, VIRTIO_GPIO_IRQ_STATUS_INVALID)?;
+                }
+                Ok(0)
+            }
+            _ => Err(GpioError::UnknownRequest(msg_type)),
+        }
+    }
+
+    /// Completes the buffer waiting for the interrupt of a line, if any.
+    fn complete_irq(&mut self, line: u16, status: u8) -> Result<(), GpioError> {
+        let Some(buffer) = self.irqs[usize::from(line)].buffer.take() else {
+            return Ok(());
+        };
+        self.mem()
+            .write_obj(status, GuestAddress(buffer.status_addr))?;
+        self.queues[EVENT_QUEUE].add_used(buffer.index, 1)?;
+        if status == VIRTIO_GPIO_IRQ_STATUS_VALID {
+            self.metrics.irq_count.inc();
+        }
+        Ok(())
+    }
+
+    /// Notifies the interrupt of a line if the driver is waiting for it, or latches edges until
+    /// it does.
+    fn trigger_irq(&mut self, line: u16) -> Result<(), GpioError> {
+        let irq = &mut self.irqs[usize::from(line)];
+        if irq.buffer.is_some() {
+            self.complete_irq(line, VIRTIO_GPIO_IRQ_STATUS_VALID)
+        } else {
+            // Level interrupts are checked again when the driver queues a buffer.
+            irq.latched = self.backend.lines()[usize::from(line)].irq_type
+                & VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH
+                != 0;
+            Ok(())
+        }
+    }
+
+    /// Takes the buffers queued by the driver to wait for the interrupts of the lines.
+    pub fn handle_event_queue(&mut self) -> Result<(), GpioError> {
+        let next_used = self.queues[EVENT_QUEUE].next_used;
+        while let Some(head) = self.queues[EVENT_QUEUE].pop()? {
+            let index = head.index;
+            let (line_addr, status_addr) = match Self::split_chain(head, 2, 1) {
+                Ok((line_addr, status_addr, _)) => (line_addr, status_addr),
+                Err(err) => {
+                    error!("gpio: {err}");
+                    self.metrics.event_fails.inc();
+                    self.queues[EVENT_QUEUE].add_used(index, 0)?;
+                    continue;
+                }
+            };
+            let line = u16::from_le(self.mem().read_obj::<u16>(line_addr)?);
+
+            let valid = self
+                .backend
+                .lines()
+                .get(usize::from(line))
+                .is_some_and(|state| {
+                    state.irq_type != VIRTIO_GPIO_IRQ_TYPE_NONE
+                        && self.irqs[usize::from(line)].buffer.is_none()
+                });
+            if !valid {
+                self.mem()
+                    .write_obj(VIRTIO_GPIO_IRQ_STATUS_INVALID, status_addr)?;
+                self.queues[EVENT_QUEUE].add_used(index, 1)?;
+                continue;
+            }
+
+            let irq = &mut self.irqs[usize::from(line)];
+            irq.buffer = Some(GpioIrqBuffer {
+                index,
+                status_addr: status_addr.0,
+            });
+            if std::mem::take(&mut irq.latched) || self.backend.irq_asserted(line) {
+                self.complete_irq(line, VIRTIO_GPIO_IRQ_STATUS_VALID)?;
+            }
+        }
+        if self.queues[EVENT_QUEUE].next_used != next_used {
+            self.signal_used_queue(EVENT_QUEUE);
+        }
+        Ok(())
+    }
+
+    /// Handles the events of the backend, and notifies the interrupts they fire.
+    pub fn process_backend_event(&mut self) {
+        let triggered = self.backend.process_events();
+        // The control socket of the mock lines is served before the device is activated, but
+        // interrupts can only be configured once it is.
+        if !self.is_activated() {
+            return;
+        }
+        let event_next_used = self.queues[EVENT_QUEUE].next_used;
+        for line in triggered {
+            self.trigger_irq(line).unwrap_or_else(|err| {
+                error!("gpio: {err}");
+                self.metrics.event_fails.inc();
+            });
+        }
+        if self.queues[EVENT_QUEUE].next_used != event_next_used {
+            self.signal_used_queue(EVENT_QUEUE);
+        }
+    }
+
+    pub fn process_request_queue(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events[REQUEST_QUEUE].read() {
+            error!("gpio: Failed to get request queue event: {err:?}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.handle_request_queue().unwrap_or_else(|err| {
+            error!("gpio: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    pub fn process_event_queue(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events[EVENT_QUEUE].read() {
+            error!("gpio: Failed to get event queue event: {err:?}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.handle_event_queue().unwrap_or_else(|err| {
+            error!("gpio: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+}
+
+impl VirtioDevice for Gpio {
+    impl_device_type!(VirtioDeviceType::Gpio);
+
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
+        self.device_state
+            .active_state()
+            .expect("Device not activated")
+            .interrupt
+            .deref()
+    }
+
+    fn read_config(&self, offset: u64, data: &mut [u8]) {
+        if let Some(config_space_bytes) = self.config_space.get(u64_to_usize(offset)..) {
+            let len = config_space_bytes.len().min(data.len());
+            data[..len].copy_from_slice(&config_space_bytes[..len]);
+        } else {
+            error!("Failed to read config space");
+            self.metrics.cfg_fails.inc();
+        }
+    }
+
+    fn write_config(&mut self, _offset: u64, _data: &[u8]) {}
+
+    fn activate(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), ActivateError> {
+        for q in self.queues.iter_mut() {
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        if self.activate_event.write(1).is_err() {
+            self.metrics.activate_fails.inc();
+            return Err(ActivateError::EventFd);
+        }
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+        Ok(())
+    }
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn kick(&mut self) {
+        if self.is_activated() {
+            info!("kick gpio {}.", self.config.id);
+            self.handle_request_queue().unwrap_or_else(|err| {
+                error!("gpio: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+            self.handle_event_queue().unwrap_or_else(|err| {
+                error!("gpio: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::gpio::VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING;
+    use crate::devices::virtio::gpio::backend::MockGpioChip;
+    use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
+    use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
+    use crate::vmm_config::gpio::GpioMockConfig;
+
+    fn mock_config(dir: &TempDir) -> GpioConfig {
+        GpioConfig {
+            id: "chip0".into(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: dir.as_path().join("gpio.sock").to_str().unwrap().into(),
+                num_lines: 2,
+                line_names: vec!["led".into()],
+            }),
+        }
+    }
+
+    fn mock_chip(gpio: &mut Gpio) -> &mut MockGpioChip {
+        let GpioBackend::Mock(chip) = &mut gpio.backend else {
+            panic!("Unexpected backend");
+        };
+        chip
+    }
+
+    fn write_request(mem: &GuestMemoryMmap, addr: u64, msg_type: u16, line: u16, value: u32) {
+        let mut request = [0u8; 8];
+        request[..2].copy_from_slice(&msg_type.to_le_bytes());
+        request[2..4].copy_from_slice(&line.to_le_bytes());
+        request[4..].copy_from_slice(&value.to_le_bytes());
+        mem.write_slice(&request, GuestAddress(addr)).unwrap();
+    }
+
+    #[test]
+    fn test_new() {
+        let dir = TempDir::new().unwrap();
+        let gpio = Gpio::new(mock_config(&dir)).unwrap();
+        assert_eq!(gpio.device_type(), VirtioDeviceType::Gpio);
+        assert!(gpio.avail_features() & (1 << VIRTIO_GPIO_F_IRQ) != 0);
+        assert_eq!(gpio.names, b"led\0\0");
+
+        let mut config = [0u8; 8];
+        gpio.read_config(0, &mut config);
+        assert_eq!(config, [2, 0, 0, 0, 5, 0, 0, 0]);
+
+        assert!(matches!(
+            Gpio::new(GpioConfig::default()).unwrap_err(),
+            GpioError::Backend(GpioBackendError::InvalidBackend),
+        ));
+    }
+
+    #[test]
+    fn test_handle_request_queue() {
+        let dir = TempDir::new().unwrap();
+        let mut gpio = Gpio::new(mock_config(&dir)).unwrap();
+
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        gpio.queues[REQUEST_QUEUE] = vq.create_queue();
+        gpio.activate(mem.clone(), interrupt).unwrap();
+
+        let requests = [
+            (VIRTIO_GPIO_MSG_GET_NAMES, 0, 0, 16),
+            (VIRTIO_GPIO_MSG_SET_VALUE, 0, 1, 2),
+            (VIRTIO_GPIO_MSG_SET_DIRECTION, 0, 1, 2),
+            (VIRTIO_GPIO_MSG_GET_DIRECTION, 0, 0, 2),
+            (VIRTIO_GPIO_MSG_GET_VALUE, 0, 0, 2),
+            // Invalid line.
+            (VIRTIO_GPIO_MSG_GET_VALUE, 2, 0, 2),
+            // Invalid direction.
+            (VIRTIO_GPIO_MSG_SET_DIRECTION, 0, 3, 2),
+            // Interrupts were not negotiated.
+            (VIRTIO_GPIO_MSG_IRQ_TYPE, 1, 1, 2),
+        ];
+        for (i, (msg_type, line, value, response_len)) in requests.into_iter().enumerate() {
+            let i = u16::try_from(i).unwrap();
+            let addr = 0x1000 * (u64::from(i) + 1);
+            write_request(&mem, addr, msg_type, line, value);
+            vq.dtable[usize::from(2 * i)].set(addr, 8, VIRTQ_DESC_F_NEXT, 2 * i + 1);
+            vq.dtable[usize::from(2 * i + 1)].set(
+                addr + 0x100,
+                response_len,
+                VIRTQ_DESC_F_WRITE,
+                0,
+            );
+            vq.avail.ring[usize::from(i)].set(2 * i);
+        }
+        vq.avail.idx.set(8);
+        gpio.handle_request_queue().unwrap();
+
+        assert_eq!(vq.used.idx.get(), 8);
+        assert_eq!(vq.used.ring[0].get().len, 6);
+        let mut names = [0u8; 6];
+        mem.read_slice(&mut names, GuestAddress(0x1100)).unwrap();
+        assert_eq!(&names, b"\0led\0\0");
+        for (i, expected) in [[0, 0], [0, 0], [0, 1], [0, 1], [1, 0], [1, 0], [1, 0]]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(vq.used.ring[i + 1].get().len, 2);
+            let addr = GuestAddress(0x1000 * (u64::try_from(i).unwrap() + 2) + 0x100);
+            let mut response = [0u8; 2];
+            mem.read_slice(&mut response, addr).unwrap();
+            assert_eq!(response, expected);
+        }
+        assert_eq!(
+            mock_chip(&mut gpio).lines[0].direction,
+            VIRTIO_GPIO_DIRECTION_OUT
+        );
+    }
+
+    #[test]
+    fn test_irq() {
+        let dir = TempDir::new().unwrap();
+        let mut gpio = Gpio::new(mock_config(&dir)).unwrap();
+
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let reqq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        let evq = VirtQueue::new(GuestAddress(0x8000), &mem, 16);
+        gpio.queues[REQUEST_QUEUE] = reqq.create_queue();
+        gpio.queues[EVENT_QUEUE] = evq.create_queue();
+        gpio.set_acked_features(gpio.avail_features());
+        gpio.activate(mem.clone(), interrupt).unwrap();
+
+        // Configure line 1 as an input with a rising edge interrupt.
+        for (i, (msg_type, value)) in [
+            (
+                VIRTIO_GPIO_MSG_SET_DIRECTION,
+                u32::from(VIRTIO_GPIO_DIRECTION_IN),
+            ),
+            (
+                VIRTIO_GPIO_MSG_IRQ_TYPE,
+                u32::from(VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING),
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let i = u16::try_from(i).unwrap();
+            let addr = 0x1000 * (u64::from(i) + 1);
+            write_request(&mem, addr, msg_type, 1, value);
+            reqq.dtable[usize::from(2 * i)].set(addr, 8, VIRTQ_DESC_F_NEXT, 2 * i + 1);
+            reqq.dtable[usize::from(2 * i + 1)].set(addr + 0x100, 2, VIRTQ_DESC_F_WRITE, 0);
+            reqq.avail.ring[usize::from(i)].set(2 * i);
+        }
+        reqq.avail.idx.set(2);
+        gpio.handle_request_queue().unwrap();
+        assert_eq!(
+            mem.read_obj::<u8>(GuestAddress(0x2100)).unwrap(),
+            VIRTIO_GPIO_STATUS_OK
+        );
+
+        // An edge occurring before the driver queues a buffer is latched.
+        assert!(mock_chip(&mut gpio).drive(1, 1).unwrap());
+        gpio.trigger_irq(1).unwrap();
+        assert!(gpio.irqs[1].latched);
+
+        // Buffers for line 1 and for line 0, whose interrupt is disabled.
+        for (i, line) in [1u16, 0].into_iter().enumerate() {
+            let i = u16::try_from(i).unwrap();
+            let addr = 0x4000 + 0x1000 * u64::from(i);
+            mem.write_obj(line.to_le(), GuestAddress(addr)).unwrap();
+            evq.dtable[usize::from(2 * i)].set(addr, 2, VIRTQ_DESC_F_NEXT, 2 * i + 1);
+            evq.dtable[usize::from(2 * i + 1)].set(addr + 0x100, 1, VIRTQ_DESC_F_WRITE, 0);
+            evq.avail.ring[usize::from(i)].set(2 * i);
+        }
+        evq.avail.idx.set(2);
+        gpio.handle_event_queue().unwrap();
+        assert_eq!(evq.used.idx.get(), 2);
+        assert_eq!(
+            mem.read_obj::<u8>(GuestAddress(0x4100)).unwrap(),
+            VIRTIO_GPIO_IRQ_STATUS_VALID
+        );
+        assert_eq!(
+            mem.read_obj::<u8>(GuestAddress(0x5100)).unwrap(),
+            VIRTIO_GPIO_IRQ_STATUS_INVALID
+        );
+        assert!(!gpio.irqs[1].latched);
+
+        // The buffer queued again waits for the next edge.
+        evq.avail.ring[2].set(0);
+        evq.avail.idx.set(3);
+        gpio.handle_event_queue().unwrap();
+        assert_eq!(evq.used.idx.get(), 2);
+        assert_eq!(gpio.irqs[1].buffer.unwrap().index, 0);
+        mock_chip(&mut gpio).drive(1, 0).unwrap();
+        assert!(mock_chip(&mut gpio).drive(1, 1).unwrap());
+        gpio.trigger_irq(1).unwrap();
+        gpio.signal_used_queue(EVENT_QUEUE);
+        assert_eq!(evq.used.idx.get(), 3);
+        assert_eq!(gpio.metrics.irq_count.count(), 2);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use event_manager::{EventOps, EventSet, Events, MutEventSubscriber};
+use log::{error, warn};
+
+use super::device::Gpio;
+use super::{EVENT_QUEUE, REQUEST_QUEUE};
+use crate::devices::virtio::device::VirtioDevice;
+
+impl Gpio {
+    const PROCESS_ACTIVATE: u32 = 0;
+    const PROCESS_REQUEST_QUEUE: u32 = 1;
+    const PROCESS_EVENT_QUEUE: u32 = 2;
+    const PROCESS_BACKEND: u32 = 3;
+
+    fn register_runtime_events(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            &self.queue_events[REQUEST_QUEUE],
+            Self::PROCESS_REQUEST_QUEUE,
+            EventSet::IN,
+        )) {
+            error!("gpio: Failed to register request queue event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.queue_events[EVENT_QUEUE],
+            Self::PROCESS_EVENT_QUEUE,
+            EventSet::IN,
+        )) {
+            error!("gpio: Failed to register event queue event: {err}");
+        }
+    }
+
+    fn register_activate_event(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            &self.activate_event,
+            Self::PROCESS_ACTIVATE,
+            EventSet::IN,
+        )) {
+            error!("gpio: Failed to register activate event: {err}");
+        }
+    }
+
+    fn register_backend_event(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::with_data(
+            &self.backend,
+            Self::PROCESS_BACKEND,
+            EventSet::IN,
+        )) {
+            error!("gpio: Failed to register backend event: {err}");
+        }
+    }
+
+    fn process_activate_event(&self, ops: &mut EventOps) {
+        if let Err(err) = self.activate_event.read() {
+            error!("gpio: Failed to consume activate event: {err}");
+        }
+
+        // Register runtime events
+        self.register_runtime_events(ops);
+
+        // Remove activate event
+        if let Err(err) = ops.remove(Events::with_data(
+            &self.activate_event,
+            Self::PROCESS_ACTIVATE,
+            EventSet::IN,
+        )) {
+            error!("gpio: Failed to unregister activate event: {err}");
+        }
+    }
+}
+
+impl MutEventSubscriber for Gpio {
+    fn init(&mut self, ops: &mut EventOps) {
+        // The control socket of the mock lines is served from the start.
+        self.register_backend_event(ops);
+        if self.is_activated() {
+            self.register_runtime_events(ops)
+        } else {
+            self.register_activate_event(ops)
+        }
+    }
+
+    fn process(&mut self, events: Events, ops: &mut EventOps) {
+        let event_set = events.event_set();
+        let source = events.data();
+
+        if !event_set.contains(EventSet::IN) {
+            warn!("gpio: Received unknown event: {event_set:#?} from source {source}");
+            return;
+        }
+
+        if source == Self::PROCESS_BACKEND {
+            self.process_backend_event();
+            return;
+        }
+
+        if !self.is_activated() {
+            warn!("gpio: The device is not activated yet. Spurious event received from {source}");
+            return;
+        }
+
+        match source {
+            Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
+            Self::PROCESS_REQUEST_QUEUE => self.process_request_queue(),
+            Self::PROCESS_EVENT_QUEUE => self.process_event_queue(),
+            _ => {
+                warn!("gpio: Unknown event received: {source}");
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements a virtio-gpio controller, whose lines are backed by a gpiochip of the host or by
+//! mock lines driven through a Unix socket.
+
+pub mod backend;
+pub mod device;
+pub mod event_handler;
+pub mod metrics;
+pub mod persist;
+
+pub const GPIO_NUM_QUEUES: usize = 2;
+pub const GPIO_QUEUE_SIZE: u16 = 64;
+
+/// Index of the queue receiving the requests of the driver.
+pub const REQUEST_QUEUE: usize = 0;
+/// Index of the queue receiving the buffers used to notify interrupts.
+pub const EVENT_QUEUE: usize = 1;
+
+/// Feature bit enabling interrupts on the lines.
+pub const VIRTIO_GPIO_F_IRQ: u32 = 0;
+
+pub const VIRTIO_GPIO_MSG_GET_NAMES: u16 = 0x0001;
+pub const VIRTIO_GPIO_MSG_GET_DIRECTION: u16 = 0x0002;
+pub const VIRTIO_GPIO_MSG_SET_DIRECTION: u16 = 0x0003;
+pub const VIRTIO_GPIO_MSG_GET_VALUE: u16 = 0x0004;
+pub const VIRTIO_GPIO_MSG_SET_VALUE: u16 = 0x0005;
+pub const VIRTIO_GPIO_MSG_IRQ_TYPE: u16 = 0x0006;
+
+pub const VIRTIO_GPIO_STATUS_OK: u8 = 0;
+pub const VIRTIO_GPIO_STATUS_ERR: u8 = 1;
+
+pub const VIRTIO_GPIO_DIRECTION_NONE: u8 = 0;
+pub const VIRTIO_GPIO_DIRECTION_OUT: u8 = 1;
+pub const VIRTIO_GPIO_DIRECTION_IN: u8 = 2;
+
+pub const VIRTIO_GPIO_IRQ_TYPE_NONE: u8 = 0;
+pub const VIRTIO_GPIO_IRQ_TYPE_EDGE_RISING: u8 = 1;
+pub const VIRTIO_GPIO_IRQ_TYPE_EDGE_FALLING: u8 = 2;
+pub const VIRTIO_GPIO_IRQ_TYPE_EDGE_BOTH: u8 = 3;
+pub const VIRTIO_GPIO_IRQ_TYPE_LEVEL_HIGH: u8 = 4;
+pub const VIRTIO_GPIO_IRQ_TYPE_LEVEL_LOW: u8 = 8;
+
+pub const VIRTIO_GPIO_IRQ_STATUS_INVALID: u8 = 0;
+pub const VIRTIO_GPIO_IRQ_STATUS_VALID: u8 = 1;
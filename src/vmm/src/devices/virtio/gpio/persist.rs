@@ -0,0 +1,159 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::{GpioBackendError, GpioLineState};
+use super::device::{Gpio, GpioError, GpioIrq};
+use super::{GPIO_NUM_QUEUES, GPIO_QUEUE_SIZE};
+use crate::devices::virtio::device::VirtioDeviceType;
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::snapshot::Persist;
+use crate::vmm_config::gpio::GpioConfig;
+use crate::vstate::memory::GuestMemoryMmap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpioState {
+    pub virtio_state: VirtioDeviceState,
+    pub config: GpioConfig,
+    /// Direction, value and interrupt type of the lines, applied again to the backend on restore.
+    pub lines: Vec<GpioLineState>,
+    /// Buffers of the event queue held by the device.
+    pub irqs: Vec<GpioIrq>,
+}
+
+#[derive(Debug)]
+pub struct GpioConstructorArgs<'a> {
+    pub mem: &'a GuestMemoryMmap,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GpioPersistError {
+    /// Error resetting VirtIO state: {0}
+    VirtioState(#[from] VirtioStateError),
+    /// Error creating gpio device: {0}
+    Gpio(#[from] GpioError),
+    /// Error restoring the lines: {0}
+    Lines(#[from] GpioBackendError),
+}
+
+impl<'a> Persist<'a> for Gpio {
+    type State = GpioState;
+    type ConstructorArgs = GpioConstructorArgs<'a>;
+    type Error = GpioPersistError;
+
+    fn save(&self) -> Self::State {
+        GpioState {
+            virtio_state: VirtioDeviceState::from_device(self),
+            config: self.config.clone(),
+            lines: self.backend.lines().to_vec(),
+            irqs: self.irqs.clone(),
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        let queues = state.virtio_state.build_queues_checked(
+            constructor_args.mem,
+            VirtioDeviceType::Gpio,
+            GPIO_NUM_QUEUES,
+            GPIO_QUEUE_SIZE,
+        )?;
+
+        let mut gpio = Gpio::new_with_queues(state.config.clone(), queues)?;
+        gpio.avail_features = state.virtio_state.avail_features;
+        gpio.acked_features = state.virtio_state.acked_features;
+        // The lines of a host gpiochip are requested again.
+        gpio.backend.restore_lines(&state.lines)?;
+        gpio.irqs.clone_from(&state.irqs);
+
+        Ok(gpio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::device::VirtioDevice;
+    use crate::devices::virtio::gpio::VIRTIO_GPIO_DIRECTION_OUT;
+    use crate::devices::virtio::gpio::device::GpioIrqBuffer;
+    use crate::devices::virtio::test_utils::default_mem;
+    use crate::snapshot::Snapshot;
+    use crate::vmm_config::gpio::GpioMockConfig;
+
+    #[test]
+    fn test_persistence() {
+        let dir = TempDir::new().unwrap();
+        let mut config = GpioConfig {
+            id: "chip1".into(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: dir.as_path().join("gpio.sock").to_str().unwrap().into(),
+                num_lines: 2,
+                line_names: vec![],
+            }),
+        };
+        let mut gpio = Gpio::new(config.clone()).unwrap();
+        gpio.backend
+            .set_direction(1, VIRTIO_GPIO_DIRECTION_OUT)
+            .unwrap();
+        gpio.backend.set_value(1, 1).unwrap();
+        gpio.irqs[0].buffer = Some(GpioIrqBuffer {
+            index: 3,
+            status_addr: 0x1000,
+        });
+        let guest_mem = default_mem();
+
+        // Save the gpio device.
+        let mut mem = vec![0; 4096];
+
+        let state = gpio.save();
+        Snapshot::new(state.clone())
+            .save(&mut mem.as_mut_slice())
+            .unwrap();
+
+        // The mock control socket is bound again by the restored device.
+        drop(gpio);
+        std::fs::remove_file(&config.mock.as_ref().unwrap().socket_path).unwrap();
+
+        // Restore the gpio device.
+        let restored_gpio = Gpio::restore(
+            GpioConstructorArgs { mem: &guest_mem },
+            &Snapshot::load_without_crc_check(mem.as_slice())
+                .unwrap()
+                .data,
+        )
+        .unwrap();
+
+        // Test that virtio specific fields are the same.
+        assert_eq!(restored_gpio.device_type(), VirtioDeviceType::Gpio);
+        assert_eq!(
+            restored_gpio.avail_features(),
+            state.virtio_state.avail_features
+        );
+        assert!(!restored_gpio.is_activated());
+        assert_eq!(restored_gpio.config, config);
+        assert_eq!(
+            restored_gpio.backend.lines()[1].direction,
+            VIRTIO_GPIO_DIRECTION_OUT
+        );
+        assert_eq!(restored_gpio.backend.lines()[1].value, 1);
+        assert_eq!(restored_gpio.irqs[0].buffer.unwrap().index, 3);
+        assert_eq!(restored_gpio.irqs[1], GpioIrq::default());
+
+        // The number of lines of the backend must match the snapshot.
+        let mut state = restored_gpio.save();
+        drop(restored_gpio);
+        std::fs::remove_file(&config.mock.as_ref().unwrap().socket_path).unwrap();
+        config.mock.as_mut().unwrap().num_lines = 3;
+        state.config = config;
+        assert!(matches!(
+            Gpio::restore(GpioConstructorArgs { mem: &guest_mem }, &state).unwrap_err(),
+            GpioPersistError::Lines(GpioBackendError::InvalidLineCount(2))
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backends executing the I2C transfers requested by the guest.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vmm_config::i2c::{I2cConfig, I2cMockDeviceConfig};
+
+/// `I2C_RDWR` ioctl of the Linux i2c-dev interface.
+const I2C_RDWR: libc::c_ulong = 0x0707;
+/// Flag of `i2c_msg` marking a read from the target.
+const I2C_M_RD: u16 = 0x0001;
+/// Highest 7-bit target address.
+pub const MAX_ADDRESS: u16 = 0x7f;
+/// Size of the register file of the mock targets.
+pub const MOCK_REGISTERS_SIZE: usize = 256;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum I2cBackendError {
+    /// Exactly one of `host_adapter` and `mock_devices` must be set
+    InvalidBackend,
+    /// Error opening the host adapter: {0}
+    OpenAdapter(std::io::Error),
+    /// Invalid 7-bit target address: {0:#x}
+    InvalidAddress(u16),
+    /// Several mock targets use the address {0:#x}
+    DuplicateAddress(u16),
+    /// The mock target at {0:#x} has more than 256 registers
+    TooManyRegisters(u16),
+    /// No target answered at {0:#x}
+    NoDevice(u16),
+    /// Transfer on the host adapter failed: {0}
+    Transfer(std::io::Error),
+}
+
+/// A single message of an I2C transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct I2cMessage {
+    /// 7-bit address of the target.
+    pub addr: u16,
+    /// Whether the message reads from the target.
+    pub read: bool,
+    /// Data written to the target, or buffer receiving the data read from it.
+    pub buf: Vec<u8>,
+}
+
+/// Layout of `struct i2c_msg` of the i2c-dev interface.
+#[repr(C)]
+#[derive(Debug)]
+#[allow(dead_code)] // The fields are only accessed by the kernel.
+struct I2cMsg {
+    addr: u16,
+    flags: u16,
+    len: u16,
+    buf: *mut u8,
+}
+
+/// Layout of `struct i2c_rdwr_ioctl_data` of the i2c-dev interface.
+#[repr(C)]
+#[derive(Debug)]
+#[allow(dead_code)] // The fields are only accessed by the kernel.
+struct I2cRdwrIoctlData {
+    msgs: *mut I2cMsg,
+    nmsgs: u32,
+}
+
+/// Proxies the transfers to an i2c-dev adapter of the host, such as `/dev/i2c-1`.
+#[derive(Debug)]
+pub struct HostI2cAdapter {
+    file: File,
+}
+
+impl HostI2cAdapter {
+    pub fn new(path: &str) -> Result<Self, I2cBackendError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(I2cBackendError::OpenAdapter)?;
+        Ok(Self { file })
+    }
+
+    /// Executes all the messages in a single `I2C_RDWR` call, so that they are separated by
+    /// repeated starts rather than stop conditions.
+    fn transfer(&mut self, msgs: &mut [I2cMessage]) -> Result<(), I2cBackendError> {
+        let mut raw_msgs = msgs
+            .iter_mut()
+            .map(|msg| I2cMsg {
+                addr: msg.addr,
+                flags: if msg.read { I2C_M_RD } else { 0 },
+                // The length of the buffers is bounded by the device.
+                len: u16::try_from(msg.buf.len()).unwrap(),
+                buf: msg.buf.as_mut_ptr(),
+            })
+            .collect::<Vec<_>>();
+        let mut data = I2cRdwrIoctlData {
+            msgs: raw_msgs.as_mut_ptr(),
+            nmsgs: u32::try_from(raw_msgs.len()).unwrap(),
+        };
+        // SAFETY: `data` points to `nmsgs` valid messages whose buffers are valid for `len`
+        // bytes and outlive the call. The return value is checked.
+        let ret = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                I2C_RDWR,
+                std::ptr::from_mut(&mut data),
+            )
+        };
+        if ret < 0 {
+            return Err(I2cBackendError::Transfer(std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+/// State of a target emulated by the mock adapter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockI2cTarget {
+    /// Register file of the target.
+    pub registers: Vec<u8>,
+    /// Register accessed by the next read or write.
+    pub pointer: u8,
+}
+
+/// Emulates targets behaving like common register based chips (EEPROMs, sensors, ...).
+///
+/// The first byte written in a message selects the register, and the following bytes are written
+/// to consecutive registers. Reads return consecutive registers starting at the selected one.
+/// Messages to addresses without a target fail, like a NACK on a real bus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockI2cAdapter {
+    pub targets: BTreeMap<u16, MockI2cTarget>,
+}
+
+impl MockI2cAdapter {
+    pub fn new(devices: &[I2cMockDeviceConfig]) -> Result<Self, I2cBackendError> {
+        let mut targets = BTreeMap::new();
+        for device in devices {
+            if device.address > MAX_ADDRESS {
+                return Err(I2cBackendError::InvalidAddress(device.address));
+            }
+            if device.registers.len() > MOCK_REGISTERS_SIZE {
+                return Err(I2cBackendError::TooManyRegisters(device.address));
+            }
+            let mut registers = device.registers.clone();
+            registers.resize(MOCK_REGISTERS_SIZE, 0);
+            let target = MockI2cTarget {
+                registers,
+                pointer: 0,
+            };
+            if targets.insert(device.address, target).is_some() {
+                return Err(I2cBackendError::DuplicateAddress(device.address));
+            }
+        }
+        Ok(Self { targets })
+    }
+
+    fn transfer(&mut self, msgs: &mut [I2cMessage]) -> Result<(), I2cBackendError> {
+        for msg in msgs {
+            let target = self
+                .targets
+                .get_mut(&msg.addr)
+                .ok_or(I2cBackendError::NoDevice(msg.addr))?;
+            if msg.read {
+                for byte in msg.buf.iter_mut() {
+                    *byte = target.registers[usize::from(target.pointer)];
+                    target.pointer = target.pointer.wrapping_add(1);
+                }
+            } else if let Some((pointer, data)) = msg.buf.split_first() {
+                target.pointer = *pointer;
+                for byte in data {
+                    target.registers[usize::from(target.pointer)] = *byte;
+                    target.pointer = target.pointer.wrapping_add(1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Backend of a virtio-i2c device.
+#[derive(Debug)]
+pub enum I2cBackend {
+    Host(HostI2cAdapter),
+    Mock(MockI2cAdapter),
+}
+
+impl I2cBackend {
+    pub fn new(config: &I2cConfig) -> Result<Self, I2cBackendError> {
+        match (&config.host_adapter, config.mock_devices.is_empty()) {
+            (Some(path), true) => Ok(Self::Host(HostI2cAdapter::new(path)?)),
+            (None, false) => Ok(Self::Mock(MockI2cAdapter::new(&config.mock_devices)?)),
+            _ => Err(I2cBackendError::InvalidBackend),
+        }
+    }
+
+    /// Executes the messages of a transfer in order. The transfer stops at the first failing
+    /// message.
+    pub fn transfer(&mut self, msgs: &mut [I2cMessage]) -> Result<(), I2cBackendError> {
+        match self {
+            Self::Host(adapter) => adapter.transfer(msgs),
+            Self::Mock(adapter) => adapter.transfer(msgs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_config(address: u16, registers: Vec<u8>) -> I2cConfig {
+        I2cConfig {
+            id: "i2c".into(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig { address, registers }],
+        }
+    }
+
+    #[test]
+    fn test_backend_config() {
+        assert!(matches!(
+            I2cBackend::new(&I2cConfig::default()).unwrap_err(),
+            I2cBackendError::InvalidBackend
+        ));
+        let mut config = mock_config(0x50, vec![]);
+        config.host_adapter = Some("/dev/i2c-0".into());
+        assert!(matches!(
+            I2cBackend::new(&config).unwrap_err(),
+            I2cBackendError::InvalidBackend
+        ));
+        config.mock_devices.clear();
+        config.host_adapter = Some("/does/not/exist".into());
+        assert!(matches!(
+            I2cBackend::new(&config).unwrap_err(),
+            I2cBackendError::OpenAdapter(_)
+        ));
+
+        assert!(matches!(
+            I2cBackend::new(&mock_config(0x80, vec![])).unwrap_err(),
+            I2cBackendError::InvalidAddress(0x80)
+        ));
+        assert!(matches!(
+            I2cBackend::new(&mock_config(0x50, vec![0; 257])).unwrap_err(),
+            I2cBackendError::TooManyRegisters(0x50)
+        ));
+        let mut config = mock_config(0x50, vec![]);
+        config.mock_devices.push(config.mock_devices[0].clone());
+        assert!(matches!(
+            I2cBackend::new(&config).unwrap_err(),
+            I2cBackendError::DuplicateAddress(0x50)
+        ));
+    }
+
+    #[test]
+    fn test_mock_transfer() {
+        let mut backend = I2cBackend::new(&mock_config(0x50, vec![1, 2, 3, 4])).unwrap();
+
+        // Register read: select register 1, then read 2 bytes with a repeated start.
+        let mut msgs = [
+            I2cMessage {
+                addr: 0x50,
+                read: false,
+                buf: vec![1],
+            },
+            I2cMessage {
+                addr: 0x50,
+                read: true,
+                buf: vec![0; 2],
+            },
+        ];
+        backend.transfer(&mut msgs).unwrap();
+        assert_eq!(msgs[1].buf, [2, 3]);
+
+        // Register write, wrapping around the register file.
+        let mut msgs = [I2cMessage {
+            addr: 0x50,
+            read: false,
+            buf: vec![0xff, 0xaa, 0xbb],
+        }];
+        backend.transfer(&mut msgs).unwrap();
+        let I2cBackend::Mock(adapter) = &backend else {
+            panic!("Unexpected backend");
+        };
+        let target = &adapter.targets[&0x50];
+        assert_eq!(target.registers[0xff], 0xaa);
+        assert_eq!(target.registers[0], 0xbb);
+        assert_eq!(target.pointer, 1);
+
+        // Zero length probe.
+        let mut msgs = [I2cMessage {
+            addr: 0x50,
+            read: false,
+            buf: vec![],
+        }];
+        backend.transfer(&mut msgs).unwrap();
+
+        // Missing target.
+        let mut msgs = [I2cMessage {
+            addr: 0x51,
+            read: true,
+            buf: vec![0; 1],
+        }];
+        assert!(matches!(
+            backend.transfer(&mut msgs).unwrap_err(),
+            I2cBackendError::NoDevice(0x51)
+        ));
+    }
+}
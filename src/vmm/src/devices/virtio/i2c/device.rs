@@ -0,0 +1,474 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use vm_memory::GuestMemoryError;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::backend::{I2cBackend, I2cBackendError, I2cMessage};
+use super::metrics::{I2cMetrics, I2cMetricsPerDevice};
+use super::{
+    I2C_QUEUE_SIZE, VIRTIO_I2C_F_ZERO_LENGTH_REQUEST, VIRTIO_I2C_FLAGS_FAIL_NEXT,
+    VIRTIO_I2C_FLAGS_M_RD, VIRTIO_I2C_MSG_ERR, VIRTIO_I2C_MSG_OK,
+};
+use crate::devices::virtio::ActivateError;
+use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
+use crate::devices::virtio::queue::{DescriptorChain, InvalidAvailIdx, Queue, QueueError};
+use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
+use crate::impl_device_type;
+use crate::logger::{IncMetric, error, info};
+use crate::utils::usize_to_u64;
+use crate::vmm_config::i2c::I2cConfig;
+use crate::vstate::memory::{Address, Bytes, GuestAddress, GuestMemoryMmap};
+
+/// Size of the header of the requests: address (`le16`), padding (`le16`) and flags (`le32`).
+const OUT_HDR_SIZE: usize = 8;
+/// Maximum length of the data of a request.
+const MAX_DATA_LEN: usize = u16::MAX as usize;
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum I2cError {
+    /// Error creating the backend: {0}
+    Backend(#[from] I2cBackendError),
+    /// Error with EventFd: {0}
+    EventFd(std::io::Error),
+    /// Unexpected read-only descriptor after a write-only one
+    ReadOnlyDescriptor,
+    /// Descriptor chain without status descriptor
+    MissingStatus,
+    /// Request data larger than 65535 bytes
+    RequestTooLarge,
+    /// Guest memory error: {0}
+    GuestMemory(#[from] GuestMemoryError),
+    /// Error handling the VirtIO queue: {0}
+    Queue(#[from] QueueError),
+    /// Error during obtaining the descriptor from the queue: {0}
+    QueuePop(#[from] InvalidAvailIdx),
+}
+
+/// A request parsed from a descriptor chain.
+#[derive(Debug)]
+struct I2cRequest {
+    /// Index of the head of the descriptor chain.
+    index: u16,
+    /// The message to execute, or `None` if the request is malformed.
+    msg: Option<I2cMessage>,
+    /// The request is followed by another one of the same transfer.
+    fail_next: bool,
+    /// Device-writable buffers receiving the data read from the target.
+    read_buffers: Vec<(GuestAddress, usize)>,
+    /// Address of the status byte.
+    status_addr: GuestAddress,
+}
+
+#[derive(Debug)]
+pub struct I2c {
+    // VirtIO fields
+    pub avail_features: u64,
+    pub acked_features: u64,
+    pub activate_event: EventFd,
+
+    // Transport fields
+    pub device_state: DeviceState,
+    pub queues: Vec<Queue>,
+    pub queue_events: Vec<EventFd>,
+
+    // I2C specific fields
+    pub backend: I2cBackend,
+    pub metrics: Arc<I2cMetrics>,
+
+    pub config: I2cConfig,
+}
+
+impl I2c {
+    /// Create a new virtio-i2c adapter.
+    pub fn new(config: I2cConfig) -> Result<Self, I2cError> {
+        Self::new_with_queues(config, vec![Queue::new(I2C_QUEUE_SIZE)])
+    }
+
+    /// Create a new virtio-i2c adapter using a pre-created set of queues.
+    pub fn new_with_queues(config: I2cConfig, queues: Vec<Queue>) -> Result<Self, I2cError> {
+        let backend = I2cBackend::new(&config)?;
+
+        Ok(Self {
+            avail_features: (1u64 << VIRTIO_F_VERSION_1)
+                | (1u64 << VIRTIO_I2C_F_ZERO_LENGTH_REQUEST),
+            acked_features: 0u64,
+            activate_event: EventFd::new(libc::EFD_NONBLOCK).map_err(I2cError::EventFd)?,
+            device_state: DeviceState::Inactive,
+            queues,
+            queue_events: vec![EventFd::new(libc::EFD_NONBLOCK).map_err(I2cError::EventFd)?],
+            backend,
+            metrics: I2cMetricsPerDevice::alloc(config.id.clone()),
+            config,
+        })
+    }
+
+    /// Executes the requests of the queue. Consecutive requests flagged with
+    /// `VIRTIO_I2C_FLAGS_FAIL_NEXT` form a single transfer, which either fully succeeds or fails.
+    pub fn handle_queue(&mut self) -> Result<(), I2cError> {
+        let mut transfer = Vec::new();
+        while let Some(head) = self.queues[0].pop()? {
+            let index = head.index;
+            match self.parse_request(head) {
+                Ok(request) => {
+                    let last = !request.fail_next;
+                    transfer.push(request);
+                    if last {
+                        self.complete_transfer(&mut transfer)?;
+                    }
+                }
+                Err(err) => {
+                    // The status byte of requests which cannot be parsed is not written.
+                    error!("i2c: {err}");
+                    self.metrics.event_fails.inc();
+                    self.queues[0].add_used(index, 0)?;
+                }
+            }
+        }
+        // The driver did not queue the end of the transfer. Execute what we got rather than
+        // waiting for requests which may never come.
+        if !transfer.is_empty() {
+            self.complete_transfer(&mut transfer)?;
+        }
+        self.queues[0].advance_used_ring_idx();
+
+        if self.queues[0].prepare_kick() {
+            // This is safe since we checked in the event handler that the device is activated.
+            let active_state = self.device_state.active_state().unwrap();
+            active_state
+                .interrupt
+                .trigger(VirtioInterruptType::Queue(0))
+                .unwrap_or_else(|err| {
+                    error!("i2c: {err}");
+                    self.metrics.event_fails.inc();
+                });
+        }
+        Ok(())
+    }
+
+    /// Parses a request made of a device-readable header and write data, followed by
+    /// device-writable read buffers and status byte.
+    fn parse_request(&self, head: DescriptorChain) -> Result<I2cRequest, I2cError> {
+        // This is safe since we checked in the event handler that the device is activated.
+        let mem = &self.device_state.active_state().unwrap().mem;
+
+        let index = head.index;
+        let mut readable = Vec::new();
+        let mut writable: Vec<(GuestAddress, usize)> = Vec::new();
+        let mut next = Some(head);
+        while let Some(desc) = next {
+            let len = desc.len as usize;
+            if desc.is_write_only() {
+                if len > 0 {
+                    writable.push((desc.addr, len));
+                }
+            } else {
+                if !writable.is_empty() {
+                    return Err(I2cError::ReadOnlyDescriptor);
+                }
+                let start = readable.len();
+                if start + len > OUT_HDR_SIZE + MAX_DATA_LEN {
+                    return Err(I2cError::RequestTooLarge);
+                }
+                readable.resize(start + len, 0);
+                mem.read_slice(&mut readable[start..], desc.addr)?;
+            }
+            next = desc.next_descriptor();
+        }
+
+        // The status byte is the last byte of the device-writable buffers.
+        let (last_addr, last_len) = writable.pop().ok_or(I2cError::MissingStatus)?;
+        let status_addr = last_addr
+            .checked_add(usize_to_u64(last_len - 1))
+            .ok_or(GuestMemoryError::InvalidGuestAddress(last_addr))?;
+        if last_len > 1 {
+            writable.push((last_addr, last_len - 1));
+        }
+        let read_len: usize = writable.iter().map(|(_, len)| len).sum();
+
+        let mut request = I2cRequest {
+            index,
+            msg: None,
+            fail_next: false,
+            read_buffers: writable,
+            status_addr,
+        };
+        let Some((hdr, data)) = readable.split_at_checked(OUT_HDR_SIZE) else {
+            return Ok(request);
+        };
+        let addr = u16::from_le_bytes([hdr[0], hdr[1]]);
+        let flags = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+        request.fail_next = flags & VIRTIO_I2C_FLAGS_FAIL_NEXT != 0;
+        let read = flags & VIRTIO_I2C_FLAGS_M_RD != 0;
+        // A request either reads or writes data, never both.
+        if (read && !data.is_empty()) || (!read && read_len > 0) || read_len > MAX_DATA_LEN {
+            return Ok(request);
+        }
+        request.msg = Some(I2cMessage {
+            // The address is the 7-bit address shifted by one bit.
+            addr: addr >> 1,
+            read,
+            buf: if read {
+                vec![0; read_len]
+            } else {
+                data.to_vec()
+            },
+        });
+        Ok(request)
+    }
+
+    /// Executes the requests of a transfer and completes them.
+    fn complete_transfer(&mut self, transfer: &mut Vec<I2cRequest>) -> Result<(), I2cError> {
+        // This is safe since we checked in the event handler that the device is activated.
+        let mem = &self.device_state.active_state().unwrap().mem;
+
+        self.metrics.transfer_count.inc();
+        let mut msgs = transfer
+            .iter_mut()
+            .map(|request| request.msg.take())
+            .collect::<Option<Vec<_>>>();
+        let succeeded = match msgs.as_mut() {
+            Some(msgs) => self
+                .backend
+                .transfer(msgs)
+                .inspect_err(|err| error!("i2c: Transfer failed: {err}"))
+                .is_ok(),
+            None => {
+                error!("i2c: Malformed request in transfer");
+                false
+            }
+        };
+        if !succeeded {
+            self.metrics.transfer_fails.inc();
+        }
+
+        let msgs = msgs.unwrap_or_default();
+        for (i, request) in transfer.drain(..).enumerate() {
+            let mut used_len = 1;
+            let mut status = VIRTIO_I2C_MSG_ERR;
+            if succeeded {
+                status = VIRTIO_I2C_MSG_OK;
+                let msg = &msgs[i];
+                if msg.read {
+                    let mut written = 0;
+                    for (addr, len) in request.read_buffers {
+                        mem.write_slice(&msg.buf[written..written + len], addr)?;
+                        written += len;
+                    }
+                    self.metrics.read_bytes.add(usize_to_u64(written));
+                    used_len += u32::try_from(written).unwrap();
+                } else {
+                    self.metrics.write_bytes.add(usize_to_u64(msg.buf.len()));
+                }
+            }
+            mem.write_obj(status, request.status_addr)?;
+            self.queues[0].add_used(request.index, used_len)?;
+        }
+        Ok(())
+    }
+
+    pub fn process_queue(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events[0].read() {
+            error!("i2c: Failed to get queue event: {err:?}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.handle_queue().unwrap_or_else(|err| {
+            error!("i2c: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+}
+
+impl VirtioDevice for I2c {
+    impl_device_type!(VirtioDeviceType::I2c);
+
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
+        self.device_state
+            .active_state()
+            .expect("Device not activated")
+            .interrupt
+            .deref()
+    }
+
+    fn read_config(&self, _offset: u64, _data: &mut [u8]) {
+        error!("i2c: The device has no configuration space");
+        self.metrics.cfg_fails.inc();
+    }
+
+    fn write_config(&mut self, _offset: u64, _data: &[u8]) {
+        error!("i2c: The device has no configuration space");
+        self.metrics.cfg_fails.inc();
+    }
+
+    fn activate(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), ActivateError> {
+        for q in self.queues.iter_mut() {
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        if self.activate_event.write(1).is_err() {
+            self.metrics.activate_fails.inc();
+            return Err(ActivateError::EventFd);
+        }
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+        Ok(())
+    }
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn kick(&mut self) {
+        if self.is_activated() {
+            info!("kick i2c {}.", self.config.id);
+            self.handle_queue().unwrap_or_else(|err| {
+                error!("i2c: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::i2c::backend::MockI2cAdapter;
+    use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
+    use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
+    use crate::vmm_config::i2c::I2cMockDeviceConfig;
+
+    fn mock_config() -> I2cConfig {
+        I2cConfig {
+            id: "bus0".into(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig {
+                address: 0x50,
+                registers: vec![0x11, 0x22, 0x33],
+            }],
+        }
+    }
+
+    fn out_hdr(addr: u16, flags: u32) -> [u8; OUT_HDR_SIZE] {
+        let mut hdr = [0u8; OUT_HDR_SIZE];
+        hdr[..2].copy_from_slice(&(addr << 1).to_le_bytes());
+        hdr[4..].copy_from_slice(&flags.to_le_bytes());
+        hdr
+    }
+
+    #[test]
+    fn test_new() {
+        let i2c = I2c::new(mock_config()).unwrap();
+        assert_eq!(i2c.device_type(), VirtioDeviceType::I2c);
+        assert!(i2c.avail_features() & (1 << VIRTIO_I2C_F_ZERO_LENGTH_REQUEST) != 0);
+
+        assert!(matches!(
+            I2c::new(I2cConfig::default()).unwrap_err(),
+            I2cError::Backend(I2cBackendError::InvalidBackend),
+        ));
+    }
+
+    #[test]
+    fn test_handle_queue() {
+        let mut i2c = I2c::new(mock_config()).unwrap();
+
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        i2c.queues[0] = vq.create_queue();
+        i2c.activate(mem.clone(), interrupt).unwrap();
+
+        // Register read: a write of the register number followed by a read of 2 bytes, in the
+        // same transfer.
+        mem.write_slice(
+            &out_hdr(0x50, VIRTIO_I2C_FLAGS_FAIL_NEXT),
+            GuestAddress(0x1000),
+        )
+        .unwrap();
+        mem.write_obj(1u8, GuestAddress(0x1100)).unwrap();
+        vq.dtable[0].set(0x1000, 8, VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(0x1100, 1, VIRTQ_DESC_F_NEXT, 2);
+        vq.dtable[2].set(0x1200, 1, VIRTQ_DESC_F_WRITE, 0);
+        mem.write_slice(&out_hdr(0x50, VIRTIO_I2C_FLAGS_M_RD), GuestAddress(0x2000))
+            .unwrap();
+        vq.dtable[3].set(0x2000, 8, VIRTQ_DESC_F_NEXT, 4);
+        vq.dtable[4].set(0x2100, 2, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 5);
+        vq.dtable[5].set(0x2200, 1, VIRTQ_DESC_F_WRITE, 0);
+        // Zero length request to a missing target.
+        mem.write_slice(&out_hdr(0x51, 0), GuestAddress(0x3000))
+            .unwrap();
+        vq.dtable[6].set(0x3000, 8, VIRTQ_DESC_F_NEXT, 7);
+        vq.dtable[7].set(0x3100, 1, VIRTQ_DESC_F_WRITE, 0);
+        // Read request carrying write data.
+        mem.write_slice(&out_hdr(0x50, VIRTIO_I2C_FLAGS_M_RD), GuestAddress(0x4000))
+            .unwrap();
+        vq.dtable[8].set(0x4000, 8, VIRTQ_DESC_F_NEXT, 9);
+        vq.dtable[9].set(0x4100, 1, VIRTQ_DESC_F_NEXT, 10);
+        vq.dtable[10].set(0x4200, 1, VIRTQ_DESC_F_WRITE, 0);
+        // Request without status byte.
+        vq.dtable[11].set(0x5000, 8, 0, 0);
+
+        for (i, head) in [0, 3, 6, 8, 11].into_iter().enumerate() {
+            vq.avail.ring[i].set(head);
+        }
+        vq.avail.idx.set(5);
+        i2c.handle_queue().unwrap();
+
+        assert_eq!(vq.used.idx.get(), 5);
+        assert_eq!(vq.used.ring[0].get().len, 1);
+        assert_eq!(mem.read_obj::<u8>(GuestAddress(0x1200)).unwrap(), 0);
+        assert_eq!(vq.used.ring[1].get().len, 3);
+        let mut data = [0u8; 2];
+        mem.read_slice(&mut data, GuestAddress(0x2100)).unwrap();
+        assert_eq!(data, [0x22, 0x33]);
+        assert_eq!(mem.read_obj::<u8>(GuestAddress(0x2200)).unwrap(), 0);
+        assert_eq!(vq.used.ring[2].get().len, 1);
+        assert_eq!(mem.read_obj::<u8>(GuestAddress(0x3100)).unwrap(), 1);
+        assert_eq!(vq.used.ring[3].get().len, 1);
+        assert_eq!(mem.read_obj::<u8>(GuestAddress(0x4200)).unwrap(), 1);
+        assert_eq!(vq.used.ring[4].get().len, 0);
+
+        let I2cBackend::Mock(MockI2cAdapter { targets }) = &i2c.backend else {
+            panic!("Unexpected backend");
+        };
+        assert_eq!(targets[&0x50].pointer, 3);
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the metrics system for virtio-i2c devices.
+//!
+//! # Metrics format
+//! The metrics are flushed in JSON when requested by vmm::logger::metrics::METRICS.write().
+//!
+//! ## JSON example with metrics:
+//! ```json
+//! {
+//!  "i2c_bus0": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "cfg_fails": "SharedIncMetric",
+//!     "event_fails": "SharedIncMetric",
+//!     "queue_event_count": "SharedIncMetric",
+//!     "transfer_count": "SharedIncMetric",
+//!     ...
+//!  }
+//!  "i2c": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "cfg_fails": "SharedIncMetric",
+//!     "event_fails": "SharedIncMetric",
+//!     "queue_event_count": "SharedIncMetric",
+//!     "transfer_count": "SharedIncMetric",
+//!     ...
+//!  }
+//! }
+//! ```
+//! Each `i2c` field in the example above is a serializable `I2cMetrics` structure collecting
+//! metrics such as `activate_fails`, `transfer_count`, etc. for the virtio-i2c device.
+//! `i2c_bus0` represents the metrics of the device attached through the endpoint "/i2c/bus0"
+//! and `i2c` is the aggregate of all the per device metrics.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::logger::{IncMetric, SharedIncMetric};
+
+/// map of i2c device id and metrics
+/// this should be protected by a lock before accessing.
+#[derive(Debug)]
+pub struct I2cMetricsPerDevice {
+    /// used to access per i2c device metrics
+    pub metrics: BTreeMap<String, Arc<I2cMetrics>>,
+}
+
+impl I2cMetricsPerDevice {
+    /// Allocate `I2cDeviceMetrics` for i2c device having
+    /// id `device_id`. Also, allocate only if it doesn't
+    /// exist to avoid overwriting previously allocated data.
+    /// lock is always initialized so it is safe the unwrap
+    /// the lock without a check.
+    pub fn alloc(device_id: String) -> Arc<I2cMetrics> {
+        Arc::clone(
+            METRICS
+                .write()
+                .unwrap()
+                .metrics
+                .entry(device_id)
+                .or_insert_with(|| Arc::new(I2cMetrics::default())),
+        )
+    }
+}
+
+/// Pool of i2c-related metrics per device behind a lock to
+/// keep things thread safe. Since the lock is initialized here
+/// it is safe to unwrap it without any check.
+static METRICS: RwLock<I2cMetricsPerDevice> = RwLock::new(I2cMetricsPerDevice {
+    metrics: BTreeMap::new(),
+});
+
+/// This function facilitates aggregation and serialization of
+/// per i2c device metrics.
+pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    let i2c_metrics = METRICS.read().unwrap();
+    let metrics_len = i2c_metrics.metrics.len();
+    // +1 to accommodate aggregate i2c metrics
+    let mut seq = serializer.serialize_map(Some(1 + metrics_len))?;
+
+    let mut i2c_aggregated: I2cMetrics = I2cMetrics::default();
+
+    for (name, metrics) in i2c_metrics.metrics.iter() {
+        let devn = format!("i2c_{}", name);
+        // serialization will flush the metrics so aggregate before it.
+        let m: &I2cMetrics = metrics;
+        i2c_aggregated.aggregate(m);
+        seq.serialize_entry(&devn, m)?;
+    }
+    seq.serialize_entry("i2c", &i2c_aggregated)?;
+    seq.end()
+}
+
+/// I2c Device associated metrics.
+#[derive(Debug, Default, Serialize)]
+pub struct I2cMetrics {
+    /// Number of times when activate failed on an i2c device.
+    pub activate_fails: SharedIncMetric,
+    /// Number of times when interacting with the space config of an i2c device failed.
+    pub cfg_fails: SharedIncMetric,
+    /// Number of times when handling events on an i2c device failed.
+    pub event_fails: SharedIncMetric,
+    /// Number of events triggered on the queue of this i2c device.
+    pub queue_event_count: SharedIncMetric,
+    /// Number of transfers executed by this i2c device.
+    pub transfer_count: SharedIncMetric,
+    /// Number of transfers which failed on this i2c device.
+    pub transfer_fails: SharedIncMetric,
+    /// Number of bytes read from the targets.
+    pub read_bytes: SharedIncMetric,
+    /// Number of bytes written to the targets.
+    pub write_bytes: SharedIncMetric,
+}
+
+impl I2cMetrics {
+    /// Const default construction.
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// i2c metrics are SharedIncMetric where the diff of current vs
+    /// old is serialized i.e. serialize_u64(current-old).
+    /// So to have the aggregate serialized in same way we need to
+    /// fetch the diff of current vs old metrics and add it to the
+    /// aggregate.
+    pub fn aggregate(&mut self, other: &Self) {
+        self.activate_fails.add(other.activate_fails.fetch_diff());
+        self.cfg_fails.add(other.cfg_fails.fetch_diff());
+        self.event_fails.add(other.event_fails.fetch_diff());
+        self.queue_event_count
+            .add(other.queue_event_count.fetch_diff());
+        self.transfer_count.add(other.transfer_count.fetch_diff());
+        self.transfer_fails.add(other.transfer_fails.fetch_diff());
+        self.read_bytes.add(other.read_bytes.fetch_diff());
+        self.write_bytes.add(other.write_bytes.fetch_diff());
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_i2c_dev_metrics() {
+        // Note: this test has nothing to do with
+        // i2c structure or IRQs, this is just to allocate
+        // metrics for max number of devices that system can have.
+        // We have 5-23 IRQ for i2c devices on x86_64 so, there
+        // are 19 i2c devices at max. And, even though we have more
+        // devices on aarch64 but we stick to 19 to keep test common.
+        const MAX_I2C_DEVICES: usize = 19;
+
+        // This is to make sure that RwLock for i2c::metrics::METRICS is good.
+        drop(METRICS.read().unwrap());
+        drop(METRICS.write().unwrap());
+
+        // i2c::metrics::METRICS is in short RwLock on Vec of I2cDeviceMetrics.
+        // Normally, pointer to unique entries of i2c::metrics::METRICS are stored
+        // in I2c device so that I2c device can do self.metrics.* to
+        // update a metric. We try to do something similar here without
+        // using I2c device by allocating max number of
+        // I2cDeviceMetrics in i2c::metrics::METRICS and store pointer to
+        // each entry in the local `metrics` vec.
+        // We then update 1 IncMetric and 2 SharedMetric for each metrics
+        // and validate if the metrics for per device was updated as
+        // expected.
+        let mut metrics: Vec<Arc<I2cMetrics>> = Vec::new();
+        for i in 0..MAX_I2C_DEVICES {
+            let i2c_name: String = format!("i2c{}", i);
+            metrics.push(I2cMetricsPerDevice::alloc(i2c_name.clone()));
+            // update IncMetric
+            metrics[i].activate_fails.inc();
+
+            if i == 0 {
+                // Unit tests run in parallel and we have
+                // `test_single_i2c_dev_metrics` that also increases
+                // the IncMetric count of drv0 by 1 (intentional to check
+                // thread safety) so we check if the count is >=1.
+                assert!(metrics[i].activate_fails.count() >= 1);
+            } else {
+                assert!(metrics[i].activate_fails.count() == 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_i2c_dev_metrics() {
+        let test_metrics = I2cMetricsPerDevice::alloc(String::from("i2c0"));
+        // Test to update IncMetrics
+        test_metrics.activate_fails.inc();
+        assert!(
+            test_metrics.activate_fails.count() > 0,
+            "{}",
+            test_metrics.activate_fails.count()
+        );
+
+        // We expect only 2 tests (this and test_max_i2c_dev_metrics)
+        // to update activate_fails count for i2c0.
+        assert!(
+            test_metrics.activate_fails.count() <= 2,
+            "{}",
+            test_metrics.activate_fails.count()
+        );
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements a virtio-i2c adapter, proxying the transfers of the guest to an i2c-dev adapter of
+//! the host or to mock targets emulated by Firecracker.
+
+pub mod backend;
+pub mod device;
+pub mod event_handler;
+pub mod metrics;
+pub mod persist;
+
+pub const I2C_NUM_QUEUES: usize = 1;
+pub const I2C_QUEUE_SIZE: u16 = 64;
+
+/// Feature bit allowing requests without data, used by the guest to probe targets.
+pub const VIRTIO_I2C_F_ZERO_LENGTH_REQUEST: u32 = 0;
+
+/// The request is part of a transfer continuing with the next request.
+pub const VIRTIO_I2C_FLAGS_FAIL_NEXT: u32 = 1 << 0;
+/// The request reads from the target.
+pub const VIRTIO_I2C_FLAGS_M_RD: u32 = 1 << 1;
+
+pub const VIRTIO_I2C_MSG_OK: u8 = 0;
+pub const VIRTIO_I2C_MSG_ERR: u8 = 1;
@@ -0,0 +1,134 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::{I2cBackend, MockI2cAdapter};
+use super::device::{I2c, I2cError};
+use super::{I2C_NUM_QUEUES, I2C_QUEUE_SIZE};
+use crate::devices::virtio::device::VirtioDeviceType;
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::snapshot::Persist;
+use crate::vmm_config::i2c::I2cConfig;
+use crate::vstate::memory::GuestMemoryMmap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct I2cState {
+    pub virtio_state: VirtioDeviceState,
+    pub config: I2cConfig,
+    /// State of the mock targets. The host adapter is opened again on restore.
+    pub mock_adapter: Option<MockI2cAdapter>,
+}
+
+#[derive(Debug)]
+pub struct I2cConstructorArgs<'a> {
+    pub mem: &'a GuestMemoryMmap,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum I2cPersistError {
+    /// Error resetting VirtIO state: {0}
+    VirtioState(#[from] VirtioStateError),
+    /// Error creating i2c device: {0}
+    I2c(#[from] I2cError),
+}
+
+impl<'a> Persist<'a> for I2c {
+    type State = I2cState;
+    type ConstructorArgs = I2cConstructorArgs<'a>;
+    type Error = I2cPersistError;
+
+    fn save(&self) -> Self::State {
+        I2cState {
+            virtio_state: VirtioDeviceState::from_device(self),
+            config: self.config.clone(),
+            mock_adapter: match &self.backend {
+                I2cBackend::Host(_) => None,
+                I2cBackend::Mock(adapter) => Some(adapter.clone()),
+            },
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        let queues = state.virtio_state.build_queues_checked(
+            constructor_args.mem,
+            VirtioDeviceType::I2c,
+            I2C_NUM_QUEUES,
+            I2C_QUEUE_SIZE,
+        )?;
+
+        let mut i2c = I2c::new_with_queues(state.config.clone(), queues)?;
+        i2c.avail_features = state.virtio_state.avail_features;
+        i2c.acked_features = state.virtio_state.acked_features;
+        if let (I2cBackend::Mock(adapter), Some(saved)) = (&mut i2c.backend, &state.mock_adapter) {
+            *adapter = saved.clone();
+        }
+
+        Ok(i2c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::device::VirtioDevice;
+    use crate::devices::virtio::i2c::backend::I2cMessage;
+    use crate::devices::virtio::test_utils::default_mem;
+    use crate::snapshot::Snapshot;
+    use crate::vmm_config::i2c::I2cMockDeviceConfig;
+
+    #[test]
+    fn test_persistence() {
+        let config = I2cConfig {
+            id: "bus0".into(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig {
+                address: 0x50,
+                registers: vec![],
+            }],
+        };
+        let mut i2c = I2c::new(config).unwrap();
+        // Write a register so that the state of the target differs from its configuration.
+        i2c.backend
+            .transfer(&mut [I2cMessage {
+                addr: 0x50,
+                read: false,
+                buf: vec![4, 0x42],
+            }])
+            .unwrap();
+        let guest_mem = default_mem();
+
+        // Save the i2c device.
+        let mut mem = vec![0; 4096];
+
+        Snapshot::new(i2c.save())
+            .save(&mut mem.as_mut_slice())
+            .unwrap();
+
+        // Restore the i2c device.
+        let restored_i2c = I2c::restore(
+            I2cConstructorArgs { mem: &guest_mem },
+            &Snapshot::load_without_crc_check(mem.as_slice())
+                .unwrap()
+                .data,
+        )
+        .unwrap();
+
+        // Test that virtio specific fields are the same.
+        assert_eq!(restored_i2c.device_type(), VirtioDeviceType::I2c);
+        assert_eq!(restored_i2c.avail_features(), i2c.avail_features());
+        assert_eq!(restored_i2c.acked_features(), i2c.acked_features());
+        assert_eq!(restored_i2c.queues(), i2c.queues());
+        assert!(!i2c.is_activated());
+        assert!(!restored_i2c.is_activated());
+        assert_eq!(restored_i2c.config, i2c.config);
+        assert_eq!(restored_i2c.save().mock_adapter, i2c.save().mock_adapter);
+        let I2cBackend::Mock(adapter) = &restored_i2c.backend else {
+            panic!("Unexpected backend");
+        };
+        assert_eq!(adapter.targets[&0x50].registers[4], 0x42);
+    }
+}
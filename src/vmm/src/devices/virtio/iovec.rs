@@ -7,12 +7,14 @@ use libc::{c_void, iovec, size_t};
 use serde::{Deserialize, Serialize};
 use vm_memory::bitmap::Bitmap;
 use vm_memory::{
-    GuestMemory, GuestMemoryError, ReadVolatile, VolatileMemoryError, VolatileSlice, WriteVolatile,
+    Address, GuestAddress, GuestMemory, GuestMemoryError, GuestMemoryRegion, ReadVolatile,
+    VolatileMemoryError, VolatileSlice, WriteVolatile,
 };
 
 use super::iov_deque::{IovDeque, IovDequeError};
 use super::queue::FIRECRACKER_MAX_QUEUE_SIZE;
 use crate::devices::virtio::queue::DescriptorChain;
+use crate::utils::u64_to_usize;
 use crate::vstate::memory::GuestMemoryMmap;
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -107,6 +109,49 @@ impl IoVecBuffer {
         Ok(new_buffer)
     }
 
+    /// Append the `len` bytes of guest memory at `addr` to the `IoVecBuffer`, with an `iovec`
+    /// per memory region they span
+    ///
+    /// # Safety
+    ///
+    /// The range cannot be referencing the same memory location as another range of the buffer
+    pub unsafe fn append_guest_range(
+        &mut self,
+        mem: &GuestMemoryMmap,
+        mut addr: GuestAddress,
+        len: u32,
+    ) -> Result<(), IoVecError> {
+        self.len
+            .checked_add(len)
+            .ok_or(IoVecError::OverflowedDescriptor)?;
+
+        let mut remaining = u64::from(len);
+        while remaining != 0 {
+            let region = mem
+                .find_region(addr)
+                .ok_or(GuestMemoryError::InvalidGuestAddress(addr))?;
+            let region_end = region.start_addr().unchecked_add(region.len());
+            let count = remaining.min(region_end.unchecked_offset_from(addr));
+
+            let iov_base = mem
+                .get_slice(addr, u64_to_usize(count))?
+                .ptr_guard_mut()
+                .as_ptr()
+                .cast::<c_void>();
+            self.vecs.push(iovec {
+                iov_base,
+                iov_len: u64_to_usize(count),
+            });
+            // `count` is at most `len`, and the total length was checked above.
+            self.len += u32::try_from(count).unwrap();
+
+            addr = addr.unchecked_add(count);
+            remaining -= count;
+        }
+
+        Ok(())
+    }
+
     /// Get the total length of the memory regions covered by this `IoVecBuffer`
     pub(crate) fn len(&self) -> u32 {
         self.len
@@ -117,6 +162,11 @@ impl IoVecBuffer {
         self.vecs.as_ptr()
     }
 
+    /// Returns the `iovec` structs
+    pub fn as_iovec_slice(&self) -> &[iovec] {
+        &self.vecs
+    }
+
     /// Returns the length of the `iovec` array.
     pub fn iovec_count(&self) -> usize {
         self.vecs.len()
@@ -482,6 +532,61 @@ impl<const L: u16> IoVecBufferMut<L> {
 
         Ok(total_bytes_read)
     }
+
+    /// Reads up to `len` bytes from the `IoVecBufferMut` starting at the given offset.
+    ///
+    /// This will try to write to the given [`WriteVolatile`].
+    pub fn read_volatile_at<W: WriteVolatile>(
+        &self,
+        dst: &mut W,
+        mut offset: usize,
+        mut len: usize,
+    ) -> Result<usize, VolatileMemoryError> {
+        let mut total_bytes_read = 0;
+
+        for iov in self.vecs.as_slice() {
+            if len == 0 {
+                break;
+            }
+
+            if offset >= iov.iov_len {
+                offset -= iov.iov_len;
+                continue;
+            }
+
+            let mut slice =
+                // SAFETY: the constructor IoVecBufferMut::from_descriptor_chain ensures that
+                // all iovecs contained point towards valid ranges of guest memory
+                unsafe { VolatileSlice::new(iov.iov_base.cast(), iov.iov_len).offset(offset)? };
+            offset = 0;
+
+            if slice.len() > len {
+                slice = slice.subslice(0, len)?;
+            }
+
+            match loop {
+                match dst.write_volatile(&slice) {
+                    Err(VolatileMemoryError::IOError(err))
+                        if err.kind() == ErrorKind::Interrupted => {}
+                    result => break result,
+                }
+            } {
+                Ok(bytes_read) => {
+                    total_bytes_read += bytes_read;
+
+                    if bytes_read < slice.len() {
+                        break;
+                    }
+                    len -= bytes_read;
+                }
+                // exit successfully if we previously managed to write some bytes
+                Err(_) if total_bytes_read > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(total_bytes_read)
+    }
 }
 
 #[cfg(test)]
@@ -635,6 +740,48 @@ mod tests {
         unsafe { IoVecBufferMutDefault::from_descriptor_chain(&mem, head).unwrap() };
     }
 
+    #[test]
+    fn test_append_guest_range() {
+        let mem = multi_region_mem(&[(GuestAddress(0), 0x1000), (GuestAddress(0x1000), 0x1000)]);
+        let v: Vec<u8> = (0..=255).cycle().take(0x2000).collect();
+        mem.write_slice(&v, GuestAddress(0)).unwrap();
+
+        let mut iovec = IoVecBuffer::default();
+        // SAFETY: The ranges don't overlap
+        unsafe {
+            iovec
+                .append_guest_range(&mem, GuestAddress(0x100), 0x10)
+                .unwrap();
+            // A range spanning two memory regions takes an `iovec` per region.
+            iovec
+                .append_guest_range(&mem, GuestAddress(0xf00), 0x200)
+                .unwrap();
+            iovec
+                .append_guest_range(&mem, GuestAddress(0x1f00), 0)
+                .unwrap();
+        }
+        assert_eq!(iovec.len(), 0x210);
+        assert_eq!(iovec.iovec_count(), 3);
+        assert_eq!(iovec.as_iovec_slice()[1].iov_len, 0x100);
+
+        let mut buf = vec![0u8; 0x210];
+        iovec.read_exact_volatile_at(&mut buf, 0).unwrap();
+        assert_eq!(&buf[..0x10], &v[0x100..0x110]);
+        assert_eq!(&buf[0x10..], &v[0xf00..0x1100]);
+
+        // The ranges must be in guest memory.
+        let mut iovec = IoVecBuffer::default();
+        // SAFETY: The ranges don't overlap
+        unsafe {
+            iovec
+                .append_guest_range(&mem, GuestAddress(0x1f00), 0x200)
+                .unwrap_err();
+            iovec
+                .append_guest_range(&mem, GuestAddress(0x3000), 1)
+                .unwrap_err();
+        }
+    }
+
     #[test]
     fn test_iovec_length() {
         let mem = default_mem();
@@ -813,6 +960,24 @@ mod tests {
         vq.dtable[1].check_data(&test_vec2);
         vq.dtable[2].check_data(&test_vec3);
         vq.dtable[3].check_data(&test_vec4);
+
+        // Reading back a range traversing two regions returns what was written
+        let mut read_buf = [0u8; 5];
+        assert_eq!(
+            iovec
+                .read_volatile_at(&mut &mut read_buf[..], 60, read_buf.len())
+                .unwrap(),
+            5
+        );
+        assert_eq!(read_buf, [0, 1, 2, 3, 4]);
+        // Reads stop at the end of the buffer
+        assert_eq!(
+            iovec
+                .read_volatile_at(&mut &mut read_buf[..], 254, read_buf.len())
+                .unwrap(),
+            2
+        );
+        assert_eq!(&read_buf[..2], &[2, 3]);
     }
 }
 
@@ -550,6 +550,9 @@ impl VirtioMem {
                     METRICS.unplug_discard_fails.inc();
                     error!("virtio-mem: Failed to discard memory range: {}", err);
                 });
+            self.vm
+                .memory_removals()
+                .notify(range.addr, self.nb_blocks_to_len(range.nb_blocks));
         }
 
         self.update_kvm_slots(range)
@@ -1115,6 +1118,7 @@ mod tests {
     fn test_unplug_request_success() {
         let mut mem_dev = default_virtio_mem();
         let guest_mem = mem_dev.vm.guest_memory().clone();
+        let removals = mem_dev.vm.memory_removals().subscribe().unwrap();
         let mut th = test_helper(mem_dev, &guest_mem);
         th.device().update_requested_size(1024);
         let addr = th.device().guest_address();
@@ -1144,6 +1148,8 @@ mod tests {
         assert_eq!(METRICS.unplug_count.count(), unplug_count + 1);
         assert_eq!(METRICS.unplug_bytes.count(), unplug_bytes + (2 << 20));
         assert_eq!(METRICS.unplug_fails.count(), unplug_fails);
+        // The devices mapping guest memory are notified of the unplugged range.
+        assert_eq!(removals.take_ranges(), [(addr, 2 << 20)]);
     }
 
     #[test]
@@ -16,10 +16,13 @@ pub mod balloon;
 pub mod block;
 pub mod device;
 pub mod generated;
+pub mod gpio;
+pub mod i2c;
 mod iov_deque;
 pub mod iovec;
 pub mod mem;
 pub mod net;
+pub mod p9;
 pub mod persist;
 pub mod pmem;
 pub mod queue;
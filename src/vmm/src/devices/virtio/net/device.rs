@@ -6,14 +6,17 @@
 // found in the THIRD-PARTY file.
 
 use std::collections::VecDeque;
+use std::fs::File;
 use std::mem::{self};
 use std::net::Ipv4Addr;
 use std::num::Wrapping;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use libc::{EAGAIN, iovec};
 use log::{error, info};
+use utils::time::{ClockType, TimerFd, get_time_us};
 use vmm_sys_util::eventfd::EventFd;
 
 use super::NET_QUEUE_MAX_SIZE;
@@ -23,16 +26,20 @@ use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
 use crate::devices::virtio::generated::virtio_net::{
     VIRTIO_NET_F_CSUM, VIRTIO_NET_F_GUEST_CSUM, VIRTIO_NET_F_GUEST_TSO4, VIRTIO_NET_F_GUEST_TSO6,
     VIRTIO_NET_F_GUEST_UFO, VIRTIO_NET_F_HOST_TSO4, VIRTIO_NET_F_HOST_TSO6, VIRTIO_NET_F_HOST_UFO,
-    VIRTIO_NET_F_MAC, VIRTIO_NET_F_MRG_RXBUF, virtio_net_hdr_v1,
+    VIRTIO_NET_F_MAC, VIRTIO_NET_F_MRG_RXBUF, VIRTIO_NET_F_STATUS, virtio_net_hdr_v1,
 };
 use crate::devices::virtio::generated::virtio_ring::VIRTIO_RING_F_EVENT_IDX;
 use crate::devices::virtio::iovec::{
     IoVecBuffer, IoVecBufferMut, IoVecError, ParsedDescriptorChain,
 };
+use crate::devices::virtio::net::flows::{
+    FLOW_HEADERS_LEN, FlowDirection, FlowSamplingConfig, FlowSamplingError, FlowTable, NetworkFlows,
+};
 use crate::devices::virtio::net::metrics::{NetDeviceMetrics, NetMetricsPerDevice};
 use crate::devices::virtio::net::tap::Tap;
 use crate::devices::virtio::net::{
-    MAX_BUFFER_SIZE, NET_QUEUE_SIZES, NetError, NetQueue, RX_INDEX, TX_INDEX, generated,
+    MAX_BUFFER_SIZE, NET_QUEUE_SIZES, NET_TX_BATCH_SIZE, NetError, NetQueue, RX_INDEX, TX_INDEX,
+    generated,
 };
 use crate::devices::virtio::queue::{DescriptorChain, InvalidAvailIdx, Queue};
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
@@ -43,6 +50,7 @@ use crate::impl_device_type;
 use crate::logger::{IncMetric, METRICS};
 use crate::mmds::data_store::Mmds;
 use crate::mmds::ns::MmdsNetworkStack;
+use crate::rate_limiter::cpu_budget::{CpuBudget, CpuBudgetHandle};
 use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenType};
 use crate::utils::net::mac::MacAddr;
 use crate::utils::u64_to_usize;
@@ -50,6 +58,13 @@ use crate::vstate::memory::{ByteValued, GuestMemoryMmap};
 
 const FRAME_HEADER_MAX_LEN: usize = PAYLOAD_OFFSET + ETH_IPV4_FRAME_LEN;
 
+// Bit of the `status` field of the config space reporting the link as up.
+const VIRTIO_NET_S_LINK_UP: u16 = 1;
+
+// TX passes closer than this are considered sustained load, during which the notification of the
+// guest is deferred by as much.
+const TX_NOTIFY_DELAY_US: u64 = 50;
+
 pub(crate) const fn vnet_hdr_len() -> usize {
     mem::size_of::<virtio_net_hdr_v1>()
 }
@@ -89,6 +104,7 @@ fn init_vnet_hdr(buf: &mut [u8]) {
 #[repr(C)]
 pub struct ConfigSpace {
     pub guest_mac: MacAddr,
+    pub status: u16,
 }
 
 // SAFETY: `ConfigSpace` contains only PODs in `repr(C)` or `repr(transparent)`, without padding.
@@ -229,6 +245,52 @@ impl RxBuffers {
     }
 }
 
+/// Frames popped from the TX queue, written to the tap together before they are returned to the
+/// guest.
+#[derive(Debug)]
+struct TxBatch {
+    // The buffers holding the frames, reused across batches.
+    frames: Vec<IoVecBuffer>,
+    // The heads of the descriptor chains of the frames in the batch.
+    heads: Vec<u16>,
+}
+
+impl TxBatch {
+    fn new() -> Self {
+        TxBatch {
+            frames: std::iter::repeat_with(IoVecBuffer::default)
+                .take(NET_TX_BATCH_SIZE)
+                .collect(),
+            heads: Vec::with_capacity(NET_TX_BATCH_SIZE),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.heads.len() == NET_TX_BATCH_SIZE
+    }
+
+    // The buffer the next frame of the batch is loaded in.
+    fn next_frame(&mut self) -> &mut IoVecBuffer {
+        &mut self.frames[self.heads.len()]
+    }
+
+    // Adds the frame loaded in `next_frame()` to the batch.
+    fn push(&mut self, head_index: u16) {
+        self.heads.push(head_index);
+    }
+
+    // Iterates over the frames of the batch and the heads of their descriptor chains.
+    fn iter(&self) -> impl Iterator<Item = (&IoVecBuffer, u16)> {
+        self.frames.iter().zip(self.heads.iter().copied())
+    }
+
+    // Empties the batch, ensuring that no buffer keeps pointing at guest memory.
+    fn clear(&mut self) {
+        self.frames.iter_mut().for_each(IoVecBuffer::clear);
+        self.heads.clear();
+    }
+}
+
 /// VirtIO network device.
 ///
 /// It emulates a network device able to exchange L2 frames between the guest
@@ -263,8 +325,16 @@ pub struct Net {
     /// Only if MMDS transport has been associated with it.
     pub mmds_ns: Option<MmdsNetworkStack>,
     pub(crate) metrics: Arc<NetDeviceMetrics>,
-
-    tx_buffer: IoVecBuffer,
+    /// The sampled flow table, if flow sampling is enabled.
+    pub(crate) flows: Option<FlowTable>,
+    /// The host CPU budget of the device I/O processing, if any.
+    pub(crate) cpu_budget: Option<CpuBudgetHandle>,
+
+    tx_batch: TxBatch,
+    /// Timer notifying the guest of the frames transmitted while its notification was deferred.
+    pub(crate) tx_notify_timer: TimerFd,
+    /// Time of the last TX pass, in microseconds.
+    pub(crate) tx_last_pass_us: u64,
     pub(crate) rx_buffer: RxBuffers,
 }
 
@@ -287,9 +357,13 @@ impl Net {
             | (1 << VIRTIO_NET_F_HOST_UFO)
             | (1 << VIRTIO_F_VERSION_1)
             | (1 << VIRTIO_NET_F_MRG_RXBUF)
+            | (1 << VIRTIO_NET_F_STATUS)
             | (1 << VIRTIO_RING_F_EVENT_IDX);
 
-        let mut config_space = ConfigSpace::default();
+        let mut config_space = ConfigSpace {
+            status: VIRTIO_NET_S_LINK_UP.to_le(),
+            ..Default::default()
+        };
         if let Some(mac) = guest_mac {
             config_space.guest_mac = mac;
             // Enabling feature for MAC address configuration
@@ -321,7 +395,11 @@ impl Net {
             activate_evt: EventFd::new(libc::EFD_NONBLOCK).map_err(NetError::EventFd)?,
             mmds_ns: None,
             metrics: NetMetricsPerDevice::alloc(id),
-            tx_buffer: Default::default(),
+            flows: None,
+            cpu_budget: None,
+            tx_batch: TxBatch::new(),
+            tx_notify_timer: TimerFd::new(),
+            tx_last_pass_us: 0,
             rx_buffer: RxBuffers::new()?,
         })
     }
@@ -335,7 +413,28 @@ impl Net {
         tx_rate_limiter: RateLimiter,
     ) -> Result<Self, NetError> {
         let tap = Tap::open_named(tap_if_name).map_err(NetError::TapOpen)?;
+        Self::new_with_vnet_hdr(id, tap, guest_mac, rx_rate_limiter, tx_rate_limiter)
+    }
 
+    /// Create a new virtio network device given the file of an already set up tap.
+    pub fn new_with_tap_file(
+        id: String,
+        tap_file: File,
+        guest_mac: Option<MacAddr>,
+        rx_rate_limiter: RateLimiter,
+        tx_rate_limiter: RateLimiter,
+    ) -> Result<Self, NetError> {
+        let tap = Tap::from_file(tap_file).map_err(NetError::TapOpen)?;
+        Self::new_with_vnet_hdr(id, tap, guest_mac, rx_rate_limiter, tx_rate_limiter)
+    }
+
+    fn new_with_vnet_hdr(
+        id: String,
+        tap: Tap,
+        guest_mac: Option<MacAddr>,
+        rx_rate_limiter: RateLimiter,
+        tx_rate_limiter: RateLimiter,
+    ) -> Result<Self, NetError> {
         let vnet_hdr_size = i32::try_from(vnet_hdr_len()).unwrap();
         tap.set_vnet_hdr_size(vnet_hdr_size)
             .map_err(NetError::TapSetVnetHdrSize)?;
@@ -348,6 +447,61 @@ impl Net {
         self.guest_mac.as_ref()
     }
 
+    /// Whether the link of this net device is reported as up to the guest.
+    pub fn link_up(&self) -> bool {
+        u16::from_le(self.config_space.status) & VIRTIO_NET_S_LINK_UP != 0
+    }
+
+    /// Reports the link of this net device as up or down to the guest, as if the cable was
+    /// (un)plugged, notifying the driver when the device is activated.
+    pub fn set_link_state(&mut self, up: bool) -> Result<(), DeviceError> {
+        if self.link_up() == up {
+            return Ok(());
+        }
+        let status = if up { VIRTIO_NET_S_LINK_UP } else { 0 };
+        self.config_space.status = status.to_le();
+        info!("{}: link {}", self.id, if up { "up" } else { "down" });
+
+        if self.is_activated() {
+            self.interrupt_trigger()
+                .trigger(VirtioInterruptType::Config)
+                .map_err(|err| {
+                    self.metrics.event_fails.inc();
+                    DeviceError::FailedSignalingIrq(err)
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the configuration of the sampled flow table, if flow sampling is enabled.
+    pub fn flow_sampling(&self) -> Option<FlowSamplingConfig> {
+        self.flows.as_ref().map(FlowTable::config)
+    }
+
+    /// Enables flow sampling with the given configuration, dropping the flows sampled so far, or
+    /// disables it.
+    pub fn set_flow_sampling(
+        &mut self,
+        config: Option<FlowSamplingConfig>,
+    ) -> Result<(), FlowSamplingError> {
+        self.flows = config.map(FlowTable::new).transpose()?;
+        Ok(())
+    }
+
+    /// Charges the I/O processing of this net device to the given host CPU budget.
+    pub fn set_cpu_budget(&mut self, budget: &Arc<Mutex<CpuBudget>>) -> std::io::Result<()> {
+        self.cpu_budget = Some(CpuBudget::attach(budget, &self.queue_evts)?);
+        Ok(())
+    }
+
+    /// Returns the flows sampled on this net device.
+    pub fn network_flows(&self) -> Result<NetworkFlows, FlowSamplingError> {
+        self.flows
+            .as_ref()
+            .map(|flows| flows.flows(get_time_us(ClockType::Monotonic)))
+            .ok_or(FlowSamplingError::Disabled)
+    }
+
     /// Provides the host IFACE name of this net device.
     pub fn iface_name(&self) -> String {
         self.tap.if_name_as_str().to_string()
@@ -359,13 +513,18 @@ impl Net {
     }
 
     /// Configures the `MmdsNetworkStack` to allow device to forward MMDS requests.
-    /// If the device already supports MMDS, updates the IPv4 address.
-    pub fn configure_mmds_network_stack(&mut self, ipv4_addr: Ipv4Addr, mmds: Arc<Mutex<Mmds>>) {
-        if let Some(mmds_ns) = self.mmds_ns.as_mut() {
-            mmds_ns.set_ipv4_addr(ipv4_addr);
-        } else {
-            self.mmds_ns = Some(MmdsNetworkStack::new_with_defaults(Some(ipv4_addr), mmds))
-        }
+    /// If the device already supports MMDS, updates the IPv4 address and the rate limiter.
+    pub fn configure_mmds_network_stack(
+        &mut self,
+        ipv4_addr: Ipv4Addr,
+        mmds: Arc<Mutex<Mmds>>,
+        rate_limiter: RateLimiter,
+    ) {
+        let mmds_ns = self
+            .mmds_ns
+            .get_or_insert_with(|| MmdsNetworkStack::new_with_defaults(Some(ipv4_addr), mmds));
+        mmds_ns.set_ipv4_addr(ipv4_addr);
+        mmds_ns.set_rate_limiter(rate_limiter);
     }
 
     /// Disables the `MmdsNetworkStack` to prevent device to forward MMDS requests.
@@ -393,7 +552,11 @@ impl Net {
             NetQueue::Tx => TX_INDEX,
         };
         self.queues[qidx].advance_used_ring_idx();
+        self.kick_queue(qidx)
+    }
 
+    // Notifies the guest of the used descriptors of the queue `qidx`, if it asked for it.
+    fn kick_queue(&mut self, qidx: usize) -> Result<(), DeviceError> {
         if self.queues[qidx].prepare_kick() {
             self.interrupt_trigger()
                 .trigger(VirtioInterruptType::Queue(qidx.try_into().unwrap()))
@@ -528,8 +691,12 @@ impl Net {
             frame_iovec
                 .read_exact_volatile_at(&mut frame, vnet_hdr_len())
                 .unwrap();
-            let _ = ns.detour_frame(&frame);
-            METRICS.mmds.rx_accepted.inc();
+            // Frames over the MMDS budget are dropped, so that a guest flooding MMDS can't
+            // monopolize the VMM thread with HTTP parsing.
+            if ns.consume_frame_budget(frame.len() as u64) {
+                let _ = ns.detour_frame(&frame);
+                METRICS.mmds.rx_accepted.inc();
+            }
 
             // MMDS frames are not accounted by the rate limiter.
             Self::rate_limiter_replenish_op(rate_limiter, u64::from(frame_iovec.len()));
@@ -611,6 +778,27 @@ impl Net {
         // SAFETY:
         // * We ensured that `self.rx_buffer` has at least one DescriptorChain parsed in it.
         let len = unsafe { self.read_tap().map_err(NetError::IO) }?;
+        if let Some(flows) = self.flows.as_mut()
+            && flows.sample()
+        {
+            let frame_len = len.saturating_sub(vnet_hdr_len());
+            let mut headers = [0u8; FLOW_HEADERS_LEN];
+            let headers_len = self
+                .rx_buffer
+                .iovec
+                .read_volatile_at(
+                    &mut &mut headers[..],
+                    vnet_hdr_len(),
+                    frame_len.min(FLOW_HEADERS_LEN),
+                )
+                .unwrap_or(0);
+            flows.record(
+                FlowDirection::Rx,
+                &headers[..headers_len],
+                frame_len as u64,
+                get_time_us(ClockType::Monotonic),
+            );
+        }
         // SAFETY:
         // * len will never be bigger that u32::MAX
         let len: u32 = len.try_into().unwrap();
@@ -689,66 +877,96 @@ impl Net {
         // with the MMDS network stack.
         let mut process_rx_for_mmds = false;
         let mut used_any = false;
-        let tx_queue = &mut self.queues[TX_INDEX];
-
-        while let Some(head) = tx_queue.pop_or_enable_notification()? {
-            self.metrics
-                .tx_remaining_reqs_count
-                .add(tx_queue.len().into());
-            let head_index = head.index;
-            // Parse IoVecBuffer from descriptor head
-            // SAFETY: This descriptor chain is only loaded once
-            // virtio requests are handled sequentially so no two IoVecBuffers
-            // are live at the same time, meaning this has exclusive ownership over the memory
-            if unsafe { self.tx_buffer.load_descriptor_chain(mem, head).is_err() } {
-                self.metrics.tx_fails.inc();
-                tx_queue.add_used(head_index, 0)?;
-                continue;
-            };
+        // Frames are popped in batches, which are written to the tap and then returned to the
+        // guest at once. The tap takes a single frame per write.
+        loop {
+            let tx_queue = &mut self.queues[TX_INDEX];
+            let mut throttled = false;
+            while !self.tx_batch.is_full() {
+                let Some(head) = tx_queue.pop_or_enable_notification()? else {
+                    break;
+                };
+                self.metrics
+                    .tx_remaining_reqs_count
+                    .add(tx_queue.len().into());
+                let head_index = head.index;
+                let frame = self.tx_batch.next_frame();
+                // Parse IoVecBuffer from descriptor head
+                // SAFETY: This descriptor chain is only loaded once, and the buffers of the batch
+                // are only read from, so chains referencing the same memory can't conflict.
+                if unsafe { frame.load_descriptor_chain(mem, head).is_err() } {
+                    self.metrics.tx_fails.inc();
+                    tx_queue.add_used(head_index, 0)?;
+                    continue;
+                };
+
+                // We only handle frames that are up to MAX_BUFFER_SIZE
+                if frame.len() as usize > MAX_BUFFER_SIZE {
+                    error!("net: received too big frame from driver");
+                    self.metrics.tx_malformed_frames.inc();
+                    tx_queue.add_used(head_index, 0)?;
+                    continue;
+                }
 
-            // We only handle frames that are up to MAX_BUFFER_SIZE
-            if self.tx_buffer.len() as usize > MAX_BUFFER_SIZE {
-                error!("net: received too big frame from driver");
-                self.metrics.tx_malformed_frames.inc();
-                tx_queue.add_used(head_index, 0)?;
-                continue;
+                if !Self::rate_limiter_consume_op(&mut self.tx_rate_limiter, u64::from(frame.len()))
+                {
+                    tx_queue.undo_pop();
+                    self.metrics.tx_rate_limiter_throttled.inc();
+                    throttled = true;
+                    break;
+                }
+                self.tx_batch.push(head_index);
             }
+            let batch_full = self.tx_batch.is_full();
+
+            for (frame, head_index) in self.tx_batch.iter() {
+                let frame_consumed_by_mmds = Self::write_to_mmds_or_tap(
+                    self.mmds_ns.as_mut(),
+                    &mut self.tx_rate_limiter,
+                    &mut self.tx_frame_headers,
+                    frame,
+                    &mut self.tap,
+                    self.guest_mac,
+                    &self.metrics,
+                )
+                .unwrap_or(false);
+                if !frame_consumed_by_mmds
+                    && let Some(flows) = self.flows.as_mut()
+                    && flows.sample()
+                {
+                    let mut headers = [0u8; FLOW_HEADERS_LEN];
+                    let headers_len = frame
+                        .read_volatile_at(&mut &mut headers[..], vnet_hdr_len(), FLOW_HEADERS_LEN)
+                        .unwrap_or(0);
+                    flows.record(
+                        FlowDirection::Tx,
+                        &headers[..headers_len],
+                        (frame.len() as usize).saturating_sub(vnet_hdr_len()) as u64,
+                        get_time_us(ClockType::Monotonic),
+                    );
+                }
+                if frame_consumed_by_mmds && self.rx_buffer.used_bytes == 0 {
+                    // MMDS consumed this frame/request, let's also try to process the response.
+                    process_rx_for_mmds = true;
+                }
 
-            if !Self::rate_limiter_consume_op(
-                &mut self.tx_rate_limiter,
-                u64::from(self.tx_buffer.len()),
-            ) {
-                tx_queue.undo_pop();
-                self.metrics.tx_rate_limiter_throttled.inc();
-                break;
+                self.queues[TX_INDEX].add_used(head_index, 0)?;
+                used_any = true;
             }
 
-            let frame_consumed_by_mmds = Self::write_to_mmds_or_tap(
-                self.mmds_ns.as_mut(),
-                &mut self.tx_rate_limiter,
-                &mut self.tx_frame_headers,
-                &self.tx_buffer,
-                &mut self.tap,
-                self.guest_mac,
-                &self.metrics,
-            )
-            .unwrap_or(false);
-            if frame_consumed_by_mmds && self.rx_buffer.used_bytes == 0 {
-                // MMDS consumed this frame/request, let's also try to process the response.
-                process_rx_for_mmds = true;
+            // Cleanup the batch to ensure no two buffers point at the same memory
+            self.tx_batch.clear();
+            self.queues[TX_INDEX].advance_used_ring_idx();
+            if throttled || !batch_full {
+                break;
             }
-
-            tx_queue.add_used(head_index, 0)?;
-            used_any = true;
         }
 
         if !used_any {
             self.metrics.no_tx_avail_buffer.inc();
         }
 
-        // Cleanup tx_buffer to ensure no two buffers point at the same memory
-        self.tx_buffer.clear();
-        self.try_signal_queue(NetQueue::Tx)?;
+        self.signal_tx_queue()?;
 
         // An incoming frame for the MMDS may trigger the transmission of a new message.
         if process_rx_for_mmds {
@@ -758,6 +976,28 @@ impl Net {
         }
     }
 
+    // Notifies the guest of the transmitted frames. Under sustained load the guest doesn't wait
+    // for each frame to complete, so its notification is deferred to cover the frames of the
+    // next passes too, unless half of the queue is already waiting to be returned.
+    fn signal_tx_queue(&mut self) -> Result<(), DeviceError> {
+        let now_us = get_time_us(ClockType::Monotonic);
+        let sustained_load = now_us.saturating_sub(self.tx_last_pass_us) < TX_NOTIFY_DELAY_US;
+        self.tx_last_pass_us = now_us;
+
+        let tx_queue = &self.queues[TX_INDEX];
+        let pending = tx_queue.num_added.0;
+        if sustained_load && 0 < pending && pending < tx_queue.size / 2 {
+            if !self.tx_notify_timer.is_armed() {
+                self.tx_notify_timer
+                    .arm(Duration::from_micros(TX_NOTIFY_DELAY_US), None);
+            }
+            self.metrics.tx_deferred_notifications.inc();
+            return Ok(());
+        }
+
+        self.kick_queue(TX_INDEX)
+    }
+
     /// Builds the offload features we will setup on the TAP device based on the features that the
     /// guest supports.
     pub fn build_tap_offload_features(guest_supported_features: u64) -> u32 {
@@ -904,6 +1144,13 @@ impl Net {
         }
     }
 
+    /// Process the expiration of the deferred notification of the TX queue.
+    pub fn process_tx_notify_timer_event(&mut self) {
+        self.tx_notify_timer.read();
+        self.kick_queue(TX_INDEX)
+            .unwrap_or_else(|err| report_net_event_fail(&self.metrics, err));
+    }
+
     pub fn process_tx_rate_limiter_event(&mut self) {
         self.metrics.tx_rate_limiter_event_count.inc();
         // Upon rate limiter event, call the rate limiter handler
@@ -921,6 +1168,17 @@ impl Net {
         }
     }
 
+    pub fn process_mmds_rate_limiter_event(&mut self) {
+        // Frames over the MMDS budget are dropped rather than deferred, so there is no queue to
+        // resume here; re-enabling the rate limiter is enough.
+        if let Some(mmds_ns) = self.mmds_ns.as_mut()
+            && let Err(err) = mmds_ns.rate_limiter.event_handler()
+        {
+            error!("Failed to get mmds rate-limiter event: {:?}", err);
+            self.metrics.event_fails.inc();
+        }
+    }
+
     /// Process device virtio queue(s).
     pub fn process_virtio_queues(&mut self) -> Result<(), InvalidAvailIdx> {
         if let Err(DeviceError::InvalidAvailIdx(err)) = self.resume_rx() {
@@ -984,7 +1242,8 @@ impl VirtioDevice for Net {
     }
 
     fn write_config(&mut self, offset: u64, data: &[u8]) {
-        let config_space_bytes = self.config_space.as_mut_slice();
+        // Only the MAC address is writable by the driver, the link status is read-only.
+        let config_space_bytes = &mut self.config_space.as_mut_slice()[..mem::size_of::<MacAddr>()];
         let start = usize::try_from(offset).ok();
         let end = start.and_then(|s| s.checked_add(data.len()));
         let Some(dst) = start
@@ -1037,6 +1296,12 @@ impl VirtioDevice for Net {
         self.device_state.is_activated()
     }
 
+    fn unplug(&mut self) {
+        // The device does not support resets, but it stops using the queues and the tap once it
+        // is inactive.
+        self.device_state = DeviceState::Inactive;
+    }
+
     /// Prepare saving state
     fn prepare_save(&mut self) {
         // We shouldn't be messing with the queue if the device is not activated.
@@ -1048,6 +1313,13 @@ impl VirtioDevice for Net {
 
         // Give potential deferred RX frame to guest
         self.rx_buffer.finish_frame(&mut self.queues[RX_INDEX]);
+        // Notify the guest of the frames transmitted since its deferred TX notification
+        if let Err(err) = self.kick_queue(TX_INDEX) {
+            error!(
+                "Failed to signal the tx queue of net device {}: {:?}",
+                self.id, err
+            );
+        }
         // Reset the parsed available descriptors, so we will re-parse them
         self.queues[RX_INDEX].next_avail -=
             Wrapping(u16::try_from(self.rx_buffer.parsed_descriptors.len()).unwrap());
@@ -1056,6 +1328,18 @@ impl VirtioDevice for Net {
         self.rx_buffer.used_bytes = 0;
         self.rx_buffer.used_descriptors = 0;
     }
+
+    fn drain(&mut self) {
+        if !self.is_activated() {
+            return;
+        }
+
+        // Only the frames the guest queued are sent; the frames of the tap are left to it.
+        if let Err(err) = self.process_tx() {
+            error!("Failed to drain net device {}: {:?}", self.id, err);
+            self.metrics.tx_fails.inc();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1185,9 +1469,14 @@ pub mod tests {
         net.read_config(0, &mut config_mac);
         assert_eq!(&config_mac, mac.get_bytes());
 
+        // The link is reported as up.
+        let mut status = [0u8; 2];
+        net.read_config(u64::from(MAC_ADDR_LEN), &mut status);
+        assert_eq!(u16::from_le_bytes(status), VIRTIO_NET_S_LINK_UP);
+
         // Invalid read.
         config_mac = [0u8; MAC_ADDR_LEN as usize];
-        net.read_config(u64::from(MAC_ADDR_LEN), &mut config_mac);
+        net.read_config(mem::size_of::<ConfigSpace>() as u64, &mut config_mac);
         assert_eq!(config_mac, [0u8, 0u8, 0u8, 0u8, 0u8, 0u8]);
     }
 
@@ -1222,6 +1511,10 @@ pub mod tests {
         net.read_config(0, &mut new_config_read);
         assert_eq!(new_config, new_config_read);
 
+        // The link status is read-only.
+        net.write_config(u64::from(MAC_ADDR_LEN), &[0, 0]);
+        assert!(net.link_up());
+
         // Large offset that may cause an overflow.
         net.write_config(u64::MAX, &new_config);
         // Verify old config was untouched.
@@ -1900,6 +2193,60 @@ pub mod tests {
         assert_eq!(&buf[..600], &frame_2[..600]);
     }
 
+    #[test]
+    fn test_tx_deferred_notification() {
+        let mem = single_region_mem(2 * MAX_BUFFER_SIZE);
+        let mut th = TestHelper::get_default(&mem);
+        th.activate_net();
+
+        // Under sustained load, the notification of the transmitted frames is deferred.
+        th.net().tx_last_pass_us = u64::MAX;
+        let desc_list = [(0, 1000, 0)];
+        th.add_desc_chain(NetQueue::Tx, 0, &desc_list);
+        let _ = th.write_tx_frame(&desc_list, 1000);
+        check_metric_after_block!(
+            th.net().metrics.tx_deferred_notifications,
+            1,
+            th.simulate_event(NetEvent::TxQueue)
+        );
+        assert_eq!(th.txq.used.idx.get(), 1);
+        assert!(
+            !th.net()
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Queue(TX_INDEX as u16))
+        );
+        assert!(th.net().tx_notify_timer.is_armed());
+
+        // The guest is notified once the timer expires.
+        th.event_manager.run_with_timeout(100).unwrap();
+        assert!(
+            th.net()
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Queue(TX_INDEX as u16))
+        );
+
+        // The guest is notified right away once half of the queue waits to be returned.
+        let mut th = TestHelper::get_default(&mem);
+        th.activate_net();
+        th.net().tx_last_pass_us = u64::MAX;
+        for i in 0..TestHelper::QUEUE_SIZE / 2 {
+            let desc_list = [(i, 100, 0)];
+            th.add_desc_chain(NetQueue::Tx, u64::from(i) * 100, &desc_list);
+            let _ = th.write_tx_frame(&desc_list, 100);
+        }
+        check_metric_after_block!(
+            th.net().metrics.tx_deferred_notifications,
+            0,
+            th.simulate_event(NetEvent::TxQueue)
+        );
+        assert_eq!(th.txq.used.idx.get(), TestHelper::QUEUE_SIZE / 2);
+        assert!(
+            th.net()
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Queue(TX_INDEX as u16))
+        );
+    }
+
     fn create_arp_request(
         src_mac: MacAddr,
         src_ip: Ipv4Addr,
@@ -2457,6 +2804,95 @@ pub mod tests {
         assert!(th.net().tx_rate_limiter.ops().is_none());
     }
 
+    #[test]
+    fn test_set_link_state() {
+        let mem = single_region_mem(2 * MAX_BUFFER_SIZE);
+        let mut th = TestHelper::get_default(&mem);
+        assert!(th.net().link_up());
+        assert_ne!(th.net().avail_features() & (1 << VIRTIO_NET_F_STATUS), 0);
+
+        // The driver is not notified while the device is not activated.
+        th.net().set_link_state(false).unwrap();
+        assert!(!th.net().link_up());
+
+        th.activate_net();
+        th.net().set_link_state(true).unwrap();
+        assert!(th.net().link_up());
+        assert!(
+            th.net()
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Config)
+        );
+        let mut status = [0u8; 2];
+        th.net().read_config(u64::from(MAC_ADDR_LEN), &mut status);
+        assert_eq!(u16::from_le_bytes(status), VIRTIO_NET_S_LINK_UP);
+
+        th.net().set_link_state(false).unwrap();
+        th.net().read_config(u64::from(MAC_ADDR_LEN), &mut status);
+        assert_eq!(u16::from_le_bytes(status), 0);
+    }
+
+    #[test]
+    fn test_flow_sampling() {
+        let mem = single_region_mem(2 * MAX_BUFFER_SIZE);
+        let mut th = TestHelper::get_default(&mem);
+        assert_eq!(
+            th.net().network_flows().unwrap_err(),
+            FlowSamplingError::Disabled
+        );
+        let config = FlowSamplingConfig {
+            sample_rate: 1,
+            max_flows: 16,
+            idle_timeout_s: 0,
+        };
+        assert_eq!(
+            th.net().set_flow_sampling(Some(FlowSamplingConfig {
+                sample_rate: 0,
+                ..config
+            })),
+            Err(FlowSamplingError::InvalidSampleRate)
+        );
+        th.net().set_flow_sampling(Some(config)).unwrap();
+        assert_eq!(th.net().flow_sampling(), Some(config));
+        th.activate_net();
+
+        // An UDP datagram from 10.0.0.2:1234 to 10.0.0.1:53.
+        let mut frame = vec![0u8; vnet_hdr_len() + 100];
+        let eth = &mut frame[vnet_hdr_len()..];
+        eth[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+        eth[14] = 0x45;
+        eth[23] = 17;
+        eth[26..30].copy_from_slice(&[10, 0, 0, 2]);
+        eth[30..34].copy_from_slice(&[10, 0, 0, 1]);
+        eth[34..36].copy_from_slice(&1234u16.to_be_bytes());
+        eth[36..38].copy_from_slice(&53u16.to_be_bytes());
+        th.add_desc_chain(
+            NetQueue::Tx,
+            0,
+            &[(0, u32::try_from(frame.len()).unwrap(), 0)],
+        );
+        th.txq.dtable[0].set_data(&frame);
+        check_metric_after_block!(
+            th.net().metrics.tx_packets_count,
+            1,
+            th.event_manager.run_with_timeout(100).unwrap()
+        );
+
+        let flows = th.net().network_flows().unwrap();
+        assert_eq!(flows.sampled_frames, 1);
+        assert_eq!(flows.flows.len(), 1);
+        let flow = &flows.flows[0];
+        assert_eq!(flow.direction, FlowDirection::Tx);
+        assert_eq!(flow.protocol, 17);
+        assert_eq!(flow.src_addr, Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(flow.dst_addr, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!((flow.src_port, flow.dst_port), (1234, 53));
+        assert_eq!((flow.packets, flow.bytes), (1, 100));
+
+        th.net().set_flow_sampling(None).unwrap();
+        assert_eq!(th.net().flow_sampling(), None);
+    }
+
     #[test]
     fn test_virtio_device() {
         let mem = single_region_mem(2 * MAX_BUFFER_SIZE);
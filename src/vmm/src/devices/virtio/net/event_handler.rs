@@ -8,6 +8,7 @@ use crate::devices::virtio::device::VirtioDevice;
 use crate::devices::virtio::net::device::Net;
 use crate::devices::virtio::net::{RX_INDEX, TX_INDEX};
 use crate::logger::{IncMetric, error, warn};
+use crate::rate_limiter::cpu_budget::CpuBudgetHandle;
 
 impl Net {
     const PROCESS_ACTIVATE: u32 = 0;
@@ -16,6 +17,8 @@ impl Net {
     const PROCESS_TAP_RX: u32 = 3;
     const PROCESS_RX_RATE_LIMITER: u32 = 4;
     const PROCESS_TX_RATE_LIMITER: u32 = 5;
+    const PROCESS_MMDS_RATE_LIMITER: u32 = 6;
+    const PROCESS_TX_NOTIFY_TIMER: u32 = 7;
 
     fn register_runtime_events(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
@@ -46,6 +49,22 @@ impl Net {
         )) {
             error!("Failed to register tx queue event: {}", err);
         }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.tx_notify_timer,
+            Self::PROCESS_TX_NOTIFY_TIMER,
+            EventSet::IN,
+        )) {
+            error!("Failed to register tx notify timer event: {}", err);
+        }
+        if let Some(mmds_ns) = &self.mmds_ns
+            && let Err(err) = ops.add(Events::with_data(
+                mmds_ns.rate_limiter(),
+                Self::PROCESS_MMDS_RATE_LIMITER,
+                EventSet::IN,
+            ))
+        {
+            error!("Failed to register mmds rate limiter event: {}", err);
+        }
         if let Err(err) = ops.add(Events::with_data(
             &self.tap,
             Self::PROCESS_TAP_RX,
@@ -78,6 +97,20 @@ impl Net {
             error!("Failed to un-register activate event: {}", err);
         }
     }
+
+    // Consumes an event deferred while the CPU budget is exhausted. Both queue events are
+    // signalled once the budget unblocks, which also resumes receiving from the tap.
+    fn process_deferred_event(&mut self, source: u32) {
+        let queue_evt = match source {
+            Self::PROCESS_VIRTQ_RX => &self.queue_evts[RX_INDEX],
+            Self::PROCESS_VIRTQ_TX => &self.queue_evts[TX_INDEX],
+            _ => return,
+        };
+        if let Err(err) = queue_evt.read() {
+            error!("Failed to get deferred queue event: {:?}", err);
+            self.metrics.event_fails.inc();
+        }
+    }
 }
 
 impl MutEventSubscriber for Net {
@@ -97,6 +130,20 @@ impl MutEventSubscriber for Net {
         }
 
         if self.is_activated() {
+            // Guest and tap driven I/O waits for the CPU budget to unblock.
+            if matches!(
+                source,
+                Self::PROCESS_VIRTQ_RX | Self::PROCESS_VIRTQ_TX | Self::PROCESS_TAP_RX
+            ) && self
+                .cpu_budget
+                .as_ref()
+                .is_some_and(CpuBudgetHandle::throttle)
+            {
+                self.process_deferred_event(source);
+                return;
+            }
+
+            let cpu_start_us = self.cpu_budget.as_ref().map(CpuBudgetHandle::start);
             match source {
                 Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
                 Self::PROCESS_VIRTQ_RX => self.process_rx_queue_event(),
@@ -104,11 +151,16 @@ impl MutEventSubscriber for Net {
                 Self::PROCESS_TAP_RX => self.process_tap_rx_event(),
                 Self::PROCESS_RX_RATE_LIMITER => self.process_rx_rate_limiter_event(),
                 Self::PROCESS_TX_RATE_LIMITER => self.process_tx_rate_limiter_event(),
+                Self::PROCESS_MMDS_RATE_LIMITER => self.process_mmds_rate_limiter_event(),
+                Self::PROCESS_TX_NOTIFY_TIMER => self.process_tx_notify_timer_event(),
                 _ => {
                     warn!("Net: Spurious event received: {:?}", source);
                     self.metrics.event_fails.inc();
                 }
             }
+            if let (Some(cpu_budget), Some(start_us)) = (&self.cpu_budget, cpu_start_us) {
+                cpu_budget.charge_since(start_us);
+            }
         } else {
             warn!(
                 "Net: The device is not yet activated. Spurious event received: {:?}",
@@ -132,9 +184,14 @@ impl MutEventSubscriber for Net {
 
 #[cfg(test)]
 pub mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
     use crate::devices::virtio::net::test_utils::NetQueue;
     use crate::devices::virtio::net::test_utils::test::TestHelper;
     use crate::devices::virtio::net::{MAX_BUFFER_SIZE, TX_INDEX};
+    use crate::rate_limiter::cpu_budget::CpuBudget;
     use crate::test_utils::single_region_mem;
 
     #[test]
@@ -166,4 +223,33 @@ pub mod tests {
         // Make sure the data queue advanced.
         assert_eq!(th.txq.used.idx.get(), 1);
     }
+
+    #[test]
+    fn test_cpu_budget() {
+        let mem = single_region_mem(2 * MAX_BUFFER_SIZE);
+        let mut th = TestHelper::get_default(&mem);
+        th.activate_net();
+        th.event_manager.run_with_timeout(50).unwrap();
+
+        // Exhaust the budget, owing 1ms of CPU time.
+        let budget = Arc::new(Mutex::new(CpuBudget::new(1_000_000, 1000).unwrap()));
+        th.net().set_cpu_budget(&budget).unwrap();
+        budget.lock().unwrap().charge(1_001_000);
+        assert!(budget.lock().unwrap().is_blocked());
+
+        // The queue event is consumed, but the TX queue is not processed.
+        th.add_desc_chain(NetQueue::Tx, 0, &[(0, 4096, 0)]);
+        th.net().queue_evts[TX_INDEX].write(1).unwrap();
+        let ev_count = th.event_manager.run_with_timeout(50).unwrap();
+        assert_eq!(ev_count, 1);
+        assert_eq!(th.txq.used.idx.get(), 0);
+        let ev_count = th.event_manager.run_with_timeout(50).unwrap();
+        assert_eq!(ev_count, 0);
+
+        // Once the budget unblocks, the deferred work is resumed.
+        thread::sleep(Duration::from_millis(20));
+        budget.lock().unwrap().event_handler().unwrap();
+        th.event_manager.run_with_timeout(50).unwrap();
+        assert_eq!(th.txq.used.idx.get(), 1);
+    }
 }
@@ -0,0 +1,448 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sampled flow table of a network device, attributing the traffic of the guest to the flows it
+//! belongs to. One frame out of `sample_rate` is parsed and accounted to its flow, identified by
+//! the direction of the frame and its 5-tuple. When the table is full, the least active half of
+//! the flows is dropped, so that the table converges to the top talkers.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+
+/// Largest number of flows tracked by a device.
+pub const FLOW_MAX_FLOWS: u32 = 65536;
+/// Bytes of a frame read to identify its flow: an Ethernet header with a VLAN tag, an IPv4
+/// header with options and the ports of the transport header.
+pub const FLOW_HEADERS_LEN: usize = 14 + 4 + 60 + 4;
+
+const ETH_HEADER_LEN: usize = 14;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const IPV6_HEADER_LEN: usize = 40;
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+const IP_PROTO_SCTP: u8 = 132;
+
+/// Configuration of the sampled flow table of a network device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowSamplingConfig {
+    /// One frame out of `sample_rate` is accounted to its flow.
+    pub sample_rate: u32,
+    /// Largest number of flows tracked, up to 65536.
+    pub max_flows: u32,
+    /// Flows not sampled for this many seconds are dropped. Flows are only dropped to make room
+    /// for new ones if 0.
+    #[serde(default)]
+    pub idle_timeout_s: u64,
+}
+
+/// Errors associated with `FlowSamplingConfig`.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum FlowSamplingError {
+    /// The sampling rate of the flow table must be at least 1.
+    InvalidSampleRate,
+    /// Invalid number of flows {0}: the flow table tracks between 1 and 65536 flows.
+    InvalidMaxFlows(u32),
+    /// Flow sampling is not enabled on the network interface.
+    Disabled,
+}
+
+impl FlowSamplingConfig {
+    /// Checks that the configuration describes a usable flow table.
+    pub fn validate(&self) -> Result<(), FlowSamplingError> {
+        if self.sample_rate == 0 {
+            return Err(FlowSamplingError::InvalidSampleRate);
+        }
+        if self.max_flows == 0 || self.max_flows > FLOW_MAX_FLOWS {
+            return Err(FlowSamplingError::InvalidMaxFlows(self.max_flows));
+        }
+        Ok(())
+    }
+}
+
+/// Direction of the frames of a flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowDirection {
+    /// Frames sent by the guest.
+    Tx,
+    /// Frames received by the guest.
+    Rx,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    direction: FlowDirection,
+    protocol: u8,
+    src_addr: IpAddr,
+    dst_addr: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FlowCounters {
+    packets: u64,
+    bytes: u64,
+    last_seen_us: u64,
+}
+
+/// Estimated traffic of a flow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlowInfo {
+    /// Direction of the frames of the flow.
+    pub direction: FlowDirection,
+    /// IP protocol number of the transport protocol.
+    pub protocol: u8,
+    /// Source address.
+    pub src_addr: IpAddr,
+    /// Destination address.
+    pub dst_addr: IpAddr,
+    /// Source port, 0 for protocols without ports.
+    pub src_port: u16,
+    /// Destination port, 0 for protocols without ports.
+    pub dst_port: u16,
+    /// Estimated number of frames: the number of sampled frames times the sampling rate.
+    pub packets: u64,
+    /// Estimated number of bytes of the frames, Ethernet header included.
+    pub bytes: u64,
+}
+
+/// Content of the flow table of a network device, by decreasing number of bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetworkFlows {
+    /// One frame out of `sample_rate` is accounted to its flow.
+    pub sample_rate: u32,
+    /// Number of frames sampled since the device was created.
+    pub sampled_frames: u64,
+    /// Tracked flows.
+    pub flows: Vec<FlowInfo>,
+}
+
+/// Extracts the flow of an Ethernet frame, or returns `None` if it doesn't carry IP.
+fn parse_flow(direction: FlowDirection, frame: &[u8]) -> Option<FlowKey> {
+    let u16_at = |bytes: &[u8], offset: usize| {
+        bytes
+            .get(offset..offset + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let mut ethertype = u16_at(frame, 12)?;
+    let mut offset = ETH_HEADER_LEN;
+    if ethertype == ETHERTYPE_VLAN {
+        ethertype = u16_at(frame, 16)?;
+        offset += 4;
+    }
+    let ip = frame.get(offset..)?;
+
+    let (protocol, src_addr, dst_addr, transport) = match ethertype {
+        ETHERTYPE_IPV4 => {
+            let header_len = usize::from(ip.first()? & 0xf) * 4;
+            let src: [u8; 4] = ip.get(12..16)?.try_into().unwrap();
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().unwrap();
+            // Only the first fragment holds the transport header.
+            let first_fragment = u16_at(ip, 6)? & 0x1fff == 0;
+            let transport = if first_fragment && header_len >= 20 {
+                ip.get(header_len..)
+            } else {
+                None
+            };
+            (
+                ip[9],
+                IpAddr::from(Ipv4Addr::from(src)),
+                IpAddr::from(Ipv4Addr::from(dst)),
+                transport,
+            )
+        }
+        ETHERTYPE_IPV6 => {
+            let src: [u8; 16] = ip.get(8..24)?.try_into().unwrap();
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().unwrap();
+            (
+                ip[6],
+                IpAddr::from(Ipv6Addr::from(src)),
+                IpAddr::from(Ipv6Addr::from(dst)),
+                ip.get(IPV6_HEADER_LEN..),
+            )
+        }
+        _ => return None,
+    };
+
+    let (src_port, dst_port) = match (protocol, transport) {
+        (IP_PROTO_TCP | IP_PROTO_UDP | IP_PROTO_SCTP, Some(transport)) => {
+            (u16_at(transport, 0)?, u16_at(transport, 2)?)
+        }
+        _ => (0, 0),
+    };
+    Some(FlowKey {
+        direction,
+        protocol,
+        src_addr,
+        dst_addr,
+        src_port,
+        dst_port,
+    })
+}
+
+/// Sampled flow table of a network device.
+#[derive(Debug)]
+pub struct FlowTable {
+    config: FlowSamplingConfig,
+    /// Frames seen since the last sampled one.
+    skipped_frames: u32,
+    sampled_frames: u64,
+    flows: HashMap<FlowKey, FlowCounters>,
+}
+
+impl FlowTable {
+    /// Creates an empty flow table.
+    pub fn new(config: FlowSamplingConfig) -> Result<Self, FlowSamplingError> {
+        config.validate()?;
+        Ok(FlowTable {
+            config,
+            skipped_frames: 0,
+            sampled_frames: 0,
+            flows: HashMap::new(),
+        })
+    }
+
+    /// Returns the configuration of the table.
+    pub fn config(&self) -> FlowSamplingConfig {
+        self.config
+    }
+
+    /// Counts a frame, returning whether it is sampled.
+    pub fn sample(&mut self) -> bool {
+        self.skipped_frames += 1;
+        if self.skipped_frames < self.config.sample_rate {
+            return false;
+        }
+        self.skipped_frames = 0;
+        true
+    }
+
+    /// Accounts a sampled frame of `len` bytes, starting with `headers`, to its flow.
+    pub fn record(&mut self, direction: FlowDirection, headers: &[u8], len: u64, now_us: u64) {
+        self.sampled_frames += 1;
+        let Some(key) = parse_flow(direction, headers) else {
+            return;
+        };
+        if !self.flows.contains_key(&key) && self.flows.len() >= self.max_flows() {
+            self.evict(now_us);
+        }
+        let counters = self.flows.entry(key).or_default();
+        counters.packets += 1;
+        counters.bytes += len;
+        counters.last_seen_us = now_us;
+    }
+
+    fn max_flows(&self) -> usize {
+        usize::try_from(self.config.max_flows).unwrap()
+    }
+
+    fn is_idle(&self, counters: &FlowCounters, now_us: u64) -> bool {
+        self.config.idle_timeout_s != 0
+            && now_us.saturating_sub(counters.last_seen_us)
+                >= self.config.idle_timeout_s.saturating_mul(1_000_000)
+    }
+
+    /// Drops the idle flows and, if the table is still full, the least active half of the flows.
+    fn evict(&mut self, now_us: u64) {
+        let idle: Vec<_> = self
+            .flows
+            .iter()
+            .filter(|(_, counters)| self.is_idle(counters, now_us))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in idle {
+            self.flows.remove(&key);
+        }
+        if self.flows.len() < self.max_flows() {
+            return;
+        }
+
+        let mut bytes: Vec<u64> = self.flows.values().map(|counters| counters.bytes).collect();
+        let median = (bytes.len() - 1) / 2;
+        let threshold = *bytes.select_nth_unstable(median).1;
+        self.flows.retain(|_, counters| counters.bytes > threshold);
+    }
+
+    /// Returns the flows that are not idle, by decreasing number of bytes.
+    pub fn flows(&self, now_us: u64) -> NetworkFlows {
+        let rate = u64::from(self.config.sample_rate);
+        let mut flows: Vec<_> = self
+            .flows
+            .iter()
+            .filter(|(_, counters)| !self.is_idle(counters, now_us))
+            .map(|(key, counters)| FlowInfo {
+                direction: key.direction,
+                protocol: key.protocol,
+                src_addr: key.src_addr,
+                dst_addr: key.dst_addr,
+                src_port: key.src_port,
+                dst_port: key.dst_port,
+                packets: counters.packets.saturating_mul(rate),
+                bytes: counters.bytes.saturating_mul(rate),
+            })
+            .collect();
+        flows.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        NetworkFlows {
+            sample_rate: self.config.sample_rate,
+            sampled_frames: self.sampled_frames,
+            flows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(sample_rate: u32, max_flows: u32) -> FlowSamplingConfig {
+        FlowSamplingConfig {
+            sample_rate,
+            max_flows,
+            idle_timeout_s: 0,
+        }
+    }
+
+    fn ipv4_frame(protocol: u8, src_port: u16, fragment_offset: u16) -> Vec<u8> {
+        let mut frame = vec![0u8; ETH_HEADER_LEN + 20 + 8];
+        frame[12..14].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        let ip = &mut frame[ETH_HEADER_LEN..];
+        ip[0] = 0x45;
+        ip[6..8].copy_from_slice(&fragment_offset.to_be_bytes());
+        ip[9] = protocol;
+        ip[12..16].copy_from_slice(&[192, 168, 0, 2]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 1]);
+        ip[20..22].copy_from_slice(&src_port.to_be_bytes());
+        ip[22..24].copy_from_slice(&443u16.to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_validate() {
+        config(1, 1).validate().unwrap();
+        config(1000, FLOW_MAX_FLOWS).validate().unwrap();
+        assert_eq!(
+            config(0, 1).validate(),
+            Err(FlowSamplingError::InvalidSampleRate)
+        );
+        assert_eq!(
+            config(1, 0).validate(),
+            Err(FlowSamplingError::InvalidMaxFlows(0))
+        );
+        assert_eq!(
+            config(1, FLOW_MAX_FLOWS + 1).validate(),
+            Err(FlowSamplingError::InvalidMaxFlows(FLOW_MAX_FLOWS + 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_flow() {
+        let key = parse_flow(FlowDirection::Tx, &ipv4_frame(IP_PROTO_TCP, 1234, 0)).unwrap();
+        assert_eq!(key.protocol, IP_PROTO_TCP);
+        assert_eq!(key.src_addr, IpAddr::from([192, 168, 0, 2]));
+        assert_eq!(key.dst_addr, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!((key.src_port, key.dst_port), (1234, 443));
+
+        // Later fragments and protocols without ports are accounted without ports.
+        let key = parse_flow(FlowDirection::Tx, &ipv4_frame(IP_PROTO_UDP, 1234, 10)).unwrap();
+        assert_eq!((key.src_port, key.dst_port), (0, 0));
+        let key = parse_flow(FlowDirection::Rx, &ipv4_frame(1, 1234, 0)).unwrap();
+        assert_eq!((key.protocol, key.src_port, key.dst_port), (1, 0, 0));
+
+        // VLAN tagged IPv6.
+        let mut frame = vec![0u8; ETH_HEADER_LEN + 4 + IPV6_HEADER_LEN + 4];
+        frame[12..14].copy_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+        frame[16..18].copy_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+        let ip = &mut frame[ETH_HEADER_LEN + 4..];
+        ip[6] = IP_PROTO_UDP;
+        ip[23] = 1;
+        ip[39] = 2;
+        ip[40..42].copy_from_slice(&53u16.to_be_bytes());
+        ip[42..44].copy_from_slice(&5353u16.to_be_bytes());
+        let key = parse_flow(FlowDirection::Rx, &frame).unwrap();
+        assert_eq!(key.src_addr, IpAddr::from(Ipv6Addr::LOCALHOST));
+        assert_eq!(key.dst_addr, IpAddr::from(Ipv6Addr::from(2u128)));
+        assert_eq!((key.src_port, key.dst_port), (53, 5353));
+
+        // ARP, and truncated frames.
+        let mut arp = vec![0u8; 42];
+        arp[12..14].copy_from_slice(&0x0806u16.to_be_bytes());
+        assert!(parse_flow(FlowDirection::Tx, &arp).is_none());
+        assert!(parse_flow(FlowDirection::Tx, &ipv4_frame(IP_PROTO_TCP, 1, 0)[..30]).is_none());
+        assert!(parse_flow(FlowDirection::Tx, &[]).is_none());
+    }
+
+    #[test]
+    fn test_sample_and_record() {
+        let mut table = FlowTable::new(config(3, 16)).unwrap();
+        let sampled: Vec<_> = (0..7).map(|_| table.sample()).collect();
+        assert_eq!(sampled, [false, false, true, false, false, true, false]);
+
+        let frame = ipv4_frame(IP_PROTO_TCP, 1000, 0);
+        table.record(FlowDirection::Tx, &frame, 100, 0);
+        table.record(FlowDirection::Tx, &frame, 50, 1);
+        table.record(FlowDirection::Rx, &frame, 1000, 2);
+        table.record(FlowDirection::Tx, &[0u8; 42], 60, 3);
+
+        let flows = table.flows(3);
+        assert_eq!(flows.sample_rate, 3);
+        assert_eq!(flows.sampled_frames, 4);
+        assert_eq!(flows.flows.len(), 2);
+        assert_eq!(flows.flows[0].direction, FlowDirection::Rx);
+        assert_eq!((flows.flows[0].packets, flows.flows[0].bytes), (3, 3000));
+        assert_eq!(flows.flows[1].direction, FlowDirection::Tx);
+        assert_eq!((flows.flows[1].packets, flows.flows[1].bytes), (6, 450));
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut table = FlowTable::new(config(1, 4)).unwrap();
+        for port in 0..4 {
+            let len = 100 * (u64::from(port) + 1);
+            table.record(
+                FlowDirection::Tx,
+                &ipv4_frame(IP_PROTO_UDP, port, 0),
+                len,
+                0,
+            );
+        }
+        // The table is full: the least active half of the flows makes room for the new one.
+        table.record(FlowDirection::Tx, &ipv4_frame(IP_PROTO_UDP, 4, 0), 50, 0);
+        let ports: Vec<_> = table.flows(0).flows.iter().map(|f| f.src_port).collect();
+        assert_eq!(ports, [3, 2, 4]);
+
+        let mut table = FlowTable::new(FlowSamplingConfig {
+            idle_timeout_s: 1,
+            ..config(1, 2)
+        })
+        .unwrap();
+        table.record(FlowDirection::Tx, &ipv4_frame(IP_PROTO_UDP, 0, 0), 1000, 0);
+        table.record(
+            FlowDirection::Tx,
+            &ipv4_frame(IP_PROTO_UDP, 1, 0),
+            10,
+            500_000,
+        );
+        assert_eq!(table.flows(999_999).flows.len(), 2);
+        assert_eq!(table.flows(1_000_000).flows.len(), 1);
+        // Idle flows are dropped first, whatever their activity.
+        table.record(
+            FlowDirection::Tx,
+            &ipv4_frame(IP_PROTO_UDP, 2, 0),
+            5,
+            1_000_000,
+        );
+        let ports: Vec<_> = table
+            .flows(1_000_000)
+            .flows
+            .iter()
+            .map(|f| f.src_port)
+            .collect();
+        assert_eq!(ports, [1, 2]);
+    }
+}
@@ -199,6 +199,8 @@ pub struct NetDeviceMetrics {
     pub tx_spoofed_mac_count: SharedIncMetric,
     /// Number of remaining requests in the TX queue.
     pub tx_remaining_reqs_count: SharedIncMetric,
+    /// Number of TX passes whose notification of the guest was deferred under sustained load.
+    pub tx_deferred_notifications: SharedIncMetric,
 }
 
 impl NetDeviceMetrics {
@@ -260,6 +262,8 @@ impl NetDeviceMetrics {
             .add(other.tx_spoofed_mac_count.fetch_diff());
         self.tx_remaining_reqs_count
             .add(other.tx_remaining_reqs_count.fetch_diff());
+        self.tx_deferred_notifications
+            .add(other.tx_deferred_notifications.fetch_diff());
     }
 }
 
@@ -12,6 +12,8 @@ pub const MAX_BUFFER_SIZE: usize = 65562;
 /// The number of queues of the network device.
 pub const NET_NUM_QUEUES: usize = 2;
 pub const NET_QUEUE_SIZES: [u16; NET_NUM_QUEUES] = [NET_QUEUE_MAX_SIZE; NET_NUM_QUEUES];
+/// Maximum number of frames popped from the tx queue before they are written to the tap.
+pub const NET_TX_BATCH_SIZE: usize = 32;
 /// The index of the rx queue from Net device queues/queues_evts vector.
 pub const RX_INDEX: usize = 0;
 /// The index of the tx queue from Net device queues/queues_evts vector.
@@ -19,6 +21,7 @@ pub const TX_INDEX: usize = 1;
 
 pub mod device;
 mod event_handler;
+pub mod flows;
 pub mod metrics;
 pub mod persist;
 mod tap;
@@ -9,6 +9,7 @@ use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 
 use super::device::{Net, RxBuffers};
+use super::flows::{FlowSamplingConfig, FlowSamplingError};
 use super::{NET_NUM_QUEUES, NET_QUEUE_MAX_SIZE, RX_INDEX, TapError};
 use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDeviceType};
 use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
@@ -27,6 +28,7 @@ use crate::vstate::memory::GuestMemoryMmap;
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NetConfigSpaceState {
     guest_mac: Option<MacAddr>,
+    link_up: bool,
 }
 
 /// Information about the network device that are saved
@@ -41,6 +43,9 @@ pub struct NetState {
     pub mmds_ns: Option<MmdsNetworkStackState>,
     config_space: NetConfigSpaceState,
     pub virtio_state: VirtioDeviceState,
+    /// The configuration of the sampled flow table. The sampled flows are not saved.
+    #[serde(default)]
+    flow_sampling: Option<FlowSamplingConfig>,
 }
 
 /// Auxiliary structure for creating a device when resuming from a snapshot.
@@ -65,6 +70,8 @@ pub enum NetPersistError {
     NoMmdsDataStore,
     /// Setting tap interface offload flags failed: {0}
     TapSetOffload(TapError),
+    /// Failed to restore the flow table: {0}
+    FlowSampling(#[from] FlowSamplingError),
 }
 
 impl Persist<'_> for Net {
@@ -81,8 +88,10 @@ impl Persist<'_> for Net {
             mmds_ns: self.mmds_ns.as_ref().map(|mmds| mmds.save()),
             config_space: NetConfigSpaceState {
                 guest_mac: self.guest_mac,
+                link_up: self.link_up(),
             },
             virtio_state: VirtioDeviceState::from_device(self),
+            flow_sampling: self.flow_sampling(),
         }
     }
 
@@ -100,22 +109,22 @@ impl Persist<'_> for Net {
             rx_rate_limiter,
             tx_rate_limiter,
         )?;
+        // The device is not activated yet, so this cannot fail.
+        net.set_link_state(state.config_space.link_up)
+            .expect("net: cannot restore the link state");
+        net.set_flow_sampling(state.flow_sampling)?;
 
         // We trust the MMIODeviceManager::restore to pass us an MMDS data store reference if
         // there is at least one net device having the MMDS NS present and/or the mmds version was
         // persisted in the snapshot.
         if let Some(mmds_ns) = &state.mmds_ns {
-            // We're safe calling unwrap() to discard the error, as MmdsNetworkStack::restore()
-            // always returns Ok.
-            net.mmds_ns = Some(
-                MmdsNetworkStack::restore(
-                    constructor_args
-                        .mmds
-                        .map_or_else(|| Err(NetPersistError::NoMmdsDataStore), Ok)?,
-                    mmds_ns,
-                )
-                .unwrap(),
-            );
+            // MmdsNetworkStack::restore() can fail at creating the rate limiter timerfd.
+            net.mmds_ns = Some(MmdsNetworkStack::restore(
+                constructor_args
+                    .mmds
+                    .map_or_else(|| Err(NetPersistError::NoMmdsDataStore), Ok)?,
+                mmds_ns,
+            )?);
         }
 
         net.queues = state.virtio_state.build_queues_checked(
@@ -148,6 +157,8 @@ mod tests {
         let tap_if_name;
         let has_mmds_ns;
         let allow_mmds_requests;
+        let link_up;
+        let flow_sampling;
         let virtio_state;
 
         // Create and save the net device.
@@ -161,6 +172,8 @@ mod tests {
             tap_if_name = net.iface_name();
             has_mmds_ns = net.mmds_ns.is_some();
             allow_mmds_requests = has_mmds_ns && mmds_ds.is_some();
+            link_up = net.link_up();
+            flow_sampling = net.flow_sampling();
             virtio_state = VirtioDeviceState::from_device(&net);
         }
 
@@ -189,6 +202,8 @@ mod tests {
                     assert_eq!(&restored_net.id, &id);
                     assert_eq!(&restored_net.iface_name(), &tap_if_name);
                     assert_eq!(restored_net.mmds_ns.is_some(), allow_mmds_requests);
+                    assert_eq!(restored_net.link_up(), link_up);
+                    assert_eq!(restored_net.flow_sampling(), flow_sampling);
                     assert_eq!(restored_net.rx_rate_limiter, RateLimiter::default());
                     assert_eq!(restored_net.tx_rate_limiter, RateLimiter::default());
                 }
@@ -206,6 +221,21 @@ mod tests {
         validate_save_and_restore(default_net(), mmds.as_ref().cloned());
         validate_save_and_restore(default_net_no_mmds(), None);
 
+        // The link state is restored.
+        let mut net = default_net_no_mmds();
+        net.set_link_state(false).unwrap();
+        validate_save_and_restore(net, None);
+
+        // The flow sampling configuration is restored.
+        let mut net = default_net_no_mmds();
+        net.set_flow_sampling(Some(FlowSamplingConfig {
+            sample_rate: 100,
+            max_flows: 1024,
+            idle_timeout_s: 30,
+        }))
+        .unwrap();
+        validate_save_and_restore(net, None);
+
         // Check what happens if the MMIODeviceManager gives us the reference to the MMDS
         // data store even if this device does not have mmds ns configured.
         // The restore should be conservative and not configure the mmds ns.
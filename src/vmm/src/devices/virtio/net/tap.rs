@@ -12,7 +12,7 @@ use std::os::raw::*;
 use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 
 use vmm_sys_util::ioctl::{ioctl_with_mut_ref, ioctl_with_ref, ioctl_with_val};
-use vmm_sys_util::ioctl_iow_nr;
+use vmm_sys_util::{ioctl_ior_nr, ioctl_iow_nr};
 
 use crate::devices::virtio::iovec::IoVecBuffer;
 use crate::devices::virtio::net::generated;
@@ -35,10 +35,17 @@ pub enum TapError {
     SetOffloadFlags(IoError),
     /// Error while setting size of the vnet header: {0}
     SetSizeOfVnetHdr(IoError),
+    /// The file descriptor is not an attached TUN/TAP device: {0}
+    InvalidTapFd(IoError),
+    /// The TUN/TAP device must be a tap set up with IFF_NO_PI and IFF_VNET_HDR, got flags {0:#x}
+    InvalidTapFlags(u32),
+    /// Error while making the tap file descriptor non blocking: {0}
+    SetNonBlocking(IoError),
 }
 
 const TUNTAP: ::std::os::raw::c_uint = 84;
 ioctl_iow_nr!(TUNSETIFF, TUNTAP, 202, ::std::os::raw::c_int);
+ioctl_ior_nr!(TUNGETIFF, TUNTAP, 210, ::std::os::raw::c_uint);
 ioctl_iow_nr!(TUNSETOFFLOAD, TUNTAP, 208, ::std::os::raw::c_uint);
 ioctl_iow_nr!(TUNSETVNETHDRSZ, TUNTAP, 216, ::std::os::raw::c_int);
 
@@ -150,6 +157,48 @@ impl Tap {
         })
     }
 
+    /// Create a TUN/TAP device from the file descriptor of an already set up tap, e.g. passed
+    /// by the process that owns the host interface.
+    ///
+    /// The tap must have been set up with the flags `open_named()` uses, since changing them
+    /// requires `CAP_NET_ADMIN`.
+    /// # Arguments
+    ///
+    /// * `tap_file` - the file of the tap.
+    pub fn from_file(tap_file: File) -> Result<Tap, TapError> {
+        let ifreq = IfReqBuilder::new()
+            .execute(&tap_file, TUNGETIFF())
+            .map_err(TapError::InvalidTapFd)?;
+
+        // SAFETY: TUNGETIFF fills in the flags of the tap.
+        let flags = u32::from(unsafe { ifreq.ifr_ifru.ifru_flags }.cast_unsigned());
+        let required_flags = generated::IFF_TAP | generated::IFF_NO_PI | generated::IFF_VNET_HDR;
+        if flags & required_flags != required_flags {
+            return Err(TapError::InvalidTapFlags(flags));
+        }
+
+        // SAFETY: fcntl is safe. Called with a valid fd, and we check the return.
+        let status_flags = unsafe { libc::fcntl(tap_file.as_raw_fd(), libc::F_GETFL) };
+        // SAFETY: fcntl is safe. Called with a valid fd, and we check the return.
+        if status_flags < 0
+            || unsafe {
+                libc::fcntl(
+                    tap_file.as_raw_fd(),
+                    libc::F_SETFL,
+                    status_flags | libc::O_NONBLOCK,
+                )
+            } < 0
+        {
+            return Err(TapError::SetNonBlocking(IoError::last_os_error()));
+        }
+
+        Ok(Tap {
+            tap_file,
+            // SAFETY: Safe since only the name is accessed, and it's cloned out.
+            if_name: unsafe { ifreq.ifr_ifrn.ifrn_name },
+        })
+    }
+
     /// Retrieve the interface's name as a str.
     pub fn if_name_as_str(&self) -> &str {
         let len = self
@@ -282,6 +331,41 @@ pub mod tests {
         tap.set_offload(0).unwrap();
     }
 
+    #[test]
+    fn test_tap_from_file() {
+        let tap = Tap::open_named("tapfromfile").unwrap();
+        let passed = Tap::from_file(tap.tap_file.try_clone().unwrap()).unwrap();
+        assert_eq!(passed.if_name_as_str(), "tapfromfile");
+        let status_flags = unsafe { libc::fcntl(passed.as_raw_fd(), libc::F_GETFL) };
+        assert_ne!(status_flags & libc::O_NONBLOCK, 0);
+        passed.set_vnet_hdr_size(12).unwrap();
+
+        // A file that is not a tap.
+        let file = vmm_sys_util::tempfile::TempFile::new().unwrap().into_file();
+        assert!(matches!(
+            Tap::from_file(file),
+            Err(TapError::InvalidTapFd(_))
+        ));
+
+        // A tap without the vnet header.
+        let fd = unsafe {
+            libc::open(
+                c"/dev/net/tun".as_ptr(),
+                libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC,
+            )
+        };
+        let tun = unsafe { File::from_raw_fd(fd) };
+        IfReqBuilder::new()
+            .if_name(&build_terminated_if_name("tapnovnethdr").unwrap())
+            .flags(i16::try_from(generated::IFF_TAP | generated::IFF_NO_PI).unwrap())
+            .execute(&tun, TUNSETIFF())
+            .unwrap();
+        assert!(matches!(
+            Tap::from_file(tun),
+            Err(TapError::InvalidTapFlags(_))
+        ));
+    }
+
     #[test]
     fn test_raw_fd() {
         let tap = Tap::open_named("").unwrap();
@@ -50,6 +50,7 @@ pub fn default_net() -> Net {
     net.configure_mmds_network_stack(
         MmdsNetworkStack::default_ipv4_addr(),
         Arc::new(Mutex::new(Mmds::default())),
+        RateLimiter::default(),
     );
     enable(&net.tap);
 
@@ -328,7 +329,7 @@ pub mod test {
     }
 
     impl<'a> TestHelper<'a> {
-        const QUEUE_SIZE: u16 = 16;
+        pub const QUEUE_SIZE: u16 = 16;
 
         pub fn get_default(mem: &'a GuestMemoryMmap) -> TestHelper<'a> {
             let mut event_manager = EventManager::new().unwrap();
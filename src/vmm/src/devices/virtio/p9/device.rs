@@ -0,0 +1,387 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use vm_memory::{GuestAddress, GuestMemoryError};
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::devices::virtio::ActivateError;
+use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
+use crate::devices::virtio::p9::metrics::{P9Metrics, P9MetricsPerDevice};
+use crate::devices::virtio::p9::server::{MAX_MSIZE, P9Server};
+use crate::devices::virtio::p9::{P9_QUEUE_SIZE, VIRTIO_9P_F_MOUNT_TAG};
+use crate::devices::virtio::queue::{DescriptorChain, InvalidAvailIdx, Queue, QueueError};
+use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
+use crate::impl_device_type;
+use crate::logger::{IncMetric, error, info};
+use crate::utils::u64_to_usize;
+use crate::vmm_config::p9::P9Config;
+use crate::vstate::memory::{Bytes, GuestMemoryMmap};
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum P9Error {
+    /// Error opening the shared directory: {0}
+    SharedDir(std::io::Error),
+    /// Invalid mount tag {0}: it must be between 1 and 36 bytes long
+    InvalidMountTag(String),
+    /// Error with EventFd: {0}
+    EventFd(std::io::Error),
+    /// Unexpected read-only descriptor after a write-only one
+    ReadOnlyDescriptor,
+    /// Request larger than the maximum message size
+    RequestTooLarge,
+    /// Response larger than the buffers provided by the driver: {0} bytes
+    ResponseTooLarge(usize),
+    /// Guest memory error: {0}
+    GuestMemory(#[from] GuestMemoryError),
+    /// Error handling the VirtIO queue: {0}
+    Queue(#[from] QueueError),
+    /// Error during obtaining the descriptor from the queue: {0}
+    QueuePop(#[from] InvalidAvailIdx),
+}
+
+#[derive(Debug)]
+pub struct P9 {
+    // VirtIO fields
+    pub avail_features: u64,
+    pub acked_features: u64,
+    pub activate_event: EventFd,
+
+    // Transport fields
+    pub device_state: DeviceState,
+    pub queues: Vec<Queue>,
+    pub queue_events: Vec<EventFd>,
+
+    // 9P specific fields
+    // Length of the mount tag as a little endian u16, followed by the tag.
+    pub config_space: Vec<u8>,
+    pub server: P9Server,
+    pub metrics: Arc<P9Metrics>,
+
+    pub config: P9Config,
+}
+
+impl P9 {
+    /// Maximum length of the mount tag.
+    pub const MAX_MOUNT_TAG_LEN: usize = 36;
+
+    /// Create a new virtio-9p device sharing the directory at `path_on_host`.
+    pub fn new(config: P9Config) -> Result<Self, P9Error> {
+        Self::new_with_queues(config, vec![Queue::new(P9_QUEUE_SIZE)])
+    }
+
+    /// Create a new virtio-9p device sharing the directory at `path_on_host` using a pre-created
+    /// set of queues.
+    pub fn new_with_queues(config: P9Config, queues: Vec<Queue>) -> Result<Self, P9Error> {
+        let tag = config.mount_tag.as_bytes();
+        if tag.is_empty() || tag.len() > Self::MAX_MOUNT_TAG_LEN {
+            return Err(P9Error::InvalidMountTag(config.mount_tag.clone()));
+        }
+        let mut config_space = u16::try_from(tag.len()).unwrap().to_le_bytes().to_vec();
+        config_space.extend_from_slice(tag);
+
+        let metrics = P9MetricsPerDevice::alloc(config.id.clone());
+        let server = P9Server::new(&config.path_on_host, config.read_only, metrics.clone())
+            .map_err(P9Error::SharedDir)?;
+
+        Ok(Self {
+            avail_features: (1u64 << VIRTIO_F_VERSION_1) | (1u64 << VIRTIO_9P_F_MOUNT_TAG),
+            acked_features: 0u64,
+            activate_event: EventFd::new(libc::EFD_NONBLOCK).map_err(P9Error::EventFd)?,
+            device_state: DeviceState::Inactive,
+            queues,
+            queue_events: vec![EventFd::new(libc::EFD_NONBLOCK).map_err(P9Error::EventFd)?],
+            config_space,
+            server,
+            metrics,
+            config,
+        })
+    }
+
+    pub fn handle_queue(&mut self) -> Result<(), P9Error> {
+        while let Some(head) = self.queues[0].pop()? {
+            let add_result = match self.process_chain(head) {
+                Ok(len) => self.queues[0].add_used(head.index, len),
+                Err(err) => {
+                    error!("p9: {err}");
+                    self.metrics.event_fails.inc();
+                    self.queues[0].add_used(head.index, 0)
+                }
+            };
+            if let Err(err) = add_result {
+                error!("p9: {err}");
+                self.metrics.event_fails.inc();
+                break;
+            }
+        }
+        self.queues[0].advance_used_ring_idx();
+
+        if self.queues[0].prepare_kick() {
+            // This is safe since we checked in the event handler that the device is activated.
+            let active_state = self.device_state.active_state().unwrap();
+            active_state
+                .interrupt
+                .trigger(VirtioInterruptType::Queue(0))
+                .unwrap_or_else(|err| {
+                    error!("p9: {err}");
+                    self.metrics.event_fails.inc();
+                });
+        }
+        Ok(())
+    }
+
+    /// Handles the request held by the device-readable descriptors of the chain and writes the
+    /// response in its device-writable descriptors. Returns the length of the response.
+    fn process_chain(&mut self, head: DescriptorChain) -> Result<u32, P9Error> {
+        // This is safe since we checked in the event handler that the device is activated.
+        let mem = &self.device_state.active_state().unwrap().mem;
+
+        let mut request = Vec::new();
+        let mut response_buffers: Vec<(GuestAddress, usize)> = Vec::new();
+        let mut response_capacity = 0;
+        let mut next = Some(head);
+        while let Some(desc) = next {
+            let len = desc.len as usize;
+            if desc.is_write_only() {
+                response_buffers.push((desc.addr, len));
+                response_capacity += len;
+            } else {
+                if !response_buffers.is_empty() {
+                    return Err(P9Error::ReadOnlyDescriptor);
+                }
+                let start = request.len();
+                if start + len > MAX_MSIZE as usize {
+                    return Err(P9Error::RequestTooLarge);
+                }
+                request.resize(start + len, 0);
+                mem.read_slice(&mut request[start..], desc.addr)?;
+            }
+            next = desc.next_descriptor();
+        }
+
+        let response = self.server.handle(&request, response_capacity);
+        if response.len() > response_capacity {
+            return Err(P9Error::ResponseTooLarge(response.len()));
+        }
+        let mut written = 0;
+        for (addr, len) in response_buffers {
+            if written == response.len() {
+                break;
+            }
+            let len = len.min(response.len() - written);
+            mem.write_slice(&response[written..written + len], addr)?;
+            written += len;
+        }
+        Ok(u32::try_from(response.len()).unwrap())
+    }
+
+    pub fn process_queue(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events[0].read() {
+            error!("p9: Failed to get queue event: {err:?}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.handle_queue().unwrap_or_else(|err| {
+            error!("p9: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+}
+
+impl VirtioDevice for P9 {
+    impl_device_type!(VirtioDeviceType::P9);
+
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
+        self.device_state
+            .active_state()
+            .expect("Device not activated")
+            .interrupt
+            .deref()
+    }
+
+    fn read_config(&self, offset: u64, data: &mut [u8]) {
+        if let Some(config_space_bytes) = self.config_space.get(u64_to_usize(offset)..) {
+            let len = config_space_bytes.len().min(data.len());
+            data[..len].copy_from_slice(&config_space_bytes[..len]);
+        } else {
+            error!("Failed to read config space");
+            self.metrics.cfg_fails.inc();
+        }
+    }
+
+    fn write_config(&mut self, _offset: u64, _data: &[u8]) {}
+
+    fn activate(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), ActivateError> {
+        for q in self.queues.iter_mut() {
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        if self.activate_event.write(1).is_err() {
+            self.metrics.activate_fails.inc();
+            return Err(ActivateError::EventFd);
+        }
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+        Ok(())
+    }
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn kick(&mut self) {
+        if self.is_activated() {
+            info!("kick p9 {}.", self.config.id);
+            self.handle_queue().unwrap_or_else(|err| {
+                error!("p9: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
+    use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
+
+    fn config(path_on_host: &str, mount_tag: &str) -> P9Config {
+        P9Config {
+            id: "share".into(),
+            path_on_host: path_on_host.into(),
+            mount_tag: mount_tag.into(),
+            read_only: true,
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.as_path().to_str().unwrap();
+
+        assert!(matches!(
+            P9::new(config("not_a_path", "share")).unwrap_err(),
+            P9Error::SharedDir(_),
+        ));
+        assert!(matches!(
+            P9::new(config(path, "")).unwrap_err(),
+            P9Error::InvalidMountTag(_),
+        ));
+        assert!(matches!(
+            P9::new(config(path, &"a".repeat(P9::MAX_MOUNT_TAG_LEN + 1))).unwrap_err(),
+            P9Error::InvalidMountTag(_),
+        ));
+
+        let p9 = P9::new(config(path, "share")).unwrap();
+        assert_eq!(p9.device_type(), VirtioDeviceType::P9);
+        assert!(p9.avail_features() & (1 << VIRTIO_9P_F_MOUNT_TAG) != 0);
+
+        let mut data = [0u8; 16];
+        p9.read_config(0, &mut data);
+        assert_eq!(&data[..7], &[5, 0, b's', b'h', b'a', b'r', b'e']);
+        let mut data = [0u8; 2];
+        p9.read_config(3, &mut data);
+        assert_eq!(data, [b'h', b'a']);
+    }
+
+    #[test]
+    fn test_process_chain() {
+        let dir = TempDir::new().unwrap();
+        let mut p9 = P9::new(config(dir.as_path().to_str().unwrap(), "share")).unwrap();
+
+        let mem = default_mem();
+        let interrupt = default_interrupt();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        p9.queues[0] = vq.create_queue();
+        p9.activate(mem.clone(), interrupt).unwrap();
+
+        // Tversion with a 4096 bytes message size, split over two readable descriptors, and
+        // a response buffer split over two writable descriptors.
+        let mut request = vec![21, 0, 0, 0, 100, 0xff, 0xff, 0, 0x10, 0, 0, 8, 0];
+        request.extend_from_slice(b"9P2000.L");
+        mem.write_slice(&request[..7], GuestAddress(0x1000))
+            .unwrap();
+        mem.write_slice(&request[7..], GuestAddress(0x2000))
+            .unwrap();
+        vq.avail.ring[0].set(0);
+        vq.dtable[0].set(0x1000, 7, VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(0x2000, 14, VIRTQ_DESC_F_NEXT, 2);
+        vq.dtable[2].set(0x3000, 7, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 3);
+        vq.dtable[3].set(0x4000, 100, VIRTQ_DESC_F_WRITE, 0);
+        vq.avail.idx.set(1);
+        let head = p9.queues[0].pop().unwrap().unwrap();
+        assert_eq!(p9.process_chain(head).unwrap(), 21);
+
+        let mut response = [0u8; 21];
+        mem.read_slice(&mut response[..7], GuestAddress(0x3000))
+            .unwrap();
+        mem.read_slice(&mut response[7..], GuestAddress(0x4000))
+            .unwrap();
+        assert_eq!(&response[..7], &[21, 0, 0, 0, 101, 0xff, 0xff]);
+        assert_eq!(&response[7..13], &[0, 0x10, 0, 0, 8, 0]);
+        assert_eq!(&response[13..], b"9P2000.L");
+
+        // Readable descriptor after a writable one.
+        vq.avail.ring[1].set(0);
+        vq.dtable[0].set(0x3000, 7, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(0x1000, 7, 0, 0);
+        vq.avail.idx.set(2);
+        let head = p9.queues[0].pop().unwrap().unwrap();
+        assert!(matches!(
+            p9.process_chain(head).unwrap_err(),
+            P9Error::ReadOnlyDescriptor,
+        ));
+
+        // Response not fitting in the writable descriptors.
+        vq.avail.ring[2].set(0);
+        vq.dtable[0].set(0x1000, 7, VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(0x2000, 14, VIRTQ_DESC_F_NEXT, 2);
+        vq.dtable[2].set(0x3000, 7, VIRTQ_DESC_F_WRITE, 0);
+        vq.avail.idx.set(3);
+        let head = p9.queues[0].pop().unwrap().unwrap();
+        assert!(matches!(
+            p9.process_chain(head).unwrap_err(),
+            P9Error::ResponseTooLarge(21),
+        ));
+    }
+}
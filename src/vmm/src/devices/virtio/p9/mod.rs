@@ -0,0 +1,17 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Implements a virtio-9p device sharing a host directory with the guest over 9P2000.L.
+
+pub mod device;
+pub mod event_handler;
+pub mod metrics;
+pub mod persist;
+mod protocol;
+pub mod server;
+
+pub const P9_NUM_QUEUES: usize = 1;
+pub const P9_QUEUE_SIZE: u16 = 128;
+
+/// Feature bit advertising the mount tag in the configuration space.
+pub const VIRTIO_9P_F_MOUNT_TAG: u32 = 0;
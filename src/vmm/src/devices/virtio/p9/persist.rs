@@ -0,0 +1,117 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use super::device::{P9, P9Error};
+use super::server::P9ServerState;
+use crate::devices::virtio::device::VirtioDeviceType;
+use crate::devices::virtio::p9::{P9_NUM_QUEUES, P9_QUEUE_SIZE};
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::snapshot::Persist;
+use crate::vmm_config::p9::P9Config;
+use crate::vstate::memory::GuestMemoryMmap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P9State {
+    pub virtio_state: VirtioDeviceState,
+    pub config: P9Config,
+    pub server: P9ServerState,
+}
+
+#[derive(Debug)]
+pub struct P9ConstructorArgs<'a> {
+    pub mem: &'a GuestMemoryMmap,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum P9PersistError {
+    /// Error resetting VirtIO state: {0}
+    VirtioState(#[from] VirtioStateError),
+    /// Error creating 9P device: {0}
+    P9(#[from] P9Error),
+}
+
+impl<'a> Persist<'a> for P9 {
+    type State = P9State;
+    type ConstructorArgs = P9ConstructorArgs<'a>;
+    type Error = P9PersistError;
+
+    fn save(&self) -> Self::State {
+        P9State {
+            virtio_state: VirtioDeviceState::from_device(self),
+            config: self.config.clone(),
+            server: self.server.save(),
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        let queues = state.virtio_state.build_queues_checked(
+            constructor_args.mem,
+            VirtioDeviceType::P9,
+            P9_NUM_QUEUES,
+            P9_QUEUE_SIZE,
+        )?;
+
+        let mut p9 = P9::new_with_queues(state.config.clone(), queues)?;
+        p9.avail_features = state.virtio_state.avail_features;
+        p9.acked_features = state.virtio_state.acked_features;
+        // Fids whose file no longer exists on the host are dropped.
+        p9.server.restore(&state.server);
+
+        Ok(p9)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::device::VirtioDevice;
+    use crate::devices::virtio::test_utils::default_mem;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn test_persistence() {
+        let dir = TempDir::new().unwrap();
+        let config = P9Config {
+            id: "1".into(),
+            path_on_host: dir.as_path().to_str().unwrap().to_string(),
+            mount_tag: "share".into(),
+            read_only: false,
+        };
+        let p9 = P9::new(config).unwrap();
+        let guest_mem = default_mem();
+
+        // Save the 9P device.
+        let mut mem = vec![0; 4096];
+
+        Snapshot::new(p9.save())
+            .save(&mut mem.as_mut_slice())
+            .unwrap();
+
+        // Restore the 9P device.
+        let restored_p9 = P9::restore(
+            P9ConstructorArgs { mem: &guest_mem },
+            &Snapshot::load_without_crc_check(mem.as_slice())
+                .unwrap()
+                .data,
+        )
+        .unwrap();
+
+        // Test that virtio specific fields are the same.
+        assert_eq!(restored_p9.device_type(), VirtioDeviceType::P9);
+        assert_eq!(restored_p9.avail_features(), p9.avail_features());
+        assert_eq!(restored_p9.acked_features(), p9.acked_features());
+        assert_eq!(restored_p9.queues(), p9.queues());
+        assert!(!p9.is_activated());
+        assert!(!restored_p9.is_activated());
+        assert_eq!(restored_p9.config, p9.config);
+        assert_eq!(restored_p9.config_space, p9.config_space);
+        assert_eq!(restored_p9.server.save(), p9.server.save());
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wire format of the 9P2000.L messages handled by the virtio-9p device.
+//!
+//! Every message starts with a header made of its size (`u32`), its type (`u8`) and its tag
+//! (`u16`). All integers are little endian and strings are prefixed by their length as a `u16`.
+
+use std::io;
+
+/// Size of the header of every message.
+pub const HEADER_SIZE: usize = 7;
+/// Size of the fixed part of `Rread` and `Rreaddir` messages (header and count).
+pub const RDATA_HEADER_SIZE: usize = HEADER_SIZE + 4;
+/// Size of an encoded qid.
+pub const QID_SIZE: usize = 13;
+/// Maximum number of path elements of a single `Twalk` message.
+pub const MAX_WALK_ELEMENTS: u16 = 16;
+
+pub const RLERROR: u8 = 7;
+pub const TSTATFS: u8 = 8;
+pub const TLOPEN: u8 = 12;
+pub const TLCREATE: u8 = 14;
+pub const TREADLINK: u8 = 22;
+pub const TGETATTR: u8 = 24;
+pub const TSETATTR: u8 = 26;
+pub const TREADDIR: u8 = 40;
+pub const TFSYNC: u8 = 50;
+pub const TLOCK: u8 = 52;
+pub const TGETLOCK: u8 = 54;
+pub const TMKDIR: u8 = 72;
+pub const TRENAMEAT: u8 = 74;
+pub const TUNLINKAT: u8 = 76;
+pub const TVERSION: u8 = 100;
+pub const TATTACH: u8 = 104;
+pub const TFLUSH: u8 = 108;
+pub const TWALK: u8 = 110;
+pub const TREAD: u8 = 116;
+pub const TWRITE: u8 = 118;
+pub const TCLUNK: u8 = 120;
+pub const TREMOVE: u8 = 122;
+
+/// Qid type of directories.
+pub const QTDIR: u8 = 0x80;
+/// Qid type of symbolic links.
+pub const QTSYMLINK: u8 = 0x02;
+/// Qid type of regular files.
+pub const QTFILE: u8 = 0x00;
+
+/// Unique identification of a file on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Qid {
+    pub qid_type: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+impl Qid {
+    /// Builds the qid of the file described by `st`.
+    pub fn from_stat(st: &libc::stat) -> Self {
+        let qid_type = match st.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => QTDIR,
+            libc::S_IFLNK => QTSYMLINK,
+            _ => QTFILE,
+        };
+        Qid {
+            qid_type,
+            version: 0,
+            path: st.st_ino,
+        }
+    }
+}
+
+fn protocol_error() -> io::Error {
+    io::Error::from_raw_os_error(libc::EPROTO)
+}
+
+/// Decodes the fields of a message.
+#[derive(Debug)]
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> WireReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        WireReader { buf }
+    }
+
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let bytes = self.read_bytes(N)?;
+        // The length of `bytes` is N.
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(u8::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    /// Reads the next `len` bytes of the message.
+    pub fn read_bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(protocol_error());
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    /// Reads a string prefixed by its length.
+    pub fn read_string(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.read_u16()?;
+        self.read_bytes(usize::from(len))
+    }
+}
+
+/// Encodes a message.
+#[derive(Debug)]
+pub struct WireWriter {
+    buf: Vec<u8>,
+}
+
+impl WireWriter {
+    /// Starts a message of type `msg_type` answering the request tagged with `tag`.
+    pub fn new(msg_type: u8, tag: u16) -> Self {
+        let mut buf = Vec::with_capacity(HEADER_SIZE);
+        buf.extend_from_slice(&[0u8; 4]);
+        buf.push(msg_type);
+        buf.extend_from_slice(&tag.to_le_bytes());
+        WireWriter { buf }
+    }
+
+    pub fn write_u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn write_u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Writes a string prefixed by its length. `s` must not be longer than `u16::MAX`.
+    pub fn write_string(&mut self, s: &[u8]) {
+        self.write_u16(u16::try_from(s.len()).unwrap());
+        self.write_bytes(s);
+    }
+
+    pub fn write_qid(&mut self, qid: &Qid) {
+        self.write_u8(qid.qid_type);
+        self.write_u32(qid.version);
+        self.write_u64(qid.path);
+    }
+
+    /// Completes the header of the message and returns it.
+    pub fn finish(mut self) -> Vec<u8> {
+        let size = u32::try_from(self.buf.len()).unwrap();
+        self.buf[..4].copy_from_slice(&size.to_le_bytes());
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_roundtrip() {
+        let qid = Qid {
+            qid_type: QTDIR,
+            version: 3,
+            path: 0x1234,
+        };
+        let mut writer = WireWriter::new(TVERSION, 0xabcd);
+        writer.write_u8(1);
+        writer.write_u16(2);
+        writer.write_u32(3);
+        writer.write_u64(4);
+        writer.write_string(b"9P2000.L");
+        writer.write_qid(&qid);
+        let msg = writer.finish();
+        assert_eq!(msg.len(), HEADER_SIZE + 1 + 2 + 4 + 8 + 10 + QID_SIZE);
+
+        let mut reader = WireReader::new(&msg);
+        assert_eq!(reader.read_u32().unwrap() as usize, msg.len());
+        assert_eq!(reader.read_u8().unwrap(), TVERSION);
+        assert_eq!(reader.read_u16().unwrap(), 0xabcd);
+        assert_eq!(reader.read_u8().unwrap(), 1);
+        assert_eq!(reader.read_u16().unwrap(), 2);
+        assert_eq!(reader.read_u32().unwrap(), 3);
+        assert_eq!(reader.read_u64().unwrap(), 4);
+        assert_eq!(reader.read_string().unwrap(), b"9P2000.L");
+        assert_eq!(reader.read_u8().unwrap(), qid.qid_type);
+        assert_eq!(reader.read_u32().unwrap(), qid.version);
+        assert_eq!(reader.read_u64().unwrap(), qid.path);
+
+        // The message is fully consumed.
+        assert_eq!(
+            reader.read_u8().unwrap_err().raw_os_error(),
+            Some(libc::EPROTO)
+        );
+    }
+
+    #[test]
+    fn test_truncated_string() {
+        let mut reader = WireReader::new(&[5, 0, b'a', b'b']);
+        assert_eq!(
+            reader.read_string().unwrap_err().raw_os_error(),
+            Some(libc::EPROTO)
+        );
+    }
+}
@@ -0,0 +1,1275 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! 9P2000.L server exporting a host directory to the guest.
+//!
+//! Each fid refers to a file by its path relative to the shared directory. Paths are resolved one
+//! component at a time with `O_NOFOLLOW`, starting from the shared directory, so that symbolic
+//! links are never followed on the host and the guest cannot reach files outside of the shared
+//! directory. The guest resolves symbolic links itself with `Treadlink`.
+
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::metrics::P9Metrics;
+use super::protocol::*;
+use crate::logger::{IncMetric, warn};
+use crate::utils::usize_to_u64;
+
+/// Largest message size negotiated with the guest.
+pub const MAX_MSIZE: u32 = 128 * 1024;
+
+const VERSION_9P2000_L: &[u8] = b"9P2000.L";
+const VERSION_UNKNOWN: &[u8] = b"unknown";
+/// Filesystem type reported by `Rstatfs`.
+const V9FS_MAGIC: u32 = 0x0102_1997;
+/// Size of the buffer holding the target of a symbolic link.
+const MAX_LINK_LEN: usize = 4096;
+/// Offset of the name in a `linux_dirent64` structure.
+const DIRENT_NAME_OFFSET: usize = 19;
+
+// Flags of `Tlopen` and `Tlcreate`. 9P2000.L uses the values of x86 Linux on all architectures.
+const P9_DOTL_ACCMODE: u32 = 0o3;
+const P9_DOTL_WRONLY: u32 = 0o1;
+const P9_DOTL_RDWR: u32 = 0o2;
+const P9_DOTL_EXCL: u32 = 0o200;
+const P9_DOTL_TRUNC: u32 = 0o1000;
+const P9_DOTL_APPEND: u32 = 0o2000;
+const P9_DOTL_DSYNC: u32 = 0o10000;
+const P9_DOTL_DIRECTORY: u32 = 0o200000;
+const P9_DOTL_SYNC: u32 = 0o4000000;
+
+/// Fields of `Rgetattr` filled by the server.
+const P9_GETATTR_BASIC: u64 = 0x7ff;
+
+// Fields of `Tsetattr`.
+const P9_SETATTR_MODE: u32 = 0x1;
+const P9_SETATTR_UID: u32 = 0x2;
+const P9_SETATTR_GID: u32 = 0x4;
+const P9_SETATTR_SIZE: u32 = 0x8;
+const P9_SETATTR_ATIME: u32 = 0x10;
+const P9_SETATTR_MTIME: u32 = 0x20;
+const P9_SETATTR_CTIME: u32 = 0x40;
+const P9_SETATTR_ATIME_SET: u32 = 0x80;
+const P9_SETATTR_MTIME_SET: u32 = 0x100;
+
+const P9_LOCK_SUCCESS: u8 = 0;
+const P9_LOCK_TYPE_UNLCK: u8 = 2;
+
+#[derive(Debug)]
+struct Fid {
+    /// Components of the path of the file, relative to the shared directory.
+    path: Vec<Vec<u8>>,
+    /// The file opened by `Tlopen` or `Tlcreate` and the flags it was opened with.
+    file: Option<(File, u32)>,
+}
+
+/// State of a fid saved in snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct P9FidState {
+    pub fid: u32,
+    pub path: Vec<Vec<u8>>,
+    pub open_flags: Option<u32>,
+}
+
+/// State of the 9P server saved in snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct P9ServerState {
+    pub msize: u32,
+    pub fids: Vec<P9FidState>,
+}
+
+fn errno(code: i32) -> io::Error {
+    io::Error::from_raw_os_error(code)
+}
+
+fn check_ret(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates a file name received from the guest.
+fn component(name: &[u8]) -> io::Result<CString> {
+    if name.is_empty() || name == b"." || name == b".." || name.contains(&b'/') {
+        return Err(errno(libc::EINVAL));
+    }
+    CString::new(name).map_err(|_| errno(libc::EINVAL))
+}
+
+/// Opens `name` in `dir` without following symbolic links.
+fn openat(dir: &File, name: &CStr, flags: i32, mode: u32) -> io::Result<File> {
+    // SAFETY: `dir` is a valid file descriptor and `name` a NUL terminated string. The return
+    // value is checked.
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            flags | libc::O_CLOEXEC | libc::O_NOFOLLOW,
+            mode,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a valid file descriptor which is not owned by anything else.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn fstatat(dir: &File, name: &CStr) -> io::Result<libc::stat> {
+    let mut st = MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `dir` is a valid file descriptor, `name` a NUL terminated string and the pointer
+    // passed to fstatat is valid for writing a libc::stat structure.
+    check_ret(unsafe {
+        libc::fstatat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            st.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    })?;
+    // SAFETY: fstatat succeeded, so the structure is initialized.
+    Ok(unsafe { st.assume_init() })
+}
+
+fn fstat(file: &File) -> io::Result<libc::stat> {
+    let mut st = MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `file` is a valid file descriptor and the pointer passed to fstat is valid for
+    // writing a libc::stat structure.
+    check_ret(unsafe { libc::fstat(file.as_raw_fd(), st.as_mut_ptr()) })?;
+    // SAFETY: fstat succeeded, so the structure is initialized.
+    Ok(unsafe { st.assume_init() })
+}
+
+/// Changes the mode of `name` in `dir` without following symbolic links.
+///
+/// The mode of a symbolic link cannot be changed on Linux, so symbolic links are rejected with
+/// `EOPNOTSUPP`. Kernels without fchmodat2 have no way to do so atomically on a name, so the
+/// entry is opened with `O_PATH` instead and its mode changed through `/proc/self/fd`.
+fn fchmodat_nofollow(dir: &File, name: &CStr, mode: u32) -> io::Result<()> {
+    // SAFETY: `dir` is a valid file descriptor and `name` a NUL terminated string. The return
+    // value is checked.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_fchmodat2,
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            mode,
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if ret == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::ENOSYS) {
+        return Err(err);
+    }
+
+    let file = openat(dir, name, libc::O_PATH, 0)?;
+    if fstat(&file)?.st_mode & libc::S_IFMT == libc::S_IFLNK {
+        return Err(errno(libc::EOPNOTSUPP));
+    }
+    let path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd())).unwrap();
+    // SAFETY: `path` is a NUL terminated string. The return value is checked.
+    match check_ret(unsafe { libc::chmod(path.as_ptr(), mode) }) {
+        // /proc is usually not mounted in the jail.
+        Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Err(errno(libc::EOPNOTSUPP)),
+        ret => ret,
+    }
+}
+
+fn is_dir(st: &libc::stat) -> bool {
+    st.st_mode & libc::S_IFMT == libc::S_IFDIR
+}
+
+/// Converts the flags of `Tlopen` and `Tlcreate` to host flags.
+fn open_flags(flags: u32) -> i32 {
+    let mut host_flags = match flags & P9_DOTL_ACCMODE {
+        P9_DOTL_WRONLY => libc::O_WRONLY,
+        P9_DOTL_RDWR => libc::O_RDWR,
+        _ => libc::O_RDONLY,
+    };
+    for (dotl_flag, host_flag) in [
+        (P9_DOTL_EXCL, libc::O_EXCL),
+        (P9_DOTL_TRUNC, libc::O_TRUNC),
+        (P9_DOTL_APPEND, libc::O_APPEND),
+        (P9_DOTL_DSYNC, libc::O_DSYNC),
+        (P9_DOTL_DIRECTORY, libc::O_DIRECTORY),
+        (P9_DOTL_SYNC, libc::O_SYNC),
+    ] {
+        if flags & dotl_flag != 0 {
+            host_flags |= host_flag;
+        }
+    }
+    // Opening a FIFO must not block the device.
+    host_flags | libc::O_NONBLOCK
+}
+
+/// Whether opening a file with `flags` modifies it.
+fn is_write(flags: u32) -> bool {
+    flags & (P9_DOTL_ACCMODE | P9_DOTL_TRUNC | P9_DOTL_APPEND) != 0
+}
+
+/// Builds the time to set for `Tsetattr`.
+fn setattr_time(valid: u32, update: u32, set: u32, sec: u64, nsec: u64) -> libc::timespec {
+    let (tv_sec, tv_nsec) = if valid & update == 0 {
+        (0, libc::UTIME_OMIT)
+    } else if valid & set == 0 {
+        (0, libc::UTIME_NOW)
+    } else {
+        (sec.cast_signed(), nsec.cast_signed())
+    };
+    libc::timespec { tv_sec, tv_nsec }
+}
+
+// The types of some fields of `libc::stat` differ between architectures.
+#[allow(clippy::useless_conversion)]
+fn write_attr(w: &mut WireWriter, st: &libc::stat) {
+    w.write_u64(P9_GETATTR_BASIC);
+    w.write_qid(&Qid::from_stat(st));
+    w.write_u32(st.st_mode);
+    w.write_u32(st.st_uid);
+    w.write_u32(st.st_gid);
+    w.write_u64(u64::from(st.st_nlink));
+    w.write_u64(st.st_rdev);
+    w.write_u64(st.st_size.cast_unsigned());
+    w.write_u64(i64::from(st.st_blksize).cast_unsigned());
+    w.write_u64(st.st_blocks.cast_unsigned());
+    w.write_u64(st.st_atime.cast_unsigned());
+    w.write_u64(st.st_atime_nsec.cast_unsigned());
+    w.write_u64(st.st_mtime.cast_unsigned());
+    w.write_u64(st.st_mtime_nsec.cast_unsigned());
+    w.write_u64(st.st_ctime.cast_unsigned());
+    w.write_u64(st.st_ctime_nsec.cast_unsigned());
+    // The birth time, generation and data version are not reported.
+    for _ in 0..4 {
+        w.write_u64(0);
+    }
+}
+
+/// Serves the 9P2000.L requests of a guest on a host directory.
+#[derive(Debug)]
+pub struct P9Server {
+    root: File,
+    read_only: bool,
+    msize: u32,
+    fids: BTreeMap<u32, Fid>,
+    metrics: Arc<P9Metrics>,
+}
+
+impl P9Server {
+    /// Creates a server exporting the directory at `shared_dir`.
+    pub fn new(shared_dir: &str, read_only: bool, metrics: Arc<P9Metrics>) -> io::Result<Self> {
+        let root = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+            .open(shared_dir)?;
+        Ok(P9Server {
+            root,
+            read_only,
+            msize: MAX_MSIZE,
+            fids: BTreeMap::new(),
+            metrics,
+        })
+    }
+
+    /// Handles a request and returns the response, which is at most `max_response_len` long.
+    pub fn handle(&mut self, request: &[u8], max_response_len: usize) -> Vec<u8> {
+        self.metrics.request_count.inc();
+
+        let mut r = WireReader::new(request);
+        let header = r.read_u32().and_then(|_| Ok((r.read_u8()?, r.read_u16()?)));
+        let (msg_type, tag) = match header {
+            Ok(header) => header,
+            Err(err) => return self.error_response(u16::MAX, &err),
+        };
+
+        // Leave room for the header of `Rread` and `Rreaddir`.
+        let max_data_len = max_response_len
+            .min(self.msize as usize)
+            .saturating_sub(RDATA_HEADER_SIZE);
+        let result = match msg_type {
+            TVERSION => self.version(&mut r, tag),
+            TATTACH => self.attach(&mut r, tag),
+            TFLUSH => r.read_u16().map(|_| WireWriter::new(TFLUSH + 1, tag)),
+            TWALK => self.walk(&mut r, tag),
+            TGETATTR => self.getattr(&mut r, tag),
+            TSETATTR => self.setattr(&mut r, tag),
+            TLOPEN => self.lopen(&mut r, tag),
+            TLCREATE => self.lcreate(&mut r, tag),
+            TREAD => self.read(&mut r, tag, max_data_len),
+            TWRITE => self.write(&mut r, tag),
+            TREADDIR => self.readdir(&mut r, tag, max_data_len),
+            TREADLINK => self.readlink(&mut r, tag),
+            TFSYNC => self.fsync(&mut r, tag),
+            TMKDIR => self.mkdir(&mut r, tag),
+            TUNLINKAT => self.unlinkat(&mut r, tag),
+            TRENAMEAT => self.renameat(&mut r, tag),
+            TREMOVE => self.remove(&mut r, tag),
+            TCLUNK => self.clunk(&mut r, tag),
+            TSTATFS => self.statfs(&mut r, tag),
+            TLOCK => self.lock(&mut r, tag),
+            TGETLOCK => self.getlock(&mut r, tag),
+            _ => Err(errno(libc::EOPNOTSUPP)),
+        };
+        match result {
+            Ok(w) => w.finish(),
+            Err(err) => self.error_response(tag, &err),
+        }
+    }
+
+    fn error_response(&self, tag: u16, err: &io::Error) -> Vec<u8> {
+        self.metrics.request_fails.inc();
+        let mut w = WireWriter::new(RLERROR, tag);
+        w.write_u32(err.raw_os_error().unwrap_or(libc::EIO).cast_unsigned());
+        w.finish()
+    }
+
+    /// Saves the fids opened by the guest.
+    pub fn save(&self) -> P9ServerState {
+        P9ServerState {
+            msize: self.msize,
+            fids: self
+                .fids
+                .iter()
+                .map(|(fid, entry)| P9FidState {
+                    fid: *fid,
+                    path: entry.path.clone(),
+                    open_flags: entry.file.as_ref().map(|(_, flags)| *flags),
+                })
+                .collect(),
+        }
+    }
+
+    /// Restores the fids opened by the guest. The fids whose file cannot be found anymore are
+    /// dropped.
+    pub fn restore(&mut self, state: &P9ServerState) {
+        self.msize = state.msize;
+        for fid_state in &state.fids {
+            match self.restore_fid(fid_state) {
+                Ok(entry) => {
+                    self.fids.insert(fid_state.fid, entry);
+                }
+                Err(err) => warn!("p9: Failed to restore fid {}: {err}", fid_state.fid),
+            }
+        }
+    }
+
+    fn restore_fid(&self, state: &P9FidState) -> io::Result<Fid> {
+        let (dir, name) = self.locate(&state.path)?;
+        let file = match state.open_flags {
+            // The file must not be created or truncated again.
+            Some(flags) => {
+                let host_flags = open_flags(flags & !(P9_DOTL_EXCL | P9_DOTL_TRUNC));
+                Some((openat(&dir, &name, host_flags, 0)?, flags))
+            }
+            None => {
+                fstatat(&dir, &name)?;
+                None
+            }
+        };
+        Ok(Fid {
+            path: state.path.clone(),
+            file,
+        })
+    }
+
+    fn fid(&self, fid: u32) -> io::Result<&Fid> {
+        self.fids.get(&fid).ok_or_else(|| errno(libc::EBADF))
+    }
+
+    fn open_file(&self, fid: u32) -> io::Result<&File> {
+        self.fid(fid)?
+            .file
+            .as_ref()
+            .map(|(file, _)| file)
+            .ok_or_else(|| errno(libc::EBADF))
+    }
+
+    fn check_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            Err(errno(libc::EROFS))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Opens the directory holding the file at `path` and returns it with the name of the file.
+    /// The shared directory itself is returned as `.` in the shared directory.
+    fn locate(&self, path: &[Vec<u8>]) -> io::Result<(File, CString)> {
+        let mut dir = openat(&self.root, c".", libc::O_PATH, 0)?;
+        let Some((name, parents)) = path.split_last() else {
+            return Ok((dir, CString::from(c".")));
+        };
+        for parent in parents {
+            dir = openat(
+                &dir,
+                &component(parent)?,
+                libc::O_PATH | libc::O_DIRECTORY,
+                0,
+            )?;
+        }
+        Ok((dir, component(name)?))
+    }
+
+    fn stat_path(&self, path: &[Vec<u8>]) -> io::Result<libc::stat> {
+        let (dir, name) = self.locate(path)?;
+        fstatat(&dir, &name)
+    }
+
+    /// Opens the directory at `path` to look up, create or remove entries in it.
+    fn open_dir(&self, path: &[Vec<u8>]) -> io::Result<File> {
+        let (dir, name) = self.locate(path)?;
+        openat(&dir, &name, libc::O_PATH | libc::O_DIRECTORY, 0)
+    }
+
+    fn version(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let msize = r.read_u32()?;
+        let version = r.read_string()?;
+
+        // A new session starts: all the fids of the previous one are released.
+        self.fids.clear();
+        self.msize = msize.min(MAX_MSIZE);
+
+        let mut w = WireWriter::new(TVERSION + 1, tag);
+        w.write_u32(self.msize);
+        if version.starts_with(VERSION_9P2000_L) {
+            w.write_string(VERSION_9P2000_L);
+        } else {
+            w.write_string(VERSION_UNKNOWN);
+        }
+        Ok(w)
+    }
+
+    fn attach(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let _afid = r.read_u32()?;
+        let _uname = r.read_string()?;
+        let _aname = r.read_string()?;
+        let _n_uname = r.read_u32()?;
+
+        let st = self.stat_path(&[])?;
+        self.fids.insert(
+            fid,
+            Fid {
+                path: Vec::new(),
+                file: None,
+            },
+        );
+
+        let mut w = WireWriter::new(TATTACH + 1, tag);
+        w.write_qid(&Qid::from_stat(&st));
+        Ok(w)
+    }
+
+    fn walk(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let newfid = r.read_u32()?;
+        let nwname = r.read_u16()?;
+        if nwname > MAX_WALK_ELEMENTS {
+            return Err(errno(libc::EINVAL));
+        }
+        let mut path = self.fid(fid)?.path.clone();
+        if newfid != fid && self.fids.contains_key(&newfid) {
+            return Err(errno(libc::EEXIST));
+        }
+
+        let mut qids = Vec::new();
+        for _ in 0..nwname {
+            let name = r.read_string()?;
+            if name == b".." {
+                // `..` of the shared directory is the shared directory itself.
+                path.pop();
+            } else {
+                component(name)?;
+                path.push(name.to_vec());
+            }
+            match self.stat_path(&path) {
+                Ok(st) => qids.push(Qid::from_stat(&st)),
+                // Only the failure to walk the first element is an error. Otherwise, the qids of
+                // the elements walked so far are returned and `newfid` is left unused.
+                Err(err) if qids.is_empty() => return Err(err),
+                Err(_) => break,
+            }
+        }
+        if qids.len() == usize::from(nwname) {
+            self.fids.insert(newfid, Fid { path, file: None });
+        }
+
+        let mut w = WireWriter::new(TWALK + 1, tag);
+        w.write_u16(u16::try_from(qids.len()).unwrap());
+        for qid in &qids {
+            w.write_qid(qid);
+        }
+        Ok(w)
+    }
+
+    fn getattr(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let _request_mask = r.read_u64()?;
+
+        let entry = self.fid(fid)?;
+        let st = match &entry.file {
+            Some((file, _)) => fstat(file)?,
+            None => self.stat_path(&entry.path)?,
+        };
+
+        let mut w = WireWriter::new(TGETATTR + 1, tag);
+        write_attr(&mut w, &st);
+        Ok(w)
+    }
+
+    fn setattr(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let valid = r.read_u32()?;
+        let mode = r.read_u32()?;
+        let _uid = r.read_u32()?;
+        let _gid = r.read_u32()?;
+        let size = r.read_u64()?;
+        let atime_sec = r.read_u64()?;
+        let atime_nsec = r.read_u64()?;
+        let mtime_sec = r.read_u64()?;
+        let mtime_nsec = r.read_u64()?;
+
+        if valid & !P9_SETATTR_CTIME != 0 {
+            self.check_writable()?;
+        }
+        // Files are owned by the user running Firecracker.
+        if valid & (P9_SETATTR_UID | P9_SETATTR_GID) != 0 {
+            return Err(errno(libc::EPERM));
+        }
+
+        let (dir, name) = self.locate(&self.fid(fid)?.path)?;
+        if valid & P9_SETATTR_MODE != 0 {
+            fchmodat_nofollow(&dir, &name, mode & 0o7777)?;
+        }
+        if valid & P9_SETATTR_SIZE != 0 {
+            openat(&dir, &name, libc::O_WRONLY | libc::O_NONBLOCK, 0)?.set_len(size)?;
+        }
+        if valid & (P9_SETATTR_ATIME | P9_SETATTR_MTIME) != 0 {
+            let times = [
+                setattr_time(
+                    valid,
+                    P9_SETATTR_ATIME,
+                    P9_SETATTR_ATIME_SET,
+                    atime_sec,
+                    atime_nsec,
+                ),
+                setattr_time(
+                    valid,
+                    P9_SETATTR_MTIME,
+                    P9_SETATTR_MTIME_SET,
+                    mtime_sec,
+                    mtime_nsec,
+                ),
+            ];
+            // SAFETY: `dir` is a valid file descriptor, `name` a NUL terminated string and
+            // `times` holds two timespec structures. The return value is checked.
+            check_ret(unsafe {
+                libc::utimensat(
+                    dir.as_raw_fd(),
+                    name.as_ptr(),
+                    times.as_ptr(),
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            })?;
+        }
+        Ok(WireWriter::new(TSETATTR + 1, tag))
+    }
+
+    fn lopen(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let flags = r.read_u32()?;
+
+        if is_write(flags) {
+            self.check_writable()?;
+        }
+        let (dir, name) = self.locate(&self.fid(fid)?.path)?;
+        let file = openat(&dir, &name, open_flags(flags), 0)?;
+        let st = fstat(&file)?;
+        if let Some(entry) = self.fids.get_mut(&fid) {
+            entry.file = Some((file, flags));
+        }
+
+        let mut w = WireWriter::new(TLOPEN + 1, tag);
+        w.write_qid(&Qid::from_stat(&st));
+        // Let the guest use the largest I/O size allowed by the message size.
+        w.write_u32(0);
+        Ok(w)
+    }
+
+    fn lcreate(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let name_bytes = r.read_string()?;
+        let flags = r.read_u32()?;
+        let mode = r.read_u32()?;
+        let _gid = r.read_u32()?;
+
+        self.check_writable()?;
+        let name = component(name_bytes)?;
+        let dir = self.open_dir(&self.fid(fid)?.path)?;
+        let file = openat(
+            &dir,
+            &name,
+            open_flags(flags) | libc::O_CREAT,
+            mode & 0o7777,
+        )?;
+        let st = fstat(&file)?;
+        // The fid now refers to the new file.
+        if let Some(entry) = self.fids.get_mut(&fid) {
+            entry.path.push(name_bytes.to_vec());
+            entry.file = Some((file, flags));
+        }
+
+        let mut w = WireWriter::new(TLCREATE + 1, tag);
+        w.write_qid(&Qid::from_stat(&st));
+        w.write_u32(0);
+        Ok(w)
+    }
+
+    fn read(&mut self, r: &mut WireReader, tag: u16, max_len: usize) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let offset = r.read_u64()?;
+        let count = r.read_u32()?;
+
+        let mut buf = vec![0u8; (count as usize).min(max_len)];
+        let len = self.open_file(fid)?.read_at(&mut buf, offset)?;
+        self.metrics.read_bytes.add(usize_to_u64(len));
+
+        let mut w = WireWriter::new(TREAD + 1, tag);
+        w.write_u32(u32::try_from(len).unwrap());
+        w.write_bytes(&buf[..len]);
+        Ok(w)
+    }
+
+    fn write(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let offset = r.read_u64()?;
+        let count = r.read_u32()?;
+        let data = r.read_bytes(count as usize)?;
+
+        self.check_writable()?;
+        let len = self.open_file(fid)?.write_at(data, offset)?;
+        self.metrics.write_bytes.add(usize_to_u64(len));
+
+        let mut w = WireWriter::new(TWRITE + 1, tag);
+        w.write_u32(u32::try_from(len).unwrap());
+        Ok(w)
+    }
+
+    fn readdir(&mut self, r: &mut WireReader, tag: u16, max_len: usize) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let offset = r.read_u64()?;
+        let count = r.read_u32()?;
+        let max_len = (count as usize).min(max_len);
+
+        let dir = self.open_file(fid)?;
+        // The offset of the entries is the position of the next entry in the directory.
+        // SAFETY: `dir` is a valid file descriptor. The return value is checked.
+        if unsafe { libc::lseek(dir.as_raw_fd(), offset.cast_signed(), libc::SEEK_SET) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Host entries are smaller than 9P ones, so reading `max_len` bytes of host entries is
+        // enough to fill the response. The buffer must be large enough for the longest entry.
+        let mut dirents = vec![0u8; max_len.max(MAX_LINK_LEN)];
+        // SAFETY: `dir` is a valid file descriptor and `dirents` is valid for writing
+        // `dirents.len()` bytes. The return value is checked.
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_getdents64,
+                dir.as_raw_fd(),
+                dirents.as_mut_ptr(),
+                dirents.len(),
+            )
+        };
+        let Ok(read) = usize::try_from(ret) else {
+            return Err(io::Error::last_os_error());
+        };
+
+        let mut entries = Vec::new();
+        let mut len = 0;
+        let mut pos = 0;
+        while pos + DIRENT_NAME_OFFSET <= read {
+            let dirent = &dirents[pos..read];
+            let ino = u64::from_ne_bytes(dirent[0..8].try_into().unwrap());
+            let next_offset = i64::from_ne_bytes(dirent[8..16].try_into().unwrap());
+            let reclen = usize::from(u16::from_ne_bytes(dirent[16..18].try_into().unwrap()));
+            let d_type = dirent[18];
+            if reclen < DIRENT_NAME_OFFSET || reclen > dirent.len() {
+                break;
+            }
+            let name_field = &dirent[DIRENT_NAME_OFFSET..reclen];
+            let name = CStr::from_bytes_until_nul(name_field)
+                .map(CStr::to_bytes)
+                .unwrap_or(name_field);
+
+            let entry_len = QID_SIZE + 8 + 1 + 2 + name.len();
+            if len + entry_len > max_len {
+                break;
+            }
+            len += entry_len;
+            let qid = Qid {
+                qid_type: match d_type {
+                    libc::DT_DIR => QTDIR,
+                    libc::DT_LNK => QTSYMLINK,
+                    _ => QTFILE,
+                },
+                version: 0,
+                path: ino,
+            };
+            entries.push((qid, next_offset.cast_unsigned(), d_type, name));
+            pos += reclen;
+        }
+
+        let mut w = WireWriter::new(TREADDIR + 1, tag);
+        w.write_u32(u32::try_from(len).unwrap());
+        for (qid, next_offset, d_type, name) in entries {
+            w.write_qid(&qid);
+            w.write_u64(next_offset);
+            w.write_u8(d_type);
+            w.write_string(name);
+        }
+        Ok(w)
+    }
+
+    fn readlink(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+
+        let (dir, name) = self.locate(&self.fid(fid)?.path)?;
+        let mut buf = vec![0u8; MAX_LINK_LEN];
+        // SAFETY: `dir` is a valid file descriptor, `name` a NUL terminated string and `buf` is
+        // valid for writing `buf.len()` bytes. The return value is checked.
+        let ret = unsafe {
+            libc::readlinkat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        };
+        let Ok(len) = usize::try_from(ret) else {
+            return Err(io::Error::last_os_error());
+        };
+
+        let mut w = WireWriter::new(TREADLINK + 1, tag);
+        w.write_string(&buf[..len]);
+        Ok(w)
+    }
+
+    fn fsync(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let datasync = r.read_u32()?;
+
+        let file = self.open_file(fid)?;
+        if datasync != 0 {
+            file.sync_data()?;
+        } else {
+            file.sync_all()?;
+        }
+        Ok(WireWriter::new(TFSYNC + 1, tag))
+    }
+
+    fn mkdir(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let dfid = r.read_u32()?;
+        let name = r.read_string()?;
+        let mode = r.read_u32()?;
+        let _gid = r.read_u32()?;
+
+        self.check_writable()?;
+        let name = component(name)?;
+        let dir = self.open_dir(&self.fid(dfid)?.path)?;
+        // SAFETY: `dir` is a valid file descriptor and `name` a NUL terminated string. The
+        // return value is checked.
+        check_ret(unsafe { libc::mkdirat(dir.as_raw_fd(), name.as_ptr(), mode & 0o7777) })?;
+        let st = fstatat(&dir, &name)?;
+
+        let mut w = WireWriter::new(TMKDIR + 1, tag);
+        w.write_qid(&Qid::from_stat(&st));
+        Ok(w)
+    }
+
+    fn unlinkat(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let dfid = r.read_u32()?;
+        let name = r.read_string()?;
+        let flags = r.read_u32()?;
+
+        self.check_writable()?;
+        let name = component(name)?;
+        let dir = self.open_dir(&self.fid(dfid)?.path)?;
+        // 9P2000.L uses the value of `AT_REMOVEDIR` of Linux.
+        let flags = flags.cast_signed() & libc::AT_REMOVEDIR;
+        // SAFETY: `dir` is a valid file descriptor and `name` a NUL terminated string. The
+        // return value is checked.
+        check_ret(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), flags) })?;
+        Ok(WireWriter::new(TUNLINKAT + 1, tag))
+    }
+
+    fn renameat(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let old_dfid = r.read_u32()?;
+        let old_name_bytes = r.read_string()?;
+        let new_dfid = r.read_u32()?;
+        let new_name_bytes = r.read_string()?;
+
+        self.check_writable()?;
+        let old_name = component(old_name_bytes)?;
+        let new_name = component(new_name_bytes)?;
+        let mut old_path = self.fid(old_dfid)?.path.clone();
+        let mut new_path = self.fid(new_dfid)?.path.clone();
+        let old_dir = self.open_dir(&old_path)?;
+        let new_dir = self.open_dir(&new_path)?;
+        // SAFETY: `old_dir` and `new_dir` are valid file descriptors and `old_name` and
+        // `new_name` NUL terminated strings. The return value is checked.
+        check_ret(unsafe {
+            libc::renameat(
+                old_dir.as_raw_fd(),
+                old_name.as_ptr(),
+                new_dir.as_raw_fd(),
+                new_name.as_ptr(),
+            )
+        })?;
+
+        // The fids of the renamed file and of the files below it follow it.
+        old_path.push(old_name_bytes.to_vec());
+        new_path.push(new_name_bytes.to_vec());
+        for entry in self.fids.values_mut() {
+            if entry.path.starts_with(&old_path) {
+                let rest = entry.path.split_off(old_path.len());
+                entry.path = new_path.iter().cloned().chain(rest).collect();
+            }
+        }
+        Ok(WireWriter::new(TRENAMEAT + 1, tag))
+    }
+
+    fn remove(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+
+        // The fid is released even if the file cannot be removed.
+        let entry = self.fids.remove(&fid).ok_or_else(|| errno(libc::EBADF))?;
+        self.check_writable()?;
+        if entry.path.is_empty() {
+            return Err(errno(libc::EBUSY));
+        }
+        let (dir, name) = self.locate(&entry.path)?;
+        let flags = if is_dir(&fstatat(&dir, &name)?) {
+            libc::AT_REMOVEDIR
+        } else {
+            0
+        };
+        // SAFETY: `dir` is a valid file descriptor and `name` a NUL terminated string. The
+        // return value is checked.
+        check_ret(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), flags) })?;
+        Ok(WireWriter::new(TREMOVE + 1, tag))
+    }
+
+    fn clunk(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+
+        self.fids.remove(&fid).ok_or_else(|| errno(libc::EBADF))?;
+        Ok(WireWriter::new(TCLUNK + 1, tag))
+    }
+
+    fn statfs(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+
+        self.fid(fid)?;
+        let mut st = MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `root` is a valid file descriptor and the pointer passed to fstatvfs is valid
+        // for writing a libc::statvfs structure.
+        check_ret(unsafe { libc::fstatvfs(self.root.as_raw_fd(), st.as_mut_ptr()) })?;
+        // SAFETY: fstatvfs succeeded, so the structure is initialized.
+        let st = unsafe { st.assume_init() };
+
+        let mut w = WireWriter::new(TSTATFS + 1, tag);
+        w.write_u32(V9FS_MAGIC);
+        w.write_u32(u32::try_from(st.f_bsize).unwrap_or(u32::MAX));
+        w.write_u64(st.f_blocks);
+        w.write_u64(st.f_bfree);
+        w.write_u64(st.f_bavail);
+        w.write_u64(st.f_files);
+        w.write_u64(st.f_ffree);
+        w.write_u64(st.f_fsid);
+        w.write_u32(u32::try_from(st.f_namemax).unwrap_or(u32::MAX));
+        Ok(w)
+    }
+
+    // Locks are only advisory and the shared directory is not meant to be shared with other
+    // writers, so they are granted without being taken on the host.
+    fn lock(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+
+        self.fid(fid)?;
+        let mut w = WireWriter::new(TLOCK + 1, tag);
+        w.write_u8(P9_LOCK_SUCCESS);
+        Ok(w)
+    }
+
+    fn getlock(&mut self, r: &mut WireReader, tag: u16) -> io::Result<WireWriter> {
+        let fid = r.read_u32()?;
+        let _lock_type = r.read_u8()?;
+        let start = r.read_u64()?;
+        let length = r.read_u64()?;
+        let proc_id = r.read_u32()?;
+        let client_id = r.read_string()?;
+
+        self.fid(fid)?;
+        let mut w = WireWriter::new(TGETLOCK + 1, tag);
+        w.write_u8(P9_LOCK_TYPE_UNLCK);
+        w.write_u64(start);
+        w.write_u64(length);
+        w.write_u32(proc_id);
+        w.write_string(client_id);
+        Ok(w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+
+    const MSG_LEN: usize = MAX_MSIZE as usize;
+
+    fn shared_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.as_path().join("file"), b"hello").unwrap();
+        fs::create_dir(dir.as_path().join("dir")).unwrap();
+        fs::write(dir.as_path().join("dir").join("nested"), b"nested").unwrap();
+        symlink("/", dir.as_path().join("escape")).unwrap();
+        dir
+    }
+
+    fn server(dir: &TempDir, read_only: bool) -> P9Server {
+        let mut server = P9Server::new(
+            dir.as_path().to_str().unwrap(),
+            read_only,
+            Arc::new(P9Metrics::default()),
+        )
+        .unwrap();
+        let resp = server.call(TVERSION, |w| {
+            w.write_u32(MAX_MSIZE);
+            w.write_string(b"9P2000.L");
+        });
+        assert_eq!(resp[4], TVERSION + 1);
+        let resp = server.call(TATTACH, |w| {
+            w.write_u32(0);
+            w.write_u32(u32::MAX);
+            w.write_string(b"root");
+            w.write_string(b"");
+            w.write_u32(0);
+        });
+        assert_eq!(resp[4], TATTACH + 1);
+        server
+    }
+
+    impl P9Server {
+        fn call(&mut self, msg_type: u8, build: impl FnOnce(&mut WireWriter)) -> Vec<u8> {
+            let mut w = WireWriter::new(msg_type, 1);
+            build(&mut w);
+            self.handle(&w.finish(), MSG_LEN)
+        }
+
+        fn walk_to(&mut self, fid: u32, newfid: u32, names: &[&[u8]]) -> Vec<u8> {
+            self.call(TWALK, |w| {
+                w.write_u32(fid);
+                w.write_u32(newfid);
+                w.write_u16(u16::try_from(names.len()).unwrap());
+                for name in names {
+                    w.write_string(name);
+                }
+            })
+        }
+
+        fn lopen_fid(&mut self, fid: u32, flags: u32) -> Vec<u8> {
+            self.call(TLOPEN, |w| {
+                w.write_u32(fid);
+                w.write_u32(flags);
+            })
+        }
+
+        fn read_fid(&mut self, fid: u32) -> Vec<u8> {
+            let resp = self.call(TREAD, |w| {
+                w.write_u32(fid);
+                w.write_u64(0);
+                w.write_u32(4096);
+            });
+            let mut r = reader(&resp, TREAD + 1);
+            let count = r.read_u32().unwrap();
+            r.read_bytes(count as usize).unwrap().to_vec()
+        }
+    }
+
+    /// Checks the header of a response and returns a reader on its body.
+    fn reader(resp: &[u8], msg_type: u8) -> WireReader<'_> {
+        let mut r = WireReader::new(resp);
+        assert_eq!(r.read_u32().unwrap() as usize, resp.len());
+        assert_eq!(r.read_u8().unwrap(), msg_type);
+        assert_eq!(r.read_u16().unwrap(), 1);
+        r
+    }
+
+    fn lerror(resp: &[u8]) -> i32 {
+        reader(resp, RLERROR).read_u32().unwrap().cast_signed()
+    }
+
+    #[test]
+    fn test_version() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+
+        let resp = server.call(TVERSION, |w| {
+            w.write_u32(8192);
+            w.write_string(b"9P2000.L");
+        });
+        let mut r = reader(&resp, TVERSION + 1);
+        assert_eq!(r.read_u32().unwrap(), 8192);
+        assert_eq!(r.read_string().unwrap(), b"9P2000.L");
+        // The fids of the previous session are released.
+        assert_eq!(lerror(&server.walk_to(0, 1, &[])), libc::EBADF);
+
+        let resp = server.call(TVERSION, |w| {
+            w.write_u32(1 << 20);
+            w.write_string(b"9P2000.u");
+        });
+        let mut r = reader(&resp, TVERSION + 1);
+        assert_eq!(r.read_u32().unwrap(), MAX_MSIZE);
+        assert_eq!(r.read_string().unwrap(), b"unknown");
+
+        // Malformed and unknown requests get an error.
+        assert_eq!(lerror(&server.handle(&[1, 2], MSG_LEN)), libc::EPROTO);
+        assert_eq!(lerror(&server.call(30, |_| {})), libc::EOPNOTSUPP);
+        assert_eq!(server.metrics.request_fails.count(), 3);
+    }
+
+    #[test]
+    fn test_walk_and_read() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+
+        let resp = server.walk_to(0, 1, &[b"dir", b"nested"]);
+        let mut r = reader(&resp, TWALK + 1);
+        assert_eq!(r.read_u16().unwrap(), 2);
+        assert_eq!(r.read_u8().unwrap(), QTDIR);
+        r.read_bytes(12).unwrap();
+        assert_eq!(r.read_u8().unwrap(), QTFILE);
+
+        reader(&server.lopen_fid(1, 0), TLOPEN + 1);
+        assert_eq!(server.read_fid(1), b"nested");
+        assert_eq!(server.metrics.read_bytes.count(), 6);
+
+        // `..` does not go above the shared directory.
+        let resp = server.walk_to(0, 2, &[b"..", b"file"]);
+        assert_eq!(reader(&resp, TWALK + 1).read_u16().unwrap(), 2);
+        reader(&server.lopen_fid(2, 0), TLOPEN + 1);
+        assert_eq!(server.read_fid(2), b"hello");
+
+        // Clunked fids cannot be used anymore.
+        reader(&server.call(TCLUNK, |w| w.write_u32(2)), TCLUNK + 1);
+        assert_eq!(server.fids.len(), 2);
+        assert_eq!(lerror(&server.lopen_fid(2, 0)), libc::EBADF);
+
+        // A walk failing on its first element is an error.
+        assert_eq!(lerror(&server.walk_to(0, 3, &[b"missing"])), libc::ENOENT);
+        assert_eq!(lerror(&server.walk_to(0, 3, &[b"a/b"])), libc::EINVAL);
+    }
+
+    #[test]
+    fn test_symlinks_are_not_followed() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+
+        // Walking through a symbolic link stops at the link.
+        let resp = server.walk_to(0, 1, &[b"escape", b"etc"]);
+        let mut r = reader(&resp, TWALK + 1);
+        assert_eq!(r.read_u16().unwrap(), 1);
+        assert_eq!(r.read_u8().unwrap(), QTSYMLINK);
+        assert!(!server.fids.contains_key(&1));
+
+        let resp = server.walk_to(0, 1, &[b"escape"]);
+        reader(&resp, TWALK + 1);
+        assert_eq!(lerror(&server.lopen_fid(1, 0)), libc::ELOOP);
+
+        let resp = server.call(TREADLINK, |w| w.write_u32(1));
+        assert_eq!(reader(&resp, TREADLINK + 1).read_string().unwrap(), b"/");
+
+        // Changing the mode of the link does not change the mode of its target.
+        let resp = server.call(TSETATTR, |w| {
+            w.write_u32(1);
+            w.write_u32(P9_SETATTR_MODE);
+            w.write_u32(0o777);
+            for _ in 0..2 {
+                w.write_u32(0);
+            }
+            for _ in 0..5 {
+                w.write_u64(0);
+            }
+        });
+        assert_eq!(lerror(&resp), libc::EOPNOTSUPP);
+    }
+
+    #[test]
+    fn test_readdir() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+
+        reader(&server.walk_to(0, 1, &[]), TWALK + 1);
+        reader(&server.lopen_fid(1, P9_DOTL_DIRECTORY), TLOPEN + 1);
+        let resp = server.call(TREADDIR, |w| {
+            w.write_u32(1);
+            w.write_u64(0);
+            w.write_u32(4096);
+        });
+        let mut r = reader(&resp, TREADDIR + 1);
+        let count = r.read_u32().unwrap();
+        let mut body = WireReader::new(r.read_bytes(count as usize).unwrap());
+        let mut names = Vec::new();
+        while let Ok(qid_type) = body.read_u8() {
+            body.read_bytes(12 + 8 + 1).unwrap();
+            let name = body.read_string().unwrap().to_vec();
+            if name == b"dir" {
+                assert_eq!(qid_type, QTDIR);
+            }
+            names.push(name);
+        }
+        names.sort();
+        let expected = [".", "..", "dir", "escape", "file"].map(|name| name.as_bytes().to_vec());
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+
+        reader(&server.walk_to(0, 1, &[b"file"]), TWALK + 1);
+        assert_eq!(lerror(&server.lopen_fid(1, P9_DOTL_RDWR)), libc::EROFS);
+        assert_eq!(lerror(&server.lopen_fid(1, P9_DOTL_TRUNC)), libc::EROFS);
+        let resp = server.call(TMKDIR, |w| {
+            w.write_u32(0);
+            w.write_string(b"new");
+            w.write_u32(0o755);
+            w.write_u32(0);
+        });
+        assert_eq!(lerror(&resp), libc::EROFS);
+        assert_eq!(
+            lerror(&server.call(TREMOVE, |w| w.write_u32(1))),
+            libc::EROFS
+        );
+        assert!(dir.as_path().join("file").exists());
+    }
+
+    #[test]
+    fn test_write_operations() {
+        let dir = shared_dir();
+        let mut server = server(&dir, false);
+
+        // Create and write a file.
+        reader(&server.walk_to(0, 1, &[]), TWALK + 1);
+        let resp = server.call(TLCREATE, |w| {
+            w.write_u32(1);
+            w.write_string(b"new");
+            w.write_u32(P9_DOTL_RDWR);
+            w.write_u32(0o644);
+            w.write_u32(0);
+        });
+        assert_eq!(reader(&resp, TLCREATE + 1).read_u8().unwrap(), QTFILE);
+        let resp = server.call(TWRITE, |w| {
+            w.write_u32(1);
+            w.write_u64(0);
+            w.write_u32(3);
+            w.write_bytes(b"xyz");
+        });
+        assert_eq!(reader(&resp, TWRITE + 1).read_u32().unwrap(), 3);
+        assert_eq!(fs::read(dir.as_path().join("new")).unwrap(), b"xyz");
+
+        // Truncate it.
+        let resp = server.call(TSETATTR, |w| {
+            w.write_u32(1);
+            w.write_u32(P9_SETATTR_SIZE);
+            for _ in 0..3 {
+                w.write_u32(0);
+            }
+            w.write_u64(1);
+            for _ in 0..4 {
+                w.write_u64(0);
+            }
+        });
+        reader(&resp, TSETATTR + 1);
+        assert_eq!(fs::read(dir.as_path().join("new")).unwrap(), b"x");
+
+        // Rename the directory holding a walked file.
+        reader(&server.walk_to(0, 2, &[b"dir", b"nested"]), TWALK + 1);
+        let resp = server.call(TRENAMEAT, |w| {
+            w.write_u32(0);
+            w.write_string(b"dir");
+            w.write_u32(0);
+            w.write_string(b"moved");
+        });
+        reader(&resp, TRENAMEAT + 1);
+        reader(&server.lopen_fid(2, 0), TLOPEN + 1);
+        assert_eq!(server.read_fid(2), b"nested");
+
+        // Create and remove a directory.
+        let resp = server.call(TMKDIR, |w| {
+            w.write_u32(0);
+            w.write_string(b"subdir");
+            w.write_u32(0o755);
+            w.write_u32(0);
+        });
+        assert_eq!(reader(&resp, TMKDIR + 1).read_u8().unwrap(), QTDIR);
+        let resp = server.call(TUNLINKAT, |w| {
+            w.write_u32(0);
+            w.write_string(b"subdir");
+            w.write_u32(libc::AT_REMOVEDIR.cast_unsigned());
+        });
+        reader(&resp, TUNLINKAT + 1);
+        assert!(!dir.as_path().join("subdir").exists());
+
+        // Names cannot refer to other directories.
+        let resp = server.call(TUNLINKAT, |w| {
+            w.write_u32(0);
+            w.write_string(b"..");
+            w.write_u32(0);
+        });
+        assert_eq!(lerror(&resp), libc::EINVAL);
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let dir = shared_dir();
+        let mut server = server(&dir, true);
+        reader(&server.walk_to(0, 1, &[b"file"]), TWALK + 1);
+        reader(&server.lopen_fid(1, 0), TLOPEN + 1);
+        reader(&server.walk_to(0, 2, &[b"dir", b"nested"]), TWALK + 1);
+        let state = server.save();
+        assert_eq!(state.fids.len(), 3);
+        assert_eq!(state.fids[1].open_flags, Some(0));
+
+        // Fids whose file disappeared are dropped.
+        fs::remove_file(dir.as_path().join("dir").join("nested")).unwrap();
+        let mut restored = P9Server::new(
+            dir.as_path().to_str().unwrap(),
+            true,
+            Arc::new(P9Metrics::default()),
+        )
+        .unwrap();
+        restored.restore(&state);
+        assert_eq!(restored.msize, MAX_MSIZE);
+        assert_eq!(restored.fids.len(), 2);
+        assert_eq!(restored.read_fid(1), b"hello");
+    }
+}
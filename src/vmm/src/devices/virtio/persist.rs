@@ -55,6 +55,14 @@ pub struct QueueState {
 
     /// The number of added used buffers since last guest kick
     num_added: Wrapping<u16>,
+
+    /// Whether the queue is a packed ring
+    #[serde(default)]
+    uses_packed_ring: bool,
+
+    /// Number of descriptors of the buffers popped from the packed ring, by buffer ID
+    #[serde(default)]
+    packed_chain_lens: Vec<u16>,
 }
 
 /// Auxiliary structure for restoring queues.
@@ -82,6 +90,8 @@ impl Persist<'_> for Queue {
             next_avail: self.next_avail,
             next_used: self.next_used,
             num_added: self.num_added,
+            uses_packed_ring: self.uses_packed_ring,
+            packed_chain_lens: self.packed_chain_lens.clone(),
         }
     }
 
@@ -105,6 +115,9 @@ impl Persist<'_> for Queue {
             next_used: state.next_used,
             uses_notif_suppression: false,
             num_added: state.num_added,
+
+            uses_packed_ring: state.uses_packed_ring,
+            packed_chain_lens: state.packed_chain_lens.clone(),
         };
         if constructor_args.is_activated {
             queue.initialize(&constructor_args.mem)?;
@@ -201,6 +214,11 @@ pub struct MmioTransportState {
     device_status: u32,
     config_generation: u32,
     interrupt_status: u32,
+    #[serde(default)]
+    shm_select: u32,
+    // Guest physical addresses and lengths of the shared memory regions of the device, by ID.
+    #[serde(default)]
+    shm_regions: Vec<(u64, u64)>,
 }
 
 /// Auxiliary structure for initializing the transport when resuming from a snapshot.
@@ -229,6 +247,8 @@ impl Persist<'_> for MmioTransport {
             device_status: self.device_status,
             config_generation: self.config_generation,
             interrupt_status: self.interrupt.irq_status.load(Ordering::SeqCst),
+            shm_select: self.shm_select,
+            shm_regions: self.shm_regions().to_vec(),
         }
     }
 
@@ -247,6 +267,8 @@ impl Persist<'_> for MmioTransport {
         transport.queue_select = state.queue_select;
         transport.device_status = state.device_status;
         transport.config_generation = state.config_generation;
+        transport.shm_select = state.shm_select;
+        transport.set_shm_regions(state.shm_regions.clone());
         transport
             .interrupt
             .irq_status
@@ -265,6 +287,7 @@ mod tests {
     use crate::devices::virtio::block::virtio::test_utils::default_block_with_path;
     use crate::devices::virtio::net::Net;
     use crate::devices::virtio::net::test_utils::default_net;
+    use crate::devices::virtio::rdma::VirtioRdma;
     use crate::devices::virtio::test_utils::default_mem;
     use crate::devices::virtio::transport::mmio::tests::DummyDevice;
     use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
@@ -283,6 +306,8 @@ mod tests {
                 next_avail: Wrapping(0),
                 next_used: Wrapping(0),
                 num_added: Wrapping(0),
+                uses_packed_ring: false,
+                packed_chain_lens: Vec::new(),
             }
         }
     }
@@ -391,6 +416,40 @@ mod tests {
         assert_eq!(restored_queue, queue);
     }
 
+    #[test]
+    fn test_packed_queue_persistence() {
+        let mem = default_mem();
+
+        let mut queue = Queue::new(128);
+        queue.ready = true;
+        queue.size = queue.max_size;
+        queue.enable_packed_ring();
+        queue.initialize(&mem).unwrap();
+        // A buffer of 3 descriptors was popped, and is not used yet.
+        queue.packed_chain_lens[5] = 3;
+
+        let mut bytes = vec![0; 4096];
+
+        Snapshot::new(queue.save())
+            .save(&mut bytes.as_mut_slice())
+            .unwrap();
+
+        let ca = QueueConstructorArgs {
+            mem,
+            is_activated: true,
+        };
+        let restored_queue = Queue::restore(
+            ca,
+            &Snapshot::load_without_crc_check(bytes.as_slice())
+                .unwrap()
+                .data,
+        )
+        .unwrap();
+
+        assert!(restored_queue.uses_packed_ring);
+        assert_eq!(restored_queue, queue);
+    }
+
     #[test]
     fn test_virtio_device_state_serde() {
         let dummy = DummyDevice::new();
@@ -413,6 +472,8 @@ mod tests {
                 self.queue_select == other.queue_select &&
                 self.device_status == other.device_status &&
                 self.config_generation == other.config_generation &&
+                self.shm_select == other.shm_select &&
+                self.shm_regions() == other.shm_regions() &&
                 self.interrupt.irq_status.load(Ordering::SeqCst) == other.interrupt.irq_status.load(Ordering::SeqCst) &&
                 // Only checking equality of device type, actual device (de)ser is tested by that
                 // device's tests.
@@ -526,4 +587,18 @@ mod tests {
         let (mmio_transport, interrupt, mem, vsock) = default_vsock();
         generic_mmiotransport_persistence_test(mmio_transport, interrupt, mem, vsock);
     }
+
+    #[test]
+    fn test_rdma_over_mmiotransport_persistence() {
+        let mem = default_mem();
+        let interrupt = Arc::new(IrqTrigger::new());
+        let rdma = Arc::new(Mutex::new(VirtioRdma::new("rdma0".to_string()).unwrap()));
+        let mut mmio_transport =
+            MmioTransport::new(mem.clone(), interrupt.clone(), rdma.clone(), false);
+        // The shared memory regions keep their addresses.
+        mmio_transport.set_shm_regions(vec![(0x1_0000_0000, 0x1000)]);
+        mmio_transport.shm_select = 1;
+
+        generic_mmiotransport_persistence_test(mmio_transport, interrupt, mem, rdma);
+    }
 }
@@ -14,6 +14,15 @@ use crate::vstate::memory::{Bitmap, ByteValued, GuestAddress, GuestMemory};
 
 pub const VIRTQ_DESC_F_NEXT: u16 = 0x1;
 pub const VIRTQ_DESC_F_WRITE: u16 = 0x2;
+/// Flags marking a descriptor of a packed ring as available, or used when equal to the wrap
+/// counter of the used descriptors.
+pub const VIRTQ_DESC_F_AVAIL: u16 = 1 << 7;
+pub const VIRTQ_DESC_F_USED: u16 = 1 << 15;
+
+/// Flags of the event suppression structures of packed rings.
+pub const RING_EVENT_FLAGS_ENABLE: u16 = 0x0;
+pub const RING_EVENT_FLAGS_DISABLE: u16 = 0x1;
+pub const RING_EVENT_FLAGS_DESC: u16 = 0x2;
 
 /// Max size of virtio queues offered by firecracker's virtio devices.
 pub(super) const FIRECRACKER_MAX_QUEUE_SIZE: u16 = 256;
@@ -72,6 +81,22 @@ pub struct Descriptor {
 // SAFETY: `Descriptor` is a POD and contains no padding.
 unsafe impl ByteValued for Descriptor {}
 
+/// A virtio descriptor of a packed ring, which also holds the used elements.
+/// Taken from Virtio spec:
+/// https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html
+/// 2.7 Packed Virtqueues
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PackedDescriptor {
+    pub addr: u64,
+    pub len: u32,
+    pub id: u16,
+    pub flags: u16,
+}
+
+// SAFETY: `PackedDescriptor` is a POD and contains no padding.
+unsafe impl ByteValued for PackedDescriptor {}
+
 /// A virtio used element in the used ring.
 /// Taken from Virtio spec:
 /// https://docs.oasis-open.org/virtio/virtio/v1.1/csprd01/virtio-v1.1-csprd01.html#x1-430008
@@ -93,8 +118,9 @@ pub struct DescriptorChain {
 
     queue_size: u16,
     ttl: u16, // used to prevent infinite chain cycles
+    packed: bool,
 
-    /// Index into the descriptor table
+    /// Index into the descriptor table, or buffer ID of the chain for packed rings
     pub index: u16,
 
     /// Guest physical address of device specific data
@@ -107,7 +133,7 @@ pub struct DescriptorChain {
     pub flags: u16,
 
     /// Index into the descriptor table of the next descriptor if flags has
-    /// the next bit set
+    /// the next bit set. Packed rings chain the descriptors following each other
     pub next: u16,
 }
 
@@ -127,6 +153,7 @@ impl DescriptorChain {
             desc_table_ptr,
             queue_size,
             ttl: queue_size,
+            packed: false,
             index,
             addr: GuestAddress(desc.addr),
             len: desc.len,
@@ -137,6 +164,42 @@ impl DescriptorChain {
         if chain.is_valid() { Some(chain) } else { None }
     }
 
+    /// Creates a new `DescriptorChain` from the descriptor at `position` in a packed ring,
+    /// followed by `ttl - 1` descriptors of the buffer `id`.
+    ///
+    /// Note that the ring and queue_size are assumed to be validated by the caller.
+    fn checked_new_packed(
+        desc_table_ptr: *const Descriptor,
+        queue_size: u16,
+        position: u16,
+        id: u16,
+        ttl: u16,
+    ) -> Option<Self> {
+        if queue_size <= position {
+            return None;
+        }
+
+        // SAFETY:
+        // position is in 0..queue_size bounds
+        let desc = unsafe {
+            desc_table_ptr
+                .cast::<PackedDescriptor>()
+                .add(usize::from(position))
+                .read_volatile()
+        };
+        Some(DescriptorChain {
+            desc_table_ptr,
+            queue_size,
+            ttl,
+            packed: true,
+            index: id,
+            addr: GuestAddress(desc.addr),
+            len: desc.len,
+            flags: desc.flags,
+            next: (position + 1) % queue_size,
+        })
+    }
+
     fn is_valid(&self) -> bool {
         !self.has_next() || self.next < self.queue_size
     }
@@ -159,7 +222,15 @@ impl DescriptorChain {
     /// Note that this is distinct from the next descriptor chain returned by `AvailIter`, which is
     /// the head of the next _available_ descriptor chain.
     pub fn next_descriptor(&self) -> Option<Self> {
-        if self.has_next() {
+        if self.has_next() && self.packed {
+            DescriptorChain::checked_new_packed(
+                self.desc_table_ptr,
+                self.queue_size,
+                self.next,
+                self.index,
+                self.ttl - 1,
+            )
+        } else if self.has_next() {
             DescriptorChain::checked_new(self.desc_table_ptr, self.queue_size, self.next).map(
                 |mut c| {
                     c.ttl = self.ttl - 1;
@@ -266,6 +337,14 @@ pub struct Queue {
     pub uses_notif_suppression: bool,
     /// The number of added used buffers since last guest kick
     pub num_added: Wrapping<u16>,
+
+    /// VIRTIO_F_RING_PACKED negotiated: the descriptor table is a packed ring, and the avail
+    /// and used rings are the driver and device event suppression structures. `next_avail` and
+    /// `next_used` then count descriptors, the wrap counters flipping each time they go around
+    /// the ring.
+    pub uses_packed_ring: bool,
+    /// Number of descriptors of the buffers popped from the packed ring, by buffer ID
+    pub packed_chain_lens: Vec<u16>,
 }
 
 /// SAFETY: Queue is Send, because we use volatile memory accesses when
@@ -294,6 +373,9 @@ impl Queue {
             next_used: Wrapping(0),
             uses_notif_suppression: false,
             num_added: Wrapping(0),
+
+            uses_packed_ring: false,
+            packed_chain_lens: Vec::new(),
         }
     }
 
@@ -302,6 +384,10 @@ impl Queue {
     }
 
     fn avail_ring_size(&self) -> usize {
+        // The event suppression structures of packed rings hold an offset and flags.
+        if self.uses_packed_ring {
+            return std::mem::size_of::<u16>() * 2;
+        }
         std::mem::size_of::<u16>()
             + std::mem::size_of::<u16>()
             + std::mem::size_of::<u16>() * usize::from(self.size)
@@ -309,6 +395,9 @@ impl Queue {
     }
 
     fn used_ring_size(&self) -> usize {
+        if self.uses_packed_ring {
+            return std::mem::size_of::<u16>() * 2;
+        }
         std::mem::size_of::<u16>()
             + std::mem::size_of::<u16>()
             + std::mem::size_of::<UsedElement>() * usize::from(self.size)
@@ -361,12 +450,22 @@ impl Queue {
         // > Available Ring   2
         // > Used Ring        4
         // > ================ ==========
+        //
+        // The event suppression structures of packed rings are 4-byte aligned.
+        let avail_ring_alignment = if self.uses_packed_ring { 4 } else { 2 };
         self.desc_table_ptr =
             self.get_aligned_slice_ptr(mem, self.desc_table_address, self.desc_table_size(), 16)?;
-        self.avail_ring_ptr =
-            self.get_aligned_slice_ptr(mem, self.avail_ring_address, self.avail_ring_size(), 2)?;
+        self.avail_ring_ptr = self.get_aligned_slice_ptr(
+            mem,
+            self.avail_ring_address,
+            self.avail_ring_size(),
+            avail_ring_alignment,
+        )?;
         self.used_ring_ptr =
             self.get_aligned_slice_ptr(mem, self.used_ring_address, self.used_ring_size(), 4)?;
+        if self.uses_packed_ring {
+            self.packed_chain_lens.resize(usize::from(self.size), 0);
+        }
 
         Ok(())
     }
@@ -455,8 +554,12 @@ impl Queue {
         }
     }
 
-    /// Returns the number of yet-to-be-popped descriptor chains in the avail ring.
+    /// Returns the number of yet-to-be-popped descriptor chains in the avail ring. For packed
+    /// rings, only tells whether there is any, as 1 or 0.
     pub fn len(&self) -> u16 {
+        if self.uses_packed_ring {
+            return u16::from(self.packed_desc_available());
+        }
         (Wrapping(self.avail_ring_idx_get()) - self.next_avail).0
     }
 
@@ -476,6 +579,10 @@ impl Queue {
     /// the error to the user (e.g. loading a corrupt snapshot file), and hence cannot panic on its
     /// own.
     pub fn pop(&mut self) -> Result<Option<DescriptorChain>, InvalidAvailIdx> {
+        if self.uses_packed_ring {
+            return Ok(self.pop_packed());
+        }
+
         let len = self.len();
         // The number of descriptor chain heads to process should always
         // be smaller or equal to the queue size, as the driver should
@@ -528,6 +635,10 @@ impl Queue {
     /// This is an internal method that ASSUMES THAT THERE ARE AVAILABLE DESCRIPTORS. Otherwise it
     /// will retrieve a descriptor that contains garbage data (obsolete/empty).
     fn pop_unchecked(&mut self) -> Option<DescriptorChain> {
+        if self.uses_packed_ring {
+            return self.pop_packed();
+        }
+
         // This fence ensures all subsequent reads see the updated driver writes.
         fence(Ordering::Acquire);
 
@@ -545,12 +656,81 @@ impl Queue {
 
         DescriptorChain::checked_new(self.desc_table_ptr, self.size, desc_index).inspect(|_| {
             self.next_avail += Wrapping(1);
+            crate::usdt_probe!(
+                queue_pop,
+                self.desc_table_address.0,
+                desc_index,
+                self.next_avail.0
+            );
         })
     }
 
+    /// Wrap counter of the packed ring at the descriptor counter `counter`.
+    fn packed_wrap_counter(&self, counter: Wrapping<u16>) -> bool {
+        // The size is a power of two, so the counter overflows after an even number of laps,
+        // where the wrap counter is back to its initial value of 1.
+        (counter.0 / self.size) % 2 == 0
+    }
+
+    /// Get descriptor from the packed ring at position
+    /// # Safety
+    /// The `position` parameter should be in 0..queue_size bounds
+    #[inline(always)]
+    unsafe fn packed_desc_get(&self, position: u16) -> PackedDescriptor {
+        // SAFETY: the packed ring is an array of `size` descriptors
+        unsafe {
+            self.desc_table_ptr
+                .cast::<PackedDescriptor>()
+                .add(usize::from(position))
+                .read_volatile()
+        }
+    }
+
+    /// Checks if the driver made the descriptor at `next_avail` of the packed ring available.
+    fn packed_desc_available(&self) -> bool {
+        let wrap_counter = self.packed_wrap_counter(self.next_avail);
+        // SAFETY:
+        // index is bound by the queue size
+        let flags = unsafe { self.packed_desc_get(self.next_avail.0 % self.size) }.flags;
+        (flags & VIRTQ_DESC_F_AVAIL != 0) == wrap_counter
+            && (flags & VIRTQ_DESC_F_USED != 0) != wrap_counter
+    }
+
+    /// Pop the first available descriptor chain from the packed ring.
+    fn pop_packed(&mut self) -> Option<DescriptorChain> {
+        if !self.packed_desc_available() {
+            return None;
+        }
+
+        // This fence ensures all subsequent reads see the updated driver writes.
+        fence(Ordering::Acquire);
+
+        // The descriptors of a chain follow each other in the ring, and the last one holds the
+        // buffer ID. Chains longer than the ring are cut, like cycles in split rings.
+        let position = self.next_avail.0 % self.size;
+        let mut chain_len = 1;
+        // SAFETY:
+        // index is bound by the queue size
+        let mut desc = unsafe { self.packed_desc_get(position) };
+        while desc.flags & VIRTQ_DESC_F_NEXT != 0 && chain_len < self.size {
+            // SAFETY:
+            // index is bound by the queue size
+            desc = unsafe { self.packed_desc_get((position + chain_len) % self.size) };
+            chain_len += 1;
+        }
+
+        let id = desc.id;
+        *self.packed_chain_lens.get_mut(usize::from(id))? = chain_len;
+        self.next_avail += Wrapping(chain_len);
+        crate::usdt_probe!(queue_pop, self.desc_table_address.0, id, self.next_avail.0);
+        DescriptorChain::checked_new_packed(self.desc_table_ptr, self.size, position, id, chain_len)
+    }
+
     /// Undo the effects of the last `self.pop()` call.
     /// The caller can use this, if it was unable to consume the last popped descriptor chain.
+    /// Only split rings support it.
     pub fn undo_pop(&mut self) {
+        debug_assert!(!self.uses_packed_ring);
         self.next_avail -= Wrapping(1);
     }
 
@@ -581,6 +761,7 @@ impl Queue {
         unsafe {
             self.used_ring_ring_set(usize::from(next_used), used_element);
         }
+        crate::usdt_probe!(queue_push, self.desc_table_address.0, desc_index, len);
         Ok(())
     }
 
@@ -595,16 +776,62 @@ impl Queue {
     pub fn advance_used_ring_idx(&mut self) {
         // This fence ensures all descriptor writes are visible before the index update is.
         fence(Ordering::Release);
-        self.used_ring_idx_set(self.next_used.0);
+        // Packed rings have no index, the flags of each used descriptor mark it used.
+        if !self.uses_packed_ring {
+            self.used_ring_idx_set(self.next_used.0);
+        }
     }
 
     /// Puts an available descriptor head into the used ring for use by the guest.
     pub fn add_used(&mut self, desc_index: u16, len: u32) -> Result<(), QueueError> {
+        if self.uses_packed_ring {
+            return self.add_used_packed(desc_index, len);
+        }
+
         self.write_used_element(0, desc_index, len)?;
         self.advance_next_used(1);
         Ok(())
     }
 
+    /// Puts the buffer `id` popped from the packed ring back into it, as a used descriptor at
+    /// `next_used` which skips the other descriptors of the buffer.
+    fn add_used_packed(&mut self, id: u16, len: u32) -> Result<(), QueueError> {
+        let chain_len = match self.packed_chain_lens.get_mut(usize::from(id)) {
+            Some(chain_len) if *chain_len != 0 => std::mem::take(chain_len),
+            _ => {
+                error!(
+                    "attempted to add a buffer not popped from the packed ring: {}",
+                    id
+                );
+                return Err(QueueError::DescIndexOutOfBounds(id));
+            }
+        };
+
+        let flags = if self.packed_wrap_counter(self.next_used) {
+            VIRTQ_DESC_F_AVAIL | VIRTQ_DESC_F_USED
+        } else {
+            0
+        };
+        let position = self.next_used.0 % self.size;
+        // SAFETY:
+        // index is bound by the queue size
+        unsafe {
+            let desc = self
+                .desc_table_ptr
+                .cast::<PackedDescriptor>()
+                .cast_mut()
+                .add(usize::from(position));
+            (&raw mut (*desc).id).write_volatile(id);
+            (&raw mut (*desc).len).write_volatile(len);
+            // This fence ensures the buffer ID and length are visible before the flags are.
+            fence(Ordering::Release);
+            (&raw mut (*desc).flags).write_volatile(flags);
+        }
+        crate::usdt_probe!(queue_push, self.desc_table_address.0, id, len);
+        self.advance_next_used(chain_len);
+        Ok(())
+    }
+
     /// Try to enable notification events from the guest driver. Returns true if notifications were
     /// successfully enabled. Otherwise it means that one or more descriptors can still be consumed
     /// from the available ring and we can't guarantee that there will be a notification. In this
@@ -616,6 +843,10 @@ impl Queue {
             return Ok(true);
         }
 
+        if self.uses_packed_ring {
+            return Ok(self.try_enable_notification_packed());
+        }
+
         let len = self.len();
         if len != 0 {
             // The number of descriptor chain heads to process should always
@@ -640,11 +871,38 @@ impl Queue {
         Ok(self.next_avail.0 == self.avail_ring_idx_get())
     }
 
+    /// Packed ring version of `try_enable_notification`, asking the driver for a notification once
+    /// it makes the descriptor at `next_avail` available.
+    fn try_enable_notification_packed(&mut self) -> bool {
+        if self.packed_desc_available() {
+            return false;
+        }
+
+        let off_wrap = (self.next_avail.0 % self.size)
+            | u16::from(self.packed_wrap_counter(self.next_avail)) << 15;
+        // SAFETY: `off_wrap` and `flags` are the 2 u16 of the device event suppression structure
+        unsafe {
+            let event = self.used_ring_ptr.cast::<u16>();
+            event.write_volatile(off_wrap);
+            event.add(1).write_volatile(RING_EVENT_FLAGS_DESC);
+        }
+
+        // Make sure all subsequent reads are performed after we set the event.
+        fence(Ordering::SeqCst);
+
+        !self.packed_desc_available()
+    }
+
     /// Enable notification suppression.
     pub fn enable_notif_suppression(&mut self) {
         self.uses_notif_suppression = true;
     }
 
+    /// Enable the packed ring layout. Must be called before the queue is initialized.
+    pub fn enable_packed_ring(&mut self) {
+        self.uses_packed_ring = true;
+    }
+
     /// Check if we need to kick the guest.
     ///
     /// Please note this method has side effects: once it returns `true`, it considers the
@@ -653,8 +911,15 @@ impl Queue {
     ///
     /// This is similar to the `vring_need_event()` method implemented by the Linux kernel.
     pub fn prepare_kick(&mut self) -> bool {
+        if self.uses_packed_ring {
+            let kick = self.prepare_kick_packed();
+            crate::usdt_probe!(queue_kick, self.desc_table_address.0, kick);
+            return kick;
+        }
+
         // If the device doesn't use notification suppression, always return true
         if !self.uses_notif_suppression {
+            crate::usdt_probe!(queue_kick, self.desc_table_address.0, true);
             return true;
         }
 
@@ -667,7 +932,42 @@ impl Queue {
 
         self.num_added = Wrapping(0);
 
-        new - used_event - Wrapping(1) < new - old
+        let kick = new - used_event - Wrapping(1) < new - old;
+        crate::usdt_probe!(queue_kick, self.desc_table_address.0, kick);
+        kick
+    }
+
+    /// Packed ring version of `prepare_kick`, following the driver event suppression structure.
+    /// The driver may disable notifications even when it didn't negotiate VIRTIO_F_EVENT_IDX.
+    fn prepare_kick_packed(&mut self) -> bool {
+        // We need to expose the used descriptors before checking the driver event.
+        fence(Ordering::SeqCst);
+
+        let new = self.next_used;
+        let old = self.next_used - self.num_added;
+        self.num_added = Wrapping(0);
+
+        // SAFETY: `off_wrap` and `flags` are the 2 u16 of the driver event suppression structure
+        let (off_wrap, flags) = unsafe {
+            (
+                self.avail_ring_ptr.read_volatile(),
+                self.avail_ring_ptr.add(1).read_volatile(),
+            )
+        };
+        match flags {
+            RING_EVENT_FLAGS_DISABLE => false,
+            RING_EVENT_FLAGS_DESC if self.uses_notif_suppression => {
+                // The driver asks for a notification once the descriptor at the offset is used,
+                // in the lap of `new` if the wrap counters match, or in the one before.
+                let lap = new - Wrapping(new.0 % self.size);
+                let mut used_event = lap + Wrapping(off_wrap & !(1 << 15));
+                if (off_wrap >> 15 != 0) != self.packed_wrap_counter(new) {
+                    used_event -= Wrapping(self.size);
+                }
+                new - used_event - Wrapping(1) < new - old
+            }
+            _ => true,
+        }
     }
 
     /// Resets the Virtio Queue
@@ -681,6 +981,8 @@ impl Queue {
         self.next_used = Wrapping(0);
         self.num_added = Wrapping(0);
         self.uses_notif_suppression = false;
+        self.uses_packed_ring = false;
+        self.packed_chain_lens.clear();
     }
 }
 
@@ -1656,6 +1958,120 @@ mod tests {
         assert_eq!(q.used_ring_avail_event_get(), 1);
     }
 
+    #[test]
+    fn test_packed_ring() {
+        let m = &default_mem();
+        let desc = |addr: u64, id: u16, flags: u16| PackedDescriptor {
+            addr,
+            len: 0x100,
+            id,
+            flags,
+        };
+        let driver_event = |off_wrap: u16, flags: u16| {
+            m.write_obj(off_wrap, GuestAddress(0x100)).unwrap();
+            m.write_obj(flags, GuestAddress(0x102)).unwrap();
+        };
+        let mut q = Queue::new(4);
+        q.ready = true;
+        q.used_ring_address = GuestAddress(0x200);
+        q.enable_packed_ring();
+        // The event suppression structures are 4-byte aligned.
+        q.avail_ring_address = GuestAddress(0x102);
+        assert!(matches!(
+            q.initialize(m).unwrap_err(),
+            QueueError::PointerNotAligned(0x102, 4)
+        ));
+        q.avail_ring_address = GuestAddress(0x100);
+        q.initialize(m).unwrap();
+        q.enable_notif_suppression();
+        assert_eq!(q.len(), 0);
+        assert!(q.pop().unwrap().is_none());
+
+        // A chain of 3 descriptors, whose buffer ID is held by the last one. The wrap counters
+        // start at 1.
+        m.write_obj(
+            desc(0x1000, 0, VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_AVAIL),
+            GuestAddress(0),
+        )
+        .unwrap();
+        m.write_obj(
+            desc(0x2000, 0, VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_AVAIL),
+            GuestAddress(16),
+        )
+        .unwrap();
+        m.write_obj(
+            desc(0x3000, 2, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_AVAIL),
+            GuestAddress(32),
+        )
+        .unwrap();
+        assert_eq!(q.len(), 1);
+        let head = q.pop().unwrap().unwrap();
+        assert_eq!(head.index, 2);
+        let addrs: Vec<_> = head.into_iter().map(|desc| desc.addr.0).collect();
+        assert_eq!(addrs, [0x1000, 0x2000, 0x3000]);
+        assert_eq!(q.next_avail.0, 3);
+        assert_eq!(q.len(), 0);
+
+        // The driver asks to be notified once the first descriptor of the next lap is used.
+        driver_event(0, RING_EVENT_FLAGS_DESC);
+        q.add_used(2, 0x80).unwrap();
+        let used: PackedDescriptor = m.read_obj(GuestAddress(0)).unwrap();
+        assert_eq!(
+            (used.id, used.len, used.flags),
+            (2, 0x80, VIRTQ_DESC_F_AVAIL | VIRTQ_DESC_F_USED)
+        );
+        assert_eq!(q.next_used.0, 3);
+        assert!(!q.prepare_kick());
+        // The buffer was already used.
+        assert!(matches!(
+            q.add_used(2, 0x80).unwrap_err(),
+            DescIndexOutOfBounds(2)
+        ));
+
+        // Having run out of descriptors, the device asks to be notified of the next one.
+        assert!(q.try_enable_notification().unwrap());
+        assert_eq!(m.read_obj::<u16>(GuestAddress(0x200)).unwrap(), 3 | 1 << 15);
+        assert_eq!(
+            m.read_obj::<u16>(GuestAddress(0x202)).unwrap(),
+            RING_EVENT_FLAGS_DESC
+        );
+
+        // A chain wrapping around the ring, whose descriptors in the second lap are available
+        // with the flipped wrap counter.
+        m.write_obj(
+            desc(0x4000, 1, VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_AVAIL),
+            GuestAddress(48),
+        )
+        .unwrap();
+        m.write_obj(desc(0x5000, 1, VIRTQ_DESC_F_USED), GuestAddress(0))
+            .unwrap();
+        assert!(!q.try_enable_notification().unwrap());
+        let head = q.pop().unwrap().unwrap();
+        assert_eq!(head.index, 1);
+        let addrs: Vec<_> = head.into_iter().map(|desc| desc.addr.0).collect();
+        assert_eq!(addrs, [0x4000, 0x5000]);
+        assert!(q.pop().unwrap().is_none());
+
+        q.add_used(1, 0).unwrap();
+        let used: PackedDescriptor = m.read_obj(GuestAddress(48)).unwrap();
+        assert_eq!(
+            (used.id, used.len, used.flags),
+            (1, 0, VIRTQ_DESC_F_AVAIL | VIRTQ_DESC_F_USED)
+        );
+        assert_eq!(q.next_used.0, 5);
+        assert!(q.prepare_kick());
+
+        // Without the event, the driver only disables or enables notifications.
+        driver_event(0, RING_EVENT_FLAGS_DISABLE);
+        assert!(!q.prepare_kick());
+        driver_event(0, RING_EVENT_FLAGS_ENABLE);
+        assert!(q.prepare_kick());
+
+        q.reset();
+        assert!(!q.uses_packed_ring);
+        assert!(q.packed_chain_lens.is_empty());
+    }
+
     #[test]
     fn test_initialize_with_aligned_pointer() {
         let mut q = Queue::new(FIRECRACKER_MAX_QUEUE_SIZE);
@@ -0,0 +1,327 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backend wrapping another one to inject the faults of a policy, so that the error paths of
+//! guest drivers can be triggered deterministically. The faults hit every given number of work
+//! requests or work completions, rather than at random.
+//!
+//! Dropped messages and forced remote access errors are decided when their work request is
+//! posted, so that they also hit the work requests the device executes itself when the backend is
+//! local. Delays only apply to the messages the wrapped backend carries: all of them when it
+//! isn't local, and those of the unreliable queue pairs when it is.
+
+use std::collections::VecDeque;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use utils::time::TimerFd;
+
+use super::{RdmaBackend, RdmaMessage, copy_iov};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::{
+    RDMA_QPT_RC, RDMA_WC_REM_ACCESS_ERR, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS,
+    RDMA_WR_ATOMIC_CMP_AND_SWP, RDMA_WR_ATOMIC_FETCH_AND_ADD, RDMA_WR_RDMA_READ,
+    RDMA_WR_RDMA_WRITE, RDMA_WR_RDMA_WRITE_WITH_IMM,
+};
+use crate::seccomp::{BpfProgram, InstallationError};
+
+/// Faults injected into an RDMA device. Each fault is disabled when its period is 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RdmaFaultPolicy {
+    /// Drops one message out of `drop_every`, failing the work requests of reliable connected
+    /// queue pairs with `RDMA_WC_RETRY_EXC_ERR`.
+    #[serde(default)]
+    pub drop_every: u32,
+    /// Fails one RDMA write, RDMA read or atomic work request of reliable connected queue pairs
+    /// out of `rem_access_err_every` with `RDMA_WC_REM_ACCESS_ERR`.
+    #[serde(default)]
+    pub rem_access_err_every: u32,
+    /// Overruns the completion queue of one work completion out of `cq_overrun_every`.
+    #[serde(default)]
+    pub cq_overrun_every: u32,
+    /// Time the messages carried by the backend are delayed for, in microseconds.
+    #[serde(default)]
+    pub delay_usecs: u32,
+}
+
+/// Period of a fault of the policy.
+#[derive(Debug)]
+struct Period {
+    every: u32,
+    count: u32,
+}
+
+impl Period {
+    fn new(every: u32) -> Self {
+        Self { every, count: 0 }
+    }
+
+    /// Counts an occurrence, returning whether the fault hits it.
+    fn hit(&mut self) -> bool {
+        if self.every == 0 {
+            return false;
+        }
+        self.count += 1;
+        if self.count < self.every {
+            return false;
+        }
+        self.count = 0;
+        true
+    }
+}
+
+/// Backend injecting the faults of a policy into the messages of another one.
+#[derive(Debug)]
+pub struct FaultInjectionBackend {
+    inner: Box<dyn RdmaBackend>,
+    policy: RdmaFaultPolicy,
+    drops: Period,
+    rem_access_errs: Period,
+    cq_overruns: Period,
+    // Messages waiting for their delay to run out, oldest first.
+    delayed: VecDeque<(Instant, RdmaMessage)>,
+    delay_timer: TimerFd,
+    failed_qps: Vec<u32>,
+}
+
+impl FaultInjectionBackend {
+    /// Wraps `inner` to inject the faults of `policy`.
+    pub fn new(inner: Box<dyn RdmaBackend>, policy: RdmaFaultPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            drops: Period::new(policy.drop_every),
+            rem_access_errs: Period::new(policy.rem_access_err_every),
+            cq_overruns: Period::new(policy.cq_overrun_every),
+            delayed: VecDeque::new(),
+            delay_timer: TimerFd::new(),
+            failed_qps: Vec::new(),
+        }
+    }
+
+    fn inner_transmit(&mut self, msg: RdmaMessage) {
+        let (qp_type, src_qpn) = (msg.qp_type, msg.src_qpn);
+        // The work request completed already, so its queue pair is reported as failed instead.
+        if self.inner.transmit(msg) != RDMA_WC_SUCCESS && qp_type == RDMA_QPT_RC {
+            self.failed_qps.push(src_qpn);
+        }
+    }
+
+    fn arm_delay_timer(&mut self) {
+        match self.delayed.front() {
+            // Arming the timer with a zero duration would disarm it.
+            Some((deadline, _)) => self.delay_timer.arm(
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_nanos(1)),
+                None,
+            ),
+            None if self.delay_timer.is_armed() => self.delay_timer.arm(Duration::ZERO, None),
+            None => {}
+        }
+    }
+}
+
+impl RdmaBackend for FaultInjectionBackend {
+    fn intercept(&mut self, msg: &RdmaMessage) -> Option<u32> {
+        if self.drops.hit() {
+            // Only reliable connected queue pairs notice that their messages are lost.
+            return Some(if msg.qp_type == RDMA_QPT_RC {
+                RDMA_WC_RETRY_EXC_ERR
+            } else {
+                RDMA_WC_SUCCESS
+            });
+        }
+        let remote_access = matches!(
+            msg.opcode,
+            RDMA_WR_RDMA_WRITE
+                | RDMA_WR_RDMA_WRITE_WITH_IMM
+                | RDMA_WR_RDMA_READ
+                | RDMA_WR_ATOMIC_CMP_AND_SWP
+                | RDMA_WR_ATOMIC_FETCH_AND_ADD
+        );
+        if msg.qp_type == RDMA_QPT_RC && remote_access && self.rem_access_errs.hit() {
+            return Some(RDMA_WC_REM_ACCESS_ERR);
+        }
+        self.inner.intercept(msg)
+    }
+
+    fn overrun_cq(&mut self) -> bool {
+        self.cq_overruns.hit() || self.inner.overrun_cq()
+    }
+
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        if self.policy.delay_usecs == 0 {
+            return self.inner.transmit(msg);
+        }
+        let deadline = Instant::now() + Duration::from_micros(u64::from(self.policy.delay_usecs));
+        self.delayed.push_back((deadline, msg));
+        if self.delayed.len() == 1 {
+            self.arm_delay_timer();
+        }
+        RDMA_WC_SUCCESS
+    }
+
+    fn transmit_iov(&mut self, mut msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        if self.policy.delay_usecs == 0 {
+            return self.inner.transmit_iov(msg, payload);
+        }
+        // The delayed messages outlive the work requests which sent them.
+        msg.payload = copy_iov(&payload, 0);
+        self.transmit(msg)
+    }
+
+    fn submit(&mut self) {
+        self.inner.submit();
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        let mut events = self.inner.events();
+        events.push(self.delay_timer.as_raw_fd());
+        events
+    }
+
+    fn process_event(&mut self) {
+        // The messages whose delay ran out are checked whatever woke the backend up.
+        self.delay_timer.read();
+        let now = Instant::now();
+        let mut transmitted = false;
+        while self
+            .delayed
+            .front()
+            .is_some_and(|(deadline, _)| *deadline <= now)
+        {
+            let (_, msg) = self.delayed.pop_front().unwrap();
+            self.inner_transmit(msg);
+            transmitted = true;
+        }
+        if transmitted {
+            self.inner.submit();
+        }
+        self.arm_delay_timer();
+        self.inner.process_event();
+    }
+
+    fn take_failed_qps(&mut self) -> Vec<u32> {
+        let mut failed_qps = std::mem::take(&mut self.failed_qps);
+        failed_qps.extend(self.inner.take_failed_qps());
+        failed_qps
+    }
+
+    fn probe(&mut self) -> bool {
+        self.inner.probe()
+    }
+
+    fn connect_qp(&mut self, qpn: u32, dgid: [u8; 16]) -> bool {
+        self.inner.connect_qp(qpn, dgid)
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.inner.receive()
+    }
+
+    fn is_local(&self) -> bool {
+        self.inner.is_local()
+    }
+
+    fn apply_seccomp_filter(&mut self, filter: Arc<BpfProgram>) -> Result<(), InstallationError> {
+        self.inner.apply_seccomp_filter(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::rdma::backend::LoopbackBackend;
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WR_SEND};
+
+    fn message(opcode: u32, qp_type: u32) -> RdmaMessage {
+        RdmaMessage {
+            opcode,
+            qp_type,
+            src_qpn: 1,
+            dest_qpn: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_period() {
+        let mut period = Period::new(0);
+        assert!((0..10).all(|_| !period.hit()));
+        let mut period = Period::new(3);
+        let hits: Vec<bool> = (0..7).map(|_| period.hit()).collect();
+        assert_eq!(hits, [false, false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn test_intercept() {
+        let mut backend = FaultInjectionBackend::new(
+            Box::new(LoopbackBackend::default()),
+            RdmaFaultPolicy {
+                drop_every: 2,
+                rem_access_err_every: 2,
+                cq_overrun_every: 3,
+                ..Default::default()
+            },
+        );
+        assert!(backend.is_local());
+
+        // Dropped messages only fail the work requests of reliable connected queue pairs.
+        let send = message(RDMA_WR_SEND, RDMA_QPT_UD);
+        assert_eq!(backend.intercept(&send), None);
+        assert_eq!(backend.intercept(&send), Some(RDMA_WC_SUCCESS));
+        let write = message(RDMA_WR_RDMA_WRITE, RDMA_QPT_RC);
+        assert_eq!(backend.intercept(&write), None);
+        assert_eq!(backend.intercept(&write), Some(RDMA_WC_RETRY_EXC_ERR));
+        // Messages which aren't dropped may fail with remote access errors.
+        assert_eq!(backend.intercept(&write), Some(RDMA_WC_REM_ACCESS_ERR));
+        assert_eq!(backend.intercept(&send), Some(RDMA_WC_SUCCESS));
+        assert_eq!(backend.intercept(&write), None);
+
+        let overruns: Vec<bool> = (0..6).map(|_| backend.overrun_cq()).collect();
+        assert_eq!(overruns, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut backend = FaultInjectionBackend::new(
+            Box::new(LoopbackBackend::default()),
+            RdmaFaultPolicy {
+                delay_usecs: 10_000,
+                ..Default::default()
+            },
+        );
+        assert_eq!(backend.events(), [backend.delay_timer.as_raw_fd()]);
+        let send = message(RDMA_WR_SEND, RDMA_QPT_UD);
+        assert_eq!(backend.transmit(send.clone()), RDMA_WC_SUCCESS);
+        assert!(backend.delay_timer.is_armed());
+        backend.process_event();
+        assert!(backend.receive().is_none());
+
+        // The message is delivered once its delay ran out.
+        std::thread::sleep(Duration::from_millis(20));
+        backend.process_event();
+        assert_eq!(backend.receive(), Some(send.clone()));
+        assert!(!backend.delay_timer.is_armed());
+        assert!(backend.take_failed_qps().is_empty());
+
+        // The payload of the delayed messages is copied before their work request completes.
+        let mut payload = [0xab; 8];
+        let iov = IoVecBuffer::from(&payload[..]);
+        assert_eq!(backend.transmit_iov(send.clone(), iov), RDMA_WC_SUCCESS);
+        payload.fill(0);
+        std::thread::sleep(Duration::from_millis(20));
+        backend.process_event();
+        assert_eq!(
+            backend.receive(),
+            Some(RdmaMessage {
+                payload: vec![0xab; 8],
+                ..send
+            })
+        );
+    }
+}
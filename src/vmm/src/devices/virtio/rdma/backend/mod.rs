@@ -0,0 +1,385 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transports carrying the messages of the work requests between queue pairs.
+
+pub mod fault;
+mod packet;
+pub mod shmem;
+pub mod tcp;
+pub mod thread;
+pub mod udp;
+pub mod unix;
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use self::shmem::{ShmemBackend, ShmemBackendConfig, ShmemBackendError};
+use self::tcp::{TcpBackend, TcpBackendConfig, TcpBackendError};
+use self::thread::{BackendLauncher, ThreadedBackend};
+use self::udp::{UdpBackend, UdpBackendConfig, UdpBackendError};
+use self::unix::{UnixBackend, UnixBackendConfig, UnixBackendError};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::{RDMA_QPT_RC, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS};
+use crate::seccomp::{BpfProgram, InstallationError};
+
+/// Message sent by a work request, as it travels on the wire.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RdmaMessage {
+    /// One of the `RDMA_WR_*` opcodes of the work request.
+    pub opcode: u32,
+    /// One of the `RDMA_QPT_*` types of the sending queue pair.
+    pub qp_type: u32,
+    pub src_qpn: u32,
+    /// GID of the source.
+    pub sgid: [u8; 16],
+    /// GID of the destination.
+    pub dgid: [u8; 16],
+    pub dest_qpn: u32,
+    /// Q_Key of the destination, for unreliable datagram queue pairs.
+    pub qkey: u32,
+    /// Remote address of RDMA writes.
+    pub remote_addr: u64,
+    /// Remote key of RDMA writes.
+    pub rkey: u32,
+    /// Immediate data, in network byte order.
+    pub imm_data: Option<u32>,
+    /// Remote key of the memory window the receiver invalidates, for sends with invalidate.
+    pub invalidate_rkey: Option<u32>,
+    /// Whether the receiver is asked for a solicited event.
+    pub solicited: bool,
+    /// Data gathered from the scatter/gather entries of the work request.
+    pub payload: Vec<u8>,
+}
+
+/// Transport of the messages of the queue pairs of a device.
+pub trait RdmaBackend: Debug + Send {
+    /// Transmits a message, returning the `RDMA_WC_*` status of the work request which sent it.
+    /// Backends performing host I/O may only queue the message until the next call to `submit`.
+    fn transmit(&mut self, msg: RdmaMessage) -> u32;
+
+    /// Transmits a message whose payload is read from the guest memory `payload` covers, rather
+    /// than from the message itself. The backends sending the payload right away read it from
+    /// guest memory with no copy, while the others, which keep it past the work request, copy it
+    /// first.
+    fn transmit_iov(&mut self, mut msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        msg.payload = copy_iov(&payload, 0);
+        self.transmit(msg)
+    }
+
+    /// Returns the `RDMA_WC_*` status of the work request sending `msg` if the backend
+    /// intercepts it rather than letting the device execute it, such as when it drops the message.
+    fn intercept(&mut self, _msg: &RdmaMessage) -> Option<u32> {
+        None
+    }
+
+    /// Whether the completion queue of the next work completion overruns, whatever its free
+    /// entries.
+    fn overrun_cq(&mut self) -> bool {
+        false
+    }
+
+    /// Submits the host I/O queued since the last call, once the device processed a batch of
+    /// work requests.
+    fn submit(&mut self) {}
+
+    /// Events signalled when host I/O submitted by the backend completes, or when the backend
+    /// has to act on its own, such as retransmitting lost messages. Backends performing no host
+    /// I/O have none.
+    fn events(&self) -> Vec<RawFd> {
+        Vec::new()
+    }
+
+    /// Consumes the events of the backend, and processes the host I/O completed since the last
+    /// call, making the messages received available to `receive`.
+    fn process_event(&mut self) {}
+
+    /// Takes the queue pairs of the device whose messages the backend gave up delivering since
+    /// the last call, which the device moves to the error state.
+    fn take_failed_qps(&mut self) -> Vec<u32> {
+        Vec::new()
+    }
+
+    /// Whether the backend can still carry the messages of the device, which probes it
+    /// periodically and sets it up again when it can't. Backends performing no host I/O always
+    /// can.
+    fn probe(&mut self) -> bool {
+        true
+    }
+
+    /// Sets up again, once the device is restored, the path of the reliable connected queue pair
+    /// `qpn` to its peer, owning the GID `dgid` the queue pair was saved with. Returns whether the
+    /// peer can be reached, the device moving the queue pair to the error state otherwise.
+    /// Backends keeping no state about the peers always can.
+    fn connect_qp(&mut self, _qpn: u32, _dgid: [u8; 16]) -> bool {
+        true
+    }
+
+    /// Returns the next message received for the queue pairs of the device, if any.
+    fn receive(&mut self) -> Option<RdmaMessage>;
+
+    /// Whether the messages are delivered to the queue pairs of the device itself, which then
+    /// executes the atomic operations of the work requests on behalf of their destination.
+    fn is_local(&self) -> bool;
+
+    /// Installs `filter` on the thread the backend runs on, if it runs on a thread of its own
+    /// rather than on the one of the device.
+    fn apply_seccomp_filter(&mut self, _filter: Arc<BpfProgram>) -> Result<(), InstallationError> {
+        Ok(())
+    }
+}
+
+/// Backend of a device, tagged by its `type`, along with the parameters of its transport.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RdmaBackendConfig {
+    /// Drops all the messages.
+    Null,
+    /// Delivers the messages back to the queue pairs of the device.
+    Loopback,
+    /// Carries the messages over UDP.
+    Udp(UdpBackendConfig),
+    /// Carries the messages over TCP connections.
+    Tcp(TcpBackendConfig),
+    /// Carries the messages over Unix datagram sockets, to the microVMs of the same host.
+    Unix(UnixBackendConfig),
+    /// Carries the messages through memory shared with another microVM of the same host.
+    Shmem(ShmemBackendConfig),
+}
+
+/// Error while setting up a backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum RdmaBackendError {
+    /// Unable to set up the UDP backend: {0}
+    Udp(#[from] UdpBackendError),
+    /// Unable to set up the TCP backend: {0}
+    Tcp(#[from] TcpBackendError),
+    /// Unable to set up the Unix backend: {0}
+    Unix(#[from] UnixBackendError),
+    /// Unable to set up the shared memory backend: {0}
+    Shmem(#[from] ShmemBackendError),
+    /// Unable to watch the events of the backend: {0}
+    Events(io::Error),
+    /// Unable to spawn the thread of the backend: {0}
+    Thread(io::Error),
+    /// Unable to install the seccomp filter of the thread of the backend: {0}
+    Seccomp(InstallationError),
+    /// Unable to replace a local backend with a remote one, or the reverse, once the device is
+    /// activated
+    LocalityChange,
+}
+
+impl RdmaBackendConfig {
+    /// Sets up the backend of the configuration.
+    pub fn build(&self) -> Result<Box<dyn RdmaBackend>, RdmaBackendError> {
+        Ok(match self {
+            Self::Null => Box::new(NullBackend),
+            Self::Loopback => Box::new(LoopbackBackend::default()),
+            Self::Udp(config) => Box::new(UdpBackend::new(config)?),
+            Self::Tcp(config) => Box::new(TcpBackend::new(config)?),
+            Self::Unix(config) => Box::new(UnixBackend::new(config)?),
+            Self::Shmem(config) => Box::new(ShmemBackend::new(config)?),
+        })
+    }
+
+    /// Whether the backend of the configuration performs host I/O, in which case it runs on a
+    /// thread of its own, and is probed by the device.
+    pub fn performs_host_io(&self) -> bool {
+        !matches!(self, Self::Null | Self::Loopback)
+    }
+
+    /// Sets up the backend of the configuration on a thread of its own if it performs host I/O,
+    /// the thread being created by `launcher` if any, or by the calling thread otherwise.
+    pub fn spawn(
+        &self,
+        launcher: Option<&BackendLauncher>,
+    ) -> Result<Box<dyn RdmaBackend>, RdmaBackendError> {
+        if !self.performs_host_io() {
+            return self.build();
+        }
+        let config = self.clone();
+        let backend = ThreadedBackend::spawn(launcher, move || config.build())?;
+        Ok(Box::new(backend))
+    }
+}
+
+/// Copies the bytes of `payload` from `offset` on.
+pub(crate) fn copy_iov(payload: &IoVecBuffer, offset: usize) -> Vec<u8> {
+    let len = (payload.len() as usize).saturating_sub(offset);
+    let mut buf = vec![0u8; len];
+    // Guest memory is copied to a buffer of the same length, which doesn't fail.
+    payload
+        .read_volatile_at(&mut buf.as_mut_slice(), offset, len)
+        .unwrap();
+    buf
+}
+
+/// Whether a socket is still bound at `path`. The backends binding Unix sockets are cut off
+/// from their peers once their socket is removed from its path.
+pub(crate) fn socket_bound_at(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket())
+}
+
+/// Sends `header` followed by `payload` with a single `sendmsg`, to `addr` unless the socket is
+/// connected, reading the payload straight from guest memory. Returns the number of bytes sent.
+pub(crate) fn send_iov(
+    fd: RawFd,
+    addr: Option<(&libc::sockaddr_storage, libc::socklen_t)>,
+    header: &[u8],
+    payload: &IoVecBuffer,
+    flags: libc::c_int,
+) -> io::Result<usize> {
+    let mut iovs = Vec::with_capacity(1 + payload.iovec_count());
+    iovs.push(libc::iovec {
+        iov_base: header.as_ptr().cast_mut().cast(),
+        iov_len: header.len(),
+    });
+    iovs.extend_from_slice(payload.as_iovec_slice());
+    // SAFETY: The header is a plain C structure, for which all-zero is a valid value.
+    let mut hdr: libc::msghdr = unsafe { std::mem::zeroed() };
+    if let Some((addr, len)) = addr {
+        hdr.msg_name = std::ptr::from_ref(addr).cast_mut().cast();
+        hdr.msg_namelen = len;
+    }
+    hdr.msg_iov = iovs.as_mut_ptr();
+    // The type of the length depends on the C library.
+    #[allow(clippy::useless_conversion, clippy::unnecessary_fallible_conversions)]
+    let iovlen = iovs.len().try_into().unwrap();
+    hdr.msg_iovlen = iovlen;
+    // SAFETY: The header points to `addr`, and to `iovs`, whose buffers are either `header` or
+    // the guest memory `payload` covers, and are only read from.
+    let sent = unsafe { libc::sendmsg(fd, &raw const hdr, flags) };
+    usize::try_from(sent).map_err(|_| io::Error::last_os_error())
+}
+
+/// Backend dropping all the messages, as if no peer was reachable.
+#[derive(Debug, Default)]
+pub struct NullBackend;
+
+impl RdmaBackend for NullBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        // Only reliable connected queue pairs notice that their messages are lost.
+        if msg.qp_type == RDMA_QPT_RC {
+            RDMA_WC_RETRY_EXC_ERR
+        } else {
+            RDMA_WC_SUCCESS
+        }
+    }
+
+    fn transmit_iov(&mut self, msg: RdmaMessage, _payload: IoVecBuffer) -> u32 {
+        // The payload of the dropped messages isn't read.
+        self.transmit(msg)
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        None
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// Backend delivering the messages back to the queue pairs of the device which sent them,
+/// whatever their destination GID. Messages the receiver drops are not reported to the sender,
+/// the device delivering the messages of reliable connected queue pairs itself to report them.
+#[derive(Debug, Default)]
+pub struct LoopbackBackend {
+    messages: VecDeque<RdmaMessage>,
+}
+
+impl RdmaBackend for LoopbackBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        self.messages.push_back(msg);
+        RDMA_WC_SUCCESS
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.messages.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixDatagram;
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::rdma::RDMA_QPT_UD;
+
+    #[test]
+    fn test_backend_config() {
+        let parse = |json: &str| serde_json::from_str::<RdmaBackendConfig>(json);
+        assert_eq!(
+            parse(r#"{"type": "null"}"#).unwrap(),
+            RdmaBackendConfig::Null
+        );
+        assert_eq!(
+            parse(r#"{"type": "udp", "bind_ip": "10.0.0.1"}"#).unwrap(),
+            RdmaBackendConfig::Udp(UdpBackendConfig {
+                bind_ip: "10.0.0.1".parse().unwrap(),
+                port: udp::UDP_BACKEND_DEFAULT_PORT,
+            })
+        );
+        parse(r#"{"type": "ibverbs"}"#).unwrap_err();
+        parse(r#"{"bind_ip": "10.0.0.1"}"#).unwrap_err();
+        parse(r#"{"type": "tcp", "bind_ip": "10.0.0.1", "peers": []}"#).unwrap_err();
+
+        let mut null = RdmaBackendConfig::Null.build().unwrap();
+        assert!(!null.is_local());
+        let msg = |qp_type| RdmaMessage {
+            qp_type,
+            ..Default::default()
+        };
+        assert_eq!(null.transmit(msg(RDMA_QPT_RC)), RDMA_WC_RETRY_EXC_ERR);
+        assert_eq!(null.transmit(msg(RDMA_QPT_UD)), RDMA_WC_SUCCESS);
+        assert!(null.receive().is_none());
+        assert!(RdmaBackendConfig::Loopback.build().unwrap().is_local());
+
+        // The errors of the backends are reported as such.
+        let dir = TempDir::new().unwrap();
+        let config = RdmaBackendConfig::Shmem(ShmemBackendConfig {
+            path: dir.as_path().join("missing").join("rdma.shm"),
+            peer_gid: "10.0.0.2".parse().unwrap(),
+        });
+        assert!(matches!(
+            config.build(),
+            Err(RdmaBackendError::Shmem(ShmemBackendError::Bind(_)))
+        ));
+    }
+
+    #[test]
+    fn test_send_iov() {
+        let payload = IoVecBuffer::from(vec![&b"pay"[..], &b"load"[..]]);
+        assert_eq!(copy_iov(&payload, 0), b"payload");
+        assert_eq!(copy_iov(&payload, 5), b"ad");
+        assert!(copy_iov(&payload, 8).is_empty());
+
+        // The header and the payload go in a single datagram.
+        let (a, b) = UnixDatagram::pair().unwrap();
+        assert_eq!(
+            send_iov(a.as_raw_fd(), None, b"header:", &payload, 0).unwrap(),
+            14
+        );
+        let mut buf = [0u8; 32];
+        let len = b.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"header:payload");
+
+        drop(b);
+        send_iov(a.as_raw_fd(), None, b"header:", &payload, 0).unwrap_err();
+    }
+}
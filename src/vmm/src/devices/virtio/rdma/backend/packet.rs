@@ -0,0 +1,206 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Wire format of the network backends. Each packet holds a header modelled after the RoCEv2
+//! transport headers, followed by the payload of the message it carries.
+
+use std::net::{IpAddr, Ipv6Addr};
+
+use super::RdmaMessage;
+
+// Magic number and version starting the header of the packets.
+const HEADER_MAGIC: u16 = 0x5244;
+const HEADER_VERSION: u8 = 1;
+/// Length of the header of the packets.
+pub(crate) const HEADER_LEN: usize = 96;
+/// Packet carrying a message.
+pub(crate) const KIND_DATA: u8 = 0;
+/// Packet acknowledging the messages of a reliable connected flow.
+pub(crate) const KIND_ACK: u8 = 1;
+// Flags of the header, telling which optional fields of the message are set.
+const FLAG_IMM: u32 = 1 << 0;
+const FLAG_INV: u32 = 1 << 1;
+const FLAG_SOLICITED: u32 = 1 << 2;
+
+/// Returns the IP address of the peer owning `gid`, as seen from a socket of the family of
+/// `local_ip`, or `None` if the GID is not an IPv4-mapped address while `local_ip` is an IPv4
+/// one.
+pub(crate) fn peer_ip(gid: [u8; 16], local_ip: IpAddr) -> Option<IpAddr> {
+    let ip = Ipv6Addr::from(gid);
+    if local_ip.is_ipv4() {
+        Some(IpAddr::V4(ip.to_ipv4_mapped()?))
+    } else {
+        Some(IpAddr::V6(ip))
+    }
+}
+
+/// Packet of the network backends: a message of a queue pair, or the acknowledgement of the
+/// messages of a reliable connected flow up to `psn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Packet {
+    /// `KIND_DATA` or `KIND_ACK`.
+    pub(crate) kind: u8,
+    /// Session of the flow, chosen by the sender when the flow starts.
+    pub(crate) session: u32,
+    /// Number of the message in its flow.
+    pub(crate) psn: u64,
+    pub(crate) msg: RdmaMessage,
+}
+
+impl Packet {
+    /// Encodes the packet, in network byte order.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = self.encode_header();
+        buf.extend_from_slice(&self.msg.payload);
+        buf
+    }
+
+    /// Encodes the header of the packet, in network byte order, which the payload of its message
+    /// follows on the wire.
+    pub(crate) fn encode_header(&self) -> Vec<u8> {
+        let msg = &self.msg;
+        let mut flags = 0;
+        if msg.imm_data.is_some() {
+            flags |= FLAG_IMM;
+        }
+        if msg.invalidate_rkey.is_some() {
+            flags |= FLAG_INV;
+        }
+        if msg.solicited {
+            flags |= FLAG_SOLICITED;
+        }
+
+        let mut buf = Vec::with_capacity(HEADER_LEN + msg.payload.len());
+        buf.extend_from_slice(&HEADER_MAGIC.to_be_bytes());
+        buf.push(HEADER_VERSION);
+        buf.push(self.kind);
+        for field in [
+            msg.opcode,
+            msg.qp_type,
+            msg.src_qpn,
+            msg.dest_qpn,
+            msg.qkey,
+            self.session,
+            flags,
+        ] {
+            buf.extend_from_slice(&field.to_be_bytes());
+        }
+        buf.extend_from_slice(&self.psn.to_be_bytes());
+        buf.extend_from_slice(&msg.remote_addr.to_be_bytes());
+        for field in [
+            msg.rkey,
+            msg.imm_data.unwrap_or(0),
+            msg.invalidate_rkey.unwrap_or(0),
+            // Reserved.
+            0,
+        ] {
+            buf.extend_from_slice(&field.to_be_bytes());
+        }
+        buf.extend_from_slice(&msg.sgid);
+        buf.extend_from_slice(&msg.dgid);
+        buf
+    }
+
+    /// Decodes a packet, or returns `None` if `buf` doesn't hold one.
+    pub(crate) fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < HEADER_LEN
+            || buf[..2] != HEADER_MAGIC.to_be_bytes()
+            || buf[2] != HEADER_VERSION
+        {
+            return None;
+        }
+        let u32_at =
+            |offset: usize| u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap());
+        let flags = u32_at(28);
+        Some(Self {
+            kind: buf[3],
+            session: u32_at(24),
+            psn: u64_at(32),
+            msg: RdmaMessage {
+                opcode: u32_at(4),
+                qp_type: u32_at(8),
+                src_qpn: u32_at(12),
+                dest_qpn: u32_at(16),
+                qkey: u32_at(20),
+                remote_addr: u64_at(40),
+                rkey: u32_at(48),
+                imm_data: (flags & FLAG_IMM != 0).then(|| u32_at(52)),
+                invalidate_rkey: (flags & FLAG_INV != 0).then(|| u32_at(56)),
+                solicited: flags & FLAG_SOLICITED != 0,
+                sgid: buf[64..80].try_into().unwrap(),
+                dgid: buf[80..96].try_into().unwrap(),
+                payload: buf[HEADER_LEN..].to_vec(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::devices::virtio::rdma::{RDMA_QPT_RC, RDMA_WR_SEND_WITH_IMM};
+
+    #[test]
+    fn test_packet_encoding() {
+        let packet = Packet {
+            kind: KIND_DATA,
+            session: 0x1234_5678,
+            psn: 0x1_0000_0002,
+            msg: RdmaMessage {
+                opcode: RDMA_WR_SEND_WITH_IMM,
+                qp_type: RDMA_QPT_RC,
+                src_qpn: 3,
+                sgid: [1; 16],
+                dgid: [2; 16],
+                dest_qpn: 4,
+                qkey: 0x1111,
+                remote_addr: 0xa000_0000_8000,
+                rkey: 0x2222,
+                imm_data: Some(0xdead_beef),
+                invalidate_rkey: Some(0),
+                solicited: true,
+                payload: b"payload".to_vec(),
+            },
+        };
+        let datagram = packet.encode();
+        assert_eq!(datagram.len(), HEADER_LEN + 7);
+        assert_eq!(packet.encode_header(), datagram[..HEADER_LEN]);
+        assert_eq!(Packet::decode(&datagram).unwrap(), packet);
+
+        // The optional fields which aren't set are not decoded.
+        let packet = Packet {
+            kind: KIND_ACK,
+            session: 1,
+            psn: 0,
+            msg: RdmaMessage::default(),
+        };
+        assert_eq!(Packet::decode(&packet.encode()).unwrap(), packet);
+
+        assert_eq!(Packet::decode(&datagram[..HEADER_LEN - 1]), None);
+        let mut invalid = datagram.clone();
+        invalid[0] ^= 0xff;
+        assert_eq!(Packet::decode(&invalid), None);
+        let mut invalid = datagram;
+        invalid[2] = HEADER_VERSION + 1;
+        assert_eq!(Packet::decode(&invalid), None);
+    }
+
+    #[test]
+    fn test_peer_ip() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let gid = Ipv4Addr::new(10, 0, 0, 2).to_ipv6_mapped().octets();
+        assert_eq!(
+            peer_ip(gid, v4),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)))
+        );
+        assert_eq!(peer_ip(gid, v6), Some(IpAddr::V6(Ipv6Addr::from(gid))));
+        let gid = [0xfe; 16];
+        assert_eq!(peer_ip(gid, v4), None);
+        assert_eq!(peer_ip(gid, v6), Some(IpAddr::V6(Ipv6Addr::from(gid))));
+    }
+}
@@ -0,0 +1,814 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backend exchanging the messages of a pair of microVMs of the same host through shared memory.
+//! Both devices map a memfd holding a ring per direction, in which the messages are written as
+//! packets of the wire format of the network backends, so that they travel without any system
+//! call but the doorbell telling the peer to read its ring.
+//!
+//! The transfers aren't zero-copy: the sender copies the payload of a work request straight from
+//! its guest memory into the ring, and the receiver copies it out of the ring before the device
+//! writes it to its own guest memory. Copying between the guest memories of the pair would need
+//! each microVM to map the guest memory of the other, and to check the remote accesses against
+//! the memory regions the other one registers, which this backend doesn't do. Like the other
+//! backends carrying the messages out of the device, it doesn't support RDMA reads and atomic
+//! operations either.
+//!
+//! The pair is negotiated through the doorbell sockets of its devices, bound next to the
+//! configured path: the first device to start binds `<path>.0.sock` and creates the memfd, the
+//! second one binds `<path>.1.sock` and says hello to the first one, which answers with the
+//! memfd. The first device also sends the memfd as it starts, to a second one which outlived the
+//! previous first one. The memfd is sealed against resizing, and the second device checks the
+//! seals before mapping it, so that its peer can't truncate the mapping under it.
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+use std::net::{IpAddr, Ipv6Addr};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use memfd::{FileSeal, Memfd, MemfdOptions, SealsHashSet};
+use serde::{Deserialize, Serialize};
+use vm_memory::{VolatileMemoryError, VolatileSlice, WriteVolatile};
+use vmm_sys_util::sock_ctrl_msg::ScmSocket;
+
+use super::packet::{KIND_DATA, Packet};
+use super::{RdmaBackend, RdmaMessage, socket_bound_at};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::{
+    RDMA_QPT_RC, RDMA_WC_LOC_LEN_ERR, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS,
+};
+use crate::logger::{debug, warn};
+use crate::vstate::memory::BitmapSlice;
+
+/// Length of the header of a ring, holding its producer and consumer indexes in distinct cache
+/// lines.
+const RING_HEADER_LEN: usize = 128;
+/// Length of the data of a ring.
+const RING_DATA_LEN: usize = 16 << 20;
+/// Length of the shared memory, holding a ring per direction.
+const SHM_LEN: usize = 2 * (RING_HEADER_LEN + RING_DATA_LEN);
+/// Length of the prefix of the records of the rings.
+const RECORD_PREFIX_LEN: usize = 4;
+/// How long the second device of a pair waits, as it starts, for the memfd of a running peer.
+const SHM_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Datagrams the devices of a pair send to the doorbell socket of each other: the doorbell tells
+// the peer to read its ring, the hello asks the first device for the memfd, which it sends along
+// with the shared memory datagram.
+const DATAGRAM_DOORBELL: u8 = 1;
+const DATAGRAM_HELLO: u8 = 2;
+const DATAGRAM_SHM: u8 = 3;
+
+/// Configuration of the shared memory backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShmemBackendConfig {
+    /// Path the doorbell sockets of the pair are bound next to, as `<path>.0.sock` and
+    /// `<path>.1.sock`.
+    pub path: PathBuf,
+    /// GID of the peer. IPv4 addresses stand for their IPv4-mapped GID.
+    pub peer_gid: IpAddr,
+}
+
+/// Error while setting up the shared memory backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ShmemBackendError {
+    /// Unable to bind the doorbell socket: {0}
+    Bind(io::Error),
+    /// Unable to set up the doorbell socket: {0}
+    Doorbell(io::Error),
+    /// Unable to create the shared memory: {0}
+    Memfd(memfd::Error),
+    /// Unable to size the shared memory: {0}
+    Resize(io::Error),
+    /// Shared memory isn't a memfd sealed against shrinking
+    NotSealed,
+    /// Unable to read the size of the shared memory: {0}
+    Stat(io::Error),
+    /// Shared memory of {0} bytes, expected {1}
+    InvalidSize(u64, usize),
+    /// Unable to map the shared memory: {0}
+    Mmap(io::Error),
+}
+
+/// Single-producer single-consumer ring of length-prefixed records. The indexes count the bytes
+/// written and read since the ring was created, and only wrap within the data.
+#[derive(Debug)]
+struct Ring {
+    // Start of the header of the ring, followed by its data.
+    base: *mut u8,
+    data_len: usize,
+}
+
+// SAFETY: The ring only accesses the memory it points to through atomics and volatile copies,
+// and the memory outlives it.
+unsafe impl Send for Ring {}
+
+impl Ring {
+    /// Creates the ring at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be 8-byte aligned, and point to `RING_HEADER_LEN + data_len` bytes which
+    /// outlive the ring, and which are only accessed by the other end of the ring otherwise.
+    unsafe fn new(base: *mut u8, data_len: usize) -> Self {
+        Self { base, data_len }
+    }
+
+    fn index(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: The header is aligned and large enough for the indexes, which are only
+        // accessed atomically.
+        unsafe { &*self.base.add(offset).cast::<AtomicU64>() }
+    }
+
+    // Index of the next byte the producer writes.
+    fn head(&self) -> &AtomicU64 {
+        self.index(0)
+    }
+
+    // Index of the next byte the consumer reads.
+    fn tail(&self) -> &AtomicU64 {
+        self.index(RING_HEADER_LEN / 2)
+    }
+
+    fn wrap(&self, index: u64) -> usize {
+        usize::try_from(index % self.data_len as u64).unwrap()
+    }
+
+    // Returns the `len` bytes of the data from `index` on, as the slice up to the end of the
+    // data, and the slice wrapping around to its start. The peer accesses the data concurrently,
+    // so it's only accessed through volatile slices.
+    fn slices(&self, index: u64, len: usize) -> (VolatileSlice<'_>, VolatileSlice<'_>) {
+        let offset = self.wrap(index);
+        let first = len.min(self.data_len - offset);
+        // SAFETY: The data follows the header of the ring, and outlives it.
+        let data = unsafe { VolatileSlice::new(self.base.add(RING_HEADER_LEN), self.data_len) };
+        // The records are shorter than the data, so they wrap around once at most.
+        (
+            data.subslice(offset, first).unwrap(),
+            data.subslice(0, len - first).unwrap(),
+        )
+    }
+
+    fn copy_in(&self, index: u64, bytes: &[u8]) {
+        let (end, start) = self.slices(index, bytes.len());
+        end.copy_from(&bytes[..end.len()]);
+        start.copy_from(&bytes[end.len()..]);
+    }
+
+    fn copy_out(&self, index: u64, bytes: &mut [u8]) {
+        let (end, start) = self.slices(index, bytes.len());
+        let first = end.len();
+        end.copy_to(&mut bytes[..first]);
+        start.copy_to(&mut bytes[first..]);
+    }
+
+    /// Largest record the ring holds.
+    fn max_record_len(&self) -> usize {
+        self.data_len - RECORD_PREFIX_LEN
+    }
+
+    // Copies the bytes of `payload` to the data of the ring, straight from guest memory.
+    fn copy_in_iov(&self, index: u64, payload: &IoVecBuffer) {
+        let mut writer = RingWriter { ring: self, index };
+        // The writer takes all the bytes of the guest memory it's given, which doesn't fail.
+        payload
+            .read_volatile_at(&mut writer, 0, payload.len() as usize)
+            .unwrap();
+    }
+
+    // Writes the prefix of a record of `len` bytes, returning the index of the record if it fits
+    // in the free space of the ring.
+    fn reserve(&self, len: usize) -> Option<u64> {
+        let head = self.head().load(Ordering::Relaxed);
+        let tail = self.tail().load(Ordering::Acquire);
+        let used = usize::try_from(head.wrapping_sub(tail)).unwrap_or(usize::MAX);
+        if used > self.data_len || RECORD_PREFIX_LEN + len > self.data_len - used {
+            return None;
+        }
+        self.copy_in(head, &u32::try_from(len).unwrap().to_le_bytes());
+        Some(head + RECORD_PREFIX_LEN as u64)
+    }
+
+    // Hands the record of `len` bytes at `index` to the consumer.
+    fn publish(&self, index: u64, len: usize) {
+        self.head().store(index + len as u64, Ordering::Release);
+    }
+
+    /// Writes a record, returning whether it fit in the free space of the ring.
+    fn push(&self, record: &[u8]) -> bool {
+        let Some(index) = self.reserve(record.len()) else {
+            return false;
+        };
+        self.copy_in(index, record);
+        self.publish(index, record.len());
+        true
+    }
+
+    /// Writes a record made of `header` followed by the bytes of `payload`, returning whether it
+    /// fit in the free space of the ring.
+    fn push_iov(&self, header: &[u8], payload: &IoVecBuffer) -> bool {
+        let len = header.len() + payload.len() as usize;
+        let Some(index) = self.reserve(len) else {
+            return false;
+        };
+        self.copy_in(index, header);
+        self.copy_in_iov(index + header.len() as u64, payload);
+        self.publish(index, len);
+        true
+    }
+
+    /// Reads the next record, if any. A ring corrupted by its producer is emptied.
+    fn pop(&self) -> Option<Vec<u8>> {
+        let tail = self.tail().load(Ordering::Relaxed);
+        let head = self.head().load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let used = usize::try_from(head.wrapping_sub(tail)).unwrap_or(usize::MAX);
+        let mut prefix = [0u8; RECORD_PREFIX_LEN];
+        self.copy_out(tail, &mut prefix);
+        let len = u32::from_le_bytes(prefix) as usize;
+        if used > self.data_len || used < RECORD_PREFIX_LEN || len > used - RECORD_PREFIX_LEN {
+            warn!("rdma: Dropped corrupted shared memory ring");
+            self.tail().store(head, Ordering::Release);
+            return None;
+        }
+        let mut record = vec![0u8; len];
+        self.copy_out(tail + RECORD_PREFIX_LEN as u64, &mut record);
+        self.tail()
+            .store(tail + (RECORD_PREFIX_LEN + len) as u64, Ordering::Release);
+        Some(record)
+    }
+}
+
+/// Writes the guest memory it's given to the data of a ring, from an index on.
+#[derive(Debug)]
+struct RingWriter<'a> {
+    ring: &'a Ring,
+    index: u64,
+}
+
+impl WriteVolatile for RingWriter<'_> {
+    fn write_volatile<B: BitmapSlice>(
+        &mut self,
+        buf: &VolatileSlice<B>,
+    ) -> Result<usize, VolatileMemoryError> {
+        let (end, start) = self.ring.slices(self.index, buf.len());
+        buf.subslice(0, end.len())?.copy_to_volatile_slice(end);
+        buf.offset(end.len())?.copy_to_volatile_slice(start);
+        self.index += buf.len() as u64;
+        Ok(buf.len())
+    }
+}
+
+/// Shared mapping of the memfd.
+#[derive(Debug)]
+struct SharedMemory {
+    addr: *mut u8,
+    len: usize,
+}
+
+// SAFETY: The mapping is owned by the backend, and only accessed through its rings.
+unsafe impl Send for SharedMemory {}
+
+impl SharedMemory {
+    fn map(file: &File, len: usize) -> io::Result<Self> {
+        // SAFETY: The file is valid, and the result is checked.
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            addr: addr.cast(),
+            len,
+        })
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        // SAFETY: The mapping was created by `map`, and the rings pointing to it are gone.
+        unsafe { libc::munmap(self.addr.cast(), self.len) };
+    }
+}
+
+/// Rings of a device, mapped from the memfd of its pair.
+#[derive(Debug)]
+struct SharedRings {
+    // The rings point to the shared memory, so they are declared, and dropped, first.
+    tx: Ring,
+    rx: Ring,
+    _shm: SharedMemory,
+    memfd: File,
+}
+
+impl SharedRings {
+    /// Creates the memfd of a pair, sealed against resizing.
+    fn create() -> Result<File, ShmemBackendError> {
+        let memfd = MemfdOptions::default()
+            .allow_sealing(true)
+            .create("fc_rdma_shm")
+            .map_err(ShmemBackendError::Memfd)?;
+        // The memfd is sized by writing to it, so that the filter of the backends doesn't allow
+        // `ftruncate`, which could truncate any file of the process.
+        io::copy(
+            &mut io::repeat(0).take(SHM_LEN as u64),
+            &mut memfd.as_file(),
+        )
+        .map_err(ShmemBackendError::Resize)?;
+        let mut seals = SealsHashSet::new();
+        seals.insert(FileSeal::SealShrink);
+        seals.insert(FileSeal::SealGrow);
+        seals.insert(FileSeal::SealSeal);
+        memfd.add_seals(&seals).map_err(ShmemBackendError::Memfd)?;
+        Ok(memfd.into_file())
+    }
+
+    /// Maps the rings of the device of `role` in the pair from `memfd`, once checked that the
+    /// memfd can't shrink under the mapping.
+    fn map(memfd: File, role: usize) -> Result<Self, ShmemBackendError> {
+        let memfd = Memfd::try_from_file(memfd).map_err(|_| ShmemBackendError::NotSealed)?;
+        let seals = memfd.seals().map_err(ShmemBackendError::Memfd)?;
+        if !seals.contains(&FileSeal::SealShrink) {
+            return Err(ShmemBackendError::NotSealed);
+        }
+        let memfd = memfd.into_file();
+        let len = memfd.metadata().map_err(ShmemBackendError::Stat)?.len();
+        if len != SHM_LEN as u64 {
+            return Err(ShmemBackendError::InvalidSize(len, SHM_LEN));
+        }
+        let shm = SharedMemory::map(&memfd, SHM_LEN).map_err(ShmemBackendError::Mmap)?;
+        let ring = |index: usize| {
+            // SAFETY: The mapping is page aligned, holds both rings, and outlives them. Only the
+            // peer accesses it otherwise.
+            unsafe {
+                Ring::new(
+                    shm.addr.add(index * (RING_HEADER_LEN + RING_DATA_LEN)),
+                    RING_DATA_LEN,
+                )
+            }
+        };
+        Ok(Self {
+            tx: ring(role),
+            rx: ring(1 - role),
+            _shm: shm,
+            memfd,
+        })
+    }
+}
+
+// Returns the path of the doorbell socket of the device of `role` in the pair of `path`.
+fn doorbell_path(path: &Path, role: usize) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(format!(".{role}.sock"));
+    PathBuf::from(path)
+}
+
+/// Binds the doorbell socket of the first device of the pair of `path`, or of the second one if
+/// the first one is bound already. Returns the socket, along with the role of the device.
+fn bind_doorbell(path: &Path) -> Result<(UnixDatagram, usize), ShmemBackendError> {
+    let bind = |role| UnixDatagram::bind(doorbell_path(path, role)).map(|socket| (socket, role));
+    match bind(0) {
+        Err(err) if err.kind() == ErrorKind::AddrInUse => bind(1),
+        bound => bound,
+    }
+    .map_err(ShmemBackendError::Bind)
+}
+
+/// Backend exchanging the messages of a pair of microVMs through shared memory.
+#[derive(Debug)]
+pub struct ShmemBackend {
+    // Rings of the device, once the first device of the pair shared its memfd.
+    rings: Option<SharedRings>,
+    // 0 for the first device of the pair, which creates the memfd, 1 for the second one.
+    role: usize,
+    peer_gid: [u8; 16],
+    doorbell: UnixDatagram,
+    doorbell_path: PathBuf,
+    peer_doorbell_path: PathBuf,
+    // Whether messages were written since the doorbell of the peer was last rung.
+    ring_peer: bool,
+    received: VecDeque<RdmaMessage>,
+}
+
+impl ShmemBackend {
+    /// Binds the doorbell socket, and sets up the shared memory of the pair: the first device
+    /// creates it, while the second one waits for it if its peer runs.
+    pub fn new(config: &ShmemBackendConfig) -> Result<Self, ShmemBackendError> {
+        let (doorbell, role) = bind_doorbell(&config.path)?;
+        // Dropping the backend removes its socket, should the rest of the setup fail.
+        let mut backend = Self {
+            rings: None,
+            role,
+            peer_gid: match config.peer_gid {
+                IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+                IpAddr::V6(ip) => ip.octets(),
+            },
+            doorbell,
+            doorbell_path: doorbell_path(&config.path, role),
+            peer_doorbell_path: doorbell_path(&config.path, 1 - role),
+            ring_peer: false,
+            received: VecDeque::new(),
+        };
+        backend
+            .doorbell
+            .set_nonblocking(true)
+            .map_err(ShmemBackendError::Doorbell)?;
+        if role == 0 {
+            backend.rings = Some(SharedRings::map(SharedRings::create()?, 0)?);
+            backend.send_shm();
+        } else if backend.signal(&backend.peer_doorbell_path, DATAGRAM_HELLO) {
+            backend
+                .wait_for_shm()
+                .map_err(ShmemBackendError::Doorbell)?;
+        }
+        // The messages written by the peer before the device started are read once it
+        // processes its own doorbell.
+        backend.signal(&backend.doorbell_path, DATAGRAM_DOORBELL);
+        Ok(backend)
+    }
+
+    // Sends a datagram of `kind` to the doorbell socket at `path`, returning whether it was sent.
+    fn signal(&self, path: &Path, kind: u8) -> bool {
+        // The doorbell only tells the peer to read its ring, so it may be lost when the peer
+        // isn't running yet, or when its doorbell was rung already.
+        self.doorbell
+            .send_to(&[kind], path)
+            .inspect_err(|err| debug!("rdma: Unable to signal {}: {err}", path.display()))
+            .is_ok()
+    }
+
+    // Sends the memfd of the pair to the second device, if it runs.
+    fn send_shm(&self) {
+        let Some(rings) = &self.rings else {
+            return;
+        };
+        // The doorbell socket isn't connected, so the memfd goes through a socket of its own.
+        let sent = UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(&self.peer_doorbell_path).map(|()| socket))
+            .and_then(|socket| {
+                socket
+                    .send_with_fd(&[DATAGRAM_SHM][..], rings.memfd.as_raw_fd())
+                    .map_err(|err| io::Error::from_raw_os_error(err.errno()))
+            });
+        if let Err(err) = sent {
+            debug!(
+                "rdma: Unable to send the shared memory to {}: {err}",
+                self.peer_doorbell_path.display()
+            );
+        }
+    }
+
+    // Waits for the memfd the first device of the pair sends in answer to the hello of the
+    // second one.
+    fn wait_for_shm(&mut self) -> io::Result<()> {
+        let deadline = Instant::now() + SHM_TIMEOUT;
+        self.doorbell.set_read_timeout(Some(SHM_TIMEOUT))?;
+        self.doorbell.set_nonblocking(false)?;
+        while self.rings.is_none() && Instant::now() < deadline && self.recv_datagram() {}
+        self.doorbell.set_nonblocking(true)
+    }
+
+    // Processes the next datagram of the doorbell socket, returning whether there was one.
+    fn recv_datagram(&mut self) -> bool {
+        let mut kind = [0u8; 1];
+        let Ok((_, memfd)) = self.doorbell.recv_with_fd(&mut kind) else {
+            return false;
+        };
+        match kind[0] {
+            DATAGRAM_HELLO if self.role == 0 => self.send_shm(),
+            // The memfd of a first device which replaced the previous one replaces its own.
+            DATAGRAM_SHM if self.role == 1 => match memfd.map(|memfd| SharedRings::map(memfd, 1)) {
+                Some(Ok(rings)) => self.rings = Some(rings),
+                Some(Err(err)) => warn!("rdma: Rejected the shared memory of the peer: {err}"),
+                None => debug!("rdma: Dropped shared memory datagram without memfd"),
+            },
+            _ => {}
+        }
+        true
+    }
+
+    // Writes `msg` to the ring of the peer, along with `payload` rather than its own payload if
+    // any, and returns the status of the work request which sent it.
+    fn send(&mut self, msg: RdmaMessage, payload: Option<&IoVecBuffer>) -> u32 {
+        // The messages of the unreliable queue pairs are simply lost.
+        let lost = if msg.qp_type == RDMA_QPT_RC {
+            RDMA_WC_RETRY_EXC_ERR
+        } else {
+            RDMA_WC_SUCCESS
+        };
+        if msg.dgid != self.peer_gid {
+            debug!("rdma: No peer owns GID {}", Ipv6Addr::from(msg.dgid));
+            return lost;
+        }
+        let Some(rings) = &self.rings else {
+            debug!("rdma: No shared memory with the peer yet");
+            return lost;
+        };
+        let packet = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        };
+        let header = packet.encode_header();
+        let payload_len =
+            payload.map_or(packet.msg.payload.len(), |payload| payload.len() as usize);
+        if header.len() + payload_len > rings.tx.max_record_len() {
+            return RDMA_WC_LOC_LEN_ERR;
+        }
+        let pushed = match payload {
+            Some(payload) => rings.tx.push_iov(&header, payload),
+            None => rings.tx.push(&packet.encode()),
+        };
+        if !pushed {
+            return lost;
+        }
+        self.ring_peer = true;
+        RDMA_WC_SUCCESS
+    }
+}
+
+impl Drop for ShmemBackend {
+    fn drop(&mut self) {
+        // Another device may then take the role of this one in the pair.
+        let _ = fs::remove_file(&self.doorbell_path);
+    }
+}
+
+impl RdmaBackend for ShmemBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        self.send(msg, None)
+    }
+
+    fn transmit_iov(&mut self, msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        self.send(msg, Some(&payload))
+    }
+
+    fn submit(&mut self) {
+        if std::mem::take(&mut self.ring_peer) {
+            self.signal(&self.peer_doorbell_path, DATAGRAM_DOORBELL);
+        }
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        vec![self.doorbell.as_raw_fd()]
+    }
+
+    fn process_event(&mut self) {
+        while self.recv_datagram() {}
+        let Some(rings) = &self.rings else {
+            return;
+        };
+        while let Some(record) = rings.rx.pop() {
+            match Packet::decode(&record) {
+                Some(packet) if packet.kind == KIND_DATA => self.received.push_back(packet.msg),
+                _ => debug!("rdma: Dropped malformed record"),
+            }
+        }
+    }
+
+    fn probe(&mut self) -> bool {
+        // The peer only rings the doorbell of the backend through the path of its socket.
+        matches!(self.doorbell.take_error(), Ok(None)) && socket_bound_at(&self.doorbell_path)
+    }
+
+    fn connect_qp(&mut self, _qpn: u32, dgid: [u8; 16]) -> bool {
+        // Ringing the doorbell of a peer which isn't running fails, while a running one merely
+        // reads its ring.
+        dgid == self.peer_gid
+            && self.rings.is_some()
+            && self
+                .doorbell
+                .send_to(&[DATAGRAM_DOORBELL], &self.peer_doorbell_path)
+                .is_ok()
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.received.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use vmm_sys_util::tempdir::TempDir;
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WR_SEND};
+
+    #[test]
+    fn test_ring() {
+        const DATA_LEN: usize = 64;
+        let mut mem = vec![0u64; (RING_HEADER_LEN + DATA_LEN) / 8];
+        // SAFETY: The buffer is aligned, large enough, and outlives the ring.
+        let ring = unsafe { Ring::new(mem.as_mut_ptr().cast(), DATA_LEN) };
+        assert_eq!(ring.pop(), None);
+
+        // The records wrap around the end of the data.
+        for round in 0..10u8 {
+            assert!(ring.push(&[round; 20]));
+            assert!(ring.push(&[round + 1; 30]));
+            assert!(!ring.push(&[0; 10]));
+            assert_eq!(ring.pop().unwrap(), [round; 20]);
+            assert_eq!(ring.pop().unwrap(), [round + 1; 30]);
+            assert_eq!(ring.pop(), None);
+        }
+        assert!(ring.push(&[7; DATA_LEN - RECORD_PREFIX_LEN]));
+        assert!(!ring.push(&[]));
+        assert_eq!(ring.pop().unwrap(), [7; DATA_LEN - RECORD_PREFIX_LEN]);
+
+        // The payloads read from guest memory wrap around as well.
+        let payload: Vec<u8> = (0..40).collect();
+        for _ in 0..4 {
+            assert!(ring.push_iov(&[0xff; 4], &IoVecBuffer::from(&payload[..])));
+            assert!(!ring.push_iov(&[], &IoVecBuffer::from(&payload[..])));
+            let record = ring.pop().unwrap();
+            assert_eq!(record[..4], [0xff; 4]);
+            assert_eq!(record[4..], payload);
+        }
+
+        // A corrupted ring is emptied.
+        assert!(ring.push(&[1; 8]));
+        ring.head().fetch_add(4, Ordering::Relaxed);
+        ring.copy_in(ring.tail().load(Ordering::Relaxed), &100u32.to_le_bytes());
+        assert_eq!(ring.pop(), None);
+        assert_eq!(
+            ring.head().load(Ordering::Relaxed),
+            ring.tail().load(Ordering::Relaxed)
+        );
+    }
+
+    #[test]
+    fn test_shmem_backend() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.as_path().join("rdma.shm");
+        let (ip_a, ip_b) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+        let config = |peer: Ipv4Addr| ShmemBackendConfig {
+            path: path.clone(),
+            peer_gid: IpAddr::V4(peer),
+        };
+        let message = |qp_type: u32, dst: Ipv4Addr, payload: &[u8]| RdmaMessage {
+            opcode: RDMA_WR_SEND,
+            qp_type,
+            src_qpn: 1,
+            dgid: dst.to_ipv6_mapped().octets(),
+            dest_qpn: 2,
+            payload: payload.to_vec(),
+            ..Default::default()
+        };
+
+        // The first device creates the shared memory, and sends messages before its peer starts.
+        let mut a = ShmemBackend::new(&config(ip_b)).unwrap();
+        assert_eq!(a.role, 0);
+        assert!(!a.is_local());
+        let early = message(RDMA_QPT_RC, ip_b, b"early");
+        assert_eq!(a.transmit(early.clone()), RDMA_WC_SUCCESS);
+        a.submit();
+
+        // The second device waits for the shared memory of the first one as it starts, and reads
+        // the messages written before it started.
+        let mut b = std::thread::scope(|scope| {
+            let b = scope.spawn(|| ShmemBackend::new(&config(ip_a)).unwrap());
+            while !b.is_finished() {
+                a.process_event();
+            }
+            b.join().unwrap()
+        });
+        assert_eq!(b.role, 1);
+        assert!(b.rings.is_some());
+        assert_eq!(b.events(), [b.doorbell.as_raw_fd()]);
+        b.process_event();
+        assert_eq!(b.receive().unwrap(), early);
+        assert!(b.receive().is_none());
+
+        // The doorbell of the peer is rung once per submission.
+        a.process_event();
+        assert!(a.receive().is_none());
+        let msgs = [
+            message(RDMA_QPT_UD, ip_a, b"datagram"),
+            message(RDMA_QPT_RC, ip_a, &vec![0xa5; 1 << 20]),
+        ];
+        for msg in &msgs {
+            assert_eq!(b.transmit(msg.clone()), RDMA_WC_SUCCESS);
+        }
+        b.submit();
+        b.submit();
+        let mut buf = [0u8; 1];
+        a.doorbell.recv(&mut buf).unwrap();
+        a.doorbell.recv(&mut buf).unwrap_err();
+        a.process_event();
+        assert_eq!(a.received, msgs);
+
+        // Messages to other GIDs, or larger than the ring, fail reliable work requests only.
+        let mut other = message(RDMA_QPT_RC, Ipv4Addr::new(10, 0, 0, 3), b"");
+        assert_eq!(a.transmit(other.clone()), RDMA_WC_RETRY_EXC_ERR);
+        other.qp_type = RDMA_QPT_UD;
+        assert_eq!(a.transmit(other), RDMA_WC_SUCCESS);
+        let too_large = message(RDMA_QPT_RC, ip_b, &vec![0; RING_DATA_LEN]);
+        assert_eq!(a.transmit(too_large), RDMA_WC_LOC_LEN_ERR);
+        let too_large = vec![0; RING_DATA_LEN];
+        let iov = IoVecBuffer::from(&too_large[..]);
+        assert_eq!(
+            a.transmit_iov(message(RDMA_QPT_RC, ip_b, b""), iov),
+            RDMA_WC_LOC_LEN_ERR
+        );
+
+        // The payloads of the work requests are written straight from guest memory.
+        let payload = vec![0x5a; 4096];
+        let iov = IoVecBuffer::from(&payload[..]);
+        assert_eq!(
+            a.transmit_iov(message(RDMA_QPT_RC, ip_b, b""), iov),
+            RDMA_WC_SUCCESS
+        );
+        a.submit();
+        b.process_event();
+        assert_eq!(b.receive().unwrap(), message(RDMA_QPT_RC, ip_b, &payload));
+
+        // A first device replacing the previous one sends its shared memory to the running
+        // second one.
+        drop(a);
+        let mut a = ShmemBackend::new(&config(ip_b)).unwrap();
+        assert_eq!(a.role, 0);
+        b.process_event();
+        assert_eq!(a.transmit(early.clone()), RDMA_WC_SUCCESS);
+        a.submit();
+        b.process_event();
+        assert_eq!(b.receive().unwrap(), early);
+
+        // Queue pairs reconnect on restore to the running peer only.
+        let gid_b = ip_b.to_ipv6_mapped().octets();
+        assert!(a.connect_qp(1, gid_b));
+        assert!(!a.connect_qp(1, ip_a.to_ipv6_mapped().octets()));
+
+        // A device fails its probe once its doorbell socket is removed from its path.
+        assert!(b.probe());
+        fs::remove_file(doorbell_path(&path, 1)).unwrap();
+        assert!(!b.probe());
+        assert!(!a.connect_qp(1, gid_b));
+
+        // The devices remove their doorbell socket.
+        drop(b);
+        drop(a);
+        assert!(!doorbell_path(&path, 0).exists());
+    }
+
+    #[test]
+    fn test_shared_rings() {
+        // The memfd of the pair can't be resized.
+        let memfd = SharedRings::create().unwrap();
+        memfd.set_len(0).unwrap_err();
+        let rings = SharedRings::map(memfd.try_clone().unwrap(), 1).unwrap();
+        let peer_rings = SharedRings::map(memfd, 0).unwrap();
+        assert!(peer_rings.tx.push(b"record"));
+        assert_eq!(rings.rx.pop().unwrap(), b"record");
+
+        // The memfds which could shrink under the mapping, or of another size, are rejected.
+        let file = TempFile::new().unwrap().into_file();
+        file.set_len(SHM_LEN as u64).unwrap();
+        assert!(matches!(
+            SharedRings::map(file, 1),
+            Err(ShmemBackendError::NotSealed)
+        ));
+        let memfd = MemfdOptions::default()
+            .allow_sealing(true)
+            .create("test")
+            .unwrap();
+        memfd.as_file().set_len(SHM_LEN as u64).unwrap();
+        assert!(matches!(
+            SharedRings::map(memfd.into_file(), 1),
+            Err(ShmemBackendError::NotSealed)
+        ));
+        let memfd = MemfdOptions::default()
+            .allow_sealing(true)
+            .create("test")
+            .unwrap();
+        memfd.add_seal(FileSeal::SealShrink).unwrap();
+        assert!(matches!(
+            SharedRings::map(memfd.into_file(), 1),
+            Err(ShmemBackendError::InvalidSize(0, SHM_LEN))
+        ));
+    }
+}
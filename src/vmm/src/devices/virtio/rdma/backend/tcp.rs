@@ -0,0 +1,701 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backend tunnelling the messages over TCP, for the networks where UDP is blocked. The backend
+//! listens on its port, and keeps a single connection with each remote peer, over which the
+//! messages of all the queue pairs travel as packets of the wire format shared with the UDP
+//! backend, each prefixed with its big-endian 32-bit length. The peers are reached at the IP
+//! address of the destination GID of the messages, on the port of the backend.
+//!
+//! The packets are written straight from guest memory when nothing is queued on their connection,
+//! the bytes the socket doesn't take being queued until it is writable.
+//!
+//! The stream being reliable and ordered, the messages are neither numbered nor acknowledged.
+//! When a connection breaks, the reliable connected queue pairs which sent messages over it are
+//! reported as failed, since their last messages may have been lost.
+//!
+//! Connections come and go, so their sockets are polled under a nested epoll FD, registered with
+//! the event manager of the device.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+use serde::{Deserialize, Serialize};
+use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
+
+use super::packet::{HEADER_LEN, KIND_DATA, Packet, peer_ip};
+use super::{RdmaBackend, RdmaMessage, copy_iov, send_iov};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::uring::sockaddr;
+use crate::devices::virtio::rdma::{
+    RDMA_MAX_MSG_SIZE, RDMA_QPT_RC, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS,
+};
+use crate::logger::{debug, warn};
+
+/// Port the backend listens on unless configured otherwise, the one of the UDP backend.
+pub const TCP_BACKEND_DEFAULT_PORT: u16 = super::udp::UDP_BACKEND_DEFAULT_PORT;
+
+/// Length of the prefix of the packets.
+const FRAME_PREFIX_LEN: usize = 4;
+/// Largest packet, carrying the largest message.
+const MAX_PACKET_LEN: usize = HEADER_LEN + RDMA_MAX_MSG_SIZE as usize;
+/// Number of bytes queued on a connection past which the work requests sending on it fail.
+const MAX_QUEUED_LEN: usize = 64 << 20;
+/// Number of bytes read from a connection at once.
+const READ_CHUNK_LEN: usize = 64 << 10;
+/// Number of epoll events processed at once.
+const EPOLL_EVENTS_LEN: usize = 32;
+// Epoll data of the listening socket. The connections use their file descriptor.
+const LISTENER: u64 = u64::MAX;
+
+fn default_port() -> u16 {
+    TCP_BACKEND_DEFAULT_PORT
+}
+
+/// Configuration of the TCP backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TcpBackendConfig {
+    /// IP address the backend listens on, and connects from. When it is an IPv4 address, the
+    /// peers are reached at the IPv4 address mapped in the destination GID of the messages.
+    pub bind_ip: IpAddr,
+    /// TCP port of the backend and of its peers.
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+/// Error while setting up the TCP backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum TcpBackendError {
+    /// Unable to listen on {0}: {1}
+    Listen(SocketAddr, io::Error),
+    /// Unable to create the epoll FD: {0}
+    EpollCreate(io::Error),
+    /// Unable to register the listening socket under the epoll FD: {0}
+    EpollAdd(io::Error),
+}
+
+/// Decodes the complete packets at the start of `rx`, and queues the messages they carry to
+/// `received`.
+fn parse_frames(rx: &mut Vec<u8>, received: &mut VecDeque<RdmaMessage>) -> io::Result<()> {
+    let mut offset = 0;
+    while let Some(prefix) = rx.get(offset..offset + FRAME_PREFIX_LEN) {
+        let len = u32::from_be_bytes(prefix.try_into().unwrap()) as usize;
+        if len > MAX_PACKET_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "packet too large"));
+        }
+        let start = offset + FRAME_PREFIX_LEN;
+        let Some(buf) = rx.get(start..start + len) else {
+            break;
+        };
+        match Packet::decode(buf) {
+            Some(packet) if packet.kind == KIND_DATA => received.push_back(packet.msg),
+            _ => debug!("rdma: Dropped malformed packet"),
+        }
+        offset = start + len;
+    }
+    rx.drain(..offset);
+    Ok(())
+}
+
+/// Connection with a peer.
+#[derive(Debug)]
+struct Connection {
+    stream: TcpStream,
+    peer: IpAddr,
+    // Whether the connection is still being established.
+    connecting: bool,
+    // Bytes waiting for the socket to be writable.
+    tx: VecDeque<u8>,
+    // Bytes of the packets not received entirely yet.
+    rx: Vec<u8>,
+    // Reliable connected queue pairs which sent messages over the connection.
+    rc_qpns: BTreeSet<u32>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream, peer: IpAddr, connecting: bool) -> Self {
+        Self {
+            stream,
+            peer,
+            connecting,
+            tx: VecDeque::new(),
+            rx: Vec::new(),
+            rc_qpns: BTreeSet::new(),
+        }
+    }
+
+    /// Events the socket is polled for.
+    fn interest(&self) -> EventSet {
+        if self.connecting || !self.tx.is_empty() {
+            EventSet::IN | EventSet::OUT
+        } else {
+            EventSet::IN
+        }
+    }
+
+    /// Writes the queued bytes until the socket would block.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.connecting && !self.tx.is_empty() {
+            let (buf, _) = self.tx.as_slices();
+            match self.stream.write(buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => drop(self.tx.drain(..len)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the bytes received until the socket would block, queueing the messages received to
+    /// `received`. Returns whether the connection is still open.
+    fn read(&mut self, received: &mut VecDeque<RdmaMessage>) -> io::Result<bool> {
+        let mut buf = vec![0u8; READ_CHUNK_LEN];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(len) => self.rx.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        parse_frames(&mut self.rx, received)?;
+        Ok(true)
+    }
+
+    /// Processes the events the socket is ready for. Returns whether the connection is still
+    /// open.
+    fn process(
+        &mut self,
+        evset: EventSet,
+        received: &mut VecDeque<RdmaMessage>,
+    ) -> io::Result<bool> {
+        if self.connecting {
+            if !evset.intersects(EventSet::OUT | EventSet::ERROR | EventSet::HANG_UP) {
+                return Ok(true);
+            }
+            if let Some(err) = self.stream.take_error()? {
+                return Err(err);
+            }
+            self.connecting = false;
+        }
+        if !self.read(received)? {
+            return Ok(false);
+        }
+        self.flush()?;
+        Ok(true)
+    }
+}
+
+/// Backend tunnelling the messages over TCP connections.
+#[derive(Debug)]
+pub struct TcpBackend {
+    listener: TcpListener,
+    local_addr: SocketAddr,
+    epoll: Epoll,
+    // Connections, by file descriptor.
+    conns: HashMap<RawFd, Connection>,
+    // Connection carrying the messages sent to each peer.
+    peers: HashMap<IpAddr, RawFd>,
+    received: VecDeque<RdmaMessage>,
+    failed_qps: Vec<u32>,
+}
+
+impl TcpBackend {
+    /// Starts listening for the connections of the peers.
+    pub fn new(config: &TcpBackendConfig) -> Result<Self, TcpBackendError> {
+        let addr = SocketAddr::new(config.bind_ip, config.port);
+        let listen = |err| TcpBackendError::Listen(addr, err);
+        let listener = TcpListener::bind(addr).map_err(listen)?;
+        listener.set_nonblocking(true).map_err(listen)?;
+        let local_addr = listener.local_addr().map_err(listen)?;
+        let epoll = Epoll::new().map_err(TcpBackendError::EpollCreate)?;
+        epoll
+            .ctl(
+                ControlOperation::Add,
+                listener.as_raw_fd(),
+                EpollEvent::new(EventSet::IN, LISTENER),
+            )
+            .map_err(TcpBackendError::EpollAdd)?;
+        Ok(Self {
+            listener,
+            local_addr,
+            epoll,
+            conns: HashMap::new(),
+            peers: HashMap::new(),
+            received: VecDeque::new(),
+            failed_qps: Vec::new(),
+        })
+    }
+
+    /// Address the backend listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn add_connection(&mut self, conn: Connection) -> io::Result<RawFd> {
+        let fd = conn.stream.as_raw_fd();
+        self.epoll.ctl(
+            ControlOperation::Add,
+            fd,
+            EpollEvent::new(conn.interest(), u64::try_from(fd).unwrap()),
+        )?;
+        self.conns.insert(fd, conn);
+        Ok(fd)
+    }
+
+    /// Starts connecting to `peer`, returning the file descriptor of the connection.
+    fn connect(&mut self, peer: IpAddr) -> io::Result<RawFd> {
+        let domain = if peer.is_ipv4() {
+            libc::AF_INET
+        } else {
+            libc::AF_INET6
+        };
+        // SAFETY: The arguments are valid constants, and the return value is checked.
+        let fd = unsafe {
+            libc::socket(
+                domain,
+                libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: The socket was just created, and nothing else owns it.
+        let stream = TcpStream::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        // The peer sends its messages over the connection to the address of the GID of the
+        // device, which the connection must come from.
+        let (addr, len) = sockaddr(SocketAddr::new(self.local_addr.ip(), 0));
+        // SAFETY: The socket is valid, and `addr` holds an address of `len` bytes.
+        if unsafe { libc::bind(fd, (&raw const addr).cast(), len) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (addr, len) = sockaddr(SocketAddr::new(peer, self.local_addr.port()));
+        // SAFETY: The socket is valid, and `addr` holds an address of `len` bytes.
+        if unsafe { libc::connect(fd, (&raw const addr).cast(), len) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINPROGRESS) {
+                return Err(err);
+            }
+        }
+        stream.set_nodelay(true)?;
+        self.add_connection(Connection::new(stream, peer, true))
+    }
+
+    /// Returns the file descriptor of the connection to `peer`, connecting to it first if there
+    /// is none.
+    fn peer_connection(&mut self, peer: IpAddr) -> io::Result<RawFd> {
+        match self.peers.get(&peer) {
+            Some(&fd) => Ok(fd),
+            None => {
+                let fd = self.connect(peer)?;
+                Ok(*self.peers.entry(peer).or_insert(fd))
+            }
+        }
+    }
+
+    fn accept(&mut self) {
+        loop {
+            let (stream, addr) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("rdma: Unable to accept connection: {err}");
+                    break;
+                }
+            };
+            let peer = addr.ip();
+            let added = stream
+                .set_nonblocking(true)
+                .and_then(|()| stream.set_nodelay(true))
+                .and_then(|()| self.add_connection(Connection::new(stream, peer, false)));
+            match added {
+                // The messages to the peer go over the connection it opened, unless both
+                // connected to each other at once.
+                Ok(fd) => {
+                    self.peers.entry(peer).or_insert(fd);
+                }
+                Err(err) => warn!("rdma: Unable to add connection from {peer}: {err}"),
+            }
+        }
+    }
+
+    fn update_interest(&self, fd: RawFd) {
+        let conn = &self.conns[&fd];
+        if let Err(err) = self.epoll.ctl(
+            ControlOperation::Modify,
+            fd,
+            EpollEvent::new(conn.interest(), u64::try_from(fd).unwrap()),
+        ) {
+            warn!("rdma: Unable to poll connection to {}: {err}", conn.peer);
+        }
+    }
+
+    fn close_connection(&mut self, fd: RawFd, err: Option<io::Error>) {
+        let Some(conn) = self.conns.remove(&fd) else {
+            return;
+        };
+        if let Err(err) = self
+            .epoll
+            .ctl(ControlOperation::Delete, fd, EpollEvent::default())
+        {
+            warn!(
+                "rdma: Unable to stop polling connection to {}: {err}",
+                conn.peer
+            );
+        }
+        if self.peers.get(&conn.peer) == Some(&fd) {
+            self.peers.remove(&conn.peer);
+        }
+        match err {
+            Some(err) => warn!("rdma: Connection to {} broke: {err}", conn.peer),
+            None => debug!("rdma: Connection to {} closed", conn.peer),
+        }
+        self.failed_qps.extend(conn.rc_qpns);
+    }
+
+    /// Returns the file descriptor of the connection carrying `msg` to its destination, or the
+    /// `RDMA_WC_*` status of the work request which sent it if the message can't be queued.
+    fn connection(&mut self, msg: &RdmaMessage) -> Result<RawFd, u32> {
+        // The messages of the unreliable queue pairs are simply lost.
+        let lost = if msg.qp_type == RDMA_QPT_RC {
+            RDMA_WC_RETRY_EXC_ERR
+        } else {
+            RDMA_WC_SUCCESS
+        };
+        let Some(peer) = peer_ip(msg.dgid, self.local_addr.ip()) else {
+            debug!("rdma: Unreachable GID {}", Ipv6Addr::from(msg.dgid));
+            return Err(lost);
+        };
+        let fd = self.peer_connection(peer).map_err(|err| {
+            warn!("rdma: Unable to connect to {peer}: {err}");
+            lost
+        })?;
+        let conn = self.conns.get_mut(&fd).unwrap();
+        if conn.tx.len() >= MAX_QUEUED_LEN {
+            return Err(lost);
+        }
+        if msg.qp_type == RDMA_QPT_RC {
+            conn.rc_qpns.insert(msg.src_qpn);
+        }
+        Ok(fd)
+    }
+}
+
+impl RdmaBackend for TcpBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        let fd = match self.connection(&msg) {
+            Ok(fd) => fd,
+            Err(status) => return status,
+        };
+        let conn = self.conns.get_mut(&fd).unwrap();
+        let packet = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        }
+        .encode();
+        conn.tx
+            .extend(u32::try_from(packet.len()).unwrap().to_be_bytes());
+        conn.tx.extend(packet);
+        RDMA_WC_SUCCESS
+    }
+
+    fn transmit_iov(&mut self, msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        let fd = match self.connection(&msg) {
+            Ok(fd) => fd,
+            Err(status) => return status,
+        };
+        let conn = self.conns.get_mut(&fd).unwrap();
+        let header = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        }
+        .encode_header();
+        let packet_len = header.len() + payload.len() as usize;
+        let mut frame = Vec::with_capacity(FRAME_PREFIX_LEN + header.len());
+        frame.extend(u32::try_from(packet_len).unwrap().to_be_bytes());
+        frame.extend(header);
+        // The bytes queued before go first. The errors of the socket are reported once the
+        // queued bytes are flushed.
+        let sent = if conn.connecting || !conn.tx.is_empty() {
+            0
+        } else {
+            send_iov(fd, None, &frame, &payload, libc::MSG_NOSIGNAL).unwrap_or(0)
+        };
+        conn.tx.extend(frame.iter().skip(sent));
+        conn.tx
+            .extend(copy_iov(&payload, sent.saturating_sub(frame.len())));
+        RDMA_WC_SUCCESS
+    }
+
+    fn submit(&mut self) {
+        let pending: Vec<RawFd> = self
+            .conns
+            .iter()
+            .filter(|(_, conn)| !conn.tx.is_empty())
+            .map(|(&fd, _)| fd)
+            .collect();
+        for fd in pending {
+            match self.conns.get_mut(&fd).unwrap().flush() {
+                Ok(()) => self.update_interest(fd),
+                Err(err) => self.close_connection(fd, Some(err)),
+            }
+        }
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        vec![self.epoll.as_raw_fd()]
+    }
+
+    fn process_event(&mut self) {
+        let mut events = vec![EpollEvent::new(EventSet::empty(), 0); EPOLL_EVENTS_LEN];
+        let count = match self.epoll.wait(0, &mut events) {
+            Ok(count) => count,
+            Err(err) => {
+                warn!("rdma: Unable to consume backend epoll event: {err}");
+                return;
+            }
+        };
+        for event in &events[..count] {
+            if event.data() == LISTENER {
+                self.accept();
+                continue;
+            }
+            let fd = RawFd::try_from(event.data()).unwrap();
+            // The connection may have been closed by a previous event.
+            let Some(conn) = self.conns.get_mut(&fd) else {
+                continue;
+            };
+            // The events are filled in by the kernel, and only hold valid flags.
+            let evset = EventSet::from_bits(event.events).unwrap();
+            match conn.process(evset, &mut self.received) {
+                Ok(true) => self.update_interest(fd),
+                Ok(false) => self.close_connection(fd, None),
+                Err(err) => self.close_connection(fd, Some(err)),
+            }
+        }
+    }
+
+    fn take_failed_qps(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.failed_qps)
+    }
+
+    fn probe(&mut self) -> bool {
+        // The connections which fail are set up again by the next message to their peer.
+        matches!(self.listener.take_error(), Ok(None))
+    }
+
+    fn connect_qp(&mut self, qpn: u32, dgid: [u8; 16]) -> bool {
+        let Some(peer) = peer_ip(dgid, self.local_addr.ip()) else {
+            debug!("rdma: Unreachable GID {}", Ipv6Addr::from(dgid));
+            return false;
+        };
+        let fd = match self.peer_connection(peer) {
+            Ok(fd) => fd,
+            Err(err) => {
+                warn!("rdma: Unable to connect to {peer}: {err}");
+                return false;
+            }
+        };
+        // The queue pair fails along with the connection, should it not be established.
+        self.conns.get_mut(&fd).unwrap().rc_qpns.insert(qpn);
+        true
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.received.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WR_SEND};
+
+    fn backend(ip: Ipv4Addr, port: u16) -> TcpBackend {
+        TcpBackend::new(&TcpBackendConfig {
+            bind_ip: IpAddr::V4(ip),
+            port,
+        })
+        .unwrap()
+    }
+
+    /// Processes the events of `backends` until `done` holds.
+    fn wait(backends: &mut [&mut TcpBackend], done: impl Fn(&[&mut TcpBackend]) -> bool) {
+        // The tests fail rather than hang if the messages never come.
+        for _ in 0..200 {
+            if done(backends) {
+                return;
+            }
+            for backend in backends.iter_mut() {
+                backend.process_event();
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out waiting for the backends");
+    }
+
+    fn message(qp_type: u32, src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8]) -> RdmaMessage {
+        RdmaMessage {
+            opcode: RDMA_WR_SEND,
+            qp_type,
+            src_qpn: 1,
+            sgid: src.to_ipv6_mapped().octets(),
+            dgid: dst.to_ipv6_mapped().octets(),
+            dest_qpn: 2,
+            payload: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn frame(msg: &RdmaMessage) -> Vec<u8> {
+        let packet = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg: msg.clone(),
+        }
+        .encode();
+        let mut frame = u32::try_from(packet.len()).unwrap().to_be_bytes().to_vec();
+        frame.extend(packet);
+        frame
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let ip = Ipv4Addr::LOCALHOST;
+        let first = message(RDMA_QPT_RC, ip, ip, b"first");
+        let second = message(RDMA_QPT_UD, ip, ip, b"second");
+        let mut stream = frame(&first);
+        stream.extend(frame(&second));
+
+        // The packets are decoded once received entirely.
+        let mut received = VecDeque::new();
+        let mut rx = Vec::new();
+        let split = stream.len() - 3;
+        rx.extend_from_slice(&stream[..2]);
+        parse_frames(&mut rx, &mut received).unwrap();
+        assert!(received.is_empty());
+        assert_eq!(rx.len(), 2);
+        rx.extend_from_slice(&stream[2..split]);
+        parse_frames(&mut rx, &mut received).unwrap();
+        assert_eq!(received, [first.clone()]);
+        rx.extend_from_slice(&stream[split..]);
+        parse_frames(&mut rx, &mut received).unwrap();
+        assert_eq!(received, [first, second]);
+        assert!(rx.is_empty());
+
+        // Malformed packets are dropped, while oversized ones break the connection.
+        rx.extend_from_slice(&4u32.to_be_bytes());
+        rx.extend_from_slice(b"junk");
+        parse_frames(&mut rx, &mut received).unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(rx.is_empty());
+        rx.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            parse_frames(&mut rx, &mut received).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_tcp_backend() {
+        let (ip_a, ip_b) = (Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2));
+        let mut a = backend(ip_a, 0);
+        let port = a.local_addr().port();
+        let mut b = backend(ip_b, port);
+        assert_eq!(a.events().len(), 1);
+        assert!(!a.is_local());
+        assert!(a.probe());
+
+        // The messages of all the queue pairs go over a single connection, in order.
+        let msgs = [
+            message(RDMA_QPT_UD, ip_a, ip_b, b"datagram"),
+            message(RDMA_QPT_RC, ip_a, ip_b, b"first"),
+            message(RDMA_QPT_RC, ip_a, ip_b, &vec![0xa5; 3 * READ_CHUNK_LEN]),
+        ];
+        for msg in &msgs {
+            assert_eq!(a.transmit(msg.clone()), RDMA_WC_SUCCESS);
+        }
+        a.submit();
+        assert_eq!(a.conns.len(), 1);
+        wait(&mut [&mut a, &mut b], |backends| {
+            backends[1].received.len() == 3
+        });
+        assert_eq!(b.received, msgs);
+        b.received.clear();
+
+        // The peer answers over the connection it accepted.
+        let reply = message(RDMA_QPT_RC, ip_b, ip_a, b"reply");
+        assert_eq!(b.transmit(reply.clone()), RDMA_WC_SUCCESS);
+        b.submit();
+        assert_eq!(b.conns.len(), 1);
+        wait(&mut [&mut a, &mut b], |backends| {
+            !backends[0].received.is_empty()
+        });
+        assert_eq!(a.receive().unwrap(), reply);
+        assert_eq!(a.conns.len(), 1);
+
+        // The payload of the work requests is written straight from guest memory, the bytes the
+        // socket doesn't take being queued after it.
+        let payload = vec![0x5a; 4 << 20];
+        let msg = message(RDMA_QPT_RC, ip_a, ip_b, b"");
+        let iov = IoVecBuffer::from(vec![&payload[..1 << 20], &payload[1 << 20..]]);
+        assert_eq!(a.transmit_iov(msg.clone(), iov), RDMA_WC_SUCCESS);
+        let iov = IoVecBuffer::from(&payload[..16]);
+        assert_eq!(a.transmit_iov(msg.clone(), iov), RDMA_WC_SUCCESS);
+        a.submit();
+        wait(&mut [&mut a, &mut b], |backends| {
+            backends[1].received.len() == 2
+        });
+        assert_eq!(
+            b.received,
+            [
+                RdmaMessage {
+                    payload: payload.clone(),
+                    ..msg.clone()
+                },
+                RdmaMessage {
+                    payload: payload[..16].to_vec(),
+                    ..msg
+                }
+            ]
+        );
+        b.received.clear();
+
+        // Messages to GIDs the socket can't reach fail reliable work requests only.
+        let mut unreachable = message(RDMA_QPT_RC, ip_a, ip_b, b"");
+        unreachable.dgid = [0xfe; 16];
+        assert_eq!(a.transmit(unreachable.clone()), RDMA_WC_RETRY_EXC_ERR);
+        unreachable.qp_type = RDMA_QPT_UD;
+        assert_eq!(a.transmit(unreachable), RDMA_WC_SUCCESS);
+
+        // The reliable connected queue pairs which sent messages over a broken connection fail,
+        // and so do the ones reconnected to their peer over it on restore.
+        assert!(a.connect_qp(2, msgs[1].dgid));
+        assert!(!a.connect_qp(3, [0xfe; 16]));
+        assert!(a.take_failed_qps().is_empty());
+        drop(b);
+        wait(&mut [&mut a], |backends| backends[0].conns.is_empty());
+        assert_eq!(a.take_failed_qps(), [1, 2]);
+        assert!(a.peers.is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backends performing host I/O run on a thread of their own, under the seccomp filter of the
+//! `rdma` thread category, so that the system calls they need, such as binding sockets or
+//! creating shared memory, aren't allowed to the VMM thread.
+//!
+//! The device calls the backend through a proxy, which forwards each call to the thread and
+//! waits for its result. The messages received and the queue pairs which failed meanwhile are
+//! sent back along with the result, so that the device takes them without a round trip.
+//!
+//! A thread inherits the filters of the thread creating it, and the filters installed on a
+//! thread add up, so the threads of the backends can't be created by the VMM thread once its own
+//! filter is installed. Until the microVM starts, the threads are created by the VMM thread and
+//! run without a filter, like it, until they are given theirs. From then on, they are created by
+//! a launcher, a thread spawned before the VMM thread installs its filter, which runs under the
+//! filter of the `rdma_launcher` thread category. Since its threads inherit that filter, it allows
+//! the system calls of the backends along with the ones creating threads, and the threads install
+//! the filter of the backends before setting them up, which leaves them without the latter.
+
+use std::collections::VecDeque;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender, SyncSender, channel, sync_channel};
+use std::thread::{self, JoinHandle};
+
+use super::{RdmaBackend, RdmaBackendError, RdmaMessage};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::seccomp::{BpfProgram, InstallationError, apply_filter};
+
+// Name of the threads of the backends.
+const THREAD_NAME: &str = "fc_rdma_backend";
+
+// Calls forwarded to the thread of the backend.
+#[derive(Debug)]
+enum Request {
+    Transmit(RdmaMessage),
+    // The proxy waits for the outcome, so the guest memory the payload is read from outlives the
+    // call.
+    TransmitIov(RdmaMessage, IoVecBuffer),
+    Submit,
+    ProcessEvent,
+    Probe,
+    ConnectQp(u32, [u8; 16]),
+    ApplySeccompFilter(Arc<BpfProgram>),
+}
+
+// Results of the calls.
+#[derive(Debug)]
+enum Outcome {
+    // `RDMA_WC_*` status of the work request which sent the message.
+    Transmitted(u32),
+    Done,
+    // Whether the backend can still carry the messages.
+    Probed(bool),
+    // Whether the peer of the queue pair can be reached.
+    Connected(bool),
+    SeccompFilterApplied(Result<(), InstallationError>),
+}
+
+// Result of a call, along with what the backend made available to the device meanwhile.
+#[derive(Debug)]
+struct Reply {
+    outcome: Outcome,
+    received: Vec<RdmaMessage>,
+    failed_qps: Vec<u32>,
+}
+
+// Body of a thread to spawn, along with where to send its handle.
+type Launch = (
+    Box<dyn FnOnce() + Send>,
+    SyncSender<io::Result<JoinHandle<()>>>,
+);
+
+/// Creates the threads of the backends set up once the microVM started, under the seccomp filter
+/// of the `rdma` thread category.
+#[derive(Debug)]
+pub struct BackendLauncher {
+    // Dropped first, which stops the thread.
+    requests: Option<Sender<Launch>>,
+    thread: Option<JoinHandle<()>>,
+    seccomp_filter: Arc<BpfProgram>,
+}
+
+impl BackendLauncher {
+    /// Spawns the launcher, once it installed `launcher_filter`, its threads installing
+    /// `seccomp_filter` before setting up their backend. The filter of the launcher must allow
+    /// the system calls of the backends, which its threads inherit. An empty filter installs none.
+    /// The launcher must be spawned before the VMM thread installs its own filter.
+    pub fn new(
+        launcher_filter: Arc<BpfProgram>,
+        seccomp_filter: Arc<BpfProgram>,
+    ) -> Result<Self, RdmaBackendError> {
+        let (requests, requests_rx) = channel::<Launch>();
+        let (setup_tx, setup) = sync_channel(1);
+        let filter = launcher_filter;
+        let thread = thread::Builder::new()
+            .name("fc_rdma_launcher".into())
+            .spawn(move || {
+                if let Err(err) = apply_filter(&filter) {
+                    let _ = setup_tx.send(Err(err));
+                    return;
+                }
+                let _ = setup_tx.send(Ok(()));
+                for (body, reply) in requests_rx {
+                    let _ = reply.send(thread::Builder::new().name(THREAD_NAME.into()).spawn(body));
+                }
+            })
+            .map_err(RdmaBackendError::Thread)?;
+        // The thread only exits without sending the outcome of the setup if it panicked.
+        let setup = setup.recv().expect("rdma: The launcher thread panicked");
+        if let Err(err) = setup {
+            let _ = thread.join();
+            return Err(RdmaBackendError::Seccomp(err));
+        }
+        Ok(Self {
+            requests: Some(requests),
+            thread: Some(thread),
+            seccomp_filter,
+        })
+    }
+
+    /// Seccomp filter the threads of the backends run under.
+    pub fn seccomp_filter(&self) -> &Arc<BpfProgram> {
+        &self.seccomp_filter
+    }
+
+    // Spawns a thread running `body`.
+    fn spawn(&self, body: Box<dyn FnOnce() + Send>) -> io::Result<JoinHandle<()>> {
+        let (reply, handle) = sync_channel(1);
+        // The thread only stops serving the requests once the launcher is dropped.
+        self.requests
+            .as_ref()
+            .expect("rdma: The launcher thread is stopped")
+            .send((body, reply))
+            .expect("rdma: The launcher thread panicked");
+        handle.recv().expect("rdma: The launcher thread panicked")
+    }
+}
+
+impl Drop for BackendLauncher {
+    fn drop(&mut self) {
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Proxy of a backend running on a thread of its own.
+#[derive(Debug)]
+pub struct ThreadedBackend {
+    // Dropped first, which stops the thread.
+    requests: Option<Sender<Request>>,
+    replies: Receiver<Reply>,
+    thread: Option<JoinHandle<()>>,
+    // The events and the locality of the backend don't change once it is set up.
+    events: Vec<RawFd>,
+    is_local: bool,
+    received: VecDeque<RdmaMessage>,
+    failed_qps: Vec<u32>,
+}
+
+impl ThreadedBackend {
+    /// Spawns the thread of the backend `build` sets up, through `launcher` if any, in which case
+    /// the thread installs the filter of the backends first. Without a launcher, the thread is
+    /// created by the calling thread, and runs under its filters.
+    pub fn spawn<F>(launcher: Option<&BackendLauncher>, build: F) -> Result<Self, RdmaBackendError>
+    where
+        F: FnOnce() -> Result<Box<dyn RdmaBackend>, RdmaBackendError> + Send + 'static,
+    {
+        let (requests, requests_rx) = channel();
+        let (replies_tx, replies) = sync_channel(1);
+        let (setup_tx, setup) = sync_channel(1);
+        let filter = launcher.map(|launcher| launcher.seccomp_filter().clone());
+        let body = move || {
+            if let Some(filter) = filter
+                && let Err(err) = apply_filter(&filter)
+            {
+                let _ = setup_tx.send(Err(RdmaBackendError::Seccomp(err)));
+                return;
+            }
+            match build() {
+                Ok(backend) => {
+                    let _ = setup_tx.send(Ok((backend.events(), backend.is_local())));
+                    serve(backend, &requests_rx, &replies_tx);
+                }
+                Err(err) => {
+                    let _ = setup_tx.send(Err(err));
+                }
+            }
+        };
+        let thread = match launcher {
+            Some(launcher) => launcher.spawn(Box::new(body)),
+            None => thread::Builder::new().name(THREAD_NAME.into()).spawn(body),
+        }
+        .map_err(RdmaBackendError::Thread)?;
+        // The thread only exits without sending the outcome of the setup if it panicked.
+        let setup = setup.recv().expect("rdma: The backend thread panicked");
+        let (events, is_local) = match setup {
+            Ok(setup) => setup,
+            Err(err) => {
+                let _ = thread.join();
+                return Err(err);
+            }
+        };
+        Ok(Self {
+            requests: Some(requests),
+            replies,
+            thread: Some(thread),
+            events,
+            is_local,
+            received: VecDeque::new(),
+            failed_qps: Vec::new(),
+        })
+    }
+
+    // Forwards `request` to the thread, and waits for its outcome.
+    fn call(&mut self, request: Request) -> Outcome {
+        // The thread only stops serving the requests once the proxy is dropped, or if the
+        // backend panicked, which leaves the device without a transport.
+        self.requests
+            .as_ref()
+            .expect("rdma: The backend thread is stopped")
+            .send(request)
+            .expect("rdma: The backend thread panicked");
+        let reply = self
+            .replies
+            .recv()
+            .expect("rdma: The backend thread panicked");
+        self.received.extend(reply.received);
+        self.failed_qps.extend(reply.failed_qps);
+        reply.outcome
+    }
+}
+
+// Serves the requests of the proxy until it is dropped.
+fn serve(
+    mut backend: Box<dyn RdmaBackend>,
+    requests: &Receiver<Request>,
+    replies: &SyncSender<Reply>,
+) {
+    for request in requests {
+        let outcome = match request {
+            Request::Transmit(msg) => Outcome::Transmitted(backend.transmit(msg)),
+            Request::TransmitIov(msg, payload) => {
+                Outcome::Transmitted(backend.transmit_iov(msg, payload))
+            }
+            Request::Submit => {
+                backend.submit();
+                Outcome::Done
+            }
+            Request::ProcessEvent => {
+                backend.process_event();
+                Outcome::Done
+            }
+            Request::Probe => Outcome::Probed(backend.probe()),
+            Request::ConnectQp(qpn, dgid) => Outcome::Connected(backend.connect_qp(qpn, dgid)),
+            Request::ApplySeccompFilter(filter) => {
+                Outcome::SeccompFilterApplied(apply_filter(&filter))
+            }
+        };
+        let received = std::iter::from_fn(|| backend.receive()).collect();
+        let failed_qps = backend.take_failed_qps();
+        let reply = Reply {
+            outcome,
+            received,
+            failed_qps,
+        };
+        if replies.send(reply).is_err() {
+            return;
+        }
+    }
+}
+
+impl Drop for ThreadedBackend {
+    fn drop(&mut self) {
+        // The backend releases its host resources before the proxy is gone.
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl RdmaBackend for ThreadedBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        match self.call(Request::Transmit(msg)) {
+            Outcome::Transmitted(status) => status,
+            outcome => unreachable!("rdma: Unexpected backend outcome {outcome:?}"),
+        }
+    }
+
+    fn transmit_iov(&mut self, msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        match self.call(Request::TransmitIov(msg, payload)) {
+            Outcome::Transmitted(status) => status,
+            outcome => unreachable!("rdma: Unexpected backend outcome {outcome:?}"),
+        }
+    }
+
+    // None of the backends running on a thread intercepts the messages or overruns the
+    // completion queues, which only the faults injected around them do.
+
+    fn submit(&mut self) {
+        self.call(Request::Submit);
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        self.events.clone()
+    }
+
+    fn process_event(&mut self) {
+        self.call(Request::ProcessEvent);
+    }
+
+    fn take_failed_qps(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.failed_qps)
+    }
+
+    fn probe(&mut self) -> bool {
+        match self.call(Request::Probe) {
+            Outcome::Probed(healthy) => healthy,
+            outcome => unreachable!("rdma: Unexpected backend outcome {outcome:?}"),
+        }
+    }
+
+    fn connect_qp(&mut self, qpn: u32, dgid: [u8; 16]) -> bool {
+        match self.call(Request::ConnectQp(qpn, dgid)) {
+            Outcome::Connected(reachable) => reachable,
+            outcome => unreachable!("rdma: Unexpected backend outcome {outcome:?}"),
+        }
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.received.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        self.is_local
+    }
+
+    fn apply_seccomp_filter(&mut self, filter: Arc<BpfProgram>) -> Result<(), InstallationError> {
+        match self.call(Request::ApplySeccompFilter(filter)) {
+            Outcome::SeccompFilterApplied(res) => res,
+            outcome => unreachable!("rdma: Unexpected backend outcome {outcome:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::rdma::backend::unix::{UnixBackendConfig, UnixBackendError};
+    use crate::devices::virtio::rdma::backend::{LoopbackBackend, RdmaBackendConfig};
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WC_SUCCESS};
+
+    #[test]
+    fn test_threaded_backend() {
+        let mut backend =
+            ThreadedBackend::spawn(None, || Ok(Box::new(LoopbackBackend::default()))).unwrap();
+        assert!(backend.is_local());
+        assert!(backend.events().is_empty());
+
+        // The messages received meanwhile come back with the outcome of the call.
+        let msg = RdmaMessage {
+            qp_type: RDMA_QPT_UD,
+            payload: vec![0xab; 16],
+            ..Default::default()
+        };
+        assert_eq!(backend.transmit(msg.clone()), RDMA_WC_SUCCESS);
+        assert_eq!(backend.receive(), Some(msg.clone()));
+        assert_eq!(backend.receive(), None);
+        assert!(backend.take_failed_qps().is_empty());
+        let payload = [0xcd; 16];
+        assert_eq!(
+            backend.transmit_iov(msg.clone(), IoVecBuffer::from(&payload[..])),
+            RDMA_WC_SUCCESS
+        );
+        assert_eq!(
+            backend.receive(),
+            Some(RdmaMessage {
+                payload: payload.to_vec(),
+                ..msg
+            })
+        );
+
+        // An empty filter installs none.
+        backend.apply_seccomp_filter(Arc::default()).unwrap();
+        backend.submit();
+        assert!(backend.probe());
+        assert!(backend.connect_qp(1, [0; 16]));
+
+        // The errors of the setup are reported as such.
+        assert!(matches!(
+            ThreadedBackend::spawn(None, || Err(RdmaBackendError::LocalityChange)),
+            Err(RdmaBackendError::LocalityChange)
+        ));
+    }
+
+    // Instruction of a classic BPF program.
+    fn bpf_stmt(code: u16, jt: u8, jf: u8, k: u32) -> u64 {
+        u64::from(code) | (u64::from(jt) << 16) | (u64::from(jf) << 24) | (u64::from(k) << 32)
+    }
+
+    /// Filter which, like the one of the VMM thread, doesn't allow the backends to create their
+    /// sockets.
+    pub(crate) fn socket_denying_filter() -> BpfProgram {
+        let eperm = libc::SECCOMP_RET_ERRNO | u32::try_from(libc::EPERM).unwrap();
+        vec![
+            // Loads the number of the system call.
+            bpf_stmt(0x20, 0, 0, 0),
+            bpf_stmt(0x15, 0, 1, u32::try_from(libc::SYS_socket).unwrap()),
+            bpf_stmt(0x06, 0, 0, eperm),
+            bpf_stmt(0x06, 0, 0, libc::SECCOMP_RET_ALLOW),
+        ]
+    }
+
+    #[test]
+    fn test_backend_launcher() {
+        // The filters installed by the test stay on its thread.
+        thread::spawn(|| {
+            let dir = TempDir::new().unwrap();
+            let config = |name: &str| {
+                RdmaBackendConfig::Unix(UnixBackendConfig {
+                    socket_path: dir.as_path().join(name),
+                    peers: Vec::new(),
+                })
+            };
+            // The threads of the launcher install the filter of the backends.
+            let launcher =
+                BackendLauncher::new(Arc::default(), Arc::new(socket_denying_filter())).unwrap();
+            assert!(matches!(
+                config("c.sock").spawn(Some(&launcher)),
+                Err(RdmaBackendError::Unix(UnixBackendError::Bind(err)))
+                    if err.raw_os_error() == Some(libc::EPERM)
+            ));
+            let launcher = BackendLauncher::new(Arc::default(), Arc::new(vec![0; 5000])).unwrap();
+            assert!(matches!(
+                config("c.sock").spawn(Some(&launcher)),
+                Err(RdmaBackendError::Seccomp(InstallationError::FilterTooLarge))
+            ));
+
+            let launcher = BackendLauncher::new(Arc::default(), Arc::default()).unwrap();
+            apply_filter(&socket_denying_filter()).unwrap();
+
+            // The threads created by the VMM thread run under its filter.
+            assert!(matches!(
+                config("a.sock").spawn(None),
+                Err(RdmaBackendError::Unix(UnixBackendError::Bind(err)))
+                    if err.raw_os_error() == Some(libc::EPERM)
+            ));
+            // The ones of the launcher don't.
+            let backend = config("b.sock").spawn(Some(&launcher)).unwrap();
+            assert!(!backend.is_local());
+            drop(backend);
+            drop(launcher);
+        })
+        .join()
+        .unwrap();
+
+        // The errors of the filter of the launcher are reported.
+        assert!(matches!(
+            BackendLauncher::new(Arc::new(vec![0; 5000]), Arc::default()),
+            Err(RdmaBackendError::Seccomp(InstallationError::FilterTooLarge))
+        ));
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backend carrying the messages over a host UDP socket, so that the queue pairs of microVMs on
+//! different hosts exchange messages without any RDMA support from the host kernel. Each message
+//! is a datagram holding a packet of the wire format shared with the TCP backend. The peers are
+//! reached at the IP address of the destination GID of the messages, on the port of the backend.
+//!
+//! The messages of reliable connected queue pairs are numbered per flow, from a queue pair to a
+//! remote one, and the receiver acknowledges the messages it received in order. Unacknowledged
+//! messages are retransmitted, starting from the oldest one, until the retries of the flow run
+//! out, after which the sending queue pair is reported as failed. Messages are acknowledged once
+//! received by the backend, so the ones the receiving queue pair drops are not reported to the
+//! sender. The messages of the other queue pairs are sent once, straight from guest memory with
+//! a `sendmsg` of their own, since the ring would read their payload past their work request.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::os::fd::OwnedFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use utils::time::TimerFd;
+use vmm_sys_util::rand::xor_pseudo_rng_u32;
+
+use super::packet::{HEADER_LEN, KIND_ACK, KIND_DATA, Packet, peer_ip};
+use super::{RdmaBackend, RdmaMessage, copy_iov, send_iov};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::uring::{RdmaUring, RdmaUringError, UringCompletion, sockaddr};
+use crate::devices::virtio::rdma::{
+    RDMA_QPT_RC, RDMA_WC_LOC_LEN_ERR, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS,
+};
+use crate::io_uring::IoUringError;
+use crate::io_uring::operation::FixedFd;
+use crate::logger::{debug, warn};
+
+/// UDP port of RoCEv2, which the backend binds to unless configured otherwise.
+pub const UDP_BACKEND_DEFAULT_PORT: u16 = 4791;
+
+/// Largest UDP datagram over IPv4, bounding the payload of the messages.
+const MAX_DATAGRAM_LEN: usize = 65507;
+/// Number of receive operations kept in flight on the socket.
+const RECV_DEPTH: usize = 8;
+/// Time after which unacknowledged messages are retransmitted.
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(50);
+/// Number of retransmissions of a flow without acknowledgement after which it fails, the
+/// largest retry count of a queue pair.
+const MAX_RETRIES: u32 = 7;
+/// Number of unacknowledged messages of a flow past which its work requests fail.
+const MAX_UNACKED: usize = 1024;
+
+// Index of the socket in the ring.
+const SOCKET: FixedFd = 0;
+
+fn default_port() -> u16 {
+    UDP_BACKEND_DEFAULT_PORT
+}
+
+/// Configuration of the UDP backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UdpBackendConfig {
+    /// IP address the socket of the backend is bound to. When it is an IPv4 address, the peers
+    /// are reached at the IPv4 address mapped in the destination GID of the messages.
+    pub bind_ip: IpAddr,
+    /// UDP port of the backend and of its peers.
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+/// Error while setting up the UDP backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum UdpBackendError {
+    /// Unable to bind the socket to {0}: {1}
+    Bind(SocketAddr, io::Error),
+    /// Unable to configure the socket: {0}
+    Socket(io::Error),
+    /// Unable to set up the ring: {0}
+    Uring(#[from] RdmaUringError),
+    /// Unable to receive datagrams: {0}
+    Recv(IoUringError),
+}
+
+/// Reliable connected flow between a queue pair of the device and a remote one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    local_qpn: u32,
+    remote_gid: [u8; 16],
+    remote_qpn: u32,
+}
+
+/// Sending end of a reliable connected flow.
+#[derive(Debug)]
+struct SendFlow {
+    session: u32,
+    dest: SocketAddr,
+    next_psn: u64,
+    // Numbers and datagrams of the messages not acknowledged yet, oldest first.
+    unacked: VecDeque<(u64, Vec<u8>)>,
+    // Retransmissions since the last acknowledgement.
+    retries: u32,
+    // Time at which the unacknowledged messages are retransmitted.
+    deadline: Option<Instant>,
+}
+
+/// Outcome of the reception of a message of a reliable connected flow.
+#[derive(Debug, PartialEq, Eq)]
+enum RecvOutcome {
+    /// The message is delivered, and acknowledged along with the previous ones.
+    Deliver { ack: u64 },
+    /// The message was already delivered, and the acknowledgement is sent again in case it was
+    /// lost.
+    Duplicate { ack: u64 },
+    /// A previous message was lost, and the message is dropped until the sender goes back to it.
+    OutOfOrder,
+}
+
+/// Receiving end of a reliable connected flow.
+#[derive(Debug)]
+struct RecvFlow {
+    session: u32,
+    expected_psn: u64,
+}
+
+impl RecvFlow {
+    fn accept(&mut self, session: u32, psn: u64) -> RecvOutcome {
+        // A new session starts when the remote queue pair is created again.
+        if session != self.session {
+            if psn != 0 {
+                return RecvOutcome::OutOfOrder;
+            }
+            self.session = session;
+            self.expected_psn = 0;
+        }
+        if psn == self.expected_psn {
+            self.expected_psn += 1;
+            RecvOutcome::Deliver { ack: psn }
+        } else if psn < self.expected_psn {
+            RecvOutcome::Duplicate {
+                ack: self.expected_psn - 1,
+            }
+        } else {
+            RecvOutcome::OutOfOrder
+        }
+    }
+}
+
+/// Backend carrying the messages over a host UDP socket.
+#[derive(Debug)]
+pub struct UdpBackend {
+    uring: RdmaUring,
+    local_addr: SocketAddr,
+    retransmit_timer: TimerFd,
+    retransmit_timeout: Duration,
+    send_flows: HashMap<FlowKey, SendFlow>,
+    recv_flows: HashMap<FlowKey, RecvFlow>,
+    received: VecDeque<RdmaMessage>,
+    failed_qps: Vec<u32>,
+    // Number of receive operations in flight, without which the backend receives nothing.
+    pending_recvs: usize,
+}
+
+impl UdpBackend {
+    /// Binds the socket of the backend, and starts receiving datagrams.
+    pub fn new(config: &UdpBackendConfig) -> Result<Self, UdpBackendError> {
+        let addr = SocketAddr::new(config.bind_ip, config.port);
+        let socket = UdpSocket::bind(addr).map_err(|err| UdpBackendError::Bind(addr, err))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(UdpBackendError::Socket)?;
+        let local_addr = socket.local_addr().map_err(UdpBackendError::Socket)?;
+        let mut backend = Self {
+            uring: RdmaUring::new(vec![File::from(OwnedFd::from(socket))])?,
+            local_addr,
+            retransmit_timer: TimerFd::new(),
+            retransmit_timeout: RETRANSMIT_TIMEOUT,
+            send_flows: HashMap::new(),
+            recv_flows: HashMap::new(),
+            received: VecDeque::new(),
+            failed_qps: Vec::new(),
+            pending_recvs: 0,
+        };
+        for _ in 0..RECV_DEPTH {
+            backend.recv().map_err(UdpBackendError::Recv)?;
+        }
+        backend.uring.submit().map_err(UdpBackendError::Recv)?;
+        Ok(backend)
+    }
+
+    /// Address the socket of the backend is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn recv(&mut self) -> Result<(), IoUringError> {
+        self.uring
+            .recv(SOCKET, u32::try_from(MAX_DATAGRAM_LEN).unwrap())?;
+        self.pending_recvs += 1;
+        Ok(())
+    }
+
+    // Takes the pending error of the socket, such as the one of an ICMP error, if any.
+    fn take_socket_error(&self) -> Option<io::Error> {
+        let mut err: libc::c_int = 0;
+        let mut len = libc::socklen_t::try_from(size_of::<libc::c_int>()).unwrap();
+        // SAFETY: The socket is valid, and the option is written to `err`, of length `len`.
+        let ret = unsafe {
+            libc::getsockopt(
+                self.uring.socket(SOCKET).as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ERROR,
+                (&raw mut err).cast(),
+                &raw mut len,
+            )
+        };
+        if ret != 0 {
+            Some(io::Error::last_os_error())
+        } else {
+            (err != 0).then(|| io::Error::from_raw_os_error(err))
+        }
+    }
+
+    /// Returns the address of the backend owning `gid`, or `None` if it can't be reached from
+    /// the socket.
+    fn peer_addr(&self, gid: [u8; 16]) -> Option<SocketAddr> {
+        let ip = peer_ip(gid, self.local_addr.ip())?;
+        Some(SocketAddr::new(ip, self.local_addr.port()))
+    }
+
+    fn send(&mut self, dest: SocketAddr, datagram: Vec<u8>) {
+        if let Err(err) = self.uring.send_to(SOCKET, dest, datagram) {
+            warn!("rdma: Unable to queue datagram to {dest}: {err:?}");
+        }
+    }
+
+    fn process_datagram(&mut self, datagram: &[u8]) {
+        let Some(packet) = Packet::decode(datagram) else {
+            debug!("rdma: Dropped malformed datagram");
+            return;
+        };
+        match packet.kind {
+            KIND_DATA if packet.msg.qp_type == RDMA_QPT_RC => self.process_rc_data(packet),
+            KIND_DATA => self.received.push_back(packet.msg),
+            KIND_ACK => self.process_ack(&packet),
+            kind => debug!("rdma: Dropped datagram of unknown kind {kind}"),
+        }
+    }
+
+    fn process_rc_data(&mut self, packet: Packet) {
+        let msg = packet.msg;
+        let key = FlowKey {
+            local_qpn: msg.dest_qpn,
+            remote_gid: msg.sgid,
+            remote_qpn: msg.src_qpn,
+        };
+        let flow = self.recv_flows.entry(key).or_insert(RecvFlow {
+            session: packet.session,
+            expected_psn: 0,
+        });
+        let (psn, deliver) = match flow.accept(packet.session, packet.psn) {
+            RecvOutcome::Deliver { ack } => (ack, true),
+            RecvOutcome::Duplicate { ack } => (ack, false),
+            RecvOutcome::OutOfOrder => return,
+        };
+        // The sender was reached, so it can be acknowledged.
+        let dest = self.peer_addr(msg.sgid).unwrap();
+        let ack = Packet {
+            kind: KIND_ACK,
+            session: packet.session,
+            psn,
+            msg: RdmaMessage {
+                qp_type: RDMA_QPT_RC,
+                src_qpn: msg.dest_qpn,
+                dest_qpn: msg.src_qpn,
+                sgid: msg.dgid,
+                dgid: msg.sgid,
+                ..Default::default()
+            },
+        };
+        self.send(dest, ack.encode());
+        if deliver {
+            self.received.push_back(msg);
+        }
+    }
+
+    fn process_ack(&mut self, packet: &Packet) {
+        let key = FlowKey {
+            local_qpn: packet.msg.dest_qpn,
+            remote_gid: packet.msg.sgid,
+            remote_qpn: packet.msg.src_qpn,
+        };
+        let timeout = self.retransmit_timeout;
+        let Some(flow) = self.send_flows.get_mut(&key) else {
+            return;
+        };
+        if flow.session != packet.session {
+            return;
+        }
+        let unacked = flow.unacked.len();
+        while flow
+            .unacked
+            .front()
+            .is_some_and(|(psn, _)| *psn <= packet.psn)
+        {
+            flow.unacked.pop_front();
+        }
+        if flow.unacked.len() != unacked {
+            flow.retries = 0;
+            flow.deadline = (!flow.unacked.is_empty()).then(|| Instant::now() + timeout);
+        }
+    }
+
+    /// Retransmits the unacknowledged messages of the flows whose deadline passed, and fails the
+    /// flows out of retries.
+    fn retransmit(&mut self) {
+        let now = Instant::now();
+        let timeout = self.retransmit_timeout;
+        let mut datagrams = Vec::new();
+        let failed_qps = &mut self.failed_qps;
+        self.send_flows.retain(|key, flow| {
+            if flow.deadline.is_none_or(|deadline| deadline > now) {
+                return true;
+            }
+            if flow.retries == MAX_RETRIES {
+                debug!(
+                    "rdma: Retries of queue pair {} to queue pair {} exceeded",
+                    key.local_qpn, key.remote_qpn
+                );
+                failed_qps.push(key.local_qpn);
+                return false;
+            }
+            flow.retries += 1;
+            flow.deadline = Some(now + timeout);
+            datagrams.extend(
+                flow.unacked
+                    .iter()
+                    .map(|(_, datagram)| (flow.dest, datagram.clone())),
+            );
+            true
+        });
+        for (dest, datagram) in datagrams {
+            self.send(dest, datagram);
+        }
+    }
+
+    /// Arms the retransmission timer for the earliest deadline of the flows.
+    fn arm_retransmit_timer(&mut self) {
+        match self
+            .send_flows
+            .values()
+            .filter_map(|flow| flow.deadline)
+            .min()
+        {
+            // Arming the timer with a zero duration would disarm it.
+            Some(deadline) => self.retransmit_timer.arm(
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_nanos(1)),
+                None,
+            ),
+            None if self.retransmit_timer.is_armed() => {
+                self.retransmit_timer.arm(Duration::ZERO, None)
+            }
+            None => (),
+        }
+    }
+}
+
+impl RdmaBackend for UdpBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        let reliable = msg.qp_type == RDMA_QPT_RC;
+        if HEADER_LEN + msg.payload.len() > MAX_DATAGRAM_LEN {
+            return RDMA_WC_LOC_LEN_ERR;
+        }
+        let Some(dest) = self.peer_addr(msg.dgid) else {
+            debug!("rdma: Unreachable GID {}", Ipv6Addr::from(msg.dgid));
+            return if reliable {
+                RDMA_WC_RETRY_EXC_ERR
+            } else {
+                RDMA_WC_SUCCESS
+            };
+        };
+        if !reliable {
+            let packet = Packet {
+                kind: KIND_DATA,
+                session: 0,
+                psn: 0,
+                msg,
+            };
+            self.send(dest, packet.encode());
+            return RDMA_WC_SUCCESS;
+        }
+
+        let key = FlowKey {
+            local_qpn: msg.src_qpn,
+            remote_gid: msg.dgid,
+            remote_qpn: msg.dest_qpn,
+        };
+        let flow = self.send_flows.entry(key).or_insert_with(|| SendFlow {
+            session: xor_pseudo_rng_u32(),
+            dest,
+            next_psn: 0,
+            unacked: VecDeque::new(),
+            retries: 0,
+            deadline: None,
+        });
+        if flow.unacked.len() >= MAX_UNACKED {
+            return RDMA_WC_RETRY_EXC_ERR;
+        }
+        let packet = Packet {
+            kind: KIND_DATA,
+            session: flow.session,
+            psn: flow.next_psn,
+            msg,
+        };
+        let datagram = packet.encode();
+        flow.next_psn += 1;
+        flow.unacked.push_back((packet.psn, datagram.clone()));
+        if flow.deadline.is_none() {
+            flow.deadline = Some(Instant::now() + self.retransmit_timeout);
+            self.arm_retransmit_timer();
+        }
+        self.send(dest, datagram);
+        RDMA_WC_SUCCESS
+    }
+
+    fn transmit_iov(&mut self, mut msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        if HEADER_LEN + payload.len() as usize > MAX_DATAGRAM_LEN {
+            return RDMA_WC_LOC_LEN_ERR;
+        }
+        // The datagrams of reliable connected queue pairs are kept until acknowledged.
+        if msg.qp_type == RDMA_QPT_RC {
+            msg.payload = copy_iov(&payload, 0);
+            return self.transmit(msg);
+        }
+        let Some(dest) = self.peer_addr(msg.dgid) else {
+            debug!("rdma: Unreachable GID {}", Ipv6Addr::from(msg.dgid));
+            return RDMA_WC_SUCCESS;
+        };
+        let header = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        }
+        .encode_header();
+        let (addr, len) = sockaddr(dest);
+        let fd = self.uring.socket(SOCKET).as_raw_fd();
+        match send_iov(fd, Some((&addr, len)), &header, &payload, 0) {
+            Ok(_) => (),
+            // The ring waits for room in the buffer of the socket, from a copy of the datagram.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                let mut datagram = header;
+                datagram.extend(copy_iov(&payload, 0));
+                self.send(dest, datagram);
+            }
+            Err(err) => debug!("rdma: Unable to send datagram to {dest}: {err}"),
+        }
+        RDMA_WC_SUCCESS
+    }
+
+    fn submit(&mut self) {
+        if let Err(err) = self.uring.submit() {
+            warn!("rdma: Unable to submit datagrams: {err:?}");
+        }
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        vec![
+            self.uring.completion_evt().as_raw_fd(),
+            self.retransmit_timer.as_raw_fd(),
+        ]
+    }
+
+    fn process_event(&mut self) {
+        for completion in self.uring.completions() {
+            match completion {
+                UringCompletion::Sent {
+                    result: Err(err), ..
+                } => debug!("rdma: Unable to send datagram: {err}"),
+                UringCompletion::Sent { .. } => (),
+                UringCompletion::Received { result, .. } => {
+                    self.pending_recvs -= 1;
+                    match result {
+                        Ok(datagram) => self.process_datagram(&datagram),
+                        Err(err) => warn!("rdma: Unable to receive datagram: {err}"),
+                    }
+                    if let Err(err) = self.recv() {
+                        warn!("rdma: Unable to queue datagram reception: {err:?}");
+                    }
+                }
+            }
+        }
+        // The deadlines of the flows are checked whatever woke the backend up.
+        self.retransmit_timer.read();
+        self.retransmit();
+        self.arm_retransmit_timer();
+        self.submit();
+    }
+
+    fn take_failed_qps(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.failed_qps)
+    }
+
+    fn probe(&mut self) -> bool {
+        if let Some(err) = self.take_socket_error() {
+            warn!("rdma: UDP socket failed: {err}");
+            return false;
+        }
+        // Receive operations are queued again as they complete, unless the ring failed.
+        self.pending_recvs > 0
+    }
+
+    fn connect_qp(&mut self, _qpn: u32, dgid: [u8; 16]) -> bool {
+        // The flows start over with the next message, and fail the queue pair if the peer never
+        // acknowledges it.
+        self.peer_addr(dgid).is_some()
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.received.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WR_SEND};
+
+    fn gid(ip: Ipv4Addr) -> [u8; 16] {
+        ip.to_ipv6_mapped().octets()
+    }
+
+    fn backend(ip: Ipv4Addr, port: u16) -> UdpBackend {
+        UdpBackend::new(&UdpBackendConfig {
+            bind_ip: IpAddr::V4(ip),
+            port,
+        })
+        .unwrap()
+    }
+
+    /// Processes the events of `backend` until `done` holds.
+    fn wait(backend: &mut UdpBackend, mut done: impl FnMut(&mut UdpBackend) -> bool) {
+        // The tests fail rather than hang if the datagrams never come.
+        for _ in 0..50 {
+            if done(backend) {
+                return;
+            }
+            let mut pollfds: Vec<libc::pollfd> = backend
+                .events()
+                .into_iter()
+                .map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            // SAFETY: The file descriptors are valid, and `pollfds` outlives the call.
+            unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, 100) };
+            backend.process_event();
+        }
+        panic!("timed out waiting for the backend");
+    }
+
+    fn message(qp_type: u32, src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8]) -> RdmaMessage {
+        RdmaMessage {
+            opcode: RDMA_WR_SEND,
+            qp_type,
+            src_qpn: 1,
+            sgid: gid(src),
+            dgid: gid(dst),
+            dest_qpn: 2,
+            payload: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recv_flow() {
+        let mut flow = RecvFlow {
+            session: 1,
+            expected_psn: 0,
+        };
+        assert_eq!(flow.accept(1, 0), RecvOutcome::Deliver { ack: 0 });
+        assert_eq!(flow.accept(1, 2), RecvOutcome::OutOfOrder);
+        assert_eq!(flow.accept(1, 1), RecvOutcome::Deliver { ack: 1 });
+        assert_eq!(flow.accept(1, 0), RecvOutcome::Duplicate { ack: 1 });
+        assert_eq!(flow.accept(1, 2), RecvOutcome::Deliver { ack: 2 });
+
+        // A new session starts from its first message.
+        assert_eq!(flow.accept(2, 1), RecvOutcome::OutOfOrder);
+        assert_eq!(flow.accept(2, 0), RecvOutcome::Deliver { ack: 0 });
+        assert_eq!(flow.accept(2, 1), RecvOutcome::Deliver { ack: 1 });
+    }
+
+    #[test]
+    fn test_udp_backend() {
+        let (ip_a, ip_b) = (Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 2));
+        let mut a = backend(ip_a, 0);
+        let port = a.local_addr().port();
+        let mut b = backend(ip_b, port);
+        assert_eq!(a.events().len(), 2);
+        assert!(!a.is_local());
+        assert_eq!(a.pending_recvs, RECV_DEPTH);
+        assert!(a.probe());
+        // Queue pairs reconnect on restore to the GIDs the socket can reach.
+        assert!(a.connect_qp(1, ip_b.to_ipv6_mapped().octets()));
+        assert!(!a.connect_qp(1, [0xfe; 16]));
+
+        // Unreliable messages are sent once.
+        let ud = message(RDMA_QPT_UD, ip_a, ip_b, b"datagram");
+        assert_eq!(a.transmit(ud.clone()), RDMA_WC_SUCCESS);
+        a.submit();
+        wait(&mut b, |b| !b.received.is_empty());
+        assert_eq!(b.receive().unwrap(), ud);
+        // Their payload is sent straight from the buffers it is gathered from.
+        let iov = IoVecBuffer::from(vec![&b"data"[..], &b"gram"[..]]);
+        let msg = message(RDMA_QPT_UD, ip_a, ip_b, b"");
+        assert_eq!(a.transmit_iov(msg, iov), RDMA_WC_SUCCESS);
+        wait(&mut b, |b| !b.received.is_empty());
+        assert_eq!(b.receive().unwrap(), ud);
+        assert!(a.send_flows.is_empty());
+
+        // Reliable messages are delivered in order, and acknowledged.
+        let rc = |payload: &[u8]| message(RDMA_QPT_RC, ip_a, ip_b, payload);
+        assert_eq!(a.transmit(rc(b"first")), RDMA_WC_SUCCESS);
+        let iov = IoVecBuffer::from(&b"second"[..]);
+        assert_eq!(a.transmit_iov(rc(b""), iov), RDMA_WC_SUCCESS);
+        a.submit();
+        assert_eq!(a.send_flows.values().next().unwrap().unacked.len(), 2);
+        wait(&mut b, |b| b.received.len() == 2);
+        assert_eq!(b.receive().unwrap(), rc(b"first"));
+        assert_eq!(b.receive().unwrap(), rc(b"second"));
+        wait(&mut a, |a| {
+            a.send_flows.values().next().unwrap().unacked.is_empty()
+        });
+        assert!(!a.retransmit_timer.is_armed());
+        assert!(a.take_failed_qps().is_empty());
+
+        // Messages to GIDs the socket can't reach fail reliable work requests only.
+        let mut unreachable = rc(b"");
+        unreachable.dgid = [0xfe; 16];
+        assert_eq!(a.transmit(unreachable.clone()), RDMA_WC_RETRY_EXC_ERR);
+        unreachable.qp_type = RDMA_QPT_UD;
+        assert_eq!(a.transmit(unreachable), RDMA_WC_SUCCESS);
+        let too_large = rc(&vec![0; MAX_DATAGRAM_LEN]);
+        assert_eq!(a.transmit(too_large), RDMA_WC_LOC_LEN_ERR);
+        let too_large = vec![0; MAX_DATAGRAM_LEN];
+        let iov = IoVecBuffer::from(&too_large[..]);
+        let msg = message(RDMA_QPT_UD, ip_a, ip_b, b"");
+        assert_eq!(a.transmit_iov(msg, iov), RDMA_WC_LOC_LEN_ERR);
+    }
+
+    #[test]
+    fn test_udp_backend_retransmit() {
+        let (ip_a, ip_b) = (Ipv4Addr::new(127, 0, 0, 1), Ipv4Addr::new(127, 0, 0, 3));
+        let mut a = backend(ip_a, 0);
+        a.retransmit_timeout = Duration::from_millis(10);
+        let port = a.local_addr().port();
+
+        // The message sent before the peer is bound is lost, and retransmitted.
+        let msg = message(RDMA_QPT_RC, ip_a, ip_b, b"lost");
+        assert_eq!(a.transmit(msg.clone()), RDMA_WC_SUCCESS);
+        a.submit();
+        assert!(a.retransmit_timer.is_armed());
+        let mut b = backend(ip_b, port);
+        wait(&mut a, |a| {
+            a.send_flows.values().next().unwrap().retries > 0
+        });
+        wait(&mut b, |b| !b.received.is_empty());
+        assert_eq!(b.receive().unwrap(), msg);
+        wait(&mut a, |a| {
+            a.send_flows.values().next().unwrap().unacked.is_empty()
+        });
+        assert_eq!(a.send_flows.values().next().unwrap().retries, 0);
+
+        // The flow fails once its retries run out.
+        drop(b);
+        assert_eq!(a.transmit(msg), RDMA_WC_SUCCESS);
+        a.submit();
+        wait(&mut a, |a| a.send_flows.is_empty());
+        assert_eq!(a.take_failed_qps(), [1]);
+        assert!(a.take_failed_qps().is_empty());
+        assert!(!a.retransmit_timer.is_armed());
+    }
+}
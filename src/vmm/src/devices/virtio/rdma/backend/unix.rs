@@ -0,0 +1,380 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backend exchanging the messages with the microVMs of the same host over Unix datagram
+//! sockets, without any network between them. Each device binds a socket at its configured path,
+//! and sends its messages, as packets of the wire format of the network backends, to the socket
+//! of the peer owning their destination GID.
+//!
+//! Unix datagrams are neither lost nor reordered, so the messages are sent right away rather than
+//! through a ring, their payload straight from guest memory, and the work requests of reliable
+//! connected queue pairs fail with `RDMA_WC_RETRY_EXC_ERR` as soon as their peer can't take their
+//! message: when it isn't running, or when its socket is full.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv6Addr};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::packet::{KIND_DATA, Packet};
+use super::{RdmaBackend, RdmaMessage, send_iov, socket_bound_at};
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::rdma::{
+    RDMA_QPT_RC, RDMA_WC_LOC_LEN_ERR, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_SUCCESS,
+};
+use crate::logger::{debug, warn};
+
+/// Peer of the Unix backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnixPeerConfig {
+    /// GID of the peer. IPv4 addresses stand for their IPv4-mapped GID.
+    pub gid: IpAddr,
+    /// Path of the socket of the peer.
+    pub socket_path: PathBuf,
+}
+
+/// Configuration of the Unix backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnixBackendConfig {
+    /// Path the socket of the backend is bound to, which must not exist.
+    pub socket_path: PathBuf,
+    /// Peers the backend sends messages to.
+    #[serde(default)]
+    pub peers: Vec<UnixPeerConfig>,
+}
+
+/// Error while setting up the Unix backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum UnixBackendError {
+    /// Unable to bind the socket: {0}
+    Bind(io::Error),
+    /// Peer GID {0} is configured more than once
+    DuplicatePeer(Ipv6Addr),
+}
+
+// Returns the GID an address of the configuration stands for.
+fn gid(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+// Converts `path` to the C socket address `sendmsg` takes, along with its length.
+fn sockaddr_un(path: &Path) -> io::Result<(libc::sockaddr_storage, libc::socklen_t)> {
+    let bytes = path.as_os_str().as_bytes();
+    // SAFETY: The address is a plain C structure, for which all-zero is a valid value.
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    // The path is terminated by a null byte.
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "path must be shorter than SUN_LEN",
+        ));
+    }
+    addr.sun_family = libc::sa_family_t::try_from(libc::AF_UNIX).unwrap();
+    for (dst, &src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = libc::c_char::from_ne_bytes([src]);
+    }
+    // SAFETY: The address is a plain C structure, for which all-zero is a valid value.
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    // SAFETY: `sockaddr_storage` is large and aligned enough for any address.
+    unsafe { std::ptr::write((&raw mut storage).cast(), addr) };
+    let len = std::mem::offset_of!(libc::sockaddr_un, sun_path) + bytes.len() + 1;
+    Ok((storage, u32::try_from(len).unwrap()))
+}
+
+// `RDMA_WC_*` status of the work requests whose message is lost. The messages of the unreliable
+// queue pairs are simply lost.
+fn lost_status(qp_type: u32) -> u32 {
+    if qp_type == RDMA_QPT_RC {
+        RDMA_WC_RETRY_EXC_ERR
+    } else {
+        RDMA_WC_SUCCESS
+    }
+}
+
+// `RDMA_WC_*` status of the work request which sent a message of a queue pair of type `qp_type`
+// to the socket at `path`.
+fn sent_status(result: io::Result<usize>, path: &Path, qp_type: u32) -> u32 {
+    match result {
+        Ok(_) => RDMA_WC_SUCCESS,
+        Err(err) if err.raw_os_error() == Some(libc::EMSGSIZE) => RDMA_WC_LOC_LEN_ERR,
+        Err(err) => {
+            debug!("rdma: Unable to send message to {}: {err}", path.display());
+            lost_status(qp_type)
+        }
+    }
+}
+
+/// Backend exchanging the messages over Unix datagram sockets.
+#[derive(Debug)]
+pub struct UnixBackend {
+    socket: UnixDatagram,
+    socket_path: PathBuf,
+    // Paths of the sockets of the peers, by GID.
+    peers: HashMap<[u8; 16], PathBuf>,
+    received: VecDeque<RdmaMessage>,
+}
+
+impl UnixBackend {
+    /// Binds the socket of the backend.
+    pub fn new(config: &UnixBackendConfig) -> Result<Self, UnixBackendError> {
+        let mut peers = HashMap::new();
+        for peer in &config.peers {
+            let gid = gid(peer.gid);
+            if peers.insert(gid, peer.socket_path.clone()).is_some() {
+                return Err(UnixBackendError::DuplicatePeer(Ipv6Addr::from(gid)));
+            }
+        }
+        let socket = UnixDatagram::bind(&config.socket_path)
+            .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+            .map_err(UnixBackendError::Bind)?;
+        Ok(Self {
+            socket,
+            socket_path: config.socket_path.clone(),
+            peers,
+            received: VecDeque::new(),
+        })
+    }
+
+    /// Receives the next datagram, or returns `None` if there is none.
+    fn recv(&self) -> io::Result<Option<Vec<u8>>> {
+        // Peeking with `MSG_TRUNC` returns the length of the datagram, whatever the buffer.
+        // SAFETY: The socket is valid, and no buffer is written to.
+        let len = unsafe {
+            libc::recv(
+                self.socket.as_raw_fd(),
+                std::ptr::null_mut(),
+                0,
+                libc::MSG_PEEK | libc::MSG_TRUNC | libc::MSG_DONTWAIT,
+            )
+        };
+        let len = match usize::try_from(len) {
+            Ok(len) => len,
+            Err(_) => {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    ErrorKind::WouldBlock => Ok(None),
+                    _ => Err(err),
+                };
+            }
+        };
+        let mut buf = vec![0u8; len];
+        let len = self.socket.recv(&mut buf)?;
+        buf.truncate(len);
+        Ok(Some(buf))
+    }
+
+    /// Path of the socket of the peer owning the destination GID of `msg`, if any.
+    fn peer_path(&self, msg: &RdmaMessage) -> Option<&PathBuf> {
+        let path = self.peers.get(&msg.dgid);
+        if path.is_none() {
+            debug!("rdma: No peer owns GID {}", Ipv6Addr::from(msg.dgid));
+        }
+        path
+    }
+}
+
+impl RdmaBackend for UnixBackend {
+    fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+        let qp_type = msg.qp_type;
+        let Some(path) = self.peer_path(&msg) else {
+            return lost_status(qp_type);
+        };
+        let datagram = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        }
+        .encode();
+        sent_status(self.socket.send_to(&datagram, path), path, qp_type)
+    }
+
+    fn transmit_iov(&mut self, msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+        let qp_type = msg.qp_type;
+        let Some(path) = self.peer_path(&msg) else {
+            return lost_status(qp_type);
+        };
+        let header = Packet {
+            kind: KIND_DATA,
+            session: 0,
+            psn: 0,
+            msg,
+        }
+        .encode_header();
+        let result = sockaddr_un(path).and_then(|(addr, len)| {
+            send_iov(
+                self.socket.as_raw_fd(),
+                Some((&addr, len)),
+                &header,
+                &payload,
+                0,
+            )
+        });
+        sent_status(result, path, qp_type)
+    }
+
+    fn events(&self) -> Vec<RawFd> {
+        vec![self.socket.as_raw_fd()]
+    }
+
+    fn process_event(&mut self) {
+        loop {
+            match self.recv() {
+                // Empty datagrams only check that the backend is running.
+                Ok(Some(datagram)) if datagram.is_empty() => {}
+                Ok(Some(datagram)) => match Packet::decode(&datagram) {
+                    Some(packet) if packet.kind == KIND_DATA => self.received.push_back(packet.msg),
+                    _ => debug!("rdma: Dropped malformed datagram"),
+                },
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("rdma: Unable to receive datagram: {err}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn probe(&mut self) -> bool {
+        // The peers only reach the backend through the path of its socket.
+        matches!(self.socket.take_error(), Ok(None)) && socket_bound_at(&self.socket_path)
+    }
+
+    fn connect_qp(&mut self, _qpn: u32, dgid: [u8; 16]) -> bool {
+        // Sending to the socket of a peer which isn't running fails, while the peer ignores
+        // empty datagrams.
+        self.peers
+            .get(&dgid)
+            .is_some_and(|path| self.socket.send_to(&[], path).is_ok())
+    }
+
+    fn receive(&mut self) -> Option<RdmaMessage> {
+        self.received.pop_front()
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::rdma::{RDMA_QPT_UD, RDMA_WR_SEND};
+
+    fn message(qp_type: u32, dgid: [u8; 16], payload: &[u8]) -> RdmaMessage {
+        RdmaMessage {
+            opcode: RDMA_WR_SEND,
+            qp_type,
+            src_qpn: 1,
+            dgid,
+            dest_qpn: 2,
+            payload: payload.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unix_backend() {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str| dir.as_path().join(name);
+        let (ip_a, ip_b) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+        let config = |name: &str, peers: &[(Ipv4Addr, &str)]| UnixBackendConfig {
+            socket_path: path(name),
+            peers: peers
+                .iter()
+                .map(|&(ip, name)| UnixPeerConfig {
+                    gid: IpAddr::V4(ip),
+                    socket_path: path(name),
+                })
+                .collect(),
+        };
+        let mut a = UnixBackend::new(&config("a.sock", &[(ip_b, "b.sock")])).unwrap();
+        let mut b = UnixBackend::new(&config("b.sock", &[(ip_a, "a.sock")])).unwrap();
+        assert_eq!(a.events(), [a.socket.as_raw_fd()]);
+        assert!(!a.is_local());
+
+        // The messages are received in order.
+        let gid_b = ip_b.to_ipv6_mapped().octets();
+        let msgs = [
+            message(RDMA_QPT_UD, gid_b, b"datagram"),
+            message(RDMA_QPT_RC, gid_b, b"first"),
+            message(RDMA_QPT_RC, gid_b, &vec![0xa5; 64 << 10]),
+        ];
+        for msg in &msgs {
+            assert_eq!(a.transmit(msg.clone()), RDMA_WC_SUCCESS);
+        }
+        b.process_event();
+        assert_eq!(b.received, msgs);
+        b.process_event();
+        assert_eq!(b.received.len(), 3);
+
+        // The payload of the work requests is sent straight from guest memory.
+        let payload = [0x5a; 256];
+        let msg = message(RDMA_QPT_RC, gid_b, b"");
+        let iov = IoVecBuffer::from(vec![&payload[..128], &payload[128..]]);
+        assert_eq!(a.transmit_iov(msg.clone(), iov), RDMA_WC_SUCCESS);
+        b.process_event();
+        assert_eq!(
+            b.received.pop_back(),
+            Some(RdmaMessage {
+                payload: payload.to_vec(),
+                ..msg
+            })
+        );
+        sockaddr_un(&PathBuf::from("a".repeat(108))).unwrap_err();
+
+        // Queue pairs reconnect on restore to running peers only.
+        assert!(a.connect_qp(1, gid_b));
+        b.process_event();
+        assert_eq!(b.received.len(), 3);
+
+        // Messages to unknown GIDs, or to peers which aren't running, fail reliable work
+        // requests only.
+        drop(b);
+        for dgid in [gid_b, [0xfe; 16]] {
+            assert_eq!(
+                a.transmit(message(RDMA_QPT_RC, dgid, b"")),
+                RDMA_WC_RETRY_EXC_ERR
+            );
+            assert_eq!(a.transmit(message(RDMA_QPT_UD, dgid, b"")), RDMA_WC_SUCCESS);
+            assert_eq!(
+                a.transmit_iov(message(RDMA_QPT_RC, dgid, b""), IoVecBuffer::default()),
+                RDMA_WC_RETRY_EXC_ERR
+            );
+        }
+        a.process_event();
+        assert!(a.receive().is_none());
+        assert!(!a.connect_qp(1, gid_b));
+        assert!(!a.connect_qp(1, [0xfe; 16]));
+
+        // The backend fails its probe once its socket is removed from its path.
+        assert!(a.probe());
+        std::fs::remove_file(path("a.sock")).unwrap();
+        assert!(!a.probe());
+
+        // The socket path must not exist, and peers must have distinct GIDs.
+        assert!(matches!(
+            UnixBackend::new(&config("b.sock", &[])),
+            Err(UnixBackendError::Bind(_))
+        ));
+        assert!(matches!(
+            UnixBackend::new(&config("c.sock", &[(ip_a, "a.sock"), (ip_a, "b.sock")])),
+            Err(UnixBackendError::DuplicatePeer(gid)) if gid == ip_a.to_ipv6_mapped()
+        ));
+    }
+}
@@ -1,127 +1,9172 @@
 // Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::{BTreeSet, VecDeque};
 use std::io;
+use std::mem::size_of;
+use std::net::IpAddr;
 use std::ops::Deref;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync::Arc;
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+use utils::time::TimerFd;
+use vm_memory::{ByteValued, GuestMemoryError};
+use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
 use vmm_sys_util::eventfd::EventFd;
 
-use super::RDMA_NUM_QUEUES;
+use super::backend::fault::{FaultInjectionBackend, RdmaFaultPolicy};
+use super::backend::thread::BackendLauncher;
+use super::backend::{
+    LoopbackBackend, NullBackend, RdmaBackend, RdmaBackendConfig, RdmaBackendError, RdmaMessage,
+    copy_iov,
+};
+use super::doorbell::RdmaDoorbells;
+use super::gid::GidTable;
+use super::metrics::{RdmaMetrics, RdmaMetricsPerDevice};
+use super::pkey::{PkeyTable, PkeyTableError};
+use super::qp::{OutstandingWork, QpAttributes, QueuePair, RecvWr};
+use super::request::{
+    RdmaAsyncEvent, RdmaAtomic, RdmaBindMw, RdmaCmdAllocMw, RdmaCmdCreateAh, RdmaCmdCreateCq,
+    RdmaCmdCreateQp, RdmaCmdCreateSrq, RdmaCmdDeallocMw, RdmaCmdDeallocPd, RdmaCmdDeregMr,
+    RdmaCmdDestroyAh, RdmaCmdDestroyCq, RdmaCmdDestroyQp, RdmaCmdDestroySrq, RdmaCmdError,
+    RdmaCmdHdr, RdmaCmdModifyQp, RdmaCmdModifySrq, RdmaCmdPollCq, RdmaCmdPostRecv, RdmaCmdPostSend,
+    RdmaCmdPostSrqRecv, RdmaCmdQueryGid, RdmaCmdQueryPkey, RdmaCmdQueryPort, RdmaCmdQueryQp,
+    RdmaCmdRegMr, RdmaCmdReqNotifyCq, RdmaCmdResizeCq, RdmaConfigSpace, RdmaCqEvent,
+    RdmaRspAllocMw, RdmaRspAllocPd, RdmaRspCreateAh, RdmaRspCreateCq, RdmaRspCreateQp,
+    RdmaRspCreateSrq, RdmaRspHdr, RdmaRspPollCq, RdmaRspQueryDevice, RdmaRspQueryGid,
+    RdmaRspQueryPkey, RdmaRspQueryPort, RdmaRspQueryQp, RdmaRspRegMr, RdmaRspResizeCq, RdmaSge,
+    RdmaWc,
+};
+use super::table::ResourceTable;
+use super::{
+    RDMA_ACCESS_LOCAL_WRITE, RDMA_ACCESS_MW_BIND, RDMA_ACCESS_REMOTE_ATOMIC,
+    RDMA_ACCESS_REMOTE_READ, RDMA_ACCESS_REMOTE_WRITE, RDMA_ATOMIC_HCA, RDMA_ATOMIC_NONE,
+    RDMA_CMD_ALLOC_MW, RDMA_CMD_ALLOC_PD, RDMA_CMD_CREATE_AH, RDMA_CMD_CREATE_CQ,
+    RDMA_CMD_CREATE_QP, RDMA_CMD_CREATE_SRQ, RDMA_CMD_DEALLOC_MW, RDMA_CMD_DEALLOC_PD,
+    RDMA_CMD_DEREG_MR, RDMA_CMD_DESTROY_AH, RDMA_CMD_DESTROY_CQ, RDMA_CMD_DESTROY_QP,
+    RDMA_CMD_DESTROY_SRQ, RDMA_CMD_MODIFY_QP, RDMA_CMD_MODIFY_SRQ, RDMA_CMD_POLL_CQ,
+    RDMA_CMD_POST_RECV, RDMA_CMD_POST_SEND, RDMA_CMD_POST_SRQ_RECV, RDMA_CMD_QUERY_DEVICE,
+    RDMA_CMD_QUERY_GID, RDMA_CMD_QUERY_PKEY, RDMA_CMD_QUERY_PORT, RDMA_CMD_QUERY_QP,
+    RDMA_CMD_REG_MR, RDMA_CMD_REQ_NOTIFY_CQ, RDMA_CMD_RESIZE_CQ, RDMA_CQ_F_ASYNC,
+    RDMA_CQ_NEXT_COMP, RDMA_CQ_QUEUE, RDMA_CQ_SOLICITED, RDMA_CTRL_QUEUE, RDMA_DOORBELL_REGION_LEN,
+    RDMA_EVENT_CQ_ERR, RDMA_EVENT_GID_CHANGE, RDMA_EVENT_MR_INVALIDATED, RDMA_EVENT_PORT_ACTIVE,
+    RDMA_EVENT_PORT_ERR, RDMA_EVENT_QP_FATAL, RDMA_EVENT_QUEUE, RDMA_EVENT_SRQ_LIMIT_REACHED,
+    RDMA_GID_TABLE_LEN, RDMA_GID_TYPE_ROCE_V2, RDMA_GRH_LEN, RDMA_LINK_LAYER_ETHERNET, RDMA_MAX_AH,
+    RDMA_MAX_CQ, RDMA_MAX_CQE, RDMA_MAX_INLINE_DATA, RDMA_MAX_MR, RDMA_MAX_MSG_SIZE, RDMA_MAX_MW,
+    RDMA_MAX_NUM_QUEUES, RDMA_MAX_PD, RDMA_MAX_QP, RDMA_MAX_QP_RD_ATOM, RDMA_MAX_QP_WR,
+    RDMA_MAX_SGE, RDMA_MAX_SRQ, RDMA_MAX_SRQ_WR, RDMA_MTU_1024, RDMA_MTU_4096, RDMA_MW_TYPE_2,
+    RDMA_NUM_PORTS, RDMA_PAGE_SIZE_CAP, RDMA_PKEY_TABLE_LEN, RDMA_PORT_ACTIVE, RDMA_PORT_DOWN,
+    RDMA_QP_PKEY_INDEX, RDMA_QPS_ERR, RDMA_QPS_INIT, RDMA_QPS_RESET, RDMA_QPS_RTR, RDMA_QPS_RTS,
+    RDMA_QPS_SQD, RDMA_QPS_SQE, RDMA_QPT_RC, RDMA_QPT_UC, RDMA_QPT_UD, RDMA_RECV_QUEUE,
+    RDMA_SEND_FENCE, RDMA_SEND_INLINE, RDMA_SEND_QUEUE, RDMA_SEND_SIGNALED, RDMA_SEND_SOLICITED,
+    RDMA_SRQ_LIMIT, RDMA_SRQ_MAX_WR, RDMA_STATUS_OK, RDMA_WC_BIND_MW, RDMA_WC_COMP_SWAP,
+    RDMA_WC_FETCH_ADD, RDMA_WC_GRH, RDMA_WC_LOC_LEN_ERR, RDMA_WC_LOC_PROT_ERR,
+    RDMA_WC_LOC_QP_OP_ERR, RDMA_WC_LOCAL_INV, RDMA_WC_MW_BIND_ERR, RDMA_WC_RDMA_READ,
+    RDMA_WC_RDMA_WRITE, RDMA_WC_RECV, RDMA_WC_RECV_RDMA_WITH_IMM, RDMA_WC_REM_ACCESS_ERR,
+    RDMA_WC_REM_INV_REQ_ERR, RDMA_WC_REM_OP_ERR, RDMA_WC_RETRY_EXC_ERR, RDMA_WC_RNR_RETRY_EXC_ERR,
+    RDMA_WC_SEND, RDMA_WC_SUCCESS, RDMA_WC_WITH_IMM, RDMA_WC_WITH_INV, RDMA_WC_WR_FLUSH_ERR,
+    RDMA_WR_ATOMIC_CMP_AND_SWP, RDMA_WR_ATOMIC_FETCH_AND_ADD, RDMA_WR_BIND_MW, RDMA_WR_LOCAL_INV,
+    RDMA_WR_RDMA_READ, RDMA_WR_RDMA_WRITE, RDMA_WR_RDMA_WRITE_WITH_IMM, RDMA_WR_SEND,
+    RDMA_WR_SEND_WITH_IMM, RDMA_WR_SEND_WITH_INV, rdma_num_queues,
+};
 use crate::devices::virtio::ActivateError;
 use crate::devices::virtio::device::{ActiveState, DeviceState, VirtioDevice, VirtioDeviceType};
-use crate::devices::virtio::queue::{FIRECRACKER_MAX_QUEUE_SIZE, Queue};
-use crate::devices::virtio::transport::VirtioInterrupt;
+use crate::devices::virtio::generated::virtio_config::{VIRTIO_F_RING_PACKED, VIRTIO_F_VERSION_1};
+use crate::devices::virtio::generated::virtio_ring::VIRTIO_RING_F_EVENT_IDX;
+use crate::devices::virtio::iovec::IoVecBuffer;
+use crate::devices::virtio::queue::{
+    DescriptorChain, FIRECRACKER_MAX_QUEUE_SIZE, InvalidAvailIdx, Queue, QueueError,
+};
+use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
 use crate::impl_device_type;
-use crate::vstate::memory::GuestMemoryMmap;
+use crate::logger::{IncMetric, debug, error, warn};
+use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenType};
+use crate::utils::net::mac::MacAddr;
+use crate::utils::u64_to_usize;
+use crate::vstate::bus::BusDeviceSync;
+use crate::vstate::memory::{
+    Address, Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRemovalListener,
+};
+
+/// IPv6 next header of the global route headers, identifying RoCE v1 transport headers.
+const GRH_NEXT_HEADER: u8 = 0x1b;
+
+/// Remote keys of the memory windows have their top bit set, unlike the keys of the memory
+/// regions, followed by the handle of the window and by the 8 bits picked on each bind.
+const MW_RKEY_FLAG: u32 = 1 << 31;
+/// Largest key of a memory region.
+const MAX_MR_KEY: u32 = MW_RKEY_FLAG - 1;
+/// Largest number of asynchronous events waiting for a buffer of the event queue.
+const MAX_PENDING_EVENTS: usize = 64;
+/// Largest handle of a memory window.
+const MAX_MW_HANDLE: u32 = MAX_MR_KEY >> 8;
+/// Period of the health probes of the backends performing host I/O.
+const BACKEND_PROBE_PERIOD: Duration = Duration::from_secs(1);
+/// Delay before the first attempt to set up a backend which went down again, doubled by each
+/// attempt which fails.
+const BACKEND_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+/// Largest delay between the attempts to set up a backend which went down again.
+const MAX_BACKEND_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Returns the handle of the memory window of remote key `rkey`, or `None` if `rkey` is the key
+/// of a memory region.
+fn mw_handle(rkey: u32) -> Option<u32> {
+    (rkey & MW_RKEY_FLAG != 0).then_some((rkey & !MW_RKEY_FLAG) >> 8)
+}
+
+/// Returns the node GUID of the device `id`, a locally administered EUI-64 derived from the FNV-1a
+/// hash of `id`, so that the device keeps its GUID across restarts.
+fn node_guid(id: &str) -> u64 {
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    // The first byte has its universal/local bit set and its individual/group bit clear.
+    (hash | 1 << 57) & !(1 << 56)
+}
+
+/// Returns the `RDMA_MTU_*` value of an MTU of `mtu` bytes, or `None` if no value matches it.
+fn mtu_value(mtu: u32) -> Option<u32> {
+    (mtu.is_power_of_two() && (256..=4096).contains(&mtu)).then(|| mtu.trailing_zeros() - 7)
+}
+
+/// Returns the `RDMA_PORT_*` state of the ports and the `RDMA_EVENT_PORT_*` event notifying it
+/// when the link is up, per `up`, or down.
+fn link_state(up: bool) -> (u32, u32) {
+    if up {
+        (RDMA_PORT_ACTIVE, RDMA_EVENT_PORT_ACTIVE)
+    } else {
+        (RDMA_PORT_DOWN, RDMA_EVENT_PORT_ERR)
+    }
+}
+
+/// Returns the delay before the next attempt to set up a backend which went down, once
+/// `attempts` attempts failed.
+fn backend_reconnect_delay(attempts: u32) -> Duration {
+    BACKEND_RECONNECT_DELAY
+        .saturating_mul(1 << attempts.min(16))
+        .min(MAX_BACKEND_RECONNECT_DELAY)
+}
+
+/// Returns the `RDMA_WC_*` status of the work request which sent a message the receiving queue
+/// pair dropped because of `err`.
+fn dropped_msg_status(err: &RdmaCmdError) -> u32 {
+    match err {
+        RdmaCmdError::NoRecvWr(_) => RDMA_WC_RNR_RETRY_EXC_ERR,
+        // The message never reached a connected queue pair.
+        RdmaCmdError::UnknownQp(_)
+        | RdmaCmdError::InvalidQpType(_)
+        | RdmaCmdError::QpStateMismatch(..)
+        | RdmaCmdError::InvalidQpAttr(..) => RDMA_WC_RETRY_EXC_ERR,
+        // The message is not one the queue pair can execute.
+        RdmaCmdError::InvalidWrOpcode(..)
+        | RdmaCmdError::InvalidMsgHeader(_)
+        | RdmaCmdError::MsgTooLong(_) => RDMA_WC_REM_INV_REQ_ERR,
+        // The other messages are dropped when they may not access the remote memory.
+        _ => RDMA_WC_REM_ACCESS_ERR,
+    }
+}
+
+/// Whether the command `opcode` may be sent on the queue `queue_index`. The data queues only take
+/// the commands posting their work requests.
+fn queue_accepts(queue_index: usize, opcode: u32) -> bool {
+    if queue_index < RDMA_SEND_QUEUE {
+        return true;
+    }
+    // The queues of the following data queue pairs come in the order of the first pair.
+    if (queue_index - RDMA_SEND_QUEUE) % 2 == RDMA_RECV_QUEUE - RDMA_SEND_QUEUE {
+        matches!(opcode, RDMA_CMD_POST_RECV | RDMA_CMD_POST_SRQ_RECV)
+    } else {
+        opcode == RDMA_CMD_POST_SEND
+    }
+}
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum RdmaError {
     /// Error while handling an Event file descriptor: {0}
     EventFd(#[from] io::Error),
+    /// Received a descriptor chain which is too short
+    DescriptorChainTooShort,
+    /// Received a descriptor with an unexpected direction
+    UnexpectedDescriptorDirection,
+    /// Received a descriptor which is too small
+    DescriptorTooSmall,
+    /// Guest memory error: {0}
+    GuestMemory(#[from] GuestMemoryError),
+    /// Error handling the VirtIO queue: {0}
+    Queue(#[from] QueueError),
+    /// Error during obtaining the descriptor from the queue: {0}
+    QueuePop(#[from] InvalidAvailIdx),
+    /// Invalid number of data queue pairs: {0}
+    InvalidNumQueues(u16),
+    /// Invalid MTU of {0} bytes, expected 256, 512, 1024, 2048 or 4096 bytes
+    InvalidMtu(u32),
+    /// Invalid completion interrupt moderation, both of its limits must be positive
+    InvalidCqModeration,
+    /// Invalid {0} limit of {1}, expected at least 1 and at most {2}
+    InvalidResourceLimit(&'static str, u64, u64),
+}
+
+/// Moderation of the interrupts of the completion queue of the device, so that a burst of work
+/// completions interrupts the driver once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RdmaCqModeration {
+    /// Number of work completions after which the driver is interrupted.
+    pub max_cqes: u16,
+    /// Time after which the driver is interrupted for fewer work completions, in microseconds.
+    pub max_usecs: u32,
+}
+
+/// Limits on the resources the driver of a device creates, below the ones of the device, and
+/// quotas on the work requests it posts which the device hasn't completed yet. The resources are
+/// only limited by the device, and the work requests by their queues, when `None`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RdmaResourceLimits {
+    /// Maximum number of queue pairs.
+    pub max_qp: Option<u32>,
+    /// Maximum number of completion queues.
+    pub max_cq: Option<u32>,
+    /// Maximum number of memory regions.
+    pub max_mr: Option<u32>,
+    /// Maximum number of bytes of guest memory registered by all the memory regions.
+    pub max_memory_registration_bytes: Option<u64>,
+    /// Maximum number of work requests outstanding on a queue pair.
+    pub max_outstanding_wr_per_qp: Option<u32>,
+    /// Maximum number of bytes of the buffers of the work requests outstanding on a queue pair.
+    pub max_outstanding_bytes_per_qp: Option<u64>,
+    /// Maximum number of work requests outstanding in a protection domain, on its queue pairs
+    /// and shared receive queues.
+    pub max_outstanding_wr_per_pd: Option<u32>,
+    /// Maximum number of bytes of the buffers of the work requests outstanding in a protection
+    /// domain.
+    pub max_outstanding_bytes_per_pd: Option<u64>,
+}
+
+/// State of the backend of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RdmaBackendState {
+    /// The backend carries the messages of the device.
+    Up,
+    /// The backend failed its health probe, or couldn't be set up on restore. The device drops
+    /// the messages, with the link of its ports down, until the backend is set up again.
+    Down,
+}
+
+/// Status of a device and of its backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RdmaDeviceStatus {
+    /// Identifier of the device.
+    pub id: String,
+    /// Whether the link of the ports is up.
+    pub link_up: bool,
+    /// State of the backend.
+    pub backend_state: RdmaBackendState,
+    /// Number of attempts to set up the backend again which failed since it went down.
+    pub reconnect_attempts: u32,
+}
+
+/// Completion queue created by the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionQueue {
+    /// Number of entries of the queue.
+    pub cqe: u32,
+    /// Combination of the `RDMA_CQ_F_*` flags.
+    pub flags: u32,
+    /// Work completions not polled by the driver yet, oldest first.
+    pub completions: VecDeque<RdmaWc>,
+    /// `RDMA_CQ_*` notification flag the queue is armed with, or 0 if it is not armed.
+    pub armed: u32,
+    /// Whether a completion event waits for a buffer of the completion queue of the device.
+    pub event_pending: bool,
+}
+
+impl CompletionQueue {
+    /// Creates an empty completion queue of `cqe` entries.
+    pub fn new(cqe: u32, flags: u32) -> Self {
+        Self {
+            cqe,
+            flags,
+            completions: VecDeque::new(),
+            armed: 0,
+            event_pending: false,
+        }
+    }
+
+    /// Adds a work completion, returning whether the queue had room for it.
+    pub fn push(&mut self, wc: RdmaWc) -> bool {
+        if self.completions.len() >= usize::try_from(self.cqe).unwrap() {
+            return false;
+        }
+        self.completions.push_back(wc);
+        true
+    }
+
+    /// Resizes the queue to `cqe` entries, returning whether they fit its work completions.
+    pub fn resize(&mut self, cqe: u32) -> bool {
+        if self.completions.len() > usize::try_from(cqe).unwrap() {
+            return false;
+        }
+        self.cqe = cqe;
+        true
+    }
+
+    /// Raises a completion event if the work completion matches the notification the queue is
+    /// armed with, disarming it.
+    pub fn notify(&mut self, wc: &RdmaWc, solicited: bool) {
+        let triggered = match self.armed {
+            RDMA_CQ_NEXT_COMP => true,
+            RDMA_CQ_SOLICITED => solicited || wc.status != RDMA_WC_SUCCESS,
+            _ => false,
+        };
+        if triggered {
+            self.armed = 0;
+            self.event_pending = true;
+        }
+    }
+
+    /// Returns whether the queue waits for a buffer of the completion queue of the device, to
+    /// push its work completions or its completion event.
+    pub fn needs_buffer(&self) -> bool {
+        self.event_pending || (self.flags & RDMA_CQ_F_ASYNC != 0 && !self.completions.is_empty())
+    }
+}
+
+/// Shared receive queue created by the driver, from which the queue pairs created with it take
+/// their receive work requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedReceiveQueue {
+    /// Protection domain of the queue, which its work requests are confined to.
+    pub pd: u32,
+    pub max_wr: u32,
+    pub max_sge: u32,
+    /// Limit the queue is armed with, or 0 if it is not armed.
+    pub limit: u32,
+    /// Receive work requests not consumed yet, oldest first.
+    pub recv_queue: VecDeque<RecvWr>,
+}
+
+impl SharedReceiveQueue {
+    /// Takes the oldest receive work request, along with whether the number of work requests
+    /// left dropped below the limit of the queue, in which case the queue is disarmed.
+    pub fn pop(&mut self) -> Option<(RecvWr, bool)> {
+        let wr = self.recv_queue.pop_front()?;
+        let limit_reached =
+            self.limit != 0 && self.recv_queue.len() < usize::try_from(self.limit).unwrap();
+        if limit_reached {
+            self.limit = 0;
+        }
+        Some((wr, limit_reached))
+    }
+}
+
+/// Protection domain allocated by the driver, grouping the queue pairs and the memory regions
+/// their work requests may access.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectionDomain {
+    /// Outstanding work of the queue pairs and the shared receive queues of the domain.
+    pub outstanding: OutstandingWork,
+}
+
+/// Memory region registered by the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    /// Guest physical address of the start of the region.
+    pub iova: u64,
+    /// Length of the region, in bytes.
+    pub length: u64,
+    /// Combination of the `RDMA_ACCESS_*` flags.
+    pub access: u32,
+    /// Protection domain of the region.
+    pub pd: u32,
+    /// Whether the guest memory backing the region was removed, in which case work requests
+    /// may no longer access it.
+    pub invalidated: bool,
+}
+
+/// Memory window allocated by the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryWindow {
+    /// Protection domain of the window.
+    pub pd: u32,
+    /// Current remote key of the window.
+    pub rkey: u32,
+    /// Range of the memory region the window grants access to, until it is invalidated.
+    pub binding: Option<MwBinding>,
+}
+
+/// Range of a memory region a memory window is bound to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MwBinding {
+    /// Local key of the memory region.
+    pub lkey: u32,
+    /// Queue pair which bound the window, the only one remote peers may access it through.
+    pub qpn: u32,
+    /// Guest physical address of the start of the window.
+    pub addr: u64,
+    /// Length of the window, in bytes.
+    pub length: u64,
+    /// Combination of the `RDMA_ACCESS_REMOTE_*` flags.
+    pub access: u32,
+}
+
+/// Address handle created by the driver, addressing the remote peer of the work requests posted
+/// on unreliable datagram queue pairs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressHandle {
+    /// Protection domain of the address handle.
+    pub pd: u32,
+    pub port_num: u32,
+    /// GID of the destination.
+    pub dgid: [u8; 16],
+    /// Index of the source GID in the GID table of the port.
+    pub sgid_index: u32,
+    /// Traffic class of the global route header.
+    pub traffic_class: u8,
+    /// Hop limit of the global route header.
+    pub hop_limit: u8,
+}
+
+impl MemoryRegion {
+    /// Whether remote peers may access the region, in which case it has a remote key.
+    pub fn remote_access(&self) -> bool {
+        self.access
+            & (RDMA_ACCESS_REMOTE_WRITE | RDMA_ACCESS_REMOTE_READ | RDMA_ACCESS_REMOTE_ATOMIC)
+            != 0
+    }
+
+    /// Whether the region holds the `length` bytes at `addr`.
+    pub fn contains(&self, addr: u64, length: u64) -> bool {
+        addr >= self.iova
+            && addr
+                .checked_add(length)
+                .is_some_and(|end| end <= self.iova + self.length)
+    }
+
+    /// Whether the region overlaps the `len` bytes of guest memory at `addr`.
+    pub fn overlaps(&self, addr: GuestAddress, len: usize) -> bool {
+        self.iova < addr.raw_value().saturating_add(u64::try_from(len).unwrap())
+            && addr.raw_value() < self.iova + self.length
+    }
+}
+
+/// Limits and capabilities of a device, reported to the driver by `RDMA_CMD_QUERY_DEVICE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCapabilities {
+    /// Largest length of a memory region, in bytes.
+    pub max_mr_size: u64,
+    /// Page sizes supported by the memory regions, as a bitmask.
+    pub page_size_cap: u64,
+    /// Maximum number of queue pairs.
+    pub max_qp: u32,
+    /// Maximum number of outstanding work requests on a queue of a queue pair.
+    pub max_qp_wr: u32,
+    /// Maximum number of scatter/gather entries of a work request.
+    pub max_sge: u32,
+    /// Maximum length of the inline data of a work request, in bytes.
+    pub max_inline_data: u32,
+    /// Maximum number of completion queues.
+    pub max_cq: u32,
+    /// Maximum number of entries of a completion queue.
+    pub max_cqe: u32,
+    /// Maximum number of memory regions.
+    pub max_mr: u32,
+    /// Maximum number of memory windows.
+    pub max_mw: u32,
+    /// Maximum number of outstanding RDMA reads and atomics a queue pair handles as target.
+    pub max_qp_rd_atom: u32,
+    /// Maximum number of outstanding RDMA reads and atomics a queue pair initiates.
+    pub max_qp_init_rd_atom: u32,
+    /// One of the `RDMA_ATOMIC_*` capabilities.
+    pub atomic_cap: u32,
+    /// Number of ports, numbered from 1.
+    pub phys_port_cnt: u32,
+    /// Number of entries of the P_Key table of a port.
+    pub max_pkeys: u32,
+    /// Maximum number of protection domains.
+    pub max_pd: u32,
+    /// Maximum number of address handles.
+    pub max_ah: u32,
+    /// Maximum number of shared receive queues.
+    pub max_srq: u32,
+    /// Maximum number of outstanding work requests on a shared receive queue.
+    pub max_srq_wr: u32,
+    /// Maximum number of scatter/gather entries of a work request of a shared receive queue.
+    pub max_srq_sge: u32,
+}
+
+impl Default for RdmaCapabilities {
+    fn default() -> Self {
+        Self {
+            // Memory regions are only bounded by the guest memory backing them.
+            max_mr_size: u64::MAX,
+            page_size_cap: RDMA_PAGE_SIZE_CAP,
+            max_qp: RDMA_MAX_QP,
+            max_qp_wr: RDMA_MAX_QP_WR,
+            max_sge: RDMA_MAX_SGE,
+            max_inline_data: RDMA_MAX_INLINE_DATA,
+            max_cq: RDMA_MAX_CQ,
+            max_cqe: RDMA_MAX_CQE,
+            max_mr: RDMA_MAX_MR,
+            max_mw: RDMA_MAX_MW,
+            max_qp_rd_atom: RDMA_MAX_QP_RD_ATOM,
+            max_qp_init_rd_atom: RDMA_MAX_QP_RD_ATOM,
+            // Atomic operations are only executed when the backend is local.
+            atomic_cap: RDMA_ATOMIC_NONE,
+            phys_port_cnt: RDMA_NUM_PORTS,
+            max_pkeys: RDMA_PKEY_TABLE_LEN,
+            max_pd: RDMA_MAX_PD,
+            max_ah: RDMA_MAX_AH,
+            max_srq: RDMA_MAX_SRQ,
+            max_srq_wr: RDMA_MAX_SRQ_WR,
+            max_srq_sge: RDMA_MAX_SGE,
+        }
+    }
+}
+
+impl From<&RdmaCapabilities> for RdmaRspQueryDevice {
+    fn from(caps: &RdmaCapabilities) -> Self {
+        RdmaRspQueryDevice {
+            max_mr_size: caps.max_mr_size,
+            page_size_cap: caps.page_size_cap,
+            max_qp: caps.max_qp,
+            max_qp_wr: caps.max_qp_wr,
+            max_sge: caps.max_sge,
+            max_cq: caps.max_cq,
+            max_cqe: caps.max_cqe,
+            max_mr: caps.max_mr,
+            max_qp_rd_atom: caps.max_qp_rd_atom,
+            max_qp_init_rd_atom: caps.max_qp_init_rd_atom,
+            atomic_cap: caps.atomic_cap,
+            phys_port_cnt: caps.phys_port_cnt,
+            max_pkeys: caps.max_pkeys,
+            max_pd: caps.max_pd,
+            max_ah: caps.max_ah,
+            max_srq: caps.max_srq,
+            max_srq_wr: caps.max_srq_wr,
+            max_srq_sge: caps.max_srq_sge,
+            max_mw: caps.max_mw,
+            max_inline_data: caps.max_inline_data,
+        }
+    }
+}
+
+/// Attributes of the emulated port, reported to the driver by `RDMA_CMD_QUERY_PORT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaPortAttributes {
+    /// State of the link, one of the `RDMA_PORT_*` states.
+    pub state: u32,
+    /// Largest MTU supported by the port, one of the `RDMA_MTU_*` values.
+    pub max_mtu: u32,
+    /// Current MTU of the port, one of the `RDMA_MTU_*` values.
+    pub active_mtu: u32,
+    /// Number of entries of the GID table.
+    pub gid_tbl_len: u32,
+    /// Number of entries of the P_Key table.
+    pub pkey_tbl_len: u32,
+    /// Largest message a work request may carry, in bytes.
+    pub max_msg_sz: u32,
+    /// One of the `RDMA_LINK_LAYER_*` link layers.
+    pub link_layer: u32,
+}
+
+impl Default for RdmaPortAttributes {
+    fn default() -> Self {
+        Self {
+            // The emulated link is up until brought down.
+            state: RDMA_PORT_ACTIVE,
+            max_mtu: RDMA_MTU_4096,
+            // The largest MTU fitting in the payload of a standard 1500 bytes Ethernet frame.
+            active_mtu: RDMA_MTU_1024,
+            gid_tbl_len: RDMA_GID_TABLE_LEN,
+            pkey_tbl_len: RDMA_PKEY_TABLE_LEN,
+            max_msg_sz: RDMA_MAX_MSG_SIZE,
+            link_layer: RDMA_LINK_LAYER_ETHERNET,
+        }
+    }
+}
+
+impl From<&RdmaPortAttributes> for RdmaRspQueryPort {
+    fn from(port: &RdmaPortAttributes) -> Self {
+        RdmaRspQueryPort {
+            state: port.state,
+            max_mtu: port.max_mtu,
+            active_mtu: port.active_mtu,
+            gid_tbl_len: port.gid_tbl_len,
+            pkey_tbl_len: port.pkey_tbl_len,
+            max_msg_sz: port.max_msg_sz,
+            link_layer: port.link_layer,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct VirtioRdma {
     id: String,
-    avail_features: u64,
-    acked_features: u64,
+    pub(crate) avail_features: u64,
+    pub(crate) acked_features: u64,
     activate_event: EventFd,
     device_state: DeviceState,
-    queues: Vec<Queue>,
+    pub(crate) queues: Vec<Queue>,
     queue_events: Vec<EventFd>,
+    // Configured number of data queue pairs, the number of vCPUs if `None`.
+    num_queues: Option<u16>,
+    caps: RdmaCapabilities,
+    port: RdmaPortAttributes,
+    // Configured MTU of the ports, in bytes.
+    mtu: Option<u32>,
+    // Configured limits on the resources of the driver.
+    limits: RdmaResourceLimits,
+    node_guid: u64,
+    // GID table shared by the ports.
+    gids: GidTable,
+    // P_Key table shared by the ports.
+    pkeys: PkeyTable,
+    // Moderation of the interrupts of the completion queue, each buffer pushed to it interrupts
+    // the driver if `None`.
+    cq_moderation: Option<RdmaCqModeration>,
+    // Work completions and completion events pushed to the completion queue since its last
+    // interrupt.
+    unsignaled_cqes: u32,
+    // Timer of the interrupt of the completion queue deferred by its moderation.
+    pub(crate) cq_moderation_timer: TimerFd,
+
+    // RDMA resources created by the driver.
+    pub(crate) qps: ResourceTable<QueuePair>,
+    pub(crate) cqs: ResourceTable<CompletionQueue>,
+    // Memory regions, indexed by their local key.
+    pub(crate) mrs: ResourceTable<MemoryRegion>,
+    // Memory windows, indexed by the handle part of their remote key.
+    pub(crate) mws: ResourceTable<MemoryWindow>,
+    pub(crate) pds: ResourceTable<ProtectionDomain>,
+    pub(crate) ahs: ResourceTable<AddressHandle>,
+    pub(crate) srqs: ResourceTable<SharedReceiveQueue>,
+    // Transport of the messages sent by the work requests.
+    backend: Box<dyn RdmaBackend>,
+    // Nested epoll FD the events of the backend are registered under, which stays registered in
+    // the VMM epoll loop when the backend is replaced.
+    backend_epoll: Epoll,
+    // Configured backend, the loopback one if `None`.
+    backend_config: Option<RdmaBackendConfig>,
+    // Faults injected into the backend, if any.
+    fault_policy: Option<RdmaFaultPolicy>,
+    // Whether the configured backend is down, because it couldn't be set up on restore or it
+    // failed its health probe, in which case the device drops the messages, with the link of
+    // its ports down, until the backend is set up again.
+    backend_degraded: bool,
+    // Attempts to set up the backend again which failed since it went down.
+    backend_reconnect_attempts: u32,
+    // Timer of the next health probe of the backend, or of the next attempt to set it up again
+    // once down.
+    pub(crate) backend_timer: TimerFd,
+    // Creates the threads the backends performing host I/O run on once the microVM started.
+    launcher: Option<Arc<BackendLauncher>>,
+    // Doorbells of the queue pairs, mapped in the shared memory region of the device.
+    doorbells: Arc<RdmaDoorbells>,
+    // Removals of the guest memory which may back the memory regions.
+    pub(crate) memory_removals: Option<Arc<GuestMemoryRemovalListener>>,
+    // Asynchronous events not written to the event queue yet, oldest first.
+    pub(crate) pending_events: VecDeque<RdmaAsyncEvent>,
+    // Limits the rate of the send work requests, and of the bytes they carry.
+    pub(crate) rate_limiter: RateLimiter,
+    // Command queues whose processing stopped until the rate limiter allows more work requests.
+    throttled_queues: BTreeSet<usize>,
+    pub(crate) metrics: Arc<RdmaMetrics>,
 }
 
 impl VirtioRdma {
     pub fn new(id: String) -> Result<Self, RdmaError> {
         let activate_event = EventFd::new(libc::EFD_NONBLOCK)?;
-        let queues = vec![Queue::new(FIRECRACKER_MAX_QUEUE_SIZE); RDMA_NUM_QUEUES];
-        let queue_events = (0..RDMA_NUM_QUEUES)
-            .map(|_| EventFd::new(libc::EFD_NONBLOCK))
-            .collect::<Result<Vec<EventFd>, io::Error>>()?;
+        let metrics = RdmaMetricsPerDevice::alloc(id.clone());
+        let backend: Box<dyn RdmaBackend> = Box::new(LoopbackBackend::default());
+        let mut caps = RdmaCapabilities::default();
+        if backend.is_local() {
+            // The device owns the memory of both ends of the atomic operations, which it
+            // executes one at a time.
+            caps.atomic_cap = RDMA_ATOMIC_HCA;
+        }
 
-        Ok(Self {
+        let mut rdma = Self {
             id,
-            avail_features: 0,
+            // Packed rings are part of version 1.1 of the spec.
+            avail_features: (1 << VIRTIO_F_VERSION_1)
+                | (1 << VIRTIO_F_RING_PACKED)
+                | (1 << VIRTIO_RING_F_EVENT_IDX),
             acked_features: 0,
             activate_event,
             device_state: DeviceState::Inactive,
-            queues,
-            queue_events,
-        })
+            queues: Vec::new(),
+            queue_events: Vec::new(),
+            num_queues: None,
+            caps,
+            port: RdmaPortAttributes::default(),
+            mtu: None,
+            limits: RdmaResourceLimits::default(),
+            node_guid: node_guid(&id),
+            gids: GidTable::default(),
+            pkeys: PkeyTable::default(),
+            cq_moderation: None,
+            unsignaled_cqes: 0,
+            cq_moderation_timer: TimerFd::new(),
+            // The queue pair numbers index the pages of the doorbell region.
+            qps: ResourceTable::with_max_handle(caps.max_qp, caps.max_qp),
+            cqs: ResourceTable::new(caps.max_cq),
+            mrs: ResourceTable::with_max_handle(caps.max_mr, MAX_MR_KEY),
+            mws: ResourceTable::with_max_handle(caps.max_mw, MAX_MW_HANDLE),
+            pds: ResourceTable::new(caps.max_pd),
+            ahs: ResourceTable::new(caps.max_ah),
+            srqs: ResourceTable::new(caps.max_srq),
+            backend,
+            backend_epoll: Epoll::new()?,
+            backend_config: None,
+            fault_policy: None,
+            backend_degraded: false,
+            backend_reconnect_attempts: 0,
+            backend_timer: TimerFd::new(),
+            launcher: None,
+            doorbells: Arc::new(RdmaDoorbells::new()?),
+            memory_removals: None,
+            pending_events: VecDeque::new(),
+            rate_limiter: RateLimiter::default(),
+            throttled_queues: BTreeSet::new(),
+            metrics,
+        };
+        rdma.watch_backend_events()?;
+        rdma.create_queues(1)?;
+        Ok(rdma)
     }
 
-    pub(crate) fn activate_event(&self) -> &EventFd {
-        &self.activate_event
+    // Creates the queues of `num_queues` data queue pairs, next to the control, completion and
+    // event queues.
+    pub(crate) fn create_queues(&mut self, num_queues: u16) -> Result<(), io::Error> {
+        let count = rdma_num_queues(num_queues);
+        self.queue_events = (0..count)
+            .map(|_| EventFd::new(libc::EFD_NONBLOCK))
+            .collect::<Result<Vec<EventFd>, io::Error>>()?;
+        self.queues = vec![Queue::new(FIRECRACKER_MAX_QUEUE_SIZE); count];
+        Ok(())
     }
-}
 
-impl VirtioDevice for VirtioRdma {
-    impl_device_type!(VirtioDeviceType::Rdma);
+    /// Number of data queue pairs of the device.
+    pub fn num_queues(&self) -> u16 {
+        u16::try_from((self.queues.len() - RDMA_SEND_QUEUE) / 2).unwrap()
+    }
 
-    fn id(&self) -> &str {
-        &self.id
+    /// Configured number of data queue pairs, or `None` if it follows the number of vCPUs.
+    pub fn configured_num_queues(&self) -> Option<u16> {
+        self.num_queues
     }
 
-    fn queues(&self) -> &[Queue] {
-        &self.queues
+    /// Sets the number of data queue pairs of the device, which follows the number of vCPUs when
+    /// `None`. The queues must be set before the device is attached.
+    pub fn set_num_queues(&mut self, num_queues: Option<u16>) -> Result<(), RdmaError> {
+        if let Some(num_queues) = num_queues
+            && !(1..=RDMA_MAX_NUM_QUEUES).contains(&num_queues)
+        {
+            return Err(RdmaError::InvalidNumQueues(num_queues));
+        }
+        self.num_queues = num_queues;
+        self.create_queues(num_queues.unwrap_or(1))?;
+        Ok(())
     }
 
-    fn queues_mut(&mut self) -> &mut [Queue] {
-        &mut self.queues
+    /// Gives a data queue pair to each of the `vcpu_count` vCPUs, unless the number of data queue
+    /// pairs is configured.
+    pub fn set_vcpu_count(&mut self, vcpu_count: u8) -> Result<(), RdmaError> {
+        if self.num_queues.is_none() {
+            self.create_queues(u16::from(vcpu_count).clamp(1, RDMA_MAX_NUM_QUEUES))?;
+        }
+        Ok(())
     }
 
-    fn queue_events(&self) -> &[EventFd] {
-        &self.queue_events
+    /// Limits and capabilities of the device.
+    pub fn capabilities(&self) -> &RdmaCapabilities {
+        &self.caps
     }
 
-    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
-        self.device_state
-            .active_state()
-            .expect("Device is not initialized")
-            .interrupt
-            .deref()
+    /// Attributes of the ports of the device, which are all alike.
+    pub fn port_attributes(&self) -> &RdmaPortAttributes {
+        &self.port
     }
 
-    fn avail_features(&self) -> u64 {
-        self.avail_features
+    /// GID table of the ports of the device, which are all alike.
+    pub fn gid_table(&self) -> &GidTable {
+        &self.gids
     }
 
-    fn acked_features(&self) -> u64 {
-        self.acked_features
+    /// Sets the addresses the GIDs of the device are derived from. The driver of an activated
+    /// device is notified through the event queue when the GID table of a port changes.
+    pub fn set_addresses(
+        &mut self,
+        mac: Option<MacAddr>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RdmaError> {
+        let gids = GidTable::new(mac, ip);
+        if gids == self.gids {
+            return Ok(());
+        }
+        self.gids = gids;
+        if !self.is_activated() {
+            return Ok(());
+        }
+        for port_num in 1..=self.caps.phys_port_cnt {
+            self.push_event(RDMA_EVENT_GID_CHANGE, port_num);
+        }
+        self.deliver_events()
     }
 
-    fn set_acked_features(&mut self, acked_features: u64) {
-        self.acked_features = acked_features;
+    /// Brings the link of the ports up or down. The driver of an activated device is notified
+    /// through the event queue and a configuration change interrupt when the state of the link
+    /// changes.
+    pub fn set_link_up(&mut self, up: bool) -> Result<(), RdmaError> {
+        let (state, event_type) = link_state(up);
+        if state == self.port.state {
+            return Ok(());
+        }
+        self.port.state = state;
+        if !self.is_activated() {
+            return Ok(());
+        }
+        // The state of the link is part of the configuration space.
+        self.interrupt_trigger()
+            .trigger(VirtioInterruptType::Config)
+            .unwrap_or_else(|err| {
+                error!("rdma: {err}");
+                self.metrics.event_fails.inc();
+            });
+        for port_num in 1..=self.caps.phys_port_cnt {
+            self.push_event(event_type, port_num);
+        }
+        self.deliver_events()
     }
 
-    fn read_config(&self, _offset: u64, _data: &mut [u8]) {}
+    /// Node GUID of the device, derived from its identifier.
+    pub fn node_guid(&self) -> u64 {
+        self.node_guid
+    }
 
-    fn write_config(&mut self, _offset: u64, _data: &[u8]) {}
+    /// Configured backend, or `None` if the device uses the loopback one.
+    pub fn backend_config(&self) -> Option<&RdmaBackendConfig> {
+        self.backend_config.as_ref()
+    }
 
-    fn is_activated(&self) -> bool {
-        self.device_state.is_activated()
+    /// Sets up the backend of `config`, or the loopback one when `None`, injecting the faults of
+    /// the fault policy into it. Once the device is activated, the backend is replaced without
+    /// resetting it: the messages received by the previous backend are delivered first, those it
+    /// still carries are lost, and the new backend must be local if the previous one was, as the
+    /// driver relies on the atomic capabilities of the device.
+    pub fn set_backend(
+        &mut self,
+        config: Option<RdmaBackendConfig>,
+    ) -> Result<(), RdmaBackendError> {
+        let mut backend = match &config {
+            Some(config) => config.spawn(self.launcher.as_deref())?,
+            None => Box::new(LoopbackBackend::default()),
+        };
+        if self.is_activated() {
+            if backend.is_local() != self.backend.is_local() {
+                return Err(RdmaBackendError::LocalityChange);
+            }
+            if let Err(err) = self.process_backend() {
+                error!("rdma: {err:?}");
+                self.metrics.event_fails.inc();
+            }
+        }
+        if let Some(policy) = self.fault_policy {
+            backend = Box::new(FaultInjectionBackend::new(backend, policy));
+        }
+        self.unwatch_backend_events();
+        self.backend = backend;
+        self.watch_backend_events()
+            .map_err(RdmaBackendError::Events)?;
+        // Only the devices owning the memory of both ends execute atomic operations.
+        self.caps.atomic_cap = if self.backend.is_local() {
+            RDMA_ATOMIC_HCA
+        } else {
+            RDMA_ATOMIC_NONE
+        };
+        self.backend_config = config;
+        self.backend_reconnect_attempts = 0;
+        // The backend replaces the one which went down, which brings the link up.
+        if std::mem::take(&mut self.backend_degraded) {
+            self.set_link_up(true).unwrap_or_else(|err| {
+                error!("rdma: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+        }
+        if self.is_activated() {
+            self.arm_backend_timer();
+        }
+        Ok(())
     }
 
-    fn activate(
+    /// Installs the seccomp filter of `launcher` on the thread of the backend, if it runs on a
+    /// thread of its own, and has `launcher` create the threads of the backends set up later.
+    /// The backends performing host I/O run on a thread of their own, so that the system calls
+    /// they need aren't allowed to the VMM thread.
+    pub fn set_backend_launcher(
         &mut self,
-        mem: GuestMemoryMmap,
-        interrupt: Arc<dyn VirtioInterrupt>,
-    ) -> Result<(), ActivateError> {
-        if self.queues.len() != RDMA_NUM_QUEUES {
-            return Err(ActivateError::QueueMismatch {
-                expected: RDMA_NUM_QUEUES,
-                got: self.queues.len(),
+        launcher: Arc<BackendLauncher>,
+    ) -> Result<(), RdmaBackendError> {
+        // The filters installed on a thread add up, so the same one is only installed once.
+        if self
+            .launcher
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &launcher))
+        {
+            return Ok(());
+        }
+        self.backend
+            .apply_seccomp_filter(launcher.seccomp_filter().clone())
+            .map_err(RdmaBackendError::Seccomp)?;
+        self.launcher = Some(launcher);
+        Ok(())
+    }
+
+    /// Whether the configured backend is down, because it couldn't be set up on restore or it
+    /// failed its health probe, in which case the device drops the messages of the work
+    /// requests, with the link of its ports down, until the backend is set up again.
+    pub fn backend_degraded(&self) -> bool {
+        self.backend_degraded
+    }
+
+    /// Status of the device and of its backend.
+    pub fn status(&self) -> RdmaDeviceStatus {
+        RdmaDeviceStatus {
+            id: self.id.clone(),
+            link_up: self.port.state == RDMA_PORT_ACTIVE,
+            backend_state: if self.backend_degraded {
+                RdmaBackendState::Down
+            } else {
+                RdmaBackendState::Up
+            },
+            reconnect_attempts: self.backend_reconnect_attempts,
+        }
+    }
+
+    /// Sets up the backend of `config` on restore, `degraded` telling whether the device was
+    /// saved without it. When the backend can't be set up and `allow_degraded`, the device
+    /// drops the messages of the work requests with the link of its ports down, and keeps
+    /// `config` for the backend to be set up again once the device is activated. The reliable
+    /// connected queue pairs are reconnected to their peer once the backend is set up. The driver
+    /// of the device is notified of the changes of the state of the link, and of the queue pairs
+    /// which couldn't be reconnected, once the device is activated.
+    pub(crate) fn restore_backend(
+        &mut self,
+        config: Option<RdmaBackendConfig>,
+        degraded: bool,
+        allow_degraded: bool,
+    ) -> Result<(), RdmaBackendError> {
+        match self.set_backend(config.clone()) {
+            Ok(()) => {
+                if degraded {
+                    self.restore_link_state(true);
+                }
+                self.reconnect_qps();
+                Ok(())
+            }
+            Err(err) if allow_degraded => {
+                warn!(
+                    "rdma: Restoring device {} without its backend: {err}",
+                    self.id
+                );
+                self.set_backend(Some(RdmaBackendConfig::Null))?;
+                self.backend_config = config;
+                self.backend_degraded = true;
+                self.metrics.degraded_restores.inc();
+                if !degraded {
+                    self.restore_link_state(false);
+                }
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Arms the timer of the backend for its next health probe, or for the next attempt to set it
+    // up again once down. Only the backends performing host I/O are probed.
+    fn arm_backend_timer(&mut self) {
+        let delay = if self.backend_degraded {
+            backend_reconnect_delay(self.backend_reconnect_attempts)
+        } else if self
+            .backend_config
+            .as_ref()
+            .is_some_and(RdmaBackendConfig::performs_host_io)
+        {
+            BACKEND_PROBE_PERIOD
+        } else {
+            // Arming the timer with a zero duration disarms it.
+            Duration::ZERO
+        };
+        self.backend_timer.arm(delay, None);
+    }
+
+    /// Probes the health of the backend, or attempts to set it up again once down, when the
+    /// timer of the backend expired. A backend failing its probe is replaced with the null one
+    /// and the link of the ports goes down, which the driver is notified of, until an attempt to
+    /// set up the backend of the configuration again succeeds. The attempts back off
+    /// exponentially.
+    pub(crate) fn process_backend_timer(&mut self) {
+        if self.backend_degraded {
+            self.reconnect_backend();
+        } else if !self.backend.probe() {
+            warn!(
+                "rdma: The backend of device {} failed its health probe",
+                self.id
+            );
+            self.metrics.backend_probe_fails.inc();
+            self.take_backend_down();
+        }
+        self.arm_backend_timer();
+    }
+
+    // Replaces the backend which failed with the null one, keeping its configuration to set it
+    // up again, and brings the link of the ports down.
+    fn take_backend_down(&mut self) {
+        let config = self.backend_config.take();
+        if let Err(err) = self.set_backend(Some(RdmaBackendConfig::Null)) {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        }
+        self.backend_config = config;
+        self.backend_degraded = true;
+        self.set_link_up(false).unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    // Attempts to set up the backend of the configuration again, which brings the link of the
+    // ports up and reconnects the queue pairs when it succeeds.
+    fn reconnect_backend(&mut self) {
+        match self.set_backend(self.backend_config.clone()) {
+            Ok(()) => {
+                self.metrics.backend_reconnects.inc();
+                self.reconnect_qps();
+                if let Err(err) = self
+                    .deliver_completions()
+                    .and_then(|()| self.deliver_events())
+                {
+                    error!("rdma: {err:?}");
+                    self.metrics.event_fails.inc();
+                }
+            }
+            Err(err) => {
+                self.backend_reconnect_attempts = self.backend_reconnect_attempts.saturating_add(1);
+                warn!(
+                    "rdma: Unable to set up the backend of device {} again (attempt {}): {err}",
+                    self.id, self.backend_reconnect_attempts
+                );
+                self.metrics.backend_reconnect_fails.inc();
+            }
+        }
+    }
+
+    // Sets up again the paths of the reliable connected queue pairs which are ready to send, to
+    // the peers of the attributes they were saved with, once the backend of a restored device is
+    // set up. The queue pairs whose peer can't be reached are moved to the error state, which the
+    // driver is notified of, rather than left to fail their next work requests.
+    fn reconnect_qps(&mut self) {
+        let qps: Vec<(u32, [u8; 16], u32)> = self
+            .qps
+            .iter()
+            .filter(|(_, qp)| qp.qp_type == RDMA_QPT_RC && qp.state == RDMA_QPS_RTS)
+            .map(|(qpn, qp)| (qpn, qp.attrs.dgid, qp.attrs.dest_qp_num))
+            .collect();
+        for (qpn, dgid, dest_qpn) in qps {
+            // The peers of the queue pairs of local backends are queue pairs of the device.
+            let reachable = if self.backend.is_local() {
+                self.qps.get(dest_qpn).is_some()
+            } else {
+                self.backend.connect_qp(qpn, dgid)
+            };
+            if !reachable {
+                warn!(
+                    "rdma: Unable to reconnect queue pair {qpn} of device {} to its peer",
+                    self.id
+                );
+                self.metrics.qp_reconnect_fails.inc();
+                self.push_event(RDMA_EVENT_QP_FATAL, qpn);
+                self.set_qp_error(qpn);
+            }
+        }
+    }
+
+    // Sets the state of the link of the ports of a device being restored, queueing the events
+    // notifying the driver.
+    fn restore_link_state(&mut self, up: bool) {
+        let (state, event_type) = link_state(up);
+        if state == self.port.state {
+            return;
+        }
+        self.port.state = state;
+        for port_num in 1..=self.caps.phys_port_cnt {
+            self.push_event(event_type, port_num);
+        }
+    }
+
+    // Registers the events of the backend under the nested epoll FD.
+    fn watch_backend_events(&self) -> io::Result<()> {
+        for fd in self.backend.events() {
+            self.backend_epoll.ctl(
+                ControlOperation::Add,
+                fd,
+                EpollEvent::new(EventSet::IN, u64::try_from(fd).unwrap()),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Unregisters the events of the backend from the nested epoll FD, before it is replaced.
+    fn unwatch_backend_events(&self) {
+        for fd in self.backend.events() {
+            // The events may not have been registered if the backend was set up half way.
+            let _ = self
+                .backend_epoll
+                .ctl(ControlOperation::Delete, fd, EpollEvent::default());
+        }
+    }
+
+    /// Faults injected into the backend, or `None` if there are none.
+    pub fn fault_policy(&self) -> Option<RdmaFaultPolicy> {
+        self.fault_policy
+    }
+
+    /// Injects the faults of `policy` into the backend, and into those set up after it. The
+    /// policy is set once, before the device is activated.
+    pub fn set_fault_policy(
+        &mut self,
+        policy: Option<RdmaFaultPolicy>,
+    ) -> Result<(), RdmaBackendError> {
+        if let Some(policy) = policy {
+            self.unwatch_backend_events();
+            let backend =
+                std::mem::replace(&mut self.backend, Box::new(LoopbackBackend::default()));
+            self.backend = Box::new(FaultInjectionBackend::new(backend, policy));
+            self.watch_backend_events()
+                .map_err(RdmaBackendError::Events)?;
+        }
+        self.fault_policy = policy;
+        Ok(())
+    }
+
+    /// Configured MTU of the ports, in bytes, or `None` if they use the default one.
+    pub fn configured_mtu(&self) -> Option<u32> {
+        self.mtu
+    }
+
+    /// Sets the MTU of the ports, in bytes, which is 1024 bytes when `None`.
+    pub fn set_mtu(&mut self, mtu: Option<u32>) -> Result<(), RdmaError> {
+        self.port.active_mtu = match mtu {
+            Some(mtu) => mtu_value(mtu).ok_or(RdmaError::InvalidMtu(mtu))?,
+            None => RdmaPortAttributes::default().active_mtu,
+        };
+        self.mtu = mtu;
+        Ok(())
+    }
+
+    /// Configured limits on the resources of the driver.
+    pub fn resource_limits(&self) -> RdmaResourceLimits {
+        self.limits
+    }
+
+    /// Limits the resources the driver creates, which are reported to it as the limits of the
+    /// device. The limits must be set before the device is activated.
+    pub fn set_resource_limits(&mut self, limits: RdmaResourceLimits) -> Result<(), RdmaError> {
+        let check = |name: &'static str, limit: Option<u64>, max: u64| match limit {
+            Some(limit) if !(1..=max).contains(&limit) => {
+                Err(RdmaError::InvalidResourceLimit(name, limit, max))
+            }
+            _ => Ok(()),
+        };
+        check(
+            "queue pair",
+            limits.max_qp.map(u64::from),
+            u64::from(RDMA_MAX_QP),
+        )?;
+        check(
+            "completion queue",
+            limits.max_cq.map(u64::from),
+            u64::from(RDMA_MAX_CQ),
+        )?;
+        check(
+            "memory region",
+            limits.max_mr.map(u64::from),
+            u64::from(RDMA_MAX_MR),
+        )?;
+        check(
+            "memory registration",
+            limits.max_memory_registration_bytes,
+            u64::MAX,
+        )?;
+        check(
+            "queue pair work request",
+            limits.max_outstanding_wr_per_qp.map(u64::from),
+            u64::from(u32::MAX),
+        )?;
+        check(
+            "queue pair byte",
+            limits.max_outstanding_bytes_per_qp,
+            u64::MAX,
+        )?;
+        check(
+            "protection domain work request",
+            limits.max_outstanding_wr_per_pd.map(u64::from),
+            u64::from(u32::MAX),
+        )?;
+        check(
+            "protection domain byte",
+            limits.max_outstanding_bytes_per_pd,
+            u64::MAX,
+        )?;
+
+        let defaults = RdmaCapabilities::default();
+        self.caps.max_qp = limits.max_qp.unwrap_or(defaults.max_qp);
+        self.caps.max_cq = limits.max_cq.unwrap_or(defaults.max_cq);
+        self.caps.max_mr = limits.max_mr.unwrap_or(defaults.max_mr);
+        // No memory region can be larger than all of them together.
+        self.caps.max_mr_size = limits
+            .max_memory_registration_bytes
+            .unwrap_or(defaults.max_mr_size);
+        self.qps.set_max_entries(self.caps.max_qp);
+        self.cqs.set_max_entries(self.caps.max_cq);
+        self.mrs.set_max_entries(self.caps.max_mr);
+        self.limits = limits;
+        Ok(())
+    }
+
+    // Returns the number of bytes of guest memory registered by the memory regions.
+    fn registered_bytes(&self) -> u64 {
+        self.mrs.iter().map(|(_, mr)| mr.length).sum()
+    }
+
+    /// Rate limiter of the send work requests.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Sets the rate limiter of the send work requests, which must be set before the device is
+    /// activated.
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Updates the parameters of the rate limiter of the send work requests.
+    pub fn update_rate_limiter(&mut self, bytes: BucketUpdate, ops: BucketUpdate) {
+        self.rate_limiter.update_buckets(bytes, ops);
+    }
+
+    /// Moderation of the completion interrupts, or `None` if each completion interrupts the
+    /// driver.
+    pub fn cq_moderation(&self) -> Option<RdmaCqModeration> {
+        self.cq_moderation
+    }
+
+    /// Sets the moderation of the completion interrupts. The completions waiting for an
+    /// interrupt get it once they are no longer moderated.
+    pub fn set_cq_moderation(
+        &mut self,
+        moderation: Option<RdmaCqModeration>,
+    ) -> Result<(), RdmaError> {
+        if moderation
+            .is_some_and(|moderation| moderation.max_cqes == 0 || moderation.max_usecs == 0)
+        {
+            return Err(RdmaError::InvalidCqModeration);
+        }
+        self.cq_moderation = moderation;
+        if moderation.is_none() && self.cq_moderation_timer.is_armed() && self.is_activated() {
+            self.flush_completions();
+        }
+        Ok(())
+    }
+
+    /// P_Key table of the ports of the device, which are all alike.
+    pub fn pkey_table(&self) -> &PkeyTable {
+        &self.pkeys
+    }
+
+    /// Sets the P_Keys of the partitions the device belongs to, next to the default one.
+    pub fn set_pkeys(&mut self, pkeys: &[u16]) -> Result<(), PkeyTableError> {
+        self.pkeys = PkeyTable::new(pkeys)?;
+        Ok(())
+    }
+
+    /// Subscribes the device to the removals of guest memory, invalidating the memory regions
+    /// they overlap.
+    pub fn set_memory_removal_listener(&mut self, listener: Arc<GuestMemoryRemovalListener>) {
+        self.memory_removals = Some(listener);
+    }
+
+    pub(crate) fn activate_event(&self) -> &EventFd {
+        &self.activate_event
+    }
+
+    pub(crate) fn doorbell_event(&self) -> &EventFd {
+        self.doorbells.event()
+    }
+
+    pub(crate) fn backend_event(&self) -> RawFd {
+        self.backend_epoll.as_raw_fd()
+    }
+
+    /// Guest memory of the activated device.
+    fn mem(&self) -> &GuestMemoryMmap {
+        // This is safe since we checked in the event handler that the device is activated.
+        &self.device_state.active_state().unwrap().mem
+    }
+
+    fn signal_used_queue(&mut self, queue_index: usize) {
+        self.queues[queue_index].advance_used_ring_idx();
+
+        if self.queues[queue_index].prepare_kick() {
+            // This is safe since we checked in the event handler that the device is activated.
+            let active_state = self.device_state.active_state().unwrap();
+            active_state
+                .interrupt
+                .trigger(VirtioInterruptType::Queue(
+                    u16::try_from(queue_index).unwrap(),
+                ))
+                .unwrap_or_else(|err| {
+                    error!("rdma: {err}");
+                    self.metrics.event_fails.inc();
+                });
+        }
+    }
+
+    /// Executes the commands of the control queue or of a data queue.
+    pub fn process_cmd_queue(&mut self, queue_index: usize) -> Result<(), RdmaError> {
+        // The commands must not access guest memory removed before they were queued.
+        self.process_memory_removals()?;
+        while let Some(head) = self.queues[queue_index].pop_or_enable_notification()? {
+            let index = head.index;
+            let result = match Command::parse(head, self.mem()) {
+                Ok(command) if !self.rate_limit(&command) => {
+                    // The command, and the ones queued after it, are processed once the rate
+                    // limiter replenishes.
+                    self.queues[queue_index].undo_pop();
+                    self.throttled_queues.insert(queue_index);
+                    self.metrics.rate_limiter_throttled.inc();
+                    break;
+                }
+                Ok(command) => self.process_command(command, queue_index),
+                Err(err) => Err(err),
+            };
+            let used_len = result.unwrap_or_else(|err| {
+                error!("rdma: {err}");
+                self.metrics.event_fails.inc();
+                0
             });
+            self.queues[queue_index].add_used(index, used_len)?;
         }
+        // The messages sent by the work requests of the queue are submitted at once.
+        self.backend.submit();
+        self.signal_used_queue(queue_index);
+        // The commands may have completed work requests, and reached the limit of shared
+        // receive queues.
+        self.deliver_completions()?;
+        self.deliver_events()
+    }
 
-        for q in self.queues.iter_mut() {
-            q.initialize(&mem)
-                .map_err(ActivateError::QueueMemoryError)?;
+    /// Processes the data queues whose doorbells were rung by their queue pairs.
+    pub fn process_doorbells(&mut self) -> Result<(), RdmaError> {
+        let mut queue_indexes = BTreeSet::new();
+        for (qpn, queue_index) in self.doorbells.take_rung() {
+            self.metrics.doorbell_count.inc();
+            let queue_index = queue_index as usize;
+            if self.qps.get(qpn).is_none()
+                || !(RDMA_SEND_QUEUE..self.queues.len()).contains(&queue_index)
+            {
+                warn!("rdma: Invalid doorbell of queue pair {qpn} for queue {queue_index}");
+                self.metrics.doorbell_fails.inc();
+                continue;
+            }
+            queue_indexes.insert(queue_index);
         }
+        for queue_index in queue_indexes {
+            self.process_cmd_queue(queue_index)?;
+        }
+        Ok(())
+    }
 
-        self.activate_event.write(1).map_err(|_| ActivateError::EventFd)?;
-        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+    /// Resumes the command queues throttled by the rate limiter, once it replenished.
+    pub fn process_rate_limiter(&mut self) -> Result<(), RdmaError> {
+        for queue_index in std::mem::take(&mut self.throttled_queues) {
+            self.process_cmd_queue(queue_index)?;
+        }
         Ok(())
     }
+
+    // Consumes the tokens of a command from the rate limiter, returning whether it allows the
+    // command. Each send work request takes an operation, and the bytes of its payload.
+    fn rate_limit(&mut self, command: &Command) -> bool {
+        if command.hdr.opcode != RDMA_CMD_POST_SEND {
+            return true;
+        }
+        let bytes = self.post_send_len(&command.args);
+        if !self.rate_limiter.consume(1, TokenType::Ops) {
+            return false;
+        }
+        if !self.rate_limiter.consume(bytes, TokenType::Bytes) {
+            self.rate_limiter.manual_replenish(1, TokenType::Ops);
+            return false;
+        }
+        true
+    }
+
+    // Returns the length of the payload of a send work request, which is 0 for the invalid
+    // ones, as they fail without sending anything.
+    fn post_send_len(&self, args: &Args) -> u64 {
+        let Ok(cmd) = args.read::<RdmaCmdPostSend>(self.mem()) else {
+            return 0;
+        };
+        match cmd.opcode {
+            RDMA_WR_BIND_MW | RDMA_WR_LOCAL_INV => 0,
+            _ if cmd.send_flags & RDMA_SEND_INLINE != 0 => u64::from(cmd.inline_len),
+            _ if cmd.num_sge > self.caps.max_sge => 0,
+            opcode => {
+                let offset = if matches!(
+                    opcode,
+                    RDMA_WR_ATOMIC_CMP_AND_SWP | RDMA_WR_ATOMIC_FETCH_AND_ADD
+                ) {
+                    size_of::<RdmaCmdPostSend>() + size_of::<RdmaAtomic>()
+                } else {
+                    size_of::<RdmaCmdPostSend>()
+                };
+                args.read_sges(self.mem(), offset, cmd.num_sge)
+                    .map(|sges| sges.iter().map(|sge| u64::from(sge.length)).sum())
+                    .unwrap_or(0)
+            }
+        }
+    }
+
+    /// Delivers the messages received by the backend since its host I/O last completed, and
+    /// moves the queue pairs whose messages it gave up delivering to the error state.
+    pub fn process_backend(&mut self) -> Result<(), RdmaError> {
+        self.backend.process_event();
+        self.process_rx();
+        for qpn in self.backend.take_failed_qps() {
+            // The queue pair may have been destroyed since it sent the messages.
+            if self.qps.get(qpn).is_some_and(|qp| qp.state != RDMA_QPS_ERR) {
+                warn!("rdma: Backend gave up delivering the messages of queue pair {qpn}");
+                self.push_event(RDMA_EVENT_QP_FATAL, qpn);
+                self.set_qp_error(qpn);
+            }
+        }
+        // The messages may have completed work requests, and reached the limit of shared
+        // receive queues.
+        self.deliver_completions()?;
+        self.deliver_events()
+    }
+
+    /// Pushes the work completions of the completion queues created with `RDMA_CQ_F_ASYNC`, and
+    /// the completion events of the armed completion queues, to the buffers of the completion
+    /// queue of the device. What is left when the driver runs out of buffers is pushed once it
+    /// queues more.
+    pub fn deliver_completions(&mut self) -> Result<(), RdmaError> {
+        let next_used = self.queues[RDMA_CQ_QUEUE].next_used;
+        let pending: Vec<u32> = self
+            .cqs
+            .iter()
+            .filter(|(_, cq)| cq.needs_buffer())
+            .map(|(cqn, _)| cqn)
+            .collect();
+        'cqs: for cqn in pending {
+            while self.cqs.get(cqn).unwrap().needs_buffer() {
+                let Some(head) = self.queues[RDMA_CQ_QUEUE].pop_or_enable_notification()? else {
+                    break 'cqs;
+                };
+                let index = head.index;
+                let result = if self.cqs.get(cqn).unwrap().event_pending {
+                    self.fill_cq_notification(cqn, head)
+                } else {
+                    self.fill_cq_buffer(cqn, head)
+                };
+                let used_len = result.unwrap_or_else(|err| {
+                    error!("rdma: {err}");
+                    self.metrics.event_fails.inc();
+                    0
+                });
+                self.queues[RDMA_CQ_QUEUE].add_used(index, used_len)?;
+            }
+        }
+        if self.queues[RDMA_CQ_QUEUE].next_used != next_used {
+            self.signal_completions();
+        }
+        Ok(())
+    }
+
+    // Interrupts the driver for the buffers pushed to the completion queue, unless the moderation
+    // defers it until enough work completions wait for it or its timer expires.
+    fn signal_completions(&mut self) {
+        let Some(moderation) = self.cq_moderation else {
+            self.flush_completions();
+            return;
+        };
+        if self.unsignaled_cqes >= u32::from(moderation.max_cqes) {
+            self.flush_completions();
+            return;
+        }
+        // A driver polling the completion queue sees the buffers meanwhile.
+        self.queues[RDMA_CQ_QUEUE].advance_used_ring_idx();
+        if !self.cq_moderation_timer.is_armed() {
+            self.cq_moderation_timer
+                .arm(Duration::from_micros(u64::from(moderation.max_usecs)), None);
+        }
+        self.metrics.cq_deferred_interrupts.inc();
+    }
+
+    /// Interrupts the driver for the buffers pushed to the completion queue since its last
+    /// interrupt, which the moderation may have deferred.
+    pub fn flush_completions(&mut self) {
+        self.unsignaled_cqes = 0;
+        if self.cq_moderation_timer.is_armed() {
+            // A zero duration disarms the timer.
+            self.cq_moderation_timer.arm(Duration::ZERO, None);
+        }
+        self.signal_used_queue(RDMA_CQ_QUEUE);
+    }
+
+    /// Moves the oldest work completions of the completion queue `cqn` to a buffer of the
+    /// completion queue of the device, returning the number of bytes written to it.
+    fn fill_cq_buffer(&mut self, cqn: u32, head: DescriptorChain) -> Result<u32, RdmaError> {
+        if !head.is_write_only() {
+            return Err(RdmaError::UnexpectedDescriptorDirection);
+        }
+        let room = usize::try_from(head.len)
+            .unwrap()
+            .saturating_sub(size_of::<RdmaCqEvent>())
+            / size_of::<RdmaWc>();
+        if room == 0 {
+            return Err(RdmaError::DescriptorTooSmall);
+        }
+
+        let completions = &self.cqs.get(cqn).unwrap().completions;
+        let num_entries = room.min(completions.len());
+        let event = RdmaCqEvent {
+            cqn,
+            num_entries: u32::try_from(num_entries).unwrap(),
+        };
+        let mut buffer = event.as_slice().to_vec();
+        for wc in completions.iter().take(num_entries) {
+            buffer.extend_from_slice(wc.as_slice());
+        }
+        self.mem().write_slice(&buffer, head.addr)?;
+        // The work completions are only consumed once written.
+        self.cqs
+            .get_mut(cqn)
+            .unwrap()
+            .completions
+            .drain(..num_entries);
+        self.unsignaled_cqes = self
+            .unsignaled_cqes
+            .saturating_add(u32::try_from(num_entries).unwrap());
+        self.metrics.cq_event_count.inc();
+        Ok(u32::try_from(buffer.len()).unwrap())
+    }
+
+    /// Writes the completion event of the armed completion queue `cqn` to a buffer of the
+    /// completion queue of the device, returning the number of bytes written to it.
+    fn fill_cq_notification(&mut self, cqn: u32, head: DescriptorChain) -> Result<u32, RdmaError> {
+        if !head.is_write_only() {
+            return Err(RdmaError::UnexpectedDescriptorDirection);
+        }
+        if usize::try_from(head.len).unwrap() < size_of::<RdmaCqEvent>() {
+            return Err(RdmaError::DescriptorTooSmall);
+        }
+
+        // The work completions themselves are left to be polled.
+        let event = RdmaCqEvent {
+            cqn,
+            num_entries: 0,
+        };
+        self.mem().write_obj(event, head.addr)?;
+        self.cqs.get_mut(cqn).unwrap().event_pending = false;
+        // The completion event stands for the work completions left to be polled.
+        self.unsignaled_cqes = self.unsignaled_cqes.saturating_add(1);
+        self.metrics.cq_notify_count.inc();
+        Ok(u32::try_from(size_of::<RdmaCqEvent>()).unwrap())
+    }
+
+    /// Invalidates the memory regions overlapping the guest memory removed since the last call,
+    /// notifying the driver through the event queue.
+    pub fn process_memory_removals(&mut self) -> Result<(), RdmaError> {
+        let Some(listener) = &self.memory_removals else {
+            return Ok(());
+        };
+        let ranges = listener.take_ranges();
+        if ranges.is_empty() {
+            return Ok(());
+        }
+
+        let mut invalidated = Vec::new();
+        for (lkey, mr) in self.mrs.iter_mut() {
+            if mr.invalidated || !ranges.iter().any(|&(addr, len)| mr.overlaps(addr, len)) {
+                continue;
+            }
+            warn!(
+                "rdma: Invalidated memory region {lkey:#x} [{:#x}, +{:#x}) after the removal of \
+                 its guest memory",
+                mr.iova, mr.length
+            );
+            mr.invalidated = true;
+            self.metrics.mr_invalidations.inc();
+            invalidated.push(lkey);
+        }
+        for lkey in invalidated {
+            self.push_event(RDMA_EVENT_MR_INVALIDATED, lkey);
+        }
+        self.deliver_events()
+    }
+
+    /// Queues an asynchronous event for the driver, unless it is already pending. The event is
+    /// dropped when `MAX_PENDING_EVENTS` events already wait for a buffer.
+    fn push_event(&mut self, event_type: u32, handle: u32) {
+        let event = RdmaAsyncEvent { event_type, handle };
+        if self.pending_events.contains(&event) {
+            return;
+        }
+        if self.pending_events.len() >= MAX_PENDING_EVENTS {
+            warn!("rdma: Dropped asynchronous event {event_type} of {handle}");
+            self.metrics.event_drops.inc();
+            return;
+        }
+        self.pending_events.push_back(event);
+    }
+
+    /// Writes the pending asynchronous events to the buffers of the event queue, one event per
+    /// buffer. The events left when the driver runs out of buffers are written once it queues
+    /// more.
+    pub fn deliver_events(&mut self) -> Result<(), RdmaError> {
+        // Events are kept until the driver activates the device and queues buffers.
+        if !self.is_activated() {
+            return Ok(());
+        }
+        let next_used = self.queues[RDMA_EVENT_QUEUE].next_used;
+        while let Some(&event) = self.pending_events.front() {
+            let Some(head) = self.queues[RDMA_EVENT_QUEUE].pop_or_enable_notification()? else {
+                break;
+            };
+            let index = head.index;
+            let used_len = self
+                .fill_event_buffer(event, head)
+                .inspect(|_| {
+                    // The event is only consumed once written.
+                    self.pending_events.pop_front();
+                })
+                .unwrap_or_else(|err| {
+                    error!("rdma: {err}");
+                    self.metrics.event_fails.inc();
+                    0
+                });
+            self.queues[RDMA_EVENT_QUEUE].add_used(index, used_len)?;
+        }
+        if self.queues[RDMA_EVENT_QUEUE].next_used != next_used {
+            self.signal_used_queue(RDMA_EVENT_QUEUE);
+        }
+        Ok(())
+    }
+
+    /// Writes an asynchronous event to a buffer of the event queue, returning the number of bytes
+    /// written to it.
+    fn fill_event_buffer(
+        &self,
+        event: RdmaAsyncEvent,
+        head: DescriptorChain,
+    ) -> Result<u32, RdmaError> {
+        if !head.is_write_only() {
+            return Err(RdmaError::UnexpectedDescriptorDirection);
+        }
+        if usize::try_from(head.len).unwrap() < size_of::<RdmaAsyncEvent>() {
+            return Err(RdmaError::DescriptorTooSmall);
+        }
+        self.mem().write_obj(event, head.addr)?;
+        Ok(u32::try_from(size_of::<RdmaAsyncEvent>()).unwrap())
+    }
+
+    /// Executes a command popped from the queue `queue_index`, returning the number of bytes
+    /// written to the response.
+    fn process_command(&mut self, command: Command, queue_index: usize) -> Result<u32, RdmaError> {
+        let Command {
+            hdr,
+            args,
+            response,
+        } = command;
+        let result_room = response.len - size_of::<RdmaRspHdr>();
+
+        self.metrics.cmd_count.inc();
+        let result = match hdr.opcode {
+            opcode if !queue_accepts(queue_index, opcode) => {
+                Err(RdmaCmdError::UnexpectedQueue(opcode))
+            }
+            RDMA_CMD_CREATE_QP => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspCreateQp>(result_room)?;
+                    self.create_qp(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DESTROY_QP => args
+                .read(self.mem())
+                .and_then(|cmd| self.destroy_qp(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_CREATE_CQ => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspCreateCq>(result_room)?;
+                    self.create_cq(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DESTROY_CQ => args
+                .read(self.mem())
+                .and_then(|cmd| self.destroy_cq(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_RESIZE_CQ => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspResizeCq>(result_room)?;
+                    self.resize_cq(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_REG_MR => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspRegMr>(result_room)?;
+                    self.reg_mr(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DEREG_MR => args
+                .read(self.mem())
+                .and_then(|cmd| self.dereg_mr(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_MODIFY_QP => args
+                .read(self.mem())
+                .and_then(|cmd| self.modify_qp(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_QUERY_QP => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspQueryQp>(result_room)?;
+                    self.query_qp(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_QUERY_DEVICE => check_result_room::<RdmaRspQueryDevice>(result_room)
+                .map(|()| RdmaRspQueryDevice::from(&self.caps).as_slice().to_vec()),
+            RDMA_CMD_QUERY_PORT => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspQueryPort>(result_room)?;
+                    self.query_port(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_QUERY_GID => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspQueryGid>(result_room)?;
+                    self.query_gid(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_QUERY_PKEY => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspQueryPkey>(result_room)?;
+                    self.query_pkey(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_ALLOC_PD => check_result_room::<RdmaRspAllocPd>(result_room)
+                .and_then(|()| self.alloc_pd())
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DEALLOC_PD => args
+                .read(self.mem())
+                .and_then(|cmd| self.dealloc_pd(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_CREATE_AH => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspCreateAh>(result_room)?;
+                    self.create_ah(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DESTROY_AH => args
+                .read(self.mem())
+                .and_then(|cmd| self.destroy_ah(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_POST_SEND => self.post_send(&args).map(|()| Vec::new()),
+            RDMA_CMD_POLL_CQ => args
+                .read(self.mem())
+                .and_then(|cmd| self.poll_cq(cmd, result_room)),
+            RDMA_CMD_POST_RECV => self.post_recv(&args).map(|()| Vec::new()),
+            RDMA_CMD_REQ_NOTIFY_CQ => args
+                .read(self.mem())
+                .and_then(|cmd| self.req_notify_cq(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_CREATE_SRQ => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspCreateSrq>(result_room)?;
+                    self.create_srq(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DESTROY_SRQ => args
+                .read(self.mem())
+                .and_then(|cmd| self.destroy_srq(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_MODIFY_SRQ => args
+                .read(self.mem())
+                .and_then(|cmd| self.modify_srq(cmd))
+                .map(|()| Vec::new()),
+            RDMA_CMD_POST_SRQ_RECV => self.post_srq_recv(&args).map(|()| Vec::new()),
+            RDMA_CMD_ALLOC_MW => args
+                .read(self.mem())
+                .and_then(|cmd| {
+                    check_result_room::<RdmaRspAllocMw>(result_room)?;
+                    self.alloc_mw(cmd)
+                })
+                .map(|rsp| rsp.as_slice().to_vec()),
+            RDMA_CMD_DEALLOC_MW => args
+                .read(self.mem())
+                .and_then(|cmd| self.dealloc_mw(cmd))
+                .map(|()| Vec::new()),
+            opcode => Err(RdmaCmdError::UnsupportedOpcode(opcode)),
+        };
+        let (status, payload) = match result {
+            Ok(payload) => (RDMA_STATUS_OK, payload),
+            Err(err) => {
+                debug!("rdma: Command {} failed: {err}", hdr.opcode);
+                self.metrics.cmd_fails.inc();
+                (err.status(), Vec::new())
+            }
+        };
+
+        let rsp_hdr = RdmaRspHdr {
+            status,
+            ..Default::default()
+        };
+        let mut rsp = rsp_hdr.as_slice().to_vec();
+        rsp.extend_from_slice(&payload);
+        response.write_slice(self.mem(), &rsp)?;
+        Ok(u32::try_from(rsp.len()).unwrap())
+    }
+
+    fn create_qp(&mut self, cmd: RdmaCmdCreateQp) -> Result<RdmaRspCreateQp, RdmaCmdError> {
+        if !matches!(cmd.qp_type, RDMA_QPT_RC | RDMA_QPT_UC | RDMA_QPT_UD) {
+            return Err(RdmaCmdError::InvalidQpType(cmd.qp_type));
+        }
+        // The receive capabilities of the queue pairs using a shared receive queue are the ones
+        // of the shared receive queue.
+        let (max_recv_wr, max_recv_sge) = if cmd.srq == 0 {
+            (cmd.max_recv_wr, cmd.max_recv_sge)
+        } else {
+            (0, 0)
+        };
+        if cmd.max_send_wr > self.caps.max_qp_wr
+            || max_recv_wr > self.caps.max_qp_wr
+            || cmd.max_send_sge > self.caps.max_sge
+            || max_recv_sge > self.caps.max_sge
+            || cmd.max_inline_data > self.caps.max_inline_data
+        {
+            return Err(RdmaCmdError::InvalidQpCap);
+        }
+        if self.pds.get(cmd.pd).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pd));
+        }
+        for cqn in [cmd.send_cq, cmd.recv_cq] {
+            if self.cqs.get(cqn).is_none() {
+                return Err(RdmaCmdError::UnknownCq(cqn));
+            }
+        }
+        if cmd.srq != 0 && self.srqs.get(cmd.srq).is_none() {
+            return Err(RdmaCmdError::UnknownSrq(cmd.srq));
+        }
+
+        let qpn = self
+            .qps
+            .insert(QueuePair {
+                qp_type: cmd.qp_type,
+                send_cq: cmd.send_cq,
+                recv_cq: cmd.recv_cq,
+                max_send_wr: cmd.max_send_wr,
+                max_recv_wr,
+                max_send_sge: cmd.max_send_sge,
+                max_recv_sge,
+                max_inline_data: cmd.max_inline_data,
+                pd: cmd.pd,
+                srq: cmd.srq,
+                state: RDMA_QPS_RESET,
+                attrs: QpAttributes::default(),
+                recv_queue: VecDeque::new(),
+                outstanding: OutstandingWork::default(),
+            })
+            .ok_or(RdmaCmdError::NoQpLeft)?;
+        debug!("rdma: Created queue pair {qpn}");
+        Ok(RdmaRspCreateQp {
+            qpn,
+            ..Default::default()
+        })
+    }
+
+    fn destroy_qp(&mut self, cmd: RdmaCmdDestroyQp) -> Result<(), RdmaCmdError> {
+        if self.qps.get(cmd.qpn).is_none() {
+            return Err(RdmaCmdError::UnknownQp(cmd.qpn));
+        }
+        // The receive work requests of the queue pair are dropped along with it.
+        self.take_recv_queue(cmd.qpn);
+        self.qps.remove(cmd.qpn);
+        // The memory windows bound by the queue pair may no longer be accessed.
+        for (_, mw) in self.mws.iter_mut() {
+            if mw
+                .binding
+                .as_ref()
+                .is_some_and(|binding| binding.qpn == cmd.qpn)
+            {
+                mw.binding = None;
+            }
+        }
+        debug!("rdma: Destroyed queue pair {}", cmd.qpn);
+        Ok(())
+    }
+
+    fn modify_qp(&mut self, cmd: RdmaCmdModifyQp) -> Result<(), RdmaCmdError> {
+        // Queue pairs may only join the partitions of the P_Key table.
+        if cmd.attr_mask & RDMA_QP_PKEY_INDEX != 0 && !self.pkeys.is_valid(cmd.pkey_index) {
+            return Err(RdmaCmdError::InvalidQpAttr("pkey_index", cmd.pkey_index));
+        }
+        let qp = self
+            .qps
+            .get_mut(cmd.qpn)
+            .ok_or(RdmaCmdError::UnknownQp(cmd.qpn))?;
+        qp.modify(&cmd)?;
+        debug!("rdma: Moved queue pair {} to state {}", cmd.qpn, qp.state);
+        match qp.state {
+            RDMA_QPS_RESET => {
+                self.take_recv_queue(cmd.qpn);
+            }
+            RDMA_QPS_ERR => self.flush_recv_queue(cmd.qpn),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Queues a receive work request on the queue pair `qpn`, as outstanding work of the queue
+    /// pair and of its protection domain.
+    fn push_recv_wr(&mut self, qpn: u32, wr: RecvWr) {
+        let work = OutstandingWork::of([&wr]);
+        let qp = self.qps.get_mut(qpn).unwrap();
+        qp.recv_queue.push_back(wr);
+        qp.outstanding.add(work);
+        let pd = qp.pd;
+        // Protection domains outlive the queue pairs and the shared receive queues in them.
+        self.pds.get_mut(pd).unwrap().outstanding.add(work);
+    }
+
+    /// Takes the receive work requests of the queue pair `qpn`, which are no longer outstanding.
+    fn take_recv_queue(&mut self, qpn: u32) -> VecDeque<RecvWr> {
+        let qp = self.qps.get_mut(qpn).unwrap();
+        let wrs = std::mem::take(&mut qp.recv_queue);
+        let work = std::mem::take(&mut qp.outstanding);
+        let pd = qp.pd;
+        self.pds.get_mut(pd).unwrap().outstanding.sub(work);
+        wrs
+    }
+
+    /// Checks that a work request of `bytes` bytes, posted on the queue pair `qpn` or on a shared
+    /// receive queue when `None`, keeps the outstanding work of its queue pair and of its
+    /// protection domain `pd` within their quotas.
+    fn check_wr_quota(&self, qpn: Option<u32>, pd: u32, bytes: u64) -> Result<(), RdmaCmdError> {
+        let exceeds = |work: OutstandingWork, max_wr: Option<u32>, max_bytes: Option<u64>| {
+            max_wr.is_some_and(|max| work.wrs >= max)
+                || max_bytes.is_some_and(|max| work.bytes.saturating_add(bytes) > max)
+        };
+        if let Some(qpn) = qpn
+            && exceeds(
+                self.qps.get(qpn).unwrap().outstanding,
+                self.limits.max_outstanding_wr_per_qp,
+                self.limits.max_outstanding_bytes_per_qp,
+            )
+        {
+            self.metrics.wr_quota_exceeded.inc();
+            return Err(RdmaCmdError::QpWrQuotaExceeded(qpn));
+        }
+        if exceeds(
+            self.pds.get(pd).unwrap().outstanding,
+            self.limits.max_outstanding_wr_per_pd,
+            self.limits.max_outstanding_bytes_per_pd,
+        ) {
+            self.metrics.wr_quota_exceeded.inc();
+            return Err(RdmaCmdError::PdWrQuotaExceeded(pd));
+        }
+        Ok(())
+    }
+
+    /// Completes the receive work requests of the queue pair `qpn` with a flush error.
+    fn flush_recv_queue(&mut self, qpn: u32) {
+        let recv_cq = self.qps.get(qpn).unwrap().recv_cq;
+        for wr in self.take_recv_queue(qpn) {
+            self.complete(
+                recv_cq,
+                RdmaWc {
+                    wr_id: wr.wr_id,
+                    status: RDMA_WC_WR_FLUSH_ERR,
+                    opcode: RDMA_WC_RECV,
+                    qp_num: qpn,
+                    ..Default::default()
+                },
+                false,
+            );
+        }
+    }
+
+    /// Moves the queue pair `qpn` to the error state after a failed work request.
+    fn set_qp_error(&mut self, qpn: u32) {
+        self.qps.get_mut(qpn).unwrap().state = RDMA_QPS_ERR;
+        self.flush_recv_queue(qpn);
+    }
+
+    fn query_qp(&self, cmd: RdmaCmdQueryQp) -> Result<RdmaRspQueryQp, RdmaCmdError> {
+        self.qps
+            .get(cmd.qpn)
+            .map(QueuePair::query)
+            .ok_or(RdmaCmdError::UnknownQp(cmd.qpn))
+    }
+
+    fn query_port(&self, cmd: RdmaCmdQueryPort) -> Result<RdmaRspQueryPort, RdmaCmdError> {
+        if cmd.port_num == 0 || cmd.port_num > self.caps.phys_port_cnt {
+            return Err(RdmaCmdError::InvalidPort(cmd.port_num));
+        }
+        Ok(RdmaRspQueryPort::from(&self.port))
+    }
+
+    fn query_gid(&self, cmd: RdmaCmdQueryGid) -> Result<RdmaRspQueryGid, RdmaCmdError> {
+        if cmd.port_num == 0 || cmd.port_num > self.caps.phys_port_cnt {
+            return Err(RdmaCmdError::InvalidPort(cmd.port_num));
+        }
+        let gid = self
+            .gids
+            .get(cmd.index)
+            .ok_or(RdmaCmdError::InvalidGidIndex(cmd.index))?;
+        Ok(RdmaRspQueryGid {
+            gid: *gid,
+            gid_type: RDMA_GID_TYPE_ROCE_V2,
+            ..Default::default()
+        })
+    }
+
+    fn query_pkey(&self, cmd: RdmaCmdQueryPkey) -> Result<RdmaRspQueryPkey, RdmaCmdError> {
+        if cmd.port_num == 0 || cmd.port_num > self.caps.phys_port_cnt {
+            return Err(RdmaCmdError::InvalidPort(cmd.port_num));
+        }
+        let pkey = self
+            .pkeys
+            .get(cmd.index)
+            .ok_or(RdmaCmdError::InvalidPkeyIndex(cmd.index))?;
+        Ok(RdmaRspQueryPkey {
+            pkey: u32::from(pkey),
+            ..Default::default()
+        })
+    }
+
+    fn create_cq(&mut self, cmd: RdmaCmdCreateCq) -> Result<RdmaRspCreateCq, RdmaCmdError> {
+        if cmd.cqe == 0 || cmd.cqe > self.caps.max_cqe {
+            return Err(RdmaCmdError::InvalidCqSize(cmd.cqe));
+        }
+        if cmd.flags & !RDMA_CQ_F_ASYNC != 0 {
+            return Err(RdmaCmdError::InvalidCqFlags(cmd.flags));
+        }
+
+        let cqn = self
+            .cqs
+            .insert(CompletionQueue::new(cmd.cqe, cmd.flags))
+            .ok_or(RdmaCmdError::NoCqLeft)?;
+        debug!("rdma: Created completion queue {cqn}");
+        Ok(RdmaRspCreateCq {
+            cqn,
+            ..Default::default()
+        })
+    }
+
+    fn destroy_cq(&mut self, cmd: RdmaCmdDestroyCq) -> Result<(), RdmaCmdError> {
+        if self.cqs.get(cmd.cqn).is_none() {
+            return Err(RdmaCmdError::UnknownCq(cmd.cqn));
+        }
+        // The queue pairs using a completion queue must be destroyed first.
+        if self
+            .qps
+            .iter()
+            .any(|(_, qp)| qp.send_cq == cmd.cqn || qp.recv_cq == cmd.cqn)
+        {
+            return Err(RdmaCmdError::CqInUse(cmd.cqn));
+        }
+        self.cqs.remove(cmd.cqn);
+        debug!("rdma: Destroyed completion queue {}", cmd.cqn);
+        Ok(())
+    }
+
+    fn resize_cq(&mut self, cmd: RdmaCmdResizeCq) -> Result<RdmaRspResizeCq, RdmaCmdError> {
+        let cq = self
+            .cqs
+            .get_mut(cmd.cqn)
+            .ok_or(RdmaCmdError::UnknownCq(cmd.cqn))?;
+        if cmd.cqe == 0 || cmd.cqe > self.caps.max_cqe {
+            return Err(RdmaCmdError::InvalidCqSize(cmd.cqe));
+        }
+        // The work completions the queue holds stay in order, and are polled from the resized
+        // queue.
+        if !cq.resize(cmd.cqe) {
+            return Err(RdmaCmdError::CqTooSmall(cmd.cqn, cq.completions.len()));
+        }
+        debug!("rdma: Resized completion queue {} to {}", cmd.cqn, cmd.cqe);
+        Ok(RdmaRspResizeCq {
+            cqe: cq.cqe,
+            ..Default::default()
+        })
+    }
+
+    fn reg_mr(&mut self, cmd: RdmaCmdRegMr) -> Result<RdmaRspRegMr, RdmaCmdError> {
+        if cmd.length == 0 {
+            return Err(RdmaCmdError::InvalidMrLength(cmd.length));
+        }
+        let all_access = RDMA_ACCESS_LOCAL_WRITE
+            | RDMA_ACCESS_REMOTE_WRITE
+            | RDMA_ACCESS_REMOTE_READ
+            | RDMA_ACCESS_REMOTE_ATOMIC
+            | RDMA_ACCESS_MW_BIND;
+        // Like with ibverbs, remote writes require local writes.
+        if cmd.access & !all_access != 0
+            || (cmd.access & (RDMA_ACCESS_REMOTE_WRITE | RDMA_ACCESS_REMOTE_ATOMIC) != 0
+                && cmd.access & RDMA_ACCESS_LOCAL_WRITE == 0)
+        {
+            return Err(RdmaCmdError::InvalidMrAccess(cmd.access));
+        }
+        if self.pds.get(cmd.pd).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pd));
+        }
+        // The whole region must be backed by guest memory, possibly spanning several
+        // contiguous memory regions.
+        let backed = cmd.iova.checked_add(cmd.length).is_some()
+            && usize::try_from(cmd.length)
+                .is_ok_and(|len| self.mem().check_range(GuestAddress(cmd.iova), len));
+        if !backed {
+            return Err(RdmaCmdError::MrOutOfGuestMemory(cmd.iova, cmd.length));
+        }
+        if let Some(max_bytes) = self.limits.max_memory_registration_bytes
+            && self
+                .registered_bytes()
+                .checked_add(cmd.length)
+                .is_none_or(|bytes| bytes > max_bytes)
+        {
+            return Err(RdmaCmdError::MrLimitExceeded(cmd.length, max_bytes));
+        }
+
+        let mr = MemoryRegion {
+            iova: cmd.iova,
+            length: cmd.length,
+            access: cmd.access,
+            pd: cmd.pd,
+            invalidated: false,
+        };
+        let remote_access = mr.remote_access();
+        // The keys are the handle of the memory region, which is only reused once the
+        // allocation wraps around, so that stale keys are rejected.
+        let lkey = self.mrs.insert(mr).ok_or(RdmaCmdError::NoMrLeft)?;
+        debug!(
+            "rdma: Registered memory region {lkey:#x} [{:#x}, +{:#x})",
+            cmd.iova, cmd.length
+        );
+        Ok(RdmaRspRegMr {
+            lkey,
+            rkey: if remote_access { lkey } else { 0 },
+        })
+    }
+
+    fn dereg_mr(&mut self, cmd: RdmaCmdDeregMr) -> Result<(), RdmaCmdError> {
+        if self.mrs.get(cmd.lkey).is_none() {
+            return Err(RdmaCmdError::UnknownMr(cmd.lkey));
+        }
+        // The memory windows bound to the region must be invalidated first.
+        if self.mws.iter().any(|(_, mw)| {
+            mw.binding
+                .as_ref()
+                .is_some_and(|binding| binding.lkey == cmd.lkey)
+        }) {
+            return Err(RdmaCmdError::MrInUse(cmd.lkey));
+        }
+        self.mrs.remove(cmd.lkey);
+        debug!("rdma: Deregistered memory region {:#x}", cmd.lkey);
+        Ok(())
+    }
+
+    fn alloc_pd(&mut self) -> Result<RdmaRspAllocPd, RdmaCmdError> {
+        let pdn = self
+            .pds
+            .insert(ProtectionDomain::default())
+            .ok_or(RdmaCmdError::NoPdLeft)?;
+        debug!("rdma: Allocated protection domain {pdn}");
+        Ok(RdmaRspAllocPd {
+            pdn,
+            ..Default::default()
+        })
+    }
+
+    fn dealloc_pd(&mut self, cmd: RdmaCmdDeallocPd) -> Result<(), RdmaCmdError> {
+        if self.pds.get(cmd.pdn).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pdn));
+        }
+        // The queue pairs, the shared receive queues, the memory regions and windows and the
+        // address handles of a protection domain must be destroyed first.
+        if self.qps.iter().any(|(_, qp)| qp.pd == cmd.pdn)
+            || self.srqs.iter().any(|(_, srq)| srq.pd == cmd.pdn)
+            || self.mrs.iter().any(|(_, mr)| mr.pd == cmd.pdn)
+            || self.mws.iter().any(|(_, mw)| mw.pd == cmd.pdn)
+            || self.ahs.iter().any(|(_, ah)| ah.pd == cmd.pdn)
+        {
+            return Err(RdmaCmdError::PdInUse(cmd.pdn));
+        }
+        self.pds.remove(cmd.pdn);
+        debug!("rdma: Deallocated protection domain {}", cmd.pdn);
+        Ok(())
+    }
+
+    fn create_ah(&mut self, cmd: RdmaCmdCreateAh) -> Result<RdmaRspCreateAh, RdmaCmdError> {
+        if self.pds.get(cmd.pd).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pd));
+        }
+        if cmd.port_num == 0 || cmd.port_num > self.caps.phys_port_cnt {
+            return Err(RdmaCmdError::InvalidPort(cmd.port_num));
+        }
+        if cmd.sgid_index >= self.port.gid_tbl_len {
+            return Err(RdmaCmdError::InvalidAhAttr("sgid_index", cmd.sgid_index));
+        }
+        let traffic_class = u8::try_from(cmd.traffic_class)
+            .map_err(|_| RdmaCmdError::InvalidAhAttr("traffic_class", cmd.traffic_class))?;
+        let hop_limit = u8::try_from(cmd.hop_limit)
+            .map_err(|_| RdmaCmdError::InvalidAhAttr("hop_limit", cmd.hop_limit))?;
+        let ah = self
+            .ahs
+            .insert(AddressHandle {
+                pd: cmd.pd,
+                port_num: cmd.port_num,
+                dgid: cmd.dgid,
+                sgid_index: cmd.sgid_index,
+                traffic_class,
+                hop_limit,
+            })
+            .ok_or(RdmaCmdError::NoAhLeft)?;
+        debug!("rdma: Created address handle {ah}");
+        Ok(RdmaRspCreateAh {
+            ah,
+            ..Default::default()
+        })
+    }
+
+    fn destroy_ah(&mut self, cmd: RdmaCmdDestroyAh) -> Result<(), RdmaCmdError> {
+        self.ahs
+            .remove(cmd.ah)
+            .ok_or(RdmaCmdError::UnknownAh(cmd.ah))?;
+        debug!("rdma: Destroyed address handle {}", cmd.ah);
+        Ok(())
+    }
+
+    /// Checks that a work request posted on an unreliable datagram queue pair of the protection
+    /// domain `pd` may address its destination through the address handle `ah`.
+    pub fn check_ah(&self, pd: u32, ah: u32) -> Result<&AddressHandle, RdmaCmdError> {
+        let handle = self.ahs.get(ah).ok_or(RdmaCmdError::UnknownAh(ah))?;
+        if handle.pd != pd {
+            return Err(RdmaCmdError::AhPdMismatch(ah, pd));
+        }
+        Ok(handle)
+    }
+
+    /// Checks that a work request posted on a queue pair of the protection domain `pd` may
+    /// access `length` bytes at `addr` through the memory region `lkey`, writing to them if
+    /// `write` is set.
+    pub fn check_local_access(
+        &self,
+        pd: u32,
+        lkey: u32,
+        addr: u64,
+        length: u32,
+        write: bool,
+    ) -> Result<&MemoryRegion, RdmaCmdError> {
+        let mr = self.mrs.get(lkey).ok_or(RdmaCmdError::UnknownMr(lkey))?;
+        if mr.invalidated {
+            return Err(RdmaCmdError::MrInvalidated(lkey));
+        }
+        // Work requests may only use the memory regions of the protection domain of their
+        // queue pair.
+        if mr.pd != pd {
+            return Err(RdmaCmdError::MrPdMismatch(lkey, pd));
+        }
+        if !mr.contains(addr, u64::from(length)) {
+            return Err(RdmaCmdError::OutOfMrBounds(lkey, addr, length));
+        }
+        if write && mr.access & RDMA_ACCESS_LOCAL_WRITE == 0 {
+            return Err(RdmaCmdError::MrNotWritable(lkey));
+        }
+        Ok(mr)
+    }
+
+    /// Checks that a message received by the queue pair `qpn` may access `length` bytes at
+    /// `addr` through the memory region or the memory window of remote key `rkey`, with the
+    /// `RDMA_ACCESS_REMOTE_*` flag `access`.
+    pub fn check_remote_access(
+        &self,
+        qpn: u32,
+        rkey: u32,
+        addr: u64,
+        length: u32,
+        access: u32,
+    ) -> Result<&MemoryRegion, RdmaCmdError> {
+        let qp = self.qps.get(qpn).ok_or(RdmaCmdError::UnknownQp(qpn))?;
+        // Both the queue pair and the memory region or window must allow the access.
+        if qp.attrs.access_flags & access == 0 {
+            return Err(RdmaCmdError::RemoteAccessDenied(rkey, access));
+        }
+        if mw_handle(rkey).is_none() {
+            let mr = self.check_local_access(qp.pd, rkey, addr, length, false)?;
+            if mr.access & access == 0 {
+                return Err(RdmaCmdError::RemoteAccessDenied(rkey, access));
+            }
+            return Ok(mr);
+        }
+
+        let binding = self
+            .check_mw(qp.pd, rkey)?
+            .binding
+            .as_ref()
+            .ok_or(RdmaCmdError::MwNotBound(rkey))?;
+        if binding.qpn != qpn {
+            return Err(RdmaCmdError::MwQpMismatch(rkey, qpn));
+        }
+        let in_bounds = addr >= binding.addr
+            && addr
+                .checked_add(u64::from(length))
+                .is_some_and(|end| end <= binding.addr + binding.length);
+        if !in_bounds {
+            return Err(RdmaCmdError::OutOfMwBounds(rkey, addr, length));
+        }
+        if binding.access & access == 0 {
+            return Err(RdmaCmdError::RemoteAccessDenied(rkey, access));
+        }
+        // The memory region may have been invalidated since the window was bound.
+        self.check_local_access(qp.pd, binding.lkey, addr, length, false)
+    }
+
+    fn alloc_mw(&mut self, cmd: RdmaCmdAllocMw) -> Result<RdmaRspAllocMw, RdmaCmdError> {
+        // Type 1 memory windows, bound by commands rather than by work requests, are not
+        // supported.
+        if cmd.mw_type != RDMA_MW_TYPE_2 {
+            return Err(RdmaCmdError::InvalidMwType(cmd.mw_type));
+        }
+        if self.pds.get(cmd.pd).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pd));
+        }
+        let handle = self
+            .mws
+            .insert(MemoryWindow {
+                pd: cmd.pd,
+                rkey: 0,
+                binding: None,
+            })
+            .ok_or(RdmaCmdError::NoMwLeft)?;
+        let rkey = MW_RKEY_FLAG | (handle << 8);
+        self.mws.get_mut(handle).unwrap().rkey = rkey;
+        debug!("rdma: Allocated memory window {rkey:#x}");
+        Ok(RdmaRspAllocMw {
+            rkey,
+            ..Default::default()
+        })
+    }
+
+    fn dealloc_mw(&mut self, cmd: RdmaCmdDeallocMw) -> Result<(), RdmaCmdError> {
+        let handle = mw_handle(cmd.rkey)
+            .filter(|handle| self.mws.get(*handle).is_some_and(|mw| mw.rkey == cmd.rkey))
+            .ok_or(RdmaCmdError::UnknownMw(cmd.rkey))?;
+        // Bound memory windows are invalidated along the way.
+        self.mws.remove(handle);
+        debug!("rdma: Deallocated memory window {:#x}", cmd.rkey);
+        Ok(())
+    }
+
+    /// Returns the memory window of current remote key `rkey`, checking that it is in the
+    /// protection domain `pd`.
+    fn check_mw(&self, pd: u32, rkey: u32) -> Result<&MemoryWindow, RdmaCmdError> {
+        let mw = mw_handle(rkey)
+            .and_then(|handle| self.mws.get(handle))
+            .filter(|mw| mw.rkey == rkey)
+            .ok_or(RdmaCmdError::UnknownMw(rkey))?;
+        if mw.pd != pd {
+            return Err(RdmaCmdError::MwPdMismatch(rkey, pd));
+        }
+        Ok(mw)
+    }
+
+    /// Binds a memory window with a work request posted on the queue pair `qpn` of the
+    /// protection domain `pd`.
+    fn bind_mw(&mut self, qpn: u32, pd: u32, bind: &RdmaBindMw) -> Result<(), RdmaCmdError> {
+        if self.check_mw(pd, bind.mw_rkey)?.binding.is_some() {
+            return Err(RdmaCmdError::MwBound(bind.mw_rkey));
+        }
+        // The driver only picks the low 8 bits of the new remote key.
+        if bind.rkey >> 8 != bind.mw_rkey >> 8 {
+            return Err(RdmaCmdError::InvalidMwRkey(bind.mw_rkey, bind.rkey));
+        }
+        let remote_access =
+            RDMA_ACCESS_REMOTE_WRITE | RDMA_ACCESS_REMOTE_READ | RDMA_ACCESS_REMOTE_ATOMIC;
+        if bind.access & !remote_access != 0 {
+            return Err(RdmaCmdError::InvalidMwAccess(bind.access));
+        }
+        let mr = self
+            .mrs
+            .get(bind.lkey)
+            .ok_or(RdmaCmdError::UnknownMr(bind.lkey))?;
+        if mr.invalidated {
+            return Err(RdmaCmdError::MrInvalidated(bind.lkey));
+        }
+        if mr.pd != pd {
+            return Err(RdmaCmdError::MrPdMismatch(bind.lkey, pd));
+        }
+        if mr.access & RDMA_ACCESS_MW_BIND == 0 {
+            return Err(RdmaCmdError::MrNotBindable(bind.lkey));
+        }
+        // Like with ibverbs, remote writes through the window require local writes to the
+        // region.
+        if bind.access & (RDMA_ACCESS_REMOTE_WRITE | RDMA_ACCESS_REMOTE_ATOMIC) != 0
+            && mr.access & RDMA_ACCESS_LOCAL_WRITE == 0
+        {
+            return Err(RdmaCmdError::MrNotWritable(bind.lkey));
+        }
+        if !mr.contains(bind.addr, bind.length) {
+            return Err(RdmaCmdError::MwOutOfMrBounds(
+                bind.lkey,
+                bind.addr,
+                bind.length,
+            ));
+        }
+
+        let mw = self.mws.get_mut(mw_handle(bind.mw_rkey).unwrap()).unwrap();
+        mw.rkey = bind.rkey;
+        mw.binding = Some(MwBinding {
+            lkey: bind.lkey,
+            qpn,
+            addr: bind.addr,
+            length: bind.length,
+            access: bind.access,
+        });
+        debug!(
+            "rdma: Bound memory window {:#x} to [{:#x}, +{:#x}) of memory region {:#x}",
+            bind.rkey, bind.addr, bind.length, bind.lkey
+        );
+        Ok(())
+    }
+
+    /// Invalidates the bound memory window of remote key `rkey` from a queue pair of the
+    /// protection domain `pd`. Messages received by the queue pair `remote_qpn` may only
+    /// invalidate the windows it bound.
+    fn invalidate_mw(
+        &mut self,
+        pd: u32,
+        rkey: u32,
+        remote_qpn: Option<u32>,
+    ) -> Result<(), RdmaCmdError> {
+        let binding = self
+            .check_mw(pd, rkey)?
+            .binding
+            .as_ref()
+            .ok_or(RdmaCmdError::MwNotBound(rkey))?;
+        if let Some(qpn) = remote_qpn
+            && binding.qpn != qpn
+        {
+            return Err(RdmaCmdError::MwQpMismatch(rkey, qpn));
+        }
+        // The remote key is kept, so that the next bind changes it.
+        self.mws.get_mut(mw_handle(rkey).unwrap()).unwrap().binding = None;
+        debug!("rdma: Invalidated memory window {rkey:#x}");
+        Ok(())
+    }
+
+    fn post_send(&mut self, args: &Args) -> Result<(), RdmaCmdError> {
+        let cmd: RdmaCmdPostSend = args.read(self.mem())?;
+        let qp = self
+            .qps
+            .get(cmd.qpn)
+            .ok_or(RdmaCmdError::UnknownQp(cmd.qpn))?;
+        let (qp_type, pd, state, send_cq) = (qp.qp_type, qp.pd, qp.state, qp.send_cq);
+        let (dgid, sgid_index, dest_qp_num) =
+            (qp.attrs.dgid, qp.attrs.sgid_index, qp.attrs.dest_qp_num);
+        let atomic = matches!(
+            cmd.opcode,
+            RDMA_WR_ATOMIC_CMP_AND_SWP | RDMA_WR_ATOMIC_FETCH_AND_ADD
+        );
+        let read = cmd.opcode == RDMA_WR_RDMA_READ;
+        let valid_opcode = match qp_type {
+            RDMA_QPT_UD => matches!(cmd.opcode, RDMA_WR_SEND | RDMA_WR_SEND_WITH_IMM),
+            // Only reliable connected queue pairs execute atomic operations and RDMA reads, the
+            // device reading the memory of the remote queue pair itself.
+            RDMA_QPT_RC if atomic => self.caps.atomic_cap != RDMA_ATOMIC_NONE,
+            RDMA_QPT_RC if read => self.backend.is_local(),
+            _ => matches!(
+                cmd.opcode,
+                RDMA_WR_SEND
+                    | RDMA_WR_SEND_WITH_IMM
+                    | RDMA_WR_SEND_WITH_INV
+                    | RDMA_WR_RDMA_WRITE
+                    | RDMA_WR_RDMA_WRITE_WITH_IMM
+                    | RDMA_WR_LOCAL_INV
+                    | RDMA_WR_BIND_MW
+            ),
+        };
+        if !valid_opcode {
+            return Err(RdmaCmdError::InvalidWrOpcode(cmd.opcode, qp_type));
+        }
+        let valid_flags =
+            RDMA_SEND_FENCE | RDMA_SEND_SIGNALED | RDMA_SEND_SOLICITED | RDMA_SEND_INLINE;
+        let inline = cmd.send_flags & RDMA_SEND_INLINE != 0;
+        // Only the work requests carrying data to the remote peer may have it inline.
+        if cmd.send_flags & !valid_flags != 0
+            || (inline
+                && !matches!(
+                    cmd.opcode,
+                    RDMA_WR_SEND
+                        | RDMA_WR_SEND_WITH_IMM
+                        | RDMA_WR_SEND_WITH_INV
+                        | RDMA_WR_RDMA_WRITE
+                        | RDMA_WR_RDMA_WRITE_WITH_IMM
+                ))
+        {
+            return Err(RdmaCmdError::InvalidSendFlags(cmd.send_flags));
+        }
+        // Memory window binds are followed by their binding, local invalidations carry no data,
+        // and atomics are followed by their operands before their scatter/gather entries.
+        let (sges, bind) = match cmd.opcode {
+            RDMA_WR_BIND_MW => (
+                Vec::new(),
+                Some(args.read_at::<RdmaBindMw>(self.mem(), size_of::<RdmaCmdPostSend>())?),
+            ),
+            RDMA_WR_LOCAL_INV => (Vec::new(), None),
+            _ if inline => {
+                if cmd.inline_len > qp.max_inline_data {
+                    return Err(RdmaCmdError::InlineDataTooLong(cmd.inline_len));
+                }
+                (Vec::new(), None)
+            }
+            _ => {
+                if cmd.num_sge > qp.max_send_sge {
+                    return Err(RdmaCmdError::TooManySge(cmd.num_sge));
+                }
+                let offset = if atomic {
+                    size_of::<RdmaCmdPostSend>() + size_of::<RdmaAtomic>()
+                } else {
+                    size_of::<RdmaCmdPostSend>()
+                };
+                let sges = args.read_sges(self.mem(), offset, cmd.num_sge)?;
+                (sges, None)
+            }
+        };
+        let operands = if atomic {
+            if !matches!(sges.as_slice(), [sge] if sge.length == 8) {
+                return Err(RdmaCmdError::InvalidAtomicSge);
+            }
+            Some(args.read_at::<RdmaAtomic>(self.mem(), size_of::<RdmaCmdPostSend>())?)
+        } else {
+            None
+        };
+        // The inline data is copied from the command, so that the driver may reuse its buffer
+        // as soon as the work request is posted.
+        let inline_data = if inline {
+            Some(args.read_bytes(self.mem(), size_of::<RdmaCmdPostSend>(), cmd.inline_len)?)
+        } else {
+            None
+        };
+
+        let wc = RdmaWc {
+            wr_id: cmd.wr_id,
+            opcode: match cmd.opcode {
+                RDMA_WR_RDMA_WRITE | RDMA_WR_RDMA_WRITE_WITH_IMM => RDMA_WC_RDMA_WRITE,
+                RDMA_WR_RDMA_READ => RDMA_WC_RDMA_READ,
+                RDMA_WR_ATOMIC_CMP_AND_SWP => RDMA_WC_COMP_SWAP,
+                RDMA_WR_ATOMIC_FETCH_AND_ADD => RDMA_WC_FETCH_ADD,
+                RDMA_WR_BIND_MW => RDMA_WC_BIND_MW,
+                RDMA_WR_LOCAL_INV => RDMA_WC_LOCAL_INV,
+                _ => RDMA_WC_SEND,
+            },
+            qp_num: cmd.qpn,
+            ..Default::default()
+        };
+        match state {
+            RDMA_QPS_RTS => {}
+            // The work requests posted on a queue pair in error are flushed.
+            RDMA_QPS_SQE | RDMA_QPS_ERR => {
+                self.complete(
+                    send_cq,
+                    RdmaWc {
+                        status: RDMA_WC_WR_FLUSH_ERR,
+                        ..wc
+                    },
+                    false,
+                );
+                return Ok(());
+            }
+            state => return Err(RdmaCmdError::QpStateMismatch(state, RDMA_QPS_RTS)),
+        }
+        // Send work requests are completed as they are posted, so they are only outstanding
+        // along with the receive work requests of their queue pair until then.
+        let length = match &inline_data {
+            Some(data) => data.len() as u64,
+            None => sges.iter().map(|sge| u64::from(sge.length)).sum(),
+        };
+        self.check_wr_quota(Some(cmd.qpn), pd, length)?;
+
+        // Memory window binds and local invalidations are executed by the device, without
+        // sending any message.
+        if matches!(cmd.opcode, RDMA_WR_BIND_MW | RDMA_WR_LOCAL_INV) {
+            self.metrics.send_wr_count.inc();
+            let (result, error_status) = match bind {
+                Some(bind) => (self.bind_mw(cmd.qpn, pd, &bind), RDMA_WC_MW_BIND_ERR),
+                None => (
+                    self.invalidate_mw(pd, cmd.imm_data, None),
+                    RDMA_WC_LOC_QP_OP_ERR,
+                ),
+            };
+            let status = match result {
+                Ok(()) => RDMA_WC_SUCCESS,
+                Err(err) => {
+                    debug!(
+                        "rdma: Work request {:#x} of queue pair {} failed: {err}",
+                        cmd.wr_id, cmd.qpn
+                    );
+                    self.set_qp_error(cmd.qpn);
+                    error_status
+                }
+            };
+            if status != RDMA_WC_SUCCESS || cmd.send_flags & RDMA_SEND_SIGNALED != 0 {
+                self.complete(send_cq, RdmaWc { status, ..wc }, false);
+            }
+            return Ok(());
+        }
+
+        let mut msg = RdmaMessage {
+            opcode: cmd.opcode,
+            qp_type,
+            src_qpn: cmd.qpn,
+            remote_addr: cmd.remote_addr,
+            rkey: cmd.rkey,
+            imm_data: matches!(
+                cmd.opcode,
+                RDMA_WR_SEND_WITH_IMM | RDMA_WR_RDMA_WRITE_WITH_IMM
+            )
+            .then_some(cmd.imm_data),
+            invalidate_rkey: (cmd.opcode == RDMA_WR_SEND_WITH_INV).then_some(cmd.imm_data),
+            solicited: cmd.send_flags & RDMA_SEND_SOLICITED != 0,
+            ..Default::default()
+        };
+        // Datagrams carry a single packet, while the messages of connected queue pairs are
+        // segmented along their path MTU.
+        let max_msg_sz = if qp_type == RDMA_QPT_UD {
+            let ah = self.check_ah(pd, cmd.ah)?;
+            msg.sgid = self.gids.get(ah.sgid_index).copied().unwrap_or_default();
+            msg.dgid = ah.dgid;
+            msg.dest_qpn = cmd.remote_qpn;
+            msg.qkey = cmd.remote_qkey;
+            128 << self.port.active_mtu
+        } else {
+            msg.sgid = self.gids.get(sgid_index).copied().unwrap_or_default();
+            msg.dgid = dgid;
+            msg.dest_qpn = dest_qp_num;
+            self.port.max_msg_sz
+        };
+        self.metrics.send_wr_count.inc();
+
+        let status = if let Some(status) = self.backend.intercept(&msg) {
+            self.metrics.injected_faults.inc();
+            status
+        } else if let Some(operands) = operands {
+            self.atomic(pd, &msg, &operands, &sges[0])
+        } else if length > u64::from(max_msg_sz) {
+            RDMA_WC_LOC_LEN_ERR
+        } else if read {
+            self.rdma_read(pd, &msg, &sges)
+        } else {
+            // The payload is read from guest memory by the backend, while inline data comes with
+            // the message.
+            let payload = match inline_data {
+                Some(data) => Ok((
+                    RdmaMessage {
+                        payload: data,
+                        ..msg
+                    },
+                    None,
+                )),
+                None => self.gather(pd, &sges).map(|payload| (msg, Some(payload))),
+            };
+            match payload {
+                Ok((msg, payload)) => self.transmit(msg, payload),
+                Err(err) => {
+                    debug!(
+                        "rdma: Work request {:#x} of queue pair {} failed: {err}",
+                        cmd.wr_id, cmd.qpn
+                    );
+                    RDMA_WC_LOC_PROT_ERR
+                }
+            }
+        };
+        if status != RDMA_WC_SUCCESS {
+            // Failed work requests move connected queue pairs to the error state, and only the
+            // send queue of unreliable datagram ones.
+            if qp_type == RDMA_QPT_UD {
+                self.qps.get_mut(cmd.qpn).unwrap().state = RDMA_QPS_SQE;
+            } else {
+                self.set_qp_error(cmd.qpn);
+            }
+        }
+        if status != RDMA_WC_SUCCESS || cmd.send_flags & RDMA_SEND_SIGNALED != 0 {
+            self.complete(send_cq, RdmaWc { status, ..wc }, false);
+        }
+
+        self.process_rx();
+        Ok(())
+    }
+
+    fn post_recv(&mut self, args: &Args) -> Result<(), RdmaCmdError> {
+        let cmd: RdmaCmdPostRecv = args.read(self.mem())?;
+        let qp = self
+            .qps
+            .get(cmd.qpn)
+            .ok_or(RdmaCmdError::UnknownQp(cmd.qpn))?;
+        if qp.srq != 0 {
+            return Err(RdmaCmdError::QpHasSrq(cmd.qpn));
+        }
+        if cmd.num_sge > qp.max_recv_sge {
+            return Err(RdmaCmdError::TooManySge(cmd.num_sge));
+        }
+        let sges = args.read_sges(self.mem(), size_of::<RdmaCmdPostRecv>(), cmd.num_sge)?;
+        let wr = RecvWr {
+            wr_id: cmd.wr_id,
+            sges,
+        };
+
+        // Receive work requests may be posted from the initialized state on.
+        let qp = self.qps.get_mut(cmd.qpn).unwrap();
+        match qp.state {
+            RDMA_QPS_RESET => {
+                return Err(RdmaCmdError::QpStateMismatch(RDMA_QPS_RESET, RDMA_QPS_INIT));
+            }
+            RDMA_QPS_ERR => {
+                self.push_recv_wr(cmd.qpn, wr);
+                self.flush_recv_queue(cmd.qpn);
+                return Ok(());
+            }
+            _ => {}
+        }
+        if qp.recv_queue.len() >= usize::try_from(qp.max_recv_wr).unwrap() {
+            return Err(RdmaCmdError::RecvQueueFull(cmd.qpn));
+        }
+        let pd = qp.pd;
+        self.check_wr_quota(Some(cmd.qpn), pd, wr.byte_len())?;
+        self.push_recv_wr(cmd.qpn, wr);
+        self.metrics.recv_wr_count.inc();
+        Ok(())
+    }
+
+    /// Executes the atomic operation of a work request posted on a queue pair of the protection
+    /// domain `pd`, writing the original remote value to `sge`. Returns the `RDMA_WC_*` status of
+    /// the work request.
+    fn atomic(&self, pd: u32, msg: &RdmaMessage, operands: &RdmaAtomic, sge: &RdmaSge) -> u32 {
+        if let Err(err) = self.check_local_access(pd, sge.lkey, sge.addr, sge.length, true) {
+            debug!(
+                "rdma: Atomic work request of queue pair {} failed: {err}",
+                msg.src_qpn
+            );
+            return RDMA_WC_LOC_PROT_ERR;
+        }
+        if !self.reachable(msg) {
+            return RDMA_WC_RETRY_EXC_ERR;
+        }
+        if msg.remote_addr % 8 != 0 {
+            return RDMA_WC_REM_INV_REQ_ERR;
+        }
+        if let Err(err) = self.check_remote_access(
+            msg.dest_qpn,
+            msg.rkey,
+            msg.remote_addr,
+            8,
+            RDMA_ACCESS_REMOTE_ATOMIC,
+        ) {
+            debug!(
+                "rdma: Atomic work request of queue pair {} failed: {err}",
+                msg.src_qpn
+            );
+            return RDMA_WC_REM_ACCESS_ERR;
+        }
+
+        let remote = GuestAddress(msg.remote_addr);
+        let Ok(original) = self.mem().read_obj::<u64>(remote) else {
+            return RDMA_WC_REM_ACCESS_ERR;
+        };
+        let value = match msg.opcode {
+            RDMA_WR_ATOMIC_CMP_AND_SWP if original == operands.compare_add => operands.swap,
+            RDMA_WR_ATOMIC_CMP_AND_SWP => original,
+            _ => original.wrapping_add(operands.compare_add),
+        };
+        if self.mem().write_obj(value, remote).is_err() {
+            return RDMA_WC_REM_ACCESS_ERR;
+        }
+        if self
+            .mem()
+            .write_obj(original, GuestAddress(sge.addr))
+            .is_err()
+        {
+            return RDMA_WC_LOC_PROT_ERR;
+        }
+        RDMA_WC_SUCCESS
+    }
+
+    /// Executes the RDMA read of a work request posted on a queue pair of the protection domain
+    /// `pd`, scattering the remote data to `sges`. Returns the `RDMA_WC_*` status of the work
+    /// request.
+    fn rdma_read(&self, pd: u32, msg: &RdmaMessage, sges: &[RdmaSge]) -> u32 {
+        for sge in sges {
+            if let Err(err) = self.check_local_access(pd, sge.lkey, sge.addr, sge.length, true) {
+                debug!(
+                    "rdma: RDMA read work request of queue pair {} failed: {err}",
+                    msg.src_qpn
+                );
+                return RDMA_WC_LOC_PROT_ERR;
+            }
+        }
+        if !self.reachable(msg) {
+            return RDMA_WC_RETRY_EXC_ERR;
+        }
+        // The length was checked against the largest message size.
+        let length =
+            u32::try_from(sges.iter().map(|sge| u64::from(sge.length)).sum::<u64>()).unwrap();
+        if let Err(err) = self.check_remote_access(
+            msg.dest_qpn,
+            msg.rkey,
+            msg.remote_addr,
+            length,
+            RDMA_ACCESS_REMOTE_READ,
+        ) {
+            debug!(
+                "rdma: RDMA read work request of queue pair {} failed: {err}",
+                msg.src_qpn
+            );
+            return RDMA_WC_REM_ACCESS_ERR;
+        }
+
+        let mut data = vec![0u8; length as usize];
+        if self
+            .mem()
+            .read_slice(&mut data, GuestAddress(msg.remote_addr))
+            .is_err()
+        {
+            return RDMA_WC_REM_ACCESS_ERR;
+        }
+        self.scatter(pd, sges, &data)
+    }
+
+    /// Whether the remote queue pair of a message executed by the device itself is connected to
+    /// the sending one, as for the messages it receives.
+    fn reachable(&self, msg: &RdmaMessage) -> bool {
+        self.qps.get(msg.dest_qpn).is_some_and(|qp| {
+            qp.qp_type == RDMA_QPT_RC
+                && matches!(qp.state, RDMA_QPS_RTR | RDMA_QPS_RTS | RDMA_QPS_SQD)
+                && qp.attrs.dest_qp_num == msg.src_qpn
+        })
+    }
+
+    /// Gathers the scatter/gather entries of a work request posted on a queue pair of the
+    /// protection domain `pd` as the guest memory they cover, which the data is read from when
+    /// the message is sent, with no copy.
+    fn gather(&self, pd: u32, sges: &[RdmaSge]) -> Result<IoVecBuffer, RdmaCmdError> {
+        let mut payload = IoVecBuffer::default();
+        for sge in sges {
+            self.check_local_access(pd, sge.lkey, sge.addr, sge.length, false)?;
+            // SAFETY: The buffer is only read from, so its ranges may overlap.
+            unsafe { payload.append_guest_range(self.mem(), GuestAddress(sge.addr), sge.length) }
+                .map_err(|_| RdmaCmdError::MrOutOfGuestMemory(sge.addr, u64::from(sge.length)))?;
+        }
+        Ok(payload)
+    }
+
+    /// Scatters `data` to the scatter/gather entries of a receive work request posted on a queue
+    /// pair of the protection domain `pd`, returning the `RDMA_WC_*` status of the work request.
+    fn scatter(&self, pd: u32, sges: &[RdmaSge], mut data: &[u8]) -> u32 {
+        let capacity: u64 = sges.iter().map(|sge| u64::from(sge.length)).sum();
+        if capacity < u64::try_from(data.len()).unwrap() {
+            return RDMA_WC_LOC_LEN_ERR;
+        }
+        for sge in sges {
+            if data.is_empty() {
+                break;
+            }
+            if let Err(err) = self.check_local_access(pd, sge.lkey, sge.addr, sge.length, true) {
+                debug!("rdma: Failed to scatter received data: {err}");
+                return RDMA_WC_LOC_PROT_ERR;
+            }
+            let (chunk, rest) = data.split_at(data.len().min(usize::try_from(sge.length).unwrap()));
+            if self
+                .mem()
+                .write_slice(chunk, GuestAddress(sge.addr))
+                .is_err()
+            {
+                return RDMA_WC_LOC_PROT_ERR;
+            }
+            data = rest;
+        }
+        RDMA_WC_SUCCESS
+    }
+
+    /// Adds a work completion to the completion queue `cqn`.
+    fn complete(&mut self, cqn: u32, wc: RdmaWc, solicited: bool) {
+        let overrun = self.backend.overrun_cq();
+        if overrun {
+            self.metrics.injected_faults.inc();
+        }
+        // Completion queues outlive the queue pairs using them.
+        let cq = self.cqs.get_mut(cqn).unwrap();
+        if overrun || !cq.push(wc) {
+            warn!("rdma: Completion queue {cqn} overflowed");
+            self.metrics.cq_overflows.inc();
+            self.push_event(RDMA_EVENT_CQ_ERR, cqn);
+            // The queue pair of the lost work completion can't be relied on anymore.
+            if self
+                .qps
+                .get(wc.qp_num)
+                .is_some_and(|qp| qp.state != RDMA_QPS_ERR)
+            {
+                self.push_event(RDMA_EVENT_QP_FATAL, wc.qp_num);
+                self.set_qp_error(wc.qp_num);
+            }
+            return;
+        }
+        cq.notify(&wc, solicited);
+    }
+
+    /// Sends a message through the backend, with its payload read from the guest memory `payload`
+    /// covers if any, returning the `RDMA_WC_*` status of the work request which sent it.
+    /// Reliable connected queue pairs wait for the local backend to deliver their messages, so
+    /// that their work requests report why the remote queue pair failed them.
+    fn transmit(&mut self, mut msg: RdmaMessage, payload: Option<IoVecBuffer>) -> u32 {
+        // Only reliable connected queue pairs notice that their messages are lost while the link
+        // is down.
+        if self.port.state != RDMA_PORT_ACTIVE {
+            return if msg.qp_type == RDMA_QPT_RC {
+                RDMA_WC_RETRY_EXC_ERR
+            } else {
+                RDMA_WC_SUCCESS
+            };
+        }
+        if msg.qp_type != RDMA_QPT_RC || !self.backend.is_local() {
+            return match payload {
+                Some(payload) => self.backend.transmit_iov(msg, payload),
+                None => self.backend.transmit(msg),
+            };
+        }
+        // The device delivers the message itself, from a copy of its payload.
+        if let Some(payload) = payload {
+            msg.payload = copy_iov(&payload, 0);
+        }
+        // The messages sent before are delivered first.
+        self.process_rx();
+        self.deliver(&msg).unwrap_or_else(|err| {
+            debug!(
+                "rdma: Dropped message for queue pair {}: {err}",
+                msg.dest_qpn
+            );
+            self.metrics.rx_drops.inc();
+            dropped_msg_status(&err)
+        })
+    }
+
+    /// Delivers the messages received by the backend to their queue pairs.
+    fn process_rx(&mut self) {
+        while let Some(msg) = self.backend.receive() {
+            if let Err(err) = self.deliver(&msg) {
+                debug!(
+                    "rdma: Dropped message for queue pair {}: {err}",
+                    msg.dest_qpn
+                );
+                self.metrics.rx_drops.inc();
+            }
+        }
+    }
+
+    /// Delivers a message to its queue pair, returning the `RDMA_WC_*` status of the work request
+    /// which sent it, or the reason the message was dropped.
+    fn deliver(&mut self, msg: &RdmaMessage) -> Result<u32, RdmaCmdError> {
+        let qp = self
+            .qps
+            .get(msg.dest_qpn)
+            .ok_or(RdmaCmdError::UnknownQp(msg.dest_qpn))?;
+        if qp.qp_type != msg.qp_type {
+            return Err(RdmaCmdError::InvalidQpType(msg.qp_type));
+        }
+        // Queue pairs receive from the ready to receive state on, until they are in error.
+        if !matches!(
+            qp.state,
+            RDMA_QPS_RTR | RDMA_QPS_RTS | RDMA_QPS_SQD | RDMA_QPS_SQE
+        ) {
+            return Err(RdmaCmdError::QpStateMismatch(qp.state, RDMA_QPS_RTR));
+        }
+        // Connected queue pairs only receive from their peer, and datagrams must carry the
+        // Q_Key of their destination.
+        if qp.qp_type == RDMA_QPT_UD {
+            if msg.qkey != qp.attrs.qkey {
+                return Err(RdmaCmdError::InvalidQpAttr("qkey", msg.qkey));
+            }
+        } else if msg.src_qpn != qp.attrs.dest_qp_num {
+            return Err(RdmaCmdError::InvalidQpAttr("dest_qp_num", msg.src_qpn));
+        }
+        // The peer is not trusted to have checked the work request it sent: the operation must
+        // be one the queue pair receives, and only carry the immediate data or invalidated remote
+        // key its opcode comes with.
+        let valid_opcode = match msg.opcode {
+            RDMA_WR_SEND | RDMA_WR_SEND_WITH_IMM => true,
+            RDMA_WR_SEND_WITH_INV | RDMA_WR_RDMA_WRITE | RDMA_WR_RDMA_WRITE_WITH_IMM => {
+                qp.qp_type != RDMA_QPT_UD
+            }
+            _ => false,
+        };
+        if !valid_opcode {
+            return Err(RdmaCmdError::InvalidWrOpcode(msg.opcode, qp.qp_type));
+        }
+        let with_imm = matches!(
+            msg.opcode,
+            RDMA_WR_SEND_WITH_IMM | RDMA_WR_RDMA_WRITE_WITH_IMM
+        );
+        let with_inv = msg.opcode == RDMA_WR_SEND_WITH_INV;
+        if msg.imm_data.is_some() != with_imm || msg.invalidate_rkey.is_some() != with_inv {
+            return Err(RdmaCmdError::InvalidMsgHeader(msg.opcode));
+        }
+        // Datagrams fit in a single packet of the path MTU.
+        let max_msg_sz = if qp.qp_type == RDMA_QPT_UD {
+            128 << self.port.active_mtu
+        } else {
+            self.port.max_msg_sz
+        };
+        let length = u32::try_from(msg.payload.len())
+            .ok()
+            .filter(|&length| length <= max_msg_sz)
+            .ok_or(RdmaCmdError::MsgTooLong(msg.payload.len()))?;
+        if matches!(msg.opcode, RDMA_WR_RDMA_WRITE | RDMA_WR_RDMA_WRITE_WITH_IMM) {
+            // The immediate data needs a receive work request, which must be there before the
+            // data is written.
+            let has_recv_wr = match qp.srq {
+                0 => !qp.recv_queue.is_empty(),
+                srqn => !self.srqs.get(srqn).unwrap().recv_queue.is_empty(),
+            };
+            if msg.opcode == RDMA_WR_RDMA_WRITE_WITH_IMM && !has_recv_wr {
+                return Err(RdmaCmdError::NoRecvWr(msg.dest_qpn));
+            }
+            // Zero length writes access no memory, and their remote key is not checked.
+            if length != 0 {
+                self.check_remote_access(
+                    msg.dest_qpn,
+                    msg.rkey,
+                    msg.remote_addr,
+                    length,
+                    RDMA_ACCESS_REMOTE_WRITE,
+                )?;
+                self.mem()
+                    .write_slice(&msg.payload, GuestAddress(msg.remote_addr))
+                    .map_err(|_| {
+                        RdmaCmdError::MrOutOfGuestMemory(msg.remote_addr, u64::from(length))
+                    })?;
+            }
+            if msg.opcode == RDMA_WR_RDMA_WRITE {
+                return Ok(RDMA_WC_SUCCESS);
+            }
+        }
+
+        // The other messages consume a receive work request, of the shared receive queue of the
+        // queue pair if it has one.
+        let (qp_pd, recv_cq, qp_type, srqn) = (qp.pd, qp.recv_cq, qp.qp_type, qp.srq);
+        let mut pd = qp_pd;
+        let wr = if srqn == 0 {
+            let qp = self.qps.get_mut(msg.dest_qpn).unwrap();
+            let wr = qp
+                .recv_queue
+                .pop_front()
+                .ok_or(RdmaCmdError::NoRecvWr(msg.dest_qpn))?;
+            qp.outstanding.sub(OutstandingWork::of([&wr]));
+            wr
+        } else {
+            // Shared receive queues outlive the queue pairs using them.
+            let srq = self.srqs.get_mut(srqn).unwrap();
+            pd = srq.pd;
+            let (wr, limit_reached) = srq.pop().ok_or(RdmaCmdError::NoRecvWr(msg.dest_qpn))?;
+            if limit_reached {
+                debug!("rdma: Shared receive queue {srqn} reached its limit");
+                self.metrics.srq_limit_events.inc();
+                self.push_event(RDMA_EVENT_SRQ_LIMIT_REACHED, srqn);
+            }
+            wr
+        };
+        // The work request is consumed, whether the message completes it or fails it.
+        self.pds
+            .get_mut(pd)
+            .unwrap()
+            .outstanding
+            .sub(OutstandingWork::of([&wr]));
+        let mut wc = RdmaWc {
+            wr_id: wr.wr_id,
+            opcode: RDMA_WC_RECV,
+            byte_len: length,
+            imm_data: msg.imm_data.unwrap_or_default(),
+            qp_num: msg.dest_qpn,
+            src_qp: msg.src_qpn,
+            wc_flags: if msg.imm_data.is_some() {
+                RDMA_WC_WITH_IMM
+            } else {
+                0
+            },
+            ..Default::default()
+        };
+
+        if msg.opcode == RDMA_WR_RDMA_WRITE_WITH_IMM {
+            // The data of RDMA writes already went to the remote address.
+            wc.opcode = RDMA_WC_RECV_RDMA_WITH_IMM;
+        } else if qp_type == RDMA_QPT_UD {
+            // Datagrams are preceded by their global route header.
+            let mut data = vec![0u8; RDMA_GRH_LEN];
+            data[..4].copy_from_slice(&0x6000_0000u32.to_be_bytes());
+            data[4..6].copy_from_slice(&u16::try_from(length).unwrap().to_be_bytes());
+            data[6] = GRH_NEXT_HEADER;
+            data[8..24].copy_from_slice(&msg.sgid);
+            data[24..40].copy_from_slice(&msg.dgid);
+            data.extend_from_slice(&msg.payload);
+            wc.status = self.scatter(pd, &wr.sges, &data);
+            wc.byte_len = u32::try_from(data.len()).unwrap();
+            wc.wc_flags |= RDMA_WC_GRH;
+        } else {
+            wc.status = self.scatter(pd, &wr.sges, &msg.payload);
+        }
+        // Sends with invalidate invalidate a memory window the receiving queue pair bound.
+        if wc.status == RDMA_WC_SUCCESS
+            && let Some(rkey) = msg.invalidate_rkey
+        {
+            match self.invalidate_mw(qp_pd, rkey, Some(msg.dest_qpn)) {
+                Ok(()) => {
+                    // The invalidated remote key takes the place of the immediate data.
+                    wc.imm_data = rkey;
+                    wc.wc_flags |= RDMA_WC_WITH_INV;
+                }
+                Err(err) => {
+                    debug!("rdma: Failed to invalidate a memory window: {err}");
+                    wc.status = RDMA_WC_REM_INV_REQ_ERR;
+                }
+            }
+        }
+        let status = wc.status;
+        self.complete(recv_cq, wc, msg.solicited);
+        if status != RDMA_WC_SUCCESS {
+            self.set_qp_error(msg.dest_qpn);
+        }
+        Ok(match status {
+            RDMA_WC_SUCCESS => RDMA_WC_SUCCESS,
+            RDMA_WC_LOC_LEN_ERR | RDMA_WC_REM_INV_REQ_ERR => RDMA_WC_REM_INV_REQ_ERR,
+            _ => RDMA_WC_REM_OP_ERR,
+        })
+    }
+
+    fn poll_cq(&mut self, cmd: RdmaCmdPollCq, result_room: usize) -> Result<Vec<u8>, RdmaCmdError> {
+        check_result_room::<RdmaRspPollCq>(result_room)?;
+        let cq = self
+            .cqs
+            .get_mut(cmd.cqn)
+            .ok_or(RdmaCmdError::UnknownCq(cmd.cqn))?;
+        // Only return the completions the response buffer has room for.
+        let room = (result_room - size_of::<RdmaRspPollCq>()) / size_of::<RdmaWc>();
+        let count = cq
+            .completions
+            .len()
+            .min(room)
+            .min(usize::try_from(cmd.num_entries).unwrap());
+        let rsp = RdmaRspPollCq {
+            num_entries: u32::try_from(count).unwrap(),
+            ..Default::default()
+        };
+        let mut result = rsp.as_slice().to_vec();
+        for wc in cq.completions.drain(..count) {
+            result.extend_from_slice(wc.as_slice());
+        }
+        Ok(result)
+    }
+
+    fn req_notify_cq(&mut self, cmd: RdmaCmdReqNotifyCq) -> Result<(), RdmaCmdError> {
+        if !matches!(cmd.flags, RDMA_CQ_SOLICITED | RDMA_CQ_NEXT_COMP) {
+            return Err(RdmaCmdError::InvalidNotifyFlags(cmd.flags));
+        }
+        let cq = self
+            .cqs
+            .get_mut(cmd.cqn)
+            .ok_or(RdmaCmdError::UnknownCq(cmd.cqn))?;
+        // The completion queues pushing their work completions interrupt the driver anyway.
+        if cq.flags & RDMA_CQ_F_ASYNC != 0 {
+            return Err(RdmaCmdError::CqNotPolled(cmd.cqn));
+        }
+        // Being armed for the next work completion covers the solicited ones.
+        if cq.armed != RDMA_CQ_NEXT_COMP {
+            cq.armed = cmd.flags;
+        }
+        Ok(())
+    }
+
+    fn create_srq(&mut self, cmd: RdmaCmdCreateSrq) -> Result<RdmaRspCreateSrq, RdmaCmdError> {
+        if cmd.max_wr == 0
+            || cmd.max_wr > self.caps.max_srq_wr
+            || cmd.max_sge > self.caps.max_srq_sge
+        {
+            return Err(RdmaCmdError::InvalidSrqCap);
+        }
+        if cmd.srq_limit > cmd.max_wr {
+            return Err(RdmaCmdError::InvalidSrqAttr("srq_limit", cmd.srq_limit));
+        }
+        if self.pds.get(cmd.pd).is_none() {
+            return Err(RdmaCmdError::UnknownPd(cmd.pd));
+        }
+
+        let srqn = self
+            .srqs
+            .insert(SharedReceiveQueue {
+                pd: cmd.pd,
+                max_wr: cmd.max_wr,
+                max_sge: cmd.max_sge,
+                limit: cmd.srq_limit,
+                recv_queue: VecDeque::new(),
+            })
+            .ok_or(RdmaCmdError::NoSrqLeft)?;
+        debug!("rdma: Created shared receive queue {srqn}");
+        Ok(RdmaRspCreateSrq {
+            srqn,
+            ..Default::default()
+        })
+    }
+
+    fn destroy_srq(&mut self, cmd: RdmaCmdDestroySrq) -> Result<(), RdmaCmdError> {
+        if self.srqs.get(cmd.srqn).is_none() {
+            return Err(RdmaCmdError::UnknownSrq(cmd.srqn));
+        }
+        // The queue pairs using a shared receive queue must be destroyed first.
+        if self.qps.iter().any(|(_, qp)| qp.srq == cmd.srqn) {
+            return Err(RdmaCmdError::SrqInUse(cmd.srqn));
+        }
+        // The receive work requests of the queue are dropped along with it.
+        let srq = self.srqs.remove(cmd.srqn).unwrap();
+        self.pds
+            .get_mut(srq.pd)
+            .unwrap()
+            .outstanding
+            .sub(OutstandingWork::of(&srq.recv_queue));
+        debug!("rdma: Destroyed shared receive queue {}", cmd.srqn);
+        Ok(())
+    }
+
+    /// Applies `RDMA_CMD_MODIFY_SRQ`. Nothing is changed if the command fails.
+    fn modify_srq(&mut self, cmd: RdmaCmdModifySrq) -> Result<(), RdmaCmdError> {
+        if cmd.attr_mask & !(RDMA_SRQ_MAX_WR | RDMA_SRQ_LIMIT) != 0 {
+            return Err(RdmaCmdError::InvalidSrqAttrMask(cmd.attr_mask));
+        }
+        let max_srq_wr = self.caps.max_srq_wr;
+        let srq = self
+            .srqs
+            .get_mut(cmd.srqn)
+            .ok_or(RdmaCmdError::UnknownSrq(cmd.srqn))?;
+        let mut max_wr = srq.max_wr;
+        if cmd.attr_mask & RDMA_SRQ_MAX_WR != 0 {
+            // The queue may not shrink below the work requests it holds.
+            if cmd.max_wr == 0
+                || cmd.max_wr > max_srq_wr
+                || usize::try_from(cmd.max_wr).unwrap() < srq.recv_queue.len()
+            {
+                return Err(RdmaCmdError::InvalidSrqAttr("max_wr", cmd.max_wr));
+            }
+            max_wr = cmd.max_wr;
+        }
+        let mut limit = srq.limit;
+        if cmd.attr_mask & RDMA_SRQ_LIMIT != 0 {
+            if cmd.srq_limit > max_wr {
+                return Err(RdmaCmdError::InvalidSrqAttr("srq_limit", cmd.srq_limit));
+            }
+            limit = cmd.srq_limit;
+        }
+        srq.max_wr = max_wr;
+        srq.limit = limit;
+        debug!(
+            "rdma: Modified shared receive queue {}: max_wr {max_wr}, limit {limit}",
+            cmd.srqn
+        );
+        Ok(())
+    }
+
+    fn post_srq_recv(&mut self, args: &Args) -> Result<(), RdmaCmdError> {
+        let cmd: RdmaCmdPostSrqRecv = args.read(self.mem())?;
+        let srq = self
+            .srqs
+            .get(cmd.srqn)
+            .ok_or(RdmaCmdError::UnknownSrq(cmd.srqn))?;
+        if cmd.num_sge > srq.max_sge {
+            return Err(RdmaCmdError::TooManySge(cmd.num_sge));
+        }
+        let sges = args.read_sges(self.mem(), size_of::<RdmaCmdPostSrqRecv>(), cmd.num_sge)?;
+
+        let srq = self.srqs.get(cmd.srqn).unwrap();
+        if srq.recv_queue.len() >= usize::try_from(srq.max_wr).unwrap() {
+            return Err(RdmaCmdError::SrqFull(cmd.srqn));
+        }
+        let wr = RecvWr {
+            wr_id: cmd.wr_id,
+            sges,
+        };
+        let work = OutstandingWork::of([&wr]);
+        let pd = srq.pd;
+        self.check_wr_quota(None, pd, work.bytes)?;
+        self.srqs
+            .get_mut(cmd.srqn)
+            .unwrap()
+            .recv_queue
+            .push_back(wr);
+        self.pds.get_mut(pd).unwrap().outstanding.add(work);
+        self.metrics.recv_wr_count.inc();
+        Ok(())
+    }
+
+    /// Destroys all the resources created by the driver, returning how many there were.
+    fn destroy_resources(&mut self) -> usize {
+        // The events are about the resources being destroyed.
+        self.pending_events.clear();
+        self.qps.clear()
+            + self.srqs.clear()
+            + self.cqs.clear()
+            + self.mws.clear()
+            + self.mrs.clear()
+            + self.ahs.clear()
+            + self.pds.clear()
+    }
+}
+
+/// Checks that the response buffer can hold the result of a command, before executing it.
+fn check_result_room<T: ByteValued>(room: usize) -> Result<(), RdmaCmdError> {
+    if room < size_of::<T>() {
+        return Err(RdmaCmdError::ResponseTooShort);
+    }
+    Ok(())
+}
+
+/// Command popped from a command queue, along with the buffer receiving its response.
+#[derive(Debug)]
+struct Command {
+    hdr: RdmaCmdHdr,
+    /// Arguments following the header.
+    args: Args,
+    response: Args,
+}
+
+impl Command {
+    /// Parses the command of a descriptor chain, without reading its arguments.
+    fn parse(head: DescriptorChain, mem: &GuestMemoryMmap) -> Result<Self, RdmaError> {
+        if head.is_write_only() {
+            return Err(RdmaError::UnexpectedDescriptorDirection);
+        }
+        // The command may be spread over several device-readable descriptors, followed by the
+        // device-writable descriptors receiving the response.
+        let mut command = Vec::new();
+        let mut response = Vec::new();
+        let mut next = Some(head);
+        while let Some(desc) = next {
+            let segment = (desc.addr, usize::try_from(desc.len).unwrap());
+            if desc.is_write_only() {
+                response.push(segment);
+            } else if response.is_empty() {
+                command.push(segment);
+            } else {
+                return Err(RdmaError::UnexpectedDescriptorDirection);
+            }
+            next = desc.next_descriptor();
+        }
+        if response.is_empty() {
+            return Err(RdmaError::DescriptorChainTooShort);
+        }
+        let command = Args::new(command);
+        let response = Args::new(response);
+        if command.len < size_of::<RdmaCmdHdr>() || response.len < size_of::<RdmaRspHdr>() {
+            return Err(RdmaError::DescriptorTooSmall);
+        }
+
+        let mut hdr = RdmaCmdHdr::default();
+        command.read_slice(mem, 0, hdr.as_mut_slice())?;
+        Ok(Command {
+            hdr,
+            args: command.skip(size_of::<RdmaCmdHdr>()),
+            response,
+        })
+    }
+}
+
+/// Arguments following the header of a command, or buffer receiving its response, spread over
+/// the guest memory ranges of the descriptors of its chain.
+#[derive(Debug)]
+struct Args {
+    /// Guest memory ranges, in order.
+    segments: Vec<(GuestAddress, usize)>,
+    /// Total length of the ranges, in bytes.
+    len: usize,
+}
+
+impl Args {
+    fn new(segments: Vec<(GuestAddress, usize)>) -> Self {
+        let len = segments.iter().map(|(_, len)| len).sum();
+        Args { segments, len }
+    }
+
+    /// Returns the arguments located `offset` bytes after the start of these ones.
+    fn skip(&self, mut offset: usize) -> Self {
+        let mut segments = Vec::new();
+        for &(addr, len) in &self.segments {
+            if offset < len {
+                segments.push((addr.unchecked_add(offset as u64), len - offset));
+                offset = 0;
+            } else {
+                offset -= len;
+            }
+        }
+        Args::new(segments)
+    }
+
+    /// Fills `buf` with the bytes located `offset` bytes after the start of the ranges, which
+    /// must hold them.
+    fn read_slice(
+        &self,
+        mem: &GuestMemoryMmap,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(), GuestMemoryError> {
+        let mut buf = buf;
+        for &(addr, len) in &self.skip(offset).segments {
+            if buf.is_empty() {
+                break;
+            }
+            let chunk_len = len.min(buf.len());
+            let (chunk, rest) = std::mem::take(&mut buf).split_at_mut(chunk_len);
+            mem.read_slice(chunk, addr)?;
+            buf = rest;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` to the start of the ranges, which must hold it.
+    fn write_slice(&self, mem: &GuestMemoryMmap, data: &[u8]) -> Result<(), GuestMemoryError> {
+        let mut data = data;
+        for &(addr, len) in &self.segments {
+            if data.is_empty() {
+                break;
+            }
+            let (chunk, rest) = data.split_at(len.min(data.len()));
+            mem.write_slice(chunk, addr)?;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    fn read<T: ByteValued + Default>(&self, mem: &GuestMemoryMmap) -> Result<T, RdmaCmdError> {
+        self.read_at(mem, 0)
+    }
+
+    /// Reads a `T` located `offset` bytes after the start of the arguments.
+    fn read_at<T: ByteValued + Default>(
+        &self,
+        mem: &GuestMemoryMmap,
+        offset: usize,
+    ) -> Result<T, RdmaCmdError> {
+        if self.len < offset + size_of::<T>() {
+            return Err(RdmaCmdError::ArgumentsTooShort);
+        }
+        let mut obj = T::default();
+        self.read_slice(mem, offset, obj.as_mut_slice())
+            .map_err(|_| RdmaCmdError::ArgumentsTooShort)?;
+        Ok(obj)
+    }
+
+    /// Reads the `len` bytes located `offset` bytes after the start of the arguments.
+    fn read_bytes(
+        &self,
+        mem: &GuestMemoryMmap,
+        offset: usize,
+        len: u32,
+    ) -> Result<Vec<u8>, RdmaCmdError> {
+        let len = usize::try_from(len).unwrap();
+        if self.len < offset + len {
+            return Err(RdmaCmdError::ArgumentsTooShort);
+        }
+        let mut bytes = vec![0u8; len];
+        self.read_slice(mem, offset, &mut bytes)
+            .map_err(|_| RdmaCmdError::ArgumentsTooShort)?;
+        Ok(bytes)
+    }
+
+    /// Reads the `count` scatter/gather entries located `offset` bytes after the start of the
+    /// arguments.
+    fn read_sges(
+        &self,
+        mem: &GuestMemoryMmap,
+        offset: usize,
+        count: u32,
+    ) -> Result<Vec<RdmaSge>, RdmaCmdError> {
+        (0..usize::try_from(count).unwrap())
+            .map(|i| self.read_at(mem, offset + i * size_of::<RdmaSge>()))
+            .collect()
+    }
+}
+
+impl VirtioDevice for VirtioRdma {
+    impl_device_type!(VirtioDeviceType::Rdma);
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &dyn VirtioInterrupt {
+        self.device_state
+            .active_state()
+            .expect("Device is not initialized")
+            .interrupt
+            .deref()
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn shm_regions(&self) -> Vec<(u64, Arc<dyn BusDeviceSync>)> {
+        // The doorbells are region `RDMA_SHM_DOORBELLS`, the only one.
+        vec![(RDMA_DOORBELL_REGION_LEN, self.doorbells.clone())]
+    }
+
+    fn read_config(&self, offset: u64, data: &mut [u8]) {
+        let config_space = RdmaConfigSpace {
+            num_queues: u32::from(self.num_queues()),
+            max_qp: self.caps.max_qp,
+            max_cq: self.caps.max_cq,
+            max_mr: self.caps.max_mr,
+            max_pd: self.caps.max_pd,
+            max_ah: self.caps.max_ah,
+            max_sge: self.caps.max_sge,
+            port_state: self.port.state,
+            active_mtu: self.port.active_mtu,
+            node_guid: self.node_guid.to_be_bytes(),
+        };
+        if let Some(config_space_bytes) = config_space.as_slice().get(u64_to_usize(offset)..) {
+            let len = config_space_bytes.len().min(data.len());
+            data[..len].copy_from_slice(&config_space_bytes[..len]);
+        } else {
+            error!("rdma: Failed to read config space");
+            self.metrics.cfg_fails.inc();
+        }
+    }
+
+    fn write_config(&mut self, offset: u64, data: &[u8]) {
+        warn!(
+            "rdma: Rejected write of {} bytes to the read-only config space at offset {offset}",
+            data.len()
+        );
+        self.metrics.cfg_fails.inc();
+    }
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn activate(
+        &mut self,
+        mem: GuestMemoryMmap,
+        interrupt: Arc<dyn VirtioInterrupt>,
+    ) -> Result<(), ActivateError> {
+        if self.queues.len() != rdma_num_queues(self.num_queues()) {
+            self.metrics.activate_fails.inc();
+            return Err(ActivateError::QueueMismatch {
+                expected: rdma_num_queues(self.num_queues()),
+                got: self.queues.len(),
+            });
+        }
+
+        let packed = self.has_feature(u64::from(VIRTIO_F_RING_PACKED));
+        for q in self.queues.iter_mut() {
+            if packed {
+                q.enable_packed_ring();
+            }
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        // The driver is kicked and kicks the device only when the other side waits for it.
+        if self.has_feature(u64::from(VIRTIO_RING_F_EVENT_IDX)) {
+            for queue in &mut self.queues {
+                queue.enable_notif_suppression();
+            }
+        }
+
+        self.activate_event.write(1).map_err(|_| {
+            self.metrics.activate_fails.inc();
+            ActivateError::EventFd
+        })?;
+        self.device_state = DeviceState::Activated(ActiveState { mem, interrupt });
+        // The backend is probed, or set up again if a restore couldn't, once the device runs.
+        self.arm_backend_timer();
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Option<(Arc<dyn VirtioInterrupt>, Vec<EventFd>)> {
+        let queue_events = self
+            .queue_events
+            .iter()
+            .map(EventFd::try_clone)
+            .collect::<Result<Vec<EventFd>, io::Error>>()
+            .ok()?;
+
+        // The driver is expected to destroy its resources before resetting the device.
+        let leaked = self.destroy_resources();
+        if leaked != 0 {
+            warn!("rdma: {leaked} resources were not destroyed before the device reset");
+            self.metrics
+                .leaked_resources
+                .add(u64::try_from(leaked).unwrap());
+        }
+
+        self.acked_features = 0;
+        self.unsignaled_cqes = 0;
+        self.cq_moderation_timer.arm(Duration::ZERO, None);
+        self.backend_timer.arm(Duration::ZERO, None);
+        self.throttled_queues.clear();
+        match std::mem::replace(&mut self.device_state, DeviceState::Inactive) {
+            DeviceState::Activated(state) => Some((state.interrupt, queue_events)),
+            DeviceState::Inactive => None,
+        }
+    }
+
+    fn unplug(&mut self) {
+        // The outstanding work completions and events are pushed to the driver before the
+        // resources of the device are destroyed.
+        self.drain();
+        self.reset();
+        // The backend releases its host resources, such as its sockets or shared memory,
+        // rather than once the device is dropped by the event manager.
+        self.unwatch_backend_events();
+        self.backend = Box::new(NullBackend);
+    }
+
+    fn prepare_save(&mut self) {
+        if !self.is_activated() {
+            return;
+        }
+
+        // Neither the messages carried by the backend nor the timer of the completion
+        // moderation are saved: the messages in flight are delivered, and the interrupt deferred
+        // by the moderation is raised.
+        self.process_backend().unwrap_or_else(|err| {
+            error!("rdma: Failed to prepare the device for saving: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+        if self.cq_moderation_timer.is_armed() {
+            self.flush_completions();
+        }
+    }
+
+    fn drain(&mut self) {
+        if !self.is_activated() {
+            return;
+        }
+
+        // The messages in flight are delivered before the queued commands are executed, and
+        // the resulting work completions and events are pushed to the driver.
+        self.process_rx();
+        let cmd_queues = [RDMA_CTRL_QUEUE]
+            .into_iter()
+            .chain(RDMA_SEND_QUEUE..self.queues.len());
+        for queue_index in cmd_queues {
+            self.process_cmd_queue(queue_index).unwrap_or_else(|err| {
+                error!("rdma: Failed to drain the device: {err:?}");
+                self.metrics.event_fails.inc();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::os::unix::net::UnixDatagram;
+    use std::sync::Mutex;
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::queue::{
+        PackedDescriptor, RING_EVENT_FLAGS_DESC, VIRTQ_DESC_F_AVAIL, VIRTQ_DESC_F_NEXT,
+        VIRTQ_DESC_F_USED, VIRTQ_DESC_F_WRITE,
+    };
+    use crate::devices::virtio::rdma::backend::shmem::ShmemBackendConfig;
+    use crate::devices::virtio::rdma::backend::thread::tests::socket_denying_filter;
+    use crate::devices::virtio::rdma::backend::unix::UnixBackendConfig;
+    use crate::devices::virtio::rdma::gid::{GID_INDEX_IP, GID_INDEX_LINK_LOCAL};
+    use crate::devices::virtio::rdma::{
+        RDMA_DEFAULT_PKEY, RDMA_DOORBELL_SIZE, RDMA_QP_ACCESS_FLAGS, RDMA_QP_AV, RDMA_QP_DEST_QPN,
+        RDMA_QP_MAX_DEST_RD_ATOMIC, RDMA_QP_MAX_QP_RD_ATOMIC, RDMA_QP_MIN_RNR_TIMER,
+        RDMA_QP_PATH_MTU, RDMA_QP_PORT, RDMA_QP_QKEY, RDMA_QP_RETRY_CNT, RDMA_QP_RNR_RETRY,
+        RDMA_QP_RQ_PSN, RDMA_QP_SQ_PSN, RDMA_QP_STATE, RDMA_QP_TIMEOUT, RDMA_STATUS_BAD_ADDRESS,
+        RDMA_STATUS_BUSY, RDMA_STATUS_INVALID_ACCESS, RDMA_STATUS_INVALID_ARG,
+        RDMA_STATUS_INVALID_HANDLE, RDMA_STATUS_INVALID_STATE, RDMA_STATUS_NO_RESOURCES,
+        RDMA_STATUS_UNSUPPORTED,
+    };
+    use crate::devices::virtio::test_utils::{VirtQueue, default_interrupt, default_mem};
+    use crate::seccomp::apply_filter;
+    use crate::vstate::memory::GuestMemoryRemovals;
+
+    /// Command sent on the control queue: opcode, arguments and size of the response buffer.
+    type Command<'a> = (u32, &'a [u8], u32);
+
+    fn rsp_len<T>() -> u32 {
+        u32::try_from(size_of::<RdmaRspHdr>() + size_of::<T>()).unwrap()
+    }
+
+    fn create_qp_cmd(qp_type: u32) -> RdmaCmdCreateQp {
+        RdmaCmdCreateQp {
+            qp_type,
+            send_cq: 1,
+            recv_cq: 1,
+            max_send_wr: 16,
+            max_recv_wr: 16,
+            max_send_sge: 1,
+            max_recv_sge: 1,
+            pd: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Executes commands on an activated device, returning the status and the payload of their
+    /// responses.
+    fn run_commands(rdma: &mut VirtioRdma, commands: &[Command]) -> Vec<(u32, Vec<u8>)> {
+        run_queue_commands(rdma, RDMA_CTRL_QUEUE, commands)
+    }
+
+    /// Executes commands sent on the queue `queue_index` of an activated device.
+    fn run_queue_commands(
+        rdma: &mut VirtioRdma,
+        queue_index: usize,
+        commands: &[Command],
+    ) -> Vec<(u32, Vec<u8>)> {
+        let mem = rdma.mem().clone();
+        let vq = queue_commands(rdma, &mem, queue_index, commands);
+        rdma.process_cmd_queue(queue_index).unwrap();
+
+        assert_eq!(usize::from(vq.used.idx.get()), commands.len());
+        (0..commands.len())
+            .map(|i| {
+                let len = usize::try_from(vq.used.ring[i].get().len).unwrap();
+                let addr = GuestAddress(0x1000 * (u64::try_from(i).unwrap() + 1) + 0x100);
+                let mut rsp = vec![0u8; len];
+                mem.read_slice(&mut rsp, addr).unwrap();
+                if rsp.is_empty() {
+                    return (u32::MAX, rsp);
+                }
+                let status = u32::from_le_bytes(rsp[..4].try_into().unwrap());
+                (status, rsp.split_off(size_of::<RdmaRspHdr>()))
+            })
+            .collect()
+    }
+
+    /// Queues commands on the queue `queue_index` of an activated device, without executing
+    /// them.
+    fn queue_commands<'a>(
+        rdma: &mut VirtioRdma,
+        mem: &'a GuestMemoryMmap,
+        queue_index: usize,
+        commands: &[Command],
+    ) -> VirtQueue<'a> {
+        let vq = VirtQueue::new(GuestAddress(0), mem, 16);
+        rdma.queues[queue_index] = vq.create_queue();
+
+        for (i, (opcode, args, rsp_len)) in commands.iter().enumerate() {
+            let i = u16::try_from(i).unwrap();
+            let addr = 0x1000 * (u64::from(i) + 1);
+            let hdr = RdmaCmdHdr {
+                opcode: *opcode,
+                ..Default::default()
+            };
+            let mut cmd = hdr.as_slice().to_vec();
+            cmd.extend_from_slice(args);
+            mem.write_slice(&cmd, GuestAddress(addr)).unwrap();
+            vq.dtable[usize::from(2 * i)].set(
+                addr,
+                u32::try_from(cmd.len()).unwrap(),
+                VIRTQ_DESC_F_NEXT,
+                2 * i + 1,
+            );
+            vq.dtable[usize::from(2 * i + 1)].set(addr + 0x100, *rsp_len, VIRTQ_DESC_F_WRITE, 0);
+            vq.avail.ring[usize::from(i)].set(2 * i);
+        }
+        vq.avail.idx.set(u16::try_from(commands.len()).unwrap());
+        vq
+    }
+
+    /// Activates a device, with a completion queue and a protection domain for the queue pairs
+    /// of `create_qp_cmd()`.
+    fn activated_rdma(id: &str) -> VirtioRdma {
+        let mut rdma = VirtioRdma::new(id.to_string()).unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        create_cq(&mut rdma);
+        rdma.alloc_pd().unwrap();
+        rdma
+    }
+
+    fn create_cq(rdma: &mut VirtioRdma) -> u32 {
+        rdma.create_cq(RdmaCmdCreateCq {
+            cqe: 64,
+            ..Default::default()
+        })
+        .unwrap()
+        .cqn
+    }
+
+    #[test]
+    fn test_create_destroy_qp() {
+        let mut rdma = activated_rdma("rdma-qp");
+        let create = create_qp_cmd(RDMA_QPT_RC);
+        let destroy = |qpn: u32| RdmaCmdDestroyQp {
+            qpn,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_CREATE_QP,
+                    create.as_slice(),
+                    rsp_len::<RdmaRspCreateQp>(),
+                ),
+                (
+                    RDMA_CMD_CREATE_QP,
+                    create.as_slice(),
+                    rsp_len::<RdmaRspCreateQp>(),
+                ),
+                (RDMA_CMD_DESTROY_QP, destroy(1).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspCreateQp::from_slice(&responses[0].1).unwrap().qpn, 1);
+        assert_eq!(responses[1].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspCreateQp::from_slice(&responses[1].1).unwrap().qpn, 2);
+        assert_eq!(responses[2], (RDMA_STATUS_OK, Vec::new()));
+        assert!(rdma.qps.get(1).is_none());
+        assert_eq!(rdma.qps.get(2).unwrap().max_send_wr, 16);
+
+        // Destroying a queue pair twice, or one which was never created, fails.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_DESTROY_QP, destroy(1).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DESTROY_QP, destroy(42).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(rdma.qps.len(), 1);
+    }
+
+    #[test]
+    fn test_create_qp_errors() {
+        let mut rdma = activated_rdma("rdma-qp-errors");
+        let invalid_type = create_qp_cmd(1);
+        let too_many_wr = RdmaCmdCreateQp {
+            max_send_wr: RDMA_MAX_QP_WR + 1,
+            ..create_qp_cmd(RDMA_QPT_UD)
+        };
+        let too_many_sge = RdmaCmdCreateQp {
+            max_recv_sge: RDMA_MAX_SGE + 1,
+            ..create_qp_cmd(RDMA_QPT_UC)
+        };
+        let create = create_qp_cmd(RDMA_QPT_RC);
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_CREATE_QP, invalid_type.as_slice(), 64),
+                (RDMA_CMD_CREATE_QP, too_many_wr.as_slice(), 64),
+                (RDMA_CMD_CREATE_QP, too_many_sge.as_slice(), 64),
+                // Truncated arguments.
+                (RDMA_CMD_CREATE_QP, &create.as_slice()[..8], 64),
+                // The response can't hold the queue pair number.
+                (RDMA_CMD_CREATE_QP, create.as_slice(), rsp_len::<()>()),
+                (0xff, &[], 64),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_UNSUPPORTED,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert!(rdma.qps.is_empty());
+
+        // The device runs out of queue pairs.
+        for _ in 0..RDMA_MAX_QP {
+            rdma.create_qp(create).unwrap();
+        }
+        let responses = run_commands(&mut rdma, &[(RDMA_CMD_CREATE_QP, create.as_slice(), 64)]);
+        assert_eq!(responses[0], (RDMA_STATUS_NO_RESOURCES, Vec::new()));
+    }
+
+    #[test]
+    fn test_create_destroy_cq() {
+        let mut rdma = VirtioRdma::new("rdma-cq".to_string()).unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        rdma.alloc_pd().unwrap();
+        let create = |cqe: u32| RdmaCmdCreateCq {
+            cqe,
+            ..Default::default()
+        };
+        let destroy = |cqn: u32| RdmaCmdDestroyCq {
+            cqn,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_CREATE_CQ,
+                    create(RDMA_MAX_CQE).as_slice(),
+                    rsp_len::<RdmaRspCreateCq>(),
+                ),
+                (RDMA_CMD_CREATE_CQ, create(0).as_slice(), 64),
+                (RDMA_CMD_CREATE_CQ, create(RDMA_MAX_CQE + 1).as_slice(), 64),
+                // The response can't hold the completion queue number.
+                (RDMA_CMD_CREATE_CQ, create(1).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DESTROY_CQ, destroy(42).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspCreateCq::from_slice(&responses[0].1).unwrap().cqn, 1);
+        assert_eq!(rdma.cqs.get(1).unwrap().cqe, RDMA_MAX_CQE);
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[2], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[4], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(rdma.cqs.len(), 1);
+
+        // Queue pairs can only use existing completion queues, which can't be destroyed while
+        // they are in use.
+        let recv_cq = create_cq(&mut rdma);
+        let unknown_cq = RdmaCmdCreateQp {
+            recv_cq: 42,
+            ..create_qp_cmd(RDMA_QPT_RC)
+        };
+        let create_qp = RdmaCmdCreateQp {
+            recv_cq,
+            ..create_qp_cmd(RDMA_QPT_RC)
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_CREATE_QP, unknown_cq.as_slice(), 64),
+                (RDMA_CMD_CREATE_QP, create_qp.as_slice(), 64),
+                (RDMA_CMD_DESTROY_CQ, destroy(1).as_slice(), rsp_len::<()>()),
+                (
+                    RDMA_CMD_DESTROY_CQ,
+                    destroy(recv_cq).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[1].0, RDMA_STATUS_OK);
+        assert_eq!(responses[2], (RDMA_STATUS_BUSY, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_BUSY, Vec::new()));
+
+        let qpn = RdmaRspCreateQp::from_slice(&responses[1].1).unwrap().qpn;
+        rdma.destroy_qp(RdmaCmdDestroyQp {
+            qpn,
+            ..Default::default()
+        })
+        .unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_DESTROY_CQ, destroy(1).as_slice(), rsp_len::<()>()),
+                (
+                    RDMA_CMD_DESTROY_CQ,
+                    destroy(recv_cq).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert!(rdma.cqs.is_empty());
+
+        // The device runs out of completion queues.
+        for _ in 0..RDMA_MAX_CQ {
+            create_cq(&mut rdma);
+        }
+        let responses = run_commands(&mut rdma, &[(RDMA_CMD_CREATE_CQ, create(1).as_slice(), 64)]);
+        assert_eq!(responses[0], (RDMA_STATUS_NO_RESOURCES, Vec::new()));
+    }
+
+    #[test]
+    fn test_reg_dereg_mr() {
+        let mut rdma = activated_rdma("rdma-mr");
+        let mem_end = rdma.mem().last_addr().raw_value() + 1;
+        let reg = |iova: u64, length: u64, access: u32| RdmaCmdRegMr {
+            iova,
+            length,
+            access,
+            pd: 1,
+        };
+        let dereg = |lkey: u32| RdmaCmdDeregMr {
+            lkey,
+            ..Default::default()
+        };
+
+        let local = reg(0x8000, 0x1000, RDMA_ACCESS_LOCAL_WRITE);
+        let remote = reg(
+            0xa000,
+            0x2000,
+            RDMA_ACCESS_LOCAL_WRITE | RDMA_ACCESS_REMOTE_WRITE,
+        );
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_REG_MR, local.as_slice(), rsp_len::<RdmaRspRegMr>()),
+                (
+                    RDMA_CMD_REG_MR,
+                    remote.as_slice(),
+                    rsp_len::<RdmaRspRegMr>(),
+                ),
+                (RDMA_CMD_DEREG_MR, dereg(1).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(
+            *RdmaRspRegMr::from_slice(&responses[0].1).unwrap(),
+            RdmaRspRegMr { lkey: 1, rkey: 0 }
+        );
+        assert_eq!(responses[1].0, RDMA_STATUS_OK);
+        assert_eq!(
+            *RdmaRspRegMr::from_slice(&responses[1].1).unwrap(),
+            RdmaRspRegMr { lkey: 2, rkey: 2 }
+        );
+        assert_eq!(responses[2], (RDMA_STATUS_OK, Vec::new()));
+        assert!(rdma.mrs.get(1).is_none());
+        assert_eq!(rdma.mrs.get(2).unwrap().length, 0x2000);
+
+        // Each kind of invalid registration fails with its own status.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_REG_MR, reg(0, 0, 0).as_slice(), 64),
+                (RDMA_CMD_REG_MR, reg(0, 0x1000, 1 << 4).as_slice(), 64),
+                // Remote writes require local writes.
+                (
+                    RDMA_CMD_REG_MR,
+                    reg(0, 0x1000, RDMA_ACCESS_REMOTE_WRITE).as_slice(),
+                    64,
+                ),
+                (
+                    RDMA_CMD_REG_MR,
+                    reg(mem_end - 0x1000, 0x2000, 0).as_slice(),
+                    64,
+                ),
+                (RDMA_CMD_REG_MR, reg(u64::MAX, 2, 0).as_slice(), 64),
+                // The response can't hold the keys.
+                (RDMA_CMD_REG_MR, local.as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DEREG_MR, dereg(1).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ACCESS,
+            RDMA_STATUS_INVALID_ACCESS,
+            RDMA_STATUS_BAD_ADDRESS,
+            RDMA_STATUS_BAD_ADDRESS,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_HANDLE,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert_eq!(rdma.mrs.len(), 1);
+
+        // The device runs out of memory regions.
+        rdma.mrs.clear();
+        for _ in 0..RDMA_MAX_MR {
+            rdma.reg_mr(local).unwrap();
+        }
+        let responses = run_commands(&mut rdma, &[(RDMA_CMD_REG_MR, local.as_slice(), 64)]);
+        assert_eq!(responses[0], (RDMA_STATUS_NO_RESOURCES, Vec::new()));
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        let mut rdma = VirtioRdma::new("rdma-limits".to_string()).unwrap();
+        for (limits, name) in [
+            (
+                RdmaResourceLimits {
+                    max_qp: Some(0),
+                    ..Default::default()
+                },
+                "queue pair",
+            ),
+            (
+                RdmaResourceLimits {
+                    max_cq: Some(RDMA_MAX_CQ + 1),
+                    ..Default::default()
+                },
+                "completion queue",
+            ),
+            (
+                RdmaResourceLimits {
+                    max_memory_registration_bytes: Some(0),
+                    ..Default::default()
+                },
+                "memory registration",
+            ),
+            (
+                RdmaResourceLimits {
+                    max_outstanding_wr_per_qp: Some(0),
+                    ..Default::default()
+                },
+                "queue pair work request",
+            ),
+        ] {
+            assert!(matches!(
+                rdma.set_resource_limits(limits),
+                Err(RdmaError::InvalidResourceLimit(limit, _, _)) if limit == name
+            ));
+        }
+        assert_eq!(rdma.resource_limits(), RdmaResourceLimits::default());
+
+        let limits = RdmaResourceLimits {
+            max_qp: Some(1),
+            max_cq: Some(1),
+            max_mr: Some(2),
+            max_memory_registration_bytes: Some(0x3000),
+            ..Default::default()
+        };
+        rdma.set_resource_limits(limits).unwrap();
+        assert_eq!(rdma.resource_limits(), limits);
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        create_cq(&mut rdma);
+        rdma.alloc_pd().unwrap();
+
+        // The limits are the ones reported to the driver.
+        let caps = rdma.capabilities();
+        assert_eq!(
+            (caps.max_qp, caps.max_cq, caps.max_mr, caps.max_mr_size),
+            (1, 1, 2, 0x3000)
+        );
+        let mut config_space = [0u8; size_of::<RdmaConfigSpace>()];
+        rdma.read_config(0, &mut config_space);
+        let config_space = RdmaConfigSpace::from_slice(&config_space).unwrap();
+        assert_eq!(config_space.max_qp, 1);
+
+        let create = RdmaCmdCreateCq {
+            cqe: 64,
+            ..Default::default()
+        };
+        assert_eq!(rdma.create_cq(create), Err(RdmaCmdError::NoCqLeft));
+        rdma.create_qp(create_qp_cmd(RDMA_QPT_UD)).unwrap();
+        assert_eq!(
+            rdma.create_qp(create_qp_cmd(RDMA_QPT_UD)),
+            Err(RdmaCmdError::NoQpLeft)
+        );
+
+        // The memory regions register at most 0x3000 bytes together.
+        let reg = |length: u64| RdmaCmdRegMr {
+            iova: 0x8000,
+            length,
+            access: RDMA_ACCESS_LOCAL_WRITE,
+            pd: 1,
+        };
+        assert_eq!(
+            rdma.reg_mr(reg(0x4000)),
+            Err(RdmaCmdError::MrLimitExceeded(0x4000, 0x3000))
+        );
+        let lkey = rdma.reg_mr(reg(0x2000)).unwrap().lkey;
+        let responses = run_commands(&mut rdma, &[(RDMA_CMD_REG_MR, reg(0x2000).as_slice(), 64)]);
+        assert_eq!(responses[0], (RDMA_STATUS_NO_RESOURCES, Vec::new()));
+        rdma.reg_mr(reg(0x1000)).unwrap();
+        assert_eq!(rdma.reg_mr(reg(0x1)), Err(RdmaCmdError::NoMrLeft));
+
+        // Deregistered memory regions no longer count.
+        rdma.dereg_mr(RdmaCmdDeregMr {
+            lkey,
+            ..Default::default()
+        })
+        .unwrap();
+        rdma.reg_mr(reg(0x2000)).unwrap();
+    }
+
+    #[test]
+    fn test_modify_qp() {
+        let mut rdma = activated_rdma("rdma-modify-qp");
+        let qpn = rdma.create_qp(create_qp_cmd(RDMA_QPT_UD)).unwrap().qpn;
+        let modify = |qpn: u32, qp_state: u32, attr_mask: u32| RdmaCmdModifyQp {
+            qpn,
+            attr_mask: RDMA_QP_STATE | attr_mask,
+            qp_state,
+            port_num: 1,
+            qkey: 0x1234,
+            sq_psn: 0x42,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(
+                        qpn,
+                        RDMA_QPS_INIT,
+                        RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_QKEY,
+                    )
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(qpn, RDMA_QPS_RTR, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(qpn, RDMA_QPS_RTS, RDMA_QP_SQ_PSN).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                // Illegal transition.
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(qpn, RDMA_QPS_INIT, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                // Datagram queue pairs have no path MTU.
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(qpn, RDMA_QPS_RTS, RDMA_QP_PATH_MTU).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(42, RDMA_QPS_ERR, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                // Truncated arguments.
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    &modify(qpn, RDMA_QPS_ERR, 0).as_slice()[..8],
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_OK,
+            RDMA_STATUS_OK,
+            RDMA_STATUS_OK,
+            RDMA_STATUS_INVALID_STATE,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_HANDLE,
+            RDMA_STATUS_INVALID_ARG,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        let qp = rdma.qps.get(qpn).unwrap();
+        assert_eq!(qp.state, RDMA_QPS_RTS);
+        assert_eq!(qp.attrs.qkey, 0x1234);
+        assert_eq!(qp.attrs.sq_psn, 0x42);
+    }
+
+    #[test]
+    fn test_query_qp() {
+        let mut rdma = activated_rdma("rdma-query-qp");
+        let create = create_qp_cmd(RDMA_QPT_RC);
+        let qpn = rdma.create_qp(create).unwrap().qpn;
+        let modify = |qp_state: u32, attr_mask: u32| RdmaCmdModifyQp {
+            qpn,
+            attr_mask: RDMA_QP_STATE | attr_mask,
+            qp_state,
+            qp_access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            port_num: 1,
+            dgid: [0xfe; 16],
+            path_mtu: 3,
+            timeout: 14,
+            retry_cnt: 6,
+            rnr_retry: 5,
+            rq_psn: 0x1234,
+            max_rd_atomic: 4,
+            min_rnr_timer: 12,
+            sq_psn: 0x5678,
+            max_dest_rd_atomic: 2,
+            dest_qp_num: 0x42,
+            ..Default::default()
+        };
+        let query = RdmaCmdQueryQp {
+            qpn,
+            ..Default::default()
+        };
+        let mut expected = RdmaRspQueryQp {
+            qp_state: RDMA_QPS_RESET,
+            qp_type: RDMA_QPT_RC,
+            send_cq: create.send_cq,
+            recv_cq: create.recv_cq,
+            max_send_wr: create.max_send_wr,
+            max_recv_wr: create.max_recv_wr,
+            max_send_sge: create.max_send_sge,
+            max_recv_sge: create.max_recv_sge,
+            pd: create.pd,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_QUERY_QP,
+                    query.as_slice(),
+                    rsp_len::<RdmaRspQueryQp>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(
+                        RDMA_QPS_INIT,
+                        RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_ACCESS_FLAGS,
+                    )
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(
+                        RDMA_QPS_RTR,
+                        RDMA_QP_AV
+                            | RDMA_QP_PATH_MTU
+                            | RDMA_QP_DEST_QPN
+                            | RDMA_QP_RQ_PSN
+                            | RDMA_QP_MAX_DEST_RD_ATOMIC
+                            | RDMA_QP_MIN_RNR_TIMER,
+                    )
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_MODIFY_QP,
+                    modify(
+                        RDMA_QPS_RTS,
+                        RDMA_QP_SQ_PSN
+                            | RDMA_QP_TIMEOUT
+                            | RDMA_QP_RETRY_CNT
+                            | RDMA_QP_RNR_RETRY
+                            | RDMA_QP_MAX_QP_RD_ATOMIC,
+                    )
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_QP,
+                    query.as_slice(),
+                    rsp_len::<RdmaRspQueryQp>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_QP,
+                    RdmaCmdQueryQp {
+                        qpn: 42,
+                        ..Default::default()
+                    }
+                    .as_slice(),
+                    rsp_len::<RdmaRspQueryQp>(),
+                ),
+                // The response can't hold the attributes.
+                (RDMA_CMD_QUERY_QP, query.as_slice(), rsp_len::<()>()),
+            ],
+        );
+
+        assert_eq!(responses[0], (RDMA_STATUS_OK, expected.as_slice().to_vec()));
+        for response in &responses[1..4] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        expected = RdmaRspQueryQp {
+            qp_state: RDMA_QPS_RTS,
+            qp_access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            port_num: 1,
+            dgid: [0xfe; 16],
+            path_mtu: 3,
+            timeout: 14,
+            retry_cnt: 6,
+            rnr_retry: 5,
+            rq_psn: 0x1234,
+            max_rd_atomic: 4,
+            min_rnr_timer: 12,
+            sq_psn: 0x5678,
+            max_dest_rd_atomic: 2,
+            dest_qp_num: 0x42,
+            ..expected
+        };
+        assert_eq!(responses[4], (RDMA_STATUS_OK, expected.as_slice().to_vec()));
+        assert_eq!(responses[5], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[6], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+    }
+
+    #[test]
+    fn test_query_device() {
+        let mut rdma = activated_rdma("rdma-query-device");
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_QUERY_DEVICE, &[], rsp_len::<RdmaRspQueryDevice>()),
+                // The response buffer is too short.
+                (RDMA_CMD_QUERY_DEVICE, &[], rsp_len::<RdmaRspCreateQp>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        let rsp = RdmaRspQueryDevice::from_slice(&responses[0].1).unwrap();
+        assert_eq!(rsp.max_qp, RDMA_MAX_QP);
+        assert_eq!(rsp.max_qp_wr, RDMA_MAX_QP_WR);
+        assert_eq!(rsp.max_sge, RDMA_MAX_SGE);
+        assert_eq!(rsp.max_inline_data, RDMA_MAX_INLINE_DATA);
+        assert_eq!(rsp.max_cq, RDMA_MAX_CQ);
+        assert_eq!(rsp.max_cqe, RDMA_MAX_CQE);
+        assert_eq!(rsp.max_mr, RDMA_MAX_MR);
+        assert_eq!(rsp.max_mw, RDMA_MAX_MW);
+        // Atomic operations are executed by the device with the loopback backend.
+        assert_eq!(rsp.atomic_cap, RDMA_ATOMIC_HCA);
+        assert_eq!(rsp.phys_port_cnt, 1);
+        assert_eq!(rsp.max_pd, RDMA_MAX_PD);
+        assert_eq!(rsp.max_ah, RDMA_MAX_AH);
+        assert_eq!(rsp.max_srq, RDMA_MAX_SRQ);
+        assert_eq!(rsp.max_srq_wr, RDMA_MAX_SRQ_WR);
+        assert_eq!(rsp.max_srq_sge, RDMA_MAX_SGE);
+        assert_eq!(*rsp, RdmaRspQueryDevice::from(rdma.capabilities()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+
+        // The limits of the device are the ones it reports.
+        rdma.caps.max_cqe = 8;
+        assert_eq!(
+            rdma.create_cq(RdmaCmdCreateCq {
+                cqe: 9,
+                ..Default::default()
+            }),
+            Err(RdmaCmdError::InvalidCqSize(9))
+        );
+    }
+
+    #[test]
+    fn test_query_port() {
+        let mut rdma = activated_rdma("rdma-query-port");
+        let port = |port_num| RdmaCmdQueryPort {
+            port_num,
+            ..Default::default()
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_QUERY_PORT,
+                    port(1).as_slice(),
+                    rsp_len::<RdmaRspQueryPort>(),
+                ),
+                // Ports are numbered from 1.
+                (
+                    RDMA_CMD_QUERY_PORT,
+                    port(0).as_slice(),
+                    rsp_len::<RdmaRspQueryPort>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_PORT,
+                    port(RDMA_NUM_PORTS + 1).as_slice(),
+                    rsp_len::<RdmaRspQueryPort>(),
+                ),
+                // The response buffer is too short.
+                (
+                    RDMA_CMD_QUERY_PORT,
+                    port(1).as_slice(),
+                    rsp_len::<RdmaRspCreateQp>(),
+                ),
+                // The arguments are missing.
+                (RDMA_CMD_QUERY_PORT, &[], rsp_len::<RdmaRspQueryPort>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        let rsp = RdmaRspQueryPort::from_slice(&responses[0].1).unwrap();
+        assert_eq!(rsp.state, RDMA_PORT_ACTIVE);
+        assert_eq!(rsp.active_mtu, RDMA_MTU_1024);
+        assert_eq!(rsp.gid_tbl_len, RDMA_GID_TABLE_LEN);
+        assert_eq!(rsp.pkey_tbl_len, RDMA_PKEY_TABLE_LEN);
+        assert_eq!(*rsp, RdmaRspQueryPort::from(rdma.port_attributes()));
+        for response in &responses[1..] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+    }
+
+    #[test]
+    fn test_query_gid() {
+        let mac = MacAddr::from([0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        let ip = IpAddr::V4(Ipv4Addr::new(172, 16, 0, 2));
+        let mut rdma = VirtioRdma::new("rdma-query-gid".to_string()).unwrap();
+        // No event is raised before the driver activates the device.
+        rdma.set_addresses(Some(mac), Some(ip)).unwrap();
+        assert!(rdma.pending_events.is_empty());
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        let gid = |port_num, index| RdmaCmdQueryGid { port_num, index };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_QUERY_GID,
+                    gid(1, GID_INDEX_LINK_LOCAL).as_slice(),
+                    rsp_len::<RdmaRspQueryGid>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_GID,
+                    gid(1, GID_INDEX_IP).as_slice(),
+                    rsp_len::<RdmaRspQueryGid>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_GID,
+                    gid(1, RDMA_GID_TABLE_LEN - 1).as_slice(),
+                    rsp_len::<RdmaRspQueryGid>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_GID,
+                    gid(1, RDMA_GID_TABLE_LEN).as_slice(),
+                    rsp_len::<RdmaRspQueryGid>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_GID,
+                    gid(RDMA_NUM_PORTS + 1, 0).as_slice(),
+                    rsp_len::<RdmaRspQueryGid>(),
+                ),
+                // The response buffer is too short.
+                (RDMA_CMD_QUERY_GID, gid(1, 0).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        let gids: Vec<RdmaRspQueryGid> = responses[..3]
+            .iter()
+            .map(|(status, payload)| {
+                assert_eq!(*status, RDMA_STATUS_OK);
+                *RdmaRspQueryGid::from_slice(payload).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            gids[0].gid,
+            [
+                0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0x04, 0x00, 0xac, 0xff, 0xfe, 0x10, 0x00, 0x02
+            ]
+        );
+        assert_eq!(
+            gids[1].gid,
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 172, 16, 0, 2]
+        );
+        // The entries without an address are zero GIDs.
+        assert_eq!(gids[2].gid, [0; 16]);
+        assert!(gids.iter().all(|gid| gid.gid_type == RDMA_GID_TYPE_ROCE_V2));
+        for response in &responses[3..] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+
+        // Setting the same addresses leaves the GID table unchanged.
+        rdma.set_addresses(Some(mac), Some(ip)).unwrap();
+        assert!(rdma.pending_events.is_empty());
+
+        // The driver is told to read the GID table again once the addresses changed.
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_EVENT_QUEUE] = vq.create_queue();
+        vq.dtable[0].set(
+            0xd000,
+            u32::try_from(size_of::<RdmaAsyncEvent>()).unwrap(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+        rdma.set_addresses(Some(mac), None).unwrap();
+        assert_eq!(vq.used.idx.get(), 1);
+        assert_eq!(
+            mem.read_obj::<RdmaAsyncEvent>(GuestAddress(0xd000))
+                .unwrap(),
+            RdmaAsyncEvent {
+                event_type: RDMA_EVENT_GID_CHANGE,
+                handle: 1,
+            }
+        );
+        assert_eq!(rdma.gid_table().get(GID_INDEX_IP), Some(&[0; 16]));
+        assert_eq!(rdma.gid_table().mac(), Some(mac));
+    }
+
+    #[test]
+    fn test_query_pkey() {
+        let mut rdma = VirtioRdma::new("rdma-query-pkey".to_string()).unwrap();
+        rdma.set_pkeys(&[0x8001]).unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        create_cq(&mut rdma);
+        rdma.alloc_pd().unwrap();
+        let pkey = |port_num, index| RdmaCmdQueryPkey { port_num, index };
+        let qpn = rdma.create_qp(create_qp_cmd(RDMA_QPT_UD)).unwrap().qpn;
+        let init = |pkey_index| RdmaCmdModifyQp {
+            qpn,
+            attr_mask: RDMA_QP_STATE | RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_QKEY,
+            qp_state: RDMA_QPS_INIT,
+            pkey_index,
+            port_num: 1,
+            qkey: 0x1234,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_QUERY_PKEY,
+                    pkey(1, 0).as_slice(),
+                    rsp_len::<RdmaRspQueryPkey>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_PKEY,
+                    pkey(1, 1).as_slice(),
+                    rsp_len::<RdmaRspQueryPkey>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_PKEY,
+                    pkey(1, RDMA_PKEY_TABLE_LEN - 1).as_slice(),
+                    rsp_len::<RdmaRspQueryPkey>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_PKEY,
+                    pkey(1, RDMA_PKEY_TABLE_LEN).as_slice(),
+                    rsp_len::<RdmaRspQueryPkey>(),
+                ),
+                (
+                    RDMA_CMD_QUERY_PKEY,
+                    pkey(0, 0).as_slice(),
+                    rsp_len::<RdmaRspQueryPkey>(),
+                ),
+                // Queue pairs can't use the empty entries of the table.
+                (RDMA_CMD_MODIFY_QP, init(2).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_MODIFY_QP, init(1).as_slice(), rsp_len::<()>()),
+            ],
+        );
+        let pkeys: Vec<u32> = responses[..3]
+            .iter()
+            .map(|(status, payload)| {
+                assert_eq!(*status, RDMA_STATUS_OK);
+                RdmaRspQueryPkey::from_slice(payload).unwrap().pkey
+            })
+            .collect();
+        assert_eq!(pkeys, [u32::from(RDMA_DEFAULT_PKEY), 0x8001, 0]);
+        for response in &responses[3..6] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+        assert_eq!(responses[6], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.qps.get(qpn).unwrap().attrs.pkey_index, 1);
+        assert_eq!(rdma.pkey_table().configured(), [0x8001]);
+    }
+
+    #[test]
+    fn test_alloc_dealloc_pd() {
+        let mut rdma = activated_rdma("rdma-pd");
+        let dealloc = |pdn: u32| RdmaCmdDeallocPd {
+            pdn,
+            ..Default::default()
+        };
+        let reg_mr = |pd: u32| RdmaCmdRegMr {
+            iova: 0x8000,
+            length: 0x1000,
+            access: RDMA_ACCESS_LOCAL_WRITE,
+            pd,
+        };
+        let create_qp = |pd: u32| RdmaCmdCreateQp {
+            pd,
+            ..create_qp_cmd(RDMA_QPT_RC)
+        };
+        let create_ah = |pd: u32| RdmaCmdCreateAh {
+            pd,
+            port_num: 1,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_ALLOC_PD, &[], rsp_len::<RdmaRspAllocPd>()),
+                // The response can't hold the protection domain number.
+                (RDMA_CMD_ALLOC_PD, &[], rsp_len::<()>()),
+                // Queue pairs, memory regions and address handles can only use existing
+                // protection domains.
+                (RDMA_CMD_CREATE_QP, create_qp(42).as_slice(), 64),
+                (RDMA_CMD_REG_MR, reg_mr(42).as_slice(), 64),
+                (RDMA_CMD_CREATE_AH, create_ah(42).as_slice(), 64),
+                (
+                    RDMA_CMD_CREATE_QP,
+                    create_qp(2).as_slice(),
+                    rsp_len::<RdmaRspCreateQp>(),
+                ),
+                (
+                    RDMA_CMD_REG_MR,
+                    reg_mr(2).as_slice(),
+                    rsp_len::<RdmaRspRegMr>(),
+                ),
+                // Protection domains can't be deallocated while they are in use.
+                (RDMA_CMD_DEALLOC_PD, dealloc(2).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DEALLOC_PD, dealloc(42).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DEALLOC_PD, &[], rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspAllocPd::from_slice(&responses[0].1).unwrap().pdn, 2);
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        for response in &responses[2..5] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        }
+        assert_eq!(responses[5].0, RDMA_STATUS_OK);
+        assert_eq!(responses[6].0, RDMA_STATUS_OK);
+        assert_eq!(responses[7], (RDMA_STATUS_BUSY, Vec::new()));
+        assert_eq!(responses[8], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[9], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        let qpn = RdmaRspCreateQp::from_slice(&responses[5].1).unwrap().qpn;
+        let lkey = RdmaRspRegMr::from_slice(&responses[6].1).unwrap().lkey;
+        assert_eq!(rdma.qps.get(qpn).unwrap().pd, 2);
+        assert_eq!(
+            rdma.query_qp(RdmaCmdQueryQp {
+                qpn,
+                ..Default::default()
+            })
+            .unwrap()
+            .pd,
+            2
+        );
+
+        rdma.destroy_qp(RdmaCmdDestroyQp {
+            qpn,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(rdma.dealloc_pd(dealloc(2)), Err(RdmaCmdError::PdInUse(2)));
+        rdma.dereg_mr(RdmaCmdDeregMr {
+            lkey,
+            ..Default::default()
+        })
+        .unwrap();
+        let ah = rdma.create_ah(create_ah(2)).unwrap().ah;
+        assert_eq!(rdma.dealloc_pd(dealloc(2)), Err(RdmaCmdError::PdInUse(2)));
+        rdma.destroy_ah(RdmaCmdDestroyAh {
+            ah,
+            ..Default::default()
+        })
+        .unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[(RDMA_CMD_DEALLOC_PD, dealloc(2).as_slice(), rsp_len::<()>())],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.pds.len(), 1);
+
+        // The device runs out of protection domains.
+        for _ in 1..RDMA_MAX_PD {
+            rdma.alloc_pd().unwrap();
+        }
+        let responses = run_commands(&mut rdma, &[(RDMA_CMD_ALLOC_PD, &[], 64)]);
+        assert_eq!(responses[0], (RDMA_STATUS_NO_RESOURCES, Vec::new()));
+    }
+
+    #[test]
+    fn test_check_local_access() {
+        let mut rdma = activated_rdma("rdma-local-access");
+        let other_pd = rdma.alloc_pd().unwrap().pdn;
+        let reg = |access: u32, pd: u32| RdmaCmdRegMr {
+            iova: 0x8000,
+            length: 0x1000,
+            access,
+            pd,
+        };
+        let writable = rdma.reg_mr(reg(RDMA_ACCESS_LOCAL_WRITE, 1)).unwrap().lkey;
+        let read_only = rdma.reg_mr(reg(0, 1)).unwrap().lkey;
+        let foreign = rdma
+            .reg_mr(reg(RDMA_ACCESS_LOCAL_WRITE, other_pd))
+            .unwrap()
+            .lkey;
+
+        assert_eq!(
+            rdma.check_local_access(1, writable, 0x8000, 0x1000, true)
+                .unwrap()
+                .iova,
+            0x8000
+        );
+        rdma.check_local_access(1, read_only, 0x8800, 0x800, false)
+            .unwrap();
+        // Work requests can't use the memory regions of another protection domain.
+        assert_eq!(
+            rdma.check_local_access(1, foreign, 0x8000, 0x10, false),
+            Err(RdmaCmdError::MrPdMismatch(foreign, 1))
+        );
+        rdma.check_local_access(other_pd, foreign, 0x8000, 0x10, true)
+            .unwrap();
+        assert_eq!(
+            rdma.check_local_access(1, 42, 0x8000, 0x10, false),
+            Err(RdmaCmdError::UnknownMr(42))
+        );
+        // The range must be within the memory region.
+        for (addr, length) in [(0x7fff, 0x10), (0x8800, 0x801), (u64::MAX, 1)] {
+            assert_eq!(
+                rdma.check_local_access(1, writable, addr, length, false),
+                Err(RdmaCmdError::OutOfMrBounds(writable, addr, length))
+            );
+        }
+        assert_eq!(
+            rdma.check_local_access(1, read_only, 0x8000, 0x10, true),
+            Err(RdmaCmdError::MrNotWritable(read_only))
+        );
+        assert_eq!(
+            RdmaCmdError::MrPdMismatch(foreign, 1).status(),
+            RDMA_STATUS_INVALID_ACCESS
+        );
+    }
+
+    #[test]
+    fn test_create_destroy_ah() {
+        let mut rdma = activated_rdma("rdma-ah");
+        let mut dgid = [0u8; 16];
+        dgid[..2].copy_from_slice(&[0xfe, 0x80]);
+        let create = RdmaCmdCreateAh {
+            pd: 1,
+            port_num: 1,
+            dgid,
+            sgid_index: RDMA_GID_TABLE_LEN - 1,
+            traffic_class: 0xb8,
+            hop_limit: 64,
+            ..Default::default()
+        };
+        let destroy = |ah: u32| RdmaCmdDestroyAh {
+            ah,
+            ..Default::default()
+        };
+        let invalid_port = RdmaCmdCreateAh {
+            port_num: RDMA_NUM_PORTS + 1,
+            ..create
+        };
+        let invalid_sgid = RdmaCmdCreateAh {
+            sgid_index: RDMA_GID_TABLE_LEN,
+            ..create
+        };
+        let invalid_hop_limit = RdmaCmdCreateAh {
+            hop_limit: 256,
+            ..create
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_CREATE_AH,
+                    create.as_slice(),
+                    rsp_len::<RdmaRspCreateAh>(),
+                ),
+                // The response can't hold the address handle.
+                (RDMA_CMD_CREATE_AH, create.as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_CREATE_AH, invalid_port.as_slice(), 64),
+                (RDMA_CMD_CREATE_AH, invalid_sgid.as_slice(), 64),
+                (RDMA_CMD_CREATE_AH, invalid_hop_limit.as_slice(), 64),
+                (RDMA_CMD_DESTROY_AH, destroy(1).as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_DESTROY_AH, destroy(1).as_slice(), rsp_len::<()>()),
+                // The arguments are missing.
+                (RDMA_CMD_CREATE_AH, &[], 64),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspCreateAh::from_slice(&responses[0].1).unwrap().ah, 1);
+        for response in &responses[1..5] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+        assert_eq!(responses[5], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[6], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[7], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert!(rdma.ahs.is_empty());
+
+        // Work requests can only use the address handles of their protection domain.
+        let other_pd = rdma.alloc_pd().unwrap().pdn;
+        let ah = rdma.create_ah(create).unwrap().ah;
+        let handle = rdma.check_ah(1, ah).unwrap();
+        assert_eq!(handle.dgid, dgid);
+        assert_eq!(handle.traffic_class, 0xb8);
+        assert_eq!(handle.hop_limit, 64);
+        assert_eq!(
+            rdma.check_ah(other_pd, ah),
+            Err(RdmaCmdError::AhPdMismatch(ah, other_pd))
+        );
+        assert_eq!(rdma.check_ah(1, 42), Err(RdmaCmdError::UnknownAh(42)));
+        assert_eq!(
+            RdmaCmdError::AhPdMismatch(ah, other_pd).status(),
+            RDMA_STATUS_INVALID_ACCESS
+        );
+
+        // The device runs out of address handles.
+        for _ in 1..RDMA_MAX_AH {
+            rdma.create_ah(create).unwrap();
+        }
+        assert_eq!(rdma.create_ah(create), Err(RdmaCmdError::NoAhLeft));
+    }
+
+    /// Creates a queue pair, moved straight to the ready to send state with `attrs`.
+    fn ready_qp(rdma: &mut VirtioRdma, qp_type: u32, attrs: QpAttributes) -> u32 {
+        let qpn = rdma.create_qp(create_qp_cmd(qp_type)).unwrap().qpn;
+        let qp = rdma.qps.get_mut(qpn).unwrap();
+        qp.state = RDMA_QPS_RTS;
+        qp.attrs = attrs;
+        qpn
+    }
+
+    fn post_send_args(cmd: RdmaCmdPostSend, sges: &[RdmaSge]) -> Vec<u8> {
+        let mut args = cmd.as_slice().to_vec();
+        for sge in sges {
+            args.extend_from_slice(sge.as_slice());
+        }
+        args
+    }
+
+    fn poll_cq_args(cqn: u32, num_entries: u32) -> RdmaCmdPollCq {
+        RdmaCmdPollCq { cqn, num_entries }
+    }
+
+    /// Parses the work completions of the result of `RDMA_CMD_POLL_CQ`.
+    fn parse_wcs(payload: &[u8]) -> Vec<RdmaWc> {
+        let rsp = *RdmaRspPollCq::from_slice(&payload[..size_of::<RdmaRspPollCq>()]).unwrap();
+        let wcs = &payload[size_of::<RdmaRspPollCq>()..];
+        assert_eq!(
+            wcs.len(),
+            usize::try_from(rsp.num_entries).unwrap() * size_of::<RdmaWc>()
+        );
+        wcs.chunks(size_of::<RdmaWc>())
+            .map(|wc| *RdmaWc::from_slice(wc).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_post_send_rdma_write() {
+        let mut rdma = activated_rdma("rdma-post-send");
+        let src = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0x8000,
+                length: 0x1000,
+                access: 0,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let dst = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0xa000,
+                length: 0x1000,
+                access: RDMA_ACCESS_LOCAL_WRITE | RDMA_ACCESS_REMOTE_WRITE,
+                pd: 1,
+            })
+            .unwrap()
+            .rkey;
+        // Two queue pairs connected to each other through the loopback backend.
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let qpn = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+        ready_qp(&mut rdma, RDMA_QPT_RC, attrs(qpn));
+        rdma.qps.get_mut(qpn).unwrap().max_send_sge = 2;
+        let data: Vec<u8> = (0..=255).collect();
+        rdma.mem().write_slice(&data, GuestAddress(0x8000)).unwrap();
+
+        let write = |wr_id: u64, send_flags: u32, rkey: u32| RdmaCmdPostSend {
+            wr_id,
+            remote_addr: 0xa010,
+            qpn,
+            opcode: RDMA_WR_RDMA_WRITE,
+            send_flags,
+            num_sge: 2,
+            rkey,
+            ..Default::default()
+        };
+        let sges = [
+            RdmaSge {
+                addr: 0x8000,
+                length: 0x80,
+                lkey: src,
+            },
+            RdmaSge {
+                addr: 0x8080,
+                length: 0x80,
+                lkey: src,
+            },
+        ];
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(write(0x42, RDMA_SEND_SIGNALED, dst), &sges),
+                    rsp_len::<()>(),
+                ),
+                // Unsignaled work requests only complete on errors.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(write(0x43, 0, dst), &sges),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[2].0, RDMA_STATUS_OK);
+        assert_eq!(
+            parse_wcs(&responses[2].1),
+            [RdmaWc {
+                wr_id: 0x42,
+                status: RDMA_WC_SUCCESS,
+                opcode: RDMA_WC_RDMA_WRITE,
+                qp_num: qpn,
+                ..Default::default()
+            }]
+        );
+        let mut written = vec![0u8; data.len()];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa010))
+            .unwrap();
+        assert_eq!(written, data);
+        assert_eq!(rdma.metrics.send_wr_count.count(), 2);
+
+        // Writes through a memory region without remote access are dropped by the receiver,
+        // failing the work request.
+        rdma.mem()
+            .write_slice(&[0u8; 0x100], GuestAddress(0xa010))
+            .unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &post_send_args(write(0x44, 0, src), &sges),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa010))
+            .unwrap();
+        assert_eq!(written, [0u8; 0x100]);
+        assert_eq!(rdma.metrics.rx_drops.count(), 1);
+        assert_eq!(
+            rdma.cqs.get(1).unwrap().completions[0],
+            RdmaWc {
+                wr_id: 0x44,
+                status: RDMA_WC_REM_ACCESS_ERR,
+                opcode: RDMA_WC_RDMA_WRITE,
+                qp_num: qpn,
+                ..Default::default()
+            }
+        );
+        assert_eq!(rdma.qps.get(qpn).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(
+            rdma.check_remote_access(2, src, 0x8000, 0x10, RDMA_ACCESS_REMOTE_WRITE),
+            Err(RdmaCmdError::RemoteAccessDenied(
+                src,
+                RDMA_ACCESS_REMOTE_WRITE
+            ))
+        );
+    }
+
+    #[test]
+    fn test_post_send_errors() {
+        let mut rdma = activated_rdma("rdma-post-send-errors");
+        let lkey = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0x8000,
+                length: 0x1000,
+                access: 0,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        let ud = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let reset = rdma.create_qp(create_qp_cmd(RDMA_QPT_UD)).unwrap().qpn;
+        let send = |qpn: u32, opcode: u32, send_flags: u32, num_sge: u32| RdmaCmdPostSend {
+            qpn,
+            opcode,
+            send_flags,
+            num_sge,
+            ah,
+            ..Default::default()
+        };
+        let sge = |length: u32, lkey: u32| RdmaSge {
+            addr: 0x8000,
+            length,
+            lkey,
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(42, RDMA_WR_SEND, 0, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                // Datagrams can't be RDMA writes.
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(ud, RDMA_WR_RDMA_WRITE, 0, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(ud, RDMA_WR_SEND, 1 << 4, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send(ud, RDMA_WR_SEND, 0, 2), &[sge(1, lkey); 2]),
+                    rsp_len::<()>(),
+                ),
+                // Truncated scatter/gather list.
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(ud, RDMA_WR_SEND, 0, 1).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(reset, RDMA_WR_SEND, 0, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_INVALID_HANDLE,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_STATE,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert!(rdma.cqs.get(1).unwrap().completions.is_empty());
+
+        // Datagrams must fit in the MTU of the port, 1024 bytes.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send(ud, RDMA_WR_SEND, 0, 1), &[sge(1025, lkey)]),
+                    rsp_len::<()>(),
+                ),
+                // Work requests posted on a queue pair in error are flushed.
+                (
+                    RDMA_CMD_POST_SEND,
+                    send(ud, RDMA_WR_SEND, 0, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.qps.get(ud).unwrap().state, RDMA_QPS_SQE);
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(completions[0].status, RDMA_WC_LOC_LEN_ERR);
+        assert_eq!(completions[1].status, RDMA_WC_WR_FLUSH_ERR);
+
+        // Scatter/gather entries out of their memory region fail the work request.
+        let rc = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &post_send_args(send(rc, RDMA_WR_SEND, 0, 1), &[sge(0x1001, lkey)]),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.qps.get(rc).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(
+            rdma.cqs.get(1).unwrap().completions[2],
+            RdmaWc {
+                status: RDMA_WC_LOC_PROT_ERR,
+                opcode: RDMA_WC_SEND,
+                qp_num: rc,
+                ..Default::default()
+            }
+        );
+
+        // Datagrams need an address handle of the protection domain of their queue pair.
+        let ud = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        rdma.ahs.get_mut(ah).unwrap().pd = 2;
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                send(ud, RDMA_WR_SEND, 0, 0).as_slice(),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_INVALID_ACCESS, Vec::new()));
+    }
+
+    #[test]
+    fn test_post_send_atomic() {
+        let mut rdma = activated_rdma("rdma-post-send-atomic");
+        let local = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0x8000,
+                length: 0x1000,
+                access: RDMA_ACCESS_LOCAL_WRITE,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let remote = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0xa000,
+                length: 0x1000,
+                access: RDMA_ACCESS_LOCAL_WRITE | RDMA_ACCESS_REMOTE_ATOMIC,
+                pd: 1,
+            })
+            .unwrap()
+            .rkey;
+        // Creates two queue pairs connected to each other, returning the first one.
+        let connected_qps = |rdma: &mut VirtioRdma| {
+            let attrs = |dest_qp_num: u32| QpAttributes {
+                access_flags: RDMA_ACCESS_REMOTE_ATOMIC,
+                dest_qp_num,
+                ..Default::default()
+            };
+            let qpn = ready_qp(rdma, RDMA_QPT_RC, attrs(0));
+            let peer = ready_qp(rdma, RDMA_QPT_RC, attrs(qpn));
+            rdma.qps.get_mut(qpn).unwrap().attrs.dest_qp_num = peer;
+            (qpn, peer)
+        };
+        let (qpn, _) = connected_qps(&mut rdma);
+        let atomic_args = |wr_id: u64, qpn: u32, opcode: u32, remote_addr: u64, rkey: u32| {
+            let cmd = RdmaCmdPostSend {
+                wr_id,
+                remote_addr,
+                qpn,
+                opcode,
+                send_flags: RDMA_SEND_SIGNALED,
+                num_sge: 1,
+                rkey,
+                ..Default::default()
+            };
+            let operands = RdmaAtomic {
+                compare_add: 5,
+                swap: 7,
+            };
+            let mut args = cmd.as_slice().to_vec();
+            args.extend_from_slice(operands.as_slice());
+            args.extend_from_slice(
+                RdmaSge {
+                    addr: 0x8000 + 8 * wr_id,
+                    length: 8,
+                    lkey: local,
+                }
+                .as_slice(),
+            );
+            args
+        };
+        rdma.mem().write_obj(5u64, GuestAddress(0xa008)).unwrap();
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                // 5 equals the compared value and is swapped with 7.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(0, qpn, RDMA_WR_ATOMIC_CMP_AND_SWP, 0xa008, remote),
+                    rsp_len::<()>(),
+                ),
+                // 7 + 5.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(1, qpn, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0xa008, remote),
+                    rsp_len::<()>(),
+                ),
+                // 12 differs from the compared value and is left alone.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(2, qpn, RDMA_WR_ATOMIC_CMP_AND_SWP, 0xa008, remote),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        for response in &responses[..3] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        let wcs = parse_wcs(&responses[3].1);
+        assert_eq!(
+            wcs.iter()
+                .map(|wc| (wc.status, wc.opcode))
+                .collect::<Vec<_>>(),
+            [
+                (RDMA_WC_SUCCESS, RDMA_WC_COMP_SWAP),
+                (RDMA_WC_SUCCESS, RDMA_WC_FETCH_ADD),
+                (RDMA_WC_SUCCESS, RDMA_WC_COMP_SWAP),
+            ]
+        );
+        // The original values are written to the scatter/gather entries.
+        let read = |rdma: &VirtioRdma, addr: u64| rdma.mem().read_obj::<u64>(GuestAddress(addr));
+        assert_eq!(read(&rdma, 0x8000).unwrap(), 5);
+        assert_eq!(read(&rdma, 0x8008).unwrap(), 7);
+        assert_eq!(read(&rdma, 0x8010).unwrap(), 12);
+        assert_eq!(read(&rdma, 0xa008).unwrap(), 12);
+
+        // Atomics must target an 8-byte aligned address of a memory region allowing them, on a
+        // queue pair ready to receive.
+        let (misaligned, _) = connected_qps(&mut rdma);
+        let (denied, _) = connected_qps(&mut rdma);
+        let (unreachable, peer) = connected_qps(&mut rdma);
+        rdma.qps.get_mut(peer).unwrap().state = RDMA_QPS_INIT;
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(0, misaligned, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0xa004, remote),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(0, denied, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0x8008, local),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(0, unreachable, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0xa008, remote),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for response in responses {
+            assert_eq!(response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(
+            completions
+                .iter()
+                .map(|wc| (wc.qp_num, wc.status))
+                .collect::<Vec<_>>(),
+            [
+                (misaligned, RDMA_WC_REM_INV_REQ_ERR),
+                (denied, RDMA_WC_REM_ACCESS_ERR),
+                (unreachable, RDMA_WC_RETRY_EXC_ERR),
+            ]
+        );
+        for qpn in [misaligned, denied, unreachable] {
+            assert_eq!(rdma.qps.get(qpn).unwrap().state, RDMA_QPS_ERR);
+        }
+        assert_eq!(read(&rdma, 0xa008).unwrap(), 12);
+        assert_eq!(read(&rdma, 0x8008).unwrap(), 7);
+
+        // Atomics take a single 8 bytes scatter/gather entry, on reliable connected queue pairs
+        // of a device supporting them.
+        let mut short_sge = atomic_args(0, qpn, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0xa008, remote);
+        let len = short_sge.len();
+        short_sge[len - 8..len - 4].copy_from_slice(&4u32.to_le_bytes());
+        let uc = ready_qp(&mut rdma, RDMA_QPT_UC, QpAttributes::default());
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SEND, &short_sge, rsp_len::<()>()),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &atomic_args(0, uc, RDMA_WR_ATOMIC_FETCH_AND_ADD, 0xa008, remote),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for response in responses {
+            assert_eq!(response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+        rdma.caps.atomic_cap = RDMA_ATOMIC_NONE;
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &atomic_args(0, qpn, RDMA_WR_ATOMIC_CMP_AND_SWP, 0xa008, remote),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(read(&rdma, 0xa008).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_post_send_rdma_read() {
+        let mut rdma = activated_rdma("rdma-post-send-rdma-read");
+        let local = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0x8000,
+                length: 0x1000,
+                access: RDMA_ACCESS_LOCAL_WRITE,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let remote = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0xa000,
+                length: 0x1000,
+                access: RDMA_ACCESS_REMOTE_READ,
+                pd: 1,
+            })
+            .unwrap()
+            .rkey;
+        // Two queue pairs connected to each other through the loopback backend.
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_READ,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let qpn = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+        ready_qp(&mut rdma, RDMA_QPT_RC, attrs(qpn));
+        rdma.qps.get_mut(qpn).unwrap().max_send_sge = 2;
+        let data: Vec<u8> = (0..=255).collect();
+        rdma.mem().write_slice(&data, GuestAddress(0xa010)).unwrap();
+
+        let read = |wr_id: u64, qpn: u32, rkey: u32, lkey: u32| {
+            let cmd = RdmaCmdPostSend {
+                wr_id,
+                remote_addr: 0xa010,
+                qpn,
+                opcode: RDMA_WR_RDMA_READ,
+                send_flags: RDMA_SEND_SIGNALED,
+                num_sge: 2,
+                rkey,
+                ..Default::default()
+            };
+            let sges = [
+                RdmaSge {
+                    addr: 0x8000,
+                    length: 0x80,
+                    lkey,
+                },
+                RdmaSge {
+                    addr: 0x8800,
+                    length: 0x80,
+                    lkey,
+                },
+            ];
+            post_send_args(cmd, &sges)
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &read(0x42, qpn, remote, local),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(
+            parse_wcs(&responses[1].1),
+            [RdmaWc {
+                wr_id: 0x42,
+                status: RDMA_WC_SUCCESS,
+                opcode: RDMA_WC_RDMA_READ,
+                qp_num: qpn,
+                ..Default::default()
+            }]
+        );
+        // The remote data is scattered to both scatter/gather entries.
+        let mut read_data = vec![0u8; 0x80];
+        rdma.mem()
+            .read_slice(&mut read_data, GuestAddress(0x8000))
+            .unwrap();
+        assert_eq!(read_data, data[..0x80]);
+        rdma.mem()
+            .read_slice(&mut read_data, GuestAddress(0x8800))
+            .unwrap();
+        assert_eq!(read_data, data[0x80..]);
+
+        // Reads must target a memory region allowing them, into scatter/gather entries of a
+        // locally writable memory region.
+        let denied = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(qpn));
+        rdma.qps.get_mut(denied).unwrap().max_send_sge = 2;
+        rdma.qps.get_mut(qpn).unwrap().attrs.dest_qp_num = denied;
+        let read_only = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(qpn));
+        rdma.qps.get_mut(read_only).unwrap().max_send_sge = 2;
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &read(0, denied, local, local),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &read(0, read_only, remote, remote),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for response in responses {
+            assert_eq!(response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(
+            completions
+                .iter()
+                .map(|wc| (wc.qp_num, wc.status))
+                .collect::<Vec<_>>(),
+            [
+                (denied, RDMA_WC_REM_ACCESS_ERR),
+                (read_only, RDMA_WC_LOC_PROT_ERR),
+            ]
+        );
+
+        // Only local backends execute reads, on reliable connected queue pairs.
+        let uc = ready_qp(&mut rdma, RDMA_QPT_UC, QpAttributes::default());
+        let rc = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(qpn));
+        rdma.backend = Box::new(DeferredBackend {
+            event: EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+            queued: Vec::new(),
+            in_flight: Vec::new(),
+            received: VecDeque::new(),
+            failed_qps: Vec::new(),
+        });
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &read(0, uc, remote, local),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &read(0, rc, remote, local),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for response in responses {
+            assert_eq!(response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+    }
+
+    #[test]
+    fn test_poll_cq() {
+        let mut rdma = activated_rdma("rdma-poll-cq");
+        let cqn = rdma
+            .create_cq(RdmaCmdCreateCq {
+                cqe: 2,
+                ..Default::default()
+            })
+            .unwrap()
+            .cqn;
+        for wr_id in 0..3 {
+            rdma.complete(
+                cqn,
+                RdmaWc {
+                    wr_id,
+                    ..Default::default()
+                },
+                false,
+            );
+        }
+        // The last completion overflowed the queue.
+        assert_eq!(rdma.cqs.get(cqn).unwrap().completions.len(), 2);
+        assert_eq!(rdma.metrics.cq_overflows.count(), 1);
+
+        let wc_len = u32::try_from(size_of::<RdmaWc>()).unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                // The response buffer only has room for one completion.
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(cqn, 2).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + wc_len + 1,
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(cqn, 2).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 2 * wc_len,
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(cqn, 2).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 2 * wc_len,
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(42, 2).as_slice(),
+                    rsp_len::<RdmaRspPollCq>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(cqn, 2).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        let wr_ids =
+            |payload: &[u8]| -> Vec<u64> { parse_wcs(payload).iter().map(|wc| wc.wr_id).collect() };
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        assert_eq!(wr_ids(&responses[0].1), [0]);
+        assert_eq!(responses[1].0, RDMA_STATUS_OK);
+        assert_eq!(wr_ids(&responses[1].1), [1]);
+        assert_eq!(responses[2].0, RDMA_STATUS_OK);
+        assert!(wr_ids(&responses[2].1).is_empty());
+        assert_eq!(responses[3], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[4], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+    }
+
+    #[test]
+    fn test_resize_cq() {
+        let mut rdma = activated_rdma("rdma-resize-cq");
+        let cqn = rdma
+            .create_cq(RdmaCmdCreateCq {
+                cqe: 4,
+                ..Default::default()
+            })
+            .unwrap()
+            .cqn;
+        for wr_id in 0..3 {
+            rdma.complete(
+                cqn,
+                RdmaWc {
+                    wr_id,
+                    ..Default::default()
+                },
+                false,
+            );
+        }
+        let resize = |cqn, cqe| RdmaCmdResizeCq { cqn, cqe };
+
+        let wc_len = u32::try_from(size_of::<RdmaWc>()).unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                // The queue holds more work completions than the new size.
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(cqn, 2).as_slice(),
+                    rsp_len::<RdmaRspResizeCq>(),
+                ),
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(cqn, 0).as_slice(),
+                    rsp_len::<RdmaRspResizeCq>(),
+                ),
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(cqn, RDMA_MAX_CQE + 1).as_slice(),
+                    rsp_len::<RdmaRspResizeCq>(),
+                ),
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(42, 8).as_slice(),
+                    rsp_len::<RdmaRspResizeCq>(),
+                ),
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(cqn, 8).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_RESIZE_CQ,
+                    resize(cqn, 3).as_slice(),
+                    rsp_len::<RdmaRspResizeCq>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(cqn, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * wc_len,
+                ),
+            ],
+        );
+        for response in &responses[..3] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+        assert_eq!(responses[3], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[4], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[5].0, RDMA_STATUS_OK);
+        assert_eq!(RdmaRspResizeCq::from_slice(&responses[5].1).unwrap().cqe, 3);
+        // The work completions are kept across the resize.
+        assert_eq!(responses[6].0, RDMA_STATUS_OK);
+        let wr_ids: Vec<u64> = parse_wcs(&responses[6].1)
+            .iter()
+            .map(|wc| wc.wr_id)
+            .collect();
+        assert_eq!(wr_ids, [0, 1, 2]);
+
+        // The resized queue overflows past its new size.
+        for wr_id in 0..4 {
+            rdma.complete(
+                cqn,
+                RdmaWc {
+                    wr_id,
+                    ..Default::default()
+                },
+                false,
+            );
+        }
+        assert_eq!(rdma.cqs.get(cqn).unwrap().completions.len(), 3);
+        assert_eq!(rdma.metrics.cq_overflows.count(), 1);
+    }
+
+    fn post_recv_args(wr_id: u64, qpn: u32, sges: &[RdmaSge]) -> Vec<u8> {
+        let cmd = RdmaCmdPostRecv {
+            wr_id,
+            qpn,
+            num_sge: u32::try_from(sges.len()).unwrap(),
+        };
+        let mut args = cmd.as_slice().to_vec();
+        for sge in sges {
+            args.extend_from_slice(sge.as_slice());
+        }
+        args
+    }
+
+    /// Registers the memory regions of the receive tests: a source one at 0x8000, holding
+    /// `0..=255`, and a locally and remotely writable one at 0xa000. Returns their keys.
+    fn recv_mrs(rdma: &mut VirtioRdma) -> (u32, u32) {
+        let data: Vec<u8> = (0..=255).collect();
+        rdma.mem().write_slice(&data, GuestAddress(0x8000)).unwrap();
+        let mut reg_mr = |iova: u64, access: u32| {
+            rdma.reg_mr(RdmaCmdRegMr {
+                iova,
+                length: 0x1000,
+                access,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey
+        };
+        (
+            reg_mr(0x8000, 0),
+            reg_mr(0xa000, RDMA_ACCESS_LOCAL_WRITE | RDMA_ACCESS_REMOTE_WRITE),
+        )
+    }
+
+    #[test]
+    fn test_post_recv_ud() {
+        let mut rdma = activated_rdma("rdma-post-recv-ud");
+        let mac = MacAddr::from([0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        rdma.gids = GidTable::new(Some(mac), None);
+        let (src, dst) = recv_mrs(&mut rdma);
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                dgid: [0xfe; 16],
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_UD,
+            QpAttributes {
+                qkey: 0x1111,
+                ..Default::default()
+            },
+        );
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let send = RdmaCmdPostSend {
+            qpn: sender,
+            opcode: RDMA_WR_SEND,
+            num_sge: 1,
+            ah,
+            remote_qpn: receiver,
+            remote_qkey: 0x1111,
+            ..Default::default()
+        };
+        let sge = |addr: u64, length: u32, lkey: u32| RdmaSge { addr, length, lkey };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(7, receiver, &[sge(0xa000, 0x100, dst)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send, &[sge(0x8000, 16, src)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[2].0, RDMA_STATUS_OK);
+        // The receive buffer holds the global route header, followed by the payload.
+        assert_eq!(
+            parse_wcs(&responses[2].1),
+            [RdmaWc {
+                wr_id: 7,
+                status: RDMA_WC_SUCCESS,
+                opcode: RDMA_WC_RECV,
+                byte_len: 56,
+                qp_num: receiver,
+                src_qp: sender,
+                wc_flags: RDMA_WC_GRH,
+                ..Default::default()
+            }]
+        );
+        let mut received = [0u8; 56];
+        rdma.mem()
+            .read_slice(&mut received, GuestAddress(0xa000))
+            .unwrap();
+        assert_eq!(received[..7], [0x60, 0, 0, 0, 0, 16, GRH_NEXT_HEADER]);
+        // The source GID is the one of the address handle.
+        assert_eq!(
+            received[8..24],
+            *rdma.gid_table().get(GID_INDEX_LINK_LOCAL).unwrap()
+        );
+        assert_eq!(received[24..40], [0xfe; 16]);
+        assert_eq!(received[40..], (0..16).collect::<Vec<u8>>());
+        assert!(rdma.qps.get(receiver).unwrap().recv_queue.is_empty());
+        assert_eq!(rdma.metrics.recv_wr_count.count(), 1);
+    }
+
+    /// Backend recording the guest memory the payload of the messages it transmits is read from.
+    #[derive(Debug)]
+    struct IovBackend {
+        iovecs: Arc<Mutex<Vec<(usize, usize)>>>,
+    }
+
+    impl RdmaBackend for IovBackend {
+        fn transmit(&mut self, _msg: RdmaMessage) -> u32 {
+            RDMA_WC_SUCCESS
+        }
+
+        fn transmit_iov(&mut self, _msg: RdmaMessage, payload: IoVecBuffer) -> u32 {
+            let iovecs = payload.as_iovec_slice().iter();
+            self.iovecs
+                .lock()
+                .unwrap()
+                .extend(iovecs.map(|iov| (iov.iov_base.addr(), iov.iov_len)));
+            RDMA_WC_SUCCESS
+        }
+
+        fn receive(&mut self) -> Option<RdmaMessage> {
+            None
+        }
+
+        fn is_local(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_post_send_iov() {
+        let mut rdma = activated_rdma("rdma-post-send-iov");
+        let iovecs = Arc::new(Mutex::new(Vec::new()));
+        rdma.backend = Box::new(IovBackend {
+            iovecs: iovecs.clone(),
+        });
+        let mac = MacAddr::from([0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        rdma.gids = GidTable::new(Some(mac), None);
+        let (src, _) = recv_mrs(&mut rdma);
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                dgid: [0xfe; 16],
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        rdma.qps.get_mut(sender).unwrap().max_send_sge = 2;
+        let sge = |addr: u64, length: u32| RdmaSge {
+            addr,
+            length,
+            lkey: src,
+        };
+        let send = RdmaCmdPostSend {
+            wr_id: 1,
+            qpn: sender,
+            opcode: RDMA_WR_SEND,
+            send_flags: RDMA_SEND_SIGNALED,
+            num_sge: 2,
+            ah,
+            remote_qpn: 3,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &post_send_args(send, &[sge(0x8000, 16), sge(0x8100, 8)]),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        // The backend reads the payload straight from the guest memory of the scatter/gather
+        // entries.
+        let host_addr = |addr| {
+            rdma.mem()
+                .get_host_address(GuestAddress(addr))
+                .unwrap()
+                .addr()
+        };
+        assert_eq!(
+            *iovecs.lock().unwrap(),
+            [(host_addr(0x8000), 16), (host_addr(0x8100), 8)]
+        );
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].status, RDMA_WC_SUCCESS);
+    }
+
+    /// Backend holding the messages it transmits until its event is processed, as the backends
+    /// performing host I/O do until it completes.
+    #[derive(Debug)]
+    struct DeferredBackend {
+        event: EventFd,
+        queued: Vec<RdmaMessage>,
+        in_flight: Vec<RdmaMessage>,
+        received: VecDeque<RdmaMessage>,
+        failed_qps: Vec<u32>,
+    }
+
+    impl RdmaBackend for DeferredBackend {
+        fn transmit(&mut self, msg: RdmaMessage) -> u32 {
+            self.queued.push(msg);
+            RDMA_WC_SUCCESS
+        }
+
+        fn submit(&mut self) {
+            if !self.queued.is_empty() {
+                self.in_flight.append(&mut self.queued);
+                self.event.write(1).unwrap();
+            }
+        }
+
+        fn events(&self) -> Vec<RawFd> {
+            vec![self.event.as_raw_fd()]
+        }
+
+        fn process_event(&mut self) {
+            self.event.read().unwrap();
+            self.received.extend(self.in_flight.drain(..));
+        }
+
+        fn receive(&mut self) -> Option<RdmaMessage> {
+            self.received.pop_front()
+        }
+
+        fn take_failed_qps(&mut self) -> Vec<u32> {
+            std::mem::take(&mut self.failed_qps)
+        }
+
+        fn is_local(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_backend_event() {
+        let mut rdma = activated_rdma("rdma-backend-event");
+        let event = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        rdma.backend = Box::new(DeferredBackend {
+            event: event.try_clone().unwrap(),
+            queued: Vec::new(),
+            in_flight: Vec::new(),
+            received: VecDeque::new(),
+            // The backend gives up delivering the messages of the sender, and of a queue pair
+            // destroyed since.
+            failed_qps: vec![2, 99],
+        });
+        assert_eq!(rdma.backend.events().len(), 1);
+        let mac = MacAddr::from([0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        rdma.gids = GidTable::new(Some(mac), None);
+        let (src, dst) = recv_mrs(&mut rdma);
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                dgid: [0xfe; 16],
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_UD,
+            QpAttributes {
+                qkey: 0x1111,
+                ..Default::default()
+            },
+        );
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let sge = |addr: u64, length: u32, lkey: u32| RdmaSge { addr, length, lkey };
+        let send = |wr_id: u64| RdmaCmdPostSend {
+            wr_id,
+            qpn: sender,
+            opcode: RDMA_WR_SEND,
+            num_sge: 1,
+            ah,
+            remote_qpn: receiver,
+            remote_qkey: 0x1111,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(7, receiver, &[sge(0xa000, 0x100, dst)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(8, receiver, &[sge(0xa100, 0x100, dst)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send(1), &[sge(0x8000, 16, src)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send(2), &[sge(0x8000, 16, src)]),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert!(responses.iter().all(|rsp| rsp.0 == RDMA_STATUS_OK));
+        // The messages of the batch of commands were submitted together, and are only delivered
+        // once the backend reports their completion.
+        assert_eq!(event.read().unwrap(), 1);
+        assert_eq!(rdma.qps.get(receiver).unwrap().recv_queue.len(), 2);
+        event.write(1).unwrap();
+        rdma.process_backend().unwrap();
+        assert!(rdma.qps.get(receiver).unwrap().recv_queue.is_empty());
+        assert_eq!(rdma.metrics.rx_drops.count(), 0);
+        let wr_ids: Vec<u64> = rdma
+            .cqs
+            .get(1)
+            .unwrap()
+            .completions
+            .iter()
+            .map(|wc| wc.wr_id)
+            .collect();
+        assert_eq!(wr_ids, [7, 8]);
+        assert_eq!(sender, 2);
+        assert_eq!(rdma.qps.get(sender).unwrap().state, RDMA_QPS_ERR);
+        assert_ne!(rdma.qps.get(receiver).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(
+            rdma.pending_events,
+            [RdmaAsyncEvent {
+                event_type: RDMA_EVENT_QP_FATAL,
+                handle: sender,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_post_recv_rc() {
+        let mut rdma = activated_rdma("rdma-post-recv-rc");
+        let (src, dst) = recv_mrs(&mut rdma);
+        // Two queue pairs connected to each other through the loopback backend.
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let sender = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+        let receiver = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(sender));
+        let send = |opcode: u32, imm_data: u32| RdmaCmdPostSend {
+            remote_addr: 0xa800,
+            qpn: sender,
+            opcode,
+            num_sge: 1,
+            imm_data,
+            rkey: dst,
+            ..Default::default()
+        };
+        let sge = |addr: u64, length: u32, lkey: u32| RdmaSge { addr, length, lkey };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(1, receiver, &[sge(0xa000, 0x100, dst)]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(2, receiver, &[]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send(RDMA_WR_SEND_WITH_IMM, 0x1234), &[sge(0x8000, 16, src)]),
+                    rsp_len::<()>(),
+                ),
+                // RDMA writes with immediate data consume a receive work request, without
+                // writing to its buffers.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(
+                        send(RDMA_WR_RDMA_WRITE_WITH_IMM, 0x5678),
+                        &[sge(0x8010, 32, src)],
+                    ),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        for response in &responses[..4] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        assert_eq!(responses[4].0, RDMA_STATUS_OK);
+        let recv = |wr_id: u64, opcode: u32, byte_len: u32, imm_data: u32| RdmaWc {
+            wr_id,
+            status: RDMA_WC_SUCCESS,
+            opcode,
+            byte_len,
+            imm_data,
+            qp_num: receiver,
+            src_qp: sender,
+            wc_flags: RDMA_WC_WITH_IMM,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_wcs(&responses[4].1),
+            [
+                recv(1, RDMA_WC_RECV, 16, 0x1234),
+                recv(2, RDMA_WC_RECV_RDMA_WITH_IMM, 32, 0x5678),
+            ]
+        );
+        let mut received = [0u8; 16];
+        rdma.mem()
+            .read_slice(&mut received, GuestAddress(0xa000))
+            .unwrap();
+        assert_eq!(received.to_vec(), (0..16).collect::<Vec<u8>>());
+        let mut written = [0u8; 32];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa800))
+            .unwrap();
+        assert_eq!(written.to_vec(), (16..48).collect::<Vec<u8>>());
+        assert_eq!(rdma.metrics.recv_wr_count.count(), 2);
+    }
+
+    #[test]
+    fn test_post_send_rc_remote_errors() {
+        let mut rdma = activated_rdma("rdma-rc-remote-errors");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let sender = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_RC,
+            QpAttributes {
+                dest_qp_num: sender,
+                ..Default::default()
+            },
+        );
+        rdma.qps.get_mut(sender).unwrap().attrs.dest_qp_num = receiver;
+        let sge = RdmaSge {
+            addr: 0x8000,
+            length: 16,
+            lkey: src,
+        };
+        // Posts a send, returning the status of its work completion before restoring the
+        // queue pairs.
+        let send = |rdma: &mut VirtioRdma, wr_id: u64| {
+            let cmd = RdmaCmdPostSend {
+                wr_id,
+                qpn: sender,
+                opcode: RDMA_WR_SEND,
+                send_flags: RDMA_SEND_SIGNALED,
+                num_sge: 1,
+                ..Default::default()
+            };
+            let responses = run_commands(
+                rdma,
+                &[(
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(cmd, &[sge]),
+                    rsp_len::<()>(),
+                )],
+            );
+            assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+            let wc = rdma.cqs.get_mut(1).unwrap().completions.pop_back().unwrap();
+            assert_eq!((wc.wr_id, wc.qp_num), (wr_id, sender));
+            let state = rdma.qps.get(sender).unwrap().state;
+            for qpn in [sender, receiver] {
+                rdma.qps.get_mut(qpn).unwrap().state = RDMA_QPS_RTS;
+            }
+            (wc.status, state)
+        };
+        let post_recv = |rdma: &mut VirtioRdma, length: u32| {
+            rdma.qps
+                .get_mut(receiver)
+                .unwrap()
+                .recv_queue
+                .push_back(RecvWr {
+                    wr_id: 0,
+                    sges: vec![RdmaSge {
+                        addr: 0xa000,
+                        length,
+                        lkey: dst,
+                    }],
+                });
+        };
+
+        // The sender waits for the receiver to deliver the message.
+        post_recv(&mut rdma, 16);
+        assert_eq!(send(&mut rdma, 1), (RDMA_WC_SUCCESS, RDMA_QPS_RTS));
+        assert_eq!(rdma.cqs.get(1).unwrap().completions.len(), 1);
+        // The receiver has no receive work request.
+        assert_eq!(
+            send(&mut rdma, 2),
+            (RDMA_WC_RNR_RETRY_EXC_ERR, RDMA_QPS_ERR)
+        );
+        // The message doesn't fit in the receive work request, which the receiver fails.
+        post_recv(&mut rdma, 8);
+        assert_eq!(send(&mut rdma, 3), (RDMA_WC_REM_INV_REQ_ERR, RDMA_QPS_ERR));
+        assert_eq!(
+            rdma.cqs.get(1).unwrap().completions.back().unwrap().status,
+            RDMA_WC_LOC_LEN_ERR
+        );
+        // The receive work request is not in the protection domain of the receiver.
+        rdma.qps
+            .get_mut(receiver)
+            .unwrap()
+            .recv_queue
+            .push_back(RecvWr {
+                wr_id: 0,
+                sges: vec![RdmaSge { lkey: 42, ..sge }],
+            });
+        assert_eq!(send(&mut rdma, 4), (RDMA_WC_REM_OP_ERR, RDMA_QPS_ERR));
+        // The receiver is not ready to receive.
+        rdma.qps.get_mut(receiver).unwrap().state = RDMA_QPS_INIT;
+        post_recv(&mut rdma, 16);
+        assert_eq!(send(&mut rdma, 5), (RDMA_WC_RETRY_EXC_ERR, RDMA_QPS_ERR));
+        assert_eq!(rdma.metrics.rx_drops.count(), 2);
+    }
+
+    #[test]
+    fn test_set_backend() {
+        let mut rdma = VirtioRdma::new("rdma-set-backend".to_string()).unwrap();
+        assert_eq!(rdma.backend_config(), None);
+        assert_eq!(rdma.caps.atomic_cap, RDMA_ATOMIC_HCA);
+
+        // Atomics are only supported by local backends.
+        rdma.set_backend(Some(RdmaBackendConfig::Null)).unwrap();
+        assert_eq!(rdma.backend_config(), Some(&RdmaBackendConfig::Null));
+        assert!(!rdma.backend.is_local());
+        assert_eq!(rdma.caps.atomic_cap, RDMA_ATOMIC_NONE);
+        rdma.set_backend(None).unwrap();
+        assert!(rdma.backend.is_local());
+        assert_eq!(rdma.caps.atomic_cap, RDMA_ATOMIC_HCA);
+
+        // A backend which can't be set up leaves the device alone.
+        let config = RdmaBackendConfig::Shmem(ShmemBackendConfig {
+            path: "/nonexistent/rdma.shm".into(),
+            peer_gid: "10.0.0.2".parse().unwrap(),
+        });
+        assert!(matches!(
+            rdma.set_backend(Some(config)),
+            Err(RdmaBackendError::Shmem(_))
+        ));
+        assert_eq!(rdma.backend_config(), None);
+        assert!(rdma.backend.is_local());
+    }
+
+    #[test]
+    fn test_replace_backend() {
+        let dir = TempDir::new().unwrap();
+        let unix = |name: &str| {
+            RdmaBackendConfig::Unix(UnixBackendConfig {
+                socket_path: dir.as_path().join(name),
+                peers: Vec::new(),
+            })
+        };
+        let mut rdma = VirtioRdma::new("rdma-replace-backend".to_string()).unwrap();
+        let policy = RdmaFaultPolicy {
+            delay_usecs: 1000,
+            ..Default::default()
+        };
+        rdma.set_fault_policy(Some(policy)).unwrap();
+        rdma.set_backend(Some(unix("a.sock"))).unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+
+        // The atomic capabilities of the activated device can't change.
+        assert!(matches!(
+            rdma.set_backend(None),
+            Err(RdmaBackendError::LocalityChange)
+        ));
+        assert_eq!(rdma.backend_config(), Some(&unix("a.sock")));
+
+        // The faults are injected into the new backend, whose events replace those of the
+        // previous one under the nested epoll FD.
+        rdma.set_backend(Some(unix("b.sock"))).unwrap();
+        assert_eq!(rdma.backend_config(), Some(&unix("b.sock")));
+        assert_eq!(rdma.caps.atomic_cap, RDMA_ATOMIC_NONE);
+        let events = rdma.backend.events();
+        assert_eq!(events.len(), 2);
+        let mut ready = [EpollEvent::default(); 4];
+        assert_eq!(rdma.backend_epoll.wait(0, &mut ready).unwrap(), 0);
+        UnixDatagram::unbound()
+            .unwrap()
+            .send_to(b"malformed", dir.as_path().join("b.sock"))
+            .unwrap();
+        assert_eq!(rdma.backend_epoll.wait(0, &mut ready).unwrap(), 1);
+        assert_eq!(ready[0].data(), u64::try_from(events[0]).unwrap());
+        rdma.process_backend().unwrap();
+        assert_eq!(rdma.backend_epoll.wait(0, &mut ready).unwrap(), 0);
+        assert_eq!(rdma.metrics.event_fails.count(), 0);
+    }
+
+    #[test]
+    fn test_backend_launcher() {
+        let dir = TempDir::new().unwrap();
+        let unix = |name: &str| {
+            Some(RdmaBackendConfig::Unix(UnixBackendConfig {
+                socket_path: dir.as_path().join(name),
+                peers: Vec::new(),
+            }))
+        };
+        let launcher = Arc::new(BackendLauncher::new(Arc::default(), Arc::default()).unwrap());
+
+        // The loopback backend runs on the thread of the device, which has no filter to install.
+        let mut rdma = VirtioRdma::new("rdma-backend-launcher".to_string()).unwrap();
+        rdma.set_backend_launcher(launcher.clone()).unwrap();
+        assert!(Arc::ptr_eq(rdma.launcher.as_ref().unwrap(), &launcher));
+
+        // The backends set up before install the filter of the launcher on their thread, and the
+        // ones set up later run on the threads of the launcher.
+        let mut rdma = VirtioRdma::new("rdma-backend-launcher".to_string()).unwrap();
+        rdma.set_backend(unix("a.sock")).unwrap();
+        rdma.set_backend_launcher(launcher.clone()).unwrap();
+        rdma.set_backend_launcher(launcher.clone()).unwrap();
+        rdma.set_backend(unix("b.sock")).unwrap();
+        assert!(!rdma.backend.is_local());
+        assert!(Arc::ptr_eq(rdma.launcher.as_ref().unwrap(), &launcher));
+    }
+
+    #[test]
+    fn test_replace_backend_after_boot() {
+        // The filter installed by the test stays on its thread.
+        std::thread::spawn(|| {
+            let dir = TempDir::new().unwrap();
+            let unix = |name: &str| {
+                RdmaBackendConfig::Unix(UnixBackendConfig {
+                    socket_path: dir.as_path().join(name),
+                    peers: Vec::new(),
+                })
+            };
+            let mut rdma = VirtioRdma::new("rdma-replace-backend-after-boot".to_string()).unwrap();
+            rdma.set_fault_policy(Some(RdmaFaultPolicy::default()))
+                .unwrap();
+            rdma.set_backend(Some(unix("a.sock"))).unwrap();
+            let launcher = BackendLauncher::new(Arc::default(), Arc::default()).unwrap();
+            rdma.set_backend_launcher(Arc::new(launcher)).unwrap();
+            rdma.activate(default_mem(), default_interrupt()).unwrap();
+
+            // The backend is replaced once the VMM thread can't create sockets anymore.
+            apply_filter(&socket_denying_filter()).unwrap();
+            rdma.set_backend(Some(unix("b.sock"))).unwrap();
+            assert_eq!(rdma.backend_config(), Some(&unix("b.sock")));
+            assert!(dir.as_path().join("b.sock").exists());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_backend_health() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.as_path().join("rdma.sock");
+        let config = RdmaBackendConfig::Unix(UnixBackendConfig {
+            socket_path: path.clone(),
+            peers: Vec::new(),
+        });
+        let status = |backend_state, reconnect_attempts| RdmaDeviceStatus {
+            id: "rdma-backend-health".to_string(),
+            link_up: backend_state == RdmaBackendState::Up,
+            backend_state,
+            reconnect_attempts,
+        };
+        let mut rdma = VirtioRdma::new("rdma-backend-health".to_string()).unwrap();
+        rdma.set_backend(Some(config.clone())).unwrap();
+
+        // Only the backends of the activated devices are probed.
+        assert!(!rdma.backend_timer.is_armed());
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        assert!(rdma.backend_timer.is_armed());
+        rdma.process_backend_timer();
+        assert_eq!(rdma.status(), status(RdmaBackendState::Up, 0));
+        assert!(rdma.backend_timer.is_armed());
+
+        // The backend whose socket is removed fails its probe, which brings the link down.
+        std::fs::remove_file(&path).unwrap();
+        rdma.process_backend_timer();
+        assert_eq!(rdma.status(), status(RdmaBackendState::Down, 0));
+        assert_eq!(rdma.backend_config(), Some(&config));
+        assert_eq!(
+            rdma.pending_events.back(),
+            Some(&RdmaAsyncEvent {
+                event_type: RDMA_EVENT_PORT_ERR,
+                handle: 1,
+            })
+        );
+        assert_eq!(rdma.metrics.backend_probe_fails.count(), 1);
+
+        // The attempts to set it up again back off while they fail.
+        std::fs::write(&path, b"").unwrap();
+        rdma.process_backend_timer();
+        assert_eq!(rdma.status(), status(RdmaBackendState::Down, 1));
+        assert_eq!(rdma.metrics.backend_reconnect_fails.count(), 1);
+        assert_eq!(backend_reconnect_delay(0), BACKEND_RECONNECT_DELAY);
+        assert_eq!(backend_reconnect_delay(1), 2 * BACKEND_RECONNECT_DELAY);
+        assert_eq!(
+            backend_reconnect_delay(u32::MAX),
+            MAX_BACKEND_RECONNECT_DELAY
+        );
+
+        // The attempt which succeeds brings the link up.
+        std::fs::remove_file(&path).unwrap();
+        rdma.process_backend_timer();
+        assert_eq!(rdma.status(), status(RdmaBackendState::Up, 0));
+        assert_eq!(
+            rdma.pending_events.back(),
+            Some(&RdmaAsyncEvent {
+                event_type: RDMA_EVENT_PORT_ACTIVE,
+                handle: 1,
+            })
+        );
+        assert_eq!(rdma.metrics.backend_reconnects.count(), 1);
+        assert!(path.exists());
+
+        // The backends performing no host I/O aren't probed.
+        rdma.reset();
+        assert!(!rdma.backend_timer.is_armed());
+        rdma.set_backend(Some(RdmaBackendConfig::Null)).unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        assert!(!rdma.backend_timer.is_armed());
+    }
+
+    #[test]
+    fn test_fault_policy() {
+        let mut rdma = activated_rdma("rdma-fault-policy");
+        assert_eq!(rdma.fault_policy(), None);
+        let policy = RdmaFaultPolicy {
+            drop_every: 2,
+            cq_overrun_every: 4,
+            ..Default::default()
+        };
+        rdma.set_fault_policy(Some(policy)).unwrap();
+        assert_eq!(rdma.fault_policy(), Some(policy));
+        // The faults don't change what the backend supports.
+        assert!(rdma.backend.is_local());
+        assert_eq!(rdma.backend.events().len(), 1);
+
+        let (src, dst) = recv_mrs(&mut rdma);
+        let sender = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_RC,
+            QpAttributes {
+                dest_qp_num: sender,
+                ..Default::default()
+            },
+        );
+        rdma.qps.get_mut(sender).unwrap().attrs.dest_qp_num = receiver;
+        for wr_id in 0..2 {
+            rdma.qps
+                .get_mut(receiver)
+                .unwrap()
+                .recv_queue
+                .push_back(RecvWr {
+                    wr_id,
+                    sges: vec![RdmaSge {
+                        addr: 0xa000,
+                        length: 16,
+                        lkey: dst,
+                    }],
+                });
+        }
+        let send = |wr_id: u64| {
+            let cmd = RdmaCmdPostSend {
+                wr_id,
+                qpn: sender,
+                opcode: RDMA_WR_SEND,
+                send_flags: RDMA_SEND_SIGNALED,
+                num_sge: 1,
+                ..Default::default()
+            };
+            let sge = RdmaSge {
+                addr: 0x8000,
+                length: 16,
+                lkey: src,
+            };
+            post_send_args(cmd, &[sge])
+        };
+
+        // The second message is dropped before reaching the receiver.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SEND, &send(1), rsp_len::<()>()),
+                (RDMA_CMD_POST_SEND, &send(2), rsp_len::<()>()),
+            ],
+        );
+        for response in responses {
+            assert_eq!(response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(
+            completions
+                .iter()
+                .map(|wc| (wc.qp_num, wc.status))
+                .collect::<Vec<_>>(),
+            [
+                (receiver, RDMA_WC_SUCCESS),
+                (sender, RDMA_WC_SUCCESS),
+                (sender, RDMA_WC_RETRY_EXC_ERR),
+            ]
+        );
+        assert_eq!(rdma.qps.get(sender).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(rdma.qps.get(receiver).unwrap().recv_queue.len(), 1);
+
+        // The fourth work completion overruns its completion queue.
+        rdma.qps.get_mut(sender).unwrap().state = RDMA_QPS_RTS;
+        let responses = run_commands(
+            &mut rdma,
+            &[(RDMA_CMD_POST_SEND, &send(3), rsp_len::<()>())],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.cqs.get(1).unwrap().completions.len(), 4);
+        assert_eq!(
+            rdma.pending_events,
+            [
+                RdmaAsyncEvent {
+                    event_type: RDMA_EVENT_CQ_ERR,
+                    handle: 1,
+                },
+                RdmaAsyncEvent {
+                    event_type: RDMA_EVENT_QP_FATAL,
+                    handle: receiver,
+                },
+            ]
+        );
+        assert_eq!(rdma.qps.get(receiver).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(rdma.metrics.cq_overflows.count(), 1);
+        assert_eq!(rdma.metrics.injected_faults.count(), 2);
+    }
+
+    #[test]
+    fn test_rdma_write_with_imm() {
+        let mut rdma = activated_rdma("rdma-write-with-imm");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UC, attrs(2));
+        let receiver = ready_qp(&mut rdma, RDMA_QPT_UC, attrs(sender));
+        let write = |num_sge: u32, imm_data: u32, rkey: u32| RdmaCmdPostSend {
+            remote_addr: 0xa800,
+            qpn: sender,
+            opcode: RDMA_WR_RDMA_WRITE_WITH_IMM,
+            num_sge,
+            imm_data,
+            rkey,
+            ..Default::default()
+        };
+        let sge = RdmaSge {
+            addr: 0x8000,
+            length: 16,
+            lkey: src,
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                // Without a receive work request, the write is dropped before writing its data.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(write(1, 0x1111, dst), &[sge]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(1, receiver, &[]),
+                    rsp_len::<()>(),
+                ),
+                // Zero length writes only carry their immediate data, whatever their remote key.
+                (
+                    RDMA_CMD_POST_SEND,
+                    write(0, 0x2222, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        for response in &responses[..3] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        assert_eq!(rdma.metrics.rx_drops.count(), 1);
+        let mut written = [0u8; 16];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa800))
+            .unwrap();
+        assert_eq!(written, [0u8; 16]);
+        assert_eq!(
+            parse_wcs(&responses[3].1),
+            [RdmaWc {
+                wr_id: 1,
+                status: RDMA_WC_SUCCESS,
+                opcode: RDMA_WC_RECV_RDMA_WITH_IMM,
+                imm_data: 0x2222,
+                qp_num: receiver,
+                src_qp: sender,
+                wc_flags: RDMA_WC_WITH_IMM,
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_deliver_invalid_msg() {
+        let mut rdma = activated_rdma("rdma-deliver-invalid-msg");
+        let (_, dst) = recv_mrs(&mut rdma);
+        let uc = ready_qp(
+            &mut rdma,
+            RDMA_QPT_UC,
+            QpAttributes {
+                access_flags: RDMA_ACCESS_REMOTE_WRITE,
+                dest_qp_num: 7,
+                ..Default::default()
+            },
+        );
+        let ud = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let msg = |opcode: u32, imm_data: Option<u32>| RdmaMessage {
+            opcode,
+            qp_type: RDMA_QPT_UC,
+            src_qpn: 7,
+            dest_qpn: uc,
+            remote_addr: 0xa000,
+            rkey: dst,
+            imm_data,
+            payload: vec![0xab; 16],
+            ..Default::default()
+        };
+        let datagram = |opcode: u32, len: usize| RdmaMessage {
+            qp_type: RDMA_QPT_UD,
+            dest_qpn: ud,
+            payload: vec![0xab; len],
+            ..msg(opcode, None)
+        };
+
+        // Operations the receiving queue pair doesn't execute.
+        let err = rdma.deliver(&msg(RDMA_WR_RDMA_READ, None)).unwrap_err();
+        assert_eq!(
+            err,
+            RdmaCmdError::InvalidWrOpcode(RDMA_WR_RDMA_READ, RDMA_QPT_UC)
+        );
+        assert_eq!(dropped_msg_status(&err), RDMA_WC_REM_INV_REQ_ERR);
+        assert_eq!(
+            rdma.deliver(&datagram(RDMA_WR_RDMA_WRITE, 16)),
+            Err(RdmaCmdError::InvalidWrOpcode(
+                RDMA_WR_RDMA_WRITE,
+                RDMA_QPT_UD
+            ))
+        );
+        // Immediate data and invalidated remote keys only come with their opcodes.
+        for (opcode, imm_data) in [
+            (RDMA_WR_SEND_WITH_IMM, None),
+            (RDMA_WR_RDMA_WRITE_WITH_IMM, None),
+            (RDMA_WR_SEND, Some(0x1234)),
+            (RDMA_WR_RDMA_WRITE, Some(0x1234)),
+        ] {
+            assert_eq!(
+                rdma.deliver(&msg(opcode, imm_data)),
+                Err(RdmaCmdError::InvalidMsgHeader(opcode))
+            );
+        }
+        for (opcode, invalidate_rkey) in [(RDMA_WR_SEND_WITH_INV, None), (RDMA_WR_SEND, Some(1))] {
+            assert_eq!(
+                rdma.deliver(&RdmaMessage {
+                    invalidate_rkey,
+                    ..msg(opcode, None)
+                }),
+                Err(RdmaCmdError::InvalidMsgHeader(opcode))
+            );
+        }
+        // Datagrams larger than the path MTU.
+        let mtu = 128 << rdma.port.active_mtu;
+        assert_eq!(
+            rdma.deliver(&datagram(RDMA_WR_SEND, mtu + 1)),
+            Err(RdmaCmdError::MsgTooLong(mtu + 1))
+        );
+        // Nothing was written to the remote address of the dropped writes.
+        let mut written = [0u8; 16];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa000))
+            .unwrap();
+        assert_eq!(written, [0u8; 16]);
+
+        // Valid messages go on to look for a receive work request.
+        assert_eq!(
+            rdma.deliver(&msg(RDMA_WR_RDMA_WRITE_WITH_IMM, Some(0x1234))),
+            Err(RdmaCmdError::NoRecvWr(uc))
+        );
+        assert_eq!(
+            rdma.deliver(&datagram(RDMA_WR_SEND, mtu)),
+            Err(RdmaCmdError::NoRecvWr(ud))
+        );
+    }
+
+    #[test]
+    fn test_post_send_inline() {
+        let mut rdma = activated_rdma("rdma-post-send-inline");
+        let (_, dst) = recv_mrs(&mut rdma);
+        assert_eq!(
+            rdma.create_qp(RdmaCmdCreateQp {
+                max_inline_data: RDMA_MAX_INLINE_DATA + 1,
+                ..create_qp_cmd(RDMA_QPT_RC)
+            }),
+            Err(RdmaCmdError::InvalidQpCap)
+        );
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let sender = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+        let receiver = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(sender));
+        rdma.qps.get_mut(sender).unwrap().max_inline_data = 16;
+        let inline = |opcode: u32, inline_len: u32, data: &[u8]| {
+            let cmd = RdmaCmdPostSend {
+                wr_id: u64::from(opcode),
+                remote_addr: 0xa800,
+                qpn: sender,
+                opcode,
+                send_flags: RDMA_SEND_INLINE | RDMA_SEND_SIGNALED,
+                rkey: dst,
+                inline_len,
+                ..Default::default()
+            };
+            [cmd.as_slice(), data].concat()
+        };
+        let recv_sge = RdmaSge {
+            addr: 0xa000,
+            length: 16,
+            lkey: dst,
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(1, receiver, &[recv_sge]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &inline(RDMA_WR_SEND, 8, b"inlined!"),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &inline(RDMA_WR_RDMA_WRITE, 4, b"data"),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &inline(RDMA_WR_SEND, 32, &[0u8; 32]),
+                    rsp_len::<()>(),
+                ),
+                // Only the work requests carrying data may have it inline.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &inline(RDMA_WR_LOCAL_INV, 0, &[]),
+                    rsp_len::<()>(),
+                ),
+                // Truncated inline data.
+                (
+                    RDMA_CMD_POST_SEND,
+                    &inline(RDMA_WR_SEND, 8, b"data"),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for response in &responses[..3] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        for response in &responses[3..] {
+            assert_eq!(*response, (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        }
+        let mut received = [0u8; 8];
+        rdma.mem()
+            .read_slice(&mut received, GuestAddress(0xa000))
+            .unwrap();
+        assert_eq!(&received, b"inlined!");
+        let mut written = [0u8; 4];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa800))
+            .unwrap();
+        assert_eq!(&written, b"data");
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(completions.len(), 3);
+        assert!(completions.iter().all(|wc| wc.status == RDMA_WC_SUCCESS));
+        assert_eq!(rdma.qps.get(sender).unwrap().state, RDMA_QPS_RTS);
+    }
+
+    #[test]
+    fn test_post_recv_errors() {
+        let mut rdma = activated_rdma("rdma-post-recv-errors");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UC, QpAttributes::default());
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_UC,
+            QpAttributes {
+                dest_qp_num: sender,
+                ..Default::default()
+            },
+        );
+        rdma.qps.get_mut(sender).unwrap().attrs.dest_qp_num = receiver;
+        rdma.qps.get_mut(receiver).unwrap().max_recv_wr = 1;
+        let reset = rdma.create_qp(create_qp_cmd(RDMA_QPT_UC)).unwrap().qpn;
+        let sge = |addr: u64, length: u32, lkey: u32| RdmaSge { addr, length, lkey };
+        let short = sge(0xa000, 4, dst);
+
+        let truncated = RdmaCmdPostRecv {
+            qpn: receiver,
+            num_sge: 1,
+            ..Default::default()
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(0, 42, &[]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(0, reset, &[]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(0, receiver, &[short; 2]),
+                    rsp_len::<()>(),
+                ),
+                (RDMA_CMD_POST_RECV, truncated.as_slice(), rsp_len::<()>()),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(1, receiver, &[short]),
+                    rsp_len::<()>(),
+                ),
+                // The receive queue only has room for one work request.
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(2, receiver, &[short]),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_INVALID_HANDLE,
+            RDMA_STATUS_INVALID_STATE,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_OK,
+            RDMA_STATUS_NO_RESOURCES,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert_eq!(rdma.metrics.recv_wr_count.count(), 1);
+
+        // Receive buffers too small for the message fail the work request, and move the queue
+        // pair to the error state.
+        let send = RdmaCmdPostSend {
+            qpn: sender,
+            opcode: RDMA_WR_SEND,
+            num_sge: 1,
+            ..Default::default()
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send, &[sge(0x8000, 16, src)]),
+                    rsp_len::<()>(),
+                ),
+                // Work requests posted on a queue pair in error are flushed.
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(3, receiver, &[short]),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.qps.get(receiver).unwrap().state, RDMA_QPS_ERR);
+        let completions = &rdma.cqs.get(1).unwrap().completions;
+        assert_eq!(
+            completions
+                .iter()
+                .map(|wc| (wc.wr_id, wc.status))
+                .collect::<Vec<_>>(),
+            [(1, RDMA_WC_LOC_LEN_ERR), (3, RDMA_WC_WR_FLUSH_ERR)]
+        );
+        assert!(rdma.qps.get(receiver).unwrap().recv_queue.is_empty());
+
+        // Messages for queue pairs without receive work requests are dropped.
+        rdma.qps.get_mut(receiver).unwrap().state = RDMA_QPS_RTS;
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &post_send_args(send, &[sge(0x8000, 16, src)]),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.metrics.rx_drops.count(), 1);
+
+        // Moving a queue pair to the error state flushes its receive queue.
+        rdma.qps
+            .get_mut(receiver)
+            .unwrap()
+            .recv_queue
+            .push_back(RecvWr {
+                wr_id: 4,
+                sges: Vec::new(),
+            });
+        rdma.modify_qp(RdmaCmdModifyQp {
+            qpn: receiver,
+            attr_mask: RDMA_QP_STATE,
+            qp_state: RDMA_QPS_ERR,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            rdma.cqs.get(1).unwrap().completions[2],
+            RdmaWc {
+                wr_id: 4,
+                status: RDMA_WC_WR_FLUSH_ERR,
+                opcode: RDMA_WC_RECV,
+                qp_num: receiver,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_outstanding_work_quotas() {
+        let mut rdma = activated_rdma("rdma-wr-quotas");
+        rdma.set_resource_limits(RdmaResourceLimits {
+            max_outstanding_wr_per_qp: Some(2),
+            max_outstanding_bytes_per_pd: Some(0x300),
+            ..Default::default()
+        })
+        .unwrap();
+        let (src, dst) = recv_mrs(&mut rdma);
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        let attrs = QpAttributes {
+            qkey: 0x1111,
+            ..Default::default()
+        };
+        let first = ready_qp(&mut rdma, RDMA_QPT_UD, attrs.clone());
+        let second = ready_qp(&mut rdma, RDMA_QPT_UD, attrs);
+        let post_recv = |rdma: &mut VirtioRdma, wr_id: u64, qpn: u32| {
+            let sge = RdmaSge {
+                addr: 0xa000 + 0x100 * wr_id,
+                length: 0x100,
+                lkey: dst,
+            };
+            run_commands(
+                rdma,
+                &[(
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(wr_id, qpn, &[sge]),
+                    rsp_len::<()>(),
+                )],
+            )[0]
+            .0
+        };
+        let post_send = |rdma: &mut VirtioRdma, qpn: u32, remote_qpn: u32| {
+            let cmd = RdmaCmdPostSend {
+                qpn,
+                opcode: RDMA_WR_SEND,
+                num_sge: 1,
+                ah,
+                remote_qpn,
+                remote_qkey: 0x1111,
+                ..Default::default()
+            };
+            let sge = RdmaSge {
+                addr: 0x8000,
+                length: 16,
+                lkey: src,
+            };
+            run_commands(
+                rdma,
+                &[(
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(cmd, &[sge]),
+                    rsp_len::<()>(),
+                )],
+            )[0]
+            .0
+        };
+
+        // The queue pair reaches its quota of work requests before its receive queue is full.
+        assert_eq!(post_recv(&mut rdma, 0, first), RDMA_STATUS_OK);
+        assert_eq!(post_recv(&mut rdma, 1, first), RDMA_STATUS_OK);
+        assert_eq!(post_recv(&mut rdma, 2, first), RDMA_STATUS_NO_RESOURCES);
+        assert_eq!(
+            post_send(&mut rdma, first, second),
+            RDMA_STATUS_NO_RESOURCES
+        );
+        // The queue pairs of the protection domain share its quota of bytes.
+        assert_eq!(post_recv(&mut rdma, 2, second), RDMA_STATUS_OK);
+        assert_eq!(post_recv(&mut rdma, 3, second), RDMA_STATUS_NO_RESOURCES);
+        assert_eq!(
+            post_send(&mut rdma, second, second),
+            RDMA_STATUS_NO_RESOURCES
+        );
+        assert_eq!(rdma.metrics.wr_quota_exceeded.count(), 4);
+        assert_eq!(
+            rdma.qps.get(first).unwrap().outstanding,
+            OutstandingWork {
+                wrs: 2,
+                bytes: 0x200
+            }
+        );
+        assert_eq!(
+            rdma.pds.get(1).unwrap().outstanding,
+            OutstandingWork {
+                wrs: 3,
+                bytes: 0x300
+            }
+        );
+
+        // Flushed and consumed work requests are no longer outstanding.
+        rdma.set_qp_error(first);
+        assert_eq!(
+            rdma.qps.get(first).unwrap().outstanding,
+            OutstandingWork::default()
+        );
+        assert_eq!(post_send(&mut rdma, second, second), RDMA_STATUS_OK);
+        assert_eq!(
+            rdma.pds.get(1).unwrap().outstanding,
+            OutstandingWork::default()
+        );
+        assert_eq!(
+            rdma.qps.get(second).unwrap().outstanding,
+            OutstandingWork::default()
+        );
+
+        // And neither are the ones of destroyed queue pairs.
+        assert_eq!(post_recv(&mut rdma, 4, second), RDMA_STATUS_OK);
+        rdma.destroy_qp(RdmaCmdDestroyQp { qpn: second }).unwrap();
+        assert_eq!(
+            rdma.pds.get(1).unwrap().outstanding,
+            OutstandingWork::default()
+        );
+    }
+
+    #[test]
+    fn test_deliver_completions() {
+        let mut rdma = activated_rdma("rdma-cq-queue");
+        let create = |flags: u32| RdmaCmdCreateCq { cqe: 8, flags };
+        assert_eq!(
+            rdma.create_cq(create(RDMA_CQ_F_ASYNC << 1)),
+            Err(RdmaCmdError::InvalidCqFlags(RDMA_CQ_F_ASYNC << 1))
+        );
+        let cqn = rdma.create_cq(create(RDMA_CQ_F_ASYNC)).unwrap().cqn;
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_CQ_QUEUE] = vq.create_queue();
+        let complete = |rdma: &mut VirtioRdma, cqn: u32, wr_id: u64| {
+            rdma.complete(
+                cqn,
+                RdmaWc {
+                    wr_id,
+                    ..Default::default()
+                },
+                false,
+            )
+        };
+        for wr_id in 0..3 {
+            complete(&mut rdma, cqn, wr_id);
+        }
+        // Completion queues without `RDMA_CQ_F_ASYNC` are polled.
+        complete(&mut rdma, 1, 42);
+
+        // The work completions wait for the driver to queue buffers.
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 0);
+        assert_eq!(rdma.cqs.get(cqn).unwrap().completions.len(), 3);
+
+        let wc_len = size_of::<RdmaWc>();
+        let buffer_len = u32::try_from(size_of::<RdmaCqEvent>() + 2 * wc_len).unwrap();
+        for i in 0..2u16 {
+            vq.dtable[usize::from(i)].set(
+                0xd000 + 0x800 * u64::from(i),
+                buffer_len,
+                VIRTQ_DESC_F_WRITE,
+                0,
+            );
+            vq.avail.ring[usize::from(i)].set(i);
+        }
+        vq.avail.idx.set(2);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 2);
+        let read_buffer = |i: u64| {
+            let event: RdmaCqEvent = mem.read_obj(GuestAddress(0xd000 + 0x800 * i)).unwrap();
+            let wr_ids: Vec<u64> = (0..u64::from(event.num_entries))
+                .map(|j| {
+                    let addr = 0xd000
+                        + 0x800 * i
+                        + (size_of::<RdmaCqEvent>() + usize::try_from(j).unwrap() * wc_len) as u64;
+                    mem.read_obj::<RdmaWc>(GuestAddress(addr)).unwrap().wr_id
+                })
+                .collect();
+            (event.cqn, wr_ids)
+        };
+        // Buffers are filled as much as they can hold.
+        assert_eq!(vq.used.ring[0].get().len, buffer_len);
+        assert_eq!(read_buffer(0), (cqn, vec![0, 1]));
+        assert_eq!(
+            vq.used.ring[1].get().len,
+            u32::try_from(size_of::<RdmaCqEvent>() + wc_len).unwrap()
+        );
+        assert_eq!(read_buffer(1), (cqn, vec![2]));
+        assert!(rdma.cqs.get(cqn).unwrap().completions.is_empty());
+        assert_eq!(rdma.cqs.get(1).unwrap().completions.len(), 1);
+        assert_eq!(rdma.metrics.cq_event_count.count(), 2);
+
+        // Buffers too small for a work completion are returned empty, without losing it.
+        complete(&mut rdma, cqn, 3);
+        vq.dtable[2].set(
+            0xe000,
+            u32::try_from(size_of::<RdmaCqEvent>()).unwrap(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[2].set(2);
+        vq.avail.idx.set(3);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 3);
+        assert_eq!(vq.used.ring[2].get().len, 0);
+        assert_eq!(rdma.metrics.event_fails.count(), 1);
+        assert_eq!(rdma.cqs.get(cqn).unwrap().completions.len(), 1);
+    }
+
+    #[test]
+    fn test_cq_moderation() {
+        let mut rdma = activated_rdma("rdma-cq-moderation");
+        let moderation = |max_cqes: u16, max_usecs: u32| {
+            Some(RdmaCqModeration {
+                max_cqes,
+                max_usecs,
+            })
+        };
+        assert!(matches!(
+            rdma.set_cq_moderation(moderation(0, 100)),
+            Err(RdmaError::InvalidCqModeration)
+        ));
+        assert!(matches!(
+            rdma.set_cq_moderation(moderation(4, 0)),
+            Err(RdmaError::InvalidCqModeration)
+        ));
+        assert_eq!(rdma.cq_moderation(), None);
+        rdma.set_cq_moderation(moderation(3, 1_000_000)).unwrap();
+        assert_eq!(rdma.cq_moderation(), moderation(3, 1_000_000));
+
+        let cqn = rdma
+            .create_cq(RdmaCmdCreateCq {
+                cqe: 8,
+                flags: RDMA_CQ_F_ASYNC,
+            })
+            .unwrap()
+            .cqn;
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_CQ_QUEUE] = vq.create_queue();
+        let buffer_len = u32::try_from(size_of::<RdmaCqEvent>() + size_of::<RdmaWc>()).unwrap();
+        for i in 0..8u16 {
+            vq.dtable[usize::from(i)].set(
+                0xd000 + 0x100 * u64::from(i),
+                buffer_len,
+                VIRTQ_DESC_F_WRITE,
+                0,
+            );
+            vq.avail.ring[usize::from(i)].set(i);
+        }
+        vq.avail.idx.set(8);
+        let interrupt = VirtioInterruptType::Queue(u16::try_from(RDMA_CQ_QUEUE).unwrap());
+        let complete = |rdma: &mut VirtioRdma, count: u64| {
+            for wr_id in 0..count {
+                rdma.complete(
+                    cqn,
+                    RdmaWc {
+                        wr_id,
+                        ..Default::default()
+                    },
+                    false,
+                );
+            }
+            rdma.deliver_completions().unwrap();
+        };
+
+        // The work completions under the limit are pushed without interrupting the driver.
+        complete(&mut rdma, 2);
+        assert_eq!(vq.used.idx.get(), 2);
+        assert!(!rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        assert!(rdma.cq_moderation_timer.is_armed());
+        assert_eq!(rdma.metrics.cq_deferred_interrupts.count(), 1);
+        // Reaching the limit interrupts the driver for all of them.
+        complete(&mut rdma, 1);
+        assert_eq!(vq.used.idx.get(), 3);
+        assert!(rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        assert!(!rdma.cq_moderation_timer.is_armed());
+
+        // Once the timer expires, the driver is interrupted for fewer work completions.
+        complete(&mut rdma, 1);
+        assert!(!rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        rdma.flush_completions();
+        assert!(rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        assert!(!rdma.cq_moderation_timer.is_armed());
+
+        // Disabling the moderation interrupts the driver for the deferred work completions.
+        complete(&mut rdma, 1);
+        assert!(!rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        rdma.set_cq_moderation(None).unwrap();
+        assert!(rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        complete(&mut rdma, 1);
+        assert_eq!(vq.used.idx.get(), 6);
+        assert!(rdma.interrupt_trigger().has_pending_interrupt(interrupt));
+        assert_eq!(rdma.metrics.cq_deferred_interrupts.count(), 3);
+    }
+
+    #[test]
+    fn test_req_notify_cq() {
+        let mut rdma = activated_rdma("rdma-req-notify-cq");
+        let cqn = create_cq(&mut rdma);
+        let async_cqn = rdma
+            .create_cq(RdmaCmdCreateCq {
+                cqe: 8,
+                flags: RDMA_CQ_F_ASYNC,
+            })
+            .unwrap()
+            .cqn;
+        let arm = |cqn: u32, flags: u32| RdmaCmdReqNotifyCq { cqn, flags };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_REQ_NOTIFY_CQ,
+                    arm(cqn, RDMA_CQ_SOLICITED | RDMA_CQ_NEXT_COMP).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_REQ_NOTIFY_CQ,
+                    arm(42, RDMA_CQ_NEXT_COMP).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_REQ_NOTIFY_CQ,
+                    arm(async_cqn, RDMA_CQ_NEXT_COMP).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_REQ_NOTIFY_CQ,
+                    arm(cqn, RDMA_CQ_NEXT_COMP).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[2], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(rdma.cqs.get(cqn).unwrap().armed, RDMA_CQ_NEXT_COMP);
+
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_CQ_QUEUE] = vq.create_queue();
+        let event_len = u32::try_from(size_of::<RdmaCqEvent>()).unwrap();
+        for i in 0..4u16 {
+            vq.dtable[usize::from(i)].set(
+                0xd000 + 0x100 * u64::from(i),
+                event_len,
+                VIRTQ_DESC_F_WRITE,
+                0,
+            );
+            vq.avail.ring[usize::from(i)].set(i);
+        }
+        vq.avail.idx.set(4);
+        let wc = |status: u32| RdmaWc {
+            status,
+            ..Default::default()
+        };
+
+        // Only the next work completion raises an event, which disarms the queue.
+        rdma.complete(cqn, wc(RDMA_WC_SUCCESS), false);
+        rdma.complete(cqn, wc(RDMA_WC_SUCCESS), false);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 1);
+        assert_eq!(vq.used.ring[0].get().len, event_len);
+        let event: RdmaCqEvent = mem.read_obj(GuestAddress(0xd000)).unwrap();
+        assert_eq!(
+            event,
+            RdmaCqEvent {
+                cqn,
+                num_entries: 0
+            }
+        );
+        // The work completions are left to be polled.
+        assert_eq!(rdma.cqs.get(cqn).unwrap().completions.len(), 2);
+        assert_eq!(rdma.cqs.get(cqn).unwrap().armed, 0);
+
+        // Disarmed queues raise no event.
+        rdma.complete(cqn, wc(RDMA_WC_SUCCESS), true);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 1);
+
+        // Queues armed for solicited work completions ignore the unsolicited successful ones.
+        rdma.req_notify_cq(arm(cqn, RDMA_CQ_SOLICITED)).unwrap();
+        rdma.complete(cqn, wc(RDMA_WC_SUCCESS), false);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 1);
+        rdma.complete(cqn, wc(RDMA_WC_SUCCESS), true);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 2);
+        // Unsuccessful work completions are always notified.
+        rdma.req_notify_cq(arm(cqn, RDMA_CQ_SOLICITED)).unwrap();
+        rdma.complete(cqn, wc(RDMA_WC_WR_FLUSH_ERR), false);
+        rdma.deliver_completions().unwrap();
+        assert_eq!(vq.used.idx.get(), 3);
+        assert_eq!(rdma.metrics.cq_notify_count.count(), 3);
+        assert_eq!(rdma.metrics.cq_event_count.count(), 0);
+
+        // A queue armed for its next work completion stays so when armed for solicited ones.
+        rdma.req_notify_cq(arm(cqn, RDMA_CQ_NEXT_COMP)).unwrap();
+        rdma.req_notify_cq(arm(cqn, RDMA_CQ_SOLICITED)).unwrap();
+        assert_eq!(rdma.cqs.get(cqn).unwrap().armed, RDMA_CQ_NEXT_COMP);
+    }
+
+    fn post_srq_recv_args(wr_id: u64, srqn: u32, sges: &[RdmaSge]) -> Vec<u8> {
+        let cmd = RdmaCmdPostSrqRecv {
+            wr_id,
+            srqn,
+            num_sge: u32::try_from(sges.len()).unwrap(),
+        };
+        let mut args = cmd.as_slice().to_vec();
+        for sge in sges {
+            args.extend_from_slice(sge.as_slice());
+        }
+        args
+    }
+
+    #[test]
+    fn test_create_destroy_srq() {
+        let mut rdma = activated_rdma("rdma-srq");
+        let create = |pd: u32, max_wr: u32, srq_limit: u32| RdmaCmdCreateSrq {
+            pd,
+            max_wr,
+            max_sge: 1,
+            srq_limit,
+        };
+        let destroy = |srqn: u32| RdmaCmdDestroySrq {
+            srqn,
+            ..Default::default()
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_CREATE_SRQ,
+                    create(1, 4, 0).as_slice(),
+                    rsp_len::<RdmaRspCreateSrq>(),
+                ),
+                (RDMA_CMD_CREATE_SRQ, create(1, 0, 0).as_slice(), 64),
+                (
+                    RDMA_CMD_CREATE_SRQ,
+                    create(1, RDMA_MAX_SRQ_WR + 1, 0).as_slice(),
+                    64,
+                ),
+                (RDMA_CMD_CREATE_SRQ, create(1, 4, 5).as_slice(), 64),
+                (RDMA_CMD_CREATE_SRQ, create(42, 4, 0).as_slice(), 64),
+                // The response can't hold the shared receive queue number.
+                (
+                    RDMA_CMD_CREATE_SRQ,
+                    create(1, 4, 0).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0].0, RDMA_STATUS_OK);
+        let srqn = RdmaRspCreateSrq::from_slice(&responses[0].1).unwrap().srqn;
+        assert_eq!(srqn, 1);
+        for (response, status) in responses[1..].iter().zip([
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_HANDLE,
+            RDMA_STATUS_INVALID_ARG,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert_eq!(rdma.srqs.len(), 1);
+
+        // Queue pairs using a shared receive queue ignore their receive capabilities.
+        let create_qp = |srq: u32| RdmaCmdCreateQp {
+            srq,
+            max_recv_wr: RDMA_MAX_QP_WR + 1,
+            ..create_qp_cmd(RDMA_QPT_RC)
+        };
+        assert_eq!(
+            rdma.create_qp(create_qp(42)),
+            Err(RdmaCmdError::UnknownSrq(42))
+        );
+        let qpn = rdma.create_qp(create_qp(srqn)).unwrap().qpn;
+        let qp = rdma.qps.get(qpn).unwrap();
+        assert_eq!((qp.srq, qp.max_recv_wr, qp.max_recv_sge), (srqn, 0, 0));
+
+        // The shared receive queue and its protection domain outlive the queue pairs using it.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_DESTROY_SRQ,
+                    destroy(srqn).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_DESTROY_QP,
+                    RdmaCmdDestroyQp {
+                        qpn,
+                        ..Default::default()
+                    }
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_DEALLOC_PD,
+                    RdmaCmdDeallocPd {
+                        pdn: 1,
+                        ..Default::default()
+                    }
+                    .as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_DESTROY_SRQ,
+                    destroy(srqn).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_DESTROY_SRQ,
+                    destroy(srqn).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_BUSY, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[2], (RDMA_STATUS_BUSY, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[4], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert!(rdma.srqs.is_empty());
+
+        // The device runs out of shared receive queues.
+        for _ in 0..RDMA_MAX_SRQ {
+            rdma.create_srq(create(1, 4, 0)).unwrap();
+        }
+        assert_eq!(
+            rdma.create_srq(create(1, 4, 0)),
+            Err(RdmaCmdError::NoSrqLeft)
+        );
+    }
+
+    #[test]
+    fn test_modify_srq() {
+        let mut rdma = activated_rdma("rdma-modify-srq");
+        let srqn = rdma
+            .create_srq(RdmaCmdCreateSrq {
+                pd: 1,
+                max_wr: 4,
+                max_sge: 1,
+                srq_limit: 0,
+            })
+            .unwrap()
+            .srqn;
+        let modify = |attr_mask: u32, max_wr: u32, srq_limit: u32| RdmaCmdModifySrq {
+            srqn,
+            attr_mask,
+            max_wr,
+            srq_limit,
+        };
+
+        rdma.modify_srq(modify(RDMA_SRQ_LIMIT, 0, 2)).unwrap();
+        assert_eq!(rdma.srqs.get(srqn).unwrap().limit, 2);
+        // The limit is checked against the new size.
+        rdma.modify_srq(modify(RDMA_SRQ_MAX_WR | RDMA_SRQ_LIMIT, 8, 8))
+            .unwrap();
+        let srq = rdma.srqs.get(srqn).unwrap();
+        assert_eq!((srq.max_wr, srq.limit), (8, 8));
+
+        // The queue may not shrink below the work requests it holds.
+        for wr_id in 0..3 {
+            rdma.srqs
+                .get_mut(srqn)
+                .unwrap()
+                .recv_queue
+                .push_back(RecvWr {
+                    wr_id,
+                    sges: Vec::new(),
+                });
+        }
+        for (cmd, err) in [
+            (
+                modify(RDMA_SRQ_MAX_WR, 2, 0),
+                RdmaCmdError::InvalidSrqAttr("max_wr", 2),
+            ),
+            (
+                modify(RDMA_SRQ_MAX_WR, RDMA_MAX_SRQ_WR + 1, 0),
+                RdmaCmdError::InvalidSrqAttr("max_wr", RDMA_MAX_SRQ_WR + 1),
+            ),
+            (
+                modify(RDMA_SRQ_MAX_WR | RDMA_SRQ_LIMIT, 4, 5),
+                RdmaCmdError::InvalidSrqAttr("srq_limit", 5),
+            ),
+            (
+                modify(1 << 2, 0, 0),
+                RdmaCmdError::InvalidSrqAttrMask(1 << 2),
+            ),
+            (
+                RdmaCmdModifySrq {
+                    srqn: 42,
+                    ..modify(RDMA_SRQ_LIMIT, 0, 1)
+                },
+                RdmaCmdError::UnknownSrq(42),
+            ),
+        ] {
+            assert_eq!(rdma.modify_srq(cmd), Err(err));
+        }
+        // Failed commands change nothing.
+        let srq = rdma.srqs.get(srqn).unwrap();
+        assert_eq!((srq.max_wr, srq.limit), (8, 8));
+        rdma.modify_srq(modify(RDMA_SRQ_MAX_WR, 3, 0)).unwrap_err();
+        rdma.modify_srq(modify(RDMA_SRQ_MAX_WR | RDMA_SRQ_LIMIT, 3, 0))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_post_srq_recv() {
+        let mut rdma = activated_rdma("rdma-post-srq-recv");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let srqn = rdma
+            .create_srq(RdmaCmdCreateSrq {
+                pd: 1,
+                max_wr: 3,
+                max_sge: 1,
+                srq_limit: 0,
+            })
+            .unwrap()
+            .srqn;
+        let ah = rdma
+            .create_ah(RdmaCmdCreateAh {
+                pd: 1,
+                port_num: 1,
+                ..Default::default()
+            })
+            .unwrap()
+            .ah;
+        // Two datagram queue pairs share the receive queue.
+        let receivers: Vec<u32> = (0..2)
+            .map(|_| {
+                let qpn = rdma
+                    .create_qp(RdmaCmdCreateQp {
+                        srq: srqn,
+                        ..create_qp_cmd(RDMA_QPT_UD)
+                    })
+                    .unwrap()
+                    .qpn;
+                let qp = rdma.qps.get_mut(qpn).unwrap();
+                qp.state = RDMA_QPS_RTS;
+                qp.attrs.qkey = 0x1111;
+                qpn
+            })
+            .collect();
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let send = |remote_qpn: u32| {
+            let cmd = RdmaCmdPostSend {
+                qpn: sender,
+                opcode: RDMA_WR_SEND,
+                num_sge: 1,
+                ah,
+                remote_qpn,
+                remote_qkey: 0x1111,
+                ..Default::default()
+            };
+            post_send_args(
+                cmd,
+                &[RdmaSge {
+                    addr: 0x8000,
+                    length: 16,
+                    lkey: src,
+                }],
+            )
+        };
+        let recv = |wr_id: u64| {
+            let sge = RdmaSge {
+                addr: 0xa000 + 0x100 * wr_id,
+                length: 0x100,
+                lkey: dst,
+            };
+            post_srq_recv_args(wr_id, srqn, &[sge])
+        };
+
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SRQ_RECV, &recv(0), rsp_len::<()>()),
+                (RDMA_CMD_POST_SRQ_RECV, &recv(1), rsp_len::<()>()),
+                (RDMA_CMD_POST_SRQ_RECV, &recv(2), rsp_len::<()>()),
+                // The shared receive queue is full.
+                (RDMA_CMD_POST_SRQ_RECV, &recv(3), rsp_len::<()>()),
+                // The queue pairs have no receive queue of their own.
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(4, receivers[0], &[]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SRQ_RECV,
+                    &post_srq_recv_args(5, 42, &[]),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        for (response, status) in responses.iter().zip([
+            RDMA_STATUS_OK,
+            RDMA_STATUS_OK,
+            RDMA_STATUS_OK,
+            RDMA_STATUS_NO_RESOURCES,
+            RDMA_STATUS_INVALID_ARG,
+            RDMA_STATUS_INVALID_HANDLE,
+        ]) {
+            assert_eq!(*response, (status, Vec::new()));
+        }
+        assert_eq!(rdma.metrics.recv_wr_count.count(), 3);
+
+        rdma.modify_srq(RdmaCmdModifySrq {
+            srqn,
+            attr_mask: RDMA_SRQ_LIMIT,
+            srq_limit: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_EVENT_QUEUE] = vq.create_queue();
+        vq.dtable[0].set(
+            0xd000,
+            u32::try_from(size_of::<RdmaAsyncEvent>()).unwrap(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+
+        // The messages of both queue pairs consume the work requests in order, and the limit
+        // is reached once fewer than 2 work requests are left.
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SEND, &send(receivers[0]), rsp_len::<()>()),
+                (RDMA_CMD_POST_SEND, &send(receivers[1]), rsp_len::<()>()),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_OK, Vec::new()));
+        let wcs = parse_wcs(&responses[2].1);
+        assert_eq!(
+            wcs.iter()
+                .map(|wc| (wc.wr_id, wc.status, wc.qp_num))
+                .collect::<Vec<_>>(),
+            [
+                (0, RDMA_WC_SUCCESS, receivers[0]),
+                (1, RDMA_WC_SUCCESS, receivers[1])
+            ]
+        );
+        let mut received = [0u8; 16];
+        mem.read_slice(&mut received, GuestAddress(0xa100 + 40))
+            .unwrap();
+        assert_eq!(received.to_vec(), (0..16).collect::<Vec<u8>>());
+
+        assert_eq!(vq.used.idx.get(), 1);
+        assert_eq!(
+            mem.read_obj::<RdmaAsyncEvent>(GuestAddress(0xd000))
+                .unwrap(),
+            RdmaAsyncEvent {
+                event_type: RDMA_EVENT_SRQ_LIMIT_REACHED,
+                handle: srqn,
+            }
+        );
+        // The limit is disarmed.
+        let srq = rdma.srqs.get(srqn).unwrap();
+        assert_eq!((srq.limit, srq.recv_queue.len()), (0, 1));
+        assert_eq!(rdma.metrics.srq_limit_events.count(), 1);
+
+        // Work requests of shared receive queues are not flushed with the queue pairs.
+        rdma.set_qp_error(receivers[0]);
+        assert_eq!(rdma.srqs.get(srqn).unwrap().recv_queue.len(), 1);
+        assert!(rdma.cqs.get(1).unwrap().completions.is_empty());
+    }
+
+    #[test]
+    fn test_alloc_dealloc_mw() {
+        let mut rdma = activated_rdma("rdma-mw");
+        let alloc = |pd: u32, mw_type: u32| RdmaCmdAllocMw { pd, mw_type };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_ALLOC_MW,
+                    alloc(1, RDMA_MW_TYPE_2).as_slice(),
+                    rsp_len::<RdmaRspAllocMw>(),
+                ),
+                // Type 1 memory windows are not supported.
+                (
+                    RDMA_CMD_ALLOC_MW,
+                    alloc(1, 1).as_slice(),
+                    rsp_len::<RdmaRspAllocMw>(),
+                ),
+                (
+                    RDMA_CMD_ALLOC_MW,
+                    alloc(42, RDMA_MW_TYPE_2).as_slice(),
+                    rsp_len::<RdmaRspAllocMw>(),
+                ),
+                (
+                    RDMA_CMD_ALLOC_MW,
+                    alloc(1, RDMA_MW_TYPE_2).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        // The remote keys of the memory windows don't collide with the ones of the memory
+        // regions.
+        let rkey = MW_RKEY_FLAG | (1 << 8);
+        let expected = RdmaRspAllocMw {
+            rkey,
+            ..Default::default()
+        };
+        assert_eq!(responses[0], (RDMA_STATUS_OK, expected.as_slice().to_vec()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(responses[2], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(rdma.mws.len(), 1);
+
+        let dealloc = |rkey: u32| RdmaCmdDeallocMw {
+            rkey,
+            ..Default::default()
+        };
+        let dealloc_pd = RdmaCmdDeallocPd {
+            pdn: 1,
+            ..Default::default()
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                // The memory windows of a protection domain must be deallocated first.
+                (RDMA_CMD_DEALLOC_PD, dealloc_pd.as_slice(), rsp_len::<()>()),
+                // Neither a stale remote key nor the key of a memory region reference the
+                // window.
+                (
+                    RDMA_CMD_DEALLOC_MW,
+                    dealloc(rkey + 1).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (RDMA_CMD_DEALLOC_MW, dealloc(1).as_slice(), rsp_len::<()>()),
+                (
+                    RDMA_CMD_DEALLOC_MW,
+                    dealloc(rkey).as_slice(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_DEALLOC_MW,
+                    dealloc(rkey).as_slice(),
+                    rsp_len::<()>(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_BUSY, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[2], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert_eq!(responses[3], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[4], (RDMA_STATUS_INVALID_HANDLE, Vec::new()));
+        assert!(rdma.mws.is_empty());
+
+        rdma.mws = ResourceTable::with_max_handle(1, MAX_MW_HANDLE);
+        rdma.alloc_mw(alloc(1, RDMA_MW_TYPE_2)).unwrap();
+        assert_eq!(
+            rdma.alloc_mw(alloc(1, RDMA_MW_TYPE_2)),
+            Err(RdmaCmdError::NoMwLeft)
+        );
+    }
+
+    #[test]
+    fn test_bind_mw() {
+        let mut rdma = activated_rdma("rdma-bind-mw");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let bindable = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0xa000,
+                length: 0x1000,
+                access: RDMA_ACCESS_LOCAL_WRITE | RDMA_ACCESS_MW_BIND,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            access_flags: RDMA_ACCESS_REMOTE_WRITE,
+            dest_qp_num,
+            ..Default::default()
+        };
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UC, attrs(2));
+        let receiver = ready_qp(&mut rdma, RDMA_QPT_UC, attrs(sender));
+        let alloc = RdmaCmdAllocMw {
+            pd: 1,
+            mw_type: RDMA_MW_TYPE_2,
+        };
+        let mw = rdma.alloc_mw(alloc).unwrap().rkey;
+
+        // The receiver binds the window, through which the sender writes.
+        let binding = |mw_rkey: u32, rkey: u32, addr: u64| RdmaBindMw {
+            addr,
+            length: 0x100,
+            mw_rkey,
+            rkey,
+            lkey: bindable,
+            access: RDMA_ACCESS_REMOTE_WRITE,
+        };
+        let bind = |wr_id: u64, binding: RdmaBindMw| {
+            let cmd = RdmaCmdPostSend {
+                wr_id,
+                qpn: receiver,
+                opcode: RDMA_WR_BIND_MW,
+                send_flags: RDMA_SEND_SIGNALED,
+                ..Default::default()
+            };
+            [cmd.as_slice(), binding.as_slice()].concat()
+        };
+        let sge = RdmaSge {
+            addr: 0x8000,
+            length: 16,
+            lkey: src,
+        };
+        let write = |rkey: u32, remote_addr: u64| {
+            let cmd = RdmaCmdPostSend {
+                remote_addr,
+                qpn: sender,
+                opcode: RDMA_WR_RDMA_WRITE,
+                num_sge: 1,
+                rkey,
+                ..Default::default()
+            };
+            post_send_args(cmd, &[sge])
+        };
+        let poll_len = rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap();
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (
+                    RDMA_CMD_POST_SEND,
+                    &bind(1, binding(mw, mw + 1, 0xa100)),
+                    rsp_len::<()>(),
+                ),
+                (RDMA_CMD_POST_SEND, &write(mw + 1, 0xa100), rsp_len::<()>()),
+                // The remote key of the window changed with the bind.
+                (RDMA_CMD_POST_SEND, &write(mw, 0xa100), rsp_len::<()>()),
+                (RDMA_CMD_POST_SEND, &write(mw + 1, 0xa1f8), rsp_len::<()>()),
+                (RDMA_CMD_POLL_CQ, poll_cq_args(1, 4).as_slice(), poll_len),
+            ],
+        );
+        for response in &responses[..4] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        let wc = |wr_id: u64, opcode: u32| RdmaWc {
+            wr_id,
+            status: RDMA_WC_SUCCESS,
+            opcode,
+            qp_num: receiver,
+            ..Default::default()
+        };
+        assert_eq!(parse_wcs(&responses[4].1), [wc(1, RDMA_WC_BIND_MW)]);
+        let mut written = [0u8; 16];
+        rdma.mem()
+            .read_slice(&mut written, GuestAddress(0xa100))
+            .unwrap();
+        assert_eq!(written.to_vec(), (0..16).collect::<Vec<u8>>());
+        assert_eq!(rdma.metrics.rx_drops.count(), 2);
+        assert_eq!(
+            rdma.check_remote_access(receiver, mw + 1, 0xa1f8, 16, RDMA_ACCESS_REMOTE_WRITE),
+            Err(RdmaCmdError::OutOfMwBounds(mw + 1, 0xa1f8, 16))
+        );
+        assert_eq!(
+            rdma.check_remote_access(sender, mw + 1, 0xa100, 16, RDMA_ACCESS_REMOTE_WRITE),
+            Err(RdmaCmdError::MwQpMismatch(mw + 1, sender))
+        );
+        // The memory region can't be deregistered while the window is bound to it.
+        let dereg = RdmaCmdDeregMr {
+            lkey: bindable,
+            ..Default::default()
+        };
+        assert_eq!(rdma.dereg_mr(dereg), Err(RdmaCmdError::MrInUse(bindable)));
+
+        // The window may be bound again once invalidated, locally or by a received message.
+        let local_inv = RdmaCmdPostSend {
+            wr_id: 2,
+            qpn: receiver,
+            opcode: RDMA_WR_LOCAL_INV,
+            send_flags: RDMA_SEND_SIGNALED,
+            imm_data: mw + 1,
+            ..Default::default()
+        };
+        let send_with_inv = RdmaCmdPostSend {
+            qpn: sender,
+            opcode: RDMA_WR_SEND_WITH_INV,
+            num_sge: 1,
+            imm_data: mw + 2,
+            ..Default::default()
+        };
+        let recv_sge = RdmaSge {
+            addr: 0xa800,
+            length: 0x100,
+            lkey: dst,
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SEND, local_inv.as_slice(), rsp_len::<()>()),
+                (RDMA_CMD_POST_SEND, &write(mw + 1, 0xa100), rsp_len::<()>()),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &bind(3, binding(mw + 1, mw + 2, 0xa100)),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_RECV,
+                    &post_recv_args(4, receiver, &[recv_sge]),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &post_send_args(send_with_inv, &[sge]),
+                    rsp_len::<()>(),
+                ),
+                (RDMA_CMD_POLL_CQ, poll_cq_args(1, 4).as_slice(), poll_len),
+            ],
+        );
+        for response in &responses[..5] {
+            assert_eq!(*response, (RDMA_STATUS_OK, Vec::new()));
+        }
+        assert_eq!(
+            parse_wcs(&responses[5].1),
+            [
+                wc(2, RDMA_WC_LOCAL_INV),
+                wc(3, RDMA_WC_BIND_MW),
+                RdmaWc {
+                    byte_len: 16,
+                    imm_data: mw + 2,
+                    src_qp: sender,
+                    wc_flags: RDMA_WC_WITH_INV,
+                    ..wc(4, RDMA_WC_RECV)
+                },
+            ]
+        );
+        assert_eq!(rdma.metrics.rx_drops.count(), 3);
+        let handle = mw_handle(mw).unwrap();
+        assert_eq!(
+            *rdma.mws.get(handle).unwrap(),
+            MemoryWindow {
+                pd: 1,
+                rkey: mw + 2,
+                binding: None,
+            }
+        );
+        rdma.dereg_mr(dereg).unwrap();
+    }
+
+    #[test]
+    fn test_bind_mw_errors() {
+        let mut rdma = activated_rdma("rdma-bind-mw-errors");
+        let (_, dst) = recv_mrs(&mut rdma);
+        let bindable = rdma
+            .reg_mr(RdmaCmdRegMr {
+                iova: 0xa000,
+                length: 0x1000,
+                access: RDMA_ACCESS_MW_BIND,
+                pd: 1,
+            })
+            .unwrap()
+            .lkey;
+        let qpn = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let alloc = RdmaCmdAllocMw {
+            pd: 1,
+            mw_type: RDMA_MW_TYPE_2,
+        };
+        let mw = rdma.alloc_mw(alloc).unwrap().rkey;
+        let bind = RdmaBindMw {
+            addr: 0xa000,
+            length: 0x1000,
+            mw_rkey: mw,
+            rkey: mw + 1,
+            lkey: bindable,
+            access: RDMA_ACCESS_REMOTE_READ,
+        };
+
+        let cases = [
+            (
+                RdmaBindMw {
+                    mw_rkey: mw + 1,
+                    ..bind
+                },
+                RdmaCmdError::UnknownMw(mw + 1),
+            ),
+            (
+                RdmaBindMw {
+                    rkey: mw + 0x100,
+                    ..bind
+                },
+                RdmaCmdError::InvalidMwRkey(mw, mw + 0x100),
+            ),
+            (
+                RdmaBindMw {
+                    access: RDMA_ACCESS_LOCAL_WRITE,
+                    ..bind
+                },
+                RdmaCmdError::InvalidMwAccess(RDMA_ACCESS_LOCAL_WRITE),
+            ),
+            (
+                RdmaBindMw { lkey: dst, ..bind },
+                RdmaCmdError::MrNotBindable(dst),
+            ),
+            // Remote writes through the window require local writes to the memory region.
+            (
+                RdmaBindMw {
+                    access: RDMA_ACCESS_REMOTE_WRITE,
+                    ..bind
+                },
+                RdmaCmdError::MrNotWritable(bindable),
+            ),
+            (
+                RdmaBindMw {
+                    length: 0x1001,
+                    ..bind
+                },
+                RdmaCmdError::MwOutOfMrBounds(bindable, 0xa000, 0x1001),
+            ),
+        ];
+        for (bind, err) in cases {
+            assert_eq!(rdma.bind_mw(qpn, 1, &bind), Err(err));
+        }
+        rdma.bind_mw(qpn, 1, &bind).unwrap();
+        assert_eq!(
+            rdma.bind_mw(
+                qpn,
+                1,
+                &RdmaBindMw {
+                    mw_rkey: mw + 1,
+                    rkey: mw + 2,
+                    ..bind
+                }
+            ),
+            Err(RdmaCmdError::MwBound(mw + 1))
+        );
+        // Destroying the queue pair invalidates the windows it bound.
+        rdma.destroy_qp(RdmaCmdDestroyQp {
+            qpn,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            rdma.invalidate_mw(1, mw + 1, None),
+            Err(RdmaCmdError::MwNotBound(mw + 1))
+        );
+
+        // Failed binds and local invalidations complete in error, and move the queue pair to
+        // the error state. Unreliable datagram queue pairs don't bind memory windows.
+        let qpn = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let ud_qpn = ready_qp(&mut rdma, RDMA_QPT_UD, QpAttributes::default());
+        let local_inv = RdmaCmdPostSend {
+            wr_id: 1,
+            qpn,
+            opcode: RDMA_WR_LOCAL_INV,
+            imm_data: mw + 1,
+            ..Default::default()
+        };
+        let ud_bind = RdmaCmdPostSend {
+            qpn: ud_qpn,
+            opcode: RDMA_WR_BIND_MW,
+            ..Default::default()
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[
+                (RDMA_CMD_POST_SEND, local_inv.as_slice(), rsp_len::<()>()),
+                (
+                    RDMA_CMD_POST_SEND,
+                    &[ud_bind.as_slice(), bind.as_slice()].concat(),
+                    rsp_len::<()>(),
+                ),
+                (
+                    RDMA_CMD_POLL_CQ,
+                    poll_cq_args(1, 4).as_slice(),
+                    rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+                ),
+            ],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(responses[1], (RDMA_STATUS_INVALID_ARG, Vec::new()));
+        assert_eq!(
+            parse_wcs(&responses[2].1),
+            [RdmaWc {
+                wr_id: 1,
+                status: RDMA_WC_LOC_QP_OP_ERR,
+                opcode: RDMA_WC_LOCAL_INV,
+                qp_num: qpn,
+                ..Default::default()
+            }]
+        );
+        assert_eq!(rdma.qps.get(qpn).unwrap().state, RDMA_QPS_ERR);
+    }
+
+    #[test]
+    fn test_async_events() {
+        let mut rdma = activated_rdma("rdma-async-events");
+        let (src, _) = recv_mrs(&mut rdma);
+        // The events wait for buffers of the event queue.
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_EVENT_QUEUE] = vq.create_queue();
+        let event = |event_type: u32, handle: u32| RdmaAsyncEvent { event_type, handle };
+
+        // Losing a work completion raises an error on its completion queue and queue pair.
+        let cqn = rdma
+            .create_cq(RdmaCmdCreateCq {
+                cqe: 1,
+                ..Default::default()
+            })
+            .unwrap()
+            .cqn;
+        let qpn = rdma
+            .create_qp(RdmaCmdCreateQp {
+                send_cq: cqn,
+                recv_cq: cqn,
+                ..create_qp_cmd(RDMA_QPT_RC)
+            })
+            .unwrap()
+            .qpn;
+        rdma.qps.get_mut(qpn).unwrap().state = RDMA_QPS_RTS;
+        for _ in 0..3 {
+            rdma.complete(
+                cqn,
+                RdmaWc {
+                    qp_num: qpn,
+                    ..Default::default()
+                },
+                false,
+            );
+        }
+        assert_eq!(rdma.qps.get(qpn).unwrap().state, RDMA_QPS_ERR);
+        assert_eq!(
+            rdma.pending_events,
+            [
+                event(RDMA_EVENT_CQ_ERR, cqn),
+                event(RDMA_EVENT_QP_FATAL, qpn)
+            ]
+        );
+        rdma.pending_events.clear();
+
+        // The work requests of reliable connected queue pairs fail while the link is down.
+        rdma.set_link_up(false).unwrap();
+        rdma.set_link_up(false).unwrap();
+        assert_eq!(rdma.pending_events, [event(RDMA_EVENT_PORT_ERR, 1)]);
+        assert!(
+            rdma.interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Config)
+        );
+        let port = RdmaCmdQueryPort {
+            port_num: 1,
+            ..Default::default()
+        };
+        assert_eq!(rdma.query_port(port).unwrap().state, RDMA_PORT_DOWN);
+        let qpn = ready_qp(&mut rdma, RDMA_QPT_RC, QpAttributes::default());
+        let send = RdmaCmdPostSend {
+            qpn,
+            opcode: RDMA_WR_SEND,
+            num_sge: 1,
+            ..Default::default()
+        };
+        let sge = RdmaSge {
+            addr: 0x8000,
+            length: 16,
+            lkey: src,
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_SEND,
+                &post_send_args(send, &[sge]),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+        assert_eq!(
+            rdma.cqs.get(1).unwrap().completions[0].status,
+            RDMA_WC_RETRY_EXC_ERR
+        );
+        rdma.set_link_up(true).unwrap();
+        assert_eq!(
+            rdma.pending_events,
+            [
+                event(RDMA_EVENT_PORT_ERR, 1),
+                event(RDMA_EVENT_PORT_ACTIVE, 1)
+            ]
+        );
+        assert_eq!(rdma.query_port(port).unwrap().state, RDMA_PORT_ACTIVE);
+
+        // The pending events are written once the driver queues buffers.
+        vq.dtable[0].set(
+            0xd000,
+            u32::try_from(size_of::<RdmaAsyncEvent>()).unwrap(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+        rdma.deliver_events().unwrap();
+        assert_eq!(
+            mem.read_obj::<RdmaAsyncEvent>(GuestAddress(0xd000))
+                .unwrap(),
+            event(RDMA_EVENT_PORT_ERR, 1)
+        );
+        assert_eq!(rdma.pending_events, [event(RDMA_EVENT_PORT_ACTIVE, 1)]);
+
+        // Events past the capacity of the FIFO are dropped.
+        rdma.pending_events.clear();
+        for handle in 0..=u32::try_from(MAX_PENDING_EVENTS).unwrap() {
+            rdma.push_event(RDMA_EVENT_MR_INVALIDATED, handle);
+        }
+        assert_eq!(rdma.pending_events.len(), MAX_PENDING_EVENTS);
+        assert_eq!(rdma.metrics.event_drops.count(), 1);
+    }
+
+    #[test]
+    fn test_drain() {
+        // Draining an inactive device does nothing.
+        let mut rdma = VirtioRdma::new("rdma-drain".to_string()).unwrap();
+        rdma.drain();
+
+        let mut rdma = activated_rdma("rdma-drain");
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_CTRL_QUEUE] = vq.create_queue();
+        let hdr = RdmaCmdHdr {
+            opcode: RDMA_CMD_QUERY_DEVICE,
+            ..Default::default()
+        };
+        mem.write_obj(hdr, GuestAddress(0x1000)).unwrap();
+        let cmd_len = u32::try_from(size_of::<RdmaCmdHdr>()).unwrap();
+        vq.dtable[0].set(0x1000, cmd_len, VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(
+            0x1100,
+            rsp_len::<RdmaRspQueryDevice>(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+
+        // The queued command is executed.
+        rdma.drain();
+        assert_eq!(vq.used.idx.get(), 1);
+        let rsp: RdmaRspHdr = mem.read_obj(GuestAddress(0x1100)).unwrap();
+        assert_eq!(rsp.status, RDMA_STATUS_OK);
+    }
+
+    #[test]
+    fn test_unplug() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.as_path().join("rdma.sock");
+        let mut rdma = VirtioRdma::new("rdma-unplug".to_string()).unwrap();
+        rdma.set_backend(Some(RdmaBackendConfig::Unix(UnixBackendConfig {
+            socket_path: socket_path.clone(),
+            peers: Vec::new(),
+        })))
+        .unwrap();
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        create_cq(&mut rdma);
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_CTRL_QUEUE] = vq.create_queue();
+        let hdr = RdmaCmdHdr {
+            opcode: RDMA_CMD_QUERY_DEVICE,
+            ..Default::default()
+        };
+        mem.write_obj(hdr, GuestAddress(0x1000)).unwrap();
+        let cmd_len = u32::try_from(size_of::<RdmaCmdHdr>()).unwrap();
+        vq.dtable[0].set(0x1000, cmd_len, VIRTQ_DESC_F_NEXT, 1);
+        vq.dtable[1].set(
+            0x1100,
+            rsp_len::<RdmaRspQueryDevice>(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+
+        // The queued command is executed before the device stops, and its resources and backend
+        // are torn down.
+        rdma.unplug();
+        assert_eq!(vq.used.idx.get(), 1);
+        assert!(!rdma.is_activated());
+        assert!(rdma.cqs.is_empty());
+        assert_eq!(rdma.metrics.leaked_resources.count(), 1);
+        assert!(rdma.backend.events().is_empty());
+        assert!(
+            UnixDatagram::unbound()
+                .unwrap()
+                .send_to(b"", &socket_path)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_memory_removals() {
+        let mut rdma = activated_rdma("rdma-memory-removals");
+        let removals = GuestMemoryRemovals::default();
+        let listener = removals.subscribe().unwrap();
+        rdma.set_memory_removal_listener(listener.clone());
+        let reg = |iova: u64| RdmaCmdRegMr {
+            iova,
+            length: 0x1000,
+            access: RDMA_ACCESS_LOCAL_WRITE,
+            pd: 1,
+        };
+        let removed = rdma.reg_mr(reg(0x8000)).unwrap().lkey;
+        let kept = rdma.reg_mr(reg(0xa000)).unwrap().lkey;
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0xc000), &mem, 16);
+        rdma.queues[RDMA_EVENT_QUEUE] = vq.create_queue();
+
+        // Only the memory regions overlapping the removed range are invalidated.
+        removals.notify(GuestAddress(0x8800), 0x1000);
+        assert_eq!(listener.event().read().unwrap(), 1);
+        rdma.process_memory_removals().unwrap();
+        assert!(rdma.mrs.get(removed).unwrap().invalidated);
+        assert!(!rdma.mrs.get(kept).unwrap().invalidated);
+        assert_eq!(
+            rdma.check_local_access(1, removed, 0x8000, 0x10, false),
+            Err(RdmaCmdError::MrInvalidated(removed))
+        );
+        rdma.check_local_access(1, kept, 0xa000, 0x10, true)
+            .unwrap();
+        assert_eq!(rdma.metrics.mr_invalidations.count(), 1);
+
+        // The event waits for the driver to queue a buffer.
+        assert_eq!(vq.used.idx.get(), 0);
+        vq.dtable[0].set(
+            0xd000,
+            u32::try_from(size_of::<RdmaAsyncEvent>()).unwrap(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+        rdma.deliver_events().unwrap();
+        assert_eq!(vq.used.idx.get(), 1);
+        assert_eq!(
+            mem.read_obj::<RdmaAsyncEvent>(GuestAddress(0xd000))
+                .unwrap(),
+            RdmaAsyncEvent {
+                event_type: RDMA_EVENT_MR_INVALIDATED,
+                handle: removed,
+            }
+        );
+
+        // Memory regions are only invalidated once.
+        removals.notify(GuestAddress(0x8000), 0x100);
+        rdma.process_memory_removals().unwrap();
+        assert_eq!(rdma.metrics.mr_invalidations.count(), 1);
+        assert!(rdma.pending_events.is_empty());
+
+        // The driver may still deregister invalidated memory regions.
+        rdma.dereg_mr(RdmaCmdDeregMr {
+            lkey: removed,
+            ..Default::default()
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_chains() {
+        let mut rdma = activated_rdma("rdma-chains");
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_CTRL_QUEUE] = vq.create_queue();
+
+        // A command without a response descriptor, one whose response descriptor is read-only,
+        // a truncated header and a response descriptor too small for the response header.
+        vq.dtable[0].set(0x1000, 8, 0, 0);
+        vq.dtable[1].set(0x2000, 8, VIRTQ_DESC_F_NEXT, 2);
+        vq.dtable[2].set(0x2100, 8, 0, 0);
+        vq.dtable[3].set(0x3000, 4, VIRTQ_DESC_F_NEXT, 4);
+        vq.dtable[4].set(0x3100, 8, VIRTQ_DESC_F_WRITE, 0);
+        vq.dtable[5].set(0x4000, 8, VIRTQ_DESC_F_NEXT, 6);
+        vq.dtable[6].set(0x4100, 4, VIRTQ_DESC_F_WRITE, 0);
+        for (i, head) in [0, 1, 3, 5].into_iter().enumerate() {
+            vq.avail.ring[i].set(head);
+        }
+        vq.avail.idx.set(4);
+        rdma.process_cmd_queue(RDMA_CTRL_QUEUE).unwrap();
+
+        assert_eq!(vq.used.idx.get(), 4);
+        for i in 0..4 {
+            assert_eq!(vq.used.ring[i].get().len, 0);
+        }
+        assert_eq!(rdma.metrics.event_fails.count(), 4);
+    }
+
+    #[test]
+    fn test_fragmented_chains() {
+        let mut rdma = activated_rdma("rdma-fragmented-chains");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let sender = ready_qp(&mut rdma, RDMA_QPT_UC, QpAttributes::default());
+        let receiver = ready_qp(
+            &mut rdma,
+            RDMA_QPT_UC,
+            QpAttributes {
+                dest_qp_num: sender,
+                ..Default::default()
+            },
+        );
+        let qp = rdma.qps.get_mut(sender).unwrap();
+        qp.attrs.dest_qp_num = receiver;
+        qp.max_send_sge = RDMA_MAX_SGE;
+        let recv_sge = RdmaSge {
+            addr: 0xa000,
+            length: 64,
+            lkey: dst,
+        };
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POST_RECV,
+                &post_recv_args(1, receiver, &[recv_sge]),
+                rsp_len::<()>(),
+            )],
+        );
+        assert_eq!(responses[0], (RDMA_STATUS_OK, Vec::new()));
+
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_CTRL_QUEUE] = vq.create_queue();
+        let hdr = |opcode: u32| RdmaCmdHdr {
+            opcode,
+            ..Default::default()
+        };
+        mem.write_slice(&[0xff; 0x400], GuestAddress(0x2000))
+            .unwrap();
+
+        // A send with as many scatter/gather entries as the device allows, whose header,
+        // arguments and entries are split across descriptors, as is its response.
+        let sges: Vec<RdmaSge> = (0..u64::from(RDMA_MAX_SGE))
+            .map(|i| RdmaSge {
+                addr: 0x8000 + 4 * i,
+                length: 4,
+                lkey: src,
+            })
+            .collect();
+        let send = RdmaCmdPostSend {
+            qpn: sender,
+            opcode: RDMA_WR_SEND,
+            num_sge: RDMA_MAX_SGE,
+            ..Default::default()
+        };
+        let cmd = [
+            hdr(RDMA_CMD_POST_SEND).as_slice(),
+            &post_send_args(send, &sges),
+        ]
+        .concat();
+        let bounds = [0, 3, 13, 100, cmd.len()];
+        for (i, range) in bounds.windows(2).enumerate() {
+            let i = u16::try_from(i).unwrap();
+            let addr = 0x1000 + 0x400 * u64::from(i);
+            mem.write_slice(&cmd[range[0]..range[1]], GuestAddress(addr))
+                .unwrap();
+            let len = u32::try_from(range[1] - range[0]).unwrap();
+            vq.dtable[usize::from(i)].set(addr, len, VIRTQ_DESC_F_NEXT, i + 1);
+        }
+        vq.dtable[4].set(0x2000, 3, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 5);
+        vq.dtable[5].set(0x2100, 5, VIRTQ_DESC_F_WRITE, 0);
+        // A response split in the middle of its result.
+        mem.write_slice(hdr(RDMA_CMD_QUERY_DEVICE).as_slice(), GuestAddress(0x3000))
+            .unwrap();
+        let query_len = rsp_len::<RdmaRspQueryDevice>();
+        vq.dtable[6].set(0x3000, 8, VIRTQ_DESC_F_NEXT, 7);
+        vq.dtable[7].set(0x2200, 13, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 8);
+        vq.dtable[8].set(0x2300, query_len - 13, VIRTQ_DESC_F_WRITE, 0);
+        // A device-readable descriptor can't follow the device-writable ones.
+        vq.dtable[9].set(0x3000, 8, VIRTQ_DESC_F_NEXT, 10);
+        vq.dtable[10].set(0x2380, 8, VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_NEXT, 11);
+        vq.dtable[11].set(0x3000, 8, 0, 0);
+        for (i, head) in [0, 6, 9].into_iter().enumerate() {
+            vq.avail.ring[i].set(head);
+        }
+        vq.avail.idx.set(3);
+        rdma.process_cmd_queue(RDMA_CTRL_QUEUE).unwrap();
+
+        assert_eq!(vq.used.idx.get(), 3);
+        assert_eq!(vq.used.ring[0].get().len, rsp_len::<()>());
+        let mut rsp = [0xffu8; 8];
+        mem.read_slice(&mut rsp[..3], GuestAddress(0x2000)).unwrap();
+        mem.read_slice(&mut rsp[3..], GuestAddress(0x2100)).unwrap();
+        assert_eq!(rsp, [0u8; 8]);
+        let mut received = [0u8; 64];
+        mem.read_slice(&mut received, GuestAddress(0xa000)).unwrap();
+        assert_eq!(received.to_vec(), (0..64).collect::<Vec<u8>>());
+        assert_eq!(rdma.cqs.get(1).unwrap().completions[0].byte_len, 64);
+
+        assert_eq!(vq.used.ring[1].get().len, query_len);
+        let mut rsp = vec![0u8; usize::try_from(query_len).unwrap()];
+        mem.read_slice(&mut rsp[..13], GuestAddress(0x2200))
+            .unwrap();
+        mem.read_slice(&mut rsp[13..], GuestAddress(0x2300))
+            .unwrap();
+        assert_eq!(
+            u32::from_le_bytes(rsp[..4].try_into().unwrap()),
+            RDMA_STATUS_OK
+        );
+        assert_eq!(
+            *RdmaRspQueryDevice::from_slice(&rsp[size_of::<RdmaRspHdr>()..]).unwrap(),
+            RdmaRspQueryDevice::from(&rdma.caps)
+        );
+
+        assert_eq!(vq.used.ring[2].get().len, 0);
+        assert_eq!(rdma.metrics.event_fails.count(), 1);
+    }
+
+    #[test]
+    fn test_data_queues() {
+        let mut rdma = activated_rdma("rdma-data-queues");
+        let (src, dst) = recv_mrs(&mut rdma);
+        let attrs = |dest_qp_num: u32| QpAttributes {
+            dest_qp_num,
+            ..Default::default()
+        };
+        let sender = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+        let receiver = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(sender));
+        let recv = post_recv_args(
+            1,
+            receiver,
+            &[RdmaSge {
+                addr: 0xa000,
+                length: 16,
+                lkey: dst,
+            }],
+        );
+        let send = post_send_args(
+            RdmaCmdPostSend {
+                wr_id: 2,
+                qpn: sender,
+                opcode: RDMA_WR_SEND,
+                send_flags: RDMA_SEND_SIGNALED,
+                num_sge: 1,
+                ..Default::default()
+            },
+            &[RdmaSge {
+                addr: 0x8000,
+                length: 16,
+                lkey: src,
+            }],
+        );
+
+        // The data queues only take the commands posting their work requests.
+        let responses = run_queue_commands(
+            &mut rdma,
+            RDMA_RECV_QUEUE,
+            &[
+                (RDMA_CMD_POST_RECV, &recv, rsp_len::<()>()),
+                (RDMA_CMD_POST_SEND, &send, rsp_len::<()>()),
+                (RDMA_CMD_QUERY_DEVICE, &[], rsp_len::<RdmaRspQueryDevice>()),
+            ],
+        );
+        assert_eq!(
+            responses,
+            [
+                (RDMA_STATUS_OK, Vec::new()),
+                (RDMA_STATUS_UNSUPPORTED, Vec::new()),
+                (RDMA_STATUS_UNSUPPORTED, Vec::new()),
+            ]
+        );
+        let responses = run_queue_commands(
+            &mut rdma,
+            RDMA_SEND_QUEUE,
+            &[
+                (RDMA_CMD_POST_SEND, &send, rsp_len::<()>()),
+                (RDMA_CMD_POST_RECV, &recv, rsp_len::<()>()),
+            ],
+        );
+        assert_eq!(
+            responses,
+            [
+                (RDMA_STATUS_OK, Vec::new()),
+                (RDMA_STATUS_UNSUPPORTED, Vec::new()),
+            ]
+        );
+
+        let responses = run_commands(
+            &mut rdma,
+            &[(
+                RDMA_CMD_POLL_CQ,
+                poll_cq_args(1, 4).as_slice(),
+                rsp_len::<RdmaRspPollCq>() + 4 * u32::try_from(size_of::<RdmaWc>()).unwrap(),
+            )],
+        );
+        let wcs: Vec<_> = parse_wcs(&responses[0].1)
+            .iter()
+            .map(|wc| (wc.wr_id, wc.status, wc.opcode))
+            .collect();
+        assert_eq!(
+            wcs,
+            [
+                (1, RDMA_WC_SUCCESS, RDMA_WC_RECV),
+                (2, RDMA_WC_SUCCESS, RDMA_WC_SEND)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        // An ops limiter allowing a send work request, and a bandwidth limiter allowing 16 bytes,
+        // both replenished after 100ms.
+        for (id, rate_limiter) in [
+            (
+                "rdma-ops-limiter",
+                RateLimiter::new(0, 0, 0, 1, 0, 100).unwrap(),
+            ),
+            (
+                "rdma-bytes-limiter",
+                RateLimiter::new(16, 0, 100, 0, 0, 0).unwrap(),
+            ),
+        ] {
+            let mut rdma = activated_rdma(id);
+            rdma.set_rate_limiter(rate_limiter);
+            let (src, dst) = recv_mrs(&mut rdma);
+            let attrs = |dest_qp_num: u32| QpAttributes {
+                dest_qp_num,
+                ..Default::default()
+            };
+            let sender = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(2));
+            let receiver = ready_qp(&mut rdma, RDMA_QPT_RC, attrs(sender));
+            let recv = post_recv_args(
+                1,
+                receiver,
+                &[RdmaSge {
+                    addr: 0xa000,
+                    length: 16,
+                    lkey: dst,
+                }],
+            );
+            let send = post_send_args(
+                RdmaCmdPostSend {
+                    wr_id: 2,
+                    qpn: sender,
+                    opcode: RDMA_WR_SEND,
+                    send_flags: RDMA_SEND_SIGNALED,
+                    num_sge: 1,
+                    ..Default::default()
+                },
+                &[RdmaSge {
+                    addr: 0x8000,
+                    length: 16,
+                    lkey: src,
+                }],
+            );
+
+            // The commands other than the send work requests are not limited.
+            run_queue_commands(
+                &mut rdma,
+                RDMA_RECV_QUEUE,
+                &[
+                    (RDMA_CMD_POST_RECV, &recv, rsp_len::<()>()),
+                    (RDMA_CMD_POST_RECV, &recv, rsp_len::<()>()),
+                ],
+            );
+            // The second send work request stops the queue.
+            let mem = rdma.mem().clone();
+            let vq = queue_commands(
+                &mut rdma,
+                &mem,
+                RDMA_SEND_QUEUE,
+                &[
+                    (RDMA_CMD_POST_SEND, &send, rsp_len::<()>()),
+                    (RDMA_CMD_POST_SEND, &send, rsp_len::<()>()),
+                ],
+            );
+            rdma.process_cmd_queue(RDMA_SEND_QUEUE).unwrap();
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(rdma.throttled_queues, BTreeSet::from([RDMA_SEND_QUEUE]));
+            assert_eq!(rdma.metrics.rate_limiter_throttled.count(), 1);
+            assert_eq!(rdma.metrics.send_wr_count.count(), 1);
+            rdma.process_rate_limiter().unwrap();
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(rdma.metrics.rate_limiter_throttled.count(), 2);
+
+            // The queue resumes once the rate limiter replenished.
+            std::thread::sleep(Duration::from_millis(100));
+            rdma.process_rate_limiter().unwrap();
+            assert_eq!(vq.used.idx.get(), 2);
+            assert!(rdma.throttled_queues.is_empty());
+            assert_eq!(rdma.metrics.rate_limiter_throttled.count(), 2);
+            assert_eq!(rdma.metrics.send_wr_count.count(), 2);
+            assert_eq!(rdma.cqs.get(1).unwrap().completions.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_num_queues() {
+        let mut rdma = VirtioRdma::new("rdma-num-queues".to_string()).unwrap();
+        assert_eq!(rdma.num_queues(), 1);
+        assert_eq!(rdma.configured_num_queues(), None);
+        // Without a configured number, there is a data queue pair per vCPU.
+        rdma.set_vcpu_count(4).unwrap();
+        assert_eq!(rdma.num_queues(), 4);
+        assert_eq!(rdma.queues().len(), rdma_num_queues(4));
+        assert_eq!(rdma.queue_events().len(), rdma_num_queues(4));
+        let mut config = [0u8; 4];
+        rdma.read_config(0, &mut config);
+        assert_eq!(u32::from_le_bytes(config), 4);
+        rdma.read_config(
+            u64::try_from(size_of::<RdmaConfigSpace>()).unwrap() + 4,
+            &mut config,
+        );
+        assert_eq!(rdma.metrics.cfg_fails.count(), 1);
+
+        rdma.set_num_queues(Some(2)).unwrap();
+        rdma.set_vcpu_count(8).unwrap();
+        assert_eq!(rdma.num_queues(), 2);
+        assert_eq!(rdma.configured_num_queues(), Some(2));
+        assert!(matches!(
+            rdma.set_num_queues(Some(0)),
+            Err(RdmaError::InvalidNumQueues(0))
+        ));
+        assert!(matches!(
+            rdma.set_num_queues(Some(RDMA_MAX_NUM_QUEUES + 1)),
+            Err(RdmaError::InvalidNumQueues(_))
+        ));
+        assert_eq!(rdma.num_queues(), 2);
+
+        // The queues of each data queue pair take the commands of their work requests.
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        let responses = run_queue_commands(
+            &mut rdma,
+            RDMA_SEND_QUEUE + 2,
+            &[(RDMA_CMD_POST_RECV, &[], rsp_len::<()>())],
+        );
+        assert_eq!(responses, [(RDMA_STATUS_UNSUPPORTED, Vec::new())]);
+        let responses = run_queue_commands(
+            &mut rdma,
+            RDMA_RECV_QUEUE + 2,
+            &[(RDMA_CMD_POST_SEND, &[], rsp_len::<()>())],
+        );
+        assert_eq!(responses, [(RDMA_STATUS_UNSUPPORTED, Vec::new())]);
+    }
+
+    #[test]
+    fn test_config_space() {
+        let mut rdma = VirtioRdma::new("rdma-config-space".to_string()).unwrap();
+        let mut config = RdmaConfigSpace::default();
+        rdma.read_config(0, config.as_mut_slice());
+        assert_eq!(
+            config,
+            RdmaConfigSpace {
+                num_queues: 1,
+                max_qp: RDMA_MAX_QP,
+                max_cq: RDMA_MAX_CQ,
+                max_mr: RDMA_MAX_MR,
+                max_pd: RDMA_MAX_PD,
+                max_ah: RDMA_MAX_AH,
+                max_sge: RDMA_MAX_SGE,
+                port_state: RDMA_PORT_ACTIVE,
+                active_mtu: RDMA_MTU_1024,
+                node_guid: rdma.node_guid().to_be_bytes(),
+            }
+        );
+        // Reads may start at any offset.
+        let mut node_guid = [0u8; 8];
+        let offset = size_of::<RdmaConfigSpace>() - node_guid.len();
+        rdma.read_config(u64::try_from(offset).unwrap(), &mut node_guid);
+        assert_eq!(u64::from_be_bytes(node_guid), rdma.node_guid());
+
+        // The driver may not write the configuration space.
+        rdma.write_config(0, &[0xff; 4]);
+        assert_eq!(rdma.metrics.cfg_fails.count(), 1);
+        let mut num_queues = [0u8; 4];
+        rdma.read_config(0, &mut num_queues);
+        assert_eq!(u32::from_le_bytes(num_queues), 1);
+
+        rdma.set_mtu(Some(4096)).unwrap();
+        rdma.set_link_up(false).unwrap();
+        rdma.read_config(0, config.as_mut_slice());
+        assert_eq!(config.active_mtu, RDMA_MTU_4096);
+        assert_eq!(config.port_state, RDMA_PORT_DOWN);
+        assert_eq!(rdma.configured_mtu(), Some(4096));
+        assert!(matches!(
+            rdma.set_mtu(Some(1500)),
+            Err(RdmaError::InvalidMtu(1500))
+        ));
+        assert!(matches!(
+            rdma.set_mtu(Some(8192)),
+            Err(RdmaError::InvalidMtu(8192))
+        ));
+        rdma.set_mtu(None).unwrap();
+        assert_eq!(rdma.port_attributes().active_mtu, RDMA_MTU_1024);
+    }
+
+    #[test]
+    fn test_node_guid() {
+        // The node GUID is a stable, locally administered and individual EUI-64.
+        let guid = node_guid("rdma0");
+        assert_eq!(guid, node_guid("rdma0"));
+        assert_ne!(guid, node_guid("rdma1"));
+        assert_eq!(guid.to_be_bytes()[0] & 0x03, 0x02);
+        assert_eq!(
+            VirtioRdma::new("rdma0".to_string()).unwrap().node_guid(),
+            guid
+        );
+
+        assert_eq!(mtu_value(256), Some(1));
+        assert_eq!(mtu_value(1024), Some(RDMA_MTU_1024));
+        assert_eq!(mtu_value(4096), Some(RDMA_MTU_4096));
+        assert_eq!(mtu_value(128), None);
+        assert_eq!(mtu_value(1500), None);
+    }
+
+    #[test]
+    fn test_notification_suppression() {
+        let mut rdma = VirtioRdma::new("rdma-event-idx".to_string()).unwrap();
+        rdma.set_acked_features(rdma.avail_features() & (1 << VIRTIO_RING_F_EVENT_IDX));
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        assert!(
+            rdma.queues()
+                .iter()
+                .all(|queue| queue.uses_notif_suppression)
+        );
+
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_CTRL_QUEUE] = vq.create_queue();
+        rdma.queues[RDMA_CTRL_QUEUE].enable_notif_suppression();
+        let hdr = RdmaCmdHdr {
+            opcode: RDMA_CMD_QUERY_DEVICE,
+            ..Default::default()
+        };
+        mem.write_obj(hdr, GuestAddress(0x1000)).unwrap();
+        vq.dtable[0].set(
+            0x1000,
+            u32::try_from(size_of::<RdmaCmdHdr>()).unwrap(),
+            VIRTQ_DESC_F_NEXT,
+            1,
+        );
+        vq.dtable[1].set(
+            0x1100,
+            rsp_len::<RdmaRspQueryDevice>(),
+            VIRTQ_DESC_F_WRITE,
+            0,
+        );
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+        // The driver polls the used ring, and only waits for the buffers past the first one.
+        vq.avail.event.set(1);
+        rdma.process_cmd_queue(RDMA_CTRL_QUEUE).unwrap();
+
+        assert_eq!(vq.used.idx.get(), 1);
+        assert!(
+            !rdma
+                .interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Queue(0))
+        );
+        // Having run out of commands, the device asks to be kicked for the next one.
+        assert_eq!(vq.used.event.get(), 1);
+    }
+
+    #[test]
+    fn test_packed_ring() {
+        let mut rdma = VirtioRdma::new("rdma-packed".to_string()).unwrap();
+        rdma.set_acked_features(rdma.avail_features());
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        assert!(rdma.queues().iter().all(|queue| queue.uses_packed_ring));
+
+        let mem = rdma.mem().clone();
+        let mut queue = Queue::new(16);
+        queue.ready = true;
+        queue.avail_ring_address = GuestAddress(0x100);
+        queue.used_ring_address = GuestAddress(0x200);
+        queue.enable_packed_ring();
+        queue.enable_notif_suppression();
+        queue.initialize(&mem).unwrap();
+        rdma.queues[RDMA_CTRL_QUEUE] = queue;
+
+        // The command and response descriptors follow each other in the ring, the wrap counters
+        // starting at 1.
+        let hdr = RdmaCmdHdr {
+            opcode: RDMA_CMD_QUERY_DEVICE,
+            ..Default::default()
+        };
+        mem.write_obj(hdr, GuestAddress(0x1000)).unwrap();
+        let rsp_desc = PackedDescriptor {
+            addr: 0x1100,
+            len: rsp_len::<RdmaRspQueryDevice>(),
+            id: 3,
+            flags: VIRTQ_DESC_F_WRITE | VIRTQ_DESC_F_AVAIL,
+        };
+        mem.write_obj(rsp_desc, GuestAddress(16)).unwrap();
+        let cmd_desc = PackedDescriptor {
+            addr: 0x1000,
+            len: u32::try_from(size_of::<RdmaCmdHdr>()).unwrap(),
+            id: 3,
+            flags: VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_AVAIL,
+        };
+        mem.write_obj(cmd_desc, GuestAddress(0)).unwrap();
+        rdma.process_cmd_queue(RDMA_CTRL_QUEUE).unwrap();
+
+        let used: PackedDescriptor = mem.read_obj(GuestAddress(0)).unwrap();
+        assert_eq!(
+            (used.id, used.len, used.flags),
+            (
+                3,
+                rsp_len::<RdmaRspQueryDevice>(),
+                VIRTQ_DESC_F_AVAIL | VIRTQ_DESC_F_USED
+            )
+        );
+        assert_eq!(
+            mem.read_obj::<u32>(GuestAddress(0x1100)).unwrap(),
+            RDMA_STATUS_OK
+        );
+        assert!(
+            rdma.interrupt_trigger()
+                .has_pending_interrupt(VirtioInterruptType::Queue(0))
+        );
+        // Having run out of commands, the device asks to be notified of the next one.
+        assert_eq!(
+            mem.read_obj::<u16>(GuestAddress(0x200)).unwrap(),
+            2 | 1 << 15
+        );
+        assert_eq!(
+            mem.read_obj::<u16>(GuestAddress(0x202)).unwrap(),
+            RING_EVENT_FLAGS_DESC
+        );
+    }
+
+    #[test]
+    fn test_doorbells() {
+        let mut rdma = activated_rdma("rdma-doorbells");
+        let shm_regions = rdma.shm_regions();
+        assert_eq!(shm_regions.len(), 1);
+        assert_eq!(shm_regions[0].0, RDMA_DOORBELL_REGION_LEN);
+        let qpn = rdma.create_qp(create_qp_cmd(RDMA_QPT_RC)).unwrap().qpn;
+
+        let mem = rdma.mem().clone();
+        let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        rdma.queues[RDMA_SEND_QUEUE] = vq.create_queue();
+        let hdr = RdmaCmdHdr {
+            opcode: RDMA_CMD_POST_SEND,
+            ..Default::default()
+        };
+        mem.write_obj(hdr, GuestAddress(0x1000)).unwrap();
+        vq.dtable[0].set(
+            0x1000,
+            u32::try_from(size_of::<RdmaCmdHdr>()).unwrap(),
+            VIRTQ_DESC_F_NEXT,
+            1,
+        );
+        vq.dtable[1].set(0x1100, rsp_len::<()>(), VIRTQ_DESC_F_WRITE, 0);
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+
+        // Doorbells of a queue pair the device doesn't have, or of a queue which isn't a data
+        // queue, are ignored.
+        let send_queue = u32::try_from(RDMA_SEND_QUEUE).unwrap();
+        let doorbell = |qpn: u32| u64::from(qpn) * RDMA_DOORBELL_SIZE;
+        shm_regions[0]
+            .1
+            .write(0, doorbell(qpn + 1), &send_queue.to_le_bytes());
+        shm_regions[0].1.write(
+            0,
+            doorbell(qpn),
+            &u32::try_from(RDMA_CTRL_QUEUE).unwrap().to_le_bytes(),
+        );
+        rdma.process_doorbells().unwrap();
+        assert_eq!(rdma.metrics.doorbell_count.count(), 2);
+        assert_eq!(rdma.metrics.doorbell_fails.count(), 2);
+        assert_eq!(vq.used.idx.get(), 0);
+
+        // Ringing the doorbell of the queue pair processes the send queue.
+        shm_regions[0]
+            .1
+            .write(0, doorbell(qpn), &send_queue.to_le_bytes());
+        rdma.process_doorbells().unwrap();
+        assert_eq!(rdma.metrics.doorbell_count.count(), 3);
+        assert_eq!(rdma.metrics.doorbell_fails.count(), 2);
+        assert_eq!(vq.used.idx.get(), 1);
+    }
+
+    #[test]
+    fn test_reset_leaked_resources() {
+        let mut rdma = VirtioRdma::new("rdma-reset".to_string()).unwrap();
+        // An inactive device can't be reset.
+        assert!(rdma.reset().is_none());
+
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        create_cq(&mut rdma);
+        rdma.alloc_pd().unwrap();
+        for _ in 0..3 {
+            rdma.create_qp(create_qp_cmd(RDMA_QPT_RC)).unwrap();
+        }
+        rdma.reg_mr(RdmaCmdRegMr {
+            length: 0x1000,
+            pd: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        rdma.create_ah(RdmaCmdCreateAh {
+            pd: 1,
+            port_num: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        rdma.alloc_mw(RdmaCmdAllocMw {
+            pd: 1,
+            mw_type: RDMA_MW_TYPE_2,
+        })
+        .unwrap();
+        let (_interrupt, queue_events) = rdma.reset().unwrap();
+        assert_eq!(queue_events.len(), rdma_num_queues(1));
+        assert!(!rdma.is_activated());
+        assert!(rdma.qps.is_empty());
+        assert!(rdma.cqs.is_empty());
+        assert!(rdma.mrs.is_empty());
+        assert!(rdma.mws.is_empty());
+        assert!(rdma.pds.is_empty());
+        assert!(rdma.ahs.is_empty());
+        assert_eq!(rdma.metrics.leaked_resources.count(), 8);
+
+        // Resource numbers start over after the reset.
+        rdma.activate(default_mem(), default_interrupt()).unwrap();
+        assert_eq!(create_cq(&mut rdma), 1);
+        assert_eq!(rdma.alloc_pd().unwrap().pdn, 1);
+        assert_eq!(rdma.create_qp(create_qp_cmd(RDMA_QPT_RC)).unwrap().qpn, 1);
+    }
 }
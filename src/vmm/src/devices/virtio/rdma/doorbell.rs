@@ -0,0 +1,90 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::{Arc, Barrier, Mutex};
+
+use vmm_sys_util::eventfd::EventFd;
+
+use super::RDMA_DOORBELL_SIZE;
+use crate::logger::{error, warn};
+use crate::utils::byte_order;
+use crate::vstate::bus::BusDeviceSync;
+
+/// Doorbells of the queue pairs, in the shared memory region of the device. The doorbell of a
+/// queue pair is the page whose index is its number, so that the driver can map it in the
+/// process owning the queue pair. Writing to it the index of the data queue holding the work
+/// requests of the queue pair rings it, and the device processes the queue from its event loop.
+#[derive(Debug)]
+pub struct RdmaDoorbells {
+    // Queue pair numbers and data queue indexes of the doorbells rung since they were taken.
+    rung: Mutex<BTreeSet<(u32, u32)>>,
+    event: EventFd,
+}
+
+impl RdmaDoorbells {
+    /// Creates the doorbells, none of them rung.
+    pub fn new() -> Result<Self, io::Error> {
+        Ok(Self {
+            rung: Mutex::new(BTreeSet::new()),
+            event: EventFd::new(libc::EFD_NONBLOCK)?,
+        })
+    }
+
+    /// Event signaled when a doorbell is rung.
+    pub fn event(&self) -> &EventFd {
+        &self.event
+    }
+
+    /// Takes the doorbells rung since the last call, as queue pair numbers and data queue
+    /// indexes.
+    pub fn take_rung(&self) -> BTreeSet<(u32, u32)> {
+        std::mem::take(&mut self.rung.lock().expect("Poisoned lock"))
+    }
+}
+
+impl BusDeviceSync for RdmaDoorbells {
+    fn write(&self, base: u64, offset: u64, data: &[u8]) -> Option<Arc<Barrier>> {
+        if data.len() != 4 {
+            warn!(
+                "rdma: Invalid doorbell write: {base:#x}:{offset:#x}:{:#x}",
+                data.len()
+            );
+            return None;
+        }
+        let qpn = u32::try_from(offset / RDMA_DOORBELL_SIZE).unwrap();
+        let queue_index = byte_order::read_le_u32(data);
+        self.rung
+            .lock()
+            .expect("Poisoned lock")
+            .insert((qpn, queue_index));
+        if let Err(err) = self.event.write(1) {
+            error!("rdma: Failed to signal doorbell event: {err}");
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doorbells() {
+        let doorbells = RdmaDoorbells::new().unwrap();
+        assert!(doorbells.take_rung().is_empty());
+
+        doorbells.write(0, 2 * RDMA_DOORBELL_SIZE, &3u32.to_le_bytes());
+        doorbells.write(0, 2 * RDMA_DOORBELL_SIZE + 8, &3u32.to_le_bytes());
+        doorbells.write(0, 5 * RDMA_DOORBELL_SIZE, &4u32.to_le_bytes());
+        // Only 4-byte writes ring the doorbells.
+        doorbells.write(0, 6 * RDMA_DOORBELL_SIZE, &[3]);
+        assert_eq!(doorbells.event().read().unwrap(), 3);
+        assert_eq!(
+            doorbells.take_rung().into_iter().collect::<Vec<_>>(),
+            [(2, 3), (5, 4)]
+        );
+        assert!(doorbells.take_rung().is_empty());
+    }
+}
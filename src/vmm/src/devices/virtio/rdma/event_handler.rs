@@ -4,22 +4,106 @@
 use event_manager::{EventOps, Events, MutEventSubscriber};
 use vmm_sys_util::epoll::EventSet;
 
-use super::{RDMA_QUEUE, VirtioRdma};
+use super::{RDMA_CQ_QUEUE, RDMA_CTRL_QUEUE, RDMA_EVENT_QUEUE, RDMA_SEND_QUEUE, VirtioRdma};
 use crate::devices::virtio::device::VirtioDevice;
-use crate::logger::{error, warn};
+use crate::logger::{IncMetric, error, warn};
 
 impl VirtioRdma {
     const PROCESS_ACTIVATE: u32 = 0;
-    const PROCESS_RDMA_QUEUE: u32 = 1;
+    const PROCESS_CTRL_QUEUE: u32 = 1;
+    const PROCESS_CQ_QUEUE: u32 = 2;
+    const PROCESS_EVENT_QUEUE: u32 = 3;
+    const PROCESS_MEMORY_REMOVAL: u32 = 4;
+    const PROCESS_DOORBELL: u32 = 5;
+    const PROCESS_CQ_MODERATION: u32 = 6;
+    const PROCESS_BACKEND: u32 = 7;
+    const PROCESS_RATE_LIMITER: u32 = 8;
+    const PROCESS_BACKEND_TIMER: u32 = 9;
+    // The events of the data queues follow, in the order of the queues.
+    const PROCESS_DATA_QUEUE: u32 = 10;
 
     fn register_runtime_events(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
-            &self.queue_events()[RDMA_QUEUE],
-            Self::PROCESS_RDMA_QUEUE,
+            &self.queue_events()[RDMA_CTRL_QUEUE],
+            Self::PROCESS_CTRL_QUEUE,
             EventSet::IN,
         )) {
-            error!("rdma: Failed to register queue event: {err}");
+            error!("rdma: Failed to register control queue event: {err}");
         }
+        for (data, queue_event) in
+            (Self::PROCESS_DATA_QUEUE..).zip(&self.queue_events()[RDMA_SEND_QUEUE..])
+        {
+            if let Err(err) = ops.add(Events::with_data(queue_event, data, EventSet::IN)) {
+                error!("rdma: Failed to register data queue event: {err}");
+            }
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.queue_events()[RDMA_CQ_QUEUE],
+            Self::PROCESS_CQ_QUEUE,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register completion queue event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.queue_events()[RDMA_EVENT_QUEUE],
+            Self::PROCESS_EVENT_QUEUE,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register event queue event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            self.doorbell_event(),
+            Self::PROCESS_DOORBELL,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register doorbell event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.cq_moderation_timer,
+            Self::PROCESS_CQ_MODERATION,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register completion moderation timer event: {err}");
+        }
+        // The events of the backend are registered under a nested epoll FD, so that the backend
+        // can be replaced without registering them here again.
+        if let Err(err) = ops.add(Events::with_data_raw(
+            self.backend_event(),
+            Self::PROCESS_BACKEND,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register backend event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.rate_limiter,
+            Self::PROCESS_RATE_LIMITER,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register rate limiter event: {err}");
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.backend_timer,
+            Self::PROCESS_BACKEND_TIMER,
+            EventSet::IN,
+        )) {
+            error!("rdma: Failed to register backend timer event: {err}");
+        }
+        if let Some(listener) = &self.memory_removals
+            && let Err(err) = ops.add(Events::with_data(
+                listener.event(),
+                Self::PROCESS_MEMORY_REMOVAL,
+                EventSet::IN,
+            ))
+        {
+            error!("rdma: Failed to register memory removal event: {err}");
+        }
+    }
+
+    // Returns the index of the data queue whose event is identified by `source`.
+    fn data_queue_index(&self, source: u32) -> Option<usize> {
+        let offset = source.checked_sub(Self::PROCESS_DATA_QUEUE)? as usize;
+        let queue_index = RDMA_SEND_QUEUE + offset;
+        (queue_index < self.queue_events().len()).then_some(queue_index)
     }
 
     fn register_activate_event(&self, ops: &mut EventOps) {
@@ -48,10 +132,115 @@ impl VirtioRdma {
         }
     }
 
-    fn process_queue_event(&mut self) {
-        if let Err(err) = self.queue_events()[RDMA_QUEUE].read() {
-            error!("rdma: Failed to read queue event: {err}");
+    fn process_cmd_queue_event(&mut self, queue_index: usize) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events()[queue_index].read() {
+            error!("rdma: Failed to read event of command queue {queue_index}: {err}");
+            self.metrics.event_fails.inc();
+            return;
         }
+
+        self.process_cmd_queue(queue_index).unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_cq_queue_event(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events()[RDMA_CQ_QUEUE].read() {
+            error!("rdma: Failed to read completion queue event: {err}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        // The driver queued buffers, which may receive pending work completions.
+        self.deliver_completions().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_event_queue_event(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.queue_events()[RDMA_EVENT_QUEUE].read() {
+            error!("rdma: Failed to read event queue event: {err}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        // The driver queued buffers, which may receive pending asynchronous events.
+        self.deliver_events().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_doorbell_event(&mut self) {
+        self.metrics.queue_event_count.inc();
+        if let Err(err) = self.doorbell_event().read() {
+            error!("rdma: Failed to read doorbell event: {err}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.process_doorbells().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_cq_moderation_event(&mut self) {
+        // Disarming the timer, once the completions were signaled, drops its expiration.
+        if self.cq_moderation_timer.read() != 0 {
+            self.flush_completions();
+        }
+    }
+
+    fn process_backend_event(&mut self) {
+        // The backend consumes its own events, along with the host I/O it reports, which leaves
+        // the nested epoll FD without readable events.
+        self.process_backend().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_backend_timer_event(&mut self) {
+        if self.backend_timer.read() != 0 {
+            self.process_backend_timer();
+        }
+    }
+
+    fn process_rate_limiter_event(&mut self) {
+        self.metrics.rate_limiter_event_count.inc();
+        if let Err(err) = self.rate_limiter.event_handler() {
+            error!("rdma: Failed to handle rate limiter event: {err:?}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        // The rate limiter replenished, which may allow the throttled work requests.
+        self.process_rate_limiter().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
+    }
+
+    fn process_memory_removal_event(&mut self) {
+        let Some(listener) = &self.memory_removals else {
+            return;
+        };
+        if let Err(err) = listener.event().read() {
+            error!("rdma: Failed to read memory removal event: {err}");
+            self.metrics.event_fails.inc();
+            return;
+        }
+
+        self.process_memory_removals().unwrap_or_else(|err| {
+            error!("rdma: {err:?}");
+            self.metrics.event_fails.inc();
+        });
     }
 }
 
@@ -80,10 +269,19 @@ impl MutEventSubscriber for VirtioRdma {
 
         match source {
             Self::PROCESS_ACTIVATE => self.process_activate_event(ops),
-            Self::PROCESS_RDMA_QUEUE => self.process_queue_event(),
-            _ => {
-                warn!("rdma: Unknown event received: {source}");
-            }
+            Self::PROCESS_CTRL_QUEUE => self.process_cmd_queue_event(RDMA_CTRL_QUEUE),
+            Self::PROCESS_CQ_QUEUE => self.process_cq_queue_event(),
+            Self::PROCESS_EVENT_QUEUE => self.process_event_queue_event(),
+            Self::PROCESS_MEMORY_REMOVAL => self.process_memory_removal_event(),
+            Self::PROCESS_DOORBELL => self.process_doorbell_event(),
+            Self::PROCESS_CQ_MODERATION => self.process_cq_moderation_event(),
+            Self::PROCESS_BACKEND => self.process_backend_event(),
+            Self::PROCESS_RATE_LIMITER => self.process_rate_limiter_event(),
+            Self::PROCESS_BACKEND_TIMER => self.process_backend_timer_event(),
+            _ => match self.data_queue_index(source) {
+                Some(queue_index) => self.process_cmd_queue_event(queue_index),
+                None => warn!("rdma: Unknown event received: {source}"),
+            },
         }
     }
 }
@@ -0,0 +1,109 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::net::IpAddr;
+
+use super::RDMA_GID_TABLE_LEN;
+use crate::utils::net::mac::MacAddr;
+
+/// Index of the link-local GID derived from the MAC address of the device.
+pub const GID_INDEX_LINK_LOCAL: u32 = 0;
+/// Index of the GID derived from the IP address of the device.
+pub const GID_INDEX_IP: u32 = 1;
+
+/// GID table of a port, holding the RoCEv2 GIDs derived from the addresses of the device. The
+/// entries without an address are zero GIDs, which the driver ignores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GidTable {
+    mac: Option<MacAddr>,
+    ip: Option<IpAddr>,
+    entries: Vec<[u8; 16]>,
+}
+
+impl GidTable {
+    /// Creates the GID table of a device with the given addresses.
+    pub fn new(mac: Option<MacAddr>, ip: Option<IpAddr>) -> Self {
+        let mut entries = vec![[0u8; 16]; usize::try_from(RDMA_GID_TABLE_LEN).unwrap()];
+        if let Some(mac) = mac {
+            entries[GID_INDEX_LINK_LOCAL as usize] = link_local_gid(mac);
+        }
+        if let Some(ip) = ip {
+            // IPv4 addresses are carried as IPv4-mapped IPv6 addresses.
+            let ipv6 = match ip {
+                IpAddr::V4(ipv4) => ipv4.to_ipv6_mapped(),
+                IpAddr::V6(ipv6) => ipv6,
+            };
+            entries[GID_INDEX_IP as usize] = ipv6.octets();
+        }
+        Self { mac, ip, entries }
+    }
+
+    /// MAC address the link-local GID is derived from.
+    pub fn mac(&self) -> Option<MacAddr> {
+        self.mac
+    }
+
+    /// IP address the routable GID is derived from.
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.ip
+    }
+
+    /// Returns the GID at `index`, or `None` if `index` is out of the table.
+    pub fn get(&self, index: u32) -> Option<&[u8; 16]> {
+        self.entries.get(usize::try_from(index).ok()?)
+    }
+}
+
+impl Default for GidTable {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// Returns the `fe80::/64` GID whose interface identifier is the modified EUI-64 of `mac`.
+fn link_local_gid(mac: MacAddr) -> [u8; 16] {
+    let mac: [u8; 6] = mac.into();
+    let mut gid = [0u8; 16];
+    gid[..2].copy_from_slice(&[0xfe, 0x80]);
+    gid[8..11].copy_from_slice(&mac[..3]);
+    // The universal/local bit is inverted.
+    gid[8] ^= 0x02;
+    gid[11..13].copy_from_slice(&[0xff, 0xfe]);
+    gid[13..].copy_from_slice(&mac[3..]);
+    gid
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn test_gid_table() {
+        let table = GidTable::default();
+        for index in 0..RDMA_GID_TABLE_LEN {
+            assert_eq!(table.get(index), Some(&[0u8; 16]));
+        }
+        assert_eq!(table.get(RDMA_GID_TABLE_LEN), None);
+
+        let mac = MacAddr::from([0x06, 0x00, 0xac, 0x10, 0x00, 0x02]);
+        let table = GidTable::new(Some(mac), Some(IpAddr::V4(Ipv4Addr::new(172, 16, 0, 2))));
+        assert_eq!(
+            Ipv6Addr::from(*table.get(GID_INDEX_LINK_LOCAL).unwrap()),
+            "fe80::400:acff:fe10:2".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(
+            Ipv6Addr::from(*table.get(GID_INDEX_IP).unwrap()),
+            "::ffff:172.16.0.2".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(table.get(2), Some(&[0u8; 16]));
+        assert_eq!(table.mac(), Some(mac));
+
+        let ip = "2001:db8::2".parse::<Ipv6Addr>().unwrap();
+        let table = GidTable::new(None, Some(IpAddr::V6(ip)));
+        assert_eq!(table.get(GID_INDEX_LINK_LOCAL), Some(&[0u8; 16]));
+        assert_eq!(table.get(GID_INDEX_IP), Some(&ip.octets()));
+        assert_eq!(table.ip(), Some(IpAddr::V6(ip)));
+    }
+}
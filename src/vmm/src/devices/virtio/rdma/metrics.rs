@@ -0,0 +1,283 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the metrics system for virtio-rdma devices.
+//!
+//! # Metrics format
+//! The metrics are flushed in JSON when requested by vmm::logger::metrics::METRICS.write().
+//!
+//! ## JSON example with metrics:
+//! ```json
+//! {
+//!  "rdma_rdma0": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "event_fails": "SharedIncMetric",
+//!     "queue_event_count": "SharedIncMetric",
+//!     "cmd_count": "SharedIncMetric",
+//!     ...
+//!  }
+//!  "rdma": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "event_fails": "SharedIncMetric",
+//!     "queue_event_count": "SharedIncMetric",
+//!     "cmd_count": "SharedIncMetric",
+//!     ...
+//!  }
+//! }
+//! ```
+//! Each `rdma` field in the example above is a serializable `RdmaMetrics` structure collecting
+//! metrics such as `activate_fails`, `cmd_count`, etc. for the virtio-rdma device.
+//! `rdma_rdma0` represents the metrics of the device attached through the endpoint
+//! "/rdma-devices/rdma0" and `rdma` is the aggregate of all the per device metrics.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::logger::{IncMetric, SharedIncMetric};
+
+/// map of rdma device id and metrics
+/// this should be protected by a lock before accessing.
+#[derive(Debug)]
+pub struct RdmaMetricsPerDevice {
+    /// used to access per rdma device metrics
+    pub metrics: BTreeMap<String, Arc<RdmaMetrics>>,
+}
+
+impl RdmaMetricsPerDevice {
+    /// Allocate `RdmaDeviceMetrics` for rdma device having
+    /// id `device_id`. Also, allocate only if it doesn't
+    /// exist to avoid overwriting previously allocated data.
+    /// lock is always initialized so it is safe the unwrap
+    /// the lock without a check.
+    pub fn alloc(device_id: String) -> Arc<RdmaMetrics> {
+        Arc::clone(
+            METRICS
+                .write()
+                .unwrap()
+                .metrics
+                .entry(device_id)
+                .or_insert_with(|| Arc::new(RdmaMetrics::default())),
+        )
+    }
+}
+
+/// Pool of rdma-related metrics per device behind a lock to
+/// keep things thread safe. Since the lock is initialized here
+/// it is safe to unwrap it without any check.
+static METRICS: RwLock<RdmaMetricsPerDevice> = RwLock::new(RdmaMetricsPerDevice {
+    metrics: BTreeMap::new(),
+});
+
+/// This function facilitates aggregation and serialization of
+/// per rdma device metrics.
+pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    let rdma_metrics = METRICS.read().unwrap();
+    let metrics_len = rdma_metrics.metrics.len();
+    // +1 to accommodate aggregate rdma metrics
+    let mut seq = serializer.serialize_map(Some(1 + metrics_len))?;
+
+    let mut rdma_aggregated: RdmaMetrics = RdmaMetrics::default();
+
+    for (name, metrics) in rdma_metrics.metrics.iter() {
+        let devn = format!("rdma_{}", name);
+        // serialization will flush the metrics so aggregate before it.
+        let m: &RdmaMetrics = metrics;
+        rdma_aggregated.aggregate(m);
+        seq.serialize_entry(&devn, m)?;
+    }
+    seq.serialize_entry("rdma", &rdma_aggregated)?;
+    seq.end()
+}
+
+/// Rdma Device associated metrics.
+#[derive(Debug, Default, Serialize)]
+pub struct RdmaMetrics {
+    /// Number of times when activate failed on a rdma device.
+    pub activate_fails: SharedIncMetric,
+    /// Number of times when handling events on a rdma device failed.
+    pub event_fails: SharedIncMetric,
+    /// Number of times when accessing the configuration space of a rdma device failed.
+    pub cfg_fails: SharedIncMetric,
+    /// Number of events triggered on the queues of this rdma device.
+    pub queue_event_count: SharedIncMetric,
+    /// Number of commands executed by this rdma device.
+    pub cmd_count: SharedIncMetric,
+    /// Number of commands which failed on this rdma device.
+    pub cmd_fails: SharedIncMetric,
+    /// Number of resources the driver didn't destroy before resetting the device.
+    pub leaked_resources: SharedIncMetric,
+    /// Number of send work requests posted on this rdma device.
+    pub send_wr_count: SharedIncMetric,
+    /// Number of receive work requests posted on this rdma device.
+    pub recv_wr_count: SharedIncMetric,
+    /// Number of work requests which failed to be posted because the outstanding work of their
+    /// queue pair or protection domain reached its quota.
+    pub wr_quota_exceeded: SharedIncMetric,
+    /// Number of work completions dropped because their completion queue was full.
+    pub cq_overflows: SharedIncMetric,
+    /// Number of received messages which could not be delivered to their queue pair.
+    pub rx_drops: SharedIncMetric,
+    /// Number of buffers of the completion queue the work completions were pushed to.
+    pub cq_event_count: SharedIncMetric,
+    /// Number of completion events of the armed completion queues.
+    pub cq_notify_count: SharedIncMetric,
+    /// Number of memory regions invalidated by the removal of their guest memory.
+    pub mr_invalidations: SharedIncMetric,
+    /// Number of shared receive queues whose work requests dropped below their limit.
+    pub srq_limit_events: SharedIncMetric,
+    /// Number of asynchronous events dropped because too many were pending.
+    pub event_drops: SharedIncMetric,
+    /// Number of doorbells rung by the queue pairs of this rdma device.
+    pub doorbell_count: SharedIncMetric,
+    /// Number of doorbells rung for a queue pair or a data queue the device doesn't have.
+    pub doorbell_fails: SharedIncMetric,
+    /// Number of completion interrupts deferred by their moderation.
+    pub cq_deferred_interrupts: SharedIncMetric,
+    /// Number of faults injected by the fault policy of this rdma device.
+    pub injected_faults: SharedIncMetric,
+    /// Number of times the command queues of this rdma device were throttled by its rate
+    /// limiter.
+    pub rate_limiter_throttled: SharedIncMetric,
+    /// Number of events of the rate limiter of this rdma device.
+    pub rate_limiter_event_count: SharedIncMetric,
+    /// Number of times this rdma device was restored without its backend.
+    pub degraded_restores: SharedIncMetric,
+    /// Number of health probes the backend of this rdma device failed.
+    pub backend_probe_fails: SharedIncMetric,
+    /// Number of times the backend of this rdma device was set up again once down.
+    pub backend_reconnects: SharedIncMetric,
+    /// Number of attempts to set up the backend of this rdma device again which failed.
+    pub backend_reconnect_fails: SharedIncMetric,
+    /// Number of reliable connected queue pairs which couldn't be reconnected to their peer
+    /// once this rdma device was restored.
+    pub qp_reconnect_fails: SharedIncMetric,
+}
+
+impl RdmaMetrics {
+    /// Const default construction.
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// rdma metrics are SharedIncMetric where the diff of current vs
+    /// old is serialized i.e. serialize_u64(current-old).
+    /// So to have the aggregate serialized in same way we need to
+    /// fetch the diff of current vs old metrics and add it to the
+    /// aggregate.
+    pub fn aggregate(&mut self, other: &Self) {
+        self.activate_fails.add(other.activate_fails.fetch_diff());
+        self.event_fails.add(other.event_fails.fetch_diff());
+        self.cfg_fails.add(other.cfg_fails.fetch_diff());
+        self.queue_event_count
+            .add(other.queue_event_count.fetch_diff());
+        self.cmd_count.add(other.cmd_count.fetch_diff());
+        self.cmd_fails.add(other.cmd_fails.fetch_diff());
+        self.leaked_resources
+            .add(other.leaked_resources.fetch_diff());
+        self.send_wr_count.add(other.send_wr_count.fetch_diff());
+        self.recv_wr_count.add(other.recv_wr_count.fetch_diff());
+        self.wr_quota_exceeded
+            .add(other.wr_quota_exceeded.fetch_diff());
+        self.cq_overflows.add(other.cq_overflows.fetch_diff());
+        self.rx_drops.add(other.rx_drops.fetch_diff());
+        self.cq_event_count.add(other.cq_event_count.fetch_diff());
+        self.cq_notify_count.add(other.cq_notify_count.fetch_diff());
+        self.mr_invalidations
+            .add(other.mr_invalidations.fetch_diff());
+        self.srq_limit_events
+            .add(other.srq_limit_events.fetch_diff());
+        self.event_drops.add(other.event_drops.fetch_diff());
+        self.doorbell_count.add(other.doorbell_count.fetch_diff());
+        self.doorbell_fails.add(other.doorbell_fails.fetch_diff());
+        self.cq_deferred_interrupts
+            .add(other.cq_deferred_interrupts.fetch_diff());
+        self.injected_faults.add(other.injected_faults.fetch_diff());
+        self.rate_limiter_throttled
+            .add(other.rate_limiter_throttled.fetch_diff());
+        self.rate_limiter_event_count
+            .add(other.rate_limiter_event_count.fetch_diff());
+        self.degraded_restores
+            .add(other.degraded_restores.fetch_diff());
+        self.backend_probe_fails
+            .add(other.backend_probe_fails.fetch_diff());
+        self.backend_reconnects
+            .add(other.backend_reconnects.fetch_diff());
+        self.backend_reconnect_fails
+            .add(other.backend_reconnect_fails.fetch_diff());
+        self.qp_reconnect_fails
+            .add(other.qp_reconnect_fails.fetch_diff());
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_rdma_dev_metrics() {
+        // Note: this test has nothing to do with
+        // rdma structure or IRQs, this is just to allocate
+        // metrics for max number of devices that system can have.
+        // We have 5-23 IRQ for rdma devices on x86_64 so, there
+        // are 19 rdma devices at max. And, even though we have more
+        // devices on aarch64 but we stick to 19 to keep test common.
+        const MAX_RDMA_DEVICES: usize = 19;
+
+        // This is to make sure that RwLock for rdma::metrics::METRICS is good.
+        drop(METRICS.read().unwrap());
+        drop(METRICS.write().unwrap());
+
+        // rdma::metrics::METRICS is in short RwLock on Vec of RdmaDeviceMetrics.
+        // Normally, pointer to unique entries of rdma::metrics::METRICS are stored
+        // in Rdma device so that Rdma device can do self.metrics.* to
+        // update a metric. We try to do something similar here without
+        // using Rdma device by allocating max number of
+        // RdmaDeviceMetrics in rdma::metrics::METRICS and store pointer to
+        // each entry in the local `metrics` vec.
+        // We then update 1 IncMetric and 2 SharedMetric for each metrics
+        // and validate if the metrics for per device was updated as
+        // expected.
+        let mut metrics: Vec<Arc<RdmaMetrics>> = Vec::new();
+        for i in 0..MAX_RDMA_DEVICES {
+            let rdma_name: String = format!("rdma{}", i);
+            metrics.push(RdmaMetricsPerDevice::alloc(rdma_name.clone()));
+            // update IncMetric
+            metrics[i].activate_fails.inc();
+
+            if i == 0 {
+                // Unit tests run in parallel and we have
+                // `test_single_rdma_dev_metrics` that also increases
+                // the IncMetric count of drv0 by 1 (intentional to check
+                // thread safety) so we check if the count is >=1.
+                assert!(metrics[i].activate_fails.count() >= 1);
+            } else {
+                assert!(metrics[i].activate_fails.count() == 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_rdma_dev_metrics() {
+        let test_metrics = RdmaMetricsPerDevice::alloc(String::from("rdma0"));
+        // Test to update IncMetrics
+        test_metrics.activate_fails.inc();
+        assert!(
+            test_metrics.activate_fails.count() > 0,
+            "{}",
+            test_metrics.activate_fails.count()
+        );
+
+        // We expect only 2 tests (this and test_max_rdma_dev_metrics)
+        // to update activate_fails count for rdma0.
+        assert!(
+            test_metrics.activate_fails.count() <= 2,
+            "{}",
+            test_metrics.activate_fails.count()
+        );
+    }
+}
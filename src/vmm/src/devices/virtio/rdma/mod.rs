@@ -1,10 +1,393 @@
 // Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+//! Implements a virtio-rdma device.
+//!
+//! The driver sends resource management commands on the control queue, and posts work requests
+//! on a pair of data queues per vCPU. The device executes them through a backend carrying the
+//! messages between queue pairs, within the microVM, to the other microVMs of the host or over the
+//! network. How the device behaves towards the driver is described in `docs/rdma.md`.
+
+pub mod backend;
 pub mod device;
+pub mod doorbell;
 mod event_handler;
+pub mod gid;
+pub mod metrics;
+pub mod persist;
+pub mod pkey;
+pub mod qp;
+pub mod request;
+pub mod table;
+pub mod uring;
 
 pub use self::device::{RdmaError, VirtioRdma};
 
-pub(crate) const RDMA_NUM_QUEUES: usize = 1;
-pub(crate) const RDMA_QUEUE: usize = 0;
+/// Index of the queue receiving the commands of the driver.
+pub(crate) const RDMA_CTRL_QUEUE: usize = 0;
+/// Index of the queue receiving the buffers the work completions are pushed to.
+pub(crate) const RDMA_CQ_QUEUE: usize = 1;
+/// Index of the queue receiving the buffers the asynchronous events are written to.
+pub(crate) const RDMA_EVENT_QUEUE: usize = 2;
+/// Index of the send queue of the first data queue pair, receiving the `RDMA_CMD_POST_SEND`
+/// commands of the driver. The queues of the following pairs come after the first pair, in the
+/// same order.
+pub(crate) const RDMA_SEND_QUEUE: usize = 3;
+/// Index of the receive queue of the first data queue pair, receiving the `RDMA_CMD_POST_RECV` and
+/// `RDMA_CMD_POST_SRQ_RECV` commands of the driver.
+pub(crate) const RDMA_RECV_QUEUE: usize = 4;
+/// Maximum number of data queue pairs of a device, one per vCPU.
+pub const RDMA_MAX_NUM_QUEUES: u16 = 32;
+
+/// Returns the number of queues of a device with `num_queues` data queue pairs.
+pub(crate) fn rdma_num_queues(num_queues: u16) -> usize {
+    RDMA_SEND_QUEUE + 2 * usize::from(num_queues)
+}
+
+/// ID of the shared memory region holding the doorbells of the queue pairs.
+pub const RDMA_SHM_DOORBELLS: u8 = 0;
+/// Size of the doorbell of a queue pair, a page of the doorbell region.
+pub const RDMA_DOORBELL_SIZE: u64 = 0x1000;
+/// Length of the doorbell region, holding the doorbells of the queue pair numbers up to
+/// `RDMA_MAX_QP`.
+pub const RDMA_DOORBELL_REGION_LEN: u64 = RDMA_DOORBELL_SIZE * (RDMA_MAX_QP as u64 + 1);
+
+/// Creates a queue pair.
+pub const RDMA_CMD_CREATE_QP: u32 = 1;
+/// Destroys a queue pair.
+pub const RDMA_CMD_DESTROY_QP: u32 = 2;
+/// Creates a completion queue.
+pub const RDMA_CMD_CREATE_CQ: u32 = 3;
+/// Destroys a completion queue.
+pub const RDMA_CMD_DESTROY_CQ: u32 = 4;
+/// Registers a memory region.
+pub const RDMA_CMD_REG_MR: u32 = 5;
+/// Deregisters a memory region.
+pub const RDMA_CMD_DEREG_MR: u32 = 6;
+/// Modifies the state and the attributes of a queue pair.
+pub const RDMA_CMD_MODIFY_QP: u32 = 7;
+/// Reads the state, the capabilities and the attributes of a queue pair.
+pub const RDMA_CMD_QUERY_QP: u32 = 8;
+/// Reads the limits and the capabilities of the device.
+pub const RDMA_CMD_QUERY_DEVICE: u32 = 9;
+/// Reads the attributes of a port.
+pub const RDMA_CMD_QUERY_PORT: u32 = 10;
+/// Allocates a protection domain.
+pub const RDMA_CMD_ALLOC_PD: u32 = 11;
+/// Deallocates a protection domain.
+pub const RDMA_CMD_DEALLOC_PD: u32 = 12;
+/// Creates an address handle.
+pub const RDMA_CMD_CREATE_AH: u32 = 13;
+/// Destroys an address handle.
+pub const RDMA_CMD_DESTROY_AH: u32 = 14;
+/// Posts a work request on the send queue of a queue pair.
+pub const RDMA_CMD_POST_SEND: u32 = 15;
+/// Retrieves the work completions of a completion queue.
+pub const RDMA_CMD_POLL_CQ: u32 = 16;
+/// Posts a work request on the receive queue of a queue pair.
+pub const RDMA_CMD_POST_RECV: u32 = 17;
+/// Arms a completion queue, requesting a completion event for its next work completion.
+pub const RDMA_CMD_REQ_NOTIFY_CQ: u32 = 18;
+/// Creates a shared receive queue.
+pub const RDMA_CMD_CREATE_SRQ: u32 = 19;
+/// Destroys a shared receive queue.
+pub const RDMA_CMD_DESTROY_SRQ: u32 = 20;
+/// Resizes a shared receive queue, or arms its limit.
+pub const RDMA_CMD_MODIFY_SRQ: u32 = 21;
+/// Posts a work request on a shared receive queue.
+pub const RDMA_CMD_POST_SRQ_RECV: u32 = 22;
+/// Allocates a memory window.
+pub const RDMA_CMD_ALLOC_MW: u32 = 23;
+/// Deallocates a memory window.
+pub const RDMA_CMD_DEALLOC_MW: u32 = 24;
+/// Queries an entry of the GID table of a port.
+pub const RDMA_CMD_QUERY_GID: u32 = 25;
+/// Queries an entry of the P_Key table of a port.
+pub const RDMA_CMD_QUERY_PKEY: u32 = 26;
+/// Resizes a completion queue, keeping the work completions it holds.
+pub const RDMA_CMD_RESIZE_CQ: u32 = 27;
+
+/// The command succeeded.
+pub const RDMA_STATUS_OK: u32 = 0;
+/// The opcode of the command is not supported.
+pub const RDMA_STATUS_UNSUPPORTED: u32 = 1;
+/// The arguments of the command are invalid.
+pub const RDMA_STATUS_INVALID_ARG: u32 = 2;
+/// The command references a resource which doesn't exist.
+pub const RDMA_STATUS_INVALID_HANDLE: u32 = 3;
+/// The device ran out of the resource to create.
+pub const RDMA_STATUS_NO_RESOURCES: u32 = 4;
+/// The resource is still referenced by other resources.
+pub const RDMA_STATUS_BUSY: u32 = 5;
+/// The access flags of the memory region are invalid.
+pub const RDMA_STATUS_INVALID_ACCESS: u32 = 6;
+/// The memory region is not backed by guest memory.
+pub const RDMA_STATUS_BAD_ADDRESS: u32 = 7;
+/// The state of the resource doesn't allow the command.
+pub const RDMA_STATUS_INVALID_STATE: u32 = 8;
+
+// States of a port, with the values of `enum ibv_port_state`.
+/// The link of the port is down.
+pub const RDMA_PORT_DOWN: u32 = 1;
+/// The port is up and may carry traffic.
+pub const RDMA_PORT_ACTIVE: u32 = 4;
+
+// MTUs, with the values of `enum ibv_mtu`.
+/// 1024 bytes.
+pub const RDMA_MTU_1024: u32 = 3;
+/// 4096 bytes.
+pub const RDMA_MTU_4096: u32 = 5;
+
+/// Ethernet link layer of RoCE ports, with the value of `IBV_LINK_LAYER_ETHERNET`.
+pub const RDMA_LINK_LAYER_ETHERNET: u32 = 2;
+
+/// GID of RoCEv2 traffic, encapsulated in UDP/IP, with the value of `IB_GID_TYPE_ROCE_UDP_ENCAP`.
+pub const RDMA_GID_TYPE_ROCE_V2: u32 = 2;
+
+/// Reliable connected queue pair.
+pub const RDMA_QPT_RC: u32 = 2;
+/// Unreliable connected queue pair.
+pub const RDMA_QPT_UC: u32 = 3;
+/// Unreliable datagram queue pair.
+pub const RDMA_QPT_UD: u32 = 4;
+
+// States of a queue pair, with the values of `enum ibv_qp_state`.
+/// The queue pair was created, or reset.
+pub const RDMA_QPS_RESET: u32 = 0;
+/// The queue pair is initialized, and may receive work requests.
+pub const RDMA_QPS_INIT: u32 = 1;
+/// Ready to receive.
+pub const RDMA_QPS_RTR: u32 = 2;
+/// Ready to send.
+pub const RDMA_QPS_RTS: u32 = 3;
+/// The send queue is drained.
+pub const RDMA_QPS_SQD: u32 = 4;
+/// A send work request completed in error.
+pub const RDMA_QPS_SQE: u32 = 5;
+/// The queue pair is in error.
+pub const RDMA_QPS_ERR: u32 = 6;
+
+// Attributes of `RDMA_CMD_MODIFY_QP`, with the values of `enum ibv_qp_attr_mask`.
+/// Sets the state of the queue pair.
+pub const RDMA_QP_STATE: u32 = 1 << 0;
+/// Checks the current state of the queue pair.
+pub const RDMA_QP_CUR_STATE: u32 = 1 << 1;
+/// Sets the remote access flags.
+pub const RDMA_QP_ACCESS_FLAGS: u32 = 1 << 3;
+/// Sets the P_Key index.
+pub const RDMA_QP_PKEY_INDEX: u32 = 1 << 4;
+/// Sets the port number.
+pub const RDMA_QP_PORT: u32 = 1 << 5;
+/// Sets the Q_Key of an unreliable datagram queue pair.
+pub const RDMA_QP_QKEY: u32 = 1 << 6;
+/// Sets the address vector of the destination.
+pub const RDMA_QP_AV: u32 = 1 << 7;
+/// Sets the path MTU.
+pub const RDMA_QP_PATH_MTU: u32 = 1 << 8;
+/// Sets the local ACK timeout.
+pub const RDMA_QP_TIMEOUT: u32 = 1 << 9;
+/// Sets the retry count.
+pub const RDMA_QP_RETRY_CNT: u32 = 1 << 10;
+/// Sets the RNR retry count.
+pub const RDMA_QP_RNR_RETRY: u32 = 1 << 11;
+/// Sets the receive queue PSN.
+pub const RDMA_QP_RQ_PSN: u32 = 1 << 12;
+/// Sets the number of outstanding RDMA reads and atomics the queue pair initiates.
+pub const RDMA_QP_MAX_QP_RD_ATOMIC: u32 = 1 << 13;
+/// Sets the minimum RNR NAK timer.
+pub const RDMA_QP_MIN_RNR_TIMER: u32 = 1 << 15;
+/// Sets the send queue PSN.
+pub const RDMA_QP_SQ_PSN: u32 = 1 << 16;
+/// Sets the number of outstanding RDMA reads and atomics the queue pair handles as target.
+pub const RDMA_QP_MAX_DEST_RD_ATOMIC: u32 = 1 << 17;
+/// Sets the destination queue pair number.
+pub const RDMA_QP_DEST_QPN: u32 = 1 << 20;
+
+// Opcodes of the send work requests, with the values of `enum ibv_wr_opcode`.
+/// Writes to a memory region of the remote peer.
+pub const RDMA_WR_RDMA_WRITE: u32 = 0;
+/// Writes to a memory region of the remote peer, notifying it with immediate data.
+pub const RDMA_WR_RDMA_WRITE_WITH_IMM: u32 = 1;
+/// Sends a message to the remote peer.
+pub const RDMA_WR_SEND: u32 = 2;
+/// Sends a message to the remote peer, along with immediate data.
+pub const RDMA_WR_SEND_WITH_IMM: u32 = 3;
+/// Reads from a memory region of the remote peer.
+pub const RDMA_WR_RDMA_READ: u32 = 4;
+/// Swaps a 64-bit value of the remote peer with an operand if it equals another one.
+pub const RDMA_WR_ATOMIC_CMP_AND_SWP: u32 = 5;
+/// Adds an operand to a 64-bit value of the remote peer.
+pub const RDMA_WR_ATOMIC_FETCH_AND_ADD: u32 = 6;
+/// Invalidates a memory window of the local device.
+pub const RDMA_WR_LOCAL_INV: u32 = 7;
+/// Binds a memory window to a range of a memory region.
+pub const RDMA_WR_BIND_MW: u32 = 8;
+/// Sends a message to the remote peer, invalidating one of its memory windows.
+pub const RDMA_WR_SEND_WITH_INV: u32 = 9;
+
+// Flags of the send work requests, with the values of `enum ibv_send_flags`.
+/// Orders the work request after the previous RDMA reads and atomics.
+pub const RDMA_SEND_FENCE: u32 = 1 << 0;
+/// Generates a work completion when the work request succeeds.
+pub const RDMA_SEND_SIGNALED: u32 = 1 << 1;
+/// Requests a solicited event from the remote peer.
+pub const RDMA_SEND_SOLICITED: u32 = 1 << 2;
+/// The data of the work request follows its arguments, rather than being described by
+/// scatter/gather entries.
+pub const RDMA_SEND_INLINE: u32 = 1 << 3;
+
+// Status of the work completions, with the values of `enum ibv_wc_status`.
+/// The work request succeeded.
+pub const RDMA_WC_SUCCESS: u32 = 0;
+/// The message doesn't fit in the path MTU of an unreliable datagram queue pair.
+pub const RDMA_WC_LOC_LEN_ERR: u32 = 1;
+/// The memory window of a local invalidation is not valid.
+pub const RDMA_WC_LOC_QP_OP_ERR: u32 = 2;
+/// A scatter/gather entry is not allowed by its memory region.
+pub const RDMA_WC_LOC_PROT_ERR: u32 = 4;
+/// The work request was flushed because the queue pair is in error.
+pub const RDMA_WC_WR_FLUSH_ERR: u32 = 5;
+/// The memory window could not be bound.
+pub const RDMA_WC_MW_BIND_ERR: u32 = 6;
+/// The received message requested an invalid operation, such as the invalidation of a memory
+/// window it may not invalidate, or didn't fit in the receive work request of the remote peer.
+pub const RDMA_WC_REM_INV_REQ_ERR: u32 = 9;
+/// The remote peer denied the access to its memory.
+pub const RDMA_WC_REM_ACCESS_ERR: u32 = 10;
+/// The remote peer failed to complete the operation, such as scattering a message to its
+/// receive work request.
+pub const RDMA_WC_REM_OP_ERR: u32 = 11;
+/// The remote peer could not be reached.
+pub const RDMA_WC_RETRY_EXC_ERR: u32 = 12;
+/// The remote peer had no receive work request for the message.
+pub const RDMA_WC_RNR_RETRY_EXC_ERR: u32 = 13;
+
+/// The work completions of the completion queue are pushed to the completion queue of the device
+/// rather than polled.
+pub const RDMA_CQ_F_ASYNC: u32 = 1 << 0;
+
+// Flags of `RDMA_CMD_REQ_NOTIFY_CQ`, with the values of `enum ib_cq_notify_flags`.
+/// Requests a completion event for the next solicited or unsuccessful work completion.
+pub const RDMA_CQ_SOLICITED: u32 = 1 << 0;
+/// Requests a completion event for the next work completion.
+pub const RDMA_CQ_NEXT_COMP: u32 = 1 << 1;
+
+// Attributes of `RDMA_CMD_MODIFY_SRQ`, with the values of `enum ibv_srq_attr_mask`.
+/// Resizes the shared receive queue.
+pub const RDMA_SRQ_MAX_WR: u32 = 1 << 0;
+/// Arms the limit of the shared receive queue.
+pub const RDMA_SRQ_LIMIT: u32 = 1 << 1;
+
+// Types of the asynchronous events.
+/// The guest memory backing the memory region was removed, and the region may no longer be
+/// accessed. The handle of the event is the local key of the region.
+pub const RDMA_EVENT_MR_INVALIDATED: u32 = 0;
+/// The number of receive work requests of the shared receive queue dropped below its limit,
+/// which is disarmed. The handle of the event is the number of the shared receive queue.
+pub const RDMA_EVENT_SRQ_LIMIT_REACHED: u32 = 1;
+/// The GID table of the port changed. The handle of the event is the number of the port.
+pub const RDMA_EVENT_GID_CHANGE: u32 = 2;
+/// A work completion was lost because the completion queue was full. The handle of the event is
+/// the number of the completion queue.
+pub const RDMA_EVENT_CQ_ERR: u32 = 3;
+/// The queue pair moved to the error state without a work completion telling why, such as when
+/// its completion queue overflowed. The handle of the event is the number of the queue pair.
+pub const RDMA_EVENT_QP_FATAL: u32 = 4;
+/// The link of the port came up. The handle of the event is the number of the port.
+pub const RDMA_EVENT_PORT_ACTIVE: u32 = 5;
+/// The link of the port went down. The handle of the event is the number of the port.
+pub const RDMA_EVENT_PORT_ERR: u32 = 6;
+
+// Opcodes of the work completions, with the values of `enum ibv_wc_opcode`.
+/// Completion of a send.
+pub const RDMA_WC_SEND: u32 = 0;
+/// Completion of an RDMA write.
+pub const RDMA_WC_RDMA_WRITE: u32 = 1;
+/// Completion of an RDMA read.
+pub const RDMA_WC_RDMA_READ: u32 = 2;
+/// Completion of an atomic compare and swap.
+pub const RDMA_WC_COMP_SWAP: u32 = 3;
+/// Completion of an atomic fetch and add.
+pub const RDMA_WC_FETCH_ADD: u32 = 4;
+/// Completion of a memory window bind.
+pub const RDMA_WC_BIND_MW: u32 = 5;
+/// Completion of a local invalidation.
+pub const RDMA_WC_LOCAL_INV: u32 = 6;
+/// Completion of a receive.
+pub const RDMA_WC_RECV: u32 = 128;
+/// Completion of a receive consumed by an RDMA write with immediate data.
+pub const RDMA_WC_RECV_RDMA_WITH_IMM: u32 = 129;
+
+// Flags of the work completions, with the values of `enum ibv_wc_flags`.
+/// The receive buffer starts with the global route header of the datagram.
+pub const RDMA_WC_GRH: u32 = 1 << 0;
+/// The work completion carries immediate data.
+pub const RDMA_WC_WITH_IMM: u32 = 1 << 1;
+/// The received message invalidated the memory window whose remote key is in the immediate data
+/// of the work completion.
+pub const RDMA_WC_WITH_INV: u32 = 1 << 3;
+
+/// Length of the global route header written at the start of the receive buffers of unreliable
+/// datagram queue pairs.
+pub const RDMA_GRH_LEN: usize = 40;
+
+/// The device may write to the memory region.
+pub const RDMA_ACCESS_LOCAL_WRITE: u32 = 1 << 0;
+/// Remote peers may write to the memory region.
+pub const RDMA_ACCESS_REMOTE_WRITE: u32 = 1 << 1;
+/// Remote peers may read from the memory region.
+pub const RDMA_ACCESS_REMOTE_READ: u32 = 1 << 2;
+/// Remote peers may execute atomic operations on the memory region.
+pub const RDMA_ACCESS_REMOTE_ATOMIC: u32 = 1 << 3;
+/// Memory windows may be bound to the memory region.
+pub const RDMA_ACCESS_MW_BIND: u32 = 1 << 4;
+
+/// Memory window bound by work requests and accessed through the queue pair which bound it, with
+/// the value of `IBV_MW_TYPE_2`.
+pub const RDMA_MW_TYPE_2: u32 = 2;
+
+// Atomic capabilities of the device, with the values of `enum ibv_atomic_cap`.
+/// Atomic operations are not supported.
+pub const RDMA_ATOMIC_NONE: u32 = 0;
+/// Atomic operations are atomic with respect to the other operations of the device.
+pub const RDMA_ATOMIC_HCA: u32 = 1;
+
+/// Maximum number of queue pairs of a device.
+pub const RDMA_MAX_QP: u32 = 1024;
+/// Maximum number of outstanding work requests on a queue of a queue pair.
+pub const RDMA_MAX_QP_WR: u32 = 1024;
+/// Maximum number of scatter/gather entries of a work request.
+pub const RDMA_MAX_SGE: u32 = 16;
+/// Maximum length of the inline data of a work request, in bytes.
+pub const RDMA_MAX_INLINE_DATA: u32 = 256;
+/// Maximum number of completion queues of a device.
+pub const RDMA_MAX_CQ: u32 = 1024;
+/// Maximum number of entries of a completion queue.
+pub const RDMA_MAX_CQE: u32 = 4096;
+/// Maximum number of shared receive queues of a device.
+pub const RDMA_MAX_SRQ: u32 = 1024;
+/// Maximum number of outstanding work requests on a shared receive queue.
+pub const RDMA_MAX_SRQ_WR: u32 = 4096;
+/// Maximum number of memory regions of a device.
+pub const RDMA_MAX_MR: u32 = 4096;
+/// Maximum number of memory windows of a device.
+pub const RDMA_MAX_MW: u32 = 4096;
+/// Maximum number of protection domains of a device.
+pub const RDMA_MAX_PD: u32 = 1024;
+/// Maximum number of address handles of a device.
+pub const RDMA_MAX_AH: u32 = 4096;
+/// Number of ports of a device, numbered from 1.
+pub const RDMA_NUM_PORTS: u32 = 1;
+/// Number of entries of the P_Key table of a port.
+pub const RDMA_PKEY_TABLE_LEN: u32 = 16;
+/// P_Key of the first entry of the P_Key table, granting full membership of the default
+/// partition.
+pub const RDMA_DEFAULT_PKEY: u16 = 0xffff;
+/// Number of entries of the GID table of a port.
+pub const RDMA_GID_TABLE_LEN: u32 = 16;
+/// Largest message a work request may carry, in bytes.
+pub const RDMA_MAX_MSG_SIZE: u32 = 1 << 31;
+/// Maximum number of outstanding RDMA reads and atomics of a queue pair.
+pub const RDMA_MAX_QP_RD_ATOM: u32 = 16;
+/// Page sizes supported by the memory regions, as a bitmask.
+pub const RDMA_PAGE_SIZE_CAP: u64 = 0x1000;
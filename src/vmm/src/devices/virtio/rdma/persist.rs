@@ -0,0 +1,497 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the structures needed for saving/restoring rdma devices.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::fault::RdmaFaultPolicy;
+use super::backend::shmem::ShmemBackendConfig;
+use super::backend::tcp::TcpBackendConfig;
+use super::backend::udp::UdpBackendConfig;
+use super::backend::unix::UnixBackendConfig;
+use super::backend::{RdmaBackendConfig, RdmaBackendError};
+use super::device::{
+    AddressHandle, CompletionQueue, MemoryRegion, MemoryWindow, ProtectionDomain, RdmaCqModeration,
+    RdmaResourceLimits, SharedReceiveQueue,
+};
+use super::pkey::PkeyTableError;
+use super::qp::QueuePair;
+use super::request::RdmaAsyncEvent;
+use super::table::ResourceTable;
+use super::{RDMA_MAX_NUM_QUEUES, RDMA_PORT_ACTIVE, RdmaError, VirtioRdma, rdma_num_queues};
+use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
+use crate::rate_limiter::RateLimiter;
+use crate::rate_limiter::persist::RateLimiterState;
+use crate::snapshot::Persist;
+use crate::utils::net::mac::MacAddr;
+use crate::vstate::memory::GuestMemoryMmap;
+
+/// Backend of a saved rdma device. The configuration of the backend is tagged by its `type`,
+/// which the snapshot format can't decode, hence this mirror of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RdmaBackendState {
+    Null,
+    Loopback,
+    Udp(UdpBackendConfig),
+    Tcp(TcpBackendConfig),
+    Unix(UnixBackendConfig),
+    Shmem(ShmemBackendConfig),
+}
+
+impl From<RdmaBackendConfig> for RdmaBackendState {
+    fn from(config: RdmaBackendConfig) -> Self {
+        match config {
+            RdmaBackendConfig::Null => Self::Null,
+            RdmaBackendConfig::Loopback => Self::Loopback,
+            RdmaBackendConfig::Udp(config) => Self::Udp(config),
+            RdmaBackendConfig::Tcp(config) => Self::Tcp(config),
+            RdmaBackendConfig::Unix(config) => Self::Unix(config),
+            RdmaBackendConfig::Shmem(config) => Self::Shmem(config),
+        }
+    }
+}
+
+impl From<RdmaBackendState> for RdmaBackendConfig {
+    fn from(state: RdmaBackendState) -> Self {
+        match state {
+            RdmaBackendState::Null => Self::Null,
+            RdmaBackendState::Loopback => Self::Loopback,
+            RdmaBackendState::Udp(config) => Self::Udp(config),
+            RdmaBackendState::Tcp(config) => Self::Tcp(config),
+            RdmaBackendState::Unix(config) => Self::Unix(config),
+            RdmaBackendState::Shmem(config) => Self::Shmem(config),
+        }
+    }
+}
+
+/// Information about the rdma device that is saved at snapshot. The messages carried by the
+/// backend are delivered before the device is saved, and the backend is set up again on
+/// restore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RdmaState {
+    pub id: String,
+    mac: Option<MacAddr>,
+    ip: Option<IpAddr>,
+    pkeys: Vec<u16>,
+    // Configured number of data queue pairs, and the number the device has.
+    configured_num_queues: Option<u16>,
+    num_queues: u16,
+    mtu: Option<u32>,
+    limits: RdmaResourceLimits,
+    cq_moderation: Option<RdmaCqModeration>,
+    backend: Option<RdmaBackendState>,
+    fault_policy: Option<RdmaFaultPolicy>,
+    // Whether the backend couldn't be set up by the restore the device was saved after.
+    backend_degraded: bool,
+    rate_limiter_state: RateLimiterState,
+    link_up: bool,
+    qps: ResourceTable<QueuePair>,
+    cqs: ResourceTable<CompletionQueue>,
+    mrs: ResourceTable<MemoryRegion>,
+    mws: ResourceTable<MemoryWindow>,
+    pds: ResourceTable<ProtectionDomain>,
+    ahs: ResourceTable<AddressHandle>,
+    srqs: ResourceTable<SharedReceiveQueue>,
+    pending_events: VecDeque<RdmaAsyncEvent>,
+    pub virtio_state: VirtioDeviceState,
+}
+
+/// Auxiliary structure for creating a device when resuming from a snapshot.
+#[derive(Debug)]
+pub struct RdmaConstructorArgs<'a> {
+    /// Pointer to guest memory.
+    pub mem: &'a GuestMemoryMmap,
+    /// Whether the device is restored with the link of its ports down, rather than failing,
+    /// when its backend can't be set up.
+    pub allow_degraded: bool,
+}
+
+/// Errors triggered when trying to construct a rdma device at resume time.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum RdmaPersistError {
+    /// Failed to re-create the virtio state (i.e queues etc): {0}
+    VirtioState(#[from] VirtioStateError),
+    /// Failed to create a rdma device: {0}
+    CreateRdma(#[from] RdmaError),
+    /// Failed to restore the P_Key table: {0}
+    PkeyTable(#[from] PkeyTableError),
+    /// Failed to set up the backend: {0}
+    Backend(#[from] RdmaBackendError),
+    /// Failed to create a rate limiter: {0}
+    CreateRateLimiter(#[from] io::Error),
+}
+
+impl<'a> Persist<'a> for VirtioRdma {
+    type State = RdmaState;
+    type ConstructorArgs = RdmaConstructorArgs<'a>;
+    type Error = RdmaPersistError;
+
+    fn save(&self) -> Self::State {
+        RdmaState {
+            id: self.id().to_string(),
+            mac: self.gid_table().mac(),
+            ip: self.gid_table().ip(),
+            pkeys: self.pkey_table().configured(),
+            configured_num_queues: self.configured_num_queues(),
+            num_queues: self.num_queues(),
+            mtu: self.configured_mtu(),
+            limits: self.resource_limits(),
+            cq_moderation: self.cq_moderation(),
+            backend: self.backend_config().cloned().map(RdmaBackendState::from),
+            fault_policy: self.fault_policy(),
+            backend_degraded: self.backend_degraded(),
+            rate_limiter_state: self.rate_limiter.save(),
+            link_up: self.port_attributes().state == RDMA_PORT_ACTIVE,
+            qps: self.qps.clone(),
+            cqs: self.cqs.clone(),
+            mrs: self.mrs.clone(),
+            mws: self.mws.clone(),
+            pds: self.pds.clone(),
+            ahs: self.ahs.clone(),
+            srqs: self.srqs.clone(),
+            pending_events: self.pending_events.clone(),
+            virtio_state: VirtioDeviceState::from_device(self),
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        if !(1..=RDMA_MAX_NUM_QUEUES).contains(&state.num_queues) {
+            return Err(RdmaError::InvalidNumQueues(state.num_queues).into());
+        }
+        // RateLimiter::restore() can fail at creating a timerfd.
+        let rate_limiter = RateLimiter::restore((), &state.rate_limiter_state)?;
+
+        // The device is set up the way it was configured, and not activated yet, so that none of
+        // the changes is notified to the driver.
+        let mut rdma = VirtioRdma::new(state.id.clone())?;
+        rdma.set_addresses(state.mac, state.ip)?;
+        rdma.set_pkeys(&state.pkeys)?;
+        rdma.set_num_queues(state.configured_num_queues)?;
+        rdma.create_queues(state.num_queues)
+            .map_err(RdmaError::EventFd)?;
+        rdma.set_mtu(state.mtu)?;
+        rdma.set_resource_limits(state.limits)?;
+        rdma.set_cq_moderation(state.cq_moderation)?;
+        rdma.set_rate_limiter(rate_limiter);
+
+        rdma.queues = state.virtio_state.build_queues_checked(
+            constructor_args.mem,
+            VirtioDeviceType::Rdma,
+            rdma_num_queues(state.num_queues),
+            FIRECRACKER_MAX_QUEUE_SIZE,
+        )?;
+        rdma.avail_features = state.virtio_state.avail_features;
+        rdma.acked_features = state.virtio_state.acked_features;
+
+        rdma.qps = state.qps.clone();
+        rdma.cqs = state.cqs.clone();
+        rdma.mrs = state.mrs.clone();
+        rdma.mws = state.mws.clone();
+        rdma.pds = state.pds.clone();
+        rdma.ahs = state.ahs.clone();
+        rdma.srqs = state.srqs.clone();
+        rdma.pending_events = state.pending_events.clone();
+
+        // Restoring the backend queues the events notifying the driver of a change of the state
+        // of the link.
+        rdma.set_link_up(state.link_up)?;
+        rdma.restore_backend(
+            state.backend.clone().map(RdmaBackendConfig::from),
+            state.backend_degraded,
+            constructor_args.allow_degraded,
+        )?;
+        rdma.set_fault_policy(state.fault_policy)?;
+
+        Ok(rdma)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::rdma::qp::{OutstandingWork, QpAttributes};
+    use crate::devices::virtio::rdma::{
+        RDMA_EVENT_PORT_ACTIVE, RDMA_EVENT_PORT_ERR, RDMA_EVENT_QP_FATAL, RDMA_PORT_DOWN,
+        RDMA_QPS_ERR, RDMA_QPS_RTS, RDMA_QPT_RC,
+    };
+    use crate::devices::virtio::test_utils::default_mem;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn test_persistence() {
+        let mut rdma = VirtioRdma::new("rdma-persist".to_string()).unwrap();
+        rdma.set_addresses(
+            Some(MacAddr::from_str("06:00:ac:10:00:02").unwrap()),
+            Some(IpAddr::V4(Ipv4Addr::new(172, 16, 0, 2))),
+        )
+        .unwrap();
+        rdma.set_pkeys(&[0x8001]).unwrap();
+        rdma.set_num_queues(Some(2)).unwrap();
+        rdma.set_mtu(Some(4096)).unwrap();
+        rdma.set_resource_limits(RdmaResourceLimits {
+            max_qp: Some(16),
+            ..Default::default()
+        })
+        .unwrap();
+        rdma.set_cq_moderation(Some(RdmaCqModeration {
+            max_cqes: 8,
+            max_usecs: 100,
+        }))
+        .unwrap();
+        rdma.set_backend(Some(RdmaBackendConfig::Null)).unwrap();
+        rdma.set_link_up(false).unwrap();
+        rdma.acked_features = rdma.avail_features;
+
+        // Resources the driver created, along with an event it didn't read yet.
+        let pd = rdma.pds.insert(ProtectionDomain::default()).unwrap();
+        let cq = rdma.cqs.insert(CompletionQueue::new(64, 0)).unwrap();
+        let qpn = rdma
+            .qps
+            .insert(QueuePair {
+                qp_type: 0,
+                send_cq: cq,
+                recv_cq: cq,
+                max_send_wr: 16,
+                max_recv_wr: 16,
+                max_send_sge: 1,
+                max_recv_sge: 1,
+                max_inline_data: 0,
+                pd,
+                srq: 0,
+                state: 0,
+                attrs: QpAttributes::default(),
+                recv_queue: VecDeque::new(),
+                outstanding: OutstandingWork::default(),
+            })
+            .unwrap();
+        rdma.mrs
+            .insert(MemoryRegion {
+                iova: 0x1000,
+                length: 0x2000,
+                access: 0,
+                pd,
+                invalidated: false,
+            })
+            .unwrap();
+        rdma.pending_events.push_back(RdmaAsyncEvent {
+            event_type: RDMA_EVENT_QP_FATAL,
+            handle: qpn,
+        });
+
+        // Save the rdma device.
+        let mut mem = vec![0; 4096];
+
+        Snapshot::new(rdma.save())
+            .save(&mut mem.as_mut_slice())
+            .unwrap();
+
+        // Restore the rdma device.
+        let guest_mem = default_mem();
+        let restored_rdma = VirtioRdma::restore(
+            RdmaConstructorArgs {
+                mem: &guest_mem,
+                allow_degraded: false,
+            },
+            &Snapshot::load_without_crc_check(mem.as_slice())
+                .unwrap()
+                .data,
+        )
+        .unwrap();
+
+        // Test that virtio specific fields are the same.
+        assert_eq!(restored_rdma.device_type(), VirtioDeviceType::Rdma);
+        assert_eq!(restored_rdma.avail_features(), rdma.avail_features());
+        assert_eq!(restored_rdma.acked_features(), rdma.acked_features());
+        assert_eq!(restored_rdma.queues(), rdma.queues());
+        assert_eq!(
+            restored_rdma.queue_events().len(),
+            rdma.queue_events().len()
+        );
+        assert!(!restored_rdma.is_activated());
+
+        // Test that the configuration and the resources of the device are the same.
+        assert_eq!(restored_rdma.gid_table(), rdma.gid_table());
+        assert_eq!(restored_rdma.pkey_table(), rdma.pkey_table());
+        assert_eq!(restored_rdma.num_queues(), 2);
+        assert_eq!(restored_rdma.configured_mtu(), Some(4096));
+        assert_eq!(restored_rdma.capabilities(), rdma.capabilities());
+        assert_eq!(restored_rdma.port_attributes(), rdma.port_attributes());
+        assert_eq!(restored_rdma.cq_moderation(), rdma.cq_moderation());
+        assert_eq!(
+            restored_rdma.backend_config(),
+            Some(&RdmaBackendConfig::Null)
+        );
+        assert_eq!(
+            restored_rdma.qps.iter().collect::<Vec<_>>(),
+            rdma.qps.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored_rdma.cqs.iter().collect::<Vec<_>>(),
+            rdma.cqs.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored_rdma.mrs.iter().collect::<Vec<_>>(),
+            rdma.mrs.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored_rdma.pds.iter().collect::<Vec<_>>(),
+            rdma.pds.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(restored_rdma.pending_events, rdma.pending_events);
+    }
+
+    #[test]
+    fn test_degraded_restore() {
+        let dir = TempDir::new().unwrap();
+        let config = RdmaBackendConfig::Unix(UnixBackendConfig {
+            socket_path: dir.as_path().join("rdma.sock"),
+            peers: Vec::new(),
+        });
+        let mut rdma = VirtioRdma::new("rdma-persist-degraded".to_string()).unwrap();
+        rdma.set_backend(Some(config.clone())).unwrap();
+        let state = rdma.save();
+        let guest_mem = default_mem();
+        let args = |allow_degraded| RdmaConstructorArgs {
+            mem: &guest_mem,
+            allow_degraded,
+        };
+
+        // The socket of the backend is still bound by the saved device.
+        assert!(matches!(
+            VirtioRdma::restore(args(false), &state),
+            Err(RdmaPersistError::Backend(RdmaBackendError::Unix(_)))
+        ));
+        let mut degraded_rdma = VirtioRdma::restore(args(true), &state).unwrap();
+        assert!(degraded_rdma.backend_degraded());
+        assert_eq!(degraded_rdma.backend_config(), Some(&config));
+        assert_eq!(degraded_rdma.port_attributes().state, RDMA_PORT_DOWN);
+        assert_eq!(
+            degraded_rdma.pending_events,
+            [RdmaAsyncEvent {
+                event_type: RDMA_EVENT_PORT_ERR,
+                handle: 1,
+            }]
+        );
+        assert_eq!(degraded_rdma.metrics.degraded_restores.count(), 1);
+
+        // The backend is set up by the next restore, which brings the link up.
+        let mut state = degraded_rdma.save();
+        state.pending_events.clear();
+        drop(rdma);
+        std::fs::remove_file(dir.as_path().join("rdma.sock")).unwrap();
+        let restored_rdma = VirtioRdma::restore(args(false), &state).unwrap();
+        assert!(!restored_rdma.backend_degraded());
+        assert_eq!(restored_rdma.backend_config(), Some(&config));
+        assert_eq!(restored_rdma.port_attributes().state, RDMA_PORT_ACTIVE);
+        assert_eq!(
+            restored_rdma.pending_events,
+            [RdmaAsyncEvent {
+                event_type: RDMA_EVENT_PORT_ACTIVE,
+                handle: 1,
+            }]
+        );
+
+        // Replacing the backend also brings the link up.
+        degraded_rdma
+            .set_backend(Some(RdmaBackendConfig::Null))
+            .unwrap();
+        assert!(!degraded_rdma.backend_degraded());
+        assert_eq!(degraded_rdma.port_attributes().state, RDMA_PORT_ACTIVE);
+    }
+
+    #[test]
+    fn test_restore_reconnects_qps() {
+        let mut rdma = VirtioRdma::new("rdma-persist-reconnect".to_string()).unwrap();
+        rdma.set_backend(None).unwrap();
+        let pd = rdma.pds.insert(ProtectionDomain::default()).unwrap();
+        let cq = rdma.cqs.insert(CompletionQueue::new(64, 0)).unwrap();
+        let mut insert_qp = |dest_qp_num| {
+            rdma.qps
+                .insert(QueuePair {
+                    qp_type: RDMA_QPT_RC,
+                    send_cq: cq,
+                    recv_cq: cq,
+                    max_send_wr: 16,
+                    max_recv_wr: 16,
+                    max_send_sge: 1,
+                    max_recv_sge: 1,
+                    max_inline_data: 0,
+                    pd,
+                    srq: 0,
+                    state: RDMA_QPS_RTS,
+                    attrs: QpAttributes {
+                        dest_qp_num,
+                        ..Default::default()
+                    },
+                    recv_queue: VecDeque::new(),
+                    outstanding: OutstandingWork::default(),
+                })
+                .unwrap()
+        };
+        // The first queue pair is connected to the second one, which is connected to a queue
+        // pair the driver destroyed before the snapshot.
+        let connected_qpn = insert_qp(2);
+        let orphan_qpn = insert_qp(3);
+        let state = rdma.save();
+
+        let guest_mem = default_mem();
+        let restored_rdma = VirtioRdma::restore(
+            RdmaConstructorArgs {
+                mem: &guest_mem,
+                allow_degraded: false,
+            },
+            &state,
+        )
+        .unwrap();
+        assert_eq!(
+            restored_rdma.qps.get(connected_qpn).unwrap().state,
+            RDMA_QPS_RTS
+        );
+        assert_eq!(
+            restored_rdma.qps.get(orphan_qpn).unwrap().state,
+            RDMA_QPS_ERR
+        );
+        assert_eq!(
+            restored_rdma.pending_events,
+            [RdmaAsyncEvent {
+                event_type: RDMA_EVENT_QP_FATAL,
+                handle: orphan_qpn,
+            }]
+        );
+        assert_eq!(restored_rdma.metrics.qp_reconnect_fails.count(), 1);
+    }
+
+    #[test]
+    fn test_restore_invalid_num_queues() {
+        let rdma = VirtioRdma::new("rdma-persist-invalid".to_string()).unwrap();
+        let mut state = rdma.save();
+        state.num_queues = RDMA_MAX_NUM_QUEUES + 1;
+
+        let guest_mem = default_mem();
+        assert!(matches!(
+            VirtioRdma::restore(
+                RdmaConstructorArgs {
+                    mem: &guest_mem,
+                    allow_degraded: false,
+                },
+                &state
+            ),
+            Err(RdmaPersistError::CreateRdma(RdmaError::InvalidNumQueues(_)))
+        ));
+    }
+}
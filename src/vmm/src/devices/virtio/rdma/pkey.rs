@@ -0,0 +1,101 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{RDMA_DEFAULT_PKEY, RDMA_PKEY_TABLE_LEN};
+
+/// Bit of the P_Keys granting full membership of their partition, rather than limited one.
+const PKEY_FULL_MEMBER: u16 = 1 << 15;
+
+/// Error while configuring the P_Key table of a device.
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum PkeyTableError {
+    /// {0} P_Keys were configured, at most {1} fit next to the default one
+    TooManyPkeys(usize, usize),
+    /// P_Key {0:#06x} doesn't identify a partition
+    InvalidPkey(u16),
+}
+
+/// P_Key table of a port, starting with the default P_Key followed by the configured ones. The
+/// entries past them are invalid zero P_Keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkeyTable {
+    entries: Vec<u16>,
+}
+
+impl PkeyTable {
+    /// Creates the P_Key table holding the default P_Key and `pkeys`.
+    pub fn new(pkeys: &[u16]) -> Result<Self, PkeyTableError> {
+        let len = usize::try_from(RDMA_PKEY_TABLE_LEN).unwrap();
+        if pkeys.len() >= len {
+            return Err(PkeyTableError::TooManyPkeys(pkeys.len(), len - 1));
+        }
+        // The partition number is the low 15 bits of the P_Key, and 0 is no partition.
+        if let Some(&pkey) = pkeys.iter().find(|&&pkey| pkey & !PKEY_FULL_MEMBER == 0) {
+            return Err(PkeyTableError::InvalidPkey(pkey));
+        }
+        let mut entries = vec![0; len];
+        entries[0] = RDMA_DEFAULT_PKEY;
+        entries[1..=pkeys.len()].copy_from_slice(pkeys);
+        Ok(Self { entries })
+    }
+
+    /// P_Keys configured next to the default one.
+    pub fn configured(&self) -> Vec<u16> {
+        self.entries[1..]
+            .iter()
+            .copied()
+            .take_while(|&pkey| pkey != 0)
+            .collect()
+    }
+
+    /// Returns the P_Key at `index`, or `None` if `index` is out of the table.
+    pub fn get(&self, index: u32) -> Option<u16> {
+        self.entries.get(usize::try_from(index).ok()?).copied()
+    }
+
+    /// Whether the entry at `index` holds a P_Key queue pairs may use.
+    pub fn is_valid(&self, index: u32) -> bool {
+        self.get(index).is_some_and(|pkey| pkey != 0)
+    }
+}
+
+impl Default for PkeyTable {
+    fn default() -> Self {
+        Self::new(&[]).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkey_table() {
+        let table = PkeyTable::default();
+        assert_eq!(table.get(0), Some(RDMA_DEFAULT_PKEY));
+        assert!(table.is_valid(0));
+        assert!(!table.is_valid(1));
+        assert!(table.configured().is_empty());
+
+        let table = PkeyTable::new(&[0x8001, 0x0002]).unwrap();
+        assert_eq!(table.get(1), Some(0x8001));
+        assert_eq!(table.get(2), Some(0x0002));
+        assert!(table.is_valid(2));
+        assert!(!table.is_valid(3));
+        assert!(!table.is_valid(RDMA_PKEY_TABLE_LEN));
+        assert_eq!(table.get(RDMA_PKEY_TABLE_LEN), None);
+        assert_eq!(table.configured(), [0x8001, 0x0002]);
+
+        let len = usize::try_from(RDMA_PKEY_TABLE_LEN).unwrap();
+        assert_eq!(
+            PkeyTable::new(&vec![1; len]),
+            Err(PkeyTableError::TooManyPkeys(len, len - 1))
+        );
+        PkeyTable::new(&vec![1; len - 1]).unwrap();
+        assert_eq!(
+            PkeyTable::new(&[0x8001, 0x8000]),
+            Err(PkeyTableError::InvalidPkey(0x8000))
+        );
+        assert_eq!(PkeyTable::new(&[0]), Err(PkeyTableError::InvalidPkey(0)));
+    }
+}
@@ -0,0 +1,595 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! State machine of the queue pairs. The transitions, and the attributes each of them requires
+//! or accepts, follow the queue pair state table of the InfiniBand specification, as implemented
+//! by `ib_modify_qp_is_ok()` in Linux. Alternate paths, path migration and SQD notifications are
+//! not supported.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::request::{RdmaCmdError, RdmaCmdModifyQp, RdmaRspQueryQp, RdmaSge};
+use super::{
+    RDMA_ACCESS_LOCAL_WRITE, RDMA_ACCESS_REMOTE_ATOMIC, RDMA_ACCESS_REMOTE_READ,
+    RDMA_ACCESS_REMOTE_WRITE, RDMA_GID_TABLE_LEN, RDMA_MAX_QP_RD_ATOM, RDMA_NUM_PORTS,
+    RDMA_PKEY_TABLE_LEN, RDMA_QP_ACCESS_FLAGS, RDMA_QP_AV, RDMA_QP_CUR_STATE, RDMA_QP_DEST_QPN,
+    RDMA_QP_MAX_DEST_RD_ATOMIC, RDMA_QP_MAX_QP_RD_ATOMIC, RDMA_QP_MIN_RNR_TIMER, RDMA_QP_PATH_MTU,
+    RDMA_QP_PKEY_INDEX, RDMA_QP_PORT, RDMA_QP_QKEY, RDMA_QP_RETRY_CNT, RDMA_QP_RNR_RETRY,
+    RDMA_QP_RQ_PSN, RDMA_QP_SQ_PSN, RDMA_QP_STATE, RDMA_QP_TIMEOUT, RDMA_QPS_ERR, RDMA_QPS_INIT,
+    RDMA_QPS_RESET, RDMA_QPS_RTR, RDMA_QPS_RTS, RDMA_QPS_SQD, RDMA_QPS_SQE, RDMA_QPT_RC,
+    RDMA_QPT_UD,
+};
+
+/// Packet sequence numbers are 24 bits wide, and so are queue pair numbers on the wire.
+const MAX_24BIT: u32 = (1 << 24) - 1;
+/// Largest value of `enum ibv_mtu`, for 4096 bytes.
+const MAX_PATH_MTU: u32 = 5;
+
+/// Attributes of a queue pair, set through `RDMA_CMD_MODIFY_QP`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QpAttributes {
+    /// Combination of the `RDMA_ACCESS_REMOTE_*` flags.
+    pub access_flags: u32,
+    pub pkey_index: u32,
+    pub port_num: u32,
+    pub qkey: u32,
+    /// GID of the destination.
+    pub dgid: [u8; 16],
+    /// Index of the source GID.
+    pub sgid_index: u32,
+    /// One of the values of `enum ibv_mtu`.
+    pub path_mtu: u32,
+    pub timeout: u32,
+    pub retry_cnt: u32,
+    pub rnr_retry: u32,
+    pub rq_psn: u32,
+    pub max_rd_atomic: u32,
+    pub min_rnr_timer: u32,
+    pub sq_psn: u32,
+    pub max_dest_rd_atomic: u32,
+    pub dest_qp_num: u32,
+}
+
+/// Receive work request posted by the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecvWr {
+    /// Identifier of the work request, returned in its work completion.
+    pub wr_id: u64,
+    /// Scatter/gather entries the received data is written to.
+    pub sges: Vec<RdmaSge>,
+}
+
+impl RecvWr {
+    /// Number of bytes the work request receives at most.
+    pub fn byte_len(&self) -> u64 {
+        self.sges.iter().map(|sge| u64::from(sge.length)).sum()
+    }
+}
+
+/// Work requests posted by the driver which the device hasn't completed yet, and the number of
+/// bytes of their buffers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutstandingWork {
+    pub wrs: u32,
+    pub bytes: u64,
+}
+
+impl OutstandingWork {
+    /// Outstanding work of the receive work requests `wrs`.
+    pub fn of<'a>(wrs: impl IntoIterator<Item = &'a RecvWr>) -> Self {
+        wrs.into_iter().fold(Self::default(), |mut work, wr| {
+            work.add(Self {
+                wrs: 1,
+                bytes: wr.byte_len(),
+            });
+            work
+        })
+    }
+
+    pub fn add(&mut self, other: Self) {
+        self.wrs = self.wrs.saturating_add(other.wrs);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+    }
+
+    pub fn sub(&mut self, other: Self) {
+        self.wrs = self.wrs.saturating_sub(other.wrs);
+        self.bytes = self.bytes.saturating_sub(other.bytes);
+    }
+}
+
+/// Queue pair created by the driver.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuePair {
+    /// One of the `RDMA_QPT_*` types.
+    pub qp_type: u32,
+    /// Completion queue of the send queue.
+    pub send_cq: u32,
+    /// Completion queue of the receive queue.
+    pub recv_cq: u32,
+    pub max_send_wr: u32,
+    pub max_recv_wr: u32,
+    pub max_send_sge: u32,
+    pub max_recv_sge: u32,
+    /// Maximum length of the inline data of the send work requests, in bytes.
+    pub max_inline_data: u32,
+    /// Protection domain of the queue pair, which its work requests are confined to.
+    pub pd: u32,
+    /// Shared receive queue the receive work requests are taken from, or 0 if the queue pair
+    /// has its own.
+    pub srq: u32,
+    /// One of the `RDMA_QPS_*` states.
+    pub state: u32,
+    pub attrs: QpAttributes,
+    /// Receive work requests not consumed yet, oldest first.
+    pub recv_queue: VecDeque<RecvWr>,
+    /// Outstanding work of the receive work requests.
+    pub outstanding: OutstandingWork,
+}
+
+impl QueuePair {
+    /// Applies `RDMA_CMD_MODIFY_QP` to the queue pair. Nothing is changed if the command fails.
+    pub fn modify(&mut self, cmd: &RdmaCmdModifyQp) -> Result<(), RdmaCmdError> {
+        if cmd.attr_mask & RDMA_QP_CUR_STATE != 0 && cmd.cur_qp_state != self.state {
+            return Err(RdmaCmdError::QpStateMismatch(self.state, cmd.cur_qp_state));
+        }
+        let next_state = if cmd.attr_mask & RDMA_QP_STATE != 0 {
+            cmd.qp_state
+        } else {
+            self.state
+        };
+        let (required, optional) = transition_attrs(self.qp_type, self.state, next_state)
+            .ok_or(RdmaCmdError::IllegalQpTransition(self.state, next_state))?;
+        let attr_mask = cmd.attr_mask & !(RDMA_QP_STATE | RDMA_QP_CUR_STATE);
+        if attr_mask & required != required || attr_mask & !(required | optional) != 0 {
+            return Err(RdmaCmdError::InvalidQpAttrMask(cmd.attr_mask));
+        }
+
+        let mut attrs = if next_state == RDMA_QPS_RESET {
+            QpAttributes::default()
+        } else {
+            self.attrs.clone()
+        };
+        let has = |attr: u32| attr_mask & attr != 0;
+        if has(RDMA_QP_ACCESS_FLAGS) {
+            let remote_access =
+                RDMA_ACCESS_REMOTE_WRITE | RDMA_ACCESS_REMOTE_READ | RDMA_ACCESS_REMOTE_ATOMIC;
+            // Local writes are always allowed, so drivers may pass them along.
+            if cmd.qp_access_flags & !(remote_access | RDMA_ACCESS_LOCAL_WRITE) != 0 {
+                return Err(RdmaCmdError::InvalidQpAttr(
+                    "qp_access_flags",
+                    cmd.qp_access_flags,
+                ));
+            }
+            attrs.access_flags = cmd.qp_access_flags & remote_access;
+        }
+        if has(RDMA_QP_PKEY_INDEX) {
+            attrs.pkey_index = check_attr("pkey_index", cmd.pkey_index, RDMA_PKEY_TABLE_LEN - 1)?;
+        }
+        if has(RDMA_QP_PORT) {
+            if cmd.port_num == 0 {
+                return Err(RdmaCmdError::InvalidQpAttr("port_num", cmd.port_num));
+            }
+            attrs.port_num = check_attr("port_num", cmd.port_num, RDMA_NUM_PORTS)?;
+        }
+        if has(RDMA_QP_QKEY) {
+            attrs.qkey = cmd.qkey;
+        }
+        if has(RDMA_QP_AV) {
+            attrs.dgid = cmd.dgid;
+            attrs.sgid_index = check_attr("sgid_index", cmd.sgid_index, RDMA_GID_TABLE_LEN - 1)?;
+        }
+        if has(RDMA_QP_PATH_MTU) {
+            if cmd.path_mtu == 0 {
+                return Err(RdmaCmdError::InvalidQpAttr("path_mtu", cmd.path_mtu));
+            }
+            attrs.path_mtu = check_attr("path_mtu", cmd.path_mtu, MAX_PATH_MTU)?;
+        }
+        if has(RDMA_QP_TIMEOUT) {
+            attrs.timeout = check_attr("timeout", cmd.timeout, 31)?;
+        }
+        if has(RDMA_QP_RETRY_CNT) {
+            attrs.retry_cnt = check_attr("retry_cnt", cmd.retry_cnt, 7)?;
+        }
+        if has(RDMA_QP_RNR_RETRY) {
+            attrs.rnr_retry = check_attr("rnr_retry", cmd.rnr_retry, 7)?;
+        }
+        if has(RDMA_QP_RQ_PSN) {
+            attrs.rq_psn = check_attr("rq_psn", cmd.rq_psn, MAX_24BIT)?;
+        }
+        if has(RDMA_QP_MAX_QP_RD_ATOMIC) {
+            attrs.max_rd_atomic =
+                check_attr("max_rd_atomic", cmd.max_rd_atomic, RDMA_MAX_QP_RD_ATOM)?;
+        }
+        if has(RDMA_QP_MIN_RNR_TIMER) {
+            attrs.min_rnr_timer = check_attr("min_rnr_timer", cmd.min_rnr_timer, 31)?;
+        }
+        if has(RDMA_QP_SQ_PSN) {
+            attrs.sq_psn = check_attr("sq_psn", cmd.sq_psn, MAX_24BIT)?;
+        }
+        if has(RDMA_QP_MAX_DEST_RD_ATOMIC) {
+            attrs.max_dest_rd_atomic = check_attr(
+                "max_dest_rd_atomic",
+                cmd.max_dest_rd_atomic,
+                RDMA_MAX_QP_RD_ATOM,
+            )?;
+        }
+        if has(RDMA_QP_DEST_QPN) {
+            attrs.dest_qp_num = check_attr("dest_qp_num", cmd.dest_qp_num, MAX_24BIT)?;
+        }
+
+        self.state = next_state;
+        self.attrs = attrs;
+        Ok(())
+    }
+
+    /// Builds the result of `RDMA_CMD_QUERY_QP`.
+    pub fn query(&self) -> RdmaRspQueryQp {
+        RdmaRspQueryQp {
+            qp_state: self.state,
+            qp_type: self.qp_type,
+            send_cq: self.send_cq,
+            recv_cq: self.recv_cq,
+            max_send_wr: self.max_send_wr,
+            max_recv_wr: self.max_recv_wr,
+            max_send_sge: self.max_send_sge,
+            max_recv_sge: self.max_recv_sge,
+            qp_access_flags: self.attrs.access_flags,
+            pkey_index: self.attrs.pkey_index,
+            port_num: self.attrs.port_num,
+            qkey: self.attrs.qkey,
+            dgid: self.attrs.dgid,
+            sgid_index: self.attrs.sgid_index,
+            path_mtu: self.attrs.path_mtu,
+            timeout: self.attrs.timeout,
+            retry_cnt: self.attrs.retry_cnt,
+            rnr_retry: self.attrs.rnr_retry,
+            rq_psn: self.attrs.rq_psn,
+            max_rd_atomic: self.attrs.max_rd_atomic,
+            min_rnr_timer: self.attrs.min_rnr_timer,
+            sq_psn: self.attrs.sq_psn,
+            max_dest_rd_atomic: self.attrs.max_dest_rd_atomic,
+            dest_qp_num: self.attrs.dest_qp_num,
+            pd: self.pd,
+            max_inline_data: self.max_inline_data,
+        }
+    }
+}
+
+fn check_attr(name: &'static str, value: u32, max: u32) -> Result<u32, RdmaCmdError> {
+    if value > max {
+        return Err(RdmaCmdError::InvalidQpAttr(name, value));
+    }
+    Ok(value)
+}
+
+/// Returns the attributes a transition requires and the ones it accepts on top of them, or
+/// `None` if the transition is illegal. The current state may always be checked.
+fn transition_attrs(qp_type: u32, cur_state: u32, next_state: u32) -> Option<(u32, u32)> {
+    let ud = qp_type == RDMA_QPT_UD;
+    let rc = qp_type == RDMA_QPT_RC;
+    // Attributes set when the queue pair is initialized.
+    let init_attrs = RDMA_QP_PKEY_INDEX
+        | RDMA_QP_PORT
+        | if ud {
+            RDMA_QP_QKEY
+        } else {
+            RDMA_QP_ACCESS_FLAGS
+        };
+    // Attributes which may change while the queue pair sends.
+    let rts_attrs = match qp_type {
+        RDMA_QPT_UD => RDMA_QP_QKEY,
+        RDMA_QPT_RC => RDMA_QP_ACCESS_FLAGS | RDMA_QP_MIN_RNR_TIMER,
+        _ => RDMA_QP_ACCESS_FLAGS,
+    };
+
+    let attrs = match (cur_state, next_state) {
+        // Any queue pair may be reset, or moved to the error state.
+        (RDMA_QPS_RESET..=RDMA_QPS_ERR, RDMA_QPS_RESET | RDMA_QPS_ERR) => (0, 0),
+        (RDMA_QPS_RESET, RDMA_QPS_INIT) => (init_attrs, 0),
+        (RDMA_QPS_INIT, RDMA_QPS_INIT) => (0, init_attrs),
+        (RDMA_QPS_INIT, RDMA_QPS_RTR) if ud => (0, RDMA_QP_PKEY_INDEX | RDMA_QP_QKEY),
+        (RDMA_QPS_INIT, RDMA_QPS_RTR) => {
+            let mut required = RDMA_QP_AV | RDMA_QP_PATH_MTU | RDMA_QP_DEST_QPN | RDMA_QP_RQ_PSN;
+            if rc {
+                required |= RDMA_QP_MAX_DEST_RD_ATOMIC | RDMA_QP_MIN_RNR_TIMER;
+            }
+            (required, RDMA_QP_ACCESS_FLAGS | RDMA_QP_PKEY_INDEX)
+        }
+        (RDMA_QPS_RTR, RDMA_QPS_RTS) if rc => (
+            RDMA_QP_SQ_PSN
+                | RDMA_QP_TIMEOUT
+                | RDMA_QP_RETRY_CNT
+                | RDMA_QP_RNR_RETRY
+                | RDMA_QP_MAX_QP_RD_ATOMIC,
+            rts_attrs,
+        ),
+        (RDMA_QPS_RTR, RDMA_QPS_RTS) => (RDMA_QP_SQ_PSN, rts_attrs),
+        (RDMA_QPS_RTS | RDMA_QPS_SQD, RDMA_QPS_RTS) => (0, rts_attrs),
+        // Reliable connections report send errors by moving to the error state instead.
+        (RDMA_QPS_SQE, RDMA_QPS_RTS) if !rc => (0, rts_attrs),
+        (RDMA_QPS_RTS, RDMA_QPS_SQD) => (0, 0),
+        (RDMA_QPS_SQD, RDMA_QPS_SQD) => match qp_type {
+            RDMA_QPT_UD => (0, RDMA_QP_PKEY_INDEX | RDMA_QP_QKEY),
+            RDMA_QPT_RC => (
+                0,
+                RDMA_QP_AV
+                    | RDMA_QP_TIMEOUT
+                    | RDMA_QP_RETRY_CNT
+                    | RDMA_QP_RNR_RETRY
+                    | RDMA_QP_MAX_QP_RD_ATOMIC
+                    | RDMA_QP_MAX_DEST_RD_ATOMIC
+                    | RDMA_QP_ACCESS_FLAGS
+                    | RDMA_QP_PKEY_INDEX
+                    | RDMA_QP_MIN_RNR_TIMER,
+            ),
+            _ => (0, RDMA_QP_AV | RDMA_QP_ACCESS_FLAGS | RDMA_QP_PKEY_INDEX),
+        },
+        _ => return None,
+    };
+    Some(attrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::rdma::RDMA_QPT_UC;
+
+    fn queue_pair(qp_type: u32) -> QueuePair {
+        QueuePair {
+            qp_type,
+            send_cq: 1,
+            recv_cq: 1,
+            max_send_wr: 16,
+            max_recv_wr: 16,
+            max_send_sge: 1,
+            max_recv_sge: 1,
+            max_inline_data: 0,
+            pd: 1,
+            srq: 0,
+            state: RDMA_QPS_RESET,
+            attrs: QpAttributes::default(),
+            recv_queue: VecDeque::new(),
+            outstanding: OutstandingWork::default(),
+        }
+    }
+
+    fn to_state(qp_state: u32, attr_mask: u32) -> RdmaCmdModifyQp {
+        RdmaCmdModifyQp {
+            attr_mask: RDMA_QP_STATE | attr_mask,
+            qp_state,
+            port_num: 1,
+            path_mtu: 5,
+            dest_qp_num: 7,
+            rq_psn: 0x100,
+            sq_psn: 0x200,
+            timeout: 14,
+            retry_cnt: 7,
+            rnr_retry: 7,
+            min_rnr_timer: 12,
+            max_rd_atomic: 1,
+            max_dest_rd_atomic: 1,
+            qp_access_flags: RDMA_ACCESS_REMOTE_READ,
+            qkey: 0x1111_1111,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rc_transitions() {
+        let mut qp = queue_pair(RDMA_QPT_RC);
+
+        // RESET -> RTR skips INIT.
+        assert_eq!(
+            qp.modify(&to_state(RDMA_QPS_RTR, 0)),
+            Err(RdmaCmdError::IllegalQpTransition(
+                RDMA_QPS_RESET,
+                RDMA_QPS_RTR
+            ))
+        );
+        // INIT requires the port.
+        let init_attrs = RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_ACCESS_FLAGS;
+        qp.modify(&to_state(
+            RDMA_QPS_INIT,
+            RDMA_QP_PKEY_INDEX | RDMA_QP_ACCESS_FLAGS,
+        ))
+        .unwrap_err();
+        qp.modify(&to_state(RDMA_QPS_INIT, init_attrs)).unwrap();
+        assert_eq!(qp.state, RDMA_QPS_INIT);
+        assert_eq!(qp.attrs.port_num, 1);
+        assert_eq!(qp.attrs.access_flags, RDMA_ACCESS_REMOTE_READ);
+
+        // RTR requires the path to the destination.
+        let rtr_attrs = RDMA_QP_AV
+            | RDMA_QP_PATH_MTU
+            | RDMA_QP_DEST_QPN
+            | RDMA_QP_RQ_PSN
+            | RDMA_QP_MAX_DEST_RD_ATOMIC
+            | RDMA_QP_MIN_RNR_TIMER;
+        assert_eq!(
+            qp.modify(&to_state(RDMA_QPS_RTR, rtr_attrs & !RDMA_QP_RQ_PSN)),
+            Err(RdmaCmdError::InvalidQpAttrMask(
+                RDMA_QP_STATE | (rtr_attrs & !RDMA_QP_RQ_PSN)
+            ))
+        );
+        // The send queue PSN can't be set yet.
+        qp.modify(&to_state(RDMA_QPS_RTR, rtr_attrs | RDMA_QP_SQ_PSN))
+            .unwrap_err();
+        qp.modify(&to_state(RDMA_QPS_RTR, rtr_attrs)).unwrap();
+        assert_eq!(qp.attrs.dest_qp_num, 7);
+        assert_eq!(qp.attrs.rq_psn, 0x100);
+        assert_eq!(qp.attrs.path_mtu, 5);
+
+        let rts_attrs = RDMA_QP_SQ_PSN
+            | RDMA_QP_TIMEOUT
+            | RDMA_QP_RETRY_CNT
+            | RDMA_QP_RNR_RETRY
+            | RDMA_QP_MAX_QP_RD_ATOMIC;
+        qp.modify(&to_state(RDMA_QPS_RTS, rts_attrs)).unwrap();
+        assert_eq!(qp.attrs.sq_psn, 0x200);
+
+        // The send queue is drained, then the queue pair sends again.
+        qp.modify(&to_state(RDMA_QPS_SQD, 0)).unwrap();
+        qp.modify(&to_state(RDMA_QPS_SQD, RDMA_QP_TIMEOUT)).unwrap();
+        qp.modify(&to_state(RDMA_QPS_RTS, RDMA_QP_MIN_RNR_TIMER))
+            .unwrap();
+        // Attributes may be changed without a transition.
+        let mut cmd = to_state(0, RDMA_QP_ACCESS_FLAGS);
+        cmd.attr_mask &= !RDMA_QP_STATE;
+        cmd.qp_access_flags = RDMA_ACCESS_REMOTE_WRITE;
+        qp.modify(&cmd).unwrap();
+        assert_eq!(qp.state, RDMA_QPS_RTS);
+        assert_eq!(qp.attrs.access_flags, RDMA_ACCESS_REMOTE_WRITE);
+        // Reliable connections never enter SQE.
+        qp.modify(&to_state(RDMA_QPS_SQE, 0)).unwrap_err();
+
+        qp.modify(&to_state(RDMA_QPS_ERR, 0)).unwrap();
+        qp.modify(&to_state(RDMA_QPS_RTS, 0)).unwrap_err();
+        // Resetting the queue pair clears its attributes.
+        qp.modify(&to_state(RDMA_QPS_RESET, 0)).unwrap();
+        assert_eq!(qp.state, RDMA_QPS_RESET);
+        assert_eq!(qp.attrs, QpAttributes::default());
+    }
+
+    #[test]
+    fn test_ud_transitions() {
+        let mut qp = queue_pair(RDMA_QPT_UD);
+
+        // Datagram queue pairs have a Q_Key and no access flags.
+        qp.modify(&to_state(
+            RDMA_QPS_INIT,
+            RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_ACCESS_FLAGS,
+        ))
+        .unwrap_err();
+        qp.modify(&to_state(
+            RDMA_QPS_INIT,
+            RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_QKEY,
+        ))
+        .unwrap();
+        assert_eq!(qp.attrs.qkey, 0x1111_1111);
+        // ... and no destination.
+        qp.modify(&to_state(RDMA_QPS_RTR, RDMA_QP_DEST_QPN))
+            .unwrap_err();
+        qp.modify(&to_state(RDMA_QPS_RTR, 0)).unwrap();
+        qp.modify(&to_state(RDMA_QPS_RTS, RDMA_QP_SQ_PSN)).unwrap();
+
+        // A send error is recovered from.
+        qp.state = RDMA_QPS_SQE;
+        qp.modify(&to_state(RDMA_QPS_RTS, 0)).unwrap();
+    }
+
+    #[test]
+    fn test_uc_transitions() {
+        let mut qp = queue_pair(RDMA_QPT_UC);
+        qp.modify(&to_state(
+            RDMA_QPS_INIT,
+            RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_ACCESS_FLAGS,
+        ))
+        .unwrap();
+        // Unreliable connections have no RDMA reads.
+        let rtr_attrs = RDMA_QP_AV | RDMA_QP_PATH_MTU | RDMA_QP_DEST_QPN | RDMA_QP_RQ_PSN;
+        qp.modify(&to_state(
+            RDMA_QPS_RTR,
+            rtr_attrs | RDMA_QP_MAX_DEST_RD_ATOMIC,
+        ))
+        .unwrap_err();
+        qp.modify(&to_state(RDMA_QPS_RTR, rtr_attrs)).unwrap();
+        qp.modify(&to_state(RDMA_QPS_RTS, RDMA_QP_SQ_PSN)).unwrap();
+        assert_eq!(qp.state, RDMA_QPS_RTS);
+    }
+
+    #[test]
+    fn test_modify_errors() {
+        let mut qp = queue_pair(RDMA_QPT_RC);
+        let init_attrs = RDMA_QP_PKEY_INDEX | RDMA_QP_PORT | RDMA_QP_ACCESS_FLAGS;
+
+        // The current state doesn't match.
+        let mut cmd = to_state(RDMA_QPS_INIT, init_attrs | RDMA_QP_CUR_STATE);
+        cmd.cur_qp_state = RDMA_QPS_RTS;
+        assert_eq!(
+            qp.modify(&cmd),
+            Err(RdmaCmdError::QpStateMismatch(RDMA_QPS_RESET, RDMA_QPS_RTS))
+        );
+        cmd.cur_qp_state = RDMA_QPS_RESET;
+        qp.modify(&cmd).unwrap();
+
+        // Unknown states and attributes.
+        assert_eq!(
+            qp.modify(&to_state(42, 0)),
+            Err(RdmaCmdError::IllegalQpTransition(RDMA_QPS_INIT, 42))
+        );
+        qp.modify(&to_state(RDMA_QPS_INIT, 1 << 31)).unwrap_err();
+
+        // Out of range attributes leave the queue pair untouched.
+        let before = qp.clone();
+        for (attr, cmd) in [
+            (
+                "port_num",
+                RdmaCmdModifyQp {
+                    port_num: 2,
+                    ..to_state(RDMA_QPS_INIT, RDMA_QP_PORT)
+                },
+            ),
+            (
+                "port_num",
+                RdmaCmdModifyQp {
+                    port_num: 0,
+                    ..to_state(RDMA_QPS_INIT, RDMA_QP_PORT)
+                },
+            ),
+            (
+                "pkey_index",
+                RdmaCmdModifyQp {
+                    pkey_index: RDMA_PKEY_TABLE_LEN,
+                    ..to_state(RDMA_QPS_INIT, RDMA_QP_PKEY_INDEX)
+                },
+            ),
+            (
+                "qp_access_flags",
+                RdmaCmdModifyQp {
+                    qp_access_flags: 1 << 4,
+                    ..to_state(RDMA_QPS_INIT, RDMA_QP_ACCESS_FLAGS)
+                },
+            ),
+        ] {
+            assert!(
+                matches!(qp.modify(&cmd), Err(RdmaCmdError::InvalidQpAttr(name, _)) if name == attr)
+            );
+        }
+        let rtr_attrs = RDMA_QP_AV
+            | RDMA_QP_PATH_MTU
+            | RDMA_QP_DEST_QPN
+            | RDMA_QP_RQ_PSN
+            | RDMA_QP_MAX_DEST_RD_ATOMIC
+            | RDMA_QP_MIN_RNR_TIMER;
+        for cmd in [
+            RdmaCmdModifyQp {
+                path_mtu: 6,
+                ..to_state(RDMA_QPS_RTR, rtr_attrs)
+            },
+            RdmaCmdModifyQp {
+                rq_psn: 1 << 24,
+                ..to_state(RDMA_QPS_RTR, rtr_attrs)
+            },
+            RdmaCmdModifyQp {
+                dest_qp_num: 1 << 24,
+                ..to_state(RDMA_QPS_RTR, rtr_attrs)
+            },
+            RdmaCmdModifyQp {
+                min_rnr_timer: 32,
+                ..to_state(RDMA_QPS_RTR, rtr_attrs)
+            },
+            RdmaCmdModifyQp {
+                max_dest_rd_atomic: RDMA_MAX_QP_RD_ATOM + 1,
+                ..to_state(RDMA_QPS_RTR, rtr_attrs)
+            },
+        ] {
+            assert!(matches!(
+                qp.modify(&cmd),
+                Err(RdmaCmdError::InvalidQpAttr(..))
+            ));
+        }
+        assert_eq!(qp, before);
+    }
+}
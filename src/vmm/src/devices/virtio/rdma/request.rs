@@ -0,0 +1,975 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Layout of the commands of the control queue. All the fields are little endian.
+
+use serde::{Deserialize, Serialize};
+use vm_memory::ByteValued;
+
+use super::{
+    RDMA_STATUS_BAD_ADDRESS, RDMA_STATUS_BUSY, RDMA_STATUS_INVALID_ACCESS, RDMA_STATUS_INVALID_ARG,
+    RDMA_STATUS_INVALID_HANDLE, RDMA_STATUS_INVALID_STATE, RDMA_STATUS_NO_RESOURCES,
+    RDMA_STATUS_UNSUPPORTED,
+};
+
+/// Header of a command.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdHdr {
+    /// One of the `RDMA_CMD_*` opcodes.
+    pub opcode: u32,
+    pub reserved: u32,
+}
+
+/// Header of the response to a command.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspHdr {
+    /// One of the `RDMA_STATUS_*` codes.
+    pub status: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_CREATE_QP`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdCreateQp {
+    /// One of the `RDMA_QPT_*` types.
+    pub qp_type: u32,
+    /// Completion queue of the send queue.
+    pub send_cq: u32,
+    /// Completion queue of the receive queue.
+    pub recv_cq: u32,
+    pub max_send_wr: u32,
+    pub max_recv_wr: u32,
+    pub max_send_sge: u32,
+    pub max_recv_sge: u32,
+    /// Protection domain of the queue pair.
+    pub pd: u32,
+    /// Shared receive queue the receive work requests are taken from, or 0 for the queue pair
+    /// to have its own receive queue, in which case `max_recv_wr` and `max_recv_sge` are
+    /// ignored.
+    pub srq: u32,
+    /// Maximum length of the inline data of the send work requests, in bytes.
+    pub max_inline_data: u32,
+}
+
+/// Result of `RDMA_CMD_CREATE_QP`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspCreateQp {
+    /// Number of the queue pair, used to reference it in later commands.
+    pub qpn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DESTROY_QP`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDestroyQp {
+    pub qpn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_CREATE_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdCreateCq {
+    /// Number of entries of the completion queue.
+    pub cqe: u32,
+    /// Combination of the `RDMA_CQ_F_*` flags.
+    pub flags: u32,
+}
+
+/// Result of `RDMA_CMD_CREATE_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspCreateCq {
+    /// Number of the completion queue, used to reference it in later commands.
+    pub cqn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_RESIZE_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdResizeCq {
+    pub cqn: u32,
+    /// New number of entries of the completion queue.
+    pub cqe: u32,
+}
+
+/// Result of `RDMA_CMD_RESIZE_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspResizeCq {
+    /// Number of entries of the resized completion queue.
+    pub cqe: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DESTROY_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDestroyCq {
+    pub cqn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_REG_MR`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdRegMr {
+    /// Guest physical address of the start of the memory region.
+    pub iova: u64,
+    /// Length of the memory region, in bytes.
+    pub length: u64,
+    /// Combination of the `RDMA_ACCESS_*` flags.
+    pub access: u32,
+    /// Protection domain of the memory region.
+    pub pd: u32,
+}
+
+/// Result of `RDMA_CMD_REG_MR`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspRegMr {
+    /// Key used by the local work requests to reference the memory region.
+    pub lkey: u32,
+    /// Key used by remote peers to reference the memory region, or 0 if the memory region
+    /// doesn't allow remote access.
+    pub rkey: u32,
+}
+
+/// Arguments of `RDMA_CMD_DEREG_MR`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDeregMr {
+    pub lkey: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_MODIFY_QP`. Only the attributes selected by `attr_mask` are read.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdModifyQp {
+    pub qpn: u32,
+    /// Combination of the `RDMA_QP_*` attributes.
+    pub attr_mask: u32,
+    /// New state, one of the `RDMA_QPS_*` states.
+    pub qp_state: u32,
+    /// Expected current state.
+    pub cur_qp_state: u32,
+    /// Combination of the `RDMA_ACCESS_REMOTE_*` flags.
+    pub qp_access_flags: u32,
+    pub pkey_index: u32,
+    pub port_num: u32,
+    pub qkey: u32,
+    /// GID of the destination, part of the address vector.
+    pub dgid: [u8; 16],
+    /// Index of the source GID, part of the address vector.
+    pub sgid_index: u32,
+    /// One of the values of `enum ibv_mtu`.
+    pub path_mtu: u32,
+    pub timeout: u32,
+    pub retry_cnt: u32,
+    pub rnr_retry: u32,
+    pub rq_psn: u32,
+    pub max_rd_atomic: u32,
+    pub min_rnr_timer: u32,
+    pub sq_psn: u32,
+    pub max_dest_rd_atomic: u32,
+    pub dest_qp_num: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_QUERY_QP`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdQueryQp {
+    pub qpn: u32,
+    pub reserved: u32,
+}
+
+/// Result of `RDMA_CMD_QUERY_QP`. Attributes that were never set through `RDMA_CMD_MODIFY_QP`,
+/// or were cleared by moving the queue pair to the reset state, are 0.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspQueryQp {
+    /// Current state, one of the `RDMA_QPS_*` states.
+    pub qp_state: u32,
+    /// One of the `RDMA_QPT_*` types.
+    pub qp_type: u32,
+    /// Completion queue of the send queue.
+    pub send_cq: u32,
+    /// Completion queue of the receive queue.
+    pub recv_cq: u32,
+    pub max_send_wr: u32,
+    pub max_recv_wr: u32,
+    pub max_send_sge: u32,
+    pub max_recv_sge: u32,
+    /// Combination of the `RDMA_ACCESS_REMOTE_*` flags.
+    pub qp_access_flags: u32,
+    pub pkey_index: u32,
+    pub port_num: u32,
+    pub qkey: u32,
+    /// GID of the destination, part of the address vector.
+    pub dgid: [u8; 16],
+    /// Index of the source GID, part of the address vector.
+    pub sgid_index: u32,
+    /// One of the values of `enum ibv_mtu`.
+    pub path_mtu: u32,
+    pub timeout: u32,
+    pub retry_cnt: u32,
+    pub rnr_retry: u32,
+    pub rq_psn: u32,
+    pub max_rd_atomic: u32,
+    pub min_rnr_timer: u32,
+    pub sq_psn: u32,
+    pub max_dest_rd_atomic: u32,
+    pub dest_qp_num: u32,
+    /// Protection domain of the queue pair.
+    pub pd: u32,
+    /// Maximum length of the inline data of the send work requests, in bytes.
+    pub max_inline_data: u32,
+}
+
+/// Result of `RDMA_CMD_QUERY_DEVICE`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspQueryDevice {
+    /// Largest length of a memory region, in bytes.
+    pub max_mr_size: u64,
+    /// Page sizes supported by the memory regions, as a bitmask.
+    pub page_size_cap: u64,
+    pub max_qp: u32,
+    /// Maximum number of outstanding work requests on a queue of a queue pair.
+    pub max_qp_wr: u32,
+    /// Maximum number of scatter/gather entries of a work request.
+    pub max_sge: u32,
+    pub max_cq: u32,
+    /// Maximum number of entries of a completion queue.
+    pub max_cqe: u32,
+    pub max_mr: u32,
+    /// Maximum number of outstanding RDMA reads and atomics a queue pair handles as target.
+    pub max_qp_rd_atom: u32,
+    /// Maximum number of outstanding RDMA reads and atomics a queue pair initiates.
+    pub max_qp_init_rd_atom: u32,
+    /// One of the `RDMA_ATOMIC_*` capabilities.
+    pub atomic_cap: u32,
+    /// Number of ports, numbered from 1.
+    pub phys_port_cnt: u32,
+    /// Number of entries of the P_Key table of a port.
+    pub max_pkeys: u32,
+    pub max_pd: u32,
+    pub max_ah: u32,
+    pub max_srq: u32,
+    /// Maximum number of outstanding work requests on a shared receive queue.
+    pub max_srq_wr: u32,
+    /// Maximum number of scatter/gather entries of a work request of a shared receive queue.
+    pub max_srq_sge: u32,
+    pub max_mw: u32,
+    /// Maximum length of the inline data of a work request, in bytes.
+    pub max_inline_data: u32,
+}
+
+/// Arguments of `RDMA_CMD_QUERY_PORT`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdQueryPort {
+    /// Number of the port, starting from 1.
+    pub port_num: u32,
+    pub reserved: u32,
+}
+
+/// Result of `RDMA_CMD_QUERY_PORT`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspQueryPort {
+    /// State of the link, one of the `RDMA_PORT_*` states.
+    pub state: u32,
+    /// Largest MTU supported by the port, one of the `RDMA_MTU_*` values.
+    pub max_mtu: u32,
+    /// Current MTU of the port, one of the `RDMA_MTU_*` values.
+    pub active_mtu: u32,
+    /// Number of entries of the GID table.
+    pub gid_tbl_len: u32,
+    /// Number of entries of the P_Key table.
+    pub pkey_tbl_len: u32,
+    /// Largest message a work request may carry, in bytes.
+    pub max_msg_sz: u32,
+    /// One of the `RDMA_LINK_LAYER_*` link layers.
+    pub link_layer: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_QUERY_GID`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdQueryGid {
+    /// Number of the port, starting from 1.
+    pub port_num: u32,
+    /// Index of the entry in the GID table of the port.
+    pub index: u32,
+}
+
+/// Result of `RDMA_CMD_QUERY_GID`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspQueryGid {
+    /// GID of the entry, all zeros if the entry is empty.
+    pub gid: [u8; 16],
+    /// One of the `RDMA_GID_TYPE_*` types.
+    pub gid_type: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_QUERY_PKEY`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdQueryPkey {
+    /// Number of the port, starting from 1.
+    pub port_num: u32,
+    /// Index of the entry in the P_Key table of the port.
+    pub index: u32,
+}
+
+/// Result of `RDMA_CMD_QUERY_PKEY`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspQueryPkey {
+    /// P_Key of the entry, 0 if the entry is empty.
+    pub pkey: u32,
+    pub reserved: u32,
+}
+
+/// Result of `RDMA_CMD_ALLOC_PD`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspAllocPd {
+    /// Number of the protection domain, used to reference it in later commands.
+    pub pdn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DEALLOC_PD`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDeallocPd {
+    pub pdn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_CREATE_AH`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdCreateAh {
+    /// Protection domain of the address handle.
+    pub pd: u32,
+    pub port_num: u32,
+    /// GID of the destination.
+    pub dgid: [u8; 16],
+    /// Index of the source GID.
+    pub sgid_index: u32,
+    /// Traffic class of the global route header, up to 255.
+    pub traffic_class: u32,
+    /// Hop limit of the global route header, up to 255.
+    pub hop_limit: u32,
+    pub reserved: u32,
+}
+
+/// Result of `RDMA_CMD_CREATE_AH`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspCreateAh {
+    /// Handle of the address handle, used to reference it in later commands and work requests.
+    pub ah: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DESTROY_AH`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDestroyAh {
+    pub ah: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_POST_SEND`, followed by `num_sge` `RdmaSge` entries, by `inline_len`
+/// bytes of data for `RDMA_SEND_INLINE`, by a `RdmaBindMw` for `RDMA_WR_BIND_MW`, or by a
+/// `RdmaAtomic` and then the `RdmaSge` entries for atomics.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdPostSend {
+    /// Identifier of the work request, returned in its work completion.
+    pub wr_id: u64,
+    /// Remote address of RDMA writes and atomics.
+    pub remote_addr: u64,
+    pub qpn: u32,
+    /// One of the `RDMA_WR_*` opcodes.
+    pub opcode: u32,
+    /// Combination of the `RDMA_SEND_*` flags.
+    pub send_flags: u32,
+    pub num_sge: u32,
+    /// Immediate data, in network byte order, or the remote key `RDMA_WR_LOCAL_INV` and
+    /// `RDMA_WR_SEND_WITH_INV` invalidate.
+    pub imm_data: u32,
+    /// Remote key of RDMA writes and atomics.
+    pub rkey: u32,
+    /// Address handle of the destination of unreliable datagram queue pairs.
+    pub ah: u32,
+    /// Destination queue pair of unreliable datagram queue pairs.
+    pub remote_qpn: u32,
+    /// Q_Key of the destination of unreliable datagram queue pairs.
+    pub remote_qkey: u32,
+    /// Length of the data following the arguments of `RDMA_SEND_INLINE` work requests.
+    pub inline_len: u32,
+}
+
+/// Binding of a memory window, following the arguments of `RDMA_CMD_POST_SEND` for
+/// `RDMA_WR_BIND_MW`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaBindMw {
+    /// Guest physical address of the start of the window, within the memory region.
+    pub addr: u64,
+    /// Length of the window, in bytes.
+    pub length: u64,
+    /// Current remote key of the memory window.
+    pub mw_rkey: u32,
+    /// Remote key of the memory window once bound, only differing from `mw_rkey` in its low 8
+    /// bits.
+    pub rkey: u32,
+    /// Local key of the memory region the window is bound to.
+    pub lkey: u32,
+    /// Combination of the `RDMA_ACCESS_REMOTE_*` flags.
+    pub access: u32,
+}
+
+/// Operands of an atomic operation, following the arguments of `RDMA_CMD_POST_SEND` for
+/// `RDMA_WR_ATOMIC_CMP_AND_SWP` and `RDMA_WR_ATOMIC_FETCH_AND_ADD`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaAtomic {
+    /// Value compared with the remote one, or added to it.
+    pub compare_add: u64,
+    /// Value swapped with the remote one when they are equal.
+    pub swap: u64,
+}
+
+/// Arguments of `RDMA_CMD_POST_RECV`, followed by `num_sge` `RdmaSge` entries.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdPostRecv {
+    /// Identifier of the work request, returned in its work completion.
+    pub wr_id: u64,
+    pub qpn: u32,
+    pub num_sge: u32,
+}
+
+/// Arguments of `RDMA_CMD_CREATE_SRQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdCreateSrq {
+    /// Protection domain of the shared receive queue.
+    pub pd: u32,
+    pub max_wr: u32,
+    pub max_sge: u32,
+    /// Limit the shared receive queue is armed with, or 0.
+    pub srq_limit: u32,
+}
+
+/// Result of `RDMA_CMD_CREATE_SRQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspCreateSrq {
+    /// Number of the shared receive queue, used to reference it in later commands.
+    pub srqn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DESTROY_SRQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDestroySrq {
+    pub srqn: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_MODIFY_SRQ`. Only the attributes selected by `attr_mask` are read.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdModifySrq {
+    pub srqn: u32,
+    /// Combination of the `RDMA_SRQ_*` attributes.
+    pub attr_mask: u32,
+    pub max_wr: u32,
+    pub srq_limit: u32,
+}
+
+/// Arguments of `RDMA_CMD_POST_SRQ_RECV`, followed by `num_sge` `RdmaSge` entries.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdPostSrqRecv {
+    /// Identifier of the work request, returned in its work completion.
+    pub wr_id: u64,
+    pub srqn: u32,
+    pub num_sge: u32,
+}
+
+/// Arguments of `RDMA_CMD_ALLOC_MW`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdAllocMw {
+    /// Protection domain of the memory window.
+    pub pd: u32,
+    /// Type of the memory window, which must be `RDMA_MW_TYPE_2`.
+    pub mw_type: u32,
+}
+
+/// Result of `RDMA_CMD_ALLOC_MW`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspAllocMw {
+    /// Remote key of the memory window, used to reference it in later commands and work
+    /// requests.
+    pub rkey: u32,
+    pub reserved: u32,
+}
+
+/// Arguments of `RDMA_CMD_DEALLOC_MW`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdDeallocMw {
+    /// Current remote key of the memory window.
+    pub rkey: u32,
+    pub reserved: u32,
+}
+
+/// Scatter/gather entry of a work request.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RdmaSge {
+    pub addr: u64,
+    pub length: u32,
+    /// Local key of the memory region holding the entry.
+    pub lkey: u32,
+}
+
+/// Arguments of `RDMA_CMD_POLL_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdPollCq {
+    pub cqn: u32,
+    /// Maximum number of work completions to retrieve.
+    pub num_entries: u32,
+}
+
+/// Arguments of `RDMA_CMD_REQ_NOTIFY_CQ`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCmdReqNotifyCq {
+    pub cqn: u32,
+    /// One of the `RDMA_CQ_SOLICITED` and `RDMA_CQ_NEXT_COMP` flags.
+    pub flags: u32,
+}
+
+/// Result of `RDMA_CMD_POLL_CQ`, followed by `num_entries` `RdmaWc` entries.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaRspPollCq {
+    pub num_entries: u32,
+    pub reserved: u32,
+}
+
+/// Header of the buffers of the completion queue of the device, followed by `num_entries`
+/// `RdmaWc` entries of the completion queue `cqn`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaCqEvent {
+    pub cqn: u32,
+    pub num_entries: u32,
+}
+
+/// Asynchronous event, written to the buffers of the event queue of the device.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RdmaAsyncEvent {
+    /// One of the `RDMA_EVENT_*` types.
+    pub event_type: u32,
+    /// Handle of the resource the event is about.
+    pub handle: u32,
+}
+
+/// Work completion.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RdmaWc {
+    /// Identifier of the completed work request.
+    pub wr_id: u64,
+    /// One of the `RDMA_WC_*` status.
+    pub status: u32,
+    /// One of the `RDMA_WC_*` opcodes.
+    pub opcode: u32,
+    /// Number of bytes received, for receive completions.
+    pub byte_len: u32,
+    /// Immediate data, in network byte order.
+    pub imm_data: u32,
+    pub qp_num: u32,
+    /// Source queue pair, for receive completions of unreliable datagram queue pairs.
+    pub src_qp: u32,
+    /// Combination of the `RDMA_WC_*` flags.
+    pub wc_flags: u32,
+    pub reserved: u32,
+}
+
+/// Configuration space of the device, which the driver may not write.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RdmaConfigSpace {
+    /// Number of data queue pairs, each made of a send queue and a receive queue.
+    pub num_queues: u32,
+    /// Limits of the device, as reported by `RDMA_CMD_QUERY_DEVICE`.
+    pub max_qp: u32,
+    pub max_cq: u32,
+    pub max_mr: u32,
+    pub max_pd: u32,
+    pub max_ah: u32,
+    pub max_sge: u32,
+    /// State of the link of the ports, one of the `RDMA_PORT_*` states.
+    pub port_state: u32,
+    /// Current MTU of the ports, one of the `RDMA_MTU_*` values.
+    pub active_mtu: u32,
+    /// Node GUID of the device, in network byte order.
+    pub node_guid: [u8; 8],
+}
+
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdHdr {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspHdr {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdCreateQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspCreateQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDestroyQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdCreateCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspCreateCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDestroyCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdResizeCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspResizeCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdRegMr {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspRegMr {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDeregMr {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdModifyQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdQueryQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspQueryQp {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspQueryDevice {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdQueryPort {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspQueryPort {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdQueryGid {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspQueryGid {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdQueryPkey {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspQueryPkey {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspAllocPd {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDeallocPd {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdCreateAh {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspCreateAh {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDestroyAh {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdPostSend {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaBindMw {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaAtomic {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdCreateSrq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspCreateSrq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDestroySrq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdModifySrq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdPostSrqRecv {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdAllocMw {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspAllocMw {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdDeallocMw {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaSge {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdPostRecv {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdPollCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaRspPollCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCmdReqNotifyCq {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaCqEvent {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaAsyncEvent {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaWc {}
+// SAFETY: The structures only contain integers and have no padding.
+unsafe impl ByteValued for RdmaConfigSpace {}
+
+/// Errors of a command, reported to the driver in the status of the response.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum RdmaCmdError {
+    /// Unsupported opcode {0}
+    UnsupportedOpcode(u32),
+    /// Command {0} can't be sent on this queue
+    UnexpectedQueue(u32),
+    /// The arguments of the command are too short
+    ArgumentsTooShort,
+    /// The response buffer cannot hold the result of the command
+    ResponseTooShort,
+    /// Invalid queue pair type {0}
+    InvalidQpType(u32),
+    /// The queue pair capabilities exceed the device limits
+    InvalidQpCap,
+    /// Unknown queue pair {0}
+    UnknownQp(u32),
+    /// No queue pair left
+    NoQpLeft,
+    /// Invalid number of completion queue entries {0}
+    InvalidCqSize(u32),
+    /// Invalid completion queue flags {0:#x}
+    InvalidCqFlags(u32),
+    /// Unknown completion queue {0}
+    UnknownCq(u32),
+    /// Completion queue {0} is still used by a queue pair
+    CqInUse(u32),
+    /// Completion queue {0} holds {1} work completions, more than its new size
+    CqTooSmall(u32, usize),
+    /// No completion queue left
+    NoCqLeft,
+    /// Invalid completion queue notification flags {0:#x}
+    InvalidNotifyFlags(u32),
+    /// Completion queue {0} pushes its work completions and cannot be armed
+    CqNotPolled(u32),
+    /// Invalid memory region length {0}
+    InvalidMrLength(u64),
+    /// Invalid memory region access flags {0:#x}
+    InvalidMrAccess(u32),
+    /// The memory region [{0:#x}, +{1:#x}) is not backed by guest memory
+    MrOutOfGuestMemory(u64, u64),
+    /// Unknown memory region {0:#x}
+    UnknownMr(u32),
+    /// No memory region left
+    NoMrLeft,
+    /// Registering {0} more bytes of memory regions exceeds the limit of {1} bytes
+    MrLimitExceeded(u64, u64),
+    /// Illegal queue pair transition from state {0} to state {1}
+    IllegalQpTransition(u32, u32),
+    /// The queue pair is in state {0}, not in state {1}
+    QpStateMismatch(u32, u32),
+    /// Invalid queue pair attribute mask {0:#x}
+    InvalidQpAttrMask(u32),
+    /// Invalid queue pair attribute {0}: {1}
+    InvalidQpAttr(&'static str, u32),
+    /// Invalid port number {0}
+    InvalidPort(u32),
+    /// Invalid GID table index {0}
+    InvalidGidIndex(u32),
+    /// Invalid P_Key table index {0}
+    InvalidPkeyIndex(u32),
+    /// Unknown protection domain {0}
+    UnknownPd(u32),
+    /// Protection domain {0} is still used by a queue pair, a memory region or another resource
+    PdInUse(u32),
+    /// No protection domain left
+    NoPdLeft,
+    /// Memory region {0:#x} is not in protection domain {1}
+    MrPdMismatch(u32, u32),
+    /// The range [{1:#x}, +{2:#x}) is not within memory region {0:#x}
+    OutOfMrBounds(u32, u64, u32),
+    /// Memory region {0:#x} does not allow local writes
+    MrNotWritable(u32),
+    /// Memory region {0:#x} was invalidated by the removal of its guest memory
+    MrInvalidated(u32),
+    /// Invalid address handle attribute {0}: {1}
+    InvalidAhAttr(&'static str, u32),
+    /// Unknown address handle {0}
+    UnknownAh(u32),
+    /// No address handle left
+    NoAhLeft,
+    /// Address handle {0} is not in protection domain {1}
+    AhPdMismatch(u32, u32),
+    /// Invalid work request opcode {0} for queue pair type {1}
+    InvalidWrOpcode(u32, u32),
+    /// Invalid send flags {0:#x}
+    InvalidSendFlags(u32),
+    /// The work request has {0} scatter/gather entries, more than its queue pair allows
+    TooManySge(u32),
+    /// Atomic work requests take a single scatter/gather entry of 8 bytes
+    InvalidAtomicSge,
+    /// The work request has {0} bytes of inline data, more than its queue pair allows
+    InlineDataTooLong(u32),
+    /// The memory region or window {0:#x} does not allow the remote access {1:#x}
+    RemoteAccessDenied(u32, u32),
+    /// Queue pair {0} has no receive work request
+    NoRecvWr(u32),
+    /// The receive queue of queue pair {0} is full
+    RecvQueueFull(u32),
+    /// The work requests outstanding on queue pair {0} reached its quota
+    QpWrQuotaExceeded(u32),
+    /// The work requests outstanding in protection domain {0} reached its quota
+    PdWrQuotaExceeded(u32),
+    /// The shared receive queue capabilities exceed the device limits
+    InvalidSrqCap,
+    /// Invalid shared receive queue attribute mask {0:#x}
+    InvalidSrqAttrMask(u32),
+    /// Invalid shared receive queue attribute {0}: {1}
+    InvalidSrqAttr(&'static str, u32),
+    /// Unknown shared receive queue {0}
+    UnknownSrq(u32),
+    /// Shared receive queue {0} is still used by a queue pair
+    SrqInUse(u32),
+    /// No shared receive queue left
+    NoSrqLeft,
+    /// The shared receive queue {0} is full
+    SrqFull(u32),
+    /// Queue pair {0} takes its receive work requests from a shared receive queue
+    QpHasSrq(u32),
+    /// Invalid memory window type {0}
+    InvalidMwType(u32),
+    /// Unknown memory window {0:#x}
+    UnknownMw(u32),
+    /// No memory window left
+    NoMwLeft,
+    /// Memory window {0:#x} is not in protection domain {1}
+    MwPdMismatch(u32, u32),
+    /// Memory region {0:#x} is still bound to a memory window
+    MrInUse(u32),
+    /// Memory region {0:#x} does not allow binding memory windows
+    MrNotBindable(u32),
+    /// Invalid memory window access flags {0:#x}
+    InvalidMwAccess(u32),
+    /// Remote key {1:#x} differs from the key {0:#x} of its memory window beyond its low 8 bits
+    InvalidMwRkey(u32, u32),
+    /// The memory window [{1:#x}, +{2:#x}) is not within memory region {0:#x}
+    MwOutOfMrBounds(u32, u64, u64),
+    /// Memory window {0:#x} is already bound
+    MwBound(u32),
+    /// Memory window {0:#x} is not bound
+    MwNotBound(u32),
+    /// Memory window {0:#x} is not bound to queue pair {1}
+    MwQpMismatch(u32, u32),
+    /// The range [{1:#x}, +{2:#x}) is not within memory window {0:#x}
+    OutOfMwBounds(u32, u64, u32),
+    /// The immediate data or invalidated remote key of a message of opcode {0} doesn't match it
+    InvalidMsgHeader(u32),
+    /// The message of {0} bytes exceeds the maximum message size
+    MsgTooLong(usize),
+}
+
+impl RdmaCmdError {
+    /// Status reported to the driver.
+    pub fn status(&self) -> u32 {
+        match self {
+            RdmaCmdError::UnsupportedOpcode(_) | RdmaCmdError::UnexpectedQueue(_) => {
+                RDMA_STATUS_UNSUPPORTED
+            }
+            RdmaCmdError::ArgumentsTooShort
+            | RdmaCmdError::ResponseTooShort
+            | RdmaCmdError::InvalidQpType(_)
+            | RdmaCmdError::InvalidQpCap
+            | RdmaCmdError::InvalidCqSize(_)
+            | RdmaCmdError::InvalidCqFlags(_)
+            | RdmaCmdError::CqTooSmall(..)
+            | RdmaCmdError::InvalidNotifyFlags(_)
+            | RdmaCmdError::CqNotPolled(_)
+            | RdmaCmdError::InvalidMrLength(_)
+            | RdmaCmdError::InvalidQpAttrMask(_)
+            | RdmaCmdError::InvalidQpAttr(..)
+            | RdmaCmdError::InvalidPort(_)
+            | RdmaCmdError::InvalidGidIndex(_)
+            | RdmaCmdError::InvalidPkeyIndex(_)
+            | RdmaCmdError::InvalidAhAttr(..)
+            | RdmaCmdError::InvalidWrOpcode(..)
+            | RdmaCmdError::InvalidSendFlags(_)
+            | RdmaCmdError::TooManySge(_)
+            | RdmaCmdError::InvalidAtomicSge
+            | RdmaCmdError::InlineDataTooLong(_)
+            | RdmaCmdError::InvalidSrqCap
+            | RdmaCmdError::InvalidSrqAttrMask(_)
+            | RdmaCmdError::InvalidSrqAttr(..)
+            | RdmaCmdError::QpHasSrq(_)
+            | RdmaCmdError::InvalidMwType(_)
+            | RdmaCmdError::InvalidMwRkey(..)
+            | RdmaCmdError::InvalidMsgHeader(_)
+            | RdmaCmdError::MsgTooLong(_) => RDMA_STATUS_INVALID_ARG,
+            RdmaCmdError::UnknownQp(_)
+            | RdmaCmdError::UnknownCq(_)
+            | RdmaCmdError::UnknownMr(_)
+            | RdmaCmdError::UnknownPd(_)
+            | RdmaCmdError::UnknownAh(_)
+            | RdmaCmdError::UnknownSrq(_)
+            | RdmaCmdError::UnknownMw(_) => RDMA_STATUS_INVALID_HANDLE,
+            RdmaCmdError::NoQpLeft
+            | RdmaCmdError::NoCqLeft
+            | RdmaCmdError::NoMrLeft
+            | RdmaCmdError::MrLimitExceeded(..)
+            | RdmaCmdError::NoPdLeft
+            | RdmaCmdError::NoAhLeft
+            | RdmaCmdError::NoRecvWr(_)
+            | RdmaCmdError::RecvQueueFull(_)
+            | RdmaCmdError::QpWrQuotaExceeded(_)
+            | RdmaCmdError::PdWrQuotaExceeded(_)
+            | RdmaCmdError::NoSrqLeft
+            | RdmaCmdError::SrqFull(_)
+            | RdmaCmdError::NoMwLeft => RDMA_STATUS_NO_RESOURCES,
+            RdmaCmdError::CqInUse(_)
+            | RdmaCmdError::PdInUse(_)
+            | RdmaCmdError::SrqInUse(_)
+            | RdmaCmdError::MrInUse(_) => RDMA_STATUS_BUSY,
+            RdmaCmdError::InvalidMrAccess(_)
+            | RdmaCmdError::MrPdMismatch(..)
+            | RdmaCmdError::OutOfMrBounds(..)
+            | RdmaCmdError::MrNotWritable(_)
+            | RdmaCmdError::MrInvalidated(_)
+            | RdmaCmdError::AhPdMismatch(..)
+            | RdmaCmdError::RemoteAccessDenied(..)
+            | RdmaCmdError::MwPdMismatch(..)
+            | RdmaCmdError::MrNotBindable(_)
+            | RdmaCmdError::InvalidMwAccess(_)
+            | RdmaCmdError::MwOutOfMrBounds(..)
+            | RdmaCmdError::MwNotBound(_)
+            | RdmaCmdError::MwQpMismatch(..)
+            | RdmaCmdError::OutOfMwBounds(..) => RDMA_STATUS_INVALID_ACCESS,
+            RdmaCmdError::MrOutOfGuestMemory(..) => RDMA_STATUS_BAD_ADDRESS,
+            RdmaCmdError::IllegalQpTransition(..)
+            | RdmaCmdError::QpStateMismatch(..)
+            | RdmaCmdError::MwBound(_) => RDMA_STATUS_INVALID_STATE,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Resources of one kind created by the driver, identified by the handle the device assigned
+/// them. Handles start at 1, and a handle is only reused once the allocation wraps around.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceTable<T> {
+    entries: BTreeMap<u32, T>,
+    next_handle: u32,
+    max_entries: u32,
+    max_handle: u32,
+}
+
+impl<T> ResourceTable<T> {
+    /// Creates an empty table holding up to `max_entries` resources.
+    pub fn new(max_entries: u32) -> Self {
+        Self::with_max_handle(max_entries, u32::MAX)
+    }
+
+    /// Creates an empty table holding up to `max_entries` resources, whose handles don't exceed
+    /// `max_handle`.
+    pub fn with_max_handle(max_entries: u32, max_handle: u32) -> Self {
+        assert!(max_entries <= max_handle);
+        Self {
+            entries: BTreeMap::new(),
+            next_handle: 1,
+            max_entries,
+            max_handle,
+        }
+    }
+
+    /// Sets the number of resources the table holds, keeping the ones it already holds.
+    pub fn set_max_entries(&mut self, max_entries: u32) {
+        assert!(max_entries <= self.max_handle);
+        self.max_entries = max_entries;
+    }
+
+    /// Number of resources in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the table holds no resource.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts a resource, returning its handle, or `None` if the table is full.
+    pub fn insert(&mut self, entry: T) -> Option<u32> {
+        if self.entries.len() >= usize::try_from(self.max_entries).unwrap() {
+            return None;
+        }
+        // The table is not full, so there is a free handle.
+        let mut handle = self.next_handle;
+        while handle == 0 || handle > self.max_handle || self.entries.contains_key(&handle) {
+            handle = if handle >= self.max_handle {
+                1
+            } else {
+                handle + 1
+            };
+        }
+        self.next_handle = handle.wrapping_add(1);
+        self.entries.insert(handle, entry);
+        Some(handle)
+    }
+
+    /// Returns the resource with the given handle.
+    pub fn get(&self, handle: u32) -> Option<&T> {
+        self.entries.get(&handle)
+    }
+
+    /// Returns the resource with the given handle.
+    pub fn get_mut(&mut self, handle: u32) -> Option<&mut T> {
+        self.entries.get_mut(&handle)
+    }
+
+    /// Removes the resource with the given handle.
+    pub fn remove(&mut self, handle: u32) -> Option<T> {
+        self.entries.remove(&handle)
+    }
+
+    /// Iterates over the resources and their handles, in handle order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.entries.iter().map(|(handle, entry)| (*handle, entry))
+    }
+
+    /// Iterates mutably over the resources and their handles, in handle order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u32, &mut T)> {
+        self.entries
+            .iter_mut()
+            .map(|(handle, entry)| (*handle, entry))
+    }
+
+    /// Removes all the resources, returning how many there were.
+    pub fn clear(&mut self) -> usize {
+        let len = self.entries.len();
+        self.entries.clear();
+        self.next_handle = 1;
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_table() {
+        let mut table = ResourceTable::new(2);
+        assert!(table.is_empty());
+        assert_eq!(table.insert("a"), Some(1));
+        assert_eq!(table.insert("b"), Some(2));
+        assert_eq!(table.insert("c"), None);
+        assert_eq!(table.len(), 2);
+
+        assert_eq!(table.get(1), Some(&"a"));
+        *table.get_mut(2).unwrap() = "B";
+        assert_eq!(table.iter().collect::<Vec<_>>(), [(1, &"a"), (2, &"B")]);
+
+        // Handles are not reused right away.
+        assert_eq!(table.remove(1), Some("a"));
+        assert_eq!(table.remove(1), None);
+        assert_eq!(table.insert("c"), Some(3));
+
+        assert_eq!(table.clear(), 2);
+        assert!(table.get(3).is_none());
+        assert_eq!(table.insert("d"), Some(1));
+    }
+
+    #[test]
+    fn test_resource_table_wrap_around() {
+        let mut table = ResourceTable::new(2);
+        table.next_handle = u32::MAX;
+        assert_eq!(table.insert("a"), Some(u32::MAX));
+        // The handle 0 is skipped.
+        assert_eq!(table.insert("b"), Some(1));
+        table.remove(u32::MAX);
+        table.next_handle = u32::MAX;
+        // Handles in use are skipped.
+        assert_eq!(table.insert("c"), Some(u32::MAX));
+        table.remove(u32::MAX);
+        assert_eq!(table.insert("d"), Some(2));
+    }
+
+    #[test]
+    fn test_resource_table_max_handle() {
+        let mut table = ResourceTable::with_max_handle(2, 3);
+        assert_eq!(table.insert("a"), Some(1));
+        assert_eq!(table.insert("b"), Some(2));
+        table.remove(1);
+        assert_eq!(table.insert("c"), Some(3));
+        table.remove(2);
+        // The allocation wraps around past the largest handle.
+        assert_eq!(table.insert("d"), Some(1));
+    }
+}
@@ -0,0 +1,407 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Submission path of the backends performing host I/O. Rather than blocking the event loop of
+//! the device in a system call per message, the backends queue the messages they send, and the
+//! buffers they receive messages in, as operations of an io_uring instance over their sockets.
+//! The operations queued while the device processes a queue are submitted with a single system
+//! call, and their completions are signalled on an eventfd the device registers with its event
+//! manager.
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::io::AsRawFd;
+
+use vmm_sys_util::eventfd::EventFd;
+
+use crate::io_uring::operation::{FixedFd, OpCode, Operation};
+use crate::io_uring::restriction::Restriction;
+use crate::io_uring::{IoUring, IoUringError};
+use crate::logger::warn;
+
+/// Number of entries of the submission queue. The completion queue is twice as large, which
+/// leaves room for the messages sent by a full submission queue while a receive operation is in
+/// flight on every socket.
+const RING_SIZE: u32 = 256;
+
+/// Error while setting up the ring of a backend.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum RdmaUringError {
+    /// Unable to create the completion eventfd: {0}
+    EventFd(io::Error),
+    /// Unable to create the ring: {0}
+    IoUring(IoUringError),
+}
+
+/// Message sent to an address by a `sendmsg` operation. The header points to the other fields,
+/// which are boxed so that they don't move while the kernel reads them.
+struct SendMsg {
+    hdr: libc::msghdr,
+    iov: libc::iovec,
+    addr: libc::sockaddr_storage,
+    buf: Vec<u8>,
+}
+
+// SAFETY: The pointers of the header only point to the fields of the message itself, which is
+// never shared.
+unsafe impl Send for SendMsg {}
+
+impl fmt::Debug for SendMsg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SendMsg")
+            .field("len", &self.buf.len())
+            .finish()
+    }
+}
+
+/// Converts `addr` to the C socket address the system calls take, along with its length.
+pub(crate) fn sockaddr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: The address is a plain C structure, for which all-zero is a valid value.
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = libc::sockaddr_in {
+                sin_family: u16::try_from(libc::AF_INET).unwrap(),
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            // SAFETY: `sockaddr_storage` is large and aligned enough for any address.
+            unsafe { std::ptr::write((&raw mut storage).cast(), sin) };
+            size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 = libc::sockaddr_in6 {
+                sin6_family: u16::try_from(libc::AF_INET6).unwrap(),
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            // SAFETY: `sockaddr_storage` is large and aligned enough for any address.
+            unsafe { std::ptr::write((&raw mut storage).cast(), sin6) };
+            size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, u32::try_from(len).unwrap())
+}
+
+impl SendMsg {
+    fn new(addr: SocketAddr, buf: Vec<u8>) -> Box<Self> {
+        let (addr, addr_len) = sockaddr(addr);
+        // SAFETY: The header and the vector are plain C structures, for which all-zero is a valid
+        // value.
+        let mut msg = Box::new(unsafe {
+            SendMsg {
+                hdr: std::mem::zeroed(),
+                iov: std::mem::zeroed(),
+                addr,
+                buf,
+            }
+        });
+        msg.iov.iov_base = msg.buf.as_mut_ptr().cast();
+        msg.iov.iov_len = msg.buf.len();
+        msg.hdr.msg_name = (&raw mut msg.addr).cast();
+        msg.hdr.msg_namelen = addr_len;
+        msg.hdr.msg_iov = &raw mut msg.iov;
+        msg.hdr.msg_iovlen = 1;
+        msg
+    }
+}
+
+/// Operation of the ring, owning the buffer it lends to the kernel until it completes.
+#[derive(Debug)]
+enum Token {
+    // The buffer of a send operation is only kept alive.
+    Send { socket: FixedFd, _buf: Vec<u8> },
+    SendMsg { socket: FixedFd, _msg: Box<SendMsg> },
+    Recv { socket: FixedFd, buf: Vec<u8> },
+}
+
+/// Host I/O completed by the ring.
+#[derive(Debug)]
+pub enum UringCompletion {
+    /// A message was sent on `socket`.
+    Sent {
+        /// Index of the socket.
+        socket: FixedFd,
+        /// Number of bytes sent, or the error of the operation.
+        result: io::Result<u32>,
+    },
+    /// A message was received on `socket`, whose receive operation is now disarmed.
+    Received {
+        /// Index of the socket.
+        socket: FixedFd,
+        /// Message received, or the error of the operation.
+        result: io::Result<Vec<u8>>,
+    },
+}
+
+/// io_uring instance of a backend, over the sockets it owns. The sockets are identified by their
+/// index in the list the ring was created with.
+#[derive(Debug)]
+pub struct RdmaUring {
+    ring: IoUring<Token>,
+    completion_evt: EventFd,
+    sockets: Vec<File>,
+}
+
+impl RdmaUring {
+    /// Creates the ring over `sockets`, which must be non-blocking.
+    pub fn new(sockets: Vec<File>) -> Result<Self, RdmaUringError> {
+        let completion_evt = EventFd::new(libc::EFD_NONBLOCK).map_err(RdmaUringError::EventFd)?;
+        let ring = IoUring::new(
+            RING_SIZE,
+            sockets.iter().collect(),
+            vec![
+                // The backends only do I/O on their own sockets.
+                Restriction::RequireFixedFds,
+                Restriction::AllowOpCode(OpCode::Send),
+                Restriction::AllowOpCode(OpCode::Recv),
+                Restriction::AllowOpCode(OpCode::SendMsg),
+            ],
+            Some(completion_evt.as_raw_fd()),
+        )
+        .map_err(RdmaUringError::IoUring)?;
+        Ok(Self {
+            ring,
+            completion_evt,
+            sockets,
+        })
+    }
+
+    /// Event signalled when operations complete.
+    pub fn completion_evt(&self) -> &EventFd {
+        &self.completion_evt
+    }
+
+    /// Returns the socket at index `socket`.
+    pub fn socket(&self, socket: FixedFd) -> &File {
+        &self.sockets[socket as usize]
+    }
+
+    fn push(&mut self, op: Operation<Token>) -> Result<(), IoUringError> {
+        if self.ring.pending_sqes()? == RING_SIZE {
+            self.ring.submit()?;
+        }
+        self.ring.push(op).map_err(|(err, _)| err)
+    }
+
+    /// Queues the sending of `buf` on `socket`. The operation is only submitted by the next call
+    /// to `submit()`.
+    pub fn send(&mut self, socket: FixedFd, buf: Vec<u8>) -> Result<(), IoUringError> {
+        // The buffer is owned by the token, which the ring keeps until the operation completes.
+        let (addr, len) = (buf.as_ptr() as usize, u32::try_from(buf.len()).unwrap());
+        self.push(Operation::send(
+            socket,
+            addr,
+            len,
+            Token::Send { socket, _buf: buf },
+        ))
+    }
+
+    /// Queues the sending of `buf` to `addr` on the unconnected `socket`. The operation is only
+    /// submitted by the next call to `submit()`.
+    pub fn send_to(
+        &mut self,
+        socket: FixedFd,
+        addr: SocketAddr,
+        buf: Vec<u8>,
+    ) -> Result<(), IoUringError> {
+        let msg = SendMsg::new(addr, buf);
+        let hdr = &raw const msg.hdr as usize;
+        self.push(Operation::sendmsg(
+            socket,
+            hdr,
+            Token::SendMsg { socket, _msg: msg },
+        ))
+    }
+
+    /// Queues the receiving of a message of up to `len` bytes on `socket`. The operation is only
+    /// submitted by the next call to `submit()`.
+    pub fn recv(&mut self, socket: FixedFd, len: u32) -> Result<(), IoUringError> {
+        let mut buf = vec![0u8; len as usize];
+        let addr = buf.as_mut_ptr() as usize;
+        self.push(Operation::recv(
+            socket,
+            addr,
+            len,
+            Token::Recv { socket, buf },
+        ))
+    }
+
+    /// Submits the operations queued since the last call.
+    pub fn submit(&mut self) -> Result<(), IoUringError> {
+        self.ring.submit().map(|_| ())
+    }
+
+    /// Consumes the completion notification, and returns the host I/O completed since the last
+    /// call.
+    pub fn completions(&mut self) -> Vec<UringCompletion> {
+        // The eventfd is non-blocking, and a spurious notification simply yields no completion.
+        let _ = self.completion_evt.read();
+
+        let mut completions = Vec::new();
+        loop {
+            let cqe = match self.ring.pop() {
+                Ok(Some(cqe)) => cqe,
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("rdma: Unable to pop io_uring completion: {err:?}");
+                    break;
+                }
+            };
+            let result = cqe.result();
+            completions.push(match cqe.user_data() {
+                Token::Send { socket, .. } | Token::SendMsg { socket, .. } => {
+                    UringCompletion::Sent { socket, result }
+                }
+                Token::Recv { socket, mut buf } => UringCompletion::Received {
+                    socket,
+                    result: result.map(|len| {
+                        buf.truncate(len as usize);
+                        buf
+                    }),
+                },
+            });
+        }
+        completions
+    }
+}
+
+impl Drop for RdmaUring {
+    fn drop(&mut self) {
+        // The buffers of the operations in flight must outlive them. Shutting the sockets down
+        // completes the receive operations, which would otherwise wait for messages forever.
+        for socket in &self.sockets {
+            // SAFETY: The socket is a valid file descriptor owned by the ring.
+            unsafe { libc::shutdown(socket.as_raw_fd(), libc::SHUT_RDWR) };
+        }
+        if let Err(err) = self.ring.submit_and_wait_all() {
+            warn!("rdma: Unable to wait for the io_uring operations in flight: {err:?}");
+        }
+        while let Ok(Some(_)) = self.ring.pop() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::os::fd::OwnedFd;
+    use std::os::unix::net::UnixDatagram;
+
+    use super::*;
+
+    /// Waits for `count` completions, and returns them.
+    fn wait_completions(uring: &mut RdmaUring, count: usize) -> Vec<UringCompletion> {
+        let mut completions = Vec::new();
+        // The tests fail rather than hang if the completions never come.
+        for _ in 0..10 {
+            if completions.len() >= count {
+                break;
+            }
+            let mut pollfd = libc::pollfd {
+                fd: uring.completion_evt().as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: The file descriptor is valid, and `pollfd` outlives the call.
+            unsafe { libc::poll(&mut pollfd, 1, 100) };
+            completions.extend(uring.completions());
+        }
+        completions
+    }
+
+    #[test]
+    fn test_rdma_uring() {
+        let (local, peer) = UnixDatagram::pair().unwrap();
+        local.set_nonblocking(true).unwrap();
+        let mut uring = RdmaUring::new(vec![File::from(OwnedFd::from(local))]).unwrap();
+
+        // Messages are only sent once submitted.
+        uring.send(0, b"hello".to_vec()).unwrap();
+        uring.send(0, b"world".to_vec()).unwrap();
+        peer.set_nonblocking(true).unwrap();
+        let mut buf = [0u8; 16];
+        peer.recv(&mut buf).unwrap_err();
+        uring.submit().unwrap();
+        let completions = wait_completions(&mut uring, 2);
+        assert_eq!(completions.len(), 2);
+        for completion in completions {
+            match completion {
+                UringCompletion::Sent { socket, result } => {
+                    assert_eq!(socket, 0);
+                    assert_eq!(result.unwrap(), 5);
+                }
+                completion => panic!("unexpected completion {completion:?}"),
+            }
+        }
+        assert_eq!(peer.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(peer.recv(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"world");
+
+        // Receive operations complete once a message arrives.
+        uring.recv(0, 16).unwrap();
+        uring.submit().unwrap();
+        assert!(uring.completions().is_empty());
+        peer.send(b"ping").unwrap();
+        match wait_completions(&mut uring, 1).as_slice() {
+            [UringCompletion::Received { socket, result }] => {
+                assert_eq!(*socket, 0);
+                assert_eq!(result.as_ref().unwrap(), b"ping");
+            }
+            completions => panic!("unexpected completions {completions:?}"),
+        }
+
+        // Dropping the ring completes the receive operations in flight.
+        uring.recv(0, 16).unwrap();
+        uring.submit().unwrap();
+        drop(uring);
+    }
+
+    #[test]
+    fn test_rdma_uring_send_to() {
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        local.set_nonblocking(true).unwrap();
+        let mut uring = RdmaUring::new(vec![File::from(OwnedFd::from(local))]).unwrap();
+        let peers = [
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+            UdpSocket::bind("[::1]:0").unwrap(),
+        ];
+
+        // The unconnected socket sends its messages to the address of each of them.
+        let v6 = UdpSocket::bind("[::]:0").unwrap();
+        v6.set_nonblocking(true).unwrap();
+        let mut uring_v6 = RdmaUring::new(vec![File::from(OwnedFd::from(v6))]).unwrap();
+        uring
+            .send_to(0, peers[0].local_addr().unwrap(), b"v4".to_vec())
+            .unwrap();
+        uring.submit().unwrap();
+        uring_v6
+            .send_to(0, peers[1].local_addr().unwrap(), b"v6".to_vec())
+            .unwrap();
+        uring_v6.submit().unwrap();
+        for (uring, peer, msg) in [
+            (&mut uring, &peers[0], b"v4"),
+            (&mut uring_v6, &peers[1], b"v6"),
+        ] {
+            match wait_completions(uring, 1).as_slice() {
+                [UringCompletion::Sent { socket, result }] => {
+                    assert_eq!(*socket, 0);
+                    assert_eq!(*result.as_ref().unwrap(), 2);
+                }
+                completions => panic!("unexpected completions {completions:?}"),
+            }
+            let mut buf = [0u8; 16];
+            assert_eq!(peer.recv(&mut buf).unwrap(), 2);
+            assert_eq!(&buf[..2], msg);
+        }
+    }
+}
@@ -7,6 +7,7 @@ use std::sync::Arc;
 
 use aws_lc_rs::rand;
 use log::info;
+use utils::time::{ClockType, get_time_us};
 use vm_memory::GuestMemoryError;
 use vmm_sys_util::eventfd::EventFd;
 
@@ -22,7 +23,7 @@ use crate::devices::virtio::queue::{FIRECRACKER_MAX_QUEUE_SIZE, InvalidAvailIdx,
 use crate::devices::virtio::transport::{VirtioInterrupt, VirtioInterruptType};
 use crate::impl_device_type;
 use crate::logger::{IncMetric, debug, error};
-use crate::rate_limiter::{RateLimiter, TokenType};
+use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenType};
 use crate::vstate::memory::GuestMemoryMmap;
 
 pub const ENTROPY_DEV_ID: &str = "rng";
@@ -53,6 +54,8 @@ pub struct Entropy {
 
     // Device specific fields
     rate_limiter: RateLimiter,
+    // Time at which the request at the head of the queue was first throttled, if it was.
+    throttled_since_us: Option<u64>,
 
     buffer: IoVecBufferMut,
 }
@@ -80,6 +83,7 @@ impl Entropy {
             queues,
             queue_events,
             rate_limiter,
+            throttled_since_us: None,
             buffer: IoVecBufferMut::new()?,
         })
     }
@@ -142,21 +146,32 @@ impl Entropy {
                         self.buffer.len()
                     );
 
+                    // The latency of throttled requests includes the time they were held back.
+                    let start_us = self
+                        .throttled_since_us
+                        .take()
+                        .unwrap_or_else(|| get_time_us(ClockType::Monotonic));
+
                     // Check for available rate limiting budget.
                     // If not enough budget is available, leave the request descriptor in the queue
                     // to handle once we do have budget.
                     if !self.rate_limit_request(u64::from(self.buffer.len())) {
                         debug!("entropy: throttling entropy queue");
                         METRICS.entropy_rate_limiter_throttled.inc();
+                        self.throttled_since_us = Some(start_us);
                         self.queues[RNG_QUEUE].undo_pop();
                         break;
                     }
 
-                    self.handle_one().unwrap_or_else(|err| {
+                    let bytes = self.handle_one().unwrap_or_else(|err| {
                         error!("entropy: {err}");
                         METRICS.entropy_event_fails.inc();
                         0
-                    })
+                    });
+                    METRICS.entropy_agg.record_latency_us(
+                        get_time_us(ClockType::Monotonic).saturating_sub(start_us),
+                    );
+                    bytes
                 }
                 Err(err) => {
                     error!("entropy: Could not parse descriptor chain: {err}");
@@ -227,6 +242,11 @@ impl Entropy {
         &self.rate_limiter
     }
 
+    /// Updates the parameters of the rate limiter.
+    pub fn patch_rate_limiter(&mut self, bytes: BucketUpdate, ops: BucketUpdate) {
+        self.rate_limiter.update_buckets(bytes, ops);
+    }
+
     pub(crate) fn set_avail_features(&mut self, features: u64) {
         self.avail_features = features;
     }
@@ -325,6 +345,8 @@ mod tests {
     use crate::devices::virtio::test_utils::test::{
         VirtioTestDevice, VirtioTestHelper, create_virtio_mem,
     };
+    use crate::logger::StoreMetric;
+    use crate::rate_limiter::TokenBucket;
 
     impl VirtioTestDevice for Entropy {
         fn set_queues(&mut self, queues: Vec<Queue>) {
@@ -545,12 +567,41 @@ mod tests {
             th.device().process_entropy_queue()
         );
         assert!(th.device().rate_limiter().is_blocked());
+        assert!(th.device().throttled_since_us.is_some());
 
         // 250 msec should give enough time for replenishing 1000 bytes worth of tokens.
         // Give it an extra 100 ms just to be sure the timer event reaches us from the kernel.
         std::thread::sleep(Duration::from_millis(350));
         check_metric_after_block!(METRICS.entropy_bytes, 1000, th.emulate_for_msec(100));
         assert!(!th.device().rate_limiter().is_blocked());
+        assert!(th.device().throttled_since_us.is_none());
+        // The throttled request waited for at least 250 msec.
+        assert!(METRICS.entropy_agg.max_us.fetch() >= 250_000);
+    }
+
+    #[test]
+    fn test_patch_rate_limiter() {
+        let mut entropy_dev =
+            Entropy::new(RateLimiter::new(4000, 0, 1000, 0, 0, 0).unwrap()).unwrap();
+
+        let bytes = TokenBucket::new(1000, 1001, 1002).unwrap();
+        let ops = TokenBucket::new(1003, 1004, 1005).unwrap();
+        entropy_dev.patch_rate_limiter(
+            BucketUpdate::Update(bytes.clone()),
+            BucketUpdate::Update(ops.clone()),
+        );
+        let compare_buckets = |a: &TokenBucket, b: &TokenBucket| {
+            assert_eq!(a.capacity(), b.capacity());
+            assert_eq!(a.one_time_burst(), b.one_time_burst());
+            assert_eq!(a.refill_time_ms(), b.refill_time_ms());
+        };
+        compare_buckets(entropy_dev.rate_limiter().bandwidth().unwrap(), &bytes);
+        compare_buckets(entropy_dev.rate_limiter().ops().unwrap(), &ops);
+
+        // Missing buckets are left unchanged.
+        entropy_dev.patch_rate_limiter(BucketUpdate::Disabled, BucketUpdate::None);
+        assert!(entropy_dev.rate_limiter().bandwidth().is_none());
+        compare_buckets(entropy_dev.rate_limiter().ops().unwrap(), &ops);
     }
 
     #[test]
@@ -36,7 +36,7 @@
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::SharedIncMetric;
+use crate::logger::{LatencyAggregateMetrics, SharedIncMetric};
 
 /// Stores aggregated entropy metrics
 pub(super) static METRICS: EntropyDeviceMetrics = EntropyDeviceMetrics::new();
@@ -64,6 +64,8 @@ pub(super) struct EntropyDeviceMetrics {
     pub entropy_rate_limiter_throttled: SharedIncMetric,
     /// Number of events associated with the rate limiter
     pub rate_limiter_event_count: SharedIncMetric,
+    /// Latency of the entropy requests, including the time they were throttled
+    pub entropy_agg: LatencyAggregateMetrics,
 }
 impl EntropyDeviceMetrics {
     /// Const default construction.
@@ -76,6 +78,7 @@ impl EntropyDeviceMetrics {
             host_rng_fails: SharedIncMetric::new(),
             entropy_rate_limiter_throttled: SharedIncMetric::new(),
             rate_limiter_event_count: SharedIncMetric::new(),
+            entropy_agg: LatencyAggregateMetrics::new(),
         }
     }
 }
@@ -83,18 +86,27 @@ impl EntropyDeviceMetrics {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::logger::IncMetric;
+    use crate::logger::{IncMetric, StoreMetric};
 
     #[test]
     fn test_entropy_dev_metrics() {
         let entropy_metrics: EntropyDeviceMetrics = EntropyDeviceMetrics::new();
-        let entropy_metrics_local: String = serde_json::to_string(&entropy_metrics).unwrap();
+        let mut entropy_metrics_local = serde_json::to_value(&entropy_metrics).unwrap();
         // the 1st serialize flushes the metrics and resets values to 0 so that
         // we can compare the values with local metrics.
         serde_json::to_string(&METRICS).unwrap();
-        let entropy_metrics_global: String = serde_json::to_string(&METRICS).unwrap();
+        let mut entropy_metrics_global = serde_json::to_value(&METRICS).unwrap();
+        // The min and max latencies are not reset by flushes, and other tests may have updated
+        // them.
+        for metrics in [&mut entropy_metrics_local, &mut entropy_metrics_global] {
+            metrics.as_object_mut().unwrap().remove("entropy_agg");
+        }
         assert_eq!(entropy_metrics_local, entropy_metrics_global);
         entropy_metrics.entropy_event_count.inc();
         assert_eq!(entropy_metrics.entropy_event_count.count(), 1);
+
+        entropy_metrics.entropy_agg.record_latency_us(42);
+        assert_eq!(entropy_metrics.entropy_agg.min_us.fetch(), 42);
+        assert_eq!(entropy_metrics.entropy_agg.max_us.fetch(), 42);
     }
 }
@@ -59,6 +59,10 @@ pub struct MmioTransport {
     pub(crate) queue_select: u32,
     pub(crate) device_status: u32,
     pub(crate) config_generation: u32,
+    // The register where the shared memory region is selected.
+    pub(crate) shm_select: u32,
+    // Guest physical addresses and lengths of the shared memory regions of the device, by ID.
+    shm_regions: Vec<(u64, u64)>,
     mem: GuestMemoryMmap,
     pub(crate) interrupt: Arc<IrqTrigger>,
     pub is_vhost_user: bool,
@@ -79,6 +83,8 @@ impl MmioTransport {
             queue_select: 0,
             device_status: device_status::INIT,
             config_generation: 0,
+            shm_select: 0,
+            shm_regions: Vec::new(),
             mem,
             interrupt,
             is_vhost_user,
@@ -95,6 +101,34 @@ impl MmioTransport {
         self.device.clone()
     }
 
+    /// Sets the guest physical addresses and lengths of the shared memory regions of the device,
+    /// by ID.
+    pub fn set_shm_regions(&mut self, shm_regions: Vec<(u64, u64)>) {
+        self.shm_regions = shm_regions;
+    }
+
+    /// Gets the guest physical addresses and lengths of the shared memory regions of the device,
+    /// by ID.
+    pub fn shm_regions(&self) -> &[(u64, u64)] {
+        &self.shm_regions
+    }
+
+    // Reads a half of the length or of the address of the selected shared memory region.
+    fn shm_region_register(&self, offset: u64) -> u32 {
+        // Regions the device doesn't have read as a length of -1.
+        let (addr, len) = self
+            .shm_regions
+            .get(self.shm_select as usize)
+            .copied()
+            .unwrap_or((u64::MAX, u64::MAX));
+        let value = if offset < 0xb8 { len } else { addr };
+        if offset % 8 == 0 {
+            u32::try_from(value & 0xffff_ffff).unwrap()
+        } else {
+            u32::try_from(value >> 32).unwrap()
+        }
+    }
+
     fn check_device_status(&self, set: u32, clr: u32) -> bool {
         self.device_status & (set | clr) == set
     }
@@ -148,6 +182,7 @@ impl MmioTransport {
         self.features_select = 0;
         self.acked_features_select = 0;
         self.queue_select = 0;
+        self.shm_select = 0;
         self.interrupt.irq_status.store(0, Ordering::SeqCst);
         self.device_status = device_status::INIT;
         // . Keep interrupt_evt and queue_evts as is. There may be pending notifications in those
@@ -279,6 +314,7 @@ impl BusDevice for MmioTransport {
                         }
                     }
                     0x70 => self.device_status,
+                    0xb0..=0xbc if offset % 4 == 0 => self.shm_region_register(offset),
                     0xfc => self.config_generation,
                     _ => {
                         warn!("unknown virtio mmio register read: {:#x}", offset);
@@ -337,6 +373,7 @@ impl BusDevice for MmioTransport {
                         }
                     }
                     0x70 => self.set_device_status(v),
+                    0xac => self.shm_select = v,
                     0x80 => self.update_queue_field(|q| lo(&mut q.desc_table_address, v)),
                     0x84 => self.update_queue_field(|q| hi(&mut q.desc_table_address, v)),
                     0x90 => self.update_queue_field(|q| lo(&mut q.avail_ring_address, v)),
@@ -467,6 +504,7 @@ impl IrqTrigger {
             IrqType::Vring => VIRTIO_MMIO_INT_VRING,
         };
         self.irq_status.fetch_or(irq, Ordering::SeqCst);
+        crate::usdt_probe!(irq_inject, std::ptr::from_ref(self), irq);
 
         self.irq_evt.write(1).map_err(|err| {
             error!("Failed to send irq to the guest: {:?}", err);
@@ -728,6 +766,40 @@ pub(crate) mod tests {
         assert_eq!(buf[..], buf_copy[..]);
     }
 
+    #[test]
+    fn test_bus_device_shm_regions() {
+        let m = single_region_mem(0x1000);
+        let interrupt = Arc::new(IrqTrigger::new());
+        let mut d = MmioTransport::new(
+            m,
+            interrupt,
+            Arc::new(Mutex::new(DummyDevice::new())),
+            false,
+        );
+        d.set_shm_regions(vec![(0x1_2345_6000, 0x2_0000_1000)]);
+        let mut buf = vec![0; 4];
+        let read = |d: &mut MmioTransport, offset, buf: &mut Vec<u8>| {
+            d.read(0x0, offset, &mut buf[..]);
+            read_le_u32(&buf[..])
+        };
+
+        assert_eq!(read(&mut d, 0xb0, &mut buf), 0x1000);
+        assert_eq!(read(&mut d, 0xb4, &mut buf), 0x2);
+        assert_eq!(read(&mut d, 0xb8, &mut buf), 0x2345_6000);
+        assert_eq!(read(&mut d, 0xbc, &mut buf), 0x1);
+
+        // The device has a single region, the others read as a length of -1.
+        write_le_u32(&mut buf[..], 1);
+        d.write(0x0, 0xac, &buf[..]);
+        assert_eq!(d.shm_select, 1);
+        assert_eq!(read(&mut d, 0xb0, &mut buf), u32::MAX);
+        assert_eq!(read(&mut d, 0xb4, &mut buf), u32::MAX);
+
+        d.reset();
+        assert_eq!(d.shm_select, 0);
+        assert_eq!(read(&mut d, 0xb0, &mut buf), 0x1000);
+    }
+
     #[test]
     #[allow(clippy::cognitive_complexity)]
     fn test_bus_device_write() {
@@ -41,7 +41,7 @@ use crate::pci::msix::{MsixCap, MsixConfig, MsixConfigState};
 use crate::pci::{BarReprogrammingParams, DeviceRelocationError, PciDevice};
 use crate::snapshot::Persist;
 use crate::utils::u64_to_usize;
-use crate::vstate::bus::BusDevice;
+use crate::vstate::bus::{BusDevice, BusDeviceSync};
 use crate::vstate::interrupts::{InterruptError, MsixVectorGroup};
 use crate::vstate::memory::GuestMemoryMmap;
 use crate::vstate::resources::ResourceAllocator;
@@ -152,6 +152,44 @@ impl VirtioPciNotifyCap {
     }
 }
 
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct VirtioPciCap64 {
+    cap: VirtioPciCap,
+    offset_hi: Le32,
+    length_hi: Le32,
+}
+// SAFETY: All members are simple numbers and any value is valid.
+unsafe impl ByteValued for VirtioPciCap64 {}
+
+impl PciCapability for VirtioPciCap64 {
+    fn bytes(&self) -> &[u8] {
+        self.as_slice()
+    }
+
+    fn id(&self) -> PciCapabilityId {
+        PciCapabilityId::VendorSpecific
+    }
+}
+
+impl VirtioPciCap64 {
+    pub fn new(cfg_type: PciCapabilityType, pci_bar: u8, id: u8, offset: u64, length: u64) -> Self {
+        VirtioPciCap64 {
+            cap: VirtioPciCap {
+                cap_len: u8::try_from(size_of::<Self>()).unwrap() + VIRTIO_PCI_CAP_LEN_OFFSET,
+                cfg_type: cfg_type as u8,
+                pci_bar,
+                id,
+                padding: [0; 2],
+                offset: Le32::from(u32::try_from(offset & 0xffff_ffff).unwrap()),
+                length: Le32::from(u32::try_from(length & 0xffff_ffff).unwrap()),
+            },
+            offset_hi: Le32::from(u32::try_from(offset >> 32).unwrap()),
+            length_hi: Le32::from(u32::try_from(length >> 32).unwrap()),
+        }
+    }
+}
+
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 struct VirtioPciCfgCap {
@@ -229,12 +267,26 @@ const MSIX_PBA_SIZE: u64 = 0x800;
 pub const CAPABILITY_BAR_SIZE: u64 = 0x80000;
 const VIRTIO_COMMON_BAR_INDEX: usize = 0;
 const VIRTIO_SHM_BAR_INDEX: usize = 2;
+// Shared memory regions start on a page boundary, so that the driver can map them.
+const SHM_REGION_ALIGNMENT: u64 = 0x1000;
 
 const NOTIFY_OFF_MULTIPLIER: u32 = 4; // A dword per notification address.
 
 const VIRTIO_PCI_VENDOR_ID: u16 = 0x1af4;
 const VIRTIO_PCI_DEVICE_ID_BASE: u16 = 0x1040; // Add to device type to get device ID.
 
+// Returns the offsets of the shared memory regions in their BAR, where they follow each other, and
+// the size of the BAR.
+fn shm_bar_layout(shm_regions: &[(u64, Arc<dyn BusDeviceSync>)]) -> (Vec<u64>, u64) {
+    let mut offsets = Vec::with_capacity(shm_regions.len());
+    let mut shm_bar_size = 0;
+    for (len, _) in shm_regions {
+        offsets.push(shm_bar_size);
+        shm_bar_size = (shm_bar_size + len).next_multiple_of(SHM_REGION_ALIGNMENT);
+    }
+    (offsets, shm_bar_size.next_power_of_two())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VirtioPciDeviceState {
     pub pci_device_bdf: PciBdf,
@@ -245,6 +297,7 @@ pub struct VirtioPciDeviceState {
     pub pci_dev_state: VirtioPciCommonConfigState,
     pub msix_state: MsixConfigState,
     pub bar_address: u64,
+    pub queue_vcpus: Vec<u8>,
 }
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -287,6 +340,18 @@ pub struct VirtioPciDevice {
 
     // Allocated address for the BAR
     pub bar_address: u64,
+
+    // Allocated address and size of the BAR holding the shared memory regions of the device, the
+    // size being 0 if it has none. The devices having some don't support snapshots, so neither
+    // is saved.
+    shm_bar_address: u64,
+    shm_bar_size: u64,
+    // Shared memory regions of the device, as their address, length and handler.
+    shm_regions: Vec<(u64, u64, Arc<dyn BusDeviceSync>)>,
+
+    // vCPUs the interrupts of the queues are delivered to, in the order of the queues, in place
+    // of the ones programmed by the guest.
+    queue_vcpus: Vec<u8>,
 }
 
 impl Debug for VirtioPciDevice {
@@ -356,11 +421,54 @@ impl VirtioPciDevice {
             CAPABILITY_BAR_SIZE,
         );
 
+        // Allocate the BAR of the shared memory regions, which follow each other in it.
+        let shm_regions = device.shm_regions();
+        if !shm_regions.is_empty() {
+            let (offsets, shm_bar_size) = shm_bar_layout(&shm_regions);
+            let shm_bar_addr = mmio64_allocator
+                .allocate(shm_bar_size, shm_bar_size, AllocPolicy::FirstMatch)
+                .unwrap()
+                .start();
+            self.configuration
+                .add_pci_bar(VIRTIO_SHM_BAR_INDEX, shm_bar_addr, shm_bar_size);
+            self.shm_bar_address = shm_bar_addr;
+            self.shm_bar_size = shm_bar_size;
+            self.shm_regions = offsets
+                .into_iter()
+                .zip(shm_regions)
+                .map(|(offset, (len, region))| (shm_bar_addr + offset, len, region))
+                .collect();
+        }
+
         // Once the BARs are allocated, the capabilities can be added to the PCI configuration.
         self.add_pci_capabilities();
         self.bar_address = virtio_pci_bar_addr;
     }
 
+    /// Free the BAR allocated by `allocate_bars()`, once the device is unplugged.
+    pub fn free_bars(
+        &self,
+        mmio64_allocator: &mut AddressAllocator,
+    ) -> Result<(), vm_allocator::Error> {
+        mmio64_allocator.free(&RangeInclusive::new(
+            self.bar_address,
+            self.bar_address + CAPABILITY_BAR_SIZE - 1,
+        )?)?;
+        if self.shm_bar_size != 0 {
+            mmio64_allocator.free(&RangeInclusive::new(
+                self.shm_bar_address,
+                self.shm_bar_address + self.shm_bar_size - 1,
+            )?)?;
+        }
+        Ok(())
+    }
+
+    /// Shared memory regions of the device, as their address, length and the handler of the
+    /// accesses to them.
+    pub fn shm_regions(&self) -> &[(u64, u64, Arc<dyn BusDeviceSync>)] {
+        &self.shm_regions
+    }
+
     /// Constructs a new PCI transport for the given virtio device.
     pub fn new(
         id: String,
@@ -407,6 +515,10 @@ impl VirtioPciDevice {
             memory,
             cap_pci_cfg_info: VirtioPciCfgCapInfo::default(),
             bar_address: 0,
+            shm_bar_address: 0,
+            shm_bar_size: 0,
+            shm_regions: Vec::new(),
+            queue_vcpus: Vec::new(),
         };
 
         Ok(virtio_pci_device)
@@ -440,6 +552,22 @@ impl VirtioPciDevice {
             vectors,
         ));
 
+        // The BAR of the shared memory regions is part of the restored PCI configuration, and
+        // the regions are laid out in it the same way as when it was allocated.
+        let shm_regions = device.lock().expect("Poisoned lock").shm_regions();
+        let (shm_bar_address, shm_bar_size, shm_regions) = if shm_regions.is_empty() {
+            (0, 0, Vec::new())
+        } else {
+            let (offsets, shm_bar_size) = shm_bar_layout(&shm_regions);
+            let shm_bar_addr = pci_config.get_bar_addr(VIRTIO_SHM_BAR_INDEX);
+            let shm_regions = offsets
+                .into_iter()
+                .zip(shm_regions)
+                .map(|(offset, (len, region))| (shm_bar_addr + offset, len, region))
+                .collect();
+            (shm_bar_addr, shm_bar_size, shm_regions)
+        };
+
         let virtio_pci_device = VirtioPciDevice {
             id,
             pci_device_bdf: state.pci_device_bdf,
@@ -451,7 +579,12 @@ impl VirtioPciDevice {
             memory: vm.guest_memory().clone(),
             cap_pci_cfg_info,
             bar_address: state.bar_address,
+            shm_bar_address,
+            shm_bar_size,
+            shm_regions,
+            queue_vcpus: state.queue_vcpus,
         };
+        virtio_pci_device.update_interrupt_affinity();
 
         if state.device_activated {
             virtio_pci_device
@@ -479,6 +612,11 @@ impl VirtioPciDevice {
         self.common_config.driver_status == DEVICE_INIT
     }
 
+    /// BDF assigned to the device
+    pub fn pci_device_bdf(&self) -> PciBdf {
+        self.pci_device_bdf
+    }
+
     pub fn config_bar_addr(&self) -> u64 {
         self.configuration.get_bar_addr(VIRTIO_BAR_INDEX as usize)
     }
@@ -535,6 +673,17 @@ impl VirtioPciDevice {
             );
             self.configuration.add_capability(&msix_cap);
         }
+
+        for (id, (addr, len, _)) in self.shm_regions.iter().enumerate() {
+            let shm_cap = VirtioPciCap64::new(
+                PciCapabilityType::SharedMemory,
+                u8::try_from(VIRTIO_SHM_BAR_INDEX).unwrap(),
+                u8::try_from(id).unwrap(),
+                addr - self.shm_bar_address,
+                *len,
+            );
+            self.configuration.add_capability(&shm_cap);
+        }
     }
 
     fn read_cap_pci_cfg(&mut self, offset: usize, mut data: &mut [u8]) {
@@ -619,6 +768,34 @@ impl VirtioPciDevice {
         Ok(())
     }
 
+    /// Unregister the ioeventfds of the queues, once the device is unplugged.
+    pub fn unregister_notification_ioevent(&self, vm: &Vm) -> Result<(), errno::Error> {
+        let bar_addr = self.config_bar_addr();
+        for (i, queue_evt) in self
+            .device
+            .lock()
+            .expect("Poisoned lock")
+            .queue_events()
+            .iter()
+            .enumerate()
+        {
+            let notify_base = bar_addr + NOTIFICATION_BAR_OFFSET;
+            let io_addr =
+                IoEventAddress::Mmio(notify_base + i as u64 * NOTIFY_OFF_MULTIPLIER as u64);
+            vm.fd()
+                .unregister_ioevent(queue_evt, &io_addr, NoDatamatch)?;
+        }
+        Ok(())
+    }
+
+    /// Disable the MSI-X vectors of the device, so that it cannot interrupt the guest anymore.
+    pub fn disable_interrupts(&self) -> Result<(), InterruptError> {
+        match &self.virtio_interrupt {
+            Some(interrupt) => interrupt.vectors.disable(),
+            None => Ok(()),
+        }
+    }
+
     pub fn state(&self) -> VirtioPciDeviceState {
         VirtioPciDeviceState {
             pci_device_bdf: self.pci_device_bdf,
@@ -636,6 +813,46 @@ impl VirtioPciDevice {
                 .expect("Poisoned lock")
                 .state(),
             bar_address: self.bar_address,
+            queue_vcpus: self.queue_vcpus.clone(),
+        }
+    }
+
+    /// Deliver the interrupts of the queues of the device to the vCPUs of `queue_vcpus`, in the
+    /// order of the queues, rather than to the ones programmed by the guest.
+    pub fn set_interrupt_affinity(&mut self, queue_vcpus: Vec<u8>) {
+        self.queue_vcpus = queue_vcpus;
+        self.update_interrupt_affinity();
+    }
+
+    // Sets the vCPUs of the MSI-X vectors from the ones of the queues the guest assigned them to.
+    fn update_interrupt_affinity(&self) {
+        let Some(interrupt) = &self.virtio_interrupt else {
+            return;
+        };
+        if self.queue_vcpus.is_empty() {
+            return;
+        }
+
+        // The vectors are looked up before locking the MSI-X configuration, as the interrupts
+        // of the device do.
+        let queue_vectors = self
+            .common_config
+            .msix_queues
+            .lock()
+            .expect("Poisoned lock")
+            .clone();
+        let mut msix_config = interrupt.msix_config.lock().expect("Poisoned lock");
+        let mut vcpus = vec![None; msix_config.table_entries.len()];
+        for (&vector, &vcpu) in queue_vectors.iter().zip(&self.queue_vcpus) {
+            // Unassigned queues have `VIRTQ_MSI_NO_VECTOR`, past the end of the table.
+            if let Some(vector_vcpu) = vcpus.get_mut(usize::from(vector)) {
+                *vector_vcpu = Some(vcpu);
+            }
+        }
+        for (index, vcpu) in vcpus.into_iter().enumerate() {
+            if let Err(err) = msix_config.set_vector_vcpu(index, vcpu) {
+                error!("Failed setting the vCPU of vector {index}: {err:?}");
+            }
         }
     }
 }
@@ -696,7 +913,9 @@ impl VirtioInterrupt for VirtioInterruptMsix {
         // device should not inject the interrupt.
         // Instead, the Pending Bit Array table is updated to reflect there
         // is a pending interrupt for this specific vector.
-        if config.masked || entry.masked() {
+        let masked = config.masked || entry.masked();
+        crate::usdt_probe!(msix_inject, std::ptr::from_ref(self), vector, masked);
+        if masked {
             config.set_pba_bit(vector, false);
             return Ok(());
         }
@@ -845,7 +1064,9 @@ impl PciDevice for VirtioPciDevice {
         match offset {
             o if o < COMMON_CONFIG_BAR_OFFSET + COMMON_CONFIG_SIZE => {
                 self.common_config
-                    .write(o - COMMON_CONFIG_BAR_OFFSET, data, self.device.clone())
+                    .write(o - COMMON_CONFIG_BAR_OFFSET, data, self.device.clone());
+                // The guest may have assigned another vector to a queue.
+                self.update_interrupt_affinity();
             }
             o if (ISR_CONFIG_BAR_OFFSET..ISR_CONFIG_BAR_OFFSET + ISR_CONFIG_SIZE).contains(&o) => {
                 // We don't actually support legacy INT#x interrupts for VirtIO PCI devices
@@ -962,12 +1183,13 @@ mod tests {
     use crate::devices::virtio::device::{VirtioDevice, VirtioDeviceType};
     use crate::devices::virtio::device_status::{ACKNOWLEDGE, DRIVER, DRIVER_OK, FEATURES_OK};
     use crate::devices::virtio::generated::virtio_config::VIRTIO_F_VERSION_1;
+    use crate::devices::virtio::rdma::{RDMA_DOORBELL_REGION_LEN, RDMA_DOORBELL_SIZE, VirtioRdma};
     use crate::devices::virtio::rng::Entropy;
     use crate::devices::virtio::transport::pci::device::{
         COMMON_CONFIG_BAR_OFFSET, COMMON_CONFIG_SIZE, DEVICE_CONFIG_BAR_OFFSET, DEVICE_CONFIG_SIZE,
         ISR_CONFIG_BAR_OFFSET, ISR_CONFIG_SIZE, NOTIFICATION_BAR_OFFSET, NOTIFICATION_SIZE,
-        NOTIFY_OFF_MULTIPLIER, PciVirtioSubclass, VirtioPciCap, VirtioPciCfgCap,
-        VirtioPciNotifyCap,
+        NOTIFY_OFF_MULTIPLIER, PciVirtioSubclass, VIRTIO_SHM_BAR_INDEX, VirtioPciCap,
+        VirtioPciCap64, VirtioPciCfgCap, VirtioPciNotifyCap,
     };
     use crate::pci::PciDevice;
     use crate::pci::msix::MsixCap;
@@ -1427,6 +1649,94 @@ mod tests {
         device.write_bar(0, ISR_CONFIG_BAR_OFFSET, data.as_slice());
     }
 
+    #[test]
+    fn test_shared_memory_capability() {
+        let mut vmm = default_vmm();
+        vmm.device_manager.enable_pci(&vmm.vm);
+        let rdma = Arc::new(Mutex::new(VirtioRdma::new("rdma".to_string()).unwrap()));
+        rdma.lock().unwrap().set_vcpu_count(2).unwrap();
+        vmm.device_manager
+            .attach_virtio_device(
+                &vmm.vm,
+                "rdma".to_string(),
+                rdma.clone(),
+                &mut Cmdline::new(1024).unwrap(),
+                false,
+            )
+            .unwrap();
+        let device = vmm
+            .device_manager
+            .pci_devices
+            .get_virtio_device(VirtioDeviceType::Rdma, "rdma")
+            .unwrap()
+            .clone();
+        let mut locked_virtio_pci_device = device.lock().unwrap();
+
+        // Each queue has its own MSI-X vector, next to the one of the configuration changes.
+        let num_vectors = locked_virtio_pci_device
+            .virtio_interrupt
+            .as_ref()
+            .unwrap()
+            .msix_config
+            .lock()
+            .unwrap()
+            .vectors
+            .num_vectors();
+        assert_eq!(
+            usize::from(num_vectors),
+            rdma.lock().unwrap().queues().len() + 1
+        );
+
+        // The shared memory capabilities follow the MSI-X one.
+        let msix_cap_offset = capabilities_start(&mut locked_virtio_pci_device) as usize
+            + 3 * (size_of::<VirtioPciCap>() + 2)
+            + (size_of::<VirtioPciNotifyCap>() + 2)
+            + (size_of::<VirtioPciCfgCap>() + 2);
+        let (id, next, _) = read_msix_cap(
+            &mut locked_virtio_pci_device,
+            u32::try_from(msix_cap_offset).unwrap(),
+        );
+        assert_eq!(id, PciCapabilityId::MsiX);
+        let shm_cap_offset = u32::from(next);
+        let (id, next, cap) = read_virtio_pci_cap(&mut locked_virtio_pci_device, shm_cap_offset);
+        assert_eq!(id, PciCapabilityId::VendorSpecific);
+        assert_eq!(usize::from(cap.cap_len), size_of::<VirtioPciCap64>() + 2);
+        assert_eq!(cap.cfg_type, PciCapabilityType::SharedMemory as u8);
+        assert_eq!(usize::from(cap.pci_bar), VIRTIO_SHM_BAR_INDEX);
+        let offset_hi =
+            locked_virtio_pci_device.read_config_register((shm_cap_offset >> 2) as usize + 4);
+        let length_hi =
+            locked_virtio_pci_device.read_config_register((shm_cap_offset >> 2) as usize + 5);
+        assert_eq!(
+            (u64::from(offset_hi) << 32) | u64::from(u32::from(cap.offset)),
+            0
+        );
+        assert_eq!(
+            (u64::from(length_hi) << 32) | u64::from(u32::from(cap.length)),
+            RDMA_DOORBELL_REGION_LEN
+        );
+        assert_eq!(next, 0);
+
+        // The doorbell region starts the BAR, which is the next power of two past its length.
+        let shm_bar_addr = locked_virtio_pci_device
+            .configuration
+            .get_bar_addr(VIRTIO_SHM_BAR_INDEX);
+        let (addr, len, _) = locked_virtio_pci_device.shm_regions()[0].clone();
+        assert_eq!((addr, len), (shm_bar_addr, RDMA_DOORBELL_REGION_LEN));
+        assert_eq!(
+            locked_virtio_pci_device.shm_bar_size,
+            RDMA_DOORBELL_REGION_LEN.next_power_of_two()
+        );
+
+        // Writes to the region ring the doorbells of the device.
+        vmm.vm
+            .common
+            .mmio_bus
+            .write(addr + RDMA_DOORBELL_SIZE, &3u32.to_le_bytes())
+            .unwrap();
+        assert_eq!(rdma.lock().unwrap().doorbell_event().read().unwrap(), 1);
+    }
+
     #[test]
     fn test_isr_capability() {
         let mut vmm = create_vmm_with_virtio_pci_device();
@@ -1542,6 +1852,37 @@ mod tests {
         device.write_bar(0, COMMON_CONFIG_BAR_OFFSET + 0x1c, 1u16.as_slice());
     }
 
+    #[test]
+    fn test_interrupt_affinity() {
+        let vmm = create_vmm_with_virtio_pci_device();
+        let device = get_virtio_device(&vmm);
+        let mut locked_virtio_pci_device = device.lock().unwrap();
+        let vector_vcpus = |device: &VirtioPciDevice| {
+            device
+                .virtio_interrupt
+                .as_ref()
+                .unwrap()
+                .vectors
+                .vectors
+                .iter()
+                .map(|vector| *vector.vcpu.lock().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // The vCPU of the queue applies to the vector the guest assigns it.
+        locked_virtio_pci_device.set_interrupt_affinity(vec![1]);
+        assert_eq!(vector_vcpus(&locked_virtio_pci_device), [None, None]);
+        let set_queue_vector = |device: &mut VirtioPciDevice, vector: u16| {
+            device.write_bar(0, COMMON_CONFIG_BAR_OFFSET + 0x16, 0u16.as_slice());
+            device.write_bar(0, COMMON_CONFIG_BAR_OFFSET + 0x1a, vector.as_slice());
+        };
+        set_queue_vector(&mut locked_virtio_pci_device, 1);
+        assert_eq!(vector_vcpus(&locked_virtio_pci_device), [None, Some(1)]);
+        set_queue_vector(&mut locked_virtio_pci_device, 0);
+        assert_eq!(vector_vcpus(&locked_virtio_pci_device), [Some(1), None]);
+        assert_eq!(locked_virtio_pci_device.state().queue_vcpus, [1]);
+    }
+
     #[test]
     fn test_device_initialization() {
         let mut vmm = create_vmm_with_virtio_pci_device();
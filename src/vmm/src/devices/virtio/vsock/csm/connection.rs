@@ -139,6 +139,12 @@ pub struct VsockConnection<S: VsockConnectionBackend> {
     /// Instant when this connection should be scheduled for immediate termination, due to some
     /// timeout condition having been fulfilled.
     expiry: Option<Instant>,
+    /// Total number of bytes read from the host stream and sent to the peer. Unlike
+    /// `self.rx_cnt`, this does not wrap around.
+    rx_bytes: u64,
+    /// Total number of bytes written to the host stream. Unlike `self.fwd_cnt`, this does not
+    /// wrap around.
+    tx_bytes: u64,
 }
 
 impl<S> VsockChannel for VsockConnection<S>
@@ -240,6 +246,7 @@ where
                         // by self.peer_avail_credit(), a u32 internally.
                         pkt.hdr.set_op(uapi::VSOCK_OP_RW).set_len(read_cnt);
                         METRICS.rx_bytes_count.add(read_cnt as u64);
+                        self.rx_bytes += u64::from(read_cnt);
                     }
                     self.rx_cnt += Wrapping(pkt.hdr.len());
                     self.last_fwd_cnt_to_peer = self.fwd_cnt;
@@ -483,6 +490,7 @@ where
                 });
             self.fwd_cnt += wrap_usize_to_u32(flushed);
             METRICS.tx_bytes_count.add(flushed as u64);
+            self.tx_bytes += flushed as u64;
 
             // If this connection was shutting down, but is waiting to drain the TX buffer
             // before forceful termination, the wait might be over.
@@ -525,6 +533,8 @@ where
             last_fwd_cnt_to_peer: Wrapping(0),
             pending_rx: PendingRxSet::from(PendingRx::Response),
             expiry: None,
+            rx_bytes: 0,
+            tx_bytes: 0,
         }
     }
 
@@ -551,6 +561,8 @@ where
             last_fwd_cnt_to_peer: Wrapping(0),
             pending_rx: PendingRxSet::from(PendingRx::Request),
             expiry: None,
+            rx_bytes: 0,
+            tx_bytes: 0,
         }
     }
 
@@ -589,6 +601,16 @@ where
         self.state
     }
 
+    /// Return the total number of bytes sent to the peer.
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+
+    /// Return the total number of bytes received from the peer and written to the host stream.
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
     /// Send some raw, untracked, data straight to the underlying connected stream.
     /// Returns: number of bytes written, or the error describing the write failure.
     ///
@@ -636,6 +658,7 @@ where
         // Safe to unwrap because the maximum value is pkt.len(), which is a u32.
         self.fwd_cnt += written;
         METRICS.tx_bytes_count.add(written as u64);
+        self.tx_bytes += u64::from(written);
 
         // If we couldn't write the whole slice, we'll need to push the remaining data to our
         // buffer.
@@ -16,6 +16,11 @@
 //!     "ev_queue_event_fails": "SharedIncMetric",
 //!     "muxer_event_fails": "SharedIncMetric",
 //!     ...
+//!  },
+//!  "vsock_port_52": {
+//!     "active_conns": "SharedStoreMetric",
+//!     "conns_added": "SharedIncMetric",
+//!     ...
 //!  }
 //! }
 //! ```
@@ -24,6 +29,12 @@
 //! Since vsock doesn't support multiple devices, there is no per device metrics and
 //! `vsock` represents the aggregate metrics for all vsock connections.
 //!
+//! Each `vsock_port_<port>` field is a serializable `VsockPortMetrics` structure collecting the
+//! metrics of the connections to a service port, i.e. the host port of guest-initiated
+//! connections, or the guest port of host-initiated ones. A port is tracked while it has open
+//! connections, and up to [`MAX_TRACKED_PORTS`] ports are tracked at once; the connections to
+//! other ports are only accounted for in the aggregate metrics.
+//!
 //! # Design
 //! The main design goals of this system are:
 //! * Have a consistent approach of keeping device related metrics in the individual devices
@@ -36,21 +47,93 @@
 //! * Shared Incremental Metrics (SharedIncMetrics) - dedicated for the metrics which need a counter
 //!   (i.e the number of times an API request failed). These metrics are reset upon flush.
 
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::SharedIncMetric;
+use crate::logger::{SharedIncMetric, SharedStoreMetric};
+
+/// Maximum number of service ports having their own metrics.
+pub const MAX_TRACKED_PORTS: usize = 64;
 
 /// Stores aggregate metrics of all Vsock connections/actions
 pub(super) static METRICS: VsockDeviceMetrics = VsockDeviceMetrics::new();
 
+/// Map of service ports and their metrics.
+/// This should be protected by a lock before accessing.
+#[derive(Debug)]
+pub struct VsockMetricsPerPort {
+    /// Used to access per port metrics.
+    pub metrics: BTreeMap<u32, Arc<VsockPortMetrics>>,
+}
+
+impl VsockMetricsPerPort {
+    /// Allocate `VsockPortMetrics` for the service port `port`, unless already allocated.
+    /// Returns `None` when [`MAX_TRACKED_PORTS`] other ports are already tracked.
+    /// Lock is always initialized so it is safe the unwrap the lock without a check.
+    pub fn alloc(port: u32) -> Option<Arc<VsockPortMetrics>> {
+        PORT_METRICS.write().unwrap().get_or_insert(port)
+    }
+
+    /// Release the `VsockPortMetrics` of the service port `port`, so that their slot can be
+    /// allocated to another port.
+    pub fn release(port: u32) {
+        PORT_METRICS.write().unwrap().remove(port);
+    }
+
+    fn get_or_insert(&mut self, port: u32) -> Option<Arc<VsockPortMetrics>> {
+        if self.metrics.len() >= MAX_TRACKED_PORTS && !self.metrics.contains_key(&port) {
+            return None;
+        }
+        Some(Arc::clone(self.metrics.entry(port).or_default()))
+    }
+
+    fn remove(&mut self, port: u32) {
+        self.metrics.remove(&port);
+    }
+}
+
+/// Pool of per port metrics behind a lock to keep things thread safe. Since the lock is
+/// initialized here it is safe to unwrap it without any check.
+static PORT_METRICS: RwLock<VsockMetricsPerPort> = RwLock::new(VsockMetricsPerPort {
+    metrics: BTreeMap::new(),
+});
+
 /// Called by METRICS.flush(), this function facilitates serialization of vsock device metrics.
 pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
-    let mut seq = serializer.serialize_map(Some(1))?;
+    let port_metrics = PORT_METRICS.read().unwrap();
+    // +1 to accomodate aggregate vsock metrics
+    let mut seq = serializer.serialize_map(Some(1 + port_metrics.metrics.len()))?;
     seq.serialize_entry("vsock", &METRICS)?;
+    for (port, metrics) in port_metrics.metrics.iter() {
+        seq.serialize_entry(&format!("vsock_port_{}", port), metrics.as_ref())?;
+    }
     seq.end()
 }
 
+/// Metrics of the vsock connections to a service port.
+#[derive(Debug, Default, Serialize)]
+pub struct VsockPortMetrics {
+    /// Number of connections currently open.
+    pub active_conns: SharedStoreMetric,
+    /// Number of added connections.
+    pub conns_added: SharedIncMetric,
+    /// Number of removed connections.
+    pub conns_removed: SharedIncMetric,
+    /// Number of connections dropped because the connection limit was reached while the port
+    /// had open connections.
+    pub conns_backlog_drops: SharedIncMetric,
+    /// Number of guest connection requests rejected because no host socket listens on the port,
+    /// while the port had open connections.
+    pub conns_rejected: SharedIncMetric,
+    /// Number of bytes received by the guest.
+    pub rx_bytes_count: SharedIncMetric,
+    /// Number of bytes transmitted by the guest.
+    pub tx_bytes_count: SharedIncMetric,
+}
+
 /// Vsock-related metrics.
 #[derive(Debug, Serialize)]
 pub(super) struct VsockDeviceMetrics {
@@ -86,6 +169,12 @@ pub(super) struct VsockDeviceMetrics {
     pub conns_killed: SharedIncMetric,
     /// Number of removed connections.
     pub conns_removed: SharedIncMetric,
+    /// Number of connections dropped because the connection limit was reached.
+    pub conns_backlog_drops: SharedIncMetric,
+    /// Number of guest connection requests rejected because no host socket listens on the port.
+    pub conns_rejected: SharedIncMetric,
+    /// Number of RST packets dropped because the muxer RX queue was full.
+    pub rst_drops: SharedIncMetric,
     /// How many times the killq has been resynced.
     pub killq_resync: SharedIncMetric,
     /// How many flush fails have been seen.
@@ -117,6 +206,9 @@ impl VsockDeviceMetrics {
             conns_added: SharedIncMetric::new(),
             conns_killed: SharedIncMetric::new(),
             conns_removed: SharedIncMetric::new(),
+            conns_backlog_drops: SharedIncMetric::new(),
+            conns_rejected: SharedIncMetric::new(),
+            rst_drops: SharedIncMetric::new(),
             killq_resync: SharedIncMetric::new(),
             tx_flush_fails: SharedIncMetric::new(),
             tx_write_fails: SharedIncMetric::new(),
@@ -142,4 +234,30 @@ pub mod tests {
         vsock_metrics.conns_added.inc();
         assert_eq!(vsock_metrics.conns_added.count(), 1);
     }
+
+    #[test]
+    fn test_vsock_port_metrics() {
+        let mut port_metrics = VsockMetricsPerPort {
+            metrics: BTreeMap::new(),
+        };
+        let metrics = port_metrics.get_or_insert(52).unwrap();
+        metrics.conns_added.inc();
+        // Allocating the metrics of a tracked port returns the same metrics.
+        assert_eq!(
+            port_metrics.get_or_insert(52).unwrap().conns_added.count(),
+            1
+        );
+
+        // Ports beyond the limit are not tracked.
+        for port in 1..u32::try_from(MAX_TRACKED_PORTS).unwrap() {
+            port_metrics.get_or_insert(port + 100).unwrap();
+        }
+        assert_eq!(port_metrics.metrics.len(), MAX_TRACKED_PORTS);
+        assert!(port_metrics.get_or_insert(1).is_none());
+        port_metrics.get_or_insert(52).unwrap();
+
+        // Releasing a port frees its slot.
+        port_metrics.remove(52);
+        port_metrics.get_or_insert(1).unwrap();
+    }
 }
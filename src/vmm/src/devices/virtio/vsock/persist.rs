@@ -45,6 +45,9 @@ pub enum VsockBackendState {
 pub struct VsockUdsState {
     /// The path for the UDS socket.
     pub(crate) path: String,
+    /// The engine driving the host-side Unix sockets.
+    #[serde(default)]
+    pub(crate) io_engine: VsockIoEngine,
 }
 
 /// A helper structure that holds the constructor arguments for VsockUnixBackend
@@ -71,6 +74,7 @@ impl Persist<'_> for VsockUnixBackend {
     fn save(&self) -> Self::State {
         VsockBackendState::Uds(VsockUdsState {
             path: self.host_sock_path.clone(),
+            io_engine: self.io_engine(),
         })
     }
 
@@ -79,9 +83,10 @@ impl Persist<'_> for VsockUnixBackend {
         state: &Self::State,
     ) -> Result<Self, Self::Error> {
         match state {
-            VsockBackendState::Uds(uds_state) => Ok(VsockUnixBackend::new(
+            VsockBackendState::Uds(uds_state) => Ok(VsockUnixBackend::new_with_io_engine(
                 constructor_args.cid,
                 uds_state.path.clone(),
+                uds_state.io_engine,
             )?),
         }
     }
@@ -144,6 +149,7 @@ pub(crate) mod tests {
         fn save(&self) -> Self::State {
             VsockBackendState::Uds(VsockUdsState {
                 path: "test".to_owned(),
+                io_engine: VsockIoEngine::default(),
             })
         }
 
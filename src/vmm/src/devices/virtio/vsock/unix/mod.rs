@@ -10,10 +10,13 @@
 mod muxer;
 mod muxer_killq;
 mod muxer_rxq;
+mod muxer_uring;
 
-pub use muxer::VsockMuxer as VsockUnixBackend;
+pub use muxer::{VsockConnectionInfo, VsockMuxer as VsockUnixBackend};
+use serde::{Deserialize, Serialize};
 
 use crate::devices::virtio::vsock::csm::VsockConnectionBackend;
+use crate::io_uring::IoUringError;
 
 mod defs {
     /// Maximum number of established connections that we can handle.
@@ -45,6 +48,25 @@ pub enum VsockUnixBackendError {
     UnixRead(std::io::Error),
     /// Muxer connection limit reached.
     TooManyConnections,
+    /// Error creating an EventFd: {0}
+    EventFd(std::io::Error),
+    /// Error updating an epoll-listening FD: {0}
+    EpollModify(std::io::Error),
+    /// Error driving the host-side Unix sockets through io_uring: {0}
+    IoUring(IoUringError),
+    /// The io_uring engine requires a host kernel 5.19 or newer.
+    IoUringUnsupported,
+}
+
+/// The engine driving the host-side Unix sockets of the vsock backend.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum VsockIoEngine {
+    /// Register the sockets under an epoll FD.
+    #[default]
+    Epoll,
+    /// Drive the sockets through io_uring, accepting host-initiated connections with a
+    /// multishot accept operation.
+    IoUring,
 }
 
 type MuxerConnection = super::csm::VsockConnection<std::os::unix::net::UnixStream>;
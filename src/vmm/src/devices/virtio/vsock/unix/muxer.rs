@@ -27,7 +27,9 @@
 ///
 ///  The muxer gets notified about all of these events, because, as a `VsockEpollListener`
 ///  implementor, it gets to register a nested epoll FD into the main VMM epolling loop. All
-///  other pollable FDs are then registered under this nested epoll FD.
+///  other pollable FDs are then registered under this nested epoll FD, or, with the io_uring
+///  engine, polled through the ring of `MuxerUring`, whose completion eventfd is the only FD
+///  registered under the nested epoll FD.
 ///  To route all these events to their handlers, the muxer uses another `HashMap` object,
 ///  mapping `RawFd`s to `EpollListener`s.
 use std::collections::{HashMap, HashSet};
@@ -35,8 +37,10 @@ use std::fmt::Debug;
 use std::io::Read;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::Arc;
 
 use log::{debug, error, info, warn};
+use serde::Serialize;
 use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
 
 use super::super::csm::ConnState;
@@ -44,10 +48,11 @@ use super::super::defs::uapi;
 use super::super::{VsockBackend, VsockChannel, VsockEpollListener, VsockError};
 use super::muxer_killq::MuxerKillQ;
 use super::muxer_rxq::MuxerRxQ;
-use super::{MuxerConnection, VsockUnixBackendError, defs};
-use crate::devices::virtio::vsock::metrics::METRICS;
+use super::muxer_uring::{MuxerUring, UringEvent};
+use super::{MuxerConnection, VsockIoEngine, VsockUnixBackendError, defs};
+use crate::devices::virtio::vsock::metrics::{METRICS, VsockMetricsPerPort, VsockPortMetrics};
 use crate::devices::virtio::vsock::packet::{VsockPacketRx, VsockPacketTx};
-use crate::logger::IncMetric;
+use crate::logger::{IncMetric, StoreMetric};
 
 /// A unique identifier of a `MuxerConnection` object. Connections are stored in a hash map,
 /// keyed by a `ConnMapKey` object.
@@ -57,6 +62,23 @@ pub struct ConnMapKey {
     peer_port: u32,
 }
 
+/// Description of a live vsock connection.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VsockConnectionInfo {
+    /// Host-side port.
+    pub local_port: u32,
+    /// Guest-side port.
+    pub peer_port: u32,
+    /// Whether the connection was initiated by the host.
+    pub host_initiated: bool,
+    /// Connection state.
+    pub state: &'static str,
+    /// Number of bytes received by the guest.
+    pub rx_bytes: u64,
+    /// Number of bytes transmitted by the guest.
+    pub tx_bytes: u64,
+}
+
 /// A muxer RX queue item.
 #[derive(Clone, Copy, Debug)]
 pub enum MuxerRx {
@@ -78,6 +100,8 @@ enum EpollListener {
     /// A listener interested in reading host `connect <port>` commands from a freshly
     /// connected host socket.
     LocalStream(UnixStream),
+    /// A listener interested in the completions of the io_uring engine.
+    Uring,
 }
 
 /// The vsock connection multiplexer.
@@ -108,6 +132,12 @@ pub struct VsockMuxer {
     local_port_set: HashSet<u32>,
     /// The last used host-side port.
     local_port_last: u32,
+    /// Metrics of the service ports with open connections, keyed by port.
+    port_metrics: HashMap<u32, Arc<VsockPortMetrics>>,
+    /// The engine driving the host-side Unix sockets.
+    io_engine: VsockIoEngine,
+    /// The io_uring engine, which then polls all the listeners but `EpollListener::Uring`.
+    uring: Option<MuxerUring>,
 }
 
 impl VsockChannel for VsockMuxer {
@@ -296,6 +326,7 @@ impl VsockEpollListener for VsockMuxer {
                 METRICS.muxer_event_fails.inc();
             }
         }
+        self.submit_uring();
     }
 }
 
@@ -304,11 +335,27 @@ impl VsockBackend for VsockMuxer {}
 impl VsockMuxer {
     /// Muxer constructor.
     pub fn new(cid: u64, host_sock_path: String) -> Result<Self, VsockUnixBackendError> {
+        Self::new_with_io_engine(cid, host_sock_path, VsockIoEngine::default())
+    }
+
+    /// Muxer constructor, driving the host-side Unix sockets with the given engine.
+    pub fn new_with_io_engine(
+        cid: u64,
+        host_sock_path: String,
+        io_engine: VsockIoEngine,
+    ) -> Result<Self, VsockUnixBackendError> {
         // Open/bind on the host Unix socket, so we can accept host-initiated
         // connections.
         let host_sock = UnixListener::bind(&host_sock_path)
             .and_then(|sock| sock.set_nonblocking(true).map(|_| sock))
             .map_err(VsockUnixBackendError::UnixBind)?;
+        let uring = match io_engine {
+            VsockIoEngine::Epoll => None,
+            VsockIoEngine::IoUring => Some(MuxerUring::new(&host_sock).inspect_err(|_| {
+                // Don't leave the socket behind, so that the device can be configured again.
+                let _ = std::fs::remove_file(&host_sock_path);
+            })?),
+        };
 
         let mut muxer = Self {
             cid,
@@ -321,10 +368,21 @@ impl VsockMuxer {
             killq: MuxerKillQ::new(),
             local_port_last: (1u32 << 30) - 1,
             local_port_set: HashSet::with_capacity(defs::MAX_CONNECTIONS),
+            port_metrics: HashMap::new(),
+            io_engine,
+            uring,
         };
 
-        // Listen on the host initiated socket, for incoming connections.
-        muxer.add_listener(muxer.host_sock.as_raw_fd(), EpollListener::HostSock)?;
+        let uring_fd = muxer
+            .uring
+            .as_ref()
+            .map(|uring| uring.completion_evt().as_raw_fd());
+        match uring_fd {
+            // The ring accepts the incoming connections on the host initiated socket.
+            Some(fd) => muxer.add_listener(fd, EpollListener::Uring)?,
+            // Listen on the host initiated socket, for incoming connections.
+            None => muxer.add_listener(muxer.host_sock.as_raw_fd(), EpollListener::HostSock)?,
+        }
         Ok(muxer)
     }
 
@@ -333,6 +391,56 @@ impl VsockMuxer {
         &self.host_sock_path
     }
 
+    /// Return the engine driving the host-side Unix sockets.
+    pub fn io_engine(&self) -> VsockIoEngine {
+        self.io_engine
+    }
+
+    /// Describe the live connections, sorted by ports.
+    pub fn connections(&self) -> Vec<VsockConnectionInfo> {
+        let mut conns: Vec<_> = self
+            .conn_map
+            .iter()
+            .map(|(key, conn)| VsockConnectionInfo {
+                local_port: key.local_port,
+                peer_port: key.peer_port,
+                host_initiated: self.is_host_initiated(*key),
+                state: match conn.state() {
+                    ConnState::LocalInit | ConnState::PeerInit => "connecting",
+                    ConnState::Established => "established",
+                    ConnState::LocalClosed | ConnState::PeerClosed(..) => "closing",
+                    ConnState::Killed => "killed",
+                },
+                rx_bytes: conn.rx_bytes(),
+                tx_bytes: conn.tx_bytes(),
+            })
+            .collect();
+        conns.sort_by_key(|info| (info.local_port, info.peer_port));
+        conns
+    }
+
+    /// Check whether a connection was initiated by the host, i.e. whether its host-side port
+    /// was allocated by the muxer.
+    fn is_host_initiated(&self, key: ConnMapKey) -> bool {
+        self.local_port_set.contains(&key.local_port)
+    }
+
+    /// Get the service port of a connection: the guest-side port of host-initiated
+    /// connections, or the host-side port of guest-initiated ones.
+    fn service_port(&self, key: ConnMapKey) -> u32 {
+        if self.is_host_initiated(key) {
+            key.peer_port
+        } else {
+            key.local_port
+        }
+    }
+
+    /// Get the metrics of the service port of a connection, if the port has open connections
+    /// and is tracked.
+    fn port_metrics(&self, key: ConnMapKey) -> Option<Arc<VsockPortMetrics>> {
+        self.port_metrics.get(&self.service_port(key)).cloned()
+    }
+
     /// Handle/dispatch an epoll event to its listener.
     fn handle_event(&mut self, fd: RawFd, event_set: EventSet) {
         debug!(
@@ -356,34 +464,19 @@ impl VsockMuxer {
 
             // A new host-initiated connection is ready to be accepted.
             Some(EpollListener::HostSock) => {
-                if self.conn_map.len() == defs::MAX_CONNECTIONS {
-                    // If we're already maxed-out on connections, we'll just accept and
-                    // immediately discard this potentially new one.
-                    warn!("vsock: connection limit reached; refusing new host connection");
-                    self.host_sock.accept().map(|_| 0).unwrap_or(0);
-                    return;
-                }
                 self.host_sock
                     .accept()
+                    .and_then(|(stream, _)| stream.set_nonblocking(true).map(|_| stream))
                     .map_err(VsockUnixBackendError::UnixAccept)
-                    .and_then(|(stream, _)| {
-                        stream
-                            .set_nonblocking(true)
-                            .map(|_| stream)
-                            .map_err(VsockUnixBackendError::UnixAccept)
-                    })
-                    .and_then(|stream| {
-                        // Before forwarding this connection to a listening AF_VSOCK socket on
-                        // the guest side, we need to know the destination port. We'll read
-                        // that port from a "connect" command received on this socket, so the
-                        // next step is to ask to be notified the moment we can read from it.
-                        self.add_listener(stream.as_raw_fd(), EpollListener::LocalStream(stream))
-                    })
+                    .map(|stream| self.add_local_stream(stream))
                     .unwrap_or_else(|err| {
                         warn!("vsock: unable to accept local connection: {:?}", err);
                     });
             }
 
+            // The io_uring engine has completed some operations.
+            Some(EpollListener::Uring) => self.handle_uring_events(),
+
             // Data is ready to be read from a host-initiated connection. That would be the
             // "connect" command that we're expecting.
             Some(EpollListener::LocalStream(_)) => {
@@ -421,6 +514,72 @@ impl VsockMuxer {
         }
     }
 
+    /// Handle a newly accepted host-initiated connection.
+    fn add_local_stream(&mut self, stream: UnixStream) {
+        if self.conn_map.len() == defs::MAX_CONNECTIONS {
+            // If we're already maxed-out on connections, we'll just discard this potentially
+            // new one.
+            warn!("vsock: connection limit reached; refusing new host connection");
+            METRICS.conns_backlog_drops.inc();
+            return;
+        }
+        // Before forwarding this connection to a listening AF_VSOCK socket on the guest side,
+        // we need to know the destination port. We'll read that port from a "connect" command
+        // received on this socket, so the next step is to ask to be notified the moment we can
+        // read from it.
+        self.add_listener(stream.as_raw_fd(), EpollListener::LocalStream(stream))
+            .unwrap_or_else(|err| {
+                warn!("vsock: unable to accept local connection: {:?}", err);
+            });
+    }
+
+    /// Dispatch the events reported by the io_uring engine, and re-arm the polls they consumed
+    /// with the events their listeners are now interested in.
+    fn handle_uring_events(&mut self) {
+        // It's safe to unwrap here, since the `Uring` listener is only registered along with
+        // the ring.
+        let events = self.uring.as_mut().unwrap().events();
+        for event in events {
+            match event {
+                UringEvent::Accepted(stream) => self.add_local_stream(stream),
+                UringEvent::Ready { fd, evset } => {
+                    self.handle_event(fd, evset);
+
+                    let (key, evset) = match self.listener_map.get(&fd) {
+                        Some(EpollListener::Connection { key, evset }) => (Some(*key), *evset),
+                        Some(EpollListener::LocalStream(_)) => (None, EventSet::IN),
+                        _ => continue,
+                    };
+                    let uring = self.uring.as_mut().unwrap();
+                    // Handling the event may already have re-armed the poll.
+                    if uring.is_armed(fd) {
+                        continue;
+                    }
+                    if let Err(err) = uring.arm(fd, evset) {
+                        warn!("vsock: unable to re-arm poll of fd {}: {:?}", fd, err);
+                        METRICS.muxer_event_fails.inc();
+                        match key {
+                            Some(key) => self.kill_connection(key),
+                            None => {
+                                self.remove_listener(fd);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Submit the polls armed with the io_uring engine.
+    fn submit_uring(&mut self) {
+        if let Some(uring) = self.uring.as_mut() {
+            uring.submit().unwrap_or_else(|err| {
+                warn!("vsock: unable to submit io_uring operations: {:?}", err);
+                METRICS.muxer_event_fails.inc();
+            });
+        }
+    }
+
     /// Parse a host "connect" command, and extract the destination vsock port.
     fn read_local_stream_port(stream: &mut UnixStream) -> Result<u32, VsockUnixBackendError> {
         let mut buf = [0u8; 32];
@@ -488,6 +647,10 @@ impl VsockMuxer {
                 "vsock: muxer connection limit reached ({})",
                 defs::MAX_CONNECTIONS
             );
+            METRICS.conns_backlog_drops.inc();
+            if let Some(metrics) = self.port_metrics(key) {
+                metrics.conns_backlog_drops.inc();
+            }
             return Err(VsockUnixBackendError::TooManyConnections);
         }
 
@@ -507,6 +670,16 @@ impl VsockMuxer {
             }
             self.conn_map.insert(key, conn);
             METRICS.conns_added.inc();
+            let port = self.service_port(key);
+            if !self.port_metrics.contains_key(&port)
+                && let Some(metrics) = VsockMetricsPerPort::alloc(port)
+            {
+                self.port_metrics.insert(port, metrics);
+            }
+            if let Some(metrics) = self.port_metrics(key) {
+                metrics.conns_added.inc();
+                metrics.active_conns.store(metrics.active_conns.fetch() + 1);
+            }
         })
     }
 
@@ -515,6 +688,17 @@ impl VsockMuxer {
         if let Some(conn) = self.conn_map.remove(&key) {
             self.remove_listener(conn.as_raw_fd());
             METRICS.conns_removed.inc();
+            if let Some(metrics) = self.port_metrics(key) {
+                metrics.conns_removed.inc();
+                let active_conns = metrics.active_conns.fetch().saturating_sub(1);
+                metrics.active_conns.store(active_conns);
+                // Free the slot of the port once its last connection is closed.
+                if active_conns == 0 {
+                    let port = self.service_port(key);
+                    self.port_metrics.remove(&port);
+                    VsockMetricsPerPort::release(port);
+                }
+            }
         }
         self.free_local_port(key.local_port);
     }
@@ -540,7 +724,8 @@ impl VsockMuxer {
         }
     }
 
-    /// Register a new epoll listener under the muxer's nested epoll FD.
+    /// Register a new epoll listener under the muxer's nested epoll FD, or arm its poll with the
+    /// io_uring engine.
     fn add_listener(
         &mut self,
         fd: RawFd,
@@ -549,9 +734,20 @@ impl VsockMuxer {
         let evset = match listener {
             EpollListener::Connection { evset, .. } => evset,
             EpollListener::LocalStream(_) => EventSet::IN,
-            EpollListener::HostSock => EventSet::IN,
+            EpollListener::HostSock | EpollListener::Uring => EventSet::IN,
         };
 
+        if let Some(uring) = self.uring.as_mut()
+            && !matches!(listener, EpollListener::Uring)
+        {
+            uring
+                .arm(fd, evset)
+                .and_then(|()| uring.submit())
+                .map_err(VsockUnixBackendError::IoUring)?;
+            self.listener_map.insert(fd, listener);
+            return Ok(());
+        }
+
         self.epoll
             .ctl(
                 ControlOperation::Add,
@@ -570,7 +766,9 @@ impl VsockMuxer {
     fn remove_listener(&mut self, fd: RawFd) -> Option<EpollListener> {
         let maybe_listener = self.listener_map.remove(&fd);
 
-        if maybe_listener.is_some() {
+        if let (Some(_), Some(uring)) = (&maybe_listener, self.uring.as_mut()) {
+            uring.disarm(fd);
+        } else if maybe_listener.is_some() {
             self.epoll
                 .ctl(ControlOperation::Delete, fd, EpollEvent::default())
                 .unwrap_or_else(|err| {
@@ -613,15 +811,22 @@ impl VsockMuxer {
     fn handle_peer_request_pkt(&mut self, pkt: &VsockPacketTx) {
         let port_path = format!("{}_{}", self.host_sock_path, pkt.hdr.dst_port());
 
+        let key = ConnMapKey {
+            local_port: pkt.hdr.dst_port(),
+            peer_port: pkt.hdr.src_port(),
+        };
         UnixStream::connect(port_path)
             .and_then(|stream| stream.set_nonblocking(true).map(|_| stream))
-            .map_err(VsockUnixBackendError::UnixConnect)
+            .map_err(|err| {
+                METRICS.conns_rejected.inc();
+                if let Some(metrics) = self.port_metrics(key) {
+                    metrics.conns_rejected.inc();
+                }
+                VsockUnixBackendError::UnixConnect(err)
+            })
             .and_then(|stream| {
                 self.add_connection(
-                    ConnMapKey {
-                        local_port: pkt.hdr.dst_port(),
-                        peer_port: pkt.hdr.src_port(),
-                    },
+                    key,
                     MuxerConnection::new_peer_init(
                         stream,
                         uapi::VSOCK_HOST_CID,
@@ -646,13 +851,20 @@ impl VsockMuxer {
     where
         F: FnOnce(&mut MuxerConnection),
     {
+        let port_metrics = self.port_metrics(key);
         if let Some(conn) = self.conn_map.get_mut(&key) {
             let had_rx = conn.has_pending_rx();
             let was_expiring = conn.will_expire();
             let prev_state = conn.state();
+            let (prev_rx_bytes, prev_tx_bytes) = (conn.rx_bytes(), conn.tx_bytes());
 
             mut_fn(conn);
 
+            if let Some(metrics) = port_metrics {
+                metrics.rx_bytes_count.add(conn.rx_bytes() - prev_rx_bytes);
+                metrics.tx_bytes_count.add(conn.tx_bytes() - prev_tx_bytes);
+            }
+
             // If this is a host-initiated connection that has just become established, we'll have
             // to send an ack message to the host end.
             if prev_state == ConnState::LocalInit && conn.state() == ConnState::Established {
@@ -703,22 +915,30 @@ impl VsockMuxer {
                     );
 
                     *evset = new_evset;
-                    self.epoll
-                        .ctl(
-                            ControlOperation::Modify,
-                            fd,
-                            EpollEvent::new(new_evset, u64::try_from(fd).unwrap()),
-                        )
-                        .unwrap_or_else(|err| {
-                            // This really shouldn't happen, like, ever. However, "famous last
-                            // words" and all that, so let's just kill it with fire, and walk away.
-                            self.kill_connection(key);
-                            error!(
-                                "vsock: error updating epoll listener for (lp={}, pp={}): {:?}",
-                                key.local_port, key.peer_port, err
-                            );
-                            METRICS.muxer_event_fails.inc();
-                        });
+                    let res = match self.uring.as_mut() {
+                        Some(uring) => uring
+                            .arm(fd, new_evset)
+                            .and_then(|()| uring.submit())
+                            .map_err(VsockUnixBackendError::IoUring),
+                        None => self
+                            .epoll
+                            .ctl(
+                                ControlOperation::Modify,
+                                fd,
+                                EpollEvent::new(new_evset, u64::try_from(fd).unwrap()),
+                            )
+                            .map_err(VsockUnixBackendError::EpollModify),
+                    };
+                    res.unwrap_or_else(|err| {
+                        // This really shouldn't happen, like, ever. However, "famous last
+                        // words" and all that, so let's just kill it with fire, and walk away.
+                        self.kill_connection(key);
+                        error!(
+                            "vsock: error updating epoll listener for (lp={}, pp={}): {:?}",
+                            key.local_port, key.peer_port, err
+                        );
+                        METRICS.muxer_event_fails.inc();
+                    });
                 }
             } else {
                 // The connection had previously asked to be removed from the listener map (by
@@ -782,6 +1002,7 @@ impl VsockMuxer {
                 "vsock: muxer.rxq full; dropping RST packet for lp={}, pp={}",
                 local_port, peer_port
             );
+            METRICS.rst_drops.inc();
         }
     }
 }
@@ -832,6 +1053,13 @@ mod tests {
 
     impl MuxerTestContext {
         fn new(name: &str) -> Self {
+            Self::new_with_io_engine(name, VsockIoEngine::Epoll).unwrap()
+        }
+
+        fn new_with_io_engine(
+            name: &str,
+            io_engine: VsockIoEngine,
+        ) -> Result<Self, VsockUnixBackendError> {
             let vsock_test_ctx = VsockTestContext::new();
             let mut handler_ctx = vsock_test_ctx.create_event_handler_context();
             let mut rx_pkt = VsockPacketRx::new().unwrap();
@@ -849,13 +1077,13 @@ mod tests {
                 )
                 .unwrap();
 
-            let muxer = VsockMuxer::new(PEER_CID, get_file(name)).unwrap();
-            Self {
+            let muxer = VsockMuxer::new_with_io_engine(PEER_CID, get_file(name), io_engine)?;
+            Ok(Self {
                 _vsock_test_ctx: vsock_test_ctx,
                 rx_pkt,
                 tx_pkt,
                 muxer,
-            }
+            })
         }
 
         fn init_tx_pkt(&mut self, local_port: u32, peer_port: u32, op: u16) -> &mut VsockPacketTx {
@@ -897,6 +1125,21 @@ mod tests {
         }
 
         fn notify_muxer(&mut self) {
+            if self.muxer.io_engine() == VsockIoEngine::IoUring {
+                // The ring posts its completions asynchronously, so wait for them to be
+                // signalled.
+                let mut pollfd = libc::pollfd {
+                    fd: self.muxer.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                for _ in 0..5 {
+                    // SAFETY: The file descriptor is valid, and `pollfd` outlives the call.
+                    if unsafe { libc::poll(&mut pollfd, 1, 100) } > 0 {
+                        break;
+                    }
+                }
+            }
             self.muxer.notify(EventSet::IN);
         }
 
@@ -1172,6 +1415,49 @@ mod tests {
         assert_eq!(&buf, &data);
     }
 
+    #[test]
+    fn test_local_connection_io_uring() {
+        let mut ctx = match MuxerTestContext::new_with_io_engine(
+            "local_connection_io_uring",
+            VsockIoEngine::IoUring,
+        ) {
+            Ok(ctx) => ctx,
+            // The host kernel is too old for this engine.
+            Err(VsockUnixBackendError::IoUringUnsupported) => return,
+            Err(err) => panic!("{err}"),
+        };
+        let peer_port = 1025;
+        let (mut stream, local_port) = ctx.local_connect(peer_port);
+
+        // Test guest -> host data flow.
+        let data = [1, 2, 3, 4];
+        ctx.init_data_tx_pkt(local_port, peer_port, &data);
+        ctx.send();
+
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(buf.as_mut_slice()).unwrap();
+        assert_eq!(buf.as_slice(), &data);
+
+        // Test host -> guest data flow.
+        let data = [5, 6, 7, 8];
+        stream.write_all(&data).unwrap();
+        ctx.notify_muxer();
+
+        assert!(ctx.muxer.has_pending_rx());
+        ctx.recv();
+        assert_eq!(ctx.rx_pkt.hdr.op(), uapi::VSOCK_OP_RW);
+        assert_eq!(ctx.rx_pkt.hdr.src_port(), local_port);
+        assert_eq!(ctx.rx_pkt.hdr.dst_port(), peer_port);
+
+        let buf = test_utils::read_packet_data(&ctx.tx_pkt, 4);
+        assert_eq!(&buf, &data);
+
+        // The poll of a connection is re-armed after each event.
+        stream.write_all(&data).unwrap();
+        ctx.notify_muxer();
+        assert!(ctx.muxer.has_pending_rx());
+    }
+
     #[test]
     fn test_local_close() {
         let peer_port = 1025;
@@ -1534,4 +1820,82 @@ mod tests {
         // Check that the connection was removed.
         assert_eq!(METRICS.conns_removed.count(), conns_removed + 1);
     }
+
+    #[test]
+    fn test_connections_and_port_metrics() {
+        // A port used by no other test, so that its metrics are not shared.
+        const LOCAL_PORT: u32 = 1040;
+        const PEER_PORT: u32 = 1025;
+
+        let mut ctx = MuxerTestContext::new("connections_and_port_metrics");
+        let key = ConnMapKey {
+            local_port: LOCAL_PORT,
+            peer_port: PEER_PORT,
+        };
+
+        // A connection request is rejected when no host socket listens on the port, and the
+        // port is not tracked.
+        ctx.init_tx_pkt(LOCAL_PORT, PEER_PORT, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        ctx.recv();
+        assert_eq!(ctx.rx_pkt.hdr.op(), uapi::VSOCK_OP_RST);
+        assert!(ctx.muxer.port_metrics(key).is_none());
+        assert!(ctx.muxer.connections().is_empty());
+
+        let mut listener = ctx.create_local_listener(LOCAL_PORT);
+        ctx.init_tx_pkt(LOCAL_PORT, PEER_PORT, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+        let mut stream = listener.accept();
+        ctx.recv();
+        let metrics = ctx.muxer.port_metrics(key).unwrap();
+        assert_eq!(metrics.conns_added.count(), 1);
+        assert_eq!(metrics.active_conns.fetch(), 1);
+
+        // Guest -> host data flow.
+        let data = [1, 2, 3, 4];
+        ctx.init_data_tx_pkt(LOCAL_PORT, PEER_PORT, &data);
+        ctx.send();
+        let mut buf = vec![0; data.len()];
+        stream.read_exact(buf.as_mut_slice()).unwrap();
+
+        // Host -> guest data flow.
+        stream.write_all(&[5, 6]).unwrap();
+        ctx.notify_muxer();
+        ctx.recv();
+
+        assert_eq!(metrics.tx_bytes_count.count(), 4);
+        assert_eq!(metrics.rx_bytes_count.count(), 2);
+        assert_eq!(
+            ctx.muxer.connections(),
+            vec![VsockConnectionInfo {
+                local_port: LOCAL_PORT,
+                peer_port: PEER_PORT,
+                host_initiated: false,
+                state: "established",
+                rx_bytes: 2,
+                tx_bytes: 4,
+            }]
+        );
+
+        // Host-initiated connections are accounted for on the guest-side port.
+        let (_local_stream, local_port) = ctx.local_connect(LOCAL_PORT);
+        let conns = ctx.muxer.connections();
+        assert_eq!(conns.len(), 2);
+        assert_eq!(conns[1].local_port, local_port);
+        assert!(conns[1].host_initiated);
+        assert_eq!(metrics.active_conns.fetch(), 2);
+
+        ctx.init_tx_pkt(LOCAL_PORT, PEER_PORT, uapi::VSOCK_OP_RST);
+        ctx.send();
+        assert_eq!(metrics.conns_removed.count(), 1);
+        assert_eq!(metrics.active_conns.fetch(), 1);
+
+        // The port is no longer tracked once its last connection is closed.
+        ctx.init_tx_pkt(local_port, LOCAL_PORT, uapi::VSOCK_OP_RST);
+        ctx.send();
+        assert_eq!(metrics.conns_removed.count(), 2);
+        assert_eq!(metrics.active_conns.fetch(), 0);
+        assert!(ctx.muxer.port_metrics(key).is_none());
+        assert!(ctx.muxer.port_metrics.is_empty());
+    }
 }
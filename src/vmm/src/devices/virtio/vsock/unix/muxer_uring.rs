@@ -0,0 +1,353 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+/// `MuxerUring` implements the io_uring engine of the vsock muxer. Instead of registering every
+/// host-side Unix socket under the muxer's nested epoll FD, the muxer drives them through an
+/// io_uring instance, whose completions are signalled on an eventfd. That eventfd is then the
+/// only FD registered under the nested epoll FD.
+///
+/// - Host-initiated connections are accepted by a single multishot accept operation on the host
+///   socket: the kernel accepts the connections as they arrive, and posts one completion per
+///   connection, holding the new socket.
+/// - The other sockets (connections, and freshly accepted streams waiting for their `connect`
+///   command) are watched by one-shot poll operations. Once the muxer has handled the completion
+///   of a poll, it re-arms it with the events the socket is currently interested in. This keeps
+///   the level-triggered semantics the connections rely on, while all the polls re-armed during
+///   an event loop iteration are submitted with a single system call, instead of an `epoll_ctl`
+///   call each.
+///
+/// A poll is identified by its file descriptor and the generation it was armed at, so that the
+/// completions of cancelled polls, or of polls armed on a file descriptor since closed and
+/// reused, are told apart from the current ones.
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::OwnedFd;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use log::warn;
+use vmm_sys_util::epoll::EventSet;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::VsockUnixBackendError;
+use crate::devices::virtio::vsock::metrics::METRICS;
+use crate::io_uring::operation::{OpCode, Operation};
+use crate::io_uring::restriction::Restriction;
+use crate::io_uring::{IoUring, IoUringError};
+use crate::logger::IncMetric;
+
+/// Number of entries of the submission queue. The completion queue is twice as large, which
+/// leaves room for a poll per connection and per pending `connect` command, along with the
+/// accept and cancel operations.
+const RING_SIZE: u32 = 2048;
+/// Index of the host socket among the files registered with the ring.
+const HOST_SOCK_FIXED_FD: u32 = 0;
+
+/// Identifies the operation of a completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    /// The multishot accept operation on the host socket.
+    Accept,
+    /// A poll operation on `fd`.
+    Poll { fd: RawFd, generation: u64 },
+    /// A cancel operation.
+    Cancel,
+}
+
+/// An event reported by the ring.
+#[derive(Debug)]
+pub enum UringEvent {
+    /// A new host-initiated connection has been accepted.
+    Accepted(UnixStream),
+    /// A polled socket is ready for the events in `evset`. Its poll is now disarmed.
+    Ready { fd: RawFd, evset: EventSet },
+}
+
+/// The io_uring engine of the vsock muxer.
+#[derive(Debug)]
+pub struct MuxerUring {
+    ring: IoUring<Token>,
+    completion_evt: EventFd,
+    /// Generation of the poll armed on each file descriptor.
+    armed: HashMap<RawFd, u64>,
+    next_generation: u64,
+}
+
+impl MuxerUring {
+    /// Create the ring, and start accepting connections on the host socket.
+    pub fn new(host_sock: &UnixListener) -> Result<Self, VsockUnixBackendError> {
+        let completion_evt =
+            EventFd::new(libc::EFD_NONBLOCK).map_err(VsockUnixBackendError::EventFd)?;
+        // The ring only needs to hold a reference to the host socket.
+        let host_sock_file = host_sock
+            .try_clone()
+            .map(|sock| File::from(OwnedFd::from(sock)))
+            .map_err(VsockUnixBackendError::UnixBind)?;
+        let ring = IoUring::new(
+            RING_SIZE,
+            vec![&host_sock_file],
+            vec![
+                Restriction::AllowOpCode(OpCode::Accept),
+                Restriction::AllowOpCode(OpCode::PollAdd),
+                Restriction::AllowOpCode(OpCode::AsyncCancel),
+                Restriction::AllowFixedFds,
+            ],
+            Some(completion_evt.as_raw_fd()),
+        )
+        .map_err(VsockUnixBackendError::IoUring)?;
+
+        let mut uring = Self {
+            ring,
+            completion_evt,
+            armed: HashMap::new(),
+            next_generation: 0,
+        };
+        uring.check_support(host_sock.as_raw_fd())?;
+        uring.arm_accept().map_err(VsockUnixBackendError::IoUring)?;
+        uring.submit().map_err(VsockUnixBackendError::IoUring)?;
+        Ok(uring)
+    }
+
+    /// Check that the host kernel supports multishot accept and cancelling the operations of a
+    /// file descriptor, which both came with Linux 5.19. Older kernels reject the flags of the
+    /// cancel operation, while newer ones report that nothing was cancelled.
+    fn check_support(&mut self, fd: RawFd) -> Result<(), VsockUnixBackendError> {
+        self.push(Operation::cancel_fd(fd, Token::Cancel))
+            .and_then(|()| self.ring.submit_and_wait_all())
+            .and_then(|_| self.ring.pop())
+            .map_err(VsockUnixBackendError::IoUring)?
+            .map(|cqe| cqe.result())
+            .and_then(Result::err)
+            // The ring reports errors as negated errno values.
+            .filter(|err| err.raw_os_error() == Some(-libc::ENOENT))
+            .map(|_| ())
+            .ok_or(VsockUnixBackendError::IoUringUnsupported)
+    }
+
+    /// Get the eventfd signalled when operations complete.
+    pub fn completion_evt(&self) -> &EventFd {
+        &self.completion_evt
+    }
+
+    fn push(&mut self, op: Operation<Token>) -> Result<(), IoUringError> {
+        if self.ring.pending_sqes()? == RING_SIZE {
+            self.ring.submit()?;
+        }
+        self.ring.push(op).map_err(|(err, _)| err)
+    }
+
+    fn arm_accept(&mut self) -> Result<(), IoUringError> {
+        self.push(Operation::accept_multishot(
+            HOST_SOCK_FIXED_FD,
+            Token::Accept,
+        ))
+    }
+
+    /// Check whether a poll is armed on `fd`.
+    pub fn is_armed(&self, fd: RawFd) -> bool {
+        self.armed.contains_key(&fd)
+    }
+
+    /// Arm a poll of `fd` for the events in `evset`, replacing the poll already armed on it, if
+    /// any. The poll is only submitted by the next call to `submit()`.
+    pub fn arm(&mut self, fd: RawFd, evset: EventSet) -> Result<(), IoUringError> {
+        // Operations are started in submission order, so the previous poll is cancelled before
+        // the new one is armed.
+        if self.armed.contains_key(&fd) {
+            self.push(Operation::cancel_fd(fd, Token::Cancel))?;
+        }
+        let generation = self.next_generation;
+        self.push(Operation::poll(
+            fd,
+            evset.bits(),
+            Token::Poll { fd, generation },
+        ))?;
+        self.next_generation += 1;
+        self.armed.insert(fd, generation);
+        Ok(())
+    }
+
+    /// Disarm the poll of `fd`, if any. This must happen before `fd` is closed, since the poll
+    /// holds a reference to the socket, which would otherwise be kept open.
+    pub fn disarm(&mut self, fd: RawFd) {
+        if self.armed.remove(&fd).is_some() {
+            self.push(Operation::cancel_fd(fd, Token::Cancel))
+                .and_then(|()| self.submit())
+                .unwrap_or_else(|err| {
+                    warn!("vsock: unable to cancel the poll of fd {}: {:?}", fd, err);
+                    METRICS.muxer_event_fails.inc();
+                });
+        }
+    }
+
+    /// Submit the operations pushed since the last call.
+    pub fn submit(&mut self) -> Result<(), IoUringError> {
+        self.ring.submit().map(|_| ())
+    }
+
+    /// Consume the completion notification, and return the events reported since the last
+    /// call.
+    pub fn events(&mut self) -> Vec<UringEvent> {
+        // The eventfd is non-blocking, and a spurious notification simply yields no event.
+        let _ = self.completion_evt.read();
+
+        let mut events = Vec::new();
+        loop {
+            let cqe = match self.ring.pop_multishot() {
+                Ok(Some(cqe)) => cqe,
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("vsock: unable to pop io_uring completion: {:?}", err);
+                    METRICS.muxer_event_fails.inc();
+                    break;
+                }
+            };
+            let more = cqe.more();
+            let result = cqe.result();
+            match cqe.user_data() {
+                Token::Accept => {
+                    match result {
+                        Ok(fd) => {
+                            // SAFETY: The kernel installed a new file descriptor for the
+                            // accepted socket, which nothing else owns.
+                            let stream =
+                                unsafe { UnixStream::from_raw_fd(RawFd::try_from(fd).unwrap()) };
+                            events.push(UringEvent::Accepted(stream));
+                        }
+                        Err(err) => {
+                            warn!("vsock: unable to accept local connection: {:?}", err);
+                            METRICS.muxer_event_fails.inc();
+                        }
+                    }
+                    // The kernel terminates multishot operations on errors, or when it runs out
+                    // of room for their completions.
+                    if !more {
+                        self.arm_accept().unwrap_or_else(|err| {
+                            warn!("vsock: unable to accept local connections: {:?}", err);
+                            METRICS.muxer_event_fails.inc();
+                        });
+                    }
+                }
+                Token::Poll { fd, generation } => {
+                    if self.armed.get(&fd) != Some(&generation) {
+                        // This poll has been cancelled or replaced.
+                        continue;
+                    }
+                    self.armed.remove(&fd);
+                    match result {
+                        // Poll results are `POLL*` masks, which match the epoll flags.
+                        Ok(mask) => events.push(UringEvent::Ready {
+                            fd,
+                            evset: EventSet::from_bits_truncate(mask),
+                        }),
+                        Err(err) => {
+                            warn!("vsock: unable to poll fd {}: {:?}", fd, err);
+                            METRICS.muxer_event_fails.inc();
+                        }
+                    }
+                }
+                Token::Cancel => (),
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    /// Wait for completions, and return the events they report.
+    fn wait_events(uring: &mut MuxerUring) -> Vec<UringEvent> {
+        let mut pollfd = libc::pollfd {
+            fd: uring.completion_evt().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // The completions may only be posted once the kernel runs the work they are queued
+        // with, which happens when this thread enters the kernel.
+        for _ in 0..5 {
+            // SAFETY: The file descriptor is valid, and `pollfd` outlives the call.
+            if unsafe { libc::poll(&mut pollfd, 1, 100) } > 0 {
+                break;
+            }
+        }
+        uring.events()
+    }
+
+    #[test]
+    fn test_muxer_uring() {
+        let path = TempFile::new_with_prefix("muxer_uring")
+            .unwrap()
+            .as_path()
+            .to_path_buf();
+        let host_sock = UnixListener::bind(&path).unwrap();
+        let mut uring = match MuxerUring::new(&host_sock) {
+            Ok(uring) => uring,
+            // The host kernel is too old for this engine.
+            Err(VsockUnixBackendError::IoUringUnsupported) => {
+                std::fs::remove_file(&path).unwrap();
+                return;
+            }
+            Err(err) => panic!("{err}"),
+        };
+
+        // Connections are accepted as they arrive.
+        let mut clients: Vec<_> = (0..2)
+            .map(|_| UnixStream::connect(&path).unwrap())
+            .collect();
+        let mut streams = Vec::new();
+        while streams.len() < 2 {
+            match wait_events(&mut uring).pop() {
+                Some(UringEvent::Accepted(stream)) => streams.insert(0, stream),
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+
+        // A poll completes once its socket is ready, and is then disarmed.
+        let fd = streams[0].as_raw_fd();
+        uring.arm(fd, EventSet::IN).unwrap();
+        uring.submit().unwrap();
+        assert!(uring.is_armed(fd));
+        clients[0].write_all(b"connect 52\n").unwrap();
+        match wait_events(&mut uring).as_slice() {
+            [
+                UringEvent::Ready {
+                    fd: ready_fd,
+                    evset,
+                },
+            ] => {
+                assert_eq!(*ready_fd, fd);
+                assert_eq!(*evset, EventSet::IN);
+            }
+            events => panic!("unexpected events {events:?}"),
+        }
+        assert!(!uring.is_armed(fd));
+
+        // Re-arming a poll replaces it, and disarming it cancels it.
+        let fd = streams[1].as_raw_fd();
+        uring.arm(fd, EventSet::IN).unwrap();
+        uring.arm(fd, EventSet::OUT).unwrap();
+        uring.submit().unwrap();
+        match wait_events(&mut uring).as_slice() {
+            [UringEvent::Ready { evset, .. }] => assert_eq!(*evset, EventSet::OUT),
+            events => panic!("unexpected events {events:?}"),
+        }
+        uring.arm(fd, EventSet::IN).unwrap();
+        uring.disarm(fd);
+        clients[1].write_all(b"connect 52\n").unwrap();
+        assert!(wait_events(&mut uring).is_empty());
+
+        // Closing the accepted socket, once its poll is cancelled, closes the connection.
+        drop(streams.pop());
+        let mut buf = [0u8; 1];
+        assert_eq!(std::io::Read::read(&mut clients[1], &mut buf).unwrap(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
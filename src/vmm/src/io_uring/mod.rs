@@ -166,10 +166,9 @@ impl<T: Debug> IoUring<T> {
     /// Push an [`Operation`](operation/struct.Operation.html) onto the submission queue.
     pub fn push(&mut self, op: Operation<T>) -> Result<(), (IoUringError, T)> {
         // validate that we actually did register fds
-        let fd = op.fd();
-        match self.registered_fds_count {
-            0 => Err((IoUringError::NoRegisteredFds, op.user_data)),
-            len if fd >= len => Err((IoUringError::InvalidFixedFd(fd), op.user_data)),
+        match (op.fixed_fd(), self.registered_fds_count) {
+            (Some(_), 0) => Err((IoUringError::NoRegisteredFds, op.user_data)),
+            (Some(fd), len) if fd >= len => Err((IoUringError::InvalidFixedFd(fd), op.user_data)),
             _ => {
                 if self.num_ops >= self.cqueue.count() {
                     return Err((IoUringError::FullCQueue, op.user_data));
@@ -205,7 +204,7 @@ impl<T: Debug> IoUring<T> {
     /// The type `T` must be the same as the `user_data` type used for `push`-ing the operation.
     pub fn pop(&mut self) -> Result<Option<Cqe<T>>, IoUringError> {
         self.cqueue
-            .pop(&mut self.slab)
+            .pop(&mut self.slab, |_| None)
             .map(|maybe_cqe| {
                 maybe_cqe.inspect(|_| {
                     // This is safe since the pop-ed CQEs have been previously pushed. However
@@ -216,6 +215,25 @@ impl<T: Debug> IoUring<T> {
             .map_err(IoUringError::CQueue)
     }
 
+    /// Pop a completed entry off the completion queue of a ring running multishot operations.
+    /// The `user_data` of an operation which will post more completions is cloned, and only
+    /// released with its last completion.
+    pub fn pop_multishot(&mut self) -> Result<Option<Cqe<T>>, IoUringError>
+    where
+        T: Clone,
+    {
+        self.cqueue
+            .pop(&mut self.slab, |user_data| Some(user_data.clone()))
+            .map(|maybe_cqe| {
+                maybe_cqe.inspect(|cqe| {
+                    if !cqe.more() {
+                        self.num_ops = self.num_ops.saturating_sub(1);
+                    }
+                })
+            })
+            .map_err(IoUringError::CQueue)
+    }
+
     fn do_submit(&mut self, min_complete: u32) -> Result<u32, IoUringError> {
         self.squeue
             .submit(min_complete)
@@ -3,7 +3,7 @@
 
 use std::fmt::Debug;
 
-use crate::io_uring::generated::io_uring_cqe;
+use crate::io_uring::generated::{IORING_CQE_F_MORE, io_uring_cqe};
 use crate::vstate::memory::ByteValued;
 
 // SAFETY: Struct is POD and contains no references or niches.
@@ -13,13 +13,29 @@ unsafe impl ByteValued for io_uring_cqe {}
 #[derive(Debug)]
 pub struct Cqe<T> {
     res: i32,
+    flags: u32,
     user_data: T,
 }
 
 impl<T: Debug> Cqe<T> {
     /// Construct a Cqe object.
     pub fn new(res: i32, user_data: T) -> Self {
-        Self { res, user_data }
+        Self::with_flags(res, 0, user_data)
+    }
+
+    /// Construct a Cqe object with the given `IORING_CQE_F_*` flags.
+    pub(crate) fn with_flags(res: i32, flags: u32, user_data: T) -> Self {
+        Self {
+            res,
+            flags,
+            user_data,
+        }
+    }
+
+    /// Return whether the operation will post more completions, i.e. it is a multishot
+    /// operation which is still active.
+    pub fn more(&self) -> bool {
+        self.flags & IORING_CQE_F_MORE != 0
     }
 
     /// Return the number of bytes successfully transferred by this operation.
@@ -42,6 +58,7 @@ impl<T: Debug> Cqe<T> {
     pub fn map_user_data<U: Debug, F: FnOnce(T) -> U>(self, op: F) -> Cqe<U> {
         Cqe {
             res: self.res,
+            flags: self.flags,
             user_data: op(self.user_data()),
         }
     }
@@ -86,6 +103,17 @@ mod tests {
         assert_eq!(cqe.user_data(), 10);
     }
 
+    #[test]
+    fn test_more() {
+        assert!(!Cqe::new(0, 10_u8).more());
+        assert!(Cqe::with_flags(0, IORING_CQE_F_MORE, 10_u8).more());
+        assert!(
+            Cqe::with_flags(0, IORING_CQE_F_MORE, 10_u8)
+                .map_user_data(|x| x + 1)
+                .more()
+        );
+    }
+
     #[test]
     fn test_map_user_data() {
         let user_data = 10_u8;
@@ -8,11 +8,15 @@ mod sqe;
 
 use std::convert::From;
 use std::fmt::{self, Debug};
+use std::os::unix::io::RawFd;
 
 pub use cqe::Cqe;
 pub(crate) use sqe::Sqe;
 
-use crate::io_uring::generated::{io_uring_op, io_uring_sqe, io_uring_sqe_flags_bit};
+use crate::io_uring::generated::{
+    IORING_ACCEPT_MULTISHOT, IORING_ASYNC_CANCEL_ALL, IORING_ASYNC_CANCEL_FD, io_uring_op,
+    io_uring_sqe, io_uring_sqe_flags_bit,
+};
 
 /// The index of a registered fd.
 pub type FixedFd = u32;
@@ -29,6 +33,18 @@ pub enum OpCode {
     Write = io_uring_op::IORING_OP_WRITE as u8,
     /// Fsync operation.
     Fsync = io_uring_op::IORING_OP_FSYNC as u8,
+    /// Accept operation.
+    Accept = io_uring_op::IORING_OP_ACCEPT as u8,
+    /// Poll operation.
+    PollAdd = io_uring_op::IORING_OP_POLL_ADD as u8,
+    /// Cancel operation.
+    AsyncCancel = io_uring_op::IORING_OP_ASYNC_CANCEL as u8,
+    /// Socket send operation.
+    Send = io_uring_op::IORING_OP_SEND as u8,
+    /// Socket receive operation.
+    Recv = io_uring_op::IORING_OP_RECV as u8,
+    /// Socket message send operation.
+    SendMsg = io_uring_op::IORING_OP_SENDMSG as u8,
 }
 
 // Useful for outputting errors.
@@ -38,18 +54,35 @@ impl From<OpCode> for &'static str {
             OpCode::Read => "read",
             OpCode::Write => "write",
             OpCode::Fsync => "fsync",
+            OpCode::Accept => "accept",
+            OpCode::PollAdd => "poll_add",
+            OpCode::AsyncCancel => "async_cancel",
+            OpCode::Send => "send",
+            OpCode::Recv => "recv",
+            OpCode::SendMsg => "sendmsg",
         }
     }
 }
 
+/// File targeted by an operation.
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    /// A registered file.
+    Fixed(FixedFd),
+    /// A file descriptor which was not registered with the ring.
+    Raw(RawFd),
+}
+
 /// Operation type for populating the submission queue, parametrised with the `user_data` type `T`.
 /// The `user_data` is used for identifying the operation once completed.
 pub struct Operation<T> {
-    fd: FixedFd,
+    target: Target,
     pub(crate) opcode: OpCode,
     pub(crate) addr: Option<usize>,
     pub(crate) len: Option<u32>,
     flags: u8,
+    ioprio: u16,
+    op_flags: u32,
     pub(crate) offset: Option<u64>,
     pub(crate) user_data: T,
 }
@@ -77,11 +110,13 @@ impl<T: Debug> Operation<T> {
     /// Construct a read operation.
     pub fn read(fd: FixedFd, addr: usize, len: u32, offset: u64, user_data: T) -> Self {
         Self {
-            fd,
+            target: Target::Fixed(fd),
             opcode: OpCode::Read,
             addr: Some(addr),
             len: Some(len),
             flags: 0,
+            ioprio: 0,
+            op_flags: 0,
             offset: Some(offset),
             user_data,
         }
@@ -90,11 +125,13 @@ impl<T: Debug> Operation<T> {
     /// Construct a write operation.
     pub fn write(fd: FixedFd, addr: usize, len: u32, offset: u64, user_data: T) -> Self {
         Self {
-            fd,
+            target: Target::Fixed(fd),
             opcode: OpCode::Write,
             addr: Some(addr),
             len: Some(len),
             flags: 0,
+            ioprio: 0,
+            op_flags: 0,
             offset: Some(offset),
             user_data,
         }
@@ -103,18 +140,125 @@ impl<T: Debug> Operation<T> {
     /// Construct a fsync operation.
     pub fn fsync(fd: FixedFd, user_data: T) -> Self {
         Self {
-            fd,
+            target: Target::Fixed(fd),
             opcode: OpCode::Fsync,
             addr: None,
             len: None,
             flags: 0,
+            ioprio: 0,
+            op_flags: 0,
             offset: None,
             user_data,
         }
     }
 
-    pub(crate) fn fd(&self) -> FixedFd {
-        self.fd
+    /// Construct a send operation on a registered socket. Broken connections fail the operation
+    /// with `EPIPE` rather than raising `SIGPIPE`.
+    pub fn send(fd: FixedFd, addr: usize, len: u32, user_data: T) -> Self {
+        Self {
+            target: Target::Fixed(fd),
+            opcode: OpCode::Send,
+            addr: Some(addr),
+            len: Some(len),
+            flags: 0,
+            ioprio: 0,
+            op_flags: u32::try_from(libc::MSG_NOSIGNAL).unwrap(),
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// Construct a send operation of the message described by the `msghdr` at `msghdr` on a
+    /// registered socket, which may be unconnected. Broken connections fail the operation with
+    /// `EPIPE` rather than raising `SIGPIPE`.
+    pub fn sendmsg(fd: FixedFd, msghdr: usize, user_data: T) -> Self {
+        Self {
+            target: Target::Fixed(fd),
+            opcode: OpCode::SendMsg,
+            addr: Some(msghdr),
+            // A single message is sent.
+            len: Some(1),
+            flags: 0,
+            ioprio: 0,
+            op_flags: u32::try_from(libc::MSG_NOSIGNAL).unwrap(),
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// Construct a receive operation on a registered socket.
+    pub fn recv(fd: FixedFd, addr: usize, len: u32, user_data: T) -> Self {
+        Self {
+            target: Target::Fixed(fd),
+            opcode: OpCode::Recv,
+            addr: Some(addr),
+            len: Some(len),
+            flags: 0,
+            ioprio: 0,
+            op_flags: 0,
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// Construct a multishot accept operation on a listening socket. A completion is posted for
+    /// every accepted connection, holding the non-blocking file descriptor of the new socket,
+    /// until the operation fails or is cancelled. Requires Linux 5.19.
+    pub fn accept_multishot(fd: FixedFd, user_data: T) -> Self {
+        Self {
+            target: Target::Fixed(fd),
+            opcode: OpCode::Accept,
+            addr: None,
+            len: None,
+            flags: 0,
+            // The multishot flag of accept operations lives in the ioprio field.
+            ioprio: u16::try_from(IORING_ACCEPT_MULTISHOT).unwrap(),
+            op_flags: u32::try_from(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC).unwrap(),
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// Construct a one-shot poll operation on a file descriptor which was not registered with
+    /// the ring. It completes with the mask of the `events` (`POLL*` flags) the file is ready
+    /// for.
+    pub fn poll(fd: RawFd, events: u32, user_data: T) -> Self {
+        Self {
+            target: Target::Raw(fd),
+            opcode: OpCode::PollAdd,
+            addr: None,
+            len: None,
+            flags: 0,
+            ioprio: 0,
+            op_flags: events,
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// Construct an operation cancelling all the operations in flight on a file descriptor which
+    /// was not registered with the ring. It completes with the number of cancelled operations.
+    /// Requires Linux 5.19.
+    pub fn cancel_fd(fd: RawFd, user_data: T) -> Self {
+        Self {
+            target: Target::Raw(fd),
+            opcode: OpCode::AsyncCancel,
+            addr: None,
+            len: None,
+            flags: 0,
+            ioprio: 0,
+            op_flags: IORING_ASYNC_CANCEL_FD | IORING_ASYNC_CANCEL_ALL,
+            offset: None,
+            user_data,
+        }
+    }
+
+    /// The registered file targeted by the operation, if any.
+    pub(crate) fn fixed_fd(&self) -> Option<FixedFd> {
+        match self.target {
+            Target::Fixed(fd) => Some(fd),
+            Target::Raw(_) => None,
+        }
     }
 
     // Needed for proptesting.
@@ -123,6 +267,13 @@ impl<T: Debug> Operation<T> {
         self.flags |= 1 << io_uring_sqe_flags_bit::IOSQE_IO_LINK_BIT;
     }
 
+    /// Mark the operation as a barrier: it is only started once all the previously submitted
+    /// operations have completed, and the operations submitted after it are only started once it
+    /// has completed.
+    pub(crate) fn set_drain(&mut self) {
+        self.flags |= 1 << io_uring_sqe_flags_bit::IOSQE_IO_DRAIN_BIT;
+    }
+
     /// Transform the operation into an `Sqe`.
     /// Note: remember remove user_data from slab or it will leak.
     pub(crate) fn into_sqe(self, slab: &mut slab::Slab<T>) -> Sqe {
@@ -131,9 +282,27 @@ impl<T: Debug> Operation<T> {
         let mut inner: io_uring_sqe = unsafe { std::mem::zeroed() };
 
         inner.opcode = self.opcode as u8;
-        inner.fd = i32::try_from(self.fd).unwrap();
-        // Simplifying assumption that we only used pre-registered FDs.
-        inner.flags = self.flags | (1 << io_uring_sqe_flags_bit::IOSQE_FIXED_FILE_BIT);
+        inner.ioprio = self.ioprio;
+        match self.target {
+            Target::Fixed(fd) => {
+                inner.fd = i32::try_from(fd).unwrap();
+                inner.flags = self.flags | (1 << io_uring_sqe_flags_bit::IOSQE_FIXED_FILE_BIT);
+            }
+            Target::Raw(fd) => {
+                inner.fd = fd;
+                inner.flags = self.flags;
+            }
+        }
+
+        match self.opcode {
+            OpCode::Accept => inner.__bindgen_anon_3.accept_flags = self.op_flags,
+            OpCode::PollAdd => inner.__bindgen_anon_3.poll32_events = self.op_flags,
+            OpCode::AsyncCancel => inner.__bindgen_anon_3.cancel_flags = self.op_flags,
+            OpCode::Send | OpCode::Recv | OpCode::SendMsg => {
+                inner.__bindgen_anon_3.msg_flags = self.op_flags
+            }
+            OpCode::Read | OpCode::Write | OpCode::Fsync => (),
+        }
 
         if let Some(addr) = self.addr {
             inner.__bindgen_anon_2.addr = addr as u64;
@@ -75,9 +75,12 @@ impl CompletionQueue {
         self.count
     }
 
+    /// Pops a completed entry. The `user_data` of an entry announcing more completions for the
+    /// same operation is kept in the slab, and a copy is returned through `keep`.
     pub(crate) fn pop<T: Debug>(
         &mut self,
         slab: &mut slab::Slab<T>,
+        keep: impl FnOnce(&T) -> Option<T>,
     ) -> Result<Option<Cqe<T>>, CQueueError> {
         let ring = self.cqes.as_volatile_slice();
         // get the head & tail
@@ -97,8 +100,13 @@ impl CompletionQueue {
             let res = cqe.res;
             #[allow(clippy::cast_possible_truncation)]
             let index = cqe.user_data as usize;
-            match slab.try_remove(index) {
-                Some(user_data) => Ok(Some(Cqe::new(res, user_data))),
+            let user_data = if cqe.flags & generated::IORING_CQE_F_MORE != 0 {
+                slab.get(index).and_then(keep)
+            } else {
+                slab.try_remove(index)
+            };
+            match user_data {
+                Some(user_data) => Ok(Some(Cqe::with_flags(res, cqe.flags, user_data))),
                 None => Err(CQueueError::SlabRemoveFailed),
             }
         } else {
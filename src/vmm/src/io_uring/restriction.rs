@@ -24,6 +24,12 @@ pub enum Restriction {
     AllowOpCode(OpCode),
     /// Only allow operations on pre-registered fds.
     RequireFixedFds,
+    /// Allow operations on pre-registered fds, along with operations on other fds. The kernel
+    /// only keeps the last restriction on the allowed flags, so this can't be combined with
+    /// `AllowIoDrain`.
+    AllowFixedFds,
+    /// Allow operations to be marked as drain barriers.
+    AllowIoDrain,
 }
 
 impl From<&Restriction> for io_uring_restriction {
@@ -48,6 +54,22 @@ impl From<&Restriction> for io_uring_restriction {
                 instance.__bindgen_anon_1.sqe_flags =
                     1 << io_uring_sqe_flags_bit::IOSQE_FIXED_FILE_BIT;
             }
+            AllowFixedFds => {
+                instance.opcode = u16::try_from(
+                    io_uring_register_restriction_op::IORING_RESTRICTION_SQE_FLAGS_ALLOWED,
+                )
+                .unwrap();
+                instance.__bindgen_anon_1.sqe_flags =
+                    1 << io_uring_sqe_flags_bit::IOSQE_FIXED_FILE_BIT;
+            }
+            AllowIoDrain => {
+                instance.opcode = u16::try_from(
+                    io_uring_register_restriction_op::IORING_RESTRICTION_SQE_FLAGS_ALLOWED,
+                )
+                .unwrap();
+                instance.__bindgen_anon_1.sqe_flags =
+                    1 << io_uring_sqe_flags_bit::IOSQE_IO_DRAIN_BIT;
+            }
         };
 
         instance
@@ -94,6 +94,8 @@ pub mod mmds;
 pub mod pci;
 /// Save/restore utilities.
 pub mod persist;
+/// Resources used by the microVM on the host.
+pub mod resource_usage;
 /// Resource store for configured microVM resources.
 pub mod resources;
 /// microVM RPC API adapters.
@@ -104,6 +106,8 @@ pub mod seccomp;
 pub mod signal_handler;
 /// Serialization and deserialization facilities
 pub mod snapshot;
+/// Channel through which a guest agent requests snapshots.
+pub mod snapshot_agent;
 /// Utility functions for integration and benchmark testing
 pub mod test_utils;
 /// Utility functions and struct
@@ -123,7 +127,11 @@ use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Barrier, Mutex};
 use std::time::Duration;
 
+#[cfg(target_arch = "x86_64")]
+use ::utils::time::{ClockType, TimerFd, get_time_us};
 use device_manager::DeviceManager;
+#[cfg(target_arch = "x86_64")]
+use device_manager::hotplug::HotplugError;
 use event_manager::{EventManager as BaseEventManager, EventOps, Events, MutEventSubscriber};
 use seccomp::BpfProgram;
 use snapshot::Persist;
@@ -134,6 +142,9 @@ use vmm_sys_util::terminal::Terminal;
 use vstate::kvm::Kvm;
 use vstate::vcpu::{self, StartThreadedError, VcpuSendEventError};
 
+use crate::cpu_config::features::host_features;
+#[cfg(target_arch = "x86_64")]
+use crate::cpu_config::features::{common_features, cpuid_features};
 use crate::cpu_config::templates::CpuConfiguration;
 use crate::devices::virtio::balloon::device::{HintingStatus, StartHintingCmd};
 use crate::devices::virtio::balloon::{
@@ -141,13 +152,31 @@ use crate::devices::virtio::balloon::{
 };
 use crate::devices::virtio::block::BlockError;
 use crate::devices::virtio::block::device::Block;
+use crate::devices::virtio::device::VirtioDeviceType;
 use crate::devices::virtio::mem::{VIRTIO_MEM_DEV_ID, VirtioMem, VirtioMemError, VirtioMemStatus};
 use crate::devices::virtio::net::Net;
-use crate::logger::{METRICS, MetricsError, error, info, warn};
-use crate::persist::{MicrovmState, MicrovmStateError, VmInfo};
+use crate::devices::virtio::net::flows::{FlowSamplingError, NetworkFlows};
+use crate::devices::virtio::rdma::backend::thread::BackendLauncher;
+use crate::devices::virtio::rdma::backend::{RdmaBackendConfig, RdmaBackendError};
+use crate::devices::virtio::rdma::device::{RdmaCqModeration, RdmaDeviceStatus};
+use crate::devices::virtio::rdma::{RdmaError, VirtioRdma};
+use crate::devices::virtio::rng::Entropy;
+use crate::devices::virtio::rng::device::ENTROPY_DEV_ID;
+use crate::devices::virtio::vsock::{VSOCK_DEV_ID, Vsock, VsockConnectionInfo, VsockUnixBackend};
+use crate::logger::{IncMetric, METRICS, MetricsError, error, info, warn};
+use crate::persist::{MicrovmState, MicrovmStateError, VmInfo, create_snapshot};
 use crate::rate_limiter::BucketUpdate;
+use crate::resource_usage::{DeviceIoUsage, ResourceUsage};
+use crate::snapshot_agent::SnapshotAgent;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::device_removal::DeviceRemovalConfig;
 use crate::vmm_config::instance_info::{InstanceInfo, VmState};
-use crate::vstate::memory::{GuestMemory, GuestMemoryMmap, GuestMemoryRegion};
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::shutdown::{GracefulShutdownConfig, GracefulShutdownOutcome, ShutdownPath};
+use crate::vstate::cold_memory::ColdMemory;
+use crate::vstate::memory::{
+    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion, MemoryWriteback,
+};
 use crate::vstate::vcpu::VcpuState;
 pub use crate::vstate::vcpu::{Vcpu, VcpuConfig, VcpuEvent, VcpuHandle, VcpuResponse};
 pub use crate::vstate::vm::Vm;
@@ -187,6 +216,8 @@ pub enum FcExitCode {
     BadConfiguration = 152,
     /// Command line arguments parsing error.
     ArgParsing = 153,
+    /// Firecracker was force-stopped after the guest did not shut down in time.
+    ForcedShutdown = 158,
 }
 
 /// Timeout used in recv_timeout, when waiting for a vcpu response on
@@ -214,6 +245,24 @@ pub enum VmmError {
     /// I8042 error: {0}
     I8042Error(devices::legacy::I8042DeviceError),
     #[cfg(target_arch = "x86_64")]
+    /// Cannot press the power button: {0}
+    PowerButton(io::Error),
+    #[cfg(target_arch = "x86_64")]
+    /// The ACPI power button is not enabled in the machine configuration.
+    NoPowerButton,
+    #[cfg(target_arch = "x86_64")]
+    /// A graceful shutdown is already in progress.
+    ShutdownInProgress,
+    #[cfg(target_arch = "x86_64")]
+    /// A paused microVM cannot shut down gracefully.
+    ShutdownWhilePaused,
+    #[cfg(target_arch = "x86_64")]
+    /// Cannot remove the device: {0}
+    DeviceRemoval(#[from] HotplugError),
+    #[cfg(target_arch = "x86_64")]
+    /// Cannot add the device: {0}
+    DeviceInsertion(HotplugError),
+    #[cfg(target_arch = "x86_64")]
     /// Cannot add devices to the legacy I/O Bus. {0}
     LegacyIOBus(device_manager::legacy::LegacyDeviceError),
     /// Metrics error: {0}
@@ -252,6 +301,20 @@ pub enum VmmError {
     Block(#[from] BlockError),
     /// Balloon: {0}
     Balloon(#[from] BalloonError),
+    /// Cannot update the link state of the net device: {0}
+    NetLinkState(devices::DeviceError),
+    /// Cannot get the flows of the net device: {0}
+    NetworkFlows(#[from] FlowSamplingError),
+    /// Cannot get the resource usage of the microVM: {0}
+    ResourceUsage(io::Error),
+    /// Cannot update the completion interrupt moderation of the rdma device: {0}
+    RdmaCqModeration(RdmaError),
+    /// Cannot replace the backend of the rdma device: {0}
+    RdmaBackend(RdmaBackendError),
+    /// Cannot set up the added rdma device: {0}
+    RdmaInsertion(RdmaError),
+    /// Cannot add an rdma device without seccomp filters for the rdma thread categories
+    MissingRdmaSeccompFilters,
     /// Failed to create memory hotplug device: {0}
     VirtioMem(#[from] VirtioMemError),
 }
@@ -305,10 +368,29 @@ pub struct Vmm {
     // Save UFFD in order to keep it open in the Firecracker process, as well.
     #[allow(unused)]
     uffd: Option<Uffd>,
+    // Compressed backing store for cold guest memory.
+    cold_memory: Option<ColdMemory>,
+    // Writeback control of file-backed guest memory.
+    memory_writeback: Option<MemoryWriteback>,
+    // Channel through which a guest agent requests snapshots.
+    snapshot_agent: Option<SnapshotAgent>,
+    // Creates the threads of the backends of the rdma devices added to the running microVM, if
+    // the filters have one for the rdma threads.
+    #[cfg_attr(target_arch = "aarch64", allow(unused))]
+    rdma_launcher: Option<Arc<BackendLauncher>>,
     /// Handles to the vcpu threads with vcpu_fds inside them.
     pub vcpus_handles: Vec<VcpuHandle>,
     // Used by Vcpus and devices to initiate teardown; Vmm should never write here.
     vcpus_exit_evt: EventFd,
+    // Bounds the time left to the guest to shut down after a graceful shutdown request.
+    #[cfg(target_arch = "x86_64")]
+    shutdown_timer: TimerFd,
+    // Start of the pending graceful shutdown, in us.
+    #[cfg(target_arch = "x86_64")]
+    shutdown_start_us: Option<u64>,
+    // Outcome of the graceful shutdown, once it ended.
+    #[cfg(target_arch = "x86_64")]
+    shutdown_outcome: Option<GracefulShutdownOutcome>,
     // Device manager
     device_manager: DeviceManager,
 }
@@ -418,9 +500,99 @@ impl Vmm {
         }
 
         self.instance_info.state = VmState::Paused;
+
+        if self
+            .memory_writeback
+            .as_ref()
+            .is_some_and(|writeback| writeback.flush_on_pause)
+        {
+            METRICS.memory_backing.flushes.inc();
+            if let Err(err) = self.vm.guest_memory().flush() {
+                METRICS.memory_backing.flush_fails.inc();
+                error!("Failed to flush guest memory to its backing file: {}", err);
+            }
+        }
         Ok(())
     }
 
+    /// Starts the periodic writeback of file-backed guest memory.
+    fn writeback_guest_memory(&mut self) {
+        if let Some(timer) = self
+            .memory_writeback
+            .as_mut()
+            .and_then(|writeback| writeback.timer.as_mut())
+        {
+            timer.read();
+        }
+        METRICS.memory_backing.writebacks.inc();
+        if let Err(err) = self.vm.guest_memory().start_writeback() {
+            METRICS.memory_backing.writeback_fails.inc();
+            error!("Failed to start the writeback of guest memory: {}", err);
+        }
+    }
+
+    /// Evicts cold guest memory to its compressed backing store, pausing the vCPUs meanwhile.
+    fn evict_cold_memory(&mut self) {
+        let running = match self.instance_info.state {
+            VmState::Running => true,
+            VmState::Paused => false,
+            VmState::NotStarted => return,
+        };
+
+        if running && let Err(err) = self.pause_vm() {
+            error!("Failed to pause the vCPUs to evict cold memory: {}", err);
+            return;
+        }
+        if let Some(cold_memory) = self.cold_memory.as_mut() {
+            match cold_memory.evict(self.vm.guest_memory()) {
+                Ok(evicted) => info!("Evicted {} cold guest pages", evicted),
+                Err(err) => error!("Failed to evict cold guest memory: {}", err),
+            }
+        }
+        if running && let Err(err) = self.resume_vm() {
+            error!(
+                "Failed to resume the vCPUs after evicting cold memory: {}",
+                err
+            );
+        }
+    }
+
+    /// Writes the snapshot requested by the guest agent, once it froze the guest filesystems,
+    /// pausing the vCPUs meanwhile.
+    fn create_agent_snapshot(&mut self) {
+        let Some(mut agent) = self.snapshot_agent.take() else {
+            return;
+        };
+        let running = self.instance_info.state == VmState::Running;
+
+        let success = if running && let Err(err) = self.pause_vm() {
+            error!("Failed to pause the vCPUs to create the snapshot: {}", err);
+            false
+        } else {
+            match create_snapshot(self, agent.vm_info(), &agent.create_params()) {
+                Ok(()) => {
+                    info!("Created the snapshot requested by the guest agent.");
+                    true
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to create the snapshot requested by the guest agent: {}",
+                        err
+                    );
+                    false
+                }
+            }
+        };
+        if running && let Err(err) = self.resume_vm() {
+            error!(
+                "Failed to resume the vCPUs after creating the snapshot: {}",
+                err
+            );
+        }
+        agent.complete(success);
+        self.snapshot_agent = Some(agent);
+    }
+
     /// Injects CTRL+ALT+DEL keystroke combo in the i8042 device.
     #[cfg(target_arch = "x86_64")]
     pub fn send_ctrl_alt_del(&mut self) -> Result<(), VmmError> {
@@ -433,6 +605,112 @@ impl Vmm {
             .map_err(VmmError::I8042Error)
     }
 
+    /// Presses the ACPI power button, asking the guest to shut down. The microVM is force-stopped
+    /// if the guest did not shut down when the timeout expires.
+    #[cfg(target_arch = "x86_64")]
+    pub fn graceful_shutdown(&mut self, config: &GracefulShutdownConfig) -> Result<(), VmmError> {
+        let Some(power_button) = &self.device_manager.acpi_devices.power_button else {
+            return Err(VmmError::NoPowerButton);
+        };
+        if self.shutdown_start_us.is_some() {
+            return Err(VmmError::ShutdownInProgress);
+        }
+        // The timer of the shutdown is not served while the microVM is paused.
+        if self.instance_info.state == VmState::Paused {
+            return Err(VmmError::ShutdownWhilePaused);
+        }
+        power_button.press().map_err(VmmError::PowerButton)?;
+        self.shutdown_timer.arm(config.timeout(), None);
+        self.shutdown_start_us = Some(get_time_us(ClockType::Monotonic));
+        info!(
+            "Graceful shutdown requested, the guest has {} ms to shut down.",
+            config.timeout_ms
+        );
+        Ok(())
+    }
+
+    /// Returns the outcome of the graceful shutdown of the microVM, once it ended.
+    #[cfg(target_arch = "x86_64")]
+    pub fn graceful_shutdown_outcome(&self) -> Option<GracefulShutdownOutcome> {
+        self.shutdown_outcome
+    }
+
+    // Records the outcome of the pending graceful shutdown, if any.
+    #[cfg(target_arch = "x86_64")]
+    fn end_shutdown(&mut self, path: ShutdownPath) -> Option<GracefulShutdownOutcome> {
+        let start_us = self.shutdown_start_us?;
+        let outcome = GracefulShutdownOutcome {
+            path,
+            elapsed_ms: (get_time_us(ClockType::Monotonic) - start_us) / 1000,
+        };
+        self.shutdown_outcome = Some(outcome);
+        Some(outcome)
+    }
+
+    // Force-stops the microVM once the guest ran out of time to shut down.
+    #[cfg(target_arch = "x86_64")]
+    fn force_shutdown(&mut self) {
+        self.shutdown_timer.read();
+        warn!("The guest did not shut down in time, force-stopping the microVM.");
+        METRICS.vmm.forced_shutdown_count.inc();
+        self.end_shutdown(ShutdownPath::Forced);
+        self.stop(FcExitCode::ForcedShutdown);
+    }
+
+    /// Asks the guest to eject a PCI device, and removes the device once the guest released it,
+    /// or when the timeout expires.
+    #[cfg(target_arch = "x86_64")]
+    pub fn remove_device(&mut self, config: &DeviceRemovalConfig) -> Result<(), VmmError> {
+        self.device_manager.remove_device(&self.vm, config)?;
+        Ok(())
+    }
+
+    /// Returns the thread creating the threads of the rdma backends set up while the microVM
+    /// runs, such as the ones of the devices added to it.
+    #[cfg(target_arch = "x86_64")]
+    pub fn rdma_launcher(&self) -> Result<Arc<BackendLauncher>, VmmError> {
+        // The launcher is only spawned when devices can be added to the microVM.
+        if !self.device_manager.supports_hotplug() {
+            return Err(VmmError::DeviceInsertion(HotplugError::Unsupported));
+        }
+        self.rdma_launcher
+            .clone()
+            .ok_or(VmmError::MissingRdmaSeccompFilters)
+    }
+
+    /// Adds an rdma device to the running microVM, and asks the guest to check its PCI slot.
+    #[cfg(target_arch = "x86_64")]
+    pub fn insert_rdma_device(&mut self, device: Arc<Mutex<VirtioRdma>>) -> Result<(), VmmError> {
+        let id = {
+            let mut locked_dev = device.lock().expect("Poisoned lock");
+            let launcher = self.rdma_launcher()?;
+            locked_dev
+                .set_backend_launcher(launcher)
+                .map_err(VmmError::RdmaBackend)?;
+            let vcpu_count = u8::try_from(self.vcpus_handles.len()).unwrap();
+            locked_dev
+                .set_vcpu_count(vcpu_count)
+                .map_err(VmmError::RdmaInsertion)?;
+            let listener = self
+                .vm
+                .memory_removals()
+                .subscribe()
+                .map_err(|err| VmmError::RdmaInsertion(err.into()))?;
+            locked_dev.set_memory_removal_listener(listener);
+            locked_dev.id().to_string()
+        };
+        self.device_manager
+            .insert_device(&self.vm, id, device)
+            .map_err(VmmError::DeviceInsertion)
+    }
+
+    /// Takes the devices added to the running microVM since the last call, which the event
+    /// manager doesn't dispatch events to yet.
+    #[cfg(target_arch = "x86_64")]
+    pub fn take_pending_subscribers(&mut self) -> Vec<Arc<Mutex<dyn MutEventSubscriber>>> {
+        self.device_manager.take_pending_subscribers()
+    }
+
     /// Saves the state of a paused Microvm.
     pub fn save_state(&mut self, vm_info: &VmInfo) -> Result<MicrovmState, MicrovmStateError> {
         use self::MicrovmStateError::SaveVmState;
@@ -457,15 +735,56 @@ impl Vmm {
             }
         };
 
+        // The features the guest observed are the ones the host restoring it must support,
+        // leaving out the ones KVM doesn't report, which Firecracker emulates.
+        #[cfg(target_arch = "x86_64")]
+        let cpu_features = vcpu_states.first().map_or_else(Vec::new, |vcpu_state| {
+            common_features(
+                &cpuid_features(&vcpu_state.cpuid),
+                &host_features(&self.kvm),
+            )
+        });
+        #[cfg(target_arch = "aarch64")]
+        let cpu_features = host_features(&self.kvm);
+
         Ok(MicrovmState {
             vm_info: vm_info.clone(),
             kvm_state,
             vm_state,
             vcpu_states,
             device_states,
+            cpu_features,
+            integrity: Default::default(),
         })
     }
 
+    /// Completes the pending I/O of the paused vCPUs, so that they all stop at an instruction
+    /// boundary.
+    pub fn synchronize_vcpus(&mut self) -> Result<(), MicrovmStateError> {
+        for handle in self.vcpus_handles.iter_mut() {
+            handle
+                .send_event(VcpuEvent::Synchronize)
+                .map_err(MicrovmStateError::SignalVcpu)?;
+        }
+
+        // Every vCPU is waited for before any error is reported.
+        let vcpu_responses = self
+            .vcpus_handles
+            .iter()
+            .map(|handle| handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC))
+            .collect::<Result<Vec<VcpuResponse>, RecvTimeoutError>>()
+            .map_err(|_| MicrovmStateError::UnexpectedVcpuResponse)?;
+
+        vcpu_responses
+            .into_iter()
+            .try_for_each(|response| match response {
+                VcpuResponse::Synchronized => Ok(()),
+                VcpuResponse::Error(err) => Err(MicrovmStateError::SynchronizeVcpu(err)),
+                VcpuResponse::NotAllowed(reason) => Err(MicrovmStateError::NotAllowed(reason)),
+                _ => Err(MicrovmStateError::UnexpectedVcpuResponse),
+            })
+    }
+
     fn save_vcpu_states(&mut self) -> Result<Vec<VcpuState>, MicrovmStateError> {
         for handle in self.vcpus_handles.iter_mut() {
             handle
@@ -573,6 +892,143 @@ impl Vmm {
         Ok(())
     }
 
+    /// Updates the rate limiter parameters for the entropy device.
+    pub fn update_entropy_rate_limiter(
+        &mut self,
+        rl_bytes: BucketUpdate,
+        rl_ops: BucketUpdate,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(ENTROPY_DEV_ID, |entropy: &mut Entropy| {
+                entropy.patch_rate_limiter(rl_bytes, rl_ops)
+            })?;
+        Ok(())
+    }
+
+    /// Reports the link of the net device with `net_id` id as up or down to the guest.
+    pub fn set_net_link_state(&mut self, net_id: &str, up: bool) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(net_id, |net: &mut Net| net.set_link_state(up))?
+            .map_err(VmmError::NetLinkState)
+    }
+
+    /// Sets the completion interrupt moderation of the rdma device with `rdma_id` id.
+    pub fn set_rdma_cq_moderation(
+        &mut self,
+        rdma_id: &str,
+        moderation: Option<RdmaCqModeration>,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(rdma_id, |rdma: &mut VirtioRdma| {
+                rdma.set_cq_moderation(moderation)
+            })?
+            .map_err(VmmError::RdmaCqModeration)
+    }
+
+    /// Replaces the backend of the rdma device with `rdma_id` id, without resetting it.
+    pub fn set_rdma_backend(
+        &mut self,
+        rdma_id: &str,
+        config: RdmaBackendConfig,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(rdma_id, |rdma: &mut VirtioRdma| {
+                rdma.set_backend(Some(config))
+            })?
+            .map_err(VmmError::RdmaBackend)
+    }
+
+    /// Updates the rate limiter parameters for the rdma device with `rdma_id` id.
+    pub fn update_rdma_rate_limiter(
+        &mut self,
+        rdma_id: &str,
+        rl_bytes: BucketUpdate,
+        rl_ops: BucketUpdate,
+    ) -> Result<(), VmmError> {
+        self.device_manager
+            .with_virtio_device(rdma_id, |rdma: &mut VirtioRdma| {
+                rdma.update_rate_limiter(rl_bytes, rl_ops)
+            })?;
+        Ok(())
+    }
+
+    /// Returns the flows sampled on the net device with `net_id` id.
+    pub fn network_flows(&self, net_id: &str) -> Result<NetworkFlows, VmmError> {
+        let flows = self
+            .device_manager
+            .with_virtio_device(net_id, |net: &mut Net| net.network_flows())??;
+        Ok(flows)
+    }
+
+    /// Returns the status of the rdma device with `rdma_id` id, and of its backend.
+    pub fn rdma_device_status(&self, rdma_id: &str) -> Result<RdmaDeviceStatus, VmmError> {
+        let status = self
+            .device_manager
+            .with_virtio_device(rdma_id, |rdma: &mut VirtioRdma| rdma.status())?;
+        Ok(status)
+    }
+
+    /// Returns the live connections of the vsock device.
+    pub fn vsock_connections(&self) -> Result<Vec<VsockConnectionInfo>, VmmError> {
+        let connections = self
+            .device_manager
+            .with_virtio_device(VSOCK_DEV_ID, |vsock: &mut Vsock<VsockUnixBackend>| {
+                vsock.backend().connections()
+            })?;
+        Ok(connections)
+    }
+
+    /// Returns the resources used by the microVM on the host.
+    pub fn resource_usage(&self) -> Result<ResourceUsage, VmmError> {
+        let mut devices = Vec::new();
+        for id in self
+            .device_manager
+            .virtio_device_ids(VirtioDeviceType::Block)
+        {
+            // vhost-user block devices do their I/O outside of the VMM.
+            let usage = self
+                .device_manager
+                .with_virtio_device(&id, |block: &mut Block| {
+                    let Block::Virtio(block) = block else {
+                        return None;
+                    };
+                    Some(DeviceIoUsage {
+                        id: id.clone(),
+                        device_type: "block".to_string(),
+                        read_bytes: block.metrics.read_bytes.count(),
+                        write_bytes: block.metrics.write_bytes.count(),
+                        read_ops: block.metrics.read_count.count(),
+                        write_ops: block.metrics.write_count.count(),
+                    })
+                })?;
+            devices.extend(usage);
+        }
+        for id in self.device_manager.virtio_device_ids(VirtioDeviceType::Net) {
+            let usage = self
+                .device_manager
+                .with_virtio_device(&id, |net: &mut Net| DeviceIoUsage {
+                    id: id.clone(),
+                    device_type: "net".to_string(),
+                    read_bytes: net.metrics.rx_bytes_count.count(),
+                    write_bytes: net.metrics.tx_bytes_count.count(),
+                    read_ops: net.metrics.rx_packets_count.count(),
+                    write_ops: net.metrics.tx_packets_count.count(),
+                })?;
+            devices.push(usage);
+        }
+
+        Ok(ResourceUsage {
+            vmm_rss_bytes: resource_usage::vmm_rss_bytes().map_err(VmmError::ResourceUsage)?,
+            guest_memory_resident_bytes: resource_usage::guest_memory_resident_bytes(
+                self.vm.guest_memory(),
+            )
+            .map_err(VmmError::ResourceUsage)?,
+            open_fds: resource_usage::open_fds().map_err(VmmError::ResourceUsage)?,
+            vcpus: resource_usage::vcpu_usage().map_err(VmmError::ResourceUsage)?,
+            devices,
+        })
+    }
+
     /// Returns a reference to the balloon device if present.
     pub fn balloon_config(&self) -> Result<BalloonConfig, VmmError> {
         let config = self
@@ -720,10 +1176,21 @@ impl Drop for Vmm {
 
 impl MutEventSubscriber for Vmm {
     /// Handle a read event (EPOLLIN).
-    fn process(&mut self, event: Events, _: &mut EventOps) {
+    fn process(&mut self, event: Events, ops: &mut EventOps) {
         let source = event.fd();
         let event_set = event.event_set();
 
+        #[cfg(target_arch = "x86_64")]
+        if source == self.shutdown_timer.as_raw_fd() && event_set == EventSet::IN {
+            self.force_shutdown();
+            return;
+        }
+        #[cfg(target_arch = "x86_64")]
+        if event_set == EventSet::IN && self.device_manager.process_hotplug_event(&self.vm, source)
+        {
+            return;
+        }
+
         if source == self.vcpus_exit_evt.as_raw_fd() && event_set == EventSet::IN {
             // Exit event handling should never do anything more than call 'self.stop()'.
             let _ = self.vcpus_exit_evt.read();
@@ -748,7 +1215,34 @@ impl MutEventSubscriber for Vmm {
                 // No CPUs exited with error status code, report "Ok"
                 FcExitCode::Ok
             };
+            #[cfg(target_arch = "x86_64")]
+            if let Some(outcome) = self.end_shutdown(ShutdownPath::Graceful) {
+                METRICS.vmm.graceful_shutdown_count.inc();
+                info!(
+                    "The guest shut down gracefully in {} ms.",
+                    outcome.elapsed_ms
+                );
+            }
             self.stop(exit_code);
+        } else if let Some(cold_memory) = &self.cold_memory
+            && source == cold_memory.pressure_trigger().as_raw_fd()
+            && event_set.contains(EventSet::PRIORITY)
+        {
+            self.evict_cold_memory();
+        } else if let Some(timer) = self
+            .memory_writeback
+            .as_ref()
+            .and_then(|writeback| writeback.timer.as_ref())
+            && source == timer.as_raw_fd()
+            && event_set == EventSet::IN
+        {
+            self.writeback_guest_memory();
+        } else if let Some(agent) = self.snapshot_agent.as_mut()
+            && agent.owns(source)
+        {
+            if agent.process(source, ops) {
+                self.create_agent_snapshot();
+            }
         } else {
             error!("Spurious EventManager event for handler: Vmm");
         }
@@ -758,5 +1252,30 @@ impl MutEventSubscriber for Vmm {
         if let Err(err) = ops.add(Events::new(&self.vcpus_exit_evt, EventSet::IN)) {
             error!("Failed to register vmm exit event: {}", err);
         }
+        if let Some(cold_memory) = &self.cold_memory
+            && let Err(err) = ops.add(Events::new(
+                cold_memory.pressure_trigger(),
+                EventSet::PRIORITY,
+            ))
+        {
+            error!("Failed to register memory pressure event: {}", err);
+        }
+        if let Some(timer) = self
+            .memory_writeback
+            .as_ref()
+            .and_then(|writeback| writeback.timer.as_ref())
+            && let Err(err) = ops.add(Events::new(timer, EventSet::IN))
+        {
+            error!("Failed to register guest memory writeback timer: {}", err);
+        }
+        if let Some(agent) = &self.snapshot_agent {
+            agent.register(ops);
+        }
+        #[cfg(target_arch = "x86_64")]
+        if let Err(err) = ops.add(Events::new(&self.shutdown_timer, EventSet::IN)) {
+            error!("Failed to register graceful shutdown timer: {}", err);
+        }
+        #[cfg(target_arch = "x86_64")]
+        self.device_manager.register_hotplug_events(ops);
     }
 }
@@ -74,9 +74,13 @@ use super::FcLineWriter;
 use crate::devices::legacy;
 use crate::devices::virtio::balloon::metrics as balloon_metrics;
 use crate::devices::virtio::block::virtio::metrics as block_metrics;
+use crate::devices::virtio::gpio::metrics as gpio_metrics;
+use crate::devices::virtio::i2c::metrics as i2c_metrics;
 use crate::devices::virtio::mem::metrics as virtio_mem_metrics;
 use crate::devices::virtio::net::metrics as net_metrics;
+use crate::devices::virtio::p9::metrics as p9_metrics;
 use crate::devices::virtio::pmem::metrics as pmem_metrics;
+use crate::devices::virtio::rdma::metrics as rdma_metrics;
 use crate::devices::virtio::rng::metrics as entropy_metrics;
 use crate::devices::virtio::vhost_user_metrics;
 use crate::devices::virtio::vsock::metrics as vsock_metrics;
@@ -363,6 +367,16 @@ pub struct GetRequestsMetrics {
     pub vmm_version_count: SharedIncMetric,
     /// Number of GETs for getting hotpluggable memory status.
     pub hotplug_memory_count: SharedIncMetric,
+    /// Number of GETs for listing the vsock connections.
+    pub vsock_connections_count: SharedIncMetric,
+    /// Number of GETs for listing the sampled flows of a network interface.
+    pub network_flows_count: SharedIncMetric,
+    /// Number of GETs for dumping the guest CPU configuration.
+    pub cpu_cfg_count: SharedIncMetric,
+    /// Number of GETs for getting the resource usage of the microVM.
+    pub resource_usage_count: SharedIncMetric,
+    /// Number of GETs for getting the status of an rdma device.
+    pub rdma_status_count: SharedIncMetric,
 }
 impl GetRequestsMetrics {
     /// Const default construction.
@@ -373,6 +387,11 @@ impl GetRequestsMetrics {
             mmds_count: SharedIncMetric::new(),
             vmm_version_count: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
+            vsock_connections_count: SharedIncMetric::new(),
+            network_flows_count: SharedIncMetric::new(),
+            cpu_cfg_count: SharedIncMetric::new(),
+            resource_usage_count: SharedIncMetric::new(),
+            rdma_status_count: SharedIncMetric::new(),
         }
     }
 }
@@ -388,10 +407,26 @@ pub struct PutRequestsMetrics {
     pub boot_source_count: SharedIncMetric,
     /// Number of failures during attaching source of boot.
     pub boot_source_fails: SharedIncMetric,
+    /// Number of PUTs for configuring the firmware.
+    pub firmware_count: SharedIncMetric,
+    /// Number of failures during configuring the firmware.
+    pub firmware_fails: SharedIncMetric,
+    /// Number of PUTs for configuring the fw_cfg device.
+    pub fw_cfg_count: SharedIncMetric,
+    /// Number of failures during configuring the fw_cfg device.
+    pub fw_cfg_fails: SharedIncMetric,
+    /// Number of PUTs for configuring the SMBIOS tables.
+    pub smbios_count: SharedIncMetric,
+    /// Number of failures during configuring the SMBIOS tables.
+    pub smbios_fails: SharedIncMetric,
     /// Number of PUTs triggering a block attach.
     pub drive_count: SharedIncMetric,
     /// Number of failures in attaching a block device.
     pub drive_fails: SharedIncMetric,
+    /// Number of PUTs for attaching the cloud-init configuration drive.
+    pub config_drive_count: SharedIncMetric,
+    /// Number of failures in attaching the cloud-init configuration drive.
+    pub config_drive_fails: SharedIncMetric,
     /// Number of PUTs for initializing the logging system.
     pub logger_count: SharedIncMetric,
     /// Number of failures in initializing the logging system.
@@ -428,14 +463,50 @@ pub struct PutRequestsMetrics {
     pub rdma_count: SharedIncMetric,
     /// Number of failures in attaching an RDMA device.
     pub rdma_fails: SharedIncMetric,
+    /// Number of PUTs triggering a 9p attach.
+    pub p9_count: SharedIncMetric,
+    /// Number of failures in attaching a 9p device.
+    pub p9_fails: SharedIncMetric,
+    /// Number of PUTs triggering a gpio attach.
+    pub gpio_count: SharedIncMetric,
+    /// Number of failures in attaching a gpio device.
+    pub gpio_fails: SharedIncMetric,
+    /// Number of PUTs triggering an i2c attach.
+    pub i2c_count: SharedIncMetric,
+    /// Number of failures in attaching an i2c device.
+    pub i2c_fails: SharedIncMetric,
     /// Number of PUTs to /serial
     pub serial_count: SharedIncMetric,
     /// Number of failed PUTs to /serial
     pub serial_fails: SharedIncMetric,
+    /// Number of PUTs to /cold-memory
+    pub cold_memory_count: SharedIncMetric,
+    /// Number of failed PUTs to /cold-memory
+    pub cold_memory_fails: SharedIncMetric,
+    /// Number of PUTs to /io-cpu-budget
+    pub io_cpu_budget_count: SharedIncMetric,
+    /// Number of failed PUTs to /io-cpu-budget
+    pub io_cpu_budget_fails: SharedIncMetric,
+    /// Number of PUTs to /interrupt-affinity
+    pub interrupt_affinity_count: SharedIncMetric,
+    /// Number of failed PUTs to /interrupt-affinity
+    pub interrupt_affinity_fails: SharedIncMetric,
+    /// Number of PUTs to /memory-backing
+    pub memory_backing_count: SharedIncMetric,
+    /// Number of failed PUTs to /memory-backing
+    pub memory_backing_fails: SharedIncMetric,
+    /// Number of PUTs to /snapshot/agent
+    pub snapshot_agent_count: SharedIncMetric,
+    /// Number of failed PUTs to /snapshot/agent
+    pub snapshot_agent_fails: SharedIncMetric,
     /// Number of PUTs to /hotplug/memory
     pub hotplug_memory_count: SharedIncMetric,
     /// Number of failed PUTs to /hotplug/memory
     pub hotplug_memory_fails: SharedIncMetric,
+    /// Number of PUTs to /hotplug/remove
+    pub hotplug_remove_count: SharedIncMetric,
+    /// Number of failed PUTs to /hotplug/remove
+    pub hotplug_remove_fails: SharedIncMetric,
 }
 impl PutRequestsMetrics {
     /// Const default construction.
@@ -445,8 +516,16 @@ impl PutRequestsMetrics {
             actions_fails: SharedIncMetric::new(),
             boot_source_count: SharedIncMetric::new(),
             boot_source_fails: SharedIncMetric::new(),
+            firmware_count: SharedIncMetric::new(),
+            firmware_fails: SharedIncMetric::new(),
+            fw_cfg_count: SharedIncMetric::new(),
+            fw_cfg_fails: SharedIncMetric::new(),
+            smbios_count: SharedIncMetric::new(),
+            smbios_fails: SharedIncMetric::new(),
             drive_count: SharedIncMetric::new(),
             drive_fails: SharedIncMetric::new(),
+            config_drive_count: SharedIncMetric::new(),
+            config_drive_fails: SharedIncMetric::new(),
             logger_count: SharedIncMetric::new(),
             logger_fails: SharedIncMetric::new(),
             machine_cfg_count: SharedIncMetric::new(),
@@ -465,10 +544,28 @@ impl PutRequestsMetrics {
             pmem_fails: SharedIncMetric::new(),
             rdma_count: SharedIncMetric::new(),
             rdma_fails: SharedIncMetric::new(),
+            p9_count: SharedIncMetric::new(),
+            p9_fails: SharedIncMetric::new(),
+            gpio_count: SharedIncMetric::new(),
+            gpio_fails: SharedIncMetric::new(),
+            i2c_count: SharedIncMetric::new(),
+            i2c_fails: SharedIncMetric::new(),
             serial_count: SharedIncMetric::new(),
             serial_fails: SharedIncMetric::new(),
+            cold_memory_count: SharedIncMetric::new(),
+            cold_memory_fails: SharedIncMetric::new(),
+            io_cpu_budget_count: SharedIncMetric::new(),
+            io_cpu_budget_fails: SharedIncMetric::new(),
+            interrupt_affinity_count: SharedIncMetric::new(),
+            interrupt_affinity_fails: SharedIncMetric::new(),
+            memory_backing_count: SharedIncMetric::new(),
+            memory_backing_fails: SharedIncMetric::new(),
+            snapshot_agent_count: SharedIncMetric::new(),
+            snapshot_agent_fails: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
             hotplug_memory_fails: SharedIncMetric::new(),
+            hotplug_remove_count: SharedIncMetric::new(),
+            hotplug_remove_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -496,6 +593,10 @@ pub struct PatchRequestsMetrics {
     pub hotplug_memory_count: SharedIncMetric,
     /// Number of failed PATCHes to /hotplug/memory
     pub hotplug_memory_fails: SharedIncMetric,
+    /// Number of tries to PATCH an rdma device.
+    pub rdma_count: SharedIncMetric,
+    /// Number of failures in PATCHing an rdma device.
+    pub rdma_fails: SharedIncMetric,
 }
 impl PatchRequestsMetrics {
     /// Const default construction.
@@ -511,6 +612,8 @@ impl PatchRequestsMetrics {
             mmds_fails: SharedIncMetric::new(),
             hotplug_memory_count: SharedIncMetric::new(),
             hotplug_memory_fails: SharedIncMetric::new(),
+            rdma_count: SharedIncMetric::new(),
+            rdma_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -566,6 +669,8 @@ pub struct MmdsMetrics {
     pub rx_invalid_token: SharedIncMetric,
     /// The number of GET requests with no tokens.
     pub rx_no_token: SharedIncMetric,
+    /// The number of frames dropped because they exceeded the MMDS rate limit.
+    pub rx_throttled: SharedIncMetric,
     /// The total number of successful receive operations by the MMDS.
     pub rx_count: SharedIncMetric,
     /// The total number of bytes sent by the MMDS.
@@ -591,6 +696,7 @@ impl MmdsMetrics {
             rx_bad_eth: SharedIncMetric::new(),
             rx_invalid_token: SharedIncMetric::new(),
             rx_no_token: SharedIncMetric::new(),
+            rx_throttled: SharedIncMetric::new(),
             rx_count: SharedIncMetric::new(),
             tx_bytes: SharedIncMetric::new(),
             tx_count: SharedIncMetric::new(),
@@ -724,16 +830,8 @@ impl Drop for LatencyMetricsRecorder<'_> {
     /// and updates min/max/sum metrics.
     ///  self.start_time is recorded in new() and metrics are updated in drop
     fn drop(&mut self) {
-        let delta_us = get_time_us(ClockType::Monotonic) - self.start_time;
-        self.metric.sum_us.add(delta_us);
-        let min_us = self.metric.min_us.fetch();
-        let max_us = self.metric.max_us.fetch();
-        if (0 == min_us) || (min_us > delta_us) {
-            self.metric.min_us.store(delta_us);
-        }
-        if (0 == max_us) || (max_us < delta_us) {
-            self.metric.max_us.store(delta_us);
-        }
+        self.metric
+            .record_latency_us(get_time_us(ClockType::Monotonic) - self.start_time);
     }
 }
 
@@ -766,6 +864,20 @@ impl LatencyAggregateMetrics {
     pub fn record_latency_metrics(&self) -> LatencyMetricsRecorder<'_> {
         LatencyMetricsRecorder::new(self)
     }
+
+    /// Updates the min/max/sum metrics with a latency measured by the caller, for operations
+    /// that don't complete within the scope of a recorder.
+    pub fn record_latency_us(&self, delta_us: u64) {
+        self.sum_us.add(delta_us);
+        let min_us = self.min_us.fetch();
+        let max_us = self.max_us.fetch();
+        if (0 == min_us) || (min_us > delta_us) {
+            self.min_us.store(delta_us);
+        }
+        if (0 == max_us) || (max_us < delta_us) {
+            self.max_us.store(delta_us);
+        }
+    }
 }
 
 /// Structure provides Metrics specific to VCPUs' mode of functioning.
@@ -839,17 +951,125 @@ impl InterruptMetrics {
     }
 }
 
+/// Metrics for the compressed backing store of cold guest memory.
+#[derive(Debug, Default, Serialize)]
+pub struct ColdMemoryMetrics {
+    /// Number of host memory pressure events handled.
+    pub pressure_events: SharedIncMetric,
+    /// Number of guest pages evicted to the backing store.
+    pub pages_evicted: SharedIncMetric,
+    /// Number of guest pages reloaded from the backing store.
+    pub pages_reloaded: SharedIncMetric,
+    /// Number of failures while evicting guest pages.
+    pub evict_fails: SharedIncMetric,
+    /// Number of bytes currently held in the backing store.
+    pub stored_bytes: SharedStoreMetric,
+}
+impl ColdMemoryMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            pressure_events: SharedIncMetric::new(),
+            pages_evicted: SharedIncMetric::new(),
+            pages_reloaded: SharedIncMetric::new(),
+            evict_fails: SharedIncMetric::new(),
+            stored_bytes: SharedStoreMetric::new(),
+        }
+    }
+}
+
+/// Metrics for the host CPU budget of the device I/O processing.
+#[derive(Debug, Default, Serialize)]
+pub struct IoCpuBudgetMetrics {
+    /// CPU time, in microseconds, charged to the budget by the devices.
+    pub charged_us: SharedIncMetric,
+    /// Number of times the budget got exhausted.
+    pub throttles: SharedIncMetric,
+    /// Number of device events deferred while the budget was exhausted.
+    pub deferred_events: SharedIncMetric,
+}
+impl IoCpuBudgetMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            charged_us: SharedIncMetric::new(),
+            throttles: SharedIncMetric::new(),
+            deferred_events: SharedIncMetric::new(),
+        }
+    }
+}
+
+/// Metrics for the writeback of file-backed guest memory.
+#[derive(Debug, Default, Serialize)]
+pub struct MemoryBackingMetrics {
+    /// Number of periodic writebacks started.
+    pub writebacks: SharedIncMetric,
+    /// Number of failures while starting a periodic writeback.
+    pub writeback_fails: SharedIncMetric,
+    /// Number of synchronous flushes of guest memory to the backing file.
+    pub flushes: SharedIncMetric,
+    /// Number of failed synchronous flushes.
+    pub flush_fails: SharedIncMetric,
+}
+impl MemoryBackingMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            writebacks: SharedIncMetric::new(),
+            writeback_fails: SharedIncMetric::new(),
+            flushes: SharedIncMetric::new(),
+            flush_fails: SharedIncMetric::new(),
+        }
+    }
+}
+
+/// Metrics for the guest-triggered snapshots.
+#[derive(Debug, Default, Serialize)]
+pub struct SnapshotAgentMetrics {
+    /// Number of snapshot requests received from the guest agent.
+    pub requests: SharedIncMetric,
+    /// Number of snapshots created on behalf of the guest agent.
+    pub snapshots: SharedIncMetric,
+    /// Number of failed guest-triggered snapshots.
+    pub snapshot_fails: SharedIncMetric,
+    /// Number of guest filesystem freezes which did not complete in time.
+    pub freeze_timeouts: SharedIncMetric,
+}
+impl SnapshotAgentMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            requests: SharedIncMetric::new(),
+            snapshots: SharedIncMetric::new(),
+            snapshot_fails: SharedIncMetric::new(),
+            freeze_timeouts: SharedIncMetric::new(),
+        }
+    }
+}
+
 /// Metrics specific to the machine manager as a whole.
 #[derive(Debug, Default, Serialize)]
 pub struct VmmMetrics {
     /// Metric for signaling a panic has occurred.
     pub panic_count: SharedStoreMetric,
+    /// Number of graceful shutdowns completed by the guest in time.
+    pub graceful_shutdown_count: SharedIncMetric,
+    /// Number of graceful shutdowns which timed out, force-stopping the microVM.
+    pub forced_shutdown_count: SharedIncMetric,
+    /// Number of devices removed after the guest ejected them.
+    pub device_eject_count: SharedIncMetric,
+    /// Number of devices removed without the guest releasing them.
+    pub device_surprise_removal_count: SharedIncMetric,
 }
 impl VmmMetrics {
     /// Const default construction.
     pub const fn new() -> Self {
         Self {
             panic_count: SharedStoreMetric::new(),
+            graceful_shutdown_count: SharedIncMetric::new(),
+            forced_shutdown_count: SharedIncMetric::new(),
+            device_eject_count: SharedIncMetric::new(),
+            device_surprise_removal_count: SharedIncMetric::new(),
         }
     }
 }
@@ -897,6 +1117,10 @@ create_serialize_proxy!(BalloonMetricsSerializeProxy, balloon_metrics);
 create_serialize_proxy!(EntropyMetricsSerializeProxy, entropy_metrics);
 create_serialize_proxy!(VsockMetricsSerializeProxy, vsock_metrics);
 create_serialize_proxy!(PmemMetricsSerializeProxy, pmem_metrics);
+create_serialize_proxy!(P9MetricsSerializeProxy, p9_metrics);
+create_serialize_proxy!(GpioMetricsSerializeProxy, gpio_metrics);
+create_serialize_proxy!(I2cMetricsSerializeProxy, i2c_metrics);
+create_serialize_proxy!(RdmaMetricsSerializeProxy, rdma_metrics);
 create_serialize_proxy!(LegacyDevMetricsSerializeProxy, legacy);
 create_serialize_proxy!(MemoryHotplugSerializeProxy, virtio_mem_metrics);
 
@@ -912,6 +1136,8 @@ pub struct FirecrackerMetrics {
     #[serde(flatten)]
     /// A block device's related metrics.
     pub block_ser: BlockMetricsSerializeProxy,
+    /// Metrics related to the compressed backing store of cold guest memory.
+    pub cold_memory: ColdMemoryMetrics,
     /// Metrics related to deprecated API calls.
     pub deprecated_api: DeprecatedApiMetrics,
     /// Metrics related to API GET requests.
@@ -919,10 +1145,14 @@ pub struct FirecrackerMetrics {
     #[serde(flatten)]
     /// Metrics related to the legacy device.
     pub legacy_dev_ser: LegacyDevMetricsSerializeProxy,
+    /// Metrics related to the host CPU budget of the device I/O processing.
+    pub io_cpu_budget: IoCpuBudgetMetrics,
     /// Metrics related to performance measurements.
     pub latencies_us: PerformanceMetrics,
     /// Logging related metrics.
     pub logger: LoggerSystemMetrics,
+    /// Metrics related to the writeback of file-backed guest memory.
+    pub memory_backing: MemoryBackingMetrics,
     /// Metrics specific to MMDS functionality.
     pub mmds: MmdsMetrics,
     #[serde(flatten)]
@@ -934,6 +1164,8 @@ pub struct FirecrackerMetrics {
     pub put_api_requests: PutRequestsMetrics,
     /// Metrics related to seccomp filtering.
     pub seccomp: SeccompMetrics,
+    /// Metrics related to the guest-triggered snapshots.
+    pub snapshot_agent: SnapshotAgentMetrics,
     /// Metrics related to a vcpu's functioning.
     pub vcpu: VcpuMetrics,
     /// Metrics related to the virtual machine manager.
@@ -950,6 +1182,18 @@ pub struct FirecrackerMetrics {
     /// Metrics related to virtio-pmem entropy device.
     pub pmem_ser: PmemMetricsSerializeProxy,
     #[serde(flatten)]
+    /// Metrics related to virtio-9p devices.
+    pub p9_ser: P9MetricsSerializeProxy,
+    #[serde(flatten)]
+    /// Metrics related to virtio-gpio devices.
+    pub gpio_ser: GpioMetricsSerializeProxy,
+    #[serde(flatten)]
+    /// Metrics related to virtio-i2c devices.
+    pub i2c_ser: I2cMetricsSerializeProxy,
+    #[serde(flatten)]
+    /// Metrics related to virtio-rdma devices.
+    pub rdma_ser: RdmaMetricsSerializeProxy,
+    #[serde(flatten)]
     /// Vhost-user device related metrics.
     pub vhost_user_ser: VhostUserMetricsSerializeProxy,
     /// Interrupt related metrics
@@ -966,22 +1210,30 @@ impl FirecrackerMetrics {
             api_server: ApiServerMetrics::new(),
             balloon_ser: BalloonMetricsSerializeProxy {},
             block_ser: BlockMetricsSerializeProxy {},
+            cold_memory: ColdMemoryMetrics::new(),
             deprecated_api: DeprecatedApiMetrics::new(),
             get_api_requests: GetRequestsMetrics::new(),
             legacy_dev_ser: LegacyDevMetricsSerializeProxy {},
+            io_cpu_budget: IoCpuBudgetMetrics::new(),
             latencies_us: PerformanceMetrics::new(),
             logger: LoggerSystemMetrics::new(),
+            memory_backing: MemoryBackingMetrics::new(),
             mmds: MmdsMetrics::new(),
             net_ser: NetMetricsSerializeProxy {},
             patch_api_requests: PatchRequestsMetrics::new(),
             put_api_requests: PutRequestsMetrics::new(),
             seccomp: SeccompMetrics::new(),
+            snapshot_agent: SnapshotAgentMetrics::new(),
             vcpu: VcpuMetrics::new(),
             vmm: VmmMetrics::new(),
             signals: SignalMetrics::new(),
             vsock_ser: VsockMetricsSerializeProxy {},
             entropy_ser: EntropyMetricsSerializeProxy {},
             pmem_ser: PmemMetricsSerializeProxy {},
+            p9_ser: P9MetricsSerializeProxy {},
+            gpio_ser: GpioMetricsSerializeProxy {},
+            i2c_ser: I2cMetricsSerializeProxy {},
+            rdma_ser: RdmaMetricsSerializeProxy {},
             vhost_user_ser: VhostUserMetricsSerializeProxy {},
             interrupts: InterruptMetrics::new(),
             memory_hotplug_ser: MemoryHotplugSerializeProxy {},
@@ -10,10 +10,15 @@ use serde_json::{Value, to_vec};
 use crate::mmds::token::{MmdsTokenError as TokenError, TokenAuthority};
 
 /// The Mmds is the Microvm Metadata Service represented as an untyped json.
+///
+/// The json is kept serialized and compressed, and is decompressed whenever it is accessed.
 #[derive(Debug)]
 pub struct Mmds {
     version: MmdsVersion,
-    data_store: Value,
+    // LZ4 compressed serialization of the data store.
+    data_store: Vec<u8>,
+    // Size of the serialized data store, bounding its decompression.
+    data_store_size: usize,
     token_authority: TokenAuthority,
     is_initialized: bool,
     data_store_limit: usize,
@@ -53,6 +58,10 @@ pub enum OutputFormat {
 pub enum MmdsDatastoreError {
     /// The MMDS patch request doesn't fit.
     DataStoreLimitExceeded,
+    /// The MMDS data store holds {0} bytes, more than the requested limit.
+    DataStoreLimitTooSmall(usize),
+    /// The MMDS data store is corrupted: {0}
+    Corrupted(String),
     /// The MMDS resource does not exist.
     NotFound,
     /// The MMDS data store is not initialized.
@@ -75,7 +84,8 @@ impl Mmds {
     pub fn try_new(data_store_limit: usize) -> Result<Self, MmdsDatastoreError> {
         Ok(Mmds {
             version: MmdsVersion::default(),
-            data_store: Value::default(),
+            data_store: Vec::new(),
+            data_store_size: 0,
             token_authority: TokenAuthority::try_new()?,
             is_initialized: false,
             data_store_limit,
@@ -131,45 +141,90 @@ impl Mmds {
     }
 
     /// set MMDS data store limit to `data_store_limit`
-    pub fn set_data_store_limit(&mut self, data_store_limit: usize) {
+    ///
+    /// The limit can't be lowered below the size of the data store.
+    pub fn set_data_store_limit(
+        &mut self,
+        data_store_limit: usize,
+    ) -> Result<(), MmdsDatastoreError> {
+        if self.data_store_size > data_store_limit {
+            return Err(MmdsDatastoreError::DataStoreLimitTooSmall(
+                self.data_store_size,
+            ));
+        }
         self.data_store_limit = data_store_limit;
+        Ok(())
     }
 
-    /// put `data` in MMDS data store
-    pub fn put_data(&mut self, data: Value) -> Result<(), MmdsDatastoreError> {
+    /// Get the MMDS data store limit.
+    pub fn data_store_limit(&self) -> usize {
+        self.data_store_limit
+    }
+
+    /// Get the size of the serialized MMDS data store, before compression.
+    pub fn data_store_size(&self) -> usize {
+        self.data_store_size
+    }
+
+    /// Get the size of the MMDS data store in memory, after compression.
+    pub fn compressed_size(&self) -> usize {
+        self.data_store.len()
+    }
+
+    // Serializes and compresses `data` as the new data store, if it fits in the limit.
+    fn store(&mut self, data: &Value) -> Result<(), MmdsDatastoreError> {
         // It is safe to unwrap because any map keys are all strings and
         // we are using default serializer which does not return error.
-        if to_vec(&data).unwrap().len() > self.data_store_limit {
-            Err(MmdsDatastoreError::DataStoreLimitExceeded)
-        } else {
-            self.data_store = data;
-            self.is_initialized = true;
+        let serialized = to_vec(data).unwrap();
+        if serialized.len() > self.data_store_limit {
+            return Err(MmdsDatastoreError::DataStoreLimitExceeded);
+        }
+        self.data_store = lz4_flex::block::compress(&serialized);
+        self.data_store_size = serialized.len();
+        Ok(())
+    }
 
-            Ok(())
+    // Decompresses and deserializes the data store.
+    fn load(&self) -> Result<Value, MmdsDatastoreError> {
+        if !self.is_initialized {
+            return Ok(Value::Null);
+        }
+        // The data store never decompresses to more than the size it was stored with, which
+        // is within the limit.
+        let mut serialized = vec![0u8; self.data_store_size];
+        let len = lz4_flex::block::decompress_into(&self.data_store, &mut serialized)
+            .map_err(|err| MmdsDatastoreError::Corrupted(err.to_string()))?;
+        if len != self.data_store_size {
+            return Err(MmdsDatastoreError::Corrupted(format!(
+                "decompressed {len} bytes instead of {}",
+                self.data_store_size
+            )));
         }
+        serde_json::from_slice(&serialized)
+            .map_err(|err| MmdsDatastoreError::Corrupted(err.to_string()))
+    }
+
+    /// put `data` in MMDS data store
+    pub fn put_data(&mut self, data: Value) -> Result<(), MmdsDatastoreError> {
+        self.store(&data)?;
+        self.is_initialized = true;
+        Ok(())
     }
 
     /// patch update MMDS data store with `patch_data`
     pub fn patch_data(&mut self, patch_data: Value) -> Result<(), MmdsDatastoreError> {
         self.check_data_store_initialized()?;
-        let mut data_store_clone = self.data_store.clone();
+        let mut data_store = self.load()?;
 
-        super::json_patch(&mut data_store_clone, &patch_data);
-        // It is safe to unwrap because our data store keys are all strings and
-        // we are using default serializer which does not return error.
-        if to_vec(&data_store_clone).unwrap().len() > self.data_store_limit {
-            return Err(MmdsDatastoreError::DataStoreLimitExceeded);
-        }
-        self.data_store = data_store_clone;
-        Ok(())
+        super::json_patch(&mut data_store, &patch_data);
+        self.store(&data_store)
     }
 
     /// return MMDS data store value
-    /// We do not check size of data_store before returning a result because due
-    /// to limit from put/patch the data_store can not be bigger than the limit
-    /// imposed by the server.
-    pub fn data_store_value(&self) -> Value {
-        self.data_store.clone()
+    /// The decompression of the data store is bounded by its size, which the limit from
+    /// put/patch keeps within the limit imposed by the server.
+    pub fn data_store_value(&self) -> Result<Value, MmdsDatastoreError> {
+        self.load()
     }
 
     /// Returns the serde::Value in IMDS format plaintext.
@@ -247,12 +302,13 @@ impl Mmds {
         path: String,
         format: OutputFormat,
     ) -> Result<String, MmdsDatastoreError> {
+        let data_store = self.load()?;
         // The pointer function splits the input by "/". With a trailing "/", pointer does not
         // know how to get the object.
         let value = if path.ends_with('/') {
-            self.data_store.pointer(&path.as_str()[..(path.len() - 1)])
+            data_store.pointer(&path.as_str()[..(path.len() - 1)])
         } else {
-            self.data_store.pointer(path.as_str())
+            data_store.pointer(path.as_str())
         };
 
         if let Some(json) = value {
@@ -276,10 +332,11 @@ mod tests {
 
     impl Mmds {
         fn get_data_str(&self) -> String {
-            if self.data_store.is_null() {
+            let data_store = self.data_store_value().unwrap();
+            if data_store.is_null() {
                 return String::from("{}");
             }
-            self.data_store.to_string()
+            data_store.to_string()
         }
     }
 
@@ -573,4 +630,59 @@ mod tests {
 
         assert_eq!(mmds.get_data_str().len(), 2);
     }
+
+    #[test]
+    fn test_data_store_limit() {
+        let mut mmds = Mmds::default();
+        assert_eq!(mmds.data_store_limit(), 51200);
+        let filling = (0..51300).map(|_| "X").collect::<String>();
+        let data: Value =
+            serde_json::from_str(&("{\"key\": \"".to_string() + &filling + "\"}")).unwrap();
+        mmds.put_data(data.clone()).unwrap_err();
+
+        // The limit can be raised once the data store exists.
+        mmds.set_data_store_limit(52000).unwrap();
+        mmds.put_data(data.clone()).unwrap();
+        assert_eq!(mmds.data_store_size(), 51310);
+        assert_eq!(mmds.data_store_value().unwrap(), data);
+        // Repetitive metadata compresses well.
+        assert!(mmds.compressed_size() < 1024);
+
+        // The limit can't be lowered below the size of the data store.
+        assert_eq!(
+            mmds.set_data_store_limit(51309).unwrap_err().to_string(),
+            MmdsDatastoreError::DataStoreLimitTooSmall(51310).to_string()
+        );
+        assert_eq!(mmds.data_store_limit(), 52000);
+        mmds.set_data_store_limit(51310).unwrap();
+        mmds.patch_data(serde_json::from_str(r#"{"foo": "bar"}"#).unwrap())
+            .unwrap_err();
+        assert_eq!(mmds.data_store_value().unwrap(), data);
+    }
+
+    #[test]
+    fn test_corrupted_data_store() {
+        let mut mmds = Mmds::default();
+        assert_eq!(mmds.data_store_value().unwrap(), Value::Null);
+        mmds.put_data(serde_json::from_str(r#"{"key": "value"}"#).unwrap())
+            .unwrap();
+        assert_eq!(
+            mmds.get_value("/key".to_string(), OutputFormat::Json)
+                .unwrap(),
+            "\"value\""
+        );
+
+        // Decompression never exceeds the size the data store was stored with.
+        mmds.data_store_size -= 1;
+        assert!(matches!(
+            mmds.data_store_value(),
+            Err(MmdsDatastoreError::Corrupted(_))
+        ));
+        mmds.data_store_size += 1;
+        mmds.data_store.truncate(mmds.data_store.len() - 1);
+        assert!(matches!(
+            mmds.get_value("/key".to_string(), OutputFormat::Json),
+            Err(MmdsDatastoreError::Corrupted(_))
+        ));
+    }
 }
@@ -234,6 +234,12 @@ fn respond_to_get_request(mmds: &Mmds, request: Request) -> Response {
                 MediaType::PlainText,
                 Body::new(err.to_string()),
             ),
+            MmdsError::Corrupted(_) => build_response(
+                request.http_version(),
+                StatusCode::InternalServerError,
+                MediaType::PlainText,
+                Body::new(err.to_string()),
+            ),
             _ => unreachable!(),
         },
     }
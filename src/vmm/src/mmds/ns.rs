@@ -27,6 +27,7 @@ use crate::dumbo::tcp::NextSegmentStatus;
 use crate::dumbo::tcp::handler::{RecvEvent, TcpIPv4Handler, WriteEvent, WriteNextError};
 use crate::logger::{IncMetric, METRICS};
 use crate::mmds::data_store::Mmds;
+use crate::rate_limiter::{RateLimiter, TokenType};
 use crate::utils::net::mac::MacAddr;
 
 const DEFAULT_MAC_ADDR: &str = "06:01:23:45:67:01";
@@ -73,6 +74,8 @@ pub struct MmdsNetworkStack {
     pub(crate) tcp_handler: TcpIPv4Handler,
     // Data store reference shared across all MmdsNetworkStack instances.
     pub mmds: Arc<Mutex<Mmds>>,
+    // Limits the frames the guest can send to MMDS through this network interface.
+    pub(crate) rate_limiter: RateLimiter,
 }
 
 impl MmdsNetworkStack {
@@ -94,6 +97,7 @@ impl MmdsNetworkStack {
                 NonZeroUsize::new(DEFAULT_MAX_PENDING_RESETS).unwrap(),
             ),
             mmds,
+            rate_limiter: RateLimiter::default(),
         }
     }
 
@@ -118,6 +122,33 @@ impl MmdsNetworkStack {
         Ipv4Addr::from(DEFAULT_IPV4_ADDR)
     }
 
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Charges a frame heading to `mmds` against the rate limiter.
+    ///
+    /// Returns `false` if there is not enough budget left, in which case the frame has to be
+    /// dropped. The guest TCP stack retransmits it once the budget is replenished.
+    pub fn consume_frame_budget(&mut self, frame_len: u64) -> bool {
+        if !self.rate_limiter.consume(1, TokenType::Ops) {
+            METRICS.mmds.rx_throttled.inc();
+            return false;
+        }
+
+        if !self.rate_limiter.consume(frame_len, TokenType::Bytes) {
+            self.rate_limiter.manual_replenish(1, TokenType::Ops);
+            METRICS.mmds.rx_throttled.inc();
+            return false;
+        }
+
+        true
+    }
+
     /// Check if a frame is destined for `mmds`
     ///
     /// This returns `true` if the frame is an ARP or IPv4 frame destined for
@@ -623,4 +654,29 @@ mod tests {
         assert!(ns.detour_arp(EthernetFrame::from_bytes(&buf[..len]).unwrap()));
         assert!(!ns.detour_ipv4(EthernetFrame::from_bytes(&buf[..len]).unwrap()));
     }
+
+    #[test]
+    fn test_consume_frame_budget() {
+        let mut ns =
+            MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+
+        // The default rate limiter never throttles.
+        for _ in 0..100 {
+            assert!(ns.consume_frame_budget(1500));
+        }
+
+        // Allow 2 frames per second.
+        ns.set_rate_limiter(RateLimiter::new(0, 0, 0, 2, 0, 1000).unwrap());
+        assert!(ns.consume_frame_budget(100));
+        assert!(ns.consume_frame_budget(100));
+        let throttled = METRICS.mmds.rx_throttled.count();
+        assert!(!ns.consume_frame_budget(100));
+        assert_eq!(METRICS.mmds.rx_throttled.count(), throttled + 1);
+
+        // A frame exceeding the bandwidth budget gives its op token back.
+        ns.set_rate_limiter(RateLimiter::new(1000, 0, 1000, 2, 0, 1000).unwrap());
+        assert!(ns.consume_frame_budget(600));
+        assert!(!ns.consume_frame_budget(600));
+        assert_eq!(ns.rate_limiter().ops().unwrap().budget(), 1);
+    }
 }
@@ -10,6 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use super::ns::MmdsNetworkStack;
 use crate::mmds::data_store::Mmds;
+use crate::rate_limiter::RateLimiter;
+use crate::rate_limiter::persist::RateLimiterState;
 use crate::snapshot::Persist;
 use crate::utils::net::mac::{MAC_ADDR_LEN, MacAddr};
 
@@ -19,12 +21,13 @@ pub struct MmdsNetworkStackState {
     mac_addr: [u8; MAC_ADDR_LEN as usize],
     ipv4_addr: u32,
     tcp_port: u16,
+    rate_limiter_state: RateLimiterState,
 }
 
 impl Persist<'_> for MmdsNetworkStack {
     type State = MmdsNetworkStackState;
     type ConstructorArgs = Arc<Mutex<Mmds>>;
-    type Error = ();
+    type Error = std::io::Error;
 
     fn save(&self) -> Self::State {
         let mut mac_addr = [0; MAC_ADDR_LEN as usize];
@@ -34,16 +37,21 @@ impl Persist<'_> for MmdsNetworkStack {
             mac_addr,
             ipv4_addr: self.ipv4_addr.into(),
             tcp_port: self.tcp_handler.local_port(),
+            rate_limiter_state: self.rate_limiter.save(),
         }
     }
 
     fn restore(mmds: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
-        Ok(MmdsNetworkStack::new(
+        // RateLimiter::restore() can fail at creating a timerfd.
+        let rate_limiter = RateLimiter::restore((), &state.rate_limiter_state)?;
+        let mut ns = MmdsNetworkStack::new(
             MacAddr::from_bytes_unchecked(&state.mac_addr),
             Ipv4Addr::from(state.ipv4_addr),
             state.tcp_port,
             mmds,
-        ))
+        );
+        ns.set_rate_limiter(rate_limiter);
+        Ok(ns)
     }
 }
 
@@ -55,7 +63,9 @@ mod tests {
 
     #[test]
     fn test_persistence() {
-        let ns = MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+        let mut ns =
+            MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+        ns.set_rate_limiter(RateLimiter::new(0, 0, 0, 10, 0, 1000).unwrap());
 
         let mut mem = vec![0; 4096];
 
@@ -77,5 +87,8 @@ mod tests {
             restored_ns.tcp_handler.local_port(),
             ns.tcp_handler.local_port()
         );
+        let restored_ops = restored_ns.rate_limiter().ops().unwrap();
+        assert_eq!(restored_ops.capacity(), 10);
+        assert_eq!(restored_ops.refill_time_ms(), 1000);
     }
 }
@@ -24,6 +24,8 @@ use crate::vstate::bus::BusDevice;
 pub enum PciRootError {
     /// Could not find an available device slot on the PCI bus.
     NoPciDeviceSlotAvailable,
+    /// No device is attached to slot {0} of the PCI bus.
+    NoPciDevice(u32),
 }
 
 const VENDOR_ID_INTEL: u16 = 0x8086;
@@ -126,6 +128,16 @@ impl PciBus {
 
         Err(PciRootError::NoPciDeviceSlotAvailable)
     }
+
+    /// Remove the device of a slot from the bus, making the slot available again
+    pub fn remove_device(&mut self, device_id: u32) -> Result<(), PciRootError> {
+        // The host bridge is never removed.
+        if device_id == 0 || self.devices.remove(&device_id).is_none() {
+            return Err(PciRootError::NoPciDevice(device_id));
+        }
+        self.device_ids[device_id as usize] = false;
+        Ok(())
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -962,4 +974,23 @@ mod tests {
         read_mmio_config(&mut mmio_config, 0, 1, 0, 0x6, 0, &mut buffer);
         assert_eq!(buffer, [0x0, 0x0, 0x0, 0x0]);
     }
+
+    #[test]
+    fn test_remove_device() {
+        let mock = Arc::new(RelocationMock::default());
+        let mut bus = PciBus::new(PciRoot::new(None), mock);
+        let device_id = bus.next_device_id().unwrap();
+        assert_eq!(device_id, 1);
+        bus.add_device(device_id, Arc::new(Mutex::new(PciDevMock::new())));
+
+        // The host bridge and empty slots cannot be removed.
+        bus.remove_device(0).unwrap_err();
+        bus.remove_device(2).unwrap_err();
+
+        // Removing the device frees its slot.
+        bus.remove_device(device_id).unwrap();
+        assert!(!bus.devices.contains_key(&device_id));
+        bus.remove_device(device_id).unwrap_err();
+        assert_eq!(bus.next_device_id().unwrap(), device_id);
+    }
 }
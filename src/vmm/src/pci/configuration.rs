@@ -238,15 +238,16 @@ impl PciConfiguration {
         let reg_idx = BAR0_REG + bar_idx;
 
         // These are a few constraints that are imposed due to the fact
-        // that only VirtIO devices are actually allocating a BAR. Moreover, this is
-        // a single 64-bit BAR. Not conforming to these requirements is an internal
+        // that only VirtIO devices are actually allocating BARs. Moreover, these are
+        // 64-bit BARs. Not conforming to these requirements is an internal
         // Firecracker bug.
 
-        // We are only using BAR 0
-        assert_eq!(bar_idx, 0);
+        // 64-bit BARs take a pair of registers, starting at an even one
+        assert_eq!(bar_idx % 2, 0);
+        assert!(bar_idx + 1 < NUM_BAR_REGS);
         // We shouldn't be trying to use the same BAR twice
-        assert!(!self.bars[0].used);
-        assert!(!self.bars[1].used);
+        assert!(!self.bars[bar_idx].used);
+        assert!(!self.bars[bar_idx + 1].used);
         // We can't have a size of 0
         assert_ne!(size, 0);
         // BAR size needs to be a power of two
@@ -410,7 +411,7 @@ impl PciConfiguration {
         // We are always using 64bit BARs, so two BAR registers. We don't do anything until
         // the upper BAR is modified, otherwise we would be moving the BAR to a wrong
         // location in memory.
-        if bar_idx == 0 {
+        if bar_idx % 2 == 0 {
             return None;
         }
 
@@ -760,6 +761,14 @@ mod tests {
         assert!(pci_config.bars[0].used);
         assert_eq!(pci_config.read_reg(BAR0_REG + 1), 1);
         assert!(pci_config.bars[0].used);
+
+        // The next 64-bit BAR starts at the third register.
+        pci_config.add_pci_bar(2, 0x2_0000_0000, 0x4000);
+        assert_eq!(pci_config.get_bar_addr(2), 0x2_0000_0000);
+        assert_eq!(pci_config.read_reg(BAR0_REG + 3), 2);
+        assert!(pci_config.bars[2].used);
+        assert!(pci_config.bars[3].used);
+        assert_eq!(pci_config.get_bar_addr(0), 0x1_0000_0000);
     }
 
     #[test]
@@ -349,6 +349,32 @@ impl MsixConfig {
         }
     }
 
+    /// Deliver the interrupts of a vector to a vCPU rather than to the one programmed by the
+    /// guest, or to the latter again when `vcpu` is `None`, updating its route if needed.
+    pub fn set_vector_vcpu(
+        &mut self,
+        index: usize,
+        vcpu: Option<u8>,
+    ) -> Result<(), InterruptError> {
+        if !self.vectors.set_vcpu(index, vcpu)? {
+            return Ok(());
+        }
+
+        // Same as for the table writes, the routes of masked entries are updated once unmasked.
+        let table_entry = &self.table_entries[index];
+        if self.enabled && !self.masked && !table_entry.masked() {
+            let config = MsixVectorConfig {
+                high_addr: table_entry.msg_addr_hi,
+                low_addr: table_entry.msg_addr_lo,
+                data: table_entry.msg_data,
+                devid: self.devid,
+            };
+            self.vectors
+                .update(index, config, table_entry.masked(), true)?;
+        }
+        Ok(())
+    }
+
     /// Read a pending bit array entry
     pub fn read_pba(&self, offset: u64, data: &mut [u8]) {
         let index: usize = (offset / MSIX_PBA_ENTRIES_MODULO) as usize;
@@ -714,6 +740,37 @@ mod tests {
         });
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_set_vector_vcpu() {
+        let mut config = MsixConfig::new(msix_vector_group(2), 0x42);
+
+        // The route of a masked entry is updated once it is unmasked.
+        check_metric_after_block!(
+            METRICS.interrupts.config_updates,
+            0,
+            config.set_vector_vcpu(0, Some(1)).unwrap()
+        );
+        assert_eq!(*config.vectors.vectors[0].vcpu.lock().unwrap(), Some(1));
+        config.set_msg_ctl(0x8000);
+        config.write_table(0, &u64::to_le_bytes(0xfee0_0000));
+        config.write_table(8, &u64::to_le_bytes(0x0_0000_0020));
+
+        // Changing the vCPU of an unmasked entry updates its route.
+        check_metric_after_block!(
+            METRICS.interrupts.config_updates,
+            1,
+            config.set_vector_vcpu(0, None).unwrap()
+        );
+        assert_eq!(*config.vectors.vectors[0].vcpu.lock().unwrap(), None);
+        check_metric_after_block!(
+            METRICS.interrupts.config_updates,
+            0,
+            config.set_vector_vcpu(0, None).unwrap()
+        );
+        config.set_vector_vcpu(2, Some(1)).unwrap_err();
+    }
+
     #[test]
     #[should_panic]
     fn test_table_access_write_too_big() {
@@ -5,13 +5,14 @@
 
 use std::fmt::Debug;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
+use std::io::{self, Seek, Write};
 use std::mem::forget;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::net::UnixStream;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+use bincode::error::DecodeError;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use userfaultfd::{FeatureFlags, Uffd, UffdBuilder};
@@ -20,22 +21,32 @@ use vmm_sys_util::sock_ctrl_msg::ScmSocket;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::aarch64::vcpu::get_manufacturer_id_from_host;
 use crate::builder::{self, BuildMicrovmFromSnapshotError};
-use crate::cpu_config::templates::StaticCpuTemplate;
+use crate::cpu_config::features::{CpuFeatureRegister, host_features, missing_features};
+use crate::cpu_config::templates::{CpuTemplateType, GetCpuTemplate, StaticCpuTemplate};
 #[cfg(target_arch = "x86_64")]
 use crate::cpu_config::x86_64::cpuid::CpuidTrait;
 #[cfg(target_arch = "x86_64")]
 use crate::cpu_config::x86_64::cpuid::common::get_vendor_id_from_host;
 use crate::device_manager::{DevicePersistError, DevicesState};
+use crate::devices::virtio::device::VirtioDeviceType;
 use crate::logger::{info, warn};
 use crate::resources::VmResources;
 use crate::seccomp::BpfThreadMap;
-use crate::snapshot::Snapshot;
+use crate::snapshot::integrity::{
+    MemoryFileDigests, SectionDigest, SnapshotIntegrity, SnapshotIntegrityError,
+};
+use crate::snapshot::{Snapshot, SnapshotError, get_format_version};
 use crate::utils::u64_to_usize;
 use crate::vmm_config::boot_source::BootSourceConfig;
 use crate::vmm_config::instance_info::InstanceInfo;
-use crate::vmm_config::machine_config::{HugePageConfig, MachineConfigError, MachineConfigUpdate};
-use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, MemBackendType};
-use crate::vstate::kvm::KvmState;
+use crate::vmm_config::machine_config::{
+    CpuTopology, HugePageConfig, MachineConfigError, MachineConfigUpdate, TscConfig,
+};
+use crate::vmm_config::snapshot::{
+    CreateSnapshotParams, LoadSnapshotParams, MemBackendType, MemVerification, SnapshotConsistency,
+    ValidateSnapshotParams,
+};
+use crate::vstate::kvm::{Kvm, KvmState};
 use crate::vstate::memory::{
     self, GuestMemoryState, GuestRegionMmap, GuestRegionType, MemoryError,
 };
@@ -56,6 +67,14 @@ pub struct VmInfo {
     pub boot_source: BootSourceConfig,
     /// Huge page configuration
     pub huge_pages: HugePageConfig,
+    /// CPU topology
+    pub cpu_topology: Option<CpuTopology>,
+    /// TSC configuration
+    pub tsc: Option<TscConfig>,
+    /// IPA size, in bits
+    pub ipa_size: Option<u8>,
+    /// ACPI power button
+    pub power_button: bool,
 }
 
 impl From<&VmResources> for VmInfo {
@@ -66,6 +85,10 @@ impl From<&VmResources> for VmInfo {
             cpu_template: StaticCpuTemplate::from(&value.machine_config.cpu_template),
             boot_source: value.boot_source.config.clone(),
             huge_pages: value.machine_config.huge_pages,
+            cpu_topology: value.machine_config.cpu_topology,
+            tsc: value.machine_config.tsc,
+            ipa_size: value.machine_config.ipa_size,
+            power_button: value.machine_config.power_button,
         }
     }
 }
@@ -83,6 +106,30 @@ pub struct MicrovmState {
     pub vcpu_states: Vec<VcpuState>,
     /// Device states.
     pub device_states: DevicesState,
+    /// CPU features the guest observed, which the host restoring the snapshot must support.
+    pub cpu_features: Vec<CpuFeatureRegister>,
+    /// Digests of the snapshot, used to detect corrupted snapshot files.
+    pub integrity: SnapshotIntegrity,
+}
+
+impl MicrovmState {
+    /// Computes the digests of the sections of the state.
+    pub fn section_digests(&self) -> Result<Vec<SectionDigest>, SnapshotError> {
+        Ok(vec![
+            SectionDigest::new("vm_info", &self.vm_info)?,
+            SectionDigest::new("kvm_state", &self.kvm_state)?,
+            SectionDigest::new("vm_state", &self.vm_state)?,
+            SectionDigest::new("vcpu_states", &self.vcpu_states)?,
+            SectionDigest::new("device_states", &self.device_states)?,
+            SectionDigest::new("cpu_features", &self.cpu_features)?,
+        ])
+    }
+
+    /// Records the digests of the sections of the state, to be done once the state is final.
+    pub fn update_section_digests(&mut self) -> Result<(), SnapshotError> {
+        self.integrity.sections = self.section_digests()?;
+        Ok(())
+    }
 }
 
 /// This describes the mapping between Firecracker base virtual address and
@@ -124,6 +171,8 @@ pub enum MicrovmStateError {
     SaveVmState(vstate::vm::ArchVmError),
     /// Cannot signal Vcpu: {0}
     SignalVcpu(VcpuSendEventError),
+    /// Cannot synchronize Vcpu: {0}
+    SynchronizeVcpu(vstate::vcpu::VcpuError),
     /// Vcpu is in unexpected state.
     UnexpectedVcpuResponse,
 }
@@ -138,16 +187,20 @@ pub enum CreateSnapshotError {
     Memory(#[from] MemoryError),
     /// Cannot perform {0} on the memory backing file: {1}
     MemoryBackingFile(&'static str, io::Error),
+    /// Cannot compute the digests of the memory file: {0}
+    MemoryDigests(SnapshotIntegrityError),
     /// Cannot save the microVM state: {0}
     MicrovmState(MicrovmStateError),
     /// Cannot serialize the microVM state: {0}
     SerializeMicrovmState(#[from] crate::snapshot::SnapshotError),
     /// Cannot perform {0} on the snapshot backing file: {1}
     SnapshotBackingFile(&'static str, io::Error),
+    /// Cannot write the snapshot epoch to guest memory: {0}
+    SnapshotEpoch(vm_memory::GuestMemoryError),
 }
 
 /// Snapshot version
-pub const SNAPSHOT_VERSION: Version = Version::new(8, 0, 0);
+pub const SNAPSHOT_VERSION: Version = Version::new(9, 0, 0);
 
 /// Creates a Microvm snapshot.
 pub fn create_snapshot(
@@ -155,14 +208,43 @@ pub fn create_snapshot(
     vm_info: &VmInfo,
     params: &CreateSnapshotParams,
 ) -> Result<(), CreateSnapshotError> {
-    let microvm_state = vmm
+    if params.consistency == SnapshotConsistency::CrashConsistent {
+        prepare_crash_consistent_snapshot(vmm)?;
+    }
+
+    let mut microvm_state = vmm
         .save_state(vm_info)
         .map_err(CreateSnapshotError::MicrovmState)?;
 
-    snapshot_state_to_file(&microvm_state, &params.snapshot_path)?;
-
+    // The digests are computed from the written memory file.
+    let mut mem_file = match &params.mem_file_fd {
+        Some(mem_file_fd) => mem_file_fd.open(params.memory_digests, true),
+        None => OpenOptions::new()
+            .read(params.memory_digests)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&params.mem_file_path),
+    }
+    .map_err(|err| CreateSnapshotError::MemoryBackingFile("open", err))?;
     vmm.vm
-        .snapshot_memory_to_file(&params.mem_file_path, params.snapshot_type)?;
+        .snapshot_memory_to_file(&mut mem_file, params.snapshot_type)?;
+
+    // The memory file is written first, so that the state file records its digests.
+    microvm_state.integrity.memory = MemoryFileDigests::new(&mem_file, params.memory_digests)
+        .map_err(CreateSnapshotError::MemoryDigests)?;
+    microvm_state.update_section_digests()?;
+
+    let mut snapshot_file = match &params.snapshot_fd {
+        Some(snapshot_fd) => snapshot_fd.open(false, true),
+        None => OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&params.snapshot_path),
+    }
+    .map_err(|err| CreateSnapshotError::SnapshotBackingFile("open", err))?;
+    snapshot_state_to_file(&microvm_state, &mut snapshot_file)?;
 
     // We need to mark queues as dirty again for all activated devices. The reason we
     // do it here is that we don't mark pages as dirty during runtime
@@ -173,20 +255,38 @@ pub fn create_snapshot(
     Ok(())
 }
 
+// Stops the vCPUs at an instruction boundary, flushes the queues of the devices and bumps the
+// snapshot epoch exposed to the guest, before the state of the microVM is saved.
+fn prepare_crash_consistent_snapshot(vmm: &mut Vmm) -> Result<(), CreateSnapshotError> {
+    vmm.synchronize_vcpus()
+        .map_err(CreateSnapshotError::MicrovmState)?;
+    // The devices may complete requests the vCPUs issued right before they were paused.
+    vmm.device_manager.drain_virtio_devices();
+    let epoch = vmm
+        .device_manager
+        .acpi_devices
+        .vmgenid
+        .bump_snapshot_epoch(vmm.vm.guest_memory())
+        .map_err(CreateSnapshotError::SnapshotEpoch)?;
+    info!("Creating a crash-consistent snapshot at epoch {}", epoch);
+    Ok(())
+}
+
 fn snapshot_state_to_file(
     microvm_state: &MicrovmState,
-    snapshot_path: &Path,
+    snapshot_file: &mut File,
 ) -> Result<(), CreateSnapshotError> {
     use self::CreateSnapshotError::*;
-    let mut snapshot_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(snapshot_path)
-        .map_err(|err| SnapshotBackingFile("open", err))?;
+    // Passed files may hold a previous state, and not be positioned at their start.
+    snapshot_file
+        .set_len(0)
+        .map_err(|err| SnapshotBackingFile("truncate", err))?;
+    snapshot_file
+        .rewind()
+        .map_err(|err| SnapshotBackingFile("seek", err))?;
 
     let snapshot = Snapshot::new(microvm_state);
-    snapshot.save(&mut snapshot_file)?;
+    snapshot.save(snapshot_file)?;
     snapshot_file
         .flush()
         .map_err(|err| SnapshotBackingFile("flush", err))?;
@@ -369,6 +469,9 @@ pub fn restore_from_snapshot(
             .map(|device_state| device_state.tap_if_name.clone_from(&entry.host_dev_name))
             .ok_or(SnapshotStateFromFileError::UnknownNetworkDevice)?;
     }
+    vm_resources
+        .rdma
+        .set_allow_degraded_restore(params.rdma_allow_degraded_restore);
     let track_dirty_pages = params.track_dirty_pages;
 
     let vcpu_count = microvm_state
@@ -386,6 +489,10 @@ pub fn restore_from_snapshot(
             cpu_template: Some(microvm_state.vm_info.cpu_template),
             track_dirty_pages: Some(track_dirty_pages),
             huge_pages: Some(microvm_state.vm_info.huge_pages),
+            cpu_topology: microvm_state.vm_info.cpu_topology,
+            tsc: microvm_state.vm_info.tsc,
+            ipa_size: microvm_state.vm_info.ipa_size,
+            power_button: Some(microvm_state.vm_info.power_button),
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         })
@@ -406,8 +513,14 @@ pub fn restore_from_snapshot(
                 .into());
             }
             (
-                guest_memory_from_file(mem_backend_path, mem_state, track_dirty_pages)
-                    .map_err(RestoreFromSnapshotGuestMemoryError::File)?,
+                guest_memory_from_file(
+                    mem_backend_path,
+                    mem_state,
+                    &microvm_state.integrity.memory,
+                    params.mem_verification,
+                    track_dirty_pages,
+                )
+                .map_err(RestoreFromSnapshotGuestMemoryError::File)?,
                 None,
             )
         }
@@ -438,6 +551,10 @@ pub enum SnapshotStateFromFileError {
     Open(#[from] std::io::Error),
     /// Failed to load snapshot state from file: {0}
     Load(#[from] crate::snapshot::SnapshotError),
+    /// The snapshot file is truncated.
+    Truncated,
+    /// The snapshot file is corrupted: {0}
+    Corrupted(#[from] SnapshotIntegrityError),
     /// Unknown Network Device.
     UnknownNetworkDevice,
 }
@@ -445,10 +562,287 @@ pub enum SnapshotStateFromFileError {
 fn snapshot_state_from_file(
     snapshot_path: &Path,
 ) -> Result<MicrovmState, SnapshotStateFromFileError> {
-    let mut snapshot_reader = File::open(snapshot_path)?;
-    let snapshot = Snapshot::load(&mut snapshot_reader)?;
+    snapshot_state_from_bytes(&std::fs::read(snapshot_path)?)
+}
+
+fn snapshot_state_from_bytes(buf: &[u8]) -> Result<MicrovmState, SnapshotStateFromFileError> {
+    let err = match Snapshot::<MicrovmState>::load(&mut &buf[..]) {
+        Ok(snapshot) => return Ok(snapshot.data),
+        Err(err) => err,
+    };
+
+    match err {
+        SnapshotError::Decode(DecodeError::UnexpectedEnd { .. }) => {
+            Err(SnapshotStateFromFileError::Truncated)
+        }
+        // Look for the corrupted section, to report a precise error.
+        SnapshotError::Crc64 => {
+            let state = Snapshot::<MicrovmState>::load_without_crc_check(buf)?.data;
+            state.integrity.verify_sections(&state.section_digests()?)?;
+            Err(err.into())
+        }
+        err => Err(err.into()),
+    }
+}
+
+/// Checks performed on a snapshot before loading it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotCheck {
+    /// The format version of the snapshot is supported.
+    Version,
+    /// The snapshot and memory files are not truncated nor corrupted.
+    Integrity,
+    /// The guest memory layout of the snapshot is valid.
+    Memory,
+    /// The host CPU matches the one the snapshot was taken on.
+    Cpu,
+    /// The host CPU has the features the guest observed.
+    CpuFeatures,
+    /// The CPU template of the microVM is supported by the host CPU.
+    CpuTemplate,
+    /// The host KVM has the capabilities the microVM requires.
+    KvmCapabilities,
+    /// The host resources backing the devices are available.
+    Devices,
+}
+
+/// Reason why a snapshot cannot be loaded on this host.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SnapshotIncompatibility {
+    /// The failed check.
+    pub check: SnapshotCheck,
+    /// Description of the incompatibility.
+    pub reason: String,
+}
+
+/// Virtio device of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SnapshotDevice {
+    /// Type of the device.
+    pub device_type: VirtioDeviceType,
+    /// Id of the device.
+    pub id: String,
+}
 
-    Ok(snapshot.data)
+/// Outcome of the checks of a snapshot against this host.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SnapshotValidationReport {
+    /// Whether the snapshot can be loaded on this host.
+    pub compatible: bool,
+    /// Format version of the snapshot, if it could be read.
+    pub snapshot_version: Option<String>,
+    /// Latest format version this Firecracker loads.
+    pub supported_version: String,
+    /// CPU template of the microVM.
+    pub cpu_template: Option<StaticCpuTemplate>,
+    /// Number of vCPUs of the microVM.
+    pub vcpu_count: Option<usize>,
+    /// Guest memory size of the microVM, in MiB.
+    pub mem_size_mib: Option<u64>,
+    /// Virtio devices of the microVM.
+    pub devices: Vec<SnapshotDevice>,
+    /// CPU features the guest observed which the host CPU lacks.
+    pub missing_cpu_features: Vec<CpuFeatureRegister>,
+    /// Reasons why the snapshot cannot be loaded on this host, empty if it can.
+    pub incompatibilities: Vec<SnapshotIncompatibility>,
+}
+
+impl SnapshotValidationReport {
+    fn fail(&mut self, check: SnapshotCheck, reason: impl ToString) {
+        self.incompatibilities.push(SnapshotIncompatibility {
+            check,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// Checks whether a snapshot can be loaded on this host, without touching any microVM state.
+///
+/// Only failing to read the snapshot file is an error, the incompatibilities of the snapshot
+/// with the host are listed in the returned report.
+pub fn validate_snapshot(
+    params: &ValidateSnapshotParams,
+) -> Result<SnapshotValidationReport, io::Error> {
+    let buf = std::fs::read(&params.snapshot_path)?;
+    let mut report = SnapshotValidationReport {
+        snapshot_version: get_format_version(&mut buf.as_slice())
+            .ok()
+            .map(|version| version.to_string()),
+        supported_version: SNAPSHOT_VERSION.to_string(),
+        ..Default::default()
+    };
+
+    let microvm_state = match snapshot_state_from_bytes(&buf) {
+        Ok(microvm_state) => microvm_state,
+        Err(SnapshotStateFromFileError::Load(SnapshotError::InvalidFormatVersion(version))) => {
+            report.fail(
+                SnapshotCheck::Version,
+                format!("Snapshot format version {version} is not supported"),
+            );
+            return Ok(report);
+        }
+        // The rest of the snapshot cannot be trusted.
+        Err(err) => {
+            report.fail(SnapshotCheck::Integrity, err);
+            return Ok(report);
+        }
+    };
+    report.cpu_template = Some(microvm_state.vm_info.cpu_template);
+    report.vcpu_count = Some(microvm_state.vcpu_states.len());
+    report.mem_size_mib = Some(microvm_state.vm_info.mem_size_mib);
+    report.devices = microvm_state
+        .device_states
+        .virtio_devices()
+        .into_iter()
+        .map(|(device_type, id)| SnapshotDevice {
+            device_type,
+            id: id.to_string(),
+        })
+        .collect();
+
+    if let Some(mem_file_path) = &params.mem_file_path
+        && let Err(err) = File::open(mem_file_path)
+            .map_err(SnapshotIntegrityError::from)
+            .and_then(|file| {
+                microvm_state
+                    .integrity
+                    .memory
+                    .verify(&file, params.mem_verification)
+            })
+    {
+        report.fail(SnapshotCheck::Integrity, err);
+    }
+    if let Err(err) = snapshot_state_sanity_check(&microvm_state) {
+        report.fail(SnapshotCheck::Memory, err);
+    }
+    if let Some(reason) = host_cpu_mismatch(&microvm_state) {
+        report.fail(SnapshotCheck::Cpu, reason);
+    }
+    let cpu_template = microvm_state.vm_info.cpu_template;
+    if !cpu_template.is_none()
+        && let Err(err) = Some(CpuTemplateType::Static(cpu_template)).get_cpu_template()
+    {
+        report.fail(
+            SnapshotCheck::CpuTemplate,
+            format!("CPU template {cpu_template:?}: {err}"),
+        );
+    }
+    match Kvm::new(microvm_state.kvm_state.kvm_cap_modifiers.clone()) {
+        Ok(kvm) => {
+            report.missing_cpu_features =
+                missing_features(&microvm_state.cpu_features, &host_features(&kvm));
+            if !report.missing_cpu_features.is_empty() {
+                let registers: Vec<String> = report
+                    .missing_cpu_features
+                    .iter()
+                    .map(|feature| format!("{} {:#x}", feature.register, feature.bits))
+                    .collect();
+                report.fail(
+                    SnapshotCheck::CpuFeatures,
+                    format!("The host CPU lacks the features {}", registers.join(", ")),
+                );
+            }
+        }
+        Err(err) => report.fail(SnapshotCheck::KvmCapabilities, err),
+    }
+    check_device_backends(&microvm_state.device_states, &mut report);
+
+    report.compatible = report.incompatibilities.is_empty();
+    Ok(report)
+}
+
+// Returns how the host CPU differs from the one the snapshot was taken on, if it is known to.
+#[cfg(target_arch = "x86_64")]
+fn host_cpu_mismatch(microvm_state: &MicrovmState) -> Option<String> {
+    let snapshot_id = microvm_state.vcpu_states.first()?.cpuid.vendor_id()?;
+    let host_id = get_vendor_id_from_host().ok()?;
+    (host_id != snapshot_id).then(|| {
+        format!(
+            "The host CPU vendor {} differs from the snapshotted one {}",
+            String::from_utf8_lossy(&host_id),
+            String::from_utf8_lossy(&snapshot_id)
+        )
+    })
+}
+
+// Returns how the host CPU differs from the one the snapshot was taken on, if it is known to.
+#[cfg(target_arch = "aarch64")]
+fn host_cpu_mismatch(microvm_state: &MicrovmState) -> Option<String> {
+    let snapshot_id = microvm_state.vcpu_states.first()?.regs.manifacturer_id()?;
+    let host_id = get_manufacturer_id_from_host()?;
+    (host_id != snapshot_id).then(|| {
+        format!(
+            "The host CPU manufacturer {host_id:#x} differs from the snapshotted one \
+             {snapshot_id:#x}"
+        )
+    })
+}
+
+// Checks that the host files backing the devices of the snapshot are available.
+fn check_device_backends(devices: &DevicesState, report: &mut SnapshotValidationReport) {
+    let (mmio, pci) = (&devices.mmio_state, &devices.pci_state);
+    let block_devices = mmio
+        .block_devices
+        .iter()
+        .map(|device| (&device.device_id, &device.device_state))
+        .chain(
+            pci.block_devices
+                .iter()
+                .map(|device| (&device.device_id, &device.device_state)),
+        );
+    for (id, state) in block_devices {
+        if let Some(path) = state.disk_path()
+            && let Err(err) = std::fs::metadata(path)
+        {
+            report.fail(
+                SnapshotCheck::Devices,
+                format!("Block device {id}: cannot access {path}: {err}"),
+            );
+        }
+    }
+
+    let pmem_configs = mmio
+        .pmem_devices
+        .iter()
+        .map(|device| &device.device_state.config)
+        .chain(
+            pci.pmem_devices
+                .iter()
+                .map(|device| &device.device_state.config),
+        );
+    for config in pmem_configs {
+        if let Err(err) = std::fs::metadata(&config.path_on_host) {
+            report.fail(
+                SnapshotCheck::Devices,
+                format!(
+                    "Pmem device {}: cannot access {}: {err}",
+                    config.id, config.path_on_host
+                ),
+            );
+        }
+    }
+
+    let p9_configs = mmio
+        .p9_devices
+        .iter()
+        .map(|device| &device.device_state.config)
+        .chain(
+            pci.p9_devices
+                .iter()
+                .map(|device| &device.device_state.config),
+        );
+    for config in p9_configs {
+        if !Path::new(&config.path_on_host).is_dir() {
+            report.fail(
+                SnapshotCheck::Devices,
+                format!(
+                    "9p device {}: {} is not a directory",
+                    config.id, config.path_on_host
+                ),
+            );
+        }
+    }
 }
 
 /// Error type for [`guest_memory_from_file`].
@@ -460,14 +854,19 @@ pub enum GuestMemoryFromFileError {
     Restore(#[from] MemoryError),
     /// Cannot restore hugetlbfs backed snapshot by mapping the memory file. Please use uffd.
     HugetlbfsSnapshot,
+    /// The memory file does not match the snapshot: {0}
+    Integrity(#[from] SnapshotIntegrityError),
 }
 
 fn guest_memory_from_file(
     mem_file_path: &Path,
     mem_state: &GuestMemoryState,
+    mem_digests: &MemoryFileDigests,
+    mem_verification: MemVerification,
     track_dirty_pages: bool,
 ) -> Result<Vec<GuestRegionMmap>, GuestMemoryFromFileError> {
     let mem_file = File::open(mem_file_path)?;
+    mem_digests.verify(&mem_file, mem_verification)?;
     let guest_mem = memory::snapshot_file(mem_file, mem_state.regions(), track_dirty_pages)?;
     Ok(guest_mem)
 }
@@ -634,6 +1033,7 @@ mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
         insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
 
@@ -655,6 +1055,8 @@ mod tests {
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
+            flow_sampling: None,
+            tap_fd: None,
         };
         insert_net_device(
             &mut vmm,
@@ -705,6 +1107,8 @@ mod tests {
             vm_state: vmm.vm.save_state(&mpidrs).unwrap(),
             #[cfg(target_arch = "x86_64")]
             vm_state: vmm.vm.save_state().unwrap(),
+            cpu_features: Vec::new(),
+            integrity: Default::default(),
         };
 
         let mut buf = vec![0; 10000];
@@ -723,6 +1127,157 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_snapshot_state_integrity() {
+        let mut microvm_state = MicrovmState {
+            vm_info: VmInfo {
+                mem_size_mib: 0x1234_5678,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        microvm_state.update_section_digests().unwrap();
+        let mut buf = Vec::new();
+        Snapshot::new(&microvm_state).save(&mut buf).unwrap();
+
+        let snapshot_file = TempFile::new().unwrap();
+        snapshot_file.as_file().write_all(&buf).unwrap();
+        snapshot_state_from_file(snapshot_file.as_path()).unwrap();
+
+        // A truncated file.
+        snapshot_file
+            .as_file()
+            .set_len(buf.len() as u64 / 2)
+            .unwrap();
+        assert!(matches!(
+            snapshot_state_from_file(snapshot_file.as_path()),
+            Err(SnapshotStateFromFileError::Truncated)
+        ));
+
+        // A corrupted section.
+        let offset = buf
+            .windows(8)
+            .position(|window| window == 0x1234_5678u64.to_le_bytes())
+            .unwrap();
+        buf[offset] ^= 0xff;
+        let snapshot_file = TempFile::new().unwrap();
+        snapshot_file.as_file().write_all(&buf).unwrap();
+        assert!(matches!(
+            snapshot_state_from_file(snapshot_file.as_path()),
+            Err(SnapshotStateFromFileError::Corrupted(
+                SnapshotIntegrityError::StateSection(section)
+            )) if section == "vm_info"
+        ));
+    }
+
+    #[test]
+    fn test_validate_snapshot() {
+        let save = |microvm_state: &MicrovmState| {
+            let mut buf = Vec::new();
+            Snapshot::new(microvm_state).save(&mut buf).unwrap();
+            let snapshot_file = TempFile::new().unwrap();
+            snapshot_file.as_file().write_all(&buf).unwrap();
+            snapshot_file
+        };
+        let mem_file = TempFile::new().unwrap();
+        mem_file.as_file().set_len(0x1000).unwrap();
+        let params = |snapshot_file: &TempFile| ValidateSnapshotParams {
+            snapshot_path: snapshot_file.as_path().to_path_buf(),
+            mem_file_path: Some(mem_file.as_path().to_path_buf()),
+            mem_verification: MemVerification::Length,
+        };
+
+        // Snapshot files that cannot be read are not validated.
+        let mut missing_file = TempFile::new().unwrap();
+        missing_file.remove().unwrap();
+        validate_snapshot(&params(&missing_file)).unwrap_err();
+
+        let mut microvm_state = MicrovmState {
+            vm_info: VmInfo {
+                mem_size_mib: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        microvm_state.vm_state.memory.regions = vec![GuestMemoryRegionState {
+            base_address: 0,
+            size: 0x1000,
+            region_type: GuestRegionType::Dram,
+            plugged: vec![true],
+        }];
+        microvm_state.integrity.memory.len = 0x1000;
+        microvm_state.update_section_digests().unwrap();
+        let snapshot_file = save(&microvm_state);
+        let report = validate_snapshot(&params(&snapshot_file)).unwrap();
+        assert_eq!(report.incompatibilities, Vec::new());
+        assert!(report.compatible);
+        assert_eq!(report.snapshot_version, Some(SNAPSHOT_VERSION.to_string()));
+        assert_eq!(report.supported_version, SNAPSHOT_VERSION.to_string());
+        assert_eq!(report.cpu_template, Some(StaticCpuTemplate::None));
+        assert_eq!(report.vcpu_count, Some(0));
+        assert_eq!(report.mem_size_mib, Some(1));
+        assert!(report.devices.is_empty());
+
+        // A memory file not matching the snapshot.
+        mem_file.as_file().set_len(0x2000).unwrap();
+        let report = validate_snapshot(&params(&snapshot_file)).unwrap();
+        assert!(!report.compatible);
+        assert_eq!(report.incompatibilities.len(), 1);
+        assert_eq!(report.incompatibilities[0].check, SnapshotCheck::Integrity);
+        mem_file.as_file().set_len(0x1000).unwrap();
+
+        // All the checks are performed on valid snapshot files.
+        microvm_state.vm_state.memory.regions.clear();
+        microvm_state.update_section_digests().unwrap();
+        let report = validate_snapshot(&params(&save(&microvm_state))).unwrap();
+        assert!(!report.compatible);
+        assert_eq!(
+            report.incompatibilities,
+            vec![SnapshotIncompatibility {
+                check: SnapshotCheck::Memory,
+                reason: SnapShotStateSanityCheckError::NoMemory.to_string(),
+            }]
+        );
+
+        // CPUID registers have no feature beyond their 32 bits.
+        microvm_state.vm_state.memory.regions = vec![GuestMemoryRegionState {
+            base_address: 0,
+            size: 0x1000,
+            region_type: GuestRegionType::Dram,
+            plugged: vec![true],
+        }];
+        let missing = CpuFeatureRegister {
+            register: "0x1:0x0:ecx".to_string(),
+            bits: 1 << 32,
+        };
+        microvm_state.cpu_features = vec![missing.clone()];
+        microvm_state.update_section_digests().unwrap();
+        let report = validate_snapshot(&params(&save(&microvm_state))).unwrap();
+        assert!(!report.compatible);
+        assert_eq!(report.missing_cpu_features, vec![missing]);
+        assert_eq!(
+            report.incompatibilities,
+            vec![SnapshotIncompatibility {
+                check: SnapshotCheck::CpuFeatures,
+                reason: "The host CPU lacks the features 0x1:0x0:ecx 0x100000000".to_string(),
+            }]
+        );
+
+        // Truncated snapshot files cannot be checked any further.
+        snapshot_file.as_file().set_len(32).unwrap();
+        let report = validate_snapshot(&params(&snapshot_file)).unwrap();
+        assert!(!report.compatible);
+        assert_eq!(report.snapshot_version, Some(SNAPSHOT_VERSION.to_string()));
+        assert_eq!(report.cpu_template, None);
+        assert_eq!(
+            report.incompatibilities,
+            vec![SnapshotIncompatibility {
+                check: SnapshotCheck::Integrity,
+                reason: SnapshotStateFromFileError::Truncated.to_string(),
+            }]
+        );
+    }
+
     #[test]
     fn test_create_guest_memory() {
         let mem_state = GuestMemoryState {
@@ -0,0 +1,289 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Token based budget of the host CPU time spent processing the I/O of devices.
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use event_manager::{EventOps, Events, MutEventSubscriber};
+use utils::time::{ClockType, TimerFd, get_time_us};
+use vmm_sys_util::epoll::EventSet;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::{BucketReduction, RateLimiterError, TokenBucket};
+use crate::logger::{IncMetric, METRICS, error, warn};
+
+#[derive(Debug)]
+struct BudgetedDevice {
+    // Clones of the queue events of the device, signalled to resume its deferred work.
+    queue_evts: Vec<EventFd>,
+    // Whether the device deferred work since the budget got exhausted.
+    deferred: bool,
+}
+
+/// Budget of host CPU time, in microseconds, that devices may spend processing I/O.
+///
+/// Devices charge the budget with the CPU time their I/O processing already took. The charges
+/// are consumed from a token bucket refilled every period. When the bucket runs dry, the CPU
+/// time it could not cover is owed and the budget blocks for as long as it takes the bucket to
+/// refill it. While blocked, devices defer the processing of their queues; the queue events of
+/// the devices that deferred work are signalled once the budget unblocks.
+///
+/// Like the `RateLimiter`, the budget unblocks on events of the FD provided by its `AsRawFd`
+/// trait implementation, which are handled by its `MutEventSubscriber` implementation.
+#[derive(Debug)]
+pub struct CpuBudget {
+    bucket: TokenBucket,
+    timer_fd: TimerFd,
+    // Internal flag that quickly determines timer state.
+    timer_active: bool,
+    // CPU time the bucket could not cover, paid back by the time the budget is blocked.
+    debt_us: u64,
+    // CPU time charged while the budget is blocked.
+    pending_us: u64,
+    devices: Vec<BudgetedDevice>,
+}
+
+impl CpuBudget {
+    /// Creates a budget of `budget_us` microseconds of CPU time every `period_ms` milliseconds.
+    ///
+    /// If `budget_us` or `period_ms` are zero, then `None` is returned.
+    pub fn new(budget_us: u64, period_ms: u64) -> Option<Self> {
+        Some(CpuBudget {
+            bucket: TokenBucket::new(budget_us, 0, period_ms)?,
+            timer_fd: TimerFd::new(),
+            timer_active: false,
+            debt_us: 0,
+            pending_us: 0,
+            devices: Vec::new(),
+        })
+    }
+
+    /// Attaches a device, identified by its queue events, to a shared budget.
+    pub fn attach(
+        budget: &Arc<Mutex<Self>>,
+        queue_evts: &[EventFd],
+    ) -> Result<CpuBudgetHandle, std::io::Error> {
+        let queue_evts = queue_evts
+            .iter()
+            .map(EventFd::try_clone)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut locked = budget.lock().expect("Poisoned lock");
+        locked.devices.push(BudgetedDevice {
+            queue_evts,
+            deferred: false,
+        });
+        Ok(CpuBudgetHandle {
+            budget: budget.clone(),
+            slot: locked.devices.len() - 1,
+        })
+    }
+
+    // Arm the timer of the budget with the provided duration.
+    fn activate_timer(&mut self, one_shot_duration: Duration) {
+        self.timer_fd.arm(one_shot_duration, None);
+        self.timer_active = true;
+    }
+
+    /// Charges `cpu_us` microseconds of CPU time to the budget, blocking it if they exceed what
+    /// is left of it.
+    pub fn charge(&mut self, cpu_us: u64) {
+        METRICS.io_cpu_budget.charged_us.add(cpu_us);
+        if self.timer_active {
+            self.pending_us += cpu_us;
+            return;
+        }
+        self.consume(cpu_us);
+    }
+
+    fn consume(&mut self, cpu_us: u64) {
+        let capacity = self.bucket.capacity();
+        // Reductions larger than the bucket are only ever partially covered.
+        let covered_us = cpu_us.min(capacity);
+        let mut debt_us = cpu_us - covered_us;
+        if self.bucket.reduce(covered_us) == BucketReduction::Failure {
+            // The CPU time was already spent, so drain what is left of the bucket.
+            let available = self.bucket.budget();
+            debt_us += covered_us - available;
+            self.bucket.reduce(available);
+        }
+        if debt_us == 0 {
+            return;
+        }
+
+        METRICS.io_cpu_budget.throttles.inc();
+        self.debt_us = debt_us;
+        let wait_ms = (debt_us * self.bucket.refill_time_ms()).div_ceil(capacity);
+        self.activate_timer(Duration::from_millis(wait_ms));
+    }
+
+    /// Returns whether the budget is exhausted, in which case devices must defer their I/O.
+    pub fn is_blocked(&self) -> bool {
+        self.timer_active
+    }
+
+    /// Returns the CPU time, in microseconds, left in the budget.
+    pub fn budget_us(&self) -> u64 {
+        self.bucket.budget()
+    }
+
+    /// This function needs to be called every time there is an event on the
+    /// FD provided by this object's `AsRawFd` trait implementation.
+    ///
+    /// # Errors
+    ///
+    /// If the budget is not blocked, an error is returned.
+    pub fn event_handler(&mut self) -> Result<(), RateLimiterError> {
+        if self.timer_fd.read() == 0 {
+            return Err(RateLimiterError::SpuriousRateLimiterEvent);
+        }
+        self.timer_active = false;
+
+        // The bucket refilled the debt while the budget was blocked, up to its capacity.
+        let debt_us = std::mem::take(&mut self.debt_us).min(self.bucket.capacity());
+        if self.bucket.reduce(debt_us) == BucketReduction::Failure {
+            self.bucket.reduce(self.bucket.budget());
+        }
+        let pending_us = std::mem::take(&mut self.pending_us);
+        self.consume(pending_us);
+        if self.timer_active {
+            return Ok(());
+        }
+
+        for device in self.devices.iter_mut().filter(|device| device.deferred) {
+            device.deferred = false;
+            for queue_evt in &device.queue_evts {
+                if let Err(err) = queue_evt.write(1) {
+                    error!("Failed to resume deferred device I/O: {}", err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsRawFd for CpuBudget {
+    /// Provides a FD which needs to be monitored for POLLIN events.
+    ///
+    /// This object's `event_handler()` method must be called on such events.
+    fn as_raw_fd(&self) -> RawFd {
+        self.timer_fd.as_raw_fd()
+    }
+}
+
+impl MutEventSubscriber for CpuBudget {
+    fn process(&mut self, event: Events, _: &mut EventOps) {
+        if !EventSet::IN.contains(event.event_set()) {
+            warn!(
+                "CPU budget: Received unknown event: {:?}",
+                event.event_set()
+            );
+            return;
+        }
+        if let Err(err) = self.event_handler() {
+            error!("Failed to handle CPU budget event: {:?}", err);
+        }
+    }
+
+    fn init(&mut self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::new(self, EventSet::IN)) {
+            error!("Failed to register CPU budget event: {}", err);
+        }
+    }
+}
+
+/// The handle through which a device uses a shared `CpuBudget`.
+#[derive(Debug, Clone)]
+pub struct CpuBudgetHandle {
+    budget: Arc<Mutex<CpuBudget>>,
+    slot: usize,
+}
+
+impl CpuBudgetHandle {
+    /// Returns whether the device must defer the processing of its queues.
+    ///
+    /// If so, the queue events of the device are signalled once the budget unblocks.
+    pub fn throttle(&self) -> bool {
+        let mut budget = self.budget.lock().expect("Poisoned lock");
+        if !budget.is_blocked() {
+            return false;
+        }
+        budget.devices[self.slot].deferred = true;
+        METRICS.io_cpu_budget.deferred_events.inc();
+        true
+    }
+
+    /// Returns the CPU time of the calling thread, to be passed to `charge_since()`.
+    pub fn start(&self) -> u64 {
+        get_time_us(ClockType::ThreadCpu)
+    }
+
+    /// Charges the budget with the CPU time the calling thread spent since `start_us`.
+    pub fn charge_since(&self, start_us: u64) {
+        let cpu_us = get_time_us(ClockType::ThreadCpu).saturating_sub(start_us);
+        self.budget.lock().expect("Poisoned lock").charge(cpu_us);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_cpu_budget() {
+        assert!(CpuBudget::new(0, 100).is_none());
+        assert!(CpuBudget::new(1000, 0).is_none());
+
+        // 1ms of CPU time every 100ms.
+        let budget = Arc::new(Mutex::new(CpuBudget::new(1000, 100).unwrap()));
+        let queue_evt = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let handle = CpuBudget::attach(&budget, std::slice::from_ref(&queue_evt)).unwrap();
+
+        // Spurious event.
+        budget.lock().unwrap().event_handler().unwrap_err();
+
+        budget.lock().unwrap().charge(600);
+        assert!(!handle.throttle());
+        assert_eq!(budget.lock().unwrap().budget_us(), 400);
+
+        // Exceeding the budget blocks it for the time needed to refill the remainder.
+        budget.lock().unwrap().charge(500);
+        assert!(budget.lock().unwrap().is_blocked());
+        assert_eq!(budget.lock().unwrap().budget_us(), 0);
+        assert!(handle.throttle());
+        queue_evt.read().unwrap_err();
+
+        // Charges while blocked are accounted once the budget unblocks.
+        budget.lock().unwrap().charge(100);
+
+        // 100us of debt take 10ms to be refilled.
+        thread::sleep(Duration::from_millis(15));
+        budget.lock().unwrap().event_handler().unwrap();
+        assert!(!budget.lock().unwrap().is_blocked());
+        assert!(budget.lock().unwrap().budget_us() < 1000);
+        // The device that deferred work is resumed.
+        assert_eq!(queue_evt.read().unwrap(), 1);
+        assert!(!handle.throttle());
+
+        // A charge larger than the whole budget blocks it for more than a period.
+        budget.lock().unwrap().charge(2500);
+        assert!(budget.lock().unwrap().is_blocked());
+        assert!(budget.lock().unwrap().timer_fd.is_armed());
+        assert!(budget.lock().unwrap().debt_us >= 1500);
+    }
+
+    #[test]
+    fn test_cpu_budget_handle() {
+        let budget = Arc::new(Mutex::new(CpuBudget::new(1_000_000, 1000).unwrap()));
+        let handle = CpuBudget::attach(&budget, &[]).unwrap();
+        let start_us = handle.start();
+        handle.charge_since(start_us);
+        assert!(!handle.throttle());
+        assert!(budget.lock().unwrap().budget_us() <= 1_000_000);
+        assert_eq!(budget.lock().unwrap().devices.len(), 1);
+    }
+}
@@ -7,6 +7,7 @@ use std::{fmt, io};
 
 use utils::time::TimerFd;
 
+pub mod cpu_budget;
 pub mod persist;
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -0,0 +1,176 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs;
+use std::io;
+
+use serde::Serialize;
+
+use crate::arch::host_page_size;
+use crate::vstate::memory::{GuestMemory, GuestMemoryMmap, GuestMemoryRegion};
+
+// Prefix of the names of the vCPU threads, followed by their index.
+const VCPU_THREAD_NAME_PREFIX: &str = "fc_vcpu ";
+
+/// Resources used by the microVM, as seen from the host.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ResourceUsage {
+    /// Resident set size of the VMM process, in bytes.
+    pub vmm_rss_bytes: u64,
+    /// Size of the guest memory resident on the host, in bytes.
+    pub guest_memory_resident_bytes: u64,
+    /// Number of file descriptors opened by the VMM process.
+    pub open_fds: u64,
+    /// CPU time of the vCPUs.
+    pub vcpus: Vec<VcpuUsage>,
+    /// I/O totals of the block devices and network interfaces.
+    pub devices: Vec<DeviceIoUsage>,
+}
+
+/// CPU time of a vCPU.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct VcpuUsage {
+    /// Index of the vCPU.
+    pub index: u32,
+    /// Time the vCPU thread spent on the host CPUs, in microseconds.
+    pub cpu_time_us: u64,
+}
+
+/// I/O totals of a device since it was created. For network interfaces, reads are the received
+/// frames and writes the transmitted ones.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceIoUsage {
+    /// Identifier of the device.
+    pub id: String,
+    /// Type of the device, `block` or `net`.
+    #[serde(rename = "type")]
+    pub device_type: String,
+    /// Number of bytes read.
+    pub read_bytes: u64,
+    /// Number of bytes written.
+    pub write_bytes: u64,
+    /// Number of read operations.
+    pub read_ops: u64,
+    /// Number of write operations.
+    pub write_ops: u64,
+}
+
+/// Returns the resident set size of the VMM process, in bytes.
+pub fn vmm_rss_bytes() -> io::Result<u64> {
+    // The second field of `statm` is the number of resident pages.
+    let statm = fs::read_to_string("/proc/self/statm")?;
+    let pages = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|pages| pages.parse::<u64>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed statm"))?;
+    Ok(pages * host_page_size() as u64)
+}
+
+/// Returns the size of `guest_memory` resident on the host, in bytes.
+pub fn guest_memory_resident_bytes(guest_memory: &GuestMemoryMmap) -> io::Result<u64> {
+    let page_size = host_page_size();
+    let mut resident_pages = 0u64;
+    for region in guest_memory.iter() {
+        let len = usize::try_from(region.len()).unwrap();
+        let mut residency = vec![0u8; len.div_ceil(page_size)];
+        // SAFETY: The region is a valid userspace mapping of `len` bytes, and `residency` has one
+        // byte per page of it.
+        let ret = unsafe { libc::mincore(region.as_ptr().cast(), len, residency.as_mut_ptr()) };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        resident_pages += residency
+            .iter()
+            .map(|page| u64::from(page & 1))
+            .sum::<u64>();
+    }
+    Ok(resident_pages * page_size as u64)
+}
+
+/// Returns the number of file descriptors opened by the VMM process.
+pub fn open_fds() -> io::Result<u64> {
+    // Listing the directory opens a file descriptor of its own.
+    let count = fs::read_dir("/proc/self/fd")?.count();
+    Ok(count.saturating_sub(1) as u64)
+}
+
+/// Returns the CPU time of the vCPU threads of the VMM process, ordered by index.
+pub fn vcpu_usage() -> io::Result<Vec<VcpuUsage>> {
+    // SAFETY: sysconf has no side effects.
+    let ticks_per_s = u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) })
+        .map_err(|_| io::Error::last_os_error())?;
+    let mut vcpus = Vec::new();
+    for task in fs::read_dir("/proc/self/task")? {
+        let path = task?.path();
+        // Threads may exit while they are listed.
+        let Ok(comm) = fs::read_to_string(path.join("comm")) else {
+            continue;
+        };
+        let Some(index) = comm
+            .trim_end()
+            .strip_prefix(VCPU_THREAD_NAME_PREFIX)
+            .and_then(|index| index.parse().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(path.join("stat")) else {
+            continue;
+        };
+        let ticks = stat_cpu_ticks(&stat)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed stat"))?;
+        vcpus.push(VcpuUsage {
+            index,
+            cpu_time_us: ticks * 1_000_000 / ticks_per_s,
+        });
+    }
+    vcpus.sort_by_key(|vcpu| vcpu.index);
+    Ok(vcpus)
+}
+
+// Returns the user and system CPU time of a `/proc/<pid>/stat` line, in clock ticks.
+fn stat_cpu_ticks(stat: &str) -> Option<u64> {
+    // The name of the thread may hold spaces, the fields after it start with the state, which is
+    // the third one, while `utime` and `stime` are the 14th and 15th.
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace().skip(11);
+    let utime = fields.next()?.parse::<u64>().ok()?;
+    let stime = fields.next()?.parse::<u64>().ok()?;
+    Some(utime + stime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::single_region_mem;
+    use crate::vstate::memory::{Bytes, GuestAddress};
+
+    #[test]
+    fn test_stat_cpu_ticks() {
+        let stat = "1234 (fc_vcpu 0) S 1 1234 1234 0 -1 4194368 1 0 0 0 250 50 0 0 20 0 1 0";
+        assert_eq!(stat_cpu_ticks(stat), Some(300));
+        // The name of the thread can hold parentheses and spaces.
+        let stat = "1234 (a) b) R 1 1234 1234 0 -1 4194368 1 0 0 0 7 3 0 0 20 0 1 0";
+        assert_eq!(stat_cpu_ticks(stat), Some(10));
+        assert_eq!(stat_cpu_ticks("1234 (fc_vcpu 0) S 1"), None);
+        assert_eq!(stat_cpu_ticks("malformed"), None);
+    }
+
+    #[test]
+    fn test_resource_usage() {
+        assert!(vmm_rss_bytes().unwrap() > 0);
+        assert!(open_fds().unwrap() > 0);
+        // The test threads aren't vCPUs.
+        assert!(vcpu_usage().unwrap().is_empty());
+
+        let mem = single_region_mem(4 * host_page_size());
+        assert_eq!(guest_memory_resident_bytes(&mem).unwrap(), 0);
+        // Writing to the guest memory faults its pages in.
+        mem.write_obj(1u64, GuestAddress(0)).unwrap();
+        mem.write_obj(1u64, GuestAddress(2 * host_page_size() as u64))
+            .unwrap();
+        assert_eq!(
+            guest_memory_resident_bytes(&mem).unwrap(),
+            2 * host_page_size() as u64
+        );
+    }
+}
@@ -12,25 +12,39 @@ use crate::cpu_config::templates::CustomCpuTemplate;
 use crate::devices::virtio::device::VirtioDevice;
 use crate::logger::info;
 use crate::mmds;
-use crate::mmds::data_store::{Mmds, MmdsVersion};
+use crate::mmds::data_store::{Mmds, MmdsDatastoreError, MmdsVersion};
 use crate::mmds::ns::MmdsNetworkStack;
 use crate::utils::mib_to_bytes;
 use crate::utils::net::ipv4addr::is_link_local_valid;
+use crate::vmm_config::RateLimiterConfig;
 use crate::vmm_config::balloon::*;
 use crate::vmm_config::boot_source::{
     BootConfig, BootSource, BootSourceConfig, BootSourceConfigError,
 };
+use crate::vmm_config::cold_memory::{ColdMemoryConfig, ColdMemoryConfigError};
+use crate::vmm_config::config_drive::{ConfigDriveConfig, ConfigDriveError};
+use crate::vmm_config::device_removal::RemovableDeviceType;
 use crate::vmm_config::drive::*;
 use crate::vmm_config::entropy::*;
+use crate::vmm_config::firmware::{Firmware, FirmwareConfig, FirmwareConfigError};
+use crate::vmm_config::fw_cfg::{FwCfgConfig, FwCfgConfigError};
+use crate::vmm_config::gpio::{GpioBuilder, GpioConfig, GpioConfigError};
+use crate::vmm_config::i2c::{I2cBuilder, I2cConfig, I2cConfigError};
 use crate::vmm_config::instance_info::InstanceInfo;
+use crate::vmm_config::interrupt_affinity::{InterruptAffinityConfig, InterruptAffinityConfigError};
+use crate::vmm_config::io_cpu_budget::{IoCpuBudgetConfig, IoCpuBudgetConfigError};
 use crate::vmm_config::machine_config::{MachineConfig, MachineConfigError, MachineConfigUpdate};
+use crate::vmm_config::memory_backing::{MemoryBackingConfig, MemoryBackingConfigError};
 use crate::vmm_config::memory_hotplug::{MemoryHotplugConfig, MemoryHotplugConfigError};
 use crate::vmm_config::metrics::{MetricsConfig, MetricsConfigError, init_metrics};
 use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
 use crate::vmm_config::net::*;
+use crate::vmm_config::p9::{P9Builder, P9Config, P9ConfigError};
 use crate::vmm_config::pmem::{PmemBuilder, PmemConfig, PmemConfigError};
 use crate::vmm_config::rdma::{RdmaDeviceBuilder, RdmaDeviceConfig, RdmaDeviceError};
 use crate::vmm_config::serial::SerialConfig;
+use crate::vmm_config::smbios::{SmbiosConfig, SmbiosConfigError};
+use crate::vmm_config::snapshot::{SnapshotAgentConfig, SnapshotAgentConfigError};
 use crate::vmm_config::vsock::*;
 use crate::vstate::memory;
 use crate::vstate::memory::{GuestRegionMmap, MemoryError};
@@ -44,8 +58,16 @@ pub enum ResourcesError {
     BlockDevice(#[from] DriveError),
     /// Boot source error: {0}
     BootSource(#[from] BootSourceConfigError),
+    /// Configuration drive error: {0}
+    ConfigDrive(#[from] ConfigDriveError),
     /// File operation error: {0}
     File(#[from] std::io::Error),
+    /// Firmware error: {0}
+    Firmware(#[from] FirmwareConfigError),
+    /// fw_cfg device error: {0}
+    FwCfg(#[from] FwCfgConfigError),
+    /// SMBIOS error: {0}
+    Smbios(#[from] SmbiosConfigError),
     /// Invalid JSON: {0}
     InvalidJson(#[from] serde_json::Error),
     /// Logger error: {0}
@@ -66,10 +88,26 @@ pub enum ResourcesError {
     EntropyDevice(#[from] EntropyDeviceError),
     /// Pmem device error: {0}
     PmemDevice(#[from] PmemConfigError),
+    /// 9p device error: {0}
+    P9Device(#[from] P9ConfigError),
+    /// gpio device error: {0}
+    GpioDevice(#[from] GpioConfigError),
+    /// i2c device error: {0}
+    I2cDevice(#[from] I2cConfigError),
     /// RDMA device error: {0}
     RdmaDevice(#[from] RdmaDeviceError),
     /// Memory hotplug config error: {0}
     MemoryHotplugConfig(#[from] MemoryHotplugConfigError),
+    /// Cold memory config error: {0}
+    ColdMemoryConfig(#[from] ColdMemoryConfigError),
+    /// Memory backing config error: {0}
+    MemoryBackingConfig(#[from] MemoryBackingConfigError),
+    /// Snapshot agent config error: {0}
+    SnapshotAgentConfig(#[from] SnapshotAgentConfigError),
+    /// I/O CPU budget config error: {0}
+    IoCpuBudgetConfig(#[from] IoCpuBudgetConfigError),
+    /// Interrupt affinity config error: {0}
+    InterruptAffinityConfig(#[from] InterruptAffinityConfigError),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
@@ -85,7 +123,16 @@ enum CustomCpuTemplateOrPath {
 pub struct VmmConfig {
     balloon: Option<BalloonDeviceConfig>,
     drives: Vec<BlockDeviceConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    config_drive: Option<ConfigDriveConfig>,
+    #[serde(default)]
     boot_source: BootSourceConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    firmware: Option<FirmwareConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fw_cfg: Option<FwCfgConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    smbios: Option<SmbiosConfig>,
     cpu_config: Option<CustomCpuTemplateOrPath>,
     logger: Option<crate::logger::LoggerConfig>,
     machine_config: Option<MachineConfig>,
@@ -99,9 +146,21 @@ pub struct VmmConfig {
     entropy: Option<EntropyDeviceConfig>,
     #[serde(default, rename = "pmem")]
     pmem_devices: Vec<PmemConfig>,
+    #[serde(default, rename = "9p")]
+    p9_devices: Vec<P9Config>,
+    #[serde(default, rename = "gpio")]
+    gpio_devices: Vec<GpioConfig>,
+    #[serde(default, rename = "i2c")]
+    i2c_devices: Vec<I2cConfig>,
     #[serde(skip)]
     serial_config: Option<SerialConfig>,
     memory_hotplug: Option<MemoryHotplugConfig>,
+    cold_memory: Option<ColdMemoryConfig>,
+    memory_backing: Option<MemoryBackingConfig>,
+    snapshot_agent: Option<SnapshotAgentConfig>,
+    io_cpu_budget: Option<IoCpuBudgetConfig>,
+    #[serde(default)]
+    interrupt_affinity: Vec<InterruptAffinityConfig>,
 }
 
 /// A data structure that encapsulates the device configurations
@@ -112,6 +171,8 @@ pub struct VmResources {
     pub machine_config: MachineConfig,
     /// The boot source spec (contains both config and builder) for this microVM.
     pub boot_source: BootSource,
+    /// The firmware booting the microVM in place of a kernel.
+    pub firmware: Option<Firmware>,
     /// The block devices.
     pub block: BlockBuilder,
     /// The vsock device.
@@ -122,12 +183,32 @@ pub struct VmResources {
     pub net_builder: NetBuilder,
     /// The entropy device builder.
     pub entropy: EntropyDeviceBuilder,
+    /// The configuration of the fw_cfg device exposing metadata to the guest.
+    pub fw_cfg: Option<FwCfgConfig>,
+    /// The system information reported to the guest through the SMBIOS tables.
+    pub smbios: Option<SmbiosConfig>,
     /// The pmem devices.
     pub pmem: PmemBuilder,
+    /// The virtio-9p devices.
+    pub p9: P9Builder,
+    /// The virtio-gpio devices.
+    pub gpio: GpioBuilder,
+    /// The virtio-i2c devices.
+    pub i2c: I2cBuilder,
     /// The RDMA devices.
     pub rdma: RdmaDeviceBuilder,
     /// The memory hotplug configuration.
     pub memory_hotplug: Option<MemoryHotplugConfig>,
+    /// The configuration of the compressed backing store for cold guest memory.
+    pub cold_memory: Option<ColdMemoryConfig>,
+    /// The configuration of the file backing the guest memory.
+    pub memory_backing: Option<MemoryBackingConfig>,
+    /// The configuration of the channel through which a guest agent requests snapshots.
+    pub snapshot_agent: Option<SnapshotAgentConfig>,
+    /// The host CPU budget of the net and block devices I/O processing.
+    pub io_cpu_budget: Option<IoCpuBudgetConfig>,
+    /// The vCPUs the interrupts of the queues of the devices are delivered to.
+    pub interrupt_affinity: Vec<InterruptAffinityConfig>,
     /// The optional Mmds data store.
     // This is initialised on demand (if ever used), so that we don't allocate it unless it's
     // actually used.
@@ -183,12 +264,22 @@ impl VmResources {
             }
         }
 
-        resources.build_boot_source(vmm_config.boot_source)?;
+        // The boot source can be left out when booting a firmware.
+        if vmm_config.firmware.is_none() || vmm_config.boot_source != BootSourceConfig::default() {
+            resources.build_boot_source(vmm_config.boot_source)?;
+        }
+        if let Some(firmware_config) = vmm_config.firmware {
+            resources.set_firmware(firmware_config)?;
+        }
 
         for drive_config in vmm_config.drives.into_iter() {
             resources.set_block_device(drive_config)?;
         }
 
+        if let Some(config_drive_config) = vmm_config.config_drive {
+            resources.set_config_drive(config_drive_config)?;
+        }
+
         for net_config in vmm_config.network_interfaces.into_iter() {
             resources.build_net_device(net_config)?;
         }
@@ -221,10 +312,30 @@ impl VmResources {
             resources.build_entropy_device(entropy_device_config)?;
         }
 
+        if let Some(fw_cfg_config) = vmm_config.fw_cfg {
+            resources.set_fw_cfg(fw_cfg_config)?;
+        }
+
+        if let Some(smbios_config) = vmm_config.smbios {
+            resources.set_smbios(smbios_config)?;
+        }
+
         for pmem_config in vmm_config.pmem_devices.into_iter() {
             resources.build_pmem_device(pmem_config)?;
         }
 
+        for p9_config in vmm_config.p9_devices.into_iter() {
+            resources.build_p9_device(p9_config)?;
+        }
+
+        for gpio_config in vmm_config.gpio_devices.into_iter() {
+            resources.build_gpio_device(gpio_config)?;
+        }
+
+        for i2c_config in vmm_config.i2c_devices.into_iter() {
+            resources.build_i2c_device(i2c_config)?;
+        }
+
         if let Some(serial_cfg) = vmm_config.serial_config {
             resources.serial_out_path = serial_cfg.serial_out_path;
         }
@@ -233,6 +344,26 @@ impl VmResources {
             resources.set_memory_hotplug_config(memory_hotplug_config)?;
         }
 
+        if let Some(cold_memory_config) = vmm_config.cold_memory {
+            resources.set_cold_memory_config(cold_memory_config)?;
+        }
+
+        if let Some(memory_backing_config) = vmm_config.memory_backing {
+            resources.set_memory_backing_config(memory_backing_config)?;
+        }
+
+        if let Some(snapshot_agent_config) = vmm_config.snapshot_agent {
+            resources.set_snapshot_agent_config(snapshot_agent_config)?;
+        }
+
+        if let Some(io_cpu_budget_config) = vmm_config.io_cpu_budget {
+            resources.set_io_cpu_budget_config(io_cpu_budget_config)?;
+        }
+
+        for interrupt_affinity_config in vmm_config.interrupt_affinity {
+            resources.set_interrupt_affinity_config(interrupt_affinity_config)?;
+        }
+
         Ok(resources)
     }
 
@@ -249,6 +380,17 @@ impl VmResources {
         Ok(mmds.lock().expect("Poisoned lock"))
     }
 
+    /// Sets the maximum size of the mmds data store, resizing the data store if it exists.
+    pub fn set_mmds_size_limit(&mut self, size_limit: usize) -> Result<(), MmdsDatastoreError> {
+        if let Some(mmds) = self.mmds.as_ref() {
+            mmds.lock()
+                .expect("Poisoned lock")
+                .set_data_store_limit(size_limit)?;
+        }
+        self.mmds_size_limit = size_limit;
+        Ok(())
+    }
+
     /// Add a custom CPU template to the VM resources
     /// to configure vCPUs.
     pub fn set_custom_cpu_template(&mut self, cpu_template: CustomCpuTemplate) {
@@ -301,6 +443,7 @@ impl VmResources {
                 network_interfaces: vec![],
                 ipv4_address: None,
                 imds_compat: mmds_guard.imds_compat(),
+                rate_limiter: None,
             };
 
             for net_dev in net_devs_with_mmds {
@@ -312,7 +455,10 @@ impl VmResources {
                 if inner_mmds_config.ipv4_address.is_none() {
                     // Safe to unwrap the mmds_ns as the filter() explicitly checks for
                     // its existence.
-                    inner_mmds_config.ipv4_address = Some(net.mmds_ns().unwrap().ipv4_addr());
+                    let mmds_ns = net.mmds_ns().unwrap();
+                    inner_mmds_config.ipv4_address = Some(mmds_ns.ipv4_addr());
+                    inner_mmds_config.rate_limiter =
+                        RateLimiterConfig::from(mmds_ns.rate_limiter()).into_option();
                 }
             }
 
@@ -349,6 +495,12 @@ impl VmResources {
         Ok(())
     }
 
+    /// Opens the firmware images booting the microVM in place of a kernel.
+    pub fn set_firmware(&mut self, config: FirmwareConfig) -> Result<(), FirmwareConfigError> {
+        self.firmware = Some(Firmware::new(config)?);
+        Ok(())
+    }
+
     /// Inserts a block to be attached when the VM starts.
     // Only call this function as part of user configuration.
     // If the drive_id does not exist, a new Block Device Config is added to the list.
@@ -360,6 +512,12 @@ impl VmResources {
         self.block.insert(block_device_config, has_pmem_root)
     }
 
+    /// Builds the cloud-init configuration drive and adds it as a read-only block device.
+    pub fn set_config_drive(&mut self, config: ConfigDriveConfig) -> Result<(), ConfigDriveError> {
+        config.write_image()?;
+        Ok(self.set_block_device(config.block_device_config())?)
+    }
+
     /// Builds a network device to be attached when the VM starts.
     pub fn build_net_device(
         &mut self,
@@ -374,6 +532,24 @@ impl VmResources {
         self.vsock.insert(config)
     }
 
+    /// Removes a device from the configuration, once it was removed from the running microVM.
+    pub fn remove_device(&mut self, device_type: RemovableDeviceType, id: &str) {
+        match device_type {
+            RemovableDeviceType::Drive => {
+                self.block.remove(id);
+            }
+            RemovableDeviceType::NetworkInterface => {
+                self.net_builder.remove(id);
+            }
+            RemovableDeviceType::RdmaDevice => {
+                self.rdma.remove(id);
+            }
+            RemovableDeviceType::Vsock => {
+                self.vsock.remove();
+            }
+        }
+    }
+
     /// Builds an entropy device to be attached when the VM starts.
     pub fn build_entropy_device(
         &mut self,
@@ -382,12 +558,44 @@ impl VmResources {
         self.entropy.insert(body)
     }
 
+    /// Sets the metadata exposed to the guest through the fw_cfg device.
+    pub fn set_fw_cfg(&mut self, config: FwCfgConfig) -> Result<(), FwCfgConfigError> {
+        config.validate()?;
+        self.fw_cfg = Some(config);
+        Ok(())
+    }
+
+    /// Sets the system information reported to the guest through the SMBIOS tables.
+    pub fn set_smbios(&mut self, config: SmbiosConfig) -> Result<(), SmbiosConfigError> {
+        if cfg!(target_arch = "aarch64") {
+            return Err(SmbiosConfigError::Unsupported);
+        }
+        config.validate()?;
+        self.smbios = Some(config);
+        Ok(())
+    }
+
     /// Builds a pmem device to be attached when the VM starts.
     pub fn build_pmem_device(&mut self, body: PmemConfig) -> Result<(), PmemConfigError> {
         let has_block_root = self.block.has_root_device();
         self.pmem.build(body, has_block_root)
     }
 
+    /// Builds a virtio-9p device to be attached when the VM starts.
+    pub fn build_p9_device(&mut self, body: P9Config) -> Result<(), P9ConfigError> {
+        self.p9.build(body)
+    }
+
+    /// Builds a virtio-gpio device to be attached when the VM starts.
+    pub fn build_gpio_device(&mut self, body: GpioConfig) -> Result<(), GpioConfigError> {
+        self.gpio.build(body)
+    }
+
+    /// Builds a virtio-i2c device to be attached when the VM starts.
+    pub fn build_i2c_device(&mut self, body: I2cConfig) -> Result<(), I2cConfigError> {
+        self.i2c.build(body)
+    }
+
     /// Builds an RDMA device to be attached when the VM starts.
     pub fn build_rdma_device(
         &mut self,
@@ -406,6 +614,64 @@ impl VmResources {
         Ok(())
     }
 
+    /// Sets the configuration of the compressed backing store for cold guest memory.
+    pub fn set_cold_memory_config(
+        &mut self,
+        config: ColdMemoryConfig,
+    ) -> Result<(), ColdMemoryConfigError> {
+        config.validate(self.machine_config.mem_size_mib)?;
+        self.cold_memory = Some(config);
+        Ok(())
+    }
+
+    /// Sets the configuration of the file backing the guest memory.
+    pub fn set_memory_backing_config(
+        &mut self,
+        config: MemoryBackingConfig,
+    ) -> Result<(), MemoryBackingConfigError> {
+        config.validate()?;
+        self.memory_backing = Some(config);
+        Ok(())
+    }
+
+    /// Sets the configuration of the channel through which a guest agent requests snapshots.
+    pub fn set_snapshot_agent_config(
+        &mut self,
+        config: SnapshotAgentConfig,
+    ) -> Result<(), SnapshotAgentConfigError> {
+        config.validate()?;
+        self.snapshot_agent = Some(config);
+        Ok(())
+    }
+
+    /// Sets the host CPU budget of the net and block devices I/O processing.
+    pub fn set_io_cpu_budget_config(
+        &mut self,
+        config: IoCpuBudgetConfig,
+    ) -> Result<(), IoCpuBudgetConfigError> {
+        config.validate()?;
+        self.io_cpu_budget = Some(config);
+        Ok(())
+    }
+
+    /// Sets the vCPUs the interrupts of the queues of a device are delivered to, replacing the
+    /// ones previously set for the device.
+    pub fn set_interrupt_affinity_config(
+        &mut self,
+        config: InterruptAffinityConfig,
+    ) -> Result<(), InterruptAffinityConfigError> {
+        config.validate()?;
+        match self
+            .interrupt_affinity
+            .iter_mut()
+            .find(|affinity| affinity.device_id == config.device_id)
+        {
+            Some(affinity) => *affinity = config,
+            None => self.interrupt_affinity.push(config),
+        }
+        Ok(())
+    }
+
     /// Setter for mmds config.
     pub fn set_mmds_config(
         &mut self,
@@ -470,7 +736,14 @@ impl VmResources {
         for net_device in self.net_builder.iter() {
             let mut net_device_lock = net_device.lock().expect("Poisoned lock");
             if network_interfaces.contains(&net_device_lock.id) {
-                net_device_lock.configure_mmds_network_stack(ipv4_addr, mmds.clone());
+                // Every interface gets its own budget.
+                let rate_limiter = config
+                    .rate_limiter()
+                    .map(RateLimiterConfig::try_into)
+                    .transpose()
+                    .map_err(MmdsConfigError::CreateRateLimiter)?
+                    .unwrap_or_default();
+                net_device_lock.configure_mmds_network_stack(ipv4_addr, mmds.clone(), rate_limiter);
             } else {
                 net_device_lock.disable_mmds_network_stack();
             }
@@ -518,10 +791,22 @@ impl VmResources {
     }
 
     /// Allocates guest memory in a configuration most appropriate for these [`VmResources`].
+    ///
+    /// If a backing file is configured, the guest DRAM is a shared mapping of that file.
     pub fn allocate_guest_memory(&self) -> Result<Vec<GuestRegionMmap>, MemoryError> {
         let regions =
             crate::arch::arch_memory_regions(mib_to_bytes(self.machine_config.mem_size_mib));
-        self.allocate_memory_regions(&regions)
+        match &self.memory_backing {
+            Some(_) if self.machine_config.huge_pages.is_hugetlbfs() => {
+                Err(MemoryError::BackingFileHugePages)
+            }
+            Some(config) => memory::file_backed(
+                &config.path,
+                &regions,
+                self.machine_config.track_dirty_pages,
+            ),
+            None => self.allocate_memory_regions(&regions),
+        }
     }
 
     /// Allocates a single guest memory region.
@@ -542,7 +827,15 @@ impl From<&VmResources> for VmmConfig {
         VmmConfig {
             balloon: resources.balloon.get_config().ok(),
             drives: resources.block.configs(),
+            // The configuration drive is part of `drives`, backed by its image.
+            config_drive: None,
             boot_source: resources.boot_source.config.clone(),
+            firmware: resources
+                .firmware
+                .as_ref()
+                .map(|firmware| firmware.config.clone()),
+            fw_cfg: resources.fw_cfg.clone(),
+            smbios: resources.smbios.clone(),
             cpu_config: None,
             logger: None,
             machine_config: Some(resources.machine_config.clone()),
@@ -553,9 +846,17 @@ impl From<&VmResources> for VmmConfig {
             vsock: resources.vsock.config(),
             entropy: resources.entropy.config(),
             pmem_devices: resources.pmem.configs(),
+            p9_devices: resources.p9.configs(),
+            gpio_devices: resources.gpio.configs(),
+            i2c_devices: resources.i2c.configs(),
             // serial_config is marked serde(skip) so that it doesnt end up in snapshots.
             serial_config: None,
             memory_hotplug: resources.memory_hotplug.clone(),
+            cold_memory: resources.cold_memory.clone(),
+            memory_backing: resources.memory_backing.clone(),
+            snapshot_agent: resources.snapshot_agent.clone(),
+            io_cpu_budget: resources.io_cpu_budget.clone(),
+            interrupt_affinity: resources.interrupt_affinity.clone(),
         }
     }
 }
@@ -568,6 +869,7 @@ mod tests {
     use std::str::FromStr;
 
     use serde_json::{Map, Value};
+    use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
@@ -577,6 +879,8 @@ mod tests {
     use crate::devices::virtio::block::virtio::VirtioBlockError;
     use crate::devices::virtio::block::{BlockError, CacheType};
     use crate::devices::virtio::device::VirtioDevice;
+    use crate::devices::virtio::rdma::RdmaError;
+    use crate::devices::virtio::rdma::backend::RdmaBackendConfig;
     use crate::devices::virtio::vsock::VSOCK_DEV_ID;
     use crate::resources::VmResources;
     use crate::utils::net::mac::MacAddr;
@@ -585,8 +889,11 @@ mod tests {
         BootConfig, BootSource, BootSourceConfig, DEFAULT_KERNEL_CMDLINE,
     };
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
+    use crate::vmm_config::gpio::GpioMockConfig;
+    use crate::vmm_config::i2c::I2cMockDeviceConfig;
     use crate::vmm_config::machine_config::{HugePageConfig, MachineConfig, MachineConfigError};
     use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
+    use crate::vmm_config::snapshot::SnapshotType;
     use crate::vmm_config::vsock::tests::default_config;
 
     fn default_net_cfg() -> NetworkInterfaceConfig {
@@ -603,6 +910,8 @@ mod tests {
             guest_mac: Some(MacAddr::from_str("01:23:45:67:89:0a").unwrap()),
             rx_rate_limiter: Some(RateLimiterConfig::default()),
             tx_rate_limiter: Some(RateLimiterConfig::default()),
+            flow_sampling: None,
+            tap_fd: None,
         }
     }
 
@@ -624,8 +933,14 @@ mod tests {
 
                 is_read_only: Some(false),
                 path_on_host: Some(tmp_file.as_path().to_str().unwrap().to_string()),
+                backing_fd: None,
                 rate_limiter: Some(RateLimiterConfig::default()),
                 file_engine_type: None,
+                verity: None,
+                shared_page_cache: None,
+                strict_ordering: None,
+                host_cache: None,
+                nbd_uri: None,
 
                 socket: None,
             },
@@ -658,6 +973,7 @@ mod tests {
         VmResources {
             machine_config: MachineConfig::default(),
             boot_source: default_boot_cfg(),
+            firmware: None,
             block: default_blocks(),
             vsock: Default::default(),
             balloon: Default::default(),
@@ -666,10 +982,20 @@ mod tests {
             boot_timer: false,
             mmds_size_limit: HTTP_MAX_PAYLOAD_SIZE,
             entropy: Default::default(),
+            fw_cfg: None,
+            smbios: None,
             pmem: Default::default(),
+            p9: Default::default(),
+            gpio: Default::default(),
+            i2c: Default::default(),
             pci_enabled: false,
             serial_out_path: None,
             memory_hotplug: Default::default(),
+            cold_memory: None,
+            memory_backing: None,
+            snapshot_agent: None,
+            io_cpu_budget: None,
+            interrupt_affinity: Vec::new(),
         }
     }
 
@@ -986,6 +1312,50 @@ mod tests {
             error
         );
 
+        // Invalid rdma device.
+        json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}",
+                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                    }},
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "{}",
+                            "is_root_device": true,
+                            "is_read_only": false
+                        }}
+                    ],
+                    "rdma-devices": [
+                        {{
+                            "id": "rdma0",
+                            "mtu": 1000
+                        }}
+                    ]
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap()
+        );
+
+        let error = VmResources::from_json(
+            json.as_str(),
+            &default_instance_info,
+            HTTP_MAX_PAYLOAD_SIZE,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                ResourcesError::RdmaDevice(RdmaDeviceError::CreateDevice(
+                    RdmaError::InvalidMtu(1000)
+                ))
+            ),
+            "{:?}",
+            error
+        );
+
         // Let's try now passing a valid configuration. We won't include any logger
         // or metrics configuration because these were already initialized in other
         // tests of this module and the reinitialization of them will cause crashing.
@@ -1067,6 +1437,13 @@ mod tests {
                             "host_dev_name": "hostname9"
                         }}
                     ],
+                    "rdma-devices": [
+                        {{
+                            "id": "rdma0",
+                            "mtu": 4096,
+                            "max_qp": 64
+                        }}
+                    ],
                     "machine-config": {{
                         "vcpu_count": 2,
                         "mem_size_mib": 1024,
@@ -1088,14 +1465,52 @@ mod tests {
             Some(r#"{"key": "value"}"#),
         )
         .unwrap();
+        assert_eq!(
+            resources.rdma.configs(),
+            [RdmaDeviceConfig {
+                id: "rdma0".to_string(),
+                mtu: Some(4096),
+                max_qp: Some(64),
+                ..Default::default()
+            }]
+        );
         let mut map = Map::new();
         map.insert("key".to_string(), Value::String("value".to_string()));
         assert_eq!(
-            resources.mmds.unwrap().lock().unwrap().data_store_value(),
+            resources
+                .mmds
+                .unwrap()
+                .lock()
+                .unwrap()
+                .data_store_value()
+                .unwrap(),
             Value::Object(map)
         );
     }
 
+    #[test]
+    fn test_set_mmds_size_limit() {
+        let mut vm_resources = default_vm_resources();
+        // The limit applies to the data store created later on.
+        vm_resources.set_mmds_size_limit(100).unwrap();
+        assert_eq!(vm_resources.mmds_size_limit, 100);
+        let mut mmds = vm_resources.locked_mmds_or_default().unwrap();
+        assert_eq!(mmds.data_store_limit(), 100);
+        mmds.put_data(Value::String("X".repeat(90))).unwrap();
+        drop(mmds);
+
+        vm_resources.set_mmds_size_limit(1000).unwrap();
+        assert_eq!(
+            vm_resources
+                .locked_mmds_or_default()
+                .unwrap()
+                .data_store_limit(),
+            1000
+        );
+        vm_resources.set_mmds_size_limit(10).unwrap_err();
+        assert_eq!(vm_resources.mmds_size_limit, 1000);
+    }
+
     #[test]
     fn test_cpu_config_from_invalid_json() {
         // Invalid cpu config file path.
@@ -1396,7 +1811,13 @@ mod tests {
                     }},
                     "mmds-config": {{
                         "network_interfaces": ["netif1", "netif2"],
-                        "ipv4_address": "169.254.1.1"
+                        "ipv4_address": "169.254.1.1",
+                        "rate_limiter": {{
+                            "ops": {{
+                                "size": 100,
+                                "refill_time": 1000
+                            }}
+                        }}
                     }}
             }}"#,
                 kernel_file.as_path().to_str().unwrap(),
@@ -1429,6 +1850,10 @@ mod tests {
             cpu_template: Some(StaticCpuTemplate::V1N1),
             track_dirty_pages: Some(false),
             huge_pages: Some(HugePageConfig::None),
+            cpu_topology: None,
+            tsc: None,
+            ipa_size: None,
+            power_button: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         };
@@ -1490,6 +1915,7 @@ mod tests {
                 stats_polling_interval_s: 0,
                 free_page_hinting: false,
                 free_page_reporting: false,
+                stats_history_len: 0,
             })
             .unwrap();
         aux_vm_config.mem_size_mib = Some(90);
@@ -1530,6 +1956,7 @@ mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
         assert!(vm_resources.balloon.get().is_none());
         vm_resources
@@ -1570,6 +1997,245 @@ mod tests {
         assert_eq!(actual_entropy_cfg, entropy_device_cfg);
     }
 
+    #[test]
+    fn test_set_fw_cfg() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = FwCfgConfig::default();
+        config
+            .entries
+            .insert(String::from("org.firecracker/instance-id"), String::new());
+
+        assert_eq!(
+            vm_resources.set_fw_cfg(config.clone()),
+            Err(FwCfgConfigError::InvalidKey(String::from(
+                "org.firecracker/instance-id"
+            )))
+        );
+        assert!(vm_resources.fw_cfg.is_none());
+
+        config.entries.clear();
+        config
+            .entries
+            .insert(String::from("role"), String::from("db"));
+        vm_resources.set_fw_cfg(config.clone()).unwrap();
+        assert_eq!(VmmConfig::from(&vm_resources).fw_cfg, Some(config));
+    }
+
+    #[test]
+    fn test_set_config_drive() {
+        let mut vm_resources = default_vm_resources();
+        let image = TempFile::new().unwrap();
+        let mut config = ConfigDriveConfig {
+            drive_id: String::from("cidata"),
+            path_on_host: String::from("/non/existent/cidata.img"),
+            user_data: String::from("#cloud-config\n"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            vm_resources.set_config_drive(config.clone()),
+            Err(ConfigDriveError::WriteImage(_, _))
+        ));
+        let drive_ids = |vm_resources: &VmResources| {
+            vm_resources
+                .block
+                .configs()
+                .into_iter()
+                .map(|drive| drive.drive_id)
+                .collect::<Vec<_>>()
+        };
+        assert!(!drive_ids(&vm_resources).contains(&String::from("cidata")));
+
+        config.path_on_host = image.as_path().to_str().unwrap().to_string();
+        vm_resources.set_config_drive(config.clone()).unwrap();
+        assert!(drive_ids(&vm_resources).contains(&String::from("cidata")));
+        let drive = vm_resources
+            .block
+            .configs()
+            .into_iter()
+            .find(|drive| drive.drive_id == "cidata")
+            .unwrap();
+        assert_eq!(drive.is_read_only, Some(true));
+        assert_eq!(drive.path_on_host, Some(config.path_on_host.clone()));
+        assert_eq!(
+            std::fs::read(image.as_path()).unwrap(),
+            config.build_image().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_set_smbios() {
+        let mut vm_resources = default_vm_resources();
+        let config = SmbiosConfig {
+            serial_number: Some(String::from("SN-0001")),
+            uuid: Some(String::from("5b1e4c9a-2a5f-4f4e-9d63-4f3c2b1a0e9d")),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.set_smbios(config),
+            Err(SmbiosConfigError::Unsupported)
+        );
+        #[cfg(target_arch = "x86_64")]
+        {
+            let invalid_config = SmbiosConfig {
+                uuid: Some(String::from("invalid")),
+                ..Default::default()
+            };
+            assert_eq!(
+                vm_resources.set_smbios(invalid_config),
+                Err(SmbiosConfigError::InvalidUuid(String::from("invalid")))
+            );
+            assert!(vm_resources.smbios.is_none());
+
+            vm_resources.set_smbios(config.clone()).unwrap();
+            assert_eq!(VmmConfig::from(&vm_resources).smbios, Some(config));
+        }
+    }
+
+    #[test]
+    fn test_set_cold_memory_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = ColdMemoryConfig {
+            backing_file_path: PathBuf::from("/tmp/cold"),
+            pressure_stall_us: 150_000,
+            pressure_window_us: 100,
+            evict_batch_mib: 64,
+        };
+
+        assert_eq!(
+            vm_resources.set_cold_memory_config(config.clone()),
+            Err(ColdMemoryConfigError::InvalidPressureWindow)
+        );
+        assert!(vm_resources.cold_memory.is_none());
+
+        config.pressure_window_us = 1_000_000;
+        config.evict_batch_mib = vm_resources.machine_config.mem_size_mib + 1;
+        assert_eq!(
+            vm_resources.set_cold_memory_config(config.clone()),
+            Err(ColdMemoryConfigError::EvictBatchTooLarge)
+        );
+        assert!(vm_resources.cold_memory.is_none());
+
+        config.evict_batch_mib = 64;
+        vm_resources.set_cold_memory_config(config.clone()).unwrap();
+        assert_eq!(vm_resources.cold_memory, Some(config.clone()));
+        assert_eq!(VmmConfig::from(&vm_resources).cold_memory, Some(config));
+    }
+
+    #[test]
+    fn test_set_memory_backing_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = MemoryBackingConfig {
+            path: PathBuf::from("/tmp/guest_mem"),
+            writeback_interval_ms: Some(0),
+            flush_on_pause: false,
+        };
+
+        assert_eq!(
+            vm_resources.set_memory_backing_config(config.clone()),
+            Err(MemoryBackingConfigError::InvalidWritebackInterval)
+        );
+        assert!(vm_resources.memory_backing.is_none());
+
+        config.writeback_interval_ms = Some(1000);
+        vm_resources
+            .set_memory_backing_config(config.clone())
+            .unwrap();
+        assert_eq!(vm_resources.memory_backing, Some(config.clone()));
+        assert_eq!(VmmConfig::from(&vm_resources).memory_backing, Some(config));
+
+        // Hugepages cannot back a regular file.
+        vm_resources.machine_config.huge_pages = HugePageConfig::Hugetlbfs2M;
+        assert!(matches!(
+            vm_resources.allocate_guest_memory(),
+            Err(MemoryError::BackingFileHugePages)
+        ));
+    }
+
+    #[test]
+    fn test_set_snapshot_agent_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = SnapshotAgentConfig {
+            port: 5000,
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::from("/tmp/vm.snap"),
+            mem_file_path: PathBuf::from("/tmp/vm.mem"),
+            freeze_timeout_ms: 0,
+        };
+
+        assert_eq!(
+            vm_resources.set_snapshot_agent_config(config.clone()),
+            Err(SnapshotAgentConfigError::InvalidFreezeTimeout)
+        );
+        assert!(vm_resources.snapshot_agent.is_none());
+
+        config.freeze_timeout_ms = 1000;
+        vm_resources
+            .set_snapshot_agent_config(config.clone())
+            .unwrap();
+        assert_eq!(vm_resources.snapshot_agent, Some(config.clone()));
+        assert_eq!(VmmConfig::from(&vm_resources).snapshot_agent, Some(config));
+    }
+
+    #[test]
+    fn test_set_io_cpu_budget_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = IoCpuBudgetConfig {
+            cpu_percent: 0,
+            period_ms: 100,
+        };
+
+        assert_eq!(
+            vm_resources.set_io_cpu_budget_config(config.clone()),
+            Err(IoCpuBudgetConfigError::InvalidCpuPercent(0))
+        );
+        assert!(vm_resources.io_cpu_budget.is_none());
+
+        config.cpu_percent = 20;
+        vm_resources
+            .set_io_cpu_budget_config(config.clone())
+            .unwrap();
+        assert_eq!(vm_resources.io_cpu_budget, Some(config.clone()));
+        assert_eq!(VmmConfig::from(&vm_resources).io_cpu_budget, Some(config));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_set_interrupt_affinity_config() {
+        let mut vm_resources = default_vm_resources();
+        let mut config = InterruptAffinityConfig {
+            device_id: "eth0".to_string(),
+            queue_vcpus: Vec::new(),
+        };
+
+        assert_eq!(
+            vm_resources.set_interrupt_affinity_config(config.clone()),
+            Err(InterruptAffinityConfigError::NoQueues("eth0".to_string()))
+        );
+        assert!(vm_resources.interrupt_affinity.is_empty());
+
+        config.queue_vcpus = vec![0, 1];
+        vm_resources
+            .set_interrupt_affinity_config(config.clone())
+            .unwrap();
+        // The configuration of a device replaces the previous one.
+        config.queue_vcpus = vec![1, 0];
+        vm_resources
+            .set_interrupt_affinity_config(config.clone())
+            .unwrap();
+        let other_config = InterruptAffinityConfig {
+            device_id: "rootfs".to_string(),
+            queue_vcpus: vec![1],
+        };
+        vm_resources
+            .set_interrupt_affinity_config(other_config.clone())
+            .unwrap();
+        let configs = vec![config, other_config];
+        assert_eq!(vm_resources.interrupt_affinity, configs);
+        assert_eq!(VmmConfig::from(&vm_resources).interrupt_affinity, configs);
+    }
+
     #[test]
     fn test_set_boot_source() {
         let tmp_file = TempFile::new().unwrap();
@@ -1657,6 +2323,26 @@ mod tests {
         assert_eq!(actual_vsock_cfg.lock().unwrap().id(), VSOCK_DEV_ID);
     }
 
+    #[test]
+    fn test_remove_device() {
+        let mut vm_resources = default_vm_resources();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        vm_resources
+            .set_vsock_device(default_config(&tmp_sock_file))
+            .unwrap();
+
+        vm_resources.remove_device(RemovableDeviceType::Drive, "block1");
+        assert!(vm_resources.block.devices.is_empty());
+        vm_resources.remove_device(RemovableDeviceType::NetworkInterface, "net_if1");
+        assert_eq!(vm_resources.net_builder.len(), 0);
+        // The ID of the vsock device is ignored.
+        vm_resources.remove_device(RemovableDeviceType::Vsock, "");
+        assert!(vm_resources.vsock.get().is_none());
+        // Removing unknown devices is a no-op.
+        vm_resources.remove_device(RemovableDeviceType::RdmaDevice, "rdma0");
+    }
+
     #[test]
     fn test_set_net_device() {
         let mut vm_resources = default_vm_resources();
@@ -1687,4 +2373,162 @@ mod tests {
         vm_resources.build_pmem_device(cfg).unwrap();
         assert_eq!(vm_resources.pmem.devices.len(), 1);
     }
+
+    #[test]
+    fn test_set_p9_device() {
+        let mut vm_resources = default_vm_resources();
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cfg = P9Config {
+            id: "share".to_string(),
+            path_on_host: tmp_dir.as_path().to_str().unwrap().to_string(),
+            mount_tag: "share".to_string(),
+            read_only: true,
+        };
+        assert_eq!(vm_resources.p9.devices.len(), 0);
+        vm_resources.build_p9_device(cfg.clone()).unwrap();
+        assert_eq!(vm_resources.p9.devices.len(), 1);
+        assert_eq!(VmmConfig::from(&vm_resources).p9_devices, vec![cfg]);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_firmware_from_json() {
+        let code_file = TempFile::new().unwrap();
+        code_file.as_file().set_len(0x20_0000).unwrap();
+        let vars_file = TempFile::new().unwrap();
+        vars_file.as_file().set_len(0x2_0000).unwrap();
+        let json = format!(
+            r#"{{
+                    "firmware": {{
+                        "path": "{}",
+                        "vars_path": "{}"
+                    }}
+            }}"#,
+            code_file.as_path().to_str().unwrap(),
+            vars_file.as_path().to_str().unwrap(),
+        );
+
+        // The boot source is optional when booting a firmware.
+        let resources = VmResources::from_json(
+            json.as_str(),
+            &InstanceInfo::default(),
+            HTTP_MAX_PAYLOAD_SIZE,
+            None,
+        )
+        .unwrap();
+        assert!(resources.boot_source.builder.is_none());
+        let firmware = resources.firmware.as_ref().unwrap();
+        assert!(firmware.vars_file.is_some());
+        assert_eq!(
+            VmmConfig::from(&resources).firmware,
+            serde_json::from_str::<VmmConfig>(&json).unwrap().firmware
+        );
+
+        let json = format!(
+            r#"{{
+                    "firmware": {{
+                        "path": "{}"
+                    }},
+                    "boot-source": {{
+                        "kernel_image_path": "/invalid/path"
+                    }}
+            }}"#,
+            code_file.as_path().to_str().unwrap(),
+        );
+        assert!(matches!(
+            VmResources::from_json(
+                json.as_str(),
+                &InstanceInfo::default(),
+                HTTP_MAX_PAYLOAD_SIZE,
+                None,
+            ),
+            Err(ResourcesError::BootSource(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_gpio_device() {
+        let mut vm_resources = default_vm_resources();
+        let tmp_dir = TempDir::new().unwrap();
+
+        let cfg = GpioConfig {
+            id: "chip0".to_string(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: tmp_dir.as_path().join("gpio.sock").to_str().unwrap().into(),
+                num_lines: 8,
+                line_names: vec!["led".to_string()],
+            }),
+        };
+        vm_resources.build_gpio_device(cfg.clone()).unwrap();
+        assert_eq!(vm_resources.gpio.devices.len(), 1);
+        assert_eq!(VmmConfig::from(&vm_resources).gpio_devices, vec![cfg]);
+
+        vm_resources
+            .build_gpio_device(GpioConfig {
+                id: "chip1".to_string(),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(vm_resources.gpio.devices.len(), 1);
+    }
+
+    #[test]
+    fn test_set_i2c_device() {
+        let mut vm_resources = default_vm_resources();
+
+        let cfg = I2cConfig {
+            id: "bus0".to_string(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig {
+                address: 0x50,
+                registers: vec![],
+            }],
+        };
+        vm_resources.build_i2c_device(cfg.clone()).unwrap();
+        assert_eq!(vm_resources.i2c.devices.len(), 1);
+        assert_eq!(VmmConfig::from(&vm_resources).i2c_devices, vec![cfg]);
+
+        vm_resources
+            .build_i2c_device(I2cConfig {
+                id: "bus1".to_string(),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(vm_resources.i2c.devices.len(), 1);
+    }
+
+    #[test]
+    fn test_build_rdma_device() {
+        let mut vm_resources = default_vm_resources();
+
+        let cfg = RdmaDeviceConfig {
+            id: "rdma0".to_string(),
+            mac: Some(MacAddr::from_str("06:00:ac:10:00:02").unwrap()),
+            pkeys: vec![0x8001],
+            mtu: Some(4096),
+            backend: Some(RdmaBackendConfig::Null),
+            max_qp: Some(64),
+            max_memory_registration_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+        vm_resources.build_rdma_device(cfg.clone()).unwrap();
+        assert_eq!(vm_resources.rdma.iter().len(), 1);
+
+        // The exported configuration builds the same device.
+        let vmm_config = VmmConfig::from(&vm_resources);
+        assert_eq!(vmm_config.rdma_devices, vec![cfg.clone()]);
+        let json = serde_json::to_value(&vmm_config).unwrap();
+        assert_eq!(json["rdma-devices"], serde_json::to_value(vec![cfg]).unwrap());
+
+        vm_resources
+            .build_rdma_device(RdmaDeviceConfig {
+                id: "rdma1".to_string(),
+                mtu: Some(1000),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert_eq!(VmmConfig::from(&vm_resources).rdma_devices.len(), 1);
+    }
 }
@@ -8,17 +8,23 @@ use serde_json::Value;
 use utils::time::{ClockType, get_time_us};
 
 use super::builder::build_and_boot_microvm;
-use super::persist::{create_snapshot, restore_from_snapshot};
+use super::persist::{create_snapshot, restore_from_snapshot, validate_snapshot};
 use super::resources::VmResources;
-use super::{Vmm, VmmError};
+use super::{DumpCpuConfigError, Vmm, VmmError};
 use crate::EventManager;
 use crate::builder::StartMicrovmError;
-use crate::cpu_config::templates::{CustomCpuTemplate, GuestConfigError};
+use crate::cpu_config::templates::{CustomCpuTemplate, GuestConfigError, config_to_template};
 use crate::devices::virtio::balloon::device::{HintingStatus, StartHintingCmd};
 use crate::devices::virtio::mem::VirtioMemStatus;
+use crate::devices::virtio::net::flows::NetworkFlows;
+use crate::devices::virtio::rdma::device::RdmaDeviceStatus;
+use crate::devices::virtio::vsock::VsockConnectionInfo;
 use crate::logger::{LoggerConfig, info, warn, *};
 use crate::mmds::data_store::{self, Mmds};
-use crate::persist::{CreateSnapshotError, RestoreFromSnapshotError, VmInfo};
+use crate::persist::{
+    CreateSnapshotError, RestoreFromSnapshotError, SnapshotValidationReport, VmInfo,
+};
+use crate::resource_usage::ResourceUsage;
 use crate::resources::VmmConfig;
 use crate::seccomp::BpfThreadMap;
 use crate::vmm_config::balloon::{
@@ -26,22 +32,44 @@ use crate::vmm_config::balloon::{
     BalloonUpdateStatsConfig,
 };
 use crate::vmm_config::boot_source::{BootSourceConfig, BootSourceConfigError};
+use crate::vmm_config::cold_memory::{ColdMemoryConfig, ColdMemoryConfigError};
+use crate::vmm_config::config_drive::{ConfigDriveConfig, ConfigDriveError};
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::device_removal::DeviceRemovalConfig;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::device_removal::RemovableDeviceType;
 use crate::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig, DriveError};
-use crate::vmm_config::entropy::{EntropyDeviceConfig, EntropyDeviceError};
+use crate::vmm_config::entropy::{
+    EntropyDeviceConfig, EntropyDeviceError, EntropyDeviceUpdateConfig,
+};
+use crate::vmm_config::firmware::{FirmwareConfig, FirmwareConfigError};
+use crate::vmm_config::fw_cfg::{FwCfgConfig, FwCfgConfigError};
+use crate::vmm_config::gpio::{GpioConfig, GpioConfigError};
+use crate::vmm_config::i2c::{I2cConfig, I2cConfigError};
 use crate::vmm_config::instance_info::InstanceInfo;
+use crate::vmm_config::interrupt_affinity::{InterruptAffinityConfig, InterruptAffinityConfigError};
+use crate::vmm_config::io_cpu_budget::{IoCpuBudgetConfig, IoCpuBudgetConfigError};
 use crate::vmm_config::machine_config::{MachineConfig, MachineConfigError, MachineConfigUpdate};
+use crate::vmm_config::memory_backing::{MemoryBackingConfig, MemoryBackingConfigError};
 use crate::vmm_config::memory_hotplug::{
     MemoryHotplugConfig, MemoryHotplugConfigError, MemoryHotplugSizeUpdate,
 };
 use crate::vmm_config::metrics::{MetricsConfig, MetricsConfigError};
-use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
+use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError, MmdsConfigUpdate};
 use crate::vmm_config::net::{
     NetworkInterfaceConfig, NetworkInterfaceError, NetworkInterfaceUpdateConfig,
 };
+use crate::vmm_config::p9::{P9Config, P9ConfigError};
 use crate::vmm_config::pmem::{PmemConfig, PmemConfigError};
-use crate::vmm_config::rdma::{RdmaDeviceConfig, RdmaDeviceError};
+use crate::vmm_config::rdma::{RdmaDeviceConfig, RdmaDeviceError, RdmaDeviceUpdateConfig};
 use crate::vmm_config::serial::SerialConfig;
-use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, SnapshotType};
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::shutdown::{GracefulShutdownConfig, GracefulShutdownOutcome};
+use crate::vmm_config::smbios::{SmbiosConfig, SmbiosConfigError};
+use crate::vmm_config::snapshot::{
+    CreateSnapshotParams, LoadSnapshotParams, SnapshotAgentConfig, SnapshotAgentConfigError,
+    SnapshotType, ValidateSnapshotParams,
+};
 use crate::vmm_config::vsock::{VsockConfigError, VsockDeviceConfig};
 use crate::vmm_config::{self, RateLimiterUpdate};
 
@@ -52,6 +80,12 @@ pub enum VmmAction {
     /// Configure the boot source of the microVM using as input the `ConfigureBootSource`. This
     /// action can only be called before the microVM has booted.
     ConfigureBootSource(BootSourceConfig),
+    /// Configure the firmware booting the microVM in place of a kernel, using as input the
+    /// `FirmwareConfig`. This action can only be called before the microVM has booted.
+    ConfigureFirmware(FirmwareConfig),
+    /// Configure the system information reported to the guest through the SMBIOS tables, using
+    /// as input the `SmbiosConfig`. This action can only be called before the microVM has booted.
+    ConfigureSmbios(SmbiosConfig),
     /// Configure the logger using as input the `LoggerConfig`. This action can only be called
     /// before the microVM has booted.
     ConfigureLogger(LoggerConfig),
@@ -67,6 +101,9 @@ pub enum VmmAction {
     GetBalloonConfig,
     /// Get the ballon device latest statistics.
     GetBalloonStats,
+    /// Get the guest CPU configuration, in the custom CPU template format. This action can only
+    /// be called after the microVM has booted and only when the microVM is in `Paused` state.
+    GetCpuConfiguration,
     /// Get complete microVM configuration in JSON format.
     GetFullVmConfig,
     /// Get MMDS contents.
@@ -77,13 +114,31 @@ pub enum VmmAction {
     GetVmInstanceInfo,
     /// Get microVM version.
     GetVmmVersion,
+    /// Get the live connections of the vsock device.
+    GetVsockConnections,
+    /// Get the resources used by the microVM on the host.
+    GetResourceUsage,
+    /// Get the flows sampled on the network interface with the given id.
+    GetNetworkFlows(String),
+    /// Get the status of the rdma device with the given id, and of its backend.
+    GetRdmaDevice(String),
     /// Flush the metrics. This action can only be called after the logger has been configured.
     FlushMetrics,
     /// Add a new block device or update one that already exists using the `BlockDeviceConfig` as
     /// input. This action can only be called before the microVM has booted.
     InsertBlockDevice(BlockDeviceConfig),
+    /// Build the cloud-init configuration drive and add it as a read-only block device, using
+    /// `ConfigDriveConfig` as input. This action can only be called before the microVM has
+    /// booted.
+    SetConfigDrive(ConfigDriveConfig),
     /// Add a virtio-pmem device.
     InsertPmemDevice(PmemConfig),
+    /// Add a virtio-9p device sharing a host directory with the guest.
+    InsertP9Device(P9Config),
+    /// Add a virtio-gpio device whose lines are backed by a host gpiochip or by mock lines.
+    InsertGpioDevice(GpioConfig),
+    /// Add a virtio-i2c adapter proxying to a host adapter or to mock targets.
+    InsertI2cDevice(I2cConfig),
     /// Add a virtio-rdma device.
     InsertRdmaDevice(RdmaDeviceConfig),
     /// Add a new network interface config or update one that already exists using the
@@ -94,12 +149,18 @@ pub enum VmmAction {
     /// called before the microVM has booted. If this action is successful, the loaded microVM will
     /// be in `Paused` state. Should change this state to `Resumed` for the microVM to run.
     LoadSnapshot(LoadSnapshotParams),
+    /// Check whether a snapshot can be loaded on this host using as input the
+    /// `ValidateSnapshotParams`, without touching the state of the microVM.
+    ValidateSnapshot(ValidateSnapshotParams),
     /// Partial update of the MMDS contents.
     PatchMMDS(Value),
     /// Pause the guest, by pausing the microVM VCPUs.
     Pause,
     /// Repopulate the MMDS contents.
     PutMMDS(Value),
+    /// Update the MMDS configuration using `MmdsConfigUpdate` as input. Currently, the only
+    /// updatable property is the size limit of the data store.
+    UpdateMmdsConfiguration(MmdsConfigUpdate),
     /// Configure the guest vCPU features.
     PutCpuConfiguration(CustomCpuTemplate),
     /// Resume the guest, by resuming the microVM VCPUs.
@@ -108,8 +169,25 @@ pub enum VmmAction {
     /// `BalloonDeviceConfig` as input. This action can only be called before the microVM
     /// has booted.
     SetBalloonDevice(BalloonDeviceConfig),
+    /// Set the compressed backing store of cold guest memory using `ColdMemoryConfig` as input.
+    /// This action can only be called before the microVM has booted.
+    SetColdMemory(ColdMemoryConfig),
+    /// Set the host CPU budget of the net and block devices I/O processing using
+    /// `IoCpuBudgetConfig` as input. This action can only be called before the microVM has booted.
+    SetIoCpuBudget(IoCpuBudgetConfig),
+    /// Set the vCPUs the interrupts of the queues of a device are delivered to using
+    /// `InterruptAffinityConfig` as input. This action can only be called before the microVM has
+    /// booted.
+    SetInterruptAffinity(InterruptAffinityConfig),
+    /// Set the file backing the guest memory using `MemoryBackingConfig` as input. This action
+    /// can only be called before the microVM has booted.
+    SetMemoryBacking(MemoryBackingConfig),
     /// Set the MMDS configuration.
     SetMmdsConfiguration(MmdsConfig),
+    /// Set the channel through which a guest agent requests snapshots using
+    /// `SnapshotAgentConfig` as input. This action can only be called before the microVM has
+    /// booted.
+    SetSnapshotAgent(SnapshotAgentConfig),
     /// Set the vsock device or update the one that already exists using the
     /// `VsockDeviceConfig` as input. This action can only be called before the microVM has
     /// booted.
@@ -117,6 +195,9 @@ pub enum VmmAction {
     /// Set the entropy device using `EntropyDeviceConfig` as input. This action can only be called
     /// before the microVM has booted.
     SetEntropyDevice(EntropyDeviceConfig),
+    /// Set the metadata exposed to the guest through the fw_cfg device, using `FwCfgConfig` as
+    /// input. This action can only be called before the microVM has booted.
+    SetFwCfgDevice(FwCfgConfig),
     /// Get the memory hotplug device configuration and status.
     GetMemoryHotplugStatus,
     /// Set the memory hotplug device using `MemoryHotplugConfig` as input. This action can only be
@@ -131,6 +212,14 @@ pub enum VmmAction {
     /// driver is listening on the guest end, this can be used to shut down the microVM gracefully.
     #[cfg(target_arch = "x86_64")]
     SendCtrlAltDel,
+    /// Press the ACPI power button of the microVM, asking the guest to shut down, and force-stop
+    /// the microVM if the guest did not shut down in time.
+    #[cfg(target_arch = "x86_64")]
+    GracefulShutdown(GracefulShutdownConfig),
+    /// Ask the guest to eject a device, and remove the device once the guest released it or when
+    /// the timeout expires. This action can only be called after the microVM has booted.
+    #[cfg(target_arch = "x86_64")]
+    RemoveDevice(DeviceRemovalConfig),
     /// Update the balloon size, after microVM start.
     UpdateBalloon(BalloonUpdateConfig),
     /// Update the balloon statistics polling interval, after microVM start.
@@ -143,9 +232,13 @@ pub enum VmmAction {
     StopFreePageHinting,
     /// Update existing block device properties such as `path_on_host` or `rate_limiter`.
     UpdateBlockDevice(BlockDeviceUpdateConfig),
+    /// Update the rate limiter of the entropy device, after microVM start.
+    UpdateEntropyDevice(EntropyDeviceUpdateConfig),
     /// Update a network interface, after microVM start. Currently, the only updatable properties
     /// are the RX and TX rate limiters.
     UpdateNetworkInterface(NetworkInterfaceUpdateConfig),
+    /// Update the completion interrupt moderation of a virtio-rdma device, after microVM start.
+    UpdateRdmaDevice(RdmaDeviceUpdateConfig),
     /// Update the microVM configuration (memory & vcpu) using `VmUpdateConfig` as input. This
     /// action can only be called before the microVM has booted.
     UpdateMachineConfiguration(MachineConfigUpdate),
@@ -160,18 +253,42 @@ pub enum VmmActionError {
     BalloonUpdate(VmmError),
     /// Boot source error: {0}
     BootSource(#[from] BootSourceConfigError),
+    /// Cold memory config error: {0}
+    ColdMemoryConfig(#[from] ColdMemoryConfigError),
     /// Create snapshot error: {0}
     CreateSnapshot(#[from] CreateSnapshotError),
+    /// Dump CPU config error: {0}
+    DumpCpuConfig(#[from] DumpCpuConfigError),
     /// Configure CPU error: {0}
     ConfigureCpu(#[from] GuestConfigError),
     /// Drive config error: {0}
     DriveConfig(#[from] DriveError),
+    /// Configuration drive error: {0}
+    ConfigDrive(#[from] ConfigDriveError),
+    /// Firmware config error: {0}
+    Firmware(#[from] FirmwareConfigError),
+    /// SMBIOS config error: {0}
+    Smbios(#[from] SmbiosConfigError),
     /// Entropy device error: {0}
     EntropyDevice(#[from] EntropyDeviceError),
+    /// fw_cfg device error: {0}
+    FwCfgDevice(#[from] FwCfgConfigError),
     /// Pmem device error: {0}
     PmemDevice(#[from] PmemConfigError),
+    /// 9p device error: {0}
+    P9Device(#[from] P9ConfigError),
+    /// gpio device error: {0}
+    GpioDevice(#[from] GpioConfigError),
+    /// i2c device error: {0}
+    I2cDevice(#[from] I2cConfigError),
     /// RDMA device error: {0}
     RdmaDevice(#[from] RdmaDeviceError),
+    /// I/O CPU budget config error: {0}
+    IoCpuBudgetConfig(#[from] IoCpuBudgetConfigError),
+    /// Interrupt affinity config error: {0}
+    InterruptAffinityConfig(#[from] InterruptAffinityConfigError),
+    /// Memory backing config error: {0}
+    MemoryBackingConfig(#[from] MemoryBackingConfigError),
     /// Memory hotplug config error: {0}
     MemoryHotplugConfig(#[from] MemoryHotplugConfigError),
     /// Memory hotplug update error: {0}
@@ -202,8 +319,12 @@ pub enum VmmActionError {
     OperationNotSupportedPostBoot,
     /// The requested operation is not supported before starting the microVM.
     OperationNotSupportedPreBoot,
+    /// Snapshot agent config error: {0}
+    SnapshotAgentConfig(#[from] SnapshotAgentConfigError),
     /// Start microvm error: {0}
     StartMicrovm(#[from] StartMicrovmError),
+    /// Cannot read the snapshot to validate: {0}
+    ValidateSnapshot(std::io::Error),
     /// Vsock config error: {0}
     VsockConfig(#[from] VsockConfigError),
 }
@@ -219,6 +340,8 @@ pub enum VmmData {
     BalloonStats(BalloonStats),
     /// No data is sent on the channel.
     Empty,
+    /// The guest CPU configuration, in the custom CPU template format.
+    CpuConfiguration(CustomCpuTemplate),
     /// The complete microVM configuration in JSON format.
     FullVmConfig(VmmConfig),
     /// The microVM configuration represented by `VmConfig`.
@@ -233,16 +356,34 @@ pub enum VmmData {
     VirtioMemStatus(VirtioMemStatus),
     /// The status of the virtio-balloon hinting run
     HintingStatus(HintingStatus),
+    /// The live connections of the vsock device.
+    VsockConnections(Vec<VsockConnectionInfo>),
+    /// The resources used by the microVM on the host.
+    ResourceUsage(ResourceUsage),
+    /// The flows sampled on a network interface.
+    NetworkFlows(NetworkFlows),
+    /// The status of an rdma device and of its backend.
+    RdmaDeviceStatus(RdmaDeviceStatus),
+    /// The outcome of the checks of a snapshot against this host.
+    SnapshotValidation(SnapshotValidationReport),
+    /// The outcome of a graceful shutdown of the microVM.
+    GracefulShutdownOutcome(GracefulShutdownOutcome),
 }
 
 /// Trait used for deduplicating the MMDS request handling across the two ApiControllers.
 /// The methods get a mutable reference to self because the methods should initialise the data
 /// store with the defaults if it's not already initialised.
 trait MmdsRequestHandler {
-    fn mmds(&mut self) -> Result<MutexGuard<'_, Mmds>, VmmActionError>;
+    fn resources(&mut self) -> &mut VmResources;
+
+    fn mmds(&mut self) -> Result<MutexGuard<'_, Mmds>, VmmActionError> {
+        self.resources()
+            .locked_mmds_or_default()
+            .map_err(VmmActionError::MmdsConfig)
+    }
 
     fn get_mmds(&mut self) -> Result<VmmData, VmmActionError> {
-        Ok(VmmData::MmdsValue(self.mmds()?.data_store_value()))
+        Ok(VmmData::MmdsValue(self.mmds()?.data_store_value()?))
     }
 
     fn patch_mmds(&mut self, value: serde_json::Value) -> Result<VmmData, VmmActionError> {
@@ -259,6 +400,13 @@ trait MmdsRequestHandler {
             })
     }
 
+    fn update_mmds_config(&mut self, update: MmdsConfigUpdate) -> Result<VmmData, VmmActionError> {
+        self.resources()
+            .set_mmds_size_limit(update.size_limit)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::Mmds)
+    }
+
     fn put_mmds(&mut self, value: serde_json::Value) -> Result<VmmData, VmmActionError> {
         self.mmds()?
             .put_data(value)
@@ -306,10 +454,8 @@ impl fmt::Debug for PrebootApiController<'_> {
 }
 
 impl MmdsRequestHandler for PrebootApiController<'_> {
-    fn mmds(&mut self) -> Result<MutexGuard<'_, Mmds>, VmmActionError> {
+    fn resources(&mut self) -> &mut VmResources {
         self.vm_resources
-            .locked_mmds_or_default()
-            .map_err(VmmActionError::MmdsConfig)
     }
 }
 
@@ -443,6 +589,8 @@ impl<'a> PrebootApiController<'a> {
         match request {
             // Supported operations allowed pre-boot.
             ConfigureBootSource(config) => self.set_boot_source(config),
+            ConfigureFirmware(config) => self.set_firmware(config),
+            ConfigureSmbios(config) => self.set_smbios(config),
             ConfigureLogger(logger_cfg) => crate::logger::LOGGER
                 .update(logger_cfg)
                 .map(|()| VmmData::Empty)
@@ -469,23 +617,37 @@ impl<'a> PrebootApiController<'a> {
             GetVmInstanceInfo => Ok(VmmData::InstanceInformation(self.instance_info.clone())),
             GetVmmVersion => Ok(VmmData::VmmVersion(self.instance_info.vmm_version.clone())),
             InsertBlockDevice(config) => self.insert_block_device(config),
+            SetConfigDrive(config) => self.set_config_drive(config),
             InsertPmemDevice(config) => self.insert_pmem_device(config),
+            InsertP9Device(config) => self.insert_p9_device(config),
+            InsertGpioDevice(config) => self.insert_gpio_device(config),
+            InsertI2cDevice(config) => self.insert_i2c_device(config),
             InsertRdmaDevice(config) => self.insert_rdma_device(config),
             InsertNetworkDevice(config) => self.insert_net_device(config),
             LoadSnapshot(config) => self
                 .load_snapshot(&config)
                 .map_err(VmmActionError::LoadSnapshot),
+            ValidateSnapshot(params) => validate_snapshot(&params)
+                .map(VmmData::SnapshotValidation)
+                .map_err(VmmActionError::ValidateSnapshot),
             PatchMMDS(value) => self.patch_mmds(value),
             PutCpuConfiguration(custom_cpu_template) => {
                 self.set_custom_cpu_template(custom_cpu_template)
             }
             PutMMDS(value) => self.put_mmds(value),
+            UpdateMmdsConfiguration(update) => self.update_mmds_config(update),
             SetBalloonDevice(config) => self.set_balloon_device(config),
+            SetColdMemory(config) => self.set_cold_memory(config),
+            SetIoCpuBudget(config) => self.set_io_cpu_budget(config),
+            SetInterruptAffinity(config) => self.set_interrupt_affinity(config),
+            SetMemoryBacking(config) => self.set_memory_backing(config),
             SetVsockDevice(config) => self.set_vsock_device(config),
             SetMmdsConfiguration(config) => self.set_mmds_config(config),
+            SetSnapshotAgent(config) => self.set_snapshot_agent(config),
             StartMicroVm => self.start_microvm(),
             UpdateMachineConfiguration(config) => self.update_machine_config(config),
             SetEntropyDevice(config) => self.set_entropy_device(config),
+            SetFwCfgDevice(config) => self.set_fw_cfg_device(config),
             SetMemoryHotplugDevice(config) => self.set_memory_hotplug_device(config),
             // Operations not allowed pre-boot.
             CreateSnapshot(_)
@@ -493,17 +655,31 @@ impl<'a> PrebootApiController<'a> {
             | Pause
             | Resume
             | GetBalloonStats
+            | GetCpuConfiguration
             | GetMemoryHotplugStatus
+            | GetNetworkFlows(_)
+            | GetRdmaDevice(_)
+            | GetVsockConnections
+            | GetResourceUsage
             | UpdateBalloon(_)
             | UpdateBalloonStatistics(_)
             | UpdateBlockDevice(_)
+            | UpdateEntropyDevice(_)
             | UpdateMemoryHotplugSize(_)
             | UpdateNetworkInterface(_)
+            | UpdateRdmaDevice(_)
             | StartFreePageHinting(_)
             | GetFreePageHintingStatus
             | StopFreePageHinting => Err(VmmActionError::OperationNotSupportedPreBoot),
+            // The rdma devices are only dropped from the configuration before boot.
             #[cfg(target_arch = "x86_64")]
-            SendCtrlAltDel => Err(VmmActionError::OperationNotSupportedPreBoot),
+            RemoveDevice(config) if config.device_type == RemovableDeviceType::RdmaDevice => {
+                self.remove_rdma_device(&config.id)
+            }
+            #[cfg(target_arch = "x86_64")]
+            SendCtrlAltDel | GracefulShutdown(_) | RemoveDevice(_) => {
+                Err(VmmActionError::OperationNotSupportedPreBoot)
+            }
         }
     }
 
@@ -523,6 +699,12 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::DriveConfig)
     }
 
+    fn set_config_drive(&mut self, cfg: ConfigDriveConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_config_drive(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
     fn insert_net_device(
         &mut self,
         cfg: NetworkInterfaceConfig,
@@ -542,6 +724,30 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::PmemDevice)
     }
 
+    fn insert_p9_device(&mut self, cfg: P9Config) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .build_p9_device(cfg)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::P9Device)
+    }
+
+    fn insert_gpio_device(&mut self, cfg: GpioConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .build_gpio_device(cfg)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::GpioDevice)
+    }
+
+    fn insert_i2c_device(&mut self, cfg: I2cConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .build_i2c_device(cfg)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::I2cDevice)
+    }
+
     fn insert_rdma_device(&mut self, cfg: RdmaDeviceConfig) -> Result<VmmData, VmmActionError> {
         self.boot_path = true;
         self.vm_resources
@@ -550,6 +756,15 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::RdmaDevice)
     }
 
+    #[cfg(target_arch = "x86_64")]
+    fn remove_rdma_device(&mut self, id: &str) -> Result<VmmData, VmmActionError> {
+        self.vm_resources
+            .rdma
+            .remove(id)
+            .map(|_| VmmData::Empty)
+            .ok_or_else(|| VmmActionError::RdmaDevice(RdmaDeviceError::NotFound(id.to_string())))
+    }
+
     fn set_balloon_device(&mut self, cfg: BalloonDeviceConfig) -> Result<VmmData, VmmActionError> {
         self.boot_path = true;
         self.vm_resources
@@ -566,6 +781,22 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::BootSource)
     }
 
+    fn set_firmware(&mut self, cfg: FirmwareConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .set_firmware(cfg)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::Firmware)
+    }
+
+    fn set_smbios(&mut self, cfg: SmbiosConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .set_smbios(cfg)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::Smbios)
+    }
+
     fn set_mmds_config(&mut self, cfg: MmdsConfig) -> Result<VmmData, VmmActionError> {
         self.boot_path = true;
         self.vm_resources
@@ -607,6 +838,45 @@ impl<'a> PrebootApiController<'a> {
         Ok(VmmData::Empty)
     }
 
+    fn set_fw_cfg_device(&mut self, cfg: FwCfgConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_fw_cfg(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
+    fn set_cold_memory(&mut self, cfg: ColdMemoryConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_cold_memory_config(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
+    fn set_io_cpu_budget(&mut self, cfg: IoCpuBudgetConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_io_cpu_budget_config(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
+    fn set_interrupt_affinity(
+        &mut self,
+        cfg: InterruptAffinityConfig,
+    ) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_interrupt_affinity_config(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
+    fn set_memory_backing(&mut self, cfg: MemoryBackingConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_memory_backing_config(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
+    fn set_snapshot_agent(&mut self, cfg: SnapshotAgentConfig) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.set_snapshot_agent_config(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
     fn set_memory_hotplug_device(
         &mut self,
         cfg: MemoryHotplugConfig,
@@ -688,10 +958,8 @@ pub struct RuntimeApiController {
 }
 
 impl MmdsRequestHandler for RuntimeApiController {
-    fn mmds(&mut self) -> Result<MutexGuard<'_, Mmds>, VmmActionError> {
-        self.vm_resources
-            .locked_mmds_or_default()
-            .map_err(VmmActionError::MmdsConfig)
+    fn resources(&mut self) -> &mut VmResources {
+        &mut self.vm_resources
     }
 }
 
@@ -717,6 +985,7 @@ impl RuntimeApiController {
                 .latest_balloon_stats()
                 .map(VmmData::BalloonStats)
                 .map_err(VmmActionError::InternalVmm),
+            GetCpuConfiguration => self.get_cpu_configuration(),
             GetFullVmConfig => Ok(VmmData::FullVmConfig((&self.vm_resources).into())),
             GetMemoryHotplugStatus => self
                 .vmm
@@ -735,12 +1004,51 @@ impl RuntimeApiController {
             GetVmmVersion => Ok(VmmData::VmmVersion(
                 self.vmm.lock().expect("Poisoned lock").version(),
             )),
+            GetVsockConnections => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .vsock_connections()
+                .map(VmmData::VsockConnections)
+                .map_err(VmmActionError::InternalVmm),
+            GetResourceUsage => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .resource_usage()
+                .map(VmmData::ResourceUsage)
+                .map_err(VmmActionError::InternalVmm),
+            GetNetworkFlows(iface_id) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .network_flows(&iface_id)
+                .map(VmmData::NetworkFlows)
+                .map_err(VmmActionError::InternalVmm),
+            GetRdmaDevice(rdma_id) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .rdma_device_status(&rdma_id)
+                .map(VmmData::RdmaDeviceStatus)
+                .map_err(VmmActionError::InternalVmm),
             PatchMMDS(value) => self.patch_mmds(value),
             Pause => self.pause(),
             PutMMDS(value) => self.put_mmds(value),
+            UpdateMmdsConfiguration(update) => self.update_mmds_config(update),
             Resume => self.resume(),
             #[cfg(target_arch = "x86_64")]
             SendCtrlAltDel => self.send_ctrl_alt_del(),
+            #[cfg(target_arch = "x86_64")]
+            GracefulShutdown(config) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .graceful_shutdown(&config)
+                .map(|()| VmmData::Empty)
+                .map_err(VmmActionError::InternalVmm),
+            #[cfg(target_arch = "x86_64")]
+            RemoveDevice(config) => self.remove_device(&config),
             UpdateBalloon(balloon_update) => self
                 .vmm
                 .lock()
@@ -777,7 +1085,12 @@ impl RuntimeApiController {
                 .map(|_| VmmData::Empty)
                 .map_err(VmmActionError::BalloonUpdate),
             UpdateBlockDevice(new_cfg) => self.update_block_device(new_cfg),
-            UpdateNetworkInterface(netif_update) => self.update_net_rate_limiters(netif_update),
+            UpdateEntropyDevice(new_cfg) => self.update_entropy_device(new_cfg),
+            UpdateNetworkInterface(netif_update) => self.update_net_interface(netif_update),
+            UpdateRdmaDevice(rdma_update) => self.update_rdma_device(rdma_update),
+            ValidateSnapshot(params) => validate_snapshot(&params)
+                .map(VmmData::SnapshotValidation)
+                .map_err(VmmActionError::ValidateSnapshot),
             UpdateMemoryHotplugSize(cfg) => self
                 .vmm
                 .lock()
@@ -785,21 +1098,36 @@ impl RuntimeApiController {
                 .update_memory_hotplug_size(cfg.requested_size_mib)
                 .map(|_| VmmData::Empty)
                 .map_err(VmmActionError::MemoryHotplugUpdate),
+            #[cfg(target_arch = "x86_64")]
+            InsertRdmaDevice(config) => self.insert_rdma_device(config),
+            #[cfg(target_arch = "aarch64")]
+            InsertRdmaDevice(_) => Err(VmmActionError::OperationNotSupportedPostBoot),
             // Operations not allowed post-boot.
             ConfigureBootSource(_)
+            | ConfigureFirmware(_)
+            | ConfigureSmbios(_)
             | ConfigureLogger(_)
             | ConfigureMetrics(_)
             | ConfigureSerial(_)
             | InsertBlockDevice(_)
+            | SetConfigDrive(_)
             | InsertPmemDevice(_)
-            | InsertRdmaDevice(_)
+            | InsertP9Device(_)
+            | InsertGpioDevice(_)
+            | InsertI2cDevice(_)
             | InsertNetworkDevice(_)
             | LoadSnapshot(_)
             | PutCpuConfiguration(_)
             | SetBalloonDevice(_)
+            | SetColdMemory(_)
+            | SetIoCpuBudget(_)
+            | SetInterruptAffinity(_)
+            | SetMemoryBacking(_)
             | SetVsockDevice(_)
             | SetMmdsConfiguration(_)
+            | SetSnapshotAgent(_)
             | SetEntropyDevice(_)
+            | SetFwCfgDevice(_)
             | SetMemoryHotplugDevice(_)
             | StartMicroVm
             | UpdateMachineConfiguration(_) => Err(VmmActionError::OperationNotSupportedPostBoot),
@@ -862,6 +1190,58 @@ impl RuntimeApiController {
             .map_err(VmmActionError::InternalVmm)
     }
 
+    /// Removes a device from the microVM.
+    #[cfg(target_arch = "x86_64")]
+    fn remove_device(&mut self, config: &DeviceRemovalConfig) -> Result<VmmData, VmmActionError> {
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .remove_device(config)
+            .map_err(VmmActionError::InternalVmm)?;
+        // The device is no longer part of the configuration, even if the guest did not release
+        // it yet.
+        self.vm_resources
+            .remove_device(config.device_type, &config.id);
+        Ok(VmmData::Empty)
+    }
+
+    /// Adds an rdma device to the microVM.
+    #[cfg(target_arch = "x86_64")]
+    fn insert_rdma_device(&mut self, cfg: RdmaDeviceConfig) -> Result<VmmData, VmmActionError> {
+        // The backend of the device is set up before the device is added.
+        let launcher = self
+            .vmm
+            .lock()
+            .expect("Poisoned lock")
+            .rdma_launcher()
+            .map_err(RdmaDeviceError::Insertion)
+            .map_err(VmmActionError::RdmaDevice)?;
+        let device = self
+            .vm_resources
+            .rdma
+            .build_hotplugged(cfg, launcher)
+            .map_err(VmmActionError::RdmaDevice)?;
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .insert_rdma_device(device.clone())
+            .map_err(RdmaDeviceError::Insertion)
+            .map_err(VmmActionError::RdmaDevice)?;
+        // The device is part of the configuration once added, so that it can be removed again.
+        self.vm_resources.rdma.add_device(device);
+        Ok(VmmData::Empty)
+    }
+
+    /// Dumps the configuration of the first vCPU as a custom CPU template, the configuration of
+    /// all the vCPUs being derived from the same template.
+    fn get_cpu_configuration(&mut self) -> Result<VmmData, VmmActionError> {
+        let cpu_configs = self.vmm.lock().expect("Poisoned lock").dump_cpu_config()?;
+        let cpu_config = cpu_configs
+            .first()
+            .ok_or(DumpCpuConfigError::UnexpectedResponse)?;
+        Ok(VmmData::CpuConfiguration(config_to_template(cpu_config)))
+    }
+
     fn create_snapshot(
         &mut self,
         create_params: &CreateSnapshotParams,
@@ -933,24 +1313,60 @@ impl RuntimeApiController {
         Ok(VmmData::Empty)
     }
 
-    /// Updates configuration for an emulated net device as described in `new_cfg`.
-    fn update_net_rate_limiters(
+    /// Updates the rate limiter of the entropy device as described in `new_cfg`.
+    fn update_entropy_device(
         &mut self,
-        new_cfg: NetworkInterfaceUpdateConfig,
+        new_cfg: EntropyDeviceUpdateConfig,
     ) -> Result<VmmData, VmmActionError> {
+        let rate_limiter = RateLimiterUpdate::from(new_cfg.rate_limiter);
         self.vmm
             .lock()
             .expect("Poisoned lock")
-            .update_net_rate_limiters(
-                &new_cfg.iface_id,
-                RateLimiterUpdate::from(new_cfg.rx_rate_limiter).bandwidth,
-                RateLimiterUpdate::from(new_cfg.rx_rate_limiter).ops,
-                RateLimiterUpdate::from(new_cfg.tx_rate_limiter).bandwidth,
-                RateLimiterUpdate::from(new_cfg.tx_rate_limiter).ops,
-            )
+            .update_entropy_rate_limiter(rate_limiter.bandwidth, rate_limiter.ops)
             .map(|()| VmmData::Empty)
-            .map_err(NetworkInterfaceError::DeviceUpdate)
-            .map_err(VmmActionError::NetworkConfig)
+            .map_err(EntropyDeviceError::DeviceUpdate)
+            .map_err(VmmActionError::EntropyDevice)
+    }
+
+    /// Updates configuration for an emulated net device as described in `new_cfg`.
+    fn update_net_interface(
+        &mut self,
+        new_cfg: NetworkInterfaceUpdateConfig,
+    ) -> Result<VmmData, VmmActionError> {
+        let mut vmm = self.vmm.lock().expect("Poisoned lock");
+        vmm.update_net_rate_limiters(
+            &new_cfg.iface_id,
+            RateLimiterUpdate::from(new_cfg.rx_rate_limiter).bandwidth,
+            RateLimiterUpdate::from(new_cfg.rx_rate_limiter).ops,
+            RateLimiterUpdate::from(new_cfg.tx_rate_limiter).bandwidth,
+            RateLimiterUpdate::from(new_cfg.tx_rate_limiter).ops,
+        )
+        .and_then(|()| match new_cfg.link_up {
+            Some(up) => vmm.set_net_link_state(&new_cfg.iface_id, up),
+            None => Ok(()),
+        })
+        .map(|()| VmmData::Empty)
+        .map_err(NetworkInterfaceError::DeviceUpdate)
+        .map_err(VmmActionError::NetworkConfig)
+    }
+
+    fn update_rdma_device(
+        &mut self,
+        new_cfg: RdmaDeviceUpdateConfig,
+    ) -> Result<VmmData, VmmActionError> {
+        let mut vmm = self.vmm.lock().expect("Poisoned lock");
+        // The backend is replaced first, as setting it up is what most likely fails.
+        if let Some(backend) = new_cfg.backend {
+            vmm.set_rdma_backend(&new_cfg.id, backend)
+                .map_err(RdmaDeviceError::DeviceUpdate)
+                .map_err(VmmActionError::RdmaDevice)?;
+        }
+        let rate_limiter = RateLimiterUpdate::from(new_cfg.rate_limiter);
+        vmm.update_rdma_rate_limiter(&new_cfg.id, rate_limiter.bandwidth, rate_limiter.ops)
+            .and_then(|()| vmm.set_rdma_cq_moderation(&new_cfg.id, new_cfg.cq_moderation))
+            .map(|()| VmmData::Empty)
+            .map_err(RdmaDeviceError::DeviceUpdate)
+            .map_err(VmmActionError::RdmaDevice)
     }
 }
 
@@ -964,7 +1380,9 @@ mod tests {
     use crate::devices::virtio::block::CacheType;
     use crate::mmds::data_store::MmdsVersion;
     use crate::seccomp::BpfThreadMap;
-    use crate::vmm_config::snapshot::{MemBackendConfig, MemBackendType};
+    use crate::vmm_config::snapshot::{
+        MemBackendConfig, MemBackendType, MemVerification, SnapshotConsistency,
+    };
 
     fn default_preboot<'a>(
         vm_resources: &'a mut VmResources,
@@ -1217,6 +1635,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_mmds_config() {
+        let update =
+            |size_limit| VmmAction::UpdateMmdsConfiguration(MmdsConfigUpdate { size_limit });
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        let filling = (0..60000).map(|_| "X").collect::<String>();
+        let data = "{\"key\": \"".to_string() + &filling + "\"}";
+
+        assert!(matches!(
+            preboot_request_with_mmds(
+                VmmAction::PutMMDS(serde_json::from_str(&data).unwrap()),
+                mmds.clone()
+            ),
+            Err(VmmActionError::MmdsLimitExceeded(_))
+        ));
+        assert_eq!(
+            preboot_request_with_mmds(update(70000), mmds.clone()).unwrap(),
+            VmmData::Empty
+        );
+        assert_eq!(
+            preboot_request_with_mmds(
+                VmmAction::PutMMDS(serde_json::from_str(&data).unwrap()),
+                mmds.clone()
+            )
+            .unwrap(),
+            VmmData::Empty
+        );
+
+        // The limit can be raised after boot, but not below the size of the data store.
+        assert_eq!(
+            runtime_request_with_mmds(update(1 << 20), mmds.clone()).unwrap(),
+            VmmData::Empty
+        );
+        assert_eq!(mmds.lock().unwrap().data_store_limit(), 1 << 20);
+        assert!(matches!(
+            runtime_request_with_mmds(update(1000), mmds.clone()),
+            Err(VmmActionError::Mmds(
+                data_store::MmdsDatastoreError::DataStoreLimitTooSmall(60010)
+            ))
+        ));
+        assert_eq!(
+            runtime_request_with_mmds(VmmAction::GetMMDS, mmds).unwrap(),
+            VmmData::MmdsValue(serde_json::from_str(&data).unwrap())
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_preboot_remove_rdma_device() {
+        let mut vm_resources = VmResources::default();
+        vm_resources
+            .build_rdma_device(RdmaDeviceConfig {
+                id: String::from("rdma0"),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut evmgr = EventManager::new().unwrap();
+        let seccomp_filters = BpfThreadMap::new();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr, &seccomp_filters);
+        let remove = || {
+            VmmAction::RemoveDevice(DeviceRemovalConfig {
+                device_type: RemovableDeviceType::RdmaDevice,
+                id: String::from("rdma0"),
+                timeout_ms: 1000,
+            })
+        };
+
+        // The device is dropped from the configuration, without asking the guest.
+        assert_eq!(
+            preboot.handle_preboot_request(remove()).unwrap(),
+            VmmData::Empty
+        );
+        assert!(matches!(
+            preboot.handle_preboot_request(remove()),
+            Err(VmmActionError::RdmaDevice(RdmaDeviceError::NotFound(id))) if id == "rdma0"
+        ));
+        assert!(vm_resources.rdma.configs().is_empty());
+    }
+
     #[test]
     fn test_preboot_disallowed() {
         fn check_unsupported(res: Result<VmmData, VmmActionError>) {
@@ -1231,6 +1728,11 @@ mod tests {
         check_unsupported(preboot_request(VmmAction::Pause));
         check_unsupported(preboot_request(VmmAction::Resume));
         check_unsupported(preboot_request(VmmAction::GetBalloonStats));
+        check_unsupported(preboot_request(VmmAction::GetCpuConfiguration));
+        check_unsupported(preboot_request(VmmAction::GetVsockConnections));
+        check_unsupported(preboot_request(VmmAction::GetResourceUsage));
+        check_unsupported(preboot_request(VmmAction::GetNetworkFlows(String::new())));
+        check_unsupported(preboot_request(VmmAction::GetRdmaDevice(String::new())));
         check_unsupported(preboot_request(VmmAction::UpdateBalloon(
             BalloonUpdateConfig { amount_mib: 0 },
         )));
@@ -1247,22 +1749,45 @@ mod tests {
         check_unsupported(preboot_request(VmmAction::UpdateBlockDevice(
             BlockDeviceUpdateConfig::default(),
         )));
+        check_unsupported(preboot_request(VmmAction::UpdateEntropyDevice(
+            EntropyDeviceUpdateConfig::default(),
+        )));
         check_unsupported(preboot_request(VmmAction::UpdateNetworkInterface(
             NetworkInterfaceUpdateConfig {
                 iface_id: String::new(),
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                link_up: None,
             },
         )));
+        check_unsupported(preboot_request(VmmAction::UpdateRdmaDevice(
+            RdmaDeviceUpdateConfig::default(),
+        )));
         check_unsupported(preboot_request(VmmAction::CreateSnapshot(
             CreateSnapshotParams {
                 snapshot_type: SnapshotType::Full,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                memory_digests: false,
+                consistency: SnapshotConsistency::Pause,
+                snapshot_fd: None,
+                mem_file_fd: None,
             },
         )));
         #[cfg(target_arch = "x86_64")]
         check_unsupported(preboot_request(VmmAction::SendCtrlAltDel));
+        #[cfg(target_arch = "x86_64")]
+        check_unsupported(preboot_request(VmmAction::GracefulShutdown(
+            GracefulShutdownConfig::default(),
+        )));
+        #[cfg(target_arch = "x86_64")]
+        check_unsupported(preboot_request(VmmAction::RemoveDevice(
+            DeviceRemovalConfig {
+                device_type: RemovableDeviceType::Drive,
+                id: String::from("rootfs"),
+                timeout_ms: 1000,
+            },
+        )));
         check_unsupported(preboot_request(VmmAction::UpdateMemoryHotplugSize(
             MemoryHotplugSizeUpdate {
                 requested_size_mib: 0,
@@ -1297,6 +1822,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_runtime_get_cpu_configuration() {
+        // The configuration of a microVM without vCPUs cannot be dumped.
+        assert!(matches!(
+            runtime_request(VmmAction::GetCpuConfiguration),
+            Err(VmmActionError::DumpCpuConfig(
+                DumpCpuConfigError::UnexpectedResponse
+            ))
+        ));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_runtime_insert_rdma_device() {
+        use crate::device_manager::hotplug::HotplugError;
+
+        // Devices can only be added to microVMs using PCI.
+        assert!(matches!(
+            runtime_request(VmmAction::InsertRdmaDevice(RdmaDeviceConfig {
+                id: String::from("rdma0"),
+                ..Default::default()
+            })),
+            Err(VmmActionError::RdmaDevice(RdmaDeviceError::Insertion(
+                VmmError::DeviceInsertion(HotplugError::Unsupported)
+            )))
+        ));
+    }
+
     #[test]
     fn test_runtime_disallowed() {
         fn check_unsupported(res: Result<VmmData, VmmActionError>) {
@@ -1310,6 +1863,12 @@ mod tests {
         check_unsupported(runtime_request(VmmAction::ConfigureBootSource(
             BootSourceConfig::default(),
         )));
+        check_unsupported(runtime_request(VmmAction::ConfigureFirmware(
+            FirmwareConfig::default(),
+        )));
+        check_unsupported(runtime_request(VmmAction::ConfigureSmbios(
+            SmbiosConfig::default(),
+        )));
         check_unsupported(runtime_request(VmmAction::ConfigureLogger(LoggerConfig {
             log_path: Some(PathBuf::new()),
             level: Some(crate::logger::LevelFilter::Debug),
@@ -1331,8 +1890,14 @@ mod tests {
 
                 is_read_only: Some(false),
                 path_on_host: Some(String::new()),
+                backing_fd: None,
                 rate_limiter: None,
                 file_engine_type: None,
+                verity: None,
+                shared_page_cache: None,
+                strict_ordering: None,
+                host_cache: None,
+                nbd_uri: None,
 
                 socket: None,
             },
@@ -1344,6 +1909,8 @@ mod tests {
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                flow_sampling: None,
+                tap_fd: None,
             },
         )));
         check_unsupported(runtime_request(VmmAction::SetVsockDevice(
@@ -1351,6 +1918,7 @@ mod tests {
                 vsock_id: Some(String::new()),
                 guest_cid: 0,
                 uds_path: String::new(),
+                io_engine: None,
             },
         )));
         check_unsupported(runtime_request(VmmAction::SetBalloonDevice(
@@ -1361,6 +1929,7 @@ mod tests {
                 vsock_id: Some(String::new()),
                 guest_cid: 0,
                 uds_path: String::new(),
+                io_engine: None,
             },
         )));
         check_unsupported(runtime_request(VmmAction::SetMmdsConfiguration(
@@ -1369,6 +1938,7 @@ mod tests {
                 version: MmdsVersion::default(),
                 network_interfaces: Vec::new(),
                 imds_compat: false,
+                rate_limiter: None,
             },
         )));
         check_unsupported(runtime_request(VmmAction::UpdateMachineConfiguration(
@@ -1384,22 +1954,76 @@ mod tests {
                 track_dirty_pages: false,
                 resume_vm: false,
                 network_overrides: vec![],
+                mem_verification: MemVerification::Length,
+                rdma_allow_degraded_restore: false,
             },
         )));
         check_unsupported(runtime_request(VmmAction::SetEntropyDevice(
             EntropyDeviceConfig::default(),
         )));
+        check_unsupported(runtime_request(VmmAction::SetFwCfgDevice(
+            FwCfgConfig::default(),
+        )));
+        check_unsupported(runtime_request(VmmAction::SetConfigDrive(
+            ConfigDriveConfig::default(),
+        )));
         check_unsupported(runtime_request(VmmAction::InsertPmemDevice(PmemConfig {
             id: String::new(),
             path_on_host: String::new(),
             root_device: false,
             read_only: false,
         })));
-        check_unsupported(runtime_request(VmmAction::InsertRdmaDevice(RdmaDeviceConfig {
-            id: String::new(),
-        })));
+        check_unsupported(runtime_request(VmmAction::InsertP9Device(
+            P9Config::default(),
+        )));
+        check_unsupported(runtime_request(VmmAction::InsertGpioDevice(
+            GpioConfig::default(),
+        )));
+        check_unsupported(runtime_request(VmmAction::InsertI2cDevice(
+            I2cConfig::default(),
+        )));
+        #[cfg(target_arch = "aarch64")]
+        check_unsupported(runtime_request(VmmAction::InsertRdmaDevice(
+            RdmaDeviceConfig::default(),
+        )));
         check_unsupported(runtime_request(VmmAction::SetMemoryHotplugDevice(
             MemoryHotplugConfig::default(),
         )));
+        check_unsupported(runtime_request(VmmAction::SetColdMemory(
+            ColdMemoryConfig {
+                backing_file_path: PathBuf::new(),
+                pressure_stall_us: 150_000,
+                pressure_window_us: 1_000_000,
+                evict_batch_mib: 64,
+            },
+        )));
+        check_unsupported(runtime_request(VmmAction::SetIoCpuBudget(
+            IoCpuBudgetConfig {
+                cpu_percent: 50,
+                period_ms: 100,
+            },
+        )));
+        check_unsupported(runtime_request(VmmAction::SetInterruptAffinity(
+            InterruptAffinityConfig {
+                device_id: "eth0".to_string(),
+                queue_vcpus: vec![0],
+            },
+        )));
+        check_unsupported(runtime_request(VmmAction::SetMemoryBacking(
+            MemoryBackingConfig {
+                path: PathBuf::new(),
+                writeback_interval_ms: None,
+                flush_on_pause: false,
+            },
+        )));
+        check_unsupported(runtime_request(VmmAction::SetSnapshotAgent(
+            SnapshotAgentConfig {
+                port: 5000,
+                snapshot_type: SnapshotType::Full,
+                snapshot_path: PathBuf::new(),
+                mem_file_path: PathBuf::new(),
+                freeze_timeout_ms: 1000,
+            },
+        )));
     }
 }
@@ -43,6 +43,8 @@ pub fn get_empty_filters() -> BpfThreadMap {
     map.insert("vmm".to_string(), Arc::new(vec![]));
     map.insert("api".to_string(), Arc::new(vec![]));
     map.insert("vcpu".to_string(), Arc::new(vec![]));
+    map.insert("rdma".to_string(), Arc::new(vec![]));
+    map.insert("rdma_launcher".to_string(), Arc::new(vec![]));
     map
 }
 
@@ -0,0 +1,272 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Digests recorded in snapshots, used to detect truncated or corrupted snapshot files when they
+//! are loaded, instead of restoring a microVM in an undefined state.
+//!
+//! The state file holds the SHA-256 digests of each of its sections, which pinpoint the corrupted
+//! section when the CRC64 of the file does not match. It also holds the length of the memory file
+//! and, optionally, the SHA-256 digests of each of its chunks of [`MEMORY_CHUNK_SIZE`] bytes.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+use aws_lc_rs::digest::{SHA256, SHA256_OUTPUT_LEN, digest};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::{SnapshotError, serialize};
+use crate::utils::u64_to_usize;
+use crate::vmm_config::snapshot::MemVerification;
+
+/// Size of the chunks of the memory file covered by a digest.
+pub const MEMORY_CHUNK_SIZE: u64 = 4 << 20;
+
+/// Number of chunks of the memory file checked by [`MemVerification::Sampled`].
+const SAMPLED_CHUNKS: usize = 32;
+
+/// SHA-256 digest.
+pub type Sha256Digest = [u8; SHA256_OUTPUT_LEN];
+
+fn sha256(data: &[u8]) -> Sha256Digest {
+    let mut sha256 = [0u8; SHA256_OUTPUT_LEN];
+    sha256.copy_from_slice(digest(&SHA256, data).as_ref());
+    sha256
+}
+
+/// Errors associated with the integrity of snapshot files.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum SnapshotIntegrityError {
+    /// Cannot read the memory file: {0}
+    Io(#[from] io::Error),
+    /// The memory file is {actual} bytes long while the snapshot expects {expected} bytes.
+    MemoryFileLength {
+        /// Length recorded in the snapshot.
+        expected: u64,
+        /// Length of the memory file.
+        actual: u64,
+    },
+    /// The memory file is corrupted at offset {0:#x}.
+    MemoryFileDigest(u64),
+    /// The snapshot holds no digest of the memory file, it was not created with `memory_digests`.
+    NoMemoryDigests,
+    /// The `{0}` section of the snapshot state is corrupted.
+    StateSection(String),
+}
+
+/// Digest of a section of the snapshot state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionDigest {
+    /// Name of the section.
+    pub name: String,
+    /// Digest of the serialized section.
+    pub digest: Sha256Digest,
+}
+
+impl SectionDigest {
+    /// Computes the digest of a section of the snapshot state.
+    pub fn new<S: Serialize>(name: &str, section: &S) -> Result<Self, SnapshotError> {
+        let mut buf = Vec::new();
+        serialize(section, &mut buf)?;
+        Ok(Self {
+            name: name.to_string(),
+            digest: sha256(&buf),
+        })
+    }
+}
+
+/// Length and digests of a memory file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryFileDigests {
+    /// Length of the memory file.
+    pub len: u64,
+    /// Digests of the consecutive chunks of the memory file, empty when they were not recorded.
+    pub chunks: Vec<Sha256Digest>,
+}
+
+impl MemoryFileDigests {
+    /// Records the length of the memory file, along with the digests of its chunks when
+    /// `with_digests` is set.
+    pub fn new(file: &File, with_digests: bool) -> Result<Self, SnapshotIntegrityError> {
+        let len = file.metadata()?.len();
+        let mut chunks = Vec::new();
+        if with_digests {
+            let mut buf = Vec::new();
+            for index in 0..u64_to_usize(len.div_ceil(MEMORY_CHUNK_SIZE)) {
+                chunks.push(chunk_digest(file, len, index, &mut buf)?);
+            }
+        }
+        Ok(Self { len, chunks })
+    }
+
+    /// Checks the memory file against the recorded length and digests.
+    pub fn verify(
+        &self,
+        file: &File,
+        verification: MemVerification,
+    ) -> Result<(), SnapshotIntegrityError> {
+        let len = file.metadata()?.len();
+        if len != self.len {
+            return Err(SnapshotIntegrityError::MemoryFileLength {
+                expected: self.len,
+                actual: len,
+            });
+        }
+
+        let count = self.chunks.len();
+        let mut indices: Vec<usize> = match verification {
+            MemVerification::Length => return Ok(()),
+            _ if count == 0 => return Err(SnapshotIntegrityError::NoMemoryDigests),
+            // Chunks evenly spread over the file, along with the last one.
+            MemVerification::Sampled => (0..count)
+                .step_by((count / SAMPLED_CHUNKS).max(1))
+                .chain([count - 1])
+                .collect(),
+            MemVerification::Full => (0..count).collect(),
+        };
+        indices.dedup();
+
+        let mut buf = Vec::new();
+        for index in indices {
+            if chunk_digest(file, len, index, &mut buf)? != self.chunks[index] {
+                return Err(SnapshotIntegrityError::MemoryFileDigest(
+                    index as u64 * MEMORY_CHUNK_SIZE,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn chunk_digest(
+    file: &File,
+    len: u64,
+    index: usize,
+    buf: &mut Vec<u8>,
+) -> Result<Sha256Digest, io::Error> {
+    let offset = index as u64 * MEMORY_CHUNK_SIZE;
+    buf.resize(u64_to_usize(MEMORY_CHUNK_SIZE.min(len - offset)), 0);
+    file.read_exact_at(buf, offset)?;
+    Ok(sha256(buf))
+}
+
+/// Digests recorded in a snapshot.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotIntegrity {
+    /// Digests of the sections of the snapshot state.
+    pub sections: Vec<SectionDigest>,
+    /// Length and digests of the memory file.
+    pub memory: MemoryFileDigests,
+}
+
+impl SnapshotIntegrity {
+    /// Checks the digests of the sections of the snapshot state against the recorded ones.
+    pub fn verify_sections(
+        &self,
+        sections: &[SectionDigest],
+    ) -> Result<(), SnapshotIntegrityError> {
+        match self
+            .sections
+            .iter()
+            .zip(sections)
+            .find(|(recorded, computed)| recorded != computed)
+        {
+            Some((_, computed)) => Err(SnapshotIntegrityError::StateSection(computed.name.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn memory_file(len: usize) -> TempFile {
+        let file = TempFile::new().unwrap();
+        let data: Vec<u8> = (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect();
+        file.as_file().write_all(&data).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_memory_file_length() {
+        let file = memory_file(0x1000);
+        let digests = MemoryFileDigests::new(file.as_file(), false).unwrap();
+        assert_eq!(digests.len, 0x1000);
+        assert!(digests.chunks.is_empty());
+        digests
+            .verify(file.as_file(), MemVerification::Length)
+            .unwrap();
+
+        // Digests are needed to verify the content.
+        assert!(matches!(
+            digests.verify(file.as_file(), MemVerification::Sampled),
+            Err(SnapshotIntegrityError::NoMemoryDigests)
+        ));
+
+        file.as_file().set_len(0x800).unwrap();
+        assert!(matches!(
+            digests.verify(file.as_file(), MemVerification::Length),
+            Err(SnapshotIntegrityError::MemoryFileLength {
+                expected: 0x1000,
+                actual: 0x800
+            })
+        ));
+    }
+
+    #[test]
+    fn test_memory_file_digests() {
+        // Three chunks, the last one being partial.
+        let len = 2 * MEMORY_CHUNK_SIZE + 0x1000;
+        let file = memory_file(u64_to_usize(len));
+        let digests = MemoryFileDigests::new(file.as_file(), true).unwrap();
+        assert_eq!(digests.len, len);
+        assert_eq!(digests.chunks.len(), 3);
+        for verification in [
+            MemVerification::Length,
+            MemVerification::Sampled,
+            MemVerification::Full,
+        ] {
+            digests.verify(file.as_file(), verification).unwrap();
+        }
+
+        // Corrupting the last chunk is caught by both the sampled and the full verification.
+        file.as_file().write_all_at(&[0xff], len - 1).unwrap();
+        for verification in [MemVerification::Sampled, MemVerification::Full] {
+            assert!(matches!(
+                digests.verify(file.as_file(), verification),
+                Err(SnapshotIntegrityError::MemoryFileDigest(offset))
+                    if offset == 2 * MEMORY_CHUNK_SIZE
+            ));
+        }
+        digests
+            .verify(file.as_file(), MemVerification::Length)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_verify_sections() {
+        let sections = vec![
+            SectionDigest::new("foo", &1u64).unwrap(),
+            SectionDigest::new("bar", &vec![1u8, 2, 3]).unwrap(),
+        ];
+        let integrity = SnapshotIntegrity {
+            sections: sections.clone(),
+            memory: MemoryFileDigests::default(),
+        };
+        integrity.verify_sections(&sections).unwrap();
+
+        let corrupted = vec![
+            sections[0].clone(),
+            SectionDigest::new("bar", &vec![1u8, 2, 4]).unwrap(),
+        ];
+        assert!(matches!(
+            integrity.verify_sections(&corrupted),
+            Err(SnapshotIntegrityError::StateSection(name)) if name == "bar"
+        ));
+    }
+}
@@ -20,10 +20,13 @@
 //!  |        optional CRC64       |
 //!  |-----------------------------|
 //!
+//! The state of a microVM also holds the digests of its sections and of the memory file, see
+//! [`integrity`].
 //!
 //! The snapshot format uses a version value in the form of `MAJOR.MINOR.PATCH`. The version is
 //! provided by the library clients (it is not tied to this crate).
 pub mod crc;
+pub mod integrity;
 mod persist;
 use std::fmt::Debug;
 use std::io::{Read, Write};
@@ -0,0 +1,375 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Channel through which a cooperating guest agent requests application-consistent snapshots.
+//!
+//! The agent connects to a host vsock port, which the vsock device forwards to the
+//! `<uds_path>_<port>` Unix socket the VMM listens on. The agent and the VMM then exchange text
+//! lines:
+//! - the agent sends `SNAPSHOT` to request a snapshot;
+//! - the VMM answers `FREEZE`, asking the agent to freeze the guest filesystems;
+//! - the agent sends `FROZEN` once they are frozen;
+//! - the VMM pauses the microVM, writes the snapshot and resumes the microVM, then answers `OK`,
+//!   or `FAILED` if the snapshot could not be written or if the agent did not freeze the
+//!   filesystems in time. The agent then thaws the filesystems.
+//!
+//! The agent must also thaw the filesystems when the connection is lost, which is what happens
+//! in a microVM restored from the snapshot, since vsock connections don't survive snapshots.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+
+use event_manager::{EventOps, Events};
+use utils::time::TimerFd;
+use vmm_sys_util::epoll::EventSet;
+
+use crate::logger::{IncMetric, METRICS, debug, error, info, warn};
+use crate::persist::VmInfo;
+use crate::vmm_config::snapshot::{CreateSnapshotParams, SnapshotAgentConfig};
+
+/// Longest line accepted from the guest agent.
+const MAX_LINE_LEN: usize = 64;
+
+/// Errors associated with the snapshot agent channel.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum SnapshotAgentError {
+    /// The snapshot agent requires a vsock device
+    MissingVsock,
+    /// Cannot listen on the snapshot agent socket: {0}
+    Bind(io::Error),
+}
+
+/// A line sent by the guest agent.
+#[derive(Debug, PartialEq, Eq)]
+enum AgentRequest {
+    /// The agent requests a snapshot.
+    Snapshot,
+    /// The agent froze the guest filesystems.
+    Frozen,
+}
+
+impl AgentRequest {
+    fn parse(line: &[u8]) -> Option<Self> {
+        match line {
+            b"SNAPSHOT" => Some(Self::Snapshot),
+            b"FROZEN" => Some(Self::Frozen),
+            _ => None,
+        }
+    }
+}
+
+/// The host side of the snapshot agent channel.
+#[derive(Debug)]
+pub struct SnapshotAgent {
+    config: SnapshotAgentConfig,
+    vm_info: VmInfo,
+    listener: UnixListener,
+    // Connection of the guest agent. A new connection replaces the previous one.
+    stream: Option<UnixStream>,
+    // Bytes received after the last complete line.
+    pending: Vec<u8>,
+    // Bounds the time left to the guest agent to freeze the filesystems.
+    freeze_timer: TimerFd,
+    freezing: bool,
+}
+
+impl SnapshotAgent {
+    /// Listens for the guest agent on the socket the vsock device forwards the connections to
+    /// the configured port to.
+    pub fn new(
+        config: SnapshotAgentConfig,
+        vm_info: VmInfo,
+        uds_path: &str,
+    ) -> Result<Self, SnapshotAgentError> {
+        let path = format!("{}_{}", uds_path, config.port);
+        // A socket left behind by a previous microVM would prevent binding.
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(SnapshotAgentError::Bind)?;
+        info!("Listening for the snapshot agent on {}", path);
+
+        Ok(Self {
+            config,
+            vm_info,
+            listener,
+            stream: None,
+            pending: Vec::new(),
+            freeze_timer: TimerFd::new(),
+            freezing: false,
+        })
+    }
+
+    /// Registers the file descriptors of the channel.
+    pub fn register(&self, ops: &mut EventOps) {
+        if let Err(err) = ops.add(Events::new(&self.listener, EventSet::IN)) {
+            error!("Failed to register the snapshot agent listener: {}", err);
+        }
+        if let Err(err) = ops.add(Events::new(&self.freeze_timer, EventSet::IN)) {
+            error!(
+                "Failed to register the snapshot agent freeze timer: {}",
+                err
+            );
+        }
+    }
+
+    /// Whether `fd` belongs to the channel.
+    pub fn owns(&self, fd: RawFd) -> bool {
+        fd == self.listener.as_raw_fd()
+            || fd == self.freeze_timer.as_raw_fd()
+            || self.stream.as_ref().is_some_and(|s| fd == s.as_raw_fd())
+    }
+
+    /// Information about the microVM recorded in the snapshots.
+    pub fn vm_info(&self) -> &VmInfo {
+        &self.vm_info
+    }
+
+    /// Parameters of the snapshots requested by the guest agent.
+    pub fn create_params(&self) -> CreateSnapshotParams {
+        self.config.create_params()
+    }
+
+    /// Handles an event on one of the file descriptors of the channel. Returns whether the guest
+    /// filesystems are frozen, and the snapshot should be written.
+    pub fn process(&mut self, fd: RawFd, ops: &mut EventOps) -> bool {
+        if fd == self.listener.as_raw_fd() {
+            if let Some(stream) = self.accept() {
+                if let Err(err) = ops.add(Events::new(&stream, EventSet::IN)) {
+                    warn!("Failed to register the snapshot agent connection: {}", err);
+                    return false;
+                }
+                self.disconnect(ops);
+                self.stream = Some(stream);
+            }
+            false
+        } else if fd == self.freeze_timer.as_raw_fd() {
+            self.handle_freeze_timeout();
+            false
+        } else {
+            self.read_requests().unwrap_or_else(|| {
+                self.disconnect(ops);
+                false
+            })
+        }
+    }
+
+    fn accept(&mut self) -> Option<UnixStream> {
+        match self
+            .listener
+            .accept()
+            .and_then(|(stream, _)| stream.set_nonblocking(true).map(|()| stream))
+        {
+            Ok(stream) => {
+                debug!("The snapshot agent connected");
+                Some(stream)
+            }
+            Err(err) => {
+                warn!("Failed to accept the snapshot agent connection: {}", err);
+                None
+            }
+        }
+    }
+
+    fn disconnect(&mut self, ops: &mut EventOps) {
+        if let Some(stream) = self.stream.take() {
+            let _ = ops.remove(Events::new(&stream, EventSet::IN));
+        }
+        self.pending.clear();
+        if self.freezing {
+            warn!("The snapshot agent disconnected while freezing the guest filesystems.");
+            self.freezing = false;
+            self.freeze_timer.arm(Duration::ZERO, None);
+            METRICS.snapshot_agent.snapshot_fails.inc();
+        }
+    }
+
+    fn handle_freeze_timeout(&mut self) {
+        self.freeze_timer.read();
+        if self.freezing {
+            warn!("The snapshot agent did not freeze the guest filesystems in time.");
+            METRICS.snapshot_agent.freeze_timeouts.inc();
+            self.complete(false);
+        }
+    }
+
+    /// Reads the lines sent by the guest agent. Returns whether it froze the filesystems, or
+    /// `None` if the connection must be closed.
+    fn read_requests(&mut self) -> Option<bool> {
+        let stream = self.stream.as_mut()?;
+        let mut buf = [0u8; MAX_LINE_LEN];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    debug!("The snapshot agent disconnected");
+                    return None;
+                }
+                Ok(len) => self.pending.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Failed to read from the snapshot agent: {}", err);
+                    return None;
+                }
+            }
+        }
+
+        let mut frozen = false;
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).take(end).collect();
+            match AgentRequest::parse(&line) {
+                Some(AgentRequest::Snapshot) if self.freezing => {
+                    warn!("The snapshot agent requested a snapshot while one is in progress.");
+                }
+                Some(AgentRequest::Snapshot) => {
+                    METRICS.snapshot_agent.requests.inc();
+                    self.freezing = true;
+                    self.freeze_timer.arm(self.config.freeze_timeout(), None);
+                    self.send(b"FREEZE\n");
+                }
+                Some(AgentRequest::Frozen) if self.freezing => {
+                    self.freeze_timer.arm(Duration::ZERO, None);
+                    frozen = true;
+                }
+                Some(AgentRequest::Frozen) => {
+                    warn!("The snapshot agent froze the guest filesystems unasked.");
+                }
+                None => warn!(
+                    "Invalid request from the snapshot agent: {}",
+                    String::from_utf8_lossy(&line)
+                ),
+            }
+        }
+        if self.pending.len() > MAX_LINE_LEN {
+            warn!("The snapshot agent sent a line too long, closing the connection.");
+            return None;
+        }
+        Some(frozen)
+    }
+
+    /// Reports the outcome of the snapshot to the guest agent, which then thaws the filesystems.
+    pub fn complete(&mut self, success: bool) {
+        self.freezing = false;
+        if success {
+            METRICS.snapshot_agent.snapshots.inc();
+            self.send(b"OK\n");
+        } else {
+            METRICS.snapshot_agent.snapshot_fails.inc();
+            self.send(b"FAILED\n");
+        }
+    }
+
+    fn send(&mut self, line: &[u8]) {
+        if let Some(stream) = self.stream.as_mut()
+            && let Err(err) = stream.write_all(line)
+        {
+            warn!("Failed to write to the snapshot agent: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+    use crate::vmm_config::snapshot::SnapshotType;
+
+    fn agent(freeze_timeout_ms: u64) -> (SnapshotAgent, String) {
+        let uds_path = TempFile::new()
+            .unwrap()
+            .as_path()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let config = SnapshotAgentConfig {
+            port: 5000,
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::from("vm.snap"),
+            mem_file_path: PathBuf::from("vm.mem"),
+            freeze_timeout_ms,
+        };
+        let agent = SnapshotAgent::new(config, VmInfo::default(), &uds_path).unwrap();
+        (agent, format!("{}_5000", uds_path))
+    }
+
+    fn read_line(stream: &mut UnixStream) -> String {
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+        while byte[0] != b'\n' {
+            stream.read_exact(&mut byte).unwrap();
+            line.push(byte[0]);
+        }
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            AgentRequest::parse(b"SNAPSHOT"),
+            Some(AgentRequest::Snapshot)
+        );
+        assert_eq!(AgentRequest::parse(b"FROZEN"), Some(AgentRequest::Frozen));
+        assert_eq!(AgentRequest::parse(b"snapshot"), None);
+        assert_eq!(AgentRequest::parse(b""), None);
+    }
+
+    #[test]
+    fn test_snapshot_agent() {
+        let (mut agent, path) = agent(10_000);
+        let mut client = UnixStream::connect(&path).unwrap();
+        agent.stream = agent.accept();
+        assert!(agent.owns(agent.stream.as_ref().unwrap().as_raw_fd()));
+
+        // A frozen notification without a request is ignored.
+        client.write_all(b"FROZEN\n").unwrap();
+        assert_eq!(agent.read_requests(), Some(false));
+
+        // The snapshot is written once the filesystems are frozen.
+        client.write_all(b"SNAP").unwrap();
+        assert_eq!(agent.read_requests(), Some(false));
+        client.write_all(b"SHOT\n").unwrap();
+        assert_eq!(agent.read_requests(), Some(false));
+        assert_eq!(read_line(&mut client), "FREEZE\n");
+        assert!(agent.freeze_timer.is_armed());
+        client.write_all(b"FROZEN\n").unwrap();
+        assert_eq!(agent.read_requests(), Some(true));
+        assert!(!agent.freeze_timer.is_armed());
+        agent.complete(true);
+        assert_eq!(read_line(&mut client), "OK\n");
+
+        // Lines that are too long close the connection, and so does the guest agent.
+        client.write_all(&[b'A'; 2 * MAX_LINE_LEN]).unwrap();
+        assert_eq!(agent.read_requests(), None);
+        agent.pending.clear();
+        drop(client);
+        assert_eq!(agent.read_requests(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_agent_freeze_timeout() {
+        let (mut agent, path) = agent(1);
+        let mut client = UnixStream::connect(&path).unwrap();
+        agent.stream = agent.accept();
+        client.write_all(b"SNAPSHOT\n").unwrap();
+        assert_eq!(agent.read_requests(), Some(false));
+        assert_eq!(read_line(&mut client), "FREEZE\n");
+
+        std::thread::sleep(Duration::from_millis(10));
+        let timeouts = METRICS.snapshot_agent.freeze_timeouts.count();
+        agent.handle_freeze_timeout();
+        assert_eq!(read_line(&mut client), "FAILED\n");
+        assert_eq!(METRICS.snapshot_agent.freeze_timeouts.count(), timeouts + 1);
+
+        // Freezing too late doesn't trigger the snapshot.
+        client.write_all(b"FROZEN\n").unwrap();
+        assert_eq!(agent.read_requests(), Some(false));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,426 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builder of small FAT12 images holding a flat list of read-only files, with VFAT long file
+//! names. The layout follows what `mkfs.fat` produces for a floppy: one reserved sector, two
+//! copies of the allocation table, a fixed size root directory and the data clusters, each file
+//! being stored in contiguous clusters.
+
+const SECTOR_SIZE: usize = 512;
+const DIR_ENTRY_SIZE: usize = 32;
+/// Number of entries of the root directory, filling 4 sectors.
+const ROOT_DIR_ENTRIES: usize = 64;
+/// Largest number of clusters of a FAT12 file system.
+const MAX_CLUSTERS: usize = 4084;
+/// Largest number of sectors per cluster.
+const MAX_SECTORS_PER_CLUSTER: usize = 64;
+/// Characters held by a long file name entry.
+const LFN_CHARS_PER_ENTRY: usize = 13;
+/// Longest file name accepted.
+pub const FAT_MAX_NAME_LEN: usize = 255;
+
+const MEDIA_FIXED_DISK: u8 = 0xf8;
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const LFN_LAST_ENTRY: u8 = 0x40;
+const FAT12_END_OF_CHAIN: u16 = 0xfff;
+/// Timestamps are fixed to 1980-01-01 00:00:00, so that images only depend on their content.
+const FAT_DATE: u16 = (1 << 5) | 1;
+
+/// Errors building a FAT image.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum FatImageError {
+    /// Invalid file name `{0}`: names are made of up to 255 printable ASCII characters, other than `/`.
+    InvalidName(String),
+    /// Duplicate file name `{0}`.
+    DuplicateName(String),
+    /// Invalid volume label `{0}`: labels are made of up to 11 upper case letters, digits, `-` or `_`.
+    InvalidLabel(String),
+    /// The files do not fit in the root directory.
+    TooManyFiles,
+    /// The files do not fit in a FAT12 file system.
+    TooLarge,
+}
+
+/// File stored at the root of a FAT image.
+#[derive(Debug)]
+pub struct FatFile<'a> {
+    /// Long file name.
+    pub name: &'a str,
+    /// Content of the file.
+    pub data: &'a [u8],
+}
+
+fn validate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= FAT_MAX_NAME_LEN
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b'/')
+        && !matches!(name, "." | "..")
+}
+
+/// Builds the 8.3 name of the `index`th file, as `NAME~N`, without extension.
+fn short_name(name: &str, index: usize) -> [u8; 11] {
+    let tail = format!("~{}", index + 1);
+    let mut short = [b' '; 11];
+    let base = name
+        .bytes()
+        .map(|byte| byte.to_ascii_uppercase())
+        .filter(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'()-@^_`{}~".contains(byte))
+        .take(8 - tail.len());
+    for (dst, src) in short.iter_mut().zip(base.chain(tail.bytes())) {
+        *dst = src;
+    }
+    short
+}
+
+fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name.iter().fold(0u8, |sum, byte| {
+        (sum >> 1).wrapping_add((sum & 1) << 7).wrapping_add(*byte)
+    })
+}
+
+/// Builds the long file name entries of `name`, in the order they are stored on disk.
+fn long_name_entries(name: &str, checksum: u8) -> Vec<[u8; DIR_ENTRY_SIZE]> {
+    // The name is NUL terminated unless it fills its last entry, and padded with 0xffff.
+    let mut chars: Vec<u16> = name.bytes().map(u16::from).collect();
+    if !chars.len().is_multiple_of(LFN_CHARS_PER_ENTRY) {
+        chars.push(0);
+    }
+    while !chars.len().is_multiple_of(LFN_CHARS_PER_ENTRY) {
+        chars.push(0xffff);
+    }
+
+    let count = chars.len() / LFN_CHARS_PER_ENTRY;
+    let mut entries: Vec<_> = chars
+        .chunks(LFN_CHARS_PER_ENTRY)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut entry = [0u8; DIR_ENTRY_SIZE];
+            // The sequence numbers are below 20, as names are shorter than 256 characters.
+            entry[0] = u8::try_from(i + 1).unwrap();
+            if i + 1 == count {
+                entry[0] |= LFN_LAST_ENTRY;
+            }
+            entry[11] = ATTR_LONG_NAME;
+            entry[13] = checksum;
+            let offsets = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2));
+            for (offset, char) in offsets.zip(chunk) {
+                entry[offset..offset + 2].copy_from_slice(&char.to_le_bytes());
+            }
+            entry
+        })
+        .collect();
+    entries.reverse();
+    entries
+}
+
+fn short_entry(name: &[u8; 11], attr: u8, cluster: u16, size: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let mut entry = [0u8; DIR_ENTRY_SIZE];
+    entry[..11].copy_from_slice(name);
+    entry[11] = attr;
+    for offset in [16, 18, 24] {
+        entry[offset..offset + 2].copy_from_slice(&FAT_DATE.to_le_bytes());
+    }
+    entry[26..28].copy_from_slice(&cluster.to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// Sets the 12 bit allocation table entry of `cluster`.
+fn set_fat12_entry(fat: &mut [u8], cluster: usize, value: u16) {
+    let offset = cluster + cluster / 2;
+    let [low, high] = value.to_le_bytes();
+    if cluster.is_multiple_of(2) {
+        fat[offset] = low;
+        fat[offset + 1] = (fat[offset + 1] & 0xf0) | (high & 0x0f);
+    } else {
+        fat[offset] = (fat[offset] & 0x0f) | (low << 4);
+        fat[offset + 1] = (high << 4) | (low >> 4);
+    }
+}
+
+/// Builds a FAT12 image with the volume label `label`, holding `files` in its root directory.
+pub fn build_fat12_image(label: &str, files: &[FatFile]) -> Result<Vec<u8>, FatImageError> {
+    if label.is_empty()
+        || label.len() > 11
+        || !label
+            .bytes()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || b"-_".contains(&byte))
+    {
+        return Err(FatImageError::InvalidLabel(label.to_string()));
+    }
+    for (i, file) in files.iter().enumerate() {
+        if !validate_name(file.name) {
+            return Err(FatImageError::InvalidName(file.name.to_string()));
+        }
+        if files[..i]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(file.name))
+        {
+            return Err(FatImageError::DuplicateName(file.name.to_string()));
+        }
+    }
+
+    // The volume label, then the long and short entries of each file.
+    let dir_entries = 1 + files
+        .iter()
+        .map(|file| file.name.len().div_ceil(LFN_CHARS_PER_ENTRY) + 1)
+        .sum::<usize>();
+    if dir_entries > ROOT_DIR_ENTRIES {
+        return Err(FatImageError::TooManyFiles);
+    }
+
+    // Use the smallest clusters that keep the file system within the FAT12 limits.
+    let clusters_for = |cluster_size: usize| {
+        files
+            .iter()
+            .map(|file| file.data.len().div_ceil(cluster_size))
+            .sum::<usize>()
+    };
+    let sectors_per_cluster = std::iter::successors(Some(1usize), |spc| Some(spc * 2))
+        .take_while(|spc| *spc <= MAX_SECTORS_PER_CLUSTER)
+        .find(|spc| clusters_for(spc * SECTOR_SIZE) <= MAX_CLUSTERS)
+        .ok_or(FatImageError::TooLarge)?;
+    let cluster_size = sectors_per_cluster * SECTOR_SIZE;
+    // Keep at least one data cluster, as empty file systems are rejected by some tools.
+    let clusters = clusters_for(cluster_size).max(1);
+
+    // Two reserved entries precede the first data cluster, and entries are 12 bits wide.
+    let fat_sectors = ((clusters + 2) * 3).div_ceil(2).div_ceil(SECTOR_SIZE);
+    let root_dir_sectors = ROOT_DIR_ENTRIES * DIR_ENTRY_SIZE / SECTOR_SIZE;
+    let data_start = (1 + 2 * fat_sectors + root_dir_sectors) * SECTOR_SIZE;
+    let total_sectors = data_start / SECTOR_SIZE + clusters * sectors_per_cluster;
+    let mut image = vec![0u8; total_sectors * SECTOR_SIZE];
+
+    let mut volume_label = [b' '; 11];
+    volume_label[..label.len()].copy_from_slice(label.as_bytes());
+
+    // Boot sector, with the BIOS parameter block of the file system.
+    let boot = &mut image[..SECTOR_SIZE];
+    boot[..3].copy_from_slice(&[0xeb, 0x3c, 0x90]);
+    boot[3..11].copy_from_slice(b"FIRECRKR");
+    boot[11..13].copy_from_slice(&u16::try_from(SECTOR_SIZE).unwrap().to_le_bytes());
+    boot[13] = u8::try_from(sectors_per_cluster).unwrap();
+    boot[14..16].copy_from_slice(&1u16.to_le_bytes());
+    boot[16] = 2;
+    boot[17..19].copy_from_slice(&u16::try_from(ROOT_DIR_ENTRIES).unwrap().to_le_bytes());
+    match u16::try_from(total_sectors) {
+        Ok(total_sectors) => boot[19..21].copy_from_slice(&total_sectors.to_le_bytes()),
+        Err(_) => {
+            boot[32..36].copy_from_slice(&u32::try_from(total_sectors).unwrap().to_le_bytes())
+        }
+    }
+    boot[21] = MEDIA_FIXED_DISK;
+    boot[22..24].copy_from_slice(&u16::try_from(fat_sectors).unwrap().to_le_bytes());
+    boot[24..26].copy_from_slice(&32u16.to_le_bytes());
+    boot[26..28].copy_from_slice(&64u16.to_le_bytes());
+    boot[36] = 0x80;
+    boot[38] = 0x29;
+    let volume_id = short_name_checksum(&volume_label);
+    boot[39..43].copy_from_slice(&u32::from(volume_id).to_le_bytes());
+    boot[43..54].copy_from_slice(&volume_label);
+    boot[54..62].copy_from_slice(b"FAT12   ");
+    boot[510..512].copy_from_slice(&[0x55, 0xaa]);
+
+    let mut fat = vec![0u8; fat_sectors * SECTOR_SIZE];
+    set_fat12_entry(&mut fat, 0, 0xf00 | u16::from(MEDIA_FIXED_DISK));
+    set_fat12_entry(&mut fat, 1, FAT12_END_OF_CHAIN);
+
+    let mut dir = Vec::with_capacity(ROOT_DIR_ENTRIES * DIR_ENTRY_SIZE);
+    dir.extend_from_slice(&short_entry(&volume_label, ATTR_VOLUME_ID, 0, 0));
+
+    let mut next_cluster = 2;
+    for (i, file) in files.iter().enumerate() {
+        let file_clusters = file.data.len().div_ceil(cluster_size);
+        // Empty files have no cluster.
+        let first_cluster = if file_clusters == 0 { 0 } else { next_cluster };
+        for cluster in next_cluster..next_cluster + file_clusters {
+            let next = if cluster + 1 == next_cluster + file_clusters {
+                FAT12_END_OF_CHAIN
+            } else {
+                u16::try_from(cluster + 1).unwrap()
+            };
+            set_fat12_entry(&mut fat, cluster, next);
+        }
+        let offset = data_start + (next_cluster - 2) * cluster_size;
+        image[offset..offset + file.data.len()].copy_from_slice(file.data);
+        next_cluster += file_clusters;
+
+        let name = short_name(file.name, i);
+        for entry in long_name_entries(file.name, short_name_checksum(&name)) {
+            dir.extend_from_slice(&entry);
+        }
+        dir.extend_from_slice(&short_entry(
+            &name,
+            ATTR_READ_ONLY | ATTR_ARCHIVE,
+            u16::try_from(first_cluster).unwrap(),
+            u32::try_from(file.data.len()).map_err(|_| FatImageError::TooLarge)?,
+        ));
+    }
+
+    for copy in 0..2 {
+        let offset = (1 + copy * fat_sectors) * SECTOR_SIZE;
+        image[offset..offset + fat.len()].copy_from_slice(&fat);
+    }
+    let offset = (1 + 2 * fat_sectors) * SECTOR_SIZE;
+    image[offset..offset + dir.len()].copy_from_slice(&dir);
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u16(image: &[u8], offset: usize) -> usize {
+        usize::from(u16::from_le_bytes([image[offset], image[offset + 1]]))
+    }
+
+    fn fat12_entry(fat: &[u8], cluster: usize) -> usize {
+        let value = read_u16(fat, cluster + cluster / 2);
+        if cluster.is_multiple_of(2) {
+            value & 0xfff
+        } else {
+            value >> 4
+        }
+    }
+
+    /// Reads back the files of an image, following the allocation table.
+    fn read_files(image: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let cluster_size = usize::from(image[13]) * SECTOR_SIZE;
+        let fat_sectors = read_u16(image, 22);
+        let fat = &image[SECTOR_SIZE..(1 + fat_sectors) * SECTOR_SIZE];
+        assert_eq!(fat, &image[(1 + fat_sectors) * SECTOR_SIZE..][..fat.len()]);
+        let dir_start = (1 + 2 * fat_sectors) * SECTOR_SIZE;
+        let data_start = dir_start + ROOT_DIR_ENTRIES * DIR_ENTRY_SIZE;
+
+        let mut files = Vec::new();
+        let mut long_name = Vec::new();
+        for entry in image[dir_start..data_start].chunks(DIR_ENTRY_SIZE) {
+            match entry[11] {
+                _ if entry[0] == 0 => break,
+                ATTR_VOLUME_ID => assert_eq!(&entry[..11], &image[43..54]),
+                ATTR_LONG_NAME => {
+                    let chars = [1..11, 14..26, 28..32]
+                        .into_iter()
+                        .flat_map(|range| entry[range].chunks(2).map(|c| read_u16(c, 0)))
+                        .take_while(|char| *char != 0 && *char != 0xffff)
+                        .map(|char| u8::try_from(char).unwrap());
+                    long_name.splice(0..0, chars);
+                }
+                _ => {
+                    let size =
+                        usize::try_from(u32::from_le_bytes(entry[28..32].try_into().unwrap()))
+                            .unwrap();
+                    let mut data = Vec::new();
+                    let mut cluster = read_u16(entry, 26);
+                    while cluster != 0 && cluster != usize::from(FAT12_END_OF_CHAIN) {
+                        let offset = data_start + (cluster - 2) * cluster_size;
+                        data.extend_from_slice(&image[offset..offset + cluster_size]);
+                        cluster = fat12_entry(fat, cluster);
+                    }
+                    data.truncate(size);
+                    files.push((String::from_utf8(long_name.split_off(0)).unwrap(), data));
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(&short_name("user-data", 0), b"USER-D~1   ");
+        assert_eq!(&short_name("a.b", 1), b"AB~2       ");
+        assert_eq!(&short_name("network-config", 9), b"NETWO~10   ");
+        assert_eq!(short_name_checksum(b"USER-D~1   "), 0xb0);
+    }
+
+    #[test]
+    fn test_build_image() {
+        let large = vec![0xa5u8; 3 * 1024 * 1024];
+        let files = [
+            FatFile {
+                name: "meta-data",
+                data: b"instance-id: i-1234\n",
+            },
+            FatFile {
+                name: "user-data",
+                data: b"#cloud-config\n",
+            },
+            FatFile {
+                name: "empty",
+                data: b"",
+            },
+            FatFile {
+                name: "a-name-longer-than-26-characters",
+                data: &large,
+            },
+        ];
+        let image = build_fat12_image("CIDATA", &files).unwrap();
+
+        assert_eq!(&image[510..512], &[0x55, 0xaa]);
+        assert_eq!(&image[43..54], b"CIDATA     ");
+        assert_eq!(&image[54..62], b"FAT12   ");
+        // 3 MiB need clusters of 1 KiB to fit in 4084 clusters.
+        assert_eq!(image[13], 2);
+        assert_eq!(image.len() % SECTOR_SIZE, 0);
+        let read = read_files(&image);
+        assert_eq!(read.len(), files.len());
+        for (file, (name, data)) in files.iter().zip(read) {
+            assert_eq!(file.name, name);
+            assert_eq!(file.data, data.as_slice());
+        }
+
+        // Small images use 512 bytes clusters.
+        let image = build_fat12_image("CIDATA", &files[..2]).unwrap();
+        assert_eq!(image[13], 1);
+        assert_eq!(read_files(&image).len(), 2);
+        build_fat12_image("CIDATA", &[]).unwrap();
+    }
+
+    #[test]
+    fn test_build_image_errors() {
+        let file = |name| FatFile { name, data: b"" };
+        for label in ["", "cidata", "LABEL TOO LONG", "A B"] {
+            assert_eq!(
+                build_fat12_image(label, &[]),
+                Err(FatImageError::InvalidLabel(label.to_string()))
+            );
+        }
+        let long_name = "n".repeat(FAT_MAX_NAME_LEN + 1);
+        for name in ["", ".", "..", "a/b", "with space", long_name.as_str()] {
+            assert_eq!(
+                build_fat12_image("CIDATA", &[file(name)]),
+                Err(FatImageError::InvalidName(name.to_string()))
+            );
+        }
+        assert_eq!(
+            build_fat12_image("CIDATA", &[file("user-data"), file("USER-DATA")]),
+            Err(FatImageError::DuplicateName("USER-DATA".to_string()))
+        );
+
+        let names: Vec<_> = (0..32).map(|i| format!("file-{i}")).collect();
+        let files: Vec<_> = names.iter().map(|name| file(name)).collect();
+        assert_eq!(
+            build_fat12_image("CIDATA", &files),
+            Err(FatImageError::TooManyFiles)
+        );
+        let huge = vec![0u8; MAX_CLUSTERS * MAX_SECTORS_PER_CLUSTER * SECTOR_SIZE + 1];
+        assert_eq!(
+            build_fat12_image(
+                "CIDATA",
+                &[FatFile {
+                    name: "huge",
+                    data: &huge
+                }]
+            ),
+            Err(FatImageError::TooLarge)
+        );
+    }
+}
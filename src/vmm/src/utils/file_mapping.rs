@@ -0,0 +1,135 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::ptr::NonNull;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::u64_to_usize;
+
+/// Configures how a read-only file is shared through the host page cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedPageCacheConfig {
+    /// Populate the whole mapping up front, so that the first guest accesses don't fault.
+    #[serde(default)]
+    pub prefault: bool,
+}
+
+/// Read-only `MAP_SHARED` mapping of a whole file.
+///
+/// Every process mapping the same file references the same page cache pages, so identical
+/// microVMs share a single copy of the file contents on the host.
+#[derive(Debug)]
+pub struct SharedFileMapping {
+    addr: Option<NonNull<u8>>,
+    len: usize,
+}
+
+// SAFETY: The mapping is read-only and owned by this object, so it can be accessed from any
+// thread.
+unsafe impl Send for SharedFileMapping {}
+// SAFETY: The mapping is read-only, so concurrent accesses can't race.
+unsafe impl Sync for SharedFileMapping {}
+
+impl SharedFileMapping {
+    /// Maps `file` read-only, populating the page tables if `config.prefault` is set.
+    pub fn new(file: &File, config: SharedPageCacheConfig) -> Result<Self, io::Error> {
+        let len = u64_to_usize(file.metadata()?.len());
+        // mmap() rejects zero length mappings.
+        if len == 0 {
+            return Ok(Self { addr: None, len });
+        }
+
+        let mut flags = libc::MAP_SHARED | libc::MAP_NORESERVE;
+        if config.prefault {
+            flags |= libc::MAP_POPULATE;
+        }
+
+        // SAFETY: We are calling the system call with valid arguments and checking the returned
+        // value.
+        let addr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                flags,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            addr: NonNull::new(addr.cast()),
+            len,
+        })
+    }
+
+    /// Returns the length of the mapping.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the mapped file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the contents of the mapped file.
+    pub fn as_slice(&self) -> &[u8] {
+        match self.addr {
+            // SAFETY: `addr` points to a readable mapping of `len` bytes which lives as long as
+            // `self`.
+            Some(addr) => unsafe { std::slice::from_raw_parts(addr.as_ptr(), self.len) },
+            None => &[],
+        }
+    }
+}
+
+impl Drop for SharedFileMapping {
+    fn drop(&mut self) {
+        if let Some(addr) = self.addr {
+            // SAFETY: `addr` and `len` describe a mapping created by `new()`.
+            unsafe {
+                _ = libc::munmap(addr.as_ptr().cast(), self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::fs::FileExt;
+
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_shared_file_mapping() {
+        let file = TempFile::new().unwrap();
+        let mapping = SharedFileMapping::new(file.as_file(), Default::default()).unwrap();
+        assert!(mapping.is_empty());
+        assert_eq!(mapping.as_slice(), &[] as &[u8]);
+
+        file.as_file().write_all(&[0xab; 0x1800]).unwrap();
+        for prefault in [false, true] {
+            let mapping =
+                SharedFileMapping::new(file.as_file(), SharedPageCacheConfig { prefault }).unwrap();
+            assert_eq!(mapping.len(), 0x1800);
+            assert_eq!(mapping.as_slice(), &[0xab; 0x1800]);
+        }
+
+        // Writes to the file are visible through the mapping, as they share the page cache.
+        let mapping = SharedFileMapping::new(file.as_file(), Default::default()).unwrap();
+        file.as_file().write_all_at(&[0xcd], 0x10).unwrap();
+        assert_eq!(mapping.as_slice()[0x10], 0xcd);
+    }
+}
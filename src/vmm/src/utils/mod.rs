@@ -3,12 +3,18 @@
 
 /// Module with helpers to read/write bytes into slices
 pub mod byte_order;
+/// Module with a builder of FAT images
+pub mod fat;
+/// Module with read-only file mappings sharing the host page cache
+pub mod file_mapping;
 /// Module with network related helpers
 pub mod net;
 /// Module with external libc functions
 pub mod signal;
 /// Module with state machine
 pub mod sm;
+/// Module with static tracepoints for eBPF tooling
+pub mod usdt;
 
 use std::fs::{File, OpenOptions};
 use std::num::Wrapping;
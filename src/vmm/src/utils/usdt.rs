@@ -0,0 +1,188 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! User-level statically defined tracepoints (USDT).
+//!
+//! Probes are emitted in the SystemTap SDT format understood by `bpftrace`, `perf` and the other
+//! eBPF tooling: each probe is a single `nop` instruction, described by an ELF note in the
+//! `.note.stapsdt` section. When no tracer is attached, the cost of a probe is the `nop` and the
+//! evaluation of its arguments, so arguments must be cheap to compute.
+//!
+//! All probes belong to the `firecracker` provider:
+//!
+//! ```text
+//! bpftrace -e 'usdt:./firecracker:firecracker:queue_pop { @[arg0] = count(); }'
+//! ```
+
+/// Argument of a probe, passed to the tracers as a 64-bit register.
+pub trait UsdtArg {
+    /// Converts the argument to the value read by the tracers.
+    fn to_probe_arg(self) -> u64;
+}
+
+macro_rules! impl_usdt_arg {
+    ($($t:ty),*) => {
+        $(
+            impl UsdtArg for $t {
+                fn to_probe_arg(self) -> u64 {
+                    u64::from(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_usdt_arg!(bool, u8, u16, u32, u64);
+
+impl UsdtArg for usize {
+    fn to_probe_arg(self) -> u64 {
+        self as u64
+    }
+}
+
+impl<T> UsdtArg for *const T {
+    fn to_probe_arg(self) -> u64 {
+        self as u64
+    }
+}
+
+/// Fires the USDT probe `firecracker:$name`, with up to 4 arguments implementing
+/// [`UsdtArg`](crate::utils::usdt::UsdtArg).
+#[macro_export]
+macro_rules! usdt_probe {
+    ($name:ident) => {
+        $crate::__usdt_asm!($name, "")
+    };
+    ($name:ident, $a0:expr) => {
+        $crate::__usdt_asm!($name, "8@{0}", $a0)
+    };
+    ($name:ident, $a0:expr, $a1:expr) => {
+        $crate::__usdt_asm!($name, "8@{0} 8@{1}", $a0, $a1)
+    };
+    ($name:ident, $a0:expr, $a1:expr, $a2:expr) => {
+        $crate::__usdt_asm!($name, "8@{0} 8@{1} 8@{2}", $a0, $a1, $a2)
+    };
+    ($name:ident, $a0:expr, $a1:expr, $a2:expr, $a3:expr) => {
+        $crate::__usdt_asm!($name, "8@{0} 8@{1} 8@{2} 8@{3}", $a0, $a1, $a2, $a3)
+    };
+}
+
+/// Emits the `nop` of a probe along with its SDT note. The base section lets the tracers
+/// compute the address of the probe when the binary is relocated.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __usdt_asm_template {
+    ($name:ident, $args:literal) => {
+        concat!(
+            "990: nop\n",
+            ".pushsection .note.stapsdt, \"?\", \"note\"\n",
+            ".balign 4\n",
+            ".4byte 992f-991f, 994f-993f, 3\n",
+            "991: .asciz \"stapsdt\"\n",
+            "992: .balign 4\n",
+            "993: .8byte 990b\n",
+            ".8byte _.stapsdt.base\n",
+            ".8byte 0\n",
+            ".asciz \"firecracker\"\n",
+            ".asciz \"",
+            stringify!($name),
+            "\"\n",
+            ".asciz \"",
+            $args,
+            "\"\n",
+            "994: .balign 4\n",
+            ".popsection\n",
+            ".ifndef _.stapsdt.base\n",
+            ".pushsection .stapsdt.base, \"aG\", \"progbits\", .stapsdt.base, comdat\n",
+            ".weak _.stapsdt.base\n",
+            ".hidden _.stapsdt.base\n",
+            "_.stapsdt.base: .space 1\n",
+            ".size _.stapsdt.base, 1\n",
+            ".popsection\n",
+            ".endif\n",
+        )
+    };
+}
+
+#[doc(hidden)]
+#[cfg(all(target_arch = "x86_64", not(kani)))]
+#[macro_export]
+macro_rules! __usdt_asm {
+    ($name:ident, $args:literal $(, $arg:expr)*) => {
+        // SAFETY: The probe is a `nop` instruction which only reads its register operands.
+        unsafe {
+            ::core::arch::asm!(
+                $crate::__usdt_asm_template!($name, $args),
+                $(in(reg) $crate::utils::usdt::UsdtArg::to_probe_arg($arg),)*
+                options(att_syntax, nomem, nostack, preserves_flags)
+            )
+        }
+    };
+}
+
+#[doc(hidden)]
+#[cfg(all(target_arch = "aarch64", not(kani)))]
+#[macro_export]
+macro_rules! __usdt_asm {
+    ($name:ident, $args:literal $(, $arg:expr)*) => {
+        // SAFETY: The probe is a `nop` instruction which only reads its register operands.
+        unsafe {
+            ::core::arch::asm!(
+                $crate::__usdt_asm_template!($name, $args),
+                $(in(reg) $crate::utils::usdt::UsdtArg::to_probe_arg($arg),)*
+                options(nomem, nostack, preserves_flags)
+            )
+        }
+    };
+}
+
+// Kani does not support inline assembly, the probes only evaluate their arguments.
+#[doc(hidden)]
+#[cfg(kani)]
+#[macro_export]
+macro_rules! __usdt_asm {
+    ($name:ident, $args:literal $(, $arg:expr)*) => {
+        $(let _ = $crate::utils::usdt::UsdtArg::to_probe_arg($arg);)*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_usdt_arg() {
+        assert_eq!(true.to_probe_arg(), 1);
+        assert_eq!(0xffu8.to_probe_arg(), 0xff);
+        assert_eq!(0xffff_ffffu32.to_probe_arg(), 0xffff_ffff);
+        assert_eq!(usize::MAX.to_probe_arg(), u64::MAX);
+        let value = 0u8;
+        assert_eq!(
+            std::ptr::from_ref(&value).to_probe_arg(),
+            std::ptr::from_ref(&value) as u64
+        );
+    }
+
+    #[test]
+    fn test_usdt_probe() {
+        // Firing the probes without a tracer attached is a no-op.
+        crate::usdt_probe!(test_probe_0);
+        crate::usdt_probe!(test_probe_1, 1u8);
+        crate::usdt_probe!(test_probe_2, 1u16, 2u32);
+        crate::usdt_probe!(test_probe_3, 1u64, 2usize, true);
+        crate::usdt_probe!(test_probe_4, 1u64, 2u64, 3u64, "foo".as_ptr());
+
+        // The probes are described in the notes of the binary.
+        let binary = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        assert!(contains(&binary, b".note.stapsdt\0"));
+        assert!(contains(&binary, b"stapsdt\0"));
+        assert!(contains(&binary, b"firecracker\0test_probe_0\0\0"));
+        assert!(contains(&binary, b"firecracker\0test_probe_4\0"));
+    }
+}
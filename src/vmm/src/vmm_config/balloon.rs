@@ -40,6 +40,9 @@ pub struct BalloonDeviceConfig {
     /// Free page reporting enabled
     #[serde(default)]
     pub free_page_reporting: bool,
+    /// Number of statistics samples retained and reported along with the latest statistics.
+    #[serde(default)]
+    pub stats_history_len: u16,
 }
 
 impl From<BalloonConfig> for BalloonDeviceConfig {
@@ -50,6 +53,7 @@ impl From<BalloonConfig> for BalloonDeviceConfig {
             stats_polling_interval_s: state.stats_polling_interval_s,
             free_page_hinting: state.free_page_hinting,
             free_page_reporting: state.free_page_reporting,
+            stats_history_len: state.stats_history_len,
         }
     }
 }
@@ -90,13 +94,15 @@ impl BalloonBuilder {
     /// Inserts a Balloon device in the store.
     /// If an entry already exists, it will overwrite it.
     pub fn set(&mut self, cfg: BalloonDeviceConfig) -> Result<(), BalloonConfigError> {
-        self.inner = Some(Arc::new(Mutex::new(Balloon::new(
+        let mut balloon = Balloon::new(
             cfg.amount_mib,
             cfg.deflate_on_oom,
             cfg.stats_polling_interval_s,
             cfg.free_page_hinting,
             cfg.free_page_reporting,
-        )?)));
+        )?;
+        balloon.set_stats_history_len(cfg.stats_history_len)?;
+        self.inner = Some(Arc::new(Mutex::new(balloon)));
 
         Ok(())
     }
@@ -140,6 +146,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         }
     }
 
@@ -152,6 +159,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 0,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
         assert_eq!(default_balloon_config, balloon_config);
         let mut builder = BalloonBuilder::new();
@@ -175,6 +183,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 3,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         };
 
         let actual_balloon_config = BalloonDeviceConfig::from(BalloonConfig {
@@ -183,6 +192,7 @@ pub(crate) mod tests {
             stats_polling_interval_s: 3,
             free_page_hinting: false,
             free_page_reporting: false,
+            stats_history_len: 0,
         });
 
         assert_eq!(expected_balloon_config, actual_balloon_config);
@@ -0,0 +1,122 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Smallest PSI trigger window accepted by the kernel, in microseconds.
+pub const PSI_MIN_WINDOW_US: u32 = 500_000;
+/// Largest PSI trigger window accepted by the kernel, in microseconds.
+pub const PSI_MAX_WINDOW_US: u32 = 10_000_000;
+
+/// Errors associated with the cold memory configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum ColdMemoryConfigError {
+    /// Pressure window must be between {PSI_MIN_WINDOW_US} and {PSI_MAX_WINDOW_US} us
+    InvalidPressureWindow,
+    /// Pressure stall time must be positive and not greater than the pressure window
+    InvalidPressureStall,
+    /// The amount of memory evicted per pressure event must be positive
+    InvalidEvictBatch,
+    /// The amount of memory evicted per pressure event must not exceed the guest memory size
+    EvictBatchTooLarge,
+}
+
+fn default_pressure_stall_us() -> u32 {
+    150_000
+}
+
+fn default_pressure_window_us() -> u32 {
+    1_000_000
+}
+
+fn default_evict_batch_mib() -> usize {
+    64
+}
+
+/// Configuration of the compressed backing store for cold guest memory.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColdMemoryConfig {
+    /// Path of the file holding the compressed evicted pages. It is truncated when the microVM
+    /// starts.
+    pub backing_file_path: PathBuf,
+    /// Total memory stall time within a pressure window that triggers an eviction, in
+    /// microseconds.
+    #[serde(default = "default_pressure_stall_us")]
+    pub pressure_stall_us: u32,
+    /// Length of the window over which memory stalls are measured, in microseconds.
+    #[serde(default = "default_pressure_window_us")]
+    pub pressure_window_us: u32,
+    /// Maximum amount of guest memory evicted per pressure event, in MiB.
+    #[serde(default = "default_evict_batch_mib")]
+    pub evict_batch_mib: usize,
+}
+
+impl ColdMemoryConfig {
+    /// Validates the configuration, for a guest memory of `mem_size_mib`.
+    pub fn validate(&self, mem_size_mib: usize) -> Result<(), ColdMemoryConfigError> {
+        if !(PSI_MIN_WINDOW_US..=PSI_MAX_WINDOW_US).contains(&self.pressure_window_us) {
+            return Err(ColdMemoryConfigError::InvalidPressureWindow);
+        }
+        if self.pressure_stall_us == 0 || self.pressure_stall_us > self.pressure_window_us {
+            return Err(ColdMemoryConfigError::InvalidPressureStall);
+        }
+        if self.evict_batch_mib == 0 {
+            return Err(ColdMemoryConfigError::InvalidEvictBatch);
+        }
+        if self.evict_batch_mib > mem_size_mib {
+            return Err(ColdMemoryConfigError::EvictBatchTooLarge);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cold_memory_config() {
+        let config: ColdMemoryConfig =
+            serde_json::from_str(r#"{"backing_file_path": "/tmp/cold"}"#).unwrap();
+        assert_eq!(config.backing_file_path, PathBuf::from("/tmp/cold"));
+        assert_eq!(config.pressure_stall_us, 150_000);
+        assert_eq!(config.pressure_window_us, 1_000_000);
+        assert_eq!(config.evict_batch_mib, 64);
+        config.validate(128).unwrap();
+
+        let mut bad_config = config.clone();
+        bad_config.pressure_window_us = 100_000;
+        assert_eq!(
+            bad_config.validate(128),
+            Err(ColdMemoryConfigError::InvalidPressureWindow)
+        );
+
+        let mut bad_config = config.clone();
+        bad_config.pressure_stall_us = 2_000_000;
+        assert_eq!(
+            bad_config.validate(128),
+            Err(ColdMemoryConfigError::InvalidPressureStall)
+        );
+
+        let mut bad_config = config.clone();
+        bad_config.evict_batch_mib = 0;
+        assert_eq!(
+            bad_config.validate(128),
+            Err(ColdMemoryConfigError::InvalidEvictBatch)
+        );
+
+        assert_eq!(
+            config.validate(32),
+            Err(ColdMemoryConfigError::EvictBatchTooLarge)
+        );
+        let mut bad_config = config;
+        bad_config.evict_batch_mib = usize::MAX;
+        assert_eq!(
+            bad_config.validate(128),
+            Err(ColdMemoryConfigError::EvictBatchTooLarge)
+        );
+    }
+}
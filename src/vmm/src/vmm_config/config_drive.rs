@@ -0,0 +1,158 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::fat::{FatFile, FatImageError, build_fat12_image};
+use crate::vmm_config::drive::{BlockDeviceConfig, DriveError};
+
+/// Volume label looked for by the NoCloud data source of cloud-init.
+pub const CONFIG_DRIVE_LABEL: &str = "CIDATA";
+/// Maximum size of the files of the configuration drive, in bytes.
+pub const CONFIG_DRIVE_MAX_DATA_LEN: usize = 16 << 20;
+
+/// Strongly typed data structure used to configure the read-only drive from which cloud-init
+/// reads the instance configuration, following the layout of its NoCloud data source.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigDriveConfig {
+    /// Unique identifier of the drive.
+    pub drive_id: String,
+    /// Path of the host file the image of the drive is written to. The file is created, or
+    /// truncated if it exists.
+    pub path_on_host: String,
+    /// Content of the `user-data` file.
+    pub user_data: String,
+    /// Content of the `meta-data` file.
+    #[serde(default)]
+    pub meta_data: String,
+    /// Content of the `network-config` file, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_config: Option<String>,
+    /// Content of the `vendor-data` file, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_data: Option<String>,
+}
+
+/// Errors associated with actions on `ConfigDriveConfig`.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ConfigDriveError {
+    /// The files of the configuration drive are larger than 16 MiB.
+    TooLarge,
+    /// Cannot build the image of the configuration drive: {0}
+    Image(#[from] FatImageError),
+    /// Cannot write the image of the configuration drive to {0}: {1}
+    WriteImage(String, std::io::Error),
+    /// Cannot attach the configuration drive: {0}
+    Drive(#[from] DriveError),
+}
+
+impl ConfigDriveConfig {
+    /// Returns the files of the drive.
+    pub fn files(&self) -> Vec<FatFile<'_>> {
+        let mut files = vec![
+            FatFile {
+                name: "meta-data",
+                data: self.meta_data.as_bytes(),
+            },
+            FatFile {
+                name: "user-data",
+                data: self.user_data.as_bytes(),
+            },
+        ];
+        if let Some(network_config) = &self.network_config {
+            files.push(FatFile {
+                name: "network-config",
+                data: network_config.as_bytes(),
+            });
+        }
+        if let Some(vendor_data) = &self.vendor_data {
+            files.push(FatFile {
+                name: "vendor-data",
+                data: vendor_data.as_bytes(),
+            });
+        }
+        files
+    }
+
+    /// Builds the FAT image of the drive.
+    pub fn build_image(&self) -> Result<Vec<u8>, ConfigDriveError> {
+        let files = self.files();
+        if files.iter().map(|file| file.data.len()).sum::<usize>() > CONFIG_DRIVE_MAX_DATA_LEN {
+            return Err(ConfigDriveError::TooLarge);
+        }
+        Ok(build_fat12_image(CONFIG_DRIVE_LABEL, &files)?)
+    }
+
+    /// Builds the image of the drive and writes it to `path_on_host`.
+    pub fn write_image(&self) -> Result<(), ConfigDriveError> {
+        let image = self.build_image()?;
+        std::fs::write(&self.path_on_host, image)
+            .map_err(|err| ConfigDriveError::WriteImage(self.path_on_host.clone(), err))
+    }
+
+    /// Returns the configuration of the read-only block device exposing the image.
+    pub fn block_device_config(&self) -> BlockDeviceConfig {
+        BlockDeviceConfig {
+            drive_id: self.drive_id.clone(),
+            is_read_only: Some(true),
+            path_on_host: Some(self.path_on_host.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_write_image() {
+        let file = TempFile::new().unwrap();
+        let mut config = ConfigDriveConfig {
+            drive_id: String::from("cidata"),
+            path_on_host: file.as_path().to_str().unwrap().to_string(),
+            user_data: String::from("#cloud-config\nhostname: vm\n"),
+            ..Default::default()
+        };
+        let names = |config: &ConfigDriveConfig| {
+            config
+                .files()
+                .iter()
+                .map(|file| file.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&config), ["meta-data", "user-data"]);
+        config.vendor_data = Some(String::new());
+        config.network_config = Some(String::from("version: 2\n"));
+        assert_eq!(
+            names(&config),
+            ["meta-data", "user-data", "network-config", "vendor-data"]
+        );
+
+        config.write_image().unwrap();
+        let image = std::fs::read(file.as_path()).unwrap();
+        assert_eq!(image, config.build_image().unwrap());
+        assert_eq!(&image[43..54], b"CIDATA     ");
+
+        let block_config = config.block_device_config();
+        assert_eq!(block_config.drive_id, "cidata");
+        assert_eq!(block_config.is_read_only, Some(true));
+        assert!(!block_config.is_root_device);
+        assert_eq!(block_config.path_on_host, Some(config.path_on_host.clone()));
+
+        config.user_data = "a".repeat(CONFIG_DRIVE_MAX_DATA_LEN);
+        assert!(matches!(
+            config.build_image(),
+            Err(ConfigDriveError::TooLarge)
+        ));
+        config.user_data = String::new();
+        config.path_on_host = String::from("/non/existent/cidata.img");
+        assert!(matches!(
+            config.write_image(),
+            Err(ConfigDriveError::WriteImage(_, _))
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::virtio::device::VirtioDeviceType;
+use crate::devices::virtio::vsock::VSOCK_DEV_ID;
+
+/// Default time left to the guest to release a device, in milliseconds.
+pub const DEFAULT_REMOVAL_TIMEOUT_MS: u64 = 5_000;
+
+/// Kinds of devices which can be removed from a running microVM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RemovableDeviceType {
+    /// Block device, identified by its `drive_id`.
+    Drive,
+    /// Network device, identified by its `iface_id`.
+    NetworkInterface,
+    /// RDMA device, identified by its `rdma_id`.
+    RdmaDevice,
+    /// Vsock device, of which there is at most one. Its ID is ignored.
+    Vsock,
+}
+
+impl RemovableDeviceType {
+    /// Type of the VirtIO device backing the removable device.
+    pub fn virtio_device_type(self) -> VirtioDeviceType {
+        match self {
+            RemovableDeviceType::Drive => VirtioDeviceType::Block,
+            RemovableDeviceType::NetworkInterface => VirtioDeviceType::Net,
+            RemovableDeviceType::RdmaDevice => VirtioDeviceType::Rdma,
+            RemovableDeviceType::Vsock => VirtioDeviceType::Vsock,
+        }
+    }
+
+    /// ID of the VirtIO device backing the removable device of ID `id`.
+    pub fn device_id(self, id: &str) -> &str {
+        match self {
+            RemovableDeviceType::Vsock => VSOCK_DEV_ID,
+            _ => id,
+        }
+    }
+}
+
+fn default_removal_timeout_ms() -> u64 {
+    DEFAULT_REMOVAL_TIMEOUT_MS
+}
+
+/// Configuration of the removal of a device from a running microVM.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceRemovalConfig {
+    /// Kind of the device.
+    pub device_type: RemovableDeviceType,
+    /// ID of the device.
+    pub id: String,
+    /// Time left to the guest to release the device once asked to eject it, after which the
+    /// device is removed anyway, in milliseconds. With a timeout of 0 the guest is not asked,
+    /// the device is removed right away.
+    #[serde(default = "default_removal_timeout_ms")]
+    pub timeout_ms: u64,
+}
@@ -7,12 +7,15 @@ use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
-use super::RateLimiterConfig;
+use super::{PassedFd, RateLimiterConfig};
 use crate::VmmError;
 use crate::devices::virtio::block::device::Block;
 pub use crate::devices::virtio::block::virtio::device::FileEngineType;
+pub use crate::devices::virtio::block::virtio::host_cache::{FileAdvice, HostCacheConfig};
+pub use crate::devices::virtio::block::virtio::verity::VerityConfig;
 use crate::devices::virtio::block::{BlockError, CacheType};
 use crate::devices::virtio::device::VirtioDevice;
+pub use crate::utils::file_mapping::SharedPageCacheConfig;
 
 /// Errors associated with the operations allowed on a drive.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
@@ -51,8 +54,12 @@ pub struct BlockDeviceConfig {
     /// If set to true, the drive is opened in read-only mode. Otherwise, the
     /// drive is opened as read-write.
     pub is_read_only: Option<bool>,
-    /// Path of the drive.
+    /// Path of the drive. Can be omitted if the backing file is passed in `backing_fd`, in which
+    /// case it is only reported back.
     pub path_on_host: Option<String>,
+    /// Already open backing file, used instead of opening `path_on_host`.
+    #[serde(skip)]
+    pub backing_fd: Option<PassedFd>,
     /// Rate Limiter for I/O operations.
     pub rate_limiter: Option<RateLimiterConfig>,
     /// The type of IO engine used by the device.
@@ -61,6 +68,26 @@ pub struct BlockDeviceConfig {
     // pub file_engine_type: FileEngineType,
     #[serde(rename = "io_engine")]
     pub file_engine_type: Option<FileEngineType>,
+    /// Integrity verification settings. Only supported for read-only drives.
+    #[serde(default)]
+    pub verity: Option<VerityConfig>,
+    /// Serve reads from a shared mapping of the host page cache. Only supported for read-only
+    /// drives.
+    #[serde(default)]
+    pub shared_page_cache: Option<SharedPageCacheConfig>,
+    /// Make flush requests act as barriers: a flush is only started once all the requests
+    /// submitted before it have completed, and no request submitted after it is started before
+    /// the flush has completed.
+    #[serde(default)]
+    pub strict_ordering: Option<bool>,
+    /// Host page cache policy of the backing file: direct IO, access pattern advice and
+    /// read-ahead window.
+    #[serde(default)]
+    pub host_cache: Option<HostCacheConfig>,
+    /// URI of a network block device export backing the drive, used instead of `path_on_host`:
+    /// `nbd://host[:port]/export` or `nbd+unix:///export?socket=path`.
+    #[serde(default)]
+    pub nbd_uri: Option<String>,
 
     // VhostUserBlock specific fields
     /// Path to the vhost-user socket.
@@ -127,6 +154,12 @@ impl BlockBuilder {
         }
     }
 
+    /// Removes the block device with the specified `drive_id` from the list.
+    pub fn remove(&mut self, drive_id: &str) -> Option<Arc<Mutex<Block>>> {
+        self.get_index_of_drive_id(drive_id)
+            .and_then(|index| self.devices.remove(index))
+    }
+
     /// Inserts a `Block` in the block devices list using the specified configuration.
     /// If a block with the same id already exists, it will overwrite it.
     /// Inserting a secondary root block device will fail.
@@ -211,8 +244,14 @@ mod tests {
                 cache_type: self.cache_type,
 
                 path_on_host: self.path_on_host.clone(),
+                backing_fd: None,
                 rate_limiter: self.rate_limiter,
                 file_engine_type: self.file_engine_type,
+                verity: self.verity.clone(),
+                shared_page_cache: self.shared_page_cache,
+                strict_ordering: self.strict_ordering,
+                host_cache: self.host_cache,
+                nbd_uri: self.nbd_uri.clone(),
 
                 socket: self.socket.clone(),
             }
@@ -238,8 +277,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -259,6 +304,32 @@ mod tests {
         assert_eq!(block.read_only(), dummy_block_device.is_read_only.unwrap());
     }
 
+    #[test]
+    fn test_add_block_device_with_backing_fd() {
+        let dummy_file = TempFile::new().unwrap();
+        dummy_file.as_file().set_len(512).unwrap();
+        let backing_file = std::fs::File::open(dummy_file.as_path()).unwrap();
+        let mut block_devs = BlockBuilder::new();
+
+        let config = |is_read_only| BlockDeviceConfig {
+            drive_id: String::from("1"),
+            is_read_only: Some(is_read_only),
+            backing_fd: Some(PassedFd(backing_file.try_clone().unwrap())),
+            ..Default::default()
+        };
+
+        // A read-only file descriptor can't back a writable drive.
+        assert!(matches!(
+            block_devs.insert(config(false), false),
+            Err(DriveError::CreateBlockDevice(_))
+        ));
+
+        block_devs.insert(config(true), false).unwrap();
+        let block = block_devs.devices[0].lock().unwrap();
+        assert!(block.read_only());
+        assert_eq!(block.config().path_on_host.as_deref(), Some(""));
+    }
+
     #[test]
     fn test_add_one_root_block_device() {
         let dummy_file = TempFile::new().unwrap();
@@ -272,8 +343,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some(dummy_path),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -304,8 +381,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some(dummy_path),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -333,8 +416,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_1),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -349,8 +438,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_2),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -376,8 +471,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_1),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -392,8 +493,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_2),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -408,8 +515,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_3),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -449,8 +562,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_1),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -465,8 +584,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_2),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -481,8 +606,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_3),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -523,8 +654,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_1.clone()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -539,8 +676,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_2.clone()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -611,8 +754,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_1),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -627,8 +776,14 @@ mod tests {
 
             is_read_only: Some(false),
             path_on_host: Some(dummy_path_2),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -653,8 +808,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some(dummy_file.as_path().to_str().unwrap().to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -683,8 +844,14 @@ mod tests {
 
             is_read_only: Some(true),
             path_on_host: Some(backing_file.as_path().to_str().unwrap().to_string()),
+            backing_fd: None,
             rate_limiter: None,
             file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
 
             socket: None,
         };
@@ -698,4 +865,34 @@ mod tests {
             block_id
         );
     }
+
+    #[test]
+    fn test_remove() {
+        let mut block_devs = BlockBuilder::new();
+        let backing_file = TempFile::new().unwrap();
+        let config = BlockDeviceConfig {
+            drive_id: String::from("1"),
+            partuuid: None,
+            is_root_device: false,
+            cache_type: CacheType::Unsafe,
+
+            is_read_only: Some(false),
+            path_on_host: Some(backing_file.as_path().to_str().unwrap().to_string()),
+            backing_fd: None,
+            rate_limiter: None,
+            file_engine_type: None,
+            verity: None,
+            shared_page_cache: None,
+            strict_ordering: None,
+            host_cache: None,
+            nbd_uri: None,
+
+            socket: None,
+        };
+        block_devs.insert(config, false).unwrap();
+
+        assert!(block_devs.remove("2").is_none());
+        assert_eq!(block_devs.remove("1").unwrap().lock().unwrap().id(), "1");
+        assert!(block_devs.devices.is_empty());
+    }
 }
@@ -7,6 +7,7 @@ use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 
 use super::RateLimiterConfig;
+use crate::VmmError;
 use crate::devices::virtio::rng::{Entropy, EntropyError};
 
 /// This struct represents the strongly typed equivalent of the json body from entropy device
@@ -18,6 +19,16 @@ pub struct EntropyDeviceConfig {
     pub rate_limiter: Option<RateLimiterConfig>,
 }
 
+/// The data fed into an entropy device update request. Currently, only the rate limiter can be
+/// updated.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntropyDeviceUpdateConfig {
+    /// New rate limiter config. Only provided data will be updated. I.e. if any optional data
+    /// is missing, it will not be nullified, but left unchanged.
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
 impl From<&Entropy> for EntropyDeviceConfig {
     fn from(dev: &Entropy) -> Self {
         let rate_limiter: RateLimiterConfig = dev.rate_limiter().into();
@@ -35,6 +46,8 @@ pub enum EntropyDeviceError {
     CreateDevice(#[from] EntropyError),
     /// Could not create RateLimiter from configuration: {0}
     CreateRateLimiter(#[from] std::io::Error),
+    /// Unable to update the entropy device: {0}
+    DeviceUpdate(VmmError),
 }
 
 /// A builder type used to construct an Entropy device
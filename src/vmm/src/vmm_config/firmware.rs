@@ -0,0 +1,171 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fs::File;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+/// Strongly typed data structure used to configure the firmware booting the microVM, in place of
+/// a kernel.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FirmwareConfig {
+    /// Path of the firmware code, mapped read-only to the guest.
+    pub path: String,
+    /// Path of the variable store of the firmware. The writes of the guest to the variable store
+    /// are persisted to this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vars_path: Option<String>,
+}
+
+/// Errors associated with actions on `FirmwareConfig`.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum FirmwareConfigError {
+    /// Firmware boot is not supported on this architecture.
+    Unsupported,
+    /// The firmware file cannot be opened: {0}
+    InvalidFirmwarePath(io::Error),
+    /// The variable store file cannot be opened: {0}
+    InvalidVarsPath(io::Error),
+    /// The size of the firmware images must be a non zero multiple of 4 KiB, got {0} bytes.
+    UnalignedSize(u64),
+    /// The firmware images take {0} bytes, more than the 16 MiB available to them.
+    TooLarge(u64),
+}
+
+/// Holds the firmware configuration along with the opened images.
+#[derive(Debug)]
+pub struct Firmware {
+    /// The firmware configuration.
+    pub config: FirmwareConfig,
+    /// The descriptor to the firmware code.
+    pub code_file: File,
+    /// The descriptor to the variable store, opened for writing.
+    pub vars_file: Option<File>,
+}
+
+impl Firmware {
+    /// Opens and validates the firmware images.
+    #[cfg(target_arch = "x86_64")]
+    pub fn new(config: FirmwareConfig) -> Result<Self, FirmwareConfigError> {
+        use crate::arch::FIRMWARE_MAX_SIZE;
+        use crate::devices::legacy::pflash::PFLASH_BLOCK_SIZE;
+
+        let image_size = |file: &File| -> Result<u64, io::Error> { Ok(file.metadata()?.len()) };
+
+        let code_file =
+            File::open(&config.path).map_err(FirmwareConfigError::InvalidFirmwarePath)?;
+        let mut total_size =
+            image_size(&code_file).map_err(FirmwareConfigError::InvalidFirmwarePath)?;
+        let mut sizes = vec![total_size];
+
+        let vars_file = match &config.vars_path {
+            Some(path) => {
+                let file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(path)
+                    .map_err(FirmwareConfigError::InvalidVarsPath)?;
+                let size = image_size(&file).map_err(FirmwareConfigError::InvalidVarsPath)?;
+                sizes.push(size);
+                total_size += size;
+                Some(file)
+            }
+            None => None,
+        };
+
+        if let Some(size) = sizes
+            .into_iter()
+            .find(|size| *size == 0 || size % PFLASH_BLOCK_SIZE != 0)
+        {
+            return Err(FirmwareConfigError::UnalignedSize(size));
+        }
+        if total_size > FIRMWARE_MAX_SIZE {
+            return Err(FirmwareConfigError::TooLarge(total_size));
+        }
+
+        Ok(Firmware {
+            config,
+            code_file,
+            vars_file,
+        })
+    }
+
+    /// Opens and validates the firmware images.
+    #[cfg(target_arch = "aarch64")]
+    pub fn new(_: FirmwareConfig) -> Result<Self, FirmwareConfigError> {
+        Err(FirmwareConfigError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+#[cfg(target_arch = "x86_64")]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn image(size: u64) -> TempFile {
+        let file = TempFile::new().unwrap();
+        file.as_file().set_len(size).unwrap();
+        file
+    }
+
+    fn config(code: &TempFile, vars: Option<&TempFile>) -> FirmwareConfig {
+        FirmwareConfig {
+            path: code.as_path().to_str().unwrap().to_string(),
+            vars_path: vars.map(|vars| vars.as_path().to_str().unwrap().to_string()),
+        }
+    }
+
+    #[test]
+    fn test_firmware() {
+        let code = image(0x1e_0000);
+        let vars = image(0x2_0000);
+
+        let firmware = Firmware::new(config(&code, None)).unwrap();
+        assert!(firmware.vars_file.is_none());
+        let firmware = Firmware::new(config(&code, Some(&vars))).unwrap();
+        assert!(firmware.vars_file.is_some());
+
+        assert!(matches!(
+            Firmware::new(FirmwareConfig {
+                path: "/foo/bar".to_string(),
+                vars_path: None,
+            }),
+            Err(FirmwareConfigError::InvalidFirmwarePath(_))
+        ));
+        let mut invalid_vars = config(&code, None);
+        invalid_vars.vars_path = Some("/foo/bar".to_string());
+        assert!(matches!(
+            Firmware::new(invalid_vars),
+            Err(FirmwareConfigError::InvalidVarsPath(_))
+        ));
+
+        assert!(matches!(
+            Firmware::new(config(&image(0), None)),
+            Err(FirmwareConfigError::UnalignedSize(0))
+        ));
+        assert!(matches!(
+            Firmware::new(config(&code, Some(&image(0x1001)))),
+            Err(FirmwareConfigError::UnalignedSize(0x1001))
+        ));
+        assert!(matches!(
+            Firmware::new(config(&image(16 << 20), Some(&vars))),
+            Err(FirmwareConfigError::TooLarge(0x102_0000))
+        ));
+    }
+
+    #[test]
+    fn test_firmware_config_serde() {
+        let config: FirmwareConfig = serde_json::from_str(r#"{"path": "/tmp/OVMF.fd"}"#).unwrap();
+        assert_eq!(config.vars_path, None);
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"path":"/tmp/OVMF.fd"}"#
+        );
+        serde_json::from_str::<FirmwareConfig>(r#"{"path": "/tmp/OVMF.fd", "foo": 1}"#)
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::legacy::fw_cfg::{FW_CFG_MAX_NAME_LEN, FwCfgFile};
+use crate::vmm_config::instance_info::InstanceInfo;
+
+/// Namespace of the items exposed by Firecracker.
+pub const FW_CFG_FIRECRACKER_PREFIX: &str = "opt/org.firecracker/";
+/// Namespace of the items supplied by the user.
+const FW_CFG_USER_PREFIX: &str = "opt/";
+/// Maximum number of user supplied entries.
+pub const FW_CFG_MAX_ENTRIES: usize = 64;
+/// Maximum size of the value of a user supplied entry, in bytes.
+pub const FW_CFG_MAX_VALUE_LEN: usize = 4096;
+
+/// Strongly typed data structure used to configure the fw_cfg device, through which the guest
+/// reads metadata about the microVM.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FwCfgConfig {
+    /// Key/value pairs exposed to the guest, each as the `opt/<key>` item.
+    #[serde(default)]
+    pub entries: BTreeMap<String, String>,
+}
+
+/// Errors associated with actions on `FwCfgConfig`.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum FwCfgConfigError {
+    /// Too many fw_cfg entries: {0}, the maximum is 64.
+    TooManyEntries(usize),
+    /// Invalid fw_cfg entry key `{0}`: keys are made of up to 51 printable ASCII characters, split in non empty components by `/`, outside of the reserved `org.firecracker` namespace.
+    InvalidKey(String),
+    /// The value of the fw_cfg entry `{0}` is longer than 4096 bytes.
+    ValueTooLong(String),
+}
+
+fn validate_key(key: &str) -> bool {
+    FW_CFG_USER_PREFIX.len() + key.len() < FW_CFG_MAX_NAME_LEN
+        && key.bytes().all(|byte| byte.is_ascii_graphic())
+        && key
+            .split('/')
+            .all(|component| !matches!(component, "" | "." | ".."))
+        && key.split('/').next() != Some("org.firecracker")
+}
+
+impl FwCfgConfig {
+    /// Checks that the entries fit in the fw_cfg device.
+    pub fn validate(&self) -> Result<(), FwCfgConfigError> {
+        if self.entries.len() > FW_CFG_MAX_ENTRIES {
+            return Err(FwCfgConfigError::TooManyEntries(self.entries.len()));
+        }
+        for (key, value) in &self.entries {
+            if !validate_key(key) {
+                return Err(FwCfgConfigError::InvalidKey(key.clone()));
+            }
+            if value.len() > FW_CFG_MAX_VALUE_LEN {
+                return Err(FwCfgConfigError::ValueTooLong(key.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the items exposed to the guest: the identity of the microVM, the wall-clock time
+    /// it was launched at, in microseconds since the epoch, and the user supplied entries.
+    pub fn files(&self, instance_info: &InstanceInfo, launch_time_us: u64) -> Vec<FwCfgFile> {
+        let firecracker_file = |name: &str, data: &str| FwCfgFile {
+            name: format!("{FW_CFG_FIRECRACKER_PREFIX}{name}"),
+            data: data.as_bytes().to_vec(),
+        };
+
+        let mut files = vec![
+            firecracker_file("instance-id", &instance_info.id),
+            firecracker_file("vmm-version", &instance_info.vmm_version),
+            firecracker_file("launch-time-us", &launch_time_us.to_string()),
+        ];
+        files.extend(self.entries.iter().map(|(key, value)| FwCfgFile {
+            name: format!("{FW_CFG_USER_PREFIX}{key}"),
+            data: value.as_bytes().to_vec(),
+        }));
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> FwCfgConfig {
+        FwCfgConfig {
+            entries: entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        config(&[("role", "db"), ("com.example/zone", "eu-1")])
+            .validate()
+            .unwrap();
+        let longest_key = "k".repeat(51);
+        config(&[(longest_key.as_str(), "")]).validate().unwrap();
+
+        let long_key = "k".repeat(52);
+        for key in [
+            "",
+            "/role",
+            "role/",
+            "a//b",
+            "a/../b",
+            "with space",
+            "org.firecracker",
+            "org.firecracker/instance-id",
+            long_key.as_str(),
+        ] {
+            assert_eq!(
+                config(&[(key, "")]).validate(),
+                Err(FwCfgConfigError::InvalidKey(key.to_string()))
+            );
+        }
+
+        let value = "v".repeat(FW_CFG_MAX_VALUE_LEN + 1);
+        assert_eq!(
+            config(&[("role", value.as_str())]).validate(),
+            Err(FwCfgConfigError::ValueTooLong(String::from("role")))
+        );
+
+        let keys: Vec<String> = (0..=FW_CFG_MAX_ENTRIES).map(|i| i.to_string()).collect();
+        let entries: Vec<(&str, &str)> = keys.iter().map(|key| (key.as_str(), "")).collect();
+        assert_eq!(
+            config(&entries).validate(),
+            Err(FwCfgConfigError::TooManyEntries(FW_CFG_MAX_ENTRIES + 1))
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let instance_info = InstanceInfo {
+            id: String::from("vm-0"),
+            vmm_version: String::from("1.2.3"),
+            ..Default::default()
+        };
+        let files = config(&[("role", "db")]).files(&instance_info, 42);
+        let expected = [
+            ("opt/org.firecracker/instance-id", "vm-0"),
+            ("opt/org.firecracker/vmm-version", "1.2.3"),
+            ("opt/org.firecracker/launch-time-us", "42"),
+            ("opt/role", "db"),
+        ];
+        assert_eq!(files.len(), expected.len());
+        for (file, (name, data)) in files.iter().zip(expected) {
+            assert_eq!(file.name, name);
+            assert_eq!(file.data, data.as_bytes());
+        }
+    }
+}
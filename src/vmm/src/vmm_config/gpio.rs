@@ -0,0 +1,146 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::virtio::gpio::device::{Gpio, GpioError};
+
+/// Errors associated with the operations allowed on a virtio-gpio device.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum GpioConfigError {
+    /// Unable to create the virtio-gpio device: {0}
+    CreateDevice(#[from] GpioError),
+    /// Unable to remove the control socket of the replaced device: {0}
+    ClearSocket(std::io::Error),
+}
+
+/// Lines emulated by Firecracker and driven from the host through a Unix socket.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GpioMockConfig {
+    /// Path of the Unix socket used to drive and read the lines.
+    pub socket_path: String,
+    /// Number of lines.
+    pub num_lines: u16,
+    /// Names of the first lines. Missing names are empty.
+    #[serde(default)]
+    pub line_names: Vec<String>,
+}
+
+/// Use this structure to set up a virtio-gpio device before booting the kernel. Exactly one of
+/// `host_chip` and `mock` must be set.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GpioConfig {
+    /// Unique identifier of the device.
+    pub id: String,
+    /// Path of the host gpiochip backing the lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_chip: Option<String>,
+    /// Lines emulated by Firecracker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mock: Option<GpioMockConfig>,
+}
+
+/// Wrapper for the collection that holds all the virtio-gpio devices.
+#[derive(Debug, Default)]
+pub struct GpioBuilder {
+    /// The list of virtio-gpio devices
+    pub devices: Vec<Arc<Mutex<Gpio>>>,
+}
+
+impl GpioBuilder {
+    /// Build a device from the config, replacing the device with the same id if any.
+    pub fn build(&mut self, config: GpioConfig) -> Result<(), GpioConfigError> {
+        let position = self
+            .devices
+            .iter()
+            .position(|d| d.lock().unwrap().config.id == config.id);
+        // Make sure to remove the control socket of the old device before creating a new one.
+        if let Some(index) = position
+            && let Some(mock) = &self.devices[index].lock().unwrap().config.mock
+        {
+            std::fs::remove_file(&mock.socket_path).map_err(GpioConfigError::ClearSocket)?;
+        }
+        let gpio = Arc::new(Mutex::new(Gpio::new(config)?));
+        match position {
+            Some(index) => self.devices[index] = gpio,
+            None => self.devices.push(gpio),
+        }
+        Ok(())
+    }
+
+    /// Adds an existing virtio-gpio device in the builder. This function should
+    /// only be used during snapshot restoration process and should add
+    /// devices in the same order as they were in the original VM.
+    pub fn add_device(&mut self, device: Arc<Mutex<Gpio>>) {
+        self.devices.push(device);
+    }
+
+    /// Returns a vec with the structures used to configure the devices.
+    pub fn configs(&self) -> Vec<GpioConfig> {
+        self.devices
+            .iter()
+            .map(|d| d.lock().unwrap().config.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::gpio::backend::GpioBackendError;
+
+    #[test]
+    fn test_gpio_builder_build() {
+        let dir = TempDir::new().unwrap();
+        let mut builder = GpioBuilder::default();
+
+        let mut config = GpioConfig {
+            id: "chip0".into(),
+            host_chip: None,
+            mock: Some(GpioMockConfig {
+                socket_path: dir.as_path().join("chip0.sock").to_str().unwrap().into(),
+                num_lines: 4,
+                line_names: vec![],
+            }),
+        };
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.devices.len(), 1);
+
+        // First device got replaced with new one, binding the same control socket.
+        config.mock.as_mut().unwrap().num_lines = 8;
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.configs(), vec![config.clone()]);
+
+        config.id = "chip1".into();
+        config.mock.as_mut().unwrap().socket_path =
+            dir.as_path().join("chip1.sock").to_str().unwrap().into();
+        builder.build(config).unwrap();
+        assert_eq!(builder.devices.len(), 2);
+
+        assert!(matches!(
+            builder.build(GpioConfig::default()).unwrap_err(),
+            GpioConfigError::CreateDevice(GpioError::Backend(GpioBackendError::InvalidBackend)),
+        ));
+        assert_eq!(builder.devices.len(), 2);
+    }
+
+    #[test]
+    fn test_gpio_config_serde() {
+        let config: GpioConfig = serde_json::from_str(
+            r#"{"id": "chip0", "mock": {"socket_path": "/tmp/gpio.sock", "num_lines": 2}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.host_chip, None);
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"id":"chip0","mock":{"socket_path":"/tmp/gpio.sock","num_lines":2,"line_names":[]}}"#
+        );
+        serde_json::from_str::<GpioConfig>(r#"{"id": "chip0", "foo": 1}"#).unwrap_err();
+    }
+}
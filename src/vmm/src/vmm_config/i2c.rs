@@ -0,0 +1,130 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::virtio::i2c::device::{I2c, I2cError};
+
+/// Errors associated with the operations allowed on a virtio-i2c device.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum I2cConfigError {
+    /// Unable to create the virtio-i2c device: {0}
+    CreateDevice(#[from] I2cError),
+}
+
+/// A target emulated by the mock adapter.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct I2cMockDeviceConfig {
+    /// 7-bit address of the target.
+    pub address: u16,
+    /// Initial content of the registers of the target. Missing registers are zeroed.
+    #[serde(default)]
+    pub registers: Vec<u8>,
+}
+
+/// Use this structure to set up a virtio-i2c adapter before booting the kernel. Exactly one of
+/// `host_adapter` and `mock_devices` must be set.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct I2cConfig {
+    /// Unique identifier of the device.
+    pub id: String,
+    /// Path of the host i2c-dev adapter the transfers are proxied to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_adapter: Option<String>,
+    /// Targets emulated by Firecracker.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mock_devices: Vec<I2cMockDeviceConfig>,
+}
+
+/// Wrapper for the collection that holds all the virtio-i2c devices.
+#[derive(Debug, Default)]
+pub struct I2cBuilder {
+    /// The list of virtio-i2c devices
+    pub devices: Vec<Arc<Mutex<I2c>>>,
+}
+
+impl I2cBuilder {
+    /// Build a device from the config, replacing the device with the same id if any.
+    pub fn build(&mut self, config: I2cConfig) -> Result<(), I2cConfigError> {
+        let position = self
+            .devices
+            .iter()
+            .position(|d| d.lock().unwrap().config.id == config.id);
+        let i2c = Arc::new(Mutex::new(I2c::new(config)?));
+        match position {
+            Some(index) => self.devices[index] = i2c,
+            None => self.devices.push(i2c),
+        }
+        Ok(())
+    }
+
+    /// Adds an existing virtio-i2c device in the builder. This function should
+    /// only be used during snapshot restoration process and should add
+    /// devices in the same order as they were in the original VM.
+    pub fn add_device(&mut self, device: Arc<Mutex<I2c>>) {
+        self.devices.push(device);
+    }
+
+    /// Returns a vec with the structures used to configure the devices.
+    pub fn configs(&self) -> Vec<I2cConfig> {
+        self.devices
+            .iter()
+            .map(|d| d.lock().unwrap().config.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::i2c::backend::I2cBackendError;
+
+    #[test]
+    fn test_i2c_builder_build() {
+        let mut builder = I2cBuilder::default();
+
+        let mut config = I2cConfig {
+            id: "bus0".into(),
+            host_adapter: None,
+            mock_devices: vec![I2cMockDeviceConfig {
+                address: 0x50,
+                registers: vec![1, 2],
+            }],
+        };
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.devices.len(), 1);
+
+        // First device got replaced with new one
+        config.mock_devices[0].address = 0x51;
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.configs(), vec![config.clone()]);
+
+        config.id = "bus1".into();
+        builder.build(config).unwrap();
+        assert_eq!(builder.devices.len(), 2);
+
+        assert!(matches!(
+            builder.build(I2cConfig::default()).unwrap_err(),
+            I2cConfigError::CreateDevice(I2cError::Backend(I2cBackendError::InvalidBackend)),
+        ));
+        assert_eq!(builder.devices.len(), 2);
+    }
+
+    #[test]
+    fn test_i2c_config_serde() {
+        let config: I2cConfig = serde_json::from_str(
+            r#"{"id": "bus0", "mock_devices": [{"address": 80, "registers": [1]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.host_adapter, None);
+        assert_eq!(
+            serde_json::to_string(&config).unwrap(),
+            r#"{"id":"bus0","mock_devices":[{"address":80,"registers":[1]}]}"#
+        );
+        serde_json::from_str::<I2cConfig>(r#"{"id": "bus0", "foo": 1}"#).unwrap_err();
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// Errors associated with the interrupt affinity configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum InterruptAffinityConfigError {
+    /// The interrupt affinity of a device can only be set on x86_64
+    UnsupportedArch,
+    /// The interrupt affinity of a device can only be set when the PCI transport is enabled
+    PciDisabled,
+    /// The vCPUs of the queues of device {0} must not be empty
+    NoQueues(String),
+    /// vCPU {1} targeted by the interrupts of device {0} does not exist
+    InvalidVcpu(String, u8),
+}
+
+/// Configuration of the vCPUs the interrupts of the queues of a device are delivered to.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterruptAffinityConfig {
+    /// ID of the device, as configured through the API.
+    pub device_id: String,
+    /// Index of the vCPU targeted by the interrupts of each queue of the device, in the order
+    /// of its queues. The queues past the end of the list keep the vCPU the guest programs.
+    pub queue_vcpus: Vec<u8>,
+}
+
+impl InterruptAffinityConfig {
+    /// Validates the configuration.
+    pub fn validate(&self) -> Result<(), InterruptAffinityConfigError> {
+        if cfg!(not(target_arch = "x86_64")) {
+            return Err(InterruptAffinityConfigError::UnsupportedArch);
+        }
+        if self.queue_vcpus.is_empty() {
+            return Err(InterruptAffinityConfigError::NoQueues(
+                self.device_id.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the vCPUs targeted by the interrupts exist, the microVM having `vcpu_count`
+    /// of them.
+    pub fn check_vcpus(&self, vcpu_count: u8) -> Result<(), InterruptAffinityConfigError> {
+        if let Some(&vcpu) = self.queue_vcpus.iter().find(|&&vcpu| vcpu >= vcpu_count) {
+            return Err(InterruptAffinityConfigError::InvalidVcpu(
+                self.device_id.clone(),
+                vcpu,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_interrupt_affinity_config() {
+        let config: InterruptAffinityConfig =
+            serde_json::from_str(r#"{"device_id": "eth0", "queue_vcpus": [1, 0]}"#).unwrap();
+        assert_eq!(config.queue_vcpus, [1, 0]);
+        config.validate().unwrap();
+        config.check_vcpus(2).unwrap();
+        assert_eq!(
+            config.check_vcpus(1),
+            Err(InterruptAffinityConfigError::InvalidVcpu(
+                "eth0".to_string(),
+                1
+            ))
+        );
+
+        let config = InterruptAffinityConfig {
+            device_id: "rootfs".to_string(),
+            queue_vcpus: Vec::new(),
+        };
+        assert_eq!(
+            config.validate(),
+            Err(InterruptAffinityConfigError::NoQueues("rootfs".to_string()))
+        );
+
+        serde_json::from_str::<InterruptAffinityConfig>(
+            r#"{"device_id": "eth0", "queue_vcpus": [0], "foo": 1}"#,
+        )
+        .unwrap_err();
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn test_interrupt_affinity_unsupported() {
+        let config = InterruptAffinityConfig {
+            device_id: "eth0".to_string(),
+            queue_vcpus: vec![0],
+        };
+        assert_eq!(
+            config.validate(),
+            Err(InterruptAffinityConfigError::UnsupportedArch)
+        );
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// Default period, in milliseconds, over which the I/O CPU budget is replenished.
+pub const DEFAULT_IO_CPU_BUDGET_PERIOD_MS: u64 = 100;
+/// Maximum period, in milliseconds, over which the I/O CPU budget is replenished.
+pub const MAX_IO_CPU_BUDGET_PERIOD_MS: u64 = 1000;
+
+/// Errors associated with the I/O CPU budget configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum IoCpuBudgetConfigError {
+    /// The CPU share must be between 1 and 100 percent, got {0}
+    InvalidCpuPercent(u32),
+    /// The budget period must be between 1 and 1000 milliseconds, got {0}
+    InvalidPeriod(u64),
+}
+
+fn default_period_ms() -> u64 {
+    DEFAULT_IO_CPU_BUDGET_PERIOD_MS
+}
+
+/// Configuration of the host CPU time the VMM thread may spend processing the I/O of the
+/// microVM's net and block devices.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IoCpuBudgetConfig {
+    /// Share, in percent of one host CPU, of each period that may be spent processing device I/O.
+    pub cpu_percent: u32,
+    /// Period, in milliseconds, over which the budget is replenished.
+    #[serde(default = "default_period_ms")]
+    pub period_ms: u64,
+}
+
+impl IoCpuBudgetConfig {
+    /// Validates the configuration.
+    pub fn validate(&self) -> Result<(), IoCpuBudgetConfigError> {
+        if !(1..=100).contains(&self.cpu_percent) {
+            return Err(IoCpuBudgetConfigError::InvalidCpuPercent(self.cpu_percent));
+        }
+        if !(1..=MAX_IO_CPU_BUDGET_PERIOD_MS).contains(&self.period_ms) {
+            return Err(IoCpuBudgetConfigError::InvalidPeriod(self.period_ms));
+        }
+        Ok(())
+    }
+
+    /// Returns the CPU time, in microseconds, available to device I/O in each period.
+    pub fn budget_us(&self) -> u64 {
+        self.period_ms * 1000 * u64::from(self.cpu_percent) / 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_cpu_budget_config() {
+        let config: IoCpuBudgetConfig = serde_json::from_str(r#"{"cpu_percent": 25}"#).unwrap();
+        assert_eq!(config.period_ms, DEFAULT_IO_CPU_BUDGET_PERIOD_MS);
+        config.validate().unwrap();
+        assert_eq!(config.budget_us(), 25_000);
+
+        let config = IoCpuBudgetConfig {
+            cpu_percent: 1,
+            period_ms: 1,
+        };
+        config.validate().unwrap();
+        assert_eq!(config.budget_us(), 10);
+
+        for cpu_percent in [0, 101] {
+            let config = IoCpuBudgetConfig {
+                cpu_percent,
+                period_ms: 100,
+            };
+            assert_eq!(
+                config.validate(),
+                Err(IoCpuBudgetConfigError::InvalidCpuPercent(cpu_percent))
+            );
+        }
+        for period_ms in [0, MAX_IO_CPU_BUDGET_PERIOD_MS + 1] {
+            let config = IoCpuBudgetConfig {
+                cpu_percent: 50,
+                period_ms,
+            };
+            assert_eq!(
+                config.validate(),
+                Err(IoCpuBudgetConfigError::InvalidPeriod(period_ms))
+            );
+        }
+
+        serde_json::from_str::<IoCpuBudgetConfig>(r#"{"cpu_percent": 25, "foo": 1}"#).unwrap_err();
+    }
+}
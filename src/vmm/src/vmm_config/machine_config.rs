@@ -11,6 +11,10 @@ pub const DEFAULT_MEM_SIZE_MIB: usize = 128;
 /// Firecracker aims to support small scale workloads only, so limit the maximum
 /// vCPUs supported.
 pub const MAX_SUPPORTED_VCPUS: u8 = 32;
+/// The smallest IPA size, in bits, KVM creates a VM with.
+pub const MIN_IPA_SIZE: u8 = 32;
+/// The largest IPA size, in bits, of the aarch64 architecture.
+pub const MAX_IPA_SIZE: u8 = 52;
 
 /// Errors associated with configuring the microVM.
 #[rustfmt::skip]
@@ -29,6 +33,21 @@ pub enum MachineConfigError {
     SmtNotSupported,
     /// Could not determine host kernel version when checking hugetlbfs compatibility
     KernelVersion,
+    /// The CPU topology must hold exactly the configured number of vCPUs, with 2 threads per core if SMT is enabled and 1 otherwise, and, on x86_64, a power of 2 number of vCPUs per socket when there are several sockets.
+    InvalidCpuTopology,
+    /// Configuring the TSC is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    TscNotSupported,
+    /// The TSC frequency must be greater than 0.
+    InvalidTscFrequency,
+    /// Configuring the IPA size is not supported on x86_64.
+    #[cfg(target_arch = "x86_64")]
+    IpaSizeNotSupported,
+    /// The IPA size must be between {MIN_IPA_SIZE:} and {MAX_IPA_SIZE:} bits.
+    InvalidIpaSize,
+    /// The ACPI power button is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    PowerButtonNotSupported,
 }
 
 /// Describes the possible (huge)page configurations for a microVM's memory.
@@ -87,6 +106,63 @@ impl From<HugePageConfig> for Option<memfd::HugetlbSize> {
     }
 }
 
+/// Topology of the vCPUs exposed to the guest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CpuTopology {
+    /// Number of sockets.
+    pub sockets: u8,
+    /// Number of cores in each socket.
+    pub cores_per_socket: u8,
+    /// Number of threads in each core.
+    pub threads_per_core: u8,
+}
+
+impl CpuTopology {
+    /// Returns the number of vCPUs in each socket.
+    pub fn cpus_per_socket(&self) -> u8 {
+        self.cores_per_socket * self.threads_per_core
+    }
+
+    /// Checks that the topology describes `vcpu_count` vCPUs, with or without SMT.
+    fn validate(&self, vcpu_count: u8, smt: bool) -> Result<(), MachineConfigError> {
+        let threads_per_core = if smt && vcpu_count > 1 { 2 } else { 1 };
+
+        if self.sockets == 0
+            || self.cores_per_socket == 0
+            || self.threads_per_core != threads_per_core
+            || u32::from(self.sockets)
+                * u32::from(self.cores_per_socket)
+                * u32::from(self.threads_per_core)
+                != u32::from(vcpu_count)
+        {
+            return Err(MachineConfigError::InvalidCpuTopology);
+        }
+
+        // The APIC ID of a vCPU is its index, and the guest derives the socket of a vCPU from
+        // the upper bits of its APIC ID.
+        #[cfg(target_arch = "x86_64")]
+        if self.sockets > 1 && !self.cpus_per_socket().is_power_of_two() {
+            return Err(MachineConfigError::InvalidCpuTopology);
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration of the TSC exposed to the guest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TscConfig {
+    /// Advertises an invariant TSC to the guest.
+    #[serde(default)]
+    pub invariant: bool,
+    /// TSC frequency of the vCPUs, in kHz. When unset, the vCPUs keep the TSC frequency of the
+    /// host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_khz: Option<u32>,
+}
+
 /// Struct used in PUT `/machine-config` API call.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -113,6 +189,20 @@ pub struct MachineConfig {
     /// Configures what page size Firecracker should use to back guest memory.
     #[serde(default)]
     pub huge_pages: HugePageConfig,
+    /// Topology of the vCPUs. When unset, all the vCPUs are in a single socket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_topology: Option<CpuTopology>,
+    /// TSC configuration of the vCPUs. When set, the TSC frequency is advertised to the guest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tsc: Option<TscConfig>,
+    /// Size, in bits, of the guest physical address space (aarch64 only). When unset, the VM is
+    /// created with the largest IPA size supported by the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipa_size: Option<u8>,
+    /// Exposes an ACPI power button, pressed by the `GracefulShutdown` action, to the guest
+    /// (x86_64 only). The button takes one of the interrupt lines available to devices.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub power_button: bool,
     /// GDB socket address.
     #[cfg(feature = "gdb")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -155,6 +245,10 @@ impl Default for MachineConfig {
             cpu_template: None,
             track_dirty_pages: false,
             huge_pages: HugePageConfig::None,
+            cpu_topology: None,
+            tsc: None,
+            ipa_size: None,
+            power_button: false,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         }
@@ -188,6 +282,18 @@ pub struct MachineConfigUpdate {
     /// Configures what page size Firecracker should use to back guest memory.
     #[serde(default)]
     pub huge_pages: Option<HugePageConfig>,
+    /// Topology of the vCPUs.
+    #[serde(default)]
+    pub cpu_topology: Option<CpuTopology>,
+    /// TSC configuration of the vCPUs.
+    #[serde(default)]
+    pub tsc: Option<TscConfig>,
+    /// Size, in bits, of the guest physical address space.
+    #[serde(default)]
+    pub ipa_size: Option<u8>,
+    /// Exposes an ACPI power button to the guest.
+    #[serde(default)]
+    pub power_button: Option<bool>,
     /// GDB socket address.
     #[cfg(feature = "gdb")]
     #[serde(default)]
@@ -212,6 +318,10 @@ impl From<MachineConfig> for MachineConfigUpdate {
             cpu_template: cfg.static_template(),
             track_dirty_pages: Some(cfg.track_dirty_pages),
             huge_pages: Some(cfg.huge_pages),
+            cpu_topology: cfg.cpu_topology,
+            tsc: cfg.tsc,
+            ipa_size: cfg.ipa_size,
+            power_button: Some(cfg.power_button),
             #[cfg(feature = "gdb")]
             gdb_socket_path: cfg.gdb_socket_path,
         }
@@ -224,6 +334,25 @@ impl MachineConfig {
         self.cpu_template = Some(CpuTemplateType::Custom(cpu_template));
     }
 
+    /// Returns the topology of the vCPUs. When no topology is configured, all the vCPUs are in a
+    /// single socket, with 2 threads per core if SMT is enabled.
+    pub fn topology(&self) -> CpuTopology {
+        if let Some(topology) = self.cpu_topology {
+            return topology;
+        }
+
+        let threads_per_core = if self.smt && self.vcpu_count > 1 {
+            2
+        } else {
+            1
+        };
+        CpuTopology {
+            sockets: 1,
+            cores_per_socket: self.vcpu_count / threads_per_core,
+            threads_per_core,
+        }
+    }
+
     fn static_template(&self) -> Option<StaticCpuTemplate> {
         match self.cpu_template {
             Some(CpuTemplateType::Static(template)) => Some(template),
@@ -272,6 +401,45 @@ impl MachineConfig {
             Some(other) => Some(CpuTemplateType::Static(other)),
         };
 
+        // The topology describes the vCPUs, so it is dropped when they are updated without a new
+        // topology.
+        let cpu_topology = match update.cpu_topology {
+            None if update.vcpu_count.is_none() && update.smt.is_none() => self.cpu_topology,
+            topology => topology,
+        };
+        if let Some(topology) = &cpu_topology {
+            topology.validate(vcpu_count, smt)?;
+        }
+
+        let tsc = update.tsc.or(self.tsc);
+
+        #[cfg(target_arch = "aarch64")]
+        if tsc.is_some() {
+            return Err(MachineConfigError::TscNotSupported);
+        }
+
+        if tsc.is_some_and(|tsc| tsc.frequency_khz == Some(0)) {
+            return Err(MachineConfigError::InvalidTscFrequency);
+        }
+
+        let ipa_size = update.ipa_size.or(self.ipa_size);
+
+        #[cfg(target_arch = "x86_64")]
+        if ipa_size.is_some() {
+            return Err(MachineConfigError::IpaSizeNotSupported);
+        }
+
+        if ipa_size.is_some_and(|ipa_size| !(MIN_IPA_SIZE..=MAX_IPA_SIZE).contains(&ipa_size)) {
+            return Err(MachineConfigError::InvalidIpaSize);
+        }
+
+        let power_button = update.power_button.unwrap_or(self.power_button);
+
+        #[cfg(target_arch = "aarch64")]
+        if power_button {
+            return Err(MachineConfigError::PowerButtonNotSupported);
+        }
+
         Ok(MachineConfig {
             vcpu_count,
             mem_size_mib,
@@ -279,6 +447,10 @@ impl MachineConfig {
             cpu_template,
             track_dirty_pages: update.track_dirty_pages.unwrap_or(self.track_dirty_pages),
             huge_pages: page_config,
+            cpu_topology,
+            tsc,
+            ipa_size,
+            power_button,
             #[cfg(feature = "gdb")]
             gdb_socket_path: update.gdb_socket_path.clone(),
         })
@@ -288,7 +460,9 @@ impl MachineConfig {
 #[cfg(test)]
 mod tests {
     use crate::cpu_config::templates::{CpuTemplateType, CustomCpuTemplate, StaticCpuTemplate};
-    use crate::vmm_config::machine_config::MachineConfig;
+    use crate::vmm_config::machine_config::{
+        CpuTopology, MachineConfig, MachineConfigError, MachineConfigUpdate, TscConfig,
+    };
 
     // Ensure the special (de)serialization logic for the cpu_template field works:
     // only static cpu templates can be specified via the machine-config endpoint, but
@@ -335,4 +509,237 @@ mod tests {
 
         assert!(deserialized.cpu_template.is_none());
     }
+
+    #[test]
+    fn test_cpu_topology() {
+        let mconfig = MachineConfig {
+            vcpu_count: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            mconfig.topology(),
+            CpuTopology {
+                sockets: 1,
+                cores_per_socket: 4,
+                threads_per_core: 1,
+            }
+        );
+
+        let topology = CpuTopology {
+            sockets: 2,
+            cores_per_socket: 2,
+            threads_per_core: 1,
+        };
+        let mconfig = mconfig
+            .update(&MachineConfigUpdate {
+                cpu_topology: Some(topology),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(mconfig.topology(), topology);
+        assert_eq!(mconfig.topology().cpus_per_socket(), 2);
+
+        // The topology is kept when updating other fields.
+        let mconfig = mconfig
+            .update(&MachineConfigUpdate {
+                mem_size_mib: Some(256),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(mconfig.cpu_topology, Some(topology));
+
+        // The topology is dropped when updating the vCPUs without a new topology.
+        let updated = mconfig
+            .update(&MachineConfigUpdate {
+                vcpu_count: Some(3),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(updated.cpu_topology, None);
+
+        // The topology must hold all the vCPUs.
+        assert_eq!(
+            mconfig.update(&MachineConfigUpdate {
+                vcpu_count: Some(6),
+                cpu_topology: Some(topology),
+                ..Default::default()
+            }),
+            Err(MachineConfigError::InvalidCpuTopology)
+        );
+        assert_eq!(
+            mconfig.update(&MachineConfigUpdate {
+                cpu_topology: Some(CpuTopology {
+                    sockets: 0,
+                    ..topology
+                }),
+                ..Default::default()
+            }),
+            Err(MachineConfigError::InvalidCpuTopology)
+        );
+
+        // The threads per core must match SMT.
+        assert_eq!(
+            mconfig.update(&MachineConfigUpdate {
+                cpu_topology: Some(CpuTopology {
+                    sockets: 2,
+                    cores_per_socket: 1,
+                    threads_per_core: 2,
+                }),
+                ..Default::default()
+            }),
+            Err(MachineConfigError::InvalidCpuTopology)
+        );
+        #[cfg(target_arch = "x86_64")]
+        {
+            let smt_topology = CpuTopology {
+                sockets: 2,
+                cores_per_socket: 1,
+                threads_per_core: 2,
+            };
+            let updated = mconfig
+                .update(&MachineConfigUpdate {
+                    smt: Some(true),
+                    cpu_topology: Some(smt_topology),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(updated.topology(), smt_topology);
+
+            // With several sockets, the number of vCPUs per socket must be a power of 2.
+            assert_eq!(
+                mconfig.update(&MachineConfigUpdate {
+                    vcpu_count: Some(6),
+                    cpu_topology: Some(CpuTopology {
+                        sockets: 2,
+                        cores_per_socket: 3,
+                        threads_per_core: 1,
+                    }),
+                    ..Default::default()
+                }),
+                Err(MachineConfigError::InvalidCpuTopology)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tsc_config() {
+        let mconfig = MachineConfig::default();
+        let tsc = TscConfig {
+            invariant: true,
+            frequency_khz: Some(2_500_000),
+        };
+        let update = MachineConfigUpdate {
+            tsc: Some(tsc),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            mconfig.update(&update),
+            Err(MachineConfigError::TscNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let mconfig = mconfig.update(&update).unwrap();
+            assert_eq!(mconfig.tsc, Some(tsc));
+
+            // The TSC configuration is kept when updating other fields.
+            let mconfig = mconfig
+                .update(&MachineConfigUpdate {
+                    vcpu_count: Some(2),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(mconfig.tsc, Some(tsc));
+
+            assert_eq!(
+                mconfig.update(&MachineConfigUpdate {
+                    tsc: Some(TscConfig {
+                        invariant: false,
+                        frequency_khz: Some(0),
+                    }),
+                    ..Default::default()
+                }),
+                Err(MachineConfigError::InvalidTscFrequency)
+            );
+        }
+
+        let tsc: TscConfig = serde_json::from_str(r#"{"invariant": true}"#).unwrap();
+        assert_eq!(tsc.frequency_khz, None);
+        serde_json::from_str::<TscConfig>(r#"{"frequency": 1}"#).unwrap_err();
+    }
+
+    #[test]
+    fn test_ipa_size() {
+        let mconfig = MachineConfig::default();
+        let update = MachineConfigUpdate {
+            ipa_size: Some(48),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            mconfig.update(&update),
+            Err(MachineConfigError::IpaSizeNotSupported)
+        );
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            use crate::vmm_config::machine_config::{MAX_IPA_SIZE, MIN_IPA_SIZE};
+
+            let mconfig = mconfig.update(&update).unwrap();
+            assert_eq!(mconfig.ipa_size, Some(48));
+
+            // The IPA size is kept when updating other fields.
+            let mconfig = mconfig
+                .update(&MachineConfigUpdate {
+                    mem_size_mib: Some(1024),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(mconfig.ipa_size, Some(48));
+
+            for ipa_size in [0, MIN_IPA_SIZE - 1, MAX_IPA_SIZE + 1] {
+                assert_eq!(
+                    mconfig.update(&MachineConfigUpdate {
+                        ipa_size: Some(ipa_size),
+                        ..Default::default()
+                    }),
+                    Err(MachineConfigError::InvalidIpaSize)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_button() {
+        let mconfig = MachineConfig::default();
+        assert!(!mconfig.power_button);
+        let update = MachineConfigUpdate {
+            power_button: Some(true),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            mconfig.update(&update),
+            Err(MachineConfigError::PowerButtonNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let mconfig = mconfig.update(&update).unwrap();
+            assert!(mconfig.power_button);
+
+            // The power button is kept when updating other fields.
+            let mconfig = mconfig
+                .update(&MachineConfigUpdate {
+                    mem_size_mib: Some(1024),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert!(mconfig.power_button);
+        }
+    }
 }
@@ -0,0 +1,69 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Errors associated with the guest memory backing file configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum MemoryBackingConfigError {
+    /// The writeback interval must be positive
+    InvalidWritebackInterval,
+}
+
+/// Configuration of the file backing the guest memory.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryBackingConfig {
+    /// Path of the file backing the guest memory. It is created if it does not exist, and its
+    /// contents are discarded when the microVM starts.
+    pub path: PathBuf,
+    /// Interval, in milliseconds, at which Firecracker starts the writeback of dirty guest memory
+    /// to the file. When unset, dirty guest memory is written back according to the writeback
+    /// settings of the host kernel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub writeback_interval_ms: Option<u64>,
+    /// Whether to synchronously write dirty guest memory back to the file whenever the microVM is
+    /// paused.
+    #[serde(default)]
+    pub flush_on_pause: bool,
+}
+
+impl MemoryBackingConfig {
+    /// Validates the configuration.
+    pub fn validate(&self) -> Result<(), MemoryBackingConfigError> {
+        if self.writeback_interval_ms == Some(0) {
+            return Err(MemoryBackingConfigError::InvalidWritebackInterval);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_backing_config() {
+        let config: MemoryBackingConfig =
+            serde_json::from_str(r#"{"path": "/tmp/guest_mem"}"#).unwrap();
+        assert_eq!(config.path, PathBuf::from("/tmp/guest_mem"));
+        assert_eq!(config.writeback_interval_ms, None);
+        assert!(!config.flush_on_pause);
+        config.validate().unwrap();
+
+        let config: MemoryBackingConfig = serde_json::from_str(
+            r#"{"path": "/tmp/guest_mem", "writeback_interval_ms": 0, "flush_on_pause": true}"#,
+        )
+        .unwrap();
+        assert!(config.flush_on_pause);
+        assert_eq!(
+            config.validate(),
+            Err(MemoryBackingConfigError::InvalidWritebackInterval)
+        );
+
+        serde_json::from_str::<MemoryBackingConfig>(r#"{"path": "/tmp/guest_mem", "foo": 1}"#)
+            .unwrap_err();
+    }
+}
@@ -4,6 +4,7 @@ use std::net::Ipv4Addr;
 
 use serde::{Deserialize, Serialize};
 
+use super::RateLimiterConfig;
 use crate::mmds::data_store;
 use crate::mmds::data_store::MmdsVersion;
 
@@ -21,6 +22,16 @@ pub struct MmdsConfig {
     /// Compatibility with EC2 IMDS.
     #[serde(default)]
     pub imds_compat: bool,
+    /// Limits the frames each network interface can send to MMDS.
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
+/// Update of the MMDS configuration, applied to the data store at any time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MmdsConfigUpdate {
+    /// Maximum size, in bytes, of the serialized MMDS data store.
+    pub size_limit: usize,
 }
 
 impl MmdsConfig {
@@ -39,6 +50,11 @@ impl MmdsConfig {
     pub fn ipv4_addr(&self) -> Option<Ipv4Addr> {
         self.ipv4_address
     }
+
+    /// Returns the MMDS rate limiter configuration if one was configured.
+    pub fn rate_limiter(&self) -> Option<RateLimiterConfig> {
+        self.rate_limiter
+    }
 }
 
 /// MMDS configuration related errors.
@@ -53,4 +69,6 @@ pub enum MmdsConfigError {
     InvalidNetworkInterfaceId,
     /// Failed to initialize MMDS data store: {0}
     InitMmdsDatastore(#[from] data_store::MmdsDatastoreError),
+    /// Cannot create the MMDS rate limiter: {0}
+    CreateRateLimiter(std::io::Error),
 }
@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::convert::{From, TryInto};
+use std::fs::{File, OpenOptions};
 use std::io;
+use std::os::unix::io::AsRawFd;
 
 use serde::{Deserialize, Serialize};
 
@@ -12,14 +14,34 @@ use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenBucket};
 pub mod balloon;
 /// Wrapper for configuring the microVM boot source.
 pub mod boot_source;
+/// Wrapper for configuring the compressed backing store of cold guest memory.
+pub mod cold_memory;
+/// Wrapper for configuring the cloud-init configuration drive.
+pub mod config_drive;
+/// Wrapper for the removal of devices from a running microVM.
+pub mod device_removal;
 /// Wrapper for configuring the block devices.
 pub mod drive;
 /// Wrapper for configuring the entropy device attached to the microVM.
 pub mod entropy;
+/// Wrapper for configuring the firmware booting the microVM.
+pub mod firmware;
+/// Wrapper for configuring the fw_cfg device exposing metadata to the guest.
+pub mod fw_cfg;
+/// Wrapper for configuring the virtio-gpio devices attached to the microVM.
+pub mod gpio;
+/// Wrapper for configuring the virtio-i2c devices attached to the microVM.
+pub mod i2c;
 /// Wrapper over the microVM general information attached to the microVM.
 pub mod instance_info;
+/// Wrapper for configuring the vCPUs the interrupts of the devices are delivered to.
+pub mod interrupt_affinity;
+/// Wrapper for configuring the host CPU budget of the device I/O processing.
+pub mod io_cpu_budget;
 /// Wrapper for configuring the memory and CPU of the microVM.
 pub mod machine_config;
+/// Wrapper for configuring the file backing the guest memory.
+pub mod memory_backing;
 /// Wrapper for configuring memory hotplug.
 pub mod memory_hotplug;
 /// Wrapper for configuring the metrics.
@@ -28,12 +50,18 @@ pub mod metrics;
 pub mod mmds;
 /// Wrapper for configuring the network devices attached to the microVM.
 pub mod net;
+/// Wrapper for configuring the virtio-9p devices attached to the microVM.
+pub mod p9;
 /// Wrapper for configuring the pmem devises attached to the microVM.
 pub mod pmem;
 /// Wrapper for configuring RDMA devices attached to the microVM.
 pub mod rdma;
 /// Wrapper for configuring microVM snapshots and the microVM state.
 pub mod serial;
+/// Wrapper for the graceful shutdown of the microVM.
+pub mod shutdown;
+/// Wrapper for configuring the SMBIOS tables reported to the guest.
+pub mod smbios;
 pub mod snapshot;
 /// Wrapper for configuring the vsock devices attached to the microVM.
 pub mod vsock;
@@ -167,6 +195,54 @@ impl RateLimiterConfig {
     }
 }
 
+/// File descriptor passed over the API socket by the process owning the underlying file, used
+/// instead of a path on the host.
+#[derive(Debug)]
+pub struct PassedFd(pub File);
+
+impl PassedFd {
+    /// Returns a file allowing the requested access.
+    ///
+    /// File descriptors opened with `O_PATH` only identify the file, which is reopened through
+    /// `/proc/self/fd`. Other file descriptors are duplicated, provided they were opened with the
+    /// requested access mode.
+    pub fn open(&self, read: bool, write: bool) -> Result<File, io::Error> {
+        let fd = self.0.as_raw_fd();
+        // SAFETY: Safe because the fd is valid and we check the return value.
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if flags & libc::O_PATH != 0 {
+            return OpenOptions::new()
+                .read(read)
+                .write(write)
+                .open(format!("/proc/self/fd/{}", fd));
+        }
+
+        let (readable, writable) = match flags & libc::O_ACCMODE {
+            libc::O_RDONLY => (true, false),
+            libc::O_WRONLY => (false, true),
+            _ => (true, true),
+        };
+        if (read && !readable) || (write && !writable) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the file descriptor was not opened with the required access mode",
+            ));
+        }
+        self.0.try_clone()
+    }
+}
+
+impl PartialEq for PassedFd {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_raw_fd() == other.0.as_raw_fd()
+    }
+}
+
+impl Eq for PassedFd {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -218,4 +294,38 @@ mod tests {
         assert_eq!(generated_rl_conf, rl_conf);
         assert_eq!(generated_rl_conf.into_option(), Some(rl_conf));
     }
+
+    #[test]
+    fn test_passed_fd() {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let tmp_file = vmm_sys_util::tempfile::TempFile::new().unwrap();
+
+        let read_only = PassedFd(File::open(tmp_file.as_path()).unwrap());
+        read_only.open(true, false).unwrap();
+        assert_eq!(
+            read_only.open(true, true).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        let write_only = PassedFd(
+            OpenOptions::new()
+                .write(true)
+                .open(tmp_file.as_path())
+                .unwrap(),
+        );
+        write_only.open(false, true).unwrap();
+        write_only.open(true, false).unwrap_err();
+
+        // `O_PATH` file descriptors are reopened with the requested access mode.
+        let path_only = PassedFd(
+            OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH)
+                .open(tmp_file.as_path())
+                .unwrap(),
+        );
+        let file = path_only.open(true, true).unwrap();
+        assert_ne!(file.as_raw_fd(), path_only.0.as_raw_fd());
+    }
 }
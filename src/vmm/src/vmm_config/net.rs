@@ -7,9 +7,10 @@ use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
-use super::RateLimiterConfig;
+use super::{PassedFd, RateLimiterConfig};
 use crate::VmmError;
 use crate::devices::virtio::device::VirtioDevice;
+use crate::devices::virtio::net::flows::{FlowSamplingConfig, FlowSamplingError};
 use crate::devices::virtio::net::{Net, TapError};
 use crate::utils::net::mac::MacAddr;
 
@@ -20,7 +21,9 @@ use crate::utils::net::mac::MacAddr;
 pub struct NetworkInterfaceConfig {
     /// ID of the guest network interface.
     pub iface_id: String,
-    /// Host level path for the guest network interface.
+    /// Host level path for the guest network interface. Can be omitted if the tap is passed
+    /// in `tap_fd`, otherwise it must match the name of that tap.
+    #[serde(default)]
     pub host_dev_name: String,
     /// Guest MAC address.
     pub guest_mac: Option<MacAddr>,
@@ -28,6 +31,12 @@ pub struct NetworkInterfaceConfig {
     pub rx_rate_limiter: Option<RateLimiterConfig>,
     /// Rate Limiter for transmitted packages.
     pub tx_rate_limiter: Option<RateLimiterConfig>,
+    /// Sampled flow table of the interface, disabled if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_sampling: Option<FlowSamplingConfig>,
+    /// Already set up tap, used instead of opening `host_dev_name`.
+    #[serde(skip)]
+    pub tap_fd: Option<PassedFd>,
 }
 
 impl From<&Net> for NetworkInterfaceConfig {
@@ -40,12 +49,14 @@ impl From<&Net> for NetworkInterfaceConfig {
             guest_mac: net.guest_mac().copied(),
             rx_rate_limiter: rx_rl.into_option(),
             tx_rate_limiter: tx_rl.into_option(),
+            flow_sampling: net.flow_sampling(),
+            tap_fd: None,
         }
     }
 }
 
 /// The data fed into a network iface update request. Currently, only the RX and TX rate limiters
-/// can be updated.
+/// and the link state can be updated.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct NetworkInterfaceUpdateConfig {
@@ -57,6 +68,8 @@ pub struct NetworkInterfaceUpdateConfig {
     /// New TX rate limiter config. Only provided data will be updated. I.e. if any optional data
     /// is missing, it will not be nullified, but left unchanged.
     pub tx_rate_limiter: Option<RateLimiterConfig>,
+    /// New link state reported to the guest. The link state is left unchanged if missing.
+    pub link_up: Option<bool>,
 }
 
 /// Errors associated with the operations allowed on a net device.
@@ -72,6 +85,12 @@ pub enum NetworkInterfaceError {
     GuestMacAddressInUse(String),
     /// Cannot open/create the tap device: {0}
     OpenTap(#[from] TapError),
+    /// Invalid flow sampling configuration: {0}
+    FlowSampling(#[from] FlowSamplingError),
+    /// Either a host device name or a tap file descriptor must be provided
+    MissingTap,
+    /// The host device name {0} does not match the name of the passed tap {1}
+    TapNameMismatch(String, String),
 }
 
 /// Builder for a list of network devices.
@@ -99,6 +118,15 @@ impl NetBuilder {
         self.net_devices.push(device);
     }
 
+    /// Removes the network device with the specified `iface_id` from the builder.
+    pub fn remove(&mut self, iface_id: &str) -> Option<Arc<Mutex<Net>>> {
+        let index = self
+            .net_devices
+            .iter()
+            .position(|net| net.lock().expect("Poisoned lock").id() == iface_id)?;
+        Some(self.net_devices.remove(index))
+    }
+
     /// Builds a network device based on a network interface config. Keeps a device reference
     /// in the builder's internal list.
     pub fn build(
@@ -150,15 +178,41 @@ impl NetBuilder {
             .transpose()
             .map_err(NetworkInterfaceError::CreateRateLimiter)?;
 
+        if let Some(flow_sampling) = &cfg.flow_sampling {
+            flow_sampling.validate()?;
+        }
+
         // Create and return the Net device
-        crate::devices::virtio::net::Net::new(
-            cfg.iface_id,
-            &cfg.host_dev_name,
-            cfg.guest_mac,
-            rx_rate_limiter.unwrap_or_default(),
-            tx_rate_limiter.unwrap_or_default(),
-        )
-        .map_err(NetworkInterfaceError::CreateNetworkDevice)
+        let mut net = match cfg.tap_fd {
+            Some(PassedFd(tap_file)) => {
+                let net = Net::new_with_tap_file(
+                    cfg.iface_id,
+                    tap_file,
+                    cfg.guest_mac,
+                    rx_rate_limiter.unwrap_or_default(),
+                    tx_rate_limiter.unwrap_or_default(),
+                )
+                .map_err(NetworkInterfaceError::CreateNetworkDevice)?;
+                if !cfg.host_dev_name.is_empty() && cfg.host_dev_name != net.iface_name() {
+                    return Err(NetworkInterfaceError::TapNameMismatch(
+                        cfg.host_dev_name,
+                        net.iface_name(),
+                    ));
+                }
+                net
+            }
+            None if cfg.host_dev_name.is_empty() => return Err(NetworkInterfaceError::MissingTap),
+            None => Net::new(
+                cfg.iface_id,
+                &cfg.host_dev_name,
+                cfg.guest_mac,
+                rx_rate_limiter.unwrap_or_default(),
+                tx_rate_limiter.unwrap_or_default(),
+            )
+            .map_err(NetworkInterfaceError::CreateNetworkDevice)?,
+        };
+        net.set_flow_sampling(cfg.flow_sampling)?;
+        Ok(net)
     }
 
     /// Returns a vec with the structures used to configure the net devices.
@@ -191,6 +245,8 @@ mod tests {
             guest_mac: Some(MacAddr::from_str(mac).unwrap()),
             rx_rate_limiter: RateLimiterConfig::default().into_option(),
             tx_rate_limiter: RateLimiterConfig::default().into_option(),
+            flow_sampling: None,
+            tap_fd: None,
         }
     }
 
@@ -202,6 +258,8 @@ mod tests {
                 guest_mac: self.guest_mac,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                flow_sampling: self.flow_sampling,
+                tap_fd: None,
             }
         }
     }
@@ -319,6 +377,56 @@ mod tests {
         let configs = net_builder.configs();
         assert_eq!(configs.len(), 1);
         assert_eq!(configs.first().unwrap(), &net_if_cfg);
+
+        let mut net_if_cfg = create_netif(net_id, host_dev_name, guest_mac);
+        net_if_cfg.flow_sampling = Some(FlowSamplingConfig {
+            sample_rate: 10,
+            max_flows: 0,
+            idle_timeout_s: 60,
+        });
+        assert!(matches!(
+            net_builder.build(net_if_cfg.clone()),
+            Err(NetworkInterfaceError::FlowSampling(
+                FlowSamplingError::InvalidMaxFlows(0)
+            ))
+        ));
+        net_if_cfg.flow_sampling.as_mut().unwrap().max_flows = 128;
+        net_builder.build(net_if_cfg.clone()).unwrap();
+        assert_eq!(
+            net_builder.configs()[0].flow_sampling,
+            net_if_cfg.flow_sampling
+        );
+    }
+
+    #[test]
+    fn test_tap_fd() {
+        let mut net_builder = NetBuilder::new();
+
+        let netif = create_netif("id_1", "", "01:23:45:67:89:0a");
+        assert!(matches!(
+            net_builder.build(netif),
+            Err(NetworkInterfaceError::MissingTap)
+        ));
+
+        let mut netif = create_netif("id_1", "", "01:23:45:67:89:0a");
+        let file = vmm_sys_util::tempfile::TempFile::new().unwrap().into_file();
+        netif.tap_fd = Some(PassedFd(file));
+        assert!(matches!(
+            net_builder.build(netif),
+            Err(NetworkInterfaceError::CreateNetworkDevice(
+                crate::devices::virtio::net::NetError::TapOpen(TapError::InvalidTapFd(_))
+            ))
+        ));
+        assert_eq!(net_builder.net_devices.len(), 0);
+
+        // The tap file is not part of the JSON configuration.
+        let netif = create_netif("id_1", "dev1", "01:23:45:67:89:0a");
+        let json = serde_json::to_string(&netif).unwrap();
+        assert!(!json.contains("tap_fd"));
+        serde_json::from_str::<NetworkInterfaceConfig>(
+            r#"{"iface_id": "id_1", "host_dev_name": "dev1", "tap_fd": 3}"#,
+        )
+        .unwrap_err();
     }
 
     #[test]
@@ -351,4 +459,23 @@ mod tests {
             net_id
         );
     }
+
+    #[test]
+    fn test_remove() {
+        let mut net_builder = NetBuilder::new();
+        net_builder
+            .build(create_netif("id_1", "dev1", "01:23:45:67:89:0a"))
+            .unwrap();
+        net_builder
+            .build(create_netif("id_2", "dev2", "01:23:45:67:89:0b"))
+            .unwrap();
+
+        assert!(net_builder.remove("id_3").is_none());
+        assert_eq!(
+            net_builder.remove("id_1").unwrap().lock().unwrap().id(),
+            "id_1"
+        );
+        assert_eq!(net_builder.net_devices.len(), 1);
+        assert_eq!(net_builder.configs()[0].iface_id, "id_2");
+    }
 }
@@ -0,0 +1,131 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::devices::virtio::p9::device::{P9, P9Error};
+
+/// Errors associated with the operations allowed on a virtio-9p device.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum P9ConfigError {
+    /// The mount tag {0} is already used by another 9p device
+    DuplicateMountTag(String),
+    /// Unable to create the virtio-9p device: {0}
+    CreateDevice(#[from] P9Error),
+}
+
+/// Use this structure to share a host directory with the guest before booting the kernel.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct P9Config {
+    /// Unique identifier of the device.
+    pub id: String,
+    /// Path of the shared directory on the host.
+    pub path_on_host: String,
+    /// Tag used by the guest to mount the shared directory.
+    pub mount_tag: String,
+    /// Reject operations modifying the shared directory.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Wrapper for the collection that holds all the virtio-9p devices.
+#[derive(Debug, Default)]
+pub struct P9Builder {
+    /// The list of virtio-9p devices
+    pub devices: Vec<Arc<Mutex<P9>>>,
+}
+
+impl P9Builder {
+    /// Build a device from the config, replacing the device with the same id if any.
+    pub fn build(&mut self, config: P9Config) -> Result<(), P9ConfigError> {
+        if self.devices.iter().any(|d| {
+            let d = d.lock().unwrap();
+            d.config.id != config.id && d.config.mount_tag == config.mount_tag
+        }) {
+            return Err(P9ConfigError::DuplicateMountTag(config.mount_tag));
+        }
+        let position = self
+            .devices
+            .iter()
+            .position(|d| d.lock().unwrap().config.id == config.id);
+        let p9 = Arc::new(Mutex::new(P9::new(config)?));
+        match position {
+            Some(index) => self.devices[index] = p9,
+            None => self.devices.push(p9),
+        }
+        Ok(())
+    }
+
+    /// Adds an existing virtio-9p device in the builder. This function should
+    /// only be used during snapshot restoration process and should add
+    /// devices in the same order as they were in the original VM.
+    pub fn add_device(&mut self, device: Arc<Mutex<P9>>) {
+        self.devices.push(device);
+    }
+
+    /// Returns a vec with the structures used to configure the devices.
+    pub fn configs(&self) -> Vec<P9Config> {
+        self.devices
+            .iter()
+            .map(|d| d.lock().unwrap().config.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_p9_builder_build() {
+        let mut builder = P9Builder::default();
+
+        let dir = TempDir::new().unwrap();
+        let mut config = P9Config {
+            id: "1".into(),
+            path_on_host: dir.as_path().to_str().unwrap().to_string(),
+            mount_tag: "share".into(),
+            read_only: false,
+        };
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.devices.len(), 1);
+
+        // First device got replaced with new one
+        config.read_only = true;
+        builder.build(config.clone()).unwrap();
+        assert_eq!(builder.devices.len(), 1);
+        assert_eq!(builder.configs(), vec![config.clone()]);
+
+        // Mount tags must be unique
+        config.id = "2".into();
+        assert!(matches!(
+            builder.build(config.clone()).unwrap_err(),
+            P9ConfigError::DuplicateMountTag(_),
+        ));
+        config.mount_tag = "other".into();
+        builder.build(config).unwrap();
+        assert_eq!(builder.devices.len(), 2);
+    }
+
+    #[test]
+    fn test_p9_builder_build_invalid() {
+        let mut builder = P9Builder::default();
+
+        let config = P9Config {
+            id: "1".into(),
+            path_on_host: "/does/not/exist".into(),
+            mount_tag: "share".into(),
+            read_only: false,
+        };
+        assert!(matches!(
+            builder.build(config).unwrap_err(),
+            P9ConfigError::CreateDevice(P9Error::SharedDir(_)),
+        ));
+        assert!(builder.devices.is_empty());
+    }
+}
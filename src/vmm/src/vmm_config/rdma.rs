@@ -1,12 +1,24 @@
 // Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
+use super::RateLimiterConfig;
+use crate::VmmError;
 use crate::devices::virtio::device::VirtioDevice;
+use crate::devices::virtio::rdma::backend::fault::RdmaFaultPolicy;
+use crate::devices::virtio::rdma::backend::thread::BackendLauncher;
+use crate::devices::virtio::rdma::backend::{RdmaBackendConfig, RdmaBackendError};
+use crate::devices::virtio::rdma::device::{RdmaCqModeration, RdmaResourceLimits};
+use crate::devices::virtio::rdma::pkey::PkeyTableError;
 use crate::devices::virtio::rdma::{RdmaError, VirtioRdma};
+use crate::utils::net::mac::MacAddr;
+
+/// Number of RDMA devices a microVM can have unless configured otherwise.
+pub const DEFAULT_MAX_RDMA_DEVICES: usize = 8;
 
 /// Use this structure to set up an RDMA device before booting the kernel.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,27 +26,161 @@ use crate::devices::virtio::rdma::{RdmaError, VirtioRdma};
 pub struct RdmaDeviceConfig {
     /// Unique identifier of the device.
     pub id: String,
+    /// MAC address the link-local RoCEv2 GID of the device is derived from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<MacAddr>,
+    /// IPv4 or IPv6 address the routable RoCEv2 GID of the device is derived from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+    /// P_Keys added to the P_Key table of the device, next to the default one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pkeys: Vec<u16>,
+    /// Number of send and receive queue pairs of the data path, one per vCPU by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_queues: Option<u16>,
+    /// MTU of the ports, in bytes, 1024 bytes by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Moderation of the completion interrupts, which are raised for each batch of work
+    /// completions by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cq_moderation: Option<RdmaCqModeration>,
+    /// Backend carrying the messages of the device, the loopback one by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<RdmaBackendConfig>,
+    /// Faults injected into the device, to trigger the error paths of the driver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fault_policy: Option<RdmaFaultPolicy>,
+    /// Maximum number of queue pairs the driver creates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_qp: Option<u32>,
+    /// Maximum number of completion queues the driver creates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cq: Option<u32>,
+    /// Maximum number of memory regions the driver registers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_mr: Option<u32>,
+    /// Maximum number of bytes of guest memory the memory regions register together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_registration_bytes: Option<u64>,
+    /// Maximum number of work requests outstanding on a queue pair, past which posting more
+    /// fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_outstanding_wr_per_qp: Option<u32>,
+    /// Maximum number of bytes of the buffers of the work requests outstanding on a queue pair.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_outstanding_bytes_per_qp: Option<u64>,
+    /// Maximum number of work requests outstanding in a protection domain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_outstanding_wr_per_pd: Option<u32>,
+    /// Maximum number of bytes of the buffers of the work requests outstanding in a protection
+    /// domain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_outstanding_bytes_per_pd: Option<u64>,
+    /// Rate limiter of the send work requests, and of the bytes they carry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
+impl RdmaDeviceConfig {
+    fn resource_limits(&self) -> RdmaResourceLimits {
+        RdmaResourceLimits {
+            max_qp: self.max_qp,
+            max_cq: self.max_cq,
+            max_mr: self.max_mr,
+            max_memory_registration_bytes: self.max_memory_registration_bytes,
+            max_outstanding_wr_per_qp: self.max_outstanding_wr_per_qp,
+            max_outstanding_bytes_per_qp: self.max_outstanding_bytes_per_qp,
+            max_outstanding_wr_per_pd: self.max_outstanding_wr_per_pd,
+            max_outstanding_bytes_per_pd: self.max_outstanding_bytes_per_pd,
+        }
+    }
 }
 
 impl From<&VirtioRdma> for RdmaDeviceConfig {
     fn from(device: &VirtioRdma) -> Self {
+        let limits = device.resource_limits();
         RdmaDeviceConfig {
             id: device.id().to_string(),
+            mac: device.gid_table().mac(),
+            ip: device.gid_table().ip(),
+            pkeys: device.pkey_table().configured(),
+            num_queues: device.configured_num_queues(),
+            mtu: device.configured_mtu(),
+            cq_moderation: device.cq_moderation(),
+            backend: device.backend_config().cloned(),
+            fault_policy: device.fault_policy(),
+            max_qp: limits.max_qp,
+            max_cq: limits.max_cq,
+            max_mr: limits.max_mr,
+            max_memory_registration_bytes: limits.max_memory_registration_bytes,
+            max_outstanding_wr_per_qp: limits.max_outstanding_wr_per_qp,
+            max_outstanding_bytes_per_qp: limits.max_outstanding_bytes_per_qp,
+            max_outstanding_wr_per_pd: limits.max_outstanding_wr_per_pd,
+            max_outstanding_bytes_per_pd: limits.max_outstanding_bytes_per_pd,
+            rate_limiter: RateLimiterConfig::from(device.rate_limiter()).into_option(),
         }
     }
 }
 
+/// The data fed into an RDMA device update request. Only the completion interrupt moderation, the
+/// backend and the rate limiter can be updated after the microVM has started.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RdmaDeviceUpdateConfig {
+    /// Identifier of the device.
+    pub id: String,
+    /// New moderation of the completion interrupts, a missing one disables it.
+    #[serde(default)]
+    pub cq_moderation: Option<RdmaCqModeration>,
+    /// New backend of the device, a missing one keeps the current backend.
+    #[serde(default)]
+    pub backend: Option<RdmaBackendConfig>,
+    /// New rate limiter config. Only provided data will be updated. I.e. if any optional data
+    /// is missing, it will not be nullified, but left unchanged.
+    #[serde(default)]
+    pub rate_limiter: Option<RateLimiterConfig>,
+}
+
 /// Errors associated with the operations allowed on an RDMA device.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum RdmaDeviceError {
     /// Unable to create the virtio-rdma device: {0}
     CreateDevice(#[from] RdmaError),
+    /// Invalid P_Key table: {0}
+    PkeyTable(#[from] PkeyTableError),
+    /// Invalid backend: {0}
+    Backend(#[from] RdmaBackendError),
+    /// Cannot create the rate limiter: {0}
+    CreateRateLimiter(std::io::Error),
+    /// Unable to update the rdma device: {0}
+    DeviceUpdate(#[from] VmmError),
+    /// No rdma device with ID {0}
+    NotFound(String),
+    /// Unable to add the rdma device: {0}
+    Insertion(VmmError),
+    /// An rdma device with ID {0} already exists
+    DuplicateId(String),
+    /// Too many rdma devices, at most {0} are allowed
+    TooManyDevices(usize),
+    /// The maximum number of rdma devices can't be lower than the {0} existing ones
+    MaxDevicesTooLow(usize),
 }
 
 /// Builder for a list of RDMA devices.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct RdmaDeviceBuilder {
     devices: Vec<Arc<Mutex<VirtioRdma>>>,
+    max_devices: usize,
+    // Whether the devices restored from a snapshot come up with their link down when their
+    // backend can't be set up.
+    allow_degraded_restore: bool,
+}
+
+impl Default for RdmaDeviceBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RdmaDeviceBuilder {
@@ -42,7 +188,36 @@ impl RdmaDeviceBuilder {
     pub fn new() -> Self {
         RdmaDeviceBuilder {
             devices: Vec::new(),
+            max_devices: DEFAULT_MAX_RDMA_DEVICES,
+            allow_degraded_restore: false,
+        }
+    }
+
+    /// Maximum number of RDMA devices in the list.
+    pub fn max_devices(&self) -> usize {
+        self.max_devices
+    }
+
+    /// Sets the maximum number of RDMA devices in the list, which can't be lower than the number
+    /// of devices already in it.
+    pub fn set_max_devices(&mut self, max_devices: usize) -> Result<(), RdmaDeviceError> {
+        if max_devices < self.devices.len() {
+            return Err(RdmaDeviceError::MaxDevicesTooLow(self.devices.len()));
         }
+        self.max_devices = max_devices;
+        Ok(())
+    }
+
+    /// Whether the devices restored from a snapshot come up with their link down when their
+    /// backend can't be set up, instead of failing the restore.
+    pub fn allow_degraded_restore(&self) -> bool {
+        self.allow_degraded_restore
+    }
+
+    /// Sets whether the devices restored from a snapshot come up with their link down when their
+    /// backend can't be set up.
+    pub fn set_allow_degraded_restore(&mut self, allow: bool) {
+        self.allow_degraded_restore = allow;
     }
 
     /// Returns an immutable iterator over the RDMA devices.
@@ -55,17 +230,36 @@ impl RdmaDeviceBuilder {
         self.devices.push(device);
     }
 
-    /// Builds an RDMA device based on a configuration and keeps a reference in the list.
+    /// Removes the RDMA device with the specified ID from the list.
+    pub fn remove(&mut self, id: &str) -> Option<Arc<Mutex<VirtioRdma>>> {
+        let index = self
+            .devices
+            .iter()
+            .position(|dev| dev.lock().expect("Poisoned lock").id() == id)?;
+        Some(self.devices.remove(index))
+    }
+
+    // Returns the position of the device with ID `id` in the list, checking that a device of
+    // that ID can be added to the list otherwise.
+    fn check_new_device(&self, id: &str) -> Result<Option<usize>, RdmaDeviceError> {
+        let position = self
+            .devices
+            .iter()
+            .position(|dev| dev.lock().expect("Poisoned lock").id() == id);
+        if position.is_none() && self.devices.len() >= self.max_devices {
+            return Err(RdmaDeviceError::TooManyDevices(self.max_devices));
+        }
+        Ok(position)
+    }
+
+    /// Builds an RDMA device based on a configuration and keeps a reference in the list. The
+    /// device replaces the one with the same ID, if any.
     pub fn build(
         &mut self,
         config: RdmaDeviceConfig,
     ) -> Result<Arc<Mutex<VirtioRdma>>, RdmaDeviceError> {
-        let id = config.id.clone();
-        let position = self
-            .devices
-            .iter()
-            .position(|dev| dev.lock().expect("Poisoned lock").id() == config.id);
-        let device = Arc::new(Mutex::new(VirtioRdma::new(id)?));
+        let position = self.check_new_device(&config.id)?;
+        let device = Self::create_device(config, None)?;
 
         if let Some(index) = position {
             self.devices[index] = device.clone();
@@ -76,6 +270,47 @@ impl RdmaDeviceBuilder {
         Ok(device)
     }
 
+    /// Builds an RDMA device to add to the running microVM, without keeping a reference in the
+    /// list, as the device is only added to it once attached. Devices can't be replaced once
+    /// the microVM has started. The backend is set up through `launcher`, as the seccomp filter
+    /// of the VMM thread denies the system calls it makes.
+    pub fn build_hotplugged(
+        &self,
+        config: RdmaDeviceConfig,
+        launcher: Arc<BackendLauncher>,
+    ) -> Result<Arc<Mutex<VirtioRdma>>, RdmaDeviceError> {
+        if self.check_new_device(&config.id)?.is_some() {
+            return Err(RdmaDeviceError::DuplicateId(config.id));
+        }
+        Self::create_device(config, Some(launcher))
+    }
+
+    fn create_device(
+        config: RdmaDeviceConfig,
+        launcher: Option<Arc<BackendLauncher>>,
+    ) -> Result<Arc<Mutex<VirtioRdma>>, RdmaDeviceError> {
+        let limits = config.resource_limits();
+        let rate_limiter = config
+            .rate_limiter
+            .map(RateLimiterConfig::try_into)
+            .transpose()
+            .map_err(RdmaDeviceError::CreateRateLimiter)?;
+        let mut rdma = VirtioRdma::new(config.id)?;
+        rdma.set_addresses(config.mac, config.ip)?;
+        rdma.set_pkeys(&config.pkeys)?;
+        rdma.set_num_queues(config.num_queues)?;
+        rdma.set_mtu(config.mtu)?;
+        rdma.set_resource_limits(limits)?;
+        rdma.set_cq_moderation(config.cq_moderation)?;
+        if let Some(launcher) = launcher {
+            rdma.set_backend_launcher(launcher)?;
+        }
+        rdma.set_backend(config.backend)?;
+        rdma.set_fault_policy(config.fault_policy)?;
+        rdma.set_rate_limiter(rate_limiter.unwrap_or_default());
+        Ok(Arc::new(Mutex::new(rdma)))
+    }
+
     /// Inserts a new RDMA device from a configuration object.
     pub fn insert(&mut self, config: RdmaDeviceConfig) -> Result<(), RdmaDeviceError> {
         let _ = self.build(config)?;
@@ -90,3 +325,105 @@ impl RdmaDeviceBuilder {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+    use crate::devices::virtio::rdma::backend::thread::tests::socket_denying_filter;
+    use crate::devices::virtio::rdma::backend::unix::UnixBackendConfig;
+    use crate::seccomp::apply_filter;
+
+    fn config(id: &str) -> RdmaDeviceConfig {
+        RdmaDeviceConfig {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_max_devices() {
+        let mut builder = RdmaDeviceBuilder::new();
+        assert_eq!(builder.max_devices(), DEFAULT_MAX_RDMA_DEVICES);
+        builder.set_max_devices(2).unwrap();
+        builder.insert(config("rdma0")).unwrap();
+        builder.insert(config("rdma1")).unwrap();
+        assert!(matches!(
+            builder.insert(config("rdma2")),
+            Err(RdmaDeviceError::TooManyDevices(2))
+        ));
+
+        // Devices replaced before boot don't count twice.
+        builder
+            .insert(RdmaDeviceConfig {
+                mtu: Some(4096),
+                ..config("rdma1")
+            })
+            .unwrap();
+        assert_eq!(builder.configs().len(), 2);
+        assert_eq!(builder.configs()[1].mtu, Some(4096));
+
+        assert!(matches!(
+            builder.set_max_devices(1),
+            Err(RdmaDeviceError::MaxDevicesTooLow(2))
+        ));
+        builder.remove("rdma0").unwrap();
+        builder.set_max_devices(1).unwrap();
+    }
+
+    #[test]
+    fn test_build_hotplugged() {
+        let launcher = Arc::new(BackendLauncher::new(Arc::default(), Arc::default()).unwrap());
+        let mut builder = RdmaDeviceBuilder::new();
+        builder.set_max_devices(2).unwrap();
+        builder.insert(config("rdma0")).unwrap();
+
+        // Devices can't be replaced once the microVM has started.
+        assert!(matches!(
+            builder.build_hotplugged(config("rdma0"), launcher.clone()),
+            Err(RdmaDeviceError::DuplicateId(id)) if id == "rdma0"
+        ));
+
+        // The device is only part of the list once added to it.
+        let device = builder
+            .build_hotplugged(config("rdma1"), launcher.clone())
+            .unwrap();
+        assert_eq!(builder.configs().len(), 1);
+        builder.add_device(device);
+        assert!(matches!(
+            builder.build_hotplugged(config("rdma2"), launcher),
+            Err(RdmaDeviceError::TooManyDevices(2))
+        ));
+    }
+
+    #[test]
+    fn test_build_hotplugged_after_boot() {
+        // The filter installed by the test stays on its thread.
+        std::thread::spawn(|| {
+            let dir = TempDir::new().unwrap();
+            let launcher = Arc::new(BackendLauncher::new(Arc::default(), Arc::default()).unwrap());
+            let builder = RdmaDeviceBuilder::new();
+
+            // The backend is set up once the VMM thread can't create sockets anymore.
+            apply_filter(&socket_denying_filter()).unwrap();
+            let backend = RdmaBackendConfig::Unix(UnixBackendConfig {
+                socket_path: dir.as_path().join("rdma0.sock"),
+                peers: Vec::new(),
+            });
+            let device = builder
+                .build_hotplugged(
+                    RdmaDeviceConfig {
+                        backend: Some(backend.clone()),
+                        ..config("rdma0")
+                    },
+                    launcher,
+                )
+                .unwrap();
+            assert_eq!(device.lock().unwrap().backend_config(), Some(&backend));
+            assert!(dir.as_path().join("rdma0.sock").exists());
+        })
+        .join()
+        .unwrap();
+    }
+}
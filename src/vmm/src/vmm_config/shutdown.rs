@@ -0,0 +1,51 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Default time left to the guest to shut down, in milliseconds.
+pub const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 30_000;
+
+/// Configuration of a graceful shutdown of the microVM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GracefulShutdownConfig {
+    /// Time left to the guest to shut down once asked to, after which the microVM is
+    /// force-stopped, in milliseconds.
+    pub timeout_ms: u64,
+}
+
+impl Default for GracefulShutdownConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: DEFAULT_SHUTDOWN_TIMEOUT_MS,
+        }
+    }
+}
+
+impl GracefulShutdownConfig {
+    /// Time left to the guest to shut down.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// How the microVM stopped after a graceful shutdown request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownPath {
+    /// The guest shut down before the timeout.
+    Graceful,
+    /// The guest ran out of time and the microVM was force-stopped.
+    Forced,
+}
+
+/// Outcome of a graceful shutdown of the microVM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct GracefulShutdownOutcome {
+    /// How the microVM stopped.
+    pub path: ShutdownPath,
+    /// Time elapsed between the request and the stop of the microVM, in milliseconds.
+    pub elapsed_ms: u64,
+}
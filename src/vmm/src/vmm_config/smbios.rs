@@ -0,0 +1,125 @@
+// Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Maximum length of the strings of the SMBIOS tables.
+pub const SMBIOS_MAX_STRING_LEN: usize = 64;
+
+/// Strongly typed data structure used to configure the system information reported to the guest
+/// through the SMBIOS tables.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmbiosConfig {
+    /// Manufacturer of the system, `Firecracker` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<String>,
+    /// Product name of the system, `Firecracker` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Serial number of the system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    /// UUID of the system, in its hyphenated form.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+}
+
+/// Errors associated with actions on `SmbiosConfig`.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum SmbiosConfigError {
+    /// SMBIOS tables are not supported on this architecture.
+    Unsupported,
+    /// Invalid SMBIOS {0}: strings are made of 1 to 64 printable ASCII characters.
+    InvalidString(&'static str),
+    /// Invalid SMBIOS UUID: {0}
+    InvalidUuid(String),
+}
+
+impl SmbiosConfig {
+    /// Checks that the fields can be encoded in the SMBIOS tables.
+    pub fn validate(&self) -> Result<(), SmbiosConfigError> {
+        for (field, value) in [
+            ("manufacturer", &self.manufacturer),
+            ("product", &self.product),
+            ("serial number", &self.serial_number),
+        ] {
+            if let Some(value) = value
+                && (value.is_empty()
+                    || value.len() > SMBIOS_MAX_STRING_LEN
+                    || !value
+                        .bytes()
+                        .all(|byte| byte == b' ' || byte.is_ascii_graphic()))
+            {
+                return Err(SmbiosConfigError::InvalidString(field));
+            }
+        }
+        self.parsed_uuid()?;
+        Ok(())
+    }
+
+    /// The UUID of the system, if any.
+    pub fn parsed_uuid(&self) -> Result<Option<Uuid>, SmbiosConfigError> {
+        self.uuid
+            .as_deref()
+            .map(|uuid| {
+                Uuid::try_parse(uuid).map_err(|_| SmbiosConfigError::InvalidUuid(uuid.to_string()))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        SmbiosConfig::default().validate().unwrap();
+        let config = SmbiosConfig {
+            manufacturer: Some(String::from("Example Corp.")),
+            product: Some(String::from("Example VM")),
+            serial_number: Some(String::from("SN-0001")),
+            uuid: Some(String::from("5b1e4c9a-2a5f-4f4e-9d63-4f3c2b1a0e9d")),
+        };
+        config.validate().unwrap();
+        assert_eq!(
+            config.parsed_uuid().unwrap().unwrap().to_string(),
+            "5b1e4c9a-2a5f-4f4e-9d63-4f3c2b1a0e9d"
+        );
+
+        for (config, err) in [
+            (
+                SmbiosConfig {
+                    manufacturer: Some(String::new()),
+                    ..Default::default()
+                },
+                SmbiosConfigError::InvalidString("manufacturer"),
+            ),
+            (
+                SmbiosConfig {
+                    product: Some("p".repeat(SMBIOS_MAX_STRING_LEN + 1)),
+                    ..Default::default()
+                },
+                SmbiosConfigError::InvalidString("product"),
+            ),
+            (
+                SmbiosConfig {
+                    serial_number: Some(String::from("SN\n1")),
+                    ..Default::default()
+                },
+                SmbiosConfigError::InvalidString("serial number"),
+            ),
+            (
+                SmbiosConfig {
+                    uuid: Some(String::from("not-a-uuid")),
+                    ..Default::default()
+                },
+                SmbiosConfigError::InvalidUuid(String::from("not-a-uuid")),
+            ),
+        ] {
+            assert_eq!(config.validate(), Err(err));
+        }
+    }
+}
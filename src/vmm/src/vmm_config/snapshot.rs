@@ -4,11 +4,14 @@
 //! Configurations used in the snapshotting context.
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// For crates that depend on `vmm` we export.
 pub use semver::Version;
 use serde::{Deserialize, Serialize};
 
+use super::PassedFd;
+
 /// The snapshot type options that are available when
 /// creating a new snapshot.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
@@ -20,6 +23,17 @@ pub enum SnapshotType {
     Full,
 }
 
+/// How consistent the state of the devices is with the state of the vCPUs in a new snapshot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SnapshotConsistency {
+    /// The state of the paused microVM is saved as is.
+    #[default]
+    Pause,
+    /// The pending I/O of the vCPUs is completed and the device queues are flushed before the
+    /// state is saved, and the snapshot epoch exposed to the guest is incremented.
+    CrashConsistent,
+}
+
 /// Specifies the method through which guest memory will get populated when
 /// resuming from a snapshot:
 /// 1) A file that contains the guest memory to be loaded,
@@ -33,6 +47,18 @@ pub enum MemBackendType {
     Uffd,
 }
 
+/// How thoroughly the memory file is checked against the snapshot when it is loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MemVerification {
+    /// Only the length of the memory file is checked.
+    #[default]
+    Length,
+    /// The digests of a sample of the chunks of the memory file are also checked.
+    Sampled,
+    /// The digests of the whole memory file are also checked.
+    Full,
+}
+
 /// Stores the configuration that will be used for creating a snapshot.
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -41,10 +67,27 @@ pub struct CreateSnapshotParams {
     /// The default value is `Full`, which means a full snapshot.
     #[serde(default = "SnapshotType::default")]
     pub snapshot_type: SnapshotType,
-    /// Path to the file that will contain the microVM state.
+    /// Path to the file that will contain the microVM state. Can be omitted if the file is passed
+    /// in `snapshot_fd`.
+    #[serde(default)]
     pub snapshot_path: PathBuf,
-    /// Path to the file that will contain the guest memory.
+    /// Path to the file that will contain the guest memory. Can be omitted if the file is passed
+    /// in `mem_file_fd`.
+    #[serde(default)]
     pub mem_file_path: PathBuf,
+    /// Whether to record the digests of the memory file in the snapshot, allowing to verify its
+    /// content when the snapshot is loaded.
+    #[serde(default)]
+    pub memory_digests: bool,
+    /// How consistent the saved state of the devices and vCPUs is.
+    #[serde(default)]
+    pub consistency: SnapshotConsistency,
+    /// Already open file that will contain the microVM state, used instead of `snapshot_path`.
+    #[serde(skip)]
+    pub snapshot_fd: Option<PassedFd>,
+    /// Already open file that will contain the guest memory, used instead of `mem_file_path`.
+    #[serde(skip)]
+    pub mem_file_fd: Option<PassedFd>,
 }
 
 /// Allows for changing the mapping between tap devices and host devices
@@ -72,6 +115,25 @@ pub struct LoadSnapshotParams {
     pub resume_vm: bool,
     /// The network devices to override on load.
     pub network_overrides: Vec<NetworkOverride>,
+    /// How thoroughly the memory file is checked against the snapshot.
+    pub mem_verification: MemVerification,
+    /// Whether the rdma devices whose backend can't be set up are restored with their link down,
+    /// instead of failing the load.
+    pub rdma_allow_degraded_restore: bool,
+}
+
+/// Stores the configuration used to check whether a snapshot can be loaded on this host.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidateSnapshotParams {
+    /// Path to the file that contains the microVM state to be checked.
+    pub snapshot_path: PathBuf,
+    /// Path to the file that contains the guest memory, checked against the snapshot if present.
+    #[serde(default)]
+    pub mem_file_path: Option<PathBuf>,
+    /// How thoroughly the memory file is checked against the snapshot.
+    #[serde(default)]
+    pub mem_verification: MemVerification,
 }
 
 /// Stores the configuration for loading a snapshot that is provided by the user.
@@ -101,6 +163,14 @@ pub struct LoadSnapshotConfig {
     /// The network devices to override on load.
     #[serde(default)]
     pub network_overrides: Vec<NetworkOverride>,
+    /// How thoroughly the memory file is checked against the snapshot. Only supported with the
+    /// `File` memory backend.
+    #[serde(default)]
+    pub mem_verification: MemVerification,
+    /// Whether the rdma devices whose backend can't be set up are restored with their link down,
+    /// instead of failing the load.
+    #[serde(default)]
+    pub rdma_allow_degraded_restore: bool,
 }
 
 /// Stores the configuration used for managing snapshot memory.
@@ -129,3 +199,98 @@ pub struct Vm {
     /// The microVM state, which can be `paused` or `resumed`.
     pub state: VmState,
 }
+
+/// Default time left to the guest agent to freeze the guest filesystems, in milliseconds.
+pub const DEFAULT_FREEZE_TIMEOUT_MS: u64 = 10_000;
+
+fn default_freeze_timeout_ms() -> u64 {
+    DEFAULT_FREEZE_TIMEOUT_MS
+}
+
+/// Errors associated with the snapshot agent configuration.
+#[derive(Debug, thiserror::Error, displaydoc::Display, PartialEq, Eq)]
+pub enum SnapshotAgentConfigError {
+    /// The freeze timeout must be positive
+    InvalidFreezeTimeout,
+}
+
+/// Configuration of the channel through which a cooperating guest agent requests snapshots.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotAgentConfig {
+    /// Host vsock port the guest agent connects to.
+    pub port: u32,
+    /// Type of the snapshots requested by the guest agent.
+    #[serde(default)]
+    pub snapshot_type: SnapshotType,
+    /// Path to the file that will contain the microVM state.
+    pub snapshot_path: PathBuf,
+    /// Path to the file that will contain the guest memory.
+    pub mem_file_path: PathBuf,
+    /// Time left to the guest agent to freeze the guest filesystems once asked to, after which
+    /// the snapshot is abandoned, in milliseconds.
+    #[serde(default = "default_freeze_timeout_ms")]
+    pub freeze_timeout_ms: u64,
+}
+
+impl SnapshotAgentConfig {
+    /// Validates the configuration.
+    pub fn validate(&self) -> Result<(), SnapshotAgentConfigError> {
+        if self.freeze_timeout_ms == 0 {
+            return Err(SnapshotAgentConfigError::InvalidFreezeTimeout);
+        }
+        Ok(())
+    }
+
+    /// Time left to the guest agent to freeze the guest filesystems.
+    pub fn freeze_timeout(&self) -> Duration {
+        Duration::from_millis(self.freeze_timeout_ms)
+    }
+
+    /// Parameters of the snapshots requested by the guest agent.
+    pub fn create_params(&self) -> CreateSnapshotParams {
+        CreateSnapshotParams {
+            snapshot_type: self.snapshot_type,
+            snapshot_path: self.snapshot_path.clone(),
+            mem_file_path: self.mem_file_path.clone(),
+            memory_digests: false,
+            consistency: SnapshotConsistency::Pause,
+            snapshot_fd: None,
+            mem_file_fd: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_agent_config() {
+        let config: SnapshotAgentConfig = serde_json::from_str(
+            r#"{"port": 5000, "snapshot_path": "/tmp/vm.snap", "mem_file_path": "/tmp/vm.mem"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.snapshot_type, SnapshotType::Full);
+        assert_eq!(config.freeze_timeout_ms, DEFAULT_FREEZE_TIMEOUT_MS);
+        config.validate().unwrap();
+
+        let params = config.create_params();
+        assert_eq!(params.snapshot_path, PathBuf::from("/tmp/vm.snap"));
+        assert_eq!(params.mem_file_path, PathBuf::from("/tmp/vm.mem"));
+
+        let config = SnapshotAgentConfig {
+            freeze_timeout_ms: 0,
+            ..config
+        };
+        assert_eq!(
+            config.validate(),
+            Err(SnapshotAgentConfigError::InvalidFreezeTimeout)
+        );
+
+        serde_json::from_str::<SnapshotAgentConfig>(
+            r#"{"port": 5000, "snapshot_path": "a", "mem_file_path": "b", "foo": 1}"#,
+        )
+        .unwrap_err();
+    }
+}
@@ -6,7 +6,9 @@ use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
-use crate::devices::virtio::vsock::{Vsock, VsockError, VsockUnixBackend, VsockUnixBackendError};
+use crate::devices::virtio::vsock::{
+    Vsock, VsockError, VsockIoEngine, VsockUnixBackend, VsockUnixBackendError,
+};
 
 type MutexVsockUnix = Arc<Mutex<Vsock<VsockUnixBackend>>>;
 
@@ -32,6 +34,10 @@ pub struct VsockDeviceConfig {
     pub guest_cid: u32,
     /// Path to local unix socket.
     pub uds_path: String,
+    /// Engine driving the host-side Unix sockets. Defaults to epoll.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_engine: Option<VsockIoEngine>,
 }
 
 #[derive(Debug)]
@@ -43,10 +49,12 @@ struct VsockAndUnixPath {
 impl From<&VsockAndUnixPath> for VsockDeviceConfig {
     fn from(vsock: &VsockAndUnixPath) -> Self {
         let vsock_lock = vsock.vsock.lock().unwrap();
+        let io_engine = vsock_lock.backend().io_engine();
         VsockDeviceConfig {
             vsock_id: None,
             guest_cid: u32::try_from(vsock_lock.cid()).unwrap(),
             uds_path: vsock.uds_path.clone(),
+            io_engine: (io_engine != VsockIoEngine::default()).then_some(io_engine),
         }
     }
 }
@@ -90,6 +98,11 @@ impl VsockBuilder {
         Ok(())
     }
 
+    /// Removes the Vsock, if present.
+    pub fn remove(&mut self) -> Option<MutexVsockUnix> {
+        self.inner.take().map(|pair| pair.vsock)
+    }
+
     /// Provides a reference to the Vsock if present.
     pub fn get(&self) -> Option<&MutexVsockUnix> {
         self.inner.as_ref().map(|pair| &pair.vsock)
@@ -99,7 +112,11 @@ impl VsockBuilder {
     pub fn create_unixsock_vsock(
         cfg: VsockDeviceConfig,
     ) -> Result<Vsock<VsockUnixBackend>, VsockConfigError> {
-        let backend = VsockUnixBackend::new(u64::from(cfg.guest_cid), cfg.uds_path)?;
+        let backend = VsockUnixBackend::new_with_io_engine(
+            u64::from(cfg.guest_cid),
+            cfg.uds_path,
+            cfg.io_engine.unwrap_or_default(),
+        )?;
 
         Vsock::new(u64::from(cfg.guest_cid), backend).map_err(VsockConfigError::CreateVsockDevice)
     }
@@ -123,6 +140,7 @@ pub(crate) mod tests {
             vsock_id: None,
             guest_cid: 3,
             uds_path: tmp_sock_file.as_path().to_str().unwrap().to_string(),
+            io_engine: None,
         }
     }
 
@@ -165,6 +183,30 @@ pub(crate) mod tests {
         assert_eq!(config.unwrap(), vsock_config);
     }
 
+    #[test]
+    fn test_vsock_io_engine_config() {
+        let json = r#"{"guest_cid": 3, "uds_path": "v.sock", "io_engine": "IoUring"}"#;
+        let config: VsockDeviceConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.io_engine, Some(VsockIoEngine::IoUring));
+
+        let json = r#"{"guest_cid": 3, "uds_path": "v.sock", "io_engine": "Poll"}"#;
+        serde_json::from_str::<VsockDeviceConfig>(json).unwrap_err();
+
+        let mut vsock_builder = VsockBuilder::new();
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let mut vsock_config = default_config(&tmp_sock_file);
+        vsock_config.io_engine = Some(VsockIoEngine::IoUring);
+        match vsock_builder.insert(vsock_config.clone()) {
+            Ok(()) => assert_eq!(vsock_builder.config().unwrap(), vsock_config),
+            // The host kernel is too old for this engine.
+            Err(VsockConfigError::CreateVsockBackend(
+                VsockUnixBackendError::IoUringUnsupported,
+            )) => assert!(!tmp_sock_file.as_path().exists()),
+            Err(err) => panic!("{err}"),
+        }
+    }
+
     #[test]
     fn test_set_device() {
         let mut vsock_builder = VsockBuilder::new();
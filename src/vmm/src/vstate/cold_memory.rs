@@ -0,0 +1,641 @@
+// Copyright 2026 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compressed backing store for cold guest memory.
+//!
+//! Guest DRAM is registered with a userfaultfd object. When the host signals memory pressure
+//! through a PSI trigger, the guest pages which were not written since the previous pressure
+//! event are compressed into a backing file and dropped from the guest mapping. A dedicated
+//! thread serves the faults on dropped pages by reloading them from the backing file.
+//!
+//! Writes are tracked through the soft-dirty bits exposed in `/proc/self/pagemap`, which are
+//! cleared after every eviction pass. Reloaded pages are soft-dirty, so pages which are
+//! accessed again after their eviction are considered hot during the next pass.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use userfaultfd::{Event, Uffd, UffdBuilder};
+use vm_memory::GuestMemoryError;
+
+use crate::logger::{IncMetric, METRICS, StoreMetric};
+use crate::seccomp::BpfProgram;
+use crate::utils::{u64_to_usize, usize_to_u64};
+use crate::vmm_config::cold_memory::ColdMemoryConfig;
+use crate::vstate::memory::{
+    Address, Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion,
+    GuestRegionMmapExt, GuestRegionType,
+};
+
+/// Size of the guest pages managed by the store.
+const PAGE_SIZE: usize = 4096;
+const WORD_SIZE: usize = 8;
+/// Granularity of the allocations in the backing file.
+const SLOT_ALIGN: usize = 64;
+/// Number of pagemap entries read at once.
+const PAGEMAP_BATCH: usize = 512;
+const PAGEMAP_ENTRY_SIZE: usize = 8;
+const PAGEMAP_PRESENT: u64 = 1 << 63;
+const PAGEMAP_SOFT_DIRTY: u64 = 1 << 55;
+
+// Encoded pages are a sequence of runs of 8-byte words. The top bit of a run header is set for
+// runs of zero words and clear for runs of literal words, which follow the header. The other
+// bits hold the length of the run minus one.
+const ZERO_RUN: u8 = 0x80;
+const MAX_RUN_WORDS: usize = 0x80;
+
+/// Errors associated with the cold memory backing store.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum ColdMemoryError {
+    /// Cold memory requires guest memory backed by private anonymous 4 KiB pages.
+    UnsupportedMemory,
+    /// The amount of memory evicted per pressure event is too large.
+    InvalidEvictBatch,
+    /// Cannot open the backing file: {0}
+    BackingFile(std::io::Error),
+    /// Cannot access the backing file: {0}
+    BackingFileIo(std::io::Error),
+    /// Corrupted page in the backing file.
+    CorruptedPage,
+    /// Cannot set up the memory pressure trigger: {0}
+    PressureTrigger(std::io::Error),
+    /// Cannot access the page tables of the process: {0}
+    PageTables(std::io::Error),
+    /// Cannot create the userfaultfd object: {0}
+    Uffd(userfaultfd::Error),
+    /// Cannot register guest memory with the userfaultfd object: {0}
+    UffdRegister(userfaultfd::Error),
+    /// Cannot spawn the fault handling thread: {0}
+    Spawn(std::io::Error),
+    /// Cannot read guest memory: {0}
+    GuestMemory(GuestMemoryError),
+    /// Cannot drop an evicted page from guest memory: {0}
+    Madvise(std::io::Error),
+}
+
+// Returns the word a page is filled with, if it consists of a single repeated word.
+fn same_filled(page: &[u8]) -> Option<u64> {
+    let (first, rest) = page.split_at(WORD_SIZE);
+    rest.chunks_exact(WORD_SIZE)
+        .all(|word| word == first)
+        .then(|| u64::from_ne_bytes(first.try_into().unwrap()))
+}
+
+// Encodes `page` into `out`. Returns `false` if the encoded page isn't smaller than the page.
+fn compress_page(page: &[u8], out: &mut Vec<u8>) -> bool {
+    let words: Vec<&[u8]> = page.chunks_exact(WORD_SIZE).collect();
+    let is_zero = |word: &[u8]| word.iter().all(|byte| *byte == 0);
+
+    out.clear();
+    let mut start = 0;
+    while start < words.len() {
+        let zero = is_zero(words[start]);
+        let len = words[start..]
+            .iter()
+            .take(MAX_RUN_WORDS)
+            .take_while(|word| is_zero(word) == zero)
+            .count();
+
+        let header = u8::try_from(len - 1).unwrap();
+        if zero {
+            out.push(ZERO_RUN | header);
+        } else {
+            out.push(header);
+            out.extend_from_slice(&page[start * WORD_SIZE..(start + len) * WORD_SIZE]);
+        }
+        if out.len() >= PAGE_SIZE {
+            return false;
+        }
+        start += len;
+    }
+    true
+}
+
+// Decodes a page encoded by `compress_page()` into `page`.
+fn decompress_page(data: &[u8], page: &mut [u8]) -> Result<(), ColdMemoryError> {
+    let mut pos = 0;
+    let mut offset = 0;
+    while offset < page.len() {
+        let header = *data.get(pos).ok_or(ColdMemoryError::CorruptedPage)?;
+        pos += 1;
+        let len = (usize::from(header & !ZERO_RUN) + 1) * WORD_SIZE;
+        let dst = page
+            .get_mut(offset..offset + len)
+            .ok_or(ColdMemoryError::CorruptedPage)?;
+        if header & ZERO_RUN != 0 {
+            dst.fill(0);
+        } else {
+            let src = data
+                .get(pos..pos + len)
+                .ok_or(ColdMemoryError::CorruptedPage)?;
+            dst.copy_from_slice(src);
+            pos += len;
+        }
+        offset += len;
+    }
+
+    if pos != data.len() {
+        return Err(ColdMemoryError::CorruptedPage);
+    }
+    Ok(())
+}
+
+// Backing file slots are allocated by size class, in multiples of `SLOT_ALIGN` bytes.
+fn slot_class(len: usize) -> usize {
+    len.div_ceil(SLOT_ALIGN) - 1
+}
+
+fn slot_size(class: usize) -> u64 {
+    usize_to_u64((class + 1) * SLOT_ALIGN)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoredPage {
+    /// Page made of a single repeated word, which is only kept in memory.
+    SameFilled(u64),
+    /// Page encoded as runs of words in the backing file.
+    Runs { offset: u64, len: usize },
+    /// Page stored as-is in the backing file.
+    Raw { offset: u64 },
+}
+
+/// Evicted guest pages, indexed by their host address.
+#[derive(Debug)]
+struct ColdPageStore {
+    file: File,
+    pages: HashMap<u64, StoredPage>,
+    free_slots: Vec<Vec<u64>>,
+    file_len: u64,
+    stored_bytes: u64,
+    scratch: Vec<u8>,
+}
+
+impl ColdPageStore {
+    fn new(file: File) -> Self {
+        Self {
+            file,
+            pages: HashMap::new(),
+            free_slots: vec![Vec::new(); PAGE_SIZE / SLOT_ALIGN],
+            file_len: 0,
+            stored_bytes: 0,
+            scratch: Vec::with_capacity(PAGE_SIZE),
+        }
+    }
+
+    fn alloc_slot(&mut self, len: usize) -> u64 {
+        let class = slot_class(len);
+        self.free_slots[class].pop().unwrap_or_else(|| {
+            let offset = self.file_len;
+            self.file_len += slot_size(class);
+            offset
+        })
+    }
+
+    fn release(&mut self, stored: StoredPage) {
+        let (offset, len) = match stored {
+            StoredPage::SameFilled(_) => return,
+            StoredPage::Runs { offset, len } => (offset, len),
+            StoredPage::Raw { offset } => (offset, PAGE_SIZE),
+        };
+        self.free_slots[slot_class(len)].push(offset);
+        self.stored_bytes -= usize_to_u64(len);
+        METRICS.cold_memory.stored_bytes.store(self.stored_bytes);
+    }
+
+    /// Saves the contents of the page at host address `addr`.
+    fn insert(&mut self, addr: u64, page: &[u8]) -> Result<(), ColdMemoryError> {
+        let stored = match same_filled(page) {
+            Some(word) => StoredPage::SameFilled(word),
+            None => {
+                let mut scratch = std::mem::take(&mut self.scratch);
+                let compressed = compress_page(page, &mut scratch);
+                let data = if compressed { &scratch[..] } else { page };
+                let offset = self.alloc_slot(data.len());
+                let stored = if compressed {
+                    StoredPage::Runs {
+                        offset,
+                        len: data.len(),
+                    }
+                } else {
+                    StoredPage::Raw { offset }
+                };
+                let res = self.file.write_all_at(data, offset);
+                self.stored_bytes += usize_to_u64(data.len());
+                self.scratch = scratch;
+                if let Err(err) = res {
+                    self.release(stored);
+                    return Err(ColdMemoryError::BackingFileIo(err));
+                }
+                METRICS.cold_memory.stored_bytes.store(self.stored_bytes);
+                stored
+            }
+        };
+
+        if let Some(previous) = self.pages.insert(addr, stored) {
+            self.release(previous);
+        }
+        Ok(())
+    }
+
+    /// Removes the page at host address `addr` from the store and copies its contents to
+    /// `page`. Returns whether the page was stored.
+    fn take(&mut self, addr: u64, page: &mut [u8]) -> Result<bool, ColdMemoryError> {
+        let Some(stored) = self.pages.remove(&addr) else {
+            return Ok(false);
+        };
+
+        let res = match stored {
+            StoredPage::SameFilled(word) => {
+                page.chunks_exact_mut(WORD_SIZE)
+                    .for_each(|chunk| chunk.copy_from_slice(&word.to_ne_bytes()));
+                Ok(())
+            }
+            StoredPage::Runs { offset, len } => {
+                self.scratch.resize(len, 0);
+                self.file
+                    .read_exact_at(&mut self.scratch, offset)
+                    .map_err(ColdMemoryError::BackingFileIo)
+                    .and_then(|()| decompress_page(&self.scratch, page))
+            }
+            StoredPage::Raw { offset } => self
+                .file
+                .read_exact_at(page, offset)
+                .map_err(ColdMemoryError::BackingFileIo),
+        };
+        self.release(stored);
+        res.map(|()| true)
+    }
+
+    /// Drops the page at host address `addr` from the store.
+    fn discard(&mut self, addr: u64) {
+        if let Some(stored) = self.pages.remove(&addr) {
+            self.release(stored);
+        }
+    }
+}
+
+fn dram_regions(guest_memory: &GuestMemoryMmap) -> impl Iterator<Item = &GuestRegionMmapExt> {
+    guest_memory
+        .iter()
+        .filter(|region| region.region_type == GuestRegionType::Dram)
+}
+
+// Serves the faults on guest memory, reloading evicted pages from the store.
+#[derive(Debug)]
+struct FaultHandler {
+    uffd: Uffd,
+    store: Arc<Mutex<ColdPageStore>>,
+}
+
+impl FaultHandler {
+    fn run(&self, seccomp_filter: &BpfProgram) {
+        // Load seccomp filters for this thread.
+        // Execution panics if filters cannot be loaded, use --no-seccomp if skipping filters
+        // altogether is the desired behaviour.
+        if let Err(err) = crate::seccomp::apply_filter(seccomp_filter) {
+            panic!("Failed to set the requested seccomp filters on the cold memory thread: {err}");
+        }
+
+        let mut page = vec![0u8; PAGE_SIZE];
+        loop {
+            match self.uffd.read_event() {
+                Ok(Some(Event::Pagefault { addr, .. })) => {
+                    let addr = addr as u64 & !(usize_to_u64(PAGE_SIZE) - 1);
+                    self.serve_fault(addr, &mut page);
+                }
+                Ok(_) => {}
+                Err(err) => panic!("Failed to read cold memory userfaultfd event: {err}"),
+            }
+        }
+    }
+
+    fn serve_fault(&self, addr: u64, page: &mut [u8]) {
+        // A guest page which can't be reloaded can't be replaced either, so there is no way for
+        // the guest to make progress.
+        let stored = self
+            .store
+            .lock()
+            .expect("Poisoned lock")
+            .take(addr, page)
+            .unwrap_or_else(|err| panic!("Cannot reload evicted guest page {addr:#x}: {err}"));
+
+        let dst = addr as *mut libc::c_void;
+        // SAFETY: `dst` is a page aligned address within a guest memory region registered with
+        // the userfaultfd object, and `page` holds a whole page.
+        let res = unsafe {
+            if stored {
+                self.uffd.copy(page.as_ptr().cast(), dst, PAGE_SIZE, true)
+            } else {
+                self.uffd.zeropage(dst, PAGE_SIZE, true)
+            }
+        };
+        match res {
+            Ok(_) => {
+                if stored {
+                    METRICS.cold_memory.pages_reloaded.inc();
+                }
+            }
+            // Another thread populated the page in the meantime.
+            Err(userfaultfd::Error::CopyFailed(errno))
+            | Err(userfaultfd::Error::ZeropageFailed(errno))
+                if std::io::Error::from(errno).raw_os_error() == Some(libc::EEXIST) =>
+            {
+                if let Err(err) = self.uffd.wake(dst, PAGE_SIZE) {
+                    panic!("Cannot wake the threads faulting on {addr:#x}: {err}");
+                }
+            }
+            Err(err) => panic!("Cannot populate guest page {addr:#x}: {err}"),
+        }
+    }
+}
+
+/// Evicts cold guest memory to a compressed backing file when the host is under memory pressure.
+#[derive(Debug)]
+pub struct ColdMemory {
+    store: Arc<Mutex<ColdPageStore>>,
+    pressure_trigger: File,
+    pagemap: File,
+    clear_refs: File,
+    evict_batch_pages: usize,
+}
+
+impl ColdMemory {
+    /// Registers the DRAM regions of `guest_memory` with a userfaultfd object served by a new
+    /// thread, and sets up the memory pressure trigger.
+    pub fn new(
+        config: &ColdMemoryConfig,
+        guest_memory: &GuestMemoryMmap,
+        page_size: usize,
+        seccomp_filter: Arc<BpfProgram>,
+    ) -> Result<Self, ColdMemoryError> {
+        if page_size != PAGE_SIZE
+            || dram_regions(guest_memory).any(|region| region.inner.file_offset().is_some())
+        {
+            return Err(ColdMemoryError::UnsupportedMemory);
+        }
+
+        let evict_batch_pages = config
+            .evict_batch_mib
+            .checked_mul(1 << 20)
+            .ok_or(ColdMemoryError::InvalidEvictBatch)?
+            / PAGE_SIZE;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&config.backing_file_path)
+            .map_err(ColdMemoryError::BackingFile)?;
+
+        let mut pressure_trigger = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/proc/pressure/memory")
+            .map_err(ColdMemoryError::PressureTrigger)?;
+        let trigger = format!(
+            "some {} {}\0",
+            config.pressure_stall_us, config.pressure_window_us
+        );
+        pressure_trigger
+            .write_all(trigger.as_bytes())
+            .map_err(ColdMemoryError::PressureTrigger)?;
+
+        let pagemap = File::open("/proc/self/pagemap").map_err(ColdMemoryError::PageTables)?;
+        let clear_refs = OpenOptions::new()
+            .write(true)
+            .open("/proc/self/clear_refs")
+            .map_err(ColdMemoryError::PageTables)?;
+
+        let uffd = UffdBuilder::new()
+            .close_on_exec(true)
+            .non_blocking(false)
+            .user_mode_only(false)
+            .create()
+            .map_err(ColdMemoryError::Uffd)?;
+        for region in dram_regions(guest_memory) {
+            uffd.register(region.inner.as_ptr().cast(), u64_to_usize(region.len()))
+                .map_err(ColdMemoryError::UffdRegister)?;
+        }
+
+        let store = Arc::new(Mutex::new(ColdPageStore::new(file)));
+        let cold_memory = Self {
+            store: store.clone(),
+            pressure_trigger,
+            pagemap,
+            clear_refs,
+            evict_batch_pages,
+        };
+        // Only writes from now on count towards keeping pages hot.
+        cold_memory.clear_soft_dirty()?;
+
+        let handler = FaultHandler { uffd, store };
+        thread::Builder::new()
+            .name("fc_cold_mem".into())
+            .spawn(move || handler.run(&seccomp_filter))
+            .map_err(ColdMemoryError::Spawn)?;
+
+        Ok(cold_memory)
+    }
+
+    /// Returns the PSI trigger, which becomes ready for `EPOLLPRI` under memory pressure.
+    pub fn pressure_trigger(&self) -> &File {
+        &self.pressure_trigger
+    }
+
+    fn clear_soft_dirty(&self) -> Result<(), ColdMemoryError> {
+        (&self.clear_refs)
+            .write_all(b"4")
+            .map_err(ColdMemoryError::PageTables)
+    }
+
+    /// Evicts the guest pages which weren't written since the previous call, up to the
+    /// configured batch size, and returns the number of evicted pages.
+    ///
+    /// Guest memory must not be accessed concurrently, so the vCPUs must be paused.
+    pub fn evict(&mut self, guest_memory: &GuestMemoryMmap) -> Result<usize, ColdMemoryError> {
+        METRICS.cold_memory.pressure_events.inc();
+
+        let mut evicted = 0;
+        let res = self.evict_pages(guest_memory, &mut evicted);
+        METRICS.cold_memory.pages_evicted.add(usize_to_u64(evicted));
+        if res.is_err() {
+            METRICS.cold_memory.evict_fails.inc();
+        }
+        res.and_then(|()| self.clear_soft_dirty())?;
+        Ok(evicted)
+    }
+
+    fn evict_pages(
+        &self,
+        guest_memory: &GuestMemoryMmap,
+        evicted: &mut usize,
+    ) -> Result<(), ColdMemoryError> {
+        let mut entries = vec![0u8; PAGEMAP_BATCH * PAGEMAP_ENTRY_SIZE];
+        let mut page = vec![0u8; PAGE_SIZE];
+
+        for region in dram_regions(guest_memory) {
+            let host_start = region.inner.as_ptr() as u64;
+            let num_pages = u64_to_usize(region.len()) / PAGE_SIZE;
+
+            for batch_start in (0..num_pages).step_by(PAGEMAP_BATCH) {
+                let batch_len = PAGEMAP_BATCH.min(num_pages - batch_start);
+                let entries = &mut entries[..batch_len * PAGEMAP_ENTRY_SIZE];
+                let pagemap_offset = (host_start / usize_to_u64(PAGE_SIZE)
+                    + usize_to_u64(batch_start))
+                    * usize_to_u64(PAGEMAP_ENTRY_SIZE);
+                self.pagemap
+                    .read_exact_at(entries, pagemap_offset)
+                    .map_err(ColdMemoryError::PageTables)?;
+
+                for (index, entry) in (batch_start..).zip(entries.chunks_exact(PAGEMAP_ENTRY_SIZE))
+                {
+                    let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                    // Missing pages have nothing to evict and soft-dirty ones are hot.
+                    if entry & PAGEMAP_PRESENT == 0 || entry & PAGEMAP_SOFT_DIRTY != 0 {
+                        continue;
+                    }
+                    if *evicted == self.evict_batch_pages {
+                        return Ok(());
+                    }
+
+                    let page_offset = usize_to_u64(index * PAGE_SIZE);
+                    self.evict_page(
+                        guest_memory,
+                        region.start_addr().unchecked_add(page_offset),
+                        host_start + page_offset,
+                        &mut page,
+                    )?;
+                    *evicted += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evict_page(
+        &self,
+        guest_memory: &GuestMemoryMmap,
+        guest_addr: GuestAddress,
+        host_addr: u64,
+        page: &mut [u8],
+    ) -> Result<(), ColdMemoryError> {
+        guest_memory
+            .read_slice(page, guest_addr)
+            .map_err(ColdMemoryError::GuestMemory)?;
+        self.store
+            .lock()
+            .expect("Poisoned lock")
+            .insert(host_addr, page)?;
+
+        // SAFETY: `host_addr` is the address of a page of a guest memory mapping, whose
+        // contents were just saved to the store.
+        let ret = unsafe { libc::madvise(host_addr as *mut _, PAGE_SIZE, libc::MADV_DONTNEED) };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            self.store.lock().expect("Poisoned lock").discard(host_addr);
+            return Err(ColdMemoryError::Madvise(err));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempfile::TempFile;
+
+    use super::*;
+
+    fn test_page() -> Vec<u8> {
+        // Zero words interleaved with runs of data, so that the page compresses.
+        (0..PAGE_SIZE)
+            .map(|i| {
+                if (i / WORD_SIZE) % 3 == 0 {
+                    u8::try_from(i % 251).unwrap()
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compress_page() {
+        let mut out = Vec::new();
+
+        let page = test_page();
+        assert!(compress_page(&page, &mut out));
+        assert!(out.len() < PAGE_SIZE);
+        let mut decompressed = vec![0xffu8; PAGE_SIZE];
+        decompress_page(&out, &mut decompressed).unwrap();
+        assert_eq!(decompressed, page);
+
+        // Truncated and oversized encodings are rejected.
+        decompress_page(&out[..out.len() - 1], &mut decompressed).unwrap_err();
+        out.push(0);
+        decompress_page(&out, &mut decompressed).unwrap_err();
+
+        // Pages without zero words don't compress.
+        let page = vec![0xabu8; PAGE_SIZE];
+        assert!(!compress_page(&page, &mut out));
+    }
+
+    #[test]
+    fn test_same_filled() {
+        assert_eq!(same_filled(&[0u8; PAGE_SIZE]), Some(0));
+        let page: Vec<u8> = std::iter::repeat_n(0x1122_3344_5566_7788u64.to_ne_bytes(), 512)
+            .flatten()
+            .collect();
+        assert_eq!(same_filled(&page), Some(0x1122_3344_5566_7788));
+        assert_eq!(same_filled(&test_page()), None);
+    }
+
+    #[test]
+    fn test_cold_page_store() {
+        let file = TempFile::new().unwrap();
+        let mut store = ColdPageStore::new(file.as_file().try_clone().unwrap());
+        let mut page = vec![0u8; PAGE_SIZE];
+
+        // Pages which were never evicted are not found.
+        assert!(!store.take(0x1000, &mut page).unwrap());
+
+        let compressible = test_page();
+        let raw: Vec<u8> = (0..PAGE_SIZE)
+            .map(|i| u8::try_from(i % 251).unwrap() | 1)
+            .collect();
+        store.insert(0x1000, &compressible).unwrap();
+        store.insert(0x2000, &raw).unwrap();
+        store.insert(0x3000, &[0u8; PAGE_SIZE]).unwrap();
+        assert!(matches!(
+            store.pages[&0x1000],
+            StoredPage::Runs { offset: 0, .. }
+        ));
+        assert!(matches!(store.pages[&0x2000], StoredPage::Raw { .. }));
+        assert_eq!(store.pages[&0x3000], StoredPage::SameFilled(0));
+        let file_len = store.file_len;
+        assert!(store.stored_bytes < 2 * usize_to_u64(PAGE_SIZE));
+
+        assert!(store.take(0x1000, &mut page).unwrap());
+        assert_eq!(page, compressible);
+        assert!(store.take(0x2000, &mut page).unwrap());
+        assert_eq!(page, raw);
+        assert!(store.take(0x3000, &mut page).unwrap());
+        assert_eq!(page, vec![0u8; PAGE_SIZE]);
+        assert!(store.pages.is_empty());
+        assert_eq!(store.stored_bytes, 0);
+
+        // Released slots are reused.
+        store.insert(0x4000, &compressible).unwrap();
+        store.insert(0x5000, &raw).unwrap();
+        assert_eq!(store.file_len, file_len);
+
+        // Discarded pages are not reloaded.
+        store.discard(0x4000);
+        assert!(!store.take(0x4000, &mut page).unwrap());
+    }
+}
@@ -1,8 +1,8 @@
 // Copyright 2025 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use kvm_ioctls::VmFd;
 use vmm_sys_util::eventfd::EventFd;
@@ -39,6 +39,26 @@ pub struct MsixVectorConfig {
     pub devid: u32,
 }
 
+/// Mask of the destination ID in the low address of an x86 message signaled interrupt.
+#[cfg(target_arch = "x86_64")]
+const MSI_ADDR_DEST_ID_MASK: u32 = 0x000f_f000;
+/// Shift of the destination ID in the low address of an x86 message signaled interrupt.
+#[cfg(target_arch = "x86_64")]
+const MSI_ADDR_DEST_ID_SHIFT: u32 = 12;
+/// Redirection hint and destination mode bits of the low address of an x86 message signaled
+/// interrupt.
+#[cfg(target_arch = "x86_64")]
+const MSI_ADDR_RH_DM: u32 = 0b1100;
+
+/// Returns the low address `low_addr` of an x86 message signaled interrupt with its destination
+/// replaced by the local APIC of `vcpu`, in physical destination mode. The ID of the local APIC
+/// of each vCPU is its index.
+#[cfg(target_arch = "x86_64")]
+pub fn msi_address_for_vcpu(low_addr: u32, vcpu: u8) -> u32 {
+    (low_addr & !(MSI_ADDR_DEST_ID_MASK | MSI_ADDR_RH_DM))
+        | (u32::from(vcpu) << MSI_ADDR_DEST_ID_SHIFT)
+}
+
 /// Type that describes an allocated interrupt
 #[derive(Debug)]
 pub struct MsixVector {
@@ -48,6 +68,9 @@ pub struct MsixVector {
     pub event_fd: EventFd,
     /// Flag determining whether the vector is enabled
     pub enabled: AtomicBool,
+    /// vCPU the interrupts of this vector are delivered to, in place of the one programmed by
+    /// the guest, if any
+    pub vcpu: Mutex<Option<u8>>,
 }
 
 impl MsixVector {
@@ -57,6 +80,7 @@ impl MsixVector {
             gsi,
             event_fd: EventFd::new(libc::EFD_NONBLOCK)?,
             enabled: AtomicBool::new(enabled),
+            vcpu: Mutex::new(None),
         })
     }
 }
@@ -134,6 +158,19 @@ impl MsixVectorGroup {
         self.vectors.get(index).map(|route| &route.event_fd)
     }
 
+    /// Set the vCPU the interrupts of a vector in the group are delivered to, returning whether
+    /// it changed. The new vCPU applies from the next update of the configuration of the vector.
+    pub fn set_vcpu(&self, index: usize, vcpu: Option<u8>) -> Result<bool, InterruptError> {
+        let vector = self
+            .vectors
+            .get(index)
+            .ok_or(InterruptError::InvalidVectorIndex(index))?;
+        let mut current = vector.vcpu.lock().expect("Poisoned lock");
+        let changed = *current != vcpu;
+        *current = vcpu;
+        Ok(changed)
+    }
+
     /// Update the MSI-X configuration for a vector in the group
     pub fn update(
         &self,
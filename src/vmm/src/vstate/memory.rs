@@ -5,15 +5,19 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the THIRD-PARTY file.
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::SeekFrom;
 use std::ops::Deref;
-use std::sync::{Arc, Mutex};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 
 use bitvec::vec::BitVec;
 use kvm_bindings::{KVM_MEM_LOG_DIRTY_PAGES, kvm_userspace_memory_region};
 use log::error;
 use serde::{Deserialize, Serialize};
+use utils::time::TimerFd;
 pub use vm_memory::bitmap::{AtomicBitmap, BS, Bitmap, BitmapSlice};
 pub use vm_memory::mmap::MmapRegionBuilder;
 use vm_memory::mmap::{MmapRegionError, NewBitmap};
@@ -23,9 +27,11 @@ pub use vm_memory::{
 };
 use vm_memory::{GuestMemoryError, GuestMemoryRegionBytes, VolatileSlice, WriteVolatile};
 use vmm_sys_util::errno;
+use vmm_sys_util::eventfd::EventFd;
 
 use crate::utils::{get_page_size, u64_to_usize};
 use crate::vmm_config::machine_config::HugePageConfig;
+use crate::vmm_config::memory_backing::MemoryBackingConfig;
 use crate::vstate::vm::VmError;
 use crate::{DirtyBitmap, Vm};
 
@@ -59,6 +65,12 @@ pub enum MemoryError {
     Unaligned,
     /// Error protecting memory slot: {0}
     Mprotect(std::io::Error),
+    /// Cannot open or resize the guest memory backing file: {0}
+    BackingFile(std::io::Error),
+    /// Cannot write guest memory back to its backing file: {0}
+    Writeback(std::io::Error),
+    /// Guest memory backed by a file cannot use hugepages
+    BackingFileHugePages,
 }
 
 /// Type of the guest region
@@ -337,6 +349,13 @@ impl GuestRegionMmapExt {
         Ok(())
     }
 
+    /// Returns the file offset of this region if it is a DRAM region mapped shared from a file.
+    fn backing_file_offset(&self) -> Option<&FileOffset> {
+        self.inner.file_offset().filter(|_| {
+            self.region_type == GuestRegionType::Dram && self.inner.flags() & libc::MAP_SHARED != 0
+        })
+    }
+
     pub(crate) fn discard_range(
         &self,
         caddr: MemoryRegionAddress,
@@ -501,6 +520,31 @@ pub fn memfd_backed(
     )
 }
 
+/// Creates a GuestMemoryMmap shared with the file at `path`, which is resized to the memory size
+/// and whose previous contents are discarded.
+pub fn file_backed(
+    path: &Path,
+    regions: &[(GuestAddress, usize)],
+    track_dirty_pages: bool,
+) -> Result<Vec<GuestRegionMmap>, MemoryError> {
+    let size = regions.iter().map(|&(_, size)| size as u64).sum();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(MemoryError::BackingFile)?;
+    file.set_len(size).map_err(MemoryError::BackingFile)?;
+
+    create(
+        regions.iter().copied(),
+        libc::MAP_SHARED,
+        Some(file),
+        track_dirty_pages,
+    )
+}
+
 /// Creates a GuestMemoryMmap from raw regions.
 pub fn anonymous(
     regions: impl Iterator<Item = (GuestAddress, usize)>,
@@ -582,6 +626,12 @@ where
 
     /// Discards a memory range, freeing up memory pages
     fn discard_range(&self, addr: GuestAddress, range_len: usize) -> Result<(), GuestMemoryError>;
+
+    /// Starts the writeback of the dirty pages of file-backed DRAM, without waiting for it
+    fn start_writeback(&self) -> Result<(), MemoryError>;
+
+    /// Writes the dirty pages of file-backed DRAM back to the file and waits for completion
+    fn flush(&self) -> Result<(), MemoryError>;
 }
 
 /// State of a guest memory region saved to file/buffer.
@@ -759,6 +809,133 @@ impl GuestMemoryExtension for GuestMemoryMmap {
             region.discard_range(start, len)
         })
     }
+
+    fn start_writeback(&self) -> Result<(), MemoryError> {
+        self.iter().try_for_each(|region| {
+            let Some(file_offset) = region.backing_file_offset() else {
+                return Ok(());
+            };
+            // `create` guarantees that the offsets of the regions fit in an i64.
+            let offset = i64::try_from(file_offset.start()).unwrap();
+            let len = i64::try_from(region.len()).unwrap();
+            // SAFETY: The file descriptor is valid and sync_file_range does not access memory.
+            let ret = unsafe {
+                libc::sync_file_range(
+                    file_offset.file().as_raw_fd(),
+                    offset,
+                    len,
+                    libc::SYNC_FILE_RANGE_WRITE,
+                )
+            };
+            if ret < 0 {
+                return Err(MemoryError::Writeback(std::io::Error::last_os_error()));
+            }
+            Ok(())
+        })
+    }
+
+    fn flush(&self) -> Result<(), MemoryError> {
+        self.iter().try_for_each(|region| {
+            if region.backing_file_offset().is_none() {
+                return Ok(());
+            }
+            // SAFETY: The address and length describe the host mapping of the region.
+            let ret = unsafe {
+                libc::msync(
+                    region.inner.as_ptr().cast(),
+                    u64_to_usize(region.len()),
+                    libc::MS_SYNC,
+                )
+            };
+            if ret < 0 {
+                return Err(MemoryError::Writeback(std::io::Error::last_os_error()));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Controls the writeback of file-backed guest memory.
+#[derive(Debug)]
+pub struct MemoryWriteback {
+    /// Timer triggering the periodic writeback of guest memory, if configured.
+    pub timer: Option<TimerFd>,
+    /// Whether guest memory is flushed to its backing file when the microVM is paused.
+    pub flush_on_pause: bool,
+}
+
+impl MemoryWriteback {
+    /// Creates a [`MemoryWriteback`] for the given configuration, arming its timer.
+    pub fn new(config: &MemoryBackingConfig) -> Self {
+        let timer = config.writeback_interval_ms.map(|interval_ms| {
+            let interval = Duration::from_millis(interval_ms);
+            let mut timer = TimerFd::new();
+            timer.arm(interval, Some(interval));
+            timer
+        });
+        MemoryWriteback {
+            timer,
+            flush_on_pause: config.flush_on_pause,
+        }
+    }
+}
+
+/// Notifies the devices mapping guest memory of the ranges removed from the guest by unplugging
+/// or ballooning them, so that they stop accessing them.
+#[derive(Debug, Default)]
+pub struct GuestMemoryRemovals {
+    listeners: Mutex<Vec<Weak<GuestMemoryRemovalListener>>>,
+}
+
+impl GuestMemoryRemovals {
+    /// Registers a listener of the removed ranges. It is unregistered once dropped.
+    pub fn subscribe(&self) -> Result<Arc<GuestMemoryRemovalListener>, std::io::Error> {
+        let listener = Arc::new(GuestMemoryRemovalListener {
+            ranges: Mutex::new(Vec::new()),
+            event: EventFd::new(libc::EFD_NONBLOCK)?,
+        });
+        self.listeners
+            .lock()
+            .expect("Poisoned lock")
+            .push(Arc::downgrade(&listener));
+        Ok(listener)
+    }
+
+    /// Notifies the listeners of the removal of `len` bytes of guest memory at `addr`.
+    pub fn notify(&self, addr: GuestAddress, len: usize) {
+        let mut listeners = self.listeners.lock().expect("Poisoned lock");
+        listeners.retain(|listener| listener.strong_count() > 0);
+        for listener in listeners.iter().filter_map(Weak::upgrade) {
+            listener
+                .ranges
+                .lock()
+                .expect("Poisoned lock")
+                .push((addr, len));
+            if let Err(err) = listener.event.write(1) {
+                error!("Failed to notify the removal of guest memory: {}", err);
+            }
+        }
+    }
+}
+
+/// Listener of the ranges of guest memory removed from the guest, signalling its event when
+/// ranges are removed.
+#[derive(Debug)]
+pub struct GuestMemoryRemovalListener {
+    ranges: Mutex<Vec<(GuestAddress, usize)>>,
+    event: EventFd,
+}
+
+impl GuestMemoryRemovalListener {
+    /// Event signalled when ranges are removed.
+    pub fn event(&self) -> &EventFd {
+        &self.event
+    }
+
+    /// Takes the ranges removed since the last call, as their address and length.
+    pub fn take_ranges(&self) -> Vec<(GuestAddress, usize)> {
+        std::mem::take(&mut *self.ranges.lock().expect("Poisoned lock"))
+    }
 }
 
 fn create_memfd(
@@ -1232,6 +1409,44 @@ mod tests {
         memfd.add_seals(&seals).unwrap_err();
     }
 
+    #[test]
+    fn test_file_backed() {
+        let page_size = 0x1000;
+        let backing_file = TempFile::new().unwrap();
+        let mut file = backing_file.as_file().try_clone().unwrap();
+        file.write_all(&[0xffu8; 0x100]).unwrap();
+
+        let regions = vec![
+            (GuestAddress(0), page_size),
+            (GuestAddress(0x10000), 2 * page_size),
+        ];
+        let guest_memory =
+            into_region_ext(file_backed(backing_file.as_path(), &regions, false).unwrap());
+
+        // The previous contents of the file are discarded.
+        assert_eq!(file.metadata().unwrap().len(), 3 * page_size as u64);
+        assert_eq!(guest_memory.read_obj::<u64>(GuestAddress(0)).unwrap(), 0);
+
+        guest_memory
+            .write_slice(&[0x42u8; 8], GuestAddress(0x10000 + 0x10))
+            .unwrap();
+        guest_memory.start_writeback().unwrap();
+        guest_memory.flush().unwrap();
+
+        // Guest memory is visible through the file.
+        let mut data = [0u8; 8];
+        file.seek(SeekFrom::Start((page_size + 0x10) as u64))
+            .unwrap();
+        file.read_exact(&mut data).unwrap();
+        assert_eq!(data, [0x42u8; 8]);
+
+        // Writeback is a no-op for anonymous memory.
+        let anonymous_memory =
+            into_region_ext(anonymous(regions.into_iter(), false, HugePageConfig::None).unwrap());
+        anonymous_memory.start_writeback().unwrap();
+        anonymous_memory.flush().unwrap();
+    }
+
     /// This asserts that $lhs matches $rhs.
     macro_rules! assert_match {
         ($lhs:expr, $rhs:pat) => {{ assert!(matches!($lhs, $rhs)) }};
@@ -1330,4 +1545,26 @@ mod tests {
             GuestMemoryError::IOError(_)
         );
     }
+
+    #[test]
+    fn test_guest_memory_removals() {
+        let removals = GuestMemoryRemovals::default();
+        let listener = removals.subscribe().unwrap();
+        let dropped = removals.subscribe().unwrap();
+        drop(dropped);
+
+        removals.notify(GuestAddress(0x1000), 0x2000);
+        removals.notify(GuestAddress(0x8000), 0x1000);
+        assert_eq!(listener.event().read().unwrap(), 2);
+        assert_eq!(
+            listener.take_ranges(),
+            [
+                (GuestAddress(0x1000), 0x2000),
+                (GuestAddress(0x8000), 0x1000)
+            ]
+        );
+        assert!(listener.take_ranges().is_empty());
+        // Dropped listeners are unregistered.
+        assert_eq!(removals.listeners.lock().unwrap().len(), 1);
+    }
 }
@@ -3,6 +3,8 @@
 
 /// Module with the implementation of a Bus that can hold devices.
 pub mod bus;
+/// Module with the compressed backing store of cold guest memory.
+pub mod cold_memory;
 /// VM interrupts implementation.
 pub mod interrupts;
 /// Module with Kvm implementation.
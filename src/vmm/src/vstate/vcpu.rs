@@ -27,6 +27,8 @@ use crate::logger::{IncMetric, METRICS};
 use crate::seccomp::{BpfProgram, BpfProgramRef};
 use crate::utils::signal::{Killable, register_signal_handler, sigrtmin};
 use crate::utils::sm::StateMachine;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::machine_config::TscConfig;
 use crate::vstate::bus::Bus;
 use crate::vstate::vm::Vm;
 
@@ -62,6 +64,11 @@ pub struct VcpuConfig {
     pub vcpu_count: u8,
     /// Enable simultaneous multithreading in the CPUID configuration.
     pub smt: bool,
+    /// Number of guest VCPUs in each socket.
+    pub cpus_per_socket: u8,
+    /// TSC configuration of the guest VCPUs.
+    #[cfg(target_arch = "x86_64")]
+    pub tsc: Option<TscConfig>,
     /// Configuration for vCPU
     pub cpu_config: CpuConfiguration,
 }
@@ -281,6 +288,14 @@ impl Vcpu {
                     )))
                     .expect("vcpu channel unexpectedly closed");
             }
+            // Synchronize cannot be performed on a running Vcpu.
+            Ok(VcpuEvent::Synchronize) => {
+                self.response_sender
+                    .send(VcpuResponse::NotAllowed(String::from(
+                        "synchronization is unavailable while running",
+                    )))
+                    .expect("vcpu channel unexpectedly closed");
+            }
             Ok(VcpuEvent::Finish) => return StateMachine::finish(),
             // Unhandled exit of the other end.
             Err(TryRecvError::Disconnected) => {
@@ -351,6 +366,17 @@ impl Vcpu {
 
                 StateMachine::next(Self::paused)
             }
+            Ok(VcpuEvent::Synchronize) => {
+                let response = match self.complete_pending_io() {
+                    Ok(()) => VcpuResponse::Synchronized,
+                    Err(err) => VcpuResponse::Error(err),
+                };
+                self.response_sender
+                    .send(response)
+                    .expect("vcpu channel unexpectedly closed");
+
+                StateMachine::next(Self::paused)
+            }
             Ok(VcpuEvent::Finish) => StateMachine::finish(),
             // Unhandled exit of the other end.
             Err(_) => {
@@ -381,6 +407,23 @@ impl Vcpu {
         StateMachine::finish()
     }
 
+    /// Completes the MMIO or PIO access of the last KVM exit, leaving the vCPU at an instruction
+    /// boundary.
+    ///
+    /// KVM only completes these accesses when the vCPU runs again, which a pause request that
+    /// arrived right after the exit skips. With `immediate_exit` set, KVM_RUN completes them and
+    /// returns before entering the guest.
+    fn complete_pending_io(&mut self) -> Result<(), VcpuError> {
+        self.kvm_vcpu.fd.set_kvm_immediate_exit(1);
+        let result = match self.kvm_vcpu.fd.run() {
+            Err(ref err) if err.errno() == libc::EINTR => Ok(()),
+            Err(err) => Err(VcpuError::FaultyKvmExit(err.to_string())),
+            Ok(exit) => Err(VcpuError::UnhandledKvmExit(format!("{:?}", exit))),
+        };
+        self.kvm_vcpu.fd.set_kvm_immediate_exit(0);
+        result
+    }
+
     /// Runs the vCPU in KVM context and handles the kvm exit reason.
     ///
     /// Returns error or enum specifying whether emulation was handled or interrupted.
@@ -521,6 +564,8 @@ pub enum VcpuEvent {
     SaveState,
     /// Event to dump CPU configuration of a paused Vcpu.
     DumpCpuConfig,
+    /// Event to complete the pending I/O of a paused Vcpu.
+    Synchronize,
 }
 
 /// List of responses that the Vcpu reports.
@@ -539,6 +584,8 @@ pub enum VcpuResponse {
     SavedState(Box<VcpuState>),
     /// Vcpu is in the state where CPU config is dumped.
     DumpedCpuConfig(Box<CpuConfiguration>),
+    /// Vcpu completed its pending I/O.
+    Synchronized,
 }
 
 impl fmt::Debug for VcpuResponse {
@@ -552,6 +599,7 @@ impl fmt::Debug for VcpuResponse {
             Error(err) => write!(f, "VcpuResponse::Error({:?})", err),
             NotAllowed(reason) => write!(f, "VcpuResponse::NotAllowed({})", reason),
             DumpedCpuConfig(_) => write!(f, "VcpuResponse::DumpedCpuConfig"),
+            Synchronized => write!(f, "VcpuResponse::Synchronized"),
         }
     }
 }
@@ -808,11 +856,11 @@ pub(crate) mod tests {
             use crate::VcpuResponse::*;
             // Guard match with no wildcard to make sure we catch new enum variants.
             match self {
-                Paused | Resumed | Exited(_) => (),
+                Paused | Resumed | Exited(_) | Synchronized => (),
                 Error(_) | NotAllowed(_) | SavedState(_) | DumpedCpuConfig(_) => (),
             };
             match (self, other) {
-                (Paused, Paused) | (Resumed, Resumed) => true,
+                (Paused, Paused) | (Resumed, Resumed) | (Synchronized, Synchronized) => true,
                 (Exited(code), Exited(other_code)) => code == other_code,
                 (NotAllowed(_), NotAllowed(_))
                 | (SavedState(_), SavedState(_))
@@ -889,6 +937,8 @@ pub(crate) mod tests {
                     &VcpuConfig {
                         vcpu_count: 1,
                         smt: false,
+                        cpus_per_socket: 1,
+                        tsc: None,
                         cpu_config: CpuConfiguration {
                             cpuid: Cpuid::try_from(kvm.supported_cpuid.clone()).unwrap(),
                             msrs: BTreeMap::new(),
@@ -906,6 +956,7 @@ pub(crate) mod tests {
                 &VcpuConfig {
                     vcpu_count: 1,
                     smt: false,
+                    cpus_per_socket: 1,
                     cpu_config: crate::cpu_config::aarch64::CpuConfiguration::default(),
                 },
                 &kvm.optional_capabilities(),
@@ -1087,6 +1138,41 @@ pub(crate) mod tests {
         vcpu_handle.send_event(VcpuEvent::Finish).unwrap();
     }
 
+    #[test]
+    fn test_vcpu_synchronize() {
+        let (_vm, mut vcpu_handle, _) = vcpu_configured_for_boot();
+
+        // Queue a Synchronize event, expect a response.
+        queue_event_expect_response(
+            &mut vcpu_handle,
+            VcpuEvent::Synchronize,
+            VcpuResponse::Synchronized,
+        );
+
+        // Queue a Resume event, expect a response.
+        queue_event_expect_response(&mut vcpu_handle, VcpuEvent::Resume, VcpuResponse::Resumed);
+
+        // The Synchronize event is only allowed while paused.
+        queue_event_expect_response(
+            &mut vcpu_handle,
+            VcpuEvent::Synchronize,
+            VcpuResponse::NotAllowed(String::new()),
+        );
+
+        // Queue a Pause event, expect a response.
+        queue_event_expect_response(&mut vcpu_handle, VcpuEvent::Pause, VcpuResponse::Paused);
+
+        // A synchronized vCPU resumes normally.
+        queue_event_expect_response(
+            &mut vcpu_handle,
+            VcpuEvent::Synchronize,
+            VcpuResponse::Synchronized,
+        );
+        queue_event_expect_response(&mut vcpu_handle, VcpuEvent::Resume, VcpuResponse::Resumed);
+
+        vcpu_handle.send_event(VcpuEvent::Finish).unwrap();
+    }
+
     #[test]
     fn test_vcpu_dump_cpu_config() {
         let (_vm, mut vcpu_handle, _) = vcpu_configured_for_boot();
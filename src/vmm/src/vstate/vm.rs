@@ -6,9 +6,8 @@
 // found in the THIRD-PARTY file.
 
 use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::Path;
+use std::fs::File;
+use std::io::{Seek, Write};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 
@@ -31,10 +30,12 @@ use crate::pci::{DeviceRelocation, DeviceRelocationError, PciDevice};
 use crate::persist::CreateSnapshotError;
 use crate::vmm_config::snapshot::SnapshotType;
 use crate::vstate::bus::Bus;
+#[cfg(target_arch = "x86_64")]
+use crate::vstate::interrupts::msi_address_for_vcpu;
 use crate::vstate::interrupts::{InterruptError, MsixVector, MsixVectorConfig, MsixVectorGroup};
 use crate::vstate::memory::{
-    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion, GuestMemoryState,
-    GuestRegionMmap, GuestRegionMmapExt, MemoryError,
+    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion, GuestMemoryRemovals,
+    GuestMemoryState, GuestRegionMmap, GuestRegionMmapExt, MemoryError,
 };
 use crate::vstate::resources::ResourceAllocator;
 use crate::vstate::vcpu::VcpuError;
@@ -62,6 +63,8 @@ pub struct VmCommon {
     pub resource_allocator: Mutex<ResourceAllocator>,
     /// MMIO bus
     pub mmio_bus: Arc<Bus>,
+    /// Notifies the devices of the guest memory removed from the guest.
+    pub memory_removals: Arc<GuestMemoryRemovals>,
 }
 
 /// Errors associated with the wrappers over KVM ioctls.
@@ -120,7 +123,7 @@ impl Vm {
         const MAX_ATTEMPTS: u32 = 5;
         let mut attempt = 1;
         let fd = loop {
-            match kvm.fd.create_vm() {
+            match kvm.create_vm() {
                 Ok(fd) => break fd,
                 Err(e) if e.errno() == libc::EINTR && attempt < MAX_ATTEMPTS => {
                     info!("Attempt #{attempt} of KVM_CREATE_VM returned EINTR");
@@ -141,6 +144,7 @@ impl Vm {
             interrupts: Mutex::new(HashMap::with_capacity(GSI_MSI_END as usize + 1)),
             resource_allocator: Mutex::new(ResourceAllocator::new()),
             mmio_bus: Arc::new(Bus::new()),
+            memory_removals: Arc::new(GuestMemoryRemovals::default()),
         })
     }
 
@@ -286,6 +290,11 @@ impl Vm {
         &self.common.guest_memory
     }
 
+    /// Gets the notifier of the guest memory removed from this [`Vm`]
+    pub fn memory_removals(&self) -> &Arc<GuestMemoryRemovals> {
+        &self.common.memory_removals
+    }
+
     /// Gets a mutable reference to this [`Vm`]'s [`ResourceAllocator`] object
     pub fn resource_allocator(&self) -> MutexGuard<'_, ResourceAllocator> {
         self.common
@@ -326,54 +335,44 @@ impl Vm {
     }
 
     /// Takes a snapshot of the virtual machine running inside the given [`Vmm`] and saves it to
-    /// `mem_file_path`.
+    /// `file`.
     ///
-    /// If `snapshot_type` is [`SnapshotType::Diff`], and `mem_file_path` exists and is a snapshot
-    /// file of matching size, then the diff snapshot will be directly merged into the existing
-    /// snapshot. Otherwise, existing files are simply overwritten.
+    /// If `snapshot_type` is [`SnapshotType::Diff`], and `file` is a snapshot file of matching
+    /// size, then the diff snapshot will be directly merged into the existing snapshot. Otherwise,
+    /// the content of `file` is simply overwritten.
     pub(crate) fn snapshot_memory_to_file(
         &self,
-        mem_file_path: &Path,
+        file: &mut File,
         snapshot_type: SnapshotType,
     ) -> Result<(), CreateSnapshotError> {
         use self::CreateSnapshotError::*;
 
-        // Need to check this here, as we create the file in the line below
-        let file_existed = mem_file_path.exists();
-
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(mem_file_path)
-            .map_err(|err| MemoryBackingFile("open", err))?;
-
         // Determine what size our total memory area is.
         let mem_size_mib = mem_size_mib(self.guest_memory());
         let expected_size = mem_size_mib * 1024 * 1024;
 
-        if file_existed {
-            let file_size = file
-                .metadata()
-                .map_err(|e| MemoryBackingFile("get_metadata", e))?
-                .len();
-
-            // Here we only truncate the file if the size mismatches.
-            // - For full snapshots, the entire file's contents will be overwritten anyway. We have
-            //   to avoid truncating here to deal with the edge case where it represents the
-            //   snapshot file from which this very microVM was loaded (as modifying the memory file
-            //   would be reflected in the mmap of the file, meaning a truncate operation would zero
-            //   out guest memory, and thus corrupt the VM).
-            // - For diff snapshots, we want to merge the diff layer directly into the file.
-            if file_size != expected_size {
-                file.set_len(0)
-                    .map_err(|err| MemoryBackingFile("truncate", err))?;
-            }
+        let file_size = file
+            .metadata()
+            .map_err(|e| MemoryBackingFile("get_metadata", e))?
+            .len();
+
+        // Here we only truncate the file if the size mismatches.
+        // - For full snapshots, the entire file's contents will be overwritten anyway. We have to
+        //   avoid truncating here to deal with the edge case where it represents the snapshot file
+        //   from which this very microVM was loaded (as modifying the memory file would be
+        //   reflected in the mmap of the file, meaning a truncate operation would zero out guest
+        //   memory, and thus corrupt the VM).
+        // - For diff snapshots, we want to merge the diff layer directly into the file.
+        if file_size != expected_size {
+            file.set_len(0)
+                .map_err(|err| MemoryBackingFile("truncate", err))?;
         }
 
         // Set the length of the file to the full size of the memory area.
         file.set_len(expected_size)
             .map_err(|e| MemoryBackingFile("set_length", e))?;
+        // Passed files may not be positioned at their start.
+        file.rewind().map_err(|e| MemoryBackingFile("seek", e))?;
 
         match snapshot_type {
             SnapshotType::Diff => {
@@ -441,6 +440,12 @@ impl Vm {
         entry.u.msi.address_lo = config.low_addr;
         entry.u.msi.address_hi = config.high_addr;
         entry.u.msi.data = config.data;
+        // The interrupts of a vector with a vCPU are delivered to it, whatever the destination
+        // the guest programmed.
+        #[cfg(target_arch = "x86_64")]
+        if let Some(vcpu) = *route.vcpu.lock().expect("Poisoned lock") {
+            entry.u.msi.address_lo = msi_address_for_vcpu(config.low_addr, vcpu);
+        }
 
         if self.common.fd.check_extension(kvm_ioctls::Cap::MsiDevid) {
             // According to KVM documentation:
@@ -759,6 +764,42 @@ pub(crate) mod tests {
         msix_group.update(4, config, true, true).unwrap_err();
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_msi_vector_group_vcpu() {
+        let (_, mut vm) = setup_vm_with_memory(mib_to_bytes(128));
+        enable_irqchip(&mut vm);
+        let vm = Arc::new(vm);
+        let msix_group = create_msix_group(&vm);
+        let msi_address_lo = |index| {
+            let gsi = crate::arch::GSI_MSI_START + index;
+            let interrupts = vm.common.interrupts.lock().unwrap();
+            // SAFETY: because we know we setup MSI routes.
+            unsafe { interrupts.get(&gsi).unwrap().entry.u.msi.address_lo }
+        };
+
+        // The guest targets the local APIC 1 in logical destination mode, with the redirection
+        // hint set.
+        let config = MsixVectorConfig {
+            high_addr: 0,
+            low_addr: 0xfee0_100c,
+            data: 0x31,
+            devid: 0,
+        };
+        assert!(msix_group.set_vcpu(0, Some(3)).unwrap());
+        assert!(!msix_group.set_vcpu(0, Some(3)).unwrap());
+        msix_group.update(0, config, false, true).unwrap();
+        msix_group.update(1, config, false, true).unwrap();
+        assert_eq!(msi_address_lo(0), 0xfee0_3000);
+        assert_eq!(msi_address_lo(1), 0xfee0_100c);
+
+        // Without its vCPU, the vector targets the destination of the guest again.
+        assert!(msix_group.set_vcpu(0, None).unwrap());
+        msix_group.update(0, config, false, true).unwrap();
+        assert_eq!(msi_address_lo(0), 0xfee0_100c);
+        msix_group.set_vcpu(4, Some(0)).unwrap_err();
+    }
+
     #[test]
     fn test_msi_vector_group_update() {
         let (_, mut vm) = setup_vm_with_memory(mib_to_bytes(128));
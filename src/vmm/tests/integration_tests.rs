@@ -14,6 +14,7 @@ use vmm::persist::{MicrovmState, MicrovmStateError, VmInfo, snapshot_state_sanit
 use vmm::resources::VmResources;
 use vmm::rpc_interface::{
     LoadSnapshotError, PrebootApiController, RuntimeApiController, VmmAction, VmmActionError,
+    VmmData,
 };
 use vmm::seccomp::get_empty_filters;
 use vmm::snapshot::Snapshot;
@@ -26,7 +27,8 @@ use vmm::vmm_config::instance_info::{InstanceInfo, VmState};
 use vmm::vmm_config::machine_config::{MachineConfig, MachineConfigUpdate};
 use vmm::vmm_config::net::NetworkInterfaceConfig;
 use vmm::vmm_config::snapshot::{
-    CreateSnapshotParams, LoadSnapshotParams, MemBackendConfig, MemBackendType, SnapshotType,
+    CreateSnapshotParams, LoadSnapshotParams, MemBackendConfig, MemBackendType, MemVerification,
+    SnapshotConsistency, SnapshotType, ValidateSnapshotParams,
 };
 use vmm::vmm_config::vsock::VsockDeviceConfig;
 use vmm::{DumpCpuConfigError, EventManager, FcExitCode, Vmm};
@@ -236,6 +238,10 @@ fn verify_create_snapshot(
         snapshot_type,
         snapshot_path: snapshot_file.as_path().to_path_buf(),
         mem_file_path: memory_file.as_path().to_path_buf(),
+        memory_digests: false,
+        consistency: SnapshotConsistency::default(),
+        snapshot_fd: None,
+        mem_file_fd: None,
     };
 
     controller
@@ -276,6 +282,15 @@ fn verify_create_snapshot(
             .is_none()
     );
     assert_eq!(restored_microvm_state.vcpu_states.len(), 1);
+    // Only crash-consistent snapshots bump the snapshot epoch.
+    assert_eq!(
+        restored_microvm_state
+            .device_states
+            .acpi_state
+            .vmgenid
+            .snapshot_epoch,
+        0
+    );
 
     (snapshot_file, memory_file)
 }
@@ -292,6 +307,20 @@ fn verify_load_snapshot(snapshot_file: TempFile, memory_file: TempFile) {
         &mut event_manager,
     );
 
+    // The snapshot was taken on this host, so it can be loaded.
+    let report = preboot_api_controller
+        .handle_preboot_request(VmmAction::ValidateSnapshot(ValidateSnapshotParams {
+            snapshot_path: snapshot_file.as_path().to_path_buf(),
+            mem_file_path: Some(memory_file.as_path().to_path_buf()),
+            mem_verification: MemVerification::Length,
+        }))
+        .unwrap();
+    let VmmData::SnapshotValidation(report) = report else {
+        panic!("Unexpected response: {report:?}");
+    };
+    assert_eq!(report.incompatibilities, Vec::new());
+    assert!(report.compatible);
+
     preboot_api_controller
         .handle_preboot_request(VmmAction::LoadSnapshot(LoadSnapshotParams {
             snapshot_path: snapshot_file.as_path().to_path_buf(),
@@ -302,6 +331,8 @@ fn verify_load_snapshot(snapshot_file: TempFile, memory_file: TempFile) {
             track_dirty_pages: false,
             resume_vm: true,
             network_overrides: vec![],
+            mem_verification: MemVerification::Length,
+            rdma_allow_degraded_restore: false,
         }))
         .unwrap();
 
@@ -329,6 +360,60 @@ fn test_create_and_load_snapshot() {
     }
 }
 
+#[test]
+fn test_create_and_load_crash_consistent_snapshot() {
+    let snapshot_file = TempFile::new().unwrap();
+    let memory_file = TempFile::new().unwrap();
+
+    let (vmm, _) = create_vmm(Some(NOISY_KERNEL_IMAGE), false, true, false, false);
+    let resources = VmResources {
+        machine_config: MachineConfig {
+            mem_size_mib: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut controller = RuntimeApiController::new(resources, vmm.clone());
+
+    // Be sure that the microVM is running.
+    thread::sleep(Duration::from_millis(200));
+
+    // Pause microVM.
+    controller.handle_request(VmmAction::Pause).unwrap();
+
+    // Every crash-consistent snapshot bumps the snapshot epoch.
+    for epoch in 1..=2 {
+        let snapshot_params = CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: snapshot_file.as_path().to_path_buf(),
+            mem_file_path: memory_file.as_path().to_path_buf(),
+            memory_digests: false,
+            consistency: SnapshotConsistency::CrashConsistent,
+            snapshot_fd: None,
+            mem_file_fd: None,
+        };
+        controller
+            .handle_request(VmmAction::CreateSnapshot(snapshot_params))
+            .unwrap();
+
+        snapshot_file.as_file().seek(SeekFrom::Start(0)).unwrap();
+        let microvm_state: MicrovmState =
+            Snapshot::load(&mut snapshot_file.as_file()).unwrap().data;
+        assert_eq!(
+            microvm_state
+                .device_states
+                .acpi_state
+                .vmgenid
+                .snapshot_epoch,
+            epoch
+        );
+    }
+
+    vmm.lock().unwrap().stop(FcExitCode::Ok);
+
+    verify_load_snapshot(snapshot_file, memory_file);
+}
+
 #[test]
 fn test_snapshot_load_sanity_checks() {
     let microvm_state = get_microvm_state_from_snapshot(false);
@@ -386,6 +471,8 @@ fn verify_load_snap_disallowed_after_boot_resources(res: VmmAction, res_name: &s
         track_dirty_pages: false,
         resume_vm: false,
         network_overrides: vec![],
+        mem_verification: MemVerification::Length,
+        rdma_allow_degraded_restore: false,
     });
     let err = preboot_api_controller.handle_preboot_request(req);
     assert!(
@@ -417,8 +504,14 @@ fn test_preboot_load_snap_disallowed_after_boot_resources() {
 
         is_read_only: Some(false),
         path_on_host: Some(tmp_file),
+        backing_fd: None,
         rate_limiter: None,
         file_engine_type: None,
+        verity: None,
+        shared_page_cache: None,
+        strict_ordering: None,
+        host_cache: None,
+        nbd_uri: None,
 
         socket: None,
     };
@@ -432,6 +525,8 @@ fn test_preboot_load_snap_disallowed_after_boot_resources() {
         guest_mac: None,
         rx_rate_limiter: None,
         tx_rate_limiter: None,
+        flow_sampling: None,
+        tap_fd: None,
     });
     verify_load_snap_disallowed_after_boot_resources(req, "InsertNetworkDevice");
 
@@ -442,6 +537,7 @@ fn test_preboot_load_snap_disallowed_after_boot_resources() {
         vsock_id: Some(String::new()),
         guest_cid: 0,
         uds_path: String::new(),
+        io_engine: None,
     });
     verify_load_snap_disallowed_after_boot_resources(req, "SetVsockDevice");
 
@@ -181,6 +181,12 @@ class Api:
         self.snapshot_load = Resource(self, "/snapshot/load")
         self.cpu_config = Resource(self, "/cpu-config")
         self.entropy = Resource(self, "/entropy")
+        self.firmware = Resource(self, "/firmware")
+        self.fw_cfg = Resource(self, "/fw-cfg")
+        self.smbios = Resource(self, "/smbios")
         self.pmem = Resource(self, "/pmem", "id")
+        self.p9 = Resource(self, "/9p", "id")
+        self.i2c = Resource(self, "/i2c", "id")
+        self.gpio = Resource(self, "/gpio", "id")
         self.serial = Resource(self, "/serial")
         self.memory_hotplug = Resource(self, "/hotplug/memory")
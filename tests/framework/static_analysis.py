@@ -570,7 +570,11 @@ def load_seccomp_rules(seccomp_path: Path):
     filters = json.loads(seccomp_path.read_text("utf-8"))
 
     all_filters = (
-        filters["vcpu"]["filter"] + filters["vmm"]["filter"] + filters["api"]["filter"]
+        filters["vcpu"]["filter"]
+        + filters["vmm"]["filter"]
+        + filters["api"]["filter"]
+        + filters["rdma"]["filter"]
+        + filters["rdma_launcher"]["filter"]
     )
     allowlist = defaultdict(list)
 
@@ -95,8 +95,11 @@ def validate_fc_metrics(metrics):
         "rate_limiter_throttled_events",
         "io_engine_throttled_events",
         "remaining_reqs_count",
+        "verity_fails",
+        "read_ahead_count",
         {"read_agg": latency_agg_metrics_fields},
         {"write_agg": latency_agg_metrics_fields},
+        {"flush_agg": latency_agg_metrics_fields},
     ]
     net_metrics = [
         "activate_fails",
@@ -125,8 +128,68 @@ def validate_fc_metrics(metrics):
         "tx_rate_limiter_throttled",
         "tx_spoofed_mac_count",
         "tx_remaining_reqs_count",
+        "tx_deferred_notifications",
         {"tap_write_agg": latency_agg_metrics_fields},
     ]
+    p9_metrics = [
+        "activate_fails",
+        "cfg_fails",
+        "event_fails",
+        "queue_event_count",
+        "request_count",
+        "request_fails",
+        "read_bytes",
+        "write_bytes",
+    ]
+    i2c_metrics = [
+        "activate_fails",
+        "cfg_fails",
+        "event_fails",
+        "queue_event_count",
+        "transfer_count",
+        "transfer_fails",
+        "read_bytes",
+        "write_bytes",
+    ]
+    gpio_metrics = [
+        "activate_fails",
+        "cfg_fails",
+        "event_fails",
+        "queue_event_count",
+        "request_count",
+        "request_fails",
+        "irq_count",
+    ]
+    rdma_metrics = [
+        "activate_fails",
+        "event_fails",
+        "cfg_fails",
+        "queue_event_count",
+        "cmd_count",
+        "cmd_fails",
+        "leaked_resources",
+        "send_wr_count",
+        "recv_wr_count",
+        "wr_quota_exceeded",
+        "cq_overflows",
+        "rx_drops",
+        "cq_event_count",
+        "cq_notify_count",
+        "mr_invalidations",
+        "srq_limit_events",
+        "event_drops",
+        "doorbell_count",
+        "doorbell_fails",
+        "cq_deferred_interrupts",
+        "injected_faults",
+        "rate_limiter_throttled",
+        "rate_limiter_event_count",
+        "degraded_restores",
+        "backend_probe_fails",
+        "backend_reconnects",
+        "backend_reconnect_fails",
+        "qp_reconnect_fails",
+    ]
     firecracker_metrics = {
         "utc_timestamp_ms": "",
         "api_server": [
@@ -148,6 +211,13 @@ def validate_fc_metrics(metrics):
             "free_page_hint_fails",
         ],
         "block": block_metrics,
+        "cold_memory": [
+            "pressure_events",
+            "pages_evicted",
+            "pages_reloaded",
+            "evict_fails",
+            "stored_bytes",
+        ],
         "deprecated_api": [
             "deprecated_http_api_calls",
         ],
@@ -157,6 +227,11 @@ def validate_fc_metrics(metrics):
             "mmds_count",
             "vmm_version_count",
             "hotplug_memory_count",
+            "vsock_connections_count",
+            "network_flows_count",
+            "cpu_cfg_count",
+            "resource_usage_count",
+            "rdma_status_count",
         ],
         "i8042": [
             "error_count",
@@ -178,11 +253,22 @@ def validate_fc_metrics(metrics):
             "vmm_pause_vm",
             "vmm_resume_vm",
         ],
+        "io_cpu_budget": [
+            "charged_us",
+            "throttles",
+            "deferred_events",
+        ],
         "logger": [
             "missed_metrics_count",
             "metrics_fails",
             "missed_log_count",
         ],
+        "memory_backing": [
+            "writebacks",
+            "writeback_fails",
+            "flushes",
+            "flush_fails",
+        ],
         "mmds": [
             "rx_accepted",
             "rx_accepted_err",
@@ -190,6 +276,7 @@ def validate_fc_metrics(metrics):
             "rx_bad_eth",
             "rx_invalid_token",
             "rx_no_token",
+            "rx_throttled",
             "rx_count",
             "tx_bytes",
             "tx_count",
@@ -210,14 +297,24 @@ def validate_fc_metrics(metrics):
             "mmds_fails",
             "hotplug_memory_count",
             "hotplug_memory_fails",
+            "rdma_count",
+            "rdma_fails",
         ],
         "put_api_requests": [
             "actions_count",
             "actions_fails",
             "boot_source_count",
             "boot_source_fails",
+            "firmware_count",
+            "firmware_fails",
+            "fw_cfg_count",
+            "fw_cfg_fails",
+            "smbios_count",
+            "smbios_fails",
             "drive_count",
             "drive_fails",
+            "config_drive_count",
+            "config_drive_fails",
             "logger_count",
             "logger_fails",
             "machine_cfg_count",
@@ -236,14 +333,38 @@ def validate_fc_metrics(metrics):
             "pmem_fails",
             "rdma_count",
             "rdma_fails",
+            "p9_count",
+            "p9_fails",
+            "i2c_count",
+            "i2c_fails",
+            "gpio_count",
+            "gpio_fails",
             "serial_count",
             "serial_fails",
+            "cold_memory_count",
+            "cold_memory_fails",
+            "io_cpu_budget_count",
+            "io_cpu_budget_fails",
+            "interrupt_affinity_count",
+            "interrupt_affinity_fails",
+            "memory_backing_count",
+            "memory_backing_fails",
+            "snapshot_agent_count",
+            "snapshot_agent_fails",
             "hotplug_memory_count",
             "hotplug_memory_fails",
+            "hotplug_remove_count",
+            "hotplug_remove_fails",
         ],
         "seccomp": [
             "num_faults",
         ],
+        "snapshot_agent": [
+            "requests",
+            "snapshots",
+            "snapshot_fails",
+            "freeze_timeouts",
+        ],
         "vcpu": [
             "exit_io_in",
             "exit_io_out",
@@ -258,6 +379,10 @@ def validate_fc_metrics(metrics):
         ],
         "vmm": [
             "panic_count",
+            "graceful_shutdown_count",
+            "forced_shutdown_count",
+            "device_eject_count",
+            "device_surprise_removal_count",
         ],
         "uart": [
             "error_count",
@@ -293,6 +418,9 @@ def validate_fc_metrics(metrics):
             "conns_added",
             "conns_killed",
             "conns_removed",
+            "conns_backlog_drops",
+            "conns_rejected",
+            "rst_drops",
             "killq_resync",
             "tx_flush_fails",
             "tx_write_fails",
@@ -306,6 +434,7 @@ def validate_fc_metrics(metrics):
             "host_rng_fails",
             "entropy_rate_limiter_throttled",
             "rate_limiter_event_count",
+            {"entropy_agg": latency_agg_metrics_fields},
         ],
         "interrupts": ["triggers", "config_updates"],
         "pmem": [
@@ -314,6 +443,10 @@ def validate_fc_metrics(metrics):
             "event_fails",
             "queue_event_count",
         ],
+        "p9": p9_metrics,
+        "i2c": i2c_metrics,
+        "gpio": gpio_metrics,
+        "rdma": rdma_metrics,
         "memory_hotplug": [
             "activate_fails",
             "queue_event_fails",
@@ -352,6 +485,12 @@ def validate_fc_metrics(metrics):
             "missed_read_count",
             "missed_write_count",
         ]
+    else:
+        firecracker_metrics["pflash"] = [
+            "program_count",
+            "erase_count",
+            "error_count",
+        ]
 
     # add vhost-user metrics to the schema if applicable
     vhost_user_devices = []
@@ -369,6 +508,24 @@ def validate_fc_metrics(metrics):
             firecracker_metrics[metrics_name] = block_metrics
         if metrics_name.startswith("net_"):
             firecracker_metrics[metrics_name] = net_metrics
+        if metrics_name.startswith("p9_"):
+            firecracker_metrics[metrics_name] = p9_metrics
+        if metrics_name.startswith("i2c_"):
+            firecracker_metrics[metrics_name] = i2c_metrics
+        if metrics_name.startswith("gpio_"):
+            firecracker_metrics[metrics_name] = gpio_metrics
+        if metrics_name.startswith("rdma_"):
+            firecracker_metrics[metrics_name] = rdma_metrics
+        if metrics_name.startswith("vsock_port_"):
+            firecracker_metrics[metrics_name] = [
+                "active_conns",
+                "conns_added",
+                "conns_removed",
+                "conns_backlog_drops",
+                "conns_rejected",
+                "rx_bytes_count",
+                "tx_bytes_count",
+            ]
 
     firecracker_metrics_schema = create_metrics_schema_objects(firecracker_metrics)
 
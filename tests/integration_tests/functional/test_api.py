@@ -1212,6 +1212,7 @@ def test_get_full_config_after_restoring_snapshot(microvm_factory, uvm_nano):
         "stats_polling_interval_s": 0,
         "free_page_reporting": False,
         "free_page_hinting": False,
+        "stats_history_len": 0,
     }
 
     # Add a vsock device.
@@ -1347,6 +1348,7 @@ def test_get_full_config(uvm_plain):
         "stats_polling_interval_s": 0,
         "free_page_reporting": False,
         "free_page_hinting": False,
+        "stats_history_len": 0,
     }
 
     # Add a vsock device.